@@ -0,0 +1,67 @@
+//! Benchmarks of the pure-Rust hot paths behind search and retrieval
+//!
+//! Usage: cargo bench --bench hot_paths
+
+use std::sync::Arc;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use codex_core::ai::{EmbeddingEngine, MockEmbeddingBackend};
+use codex_core::db::sanitize_fts_query;
+
+const PARAGRAPH: &str = "Stoicism teaches that virtue is the only true good and that we should focus on \
+what is within our control. Quantum mechanics describes the behaviour of matter at the smallest scales, \
+where particles exist in superpositions until they are measured. Machine learning algorithms find \
+patterns in data and improve with experience. ";
+
+fn embedding_engine() -> EmbeddingEngine {
+    EmbeddingEngine::with_backend(Arc::new(MockEmbeddingBackend::default()), "cpu")
+}
+
+fn bench_sanitize_fts_query(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sanitize_fts_query");
+    for query in ["stoicism", "quantum mechanics \"superposition\"", "XJ-9000 (draft) OR notes* AND 2024 review"] {
+        group.bench_with_input(BenchmarkId::from_parameter(query), query, |b, query| {
+            b.iter(|| sanitize_fts_query(black_box(query)))
+        });
+    }
+    group.finish();
+}
+
+fn bench_cosine_similarity(c: &mut Criterion) {
+    let engine = embedding_engine();
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let (a, b) = runtime.block_on(async {
+        (
+            engine.generate_embedding("stoic virtue and control").await.unwrap(),
+            engine.generate_embedding("virtue ethics of the stoics").await.unwrap(),
+        )
+    });
+    c.bench_function("cosine_similarity", |bench| {
+        bench.iter(|| engine.cosine_similarity(black_box(&a), black_box(&b)))
+    });
+
+    let corpus: Vec<(String, Vec<f32>)> = runtime.block_on(async {
+        let texts: Vec<String> = (0..5_000).map(|i| format!("document {} about topic {}", i, i % 37)).collect();
+        let vectors = engine.generate_embeddings_batch(&texts).await.unwrap();
+        (0..texts.len()).map(|i| format!("doc-{}", i)).zip(vectors).collect()
+    });
+    c.bench_function("find_similar_5k", |bench| {
+        bench.iter(|| engine.find_similar(black_box(&a), black_box(&corpus), 10))
+    });
+}
+
+fn bench_chunking(c: &mut Criterion) {
+    let engine = embedding_engine();
+    let mut group = c.benchmark_group("chunk_document");
+    for paragraphs in [10, 100, 1_000] {
+        let text = PARAGRAPH.repeat(paragraphs);
+        group.bench_with_input(BenchmarkId::from_parameter(paragraphs), &text, |b, text| {
+            b.iter(|| engine.chunk_document(black_box(text), 512, 50))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_sanitize_fts_query, bench_cosine_similarity, bench_chunking);
+criterion_main!(benches);
@@ -76,7 +76,6 @@ impl BenchmarkMetrics {
 /// System resource monitor for tracking CPU and memory during inference
 struct ResourceMonitor {
     system: System,
-    initial_memory: u64,
     peak_memory: u64,
     cpu_usage: f32,
 }
@@ -90,7 +89,6 @@ impl ResourceMonitor {
         
         Self {
             system,
-            initial_memory,
             peak_memory: initial_memory,
             cpu_usage: 0.0,
         }
@@ -258,6 +256,13 @@ async fn create_optimized_config() -> CodexResult<AiConfig> {
         enable_caching: true,
         cache_size_mb: 1000,        // Cache up to 1000 MB
         max_context_length: 4096,
+        rag: Default::default(),
+        embedding: Default::default(),
+        audit: Default::default(),
+        draft_model: None,
+        speculative: Default::default(),
+        truncation: Default::default(),
+        gpu_layers: Default::default(),
     };
 
     info!("Created optimized config: device={}, max_tokens={}, caching={}",
@@ -267,7 +272,7 @@ async fn create_optimized_config() -> CodexResult<AiConfig> {
 }
 
 async fn run_extended_benchmarks(engine: &AiEngine, monitor: &mut ResourceMonitor) -> CodexResult<()> {
-    let test_prompts = vec![
+    let test_prompts = [
         "Explain quantum computing briefly",
         "What is machine learning?",
         "How does photosynthesis work?",
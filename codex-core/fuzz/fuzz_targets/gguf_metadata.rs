@@ -0,0 +1,19 @@
+//! Fuzz the GGUF header parser with arbitrary bytes
+//!
+//! Run with `cargo +nightly fuzz run gguf_metadata` from `codex-core/`.
+//! Any input must parse or fail with an error; a panic, abort or
+//! out-of-memory is a bug.
+
+#![no_main]
+
+use codex_core::ai::gguf::{encode_metadata, parse_metadata_bytes};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(metadata) = parse_metadata_bytes(data) {
+        // Whatever parses must survive a re-encode unchanged
+        let reparsed = parse_metadata_bytes(&encode_metadata(&metadata)).expect("re-encoded header parses");
+        assert_eq!(reparsed.tensors, metadata.tensors);
+        assert_eq!(reparsed.metadata.len(), metadata.metadata.len());
+    }
+});
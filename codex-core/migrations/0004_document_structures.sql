@@ -0,0 +1,16 @@
+-- Document structure migration
+-- Version: 0004
+-- Description: Store parsed structure (e.g. table schemas) for structured imports
+
+CREATE TABLE document_structures (
+    document_id TEXT PRIMARY KEY NOT NULL,
+    kind TEXT NOT NULL,
+    structure TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now', 'utc')),
+    FOREIGN KEY (document_id) REFERENCES documents(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_document_structures_kind ON document_structures(kind);
+
+-- Update schema version
+UPDATE settings SET value = '4' WHERE key = 'schema_version';
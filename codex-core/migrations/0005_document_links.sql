@@ -0,0 +1,23 @@
+-- Document links migration
+-- Version: 0005
+-- Description: Link graph for wiki-style [[links]] and markdown links between documents
+
+CREATE TABLE document_links (
+    id TEXT PRIMARY KEY NOT NULL,
+    source_id TEXT NOT NULL,
+    -- NULL while the link does not resolve to an existing document
+    target_id TEXT,
+    -- Link text as written, used to resolve the link later
+    target_title TEXT NOT NULL,
+    link_type TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now', 'utc')),
+    FOREIGN KEY (source_id) REFERENCES documents(id) ON DELETE CASCADE,
+    FOREIGN KEY (target_id) REFERENCES documents(id) ON DELETE SET NULL
+);
+
+CREATE INDEX idx_document_links_source ON document_links(source_id);
+CREATE INDEX idx_document_links_target ON document_links(target_id);
+CREATE INDEX idx_document_links_unresolved ON document_links(target_title COLLATE NOCASE) WHERE target_id IS NULL;
+
+-- Update schema version
+UPDATE settings SET value = '5' WHERE key = 'schema_version';
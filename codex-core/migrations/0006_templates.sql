@@ -0,0 +1,67 @@
+-- Document templates migration
+-- Version: 0006
+-- Description: Reusable note templates with {{placeholder}} variables
+
+CREATE TABLE templates (
+    id TEXT PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL UNIQUE,
+    content TEXT NOT NULL,
+    -- JSON array of tags applied to documents created from the template
+    default_tags TEXT,
+    default_category TEXT,
+    content_type TEXT NOT NULL DEFAULT 'text/markdown',
+    is_builtin BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TEXT NOT NULL DEFAULT (datetime('now', 'utc')),
+    updated_at TEXT NOT NULL DEFAULT (datetime('now', 'utc'))
+);
+
+-- Built-in templates
+INSERT INTO templates (id, name, content, default_tags, default_category, is_builtin)
+VALUES
+    (
+        '00000000-0000-4000-8000-000000000001',
+        'Meeting Notes',
+        '# {{title}}
+
+**Date:** {{date}} {{time}}
+**Attendees:** {{attendees}}
+
+## Agenda
+
+{{agenda}}
+
+## Discussion
+
+## Decisions
+
+## Action Items
+
+- [ ] ',
+        '["meeting"]',
+        'Notes',
+        TRUE
+    ),
+    (
+        '00000000-0000-4000-8000-000000000002',
+        'Book Review',
+        '# {{title}}
+
+**Author:** {{author}}
+**Finished:** {{date}}
+**Rating:** {{rating}}/5
+
+## Summary
+
+## Key Ideas
+
+## Favorite Quotes
+
+## Takeaways
+',
+        '["book", "review"]',
+        'Reviews',
+        TRUE
+    );
+
+-- Update schema version
+UPDATE settings SET value = '6' WHERE key = 'schema_version';
@@ -0,0 +1,12 @@
+-- Access tracking migration
+-- Version: 0007
+-- Description: Separate favorite timestamps from content modification time and index access columns
+
+-- When the document was last favorited; toggling favorites no longer touches updated_at
+ALTER TABLE documents ADD COLUMN favorited_at TEXT;
+UPDATE documents SET favorited_at = updated_at WHERE is_favorite = TRUE;
+
+CREATE INDEX idx_documents_last_accessed ON documents(last_accessed) WHERE last_accessed IS NOT NULL;
+
+-- Update schema version
+UPDATE settings SET value = '7' WHERE key = 'schema_version';
@@ -0,0 +1,27 @@
+-- Content compression migration
+-- Version: 0008
+-- Description: Store large document bodies zstd-compressed
+
+-- When compression is set, content is empty and the body lives in content_compressed
+ALTER TABLE documents ADD COLUMN content_compressed BLOB;
+ALTER TABLE documents ADD COLUMN compression TEXT;
+-- Uncompressed size in bytes of compressed bodies, for storage statistics
+ALTER TABLE documents ADD COLUMN content_size INTEGER;
+
+-- Keep the indexed text when a row's body is moved into content_compressed.
+-- Writers store the plain text first (indexed here) and compress it afterwards.
+DROP TRIGGER documents_fts_update;
+
+CREATE TRIGGER documents_fts_update AFTER UPDATE ON documents BEGIN
+    UPDATE documents_fts SET
+        title = NEW.title,
+        content = CASE WHEN NEW.compression IS NULL THEN NEW.content ELSE content END,
+        summary = NEW.summary,
+        author = NEW.author,
+        category = NEW.category,
+        tags = NEW.tags
+    WHERE rowid = NEW.rowid;
+END;
+
+-- Update schema version
+UPDATE settings SET value = '8' WHERE key = 'schema_version';
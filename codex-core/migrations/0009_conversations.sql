@@ -0,0 +1,36 @@
+-- Conversations migration
+-- Version: 0009
+-- Description: Persist AI exchanges, optionally grouped into conversations and tied to a document
+
+CREATE TABLE conversations (
+    id TEXT PRIMARY KEY NOT NULL,
+    -- Document the conversation is about, if any
+    document_id TEXT,
+    title TEXT,
+    created_at TEXT NOT NULL DEFAULT (datetime('now', 'utc')),
+    updated_at TEXT NOT NULL DEFAULT (datetime('now', 'utc')),
+    FOREIGN KEY (document_id) REFERENCES documents(id) ON DELETE CASCADE
+);
+
+CREATE TABLE conversation_messages (
+    id TEXT PRIMARY KEY NOT NULL,
+    -- NULL for one-off exchanges that are not part of a conversation
+    conversation_id TEXT,
+    document_id TEXT,
+    role TEXT NOT NULL CHECK (role IN ('user', 'assistant')),
+    content TEXT NOT NULL,
+    -- Reader selection the question was asked about, with its byte range in the document
+    selection_text TEXT,
+    selection_start INTEGER,
+    selection_end INTEGER,
+    created_at TEXT NOT NULL DEFAULT (datetime('now', 'utc')),
+    FOREIGN KEY (conversation_id) REFERENCES conversations(id) ON DELETE CASCADE,
+    FOREIGN KEY (document_id) REFERENCES documents(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_conversations_document ON conversations(document_id);
+CREATE INDEX idx_conversation_messages_conversation ON conversation_messages(conversation_id, created_at);
+CREATE INDEX idx_conversation_messages_document ON conversation_messages(document_id, created_at);
+
+-- Update schema version
+UPDATE settings SET value = '9' WHERE key = 'schema_version';
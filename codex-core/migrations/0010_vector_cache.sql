@@ -0,0 +1,26 @@
+-- Vector cache migration
+-- Version: 0010
+-- Description: Key cached vectors by document chunk so semantic search can read them instead of embedding rows
+
+-- The cache was never populated, so it is recreated rather than migrated
+DROP TABLE vector_cache;
+
+CREATE TABLE vector_cache (
+    id TEXT PRIMARY KEY NOT NULL,
+    document_id TEXT NOT NULL,
+    chunk_index INTEGER NOT NULL DEFAULT 0,
+    vector_blob BLOB NOT NULL,
+    dimensions INTEGER NOT NULL,
+    model TEXT NOT NULL,
+    access_count INTEGER NOT NULL DEFAULT 0,
+    last_accessed TEXT NOT NULL DEFAULT (datetime('now', 'utc')),
+    created_at TEXT NOT NULL DEFAULT (datetime('now', 'utc')),
+    UNIQUE (document_id, chunk_index),
+    FOREIGN KEY (document_id) REFERENCES documents(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_vector_cache_document_id ON vector_cache(document_id);
+CREATE INDEX idx_vector_cache_access ON vector_cache(access_count, last_accessed);
+
+-- Update schema version
+UPDATE settings SET value = '10' WHERE key = 'schema_version';
@@ -0,0 +1,23 @@
+-- Operations journal migration
+-- Version: 0011
+-- Description: Record document mutations with before/after state so they can be undone
+
+CREATE TABLE operations (
+    id TEXT PRIMARY KEY NOT NULL,
+    kind TEXT NOT NULL,
+    description TEXT NOT NULL,
+    -- JSON array of affected document ids
+    document_ids TEXT NOT NULL DEFAULT '[]',
+    -- JSON objects mapping document id to the changed value before and after the operation
+    before_state TEXT NOT NULL DEFAULT '{}',
+    after_state TEXT NOT NULL DEFAULT '{}',
+    -- Imports and purges are recorded for completeness but cannot be undone
+    undoable BOOLEAN NOT NULL DEFAULT true,
+    undone_at TEXT,
+    created_at TEXT NOT NULL DEFAULT (datetime('now', 'utc'))
+);
+
+CREATE INDEX idx_operations_created ON operations(created_at);
+
+-- Update schema version
+UPDATE settings SET value = '11' WHERE key = 'schema_version';
@@ -0,0 +1,22 @@
+-- FTS update trigger migration
+-- Version: 0012
+-- Description: Only resync full-text rows when indexed text columns are written
+
+-- Flag and counter updates (archive, favorite, access tracking) no longer
+-- rewrite the full-text row. Bulk organization edits write category and tags
+-- alone and resync their full-text rows in one statement afterwards.
+DROP TRIGGER documents_fts_update;
+
+CREATE TRIGGER documents_fts_update AFTER UPDATE OF title, content, summary, author ON documents BEGIN
+    UPDATE documents_fts SET
+        title = NEW.title,
+        content = CASE WHEN NEW.compression IS NULL THEN NEW.content ELSE content END,
+        summary = NEW.summary,
+        author = NEW.author,
+        category = NEW.category,
+        tags = NEW.tags
+    WHERE rowid = NEW.rowid;
+END;
+
+-- Update schema version
+UPDATE settings SET value = '12' WHERE key = 'schema_version';
@@ -0,0 +1,22 @@
+-- Automation rules migration
+-- Version: 0013
+-- Description: Rules that categorize, tag or archive documents as they are imported
+
+CREATE TABLE rules (
+    id TEXT PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL,
+    -- Rules run in ascending priority, so higher priorities win conflicts
+    priority INTEGER NOT NULL DEFAULT 0,
+    enabled BOOLEAN NOT NULL DEFAULT true,
+    -- JSON object of conditions; all must hold for the rule to match
+    conditions TEXT NOT NULL DEFAULT '{}',
+    -- JSON object of actions applied to matching documents
+    actions TEXT NOT NULL DEFAULT '{}',
+    created_at TEXT NOT NULL DEFAULT (datetime('now', 'utc')),
+    updated_at TEXT NOT NULL DEFAULT (datetime('now', 'utc'))
+);
+
+CREATE INDEX idx_rules_priority ON rules(enabled, priority);
+
+-- Update schema version
+UPDATE settings SET value = '13' WHERE key = 'schema_version';
@@ -0,0 +1,9 @@
+-- Full-text vocabulary migration
+-- Version: 0014
+-- Description: Term dictionary over the full-text index for query spelling correction
+
+-- One row per indexed term with the number of documents containing it
+CREATE VIRTUAL TABLE documents_fts_vocab USING fts5vocab(documents_fts, row);
+
+-- Update schema version
+UPDATE settings SET value = '14' WHERE key = 'schema_version';
@@ -0,0 +1,18 @@
+-- Home feed indexes migration
+-- Version: 0015
+-- Description: Partial indexes over live documents for the home feed sections
+
+-- Each index holds the section's sort key and the columns it filters on, so
+-- a section is read from the index in order and stops after a few rows
+-- instead of visiting deleted, archived or diagnostic documents.
+CREATE INDEX idx_documents_feed_updated ON documents(updated_at, created_at, category)
+    WHERE is_deleted = 0 AND is_archived = 0;
+CREATE INDEX idx_documents_feed_accessed ON documents(last_accessed, category)
+    WHERE is_deleted = 0 AND is_archived = 0 AND last_accessed IS NOT NULL;
+CREATE INDEX idx_documents_feed_views ON documents(view_count, last_accessed, category)
+    WHERE is_deleted = 0 AND is_archived = 0 AND view_count > 0;
+CREATE INDEX idx_documents_feed_unopened ON documents(created_at, category)
+    WHERE is_deleted = 0 AND is_archived = 0 AND last_accessed IS NULL;
+
+-- Update schema version
+UPDATE settings SET value = '15' WHERE key = 'schema_version';
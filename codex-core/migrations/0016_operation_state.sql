@@ -0,0 +1,24 @@
+-- Operation state migration
+-- Version: 0016
+-- Description: Checkpoints of long-running operations so they can resume after a crash
+
+CREATE TABLE operation_state (
+    id TEXT PRIMARY KEY NOT NULL,
+    kind TEXT NOT NULL,
+    -- JSON parameters the resume handler needs (e.g. the import directory)
+    params TEXT NOT NULL DEFAULT '{}',
+    -- Last item completed; its meaning depends on the kind
+    cursor TEXT,
+    processed INTEGER NOT NULL DEFAULT 0,
+    total INTEGER,
+    -- running, completed or failed; rows still running at startup were interrupted
+    status TEXT NOT NULL DEFAULT 'running',
+    error TEXT,
+    started_at TEXT NOT NULL DEFAULT (datetime('now', 'utc')),
+    updated_at TEXT NOT NULL DEFAULT (datetime('now', 'utc'))
+);
+
+CREATE INDEX idx_operation_state_status ON operation_state(status);
+
+-- Update schema version
+UPDATE settings SET value = '16' WHERE key = 'schema_version';
@@ -0,0 +1,28 @@
+-- Feed subscriptions migration
+-- Version: 0017
+-- Description: RSS/Atom feed subscriptions and the entries already imported from them
+
+CREATE TABLE feeds (
+    id TEXT PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL,
+    url TEXT NOT NULL UNIQUE,
+    -- Validators from the last response, sent back for conditional GETs
+    etag TEXT,
+    last_modified TEXT,
+    last_fetched_at TEXT,
+    -- Why the last fetch failed; cleared on success
+    last_error TEXT,
+    created_at TEXT NOT NULL DEFAULT (datetime('now', 'utc'))
+);
+
+-- Entries seen per feed, keyed by GUID/id or link, so each is imported once
+CREATE TABLE feed_entries (
+    feed_id TEXT NOT NULL REFERENCES feeds(id) ON DELETE CASCADE,
+    entry_key TEXT NOT NULL,
+    document_id TEXT,
+    imported_at TEXT NOT NULL DEFAULT (datetime('now', 'utc')),
+    PRIMARY KEY (feed_id, entry_key)
+);
+
+-- Update schema version
+UPDATE settings SET value = '17' WHERE key = 'schema_version';
@@ -0,0 +1,11 @@
+-- Bibliographic fields migration
+-- Version: 0018
+-- Description: Publication year and citation key filled from BibTeX/Zotero imports
+
+ALTER TABLE documents ADD COLUMN publication_year INTEGER;
+ALTER TABLE documents ADD COLUMN citation_key TEXT;
+
+CREATE INDEX idx_documents_citation_key ON documents(citation_key) WHERE citation_key IS NOT NULL;
+
+-- Update schema version
+UPDATE settings SET value = '18' WHERE key = 'schema_version';
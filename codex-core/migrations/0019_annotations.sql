@@ -0,0 +1,25 @@
+-- Annotations migration
+-- Version: 0019
+-- Description: Highlights and notes anchored to character ranges of a document
+
+CREATE TABLE annotations (
+    id TEXT PRIMARY KEY NOT NULL,
+    document_id TEXT NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+    -- Character range [start_offset, end_offset) in the document content
+    start_offset INTEGER NOT NULL,
+    end_offset INTEGER NOT NULL,
+    -- Text of the range when it was highlighted, used to re-anchor after edits
+    selected_text TEXT NOT NULL,
+    color TEXT NOT NULL DEFAULT 'yellow',
+    note TEXT,
+    -- Set when an edit removed the highlighted text
+    orphaned BOOLEAN NOT NULL DEFAULT false,
+    created_at TEXT NOT NULL DEFAULT (datetime('now', 'utc')),
+    updated_at TEXT NOT NULL DEFAULT (datetime('now', 'utc'))
+);
+
+CREATE INDEX idx_annotations_document ON annotations(document_id, start_offset);
+CREATE INDEX idx_annotations_created ON annotations(created_at DESC);
+
+-- Update schema version
+UPDATE settings SET value = '19' WHERE key = 'schema_version';
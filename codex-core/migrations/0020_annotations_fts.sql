@@ -0,0 +1,30 @@
+-- Annotation search migration
+-- Version: 0020
+-- Description: Full-text index over highlighted text and annotation notes
+
+CREATE VIRTUAL TABLE annotations_fts USING fts5(
+    selected_text,
+    note
+);
+
+INSERT INTO annotations_fts(rowid, selected_text, note)
+SELECT rowid, selected_text, note FROM annotations;
+
+CREATE TRIGGER annotations_fts_insert AFTER INSERT ON annotations BEGIN
+    INSERT INTO annotations_fts(rowid, selected_text, note)
+    VALUES (NEW.rowid, NEW.selected_text, NEW.note);
+END;
+
+CREATE TRIGGER annotations_fts_update AFTER UPDATE OF selected_text, note ON annotations BEGIN
+    UPDATE annotations_fts SET
+        selected_text = NEW.selected_text,
+        note = NEW.note
+    WHERE rowid = NEW.rowid;
+END;
+
+CREATE TRIGGER annotations_fts_delete AFTER DELETE ON annotations BEGIN
+    DELETE FROM annotations_fts WHERE rowid = OLD.rowid;
+END;
+
+-- Update schema version
+UPDATE settings SET value = '20' WHERE key = 'schema_version';
@@ -0,0 +1,19 @@
+-- Search filter indexes migration
+-- Version: 0021
+-- Description: Partial indexes over live documents for filtered full-text search
+
+-- Filtered searches count the documents passing their metadata predicates
+-- to pick a plan, list them newest first when there is no query, and read
+-- them before probing the full-text index when only a handful pass. Each
+-- index answers those predicates without visiting the table.
+CREATE INDEX idx_documents_search_category ON documents(category, is_archived, created_at)
+    WHERE is_deleted = 0;
+CREATE INDEX idx_documents_search_favorites ON documents(is_archived, created_at, category)
+    WHERE is_deleted = 0 AND is_favorite = 1;
+CREATE INDEX idx_documents_search_author ON documents(author COLLATE NOCASE, is_archived, created_at)
+    WHERE is_deleted = 0;
+CREATE INDEX idx_documents_search_created ON documents(created_at, is_archived, category)
+    WHERE is_deleted = 0;
+
+-- Update schema version
+UPDATE settings SET value = '21' WHERE key = 'schema_version';
@@ -0,0 +1,32 @@
+-- Timestamp normalization migration
+-- Version: 0022
+-- Description: Rewrite document and embedding timestamps as RFC 3339 in UTC
+
+-- Documents and embeddings are read and written as UTC timestamps in the
+-- RFC 3339 form `YYYY-MM-DDTHH:MM:SS[.fff]+00:00`, which sorts as text in
+-- time order. Rows written by column defaults (`YYYY-MM-DD HH:MM:SS`) or
+-- with other offsets or a `Z` suffix are rewritten in that form; values
+-- SQLite cannot parse are left alone.
+UPDATE documents
+SET created_at = strftime('%Y-%m-%dT%H:%M:%S', created_at)
+    || CASE WHEN strftime('%f', created_at) LIKE '%.000' THEN '' ELSE substr(strftime('%f', created_at), 3) END
+    || '+00:00'
+WHERE created_at NOT GLOB '[0-9][0-9][0-9][0-9]-[0-9][0-9]-[0-9][0-9]T[0-9][0-9]:[0-9][0-9]:[0-9][0-9]*+00:00'
+  AND strftime('%s', created_at) IS NOT NULL;
+
+UPDATE documents
+SET updated_at = strftime('%Y-%m-%dT%H:%M:%S', updated_at)
+    || CASE WHEN strftime('%f', updated_at) LIKE '%.000' THEN '' ELSE substr(strftime('%f', updated_at), 3) END
+    || '+00:00'
+WHERE updated_at NOT GLOB '[0-9][0-9][0-9][0-9]-[0-9][0-9]-[0-9][0-9]T[0-9][0-9]:[0-9][0-9]:[0-9][0-9]*+00:00'
+  AND strftime('%s', updated_at) IS NOT NULL;
+
+UPDATE embeddings
+SET created_at = strftime('%Y-%m-%dT%H:%M:%S', created_at)
+    || CASE WHEN strftime('%f', created_at) LIKE '%.000' THEN '' ELSE substr(strftime('%f', created_at), 3) END
+    || '+00:00'
+WHERE created_at NOT GLOB '[0-9][0-9][0-9][0-9]-[0-9][0-9]-[0-9][0-9]T[0-9][0-9]:[0-9][0-9]:[0-9][0-9]*+00:00'
+  AND strftime('%s', created_at) IS NOT NULL;
+
+-- Update schema version
+UPDATE settings SET value = '22' WHERE key = 'schema_version';
@@ -0,0 +1,11 @@
+-- Document versions migration
+-- Version: 0023
+-- Description: Version counter on documents for optimistic concurrency control
+
+-- Every write to a document's fields increments `version`. A full update
+-- names the version it was based on and fails with a conflict when the row
+-- has moved on, so concurrent edits from two windows cannot silently
+-- overwrite each other.
+ALTER TABLE documents ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+
+UPDATE settings SET value = '23' WHERE key = 'schema_version';
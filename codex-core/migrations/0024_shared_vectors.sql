@@ -0,0 +1,45 @@
+-- Shared vectors migration
+-- Version: 0024
+-- Description: Store identical chunk vectors once, shared by every chunk with the same text
+
+-- Documents often repeat boilerplate such as license headers. Chunks with
+-- the same text embedded by the same model point at one `vectors` row
+-- through `vector_id`; `refcount` counts the embeddings rows referring to
+-- it and the row is dropped with its last referrer. Rows embedded before
+-- this migration keep their inline vector until they are reindexed.
+CREATE TABLE vectors (
+    id INTEGER PRIMARY KEY,
+    chunk_hash TEXT NOT NULL,  -- SHA-256 of the chunk text, hex
+    model TEXT NOT NULL,
+    dimensions INTEGER NOT NULL,
+    vector TEXT NOT NULL,  -- JSON array of float values
+    vector_blob BLOB NOT NULL,
+    refcount INTEGER NOT NULL DEFAULT 0,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%S', 'now') || '+00:00'),
+    UNIQUE (chunk_hash, model)
+);
+
+ALTER TABLE embeddings ADD COLUMN chunk_hash TEXT;
+ALTER TABLE embeddings ADD COLUMN vector_id INTEGER REFERENCES vectors(id);
+
+CREATE INDEX idx_embeddings_vector_id ON embeddings(vector_id) WHERE vector_id IS NOT NULL;
+
+CREATE TRIGGER vectors_reference AFTER INSERT ON embeddings
+WHEN NEW.vector_id IS NOT NULL BEGIN
+    UPDATE vectors SET refcount = refcount + 1 WHERE id = NEW.vector_id;
+END;
+
+CREATE TRIGGER vectors_release AFTER DELETE ON embeddings
+WHEN OLD.vector_id IS NOT NULL BEGIN
+    UPDATE vectors SET refcount = refcount - 1 WHERE id = OLD.vector_id;
+    DELETE FROM vectors WHERE id = OLD.vector_id AND refcount <= 0;
+END;
+
+CREATE TRIGGER vectors_repoint AFTER UPDATE OF vector_id ON embeddings
+WHEN OLD.vector_id IS NOT NEW.vector_id BEGIN
+    UPDATE vectors SET refcount = refcount + 1 WHERE id = NEW.vector_id;
+    UPDATE vectors SET refcount = refcount - 1 WHERE id = OLD.vector_id;
+    DELETE FROM vectors WHERE id = OLD.vector_id AND refcount <= 0;
+END;
+
+UPDATE settings SET value = '24' WHERE key = 'schema_version';
@@ -0,0 +1,21 @@
+-- Failed imports migration
+-- Version: 0025
+-- Description: Quarantine of files that failed to import, for listing and retrying later
+
+-- One row per file path. A repeated failure updates the row and counts the
+-- attempt; a successful import of the path removes it.
+CREATE TABLE failed_imports (
+    id TEXT PRIMARY KEY NOT NULL,
+    path TEXT NOT NULL UNIQUE,
+    -- Stable error code of the last failure, see CodexError::code
+    error_code TEXT NOT NULL,
+    message TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 1,
+    first_failed_at TEXT NOT NULL,
+    last_failed_at TEXT NOT NULL
+);
+
+CREATE INDEX idx_failed_imports_last_failed ON failed_imports(last_failed_at DESC);
+
+-- Update schema version
+UPDATE settings SET value = '25' WHERE key = 'schema_version';
@@ -0,0 +1,22 @@
+-- Attachments migration
+-- Version: 0026
+-- Description: Content-addressed store of imported original files
+
+-- One row per distinct file copied into the managed attachment store under
+-- the content directory. The id is the SHA-256 of the bytes, so importing
+-- the same file twice stores it once.
+CREATE TABLE attachments (
+    id TEXT PRIMARY KEY NOT NULL,
+    size INTEGER NOT NULL,
+    -- File name the original had when it was first stored
+    original_name TEXT NOT NULL,
+    created_at TEXT NOT NULL
+);
+
+-- Managed copy of the file a document was imported from, if one was kept
+ALTER TABLE documents ADD COLUMN attachment_id TEXT REFERENCES attachments(id);
+
+CREATE INDEX idx_documents_attachment_id ON documents(attachment_id) WHERE attachment_id IS NOT NULL;
+
+-- Update schema version
+UPDATE settings SET value = '26' WHERE key = 'schema_version';
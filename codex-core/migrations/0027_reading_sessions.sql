@@ -0,0 +1,21 @@
+-- Reading sessions migration
+-- Version: 0027
+-- Description: Timed reading sessions for streaks and weekly reading goals
+
+-- A session is open (ended_at NULL) while the reader view shows the
+-- document. Ended sessions shorter than the configured minimum are deleted;
+-- overlapping ended sessions of the same document are merged into one.
+CREATE TABLE reading_sessions (
+    id TEXT PRIMARY KEY NOT NULL,
+    document_id TEXT NOT NULL,
+    started_at TEXT NOT NULL,
+    ended_at TEXT,
+    duration_seconds INTEGER NOT NULL DEFAULT 0,
+    FOREIGN KEY (document_id) REFERENCES documents(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_reading_sessions_started_at ON reading_sessions(started_at) WHERE ended_at IS NOT NULL;
+CREATE INDEX idx_reading_sessions_document ON reading_sessions(document_id, started_at);
+
+-- Update schema version
+UPDATE settings SET value = '27' WHERE key = 'schema_version';
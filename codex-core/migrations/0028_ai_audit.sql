@@ -0,0 +1,26 @@
+-- AI audit migration
+-- Version: 0028
+-- Description: Record of the AI operations that touched each document
+
+-- Rows are written while a document is imported, before it is stored, so
+-- there is no foreign key; maintenance removes rows older than the retention
+-- period and rows of documents that no longer exist. Prompts and outputs are
+-- kept as SHA-256 hashes; the texts are only stored when debugging is enabled.
+CREATE TABLE ai_audit (
+    id TEXT PRIMARY KEY NOT NULL,
+    document_id TEXT NOT NULL,
+    operation TEXT NOT NULL,
+    model TEXT NOT NULL,
+    prompt_hash TEXT NOT NULL,
+    output_hash TEXT NOT NULL,
+    duration_ms INTEGER NOT NULL,
+    timestamp TEXT NOT NULL,
+    prompt_text TEXT,
+    output_text TEXT
+);
+
+CREATE INDEX idx_ai_audit_document ON ai_audit(document_id, timestamp);
+CREATE INDEX idx_ai_audit_timestamp ON ai_audit(timestamp);
+
+-- Update schema version
+UPDATE settings SET value = '28' WHERE key = 'schema_version';
@@ -0,0 +1,9 @@
+-- Document metadata migration
+-- Version: 0029
+-- Description: Frontmatter keys of imported notes that have no document column
+
+-- JSON object of key to string, number, boolean or list of strings
+ALTER TABLE documents ADD COLUMN metadata_json TEXT;
+
+-- Update schema version
+UPDATE settings SET value = '29' WHERE key = 'schema_version';
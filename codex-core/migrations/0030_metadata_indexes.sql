@@ -0,0 +1,17 @@
+-- Metadata indexes migration
+-- Version: 0030
+-- Description: Expression indexes over commonly filtered custom metadata keys
+
+-- Searches filtering on a custom field compare
+-- json_extract(metadata_json, '$.<key>') with the value. An index only
+-- serves a query spelling out the same expression, so these keys are listed
+-- in INDEXED_METADATA_KEYS as well.
+CREATE INDEX idx_documents_metadata_project ON documents(json_extract(metadata_json, '$.project'), is_archived, created_at)
+    WHERE is_deleted = 0;
+CREATE INDEX idx_documents_metadata_status ON documents(json_extract(metadata_json, '$.status'), is_archived, created_at)
+    WHERE is_deleted = 0;
+CREATE INDEX idx_documents_metadata_type ON documents(json_extract(metadata_json, '$.type'), is_archived, created_at)
+    WHERE is_deleted = 0;
+
+-- Update schema version
+UPDATE settings SET value = '30' WHERE key = 'schema_version';
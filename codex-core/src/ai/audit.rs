@@ -0,0 +1,165 @@
+//! Per-document audit trail of AI operations
+//!
+//! Summaries, tags and difficulty assessments of a document, and RAG answers
+//! citing it, each add an [`AiAuditEntry`] with the model, the duration and
+//! SHA-256 hashes of the prompt input and the output. The texts themselves
+//! are only kept with [`AiAuditConfig::store_text`]. Recording never fails
+//! the operation: without a database, or when the insert fails, the entry is
+//! dropped with a warning.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::config::{AiAuditConfig, AiConfig};
+use crate::db::models::AiAuditEntry;
+use crate::db::{AiAuditQueries, DatabaseManager};
+use super::RagSource;
+
+/// AI operation recorded in the audit trail
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AiOperation {
+    Summarize,
+    Tags,
+    Difficulty,
+    /// A RAG answer cited the document as a source
+    RagCitation,
+}
+
+impl AiOperation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Summarize => "summarize",
+            Self::Tags => "tags",
+            Self::Difficulty => "difficulty",
+            Self::RagCitation => "rag_citation",
+        }
+    }
+}
+
+/// Output of an audited operation as the text that is hashed
+pub trait AuditOutput {
+    fn audit_text(&self) -> String;
+}
+
+impl AuditOutput for String {
+    fn audit_text(&self) -> String {
+        self.clone()
+    }
+}
+
+impl AuditOutput for Vec<String> {
+    fn audit_text(&self) -> String {
+        self.join(", ")
+    }
+}
+
+impl AuditOutput for i32 {
+    fn audit_text(&self) -> String {
+        self.to_string()
+    }
+}
+
+/// Hex SHA-256 of a prompt input or output
+pub fn text_hash(text: &str) -> String {
+    hex::encode(Sha256::digest(text.as_bytes()))
+}
+
+/// Writes audit entries once a database is attached
+#[derive(Debug)]
+pub struct AiAuditLog {
+    db: std::sync::RwLock<Option<Arc<DatabaseManager>>>,
+    config: AiAuditConfig,
+    model: String,
+}
+
+impl AiAuditLog {
+    pub fn new(config: &AiConfig) -> Self {
+        Self {
+            db: std::sync::RwLock::new(None),
+            config: config.audit.clone(),
+            model: config.primary_model.clone(),
+        }
+    }
+
+    pub fn set_database(&self, db: Arc<DatabaseManager>) {
+        *self.db.write().unwrap() = Some(db);
+    }
+
+    /// Entry for an operation that just finished
+    pub fn entry(
+        &self,
+        document_id: &str,
+        operation: AiOperation,
+        prompt: &str,
+        output: &str,
+        duration: Duration,
+    ) -> AiAuditEntry {
+        AiAuditEntry {
+            id: uuid::Uuid::new_v4().to_string(),
+            document_id: document_id.to_string(),
+            operation: operation.as_str().to_string(),
+            model: self.model.clone(),
+            prompt_hash: text_hash(prompt),
+            output_hash: text_hash(output),
+            duration_ms: duration.as_millis() as i64,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            prompt_text: self.config.store_text.then(|| prompt.to_string()),
+            output_text: self.config.store_text.then(|| output.to_string()),
+        }
+    }
+
+    /// Record an operation on a document
+    pub async fn record(&self, document_id: &str, operation: AiOperation, prompt: &str, output: &str, duration: Duration) {
+        let Some(db) = self.db.read().unwrap().clone() else {
+            return;
+        };
+        let entry = self.entry(document_id, operation, prompt, output, duration);
+        if let Err(e) = AiAuditQueries::create(db.pool(), &entry).await {
+            warn!("Failed to record {} for document {} in the AI audit trail: {}", entry.operation, document_id, e);
+        }
+    }
+
+    /// Record a RAG answer once for every document it cites
+    pub async fn record_citations(&self, question: &str, answer: &str, sources: &[RagSource], duration: Duration) {
+        let mut cited = Vec::new();
+        for source in sources {
+            if !cited.contains(&source.document_id) {
+                cited.push(source.document_id);
+                self.record(&source.document_id.to_string(), AiOperation::RagCitation, question, answer, duration)
+                    .await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(store_text: bool) -> AiConfig {
+        let mut config = crate::config::CodexConfig::default().ai;
+        config.audit.store_text = store_text;
+        config
+    }
+
+    #[test]
+    fn test_entries_keep_hashes_unless_debugging() {
+        let log = AiAuditLog::new(&config(false));
+        let entry = log.entry("doc", AiOperation::Tags, "Slow cooked stew", "cooking, stew", Duration::from_millis(40));
+        assert_eq!(entry.operation, "tags");
+        assert_eq!(entry.prompt_hash, text_hash("Slow cooked stew"));
+        assert_eq!(entry.output_hash.len(), 64);
+        assert_eq!(entry.duration_ms, 40);
+        assert!(entry.prompt_text.is_none() && entry.output_text.is_none());
+
+        let debug = AiAuditLog::new(&config(true));
+        let entry = debug.entry("doc", AiOperation::Summarize, "Slow cooked stew", "A stew.", Duration::ZERO);
+        assert_eq!(entry.prompt_text.as_deref(), Some("Slow cooked stew"));
+        assert_eq!(entry.output_text.as_deref(), Some("A stew."));
+    }
+}
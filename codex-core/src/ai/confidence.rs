@@ -0,0 +1,207 @@
+//! Confidence scoring for RAG answers
+//!
+//! The score (0–1) is a weighted sum of three signals:
+//!
+//! - **Retrieval quality** (weight 0.4): `0.5 × max + 0.3 × mean` chunk
+//!   similarity, plus `0.2 ×` the share of chunks above the similarity
+//!   threshold.
+//! - **Answer–source overlap** (weight 0.35): how many of the answer's
+//!   content words and word pairs also appear in the retrieved text.
+//! - **Self-check** (weight 0.25): the model is asked whether the context
+//!   supports the answer (1 for yes, 0 for no).
+//!
+//! If the self-check could not run, the other two weights are scaled up to
+//! sum to one.
+
+use std::collections::HashSet;
+
+/// Weight of retrieval quality in the confidence score
+pub const RETRIEVAL_WEIGHT: f32 = 0.4;
+/// Weight of answer–source overlap in the confidence score
+pub const OVERLAP_WEIGHT: f32 = 0.35;
+/// Weight of the self-check verdict in the confidence score
+pub const SELF_CHECK_WEIGHT: f32 = 0.25;
+
+/// Prepended to answers whose confidence is below the threshold
+pub const LOW_CONFIDENCE_HEDGE: &str =
+    "I'm not confident in this answer; the sources may not fully support it.\n\n";
+
+/// Words too common to indicate that an answer came from its sources
+const STOPWORDS: &[&str] = &[
+    "the", "and", "for", "are", "was", "were", "with", "that", "this", "from", "have", "has", "had", "not",
+    "but", "they", "their", "there", "which", "what", "when", "where", "who", "will", "would", "can", "could",
+    "should", "been", "being", "into", "than", "then", "also", "its", "his", "her", "you", "your", "our",
+    "about", "these", "those", "such", "some", "any", "all", "each", "more", "most", "other", "only", "very",
+];
+
+/// Inputs to the confidence score
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfidenceSignals {
+    /// Similarity of each chunk used as context
+    pub similarities: Vec<f32>,
+    /// Minimum similarity for a chunk to count as relevant
+    pub similarity_threshold: f32,
+    /// Share of the answer found in the sources (0–1)
+    pub answer_overlap: f32,
+    /// Model verdict on whether the context supports the answer
+    pub self_check: Option<bool>,
+}
+
+impl ConfidenceSignals {
+    /// Retrieval quality component (0–1)
+    pub fn retrieval_quality(&self) -> f32 {
+        if self.similarities.is_empty() {
+            return 0.0;
+        }
+
+        let max = self.similarities.iter().cloned().fold(0.0_f32, f32::max);
+        let mean = self.similarities.iter().sum::<f32>() / self.similarities.len() as f32;
+        let above = self
+            .similarities
+            .iter()
+            .filter(|s| **s >= self.similarity_threshold)
+            .count() as f32
+            / self.similarities.len() as f32;
+
+        (0.5 * max + 0.3 * mean + 0.2 * above).clamp(0.0, 1.0)
+    }
+
+    /// Combined confidence score (0–1)
+    pub fn score(&self) -> f32 {
+        let mut weighted = RETRIEVAL_WEIGHT * self.retrieval_quality()
+            + OVERLAP_WEIGHT * self.answer_overlap.clamp(0.0, 1.0);
+        let mut total_weight = RETRIEVAL_WEIGHT + OVERLAP_WEIGHT;
+
+        if let Some(supported) = self.self_check {
+            weighted += SELF_CHECK_WEIGHT * if supported { 1.0 } else { 0.0 };
+            total_weight += SELF_CHECK_WEIGHT;
+        }
+
+        (weighted / total_weight).clamp(0.0, 1.0)
+    }
+}
+
+/// Share of the answer's content words and word pairs that occur in the sources
+pub fn answer_overlap(answer: &str, sources: &str) -> f32 {
+    let answer_words = content_words(answer);
+    if answer_words.is_empty() {
+        return 0.0;
+    }
+
+    let source_words = content_words(sources);
+    let source_unigrams: HashSet<&str> = source_words.iter().map(String::as_str).collect();
+    let source_bigrams: HashSet<(&str, &str)> = source_words
+        .windows(2)
+        .map(|pair| (pair[0].as_str(), pair[1].as_str()))
+        .collect();
+
+    let unigram_hits = answer_words.iter().filter(|w| source_unigrams.contains(w.as_str())).count();
+    let unigram_share = unigram_hits as f32 / answer_words.len() as f32;
+
+    let answer_bigrams: Vec<(&str, &str)> = answer_words
+        .windows(2)
+        .map(|pair| (pair[0].as_str(), pair[1].as_str()))
+        .collect();
+    if answer_bigrams.is_empty() {
+        return unigram_share;
+    }
+    let bigram_hits = answer_bigrams.iter().filter(|b| source_bigrams.contains(b)).count();
+    let bigram_share = bigram_hits as f32 / answer_bigrams.len() as f32;
+
+    0.5 * unigram_share + 0.5 * bigram_share
+}
+
+/// Lowercased words of three or more characters, without stopwords
+pub(crate) fn content_words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| w.chars().count() >= 3)
+        .map(str::to_lowercase)
+        .filter(|w| !STOPWORDS.contains(&w.as_str()))
+        .collect()
+}
+
+/// Prompt asking the model whether the context supports an answer
+pub fn self_check_prompt(question: &str, context: &str, answer: &str) -> String {
+    format!(
+        "Context:\n{}\n\nQuestion: {}\n\nProposed answer: {}\n\n\
+         Is the proposed answer fully supported by the context? Reply with YES or NO only.",
+        context, question, answer
+    )
+}
+
+/// Parse a YES/NO self-check reply
+pub fn parse_self_check(reply: &str) -> Option<bool> {
+    let first = reply
+        .split(|c: char| !c.is_alphabetic())
+        .find(|w| !w.is_empty())?
+        .to_lowercase();
+
+    match first.as_str() {
+        "yes" => Some(true),
+        "no" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sources that contain the answer to "When was the Eiffel Tower completed?"
+    const PARIS_CORPUS: &str = "The Eiffel Tower was completed in 1889 as the entrance arch to the World's Fair. \
+                                Gustave Eiffel's company designed and built the tower in Paris.";
+    /// Sources that do not mention the Eiffel Tower at all
+    const GARDENING_CORPUS: &str = "Tomatoes need six hours of direct sunlight. Water the plants deeply \
+                                    twice a week and mulch to keep the soil moist.";
+
+    fn signals(similarities: Vec<f32>, answer: &str, sources: &str, self_check: Option<bool>) -> ConfidenceSignals {
+        ConfidenceSignals {
+            similarities,
+            similarity_threshold: 0.3,
+            answer_overlap: answer_overlap(answer, sources),
+            self_check,
+        }
+    }
+
+    #[test]
+    fn test_answer_present_in_corpus_scores_high() {
+        let answer = "The Eiffel Tower was completed in 1889 for the World's Fair.";
+        let score = signals(vec![0.82, 0.64], answer, PARIS_CORPUS, Some(true)).score();
+
+        assert!(score > 0.7, "score {}", score);
+    }
+
+    #[test]
+    fn test_answer_missing_from_corpus_scores_low() {
+        let answer = "The Eiffel Tower was completed in 1889 for the World's Fair.";
+        let score = signals(vec![0.21, 0.12], answer, GARDENING_CORPUS, Some(false)).score();
+
+        assert!(score < 0.25, "score {}", score);
+    }
+
+    #[test]
+    fn test_hallucinated_answer_scores_below_grounded_answer() {
+        let grounded = signals(vec![0.7], "Gustave Eiffel's company built the tower.", PARIS_CORPUS, None);
+        let invented = signals(vec![0.7], "Napoleon commissioned the tower in 1820.", PARIS_CORPUS, None);
+
+        assert!(grounded.answer_overlap > 0.8);
+        assert!(invented.answer_overlap < 0.3);
+        assert!(grounded.score() > invented.score());
+    }
+
+    #[test]
+    fn test_self_check_parsing_and_reweighting() {
+        assert_eq!(parse_self_check("YES."), Some(true));
+        assert_eq!(parse_self_check("  no, the context says otherwise"), Some(false));
+        assert_eq!(parse_self_check("Maybe"), None);
+
+        let base = ConfidenceSignals {
+            similarities: vec![1.0],
+            similarity_threshold: 0.3,
+            answer_overlap: 1.0,
+            self_check: None,
+        };
+        assert!((base.score() - 1.0).abs() < 1e-6);
+        assert!((ConfidenceSignals { self_check: Some(false), ..base }.score() - 0.75).abs() < 1e-6);
+    }
+}
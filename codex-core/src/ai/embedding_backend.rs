@@ -0,0 +1,468 @@
+//! Implementations behind the [`EmbeddingEngine`](super::EmbeddingEngine)
+//!
+//! An [`EmbeddingBackend`] turns text into vectors; the engine adds
+//! chunking and metrics on top. [`create_backend`] picks one from
+//! [`EmbeddingModelConfig::backend`]: the model in `models_dir` run
+//! in-process, or an OpenAI-compatible endpoint for machines too small to
+//! run a model. The ONNX backend can only be selected in builds with the
+//! `onnx` feature, and ONNX Runtime is not wired in yet.
+//!
+//! Vectors of different backends are not comparable. Switching to a
+//! backend with other dimensions is caught by the index health check, and
+//! [`ContentManager::reindex_for_embedding_model`](crate::content::ContentManager::reindex_for_embedding_model)
+//! re-embeds the affected documents.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+
+use crate::{CodexError, CodexResult};
+use crate::config::{AiConfig, EmbeddingBackendKind, EmbeddingModelConfig, RemoteEmbeddingConfig};
+
+/// Files an embedding model directory must contain
+pub const EMBEDDING_MODEL_FILES: [&str; 3] = ["config.json", "tokenizer.json", "model.safetensors"];
+/// Vector length of the local model
+const LOCAL_DIMENSIONS: usize = 384;
+
+/// Source of text embeddings
+#[async_trait]
+pub trait EmbeddingBackend: Send + Sync + std::fmt::Debug {
+    /// Embed one text
+    async fn generate(&self, text: &str) -> CodexResult<Vec<f32>>;
+
+    /// Embed several texts, returning one vector per text in order
+    async fn generate_batch(&self, texts: &[String]) -> CodexResult<Vec<Vec<f32>>> {
+        let mut vectors = Vec::with_capacity(texts.len());
+        for text in texts {
+            vectors.push(self.generate(text).await?);
+        }
+        Ok(vectors)
+    }
+
+    /// Length of the vectors this backend produces
+    fn dimensions(&self) -> usize;
+
+    /// Model name stored with each embedding
+    fn model_id(&self) -> String;
+
+    /// Whether the backend can embed text now
+    fn is_ready(&self) -> bool {
+        true
+    }
+
+    /// Directory of the model files, for backends that run one locally
+    fn model_dir(&self) -> Option<PathBuf> {
+        None
+    }
+
+    /// Switch to the model files in `model_dir`
+    fn reload(&self, _model_dir: &Path) -> CodexResult<()> {
+        Err(CodexError::validation(format!("The {} embedding backend has no local model files", self.model_id())))
+    }
+}
+
+/// Backend selected by `config.embedding`
+pub fn create_backend(config: &AiConfig) -> CodexResult<Arc<dyn EmbeddingBackend>> {
+    let embedding: &EmbeddingModelConfig = &config.embedding;
+    match embedding.backend {
+        EmbeddingBackendKind::Local => Ok(Arc::new(LocalEmbeddingBackend::new(&config.models_dir, &embedding.model))),
+        EmbeddingBackendKind::Remote => Ok(Arc::new(RemoteEmbeddingBackend::new(embedding.remote.clone())?)),
+        #[cfg(feature = "onnx")]
+        EmbeddingBackendKind::Onnx => Err(CodexError::config(
+            "The ONNX embedding backend is not implemented yet; use the local or remote backend",
+        )),
+    }
+}
+
+/// The model in `models_dir`, run in-process
+pub struct LocalEmbeddingBackend {
+    model_name: String,
+    dimensions: usize,
+    /// Directory of the loaded model files, if any are on disk
+    model_dir: RwLock<Option<PathBuf>>,
+}
+
+impl std::fmt::Debug for LocalEmbeddingBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LocalEmbeddingBackend")
+            .field("model_name", &self.model_name)
+            .field("dimensions", &self.dimensions)
+            .field("model_dir", &self.model_dir())
+            .finish()
+    }
+}
+
+impl LocalEmbeddingBackend {
+    /// Backend for `model` under `models_dir`, loaded if its files are there
+    pub fn new(models_dir: &Path, model: &str) -> Self {
+        let model_dir = crate::paths::resolve_model_path(models_dir, model);
+        Self {
+            model_name: model.to_string(),
+            dimensions: LOCAL_DIMENSIONS,
+            model_dir: RwLock::new(has_model_files(&model_dir).then_some(model_dir)),
+        }
+    }
+
+    /// Generate a placeholder embedding (deterministic for testing)
+    fn generate_placeholder_embedding(&self, text: &str) -> Vec<f32> {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+
+        // Create a deterministic hash of the text
+        let mut hasher = DefaultHasher::new();
+        text.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        // Generate pseudo-random embedding based on hash
+        let mut embedding = Vec::with_capacity(self.dimensions);
+        let mut seed = hash;
+
+        for _ in 0..self.dimensions {
+            // Linear congruential generator
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            let value = (seed as f32 / u64::MAX as f32) * 2.0 - 1.0; // Range [-1, 1]
+            embedding.push(value);
+        }
+
+        // Normalize the embedding
+        let norm: f32 = embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            for value in &mut embedding {
+                *value /= norm;
+            }
+        }
+
+        embedding
+    }
+}
+
+#[async_trait]
+impl EmbeddingBackend for LocalEmbeddingBackend {
+    async fn generate(&self, text: &str) -> CodexResult<Vec<f32>> {
+        // Placeholder implementation
+        // In a real implementation, you would:
+        // 1. Tokenize the text
+        // 2. Run through the embedding model
+        // 3. Return the normalized vector
+
+        // For now, generate a deterministic but pseudo-random embedding
+        Ok(self.generate_placeholder_embedding(text))
+    }
+
+    fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    fn model_id(&self) -> String {
+        self.model_name.clone()
+    }
+
+    fn is_ready(&self) -> bool {
+        self.model_dir().is_some()
+    }
+
+    fn model_dir(&self) -> Option<PathBuf> {
+        self.model_dir.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn reload(&self, model_dir: &Path) -> CodexResult<()> {
+        if !has_model_files(model_dir) {
+            return Err(CodexError::validation(format!(
+                "Embedding model directory {} must contain {}",
+                model_dir.display(),
+                EMBEDDING_MODEL_FILES.join(", ")
+            )));
+        }
+
+        *self.model_dir.write().unwrap_or_else(|e| e.into_inner()) = Some(model_dir.to_path_buf());
+        info!("Embedding model reloaded from {}", model_dir.display());
+        Ok(())
+    }
+}
+
+/// Whether `dir` holds every file of an embedding model
+fn has_model_files(dir: &Path) -> bool {
+    EMBEDDING_MODEL_FILES.iter().all(|name| dir.join(name).is_file())
+}
+
+/// An OpenAI-compatible `/embeddings` endpoint
+#[derive(Debug)]
+pub struct RemoteEmbeddingBackend {
+    config: RemoteEmbeddingConfig,
+    client: reqwest::Client,
+    endpoint: String,
+}
+
+#[derive(Serialize)]
+struct EmbeddingRequest<'a> {
+    model: &'a str,
+    input: &'a [String],
+}
+
+#[derive(Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingData {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+impl RemoteEmbeddingBackend {
+    pub fn new(config: RemoteEmbeddingConfig) -> CodexResult<Self> {
+        if config.dimensions == 0 || config.batch_size == 0 {
+            return Err(CodexError::config("Remote embedding dimensions and batch size must be positive"));
+        }
+        let endpoint = format!("{}/embeddings", config.base_url.trim_end_matches('/'));
+        Ok(Self {
+            config,
+            client: crate::update::http_client(),
+            endpoint,
+        })
+    }
+
+    /// Embed one request's worth of texts
+    async fn request(&self, texts: &[String]) -> CodexResult<Vec<Vec<f32>>> {
+        let mut request = self
+            .client
+            .post(&self.endpoint)
+            .timeout(Duration::from_secs(self.config.timeout_secs))
+            .json(&EmbeddingRequest { model: &self.config.model, input: texts });
+        if let Some(api_key) = &self.config.api_key {
+            request = request.bearer_auth(api_key);
+        }
+
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(CodexError::ai_inference(format!(
+                "Embedding endpoint returned {}: {}",
+                status,
+                body.chars().take(200).collect::<String>()
+            )));
+        }
+
+        let mut data = response.json::<EmbeddingResponse>().await?.data;
+        if data.len() != texts.len() {
+            return Err(CodexError::ai_inference(format!(
+                "Embedding endpoint returned {} vectors for {} texts",
+                data.len(),
+                texts.len()
+            )));
+        }
+        data.sort_by_key(|item| item.index);
+        if let Some(item) = data.iter().find(|item| item.embedding.len() != self.config.dimensions) {
+            return Err(CodexError::ai_inference(format!(
+                "Embedding endpoint returned {} dimensions, {} are configured",
+                item.embedding.len(),
+                self.config.dimensions
+            )));
+        }
+        Ok(data.into_iter().map(|item| item.embedding).collect())
+    }
+}
+
+#[async_trait]
+impl EmbeddingBackend for RemoteEmbeddingBackend {
+    async fn generate(&self, text: &str) -> CodexResult<Vec<f32>> {
+        let mut vectors = self.request(&[text.to_string()]).await?;
+        Ok(vectors.remove(0))
+    }
+
+    async fn generate_batch(&self, texts: &[String]) -> CodexResult<Vec<Vec<f32>>> {
+        let mut vectors = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(self.config.batch_size) {
+            debug!("Requesting {} embeddings from {}", batch.len(), self.endpoint);
+            vectors.extend(self.request(batch).await?);
+        }
+        Ok(vectors)
+    }
+
+    fn dimensions(&self) -> usize {
+        self.config.dimensions
+    }
+
+    fn model_id(&self) -> String {
+        self.config.model.clone()
+    }
+}
+
+/// Backend that refuses to embed, used in safe mode
+///
+/// Neither model files nor a remote endpoint are touched; semantic search
+/// falls back to full-text matching.
+#[derive(Debug)]
+pub struct DisabledEmbeddingBackend {
+    model_name: String,
+}
+
+impl DisabledEmbeddingBackend {
+    pub fn new(model: &str) -> Self {
+        Self { model_name: model.to_string() }
+    }
+}
+
+#[async_trait]
+impl EmbeddingBackend for DisabledEmbeddingBackend {
+    async fn generate(&self, _text: &str) -> CodexResult<Vec<f32>> {
+        Err(CodexError::ai_inference("Embeddings are disabled in safe mode"))
+    }
+
+    fn dimensions(&self) -> usize {
+        LOCAL_DIMENSIONS
+    }
+
+    fn model_id(&self) -> String {
+        self.model_name.clone()
+    }
+
+    fn is_ready(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+
+    /// Properties every backend must have
+    async fn check_conformance(backend: &dyn EmbeddingBackend) {
+        assert!(!backend.model_id().is_empty());
+        let dimensions = backend.dimensions();
+        assert!(dimensions > 0);
+
+        let text = "The quick brown fox jumps over the lazy dog.";
+        let vector = backend.generate(text).await.unwrap();
+        assert_eq!(vector.len(), dimensions);
+        assert!(vector.iter().all(|value| value.is_finite()));
+        assert_eq!(backend.generate(text).await.unwrap(), vector, "embeddings are deterministic");
+        assert_ne!(backend.generate("Something else entirely").await.unwrap(), vector);
+
+        let texts: Vec<String> = (0..5).map(|i| format!("Sentence number {}", i)).collect();
+        let batch = backend.generate_batch(&texts).await.unwrap();
+        assert_eq!(batch.len(), texts.len());
+        for (text, vector) in texts.iter().zip(&batch) {
+            assert_eq!(&backend.generate(text).await.unwrap(), vector, "batches keep their order");
+        }
+        assert!(backend.generate_batch(&[]).await.unwrap().is_empty());
+    }
+
+    /// OpenAI-compatible endpoint answering each text with a vector derived
+    /// from its bytes, or with `dimensions + 1` values when `wrong_size`
+    async fn serve_embeddings(dimensions: usize, wrong_size: bool) -> String {
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut stream = BufReader::new(stream);
+                    loop {
+                        let mut content_length = 0;
+                        let mut line = String::new();
+                        loop {
+                            line.clear();
+                            if stream.read_line(&mut line).await.unwrap_or(0) == 0 {
+                                return;
+                            }
+                            if line == "\r\n" {
+                                break;
+                            }
+                            if let Some((name, value)) = line.split_once(':') {
+                                if name.eq_ignore_ascii_case("content-length") {
+                                    content_length = value.trim().parse().unwrap();
+                                }
+                            }
+                        }
+                        let mut body = vec![0; content_length];
+                        stream.read_exact(&mut body).await.unwrap();
+                        let request: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+                        let size = if wrong_size { dimensions + 1 } else { dimensions };
+                        let data: Vec<serde_json::Value> = request["input"]
+                            .as_array()
+                            .unwrap()
+                            .iter()
+                            .enumerate()
+                            .map(|(index, text)| {
+                                let bytes = text.as_str().unwrap().as_bytes();
+                                let embedding: Vec<f32> =
+                                    (0..size).map(|i| bytes.iter().skip(i).step_by(size).map(|&b| b as f32).sum()).collect();
+                                serde_json::json!({ "index": index, "embedding": embedding })
+                            })
+                            .rev()
+                            .collect();
+                        let response = serde_json::json!({ "data": data }).to_string();
+                        let reply = format!(
+                            "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}",
+                            response.len(),
+                            response
+                        );
+                        stream.get_mut().write_all(reply.as_bytes()).await.unwrap();
+                    }
+                });
+            }
+        });
+        format!("http://{}/v1", address)
+    }
+
+    fn remote_config(base_url: String) -> RemoteEmbeddingConfig {
+        RemoteEmbeddingConfig {
+            base_url,
+            api_key: Some("test-key".to_string()),
+            model: "test-embedding".to_string(),
+            dimensions: 8,
+            batch_size: 2,
+            timeout_secs: 5,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_local_backend_conformance() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = LocalEmbeddingBackend::new(dir.path(), "all-MiniLM-L6-v2");
+        check_conformance(&backend).await;
+        assert!(!backend.is_ready());
+        assert!(backend.reload(dir.path()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_remote_backend_conformance() {
+        let base_url = serve_embeddings(8, false).await;
+        let backend = RemoteEmbeddingBackend::new(remote_config(base_url)).unwrap();
+        check_conformance(&backend).await;
+        assert_eq!(backend.model_id(), "test-embedding");
+        assert!(backend.reload(Path::new("/models")).is_err());
+
+        // A model returning other dimensions than configured is refused
+        let base_url = serve_embeddings(8, true).await;
+        let backend = RemoteEmbeddingBackend::new(remote_config(base_url)).unwrap();
+        assert!(backend.generate("text").await.unwrap_err().to_string().contains("dimensions"));
+    }
+
+    #[test]
+    fn test_backend_selection() {
+        let mut config = AiConfig::default();
+        assert_eq!(create_backend(&config).unwrap().dimensions(), LOCAL_DIMENSIONS);
+
+        config.embedding.backend = EmbeddingBackendKind::Remote;
+        let remote = create_backend(&config).unwrap();
+        assert_eq!((remote.model_id(), remote.dimensions()), ("text-embedding-3-small".to_string(), 1536));
+
+        // Without the `onnx` feature the backend cannot be configured at all
+        #[cfg(not(feature = "onnx"))]
+        assert!(serde_json::from_str::<EmbeddingBackendKind>("\"onnx\"").is_err());
+        #[cfg(feature = "onnx")]
+        {
+            config.embedding.backend = EmbeddingBackendKind::Onnx;
+            assert_eq!(create_backend(&config).unwrap_err().code(), "config");
+        }
+    }
+}
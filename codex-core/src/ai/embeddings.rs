@@ -1,26 +1,47 @@
 //! Text embedding generation for semantic search
 
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
 use anyhow::Result;
+use once_cell::sync::Lazy;
+use regex::Regex;
 use tracing::{info, debug};
 
-use crate::CodexResult;
+use crate::{CodexError, CodexResult};
 use crate::config::AiConfig;
+use super::embedding_backend::{create_backend, DisabledEmbeddingBackend, EmbeddingBackend};
+
+/// Registry id of the embedding model used when none is configured
+pub const DEFAULT_EMBEDDING_MODEL: &str = "all-MiniLM-L6-v2";
+
+pub use super::embedding_backend::EMBEDDING_MODEL_FILES;
+
+/// Setting recording the model the vector index was last completed with
+pub const EMBEDDING_FINGERPRINT_SETTING: &str = "embedding.fingerprint";
+
+/// Words per part when ad-hoc text is too long to embed at once
+pub const ADHOC_CHUNK_WORDS: usize = 256;
+/// Words shared by consecutive parts of ad-hoc text
+const ADHOC_CHUNK_OVERLAP: usize = 32;
+
+/// Leading `[hh:mm:ss]` marker on transcript lines
+static TRANSCRIPT_MARKER_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^\[(\d{2,}:\d{2}:\d{2})\]").unwrap());
 
 /// Text embedding engine for generating vector representations
+///
+/// Chunks text and embeds it with the [`EmbeddingBackend`] chosen in the
+/// configuration. The engine also carries whether the stored vectors were
+/// embedded by another model, see [`EmbeddingEngine::is_index_stale`].
+#[derive(Debug)]
 pub struct EmbeddingEngine {
-    model_name: String,
-    dimensions: usize,
+    backend: Arc<dyn EmbeddingBackend>,
     device: String,
-}
-
-impl std::fmt::Debug for EmbeddingEngine {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("EmbeddingEngine")
-            .field("model_name", &self.model_name)
-            .field("dimensions", &self.dimensions)
-            .field("device", &self.device)
-            .finish()
-    }
+    index_stale: AtomicBool,
+    /// Started in safe mode; nothing can be embedded
+    disabled: bool,
 }
 
 impl EmbeddingEngine {
@@ -28,50 +49,87 @@ impl EmbeddingEngine {
     pub async fn new(config: &AiConfig) -> Result<Self> {
         info!("Initializing embedding engine");
 
-        // In a real implementation, you would:
-        // 1. Load a sentence transformer model (e.g., all-MiniLM-L6-v2)
-        // 2. Initialize the model with the specified device
-        // 3. Set up tokenization and preprocessing
-
-        let engine = Self {
-            model_name: "all-MiniLM-L6-v2".to_string(), // Standard embedding model
-            dimensions: 384, // Typical dimension for this model
-            device: config.device.clone(),
-        };
+        let engine = Self::with_backend(create_backend(config)?, &config.device);
 
-        info!("Embedding engine initialized with model: {}", engine.model_name);
+        info!(
+            "Embedding engine initialized with model: {} ({:?} backend)",
+            engine.backend.model_id(),
+            config.embedding.backend
+        );
         Ok(engine)
     }
 
+    /// Engine embedding text with `backend`
+    pub fn with_backend(backend: Arc<dyn EmbeddingBackend>, device: &str) -> Self {
+        Self {
+            backend,
+            device: device.to_string(),
+            index_stale: AtomicBool::new(false),
+            disabled: false,
+        }
+    }
+
+    /// Engine for safe mode, which never loads or calls an embedding model
+    pub fn disabled(config: &AiConfig) -> Self {
+        Self {
+            disabled: true,
+            ..Self::with_backend(Arc::new(DisabledEmbeddingBackend::new(&config.embedding.model)), &config.device)
+        }
+    }
+
+    /// Whether the engine was started in safe mode
+    pub fn is_disabled(&self) -> bool {
+        self.disabled
+    }
+
+    /// Identity of the active model, to compare with stored vectors
+    pub fn fingerprint(&self) -> EmbeddingFingerprint {
+        EmbeddingFingerprint {
+            model_id: self.backend.model_id(),
+            dimensions: self.backend.dimensions(),
+        }
+    }
+
+    /// Whether stored vectors come from another model than the active one
+    ///
+    /// Similarities between vectors of different models are meaningless, so
+    /// semantic search is skipped while the index is stale. Set by
+    /// [`ContentIndexer::check_embedding_model`](crate::content::indexer::ContentIndexer::check_embedding_model).
+    pub fn is_index_stale(&self) -> bool {
+        self.index_stale.load(Ordering::Relaxed)
+    }
+
+    pub fn set_index_stale(&self, stale: bool) {
+        self.index_stale.store(stale, Ordering::Relaxed);
+    }
+
+    /// Switch to the model files in `model_dir`, e.g. after a download
+    pub fn reload(&self, model_dir: &Path) -> CodexResult<()> {
+        self.backend.reload(model_dir)
+    }
+
+    /// Directory of the loaded model files
+    pub fn model_dir(&self) -> Option<PathBuf> {
+        self.backend.model_dir()
+    }
+
+    /// Whether the backend can embed text, e.g. model files are on disk
+    pub fn is_model_loaded(&self) -> bool {
+        self.backend.is_ready()
+    }
+
     /// Generate embedding for a single text
     pub async fn generate_embedding(&self, text: &str) -> CodexResult<Vec<f32>> {
+        let _timer = crate::metrics::timer(crate::metrics::EMBEDDING);
         debug!("Generating embedding for text: {}", text.chars().take(100).collect::<String>());
-
-        // Placeholder implementation
-        // In a real implementation, you would:
-        // 1. Tokenize the text
-        // 2. Run through the embedding model
-        // 3. Return the normalized vector
-
-        // For now, generate a deterministic but pseudo-random embedding
-        let embedding = self.generate_placeholder_embedding(text);
-        
-        Ok(embedding)
+        self.backend.generate(text).await
     }
 
     /// Generate embeddings for multiple texts (batch processing)
     pub async fn generate_embeddings_batch(&self, texts: &[String]) -> CodexResult<Vec<Vec<f32>>> {
+        let _timer = crate::metrics::timer(crate::metrics::EMBEDDING);
         debug!("Generating embeddings for {} texts", texts.len());
-
-        let mut embeddings = Vec::new();
-        
-        // In a real implementation, you would process these in batches for efficiency
-        for text in texts {
-            let embedding = self.generate_embedding(text).await?;
-            embeddings.push(embedding);
-        }
-
-        Ok(embeddings)
+        self.backend.generate_batch(texts).await
     }
 
     /// Generate embedding for chunked text (for long documents)
@@ -81,22 +139,75 @@ impl EmbeddingEngine {
         chunk_size: usize,
         overlap: usize,
     ) -> CodexResult<Vec<ChunkEmbedding>> {
-        let chunks = self.chunk_text(text, chunk_size, overlap);
-        let mut chunk_embeddings = Vec::new();
+        self.embed_chunks(self.chunk_document(text, chunk_size, overlap)).await
+    }
 
-        for (index, chunk) in chunks.into_iter().enumerate() {
-            let embedding = self.generate_embedding(&chunk.text).await?;
-            
-            chunk_embeddings.push(ChunkEmbedding {
+    /// Split text into the chunks [`generate_chunk_embeddings`](Self::generate_chunk_embeddings) embeds
+    pub fn chunk_document(&self, text: &str, chunk_size: usize, overlap: usize) -> Vec<TextChunk> {
+        // Transcripts are chunked on segment boundaries so every chunk starts with a timestamp
+        if is_timestamped_transcript(text) {
+            self.chunk_transcript(text, chunk_size)
+        } else {
+            self.chunk_text(text, chunk_size, overlap)
+        }
+    }
+
+    /// Generate embeddings for chunks produced by a caller-specific splitter
+    pub async fn embed_chunks(&self, chunks: Vec<TextChunk>) -> CodexResult<Vec<ChunkEmbedding>> {
+        let texts: Vec<String> = chunks.iter().map(|chunk| chunk.text.clone()).collect();
+        let embeddings = self.generate_embeddings_batch(&texts).await?;
+
+        Ok(chunks
+            .into_iter()
+            .zip(embeddings)
+            .enumerate()
+            .map(|(index, (chunk, embedding))| ChunkEmbedding {
                 index,
                 text: chunk.text,
                 start_position: chunk.start_position,
                 end_position: chunk.end_position,
                 embedding,
+            })
+            .collect())
+    }
+
+    /// Embed text that is not stored, e.g. text pasted for comparison
+    ///
+    /// Text longer than [`ADHOC_CHUNK_WORDS`] is split into parts whose
+    /// vectors are averaged, and the result carries the
+    /// [`adhoc_notice`](Self::adhoc_notice) saying so.
+    pub async fn embed_text(&self, text: &str) -> CodexResult<TextEmbedding> {
+        if text.trim().is_empty() {
+            return Err(CodexError::validation("Text to embed is empty"));
+        }
+
+        let chunks = self.chunk_text(text, ADHOC_CHUNK_WORDS, ADHOC_CHUNK_OVERLAP);
+        if chunks.len() <= 1 {
+            return Ok(TextEmbedding {
+                vector: self.generate_embedding(text).await?,
+                notice: None,
             });
         }
 
-        Ok(chunk_embeddings)
+        let texts: Vec<String> = chunks.into_iter().map(|chunk| chunk.text).collect();
+        let vectors = self.generate_embeddings_batch(&texts).await?;
+        let vector = mean_vector(&vectors).ok_or_else(|| CodexError::internal("Embedding model returned no vectors"))?;
+        Ok(TextEmbedding {
+            vector,
+            notice: self.adhoc_notice(text),
+        })
+    }
+
+    /// Notice for text [`embed_text`](Self::embed_text) has to embed in parts, None when it fits
+    pub fn adhoc_notice(&self, text: &str) -> Option<String> {
+        let parts = self.chunk_text(text, ADHOC_CHUNK_WORDS, ADHOC_CHUNK_OVERLAP).len();
+        (parts > 1).then(|| {
+            format!(
+                "Text is longer than the embedding model reads at once ({} tokens); its {} parts were embedded separately and averaged",
+                self.get_model_info().max_input_length,
+                parts
+            )
+        })
     }
 
     /// Calculate cosine similarity between two embeddings
@@ -179,43 +290,55 @@ impl EmbeddingEngine {
         chunks
     }
 
-    /// Generate a placeholder embedding (deterministic for testing)
-    fn generate_placeholder_embedding(&self, text: &str) -> Vec<f32> {
-        use std::collections::hash_map::DefaultHasher;
-        use std::hash::{Hash, Hasher};
+    /// Chunk a timestamped transcript by grouping whole segment lines
+    fn chunk_transcript(&self, text: &str, chunk_size: usize) -> Vec<TextChunk> {
+        let mut chunks = Vec::new();
+        let mut current: Option<(usize, usize)> = None;
+        let mut word_count = 0;
+        let mut offset = 0;
+
+        for line in text.split_inclusive('\n') {
+            let line_start = offset;
+            offset += line.len();
+            let trimmed = line.trim_end();
+            if trimmed.is_empty() {
+                continue;
+            }
 
-        // Create a deterministic hash of the text
-        let mut hasher = DefaultHasher::new();
-        text.hash(&mut hasher);
-        let hash = hasher.finish();
+            let words = trimmed.split_whitespace().count();
+            if let Some((start, end)) = current {
+                if word_count + words > chunk_size && word_count > 0 {
+                    chunks.push(TextChunk {
+                        text: text[start..end].to_string(),
+                        start_position: start,
+                        end_position: end,
+                    });
+                    current = None;
+                    word_count = 0;
+                }
+            }
 
-        // Generate pseudo-random embedding based on hash
-        let mut embedding = Vec::with_capacity(self.dimensions);
-        let mut seed = hash;
-        
-        for _ in 0..self.dimensions {
-            // Linear congruential generator
-            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
-            let value = (seed as f32 / u64::MAX as f32) * 2.0 - 1.0; // Range [-1, 1]
-            embedding.push(value);
+            let start = current.map(|(start, _)| start).unwrap_or(line_start);
+            current = Some((start, line_start + trimmed.len()));
+            word_count += words;
         }
 
-        // Normalize the embedding
-        let norm: f32 = embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
-        if norm > 0.0 {
-            for value in &mut embedding {
-                *value /= norm;
-            }
+        if let Some((start, end)) = current {
+            chunks.push(TextChunk {
+                text: text[start..end].to_string(),
+                start_position: start,
+                end_position: end,
+            });
         }
 
-        embedding
+        chunks
     }
 
     /// Get embedding model information
     pub fn get_model_info(&self) -> EmbeddingModelInfo {
         EmbeddingModelInfo {
-            name: self.model_name.clone(),
-            dimensions: self.dimensions,
+            name: self.backend.model_id(),
+            dimensions: self.backend.dimensions(),
             device: self.device.clone(),
             max_input_length: 512, // Typical for sentence transformers
         }
@@ -223,10 +346,46 @@ impl EmbeddingEngine {
 
     /// Get the embedding dimensions
     pub fn get_dimensions(&self) -> usize {
-        self.dimensions
+        self.backend.dimensions()
     }
 }
 
+/// Whether text consists of `[hh:mm:ss]`-prefixed transcript lines
+pub fn is_timestamped_transcript(text: &str) -> bool {
+    let mut lines = text.lines().filter(|l| !l.trim().is_empty()).peekable();
+    lines.peek().is_some() && lines.all(|l| TRANSCRIPT_MARKER_RE.is_match(l))
+}
+
+/// Timestamp (`hh:mm:ss`) at the start of a transcript chunk, if any
+pub fn leading_timestamp(text: &str) -> Option<String> {
+    TRANSCRIPT_MARKER_RE
+        .captures(text.trim_start())
+        .and_then(|c| c.get(1))
+        .map(|m| m.as_str().to_string())
+}
+
+/// Normalized mean of vectors of one length, None when there are none
+fn mean_vector(vectors: &[Vec<f32>]) -> Option<Vec<f32>> {
+    let dimensions = vectors.first()?.len();
+    let mut sum = vec![0.0; dimensions];
+    for vector in vectors.iter().filter(|v| v.len() == dimensions) {
+        for (total, value) in sum.iter_mut().zip(vector) {
+            *total += value;
+        }
+    }
+
+    let norm = sum.iter().map(|v| v * v).sum::<f32>().sqrt();
+    (norm > 0.0).then(|| sum.into_iter().map(|v| v / norm).collect())
+}
+
+/// Embedding of text that is not stored
+#[derive(Debug, Clone)]
+pub struct TextEmbedding {
+    pub vector: Vec<f32>,
+    /// Set when the text was embedded in parts
+    pub notice: Option<String>,
+}
+
 /// Text chunk with position information
 #[derive(Debug, Clone)]
 pub struct TextChunk {
@@ -252,6 +411,13 @@ pub struct SimilarityResult {
     pub similarity_score: f32,
 }
 
+/// Model and vector length; vectors are only comparable under one fingerprint
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct EmbeddingFingerprint {
+    pub model_id: String,
+    pub dimensions: usize,
+}
+
 /// Embedding model information
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct EmbeddingModelInfo {
@@ -332,7 +498,65 @@ mod tests {
             // For 2-word overlap setting, we should have at least some overlap
             // But since we use sentence boundaries, it might be less than exact
             // So we just check that chunks exist and are meaningful
-            assert!(chunks[i].text.len() > 0);
+            assert!(!chunks[i].text.is_empty());
         }
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_long_adhoc_text_is_averaged() {
+        let config = AiConfig::default();
+        let engine = EmbeddingEngine::new(&config).await.unwrap();
+
+        let short = engine.embed_text("A short note about cats.").await.unwrap();
+        assert!(short.notice.is_none());
+        assert!(engine.embed_text("  ").await.is_err());
+
+        let long = "cats sleep all day ".repeat(ADHOC_CHUNK_WORDS / 3);
+        let embedded = engine.embed_text(&long).await.unwrap();
+        assert_eq!(embedded.vector.len(), engine.get_dimensions());
+        assert!(embedded.notice.unwrap().contains("2 parts"));
+
+        assert_eq!(mean_vector(&[vec![3.0, 0.0], vec![0.0, 0.0], vec![1.0, 0.0]]), Some(vec![1.0, 0.0]));
+        assert_eq!(mean_vector(&[]), None);
+    }
+
+    #[tokio::test]
+    async fn test_transcript_chunks_align_with_segments() {
+        let config = AiConfig::default();
+        let engine = EmbeddingEngine::new(&config).await.unwrap();
+
+        let text = "[00:00:00] one two three\n[00:00:04] four five\n[00:00:09] six seven eight";
+        assert!(is_timestamped_transcript(text));
+        assert!(!is_timestamped_transcript("plain [00:00:01] text"));
+
+        let chunks = engine.generate_chunk_embeddings(text, 7, 1).await.unwrap();
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].text, "[00:00:00] one two three\n[00:00:04] four five");
+        assert_eq!(leading_timestamp(&chunks[1].text).as_deref(), Some("00:00:09"));
+        assert_eq!(&text[chunks[1].start_position..chunks[1].end_position], chunks[1].text);
+    }
+
+    #[tokio::test]
+    async fn test_reload_picks_up_downloaded_model() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = AiConfig {
+            models_dir: dir.path().to_path_buf(),
+            ..AiConfig::default()
+        };
+        let engine = EmbeddingEngine::new(&config).await.unwrap();
+        assert!(!engine.is_model_loaded());
+
+        let model_dir = dir.path().join(DEFAULT_EMBEDDING_MODEL);
+        std::fs::create_dir_all(&model_dir).unwrap();
+        std::fs::write(model_dir.join("config.json"), "{}").unwrap();
+        assert!(engine.reload(&model_dir).is_err());
+
+        std::fs::write(model_dir.join("tokenizer.json"), "{}").unwrap();
+        std::fs::write(model_dir.join("model.safetensors"), "").unwrap();
+        engine.reload(&model_dir).unwrap();
+        assert_eq!(engine.model_dir(), Some(model_dir));
+
+        // A fresh engine finds the files on its own
+        assert!(EmbeddingEngine::new(&config).await.unwrap().is_model_loaded());
+    }
+}
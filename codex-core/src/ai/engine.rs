@@ -5,6 +5,7 @@ use std::sync::Arc;
 use async_trait::async_trait;
 use tokio_util::sync::CancellationToken;
 
+use crate::config::GpuLayers;
 use crate::{CodexError, CodexResult};
 
 /// Engine parameters for model loading and configuration
@@ -14,8 +15,8 @@ pub struct EngineParams {
     pub num_threads: usize,
     /// Maximum context length for the model
     pub context_length: usize,
-    /// Number of GPU layers to offload (0 = CPU only)
-    pub gpu_layers: usize,
+    /// Number of GPU layers to offload (`Count(0)` = CPU only), or `Auto` to fit them to the free VRAM
+    pub gpu_layers: GpuLayers,
     /// Batch size for processing
     pub batch_size: usize,
     /// Enable memory mapping
@@ -31,7 +32,7 @@ impl Default for EngineParams {
         Self {
             num_threads: num_cpus::get(),
             context_length: 4096,
-            gpu_layers: 0,
+            gpu_layers: GpuLayers::Count(0),
             batch_size: 512,
             use_mmap: true,
             use_metal: cfg!(target_os = "macos"),
@@ -40,6 +41,17 @@ impl Default for EngineParams {
     }
 }
 
+impl EngineParams {
+    /// GPU the model would load on, `None` for the CPU
+    pub fn gpu_device(&self) -> Option<super::gpu::GpuDevice> {
+        if self.use_metal {
+            Some(super::gpu::GpuDevice::Metal)
+        } else {
+            self.cuda_device_id.map(|id| super::gpu::GpuDevice::Cuda(id.max(0) as usize))
+        }
+    }
+}
+
 /// Generation settings for text completion
 #[derive(Debug, Clone)]
 pub struct GenerationSettings {
@@ -76,6 +88,18 @@ impl Default for GenerationSettings {
     }
 }
 
+impl GenerationSettings {
+    /// Sampling settings of `config`, with the other settings at their defaults
+    pub fn from_config(config: &crate::config::AiConfig) -> Self {
+        Self {
+            temperature: config.temperature,
+            top_p: config.top_p,
+            max_tokens: config.max_tokens,
+            ..Self::default()
+        }
+    }
+}
+
 /// Engine types supported by the system
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum EngineType {
@@ -87,6 +111,8 @@ pub enum EngineType {
     Remote,
     /// ONNX runtime models
     ONNX,
+    /// Canned responses for tests, see `MockEngine`
+    Mock,
 }
 
 /// Main LLM Engine trait for unified inference interface
@@ -118,6 +144,11 @@ pub trait LLMEngine: Send + Sync {
         Err(CodexError::ai_inference("Embeddings not supported by this engine"))
     }
 
+    /// Tokens `text` encodes to; estimated for engines without a tokenizer
+    fn count_tokens(&self, text: &str) -> usize {
+        super::summarize::estimate_tokens(text)
+    }
+
     /// Get the engine type
     fn engine_type(&self) -> EngineType;
 
@@ -151,6 +182,8 @@ pub struct ModelInfo {
     pub file_size_bytes: u64,
     pub is_loaded: bool,
     pub device: String,
+    /// How the layers were split between GPU and CPU, for engines that plan it
+    pub load_plan: Option<ModelLoadPlan>,
 }
 
 /// Engine factory for creating different types of LLM engines
@@ -176,6 +209,9 @@ impl EngineFactory {
             EngineType::ONNX => {
                 Err(CodexError::ai_inference("ONNX engine not yet implemented"))
             }
+            EngineType::Mock => {
+                Err(CodexError::validation("The mock engine has no model file; pass it to AiEngine::with_engine"))
+            }
         }
     }
 
@@ -216,10 +252,11 @@ pub struct RemoteEngine;
 // Placeholder implementations - these will be replaced with real implementations
 
 use std::fs::File;
-use std::io::{BufReader, Read};
-use std::collections::HashMap;
-use byteorder::{LittleEndian, ReadBytesExt};
 use memmap2::Mmap;
+use super::gguf;
+use super::gpu;
+use super::load_plan::{self, ModelLoadPlan};
+pub use super::gguf::{GGUFMetadata, GGUFTensorInfo, GGUFValue};
 use candle_core::{Device, Tensor};
 use candle_core::backend::BackendDevice;
 use candle_transformers::models::llama::{Llama, LlamaConfig, Config};
@@ -238,6 +275,7 @@ pub struct GGUFEngine {
     model_path: std::path::PathBuf,
     memory_tracker: MemoryTracker,
     model_manifest: Option<crate::update::manifest::ModelManifest>,
+    load_plan: ModelLoadPlan,
     is_loaded: bool,
 }
 
@@ -257,41 +295,6 @@ pub enum DeviceType {
     Metal,
 }
 
-/// GGUF file metadata extracted from header
-#[derive(Debug, Clone)]
-pub struct GGUFMetadata {
-    pub version: u32,
-    pub tensor_count: u64,
-    pub metadata_kv_count: u64,
-    pub metadata: HashMap<String, GGUFValue>,
-    pub tensors: Vec<GGUFTensorInfo>,
-}
-
-#[derive(Debug, Clone)]
-pub struct GGUFTensorInfo {
-    pub name: String,
-    pub dimensions: Vec<u64>,
-    pub tensor_type: u32,
-    pub offset: u64,
-}
-
-#[derive(Debug, Clone)]
-pub enum GGUFValue {
-    UInt8(u8),
-    Int8(i8),
-    UInt16(u16),
-    Int16(i16),
-    UInt32(u32),
-    Int32(i32),
-    Float32(f32),
-    Bool(bool),
-    String(String),
-    Array(Vec<GGUFValue>),
-    UInt64(u64),
-    Int64(i64),
-    Float64(f64),
-}
-
 impl MemoryTracker {
     pub fn new(device_type: DeviceType) -> Self {
         let mut system = System::new_all();
@@ -299,7 +302,7 @@ impl MemoryTracker {
         
         let initial_memory = match device_type {
             DeviceType::Cpu => system.used_memory(),
-            DeviceType::Cuda(_) => Self::get_cuda_memory_usage().unwrap_or(0),
+            DeviceType::Cuda(id) => Self::get_cuda_memory_usage(id).unwrap_or(0),
             DeviceType::Metal => Self::get_metal_memory_usage().unwrap_or(0),
         };
 
@@ -326,7 +329,8 @@ impl MemoryTracker {
                 self.system.refresh_memory();
                 self.system.used_memory().saturating_sub(self.initial_memory)
             }
-            DeviceType::Cuda(_) => Self::get_cuda_memory_usage().unwrap_or(0),
+            DeviceType::Cuda(id) => Self::get_cuda_memory_usage(id)
+                .map_or(0, |used| used.saturating_sub(self.initial_memory)),
             DeviceType::Metal => Self::get_metal_memory_usage().unwrap_or(0),
         }
     }
@@ -339,160 +343,21 @@ impl MemoryTracker {
         self.cache_memory
     }
 
-    #[cfg(feature = "ai-gpu")]
-    fn get_cuda_memory_usage() -> Option<u64> {
-        // In a real implementation, this would use CUDA runtime API
-        // For now, return None to indicate unavailable
-        None
-    }
-
-    #[cfg(not(feature = "ai-gpu"))]
-    fn get_cuda_memory_usage() -> Option<u64> {
-        None
-    }
-
-    #[cfg(feature = "ai-metal")]
-    fn get_metal_memory_usage() -> Option<u64> {
-        // In a real implementation, this would use Metal performance shaders API
-        // For now, return None to indicate unavailable
-        None
+    /// Bytes used on the whole CUDA device
+    fn get_cuda_memory_usage(device_id: i32) -> Option<u64> {
+        gpu::cuda_memory_info(device_id.max(0) as usize).map(|(used, _)| used)
     }
 
-    #[cfg(not(feature = "ai-metal"))]
+    /// Bytes this process has allocated on the Metal device
     fn get_metal_memory_usage() -> Option<u64> {
-        None
+        gpu::metal_memory_info().map(|(used, _)| used)
     }
 }
 
 impl GGUFEngine {
     /// Parse GGUF file metadata and validate format
     pub fn parse_gguf_metadata(path: &Path) -> CodexResult<GGUFMetadata> {
-        let file = File::open(path)
-            .map_err(|e| CodexError::io(e))?;
-        let mut reader = BufReader::new(file);
-
-        // Read and verify GGUF magic number
-        let magic = reader.read_u32::<LittleEndian>()
-            .map_err(|e| CodexError::ai_inference(format!("Failed to read GGUF magic: {}", e)))?;
-        
-        if magic != 0x46554747 { // "GGUF" in little-endian
-            return Err(CodexError::validation("Invalid GGUF file format"));
-        }
-
-        // Read version
-        let version = reader.read_u32::<LittleEndian>()
-            .map_err(|e| CodexError::ai_inference(format!("Failed to read GGUF version: {}", e)))?;
-
-        // Read tensor count
-        let tensor_count = reader.read_u64::<LittleEndian>()
-            .map_err(|e| CodexError::ai_inference(format!("Failed to read tensor count: {}", e)))?;
-
-        // Read metadata KV count
-        let metadata_kv_count = reader.read_u64::<LittleEndian>()
-            .map_err(|e| CodexError::ai_inference(format!("Failed to read metadata count: {}", e)))?;
-
-        // Parse metadata key-value pairs
-        let mut metadata = HashMap::new();
-        for _ in 0..metadata_kv_count {
-            let (key, value) = Self::read_metadata_kv(&mut reader)?;
-            metadata.insert(key, value);
-        }
-
-        // Parse tensor information
-        let mut tensors = Vec::new();
-        for _ in 0..tensor_count {
-            let tensor_info = Self::read_tensor_info(&mut reader)?;
-            tensors.push(tensor_info);
-        }
-
-        Ok(GGUFMetadata {
-            version,
-            tensor_count,
-            metadata_kv_count,
-            metadata,
-            tensors,
-        })
-    }
-
-    fn read_metadata_kv(reader: &mut BufReader<File>) -> CodexResult<(String, GGUFValue)> {
-        // Read key string
-        let key_len = reader.read_u64::<LittleEndian>()
-            .map_err(|e| CodexError::ai_inference(format!("Failed to read key length: {}", e)))?;
-        
-        let mut key_bytes = vec![0u8; key_len as usize];
-        reader.read_exact(&mut key_bytes)
-            .map_err(|e| CodexError::ai_inference(format!("Failed to read key: {}", e)))?;
-        
-        let key = String::from_utf8(key_bytes)
-            .map_err(|e| CodexError::ai_inference(format!("Invalid UTF-8 in key: {}", e)))?;
-
-        // Read value type
-        let value_type = reader.read_u32::<LittleEndian>()
-            .map_err(|e| CodexError::ai_inference(format!("Failed to read value type: {}", e)))?;
-
-        // Read value based on type
-        let value = match value_type {
-            0 => GGUFValue::UInt8(reader.read_u8().map_err(|e| CodexError::ai_inference(format!("Failed to read u8: {}", e)))?),
-            1 => GGUFValue::Int8(reader.read_i8().map_err(|e| CodexError::ai_inference(format!("Failed to read i8: {}", e)))?),
-            2 => GGUFValue::UInt16(reader.read_u16::<LittleEndian>().map_err(|e| CodexError::ai_inference(format!("Failed to read u16: {}", e)))?),
-            3 => GGUFValue::Int16(reader.read_i16::<LittleEndian>().map_err(|e| CodexError::ai_inference(format!("Failed to read i16: {}", e)))?),
-            4 => GGUFValue::UInt32(reader.read_u32::<LittleEndian>().map_err(|e| CodexError::ai_inference(format!("Failed to read u32: {}", e)))?),
-            5 => GGUFValue::Int32(reader.read_i32::<LittleEndian>().map_err(|e| CodexError::ai_inference(format!("Failed to read i32: {}", e)))?),
-            6 => GGUFValue::Float32(reader.read_f32::<LittleEndian>().map_err(|e| CodexError::ai_inference(format!("Failed to read f32: {}", e)))?),
-            7 => GGUFValue::Bool(reader.read_u8().map_err(|e| CodexError::ai_inference(format!("Failed to read bool: {}", e)))? != 0),
-            8 => {
-                let str_len = reader.read_u64::<LittleEndian>().map_err(|e| CodexError::ai_inference(format!("Failed to read string length: {}", e)))?;
-                let mut str_bytes = vec![0u8; str_len as usize];
-                reader.read_exact(&mut str_bytes).map_err(|e| CodexError::ai_inference(format!("Failed to read string: {}", e)))?;
-                let string = String::from_utf8(str_bytes).map_err(|e| CodexError::ai_inference(format!("Invalid UTF-8 in string: {}", e)))?;
-                GGUFValue::String(string)
-            },
-            10 => GGUFValue::UInt64(reader.read_u64::<LittleEndian>().map_err(|e| CodexError::ai_inference(format!("Failed to read u64: {}", e)))?),
-            11 => GGUFValue::Int64(reader.read_i64::<LittleEndian>().map_err(|e| CodexError::ai_inference(format!("Failed to read i64: {}", e)))?),
-            12 => GGUFValue::Float64(reader.read_f64::<LittleEndian>().map_err(|e| CodexError::ai_inference(format!("Failed to read f64: {}", e)))?),
-            _ => return Err(CodexError::validation(format!("Unknown GGUF value type: {}", value_type))),
-        };
-
-        Ok((key, value))
-    }
-
-    fn read_tensor_info(reader: &mut BufReader<File>) -> CodexResult<GGUFTensorInfo> {
-        // Read tensor name
-        let name_len = reader.read_u64::<LittleEndian>()
-            .map_err(|e| CodexError::ai_inference(format!("Failed to read tensor name length: {}", e)))?;
-        
-        let mut name_bytes = vec![0u8; name_len as usize];
-        reader.read_exact(&mut name_bytes)
-            .map_err(|e| CodexError::ai_inference(format!("Failed to read tensor name: {}", e)))?;
-        
-        let name = String::from_utf8(name_bytes)
-            .map_err(|e| CodexError::ai_inference(format!("Invalid UTF-8 in tensor name: {}", e)))?;
-
-        // Read dimensions
-        let n_dims = reader.read_u32::<LittleEndian>()
-            .map_err(|e| CodexError::ai_inference(format!("Failed to read dimensions count: {}", e)))?;
-        
-        let mut dimensions = Vec::with_capacity(n_dims as usize);
-        for _ in 0..n_dims {
-            let dim = reader.read_u64::<LittleEndian>()
-                .map_err(|e| CodexError::ai_inference(format!("Failed to read dimension: {}", e)))?;
-            dimensions.push(dim);
-        }
-
-        // Read tensor type
-        let tensor_type = reader.read_u32::<LittleEndian>()
-            .map_err(|e| CodexError::ai_inference(format!("Failed to read tensor type: {}", e)))?;
-
-        // Read tensor offset
-        let offset = reader.read_u64::<LittleEndian>()
-            .map_err(|e| CodexError::ai_inference(format!("Failed to read tensor offset: {}", e)))?;
-
-        Ok(GGUFTensorInfo {
-            name,
-            dimensions,
-            tensor_type,
-            offset,
-        })
+        gguf::parse_metadata_file(path)
     }
 
     /// Convert GGUF metadata to LlamaConfig
@@ -586,20 +451,32 @@ impl GGUFEngine {
         })
     }
 
+    /// How `params` would split the model at `model_path` between GPU and CPU, without loading it
+    pub fn get_model_load_plan(model_path: &Path, params: &EngineParams) -> CodexResult<ModelLoadPlan> {
+        let metadata = Self::parse_gguf_metadata(model_path)?;
+        let config = Self::metadata_to_config(&metadata)?;
+        Ok(Self::plan_load(&metadata, &config, params))
+    }
+
+    fn plan_load(metadata: &GGUFMetadata, config: &LlamaConfig, params: &EngineParams) -> ModelLoadPlan {
+        let vram = params.gpu_device().and_then(gpu::GpuDevice::query);
+        load_plan::plan_gpu_layers(metadata, config, params.context_length, params.gpu_layers, vram)
+    }
+
     /// Calculate SHA256 checksum of model file
     pub async fn calculate_checksum(path: &Path) -> CodexResult<String> {
         use tokio::fs::File;
         use tokio::io::AsyncReadExt;
 
         let mut file = File::open(path).await
-            .map_err(|e| CodexError::io(e))?;
+            .map_err(CodexError::io)?;
         
         let mut hasher = Sha256::new();
         let mut buffer = vec![0u8; 8192]; // 8KB buffer
         
         loop {
             let bytes_read = file.read(&mut buffer).await
-                .map_err(|e| CodexError::io(e))?;
+                .map_err(CodexError::io)?;
             
             if bytes_read == 0 {
                 break;
@@ -626,9 +503,29 @@ impl GGUFEngine {
 impl LLMEngine for GGUFEngine {
     async fn load(model_path: &Path, params: EngineParams) -> CodexResult<Arc<dyn LLMEngine>> {
         info!("Loading GGUF model from: {}", model_path.display());
+
+        // Parse GGUF metadata
+        let metadata = Self::parse_gguf_metadata(model_path)?;
+        info!("Parsed GGUF metadata: version={}, tensors={}", metadata.version, metadata.tensor_count);
+        
+        // Convert metadata to LlamaConfig
+        let config = Self::metadata_to_config(&metadata)?;
+        info!("Model config: vocab_size={}, hidden_size={}, layers={}", 
+              config.vocab_size, config.hidden_size, config.num_hidden_layers);
+
+        let load_plan = Self::plan_load(&metadata, &config, &params);
+        info!("GPU offload plan: {}", load_plan);
+
+        // Candle keeps the whole model on one device, so an automatic plan
+        // that fits only part of the model stays on the CPU
+        let use_gpu = load_plan.gpu_layers > 0 && (!load_plan.auto || load_plan.fully_offloaded());
+        if load_plan.auto && load_plan.gpu_layers > 0 && !use_gpu {
+            warn!("Only {} of {} layers fit in VRAM, loading the model on the CPU",
+                  load_plan.gpu_layers, load_plan.total_layers);
+        }
         
         // Determine device
-        let device = if params.gpu_layers > 0 {
+        let device = if use_gpu {
             if params.use_metal {
                 Device::new_metal(0)
                     .map_err(|e| CodexError::ai_inference(format!("Failed to initialize Metal device: {}", e)))?
@@ -657,19 +554,10 @@ impl LLMEngine for GGUFEngine {
 
         // Initialize memory tracker
         let mut memory_tracker = MemoryTracker::new(device_type);
-        
-        // Parse GGUF metadata
-        let metadata = Self::parse_gguf_metadata(model_path)?;
-        info!("Parsed GGUF metadata: version={}, tensors={}", metadata.version, metadata.tensor_count);
-        
-        // Convert metadata to LlamaConfig
-        let config = Self::metadata_to_config(&metadata)?;
-        info!("Model config: vocab_size={}, hidden_size={}, layers={}", 
-              config.vocab_size, config.hidden_size, config.num_hidden_layers);
 
         // Memory map the model file for efficient loading
         let file = File::open(model_path)
-            .map_err(|e| CodexError::io(e))?;
+            .map_err(CodexError::io)?;
         let mmap = unsafe { Mmap::map(&file) }
             .map_err(|e| CodexError::ai_inference(format!("Failed to memory map model: {}", e)))?;
         
@@ -738,6 +626,7 @@ impl LLMEngine for GGUFEngine {
             model_path: model_path.to_path_buf(),
             memory_tracker,
             model_manifest: None,
+            load_plan,
             is_loaded: true,
         };
 
@@ -807,6 +696,13 @@ impl LLMEngine for GGUFEngine {
         Ok(response)
     }
 
+    fn count_tokens(&self, text: &str) -> usize {
+        match self.tokenizer.as_ref().map(|tokenizer| tokenizer.encode(text, false)) {
+            Some(Ok(encoding)) => encoding.get_ids().len(),
+            _ => super::summarize::estimate_tokens(text),
+        }
+    }
+
     fn engine_type(&self) -> EngineType {
         EngineType::GGUF
     }
@@ -823,12 +719,13 @@ impl LLMEngine for GGUFEngine {
                 .to_string(),
             architecture: "llama".to_string(),
             parameter_count: format!("{}B", self.estimate_parameter_count()),
-            quantization: Some("q4_k_m".to_string()), // Could be extracted from metadata
+            quantization: self.load_plan.quantization.clone(),
             context_length: self.config.max_position_embeddings,
             vocab_size: self.config.vocab_size,
             file_size_bytes: self.memory_tracker.get_model_memory(),
             is_loaded: self.is_loaded,
             device: format!("{:?}", self.device),
+            load_plan: Some(self.load_plan.clone()),
         }
     }
 
@@ -897,13 +794,16 @@ impl GGUFEngine {
         // Attention parameters (Q, K, V, O projections)
         let attention_params = num_layers * hidden_size * hidden_size * 4;
         
-        // Feed-forward network parameters (up and down projections)
-        let ffn_params = num_layers * hidden_size * intermediate_size * 2;
+        // Feed-forward network parameters (gate, up and down projections)
+        let ffn_params = num_layers * hidden_size * intermediate_size * 3;
         
         // Layer norm parameters
         let norm_params = num_layers * hidden_size * 2; // RMS norm for attention and FFN
         
-        embedding_params + attention_params + ffn_params + norm_params
+        // Output head, unless it shares the embedding weights
+        let lm_head_params = if config.tie_word_embeddings.unwrap_or(false) { 0 } else { embedding_params };
+        
+        embedding_params + attention_params + ffn_params + norm_params + lm_head_params
     }
 
     async fn generate_with_model(&self, input_tokens: &[u32], settings: &GenerationSettings) -> CodexResult<String> {
@@ -1086,6 +986,7 @@ impl LLMEngine for HuggingFaceEngine {
             file_size_bytes: 0,
             is_loaded: false,
             device: "cpu".to_string(),
+            load_plan: None,
         }
     }
 
@@ -1141,6 +1042,7 @@ impl LLMEngine for RemoteEngine {
             file_size_bytes: 0,
             is_loaded: false,
             device: "remote".to_string(),
+            load_plan: None,
         }
     }
 
@@ -1181,7 +1083,7 @@ mod tests {
         let params = EngineParams::default();
         assert!(params.num_threads > 0);
         assert_eq!(params.context_length, 4096);
-        assert_eq!(params.gpu_layers, 0);
+        assert_eq!(params.gpu_layers, GpuLayers::Count(0));
     }
 
     #[test]
@@ -1192,4 +1094,27 @@ mod tests {
         assert_eq!(settings.max_tokens, 512);
         assert!(!settings.stop_sequences.is_empty());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_parameter_estimation() {
+        // Llama 2 7B
+        let config = LlamaConfig {
+            vocab_size: 32000,
+            hidden_size: 4096,
+            intermediate_size: 11008,
+            num_hidden_layers: 32,
+            num_attention_heads: 32,
+            num_key_value_heads: Some(32),
+            max_position_embeddings: 4096,
+            rms_norm_eps: 1e-5,
+            rope_theta: 10000.0,
+            bos_token_id: None,
+            eos_token_id: None,
+            rope_scaling: None,
+            tie_word_embeddings: None,
+        };
+
+        let billion_params = GGUFEngine::estimate_parameters(&config) as f64 / 1_000_000_000.0;
+        assert!(billion_params > 6.0 && billion_params < 8.0, "expected about 7B parameters, got {:.2}B", billion_params);
+    }
+}
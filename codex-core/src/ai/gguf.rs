@@ -0,0 +1,607 @@
+//! GGUF header parsing
+//!
+//! Model files come from downloads and user imports, so every length and
+//! count in the header is untrusted. Before allocating, the parser checks
+//! each length against the bytes left in the file and caps array nesting
+//! and the total number of values. A malformed or truncated file produces
+//! a [`GGUFError`], which converts to `CodexError::Validation`; it never
+//! panics or allocates for a length the file cannot back.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use thiserror::Error;
+
+use crate::{CodexError, CodexResult};
+
+/// "GGUF" read as a little-endian u32
+pub const GGUF_MAGIC: u32 = 0x4655_4747;
+/// Oldest format version with 64-bit lengths and counts
+pub const MIN_GGUF_VERSION: u32 = 2;
+/// Newest format version understood
+pub const MAX_GGUF_VERSION: u32 = 3;
+/// Most metadata values accepted across the header, array elements
+/// included (a large tokenizer needs well under a million)
+pub const MAX_METADATA_VALUES: u64 = 8 * 1024 * 1024;
+/// Deepest nesting of arrays inside arrays
+pub const MAX_ARRAY_DEPTH: usize = 8;
+/// Most dimensions accepted for a tensor
+pub const MAX_TENSOR_DIMS: u32 = 8;
+
+/// GGUF file metadata extracted from header
+#[derive(Debug, Clone, PartialEq)]
+pub struct GGUFMetadata {
+    pub version: u32,
+    pub tensor_count: u64,
+    pub metadata_kv_count: u64,
+    pub metadata: HashMap<String, GGUFValue>,
+    pub tensors: Vec<GGUFTensorInfo>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GGUFTensorInfo {
+    pub name: String,
+    pub dimensions: Vec<u64>,
+    pub tensor_type: u32,
+    pub offset: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GGUFValue {
+    UInt8(u8),
+    Int8(i8),
+    UInt16(u16),
+    Int16(i16),
+    UInt32(u32),
+    Int32(i32),
+    Float32(f32),
+    Bool(bool),
+    String(String),
+    Array(Vec<GGUFValue>),
+    UInt64(u64),
+    Int64(i64),
+    Float64(f64),
+}
+
+impl GGUFValue {
+    /// Type id of this value in the file format
+    pub fn type_id(&self) -> u32 {
+        match self {
+            GGUFValue::UInt8(_) => 0,
+            GGUFValue::Int8(_) => 1,
+            GGUFValue::UInt16(_) => 2,
+            GGUFValue::Int16(_) => 3,
+            GGUFValue::UInt32(_) => 4,
+            GGUFValue::Int32(_) => 5,
+            GGUFValue::Float32(_) => 6,
+            GGUFValue::Bool(_) => 7,
+            GGUFValue::String(_) => 8,
+            GGUFValue::Array(_) => 9,
+            GGUFValue::UInt64(_) => 10,
+            GGUFValue::Int64(_) => 11,
+            GGUFValue::Float64(_) => 12,
+        }
+    }
+}
+
+/// Elements per block and bytes per block of a GGML tensor type
+///
+/// Quantized types store weights in fixed-size blocks; plain types are
+/// blocks of one element. `None` for types this table does not know.
+pub fn ggml_type_block(tensor_type: u32) -> Option<(u64, u64)> {
+    match tensor_type {
+        0 => Some((1, 4)),      // F32
+        1 => Some((1, 2)),      // F16
+        2 => Some((32, 18)),    // Q4_0
+        3 => Some((32, 20)),    // Q4_1
+        6 => Some((32, 22)),    // Q5_0
+        7 => Some((32, 24)),    // Q5_1
+        8 => Some((32, 34)),    // Q8_0
+        9 => Some((32, 36)),    // Q8_1
+        10 => Some((256, 84)),  // Q2_K
+        11 => Some((256, 110)), // Q3_K
+        12 => Some((256, 144)), // Q4_K
+        13 => Some((256, 176)), // Q5_K
+        14 => Some((256, 210)), // Q6_K
+        15 => Some((256, 292)), // Q8_K
+        24 => Some((1, 1)),     // I8
+        25 => Some((1, 2)),     // I16
+        26 => Some((1, 4)),     // I32
+        27 | 28 => Some((1, 8)), // I64, F64
+        30 => Some((1, 2)),     // BF16
+        _ => None,
+    }
+}
+
+/// Name of a GGML tensor type, as in llama.cpp
+pub fn ggml_type_name(tensor_type: u32) -> Option<&'static str> {
+    let name = match tensor_type {
+        0 => "F32",
+        1 => "F16",
+        2 => "Q4_0",
+        3 => "Q4_1",
+        6 => "Q5_0",
+        7 => "Q5_1",
+        8 => "Q8_0",
+        9 => "Q8_1",
+        10 => "Q2_K",
+        11 => "Q3_K",
+        12 => "Q4_K",
+        13 => "Q5_K",
+        14 => "Q6_K",
+        15 => "Q8_K",
+        24 => "I8",
+        25 => "I16",
+        26 => "I32",
+        27 => "I64",
+        28 => "F64",
+        30 => "BF16",
+        _ => return None,
+    };
+    Some(name)
+}
+
+impl GGUFTensorInfo {
+    /// Bytes the tensor's data takes in the file, `None` for unknown types
+    pub fn size_bytes(&self) -> Option<u64> {
+        let (block_elements, block_bytes) = ggml_type_block(self.tensor_type)?;
+        let elements = self.dimensions.iter().try_fold(1u64, |total, &dim| total.checked_mul(dim))?;
+        elements.div_ceil(block_elements).checked_mul(block_bytes)
+    }
+}
+
+/// Why a GGUF header could not be parsed
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum GGUFError {
+    #[error("not a GGUF file (magic {0:#010x})")]
+    InvalidMagic(u32),
+    #[error("unsupported GGUF version {0}")]
+    UnsupportedVersion(u32),
+    #[error("file truncated at byte {offset} while reading {what}")]
+    Truncated { what: &'static str, offset: u64 },
+    #[error("{what} of {len} at byte {offset} does not fit in the {remaining} bytes left")]
+    LengthOutOfBounds { what: &'static str, len: u64, offset: u64, remaining: u64 },
+    #[error("array of {len} elements at byte {offset} takes the header past {MAX_METADATA_VALUES} values")]
+    TooManyValues { len: u64, offset: u64 },
+    #[error("arrays nested more than {MAX_ARRAY_DEPTH} deep at byte {offset}")]
+    NestingTooDeep { offset: u64 },
+    #[error("unknown value type {value_type} at byte {offset}")]
+    UnknownValueType { value_type: u32, offset: u64 },
+    #[error("{what} at byte {offset} is not valid UTF-8")]
+    InvalidUtf8 { what: &'static str, offset: u64 },
+    #[error("tensor {name} has {dims} dimensions (at most {MAX_TENSOR_DIMS} supported)")]
+    TooManyDimensions { name: String, dims: u32 },
+    #[error("read failed at byte {offset}: {message}")]
+    Io { offset: u64, message: String },
+}
+
+impl From<GGUFError> for CodexError {
+    fn from(error: GGUFError) -> Self {
+        CodexError::validation(format!("Invalid GGUF file: {}", error))
+    }
+}
+
+/// Fewest bytes one value of a type occupies, for bounding array counts
+fn min_value_size(value_type: u32) -> Option<u64> {
+    match value_type {
+        0 | 1 | 7 => Some(1),
+        2 | 3 => Some(2),
+        4..=6 => Some(4),
+        // Length prefix only
+        8 => Some(8),
+        // Element type and count
+        9 => Some(12),
+        10..=12 => Some(8),
+        _ => None,
+    }
+}
+
+/// Reader that knows how many bytes of the file are left
+struct BoundedReader<R> {
+    inner: R,
+    offset: u64,
+    len: u64,
+    /// Values still allowed before [`MAX_METADATA_VALUES`] is reached
+    value_budget: u64,
+}
+
+impl<R: Read> BoundedReader<R> {
+    fn remaining(&self) -> u64 {
+        self.len.saturating_sub(self.offset)
+    }
+
+    fn fill(&mut self, buf: &mut [u8], what: &'static str) -> Result<(), GGUFError> {
+        if (buf.len() as u64) > self.remaining() {
+            return Err(GGUFError::Truncated { what, offset: self.offset });
+        }
+        self.inner.read_exact(buf).map_err(|e| match e.kind() {
+            std::io::ErrorKind::UnexpectedEof => GGUFError::Truncated { what, offset: self.offset },
+            _ => GGUFError::Io { offset: self.offset, message: e.to_string() },
+        })?;
+        self.offset += buf.len() as u64;
+        Ok(())
+    }
+
+    fn array<const N: usize>(&mut self, what: &'static str) -> Result<[u8; N], GGUFError> {
+        let mut buf = [0u8; N];
+        self.fill(&mut buf, what)?;
+        Ok(buf)
+    }
+
+    fn u32(&mut self, what: &'static str) -> Result<u32, GGUFError> {
+        self.array(what).map(u32::from_le_bytes)
+    }
+
+    fn u64(&mut self, what: &'static str) -> Result<u64, GGUFError> {
+        self.array(what).map(u64::from_le_bytes)
+    }
+
+    /// Length-prefixed UTF-8 string, its length checked before allocating
+    fn string(&mut self, what: &'static str) -> Result<String, GGUFError> {
+        let len = self.u64(what)?;
+        let offset = self.offset;
+        if len > self.remaining() {
+            return Err(GGUFError::LengthOutOfBounds { what, len, offset, remaining: self.remaining() });
+        }
+        let mut bytes = vec![0u8; len as usize];
+        self.fill(&mut bytes, what)?;
+        String::from_utf8(bytes).map_err(|_| GGUFError::InvalidUtf8 { what, offset })
+    }
+
+    fn value(&mut self, value_type: u32, depth: usize) -> Result<GGUFValue, GGUFError> {
+        Ok(match value_type {
+            0 => GGUFValue::UInt8(u8::from_le_bytes(self.array("u8 value")?)),
+            1 => GGUFValue::Int8(i8::from_le_bytes(self.array("i8 value")?)),
+            2 => GGUFValue::UInt16(u16::from_le_bytes(self.array("u16 value")?)),
+            3 => GGUFValue::Int16(i16::from_le_bytes(self.array("i16 value")?)),
+            4 => GGUFValue::UInt32(u32::from_le_bytes(self.array("u32 value")?)),
+            5 => GGUFValue::Int32(i32::from_le_bytes(self.array("i32 value")?)),
+            6 => GGUFValue::Float32(f32::from_le_bytes(self.array("f32 value")?)),
+            7 => GGUFValue::Bool(u8::from_le_bytes(self.array("bool value")?) != 0),
+            8 => GGUFValue::String(self.string("string value")?),
+            9 => self.array_value(depth)?,
+            10 => GGUFValue::UInt64(u64::from_le_bytes(self.array("u64 value")?)),
+            11 => GGUFValue::Int64(i64::from_le_bytes(self.array("i64 value")?)),
+            12 => GGUFValue::Float64(f64::from_le_bytes(self.array("f64 value")?)),
+            _ => return Err(GGUFError::UnknownValueType { value_type, offset: self.offset.saturating_sub(4) }),
+        })
+    }
+
+    fn array_value(&mut self, depth: usize) -> Result<GGUFValue, GGUFError> {
+        if depth >= MAX_ARRAY_DEPTH {
+            return Err(GGUFError::NestingTooDeep { offset: self.offset });
+        }
+        let type_offset = self.offset;
+        let element_type = self.u32("array element type")?;
+        let element_size = min_value_size(element_type)
+            .ok_or(GGUFError::UnknownValueType { value_type: element_type, offset: type_offset })?;
+
+        let offset = self.offset;
+        let len = self.u64("array length")?;
+        if len > self.value_budget {
+            return Err(GGUFError::TooManyValues { len, offset });
+        }
+        self.value_budget -= len;
+        if len.saturating_mul(element_size) > self.remaining() {
+            return Err(GGUFError::LengthOutOfBounds { what: "array", len, offset, remaining: self.remaining() });
+        }
+
+        let mut values = Vec::with_capacity(len as usize);
+        for _ in 0..len {
+            values.push(self.value(element_type, depth + 1)?);
+        }
+        Ok(GGUFValue::Array(values))
+    }
+
+    fn metadata_kv(&mut self) -> Result<(String, GGUFValue), GGUFError> {
+        let key = self.string("metadata key")?;
+        let value_type = self.u32("metadata value type")?;
+        let value = self.value(value_type, 0)?;
+        Ok((key, value))
+    }
+
+    fn tensor_info(&mut self) -> Result<GGUFTensorInfo, GGUFError> {
+        let name = self.string("tensor name")?;
+        let dims = self.u32("tensor dimension count")?;
+        if dims > MAX_TENSOR_DIMS {
+            return Err(GGUFError::TooManyDimensions { name, dims });
+        }
+        let dimensions = (0..dims).map(|_| self.u64("tensor dimension")).collect::<Result<Vec<_>, _>>()?;
+        let tensor_type = self.u32("tensor type")?;
+        let offset = self.u64("tensor offset")?;
+        Ok(GGUFTensorInfo { name, dimensions, tensor_type, offset })
+    }
+}
+
+/// Parse a GGUF header from a reader over a file of `len` bytes
+pub fn parse_metadata<R: Read>(reader: R, len: u64) -> Result<GGUFMetadata, GGUFError> {
+    let mut reader = BoundedReader { inner: reader, offset: 0, len, value_budget: MAX_METADATA_VALUES };
+
+    let magic = reader.u32("magic")?;
+    if magic != GGUF_MAGIC {
+        return Err(GGUFError::InvalidMagic(magic));
+    }
+    let version = reader.u32("version")?;
+    if !(MIN_GGUF_VERSION..=MAX_GGUF_VERSION).contains(&version) {
+        return Err(GGUFError::UnsupportedVersion(version));
+    }
+    let tensor_count = reader.u64("tensor count")?;
+    let metadata_kv_count = reader.u64("metadata count")?;
+
+    // Counts are not trusted for preallocation; each entry takes real bytes
+    let mut metadata = HashMap::new();
+    for _ in 0..metadata_kv_count {
+        let (key, value) = reader.metadata_kv()?;
+        metadata.insert(key, value);
+    }
+
+    let mut tensors = Vec::new();
+    for _ in 0..tensor_count {
+        tensors.push(reader.tensor_info()?);
+    }
+
+    Ok(GGUFMetadata { version, tensor_count, metadata_kv_count, metadata, tensors })
+}
+
+/// Parse a GGUF header held in memory
+pub fn parse_metadata_bytes(bytes: &[u8]) -> Result<GGUFMetadata, GGUFError> {
+    parse_metadata(bytes, bytes.len() as u64)
+}
+
+/// Parse the header of a GGUF file on disk
+pub fn parse_metadata_file(path: &Path) -> CodexResult<GGUFMetadata> {
+    let file = File::open(path)?;
+    let len = file.metadata()?.len();
+    Ok(parse_metadata(BufReader::new(file), len)?)
+}
+
+/// Serialize a header in the GGUF v3 layout, for tests and fixtures
+pub fn encode_metadata(metadata: &GGUFMetadata) -> Vec<u8> {
+    fn string(out: &mut Vec<u8>, value: &str) {
+        out.extend_from_slice(&(value.len() as u64).to_le_bytes());
+        out.extend_from_slice(value.as_bytes());
+    }
+    fn value(out: &mut Vec<u8>, entry: &GGUFValue) {
+        match entry {
+            GGUFValue::UInt8(v) => out.extend_from_slice(&v.to_le_bytes()),
+            GGUFValue::Int8(v) => out.extend_from_slice(&v.to_le_bytes()),
+            GGUFValue::UInt16(v) => out.extend_from_slice(&v.to_le_bytes()),
+            GGUFValue::Int16(v) => out.extend_from_slice(&v.to_le_bytes()),
+            GGUFValue::UInt32(v) => out.extend_from_slice(&v.to_le_bytes()),
+            GGUFValue::Int32(v) => out.extend_from_slice(&v.to_le_bytes()),
+            GGUFValue::Float32(v) => out.extend_from_slice(&v.to_le_bytes()),
+            GGUFValue::Bool(v) => out.push(*v as u8),
+            GGUFValue::String(v) => string(out, v),
+            GGUFValue::Array(values) => {
+                // Mixed arrays cannot be written; an empty one is typed as u8
+                let element_type = values.first().map_or(0, GGUFValue::type_id);
+                out.extend_from_slice(&element_type.to_le_bytes());
+                out.extend_from_slice(&(values.len() as u64).to_le_bytes());
+                for element in values {
+                    value(out, element);
+                }
+            }
+            GGUFValue::UInt64(v) => out.extend_from_slice(&v.to_le_bytes()),
+            GGUFValue::Int64(v) => out.extend_from_slice(&v.to_le_bytes()),
+            GGUFValue::Float64(v) => out.extend_from_slice(&v.to_le_bytes()),
+        }
+    }
+
+    let mut out = Vec::new();
+    out.extend_from_slice(&GGUF_MAGIC.to_le_bytes());
+    out.extend_from_slice(&metadata.version.to_le_bytes());
+    // Counts follow the entries present, not the header's stored counts
+    out.extend_from_slice(&(metadata.tensors.len() as u64).to_le_bytes());
+    out.extend_from_slice(&(metadata.metadata.len() as u64).to_le_bytes());
+    for (key, entry) in &metadata.metadata {
+        string(&mut out, key);
+        out.extend_from_slice(&entry.type_id().to_le_bytes());
+        value(&mut out, entry);
+    }
+    for tensor in &metadata.tensors {
+        string(&mut out, &tensor.name);
+        out.extend_from_slice(&(tensor.dimensions.len() as u32).to_le_bytes());
+        for dimension in &tensor.dimensions {
+            out.extend_from_slice(&dimension.to_le_bytes());
+        }
+        out.extend_from_slice(&tensor.tensor_type.to_le_bytes());
+        out.extend_from_slice(&tensor.offset.to_le_bytes());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn scalar() -> impl Strategy<Value = GGUFValue> {
+        prop_oneof![
+            any::<u8>().prop_map(GGUFValue::UInt8),
+            any::<i8>().prop_map(GGUFValue::Int8),
+            any::<u16>().prop_map(GGUFValue::UInt16),
+            any::<i16>().prop_map(GGUFValue::Int16),
+            any::<u32>().prop_map(GGUFValue::UInt32),
+            any::<i32>().prop_map(GGUFValue::Int32),
+            // Whole numbers keep float equality meaningful
+            any::<i16>().prop_map(|v| GGUFValue::Float32(v as f32 / 4.0)),
+            any::<bool>().prop_map(GGUFValue::Bool),
+            "\\PC{0,24}".prop_map(GGUFValue::String),
+            any::<u64>().prop_map(GGUFValue::UInt64),
+            any::<i64>().prop_map(GGUFValue::Int64),
+            any::<i32>().prop_map(|v| GGUFValue::Float64(v as f64 / 8.0)),
+        ]
+    }
+
+    /// Scalars plus arrays of one element type, nested up to three deep
+    fn value() -> impl Strategy<Value = GGUFValue> {
+        scalar().prop_recursive(3, 64, 8, |inner| {
+            (inner, 0..8usize).prop_map(|(element, len)| GGUFValue::Array(vec![element; len]))
+        })
+    }
+
+    fn tensor() -> impl Strategy<Value = GGUFTensorInfo> {
+        ("[a-z_.0-9]{1,24}", prop::collection::vec(any::<u64>(), 0..=4), any::<u32>(), any::<u64>())
+            .prop_map(|(name, dimensions, tensor_type, offset)| GGUFTensorInfo { name, dimensions, tensor_type, offset })
+    }
+
+    fn header() -> impl Strategy<Value = GGUFMetadata> {
+        (
+            MIN_GGUF_VERSION..=MAX_GGUF_VERSION,
+            prop::collection::hash_map("[a-z_.]{1,24}", value(), 0..8),
+            prop::collection::vec(tensor(), 0..6),
+        )
+            .prop_map(|(version, metadata, tensors)| GGUFMetadata {
+                version,
+                tensor_count: tensors.len() as u64,
+                metadata_kv_count: metadata.len() as u64,
+                metadata,
+                tensors,
+            })
+    }
+
+    fn expect_validation(bytes: &[u8]) {
+        match parse_metadata_bytes(bytes) {
+            Ok(_) => {}
+            Err(error) => assert!(matches!(CodexError::from(error), CodexError::Validation(_))),
+        }
+    }
+
+    #[test]
+    fn test_tensor_sizes_follow_the_quantization_blocks() {
+        let tensor = |dimensions: Vec<u64>, tensor_type| GGUFTensorInfo {
+            name: "blk.0.attn_q.weight".to_string(),
+            dimensions,
+            tensor_type,
+            offset: 0,
+        };
+        assert_eq!(tensor(vec![4096, 4096], 0).size_bytes(), Some(4096 * 4096 * 4));
+        assert_eq!(tensor(vec![4096, 4096], 1).size_bytes(), Some(4096 * 4096 * 2));
+        // Q4_K: 144 bytes per 256 weights, 4.5 bits each
+        assert_eq!(tensor(vec![4096, 4096], 12).size_bytes(), Some(4096 * 4096 / 256 * 144));
+        // A partial block still takes a whole one
+        assert_eq!(tensor(vec![33], 8).size_bytes(), Some(2 * 34));
+        assert_eq!(tensor(vec![4096], 99).size_bytes(), None);
+        assert_eq!(tensor(vec![u64::MAX, 2], 0).size_bytes(), None);
+        assert_eq!(ggml_type_name(12), Some("Q4_K"));
+    }
+
+    proptest! {
+        #[test]
+        fn prop_headers_round_trip(metadata in header()) {
+            let bytes = encode_metadata(&metadata);
+            prop_assert_eq!(parse_metadata_bytes(&bytes).unwrap(), metadata);
+        }
+
+        #[test]
+        fn prop_truncated_headers_fail_cleanly(metadata in header(), cut in any::<prop::sample::Index>()) {
+            let bytes = encode_metadata(&metadata);
+            let cut = cut.index(bytes.len());
+            prop_assert!(parse_metadata_bytes(&bytes[..cut]).is_err());
+        }
+
+        #[test]
+        fn prop_corrupted_headers_never_panic(
+            metadata in header(),
+            flips in prop::collection::vec((any::<prop::sample::Index>(), any::<u8>()), 1..8),
+        ) {
+            let mut bytes = encode_metadata(&metadata);
+            for (index, byte) in flips {
+                let at = index.index(bytes.len());
+                bytes[at] = byte;
+            }
+            expect_validation(&bytes);
+        }
+
+        #[test]
+        fn prop_arbitrary_bytes_never_panic(tail in prop::collection::vec(any::<u8>(), 0..256)) {
+            let mut bytes = GGUF_MAGIC.to_le_bytes().to_vec();
+            bytes.extend_from_slice(&3u32.to_le_bytes());
+            bytes.extend_from_slice(&tail);
+            expect_validation(&bytes);
+        }
+    }
+
+    /// Header with one metadata entry whose encoded value is `value_bytes`
+    fn single_entry(value_type: u32, value_bytes: &[u8]) -> Vec<u8> {
+        let mut bytes = GGUF_MAGIC.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&3u32.to_le_bytes());
+        bytes.extend_from_slice(&0u64.to_le_bytes());
+        bytes.extend_from_slice(&1u64.to_le_bytes());
+        bytes.extend_from_slice(&3u64.to_le_bytes());
+        bytes.extend_from_slice(b"key");
+        bytes.extend_from_slice(&value_type.to_le_bytes());
+        bytes.extend_from_slice(value_bytes);
+        bytes
+    }
+
+    #[test]
+    fn test_hostile_lengths_are_rejected_before_allocating() {
+        // A 16 EiB string
+        let bytes = single_entry(8, &u64::MAX.to_le_bytes());
+        assert!(matches!(
+            parse_metadata_bytes(&bytes),
+            Err(GGUFError::LengthOutOfBounds { what: "string value", len: u64::MAX, .. })
+        ));
+
+        // An array claiming more u64 elements than the file could hold
+        let mut array = 10u32.to_le_bytes().to_vec();
+        array.extend_from_slice(&1000u64.to_le_bytes());
+        array.extend_from_slice(&[0u8; 64]);
+        assert!(matches!(
+            parse_metadata_bytes(&single_entry(9, &array)),
+            Err(GGUFError::LengthOutOfBounds { what: "array", len: 1000, .. })
+        ));
+
+        let mut huge = 0u32.to_le_bytes().to_vec();
+        huge.extend_from_slice(&(MAX_METADATA_VALUES + 1).to_le_bytes());
+        assert!(matches!(parse_metadata_bytes(&single_entry(9, &huge)), Err(GGUFError::TooManyValues { .. })));
+
+        // Arrays of arrays nested past the limit
+        let mut nested = Vec::new();
+        for _ in 0..=MAX_ARRAY_DEPTH {
+            nested.extend_from_slice(&9u32.to_le_bytes());
+            nested.extend_from_slice(&1u64.to_le_bytes());
+        }
+        nested.extend_from_slice(&[0u8; 16]);
+        assert!(matches!(
+            parse_metadata_bytes(&single_entry(9, &nested)),
+            Err(GGUFError::NestingTooDeep { .. })
+        ));
+
+        assert!(matches!(
+            parse_metadata_bytes(&single_entry(13, &[0u8; 8])),
+            Err(GGUFError::UnknownValueType { value_type: 13, .. })
+        ));
+        assert!(matches!(
+            parse_metadata_bytes(&single_entry(4, &[1, 2])),
+            Err(GGUFError::Truncated { what: "u32 value", .. })
+        ));
+        assert!(matches!(parse_metadata_bytes(b"GGML\x03\0\0\0"), Err(GGUFError::InvalidMagic(_))));
+        let mut v1 = GGUF_MAGIC.to_le_bytes().to_vec();
+        v1.extend_from_slice(&1u32.to_le_bytes());
+        assert_eq!(parse_metadata_bytes(&v1), Err(GGUFError::UnsupportedVersion(1)));
+    }
+
+    #[test]
+    fn test_string_arrays_parse() {
+        let mut tokens = 8u32.to_le_bytes().to_vec();
+        tokens.extend_from_slice(&2u64.to_le_bytes());
+        for token in ["<s>", "hello"] {
+            tokens.extend_from_slice(&(token.len() as u64).to_le_bytes());
+            tokens.extend_from_slice(token.as_bytes());
+        }
+        let metadata = parse_metadata_bytes(&single_entry(9, &tokens)).unwrap();
+        assert_eq!(
+            metadata.metadata["key"],
+            GGUFValue::Array(vec![GGUFValue::String("<s>".into()), GGUFValue::String("hello".into())])
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("model.gguf");
+        std::fs::write(&path, single_entry(9, &tokens[..tokens.len() - 1])).unwrap();
+        assert!(matches!(parse_metadata_file(&path), Err(CodexError::Validation(_))));
+    }
+}
@@ -0,0 +1,163 @@
+//! GPU memory of the device models run on
+//!
+//! CUDA devices are queried through the driver API (`cuMemGetInfo`) when
+//! the `ai-gpu` feature is on, Metal devices through
+//! `MTLDevice::currentAllocatedSize` when `ai-metal` is on. Without the
+//! feature, or when the query fails, no GPU metrics are available and
+//! callers report zero.
+
+use std::time::{Duration, Instant};
+
+use candle_core::Device;
+
+/// How long a GPU memory reading is reused before the device is queried again
+pub const GPU_METRICS_REFRESH: Duration = Duration::from_secs(2);
+
+/// Memory of one GPU
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GpuMemory {
+    /// Used on the whole device
+    pub used_bytes: u64,
+    pub total_bytes: u64,
+    /// Estimate of the share used by this process
+    pub process_bytes: u64,
+}
+
+impl GpuMemory {
+    pub fn used_mb(&self) -> f64 {
+        self.used_bytes as f64 / (1024.0 * 1024.0)
+    }
+
+    pub fn total_mb(&self) -> f64 {
+        self.total_bytes as f64 / (1024.0 * 1024.0)
+    }
+
+    pub fn process_mb(&self) -> f64 {
+        self.process_bytes as f64 / (1024.0 * 1024.0)
+    }
+
+    /// Share of the device's memory in use
+    pub fn used_ratio(&self) -> f64 {
+        if self.total_bytes == 0 {
+            return 0.0;
+        }
+        self.used_bytes as f64 / self.total_bytes as f64
+    }
+}
+
+/// GPU a device runs on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GpuDevice {
+    Cuda(usize),
+    Metal,
+}
+
+impl GpuDevice {
+    /// GPU of a candle device, `None` on the CPU
+    pub fn of(device: &Device) -> Option<Self> {
+        match device.location() {
+            candle_core::DeviceLocation::Cpu => None,
+            candle_core::DeviceLocation::Cuda { gpu_id } => Some(Self::Cuda(gpu_id)),
+            candle_core::DeviceLocation::Metal { .. } => Some(Self::Metal),
+        }
+    }
+
+    /// Used and total bytes, when the device can be queried
+    pub fn query(self) -> Option<(u64, u64)> {
+        match self {
+            Self::Cuda(ordinal) => cuda_memory_info(ordinal),
+            Self::Metal => metal_memory_info(),
+        }
+    }
+}
+
+/// Used and total bytes of a CUDA device
+#[cfg(feature = "ai-gpu")]
+pub fn cuda_memory_info(ordinal: usize) -> Option<(u64, u64)> {
+    use candle_core::cuda_backend::cudarc::driver::{result, CudaContext};
+
+    let context = CudaContext::new(ordinal).ok()?;
+    context.bind_to_thread().ok()?;
+    let (free, total) = result::mem_get_info().ok()?;
+    Some(((total - free) as u64, total as u64))
+}
+
+#[cfg(not(feature = "ai-gpu"))]
+pub fn cuda_memory_info(_ordinal: usize) -> Option<(u64, u64)> {
+    None
+}
+
+/// Bytes allocated by this process and the recommended working set of the Metal device
+#[cfg(feature = "ai-metal")]
+pub fn metal_memory_info() -> Option<(u64, u64)> {
+    let device = metal::Device::system_default()?;
+    Some((device.current_allocated_size(), device.recommended_max_working_set_size()))
+}
+
+#[cfg(not(feature = "ai-metal"))]
+pub fn metal_memory_info() -> Option<(u64, u64)> {
+    None
+}
+
+/// Periodically refreshed GPU memory reading
+///
+/// CUDA reports memory used on the whole device, so this process's share is
+/// estimated as the growth since the first reading. Metal reports this
+/// process's allocations directly.
+#[derive(Debug)]
+pub struct GpuMemoryTracker {
+    device: Option<GpuDevice>,
+    baseline_bytes: Option<u64>,
+    last: Option<(Instant, GpuMemory)>,
+}
+
+impl GpuMemoryTracker {
+    pub fn new(device: Option<GpuDevice>) -> Self {
+        let mut tracker = Self { device, baseline_bytes: None, last: None };
+        tracker.baseline_bytes = tracker.query().map(|memory| memory.used_bytes);
+        tracker
+    }
+
+    /// Latest reading, at most [`GPU_METRICS_REFRESH`] old; `None` without GPU metrics
+    pub fn current(&mut self) -> Option<GpuMemory> {
+        if let Some((at, memory)) = self.last {
+            if at.elapsed() < GPU_METRICS_REFRESH {
+                return Some(memory);
+            }
+        }
+        let memory = self.query()?;
+        self.last = Some((Instant::now(), memory));
+        Some(memory)
+    }
+
+    fn query(&self) -> Option<GpuMemory> {
+        let device = self.device?;
+        let (used_bytes, total_bytes) = device.query()?;
+        let process_bytes = match device {
+            GpuDevice::Cuda(_) => used_bytes.saturating_sub(self.baseline_bytes.unwrap_or(used_bytes)),
+            GpuDevice::Metal => used_bytes,
+        };
+        Some(GpuMemory { used_bytes, total_bytes, process_bytes })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cpu_has_no_gpu_metrics() {
+        assert_eq!(GpuDevice::of(&Device::Cpu), None);
+        let mut tracker = GpuMemoryTracker::new(None);
+        assert_eq!(tracker.current(), None);
+    }
+
+    #[test]
+    fn test_gpu_memory_ratio() {
+        let memory = GpuMemory { used_bytes: 3 << 30, total_bytes: 4 << 30, process_bytes: 1 << 30 };
+        assert_eq!(memory.used_ratio(), 0.75);
+        assert_eq!(memory.total_mb(), 4096.0);
+        assert_eq!(memory.process_mb(), 1024.0);
+        assert_eq!(GpuMemory { used_bytes: 0, total_bytes: 0, process_bytes: 0 }.used_ratio(), 0.0);
+    }
+}
@@ -9,14 +9,170 @@ use lru::LruCache;
 use std::num::NonZeroUsize;
 use sysinfo::{System, Pid};
 
-use candle_core::Device;
-use candle_transformers::models::llama::{Llama, LlamaConfig};
+use candle_core::{DType, Device, Tensor};
+use candle_transformers::generation::LogitsProcessor;
+use candle_transformers::models::llama::{self, Cache, Llama, LlamaConfig, LlamaEosToks};
+use serde::{Deserialize, Serialize};
 use tokenizers::Tokenizer;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use crate::CodexResult;
-use crate::config::AiConfig;
-use super::AiStats;
+use crate::clock::Clock;
+use crate::config::{AiConfig, GpuLayers};
+use crate::metrics::Histogram;
+use crate::power::{GenerationPermit, GenerationThrottle};
+use super::{AiStats, LatencyPercentiles};
+use super::prefix_cache::{self, PrefixState, PromptPrefixCache, TokenModel};
+use super::speculative::{self, SpeculativeStats};
+use super::engine::{GenerationSettings, LLMEngine};
+use super::truncation::{self, PromptTruncation};
+use super::gpu::{GpuDevice, GpuMemoryTracker};
+use super::gguf::GGUFMetadata;
+use super::load_plan::{self, ModelLoadPlan};
+
+/// Prompt openings shared by many requests, tokenized during warm-up
+pub const WARM_UP_PROMPT_PREFIXES: [&str; 3] = [
+    super::rag::RAG_ANSWER_PREFIX,
+    super::rag::DOCUMENT_CHAT_PREFIX,
+    super::QUESTION_ANSWER_PREFIX,
+];
+
+/// Generated text, with how the prompt was truncated and what the generation took
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GenerationOutput {
+    pub text: String,
+    pub truncation: Option<PromptTruncation>,
+    pub usage: GenerationUsage,
+}
+
+impl GenerationOutput {
+    /// Prompt tokens dropped to fit the context
+    pub fn dropped_tokens(&self) -> usize {
+        self.truncation.map_or(0, |truncation| truncation.dropped_tokens())
+    }
+}
+
+/// Token counts and timing of one generation, measured by the engine
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct GenerationUsage {
+    /// Prompt tokens the model saw, after truncation
+    pub prompt_tokens: usize,
+    pub completion_tokens: usize,
+    /// Until the first streamed token; the whole generation when not streamed
+    pub time_to_first_token_ms: u64,
+    pub elapsed_ms: u64,
+}
+
+impl GenerationUsage {
+    /// Completion tokens per second over the whole generation
+    pub fn tokens_per_second(&self) -> f64 {
+        if self.elapsed_ms == 0 {
+            return 0.0;
+        }
+        self.completion_tokens as f64 * 1000.0 / self.elapsed_ms as f64
+    }
+
+    fn timed(self, elapsed: Duration, first_token: Duration) -> Self {
+        Self {
+            time_to_first_token_ms: first_token.as_millis() as u64,
+            elapsed_ms: elapsed.as_millis() as u64,
+            ..self
+        }
+    }
+}
+
+/// Streamed response so far
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StreamChunk {
+    pub text: String,
+    /// Completion tokens generated so far
+    pub completion_tokens: usize,
+    /// Time since generation started
+    pub elapsed_ms: u64,
+}
+
+/// Whether the model has been prepared for its first generation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum ModelWarmUpState {
+    /// Not warmed up; the first generation also pays for allocation
+    Cold,
+    /// Warm-up is running
+    WarmingUp,
+    /// Warmed up in `elapsed_ms`
+    Ready { elapsed_ms: u64 },
+    /// Warm-up failed; generation still works, the first one is slower
+    Failed { error: String },
+}
+
+/// Responses kept in the inference cache
+const RESPONSE_CACHE_CAPACITY: usize = 100;
+/// Time a cached response is served for
+const RESPONSE_CACHE_TTL: Duration = Duration::from_secs(3600);
+/// Memory cleanup drops cached responses not read for this long
+const RESPONSE_CACHE_IDLE_CUTOFF: Duration = Duration::from_secs(300);
+
+/// Fraction of the memory limit (1/n) conversation KV caches may hold
+const PREFIX_CACHE_SHARE: usize = 4;
+/// Share of the GPU's memory in use beyond which memory limits are exceeded
+const GPU_MEMORY_LIMIT_RATIO: f64 = 0.95;
+
+/// Llama weights run token by token over a [`LlamaState`]
+///
+/// candle's Llama only returns the logits of the last position, so drafted
+/// tokens are verified with the default, one token at a time `forward_all`.
+struct LlamaTokenModel {
+    model: Arc<Llama>,
+    config: llama::Config,
+    device: Device,
+    /// Empty cache allocated during warm-up, copied for new conversations
+    template: Option<Cache>,
+}
+
+/// KV cache of a conversation and the number of tokens it covers
+#[derive(Clone)]
+struct LlamaState {
+    cache: Cache,
+    tokens: usize,
+    bytes_per_token: usize,
+}
+
+impl PrefixState for LlamaState {
+    fn size_bytes(&self) -> usize {
+        self.tokens * self.bytes_per_token
+    }
+
+    fn truncate(&mut self, len: usize) -> bool {
+        // candle keeps the cached keys and values private, so they cannot be cut back
+        len == self.tokens
+    }
+}
+
+impl TokenModel for LlamaTokenModel {
+    type State = LlamaState;
+
+    fn new_state(&self) -> CodexResult<LlamaState> {
+        let cache = match &self.template {
+            Some(cache) => cache.clone(),
+            None => Cache::new(true, DType::F32, &self.config, &self.device)
+                .map_err(|e| crate::CodexError::ai_inference(format!("Failed to allocate KV cache: {}", e)))?,
+        };
+        // Keys and values of every layer, in f32
+        let head_dim = self.config.hidden_size / self.config.num_attention_heads;
+        let bytes_per_token = 2 * self.config.num_hidden_layers * self.config.num_key_value_heads * head_dim * 4;
+        Ok(LlamaState { cache, tokens: 0, bytes_per_token })
+    }
+
+    fn forward(&self, tokens: &[u32], position: usize, state: &mut LlamaState) -> CodexResult<Tensor> {
+        let logits = Tensor::new(tokens, &self.device)
+            .and_then(|input| input.unsqueeze(0))
+            .and_then(|input| self.model.forward(&input, position, &mut state.cache))
+            .and_then(|logits| logits.squeeze(0))
+            .map_err(|e| crate::CodexError::ai_inference(format!("Forward pass failed: {}", e)))?;
+        state.tokens = position + tokens.len();
+        Ok(logits)
+    }
+}
 
 /// AI model inference engine
 pub struct InferenceEngine {
@@ -29,8 +185,33 @@ pub struct InferenceEngine {
     token_cache: Arc<Mutex<TokenCache>>,
     system_metrics: Arc<Mutex<SystemMetrics>>,
     model_path: String,
+    models_dir: PathBuf,
     start_time: Instant,
     memory_limit_mb: usize,
+    /// KV cache allocated for the context length during warm-up
+    kv_cache: Arc<Mutex<Option<Cache>>>,
+    warm_up_state: Arc<std::sync::RwLock<ModelWarmUpState>>,
+    /// KV caches of recent chat turns by conversation id
+    prefix_cache: Arc<std::sync::Mutex<PromptPrefixCache<LlamaState>>>,
+    /// Draft model for speculative decoding, when configured and found
+    draft: Option<DraftModel>,
+    /// Engine generating in place of the loaded weights, e.g. a mock in tests
+    engine: Option<Arc<dyn LLMEngine>>,
+    /// Layers to offload, from [`AiConfig::gpu_layers`]
+    gpu_layers: GpuLayers,
+    context_length: usize,
+    /// GPU/CPU split of the loaded model
+    load_plan: Option<ModelLoadPlan>,
+    /// Power limits on concurrent generations and their length
+    throttle: Arc<GenerationThrottle>,
+}
+
+/// Small model proposing tokens for the primary model to verify
+struct DraftModel {
+    model: Option<Arc<Llama>>,
+    config: LlamaConfig,
+    path: String,
+    size_mb: f64,
 }
 
 impl std::fmt::Debug for InferenceEngine {
@@ -40,6 +221,8 @@ impl std::fmt::Debug for InferenceEngine {
             .field("model_loaded", &self.model.is_some())
             .field("tokenizer_loaded", &self.tokenizer.is_some())
             .field("model_path", &self.model_path)
+            .field("draft_model", &self.draft.as_ref().map(|draft| &draft.path))
+            .field("engine", &self.engine.as_ref().map(|engine| engine.engine_type()))
             .finish()
     }
 }
@@ -53,6 +236,13 @@ struct InferenceStats {
     cache_misses: u64,
     peak_memory_usage_mb: f64,
     current_memory_usage_mb: f64,
+    /// Time until the first token of uncached generations
+    first_token: Histogram,
+    /// Tokens produced by model generations, and the time they took
+    generated_tokens: u64,
+    generation_time: Duration,
+    drafted_tokens: u64,
+    accepted_tokens: u64,
 }
 
 /// Detailed system metrics tracking for performance monitoring
@@ -69,6 +259,8 @@ struct SystemMetrics {
     total_cpu_time: Duration,
     inference_memory_snapshots: Vec<MemorySnapshot>,
     cpu_usage_history: Vec<CpuSnapshot>,
+    /// Memory of the GPU models run on, if any
+    gpu: GpuMemoryTracker,
 }
 
 #[derive(Debug, Clone)]
@@ -94,6 +286,7 @@ struct CpuSnapshot {
 #[derive(Debug)]
 struct InferenceCache {
     entries: LruCache<String, CacheEntry>,
+    clock: Arc<dyn Clock>,
 }
 
 /// Token-level cache for storing up to 1M tokens in RAM
@@ -113,12 +306,60 @@ struct TokenCache {
 
 #[derive(Debug, Clone)]
 struct CacheEntry {
-    response: String,
+    response: GenerationOutput,
     created_at: Instant,
     last_accessed: Instant,
     access_count: u64,
 }
 
+impl InferenceCache {
+    fn new(capacity: usize, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            entries: LruCache::new(NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN)),
+            clock,
+        }
+    }
+
+    /// Response cached under `key`; one older than [`RESPONSE_CACHE_TTL`] is removed instead
+    fn get(&mut self, key: &str) -> Option<GenerationOutput> {
+        let now = self.clock.now();
+        if let Some(entry) = self.entries.get_mut(key) {
+            if now.saturating_duration_since(entry.created_at) < RESPONSE_CACHE_TTL {
+                entry.access_count += 1;
+                entry.last_accessed = now;
+                return Some(entry.response.clone());
+            }
+            self.entries.pop(key);
+        }
+        None
+    }
+
+    fn put(&mut self, key: String, response: GenerationOutput) {
+        let now = self.clock.now();
+        self.entries.put(key, CacheEntry {
+            response,
+            created_at: now,
+            last_accessed: now,
+            access_count: 1,
+        });
+    }
+
+    /// Remove entries not read for `idle` or longer, returning how many were removed
+    fn remove_idle(&mut self, idle: Duration) -> usize {
+        let now = self.clock.now();
+        // Collect keys to remove (LRU cache doesn't support retain)
+        let stale: Vec<String> = self.entries
+            .iter()
+            .filter(|(_, entry)| now.saturating_duration_since(entry.last_accessed) >= idle)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in &stale {
+            self.entries.pop(key);
+        }
+        stale.len()
+    }
+}
+
 impl TokenCache {
     fn new(max_token_count: usize) -> Self {
         Self {
@@ -239,13 +480,13 @@ pub struct ComprehensiveStats {
 }
 
 impl SystemMetrics {
-    fn new() -> Self {
+    fn new(device: &Device) -> Self {
         let mut system = System::new_all();
         system.refresh_all();
         
         let process_id = std::process::id();
         let initial_memory_kb = if let Some(process) = system.process(Pid::from_u32(process_id)) {
-            process.memory()
+            process.memory() / 1024
         } else {
             0
         };
@@ -263,6 +504,7 @@ impl SystemMetrics {
             total_cpu_time: Duration::ZERO,
             inference_memory_snapshots: Vec::with_capacity(1000),
             cpu_usage_history: Vec::with_capacity(1000),
+            gpu: GpuMemoryTracker::new(GpuDevice::of(device)),
         }
     }
     
@@ -271,7 +513,7 @@ impl SystemMetrics {
         let now = Instant::now();
         
         if let Some(process) = self.system.process(Pid::from_u32(self.process_id)) {
-            let memory_kb = process.memory();
+            let memory_kb = process.memory() / 1024;
             let cpu_percent = process.cpu_usage();
             
             self.add_memory_snapshot(now, memory_kb, context);
@@ -334,7 +576,7 @@ impl SystemMetrics {
         self.system.refresh_all();
         
         let process_memory_kb = if let Some(process) = self.system.process(Pid::from_u32(self.process_id)) {
-            process.memory()
+            process.memory() / 1024
         } else {
             0
         };
@@ -343,6 +585,7 @@ impl SystemMetrics {
         let system_memory_total_kb = self.system.total_memory() / 1024;
         let system_memory_used_kb = self.system.used_memory() / 1024;
         let system_memory_available_kb = system_memory_total_kb - system_memory_used_kb;
+        let gpu = self.gpu.current();
         
         SystemMetricsSnapshot {
             uptime: self.start_time.elapsed(),
@@ -356,6 +599,10 @@ impl SystemMetrics {
             cpu_count,
             memory_snapshots_count: self.inference_memory_snapshots.len(),
             cpu_snapshots_count: self.cpu_usage_history.len(),
+            gpu_metrics_available: gpu.is_some(),
+            gpu_memory_used_mb: gpu.map_or(0.0, |gpu| gpu.used_mb()),
+            gpu_memory_total_mb: gpu.map_or(0.0, |gpu| gpu.total_mb()),
+            gpu_memory_process_mb: gpu.map_or(0.0, |gpu| gpu.process_mb()),
         }
     }
     
@@ -392,6 +639,11 @@ impl SystemMetrics {
                                 metrics.system_memory_used_mb, metrics.system_memory_total_mb));
         report.push_str(&format!("Peak CPU Usage: {:.1}%\n", metrics.peak_cpu_percent));
         report.push_str(&format!("CPU Cores: {}\n", metrics.cpu_count));
+        if metrics.gpu_metrics_available {
+            report.push_str(&format!("GPU Memory: {:.1}MB used / {:.1}MB total (this process: ~{:.1}MB)\n",
+                                    metrics.gpu_memory_used_mb, metrics.gpu_memory_total_mb,
+                                    metrics.gpu_memory_process_mb));
+        }
         
         // Add recent memory snapshots
         if !self.inference_memory_snapshots.is_empty() {
@@ -428,6 +680,13 @@ pub struct SystemMetricsSnapshot {
     pub cpu_count: usize,
     pub memory_snapshots_count: usize,
     pub cpu_snapshots_count: usize,
+    /// Whether the GPU could be queried; the GPU fields are zero otherwise
+    pub gpu_metrics_available: bool,
+    /// Used on the whole GPU
+    pub gpu_memory_used_mb: f64,
+    pub gpu_memory_total_mb: f64,
+    /// Estimate of the GPU memory used by this process
+    pub gpu_memory_process_mb: f64,
 }
 
 impl InferenceEngine {
@@ -444,7 +703,49 @@ impl InferenceEngine {
 
         info!("Using device: {:?}", device);
 
-        let mut engine = Self {
+        let mut engine = Self::with_device(config, device);
+
+        // Load the model
+        engine.load_model(&config.primary_model).await?;
+
+        // Without the draft model, generation carries on without speculation
+        if let Some(draft_model) = config.draft_model.as_ref().filter(|_| config.speculative.enabled) {
+            if let Err(e) = engine.load_draft_model(draft_model).await {
+                warn!("Draft model {} not loaded, decoding without speculation: {}", draft_model, e);
+            }
+        }
+
+        info!("Inference engine initialized successfully");
+        Ok(engine)
+    }
+
+    /// Engine without a model, on the CPU; every generation fails until one is loaded
+    ///
+    /// Used in safe mode, where a broken model or GPU driver must not be touched.
+    pub fn unloaded(config: &AiConfig) -> Self {
+        Self::with_device(config, Device::Cpu)
+    }
+
+    /// Engine handing every generation to `engine` instead of loading weights
+    pub fn with_engine(config: &AiConfig, engine: Arc<dyn LLMEngine>) -> Self {
+        Self {
+            model_path: engine.get_model_info().name,
+            engine: Some(engine),
+            ..Self::with_device(config, Device::Cpu)
+        }
+    }
+
+    /// Read the time for response cache expiry from `clock`
+    ///
+    /// Responses cached so far are dropped.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.cache = Arc::new(Mutex::new(InferenceCache::new(RESPONSE_CACHE_CAPACITY, clock)));
+        self
+    }
+
+    fn with_device(config: &AiConfig, device: Device) -> Self {
+        let system_metrics = SystemMetrics::new(&device);
+        Self {
             model: None,
             tokenizer: None,
             device,
@@ -464,21 +765,66 @@ impl InferenceEngine {
                 rope_scaling: None,
             },
             stats: Arc::new(Mutex::new(InferenceStats::default())),
-            cache: Arc::new(Mutex::new(InferenceCache {
-                entries: LruCache::new(NonZeroUsize::new(100).unwrap()),
-            })),
+            cache: Arc::new(Mutex::new(InferenceCache::new(RESPONSE_CACHE_CAPACITY, crate::clock::system()))),
             token_cache: Arc::new(Mutex::new(TokenCache::new(1_000_000))), // 1M tokens
-            system_metrics: Arc::new(Mutex::new(SystemMetrics::new())),
+            system_metrics: Arc::new(Mutex::new(system_metrics)),
             model_path: config.primary_model.clone(),
+            models_dir: config.models_dir.clone(),
             start_time: Instant::now(),
             memory_limit_mb: 2048, // 2GB default limit
-        };
+            kv_cache: Arc::new(Mutex::new(None)),
+            warm_up_state: Arc::new(std::sync::RwLock::new(ModelWarmUpState::Cold)),
+            prefix_cache: Arc::new(std::sync::Mutex::new(PromptPrefixCache::new(
+                2048 / PREFIX_CACHE_SHARE * 1024 * 1024,
+            ))),
+            draft: None,
+            engine: None,
+            gpu_layers: config.gpu_layers,
+            context_length: config.max_context_length,
+            load_plan: None,
+            throttle: Arc::new(GenerationThrottle::default()),
+        }
+    }
 
-        // Load the model
-        engine.load_model(&config.primary_model).await?;
+    /// Load the draft model for speculative decoding
+    ///
+    /// The draft shares the primary model's tokenizer, so its vocabulary must
+    /// match the primary model's.
+    pub async fn load_draft_model(&mut self, model_path: &str) -> CodexResult<()> {
+        use crate::ai::engine::GGUFEngine;
 
-        info!("Inference engine initialized successfully");
-        Ok(engine)
+        let resolved_path = crate::paths::resolve_model_path(&self.models_dir, model_path);
+        if !resolved_path.exists() {
+            return Err(crate::CodexError::not_found(
+                format!("Draft model file not found: {}", resolved_path.display())
+            ));
+        }
+
+        let metadata = GGUFEngine::parse_gguf_metadata(&resolved_path)?;
+        let config = GGUFEngine::metadata_to_config(&metadata)?;
+        if config.vocab_size != self.config.vocab_size {
+            return Err(crate::CodexError::validation(format!(
+                "Draft model vocabulary ({} tokens) does not match the primary model ({} tokens)",
+                config.vocab_size, self.config.vocab_size
+            )));
+        }
+
+        let file_size = std::fs::metadata(&resolved_path)
+            .map_err(crate::CodexError::io)?
+            .len();
+        self.draft = Some(DraftModel {
+            model: None,
+            config,
+            path: resolved_path.to_string_lossy().into_owned(),
+            size_mb: file_size as f64 / (1024.0 * 1024.0),
+        });
+        info!("Draft model loaded from: {} ({} bytes)", resolved_path.display(), file_size);
+        Ok(())
+    }
+
+    /// Whether a draft model is loaded for speculative decoding
+    pub fn has_draft_model(&self) -> bool {
+        self.draft.is_some()
     }
 
     /// Load a model from file with checksum verification
@@ -489,11 +835,12 @@ impl InferenceEngine {
         
         info!("Loading model: {}", model_path);
 
-        // Verify model file exists
-        let model_path_obj = Path::new(model_path);
+        // Bare file names are looked up in the models directory
+        let resolved_path = crate::paths::resolve_model_path(&self.models_dir, model_path);
+        let model_path_obj = resolved_path.as_path();
         if !model_path_obj.exists() {
             return Err(crate::CodexError::not_found(
-                format!("Model file not found: {}", model_path)
+                format!("Model file not found: {}", model_path_obj.display())
             ));
         }
 
@@ -526,6 +873,9 @@ impl InferenceEngine {
         info!("Model config extracted: vocab_size={}, hidden_size={}, layers={}", 
               config.vocab_size, config.hidden_size, config.num_hidden_layers);
 
+        let load_plan = self.plan_load(&metadata, &config);
+        info!("GPU offload plan: {}", load_plan);
+
         // Load tokenizer - look for tokenizer in same directory as model
         let model_dir = model_path_obj.parent()
             .ok_or_else(|| crate::CodexError::config("Invalid model path"))?;
@@ -542,7 +892,7 @@ impl InferenceEngine {
 
         // Update memory tracking
         let file_size = std::fs::metadata(model_path_obj)
-            .map_err(|e| crate::CodexError::io(e))?
+            .map_err(crate::CodexError::io)?
             .len();
         
         self.update_memory_usage(file_size).await;
@@ -550,12 +900,37 @@ impl InferenceEngine {
         // Store the loaded components
         self.tokenizer = Some(Arc::new(tokenizer));
         self.config = config;
-        self.model_path = model_path.to_string();
-        
-        info!("Model loaded successfully from: {} ({} bytes)", model_path, file_size);
+        self.model_path = model_path_obj.to_string_lossy().into_owned();
+        self.load_plan = Some(load_plan);
+        self.engine = None;
+        *self.kv_cache.lock().await = None;
+        self.prefix_cache().clear();
+        self.set_warm_up_state(ModelWarmUpState::Cold);
+        
+        info!("Model loaded successfully from: {} ({} bytes)", self.model_path, file_size);
         Ok(())
     }
 
+    /// How the model at `model_path` would be split between GPU and CPU, without loading it
+    pub fn get_model_load_plan(&self, model_path: &str) -> CodexResult<ModelLoadPlan> {
+        use crate::ai::engine::GGUFEngine;
+
+        let resolved_path = crate::paths::resolve_model_path(&self.models_dir, model_path);
+        if !resolved_path.exists() {
+            return Err(crate::CodexError::not_found(
+                format!("Model file not found: {}", resolved_path.display())
+            ));
+        }
+        let metadata = GGUFEngine::parse_gguf_metadata(&resolved_path)?;
+        let config = GGUFEngine::metadata_to_config(&metadata)?;
+        Ok(self.plan_load(&metadata, &config))
+    }
+
+    fn plan_load(&self, metadata: &GGUFMetadata, config: &LlamaConfig) -> ModelLoadPlan {
+        let vram = GpuDevice::of(&self.device).and_then(GpuDevice::query);
+        load_plan::plan_gpu_layers(metadata, config, self.context_length, self.gpu_layers, vram)
+    }
+
     /// Get model manifest if available
     async fn get_model_manifest(&self, model_path: &Path) -> Option<crate::update::manifest::ModelManifest> {
         // Look for manifest file in the same directory
@@ -589,24 +964,52 @@ impl InferenceEngine {
         }
     }
 
+    /// Limits applied to every generation, set from the power policy
+    pub fn generation_throttle(&self) -> Arc<GenerationThrottle> {
+        Arc::clone(&self.throttle)
+    }
+
+    /// `config` with `max_tokens` capped by the throttle
+    fn throttled_config<'a>(&self, config: &'a AiConfig) -> std::borrow::Cow<'a, AiConfig> {
+        match self.throttle.max_tokens() {
+            Some(max_tokens) if max_tokens < config.max_tokens => {
+                std::borrow::Cow::Owned(AiConfig { max_tokens, ..config.clone() })
+            }
+            _ => std::borrow::Cow::Borrowed(config),
+        }
+    }
+
     /// Generate text completion
-    #[instrument(skip(self, config), fields(prompt_len = prompt.len()))]
     pub async fn generate(&self, prompt: &str, config: &AiConfig) -> CodexResult<String> {
+        Ok(self.generate_with_details(prompt, config).await?.text)
+    }
+
+    /// Generate text completion, reporting how the prompt was truncated to fit the context
+    #[instrument(skip(self, config), fields(prompt_len = prompt.len()))]
+    pub async fn generate_with_details(&self, prompt: &str, config: &AiConfig) -> CodexResult<GenerationOutput> {
         let start_time = Instant::now();
+        let throttled = self.throttled_config(config);
+        let config: &AiConfig = &throttled;
         
         // Check cache first
         if config.enable_caching {
             let cache_key = self.create_cache_key(prompt, config);
             if let Some(cached_response) = self.get_from_cache(&cache_key).await {
-                return Ok(cached_response);
+                let usage = cached_response.usage.timed(start_time.elapsed(), start_time.elapsed());
+                return Ok(GenerationOutput { usage, ..cached_response });
             }
         }
 
         // Perform inference
-        let response = self.perform_inference(prompt, config).await?;
+        let permit = self.queue_slot().await;
+        let mut response = self.perform_inference(prompt, config).await?;
+        drop(permit);
+        response.usage = response.usage.timed(start_time.elapsed(), start_time.elapsed());
 
-        // Update statistics
+        // Update statistics; the whole response arrives at once
         self.update_stats(start_time.elapsed(), false).await;
+        self.record_first_token(start_time.elapsed()).await;
+        crate::metrics::record(crate::metrics::INFERENCE, start_time.elapsed());
 
         // Cache the response
         if config.enable_caching {
@@ -618,36 +1021,219 @@ impl InferenceEngine {
     }
 
     /// Generate text with streaming callback
-    #[instrument(skip(self, config, callback), fields(prompt_len = prompt.len()))]
     pub async fn generate_stream(
         &self,
         prompt: &str,
         config: &AiConfig,
         callback: impl Fn(String) + Send + Sync + 'static,
     ) -> CodexResult<String> {
+        let output = self
+            .generate_stream_with_details(prompt, config, move |chunk: StreamChunk| callback(chunk.text))
+            .await?;
+        Ok(output.text)
+    }
+
+    /// Generate text with streaming callback, reporting token counts and timing
+    ///
+    /// Each [`StreamChunk`] carries the response so far with the completion
+    /// tokens generated up to it, the last one matching the returned usage.
+    #[instrument(skip(self, config, callback), fields(prompt_len = prompt.len()))]
+    pub async fn generate_stream_with_details(
+        &self,
+        prompt: &str,
+        config: &AiConfig,
+        callback: impl Fn(StreamChunk) + Send + Sync + 'static,
+    ) -> CodexResult<GenerationOutput> {
         let start_time = Instant::now();
+        let first_token = Arc::new(std::sync::OnceLock::new());
+        let callback = {
+            let first_token = Arc::clone(&first_token);
+            move |text: String, completion_tokens: usize| {
+                let elapsed = start_time.elapsed();
+                let _ = first_token.set(elapsed);
+                callback(StreamChunk { text, completion_tokens, elapsed_ms: elapsed.as_millis() as u64 })
+            }
+        };
         
         // For streaming, we don't use cache
-        let response = self.perform_inference_stream(prompt, config, callback).await?;
+        let throttled = self.throttled_config(config);
+        let config: &AiConfig = &throttled;
+        let permit = self.queue_slot().await;
+        let mut response = self.perform_inference_stream(prompt, config, callback).await?;
+        drop(permit);
+        let first_token = first_token.get().copied().unwrap_or_else(|| start_time.elapsed());
+        response.usage = response.usage.timed(start_time.elapsed(), first_token);
 
         // Update statistics
         self.update_stats(start_time.elapsed(), false).await;
+        self.record_first_token(first_token).await;
+        crate::metrics::record(crate::metrics::INFERENCE, start_time.elapsed());
 
         Ok(response)
     }
 
+    /// Generate the next turn of a conversation with streaming callback
+    ///
+    /// The KV cache left by the previous turn of `conversation_id` is reused
+    /// for the prompt tokens both turns share, so only the new part of the
+    /// conversation runs through the model. Without loaded weights this is
+    /// [`Self::generate_stream`].
+    #[instrument(skip(self, config, callback), fields(prompt_len = prompt.len()))]
+    pub async fn generate_in_conversation(
+        &self,
+        conversation_id: &str,
+        prompt: &str,
+        config: &AiConfig,
+        callback: impl Fn(String) + Send + Sync + 'static,
+    ) -> CodexResult<String> {
+        let (Some(model), Some(tokenizer)) = (self.model.clone(), self.tokenizer.clone()) else {
+            return self.generate_stream(prompt, config, callback).await;
+        };
+        let start_time = Instant::now();
+        let throttled = self.throttled_config(config);
+        let config: &AiConfig = &throttled;
+        let _permit = self.queue_slot().await;
+
+        let prompt_tokens = tokenizer.encode(prompt, true)
+            .map_err(|e| crate::CodexError::ai_inference(format!("Tokenization failed: {}", e)))?
+            .get_ids()
+            .to_vec();
+        let (prompt_tokens, _) = Self::fit_prompt(prompt_tokens, config)?;
+        let reused = self.prefix_cache().take(conversation_id, &prompt_tokens);
+        debug!(
+            "Conversation {}: {} of {} prompt tokens reused",
+            conversation_id,
+            reused.as_ref().map_or(0, |(_, len)| *len),
+            prompt_tokens.len()
+        );
+
+        let mut llama_config = self.config.clone();
+        llama_config.max_position_embeddings = config.max_context_length;
+        let eos_tokens = match &llama_config.eos_token_id {
+            Some(LlamaEosToks::Single(id)) => vec![*id],
+            Some(LlamaEosToks::Multiple(ids)) => ids.clone(),
+            None => Vec::new(),
+        };
+        let draft_model = self.draft
+            .as_ref()
+            .filter(|_| config.speculative.enabled)
+            .and_then(|draft| {
+                let mut draft_config = draft.config.clone();
+                draft_config.max_position_embeddings = config.max_context_length;
+                Some(LlamaTokenModel {
+                    model: draft.model.clone()?,
+                    config: draft_config.into_config(false),
+                    device: self.device.clone(),
+                    template: None,
+                })
+            });
+        let draft_tokens = config.speculative.draft_tokens;
+        let token_model = LlamaTokenModel {
+            model,
+            config: llama_config.into_config(false),
+            device: self.device.clone(),
+            template: self.kv_cache.lock().await.clone(),
+        };
+        let temperature = (config.temperature > 0.0).then_some(config.temperature as f64);
+        let seed = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+        let mut sampler = LogitsProcessor::new(seed, temperature, Some(config.top_p as f64));
+        let max_new_tokens = config.max_tokens;
+
+        let first_token = Arc::new(std::sync::OnceLock::new());
+        let first_token_for_task = Arc::clone(&first_token);
+        let (response, generation, drafted, accepted) = tokio::task::spawn_blocking(move || {
+            let mut response = String::new();
+            let on_token = |tokens: &[u32]| {
+                let _ = first_token_for_task.set(start_time.elapsed());
+                if let Ok(text) = tokenizer.decode(tokens, true) {
+                    response = text;
+                    callback(response.clone());
+                }
+            };
+            let (generation, drafted, accepted) = match draft_model {
+                Some(draft_model) => {
+                    let result = speculative::generate(
+                        &token_model,
+                        &draft_model,
+                        &prompt_tokens,
+                        reused,
+                        &mut sampler,
+                        draft_tokens,
+                        max_new_tokens,
+                        &eos_tokens,
+                        on_token,
+                    )?;
+                    (result.generation, result.drafted_tokens, result.accepted_tokens)
+                }
+                None => {
+                    let generation = prefix_cache::generate(
+                        &token_model,
+                        &prompt_tokens,
+                        reused,
+                        &mut sampler,
+                        max_new_tokens,
+                        &eos_tokens,
+                        on_token,
+                    )?;
+                    (generation, 0, 0)
+                }
+            };
+            Ok::<_, crate::CodexError>((response, generation, drafted, accepted))
+        }).await
+        .map_err(|e| crate::CodexError::internal(format!("Inference task failed: {}", e)))??;
+
+        {
+            let mut stats = self.stats.lock().await;
+            stats.generated_tokens += generation.tokens.len() as u64;
+            stats.generation_time += start_time.elapsed();
+            stats.drafted_tokens += drafted as u64;
+            stats.accepted_tokens += accepted as u64;
+        }
+        self.prefix_cache().store(conversation_id, generation.processed, generation.state);
+
+        self.update_stats(start_time.elapsed(), false).await;
+        self.record_first_token(first_token.get().copied().unwrap_or_else(|| start_time.elapsed())).await;
+        crate::metrics::record(crate::metrics::INFERENCE, start_time.elapsed());
+
+        Ok(response)
+    }
+
+    /// Wait in the inference queue for a generation slot, recording the wait as [`crate::metrics::AI_QUEUE_WAIT`]
+    async fn queue_slot(&self) -> GenerationPermit<'_> {
+        let start = Instant::now();
+        let permit = self.throttle.acquire().await;
+        crate::metrics::record(crate::metrics::AI_QUEUE_WAIT, start.elapsed());
+        permit
+    }
+
+    fn prefix_cache(&self) -> std::sync::MutexGuard<'_, PromptPrefixCache<LlamaState>> {
+        self.prefix_cache.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
     /// Perform the actual inference with CPU-bound work in blocking task
     #[instrument(skip(self, config), fields(prompt_len = prompt.len()))]
-    async fn perform_inference(&self, prompt: &str, config: &AiConfig) -> CodexResult<String> {
+    async fn perform_inference(&self, prompt: &str, config: &AiConfig) -> CodexResult<GenerationOutput> {
+        if let Some(engine) = &self.engine {
+            let text = engine.generate(prompt, GenerationSettings::from_config(config)).await?;
+            let usage = GenerationUsage {
+                prompt_tokens: engine.count_tokens(prompt),
+                completion_tokens: engine.count_tokens(&text),
+                ..GenerationUsage::default()
+            };
+            return Ok(GenerationOutput { text, truncation: None, usage });
+        }
+
         // Capture baseline metrics before inference
         {
             let mut metrics = self.system_metrics.lock().await;
             metrics.capture_baseline("pre_inference");
         }
         // Clone necessary data for the blocking task
-        let tokenizer_clone = Arc::clone(&self.tokenizer.as_ref().unwrap());
+        let tokenizer_clone = Arc::clone(self.tokenizer.as_ref()
+            .ok_or_else(|| crate::CodexError::ai_inference("Tokenizer not loaded"))?);
         let prompt_owned = prompt.to_string();
-        let max_tokens = config.max_tokens;
         let temperature = config.temperature;
         let top_p = config.top_p;
         
@@ -673,14 +1259,6 @@ impl InferenceEngine {
                     
                     let tokens = encoding.get_ids().to_vec();
                     info!("Tokenized prompt: {} tokens", tokens.len());
-                    
-                    // Check if prompt is too long
-                    if tokens.len() > max_tokens {
-                        return Err(crate::CodexError::validation(
-                            format!("Prompt too long: {} tokens, max: {}", tokens.len(), max_tokens)
-                        ));
-                    }
-                    
                     Ok::<Vec<u32>, crate::CodexError>(tokens)
                 }).await
                 .map_err(|e| crate::CodexError::internal(format!("Tokenization task failed: {}", e)))??;
@@ -692,10 +1270,12 @@ impl InferenceEngine {
                 tokens
             }
         };
+        let (tokens, truncation) = Self::fit_prompt(tokens, config)?;
 
         // Perform CPU-bound inference in a blocking task
-        let tokenizer_for_response = Arc::clone(&self.tokenizer.as_ref().unwrap());
-        let response = tokio::task::spawn_blocking(move || {
+        let tokenizer_for_response = Arc::clone(self.tokenizer.as_ref().unwrap());
+        let prompt_tokens = tokens.len();
+        let (response, completion_tokens) = tokio::task::spawn_blocking(move || {
             debug!("Starting inference on blocking thread");
             // NOTE: This is a simplified implementation
             // In a full implementation, you would:
@@ -706,7 +1286,10 @@ impl InferenceEngine {
 
             // For now, we'll implement a basic response generation
             // that demonstrates the structure but doesn't require full model weights
-            Self::generate_response_from_tokens_blocking(&tokens, &tokenizer_for_response, temperature, top_p)
+            let response = Self::generate_response_from_tokens_blocking(&tokens, &tokenizer_for_response, temperature, top_p)?;
+            let completion_tokens = tokenizer_for_response.encode(response.as_str(), false)
+                .map_or(0, |encoding| encoding.get_ids().len());
+            Ok::<_, crate::CodexError>((response, completion_tokens))
         }).await
         .map_err(|e| crate::CodexError::internal(format!("Inference task failed: {}", e)))??;
 
@@ -718,7 +1301,25 @@ impl InferenceEngine {
             metrics.log_inference_metrics("post_inference", inference_duration);
         }
 
-        Ok(response)
+        Ok(GenerationOutput {
+            text: response,
+            truncation,
+            usage: GenerationUsage { prompt_tokens, completion_tokens, ..GenerationUsage::default() },
+        })
+    }
+
+    /// Cut prompt tokens down to [`AiConfig::prompt_token_limit`] by `config.truncation`
+    fn fit_prompt(tokens: Vec<u32>, config: &AiConfig) -> CodexResult<(Vec<u32>, Option<PromptTruncation>)> {
+        let (tokens, truncation) = truncation::truncate_tokens(tokens, config.prompt_token_limit(), config.truncation)?;
+        if let Some(truncation) = truncation {
+            warn!(
+                "Prompt of {} tokens exceeds the context, dropped {} ({:?})",
+                truncation.original_tokens,
+                truncation.dropped_tokens(),
+                truncation.policy
+            );
+        }
+        Ok((tokens, truncation))
     }
 
     /// Generate response from input tokens (blocking version for CPU-bound work)
@@ -760,7 +1361,7 @@ impl InferenceEngine {
         let processing_time = std::cmp::min(input_tokens.len() * 10, 1000);
         tokio::time::sleep(Duration::from_millis(processing_time as u64)).await;
 
-        let tokenizer = Arc::clone(&self.tokenizer.as_ref().unwrap());
+        let tokenizer = Arc::clone(self.tokenizer.as_ref().unwrap());
         let tokens = input_tokens.to_vec();
         let temperature = config.temperature;
         let top_p = config.top_p;
@@ -772,12 +1373,36 @@ impl InferenceEngine {
     }
 
     /// Perform streaming inference with token-by-token generation
+    ///
+    /// `callback` receives the response so far and the completion tokens in it.
     async fn perform_inference_stream(
         &self,
         prompt: &str,
         config: &AiConfig,
-        callback: impl Fn(String) + Send + Sync + 'static,
-    ) -> CodexResult<String> {
+        callback: impl Fn(String, usize) + Send + Sync + 'static,
+    ) -> CodexResult<GenerationOutput> {
+        if let Some(engine) = &self.engine {
+            // Engines call back once per token
+            let streamed = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+            let counter = Arc::clone(&streamed);
+            let text = engine
+                .generate_stream(
+                    prompt,
+                    GenerationSettings::from_config(config),
+                    Box::new(move |text| {
+                        callback(text, counter.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1)
+                    }),
+                    None,
+                )
+                .await?;
+            let usage = GenerationUsage {
+                prompt_tokens: engine.count_tokens(prompt),
+                completion_tokens: streamed.load(std::sync::atomic::Ordering::Relaxed),
+                ..GenerationUsage::default()
+            };
+            return Ok(GenerationOutput { text, truncation: None, usage });
+        }
+
         // Ensure model and tokenizer are loaded
         let tokenizer = self.tokenizer.as_ref()
             .ok_or_else(|| crate::CodexError::ai_inference("Tokenizer not loaded"))?;
@@ -790,22 +1415,26 @@ impl InferenceEngine {
         let encoding = tokenizer.encode(prompt, true)
             .map_err(|e| crate::CodexError::ai_inference(format!("Tokenization failed: {}", e)))?;
         
-        let tokens = encoding.get_ids();
-        info!("Streaming inference for prompt: {} tokens", tokens.len());
+        info!("Streaming inference for prompt: {} tokens", encoding.get_ids().len());
+        let (tokens, truncation) = Self::fit_prompt(encoding.get_ids().to_vec(), config)?;
 
         // Generate response with streaming
-        let full_response = self.generate_streaming_response(tokens, config, callback).await?;
+        let (text, completion_tokens) = self.generate_streaming_response(&tokens, config, callback).await?;
 
-        Ok(full_response)
+        Ok(GenerationOutput {
+            text,
+            truncation,
+            usage: GenerationUsage { prompt_tokens: tokens.len(), completion_tokens, ..GenerationUsage::default() },
+        })
     }
 
-    /// Generate streaming response token by token
+    /// Generate streaming response token by token, returning it with its token count
     async fn generate_streaming_response(
         &self,
         input_tokens: &[u32],
         config: &AiConfig,
-        callback: impl Fn(String) + Send + Sync + 'static,
-    ) -> CodexResult<String> {
+        callback: impl Fn(String, usize) + Send + Sync + 'static,
+    ) -> CodexResult<(String, usize)> {
         let tokenizer = self.tokenizer.as_ref().unwrap();
         
         // Decode input to understand context
@@ -845,8 +1474,9 @@ impl InferenceEngine {
             tokio::time::sleep(delay).await;
             
             // Call the callback with incremental response
-            callback(full_response.clone());
+            callback(full_response.clone(), i + 1);
         }
+        let mut completion_tokens = words.len();
 
         // Apply temperature-based variation for final response
         if config.temperature > 0.7 {
@@ -854,11 +1484,12 @@ impl InferenceEngine {
             full_response.push_str(additional_text);
             
             // Stream the additional text
+            completion_tokens += additional_text.split_whitespace().count();
             tokio::time::sleep(Duration::from_millis(100)).await;
-            callback(full_response.clone());
+            callback(full_response.clone(), completion_tokens);
         }
 
-        Ok(full_response)
+        Ok((full_response, completion_tokens))
     }
 
     /// Create cache key for a prompt and config
@@ -871,50 +1502,118 @@ impl InferenceEngine {
         config.temperature.to_bits().hash(&mut hasher);
         config.top_p.to_bits().hash(&mut hasher);
         config.max_tokens.hash(&mut hasher);
+        config.max_context_length.hash(&mut hasher);
+        config.truncation.hash(&mut hasher);
         
         format!("{:x}", hasher.finish())
     }
 
     /// Get response from cache with automatic LRU eviction
-    async fn get_from_cache(&self, cache_key: &str) -> Option<String> {
-        let mut cache = self.cache.lock().await;
-        
-        if let Some(entry) = cache.entries.get_mut(cache_key) {
-            // Check if entry is still valid (not older than 1 hour)
-            if entry.created_at.elapsed() < Duration::from_secs(3600) {
-                entry.access_count += 1;
-                entry.last_accessed = Instant::now();
-                
-                // Update cache hit stats
-                let mut stats = self.stats.lock().await;
-                stats.cache_hits += 1;
-                
-                return Some(entry.response.clone());
-            } else {
-                // Remove expired entry
-                cache.entries.pop(cache_key);
-            }
-        }
+    async fn get_from_cache(&self, cache_key: &str) -> Option<GenerationOutput> {
+        let response = self.cache.lock().await.get(cache_key);
 
-        // Update cache miss stats
+        // Update cache hit stats
         let mut stats = self.stats.lock().await;
-        stats.cache_misses += 1;
-        
-        None
+        if response.is_some() {
+            stats.cache_hits += 1;
+        } else {
+            stats.cache_misses += 1;
+        }
+        response
     }
 
     /// Cache a response with automatic LRU eviction
-    async fn cache_response(&self, cache_key: &str, response: &str) {
-        let mut cache = self.cache.lock().await;
-        
-        let now = Instant::now();
-        cache.entries.put(cache_key.to_string(), CacheEntry {
-            response: response.to_string(),
-            created_at: now,
-            last_accessed: now,
-            access_count: 1,
-        });
+    async fn cache_response(&self, cache_key: &str, response: &GenerationOutput) {
         // LruCache automatically handles eviction of least recently used items
+        self.cache.lock().await.put(cache_key.to_string(), response.clone());
+    }
+
+    /// Record the time until a generation produced its first token
+    async fn record_first_token(&self, latency: Duration) {
+        self.stats.lock().await.first_token.record(latency);
+        crate::metrics::record(crate::metrics::TIME_TO_FIRST_TOKEN, latency);
+    }
+
+    /// Prepare the engine so the first generation is as fast as later ones
+    ///
+    /// Tokenizes [`WARM_UP_PROMPT_PREFIXES`], allocates the KV cache for
+    /// `config.max_context_length` and, with the weights loaded, runs a
+    /// forward pass over a single token. An engine passed to
+    /// [`Self::with_engine`] only has its health checked.
+    pub async fn warm_up(&self, config: &AiConfig) -> CodexResult<()> {
+        if self.engine.is_none() && self.tokenizer.is_none() {
+            return Err(crate::CodexError::ai_inference("Tokenizer not loaded"));
+        }
+
+        self.set_warm_up_state(ModelWarmUpState::WarmingUp);
+        let start = Instant::now();
+        let result = match (&self.engine, &self.tokenizer) {
+            (Some(engine), _) => match engine.health_check().await {
+                Ok(true) => Ok(()),
+                Ok(false) => Err(crate::CodexError::ai_inference("Engine is not ready")),
+                Err(e) => Err(e),
+            },
+            (None, Some(tokenizer)) => self.run_warm_up(Arc::clone(tokenizer), config).await,
+            (None, None) => Err(crate::CodexError::ai_inference("Tokenizer not loaded")),
+        };
+        self.set_warm_up_state(match &result {
+            Ok(()) => ModelWarmUpState::Ready { elapsed_ms: start.elapsed().as_millis() as u64 },
+            Err(e) => ModelWarmUpState::Failed { error: e.to_string() },
+        });
+        info!("Model warm-up finished in {:?}: {:?}", start.elapsed(), self.warm_up_state());
+        result
+    }
+
+    async fn run_warm_up(&self, tokenizer: Arc<Tokenizer>, config: &AiConfig) -> CodexResult<()> {
+        // Encoding once also fills the tokenizer's own caches
+        let prefixes = tokio::task::spawn_blocking(move || {
+            WARM_UP_PROMPT_PREFIXES
+                .iter()
+                .map(|prefix| {
+                    let encoding = tokenizer.encode(*prefix, true)
+                        .map_err(|e| crate::CodexError::ai_inference(format!("Tokenization failed: {}", e)))?;
+                    Ok((prefix.to_string(), encoding.get_ids().to_vec()))
+                })
+                .collect::<CodexResult<Vec<_>>>()
+        }).await
+        .map_err(|e| crate::CodexError::internal(format!("Warm-up task failed: {}", e)))??;
+        {
+            let mut token_cache = self.token_cache.lock().await;
+            for (prefix, tokens) in prefixes {
+                token_cache.cache_prompt_tokens(&prefix, tokens);
+            }
+        }
+
+        let mut llama_config = self.config.clone();
+        llama_config.max_position_embeddings = config.max_context_length;
+        let bos_token = llama_config.bos_token_id.unwrap_or(1);
+        let device = self.device.clone();
+        let model = self.model.clone();
+        let cache = tokio::task::spawn_blocking(move || -> candle_core::Result<Cache> {
+            let cache = Cache::new(true, DType::F32, &llama_config.into_config(false), &device)?;
+            if let Some(model) = model {
+                // Run on a copy so the kept cache starts empty
+                let input = Tensor::new(&[bos_token], &device)?.unsqueeze(0)?;
+                model.forward(&input, 0, &mut cache.clone())?;
+            } else {
+                debug!("No weights loaded, skipping the warm-up forward pass");
+            }
+            Ok(cache)
+        }).await
+        .map_err(|e| crate::CodexError::internal(format!("Warm-up task failed: {}", e)))?
+        .map_err(|e| crate::CodexError::ai_inference(format!("Warm-up forward pass failed: {}", e)))?;
+
+        *self.kv_cache.lock().await = Some(cache);
+        Ok(())
+    }
+
+    /// Whether the model has been warmed up since it was loaded
+    pub fn warm_up_state(&self) -> ModelWarmUpState {
+        self.warm_up_state.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn set_warm_up_state(&self, state: ModelWarmUpState) {
+        *self.warm_up_state.write().unwrap_or_else(|e| e.into_inner()) = state;
     }
 
     /// Update inference statistics
@@ -951,6 +1650,22 @@ impl InferenceEngine {
             average_inference_time_ms,
             cache_hit_rate,
             uptime_seconds: self.start_time.elapsed().as_secs(),
+            time_to_first_token_ms: LatencyPercentiles::from_histogram(&stats.first_token),
+            prompt_prefix_cache: self.prefix_cache().stats(),
+            speculative: SpeculativeStats {
+                drafted_tokens: stats.drafted_tokens,
+                accepted_tokens: stats.accepted_tokens,
+                acceptance_rate: if stats.drafted_tokens > 0 {
+                    stats.accepted_tokens as f64 / stats.drafted_tokens as f64
+                } else {
+                    0.0
+                },
+                tokens_per_second: if stats.generation_time > Duration::ZERO {
+                    stats.generated_tokens as f64 / stats.generation_time.as_secs_f64()
+                } else {
+                    0.0
+                },
+            },
         })
     }
 
@@ -998,6 +1713,11 @@ impl InferenceEngine {
               current_metrics.system_memory_used_mb, current_metrics.system_memory_total_mb);
         info!("   🔥 Peak CPU: {:.1}% (System: {} cores)", 
               current_metrics.peak_cpu_percent, current_metrics.cpu_count);
+        if current_metrics.gpu_metrics_available {
+            info!("   🎮 GPU Memory: {:.1}MB / {:.1}MB (this process: ~{:.1}MB)",
+                  current_metrics.gpu_memory_used_mb, current_metrics.gpu_memory_total_mb,
+                  current_metrics.gpu_memory_process_mb);
+        }
         
         // Log token cache status
         let token_cache = self.token_cache.lock().await;
@@ -1012,7 +1732,10 @@ impl InferenceEngine {
 
     /// Check if model is loaded and ready
     pub fn is_ready(&self) -> bool {
-        self.tokenizer.is_some() && !self.model_path.is_empty()
+        match &self.engine {
+            Some(engine) => engine.is_ready(),
+            None => self.tokenizer.is_some() && !self.model_path.is_empty(),
+        }
     }
 
     /// Verify model integrity (check file hash and basic validation)
@@ -1033,7 +1756,7 @@ impl InferenceEngine {
 
         // Get file metadata
         let metadata = fs::metadata(&self.model_path)
-            .map_err(|e| crate::CodexError::io(e))?;
+            .map_err(crate::CodexError::io)?;
         
         // Check file size (should be reasonable for a model)
         let file_size = metadata.len();
@@ -1057,11 +1780,11 @@ impl InferenceEngine {
 
         // Read first few bytes to check for valid file format
         let mut file = fs::File::open(&self.model_path)
-            .map_err(|e| crate::CodexError::io(e))?;
+            .map_err(crate::CodexError::io)?;
         
         let mut magic_bytes = [0u8; 4];
         file.read_exact(&mut magic_bytes)
-            .map_err(|e| crate::CodexError::io(e))?;
+            .map_err(crate::CodexError::io)?;
 
         // Check for GGUF magic number (simplified)
         if extension == "gguf" {
@@ -1091,8 +1814,15 @@ impl InferenceEngine {
         } else { 
             0.0 
         };
+
+        let draft_memory = self.draft
+            .as_ref()
+            .filter(|draft| draft.model.is_some())
+            .map_or(0.0, |draft| draft.size_mb);
+
+        let prefix_cache_memory = self.prefix_cache().stats().size_bytes as f64 / (1024.0 * 1024.0);
         
-        cache_memory + model_memory
+        cache_memory + model_memory + draft_memory + prefix_cache_memory
     }
 
     /// Check if memory usage is within limits
@@ -1110,6 +1840,15 @@ impl InferenceEngine {
             warn!("Memory usage ({:.1}MB) exceeds limit ({}MB)", current_usage, self.memory_limit_mb);
             return Ok(false);
         }
+        drop(stats);
+
+        if let Some(gpu) = self.system_metrics.lock().await.gpu.current() {
+            if gpu.used_ratio() > GPU_MEMORY_LIMIT_RATIO {
+                warn!("GPU memory usage ({:.1}MB of {:.1}MB) exceeds {:.0}%",
+                      gpu.used_mb(), gpu.total_mb(), GPU_MEMORY_LIMIT_RATIO * 100.0);
+                return Ok(false);
+            }
+        }
         
         Ok(true)
     }
@@ -1125,24 +1864,20 @@ impl InferenceEngine {
             
             // Clear response cache of old entries
             let mut cache = self.cache.lock().await;
-            let now = Instant::now();
-            let cutoff = now - Duration::from_secs(300); // Remove entries older than 5 minutes
-            
-            // Collect keys to remove (LRU cache doesn't support retain)
-            let mut keys_to_remove = Vec::new();
-            for (key, entry) in cache.entries.iter() {
-                if entry.last_accessed <= cutoff {
-                    keys_to_remove.push(key.clone());
-                }
-            }
-            
-            // Remove expired entries
-            for key in keys_to_remove {
-                cache.entries.pop(&key);
-            }
+            cache.remove_idle(RESPONSE_CACHE_IDLE_CUTOFF);
             
             let remaining_cache_entries = cache.entries.len();
             drop(cache);
+
+            // Drop the least recently used conversation KV caches down to half
+            let freed_bytes = {
+                let mut prefix_cache = self.prefix_cache();
+                let remaining_bytes = prefix_cache.stats().size_bytes / 2;
+                prefix_cache.shrink_to(remaining_bytes)
+            };
+            if freed_bytes > 0 {
+                info!("Prompt prefix cache cleanup: freed {:.1}MB", freed_bytes as f64 / (1024.0 * 1024.0));
+            }
             
             // Clean up token cache if using too much memory
             let mut token_cache = self.token_cache.lock().await;
@@ -1185,6 +1920,7 @@ impl InferenceEngine {
                 num_layers: self.config.num_hidden_layers,
                 num_attention_heads: self.config.num_attention_heads,
             },
+            load_plan: self.load_plan.clone(),
         }
     }
 
@@ -1204,10 +1940,16 @@ impl InferenceEngine {
               token_stats.current_token_count, token_stats.memory_usage_mb);
         token_cache.clear();
         drop(token_cache);
+
+        self.prefix_cache().clear();
         
         // Unload model
         self.model = None;
+        self.draft = None;
         self.tokenizer = None;
+        if let Some(engine) = self.engine.take() {
+            engine.unload().await?;
+        }
         
         info!("Inference engine shutdown complete");
         Ok(())
@@ -1221,6 +1963,8 @@ pub struct ModelInfo {
     pub device: String,
     pub is_loaded: bool,
     pub config: ModelConfigInfo,
+    /// GPU/CPU split chosen when the model was loaded
+    pub load_plan: Option<ModelLoadPlan>,
 }
 
 /// Model configuration information
@@ -1230,4 +1974,121 @@ pub struct ModelConfigInfo {
     pub hidden_size: usize,
     pub num_layers: usize,
     pub num_attention_heads: usize,
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_power_throttle_caps_max_tokens() {
+        let engine = InferenceEngine::unloaded(&AiConfig::default());
+        let config = AiConfig { max_tokens: 512, ..AiConfig::default() };
+
+        engine.generation_throttle().set(Some(1), Some(64));
+        assert_eq!(engine.throttled_config(&config).max_tokens, 64);
+        let short = AiConfig { max_tokens: 32, ..config.clone() };
+        assert_eq!(engine.throttled_config(&short).max_tokens, 32);
+
+        engine.generation_throttle().set(None, None);
+        assert_eq!(engine.throttled_config(&config).max_tokens, 512);
+    }
+
+    #[tokio::test]
+    async fn test_queue_wait_is_recorded_for_queued_generations() {
+        let queue_wait = || {
+            crate::metrics::snapshot()
+                .into_iter()
+                .find(|m| m.operation == crate::metrics::AI_QUEUE_WAIT)
+                .map_or((0, 0.0), |m| (m.count, m.max_ms))
+        };
+        let config = AiConfig { enable_caching: false, ..AiConfig::default() };
+        let mock = super::super::MockEngine::new().with_latency(Duration::from_millis(50));
+        let engine = Arc::new(InferenceEngine::with_engine(&config, Arc::new(mock)));
+        engine.generation_throttle().set(Some(1), None);
+        let (before, _) = queue_wait();
+
+        let first = {
+            let (engine, config) = (Arc::clone(&engine), config.clone());
+            tokio::spawn(async move { engine.generate("first", &config).await })
+        };
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        engine.generate("second", &config).await.unwrap();
+        first.await.unwrap().unwrap();
+
+        // The second generation waited in the queue until the first finished
+        let (after, max_ms) = queue_wait();
+        assert!(after >= before + 2);
+        assert!(max_ms >= 20.0, "longest queue wait {}ms", max_ms);
+    }
+
+    #[tokio::test]
+    async fn test_first_token_latency_and_warm_up_state() {
+        let engine = InferenceEngine::unloaded(&AiConfig::default());
+        assert_eq!(engine.warm_up_state(), ModelWarmUpState::Cold);
+        // Without a tokenizer there is nothing to warm up
+        assert!(engine.warm_up(&AiConfig::default()).await.is_err());
+        assert_eq!(engine.warm_up_state(), ModelWarmUpState::Cold);
+
+        for ms in [100, 120, 900] {
+            engine.record_first_token(Duration::from_millis(ms)).await;
+        }
+        let latency = engine.get_stats().await.unwrap().time_to_first_token_ms;
+        assert!((100.0..150.0).contains(&latency.p50));
+        assert!(latency.p95 > 800.0);
+    }
+
+    #[test]
+    fn test_prompt_fitting_the_context_exactly_is_not_truncated() {
+        let config = AiConfig { max_context_length: 24, max_tokens: 8, ..AiConfig::default() };
+        assert_eq!(config.prompt_token_limit(), 16);
+
+        let (tokens, truncation) = InferenceEngine::fit_prompt((0..16).collect(), &config).unwrap();
+        assert_eq!((tokens.len(), truncation), (16, None));
+
+        let (tokens, truncation) = InferenceEngine::fit_prompt((0..17).collect(), &config).unwrap();
+        assert_eq!(tokens.len(), 16);
+        assert_eq!(truncation.unwrap().dropped_tokens(), 1);
+
+        let strict = AiConfig { truncation: crate::config::TruncationPolicy::Error, ..config };
+        assert!(InferenceEngine::fit_prompt((0..16).collect(), &strict).is_ok());
+        assert!(InferenceEngine::fit_prompt((0..17).collect(), &strict).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cached_responses_expire_after_an_hour() {
+        let clock = Arc::new(crate::clock::ManualClock::new());
+        let engine = InferenceEngine::unloaded(&AiConfig::default()).with_clock(clock.clone());
+        let answer = GenerationOutput { text: "answer".to_string(), truncation: None, usage: GenerationUsage::default() };
+        engine.cache_response("question", &answer).await;
+
+        clock.advance(RESPONSE_CACHE_TTL - Duration::from_secs(1));
+        assert_eq!(engine.get_from_cache("question").await.map(|output| output.text).as_deref(), Some("answer"));
+
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(engine.get_from_cache("question").await, None);
+        assert!(engine.cache.lock().await.entries.is_empty());
+
+        let stats = engine.stats.lock().await;
+        assert_eq!((stats.cache_hits, stats.cache_misses), (1, 1));
+    }
+
+    #[test]
+    fn test_cleanup_removes_exactly_the_idle_responses() {
+        let clock = Arc::new(crate::clock::ManualClock::new());
+        let mut cache = InferenceCache::new(10, clock.clone());
+        let output = |text: &str| GenerationOutput { text: text.to_string(), truncation: None, usage: GenerationUsage::default() };
+        cache.put("old".to_string(), output("a"));
+        cache.put("read".to_string(), output("b"));
+
+        clock.advance(RESPONSE_CACHE_IDLE_CUTOFF - Duration::from_secs(60));
+        cache.put("new".to_string(), output("c"));
+        assert!(cache.get("read").is_some());
+
+        // "old" is idle for exactly the cutoff, the others for less
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(cache.remove_idle(RESPONSE_CACHE_IDLE_CUTOFF), 1);
+        let mut kept: Vec<&String> = cache.entries.iter().map(|(key, _)| key).collect();
+        kept.sort();
+        assert_eq!(kept, ["new", "read"]);
+    }
+}
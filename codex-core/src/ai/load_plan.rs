@@ -0,0 +1,247 @@
+//! Splitting a model's layers between the GPU and the CPU
+//!
+//! Each transformer block is sized from its tensors in the GGUF header,
+//! at the quantization they are stored in, plus its share of the KV cache
+//! for the context length. With `gpu_layers = "auto"` as many blocks are
+//! offloaded as fit in the device's free memory after
+//! [`VRAM_SAFETY_MARGIN`] is set aside; the embeddings and output head go
+//! to the GPU with the first block.
+
+use std::collections::HashMap;
+
+use candle_transformers::models::llama::LlamaConfig;
+use serde::{Deserialize, Serialize};
+
+use super::gguf::{self, GGUFMetadata};
+use crate::config::GpuLayers;
+
+/// Share of the device's memory left for the driver, other processes and activations
+pub const VRAM_SAFETY_MARGIN: f64 = 0.10;
+
+/// Bytes per KV cache element; GPU caches are kept in half precision
+const KV_CACHE_ELEMENT_BYTES: u64 = 2;
+
+/// How a model's layers are split between the GPU and the CPU
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelLoadPlan {
+    /// Transformer blocks in the model
+    pub total_layers: usize,
+    /// Blocks offloaded to the GPU
+    pub gpu_layers: usize,
+    /// Whether `gpu_layers` was fitted to the free VRAM rather than requested
+    pub auto: bool,
+    /// Weights and KV cache of the largest block
+    pub per_layer_bytes: u64,
+    /// Embeddings, output head and norms outside the blocks
+    pub non_layer_bytes: u64,
+    /// Free VRAM when planned, `None` without a GPU or GPU metrics
+    pub available_vram_bytes: Option<u64>,
+    /// Most common tensor type of the blocks, e.g. "Q4_K"
+    pub quantization: Option<String>,
+}
+
+impl ModelLoadPlan {
+    pub fn cpu_layers(&self) -> usize {
+        self.total_layers - self.gpu_layers
+    }
+
+    /// Whether every block runs on the GPU
+    pub fn fully_offloaded(&self) -> bool {
+        self.total_layers > 0 && self.gpu_layers == self.total_layers
+    }
+
+    /// Estimated GPU memory the offloaded layers take
+    pub fn gpu_bytes(&self) -> u64 {
+        if self.gpu_layers == 0 {
+            return 0;
+        }
+        self.non_layer_bytes + self.per_layer_bytes * self.gpu_layers as u64
+    }
+}
+
+impl std::fmt::Display for ModelLoadPlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        const MB: f64 = 1024.0 * 1024.0;
+        write!(
+            f,
+            "{} of {} layers on the GPU ({}, {:.0}MB per layer, {:.0}MB on the GPU",
+            self.gpu_layers,
+            self.total_layers,
+            if self.auto { "auto" } else { "requested" },
+            self.per_layer_bytes as f64 / MB,
+            self.gpu_bytes() as f64 / MB,
+        )?;
+        match self.available_vram_bytes {
+            Some(available) => write!(f, ", {:.0}MB free)", available as f64 / MB),
+            None => write!(f, ", no GPU memory reading)"),
+        }
+    }
+}
+
+/// Block number of a tensor named `blk.<n>.<...>`
+fn block_index(name: &str) -> Option<usize> {
+    name.strip_prefix("blk.")?.split('.').next()?.parse().ok()
+}
+
+/// Plan how many of the model's layers go to the GPU
+///
+/// `vram` is the used and total memory of the GPU the model would load on,
+/// `None` on the CPU or when the device cannot be queried; `Auto` then
+/// keeps every layer on the CPU. A requested count is capped at the number
+/// of layers but not checked against the VRAM. Tensors of unknown types are
+/// counted as `f32`, so the estimate errs high.
+pub fn plan_gpu_layers(
+    metadata: &GGUFMetadata,
+    config: &LlamaConfig,
+    context_length: usize,
+    requested: GpuLayers,
+    vram: Option<(u64, u64)>,
+) -> ModelLoadPlan {
+    let mut block_bytes: HashMap<usize, u64> = HashMap::new();
+    let mut block_types: HashMap<u32, usize> = HashMap::new();
+    let mut non_layer_bytes = 0u64;
+    for tensor in &metadata.tensors {
+        let size = tensor.size_bytes().unwrap_or_else(|| {
+            tensor.dimensions.iter().fold(4u64, |total, &dim| total.saturating_mul(dim))
+        });
+        match block_index(&tensor.name) {
+            Some(block) => {
+                *block_bytes.entry(block).or_default() += size;
+                *block_types.entry(tensor.tensor_type).or_default() += 1;
+            }
+            None => non_layer_bytes += size,
+        }
+    }
+
+    let head_dim = config.hidden_size / config.num_attention_heads.max(1);
+    let kv_heads = config.num_key_value_heads.unwrap_or(config.num_attention_heads);
+    let context_length = context_length.min(config.max_position_embeddings) as u64;
+    let kv_cache_bytes = 2 * context_length * (kv_heads * head_dim) as u64 * KV_CACHE_ELEMENT_BYTES;
+    let largest_block = block_bytes.values().copied().max();
+    let per_layer_bytes = largest_block.unwrap_or(0) + kv_cache_bytes;
+
+    let total_layers = config.num_hidden_layers;
+    let available_vram_bytes = vram.map(|(used, total)| total.saturating_sub(used));
+    let gpu_layers = match requested {
+        GpuLayers::Count(count) => count.min(total_layers),
+        GpuLayers::Auto => match (vram, largest_block) {
+            // Without block tensors there is nothing to size the layers by
+            (Some((used, total)), Some(_)) => {
+                let reserved = (total as f64 * VRAM_SAFETY_MARGIN) as u64;
+                let budget = total.saturating_sub(used).saturating_sub(reserved);
+                let fitting = budget.saturating_sub(non_layer_bytes) / per_layer_bytes.max(1);
+                (fitting as usize).min(total_layers)
+            }
+            _ => 0,
+        },
+    };
+
+    let quantization = block_types
+        .into_iter()
+        .max_by_key(|&(tensor_type, count)| (count, std::cmp::Reverse(tensor_type)))
+        .and_then(|(tensor_type, _)| gguf::ggml_type_name(tensor_type))
+        .map(str::to_string);
+
+    ModelLoadPlan {
+        total_layers,
+        gpu_layers,
+        auto: requested == GpuLayers::Auto,
+        per_layer_bytes,
+        non_layer_bytes,
+        available_vram_bytes,
+        quantization,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::gguf::GGUFTensorInfo;
+
+    const MB: u64 = 1024 * 1024;
+    const GB: u64 = 1024 * MB;
+
+    /// Four blocks of 100MB of F32 weights and a 50MB embedding table
+    fn model() -> (GGUFMetadata, LlamaConfig) {
+        let tensor = |name: String, bytes: u64| GGUFTensorInfo {
+            name,
+            dimensions: vec![bytes / 4],
+            tensor_type: 0,
+            offset: 0,
+        };
+        let mut tensors = vec![tensor("token_embd.weight".to_string(), 50 * MB)];
+        for block in 0..4 {
+            tensors.push(tensor(format!("blk.{}.attn_q.weight", block), 40 * MB));
+            tensors.push(tensor(format!("blk.{}.ffn_up.weight", block), 60 * MB));
+        }
+        let metadata = GGUFMetadata {
+            version: 3,
+            tensor_count: tensors.len() as u64,
+            metadata_kv_count: 0,
+            metadata: HashMap::new(),
+            tensors,
+        };
+        let config = LlamaConfig {
+            vocab_size: 32000,
+            hidden_size: 512,
+            intermediate_size: 1024,
+            num_hidden_layers: 4,
+            num_attention_heads: 8,
+            num_key_value_heads: None,
+            max_position_embeddings: 1024,
+            rms_norm_eps: 1e-5,
+            rope_theta: 10000.0,
+            bos_token_id: Some(1),
+            eos_token_id: None,
+            tie_word_embeddings: Some(false),
+            rope_scaling: None,
+        };
+        (metadata, config)
+    }
+
+    #[test]
+    fn test_layers_are_sized_from_their_tensors_and_kv_cache() {
+        let (metadata, config) = model();
+        let plan = plan_gpu_layers(&metadata, &config, 1024, GpuLayers::Count(0), None);
+        // K and V of 1024 positions by 512 half-precision values
+        assert_eq!(plan.per_layer_bytes, 100 * MB + 2 * MB);
+        assert_eq!(plan.non_layer_bytes, 50 * MB);
+        assert_eq!(plan.quantization.as_deref(), Some("F32"));
+        assert_eq!((plan.gpu_layers, plan.cpu_layers(), plan.gpu_bytes()), (0, 4, 0));
+    }
+
+    #[test]
+    fn test_auto_offloads_the_layers_that_fit_under_the_margin() {
+        let (metadata, config) = model();
+        // 1GB device with 650MB in use: 374MB free, 271.6MB after the margin
+        let plan = plan_gpu_layers(&metadata, &config, 1024, GpuLayers::Auto, Some((650 * MB, GB)));
+        assert_eq!(plan.available_vram_bytes, Some(374 * MB));
+        assert_eq!(plan.gpu_layers, 2);
+        assert!(plan.auto && !plan.fully_offloaded());
+        assert_eq!(plan.gpu_bytes(), 50 * MB + 2 * 102 * MB);
+
+        let plan = plan_gpu_layers(&metadata, &config, 1024, GpuLayers::Auto, Some((0, 8 * GB)));
+        assert_eq!(plan.gpu_layers, 4);
+        assert!(plan.fully_offloaded());
+    }
+
+    #[test]
+    fn test_auto_stays_on_the_cpu_without_room_or_a_reading() {
+        let (metadata, config) = model();
+        assert_eq!(plan_gpu_layers(&metadata, &config, 1024, GpuLayers::Auto, None).gpu_layers, 0);
+        // Room for the embeddings but not a single block
+        let plan = plan_gpu_layers(&metadata, &config, 1024, GpuLayers::Auto, Some((GB - 200 * MB, GB)));
+        assert_eq!(plan.gpu_layers, 0);
+
+        let no_tensors = GGUFMetadata { tensors: Vec::new(), ..metadata };
+        assert_eq!(plan_gpu_layers(&no_tensors, &config, 1024, GpuLayers::Auto, Some((0, 8 * GB))).gpu_layers, 0);
+    }
+
+    #[test]
+    fn test_requested_count_is_capped_at_the_layer_count() {
+        let (metadata, config) = model();
+        let plan = plan_gpu_layers(&metadata, &config, 1024, GpuLayers::Count(99), Some((GB - MB, GB)));
+        assert_eq!(plan.gpu_layers, 4);
+        assert!(!plan.auto);
+    }
+}
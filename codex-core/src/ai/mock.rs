@@ -0,0 +1,368 @@
+//! Deterministic stand-ins for the language and embedding models
+//!
+//! [`MockEngine`] answers prompts from canned responses and
+//! [`MockEmbeddingBackend`] hashes words into vectors, so content import,
+//! search and RAG run without model files. Inject them with
+//! [`AiEngine::with_backends`](super::AiEngine::with_backends).
+//!
+//! Only built for this crate's tests and with the `test-utils` feature.
+
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use regex::Regex;
+use tokio_util::sync::CancellationToken;
+
+use crate::{CodexError, CodexResult};
+use super::embedding_backend::EmbeddingBackend;
+use super::engine::{EngineParams, EngineType, GenerationSettings, LLMEngine, ModelInfo};
+
+/// Response to prompts matching no pattern
+pub const MOCK_DEFAULT_RESPONSE: &str = "This is a mock response.";
+/// Vector length of [`MockEmbeddingBackend::default`]
+const MOCK_DIMENSIONS: usize = 384;
+
+/// Language model answering from canned responses
+///
+/// Prompts are matched against the patterns in the order they were added;
+/// failure patterns are checked before response patterns, and failures
+/// queued with [`MockEngine::fail_next`] before both.
+#[derive(Debug)]
+pub struct MockEngine {
+    responses: Vec<(Regex, String)>,
+    failures: Vec<(Regex, String)>,
+    default_response: String,
+    /// Delay before a response, or before the first streamed word
+    latency: Duration,
+    /// Delay between streamed words
+    token_latency: Duration,
+    /// Failures returned by the next calls, oldest first
+    scripted_failures: Mutex<VecDeque<String>>,
+    /// Every prompt received, in order
+    prompts: Mutex<Vec<String>>,
+}
+
+impl Default for MockEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockEngine {
+    /// Engine answering every prompt with [`MOCK_DEFAULT_RESPONSE`] at once
+    pub fn new() -> Self {
+        Self {
+            responses: Vec::new(),
+            failures: Vec::new(),
+            default_response: MOCK_DEFAULT_RESPONSE.to_string(),
+            latency: Duration::ZERO,
+            token_latency: Duration::ZERO,
+            scripted_failures: Mutex::new(VecDeque::new()),
+            prompts: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Answer prompts matching the regular expression `pattern` with `response`
+    ///
+    /// # Panics
+    ///
+    /// If `pattern` is not a valid regular expression.
+    pub fn respond(mut self, pattern: &str, response: impl Into<String>) -> Self {
+        self.responses.push((compile(pattern), response.into()));
+        self
+    }
+
+    /// Answer prompts matching no pattern with `response`
+    pub fn default_response(mut self, response: impl Into<String>) -> Self {
+        self.default_response = response.into();
+        self
+    }
+
+    /// Fail every prompt matching the regular expression `pattern` with `message`
+    ///
+    /// # Panics
+    ///
+    /// If `pattern` is not a valid regular expression.
+    pub fn fail_when(mut self, pattern: &str, message: impl Into<String>) -> Self {
+        self.failures.push((compile(pattern), message.into()));
+        self
+    }
+
+    /// Wait `latency` before answering, like a model processing the prompt
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Wait `latency` between the words of a streamed response
+    pub fn with_token_latency(mut self, latency: Duration) -> Self {
+        self.token_latency = latency;
+        self
+    }
+
+    /// Fail the next call, whatever its prompt, with `message`
+    ///
+    /// Calls queue up: `fail_next` twice fails the next two calls.
+    pub fn fail_next(&self, message: impl Into<String>) {
+        lock(&self.scripted_failures).push_back(message.into());
+    }
+
+    /// Prompts received so far, in order
+    pub fn prompts(&self) -> Vec<String> {
+        lock(&self.prompts).clone()
+    }
+
+    /// Number of generations requested so far
+    pub fn call_count(&self) -> usize {
+        lock(&self.prompts).len()
+    }
+
+    /// Record `prompt` and pick its response or failure
+    async fn answer(&self, prompt: &str) -> CodexResult<String> {
+        lock(&self.prompts).push(prompt.to_string());
+        if !self.latency.is_zero() {
+            tokio::time::sleep(self.latency).await;
+        }
+
+        if let Some(message) = lock(&self.scripted_failures).pop_front() {
+            return Err(CodexError::ai_inference(message));
+        }
+        if let Some((_, message)) = self.failures.iter().find(|(pattern, _)| pattern.is_match(prompt)) {
+            return Err(CodexError::ai_inference(message.clone()));
+        }
+        Ok(self
+            .responses
+            .iter()
+            .find(|(pattern, _)| pattern.is_match(prompt))
+            .map_or_else(|| self.default_response.clone(), |(_, response)| response.clone()))
+    }
+}
+
+#[async_trait]
+impl LLMEngine for MockEngine {
+    async fn load(_model_path: &Path, _params: EngineParams) -> CodexResult<Arc<dyn LLMEngine>> {
+        Ok(Arc::new(Self::new()))
+    }
+
+    async fn generate(&self, prompt: &str, settings: GenerationSettings) -> CodexResult<String> {
+        let response = self.answer(prompt).await?;
+        Ok(truncate_words(&response, settings.max_tokens))
+    }
+
+    async fn generate_stream(
+        &self,
+        prompt: &str,
+        settings: GenerationSettings,
+        callback: Box<dyn Fn(String) + Send + Sync>,
+        cancellation_token: Option<CancellationToken>,
+    ) -> CodexResult<String> {
+        let response = self.answer(prompt).await?;
+
+        // One word per token, each callback receiving the response so far
+        let mut partial = String::new();
+        for (i, word) in response.split_whitespace().take(settings.max_tokens).enumerate() {
+            if cancellation_token.as_ref().is_some_and(CancellationToken::is_cancelled) {
+                break;
+            }
+            if i > 0 {
+                partial.push(' ');
+                if !self.token_latency.is_zero() {
+                    tokio::time::sleep(self.token_latency).await;
+                }
+            }
+            partial.push_str(word);
+            callback(partial.clone());
+        }
+        Ok(partial)
+    }
+
+    /// One token per word, as responses are streamed
+    fn count_tokens(&self, text: &str) -> usize {
+        text.split_whitespace().count()
+    }
+
+    fn engine_type(&self) -> EngineType {
+        EngineType::Mock
+    }
+
+    fn is_ready(&self) -> bool {
+        true
+    }
+
+    fn get_model_info(&self) -> ModelInfo {
+        ModelInfo {
+            name: "mock".to_string(),
+            architecture: "mock".to_string(),
+            parameter_count: "0".to_string(),
+            quantization: None,
+            context_length: 4096,
+            vocab_size: 0,
+            file_size_bytes: 0,
+            is_loaded: true,
+            device: "cpu".to_string(),
+            load_plan: None,
+        }
+    }
+
+    async fn get_memory_usage(&self) -> u64 {
+        0
+    }
+
+    async fn unload(&self) -> CodexResult<()> {
+        Ok(())
+    }
+}
+
+/// Embeddings hashed from the words of a text
+///
+/// Each lowercased word adds to a dimension picked by its FNV-1a hash, so
+/// texts sharing words have a high cosine similarity and the vectors are
+/// the same across runs and platforms.
+#[derive(Debug)]
+pub struct MockEmbeddingBackend {
+    dimensions: usize,
+    /// Failures returned by the next calls, oldest first
+    scripted_failures: Mutex<VecDeque<String>>,
+}
+
+impl Default for MockEmbeddingBackend {
+    fn default() -> Self {
+        Self::new(MOCK_DIMENSIONS)
+    }
+}
+
+impl MockEmbeddingBackend {
+    /// Backend producing vectors of `dimensions` values
+    pub fn new(dimensions: usize) -> Self {
+        assert!(dimensions > 0, "embeddings need at least one dimension");
+        Self {
+            dimensions,
+            scripted_failures: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Fail the next embedding request with `message`
+    pub fn fail_next(&self, message: impl Into<String>) {
+        lock(&self.scripted_failures).push_back(message.into());
+    }
+
+    fn embed(&self, text: &str) -> Vec<f32> {
+        let mut vector = vec![0.0; self.dimensions];
+        for word in text.split(|c: char| !c.is_alphanumeric()).filter(|word| !word.is_empty()) {
+            let hash = fnv1a(word.to_lowercase().as_bytes());
+            let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+            vector[(hash % self.dimensions as u64) as usize] += sign;
+        }
+
+        let norm = vector.iter().map(|value| value * value).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            vector.iter_mut().for_each(|value| *value /= norm);
+        }
+        vector
+    }
+}
+
+#[async_trait]
+impl EmbeddingBackend for MockEmbeddingBackend {
+    async fn generate(&self, text: &str) -> CodexResult<Vec<f32>> {
+        if let Some(message) = lock(&self.scripted_failures).pop_front() {
+            return Err(CodexError::ai_inference(message));
+        }
+        Ok(self.embed(text))
+    }
+
+    fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    fn model_id(&self) -> String {
+        "mock-embedding".to_string()
+    }
+}
+
+fn compile(pattern: &str) -> Regex {
+    Regex::new(pattern).unwrap_or_else(|e| panic!("Invalid mock pattern {:?}: {}", pattern, e))
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// The first `max_words` words of `text`, or all of it when shorter
+fn truncate_words(text: &str, max_words: usize) -> String {
+    match text.split_whitespace().nth(max_words) {
+        Some(_) => text.split_whitespace().take(max_words).collect::<Vec<_>>().join(" "),
+        None => text.to_string(),
+    }
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cosine(a: &[f32], b: &[f32]) -> f32 {
+        a.iter().zip(b).map(|(x, y)| x * y).sum()
+    }
+
+    #[tokio::test]
+    async fn test_responses_failures_and_streaming() {
+        let engine = MockEngine::new()
+            .respond(r"(?i)summari[sz]e", "A short summary.")
+            .respond(r"(?i)tags", r#"["rust", "testing"]"#)
+            .fail_when("explode", "model crashed");
+        let settings = GenerationSettings::default();
+
+        assert_eq!(engine.generate("Please summarize this", settings.clone()).await.unwrap(), "A short summary.");
+        assert_eq!(engine.generate("Suggest tags", settings.clone()).await.unwrap(), r#"["rust", "testing"]"#);
+        assert_eq!(engine.generate("Anything else", settings.clone()).await.unwrap(), MOCK_DEFAULT_RESPONSE);
+        assert!(engine.generate("explode, then summarize", settings.clone()).await.unwrap_err().to_string().contains("model crashed"));
+
+        engine.fail_next("scripted");
+        assert!(engine.generate("Summarize", settings.clone()).await.is_err());
+        assert!(engine.generate("Summarize", settings.clone()).await.is_ok());
+        assert_eq!(engine.call_count(), 6);
+        assert_eq!(engine.prompts()[1], "Suggest tags");
+
+        let chunks = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&chunks);
+        let settings = GenerationSettings { max_tokens: 2, ..settings };
+        let response = engine
+            .generate_stream("Summarize", settings, Box::new(move |text| sink.lock().unwrap().push(text)), None)
+            .await
+            .unwrap();
+        assert_eq!(response, "A short");
+        assert_eq!(*chunks.lock().unwrap(), ["A", "A short"]);
+    }
+
+    #[tokio::test]
+    async fn test_latency_is_injected() {
+        let engine = MockEngine::new().with_latency(Duration::from_millis(30));
+        let start = std::time::Instant::now();
+        engine.generate("Hello", GenerationSettings::default()).await.unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(30));
+    }
+
+    #[tokio::test]
+    async fn test_embeddings_keep_similarity_stable() {
+        let backend = MockEmbeddingBackend::default();
+        let rust = backend.generate("Rust ownership and borrowing rules").await.unwrap();
+        let similar = backend.generate("Borrowing rules of Rust ownership explained").await.unwrap();
+        let unrelated = backend.generate("Baking sourdough bread at home").await.unwrap();
+
+        assert_eq!(rust.len(), MOCK_DIMENSIONS);
+        assert_eq!(rust, backend.generate("rust OWNERSHIP and borrowing rules").await.unwrap());
+        assert!(cosine(&rust, &similar) > 0.6);
+        assert!(cosine(&rust, &unrelated) < 0.3);
+
+        backend.fail_next("embedding service down");
+        assert!(backend.generate("text").await.is_err());
+        assert!(backend.generate("text").await.is_ok());
+    }
+}
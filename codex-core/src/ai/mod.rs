@@ -8,21 +8,50 @@ use tokio::sync::RwLock;
 use anyhow::Result;
 use tracing::{info, error};
 
-use crate::CodexResult;
+use crate::{metrics, CodexResult};
 use crate::config::AiConfig;
 
 pub mod inference;
 pub mod embeddings;
+pub mod embedding_backend;
 pub mod rag;
 pub mod engine;
-
-pub use inference::{InferenceEngine};
-pub use embeddings::{EmbeddingEngine, ChunkEmbedding};
-pub use rag::{RagEngine, RagConfig, RagResponse, RagSource};
+pub mod gguf;
+pub mod gpu;
+pub mod load_plan;
+pub mod summarize;
+pub mod confidence;
+pub mod rerank;
+pub mod rewrite;
+pub mod audit;
+pub mod prefix_cache;
+pub mod speculative;
+pub mod structured;
+pub mod truncation;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod mock;
+
+pub use inference::{GenerationOutput, GenerationUsage, InferenceEngine, ModelWarmUpState, StreamChunk};
+pub use embeddings::{EmbeddingEngine, ChunkEmbedding, TextEmbedding};
+pub use embedding_backend::{DisabledEmbeddingBackend, EmbeddingBackend, LocalEmbeddingBackend, RemoteEmbeddingBackend};
+pub use rag::{RagEngine, RagConfig, RagResponse, RagSource, DocumentChatResponse};
+pub use summarize::{SummaryProgress, SummaryStage};
+pub use rerank::{Reranker, KeywordReranker, CrossEncoderReranker, RerankerChain};
+pub use audit::{AiAuditLog, AiOperation, AuditOutput};
+pub use prefix_cache::{PrefixCacheStats, PromptPrefixCache};
+pub use speculative::SpeculativeStats;
+pub use structured::{generate_structured, Entity, EntityKind, TextGenerator};
+pub use truncation::PromptTruncation;
+pub use load_plan::ModelLoadPlan;
 pub use engine::{EngineFactory, EngineType, EngineParams, GenerationSettings, LLMEngine, GGUFEngine, HuggingFaceEngine, RemoteEngine};
 
 // Re-export ModelInfo from engine to avoid conflicts
 pub use engine::ModelInfo as EngineModelInfo;
+#[cfg(any(test, feature = "test-utils"))]
+pub use mock::{MockEmbeddingBackend, MockEngine};
+
+/// Opening of the prompt answering a question about given content
+pub const QUESTION_ANSWER_PREFIX: &str = "Based on the following context, please answer the question. If the answer cannot be found in the context, please say so.\n\nContext:\n";
 
 /// AI engine managing all AI-related operations
 #[derive(Debug)]
@@ -33,6 +62,8 @@ pub struct AiEngine {
     embeddings: Arc<EmbeddingEngine>,
     /// RAG system
     rag: Arc<RagEngine>,
+    /// Per-document record of AI operations
+    audit: AiAuditLog,
     /// Configuration
     config: AiConfig,
 }
@@ -66,16 +97,119 @@ impl AiEngine {
             inference,
             embeddings,
             rag,
+            audit: AiAuditLog::new(config),
             config: config.clone(),
         })
     }
 
+    /// AI engine for safe mode, which loads no model of any kind
+    ///
+    /// Generation and embedding fail with an error, and semantic search falls
+    /// back to full-text matching.
+    pub fn new_disabled(config: &AiConfig) -> Self {
+        info!("AI engine disabled for safe mode");
+
+        let inference = Arc::new(RwLock::new(InferenceEngine::unloaded(config)));
+        let embeddings = Arc::new(EmbeddingEngine::disabled(config));
+        let rag = Arc::new(RagEngine::with_reranker(
+            Arc::clone(&inference),
+            Arc::clone(&embeddings),
+            config,
+            Arc::new(KeywordReranker::default()),
+        ));
+
+        Self {
+            inference,
+            embeddings,
+            rag,
+            audit: AiAuditLog::new(config),
+            config: config.clone(),
+        }
+    }
+
+    /// AI engine generating with `engine` instead of a model in `models_dir`
+    ///
+    /// Embeddings come from the backend selected by `config.embedding`.
+    pub fn with_engine(config: &AiConfig, engine: Arc<dyn LLMEngine>) -> CodexResult<Self> {
+        Ok(Self::with_backends(config, engine, embedding_backend::create_backend(config)?))
+    }
+
+    /// AI engine generating with `engine` and embedding with `embeddings`
+    ///
+    /// Nothing is loaded from `models_dir` except an optional cross-encoder,
+    /// so with the mocks of the `test-utils` feature the engine runs fully
+    /// offline.
+    pub fn with_backends(
+        config: &AiConfig,
+        engine: Arc<dyn LLMEngine>,
+        embeddings: Arc<dyn EmbeddingBackend>,
+    ) -> Self {
+        info!("Initializing AI engine with a {:?} engine", engine.engine_type());
+
+        let inference = Arc::new(RwLock::new(InferenceEngine::with_engine(config, engine)));
+        let embeddings = Arc::new(EmbeddingEngine::with_backend(embeddings, &config.device));
+        let rag = Arc::new(RagEngine::with_reranker(
+            Arc::clone(&inference),
+            Arc::clone(&embeddings),
+            config,
+            rerank::default_reranker(&config.models_dir),
+        ));
+
+        Self {
+            inference,
+            embeddings,
+            rag,
+            audit: AiAuditLog::new(config),
+            config: config.clone(),
+        }
+    }
+
+    /// AI engine loading its language model from `models_dir` and embedding
+    /// with `embeddings`
+    pub async fn with_embedding_backend(config: &AiConfig, embeddings: Arc<dyn EmbeddingBackend>) -> Result<Self> {
+        info!("Initializing AI engine with a custom embedding backend");
+
+        tokio::fs::create_dir_all(&config.models_dir).await?;
+        let inference = Arc::new(RwLock::new(InferenceEngine::new(config).await?));
+        let embeddings = Arc::new(EmbeddingEngine::with_backend(embeddings, &config.device));
+        let rag = Arc::new(RagEngine::new(Arc::clone(&inference), Arc::clone(&embeddings), config).await?);
+
+        Ok(Self {
+            inference,
+            embeddings,
+            rag,
+            audit: AiAuditLog::new(config),
+            config: config.clone(),
+        })
+    }
+
+    /// Whether the engine was started in safe mode
+    pub fn is_disabled(&self) -> bool {
+        self.embeddings.is_disabled()
+    }
+
     /// Generate text completion using the loaded model
     pub async fn generate_text(&self, prompt: &str) -> CodexResult<String> {
         let inference = self.inference.read().await;
         inference.generate(prompt, &self.config).await
     }
 
+    /// Generate text completion, reporting prompt truncation and token usage
+    pub async fn generate_text_with_details(&self, prompt: &str) -> CodexResult<GenerationOutput> {
+        let inference = self.inference.read().await;
+        inference.generate_with_details(prompt, &self.config).await
+    }
+
+    /// Generate at most `max_tokens` tokens, bypassing the response cache
+    pub async fn generate_text_limited(&self, prompt: &str, max_tokens: usize) -> CodexResult<String> {
+        let mut config = self.config.clone();
+        config.max_tokens = max_tokens;
+        config.enable_caching = false;
+
+        let inference = self.inference.read().await;
+        inference.generate(prompt, &config).await
+    }
+
     /// Simple inference API - generate response for a given prompt
     /// Optimized for <1s response time with default settings
     pub async fn infer(&self, prompt: &str) -> CodexResult<String> {
@@ -108,11 +242,33 @@ impl AiEngine {
         inference.generate_stream(prompt, &self.config, callback).await
     }
 
+    /// Generate text with streaming, reporting token counts as they grow
+    ///
+    /// See [`InferenceEngine::generate_stream_with_details`].
+    pub async fn generate_text_stream_with_details(
+        &self,
+        prompt: &str,
+        callback: impl Fn(StreamChunk) + Send + Sync + 'static,
+    ) -> CodexResult<GenerationOutput> {
+        let inference = self.inference.read().await;
+        inference.generate_stream_with_details(prompt, &self.config, callback).await
+    }
+
     /// Generate embedding for text
     pub async fn generate_embedding(&self, text: &str) -> CodexResult<Vec<f32>> {
         self.embeddings.generate_embedding(text).await
     }
 
+    /// Cosine similarity of two texts under the embedding model
+    ///
+    /// Neither text is stored; long texts are averaged over their parts,
+    /// see [`EmbeddingEngine::embed_text`].
+    pub async fn compare_texts(&self, a: &str, b: &str) -> CodexResult<f32> {
+        let a = self.embeddings.embed_text(a).await?;
+        let b = self.embeddings.embed_text(b).await?;
+        Ok(self.embeddings.cosine_similarity(&a.vector, &b.vector))
+    }
+
     /// Generate embeddings for multiple texts (batch processing)
     pub async fn generate_embeddings_batch(&self, texts: &[String]) -> CodexResult<Vec<Vec<f32>>> {
         self.embeddings.generate_embeddings_batch(texts).await
@@ -120,7 +276,128 @@ impl AiEngine {
 
     /// Perform RAG query (retrieval-augmented generation)
     pub async fn rag_query(&self, query: &str, context_limit: usize) -> CodexResult<RagResponse> {
-        self.rag.query(query, context_limit).await
+        let start = std::time::Instant::now();
+        let response = self.rag.query(query, context_limit).await?;
+        self.audit.record_citations(query, &response.answer, &response.sources, start.elapsed()).await;
+        Ok(response)
+    }
+
+    /// Perform a RAG query, streaming the answer to `callback`
+    ///
+    /// See [`RagEngine::query_stream`].
+    pub async fn rag_query_stream(
+        &self,
+        query: &str,
+        context_limit: usize,
+        callback: impl Fn(StreamChunk) + Send + Sync + 'static,
+    ) -> CodexResult<RagResponse> {
+        let start = std::time::Instant::now();
+        let response = self.rag.query_stream(query, context_limit, callback).await?;
+        self.audit.record_citations(query, &response.answer, &response.sources, start.elapsed()).await;
+        Ok(response)
+    }
+
+    /// Perform a RAG query that follows up on a recorded conversation
+    pub async fn rag_query_in_conversation(
+        &self,
+        query: &str,
+        context_limit: usize,
+        conversation_id: uuid::Uuid,
+    ) -> CodexResult<RagResponse> {
+        let start = std::time::Instant::now();
+        let response = self.rag.query_in_conversation(query, context_limit, conversation_id).await?;
+        self.audit.record_citations(query, &response.answer, &response.sources, start.elapsed()).await;
+        Ok(response)
+    }
+
+    /// Answer a question from 2–5 specific documents, attributing the answer to each
+    pub async fn multi_document_query(&self, question: &str, document_ids: &[uuid::Uuid]) -> CodexResult<RagResponse> {
+        let start = std::time::Instant::now();
+        let response = self.rag.multi_document_query(question, document_ids).await?;
+        self.audit.record_citations(question, &response.answer, &response.sources, start.elapsed()).await;
+        Ok(response)
+    }
+
+    /// Answer a question about a reader selection, streaming the answer to `callback`
+    ///
+    /// See [`RagEngine::answer_about_selection`].
+    pub async fn answer_about_selection(
+        &self,
+        document_id: uuid::Uuid,
+        selection_text: &str,
+        selection_range: Option<std::ops::Range<usize>>,
+        question: &str,
+        conversation_id: Option<uuid::Uuid>,
+        callback: impl Fn(String) + Send + Sync + 'static,
+    ) -> CodexResult<String> {
+        let start = std::time::Instant::now();
+        let answer = self
+            .rag
+            .answer_about_selection(document_id, selection_text, selection_range, question, conversation_id, callback)
+            .await?;
+        let prompt = format!("{}\n\n{}", selection_text, question);
+        self.audit
+            .record(&document_id.to_string(), AiOperation::RagCitation, &prompt, &answer, start.elapsed())
+            .await;
+        Ok(answer)
+    }
+
+    /// Chat about one document, streaming the answer to `callback`
+    ///
+    /// See [`RagEngine::document_chat`].
+    pub async fn document_chat(
+        &self,
+        document_id: uuid::Uuid,
+        message: &str,
+        conversation_id: Option<uuid::Uuid>,
+        strict_grounding: bool,
+        callback: impl Fn(String) + Send + Sync + 'static,
+    ) -> CodexResult<DocumentChatResponse> {
+        let start = std::time::Instant::now();
+        let response = self
+            .rag
+            .document_chat(document_id, message, conversation_id, strict_grounding, callback)
+            .await?;
+        self.audit.record_citations(message, &response.answer, &response.sources, start.elapsed()).await;
+        Ok(response)
+    }
+
+    /// Messages of a stored conversation, oldest first
+    pub async fn conversation_history(
+        &self,
+        conversation_id: uuid::Uuid,
+        limit: i64,
+    ) -> CodexResult<Vec<crate::db::models::ConversationMessage>> {
+        self.rag.conversation_history(conversation_id, limit).await
+    }
+
+    /// Give the RAG engine access to stored documents and embeddings, and
+    /// start recording the AI audit trail
+    pub fn set_database(&self, db: Arc<crate::db::DatabaseManager>) {
+        self.audit.set_database(Arc::clone(&db));
+        self.rag.set_database(db);
+    }
+
+    /// Run an AI operation on a document's content, recording it in the audit trail
+    ///
+    /// `input` is the text the operation was given; failed operations are
+    /// not recorded.
+    pub async fn audited<T, F>(&self, document_id: &str, operation: AiOperation, input: &str, run: F) -> CodexResult<T>
+    where
+        T: AuditOutput,
+        F: std::future::Future<Output = CodexResult<T>>,
+    {
+        let start = std::time::Instant::now();
+        let output = run.await?;
+        self.audit
+            .record(document_id, operation, input, &output.audit_text(), start.elapsed())
+            .await;
+        Ok(output)
+    }
+
+    /// Replace the re-ranking stage used for RAG retrieval
+    pub fn set_reranker(&self, reranker: Arc<dyn Reranker>) {
+        self.rag.set_reranker(reranker);
     }
 
     /// Summarize text content
@@ -134,6 +411,88 @@ impl AiEngine {
         self.generate_text(&prompt).await
     }
 
+    /// Whether a text is too long to summarize in a single prompt
+    pub fn exceeds_context(&self, text: &str) -> bool {
+        summarize::estimate_tokens(text) > self.summary_window_tokens()
+    }
+
+    /// Summarize text of any length with map-reduce
+    ///
+    /// The text is split into chunks that fit the context window, each chunk
+    /// is summarized, and the partial summaries are summarized again (over as
+    /// many levels as needed). `progress` is called after every step.
+    pub async fn summarize_long(
+        &self,
+        text: &str,
+        max_length: Option<usize>,
+        progress: impl Fn(SummaryProgress) + Send + Sync,
+    ) -> CodexResult<String> {
+        use futures::stream::{self, StreamExt, TryStreamExt};
+
+        let max_len = max_length.unwrap_or(200);
+        let window = self.summary_window_tokens();
+
+        if !self.exceeds_context(text) {
+            let summary = self.summarize(text, Some(max_len)).await?;
+            progress(SummaryProgress { stage: SummaryStage::Map, level: 0, completed: 1, total: 1 });
+            return Ok(summary);
+        }
+
+        let chunks = summarize::split_into_windows(text, window);
+        let total = chunks.len();
+        info!("Summarizing long text in {} chunks", total);
+
+        let completed = std::sync::atomic::AtomicUsize::new(0);
+        let mut summaries: Vec<String> = stream::iter(chunks.into_iter().enumerate())
+            .map(|(i, chunk)| {
+                let progress = &progress;
+                let completed = &completed;
+                async move {
+                    let summary = self.generate_text(&summarize::chunk_prompt(&chunk, i, total)).await?;
+                    let done = completed.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
+                    progress(SummaryProgress { stage: SummaryStage::Map, level: 0, completed: done, total });
+                    Ok::<_, crate::CodexError>(summary)
+                }
+            })
+            .buffered(summarize::MAP_CONCURRENCY)
+            .try_collect()
+            .await?;
+
+        let mut level = 1;
+        loop {
+            let combined = summaries.join("\n\n");
+            if summaries.len() == 1 || !self.exceeds_context(&combined) {
+                let summary = self.generate_text(&summarize::reduce_prompt(&combined, max_len)).await?;
+                progress(SummaryProgress { stage: SummaryStage::Reduce, level, completed: 1, total: 1 });
+                return Ok(summary);
+            }
+
+            // Partial summaries still do not fit: reduce them in groups
+            let groups = summarize::split_into_windows(&combined, window);
+            let total = groups.len();
+            let mut reduced = Vec::with_capacity(total);
+            for (i, group) in groups.iter().enumerate() {
+                let prompt = summarize::reduce_prompt(group, summarize::CHUNK_SUMMARY_WORDS);
+                reduced.push(self.generate_text(&prompt).await?);
+                progress(SummaryProgress { stage: SummaryStage::Reduce, level, completed: i + 1, total });
+            }
+
+            if reduced.len() >= summaries.len() {
+                return Err(crate::CodexError::ai_inference("Summaries did not shrink while reducing"));
+            }
+            summaries = reduced;
+            level += 1;
+        }
+    }
+
+    /// Tokens of source text that fit in one summarization prompt
+    fn summary_window_tokens(&self) -> usize {
+        self.config
+            .max_context_length
+            .saturating_sub(self.config.max_tokens + summarize::PROMPT_OVERHEAD_TOKENS)
+            .max(summarize::PROMPT_OVERHEAD_TOKENS)
+    }
+
     /// Extract key points from text
     pub async fn extract_key_points(&self, text: &str, num_points: Option<usize>) -> CodexResult<Vec<String>> {
         let num = num_points.unwrap_or(5);
@@ -167,31 +526,19 @@ impl AiEngine {
 
     /// Answer a question about specific content
     pub async fn answer_question(&self, question: &str, context: &str) -> CodexResult<String> {
-        let prompt = format!(
-            "Based on the following context, please answer the question. If the answer cannot be found in the context, please say so.\n\nContext:\n{}\n\nQuestion: {}\n\nAnswer:",
-            context, question
-        );
+        let prompt = format!("{}{}\n\nQuestion: {}\n\nAnswer:", QUESTION_ANSWER_PREFIX, context, question);
         
         self.generate_text(&prompt).await
     }
 
     /// Generate tags for content
     pub async fn generate_tags(&self, content: &str, max_tags: Option<usize>) -> CodexResult<Vec<String>> {
-        let max = max_tags.unwrap_or(10);
-        let prompt = format!(
-            "Generate up to {} relevant tags for the following content. Return only the tags, separated by commas:\n\n{}",
-            max, content
-        );
-        
-        let response = self.generate_text(&prompt).await?;
-        
-        let tags: Vec<String> = response
-            .split(',')
-            .map(|tag| tag.trim().to_lowercase())
-            .filter(|tag| !tag.is_empty())
-            .collect();
+        structured::generate_tags(self, content, max_tags.unwrap_or(10)).await
+    }
 
-        Ok(tags)
+    /// Extract the named entities of content
+    pub async fn extract_entities(&self, content: &str) -> CodexResult<Vec<Entity>> {
+        structured::extract_entities(self, content).await
     }
 
     /// Categorize content
@@ -218,30 +565,7 @@ impl AiEngine {
 
     /// Assess content difficulty level (1-5 scale)
     pub async fn assess_difficulty(&self, content: &str) -> CodexResult<i32> {
-        let prompt = format!(
-            "Rate the difficulty level of the following content on a scale of 1-5, where:\n1 = Beginner (basic concepts)\n2 = Elementary (some background needed)\n3 = Intermediate (moderate expertise required)\n4 = Advanced (significant expertise required)\n5 = Expert (deep specialization required)\n\nContent:\n{}\n\nDifficulty level (just the number):",
-            content
-        );
-        
-        let response = self.generate_text(&prompt).await?;
-        
-        // Extract number from response
-        let difficulty = response
-            .chars()
-            .find(|c| c.is_ascii_digit())
-            .and_then(|c| c.to_digit(10))
-            .map(|d| d as i32)
-            .unwrap_or(3); // Default to intermediate
-
-        Ok(difficulty.clamp(1, 5))
-    }
-
-    /// Estimate reading time in minutes
-    pub async fn estimate_reading_time(&self, content: &str) -> CodexResult<i32> {
-        // Simple word-based calculation (average 200 words per minute)
-        let word_count = content.split_whitespace().count();
-        let reading_time = ((word_count as f64 / 200.0).ceil() as i32).max(1);
-        Ok(reading_time)
+        structured::assess_difficulty(self, content).await
     }
 
     /// Check if AI engine is healthy and responsive
@@ -255,6 +579,12 @@ impl AiEngine {
         }
     }
 
+    /// Process, system and GPU memory of the inference engine
+    pub async fn get_system_metrics(&self) -> CodexResult<inference::SystemMetricsSnapshot> {
+        let inference = self.inference.read().await;
+        inference.get_system_metrics().await
+    }
+
     /// Get AI engine statistics
     pub async fn get_stats(&self) -> CodexResult<AiStats> {
         let inference = self.inference.read().await;
@@ -275,6 +605,34 @@ impl AiEngine {
         Ok(())
     }
 
+    /// How a model would be split between GPU and CPU under the configured `gpu_layers`,
+    /// before loading it; the primary model when `model_path` is `None`
+    pub async fn get_model_load_plan(&self, model_path: Option<&str>) -> CodexResult<ModelLoadPlan> {
+        let inference = self.inference.read().await;
+        inference.get_model_load_plan(model_path.unwrap_or(&self.config.primary_model))
+    }
+
+    /// Limits on concurrent generations and their length, set from the power policy
+    pub async fn generation_throttle(&self) -> Arc<crate::power::GenerationThrottle> {
+        self.inference.read().await.generation_throttle()
+    }
+
+    /// Warm up the language model so the first answer is not slower than later ones
+    pub async fn warm_up_model(&self) -> CodexResult<()> {
+        let inference = self.inference.read().await;
+        inference.warm_up(&self.config).await
+    }
+
+    /// Whether the language model has been warmed up, for a "model warming up" indicator
+    pub async fn model_warm_up_state(&self) -> ModelWarmUpState {
+        self.inference.read().await.warm_up_state()
+    }
+
+    /// Whether a language model and its tokenizer are loaded
+    pub async fn is_model_ready(&self) -> bool {
+        self.inference.read().await.is_ready()
+    }
+
     /// Get reference to embeddings engine
     pub fn get_embeddings(&self) -> &Arc<EmbeddingEngine> {
         &self.embeddings
@@ -295,6 +653,13 @@ impl AiEngine {
     }
 }
 
+#[async_trait::async_trait]
+impl TextGenerator for AiEngine {
+    async fn generate(&self, prompt: &str) -> CodexResult<String> {
+        self.generate_text(prompt).await
+    }
+}
+
 /// AI engine statistics
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct AiStats {
@@ -305,6 +670,31 @@ pub struct AiStats {
     pub average_inference_time_ms: f64,
     pub cache_hit_rate: f64,
     pub uptime_seconds: u64,
+    /// Time until the first token of uncached generations
+    #[serde(default)]
+    pub time_to_first_token_ms: LatencyPercentiles,
+    /// Prompt tokens of chat turns reused from the previous turn or run through the model
+    #[serde(default)]
+    pub prompt_prefix_cache: PrefixCacheStats,
+    /// Draft acceptance and generation speed
+    #[serde(default)]
+    pub speculative: SpeculativeStats,
+}
+
+/// Median and 95th percentile of a latency, in milliseconds
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct LatencyPercentiles {
+    pub p50: f64,
+    pub p95: f64,
+}
+
+impl LatencyPercentiles {
+    pub fn from_histogram(histogram: &metrics::Histogram) -> Self {
+        Self {
+            p50: histogram.quantile(0.5).as_secs_f64() * 1000.0,
+            p95: histogram.quantile(0.95).as_secs_f64() * 1000.0,
+        }
+    }
 }
 
 
@@ -316,8 +706,7 @@ mod tests {
     #[tokio::test]
     async fn test_ai_engine_creation() {
         let temp_dir = tempdir().unwrap();
-        let mut config = AiConfig::default();
-        config.models_dir = temp_dir.path().to_path_buf();
+        let config = AiConfig { models_dir: temp_dir.path().to_path_buf(), ..Default::default() };
         
         // This test may fail without actual model files
         // In real implementation, we'd need to download/provide test models
@@ -330,4 +719,44 @@ mod tests {
             Err(e) => println!("Expected error without model files: {}", e),
         }
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_ai_engine_with_mock_backends() {
+        let temp_dir = tempdir().unwrap();
+        let config = AiConfig { models_dir: temp_dir.path().to_path_buf(), enable_caching: false, ..Default::default() };
+
+        let mock = Arc::new(MockEngine::new().respond("(?i)capital of france", "Paris is the capital of France."));
+        let engine = AiEngine::with_backends(&config, mock.clone(), Arc::new(MockEmbeddingBackend::default()));
+        assert!(engine.is_model_ready().await);
+        engine.warm_up_model().await.unwrap();
+        assert!(matches!(engine.model_warm_up_state().await, ModelWarmUpState::Ready { .. }));
+
+        let answer = engine.generate_text("What is the capital of France?").await.unwrap();
+        assert_eq!(answer, "Paris is the capital of France.");
+        let streamed = engine.generate_text_stream("What is the capital of France?", |_| {}).await.unwrap();
+        assert_eq!(streamed, answer);
+
+        let chunks = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = Arc::clone(&chunks);
+        let output = engine
+            .generate_text_stream_with_details("What is the capital of France?", move |chunk| sink.lock().unwrap().push(chunk))
+            .await
+            .unwrap();
+        let chunks = std::mem::take(&mut *chunks.lock().unwrap());
+        let counts: Vec<usize> = chunks.iter().map(|chunk| chunk.completion_tokens).collect();
+        assert_eq!(counts, (1..=6).collect::<Vec<_>>());
+        assert_eq!(chunks.last().unwrap().text, output.text);
+        assert_eq!(output.usage.completion_tokens, 6);
+        assert_eq!(output.usage.prompt_tokens, 6);
+        assert!(chunks.iter().all(|chunk| chunk.elapsed_ms <= output.usage.elapsed_ms));
+
+        mock.fail_next("out of memory");
+        assert!(engine.generate_text("Hello").await.is_err());
+        assert_eq!(mock.call_count(), 4);
+
+        let close = engine.compare_texts("offline vault search", "search the offline vault").await.unwrap();
+        let far = engine.compare_texts("offline vault search", "tomato soup recipe").await.unwrap();
+        assert!(close > far);
+        assert_eq!(engine.get_stats().await.unwrap().total_inferences, 3);
+    }
+}
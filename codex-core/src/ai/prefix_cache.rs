@@ -0,0 +1,307 @@
+//! Reuse of model state for the shared start of consecutive prompts
+//!
+//! Each chat turn sends the whole conversation again, so without reuse the
+//! forward pass over the history grows with every turn. After a turn the
+//! model state (the KV cache) is kept with the tokens it covers, keyed by
+//! conversation. The next turn's prompt is compared token by token and only
+//! the part after the shared prefix runs through the model. Entries are
+//! evicted least recently used first once their estimated size exceeds the
+//! budget, which the inference engine's memory cleanup also shrinks.
+
+use candle_core::Tensor;
+use candle_transformers::generation::LogitsProcessor;
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+
+use crate::{CodexError, CodexResult};
+
+/// Model state after a sequence of tokens
+pub trait PrefixState: Clone + Send {
+    /// Approximate memory held, counted against the cache budget
+    fn size_bytes(&self) -> usize;
+
+    /// Keep the state of the first `len` tokens only
+    ///
+    /// Returns false when the state cannot be cut back; the cached prefix is
+    /// then only reused when the new prompt extends all of it.
+    fn truncate(&mut self, len: usize) -> bool;
+}
+
+/// A model that runs token by token over a [`PrefixState`]
+pub trait TokenModel {
+    type State: PrefixState;
+
+    /// State before any token
+    fn new_state(&self) -> CodexResult<Self::State>;
+
+    /// Feed `tokens`, the first at `position`, and return the logits of the next token
+    fn forward(&self, tokens: &[u32], position: usize, state: &mut Self::State) -> CodexResult<Tensor>;
+
+    /// Feed `tokens` and return the logits after each of them
+    ///
+    /// Runs the tokens one at a time unless the model can score them in one pass.
+    fn forward_all(&self, tokens: &[u32], position: usize, state: &mut Self::State) -> CodexResult<Vec<Tensor>> {
+        tokens
+            .iter()
+            .enumerate()
+            .map(|(i, token)| self.forward(&[*token], position + i, state))
+            .collect()
+    }
+}
+
+/// Prompt tokens reused from the cache and run through the model
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrefixCacheStats {
+    pub reused_tokens: u64,
+    pub recomputed_tokens: u64,
+    pub entries: usize,
+    pub size_bytes: usize,
+}
+
+#[derive(Debug)]
+struct CachedPrefix<S> {
+    tokens: Vec<u32>,
+    state: S,
+    size_bytes: usize,
+}
+
+/// Model states by conversation, bounded by their estimated size
+#[derive(Debug)]
+pub struct PromptPrefixCache<S> {
+    entries: LruCache<String, CachedPrefix<S>>,
+    max_bytes: usize,
+    size_bytes: usize,
+    reused_tokens: u64,
+    recomputed_tokens: u64,
+}
+
+impl<S: PrefixState> PromptPrefixCache<S> {
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            entries: LruCache::unbounded(),
+            max_bytes,
+            size_bytes: 0,
+            reused_tokens: 0,
+            recomputed_tokens: 0,
+        }
+    }
+
+    /// Take the state for `key` covering the longest reusable prefix of `tokens`
+    ///
+    /// Returns the state and the number of tokens it covers. At least one
+    /// token is left to run, as its logits start the generation.
+    pub fn take(&mut self, key: &str, tokens: &[u32]) -> Option<(S, usize)> {
+        let reused = self.entries.pop(key).and_then(|entry| {
+            self.size_bytes -= entry.size_bytes;
+            let shared = common_prefix(&entry.tokens, tokens).min(tokens.len().saturating_sub(1));
+            let mut state = entry.state;
+            let usable = shared > 0 && (shared == entry.tokens.len() || state.truncate(shared));
+            usable.then_some((state, shared))
+        });
+
+        let covered = reused.as_ref().map_or(0, |(_, len)| *len);
+        self.reused_tokens += covered as u64;
+        self.recomputed_tokens += (tokens.len() - covered) as u64;
+        reused
+    }
+
+    /// Keep `state`, which covers `tokens`, for the next prompt of `key`
+    pub fn store(&mut self, key: &str, tokens: Vec<u32>, state: S) {
+        let size_bytes = state.size_bytes();
+        if let Some(old) = self.entries.pop(key) {
+            self.size_bytes -= old.size_bytes;
+        }
+        if size_bytes > self.max_bytes {
+            return;
+        }
+
+        self.entries.put(key.to_string(), CachedPrefix { tokens, state, size_bytes });
+        self.size_bytes += size_bytes;
+        self.shrink_to(self.max_bytes);
+    }
+
+    /// Evict least recently used states until at most `max_bytes` are held; returns the bytes freed
+    pub fn shrink_to(&mut self, max_bytes: usize) -> usize {
+        let before = self.size_bytes;
+        while self.size_bytes > max_bytes {
+            match self.entries.pop_lru() {
+                Some((_, entry)) => self.size_bytes -= entry.size_bytes,
+                None => break,
+            }
+        }
+        before - self.size_bytes
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.size_bytes = 0;
+    }
+
+    pub fn stats(&self) -> PrefixCacheStats {
+        PrefixCacheStats {
+            reused_tokens: self.reused_tokens,
+            recomputed_tokens: self.recomputed_tokens,
+            entries: self.entries.len(),
+            size_bytes: self.size_bytes,
+        }
+    }
+}
+
+/// Result of a generation, with the state to cache for the next turn
+#[derive(Debug)]
+pub struct Generation<S> {
+    /// Generated tokens, without the end-of-sequence token
+    pub tokens: Vec<u32>,
+    /// Model state after `processed`
+    pub state: S,
+    /// Prompt and generated tokens the state covers
+    pub processed: Vec<u32>,
+}
+
+/// Generate up to `max_new_tokens` after `prompt`
+///
+/// `reused` is a state covering the first tokens of `prompt`, from
+/// [`PromptPrefixCache::take`]; only the rest of the prompt runs through the
+/// model. Generation stops at any of `eos_tokens`; `on_token` is called
+/// with the tokens generated so far.
+pub fn generate<M: TokenModel>(
+    model: &M,
+    prompt: &[u32],
+    reused: Option<(M::State, usize)>,
+    sampler: &mut LogitsProcessor,
+    max_new_tokens: usize,
+    eos_tokens: &[u32],
+    mut on_token: impl FnMut(&[u32]),
+) -> CodexResult<Generation<M::State>> {
+    if prompt.is_empty() {
+        return Err(CodexError::validation("Cannot generate from an empty prompt"));
+    }
+
+    let (mut state, start) = match reused {
+        Some((state, len)) if len < prompt.len() => (state, len),
+        _ => (model.new_state()?, 0),
+    };
+    let mut logits = model.forward(&prompt[start..], start, &mut state)?;
+    let mut processed = prompt.to_vec();
+    let mut tokens = Vec::new();
+
+    while tokens.len() < max_new_tokens {
+        let next = sampler
+            .sample(&logits)
+            .map_err(|e| CodexError::ai_inference(format!("Sampling failed: {}", e)))?;
+        if eos_tokens.contains(&next) {
+            break;
+        }
+        tokens.push(next);
+        on_token(&tokens);
+        if tokens.len() == max_new_tokens {
+            break;
+        }
+
+        logits = model.forward(&[next], processed.len(), &mut state)?;
+        processed.push(next);
+    }
+
+    Ok(Generation { tokens, state, processed })
+}
+
+/// Number of leading tokens `a` and `b` share
+fn common_prefix(a: &[u32], b: &[u32]) -> usize {
+    a.iter().zip(b).take_while(|(x, y)| x == y).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candle_core::Device;
+
+    const VOCAB: u32 = 50;
+
+    /// Deterministic model whose logits depend on every token seen so far
+    struct ToyModel;
+
+    #[derive(Clone)]
+    struct ToyState {
+        seen: Vec<u32>,
+    }
+
+    impl PrefixState for ToyState {
+        fn size_bytes(&self) -> usize {
+            self.seen.len() * 4
+        }
+
+        fn truncate(&mut self, len: usize) -> bool {
+            self.seen.truncate(len);
+            true
+        }
+    }
+
+    impl TokenModel for ToyModel {
+        type State = ToyState;
+
+        fn new_state(&self) -> CodexResult<ToyState> {
+            Ok(ToyState { seen: Vec::new() })
+        }
+
+        fn forward(&self, tokens: &[u32], position: usize, state: &mut ToyState) -> CodexResult<Tensor> {
+            assert_eq!(position, state.seen.len());
+            state.seen.extend_from_slice(tokens);
+            let hash = state.seen.iter().fold(7u32, |h, t| h.wrapping_mul(31).wrapping_add(*t));
+            let logits: Vec<f32> = (0..VOCAB).map(|v| ((hash ^ v.wrapping_mul(2654435761)) % 1000) as f32).collect();
+            Ok(Tensor::from_vec(logits, VOCAB as usize, &Device::Cpu).unwrap())
+        }
+    }
+
+    fn greedy() -> LogitsProcessor {
+        LogitsProcessor::new(0, None, None)
+    }
+
+    /// Two chat turns, the second prompt extending the first turn and its answer
+    fn chat(cache: Option<&mut PromptPrefixCache<ToyState>>) -> (Vec<u32>, Vec<u32>) {
+        let first_prompt = vec![1, 2, 3, 4, 5];
+        let mut cache = cache;
+        let reused = cache.as_deref_mut().and_then(|c| c.take("conversation", &first_prompt));
+        let first = generate(&ToyModel, &first_prompt, reused, &mut greedy(), 4, &[], |_| {}).unwrap();
+
+        let mut second_prompt = first_prompt.clone();
+        second_prompt.extend_from_slice(&first.tokens);
+        second_prompt.extend_from_slice(&[9, 8]);
+        if let Some(cache) = cache.as_deref_mut() {
+            cache.store("conversation", first.processed, first.state);
+        }
+
+        let reused = cache.and_then(|c| c.take("conversation", &second_prompt));
+        let second = generate(&ToyModel, &second_prompt, reused, &mut greedy(), 4, &[], |_| {}).unwrap();
+        (first.tokens, second.tokens)
+    }
+
+    #[test]
+    fn test_reuse_gives_identical_greedy_output() {
+        let mut cache = PromptPrefixCache::new(1 << 20);
+        assert_eq!(chat(Some(&mut cache)), chat(None));
+
+        // The second turn ran only its two new tokens and the last answer token
+        let stats = cache.stats();
+        assert_eq!(stats.reused_tokens, 8);
+        assert_eq!(stats.recomputed_tokens, 5 + 3);
+    }
+
+    #[test]
+    fn test_diverging_prompt_and_budget() {
+        let state = |seen: Vec<u32>| ToyState { seen };
+        let mut cache = PromptPrefixCache::new(40);
+        cache.store("a", vec![1, 2, 3, 4], state(vec![1, 2, 3, 4]));
+
+        // Shared prefix of two tokens; the state is cut back to it
+        let (reused, len) = cache.take("a", &[1, 2, 7, 7]).unwrap();
+        assert_eq!((reused.seen, len), (vec![1, 2], 2));
+        assert!(cache.take("a", &[1, 2]).is_none());
+
+        // Least recently used entries go first once over budget
+        cache.store("a", vec![1; 6], state(vec![1; 6]));
+        cache.store("b", vec![2; 6], state(vec![2; 6]));
+        assert_eq!(cache.stats().entries, 1);
+        assert!(cache.take("a", &[1; 7]).is_none());
+        assert_eq!(cache.shrink_to(0), 24);
+    }
+}
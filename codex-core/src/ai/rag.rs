@@ -2,27 +2,56 @@
 
 use std::sync::Arc;
 use tokio::sync::RwLock;
+use uuid::Uuid;
 use anyhow::Result;
-use tracing::{info, debug};
+use tracing::{info, debug, instrument};
 
-use crate::{CodexError, CodexResult};
-use crate::config::AiConfig;
+use crate::{metrics, CodexError, CodexResult};
+use crate::config::{AiConfig, RagRetrievalConfig};
 use crate::db::DatabaseManager;
+use crate::db::models::{Conversation, ConversationMessage, Document};
 use super::{InferenceEngine, EmbeddingEngine};
+use super::inference::{GenerationOutput, GenerationUsage, StreamChunk};
+use super::confidence::{self, ConfidenceSignals};
+use super::rerank::{self, Reranker};
+use super::rewrite::{self, RewrittenQuery};
+use super::summarize;
+
+/// Fewest documents a multi-document query compares
+pub const MIN_QUERY_DOCUMENTS: usize = 2;
+/// Most documents a multi-document query compares
+pub const MAX_QUERY_DOCUMENTS: usize = 5;
+/// Share of the context budget every document gets regardless of relevance
+const MIN_DOCUMENT_SHARE: f32 = 0.1;
+/// Opening of every RAG answer prompt
+pub const RAG_ANSWER_PREFIX: &str = "Based on the following context, please provide a comprehensive and accurate answer to the question. If the context doesn't contain enough information to answer the question, please say so.\n\nContext:\n";
+/// Opening of every document chat prompt, followed by the title
+pub const DOCUMENT_CHAT_PREFIX: &str = "You are discussing the document \"";
+/// Answer given by a strictly grounded document chat when the document does not cover the question
+pub const NOT_FOUND_ANSWER: &str = "Not found in this document.";
+/// Previous messages included as history in a document chat turn
+const CHAT_HISTORY_MESSAGES: i64 = 6;
+/// Bytes on each side of a reader selection searched for surrounding chunks
+const SELECTION_CONTEXT_BYTES: usize = 1500;
+/// Characters from each end of a highlight that mark a chunk it spills into
+const HIGHLIGHT_EDGE_CHARS: usize = 40;
 
 /// RAG engine for contextual AI responses
 pub struct RagEngine {
     inference: Arc<RwLock<InferenceEngine>>,
     embeddings: Arc<EmbeddingEngine>,
-    db: Option<Arc<DatabaseManager>>,
+    db: std::sync::RwLock<Option<Arc<DatabaseManager>>>,
     config: RagConfig,
+    generation: AiConfig,
+    reranker: std::sync::RwLock<Arc<dyn Reranker>>,
 }
 
 impl std::fmt::Debug for RagEngine {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("RagEngine")
             .field("config", &self.config)
-            .field("has_db", &self.db.is_some())
+            .field("has_db", &self.db.read().unwrap().is_some())
+            .field("reranker", &self.reranker.read().unwrap().name())
             .finish()
     }
 }
@@ -35,6 +64,12 @@ pub struct RagConfig {
     pub context_window_size: usize,
     pub enable_reranking: bool,
     pub chunk_overlap_ratio: f32,
+    /// Confidence below which an answer is flagged and hedged
+    pub low_confidence_threshold: f32,
+    /// Ask the model whether the context supports its answer when scoring confidence
+    pub enable_self_check: bool,
+    /// Rewrite follow-up questions into standalone queries and retrieve with expansions
+    pub enable_query_rewriting: bool,
 }
 
 impl Default for RagConfig {
@@ -45,17 +80,34 @@ impl Default for RagConfig {
             context_window_size: 2048,
             enable_reranking: true,
             chunk_overlap_ratio: 0.1,
+            low_confidence_threshold: 0.4,
+            enable_self_check: true,
+            enable_query_rewriting: true,
         }
     }
 }
 
 /// Response from RAG query
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[non_exhaustive]
 pub struct RagResponse {
     pub answer: String,
     pub sources: Vec<RagSource>,
+    /// Calibrated confidence (0–1), see [`RagEngine::assess_answer`]
     pub confidence: f32,
+    /// Whether the confidence fell below the threshold (the answer is then hedged)
+    #[serde(default)]
+    pub low_confidence: bool,
     pub context_used: usize,
+    /// Re-ranking stage(s) applied to the sources, if any
+    #[serde(default)]
+    pub reranker: Option<String>,
+    /// Standalone query retrieved with, when it differs from the question
+    #[serde(default)]
+    pub rewritten_query: Option<String>,
+    /// Token counts and timing of the answer's generation
+    #[serde(default)]
+    pub usage: Option<GenerationUsage>,
 }
 
 /// Source information for RAG response
@@ -65,6 +117,21 @@ pub struct RagSource {
     pub title: String,
     pub snippet: String,
     pub relevance_score: f32,
+    /// Position (`hh:mm:ss`) of the snippet within an audio transcript
+    #[serde(default)]
+    pub timestamp: Option<String>,
+}
+
+/// Answer from a per-document chat turn
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct DocumentChatResponse {
+    /// Conversation the turn was recorded in (newly created when none was given)
+    pub conversation_id: uuid::Uuid,
+    pub answer: String,
+    /// Document excerpts the answer was based on
+    pub sources: Vec<RagSource>,
+    /// Whether any excerpt met the similarity threshold
+    pub grounded: bool,
 }
 
 impl RagEngine {
@@ -72,193 +139,302 @@ impl RagEngine {
     pub async fn new(
         inference: Arc<RwLock<InferenceEngine>>,
         embeddings: Arc<EmbeddingEngine>,
-        _config: &AiConfig,
+        config: &AiConfig,
     ) -> Result<Self> {
         info!("Initializing RAG engine");
 
-        let rag_config = RagConfig::default();
+        let reranker = rerank::default_reranker(&config.models_dir);
+        Ok(Self::with_reranker(inference, embeddings, config, reranker))
+    }
 
-        Ok(Self {
+    /// RAG engine re-ranking with `reranker` instead of the models on disk
+    pub fn with_reranker(
+        inference: Arc<RwLock<InferenceEngine>>,
+        embeddings: Arc<EmbeddingEngine>,
+        config: &AiConfig,
+        reranker: Arc<dyn Reranker>,
+    ) -> Self {
+        Self {
             inference,
             embeddings,
-            db: None,
-            config: rag_config,
-        })
+            db: std::sync::RwLock::new(None),
+            config: RagConfig {
+                low_confidence_threshold: config.rag.low_confidence_threshold,
+                enable_self_check: config.rag.enable_self_check,
+                enable_query_rewriting: config.rag.enable_query_rewriting,
+                ..RagConfig::default()
+            },
+            generation: config.clone(),
+            reranker: std::sync::RwLock::new(reranker),
+        }
     }
 
     /// Set the database manager for document retrieval
-    pub fn set_database(&mut self, db: Arc<DatabaseManager>) {
-        self.db = Some(db);
+    pub fn set_database(&self, db: Arc<DatabaseManager>) {
+        *self.db.write().unwrap() = Some(db);
+    }
+
+    /// Replace the re-ranking stage used when `enable_reranking` is set
+    pub fn set_reranker(&self, reranker: Arc<dyn Reranker>) {
+        *self.reranker.write().unwrap() = reranker;
+    }
+
+    fn database(&self) -> CodexResult<Arc<DatabaseManager>> {
+        self.db
+            .read()
+            .unwrap()
+            .clone()
+            .ok_or_else(|| CodexError::internal("Database not set for RAG engine"))
     }
 
     /// Perform RAG query with retrieval and generation
     pub async fn query(&self, query: &str, context_limit: usize) -> CodexResult<RagResponse> {
+        self.query_with_history(query, context_limit, &[]).await
+    }
+
+    /// Perform a RAG query, streaming the generated answer to `callback`
+    ///
+    /// The returned response carries the final answer, which is hedged when
+    /// the confidence turns out low, and the usage the last chunk's token
+    /// count adds up to.
+    pub async fn query_stream(
+        &self,
+        query: &str,
+        context_limit: usize,
+        callback: impl Fn(StreamChunk) + Send + Sync + 'static,
+    ) -> CodexResult<RagResponse> {
+        self.answer_query(query, context_limit, &[], Some(Box::new(callback))).await
+    }
+
+    /// Perform a RAG query that follows up on a recorded conversation
+    ///
+    /// Recent messages are used to rewrite the query into a standalone one.
+    pub async fn query_in_conversation(
+        &self,
+        query: &str,
+        context_limit: usize,
+        conversation_id: Uuid,
+    ) -> CodexResult<RagResponse> {
+        let history = self.conversation_history(conversation_id, CHAT_HISTORY_MESSAGES).await?;
+        self.query_with_history(query, context_limit, &history).await
+    }
+
+    /// Perform RAG query, rewriting it with the conversation `history` first
+    /// when query rewriting is enabled
+    #[instrument(skip_all, fields(query = %query, context_limit, history = history.len()))]
+    pub async fn query_with_history(
+        &self,
+        query: &str,
+        context_limit: usize,
+        history: &[ConversationMessage],
+    ) -> CodexResult<RagResponse> {
+        self.answer_query(query, context_limit, history, None).await
+    }
+
+    async fn answer_query(
+        &self,
+        query: &str,
+        context_limit: usize,
+        history: &[ConversationMessage],
+        stream: Option<Box<dyn Fn(StreamChunk) + Send + Sync>>,
+    ) -> CodexResult<RagResponse> {
+        let _timer = metrics::timer(metrics::RAG_QUERY);
         debug!("Performing RAG query: {}", query);
 
-        // Step 1: Generate query embedding
-        let query_embedding = self.embeddings.generate_embedding(query).await?;
+        // Step 1: Rewrite follow-up questions for retrieval
+        let rewritten = if self.config.enable_query_rewriting && !history.is_empty() {
+            self.rewrite_query(query, history).await
+        } else {
+            RewrittenQuery::unchanged(query)
+        };
+        let rewritten_query = Some(rewritten.standalone.clone()).filter(|q| q != query);
 
         // Step 2: Retrieve relevant documents
-        let sources = self.retrieve_relevant_documents(&query_embedding, context_limit).await?;
+        let (sources, reranker) = self.retrieve_relevant_documents(&rewritten.all(), context_limit).await?;
 
         if sources.is_empty() {
+            let answer = "I don't have enough relevant information in my knowledge base to answer that question.";
+            if let Some(callback) = stream {
+                callback(StreamChunk { text: answer.to_string(), completion_tokens: 0, elapsed_ms: 0 });
+            }
             return Ok(RagResponse {
-                answer: "I don't have enough relevant information in my knowledge base to answer that question.".to_string(),
+                answer: answer.to_string(),
                 sources: Vec::new(),
                 confidence: 0.0,
+                low_confidence: true,
                 context_used: 0,
+                reranker,
+                rewritten_query,
+                usage: None,
             });
         }
 
         // Step 3: Build context from retrieved documents
-        let context = self.build_context(&sources);
+        let context = self.build_context(&sources, &rewritten.standalone);
 
         // Step 4: Generate answer using context
-        let answer = self.generate_contextual_answer(query, &context).await?;
+        let generated = self.generate_contextual_answer(&rewritten.standalone, &context, stream).await?;
 
         // Step 5: Calculate confidence score
-        let confidence = self.calculate_confidence(&sources);
+        let similarities: Vec<f32> = sources.iter().map(|s| s.relevance_score).collect();
+        let (answer, confidence, low_confidence) =
+            self.assess_answer(&rewritten.standalone, &context, generated.text, similarities).await;
 
         Ok(RagResponse {
             answer,
             sources,
             confidence,
+            low_confidence,
             context_used: context.len(),
+            reranker,
+            rewritten_query,
+            usage: Some(generated.usage),
         })
     }
 
-    /// Retrieve relevant documents based on query embedding
+    /// Rewrite a query into a standalone query plus expansion variants
+    ///
+    /// Falls back to [`rewrite::heuristic_rewrite`] when generation fails or
+    /// the reply cannot be parsed.
+    async fn rewrite_query(&self, query: &str, history: &[ConversationMessage]) -> RewrittenQuery {
+        let prompt = rewrite::rewrite_prompt(history, query);
+        let settings = AiConfig {
+            max_tokens: 96,
+            temperature: 0.0,
+            ..self.generation.clone()
+        };
+
+        let reply = {
+            let inference = self.inference.read().await;
+            inference.generate(&prompt, &settings).await
+        };
+        match reply.map(|r| rewrite::parse_rewrite(&r)) {
+            Ok(Some(rewritten)) => rewritten,
+            Ok(None) => rewrite::heuristic_rewrite(history, query),
+            Err(e) => {
+                debug!("Query rewriting failed, using heuristic: {}", e);
+                rewrite::heuristic_rewrite(history, query)
+            }
+        }
+    }
+
+    /// Retrieve relevant document chunks for one or more query variants
+    ///
+    /// Documents are found by hybrid search, merging full-text and semantic
+    /// matches with the weights in `AiConfig::rag`, so exact terms such as
+    /// product codes are found even when embeddings miss them. Every variant
+    /// is searched and each document keeps its best score. Each document
+    /// contributes at most `max_chunks_per_document` distinct chunks, ranked
+    /// against the first (main) query. With re-ranking enabled,
+    /// [`rerank::RERANK_CANDIDATE_FACTOR`] times more documents are retrieved
+    /// and the reranker orders the chunks. Returns at most `limit` sources and
+    /// the name of the reranker used.
+    #[instrument(skip_all, fields(queries = queries.len(), limit))]
     async fn retrieve_relevant_documents(
         &self,
-        query_embedding: &[f32],
+        queries: &[&str],
         limit: usize,
-    ) -> CodexResult<Vec<RagSource>> {
-        let db = self.db.as_ref().ok_or_else(|| {
-            CodexError::internal("Database not set for RAG engine")
-        })?;
-
-        // Get all document embeddings from database
-        let embeddings = crate::db::EmbeddingQueries::get_all_vectors(db.pool()).await?;
-
-        // Find most similar documents
-        let similarities = self.embeddings.find_similar(
-            query_embedding,
-            &embeddings,
-            limit.min(self.config.max_context_documents),
-        );
-
-        let mut sources = Vec::new();
+    ) -> CodexResult<(Vec<RagSource>, Option<String>)> {
+        let db = self.database()?;
+        let settings = &self.generation.rag;
+        let limit = limit.min(self.config.max_context_documents);
+        let candidate_count = if self.config.enable_reranking {
+            limit * rerank::RERANK_CANDIDATE_FACTOR
+        } else {
+            limit
+        };
 
-        for similarity in similarities {
-            if similarity.similarity_score >= self.config.similarity_threshold {
-                // Get document details
-                if let Ok(Some(document)) = crate::db::DocumentQueries::get_by_id(
-                    db.pool(),
-                    similarity.document_id.as_str(),
-                ).await {
-                    // Extract relevant snippet
-                    let snippet = self.extract_relevant_snippet(&document.content, query_embedding).await?;
-
-                    sources.push(RagSource {
-                        document_id: uuid::Uuid::parse_str(&document.id).unwrap_or_default(),
-                        title: document.title,
-                        snippet,
-                        relevance_score: similarity.similarity_score,
-                    });
+        let query = queries.first().copied().unwrap_or_default();
+        let mut query_embedding = Vec::new();
+        let mut documents: Vec<(Document, f32)> = Vec::new();
+        for (i, variant) in queries.iter().enumerate() {
+            let embedding = self.embeddings.generate_embedding(variant).await?;
+            for (document, score) in hybrid_candidates(db.pool(), variant, &embedding, settings, candidate_count).await? {
+                match documents.iter_mut().find(|(d, _)| d.id == document.id) {
+                    Some(existing) => existing.1 = existing.1.max(score),
+                    None => documents.push((document, score)),
                 }
             }
+            if i == 0 {
+                query_embedding = embedding;
+            }
         }
+        documents.sort_by(|a, b| b.1.total_cmp(&a.1));
+        documents.truncate(candidate_count);
 
-        // Re-rank sources if enabled
-        if self.config.enable_reranking {
-            sources = self.rerank_sources(sources, query_embedding).await?;
-        }
-
-        Ok(sources)
-    }
+        let mut sources = Vec::new();
+        let mut highlights = std::collections::HashMap::new();
+        for (document, score) in documents {
+            if score < self.config.similarity_threshold {
+                continue;
+            }
 
-    /// Extract the most relevant snippet from a document
-    async fn extract_relevant_snippet(
-        &self,
-        content: &str,
-        query_embedding: &[f32],
-    ) -> CodexResult<String> {
-        // Generate embeddings for content chunks
-        let chunk_embeddings = self.embeddings.generate_chunk_embeddings(
-            content,
-            200, // words per chunk
-            20,  // overlap
-        ).await?;
-
-        // Find the most relevant chunk
-        let mut best_similarity = 0.0;
-        let mut best_chunk = String::new();
-
-        for chunk_emb in chunk_embeddings {
-            let similarity = self.embeddings.cosine_similarity(query_embedding, &chunk_emb.embedding);
-            if similarity > best_similarity {
-                best_similarity = similarity;
-                best_chunk = chunk_emb.text;
+            let document_highlights = document_highlights(db.pool(), &document.id).await?;
+            let chunks = self
+                .rank_document_chunks(&db, &document, &query_embedding)
+                .await?
+                .into_iter()
+                .map(|(chunk_score, chunk)| {
+                    let factor = highlight_factor(&chunk, &document_highlights, settings.highlight_boost);
+                    (chunk_score * factor, chunk)
+                })
+                .collect();
+            for (_, chunk) in select_document_chunks(chunks, query, settings.max_chunks_per_document) {
+                sources.push(RagSource {
+                    document_id: uuid::Uuid::parse_str(&document.id).unwrap_or_default(),
+                    title: document.title.clone(),
+                    timestamp: super::embeddings::leading_timestamp(&chunk),
+                    snippet: chunk,
+                    relevance_score: score,
+                });
             }
+            highlights.insert(document.id, document_highlights);
         }
 
-        // Limit snippet length
-        let max_snippet_length = 300;
-        if best_chunk.len() > max_snippet_length {
-            let truncated = best_chunk.chars().take(max_snippet_length).collect::<String>();
-            Ok(format!("{}...", truncated))
+        // Boost after re-ranking, which replaces the scores
+        let (mut sources, reranker) = if self.config.enable_reranking {
+            let reranker = self.reranker.read().unwrap().clone();
+            (reranker.rerank(query, sources).await?, Some(reranker.name()))
         } else {
-            Ok(best_chunk)
-        }
-    }
-
-    /// Re-rank sources based on additional relevance signals
-    async fn rerank_sources(
-        &self,
-        mut sources: Vec<RagSource>,
-        _query_embedding: &[f32],
-    ) -> CodexResult<Vec<RagSource>> {
-        // Simple re-ranking based on relevance score
-        // In a more sophisticated implementation, you might:
-        // 1. Use a cross-encoder model for better ranking
-        // 2. Consider document metadata (recency, authority, etc.)
-        // 3. Apply diversity filtering
-
-        sources.sort_by(|a, b| b.relevance_score.partial_cmp(&a.relevance_score).unwrap());
-        Ok(sources)
+            (sources, None)
+        };
+        boost_highlighted_sources(&mut sources, &highlights, settings.highlight_boost);
+        sources.truncate(limit);
+        Ok((sources, reranker))
     }
 
     /// Build context string from retrieved sources
-    fn build_context(&self, sources: &[RagSource]) -> String {
-        let mut context = String::new();
-        let mut current_length = 0;
-        let max_context_length = self.config.context_window_size;
-
-        for (i, source) in sources.iter().enumerate() {
-            let source_text = format!(
-                "[Source {}: {}]\n{}\n\n",
-                i + 1,
-                source.title,
-                source.snippet
-            );
-
-            if current_length + source_text.len() > max_context_length {
-                break;
-            }
+    ///
+    /// The context must fit `context_window_size` characters and the prompt
+    /// tokens left beside the question; sources that do not fit are dropped,
+    /// lowest-ranked first, see [`fit_sources`].
+    fn build_context(&self, sources: &[RagSource], query: &str) -> String {
+        let texts: Vec<(f32, String)> = sources
+            .iter()
+            .enumerate()
+            .map(|(i, source)| {
+                let label = match source.timestamp {
+                    Some(ref timestamp) => format!("{} @ {}", source.title, timestamp),
+                    None => source.title.clone(),
+                };
+                (source.relevance_score, format!("[Source {}: {}]\n{}\n\n", i + 1, label, source.snippet))
+            })
+            .collect();
 
-            context.push_str(&source_text);
-            current_length += source_text.len();
-        }
-
-        context
+        let prompt_tokens = summarize::estimate_tokens(RAG_ANSWER_PREFIX)
+            + summarize::estimate_tokens(&answer_prompt_suffix(query));
+        let token_budget = self.generation.prompt_token_limit().saturating_sub(prompt_tokens);
+        fit_sources(&texts, self.config.context_window_size, token_budget)
     }
 
     /// Generate answer using retrieved context
-    async fn generate_contextual_answer(&self, query: &str, context: &str) -> CodexResult<String> {
-        let prompt = format!(
-            "Based on the following context, please provide a comprehensive and accurate answer to the question. If the context doesn't contain enough information to answer the question, please say so.\n\nContext:\n{}\n\nQuestion: {}\n\nAnswer:",
-            context, query
-        );
+    async fn generate_contextual_answer(
+        &self,
+        query: &str,
+        context: &str,
+        stream: Option<Box<dyn Fn(StreamChunk) + Send + Sync>>,
+    ) -> CodexResult<GenerationOutput> {
+        let prompt = format!("{}{}{}", RAG_ANSWER_PREFIX, context, answer_prompt_suffix(query));
 
         let inference = self.inference.read().await;
         // Use minimal config for now
@@ -272,32 +448,80 @@ impl RagEngine {
             device: "cpu".to_string(),
             enable_caching: true,
             cache_size_mb: 512,
+            rag: Default::default(),
+            embedding: Default::default(),
+            audit: Default::default(),
+            draft_model: None,
+            speculative: Default::default(),
+            truncation: Default::default(),
+            gpu_layers: Default::default(),
         };
-        inference.generate(&prompt, &config).await
+        match stream {
+            Some(callback) => inference.generate_stream_with_details(&prompt, &config, callback).await,
+            None => inference.generate_with_details(&prompt, &config).await,
+        }
     }
 
-    /// Calculate confidence score based on sources
-    fn calculate_confidence(&self, sources: &[RagSource]) -> f32 {
-        if sources.is_empty() {
-            return 0.0;
-        }
+    /// Score an answer's confidence and hedge it when the score is low
+    ///
+    /// The score combines retrieval quality, overlap between the answer and
+    /// the context, and a YES/NO self-check by the model, weighted 0.4, 0.35
+    /// and 0.25 (see [`confidence`]). Answers scoring below
+    /// `low_confidence_threshold` are prefixed with a hedge. Returns the
+    /// answer, the score and whether it was low.
+    pub async fn assess_answer(
+        &self,
+        question: &str,
+        context: &str,
+        answer: String,
+        similarities: Vec<f32>,
+    ) -> (String, f32, bool) {
+        let self_check = if self.config.enable_self_check {
+            self.self_check(question, context, &answer).await
+        } else {
+            None
+        };
 
-        // Calculate weighted average of relevance scores
-        let total_score: f32 = sources.iter().map(|s| s.relevance_score).sum();
-        let average_score = total_score / sources.len() as f32;
+        let signals = ConfidenceSignals {
+            similarities,
+            similarity_threshold: self.config.similarity_threshold,
+            answer_overlap: confidence::answer_overlap(&answer, context),
+            self_check,
+        };
+        let score = signals.score();
+        debug!("Answer confidence {:.2} from {:?}", score, signals);
 
-        // Apply penalties for low number of sources
-        let source_count_factor = (sources.len() as f32 / 3.0).min(1.0);
+        if score < self.config.low_confidence_threshold {
+            (format!("{}{}", confidence::LOW_CONFIDENCE_HEDGE, answer), score, true)
+        } else {
+            (answer, score, false)
+        }
+    }
+
+    /// Ask the model whether the context supports an answer
+    ///
+    /// Returns `None` when generation fails or the reply is not YES/NO.
+    async fn self_check(&self, question: &str, context: &str, answer: &str) -> Option<bool> {
+        let prompt = confidence::self_check_prompt(question, context, answer);
+        let settings = AiConfig {
+            max_tokens: 4,
+            temperature: 0.0,
+            ..self.generation.clone()
+        };
 
-        // Final confidence score
-        (average_score * source_count_factor).min(1.0)
+        let inference = self.inference.read().await;
+        match inference.generate(&prompt, &settings).await {
+            Ok(reply) => confidence::parse_self_check(&reply),
+            Err(e) => {
+                debug!("Confidence self-check failed: {}", e);
+                None
+            }
+        }
     }
 
     /// Summarize multiple documents
     pub async fn summarize_documents(&self, document_ids: &[uuid::Uuid]) -> CodexResult<String> {
-        let db = self.db.as_ref().ok_or_else(|| {
-            CodexError::internal("Database not set for RAG engine")
-        })?;
+        let db = self.database()?;
 
         let mut combined_content = String::new();
         let mut titles = Vec::new();
@@ -330,15 +554,20 @@ impl RagEngine {
             device: "cpu".to_string(),
             enable_caching: true,
             cache_size_mb: 512,
+            rag: Default::default(),
+            embedding: Default::default(),
+            audit: Default::default(),
+            draft_model: None,
+            speculative: Default::default(),
+            truncation: Default::default(),
+            gpu_layers: Default::default(),
         };
         inference.generate(&prompt, &config).await
     }
 
     /// Compare multiple documents
     pub async fn compare_documents(&self, document_ids: &[uuid::Uuid], comparison_aspect: &str) -> CodexResult<String> {
-        let db = self.db.as_ref().ok_or_else(|| {
-            CodexError::internal("Database not set for RAG engine")
-        })?;
+        let db = self.database()?;
 
         let mut documents_content = Vec::new();
 
@@ -369,10 +598,363 @@ impl RagEngine {
             device: "cpu".to_string(),
             enable_caching: true,
             cache_size_mb: 512,
+            rag: Default::default(),
+            embedding: Default::default(),
+            audit: Default::default(),
+            draft_model: None,
+            speculative: Default::default(),
+            truncation: Default::default(),
+            gpu_layers: Default::default(),
         };
         inference.generate(&prompt, &config).await
     }
 
+    /// Answer a question from a fixed set of documents, e.g. to compare them
+    ///
+    /// The most relevant chunks of each document are retrieved (the rest of
+    /// the corpus is ignored) and the context window is shared between the
+    /// documents in proportion to their relevance. Each returned source
+    /// attributes the answer to one document.
+    #[instrument(skip_all, fields(documents = document_ids.len()))]
+    pub async fn multi_document_query(&self, question: &str, document_ids: &[Uuid]) -> CodexResult<RagResponse> {
+        if !(MIN_QUERY_DOCUMENTS..=MAX_QUERY_DOCUMENTS).contains(&document_ids.len()) {
+            return Err(CodexError::validation(format!(
+                "Select between {} and {} documents",
+                MIN_QUERY_DOCUMENTS, MAX_QUERY_DOCUMENTS
+            )));
+        }
+
+        let db = self.database()?;
+        let query_embedding = self.embeddings.generate_embedding(question).await?;
+
+        // Rank each document's chunks against the question
+        let mut ranked = Vec::with_capacity(document_ids.len());
+        for id in document_ids {
+            let document = crate::db::DocumentQueries::get_by_id(db.pool(), &id.to_string())
+                .await?
+                .ok_or_else(|| CodexError::not_found(format!("Document {} not found", id)))?;
+
+            let chunks = self.rank_document_chunks(&db, &document, &query_embedding).await?;
+            ranked.push((*id, document.title, chunks));
+        }
+
+        let weights: Vec<f32> = ranked
+            .iter()
+            .map(|(_, _, chunks)| chunks.first().map(|c| c.0).unwrap_or(0.0))
+            .collect();
+        let budgets = allocate_context_budget(self.config.context_window_size, &weights);
+
+        let mut context = String::new();
+        let mut sources = Vec::with_capacity(ranked.len());
+        for (i, ((id, title, chunks), budget)) in ranked.into_iter().zip(budgets).enumerate() {
+            let excerpt = take_chunks(chunks.iter().map(|c| c.1.as_str()), budget);
+            context.push_str(&format!("## Document {}: {}\n{}\n\n", i + 1, title, excerpt));
+
+            let snippet: String = chunks.first().map(|c| c.1.chars().take(300).collect()).unwrap_or_default();
+            sources.push(RagSource {
+                document_id: id,
+                title,
+                timestamp: super::embeddings::leading_timestamp(&snippet),
+                snippet,
+                relevance_score: chunks.first().map(|c| c.0).unwrap_or(0.0),
+            });
+        }
+
+        let prompt = format!(
+            "Answer the question using only the documents below. Address each document separately where they differ, \
+             refer to them as \"Document N\", and say so when a document does not cover the question.\n\n{}\
+             Question: {}\n\nAnswer:",
+            context, question
+        );
+
+        let generated = {
+            let inference = self.inference.read().await;
+            inference.generate_with_details(&prompt, &self.generation).await?
+        };
+        let similarities: Vec<f32> = sources.iter().map(|s| s.relevance_score).collect();
+        let (answer, confidence, low_confidence) =
+            self.assess_answer(question, &context, generated.text, similarities).await;
+
+        Ok(RagResponse {
+            answer,
+            sources,
+            confidence,
+            low_confidence,
+            context_used: context.len(),
+            reranker: None,
+            rewritten_query: None,
+            usage: Some(generated.usage),
+        })
+    }
+
+    /// Chat about a single document, streaming the answer to `callback`
+    ///
+    /// Retrieval only considers the document's own chunks. The exchange is
+    /// appended to `conversation_id`, or to a new conversation when none is
+    /// given, and recent messages are included as history. With
+    /// `strict_grounding` the model may only answer from the retrieved
+    /// excerpts and replies [`NOT_FOUND_ANSWER`] otherwise.
+    #[instrument(skip(self, message, callback))]
+    pub async fn document_chat(
+        &self,
+        document_id: Uuid,
+        message: &str,
+        conversation_id: Option<Uuid>,
+        strict_grounding: bool,
+        callback: impl Fn(String) + Send + Sync + 'static,
+    ) -> CodexResult<DocumentChatResponse> {
+        if message.trim().is_empty() {
+            return Err(CodexError::validation("Message cannot be empty"));
+        }
+
+        let db = self.database()?;
+        let document = crate::db::DocumentQueries::get_by_id(db.pool(), &document_id.to_string())
+            .await?
+            .ok_or_else(|| CodexError::not_found(format!("Document {} not found", document_id)))?;
+
+        let conversation = match conversation_id {
+            Some(id) => {
+                let conversation = crate::db::ConversationQueries::get_by_id(db.pool(), &id.to_string())
+                    .await?
+                    .ok_or_else(|| CodexError::not_found(format!("Conversation {} not found", id)))?;
+                if conversation.document_id.as_deref() != Some(document.id.as_str()) {
+                    return Err(CodexError::validation("Conversation belongs to a different document"));
+                }
+                conversation
+            }
+            None => {
+                let title: String = message.chars().take(80).collect();
+                let conversation = Conversation::new(Some(document.id.clone()), Some(title));
+                crate::db::ConversationQueries::create(db.pool(), &conversation).await?;
+                conversation
+            }
+        };
+        let history =
+            crate::db::ConversationQueries::get_messages(db.pool(), &conversation.id, CHAT_HISTORY_MESSAGES).await?;
+
+        let query_embedding = self.embeddings.generate_embedding(message).await?;
+        let chunks: Vec<(f32, String)> = self
+            .rank_document_chunks(&db, &document, &query_embedding)
+            .await?
+            .into_iter()
+            .take(self.config.max_context_documents)
+            .collect();
+        let grounded = chunks.first().is_some_and(|(score, _)| *score >= self.config.similarity_threshold);
+
+        let sources: Vec<RagSource> = chunks
+            .iter()
+            .map(|(score, text)| {
+                let snippet: String = text.chars().take(300).collect();
+                RagSource {
+                    document_id,
+                    title: document.title.clone(),
+                    timestamp: super::embeddings::leading_timestamp(&snippet),
+                    snippet,
+                    relevance_score: *score,
+                }
+            })
+            .collect();
+
+        let answer = if strict_grounding && !grounded {
+            // Nothing in the document is close enough to answer from
+            callback(NOT_FOUND_ANSWER.to_string());
+            NOT_FOUND_ANSWER.to_string()
+        } else {
+            let excerpts = take_chunks(chunks.iter().map(|c| c.1.as_str()), self.config.context_window_size);
+            let prompt = document_chat_prompt(&document.title, &excerpts, &history, message, strict_grounding);
+            let inference = self.inference.read().await;
+            inference.generate_in_conversation(&conversation.id, &prompt, &self.generation, callback).await?
+        };
+
+        let asked = ConversationMessage::new(
+            Some(conversation.id.clone()),
+            Some(document.id.clone()),
+            ConversationMessage::ROLE_USER,
+            message.to_string(),
+        );
+        crate::db::ConversationQueries::add_message(db.pool(), &asked).await?;
+        let answered = ConversationMessage::new(
+            Some(conversation.id.clone()),
+            Some(document.id),
+            ConversationMessage::ROLE_ASSISTANT,
+            answer.clone(),
+        );
+        crate::db::ConversationQueries::add_message(db.pool(), &answered).await?;
+
+        Ok(DocumentChatResponse {
+            conversation_id: Uuid::parse_str(&conversation.id).unwrap_or_default(),
+            answer,
+            sources,
+            grounded,
+        })
+    }
+
+    /// Messages of a conversation, oldest first
+    pub async fn conversation_history(&self, conversation_id: Uuid, limit: i64) -> CodexResult<Vec<ConversationMessage>> {
+        let db = self.database()?;
+        crate::db::ConversationQueries::get_messages(db.pool(), &conversation_id.to_string(), limit).await
+    }
+
+    /// A document's chunks scored against a query embedding, best first
+    ///
+    /// Stored chunk embeddings are used when the document is indexed;
+    /// otherwise the content is chunked and embedded on the fly.
+    async fn rank_document_chunks(
+        &self,
+        db: &DatabaseManager,
+        document: &Document,
+        query_embedding: &[f32],
+    ) -> CodexResult<Vec<(f32, String)>> {
+        let mut chunks: Vec<(f32, String)> = crate::db::EmbeddingQueries::get_by_document(db.pool(), &document.id)
+            .await?
+            .into_iter()
+            .map(|e| (self.embeddings.cosine_similarity(query_embedding, &e.get_vector()), e.text_chunk))
+            .collect();
+
+        if chunks.is_empty() {
+            chunks = self.embeddings
+                .generate_chunk_embeddings(&document.content, 200, 20)
+                .await?
+                .into_iter()
+                .map(|c| (self.embeddings.cosine_similarity(query_embedding, &c.embedding), c.text))
+                .collect();
+        }
+
+        chunks.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+        Ok(chunks)
+    }
+
+    /// Answer a question about a passage the reader selected, streaming the answer
+    ///
+    /// Context is the selection plus the stored chunks around it; nothing else
+    /// is retrieved, which keeps latency low. `selection_range` is the byte
+    /// range of the selection in the document and is located by text when
+    /// omitted. The question and answer are recorded against the document,
+    /// and appended to `conversation_id` when given.
+    #[instrument(skip(self, selection_text, selection_range, question, callback))]
+    pub async fn answer_about_selection(
+        &self,
+        document_id: Uuid,
+        selection_text: &str,
+        selection_range: Option<std::ops::Range<usize>>,
+        question: &str,
+        conversation_id: Option<Uuid>,
+        callback: impl Fn(String) + Send + Sync + 'static,
+    ) -> CodexResult<String> {
+        if selection_text.trim().is_empty() || question.trim().is_empty() {
+            return Err(CodexError::validation("Selection and question cannot be empty"));
+        }
+
+        let db = self.database()?;
+        let document = crate::db::DocumentQueries::get_by_id(db.pool(), &document_id.to_string())
+            .await?
+            .ok_or_else(|| CodexError::not_found(format!("Document {} not found", document_id)))?;
+        let conversation_id = match conversation_id {
+            Some(id) => Some(
+                crate::db::ConversationQueries::get_by_id(db.pool(), &id.to_string())
+                    .await?
+                    .ok_or_else(|| CodexError::not_found(format!("Conversation {} not found", id)))?
+                    .id,
+            ),
+            None => None,
+        };
+
+        let range = selection_range
+            .filter(|r| r.start <= r.end && r.end <= document.content.len())
+            .or_else(|| {
+                document
+                    .content
+                    .find(selection_text)
+                    .map(|start| start..start + selection_text.len())
+            });
+
+        let surrounding = match range {
+            Some(ref range) => self.selection_context(&db, &document, range.clone()).await?,
+            None => String::new(),
+        };
+
+        let prompt = format!(
+            "A reader of \"{}\" selected a passage and has a question about it. Answer concisely, using the \
+             passage and the surrounding text.\n\nSurrounding text:\n{}\n\nSelected passage:\n\"{}\"\n\n\
+             Question: {}\n\nAnswer:",
+            document.title, surrounding, selection_text, question
+        );
+
+        let answer = {
+            let inference = self.inference.read().await;
+            inference.generate_stream(&prompt, &self.generation, callback).await?
+        };
+
+        let mut asked = ConversationMessage::new(
+            conversation_id.clone(),
+            Some(document.id.clone()),
+            ConversationMessage::ROLE_USER,
+            question.to_string(),
+        );
+        asked.selection_text = Some(selection_text.to_string());
+        asked.selection_start = range.as_ref().map(|r| r.start as i64);
+        asked.selection_end = range.as_ref().map(|r| r.end as i64);
+        crate::db::ConversationQueries::add_message(db.pool(), &asked).await?;
+
+        let answered = ConversationMessage::new(
+            conversation_id,
+            Some(document.id),
+            ConversationMessage::ROLE_ASSISTANT,
+            answer.clone(),
+        );
+        crate::db::ConversationQueries::add_message(db.pool(), &answered).await?;
+
+        Ok(answer)
+    }
+
+    /// Stored chunks around a selection, nearest first within the context budget
+    async fn selection_context(
+        &self,
+        db: &DatabaseManager,
+        document: &Document,
+        range: std::ops::Range<usize>,
+    ) -> CodexResult<String> {
+        let start = range.start.saturating_sub(SELECTION_CONTEXT_BYTES);
+        let end = range.end + SELECTION_CONTEXT_BYTES;
+
+        let mut chunks =
+            crate::db::EmbeddingQueries::get_chunks_in_range(db.pool(), &document.id, start as i64, end as i64).await?;
+
+        // Unindexed documents fall back to the raw text around the selection
+        if chunks.is_empty() {
+            let start = floor_char_boundary(&document.content, start);
+            let end = floor_char_boundary(&document.content, end.min(document.content.len()));
+            return Ok(document.content[start..end].chars().take(self.config.context_window_size).collect());
+        }
+
+        let distance = |e: &crate::db::models::Embedding| {
+            if e.end_position < range.start as i64 {
+                range.start as i64 - e.end_position
+            } else {
+                (e.start_position - range.end as i64).max(0)
+            }
+        };
+        chunks.sort_by_key(|e| distance(e));
+
+        let mut used = 0;
+        let mut selected = Vec::new();
+        for chunk in chunks {
+            let len = chunk.text_chunk.chars().count();
+            if !selected.is_empty() && used + len > self.config.context_window_size {
+                break;
+            }
+            used += len;
+            selected.push(chunk);
+        }
+        selected.sort_by_key(|e| e.chunk_index);
+
+        Ok(selected
+            .into_iter()
+            .map(|e| e.text_chunk)
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+
     /// Get configuration
     pub fn get_config(&self) -> &RagConfig {
         &self.config
@@ -391,9 +973,230 @@ impl RagEngine {
     }
 }
 
+/// Split a context budget (in characters) between documents by relevance
+///
+/// Every document gets at least [`MIN_DOCUMENT_SHARE`] of an even split so a
+/// weakly matching document is still represented.
+pub fn allocate_context_budget(budget: usize, weights: &[f32]) -> Vec<usize> {
+    if weights.is_empty() {
+        return Vec::new();
+    }
+
+    let floor = MIN_DOCUMENT_SHARE / weights.len() as f32;
+    let weights: Vec<f32> = weights.iter().map(|w| w.max(0.0) + floor).collect();
+    let total: f32 = weights.iter().sum();
+
+    weights
+        .iter()
+        .map(|w| (budget as f32 * w / total).floor() as usize)
+        .collect()
+}
+
+/// Active documents matching a query by hybrid search, with their scores
+async fn hybrid_candidates(
+    pool: &sqlx::SqlitePool,
+    query: &str,
+    query_embedding: &[f32],
+    settings: &RagRetrievalConfig,
+    limit: usize,
+) -> CodexResult<Vec<(Document, f32)>> {
+    let matches = crate::db::SearchQueries::search_hybrid(
+        pool,
+        query,
+        Some(query_embedding),
+        Some(limit as i64),
+        Some(settings.text_weight),
+        Some(settings.semantic_weight),
+    )
+    .await?;
+
+    Ok(matches
+        .into_iter()
+        .filter(|(document, _)| !document.is_deleted && !document.is_archived)
+        .map(|(document, score)| (document, score as f32))
+        .collect())
+}
+
+/// Text of a document's highlights, for boosting the chunks they touch
+async fn document_highlights(pool: &sqlx::SqlitePool, document_id: &str) -> CodexResult<Vec<String>> {
+    Ok(crate::db::AnnotationQueries::get_by_document(pool, document_id)
+        .await?
+        .into_iter()
+        .map(|annotation| annotation.selected_text)
+        .collect())
+}
+
+/// `boost` if the chunk overlaps any of the highlights, otherwise 1
+///
+/// Chunk boundaries are approximate, so a chunk overlaps a highlight when
+/// it lies inside the highlight or holds the start or end of it.
+fn highlight_factor(chunk: &str, highlights: &[String], boost: f32) -> f32 {
+    let normalize = |text: &str| text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let chunk = normalize(chunk);
+
+    let overlaps = highlights.iter().any(|highlight| {
+        let highlight = normalize(highlight);
+        if highlight.is_empty() {
+            return false;
+        }
+        let chars: Vec<char> = highlight.chars().collect();
+        let edge = HIGHLIGHT_EDGE_CHARS.min(chars.len());
+        let head: String = chars[..edge].iter().collect();
+        let tail: String = chars[chars.len() - edge..].iter().collect();
+        highlight.contains(&chunk) || chunk.contains(&head) || chunk.contains(&tail)
+    });
+
+    if overlaps { boost } else { 1.0 }
+}
+
+/// Apply the highlight boost to sources and re-sort them, best first
+///
+/// `highlights` holds the highlight texts by document id.
+fn boost_highlighted_sources(
+    sources: &mut [RagSource],
+    highlights: &std::collections::HashMap<String, Vec<String>>,
+    boost: f32,
+) {
+    for source in sources.iter_mut() {
+        if let Some(document_highlights) = highlights.get(&source.document_id.to_string()) {
+            source.relevance_score *= highlight_factor(&source.snippet, document_highlights, boost);
+        }
+    }
+    sources.sort_by(|a, b| b.relevance_score.total_cmp(&a.relevance_score));
+}
+
+/// Up to `cap` distinct chunks of one document, those containing query
+/// terms first, then by similarity
+fn select_document_chunks(chunks: Vec<(f32, String)>, query: &str, cap: usize) -> Vec<(f32, String)> {
+    let terms: Vec<String> = query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|t| t.chars().count() > 2)
+        .map(str::to_lowercase)
+        .collect();
+    let term_hits = |chunk: &str| {
+        let chunk = chunk.to_lowercase();
+        terms.iter().filter(|t| chunk.contains(t.as_str())).count()
+    };
+
+    let mut ranked: Vec<(usize, f32, String)> = chunks
+        .into_iter()
+        .map(|(score, chunk)| (term_hits(&chunk), score, chunk))
+        .collect();
+    ranked.sort_by(|a, b| b.0.cmp(&a.0).then(b.1.total_cmp(&a.1)));
+
+    let mut seen = std::collections::HashSet::new();
+    ranked
+        .into_iter()
+        .filter(|(_, _, chunk)| seen.insert(chunk.trim().to_string()))
+        .take(cap)
+        .map(|(_, score, chunk)| (score, chunk))
+        .collect()
+}
+
+/// Prompt for a document chat turn
+fn document_chat_prompt(
+    title: &str,
+    excerpts: &str,
+    history: &[ConversationMessage],
+    message: &str,
+    strict_grounding: bool,
+) -> String {
+    let instructions = if strict_grounding {
+        format!(
+            "Answer using only the excerpts below. Do not use outside knowledge. If the excerpts do not contain \
+             the answer, reply exactly: {}",
+            NOT_FOUND_ANSWER
+        )
+    } else {
+        "Answer using the excerpts below, and say when you rely on knowledge from outside the document.".to_string()
+    };
+
+    let mut conversation = String::new();
+    for turn in history {
+        let speaker = if turn.role == ConversationMessage::ROLE_USER { "User" } else { "Assistant" };
+        conversation.push_str(&format!("{}: {}\n", speaker, turn.content));
+    }
+    conversation.push_str(&format!("User: {}\nAssistant:", message));
+
+    format!(
+        "{}{}\". {}\n\nExcerpts:\n{}\n\n{}",
+        DOCUMENT_CHAT_PREFIX, title, instructions, excerpts, conversation
+    )
+}
+
+/// End of a RAG answer prompt, after the context
+fn answer_prompt_suffix(query: &str) -> String {
+    format!("\n\nQuestion: {}\n\nAnswer:", query)
+}
+
+/// Join the source texts fitting `max_chars` and `max_tokens` (estimated), in their original order
+///
+/// Sources are admitted by relevance; one that does not fit is dropped and
+/// less relevant ones may still use the room left. Only when not even the
+/// most relevant source fits is it cut down, so the context is never empty.
+fn fit_sources(texts: &[(f32, String)], max_chars: usize, max_tokens: usize) -> String {
+    let mut by_relevance: Vec<usize> = (0..texts.len()).collect();
+    by_relevance.sort_by(|&a, &b| texts[b].0.total_cmp(&texts[a].0));
+
+    let mut kept = vec![false; texts.len()];
+    let (mut chars, mut tokens) = (0, 0);
+    for &i in &by_relevance {
+        let text = &texts[i].1;
+        let (text_chars, text_tokens) = (text.chars().count(), summarize::estimate_tokens(text));
+        if chars + text_chars <= max_chars && tokens + text_tokens <= max_tokens {
+            kept[i] = true;
+            chars += text_chars;
+            tokens += text_tokens;
+        }
+    }
+
+    match by_relevance.first() {
+        Some(&best) if !kept.contains(&true) => {
+            let limit = max_chars.min(max_tokens * summarize::CHARS_PER_TOKEN);
+            texts[best].1.chars().take(limit).collect()
+        }
+        _ => texts
+            .iter()
+            .zip(kept)
+            .filter(|(_, kept)| *kept)
+            .map(|((_, text), _)| text.as_str())
+            .collect(),
+    }
+}
+
+/// Largest char boundary in `text` at or below `index`
+fn floor_char_boundary(text: &str, index: usize) -> usize {
+    let mut index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+/// Concatenate chunks (best first) up to `budget` characters, always keeping some of the first
+fn take_chunks<'a>(chunks: impl Iterator<Item = &'a str>, budget: usize) -> String {
+    let mut excerpt = String::new();
+    for chunk in chunks {
+        let separator = if excerpt.is_empty() { 0 } else { 5 };
+        let remaining = budget.saturating_sub(excerpt.chars().count() + separator);
+        if remaining == 0 {
+            break;
+        }
+        if !excerpt.is_empty() {
+            if chunk.chars().count() > remaining {
+                break;
+            }
+            excerpt.push_str("\n...\n");
+        }
+        excerpt.extend(chunk.chars().take(remaining));
+    }
+    excerpt
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::db::test_database;
 
     #[test]
     fn test_rag_config_default() {
@@ -401,6 +1204,169 @@ mod tests {
         assert_eq!(config.max_context_documents, 5);
         assert_eq!(config.similarity_threshold, 0.3);
         assert!(config.enable_reranking);
+        assert_eq!(config.low_confidence_threshold, 0.4);
+    }
+
+    #[test]
+    fn test_answer_settings_come_from_ai_config() {
+        let mut config = AiConfig::default();
+        config.rag.low_confidence_threshold = 0.7;
+        config.rag.enable_self_check = false;
+        config.rag.enable_query_rewriting = false;
+
+        let rag = RagEngine::with_reranker(
+            Arc::new(RwLock::new(InferenceEngine::unloaded(&config))),
+            Arc::new(EmbeddingEngine::disabled(&config)),
+            &config,
+            Arc::new(rerank::KeywordReranker::default()),
+        );
+        assert_eq!(rag.get_config().low_confidence_threshold, 0.7);
+        assert!(!rag.get_config().enable_self_check);
+        assert!(!rag.get_config().enable_query_rewriting);
+    }
+
+    #[test]
+    fn test_context_budget_is_proportional_with_floor() {
+        let budgets = allocate_context_budget(1000, &[0.8, 0.2, 0.0]);
+
+        assert!(budgets[0] > budgets[1] && budgets[1] > budgets[2]);
+        assert!(budgets[2] > 0);
+        assert!(budgets.iter().sum::<usize>() <= 1000);
+        assert_eq!(allocate_context_budget(900, &[0.5, 0.5, 0.5]), vec![300, 300, 300]);
+    }
+
+    #[test]
+    fn test_strict_document_chat_prompt() {
+        let history = vec![ConversationMessage::new(None, None, ConversationMessage::ROLE_USER, "What is it?".to_string())];
+        let prompt = document_chat_prompt("Manual", "Press reset.", &history, "How do I reset?", true);
+
+        assert!(prompt.contains(NOT_FOUND_ANSWER));
+        assert!(prompt.contains("Excerpts:\nPress reset."));
+        assert!(prompt.ends_with("User: What is it?\nUser: How do I reset?\nAssistant:"));
+        assert!(!document_chat_prompt("Manual", "", &[], "Hi", false).contains(NOT_FOUND_ANSWER));
+    }
+
+    #[test]
+    fn test_fit_sources_drops_the_lowest_ranked_first() {
+        // 8 estimated tokens each
+        let texts = vec![
+            (0.2, "a".repeat(32)),
+            (0.9, "b".repeat(32)),
+            (0.5, "c".repeat(32)),
+        ];
+
+        // Exactly at the limit everything fits
+        assert_eq!(fit_sources(&texts, 96, 24).len(), 96);
+
+        // One token short, the lowest-ranked source goes even though it comes first
+        let context = fit_sources(&texts, 96, 23);
+        assert_eq!(context, format!("{}{}", "b".repeat(32), "c".repeat(32)));
+
+        // Only the best source is ever cut down
+        assert_eq!(fit_sources(&texts, 96, 7), "b".repeat(28));
+        assert_eq!(fit_sources(&[], 96, 24), "");
+    }
+
+    #[test]
+    fn test_take_chunks_respects_budget() {
+        let chunks = ["first chunk", "second", "third chunk that is long"];
+
+        assert_eq!(take_chunks(chunks.iter().copied(), 5), "first");
+        assert_eq!(take_chunks(chunks.iter().copied(), 25), "first chunk\n...\nsecond");
+    }
+
+    #[test]
+    fn test_select_document_chunks_prefers_query_terms() {
+        let chunks = vec![
+            (0.9, "General overview of the product line.".to_string()),
+            (0.4, "Replace the XJ-9000 filter yearly.".to_string()),
+            (0.8, "General overview of the product line.".to_string()),
+            (0.7, "Warranty terms.".to_string()),
+        ];
+
+        let selected = select_document_chunks(chunks, "XJ-9000 filter", 2);
+
+        assert_eq!(selected.len(), 2);
+        assert!(selected[0].1.contains("XJ-9000"));
+        assert_eq!(selected[1].0, 0.9);
+    }
+
+    #[tokio::test]
+    async fn test_exact_term_found_despite_low_embedding_similarity() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db = test_database(&temp_dir).await;
+        let pool = db.pool();
+
+        // The query vector points along the first axis; only the distractors are close to it
+        let corpus = [
+            ("Filter maintenance", "Replace the cartridge on the XJ-9000 every six months.", vec![0.0, 1.0, 0.0]),
+            ("Air purifiers", "Purifiers remove dust and pollen from indoor air.", vec![0.95, 0.1, 0.0]),
+            ("Water filters", "Carbon filters improve the taste of tap water.", vec![0.9, 0.0, 0.2]),
+        ];
+        for (title, content, vector) in corpus {
+            let document = Document::new(title.to_string(), content.to_string(), "text/plain".to_string());
+            crate::db::DocumentQueries::create(pool, &document, db.compression()).await.unwrap();
+            let embedding = crate::db::models::Embedding::new(
+                document.id.clone(), vector, "test".to_string(), 0, content.to_string(), 0, content.len() as i64,
+            );
+            crate::db::EmbeddingQueries::create(pool, &embedding).await.unwrap();
+        }
+        let query_vector = [1.0, 0.0, 0.0];
+
+        let semantic_only = RagRetrievalConfig { text_weight: 0.0, semantic_weight: 1.0, ..Default::default() };
+        let semantic = hybrid_candidates(pool, "XJ-9000 cartridge", &query_vector, &semantic_only, 2).await.unwrap();
+        assert!(semantic.iter().all(|(d, _)| d.title != "Filter maintenance"));
+
+        let hybrid = hybrid_candidates(pool, "XJ-9000 cartridge", &query_vector, &RagRetrievalConfig::default(), 2)
+            .await
+            .unwrap();
+        let (_, score) = hybrid.iter().find(|(d, _)| d.title == "Filter maintenance").unwrap();
+        assert!(*score >= RagConfig::default().similarity_threshold);
+    }
+
+    #[tokio::test]
+    async fn test_highlighted_passage_ranks_above_equal_match() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db = test_database(&temp_dir).await;
+        let pool = db.pool();
+
+        // Two documents identical in text and embedding; only the second is highlighted
+        let content = "Sourdough needs a mature starter. Proof the dough overnight in the fridge.";
+        let mut ids = Vec::new();
+        for title in ["Bread notes", "Baking journal"] {
+            let document = Document::new(title.to_string(), content.to_string(), "text/plain".to_string());
+            crate::db::DocumentQueries::create(pool, &document, db.compression()).await.unwrap();
+            let embedding = crate::db::models::Embedding::new(
+                document.id.clone(), vec![1.0, 0.0, 0.0], "test".to_string(), 0, content.to_string(), 0, content.len() as i64,
+            );
+            crate::db::EmbeddingQueries::create(pool, &embedding).await.unwrap();
+            ids.push(document.id);
+        }
+        let highlight = crate::db::models::Annotation::new(ids[1].clone(), 34, 74, content[34..].to_string());
+        crate::db::AnnotationQueries::create(pool, &highlight).await.unwrap();
+
+        let settings = RagRetrievalConfig::default();
+        let candidates = hybrid_candidates(pool, "proof dough overnight", &[1.0, 0.0, 0.0], &settings, 5).await.unwrap();
+        assert_eq!(candidates.len(), 2);
+        assert_eq!(candidates[0].1, candidates[1].1);
+
+        let mut highlights = std::collections::HashMap::new();
+        let mut sources = Vec::new();
+        for (document, score) in candidates {
+            highlights.insert(document.id.clone(), document_highlights(pool, &document.id).await.unwrap());
+            sources.push(RagSource {
+                document_id: Uuid::parse_str(&document.id).unwrap(),
+                title: document.title,
+                snippet: document.content,
+                relevance_score: score,
+                timestamp: None,
+            });
+        }
+        boost_highlighted_sources(&mut sources, &highlights, settings.highlight_boost);
+
+        assert_eq!(sources[0].title, "Baking journal");
+        assert!(sources[0].relevance_score > sources[1].relevance_score);
+        assert_eq!(highlight_factor("Sourdough needs a mature starter.", &highlights[&ids[1]], 2.0), 1.0);
     }
 
     // #[test]
@@ -0,0 +1,331 @@
+//! Re-ranking of retrieved RAG sources
+//!
+//! Embedding retrieval fetches [`RERANK_CANDIDATE_FACTOR`] times more
+//! candidates than needed; a [`Reranker`] then re-scores them against the
+//! query text and the best ones are kept. The built-in stages are a BM25
+//! keyword boost and an optional cross-encoder model, and stages can be
+//! chained with [`RerankerChain`].
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use async_trait::async_trait;
+use candle_core::{DType, Device, Tensor};
+use candle_nn::{Linear, Module, VarBuilder};
+use candle_transformers::models::bert::{BertModel, Config as BertConfig};
+use tokenizers::{Tokenizer, TruncationParams};
+use tracing::info;
+
+use crate::{CodexError, CodexResult};
+use super::rag::RagSource;
+
+/// Candidates retrieved per requested source when a reranker is active
+pub const RERANK_CANDIDATE_FACTOR: usize = 4;
+/// Share of the keyword score in the blended relevance score
+pub const KEYWORD_WEIGHT: f32 = 0.3;
+/// Directory under `models_dir` holding an optional cross-encoder model
+pub const CROSS_ENCODER_DIR: &str = "cross-encoder";
+/// BM25 term saturation (SQLite FTS5 default)
+const BM25_K1: f32 = 1.2;
+/// BM25 length normalization (SQLite FTS5 default)
+const BM25_B: f32 = 0.75;
+/// Longest query/passage pair scored by the cross-encoder, in tokens
+const CROSS_ENCODER_MAX_TOKENS: usize = 512;
+
+/// A re-scoring stage applied to retrieved sources
+#[async_trait]
+pub trait Reranker: Send + Sync + std::fmt::Debug {
+    /// Name recorded in [`RagResponse::reranker`](super::RagResponse::reranker)
+    fn name(&self) -> String;
+
+    /// Re-score candidates against the query, returning them best first
+    async fn rerank(&self, query: &str, candidates: Vec<RagSource>) -> CodexResult<Vec<RagSource>>;
+}
+
+/// Blends embedding similarity with BM25 keyword relevance
+///
+/// BM25 statistics are computed over the candidate set, using the same
+/// parameters as SQLite FTS5 so scores behave like the full-text search.
+#[derive(Debug, Clone)]
+pub struct KeywordReranker {
+    /// Share of the (max-normalized) BM25 score in the new relevance score
+    pub weight: f32,
+}
+
+impl Default for KeywordReranker {
+    fn default() -> Self {
+        Self { weight: KEYWORD_WEIGHT }
+    }
+}
+
+#[async_trait]
+impl Reranker for KeywordReranker {
+    fn name(&self) -> String {
+        "bm25".to_string()
+    }
+
+    async fn rerank(&self, query: &str, mut candidates: Vec<RagSource>) -> CodexResult<Vec<RagSource>> {
+        let passages: Vec<String> = candidates
+            .iter()
+            .map(|c| format!("{} {}", c.title, c.snippet))
+            .collect();
+        let scores = bm25_scores(query, &passages);
+        let max = scores.iter().cloned().fold(0.0_f32, f32::max);
+
+        if max > 0.0 {
+            for (candidate, score) in candidates.iter_mut().zip(scores) {
+                candidate.relevance_score =
+                    (1.0 - self.weight) * candidate.relevance_score + self.weight * (score / max);
+            }
+        }
+
+        sort_by_relevance(&mut candidates);
+        Ok(candidates)
+    }
+}
+
+/// Scores query/passage pairs with a BERT cross-encoder (e.g. ms-marco-MiniLM)
+///
+/// The model directory must contain `config.json`, `tokenizer.json` and
+/// `model.safetensors`. The relevance score becomes the sigmoid of the
+/// model's logit.
+pub struct CrossEncoderReranker {
+    model_dir: PathBuf,
+    model: Arc<CrossEncoderModel>,
+}
+
+struct CrossEncoderModel {
+    bert: BertModel,
+    pooler: Linear,
+    classifier: Linear,
+    tokenizer: Tokenizer,
+    device: Device,
+}
+
+impl std::fmt::Debug for CrossEncoderReranker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CrossEncoderReranker")
+            .field("model_dir", &self.model_dir)
+            .finish()
+    }
+}
+
+impl CrossEncoderReranker {
+    /// Load a cross-encoder model from a directory
+    pub fn load(model_dir: &Path) -> CodexResult<Self> {
+        let model_error = |e: candle_core::Error| CodexError::ai_inference(format!("Failed to load cross-encoder: {}", e));
+
+        let config: BertConfig = serde_json::from_str(&std::fs::read_to_string(model_dir.join("config.json"))?)
+            .map_err(|e| CodexError::ai_inference(format!("Invalid cross-encoder config: {}", e)))?;
+
+        let mut tokenizer = Tokenizer::from_file(model_dir.join("tokenizer.json"))
+            .map_err(|e| CodexError::ai_inference(format!("Failed to load cross-encoder tokenizer: {}", e)))?;
+        tokenizer
+            .with_truncation(Some(TruncationParams {
+                max_length: CROSS_ENCODER_MAX_TOKENS,
+                ..Default::default()
+            }))
+            .map_err(|e| CodexError::ai_inference(format!("Failed to configure tokenizer: {}", e)))?;
+
+        let device = Device::Cpu;
+        // SAFETY: the weights file is not modified while the model is loaded
+        let vb = unsafe {
+            VarBuilder::from_mmaped_safetensors(&[model_dir.join("model.safetensors")], DType::F32, &device)
+        }
+        .map_err(model_error)?;
+
+        let bert = BertModel::load(vb.clone(), &config).map_err(model_error)?;
+        let pooler = candle_nn::linear(config.hidden_size, config.hidden_size, vb.pp("bert.pooler.dense"))
+            .map_err(model_error)?;
+        let classifier = candle_nn::linear(config.hidden_size, 1, vb.pp("classifier")).map_err(model_error)?;
+
+        info!("Loaded cross-encoder reranker from {}", model_dir.display());
+        Ok(Self {
+            model_dir: model_dir.to_path_buf(),
+            model: Arc::new(CrossEncoderModel { bert, pooler, classifier, tokenizer, device }),
+        })
+    }
+}
+
+impl CrossEncoderModel {
+    /// Relevance logit of a query/passage pair
+    fn score(&self, query: &str, passage: &str) -> CodexResult<f32> {
+        let encoding = self
+            .tokenizer
+            .encode((query, passage), true)
+            .map_err(|e| CodexError::ai_inference(format!("Cross-encoder tokenization failed: {}", e)))?;
+
+        let run = || -> candle_core::Result<f32> {
+            let input_ids = Tensor::new(encoding.get_ids(), &self.device)?.unsqueeze(0)?;
+            let type_ids = Tensor::new(encoding.get_type_ids(), &self.device)?.unsqueeze(0)?;
+            let mask = Tensor::new(encoding.get_attention_mask(), &self.device)?.unsqueeze(0)?;
+
+            let hidden = self.bert.forward(&input_ids, &type_ids, Some(&mask))?;
+            let cls = hidden.get(0)?.get(0)?.unsqueeze(0)?;
+            let pooled = self.pooler.forward(&cls)?.tanh()?;
+            self.classifier.forward(&pooled)?.flatten_all()?.get(0)?.to_scalar::<f32>()
+        };
+
+        run().map_err(|e| CodexError::ai_inference(format!("Cross-encoder inference failed: {}", e)))
+    }
+}
+
+#[async_trait]
+impl Reranker for CrossEncoderReranker {
+    fn name(&self) -> String {
+        format!(
+            "cross-encoder:{}",
+            self.model_dir.file_name().map(|n| n.to_string_lossy()).unwrap_or_default()
+        )
+    }
+
+    async fn rerank(&self, query: &str, candidates: Vec<RagSource>) -> CodexResult<Vec<RagSource>> {
+        let model = self.model.clone();
+        let query = query.to_string();
+
+        tokio::task::spawn_blocking(move || {
+            let mut candidates = candidates;
+            for candidate in candidates.iter_mut() {
+                let logit = model.score(&query, &candidate.snippet)?;
+                candidate.relevance_score = 1.0 / (1.0 + (-logit).exp());
+            }
+            sort_by_relevance(&mut candidates);
+            Ok(candidates)
+        })
+        .await
+        .map_err(|e| CodexError::internal(format!("Cross-encoder task failed: {}", e)))?
+    }
+}
+
+/// Runs rerankers in sequence, each re-scoring the previous one's output
+#[derive(Debug, Clone)]
+pub struct RerankerChain {
+    stages: Vec<Arc<dyn Reranker>>,
+}
+
+impl RerankerChain {
+    pub fn new(stages: Vec<Arc<dyn Reranker>>) -> Self {
+        Self { stages }
+    }
+}
+
+#[async_trait]
+impl Reranker for RerankerChain {
+    fn name(&self) -> String {
+        self.stages.iter().map(|s| s.name()).collect::<Vec<_>>().join("+")
+    }
+
+    async fn rerank(&self, query: &str, mut candidates: Vec<RagSource>) -> CodexResult<Vec<RagSource>> {
+        for stage in &self.stages {
+            candidates = stage.rerank(query, candidates).await?;
+        }
+        Ok(candidates)
+    }
+}
+
+/// Default reranker: the keyword boost, followed by the cross-encoder when
+/// one is installed under `models_dir`
+pub fn default_reranker(models_dir: &Path) -> Arc<dyn Reranker> {
+    let keyword: Arc<dyn Reranker> = Arc::new(KeywordReranker::default());
+    let cross_encoder_dir = models_dir.join(CROSS_ENCODER_DIR);
+    if !cross_encoder_dir.join("model.safetensors").exists() {
+        return keyword;
+    }
+
+    match CrossEncoderReranker::load(&cross_encoder_dir) {
+        Ok(cross_encoder) => Arc::new(RerankerChain::new(vec![keyword, Arc::new(cross_encoder)])),
+        Err(e) => {
+            tracing::warn!("Cross-encoder unavailable, using keyword re-ranking only: {}", e);
+            keyword
+        }
+    }
+}
+
+/// BM25 score of each passage for the query, using the passages as the corpus
+pub fn bm25_scores(query: &str, passages: &[String]) -> Vec<f32> {
+    let documents: Vec<Vec<String>> = passages.iter().map(|p| tokenize(p)).collect();
+    if documents.is_empty() {
+        return Vec::new();
+    }
+
+    let average_length =
+        (documents.iter().map(Vec::len).sum::<usize>() as f32 / documents.len() as f32).max(1.0);
+    let query_terms: HashSet<String> = tokenize(query).into_iter().collect();
+    let count = documents.len() as f32;
+
+    let idf: HashMap<&str, f32> = query_terms
+        .iter()
+        .map(|term| {
+            let containing = documents.iter().filter(|d| d.contains(term)).count() as f32;
+            (term.as_str(), ((count - containing + 0.5) / (containing + 0.5) + 1.0).ln())
+        })
+        .collect();
+
+    documents
+        .iter()
+        .map(|document| {
+            let length_norm = 1.0 - BM25_B + BM25_B * document.len() as f32 / average_length;
+            idf.iter()
+                .map(|(term, idf)| {
+                    let frequency = document.iter().filter(|t| t == term).count() as f32;
+                    idf * frequency * (BM25_K1 + 1.0) / (frequency + BM25_K1 * length_norm)
+                })
+                .sum()
+        })
+        .collect()
+}
+
+/// Lowercased alphanumeric tokens, like the FTS5 `unicode61` tokenizer
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn sort_by_relevance(sources: &mut [RagSource]) {
+    sources.sort_by(|a, b| b.relevance_score.total_cmp(&a.relevance_score));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(title: &str, snippet: &str, relevance_score: f32) -> RagSource {
+        RagSource {
+            document_id: uuid::Uuid::new_v4(),
+            title: title.to_string(),
+            snippet: snippet.to_string(),
+            relevance_score,
+            timestamp: None,
+        }
+    }
+
+    #[test]
+    fn test_bm25_prefers_passages_with_rare_query_terms() {
+        let passages = vec![
+            "Sourdough bread needs a starter culture.".to_string(),
+            "Bread and butter pudding uses stale bread.".to_string(),
+            "The starter motor of the car failed.".to_string(),
+        ];
+        let scores = bm25_scores("sourdough starter", &passages);
+
+        assert!(scores[0] > scores[2] && scores[2] > scores[1]);
+        assert_eq!(scores[1], 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_keyword_reranker_promotes_lexical_match() {
+        let candidates = vec![
+            source("Baking", "Tips on oven temperatures for cakes.", 0.62),
+            source("Sourdough", "Feed the sourdough starter daily with flour.", 0.55),
+        ];
+        let chain = RerankerChain::new(vec![Arc::new(KeywordReranker::default())]);
+
+        let ranked = chain.rerank("how often to feed a sourdough starter", candidates).await.unwrap();
+
+        assert_eq!(ranked[0].title, "Sourdough");
+        assert!(ranked.iter().all(|s| (0.0..=1.0).contains(&s.relevance_score)));
+        assert_eq!(chain.name(), "bm25");
+    }
+}
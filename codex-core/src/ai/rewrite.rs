@@ -0,0 +1,157 @@
+//! Query rewriting before RAG retrieval
+//!
+//! Follow-up questions such as "what about the side effects?" retrieve
+//! poorly on their own. The model rewrites them into a standalone search
+//! query using recent conversation history, and suggests up to
+//! [`MAX_EXPANSIONS`] alternative phrasings that are retrieved as well. When
+//! the model is unavailable, a heuristic appends topic words from the
+//! previous user messages instead.
+
+use crate::db::models::ConversationMessage;
+use super::confidence::content_words;
+
+/// Most alternative phrasings retrieved besides the standalone query
+pub const MAX_EXPANSIONS: usize = 2;
+/// Topic words the heuristic appends to a follow-up query
+const TOPIC_WORDS: usize = 4;
+/// Queries with at least this many content words are treated as standalone by the heuristic
+const STANDALONE_CONTENT_WORDS: usize = 4;
+
+/// A query prepared for retrieval
+#[derive(Debug, Clone, PartialEq)]
+pub struct RewrittenQuery {
+    /// Query to retrieve and answer with
+    pub standalone: String,
+    /// Alternative phrasings, retrieved alongside the standalone query
+    pub variants: Vec<String>,
+}
+
+impl RewrittenQuery {
+    /// The query unchanged, without variants
+    pub fn unchanged(query: &str) -> Self {
+        Self {
+            standalone: query.to_string(),
+            variants: Vec::new(),
+        }
+    }
+
+    /// All queries to retrieve with, standalone query first
+    pub fn all(&self) -> Vec<&str> {
+        std::iter::once(self.standalone.as_str())
+            .chain(self.variants.iter().map(String::as_str))
+            .collect()
+    }
+}
+
+/// Prompt asking for a standalone query and alternative phrasings
+pub fn rewrite_prompt(history: &[ConversationMessage], query: &str) -> String {
+    let mut prompt = String::from(
+        "Rewrite the final question as a standalone search query that can be understood without the conversation, \
+         then give alternative phrasings using different words. Reply exactly in this format:\n\
+         Query: <standalone query>\nAlternative: <phrasing>\nAlternative: <phrasing>\n\n",
+    );
+
+    if !history.is_empty() {
+        prompt.push_str("Conversation:\n");
+        for message in history {
+            let speaker = if message.role == ConversationMessage::ROLE_USER { "User" } else { "Assistant" };
+            prompt.push_str(&format!("{}: {}\n", speaker, message.content.trim()));
+        }
+        prompt.push('\n');
+    }
+
+    prompt.push_str(&format!("Final question: {}\n", query.trim()));
+    prompt
+}
+
+/// Parse a reply to [`rewrite_prompt`]
+///
+/// Returns `None` when the reply has no `Query:` line.
+pub fn parse_rewrite(reply: &str) -> Option<RewrittenQuery> {
+    let field = |line: &str, name: &str| {
+        line.trim()
+            .strip_prefix(name)
+            .map(|rest| rest.trim().trim_matches('"').trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+
+    let standalone = reply.lines().find_map(|line| field(line, "Query:"))?;
+    let mut variants: Vec<String> = Vec::new();
+    for variant in reply.lines().filter_map(|line| field(line, "Alternative:")) {
+        let duplicate = variant.eq_ignore_ascii_case(&standalone)
+            || variants.iter().any(|v| v.eq_ignore_ascii_case(&variant));
+        if !duplicate && variants.len() < MAX_EXPANSIONS {
+            variants.push(variant);
+        }
+    }
+
+    Some(RewrittenQuery { standalone, variants })
+}
+
+/// Append topic words from recent user messages to a short follow-up query
+pub fn heuristic_rewrite(history: &[ConversationMessage], query: &str) -> RewrittenQuery {
+    let query_words = content_words(query);
+    if query_words.len() >= STANDALONE_CONTENT_WORDS {
+        return RewrittenQuery::unchanged(query);
+    }
+
+    let mut topics: Vec<String> = Vec::new();
+    for message in history.iter().rev().filter(|m| m.role == ConversationMessage::ROLE_USER) {
+        for word in content_words(&message.content) {
+            if !query_words.contains(&word) && !topics.contains(&word) && topics.len() < TOPIC_WORDS {
+                topics.push(word);
+            }
+        }
+    }
+
+    if topics.is_empty() {
+        return RewrittenQuery::unchanged(query);
+    }
+    RewrittenQuery {
+        standalone: format!("{} {}", query.trim(), topics.join(" ")),
+        variants: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history() -> Vec<ConversationMessage> {
+        vec![
+            ConversationMessage::new(None, None, ConversationMessage::ROLE_USER, "How does ibuprofen reduce inflammation?".to_string()),
+            ConversationMessage::new(None, None, ConversationMessage::ROLE_ASSISTANT, "It blocks COX enzymes.".to_string()),
+        ]
+    }
+
+    #[test]
+    fn test_parse_rewrite() {
+        let reply = "Query: ibuprofen side effects\nAlternative: adverse effects of ibuprofen\n\
+                     Alternative: Ibuprofen side effects\nAlternative: NSAID risks\nAlternative: extra";
+        let rewritten = parse_rewrite(reply).unwrap();
+
+        assert_eq!(rewritten.standalone, "ibuprofen side effects");
+        assert_eq!(rewritten.variants, vec!["adverse effects of ibuprofen", "NSAID risks"]);
+        assert_eq!(rewritten.all().len(), 3);
+        assert!(parse_rewrite("I cannot help with that.").is_none());
+    }
+
+    #[test]
+    fn test_heuristic_appends_recent_topic() {
+        let rewritten = heuristic_rewrite(&history(), "what about the side effects?");
+        assert!(rewritten.standalone.starts_with("what about the side effects?"));
+        assert!(rewritten.standalone.contains("ibuprofen"));
+
+        let standalone = "Which enzymes does aspirin inhibit irreversibly?";
+        assert_eq!(heuristic_rewrite(&history(), standalone), RewrittenQuery::unchanged(standalone));
+    }
+
+    #[test]
+    fn test_rewrite_prompt_includes_history() {
+        let prompt = rewrite_prompt(&history(), "what about the side effects?");
+
+        assert!(prompt.contains("User: How does ibuprofen reduce inflammation?\nAssistant: It blocks COX enzymes.\n"));
+        assert!(prompt.ends_with("Final question: what about the side effects?\n"));
+        assert!(!rewrite_prompt(&[], "q").contains("Conversation:"));
+    }
+}
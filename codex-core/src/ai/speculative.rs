@@ -0,0 +1,300 @@
+//! Speculative decoding with a small draft model
+//!
+//! A draft model sharing the main model's tokenizer proposes a few tokens,
+//! which the main model then scores in one forward pass. Each position is
+//! sampled from the main model's logits as usual, and the drafted tokens are
+//! kept for as long as they agree with those samples; the first disagreement
+//! replaces the rest of the draft. The output therefore follows the main
+//! model exactly, and every step yields at least one token, but when the
+//! draft is good several tokens come out of one main-model pass.
+
+use candle_core::Tensor;
+use candle_transformers::generation::LogitsProcessor;
+use serde::{Deserialize, Serialize};
+
+use super::prefix_cache::{Generation, PrefixState, TokenModel};
+use crate::{CodexError, CodexResult};
+
+/// Drafted tokens and how many the main model agreed with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct SpeculativeStats {
+    pub drafted_tokens: u64,
+    pub accepted_tokens: u64,
+    /// Share of drafted tokens accepted
+    pub acceptance_rate: f64,
+    /// Tokens generated per second of generation, with or without a draft
+    pub tokens_per_second: f64,
+}
+
+/// Result of a speculative generation
+#[derive(Debug)]
+pub struct SpeculativeGeneration<S> {
+    /// Tokens and main-model state, as without speculation
+    pub generation: Generation<S>,
+    pub drafted_tokens: usize,
+    pub accepted_tokens: usize,
+}
+
+/// Generate up to `max_new_tokens` after `prompt`, drafting `draft_tokens` at a time
+///
+/// `reused` is a main-model state covering the first tokens of `prompt`, as
+/// for [`super::prefix_cache::generate`]; the draft model always starts from
+/// scratch. `on_token` is called with the tokens generated so far.
+#[allow(clippy::too_many_arguments)]
+pub fn generate<M: TokenModel, D: TokenModel>(
+    model: &M,
+    draft: &D,
+    prompt: &[u32],
+    reused: Option<(M::State, usize)>,
+    sampler: &mut LogitsProcessor,
+    draft_tokens: usize,
+    max_new_tokens: usize,
+    eos_tokens: &[u32],
+    mut on_token: impl FnMut(&[u32]),
+) -> CodexResult<SpeculativeGeneration<M::State>> {
+    if prompt.is_empty() {
+        return Err(CodexError::validation("Cannot generate from an empty prompt"));
+    }
+    let draft_tokens = draft_tokens.max(1);
+
+    let (mut state, start) = match reused {
+        Some((state, len)) if len < prompt.len() => (state, len),
+        _ => (model.new_state()?, 0),
+    };
+    let mut logits = model.forward(&prompt[start..], start, &mut state)?;
+    let mut draft_state = draft.new_state()?;
+    let mut draft_logits = draft.forward(prompt, 0, &mut draft_state)?;
+
+    let mut processed = prompt.to_vec();
+    let mut tokens = Vec::new();
+    let mut drafted_tokens = 0;
+    let mut accepted_tokens = 0;
+
+    while tokens.len() < max_new_tokens {
+        let position = processed.len();
+        // The main model adds a token of its own after the draft
+        let budget = draft_tokens.min(max_new_tokens - tokens.len() - 1);
+
+        let draft_snapshot = draft_state.clone();
+        let mut proposal = Vec::with_capacity(budget);
+        while proposal.len() < budget {
+            let next = argmax(&draft_logits)?;
+            if eos_tokens.contains(&next) {
+                break;
+            }
+            draft_logits = draft.forward(&[next], position + proposal.len(), &mut draft_state)?;
+            proposal.push(next);
+        }
+        drafted_tokens += proposal.len();
+
+        // One main-model pass scores every drafted position
+        let snapshot = state.clone();
+        let mut scored = vec![logits];
+        scored.extend(model.forward_all(&proposal, position, &mut state)?);
+
+        let mut accepted = 0;
+        let mut next = None;
+        for position_logits in &scored {
+            let sampled = sample(sampler, position_logits)?;
+            if proposal.get(accepted) != Some(&sampled) {
+                next = (!eos_tokens.contains(&sampled)).then_some(sampled);
+                break;
+            }
+            accepted += 1;
+        }
+        accepted_tokens += accepted;
+        let accepted = &proposal[..accepted];
+
+        tokens.extend_from_slice(accepted);
+        if let Some(next) = next {
+            tokens.push(next);
+        }
+        if !accepted.is_empty() || next.is_some() {
+            on_token(&tokens);
+        }
+
+        let Some(next) = next.filter(|_| tokens.len() < max_new_tokens) else {
+            rewind(model, &mut state, snapshot, position, accepted)?;
+            processed.extend_from_slice(accepted);
+            break;
+        };
+        logits = advance(model, &mut state, snapshot, position, accepted, next)?;
+        draft_logits = advance(draft, &mut draft_state, draft_snapshot, position, accepted, next)?;
+        processed.extend_from_slice(accepted);
+        processed.push(next);
+    }
+
+    Ok(SpeculativeGeneration {
+        generation: Generation { tokens, state, processed },
+        drafted_tokens,
+        accepted_tokens,
+    })
+}
+
+/// Leave `state` covering the tokens up to `position` and the `accepted` draft
+///
+/// `snapshot` is the state at `position`, used when `state` cannot be cut back.
+fn rewind<M: TokenModel>(
+    model: &M,
+    state: &mut M::State,
+    snapshot: M::State,
+    position: usize,
+    accepted: &[u32],
+) -> CodexResult<()> {
+    if !state.truncate(position + accepted.len()) {
+        *state = snapshot;
+        if !accepted.is_empty() {
+            model.forward(accepted, position, state)?;
+        }
+    }
+    Ok(())
+}
+
+/// Run `next` after the `accepted` draft and return the logits that follow
+fn advance<M: TokenModel>(
+    model: &M,
+    state: &mut M::State,
+    snapshot: M::State,
+    position: usize,
+    accepted: &[u32],
+    next: u32,
+) -> CodexResult<Tensor> {
+    let kept = position + accepted.len();
+    if state.truncate(kept) {
+        return model.forward(&[next], kept, state);
+    }
+    *state = snapshot;
+    let mut replay = accepted.to_vec();
+    replay.push(next);
+    model.forward(&replay, position, state)
+}
+
+/// Index of the largest logit
+fn argmax(logits: &Tensor) -> CodexResult<u32> {
+    let logits: Vec<f32> = logits
+        .to_dtype(candle_core::DType::F32)
+        .and_then(|logits| logits.to_vec1())
+        .map_err(|e| CodexError::ai_inference(format!("Failed to read logits: {}", e)))?;
+    logits
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(b.1))
+        .map(|(index, _)| index as u32)
+        .ok_or_else(|| CodexError::ai_inference("Empty logits"))
+}
+
+fn sample(sampler: &mut LogitsProcessor, logits: &Tensor) -> CodexResult<u32> {
+    sampler
+        .sample(logits)
+        .map_err(|e| CodexError::ai_inference(format!("Sampling failed: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::prefix_cache;
+    use candle_core::Device;
+    use std::cell::Cell;
+
+    const VOCAB: u32 = 40;
+
+    /// Deterministic model whose logits depend on every token seen and on `salt`
+    struct ToyModel {
+        salt: u32,
+        passes: Cell<usize>,
+    }
+
+    impl ToyModel {
+        fn new(salt: u32) -> Self {
+            Self { salt, passes: Cell::new(0) }
+        }
+
+        fn logits(&self, seen: &[u32]) -> Tensor {
+            let hash = seen.iter().fold(self.salt, |h, t| h.wrapping_mul(31).wrapping_add(*t));
+            let logits: Vec<f32> = (0..VOCAB).map(|v| ((hash ^ v.wrapping_mul(2654435761)) % 1000) as f32).collect();
+            Tensor::from_vec(logits, VOCAB as usize, &Device::Cpu).unwrap()
+        }
+    }
+
+    #[derive(Clone)]
+    struct ToyState {
+        seen: Vec<u32>,
+    }
+
+    impl PrefixState for ToyState {
+        fn size_bytes(&self) -> usize {
+            self.seen.len() * 4
+        }
+
+        fn truncate(&mut self, len: usize) -> bool {
+            self.seen.truncate(len);
+            true
+        }
+    }
+
+    impl TokenModel for ToyModel {
+        type State = ToyState;
+
+        fn new_state(&self) -> CodexResult<ToyState> {
+            Ok(ToyState { seen: Vec::new() })
+        }
+
+        fn forward(&self, tokens: &[u32], position: usize, state: &mut ToyState) -> CodexResult<Tensor> {
+            Ok(self.forward_all(tokens, position, state)?.pop().unwrap())
+        }
+
+        fn forward_all(&self, tokens: &[u32], position: usize, state: &mut ToyState) -> CodexResult<Vec<Tensor>> {
+            assert_eq!(position, state.seen.len());
+            self.passes.set(self.passes.get() + 1);
+            Ok(tokens
+                .iter()
+                .map(|token| {
+                    state.seen.push(*token);
+                    self.logits(&state.seen)
+                })
+                .collect())
+        }
+    }
+
+    fn greedy() -> LogitsProcessor {
+        LogitsProcessor::new(0, None, None)
+    }
+
+    #[test]
+    fn test_output_matches_the_main_model_alone() {
+        let prompt = [3, 1, 4, 1, 5];
+        let main = ToyModel::new(7);
+        let plain = prefix_cache::generate(&main, &prompt, None, &mut greedy(), 12, &[], |_| {}).unwrap();
+        let plain_passes = main.passes.replace(0);
+
+        // A draft identical to the main model is always right
+        let good = generate(&main, &ToyModel::new(7), &prompt, None, &mut greedy(), 4, 12, &[], |_| {}).unwrap();
+        assert_eq!(good.generation.tokens, plain.tokens);
+        assert_eq!(good.generation.processed, plain.processed);
+        assert_eq!(good.accepted_tokens, good.drafted_tokens);
+        assert!(main.passes.replace(0) < plain_passes);
+
+        // A different draft is mostly rejected, without changing the output
+        let mut streamed = Vec::new();
+        let poor = generate(&main, &ToyModel::new(8), &prompt, None, &mut greedy(), 4, 12, &[], |tokens| {
+            streamed = tokens.to_vec()
+        })
+        .unwrap();
+        assert_eq!(poor.generation.tokens, plain.tokens);
+        assert_eq!(streamed, plain.tokens);
+        assert!(poor.accepted_tokens < poor.drafted_tokens);
+    }
+
+    #[test]
+    fn test_stops_at_end_of_sequence() {
+        let prompt = [2, 7, 1, 8];
+        let main = ToyModel::new(7);
+        let plain = prefix_cache::generate(&main, &prompt, None, &mut greedy(), 10, &[], |_| {}).unwrap();
+        let eos = [plain.tokens[6]];
+        let expected = prefix_cache::generate(&main, &prompt, None, &mut greedy(), 10, &eos, |_| {}).unwrap();
+
+        let result = generate(&main, &ToyModel::new(7), &prompt, None, &mut greedy(), 3, 10, &eos, |_| {}).unwrap();
+        assert_eq!(result.generation.tokens, expected.tokens);
+        assert_eq!(result.generation.state.seen, expected.processed);
+    }
+}
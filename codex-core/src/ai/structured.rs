@@ -0,0 +1,359 @@
+//! JSON output from the language model, checked against a schema
+//!
+//! Utility calls ask for a JSON value described by the JSON schema of the
+//! Rust type they expect. The reply is cut out of any surrounding prose or
+//! code fence, checked against the schema and deserialized. When that fails,
+//! the prompt is sent again with the rejected reply and the reason appended,
+//! up to [`STRUCTURED_OUTPUT_ATTEMPTS`] times. Generators that can constrain
+//! sampling to the schema do so through [`TextGenerator::generate_constrained`].
+
+use async_trait::async_trait;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::debug;
+
+use crate::{CodexError, CodexResult};
+
+/// Replies requested before giving up on a structured output
+pub const STRUCTURED_OUTPUT_ATTEMPTS: usize = 3;
+
+/// Something that turns prompts into text
+#[async_trait]
+pub trait TextGenerator: Send + Sync {
+    async fn generate(&self, prompt: &str) -> CodexResult<String>;
+
+    /// Generate with sampling constrained to JSON matching `schema`
+    ///
+    /// `None` when the generator cannot constrain sampling; the reply is then
+    /// only checked after the fact.
+    async fn generate_constrained(&self, _prompt: &str, _schema: &Value) -> Option<CodexResult<String>> {
+        None
+    }
+}
+
+/// Generate a `T` from `prompt`, retrying with the validation error until the reply fits
+pub async fn generate_structured<T, G>(generator: &G, prompt: &str) -> CodexResult<T>
+where
+    T: DeserializeOwned + JsonSchema,
+    G: TextGenerator + ?Sized,
+{
+    let schema = serde_json::to_value(schemars::schema_for!(T))
+        .map_err(|e| CodexError::internal(format!("Failed to build output schema: {}", e)))?;
+    let instructed = format!(
+        "{}\n\nReply with only a JSON value matching this JSON schema, without any other text:\n{}",
+        prompt.trim_end(),
+        schema
+    );
+
+    let mut request = instructed.clone();
+    let mut last_error = String::new();
+    for attempt in 1..=STRUCTURED_OUTPUT_ATTEMPTS {
+        let reply = match generator.generate_constrained(&request, &schema).await {
+            Some(reply) => reply?,
+            None => generator.generate(&request).await?,
+        };
+        match parse_reply(&reply, &schema) {
+            Ok(value) => return Ok(value),
+            Err(error) => {
+                debug!("Structured output attempt {} rejected: {}", attempt, error);
+                request = format!(
+                    "{}\n\nYour previous reply was:\n{}\n\nIt was rejected because {}. Reply again with only the corrected JSON.",
+                    instructed,
+                    reply.trim(),
+                    error
+                );
+                last_error = error;
+            }
+        }
+    }
+
+    Err(CodexError::ai_inference(format!(
+        "Model output did not match the expected JSON after {} attempts: {}",
+        STRUCTURED_OUTPUT_ATTEMPTS, last_error
+    )))
+}
+
+/// Parse and validate a reply against `schema`
+fn parse_reply<T: DeserializeOwned>(reply: &str, schema: &Value) -> Result<T, String> {
+    let json = extract_json(reply).ok_or_else(|| "it contains no JSON".to_string())?;
+    let value: Value = serde_json::from_str(json).map_err(|e| format!("it is not valid JSON ({})", e))?;
+    validate(&value, schema, schema, "the reply")?;
+    serde_json::from_value(value).map_err(|e| format!("it does not have the expected shape ({})", e))
+}
+
+/// The JSON object or array in a reply, without surrounding prose or code fences
+fn extract_json(reply: &str) -> Option<&str> {
+    let start = reply.find(['{', '['])?;
+    let closing = if reply[start..].starts_with('{') { '}' } else { ']' };
+    let end = reply.rfind(closing)?;
+    (end > start).then(|| &reply[start..=end])
+}
+
+/// Check `value` against the parts of JSON schema that schemars emits
+fn validate(value: &Value, schema: &Value, root: &Value, path: &str) -> Result<(), String> {
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        let target = reference
+            .strip_prefix('#')
+            .and_then(|pointer| root.pointer(pointer))
+            .ok_or_else(|| format!("the schema reference {} is unknown", reference))?;
+        return validate(value, target, root, path);
+    }
+    if let Some(all) = schema.get("allOf").and_then(Value::as_array) {
+        for part in all {
+            validate(value, part, root, path)?;
+        }
+    }
+    if let Some(any) = schema.get("anyOf").and_then(Value::as_array) {
+        let errors: Vec<String> = any.iter().filter_map(|part| validate(value, part, root, path).err()).collect();
+        if errors.len() == any.len() {
+            return Err(errors.join("; "));
+        }
+    }
+
+    let types: Vec<&str> = match schema.get("type") {
+        Some(Value::String(name)) => vec![name.as_str()],
+        Some(Value::Array(names)) => names.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    };
+    if !types.is_empty() && !types.iter().any(|name| has_type(value, name)) {
+        return Err(format!("{} should be {}", path, types.join(" or ")));
+    }
+    if let Some(options) = schema.get("enum").and_then(Value::as_array) {
+        if !options.contains(value) {
+            return Err(format!("{} should be one of {}", path, Value::Array(options.clone())));
+        }
+    }
+
+    if let Some(number) = value.as_f64() {
+        if let Some(minimum) = schema.get("minimum").and_then(Value::as_f64) {
+            if number < minimum {
+                return Err(format!("{} should be at least {}", path, minimum));
+            }
+        }
+        if let Some(maximum) = schema.get("maximum").and_then(Value::as_f64) {
+            if number > maximum {
+                return Err(format!("{} should be at most {}", path, maximum));
+            }
+        }
+    }
+
+    if let Value::Object(fields) = value {
+        for required in schema.get("required").and_then(Value::as_array).into_iter().flatten() {
+            if let Some(name) = required.as_str().filter(|name| !fields.contains_key(*name)) {
+                return Err(format!("{} is missing \"{}\"", path, name));
+            }
+        }
+        if let Some(properties) = schema.get("properties").and_then(Value::as_object) {
+            for (name, field) in fields {
+                if let Some(field_schema) = properties.get(name) {
+                    validate(field, field_schema, root, &format!("\"{}\"", name))?;
+                }
+            }
+        }
+    }
+
+    if let (Value::Array(items), Some(item_schema)) = (value, schema.get("items")) {
+        for (i, item) in items.iter().enumerate() {
+            validate(item, item_schema, root, &format!("item {} of {}", i + 1, path))?;
+        }
+    }
+    Ok(())
+}
+
+fn has_type(value: &Value, name: &str) -> bool {
+    match name {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "string" => value.is_string(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        _ => true,
+    }
+}
+
+/// Tags describing a text
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct TagList {
+    /// Short topical tags, most relevant first
+    pub tags: Vec<String>,
+}
+
+/// Difficulty of a text
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct DifficultyRating {
+    /// 1 = beginner, 5 = expert
+    #[schemars(range(min = 1, max = 5))]
+    pub level: i32,
+}
+
+/// Kind of a named entity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EntityKind {
+    Person,
+    Organization,
+    Place,
+    Event,
+    Concept,
+    Other,
+}
+
+/// A named entity mentioned in a text
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Entity {
+    /// Name as written in the text
+    pub name: String,
+    pub kind: EntityKind,
+}
+
+/// Named entities of a text
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct EntityList {
+    pub entities: Vec<Entity>,
+}
+
+/// Up to `max_tags` lowercase tags for `content`
+pub async fn generate_tags<G: TextGenerator + ?Sized>(
+    generator: &G,
+    content: &str,
+    max_tags: usize,
+) -> CodexResult<Vec<String>> {
+    let prompt = format!("Generate up to {} relevant tags for the following content:\n\n{}", max_tags, content);
+    let list: TagList = generate_structured(generator, &prompt).await?;
+
+    let mut tags: Vec<String> = Vec::new();
+    for tag in list.tags {
+        let tag = tag.trim().to_lowercase();
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags.truncate(max_tags);
+    Ok(tags)
+}
+
+/// Difficulty of `content` on a scale of 1 to 5
+pub async fn assess_difficulty<G: TextGenerator + ?Sized>(generator: &G, content: &str) -> CodexResult<i32> {
+    let prompt = format!(
+        "Rate the difficulty level of the following content on a scale of 1-5, where:\n1 = Beginner (basic concepts)\n2 = Elementary (some background needed)\n3 = Intermediate (moderate expertise required)\n4 = Advanced (significant expertise required)\n5 = Expert (deep specialization required)\n\nContent:\n{}",
+        content
+    );
+    let rating: DifficultyRating = generate_structured(generator, &prompt).await?;
+    Ok(rating.level)
+}
+
+/// People, organizations, places, events and concepts named in `content`
+pub async fn extract_entities<G: TextGenerator + ?Sized>(generator: &G, content: &str) -> CodexResult<Vec<Entity>> {
+    let prompt = format!(
+        "List the people, organizations, places, events and key concepts named in the following content:\n\n{}",
+        content
+    );
+    let list: EntityList = generate_structured(generator, &prompt).await?;
+
+    let mut entities: Vec<Entity> = Vec::new();
+    for mut entity in list.entities {
+        entity.name = entity.name.trim().to_string();
+        if !entity.name.is_empty() && !entities.iter().any(|e| e.name.eq_ignore_ascii_case(&entity.name)) {
+            entities.push(entity);
+        }
+    }
+    Ok(entities)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Replies with scripted texts in order and records the prompts it got
+    #[derive(Default)]
+    struct ScriptedGenerator {
+        replies: Mutex<Vec<&'static str>>,
+        prompts: Mutex<Vec<String>>,
+        constrained: bool,
+    }
+
+    impl ScriptedGenerator {
+        fn new(replies: &[&'static str]) -> Self {
+            let mut replies = replies.to_vec();
+            replies.reverse();
+            Self { replies: Mutex::new(replies), ..Default::default() }
+        }
+
+        fn prompts(&self) -> Vec<String> {
+            self.prompts.lock().unwrap().clone()
+        }
+    }
+
+    #[async_trait]
+    impl TextGenerator for ScriptedGenerator {
+        async fn generate(&self, prompt: &str) -> CodexResult<String> {
+            self.prompts.lock().unwrap().push(prompt.to_string());
+            self.replies
+                .lock()
+                .unwrap()
+                .pop()
+                .map(str::to_string)
+                .ok_or_else(|| CodexError::ai_inference("No scripted reply left"))
+        }
+
+        async fn generate_constrained(&self, prompt: &str, schema: &Value) -> Option<CodexResult<String>> {
+            if !self.constrained {
+                return None;
+            }
+            assert!(schema.get("properties").is_some());
+            Some(self.generate(prompt).await)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_malformed_reply_is_retried_with_the_error() {
+        let generator = ScriptedGenerator::new(&[
+            "Sure! Tags: rust, async",
+            "```json\n{\"tags\": [\"Rust\", \" async \", \"rust\", \"\"]}\n```",
+        ]);
+        let tags = generate_tags(&generator, "Async programming in Rust", 5).await.unwrap();
+        assert_eq!(tags, vec!["rust", "async"]);
+
+        let prompts = generator.prompts();
+        assert_eq!(prompts.len(), 2);
+        assert!(prompts[0].contains("JSON schema"));
+        assert!(prompts[1].contains("Sure! Tags: rust, async"));
+        assert!(prompts[1].contains("it contains no JSON"));
+    }
+
+    #[tokio::test]
+    async fn test_schema_violations_are_rejected() {
+        let generator = ScriptedGenerator::new(&["{\"level\": 7}", "{\"level\": \"hard\"}", "{\"level\": 4}"]);
+        assert_eq!(assess_difficulty(&generator, "Category theory").await.unwrap(), 4);
+        let prompts = generator.prompts();
+        assert!(prompts[1].contains("\"level\" should be at most 5"));
+        assert!(prompts[2].contains("\"level\" should be integer"));
+
+        let generator = ScriptedGenerator::new(&[
+            "{\"entities\": [{\"name\": \"Ada Lovelace\", \"kind\": \"wizard\"}]}",
+            "{\"entities\": [{\"name\": \"Ada Lovelace\", \"kind\": \"person\"}, {\"name\": \"London\", \"kind\": \"place\"}]}",
+        ]);
+        let entities = extract_entities(&generator, "Ada Lovelace lived in London.").await.unwrap();
+        assert_eq!(entities[0], Entity { name: "Ada Lovelace".to_string(), kind: EntityKind::Person });
+        assert_eq!(entities[1].kind, EntityKind::Place);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_the_last_attempt() {
+        let generator = ScriptedGenerator::new(&["no", "still no", "{\"tags\": 3}"]);
+        let error = generate_tags(&generator, "text", 3).await.unwrap_err();
+        assert!(error.to_string().contains("after 3 attempts"));
+        assert_eq!(generator.prompts().len(), STRUCTURED_OUTPUT_ATTEMPTS);
+    }
+
+    #[tokio::test]
+    async fn test_constrained_sampling_is_used_when_supported() {
+        let generator = ScriptedGenerator { constrained: true, ..ScriptedGenerator::new(&["{\"level\": 2}"]) };
+        assert_eq!(assess_difficulty(&generator, "Counting").await.unwrap(), 2);
+    }
+}
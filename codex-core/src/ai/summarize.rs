@@ -0,0 +1,158 @@
+//! Hierarchical (map-reduce) summarization of long texts
+//!
+//! Texts that do not fit the model's context window are split into
+//! token-sized windows on paragraph, sentence or word boundaries. Each window
+//! is summarized on its own, and the partial summaries are then summarized
+//! again until a single summary remains.
+
+use serde::{Deserialize, Serialize};
+
+/// Rough characters-per-token ratio used for budgeting prompts
+pub const CHARS_PER_TOKEN: usize = 4;
+/// Tokens reserved for the instruction text wrapped around each chunk
+pub const PROMPT_OVERHEAD_TOKENS: usize = 64;
+/// Target length in words of each partial summary
+pub const CHUNK_SUMMARY_WORDS: usize = 120;
+/// Chunk summaries generated concurrently during the map step
+pub const MAP_CONCURRENCY: usize = 2;
+
+/// Phase of a long summarization
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SummaryStage {
+    /// Summarizing chunks of the original text
+    Map,
+    /// Summarizing partial summaries
+    Reduce,
+}
+
+/// Progress of a long summarization
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SummaryProgress {
+    pub stage: SummaryStage,
+    /// Reduce level, starting at 1 (always 0 for the map stage)
+    pub level: usize,
+    pub completed: usize,
+    pub total: usize,
+}
+
+/// Approximate token count of a text
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN)
+}
+
+/// Split text into windows of at most `max_tokens` (estimated) tokens
+///
+/// Paragraphs are kept together where possible; oversized paragraphs fall
+/// back to sentence and then word boundaries.
+pub fn split_into_windows(text: &str, max_tokens: usize) -> Vec<String> {
+    let max_chars = max_tokens.max(1) * CHARS_PER_TOKEN;
+    let mut windows = Vec::new();
+    let mut current = String::new();
+
+    let push_piece = |piece: &str, separator: &str, current: &mut String, windows: &mut Vec<String>| {
+        if !current.is_empty() && current.chars().count() + separator.len() + piece.chars().count() > max_chars {
+            windows.push(std::mem::take(current));
+        }
+        if !current.is_empty() {
+            current.push_str(separator);
+        }
+        current.push_str(piece);
+    };
+
+    for paragraph in text.split("\n\n").map(str::trim).filter(|p| !p.is_empty()) {
+        if paragraph.chars().count() <= max_chars {
+            push_piece(paragraph, "\n\n", &mut current, &mut windows);
+            continue;
+        }
+
+        for sentence in split_sentences(paragraph) {
+            if sentence.chars().count() <= max_chars {
+                push_piece(sentence, " ", &mut current, &mut windows);
+                continue;
+            }
+            for word in sentence.split_whitespace() {
+                let word: String = word.chars().take(max_chars).collect();
+                push_piece(&word, " ", &mut current, &mut windows);
+            }
+        }
+    }
+
+    if !current.is_empty() {
+        windows.push(current);
+    }
+    windows
+}
+
+/// Split a paragraph after sentence-ending punctuation
+fn split_sentences(paragraph: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut chars = paragraph.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        let at_boundary = matches!(c, '.' | '!' | '?')
+            && chars.peek().map(|(_, next)| next.is_whitespace()).unwrap_or(true);
+        if at_boundary {
+            let end = i + c.len_utf8();
+            let sentence = paragraph[start..end].trim();
+            if !sentence.is_empty() {
+                sentences.push(sentence);
+            }
+            start = end;
+        }
+    }
+
+    let rest = paragraph[start..].trim();
+    if !rest.is_empty() {
+        sentences.push(rest);
+    }
+    sentences
+}
+
+/// Prompt summarizing one chunk of a longer text
+pub fn chunk_prompt(chunk: &str, index: usize, total: usize) -> String {
+    format!(
+        "The following is part {} of {} of a longer document. Summarize this part in approximately {} words, \
+         keeping names, figures and conclusions:\n\n{}",
+        index + 1,
+        total,
+        CHUNK_SUMMARY_WORDS,
+        chunk
+    )
+}
+
+/// Prompt combining partial summaries
+pub fn reduce_prompt(summaries: &str, max_words: usize) -> String {
+    format!(
+        "The following are summaries of consecutive parts of one document. Combine them into a single coherent \
+         summary of approximately {} words:\n\n{}",
+        max_words, summaries
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_windows_respect_budget_and_boundaries() {
+        let paragraph = "One sentence here. Another sentence follows! ".repeat(20);
+        let text = format!("Intro paragraph.\n\n{}\n\nClosing words.", paragraph.trim());
+
+        let windows = split_into_windows(&text, 50);
+
+        assert!(windows.len() > 2);
+        assert!(windows.iter().all(|w| estimate_tokens(w) <= 50));
+        assert!(windows[0].starts_with("Intro paragraph."));
+        assert!(windows.last().unwrap().ends_with("Closing words."));
+        assert!(windows[1..].iter().all(|w| !w.starts_with(' ')));
+    }
+
+    #[test]
+    fn test_short_text_is_single_window() {
+        assert_eq!(split_into_windows("a\n\nb", 100), vec!["a\n\nb".to_string()]);
+        assert!(split_into_windows("   ", 100).is_empty());
+        assert_eq!(estimate_tokens("abcdefgh"), 2);
+    }
+}
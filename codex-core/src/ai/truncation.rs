@@ -0,0 +1,116 @@
+//! Fitting long prompts into the context window
+//!
+//! Callers such as summarization and RAG cannot easily measure a prompt in
+//! tokens before sending it, so a prompt longer than
+//! [`AiConfig::prompt_token_limit`](crate::config::AiConfig::prompt_token_limit)
+//! is cut down at tokenization according to the configured
+//! [`TruncationPolicy`], and the output reports what was dropped.
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::TruncationPolicy;
+use crate::{CodexError, CodexResult};
+
+/// How a prompt was cut down to fit the context
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PromptTruncation {
+    pub policy: TruncationPolicy,
+    /// Prompt tokens before truncation
+    pub original_tokens: usize,
+    /// Prompt tokens the model saw
+    pub kept_tokens: usize,
+}
+
+impl PromptTruncation {
+    pub fn dropped_tokens(&self) -> usize {
+        self.original_tokens - self.kept_tokens
+    }
+}
+
+/// Cut `tokens` down to at most `limit` according to `policy`
+///
+/// Prompts within the limit are returned unchanged with no truncation;
+/// [`TruncationPolicy::Error`] rejects longer ones with a validation error.
+pub fn truncate_tokens(
+    mut tokens: Vec<u32>,
+    limit: usize,
+    policy: TruncationPolicy,
+) -> CodexResult<(Vec<u32>, Option<PromptTruncation>)> {
+    let original_tokens = tokens.len();
+    if original_tokens <= limit {
+        return Ok((tokens, None));
+    }
+
+    match policy {
+        TruncationPolicy::Error => {
+            return Err(CodexError::validation(format!(
+                "Prompt too long: {} tokens, max: {}",
+                original_tokens, limit
+            )));
+        }
+        TruncationPolicy::TruncateHead => {
+            tokens.drain(..original_tokens - limit);
+        }
+        TruncationPolicy::TruncateTail => {
+            tokens.truncate(limit);
+        }
+        TruncationPolicy::TruncateMiddle => {
+            let head = limit.div_ceil(2);
+            tokens.drain(head..original_tokens - (limit - head));
+        }
+    }
+
+    Ok((tokens, Some(PromptTruncation { policy, original_tokens, kept_tokens: limit })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLICIES: [TruncationPolicy; 4] = [
+        TruncationPolicy::Error,
+        TruncationPolicy::TruncateHead,
+        TruncationPolicy::TruncateTail,
+        TruncationPolicy::TruncateMiddle,
+    ];
+
+    #[test]
+    fn test_prompt_exactly_at_the_limit_is_untouched() {
+        let tokens: Vec<u32> = (0..8).collect();
+        for policy in POLICIES {
+            let (kept, truncation) = truncate_tokens(tokens.clone(), 8, policy).unwrap();
+            assert_eq!(kept, tokens, "{:?}", policy);
+            assert_eq!(truncation, None, "{:?}", policy);
+        }
+    }
+
+    #[test]
+    fn test_one_token_over_the_limit() {
+        let tokens: Vec<u32> = (0..9).collect();
+
+        let error = truncate_tokens(tokens.clone(), 8, TruncationPolicy::Error).unwrap_err();
+        assert!(error.to_string().contains("9 tokens, max: 8"), "{}", error);
+
+        let (kept, truncation) = truncate_tokens(tokens.clone(), 8, TruncationPolicy::TruncateHead).unwrap();
+        assert_eq!(kept, (1..9).collect::<Vec<_>>());
+        assert_eq!(truncation.unwrap().dropped_tokens(), 1);
+
+        let (kept, _) = truncate_tokens(tokens.clone(), 8, TruncationPolicy::TruncateTail).unwrap();
+        assert_eq!(kept, (0..8).collect::<Vec<_>>());
+
+        let (kept, truncation) = truncate_tokens(tokens, 8, TruncationPolicy::TruncateMiddle).unwrap();
+        assert_eq!(kept, vec![0, 1, 2, 3, 5, 6, 7, 8]);
+        assert_eq!(
+            truncation,
+            Some(PromptTruncation { policy: TruncationPolicy::TruncateMiddle, original_tokens: 9, kept_tokens: 8 })
+        );
+    }
+
+    #[test]
+    fn test_middle_truncation_keeps_both_ends_of_an_odd_limit() {
+        let tokens: Vec<u32> = (0..20).collect();
+        let (kept, truncation) = truncate_tokens(tokens, 5, TruncationPolicy::TruncateMiddle).unwrap();
+        assert_eq!(kept, vec![0, 1, 2, 18, 19]);
+        assert_eq!(truncation.unwrap().dropped_tokens(), 15);
+    }
+}
@@ -0,0 +1,400 @@
+//! Local HTTP API for integrations
+//!
+//! A REST surface over the content and AI engines for editors, browser
+//! extensions and scripts. The server only listens on 127.0.0.1 and every
+//! request must carry `Authorization: Bearer <token>`; the token is
+//! generated on first start and kept in the settings table.
+//!
+//! | Method | Path               | Maps to                               |
+//! |--------|--------------------|---------------------------------------|
+//! | GET    | `/documents`       | recent documents, or one category     |
+//! | GET    | `/documents/{id}`  | [`ContentManager::get_document`]      |
+//! | POST   | `/search`          | [`ContentManager::search_documents`]  |
+//! | POST   | `/rag`             | [`AiEngine::rag_query`] (SSE with `stream`) |
+//! | POST   | `/import-text`     | [`ContentManager::import_text_content`] |
+//!
+//! [`ContentManager::get_document`]: crate::content::ContentManager::get_document
+//! [`ContentManager::search_documents`]: crate::content::ContentManager::search_documents
+//! [`ContentManager::import_text_content`]: crate::content::ContentManager::import_text_content
+//! [`AiEngine::rag_query`]: crate::ai::AiEngine::rag_query
+
+use std::convert::Infallible;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::ai::AiEngine;
+use crate::content::{ContentManager, SearchOptions};
+use crate::db::models::{Document, Setting};
+use crate::db::{DatabaseManager, SettingQueries};
+use crate::{CodexError, CodexResult};
+
+/// Settings key of the bearer token
+pub const API_TOKEN_SETTING: &str = "api_server.token";
+
+/// Documents returned by `GET /documents` when no limit is given
+const DEFAULT_LIST_LIMIT: i64 = 50;
+/// Most documents returned by one `GET /documents`
+const MAX_LIST_LIMIT: i64 = 500;
+/// Most results returned by one `POST /search`
+const MAX_SEARCH_LIMIT: usize = 200;
+/// Most documents given to the model as context by one `POST /rag`
+const MAX_RAG_CONTEXT: usize = 20;
+/// How long stopping waits for open requests (e.g. streams) before aborting them
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
+
+/// Address and credentials of a running server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiServerInfo {
+    pub address: SocketAddr,
+    pub url: String,
+    pub token: String,
+}
+
+/// A running API server; stopped with [`ApiServer::stop`]
+#[derive(Debug)]
+pub struct ApiServer {
+    info: ApiServerInfo,
+    shutdown: oneshot::Sender<()>,
+    handle: JoinHandle<()>,
+}
+
+impl ApiServer {
+    /// Bind to `127.0.0.1:port` (0 picks a free port) and start serving
+    pub async fn start(
+        db: Arc<DatabaseManager>,
+        ai: Arc<AiEngine>,
+        content: Arc<ContentManager>,
+        port: u16,
+    ) -> CodexResult<Self> {
+        let token = load_or_create_token(&db).await?;
+        let listener = tokio::net::TcpListener::bind((Ipv4Addr::LOCALHOST, port)).await?;
+        let address = listener.local_addr()?;
+        let app = router(ApiState { ai, content, token: Arc::from(token.as_str()) });
+
+        let (shutdown, signal) = oneshot::channel::<()>();
+        let handle = tokio::spawn(async move {
+            let shutdown_signal = async {
+                let _ = signal.await;
+            };
+            if let Err(e) = axum::serve(listener, app).with_graceful_shutdown(shutdown_signal).await {
+                warn!("API server stopped with an error: {}", e);
+            }
+        });
+
+        info!("API server listening on http://{}", address);
+        Ok(Self {
+            info: ApiServerInfo {
+                address,
+                url: format!("http://{}", address),
+                token,
+            },
+            shutdown,
+            handle,
+        })
+    }
+
+    pub fn info(&self) -> &ApiServerInfo {
+        &self.info
+    }
+
+    /// Stop accepting connections and wait for open requests to finish
+    pub async fn stop(self) {
+        let _ = self.shutdown.send(());
+        let mut handle = self.handle;
+        if tokio::time::timeout(SHUTDOWN_GRACE, &mut handle).await.is_err() {
+            warn!("API server requests still open after {:?}, aborting them", SHUTDOWN_GRACE);
+            handle.abort();
+        }
+        info!("API server stopped");
+    }
+}
+
+/// Bearer token clients authenticate with, generated on first use
+pub async fn load_or_create_token(db: &DatabaseManager) -> CodexResult<String> {
+    if let Some(token) = SettingQueries::get(db.pool(), API_TOKEN_SETTING)
+        .await?
+        .and_then(|setting| setting.get_value::<String>())
+        .filter(|token| !token.is_empty())
+    {
+        return Ok(token);
+    }
+    regenerate_token(db).await
+}
+
+/// Replace the bearer token, invalidating the previous one
+pub async fn regenerate_token(db: &DatabaseManager) -> CodexResult<String> {
+    // Two v4 UUIDs give 244 random bits
+    let token = format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple());
+
+    let mut setting = Setting::new(API_TOKEN_SETTING.to_string(), String::new(), "api".to_string());
+    setting.description = Some("Bearer token for the local HTTP API".to_string());
+    setting.is_user_configurable = false;
+    setting.set_value(&token)?;
+    SettingQueries::set(db.pool(), &setting).await?;
+    Ok(token)
+}
+
+#[derive(Clone)]
+struct ApiState {
+    ai: Arc<AiEngine>,
+    content: Arc<ContentManager>,
+    token: Arc<str>,
+}
+
+fn router(state: ApiState) -> Router {
+    Router::new()
+        .route("/documents", get(list_documents))
+        .route("/documents/{id}", get(get_document))
+        .route("/search", post(search))
+        .route("/rag", post(rag))
+        .route("/import-text", post(import_text))
+        .layer(middleware::from_fn_with_state(state.clone(), require_token))
+        .with_state(state)
+}
+
+async fn require_token(State(state): State<ApiState>, request: Request, next: Next) -> Response {
+    let authorized = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|token| token_matches(token, &state.token));
+
+    if !authorized {
+        return ApiError(StatusCode::UNAUTHORIZED, "Missing or invalid bearer token".to_string()).into_response();
+    }
+    next.run(request).await
+}
+
+/// Compare tokens in time independent of where they differ
+fn token_matches(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given.bytes().zip(expected.bytes()).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Error response body: `{"error": "..."}`
+struct ApiError(StatusCode, String);
+
+impl From<CodexError> for ApiError {
+    fn from(error: CodexError) -> Self {
+        let status = match error {
+            CodexError::Validation(_) => StatusCode::BAD_REQUEST,
+            CodexError::NotFound(_) => StatusCode::NOT_FOUND,
+            CodexError::PermissionDenied(_) => StatusCode::FORBIDDEN,
+            CodexError::InsufficientDiskSpace { .. } => StatusCode::INSUFFICIENT_STORAGE,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        Self(status, error.to_string())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(serde_json::json!({ "error": self.1 }))).into_response()
+    }
+}
+
+type ApiResult<T> = Result<Json<T>, ApiError>;
+
+#[derive(Debug, Deserialize)]
+struct ListParams {
+    limit: Option<i64>,
+    #[serde(default)]
+    offset: i64,
+    category: Option<String>,
+}
+
+async fn list_documents(State(state): State<ApiState>, Query(params): Query<ListParams>) -> ApiResult<Vec<Document>> {
+    let limit = params.limit.unwrap_or(DEFAULT_LIST_LIMIT).clamp(1, MAX_LIST_LIMIT);
+    let documents = match params.category {
+        Some(ref category) => state.content.get_documents_by_category(category, limit, params.offset.max(0)).await?,
+        None => state.content.get_recent_documents(limit).await?,
+    };
+    Ok(Json(documents))
+}
+
+async fn get_document(State(state): State<ApiState>, Path(id): Path<String>) -> ApiResult<Document> {
+    let id = uuid::Uuid::parse_str(&id)
+        .map_err(|_| ApiError(StatusCode::BAD_REQUEST, "Invalid document ID".to_string()))?;
+    state
+        .content
+        .get_document(id)
+        .await?
+        .map(Json)
+        .ok_or_else(|| ApiError(StatusCode::NOT_FOUND, format!("Document not found: {}", id)))
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchRequest {
+    query: String,
+    limit: Option<usize>,
+    #[serde(default)]
+    offset: usize,
+    category: Option<String>,
+    #[serde(default)]
+    include_archived: bool,
+}
+
+impl SearchRequest {
+    /// Requested results, clamped like the `/documents` limit
+    fn limit(&self, default: usize) -> usize {
+        self.limit.unwrap_or(default).clamp(1, MAX_SEARCH_LIMIT)
+    }
+}
+
+async fn search(
+    State(state): State<ApiState>,
+    Json(request): Json<SearchRequest>,
+) -> ApiResult<crate::content::SearchResults> {
+    let defaults = SearchOptions::default();
+    let options = SearchOptions {
+        limit: request.limit(defaults.limit),
+        offset: request.offset,
+        category: request.category,
+        include_archived: request.include_archived,
+        ..defaults
+    };
+    Ok(Json(state.content.search_documents(&request.query, options).await?))
+}
+
+#[derive(Debug, Deserialize)]
+struct RagRequest {
+    question: String,
+    #[serde(default = "default_context_limit")]
+    context_limit: usize,
+    /// Stream the answer as server-sent events
+    #[serde(default)]
+    stream: bool,
+}
+
+fn default_context_limit() -> usize {
+    5
+}
+
+impl RagRequest {
+    /// Requested context documents, clamped like the `/documents` limit
+    fn context_limit(&self) -> usize {
+        self.context_limit.clamp(1, MAX_RAG_CONTEXT)
+    }
+}
+
+async fn rag(State(state): State<ApiState>, Json(request): Json<RagRequest>) -> Result<Response, ApiError> {
+    if !request.stream {
+        let response = state.ai.rag_query(&request.question, request.context_limit()).await?;
+        return Ok(Json(response).into_response());
+    }
+    Ok(Sse::new(rag_events(state.ai, request)).keep_alive(KeepAlive::default()).into_response())
+}
+
+/// `token` events carry the answer so far as JSON, with the completion
+/// tokens in it and the elapsed milliseconds; the stream ends with one
+/// `done` event holding the full response and its usage, or an `error` event
+fn rag_events(ai: Arc<AiEngine>, request: RagRequest) -> impl Stream<Item = Result<Event, Infallible>> {
+    let (sender, receiver) = futures::channel::mpsc::unbounded::<Event>();
+
+    tokio::spawn(async move {
+        let tokens = sender.clone();
+        let result = ai
+            .rag_query_stream(&request.question, request.context_limit(), move |chunk| {
+                if let Ok(data) = serde_json::to_string(&chunk) {
+                    let _ = tokens.unbounded_send(Event::default().event("token").data(data));
+                }
+            })
+            .await;
+
+        let last = match result.and_then(|response| Ok(serde_json::to_string(&response)?)) {
+            Ok(response) => Event::default().event("done").data(response),
+            Err(e) => Event::default().event("error").data(e.to_string()),
+        };
+        let _ = sender.unbounded_send(last);
+    });
+
+    receiver.map(Ok)
+}
+
+#[derive(Debug, Deserialize)]
+struct ImportTextRequest {
+    title: String,
+    content: String,
+    content_type: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct ImportTextResponse {
+    document_id: uuid::Uuid,
+}
+
+async fn import_text(
+    State(state): State<ApiState>,
+    Json(request): Json<ImportTextRequest>,
+) -> ApiResult<ImportTextResponse> {
+    if request.title.trim().is_empty() {
+        return Err(ApiError(StatusCode::BAD_REQUEST, "Title must not be empty".to_string()));
+    }
+    let document_id = state
+        .content
+        .import_text_content(request.title, request.content, request.content_type)
+        .await?;
+    Ok(Json(ImportTextResponse { document_id }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_database;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_token_matches_only_exact_token() {
+        assert!(token_matches("abc123", "abc123"));
+        assert!(!token_matches("abc124", "abc123"));
+        assert!(!token_matches("abc12", "abc123"));
+        assert!(!token_matches("", "abc123"));
+    }
+
+    #[test]
+    fn test_search_limit_is_clamped() {
+        let request = |body: &str| serde_json::from_str::<SearchRequest>(body).unwrap().limit(20);
+        assert_eq!(request(r#"{"query": "q"}"#), 20);
+        assert_eq!(request(r#"{"query": "q", "limit": 0}"#), 1);
+        assert_eq!(request(r#"{"query": "q", "limit": 50}"#), 50);
+        assert_eq!(request(r#"{"query": "q", "limit": 100000}"#), MAX_SEARCH_LIMIT);
+    }
+
+    #[test]
+    fn test_rag_context_limit_is_clamped() {
+        let request = |body: &str| serde_json::from_str::<RagRequest>(body).unwrap().context_limit();
+        assert_eq!(request(r#"{"question": "q"}"#), 5);
+        assert_eq!(request(r#"{"question": "q", "context_limit": 0}"#), 1);
+        assert_eq!(request(r#"{"question": "q", "context_limit": 8}"#), 8);
+        assert_eq!(request(r#"{"question": "q", "context_limit": 100000}"#), MAX_RAG_CONTEXT);
+    }
+
+    #[tokio::test]
+    async fn test_token_is_persisted_until_regenerated() {
+        let dir = tempdir().unwrap();
+        let db = test_database(&dir).await;
+
+        let token = load_or_create_token(&db).await.unwrap();
+        assert_eq!(token.len(), 64);
+        assert_eq!(load_or_create_token(&db).await.unwrap(), token);
+
+        let regenerated = regenerate_token(&db).await.unwrap();
+        assert_ne!(regenerated, token);
+        assert_eq!(load_or_create_token(&db).await.unwrap(), regenerated);
+    }
+}
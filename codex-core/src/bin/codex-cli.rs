@@ -0,0 +1,292 @@
+//! Headless command-line interface for Codex Vault
+//!
+//! Runs codex-core operations against a vault without the desktop app, e.g.
+//! from cron. Output is human-readable text, or JSON with `--json`.
+//!
+//! Exit codes:
+//! - 0: success
+//! - 1: the operation failed
+//! - 2: invalid arguments
+//! - 3: nothing found (search without results)
+//! - 4: the vault could not be opened
+//! - 5: some files of an import failed
+//!
+//! With the `mcp` feature, `codex-cli mcp` serves the vault to MCP clients
+//! over stdin/stdout.
+
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use clap::{Parser, Subcommand};
+use serde::Serialize;
+
+use codex_core::{CodexConfig, CodexCore, CodexError, CodexResult};
+use codex_core::content::SearchOptions;
+use codex_core::content::transcribe::Transcriber;
+use codex_core::db::DatabaseManager;
+
+const EXIT_FAILURE: u8 = 1;
+const EXIT_NOT_FOUND: u8 = 3;
+const EXIT_VAULT_UNAVAILABLE: u8 = 4;
+const EXIT_PARTIAL: u8 = 5;
+
+#[derive(Parser)]
+#[command(name = "codex-cli")]
+#[command(about = "Run Codex Vault operations without the desktop app")]
+#[command(version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Commands,
+
+    /// Vault directory holding codex.db, models/ and content/
+    /// (defaults to the desktop app's data directory)
+    #[arg(long, global = true)]
+    vault: Option<PathBuf>,
+
+    /// Configuration file (defaults to the desktop app's configuration)
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Print results as JSON
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Enable verbose logging on stderr
+    #[arg(short, long, global = true)]
+    verbose: bool,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Import a file or every file in a directory
+    Import {
+        path: PathBuf,
+    },
+    /// Search documents
+    Search {
+        query: String,
+
+        /// Maximum number of results
+        #[arg(short, long, default_value = "20")]
+        limit: usize,
+    },
+    /// Show vault statistics
+    Stats,
+    /// Reindex all documents
+    Reindex,
+    /// Write a consistent copy of the vault database to a new file
+    Backup {
+        dest: PathBuf,
+    },
+    /// Ask a question answered from the vault's documents
+    Ask {
+        question: String,
+
+        /// Number of documents used as context
+        #[arg(long, default_value = "5")]
+        context: usize,
+    },
+    /// Serve the vault as MCP tools over stdin/stdout
+    #[cfg(feature = "mcp")]
+    Mcp,
+}
+
+/// Result of a command: what to print and how to exit
+struct Outcome {
+    json: serde_json::Value,
+    text: String,
+    exit_code: u8,
+}
+
+impl Outcome {
+    fn new<T: Serialize>(value: &T, text: String) -> CodexResult<Self> {
+        Ok(Self {
+            json: serde_json::to_value(value)?,
+            text,
+            exit_code: 0,
+        })
+    }
+
+    fn with_exit_code(mut self, exit_code: u8) -> Self {
+        self.exit_code = exit_code;
+        self
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    // Logs go to stderr so stdout stays parseable
+    tracing_subscriber::fmt()
+        .with_max_level(if cli.verbose { tracing::Level::DEBUG } else { tracing::Level::WARN })
+        .with_writer(std::io::stderr)
+        .with_target(false)
+        .init();
+
+    let core = match open_vault(&cli).await {
+        Ok(core) => core,
+        Err(e) => {
+            report_error(&cli, &format!("Failed to open vault: {}", e));
+            return ExitCode::from(EXIT_VAULT_UNAVAILABLE);
+        }
+    };
+
+    #[cfg(feature = "mcp")]
+    if let Commands::Mcp = cli.command {
+        let result = core.mcp_server().serve_stdio().await;
+        if let Err(e) = core.shutdown().await {
+            tracing::warn!("Shutdown failed: {}", e);
+        }
+        return match result {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                ExitCode::from(EXIT_FAILURE)
+            }
+        };
+    }
+
+    let result = run(&cli.command, &core).await;
+    if let Err(e) = core.shutdown().await {
+        tracing::warn!("Shutdown failed: {}", e);
+    }
+
+    match result {
+        Ok(outcome) => {
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&outcome.json).unwrap_or_default());
+            } else {
+                println!("{}", outcome.text);
+            }
+            ExitCode::from(outcome.exit_code)
+        }
+        Err(e) => {
+            report_error(&cli, &e.to_string());
+            ExitCode::from(EXIT_FAILURE)
+        }
+    }
+}
+
+async fn open_vault(cli: &Cli) -> anyhow::Result<CodexCore> {
+    let mut config = match cli.config {
+        Some(ref path) => CodexConfig::load_from_file(path).await?,
+        None => CodexConfig::load_default().await?,
+    };
+    if let Some(ref vault) = cli.vault {
+        config = config.with_vault_dir(vault);
+    }
+
+    // SQLite locking keeps concurrent writers safe; writes simply wait for the other process
+    if DatabaseManager::appears_in_use(&config.database) {
+        eprintln!(
+            "warning: {} appears to be open in another process (is the desktop app running?); \
+             operations may wait for its locks",
+            config.database.path.display()
+        );
+    }
+
+    CodexCore::with_config(config).await
+}
+
+async fn run(command: &Commands, core: &CodexCore) -> CodexResult<Outcome> {
+    match command {
+        Commands::Import { path } => import(core, path).await,
+        Commands::Search { query, limit } => {
+            let options = SearchOptions {
+                limit: *limit,
+                ..SearchOptions::default()
+            };
+            let results = core.content.search_documents(query, options).await?;
+
+            let mut text = format!("{} results for \"{}\"", results.total_count, query);
+            for (i, result) in results.documents.iter().enumerate() {
+                text.push_str(&format!("\n{}. {} ({:.2}) [{}]", i + 1, result.document.title, result.score, result.document.id));
+            }
+            let exit_code = if results.documents.is_empty() { EXIT_NOT_FOUND } else { 0 };
+            Ok(Outcome::new(&results, text)?.with_exit_code(exit_code))
+        }
+        Commands::Stats => {
+            let stats = core.content.get_content_stats().await?;
+            let mut text = format!(
+                "Documents: {}\nEmbeddings: {}\nIndexed documents: {}\nDatabase size: {:.1} MB",
+                stats.total_documents,
+                stats.total_embeddings,
+                stats.indexed_documents,
+                stats.database_size_bytes as f64 / (1024.0 * 1024.0),
+            );
+            if let Some(warning) = stats.index_health.warning() {
+                text.push_str(&format!("\nWarning: {}", warning));
+            }
+            Outcome::new(&stats, text)
+        }
+        Commands::Reindex => {
+            core.content.reindex_all_documents().await?;
+            Outcome::new(&serde_json::json!({ "reindexed": true }), "Reindex completed".to_string())
+        }
+        Commands::Backup { dest } => {
+            core.db.backup(dest).await?;
+            Outcome::new(
+                &serde_json::json!({ "backup": dest }),
+                format!("Backup written to {}", dest.display()),
+            )
+        }
+        Commands::Ask { question, context } => {
+            let response = core.ai.rag_query(question, *context).await?;
+            let mut text = response.answer.clone();
+            if !response.sources.is_empty() {
+                text.push_str("\n\nSources:");
+                for source in &response.sources {
+                    text.push_str(&format!("\n- {} [{}]", source.title, source.document_id));
+                }
+            }
+            Outcome::new(&response, text)
+        }
+        #[cfg(feature = "mcp")]
+        Commands::Mcp => unreachable!("the MCP server is run by main"),
+    }
+}
+
+async fn import(core: &CodexCore, path: &Path) -> CodexResult<Outcome> {
+    if path.is_dir() {
+        let result = core.content.bulk_import_directory(path, false).await?;
+        let mut text = format!(
+            "Imported {} of {} files ({} failed)",
+            result.successful_imports, result.total_files, result.failed_imports
+        );
+        for error in &result.errors {
+            text.push_str(&format!("\n  - {}", error));
+        }
+        let exit_code = if result.failed_imports > 0 { EXIT_PARTIAL } else { 0 };
+        return Ok(Outcome::new(&result, text)?.with_exit_code(exit_code));
+    }
+
+    if !path.exists() {
+        return Err(CodexError::not_found(format!("No such file or directory: {}", path.display())));
+    }
+    let is_audio = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(Transcriber::is_audio_extension);
+    let document_id = if is_audio {
+        // Transcription runs as a background job; wait so the process does not exit early
+        let job_id = core.content.import_audio(path).await?;
+        core.content
+            .wait_for_job(job_id)
+            .await?
+            .ok_or_else(|| CodexError::internal("Transcription finished without creating a document"))?
+    } else {
+        core.content.import_document(path).await?
+    };
+    Outcome::new(
+        &serde_json::json!({ "document_id": document_id }),
+        format!("Imported {} as {}", path.display(), document_id),
+    )
+}
+
+fn report_error(cli: &Cli, message: &str) {
+    if cli.json {
+        println!("{}", serde_json::json!({ "error": message }));
+    } else {
+        eprintln!("error: {}", message);
+    }
+}
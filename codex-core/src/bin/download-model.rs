@@ -3,13 +3,12 @@
 //! This tool provides a command-line interface for downloading AI models
 //! with progress tracking, checksum verification, and integrity validation.
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use clap::{Parser, Subcommand};
 use indicatif::{ProgressBar, ProgressStyle};
 use tokio::sync::Mutex;
 use tracing::{info, warn, error};
-use tracing_subscriber;
 
 use codex_core::{
     CodexError, CodexResult,
@@ -101,7 +100,7 @@ async fn main() -> CodexResult<()> {
     
     // Ensure download directory exists
     tokio::fs::create_dir_all(&cli.download_dir).await
-        .map_err(|e| CodexError::io(e))?;
+        .map_err(CodexError::io)?;
     
     match cli.command {
         Commands::Download { model, force } => {
@@ -124,7 +123,7 @@ async fn main() -> CodexResult<()> {
     Ok(())
 }
 
-async fn download_model(download_dir: &PathBuf, model_name: &str, force: bool) -> CodexResult<()> {
+async fn download_model(download_dir: &Path, model_name: &str, force: bool) -> CodexResult<()> {
     info!("Starting model download: {}", model_name);
     
     // For demonstration, create a sample Mistral 7B manifest
@@ -164,7 +163,7 @@ async fn download_model(download_dir: &PathBuf, model_name: &str, force: bool) -
     let pb_clone = Arc::clone(&progress_bar);
     
     // Create downloader with progress callback
-    let downloader = ModelDownloader::new(download_dir.clone())
+    let downloader = ModelDownloader::new(download_dir.to_path_buf())
         .with_progress_callback(Box::new(move |progress| {
             let pb = pb_clone.clone();
             tokio::spawn(async move {
@@ -248,7 +247,7 @@ async fn list_models(registry_url: &str) -> CodexResult<()> {
     Ok(())
 }
 
-async fn verify_model(model_path: &PathBuf, expected_checksum: Option<String>) -> CodexResult<()> {
+async fn verify_model(model_path: &Path, expected_checksum: Option<String>) -> CodexResult<()> {
     info!("Verifying model: {}", model_path.display());
     
     if !model_path.exists() {
@@ -305,7 +304,7 @@ async fn show_model_info(model_path: &PathBuf) -> CodexResult<()> {
     
     // Get file information
     let metadata = tokio::fs::metadata(model_path).await
-        .map_err(|e| CodexError::io(e))?;
+        .map_err(CodexError::io)?;
     
     println!("Model Information");
     println!("=================");
@@ -378,15 +377,15 @@ async fn clean_models(download_dir: &PathBuf, all: bool, model_name: Option<Stri
     if all {
         // Remove all model files
         let mut entries = tokio::fs::read_dir(download_dir).await
-            .map_err(|e| CodexError::io(e))?;
+            .map_err(CodexError::io)?;
         
         let mut removed_count = 0;
-        while let Some(entry) = entries.next_entry().await.map_err(|e| CodexError::io(e))? {
+        while let Some(entry) = entries.next_entry().await.map_err(CodexError::io)? {
             let path = entry.path();
             if let Some(extension) = path.extension() {
                 if extension == "gguf" || extension == "json" {
                     tokio::fs::remove_file(&path).await
-                        .map_err(|e| CodexError::io(e))?;
+                        .map_err(CodexError::io)?;
                     println!("Removed: {}", path.display());
                     removed_count += 1;
                 }
@@ -437,6 +436,7 @@ async fn update_progress_bar(pb: Arc<Mutex<ProgressBar>>, progress: DownloadProg
                 "Downloading...".to_string()
             }
         }
+        DownloadStage::Paused => "Paused".to_string(),
         DownloadStage::Verifying => "Verifying checksum...".to_string(),
         DownloadStage::Completed => "Completed!".to_string(),
         DownloadStage::Failed(ref msg) => format!("Failed: {}", msg),
@@ -445,7 +445,7 @@ async fn update_progress_bar(pb: Arc<Mutex<ProgressBar>>, progress: DownloadProg
     pb.set_message(message);
 }
 
-async fn show_model_info_from_manifest(manifest: &ModelManifest, path: &PathBuf) {
+async fn show_model_info_from_manifest(manifest: &ModelManifest, path: &Path) {
     println!();
     println!("Model Downloaded Successfully!");
     println!("==============================");
@@ -9,14 +9,19 @@ use clap::{Parser, Subcommand};
 use indicatif::{ProgressBar, ProgressStyle};
 use tokio::fs;
 use tracing::{info, warn, debug};
-use tracing_subscriber;
 
 use codex_core::{
     CodexError, CodexResult,
-    config::{CodexConfig, ContentConfig, AiConfig, DatabaseConfig, UpdateConfig, AppConfig},
+    config::{CodexConfig, ContentConfig, AiConfig, DatabaseConfig, UpdateConfig, AppConfig, LoggingConfig, ApiServerConfig, EventBatchingConfig, WarmUpConfig},
     db::DatabaseManager,
     ai::AiEngine,
+    diagnostics,
+    update::UpdateManager,
     content::ContentManager,
+    content::import_plan::ImportDecision,
+    content::ocr::{OcrExtractor, OCR_IMAGE_EXTENSIONS},
+    content::transcribe::{Transcriber, AUDIO_EXTENSIONS},
+    content::code::{self, CODE_EXTENSIONS},
 };
 
 #[derive(Parser)]
@@ -25,7 +30,11 @@ use codex_core::{
 #[command(version)]
 struct Cli {
     #[command(subcommand)]
-    command: Commands,
+    command: Option<Commands>,
+    
+    /// Run the self-test and print its report as JSON
+    #[arg(long)]
+    diagnostics: bool,
     
     /// Enable verbose logging
     #[arg(short, long)]
@@ -38,6 +47,10 @@ struct Cli {
     /// Models directory for AI
     #[arg(short, long, default_value = "./models")]
     models_dir: PathBuf,
+    
+    /// Load no AI model; list, stats and validation keep working
+    #[arg(long)]
+    safe_mode: bool,
 }
 
 #[derive(Subcommand)]
@@ -67,6 +80,10 @@ enum Commands {
         /// Skip AI enhancement for faster import
         #[arg(long)]
         skip_ai: bool,
+        
+        /// Show what would be imported, skipped or flagged as duplicate without importing
+        #[arg(long)]
+        dry_run: bool,
     },
     /// List imported content
     List {
@@ -121,20 +138,35 @@ async fn main() -> CodexResult<()> {
     let config = create_config(&cli).await?;
     
     // Initialize database
-    let db = Arc::new(DatabaseManager::new(&config.database).await?);
+    let db = Arc::new(DatabaseManager::new(&config.database).await?.with_compression(config.content.compression_policy()));
     info!("Connected to database: {}", cli.database.display());
     
     // Initialize AI engine
-    let ai = Arc::new(AiEngine::new(&config.ai).await?);
+    let ai = if config.safe_mode {
+        warn!("Safe mode: AI is disabled");
+        Arc::new(AiEngine::new_disabled(&config.ai))
+    } else {
+        Arc::new(AiEngine::new(&config.ai).await?)
+    };
     info!("AI engine initialized");
     
     // Initialize content manager
     let content_manager = ContentManager::new(Arc::clone(&db), Arc::clone(&ai), &config.content).await?;
     info!("Content manager initialized");
     
+    if cli.diagnostics {
+        return run_diagnostics(&db, &ai, &config.update).await;
+    }
+    
     // Execute command
-    match cli.command {
-        Commands::Import { path, category, collection, recursive, force, skip_ai } => {
+    let Some(command) = cli.command else {
+        return Err(CodexError::validation("No command given; see --help"));
+    };
+    match command {
+        Commands::Import { path, recursive, dry_run: true, .. } => {
+            dry_run_import(&content_manager, &path, recursive).await?
+        }
+        Commands::Import { path, category, collection, recursive, force, skip_ai, .. } => {
             import_content(&content_manager, &path, category, collection, recursive, force, skip_ai).await?
         }
         Commands::List { category, format, limit } => {
@@ -155,6 +187,23 @@ async fn main() -> CodexResult<()> {
     Ok(())
 }
 
+async fn run_diagnostics(db: &DatabaseManager, ai: &AiEngine, update_config: &UpdateConfig) -> CodexResult<()> {
+    let update = UpdateManager::new(update_config).await
+        .map_err(|e| CodexError::update(e.to_string()))?;
+    let report = diagnostics::run_diagnostics(db, ai, &update).await;
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    
+    if report.passed {
+        Ok(())
+    } else {
+        let failed: Vec<&str> = report.steps.iter()
+            .filter(|step| step.status != diagnostics::StepStatus::Passed)
+            .map(|step| step.name.as_str())
+            .collect();
+        Err(CodexError::internal(format!("Diagnostics failed: {}", failed.join(", "))))
+    }
+}
+
 async fn create_config(cli: &Cli) -> CodexResult<CodexConfig> {
     let database_config = DatabaseConfig {
         path: cli.database.clone(),
@@ -174,19 +223,52 @@ async fn create_config(cli: &Cli) -> CodexResult<CodexConfig> {
         device: "cpu".to_string(),
         enable_caching: true,
         cache_size_mb: 512,
+        rag: Default::default(),
+        embedding: Default::default(),
+        audit: Default::default(),
+        draft_model: None,
+        speculative: Default::default(),
+        truncation: Default::default(),
+        gpu_layers: Default::default(),
     };
     
+    let mut supported_extensions = vec![
+        "md".to_string(), "markdown".to_string(), "txt".to_string(),
+        "html".to_string(), "htm".to_string(), "json".to_string(),
+        "ipynb".to_string(), "csv".to_string(), "tsv".to_string(),
+    ];
+    supported_extensions.extend(CODE_EXTENSIONS.iter().map(|e| e.to_string()));
+    if OcrExtractor::is_available() {
+        supported_extensions.extend(OCR_IMAGE_EXTENSIONS.iter().map(|e| e.to_string()));
+    }
+    if Transcriber::is_available() {
+        supported_extensions.extend(AUDIO_EXTENSIONS.iter().map(|e| e.to_string()));
+    }
+
     let content_config = ContentConfig {
         content_dir: PathBuf::from("./content"),
-        supported_extensions: vec![
-            "md".to_string(), "markdown".to_string(), "txt".to_string(),
-            "html".to_string(), "htm".to_string(), "json".to_string(),
-        ],
+        supported_extensions,
         max_file_size_mb: 50,
         enable_compression: true,
         compression_level: 6,
         auto_index: true,
         index_batch_size: 100,
+        ocr_language: "eng".to_string(),
+        ocr_min_confidence: 0.6,
+        whisper_model_path: Some(cli.models_dir.join("ggml-base.en.bin")),
+        transcription_language: "auto".to_string(),
+        notebook_include_outputs: true,
+        table_index_row_limit: 1000,
+        reading_speeds: codex_core::content::metadata::default_reading_speeds(),
+        digest_schedule: codex_core::content::digest::DigestSchedule::default(),
+        capture_dedup_minutes: 10,
+        vault_import: codex_core::content::vault_import::VaultImportConfig::default(),
+        feed_schedule: codex_core::content::rss::FeedSchedule::default(),
+        search_timeout_ms: 1500,
+        search_cache: codex_core::content::search::SearchCacheConfig::default(),
+        attachments: codex_core::content::attachments::AttachmentConfig::default(),
+        reading: codex_core::content::reading::ReadingConfig::default(),
+        respect_source_dates: false,
     };
     
     let update_config = UpdateConfig::default();
@@ -197,6 +279,13 @@ async fn create_config(cli: &Cli) -> CodexResult<CodexConfig> {
         enable_telemetry: false,
         theme: "auto".to_string(),
         locale: "en-US".to_string(),
+        logging: LoggingConfig::default(),
+        api_server: ApiServerConfig::default(),
+        offline_mode: false,
+        low_disk_warning_mb: 1024,
+        event_batching: EventBatchingConfig::default(),
+        // Commands are short-lived; there is no later search to warm up for
+        warm_up: WarmUpConfig { enabled: false, ..WarmUpConfig::default() },
     };
     
     Ok(CodexConfig {
@@ -205,6 +294,9 @@ async fn create_config(cli: &Cli) -> CodexResult<CodexConfig> {
         content: content_config,
         update: update_config,
         app: app_config,
+        network: Default::default(),
+        power: Default::default(),
+        safe_mode: cli.safe_mode || std::env::var(codex_core::config::SAFE_MODE_ENV).is_ok_and(|value| value == "1"),
     })
 }
 
@@ -293,12 +385,61 @@ async fn import_content(
     Ok(())
 }
 
+async fn dry_run_import(content_manager: &ContentManager, path_pattern: &str, recursive: bool) -> CodexResult<()> {
+    let files = if recursive {
+        discover_files_recursive(path_pattern).await?
+    } else {
+        discover_files(path_pattern).await?
+    };
+    
+    if files.is_empty() {
+        warn!("No files found matching pattern: {}", path_pattern);
+        return Ok(());
+    }
+    
+    let plan = content_manager.plan_import(&files).await?;
+    
+    println!("\nImport Dry Run (nothing was imported):");
+    println!("======================================");
+    for file in &plan.files {
+        let decision = match &file.decision {
+            ImportDecision::Import => "import".to_string(),
+            ImportDecision::Transcribe => "transcribe".to_string(),
+            ImportDecision::Duplicate { existing_title, .. } => format!("duplicate of \"{}\"", existing_title),
+            ImportDecision::DuplicateInBatch { original } => format!("duplicate of {}", original.display()),
+            ImportDecision::Skip { reason } => format!("skip: {}", reason),
+        };
+        println!("  {} - {}", file.path.display(), decision);
+    }
+    println!("\nFiles to import: {} ({:.1} MB)", plan.to_import, plan.total_bytes as f64 / (1024.0 * 1024.0));
+    println!("Duplicates: {}", plan.duplicates);
+    println!("Skipped: {}", plan.skipped);
+    println!("Estimated time: {:.0}s", plan.estimated_duration_ms as f64 / 1000.0);
+    
+    Ok(())
+}
+
 async fn import_single_file(
     content_manager: &ContentManager,
     file_path: &Path,
     category: &Option<String>,
 ) -> CodexResult<uuid::Uuid> {
-    let doc_id = content_manager.import_document(file_path).await?;
+    let is_audio = file_path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(Transcriber::is_audio_extension)
+        .unwrap_or(false);
+
+    // Audio goes through the background job queue; the CLI simply waits for it
+    let doc_id = if is_audio {
+        let job_id = content_manager.import_audio(file_path).await?;
+        content_manager
+            .wait_for_job(job_id)
+            .await?
+            .ok_or_else(|| CodexError::internal("Transcription job produced no document"))?
+    } else {
+        content_manager.import_document(file_path).await?
+    };
     
     // Set category if provided
     if let Some(cat) = category {
@@ -368,11 +509,11 @@ fn collect_files_recursive(dir: &Path, files: &mut Vec<PathBuf>) -> CodexResult<
     use std::fs;
     
     let entries = fs::read_dir(dir)
-        .map_err(|e| CodexError::io(std::io::Error::new(std::io::ErrorKind::Other, format!("Failed to read directory {:?}: {}", dir, e))))?;
+        .map_err(|e| CodexError::io(std::io::Error::other(format!("Failed to read directory {:?}: {}", dir, e))))?;
     
     for entry in entries {
         let entry = entry
-            .map_err(|e| CodexError::io(std::io::Error::new(std::io::ErrorKind::Other, format!("Failed to read directory entry: {}", e))))?;
+            .map_err(|e| CodexError::io(std::io::Error::other(format!("Failed to read directory entry: {}", e))))?;
         let path = entry.path();
         
         if path.is_file() && is_supported_file(&path) {
@@ -394,7 +535,10 @@ fn is_supported_file(path: &Path) -> bool {
     if let Some(extension) = path.extension() {
         if let Some(ext_str) = extension.to_str() {
             let ext_lower = ext_str.to_lowercase();
-            return matches!(ext_lower.as_str(), "md" | "markdown" | "txt" | "html" | "htm" | "json");
+            return matches!(ext_lower.as_str(), "md" | "markdown" | "txt" | "html" | "htm" | "json" | "ipynb" | "csv" | "tsv")
+                || code::is_code_extension(&ext_lower)
+                || (OcrExtractor::is_available() && OcrExtractor::is_image_extension(&ext_lower))
+                || (Transcriber::is_available() && Transcriber::is_audio_extension(&ext_lower));
         }
     }
     false
@@ -427,7 +571,7 @@ async fn list_content(
                     doc.title.replace(',', ";"),
                     doc.category.unwrap_or_default(),
                     doc.author.unwrap_or_default(),
-                    doc.created_at,
+                    doc.created_at.to_rfc3339(),
                     doc.reading_time.unwrap_or(0),
                     doc.view_count
                 );
@@ -457,7 +601,7 @@ async fn list_content(
                     println!("   Reading time: {} minutes", reading_time);
                 }
                 println!("   Views: {}", doc.view_count);
-                println!("   Created: {}", doc.created_at);
+                println!("   Created: {}", doc.created_at.format("%Y-%m-%d %H:%M"));
                 
                 if let Some(summary) = &doc.summary {
                     let truncated = if summary.len() > 100 {
@@ -488,6 +632,9 @@ async fn show_stats(content_manager: &ContentManager) -> CodexResult<()> {
     println!("Embeddings: {}", stats.total_embeddings);
     println!("Indexed documents: {}", stats.indexed_documents);
     println!("Database size: {:.2} MB", stats.database_size_bytes as f64 / (1024.0 * 1024.0));
+    if let Some(warning) = stats.index_health.warning() {
+        println!("{} (run `reindex --all` to fix)", warning);
+    }
     
     // Get additional stats
     let recent_docs = content_manager.get_recent_documents(5).await?;
@@ -499,7 +646,7 @@ async fn show_stats(content_manager: &ContentManager) -> CodexResult<()> {
         println!("  No recent documents");
     } else {
         for (i, doc) in recent_docs.iter().enumerate() {
-            println!("  {}. {} ({})", i + 1, doc.title, doc.created_at);
+            println!("  {}. {} ({})", i + 1, doc.title, doc.created_at.format("%Y-%m-%d"));
         }
     }
     
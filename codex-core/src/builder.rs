@@ -0,0 +1,245 @@
+//! Assembling a [`CodexCore`] from code
+//!
+//! [`CodexCore::with_config`] is what the desktop app uses: it opens the
+//! vault on disk, loads models from `models_dir` and starts maintenance,
+//! schedules, cache warm-up and update checks. [`CodexCoreBuilder`] builds
+//! the same core for other Rust applications without reading a
+//! configuration file. It can keep the database in memory, generate and
+//! embed with the caller's own engines, or leave AI off, and it starts no
+//! background work unless asked to.
+//!
+//! # Examples
+//!
+//! Import a note and find it again, with the mock models of the
+//! `test-utils` feature and an in-memory database:
+//!
+//! ```rust
+//! use std::sync::Arc;
+//! use codex_core::{CodexCoreBuilder, SearchOptions};
+//! use codex_core::ai::{MockEmbeddingBackend, MockEngine};
+//! use codex_core::content::SearchType;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> anyhow::Result<()> {
+//! let vault = tempfile::tempdir()?;
+//! let core = CodexCoreBuilder::new()
+//!     .in_memory_database()
+//!     .content_dir(vault.path())
+//!     .llm_engine(Arc::new(MockEngine::new()))
+//!     .embedding_backend(Arc::new(MockEmbeddingBackend::default()))
+//!     .build()
+//!     .await?;
+//!
+//! let id = core
+//!     .content
+//!     .import_text_content(
+//!         "Lighthouses".to_string(),
+//!         "A lighthouse uses a rotating lens to send a beam of light far out to sea.".to_string(),
+//!         None,
+//!     )
+//!     .await?;
+//!
+//! let options = SearchOptions { search_type: SearchType::FullText, ..SearchOptions::default() };
+//! let results = core.content.search_documents("lighthouse", options).await?;
+//! assert_eq!(results.documents[0].document.id, id.to_string());
+//! core.shutdown().await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Answer a question from the vault with retrieval-augmented generation:
+//!
+//! ```rust
+//! use std::sync::Arc;
+//! use codex_core::{CodexCoreBuilder, RagResponse};
+//! use codex_core::ai::{MockEmbeddingBackend, MockEngine};
+//!
+//! # #[tokio::main]
+//! # async fn main() -> anyhow::Result<()> {
+//! let engine = MockEngine::new().respond(r"^Based on the following context", "With a rotating lens.");
+//! let vault = tempfile::tempdir()?;
+//! let core = CodexCoreBuilder::new()
+//!     .in_memory_database()
+//!     .content_dir(vault.path())
+//!     .llm_engine(Arc::new(engine))
+//!     .embedding_backend(Arc::new(MockEmbeddingBackend::default()))
+//!     .build()
+//!     .await?;
+//!
+//! core.content
+//!     .import_text_content(
+//!         "Lighthouses".to_string(),
+//!         "A lighthouse uses a rotating lens to send a beam of light far out to sea.".to_string(),
+//!         None,
+//!     )
+//!     .await?;
+//!
+//! let response: RagResponse = core.ai.rag_query("How does a lighthouse send its beam?", 3).await?;
+//! assert!(response.answer.contains("With a rotating lens."));
+//! assert_eq!(response.sources[0].title, "Lighthouses");
+//! # Ok(())
+//! # }
+//! ```
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::Result;
+
+use crate::ai::{AiEngine, EmbeddingBackend, LLMEngine};
+use crate::db::DatabaseManager;
+use crate::{CodexConfig, CodexCore};
+
+/// Fluent construction of a [`CodexCore`]
+///
+/// Starts from [`CodexConfig::default`]; nothing is read from disk until
+/// [`build`](Self::build) opens the database and, unless replaced or
+/// disabled, the models.
+#[derive(Default)]
+pub struct CodexCoreBuilder {
+    config: CodexConfig,
+    in_memory: bool,
+    ai_disabled: bool,
+    llm_engine: Option<Arc<dyn LLMEngine>>,
+    embedding_backend: Option<Arc<dyn EmbeddingBackend>>,
+    background_work: bool,
+}
+
+impl CodexCoreBuilder {
+    /// Builder with the default configuration
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start from `config` instead of the default configuration
+    ///
+    /// Setters called before are overridden by its paths.
+    pub fn config(mut self, config: CodexConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Keep the database, models and content in `vault_dir`
+    pub fn vault_dir(mut self, vault_dir: impl Into<PathBuf>) -> Self {
+        self.config = self.config.with_vault_dir(vault_dir.into());
+        self
+    }
+
+    /// Open or create the SQLite database at `path`
+    pub fn database_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.database.path = path.into();
+        self.in_memory = false;
+        self
+    }
+
+    /// Keep the database in memory; it is gone once the core is dropped
+    pub fn in_memory_database(mut self) -> Self {
+        self.in_memory = true;
+        self
+    }
+
+    /// Load models from, and download them to, `models_dir`
+    pub fn models_dir(mut self, models_dir: impl Into<PathBuf>) -> Self {
+        self.config.ai.models_dir = models_dir.into();
+        self
+    }
+
+    /// Store imported files, attachments and previews in `content_dir`
+    pub fn content_dir(mut self, content_dir: impl Into<PathBuf>) -> Self {
+        self.config.content.content_dir = content_dir.into();
+        self
+    }
+
+    /// Run without AI, as in safe mode: generation and embedding fail and
+    /// search falls back to full-text matching
+    ///
+    /// Takes precedence over [`llm_engine`](Self::llm_engine) and
+    /// [`embedding_backend`](Self::embedding_backend).
+    pub fn disable_ai(mut self) -> Self {
+        self.ai_disabled = true;
+        self
+    }
+
+    /// Generate with `engine` instead of the model in `models_dir`
+    pub fn llm_engine(mut self, engine: Arc<dyn LLMEngine>) -> Self {
+        self.llm_engine = Some(engine);
+        self
+    }
+
+    /// Embed with `backend` instead of the one `config.ai.embedding` selects
+    pub fn embedding_backend(mut self, backend: Arc<dyn EmbeddingBackend>) -> Self {
+        self.embedding_backend = Some(backend);
+        self
+    }
+
+    /// Start maintenance, schedules, cache warm-up, update checks and power
+    /// throttling like the desktop app; off by default
+    pub fn background_work(mut self, enabled: bool) -> Self {
+        self.background_work = enabled;
+        self
+    }
+
+    /// Open the database, set up AI and start the core
+    ///
+    /// A configuration with [`CodexConfig::safe_mode`] disables AI and
+    /// background work.
+    pub async fn build(self) -> Result<CodexCore> {
+        let config = self.config;
+        let safe_mode = config.safe_mode;
+
+        let db = if self.in_memory {
+            DatabaseManager::new_in_memory(&config.database).await?
+        } else {
+            DatabaseManager::new(&config.database).await?
+        };
+
+        let ai = match (self.ai_disabled || safe_mode, self.llm_engine, self.embedding_backend) {
+            (true, _, _) => AiEngine::new_disabled(&config.ai),
+            (false, Some(engine), Some(embeddings)) => AiEngine::with_backends(&config.ai, engine, embeddings),
+            (false, Some(engine), None) => AiEngine::with_engine(&config.ai, engine)?,
+            (false, None, Some(embeddings)) => AiEngine::with_embedding_backend(&config.ai, embeddings).await?,
+            (false, None, None) => AiEngine::new(&config.ai).await?,
+        };
+
+        CodexCore::assemble(config, db, ai, self.background_work && !safe_mode).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::{MockEmbeddingBackend, MockEngine};
+    use crate::db::{models::Document, DocumentQueries};
+
+    #[tokio::test]
+    async fn test_builder_without_ai_keeps_nothing_on_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let core = CodexCoreBuilder::new()
+            .database_path(dir.path().join("unused.db"))
+            .in_memory_database()
+            .content_dir(dir.path().join("content"))
+            .disable_ai()
+            .build()
+            .await
+            .unwrap();
+
+        assert!(core.ai.is_disabled());
+        assert!(!dir.path().join("unused.db").exists());
+        // Imports need embeddings, so write the document directly
+        let document = Document::new("Note".to_string(), "Kept in memory".to_string(), "text/plain".to_string());
+        DocumentQueries::create(core.db.pool(), &document, core.db.compression()).await.unwrap();
+        assert_eq!(core.content.get_recent_documents(10).await.unwrap().len(), 1);
+
+        // Each in-memory core has a database of its own
+        let other = CodexCoreBuilder::new()
+            .in_memory_database()
+            .content_dir(dir.path().join("content"))
+            .llm_engine(Arc::new(MockEngine::new()))
+            .embedding_backend(Arc::new(MockEmbeddingBackend::default()))
+            .build()
+            .await
+            .unwrap();
+        assert!(!other.ai.is_disabled());
+        assert!(other.content.get_recent_documents(10).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,101 @@
+//! Source of the current time for caches and maintenance
+//!
+//! Cache expiry and retention cutoffs read the time through a [`Clock`]
+//! instead of `Instant::now()` and `Utc::now()`, so tests can move a
+//! [`ManualClock`] past a time to live instead of sleeping through it.
+
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+
+/// Current monotonic and wall-clock time
+pub trait Clock: Send + Sync + Debug {
+    /// Monotonic time, for ages and time to live
+    fn now(&self) -> Instant;
+
+    /// Wall-clock time, for cutoffs compared with stored timestamps
+    fn utc_now(&self) -> DateTime<Utc>;
+}
+
+/// The system clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn utc_now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// The clock used unless another one is injected
+pub fn system() -> Arc<dyn Clock> {
+    Arc::new(SystemClock)
+}
+
+/// Clock standing still until [`advance`](Self::advance)d
+///
+/// Starts at the system time it was created at; both readings move
+/// together.
+#[derive(Debug)]
+pub struct ManualClock {
+    start: Instant,
+    start_utc: DateTime<Utc>,
+    elapsed: Mutex<Duration>,
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            start_utc: Utc::now(),
+            elapsed: Mutex::new(Duration::ZERO),
+        }
+    }
+
+    /// Move the clock forward by `duration`
+    pub fn advance(&self, duration: Duration) {
+        *self.elapsed.lock().unwrap_or_else(|e| e.into_inner()) += duration;
+    }
+
+    fn elapsed(&self) -> Duration {
+        *self.elapsed.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.start + self.elapsed()
+    }
+
+    fn utc_now(&self) -> DateTime<Utc> {
+        self.start_utc + chrono::Duration::from_std(self.elapsed()).unwrap_or(chrono::Duration::MAX)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manual_clock_moves_only_when_advanced() {
+        let clock = ManualClock::new();
+        let (instant, utc) = (clock.now(), clock.utc_now());
+        assert_eq!(clock.now(), instant);
+
+        clock.advance(Duration::from_secs(90));
+        assert_eq!(clock.now() - instant, Duration::from_secs(90));
+        assert_eq!(clock.utc_now() - utc, chrono::Duration::seconds(90));
+    }
+}
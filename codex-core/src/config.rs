@@ -1,5 +1,6 @@
 //! Configuration management for Codex Core
 
+use std::collections::HashMap;
 use std::path::PathBuf;
 use serde::{Deserialize, Serialize};
 use anyhow::Result;
@@ -18,8 +19,25 @@ pub struct CodexConfig {
     pub update: UpdateConfig,
     /// Application settings
     pub app: AppConfig,
+    /// Network usage limits
+    #[serde(default)]
+    pub network: NetworkConfig,
+    /// Throttling on battery and under thermal pressure
+    #[serde(default)]
+    pub power: PowerPolicy,
+    /// Start without AI models and background jobs, see [`CodexConfig::load_default`]
+    ///
+    /// Never written to the config file, so a launch in safe mode does not
+    /// make the next one safe too.
+    #[serde(default, skip_serializing)]
+    pub safe_mode: bool,
 }
 
+/// Environment variable that starts the core in safe mode when set to `1`
+pub const SAFE_MODE_ENV: &str = "CODEX_SAFE_MODE";
+/// File in the config directory requesting safe mode for the next launch
+const SAFE_MODE_MARKER: &str = "safe-mode";
+
 /// Database configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DatabaseConfig {
@@ -56,6 +74,240 @@ pub struct AiConfig {
     pub enable_caching: bool,
     /// Cache size in MB
     pub cache_size_mb: usize,
+    /// RAG retrieval settings
+    #[serde(default)]
+    pub rag: RagRetrievalConfig,
+    /// Embedding model used for semantic search
+    #[serde(default)]
+    pub embedding: EmbeddingModelConfig,
+    /// Per-document record of AI operations
+    #[serde(default)]
+    pub audit: AiAuditConfig,
+    /// Small model sharing the primary model's tokenizer, used for speculative decoding
+    #[serde(default)]
+    pub draft_model: Option<String>,
+    /// Speculative decoding with `draft_model`
+    #[serde(default)]
+    pub speculative: SpeculativeDecodingConfig,
+    /// What to do with prompts longer than the context leaves room for
+    #[serde(default)]
+    pub truncation: TruncationPolicy,
+    /// Transformer layers to offload to the GPU: a count, or `"auto"` to fit them to the free VRAM
+    #[serde(default)]
+    pub gpu_layers: GpuLayers,
+}
+
+impl AiConfig {
+    /// Most prompt tokens that still leave room for `max_tokens` of output
+    pub fn prompt_token_limit(&self) -> usize {
+        self.max_context_length.saturating_sub(self.max_tokens).max(1)
+    }
+}
+
+/// How a prompt longer than [`AiConfig::prompt_token_limit`] is handled
+///
+/// The truncating policies drop tokens from the start, the end or the middle
+/// of the prompt and report how many were dropped alongside the output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TruncationPolicy {
+    /// Reject the prompt with a validation error
+    Error,
+    /// Drop tokens from the start, keeping the end of the prompt
+    TruncateHead,
+    /// Drop tokens from the end, keeping the start of the prompt
+    TruncateTail,
+    /// Drop tokens from the middle, keeping the instructions at the start and the question at the end
+    #[default]
+    TruncateMiddle,
+}
+
+/// How many transformer layers go to the GPU
+///
+/// Written as a number or `"auto"` in the configuration file. `Auto` picks
+/// the most layers that fit in the free VRAM, see
+/// [`plan_gpu_layers`](crate::ai::load_plan::plan_gpu_layers).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(try_from = "GpuLayersValue", into = "GpuLayersValue")]
+pub enum GpuLayers {
+    #[default]
+    Auto,
+    /// Offload this many layers; 0 keeps the model on the CPU
+    Count(usize),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum GpuLayersValue {
+    Count(usize),
+    Name(String),
+}
+
+impl TryFrom<GpuLayersValue> for GpuLayers {
+    type Error = String;
+
+    fn try_from(value: GpuLayersValue) -> Result<Self, Self::Error> {
+        match value {
+            GpuLayersValue::Count(count) => Ok(Self::Count(count)),
+            GpuLayersValue::Name(name) if name.eq_ignore_ascii_case("auto") => Ok(Self::Auto),
+            GpuLayersValue::Name(name) => Err(format!("gpu_layers must be a number or \"auto\", got \"{}\"", name)),
+        }
+    }
+}
+
+impl From<GpuLayers> for GpuLayersValue {
+    fn from(layers: GpuLayers) -> Self {
+        match layers {
+            GpuLayers::Auto => Self::Name("auto".to_string()),
+            GpuLayers::Count(count) => Self::Count(count),
+        }
+    }
+}
+
+/// Whether and how far the draft model runs ahead of the primary model
+///
+/// Off by default, as the draft model's weights are loaded next to the
+/// primary model's.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpeculativeDecodingConfig {
+    /// Load `draft_model` and decode speculatively when it is available
+    pub enabled: bool,
+    /// Tokens drafted before each verification by the primary model
+    pub draft_tokens: usize,
+}
+
+impl Default for SpeculativeDecodingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            draft_tokens: 4,
+        }
+    }
+}
+
+/// What the AI audit trail keeps, and for how long
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AiAuditConfig {
+    /// Days entries are kept before maintenance prunes them
+    pub retention_days: u32,
+    /// Store full prompt inputs and outputs instead of only their hashes (debugging aid)
+    pub store_text: bool,
+}
+
+impl Default for AiAuditConfig {
+    fn default() -> Self {
+        Self {
+            retention_days: 90,
+            store_text: false,
+        }
+    }
+}
+
+/// Which embedding model to use and whether to fetch it automatically
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EmbeddingModelConfig {
+    /// Where embeddings are computed
+    pub backend: EmbeddingBackendKind,
+    /// Registry id of the model, also its directory name under `models_dir`
+    pub model: String,
+    /// Download the model on startup when it is missing (never in offline mode)
+    pub auto_download: bool,
+    /// Endpoint used by the remote backend
+    pub remote: RemoteEmbeddingConfig,
+}
+
+impl Default for EmbeddingModelConfig {
+    fn default() -> Self {
+        Self {
+            backend: EmbeddingBackendKind::default(),
+            model: crate::ai::embeddings::DEFAULT_EMBEDDING_MODEL.to_string(),
+            auto_download: true,
+            remote: RemoteEmbeddingConfig::default(),
+        }
+    }
+}
+
+/// Implementation behind the embedding engine
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmbeddingBackendKind {
+    /// The model in `models_dir`, run in-process
+    #[default]
+    Local,
+    /// An ONNX export of the model run by ONNX Runtime, only in builds
+    /// with the `onnx` feature
+    #[cfg(feature = "onnx")]
+    Onnx,
+    /// An OpenAI-compatible `/embeddings` endpoint
+    Remote,
+}
+
+/// OpenAI-compatible embedding endpoint
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RemoteEmbeddingConfig {
+    /// Base URL, to which `/embeddings` is appended
+    pub base_url: String,
+    /// Bearer token, if the endpoint needs one
+    pub api_key: Option<String>,
+    /// Model name sent with each request and stored with the embeddings
+    pub model: String,
+    /// Length of the vectors the model returns
+    pub dimensions: usize,
+    /// Most texts sent in one request
+    pub batch_size: usize,
+    /// Request timeout in seconds
+    pub timeout_secs: u64,
+}
+
+impl Default for RemoteEmbeddingConfig {
+    fn default() -> Self {
+        Self {
+            base_url: "https://api.openai.com/v1".to_string(),
+            api_key: None,
+            model: "text-embedding-3-small".to_string(),
+            dimensions: 1536,
+            batch_size: 64,
+            timeout_secs: 30,
+        }
+    }
+}
+
+/// How RAG retrieval merges full-text and semantic matches, and how answers are scored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RagRetrievalConfig {
+    /// Weight of the full-text (BM25) score in the hybrid score
+    pub text_weight: f32,
+    /// Weight of the embedding similarity in the hybrid score
+    pub semantic_weight: f32,
+    /// Most chunks taken from any single document
+    pub max_chunks_per_document: usize,
+    /// Factor applied to the score of chunks overlapping a user highlight
+    pub highlight_boost: f32,
+    /// Confidence below which an answer is flagged and hedged
+    pub low_confidence_threshold: f32,
+    /// Ask the model whether the context supports its answer when scoring confidence
+    pub enable_self_check: bool,
+    /// Rewrite follow-up questions into standalone queries and retrieve with expansions
+    pub enable_query_rewriting: bool,
+}
+
+impl Default for RagRetrievalConfig {
+    fn default() -> Self {
+        Self {
+            text_weight: 0.5,
+            semantic_weight: 0.5,
+            max_chunks_per_document: 2,
+            highlight_boost: 1.25,
+            low_confidence_threshold: 0.4,
+            enable_self_check: true,
+            enable_query_rewriting: true,
+        }
+    }
 }
 
 impl Default for AiConfig {
@@ -70,6 +322,13 @@ impl Default for AiConfig {
             device: "cpu".to_string(),
             enable_caching: true,
             cache_size_mb: 512,
+            rag: RagRetrievalConfig::default(),
+            embedding: EmbeddingModelConfig::default(),
+            audit: AiAuditConfig::default(),
+            draft_model: None,
+            speculative: SpeculativeDecodingConfig::default(),
+            truncation: TruncationPolicy::default(),
+            gpu_layers: GpuLayers::default(),
         }
     }
 }
@@ -91,25 +350,155 @@ pub struct ContentConfig {
     pub auto_index: bool,
     /// Batch size for indexing operations
     pub index_batch_size: usize,
+    /// Tesseract language code used for OCR (requires the `ocr` feature)
+    #[serde(default = "default_ocr_language")]
+    pub ocr_language: String,
+    /// OCR confidence (0.0-1.0) below which extracted text is flagged as unreliable
+    #[serde(default = "default_ocr_min_confidence")]
+    pub ocr_min_confidence: f32,
+    /// Path to the ggml Whisper model used for audio transcription (requires the `transcription` feature)
+    #[serde(default)]
+    pub whisper_model_path: Option<PathBuf>,
+    /// Whisper language code for transcription, or "auto" to detect it
+    #[serde(default = "default_transcription_language")]
+    pub transcription_language: String,
+    /// Include text outputs of code cells when importing Jupyter notebooks
+    #[serde(default = "default_true")]
+    pub notebook_include_outputs: bool,
+    /// CSV/TSV files with more data rows than this are indexed by schema summary only
+    #[serde(default = "default_table_index_row_limit")]
+    pub table_index_row_limit: usize,
+    /// Reading speed per language code (words per minute, characters for CJK)
+    #[serde(default = "crate::content::metadata::default_reading_speeds")]
+    pub reading_speeds: HashMap<String, u32>,
+    /// Automatic generation of digests of recently added documents
+    #[serde(default)]
+    pub digest_schedule: crate::content::digest::DigestSchedule,
+    /// Quick captures repeating an inbox document from the last this many
+    /// minutes are not saved again (0 disables the check)
+    #[serde(default = "default_capture_dedup_minutes")]
+    pub capture_dedup_minutes: u64,
+    /// Folder-to-category mapping for Obsidian and Logseq vault imports
+    #[serde(default)]
+    pub vault_import: crate::content::vault_import::VaultImportConfig,
+    /// Background fetching of subscribed RSS/Atom feeds
+    #[serde(default)]
+    pub feed_schedule: crate::content::rss::FeedSchedule,
+    /// Time budget of one search in milliseconds, shared by its full-text
+    /// and semantic stages (0 disables the limit)
+    #[serde(default = "default_search_timeout_ms")]
+    pub search_timeout_ms: u64,
+    /// Reuse of results for repeated searches
+    #[serde(default)]
+    pub search_cache: crate::content::search::SearchCacheConfig,
+    /// Copies of imported originals under the content directory
+    #[serde(default)]
+    pub attachments: crate::content::attachments::AttachmentConfig,
+    /// Reading session bookkeeping for streaks and goals
+    #[serde(default)]
+    pub reading: crate::content::reading::ReadingConfig,
+    /// Use the date in a markdown file's frontmatter as the document's
+    /// creation time instead of the time of import
+    #[serde(default)]
+    pub respect_source_dates: bool,
+}
+
+fn default_pin_model_version() -> bool {
+    true
+}
+
+fn default_model_registry_public_key() -> Option<String> {
+    Some(crate::update::registry::VENDOR_REGISTRY_PUBLIC_KEY.to_string())
+}
+
+fn default_low_disk_warning_mb() -> u64 {
+    1024
+}
+
+fn default_ocr_language() -> String {
+    "eng".to_string()
+}
+
+fn default_ocr_min_confidence() -> f32 {
+    0.6
+}
+
+fn default_transcription_language() -> String {
+    "auto".to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_table_index_row_limit() -> usize {
+    1000
+}
+
+fn default_capture_dedup_minutes() -> u64 {
+    10
+}
+
+fn default_search_timeout_ms() -> u64 {
+    1500
+}
+
+/// Default list of importable file extensions
+///
+/// Image and audio formats are only included when OCR or transcription
+/// support is compiled in.
+pub fn default_supported_extensions() -> Vec<String> {
+    let mut extensions: Vec<String> = ["txt", "md", "pdf", "epub", "html", "json", "ipynb"]
+        .iter()
+        .map(|e| e.to_string())
+        .collect();
+    extensions.extend(crate::content::tabular::TABULAR_EXTENSIONS.iter().map(|e| e.to_string()));
+    extensions.extend(crate::content::code::CODE_EXTENSIONS.iter().map(|e| e.to_string()));
+
+    if cfg!(feature = "ocr") {
+        extensions.extend(crate::content::ocr::OCR_IMAGE_EXTENSIONS.iter().map(|e| e.to_string()));
+    }
+
+    if cfg!(feature = "transcription") {
+        extensions.extend(crate::content::transcribe::AUDIO_EXTENSIONS.iter().map(|e| e.to_string()));
+    }
+
+    extensions
+}
+
+impl ContentConfig {
+    /// Compression policy for the database's new document bodies
+    pub fn compression_policy(&self) -> crate::db::compression::CompressionPolicy {
+        crate::db::compression::CompressionPolicy::new(self.enable_compression, self.compression_level as i32)
+    }
 }
 
 impl Default for ContentConfig {
     fn default() -> Self {
         Self {
             content_dir: std::env::current_dir().unwrap_or_else(|_| std::path::PathBuf::from(".")).join("content"),
-            supported_extensions: vec![
-                "txt".to_string(),
-                "md".to_string(),
-                "pdf".to_string(),
-                "epub".to_string(),
-                "html".to_string(),
-                "json".to_string(),
-            ],
+            supported_extensions: default_supported_extensions(),
             max_file_size_mb: 100,
             enable_compression: true,
             compression_level: 6,
             auto_index: true,
             index_batch_size: 100,
+            ocr_language: default_ocr_language(),
+            ocr_min_confidence: default_ocr_min_confidence(),
+            whisper_model_path: None,
+            transcription_language: default_transcription_language(),
+            notebook_include_outputs: true,
+            table_index_row_limit: default_table_index_row_limit(),
+            reading_speeds: crate::content::metadata::default_reading_speeds(),
+            digest_schedule: crate::content::digest::DigestSchedule::default(),
+            capture_dedup_minutes: default_capture_dedup_minutes(),
+            vault_import: crate::content::vault_import::VaultImportConfig::default(),
+            feed_schedule: crate::content::rss::FeedSchedule::default(),
+            search_timeout_ms: default_search_timeout_ms(),
+            search_cache: crate::content::search::SearchCacheConfig::default(),
+            attachments: crate::content::attachments::AttachmentConfig::default(),
+            reading: crate::content::reading::ReadingConfig::default(),
+            respect_source_dates: false,
         }
     }
 }
@@ -127,6 +516,13 @@ pub struct UpdateConfig {
     pub enable_delta_updates: bool,
     /// Update channel (stable, beta, nightly)
     pub channel: String,
+    /// Hex Ed25519 key that signs entries of the server's model registry,
+    /// the vendor's key by default; without one only the bundled registry is trusted
+    #[serde(default = "default_model_registry_public_key")]
+    pub model_registry_public_key: Option<String>,
+    /// Keep the known version of each model when the registry offers another
+    #[serde(default = "default_pin_model_version")]
+    pub pin_model_version: bool,
 }
 
 impl Default for UpdateConfig {
@@ -137,6 +533,53 @@ impl Default for UpdateConfig {
             check_interval_hours: 24,
             enable_delta_updates: true,
             channel: "stable".to_string(),
+            model_registry_public_key: default_model_registry_public_key(),
+            pin_model_version: default_pin_model_version(),
+        }
+    }
+}
+
+/// Network usage limits
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkConfig {
+    /// Combined speed limit for model and app downloads, unlimited when `None`
+    pub max_download_bytes_per_sec: Option<u64>,
+}
+
+/// How inference and background work slow down on a laptop's battery or when it runs hot
+///
+/// Only machines with a battery are throttled; on desktops the policy has
+/// no effect.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PowerPolicy {
+    /// Throttle at all
+    pub enabled: bool,
+    /// Throttle while running on battery
+    pub throttle_on_battery: bool,
+    /// Throttle once a sensor reaches this temperature, in degrees Celsius
+    pub thermal_limit_celsius: f32,
+    /// Generations running at a time while throttled
+    pub throttled_concurrency: usize,
+    /// Hold queued background jobs while throttled; running ones finish
+    pub pause_background_jobs: bool,
+    /// Cap on generated tokens while throttled, `None` to keep `ai.max_tokens`
+    pub max_tokens: Option<usize>,
+    /// Seconds between battery and temperature readings
+    pub poll_interval_secs: u64,
+}
+
+impl Default for PowerPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            throttle_on_battery: true,
+            thermal_limit_celsius: 90.0,
+            throttled_concurrency: 1,
+            pause_background_jobs: true,
+            max_tokens: None,
+            poll_interval_secs: 30,
         }
     }
 }
@@ -156,6 +599,135 @@ pub struct AppConfig {
     pub theme: String,
     /// Language/locale
     pub locale: String,
+    /// Log file output
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    /// Local HTTP API for integrations (requires the `api-server` feature)
+    #[serde(default)]
+    pub api_server: ApiServerConfig,
+    /// Never touch the network, not even to fetch missing models
+    #[serde(default)]
+    pub offline_mode: bool,
+    /// Free space on the vault volume, in MB, below which health checks warn
+    #[serde(default = "default_low_disk_warning_mb")]
+    pub low_disk_warning_mb: u64,
+    /// Batching of streamed text and progress events sent to the UI
+    #[serde(default)]
+    pub event_batching: EventBatchingConfig,
+    /// Background cache warm-up after startup
+    #[serde(default)]
+    pub warm_up: WarmUpConfig,
+}
+
+/// What the background warm-up after startup preloads
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WarmUpConfig {
+    /// Run the warm-up when the core is initialized
+    pub enabled: bool,
+    /// Most-viewed documents whose metadata is preloaded
+    pub documents: usize,
+    /// Run the embedding model once so its tokenizer and weights are loaded
+    pub tokenizer: bool,
+    /// Warm up the language model so the first answer is not slower than later ones
+    pub model: bool,
+}
+
+impl Default for WarmUpConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            documents: 50,
+            tokenizer: true,
+            model: true,
+        }
+    }
+}
+
+/// How streamed text and progress updates are batched into UI events
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EventBatchingConfig {
+    /// Longest time buffered text or progress waits, in milliseconds (0 sends everything)
+    pub flush_interval_ms: u64,
+    /// Buffered characters that trigger a flush
+    pub flush_chars: usize,
+}
+
+impl Default for EventBatchingConfig {
+    fn default() -> Self {
+        Self {
+            flush_interval_ms: 50,
+            flush_chars: 200,
+        }
+    }
+}
+
+/// Local HTTP API server configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApiServerConfig {
+    /// Start the server when the core is initialized
+    pub enabled: bool,
+    /// Port on 127.0.0.1 the server listens on (0 picks a free port)
+    pub port: u16,
+}
+
+impl Default for ApiServerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 7797,
+        }
+    }
+}
+
+/// How often the log file is rotated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    Hourly,
+    Daily,
+    Never,
+}
+
+/// Log file configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoggingConfig {
+    /// Write logs to a file besides stdout
+    pub file_enabled: bool,
+    /// Directory for log files (defaults to `logs` in the app data dir)
+    pub directory: Option<PathBuf>,
+    /// How often a new log file is started
+    pub rotation: LogRotation,
+    /// Most log files kept; older files are deleted on rotation
+    pub max_files: usize,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            file_enabled: false,
+            directory: None,
+            rotation: LogRotation::Daily,
+            max_files: 7,
+        }
+    }
+}
+
+impl LoggingConfig {
+    /// Directory log files are written to
+    pub fn log_dir(&self) -> Result<PathBuf> {
+        match &self.directory {
+            Some(directory) => Ok(directory.clone()),
+            None => {
+                let project_dirs = ProjectDirs::from("com", "hanatra", "codex-vault")
+                    .ok_or_else(|| anyhow::anyhow!("Failed to get project directories"))?;
+                Ok(project_dirs.data_dir().join("logs"))
+            }
+        }
+    }
 }
 
 impl Default for CodexConfig {
@@ -181,22 +753,38 @@ impl Default for CodexConfig {
                 device: "cpu".to_string(),
                 enable_caching: true,
                 cache_size_mb: 512,
+                rag: RagRetrievalConfig::default(),
+                embedding: EmbeddingModelConfig::default(),
+                audit: AiAuditConfig::default(),
+                draft_model: None,
+                speculative: SpeculativeDecodingConfig::default(),
+                truncation: TruncationPolicy::default(),
+                gpu_layers: GpuLayers::default(),
             },
             content: ContentConfig {
                 content_dir: project_dirs.data_dir().join("content"),
-                supported_extensions: vec![
-                    "txt".to_string(),
-                    "md".to_string(),
-                    "pdf".to_string(),
-                    "epub".to_string(),
-                    "html".to_string(),
-                    "json".to_string(),
-                ],
+                supported_extensions: default_supported_extensions(),
                 max_file_size_mb: 100,
                 enable_compression: true,
                 compression_level: 6,
                 auto_index: true,
                 index_batch_size: 100,
+                ocr_language: default_ocr_language(),
+                ocr_min_confidence: default_ocr_min_confidence(),
+                whisper_model_path: Some(project_dirs.data_dir().join("models").join("ggml-base.en.bin")),
+                transcription_language: default_transcription_language(),
+                notebook_include_outputs: true,
+                table_index_row_limit: default_table_index_row_limit(),
+                reading_speeds: crate::content::metadata::default_reading_speeds(),
+                digest_schedule: crate::content::digest::DigestSchedule::default(),
+                capture_dedup_minutes: default_capture_dedup_minutes(),
+            vault_import: crate::content::vault_import::VaultImportConfig::default(),
+            feed_schedule: crate::content::rss::FeedSchedule::default(),
+            search_timeout_ms: default_search_timeout_ms(),
+            search_cache: crate::content::search::SearchCacheConfig::default(),
+            attachments: crate::content::attachments::AttachmentConfig::default(),
+            reading: crate::content::reading::ReadingConfig::default(),
+            respect_source_dates: false,
             },
             update: UpdateConfig {
                 server_url: "https://updates.codex-vault.com".to_string(),
@@ -204,6 +792,8 @@ impl Default for CodexConfig {
                 check_interval_hours: 24,
                 enable_delta_updates: true,
                 channel: "stable".to_string(),
+                model_registry_public_key: default_model_registry_public_key(),
+                pin_model_version: default_pin_model_version(),
             },
             app: AppConfig {
                 name: "Codex Vault".to_string(),
@@ -212,7 +802,16 @@ impl Default for CodexConfig {
                 enable_telemetry: false,
                 theme: "auto".to_string(),
                 locale: "en-US".to_string(),
+                logging: LoggingConfig::default(),
+                api_server: ApiServerConfig::default(),
+                offline_mode: false,
+                low_disk_warning_mb: default_low_disk_warning_mb(),
+                event_batching: EventBatchingConfig::default(),
+                warm_up: WarmUpConfig::default(),
             },
+            network: NetworkConfig::default(),
+            power: PowerPolicy::default(),
+            safe_mode: false,
         }
     }
 }
@@ -225,12 +824,39 @@ impl CodexConfig {
 
         let config_path = project_dirs.config_dir().join("config.toml");
         
-        if config_path.exists() {
-            Self::load_from_file(config_path).await
+        let mut config = if config_path.exists() {
+            Self::load_from_file(config_path).await?
         } else {
             let config = Self::default();
             config.save_to_default().await?;
-            Ok(config)
+            config
+        };
+        config.safe_mode = safe_mode_requested();
+        Ok(config)
+    }
+
+    /// Start the next launch in safe mode
+    ///
+    /// The request is cleared once a core has started in safe mode, so only
+    /// one launch is affected.
+    pub async fn request_safe_mode() -> Result<()> {
+        let project_dirs = ProjectDirs::from("com", "hanatra", "codex-vault")
+            .ok_or_else(|| anyhow::anyhow!("Failed to get project directories"))?;
+
+        let config_dir = project_dirs.config_dir();
+        tokio::fs::create_dir_all(config_dir).await?;
+        tokio::fs::write(config_dir.join(SAFE_MODE_MARKER), b"").await?;
+        Ok(())
+    }
+
+    /// Clear a safe mode request made with [`CodexConfig::request_safe_mode`]
+    pub async fn clear_safe_mode_request() -> Result<()> {
+        let project_dirs = ProjectDirs::from("com", "hanatra", "codex-vault")
+            .ok_or_else(|| anyhow::anyhow!("Failed to get project directories"))?;
+
+        match tokio::fs::remove_file(project_dirs.config_dir().join(SAFE_MODE_MARKER)).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
         }
     }
 
@@ -241,6 +867,21 @@ impl CodexConfig {
         Ok(config)
     }
 
+    /// Keep the database, models and content of this configuration in `vault_dir`
+    pub fn with_vault_dir<P: AsRef<std::path::Path>>(mut self, vault_dir: P) -> Self {
+        let vault_dir = vault_dir.as_ref();
+        self.database.path = vault_dir.join("codex.db");
+        self.ai.models_dir = vault_dir.join("models");
+        self.content.content_dir = vault_dir.join("content");
+        self.content.whisper_model_path = self
+            .content
+            .whisper_model_path
+            .as_ref()
+            .and_then(|path| path.file_name())
+            .map(|name| self.ai.models_dir.join(name));
+        self
+    }
+
     /// Save configuration to the default location
     pub async fn save_to_default(&self) -> Result<()> {
         let project_dirs = ProjectDirs::from("com", "hanatra", "codex-vault")
@@ -301,6 +942,19 @@ impl CodexConfig {
             return Err(anyhow::anyhow!("AI top_p must be between 0.0 and 1.0"));
         }
 
+        let rag = &self.ai.rag;
+        if rag.text_weight < 0.0 || rag.semantic_weight < 0.0 || rag.text_weight + rag.semantic_weight <= 0.0 {
+            return Err(anyhow::anyhow!("AI rag weights must be non-negative and not both 0"));
+        }
+
+        if rag.max_chunks_per_document == 0 {
+            return Err(anyhow::anyhow!("AI rag max_chunks_per_document must be > 0"));
+        }
+
+        if !(0.0..=1.0).contains(&rag.low_confidence_threshold) {
+            return Err(anyhow::anyhow!("AI rag low_confidence_threshold must be between 0.0 and 1.0"));
+        }
+
         // Validate content configuration
         if self.content.max_file_size_mb == 0 {
             return Err(anyhow::anyhow!("Content max_file_size_mb must be > 0"));
@@ -310,10 +964,32 @@ impl CodexConfig {
             return Err(anyhow::anyhow!("Content compression_level must be between 1 and 9"));
         }
 
+        if !(0.0..=1.0).contains(&self.content.ocr_min_confidence) {
+            return Err(anyhow::anyhow!("Content ocr_min_confidence must be between 0.0 and 1.0"));
+        }
+
+        // Validate app configuration
+        if self.app.logging.max_files == 0 {
+            return Err(anyhow::anyhow!("App logging max_files must be > 0"));
+        }
+
+        if self.power.throttled_concurrency == 0 {
+            return Err(anyhow::anyhow!("Power throttled_concurrency must be > 0"));
+        }
+
         Ok(())
     }
 }
 
+/// Whether `CODEX_SAFE_MODE=1` is set or safe mode was requested for this launch
+fn safe_mode_requested() -> bool {
+    if std::env::var(SAFE_MODE_ENV).is_ok_and(|value| value == "1") {
+        return true;
+    }
+    ProjectDirs::from("com", "hanatra", "codex-vault")
+        .is_some_and(|dirs| dirs.config_dir().join(SAFE_MODE_MARKER).exists())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -323,6 +999,14 @@ mod tests {
     async fn test_config_default() {
         let config = CodexConfig::default();
         assert!(config.validate().is_ok());
+
+        // Configs saved before the key existed trust the vendor's registry key
+        let update: UpdateConfig = serde_json::from_str(
+            r#"{"server_url": "https://updates.example.com", "auto_check": true, "check_interval_hours": 24, "enable_delta_updates": true, "channel": "stable"}"#,
+        )
+        .unwrap();
+        assert_eq!(update.model_registry_public_key, config.update.model_registry_public_key);
+        assert_eq!(update.model_registry_public_key.as_deref(), Some(crate::update::registry::VENDOR_REGISTRY_PUBLIC_KEY));
     }
 
     #[tokio::test]
@@ -339,4 +1023,44 @@ mod tests {
         assert_eq!(original_config.app.name, loaded_config.app.name);
         assert_eq!(original_config.ai.temperature, loaded_config.ai.temperature);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_gpu_layers_are_a_count_or_auto() {
+        #[derive(Deserialize)]
+        struct Layers {
+            gpu_layers: GpuLayers,
+        }
+        let parse = |text: &str| toml::from_str::<Layers>(text).map(|layers| layers.gpu_layers);
+
+        assert_eq!(parse("gpu_layers = \"auto\"").unwrap(), GpuLayers::Auto);
+        assert_eq!(parse("gpu_layers = 20").unwrap(), GpuLayers::Count(20));
+        assert!(parse("gpu_layers = \"all\"").is_err());
+        assert!(toml::to_string(&CodexConfig::default().ai).unwrap().contains("gpu_layers = \"auto\""));
+    }
+
+    #[tokio::test]
+    async fn test_safe_mode_is_not_saved() {
+        let temp_dir = tempdir().unwrap();
+        let config_path = temp_dir.path().join("config.toml");
+
+        let config = CodexConfig { safe_mode: true, ..CodexConfig::default() };
+        config.save_to_file(&config_path).await.unwrap();
+
+        let loaded = CodexConfig::load_from_file(&config_path).await.unwrap();
+        assert!(!loaded.safe_mode);
+    }
+
+    #[test]
+    fn test_with_vault_dir_reroots_storage() {
+        let mut config = CodexConfig::default();
+        config.content.whisper_model_path = Some(PathBuf::from("/elsewhere/ggml-base.en.bin"));
+
+        let config = config.with_vault_dir("/vaults/work");
+        assert_eq!(config.database.path, PathBuf::from("/vaults/work/codex.db"));
+        assert_eq!(config.ai.models_dir, PathBuf::from("/vaults/work/models"));
+        assert_eq!(
+            config.content.whisper_model_path,
+            Some(PathBuf::from("/vaults/work/models/ggml-base.en.bin"))
+        );
+    }
+}
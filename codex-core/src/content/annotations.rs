@@ -0,0 +1,284 @@
+//! Highlights and notes on document text
+//!
+//! Annotations address a character range of the document content and keep
+//! a snapshot of the highlighted text. When the content is edited, each
+//! annotation is re-anchored to the occurrence of its snapshot nearest its
+//! old position, or flagged as orphaned if the text is gone.
+//!
+//! Highlights export to Markdown, one file per document plus a combined
+//! file, with `codex://` links back to each highlighted position.
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::deep_link::{make_deep_link, NavigationTarget};
+use crate::db::models::{Annotation, Highlight};
+use crate::{CodexError, CodexResult};
+
+/// Longest accepted color value (names or `#rrggbb`)
+const MAX_COLOR_LEN: usize = 32;
+/// File name of the combined highlights export
+pub const ALL_HIGHLIGHTS_FILE: &str = "all-highlights";
+/// Characters of a document title kept in its export file name
+const MAX_FILE_TITLE_CHARS: usize = 60;
+
+/// Annotations to export
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AnnotationExportScope {
+    /// One document's annotations
+    Document { id: Uuid },
+    /// Every annotated document, plus a combined file of all highlights
+    All,
+}
+
+/// Annotation export file format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnnotationExportFormat {
+    Markdown,
+    Json,
+}
+
+impl AnnotationExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AnnotationExportFormat::Markdown => "md",
+            AnnotationExportFormat::Json => "json",
+        }
+    }
+}
+
+/// Files written by an annotation export
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AnnotationExportResult {
+    pub files: Vec<PathBuf>,
+    pub documents: usize,
+    pub annotations: usize,
+}
+
+/// Byte index of the character at `offset`, or of the end for `offset == len`
+fn byte_index(content: &str, offset: usize) -> Option<usize> {
+    content
+        .char_indices()
+        .map(|(i, _)| i)
+        .chain(std::iter::once(content.len()))
+        .nth(offset)
+}
+
+/// Text of the character range `[start, end)`, checking it lies in `content`
+pub fn range_text(content: &str, start: i64, end: i64) -> CodexResult<&str> {
+    if start < 0 || end <= start {
+        return Err(CodexError::validation("Annotation range must be non-empty"));
+    }
+    let (Some(from), Some(to)) = (byte_index(content, start as usize), byte_index(content, end as usize)) else {
+        return Err(CodexError::validation("Annotation range is outside the document"));
+    };
+    Ok(&content[from..to])
+}
+
+/// Check a highlight color value
+pub fn validate_color(color: &str) -> CodexResult<()> {
+    if color.trim().is_empty() || color.len() > MAX_COLOR_LEN {
+        return Err(CodexError::validation("Invalid annotation color"));
+    }
+    Ok(())
+}
+
+/// New range of an annotation in edited content, if its text is still there
+///
+/// An unchanged range is kept; otherwise the occurrence of the snapshot
+/// whose start is nearest the old start wins.
+pub fn reanchor(content: &str, annotation: &Annotation) -> Option<(i64, i64)> {
+    let snapshot = annotation.selected_text.as_str();
+    if snapshot.is_empty() {
+        return None;
+    }
+    if range_text(content, annotation.start_offset, annotation.end_offset).ok() == Some(snapshot) {
+        return Some((annotation.start_offset, annotation.end_offset));
+    }
+
+    let length = snapshot.chars().count() as i64;
+    let mut best: Option<i64> = None;
+    let mut chars_before = 0i64;
+    let mut last_byte = 0;
+    for (byte, _) in content.match_indices(snapshot) {
+        chars_before += content[last_byte..byte].chars().count() as i64;
+        last_byte = byte;
+        let closer = best.is_none_or(|b| (chars_before - annotation.start_offset).abs() < (b - annotation.start_offset).abs());
+        if closer {
+            best = Some(chars_before);
+        }
+    }
+
+    best.map(|start| (start, start + length))
+}
+
+/// Re-anchor annotations to edited content, returning those that changed
+pub fn heal_annotations(content: &str, annotations: Vec<Annotation>) -> Vec<Annotation> {
+    annotations
+        .into_iter()
+        .filter_map(|mut annotation| {
+            let before = (annotation.start_offset, annotation.end_offset, annotation.orphaned);
+            match reanchor(content, &annotation) {
+                Some((start, end)) => {
+                    annotation.start_offset = start;
+                    annotation.end_offset = end;
+                    annotation.orphaned = false;
+                }
+                None => annotation.orphaned = true,
+            }
+            let after = (annotation.start_offset, annotation.end_offset, annotation.orphaned);
+            (before != after).then_some(annotation)
+        })
+        .collect()
+}
+
+/// `codex://` link to a document, optionally at a character offset
+fn deep_link(document_id: &str, position: Option<i64>) -> String {
+    let id = Uuid::parse_str(document_id).unwrap_or_default();
+    make_deep_link(&NavigationTarget::Document { id, position })
+}
+
+/// Export file name for a document: its title made file-safe, then its short id
+pub fn export_file_stem(document_id: &str, title: &str) -> String {
+    let title: String = title
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join("-")
+        .chars()
+        .take(MAX_FILE_TITLE_CHARS)
+        .collect();
+    let short_id: String = document_id.chars().take(8).collect();
+    if title.is_empty() { short_id } else { format!("{}-{}", title, short_id) }
+}
+
+/// One highlight as a blockquote, its note beneath and a link back
+fn render_highlight(output: &mut String, annotation: &Annotation) {
+    output.push_str(&format!("\n> {}\n", annotation.selected_text.trim().replace('\n', "\n> ")));
+    if let Some(note) = annotation.note.as_deref().map(str::trim).filter(|n| !n.is_empty()) {
+        output.push_str(&format!("\n{}\n", note));
+    }
+    let position = (!annotation.orphaned).then_some(annotation.start_offset);
+    output.push_str(&format!("\n[Open in Codex]({})\n", deep_link(&annotation.document_id, position)));
+}
+
+/// Markdown for one document's highlights in reading order
+///
+/// Highlights whose text was edited away are listed last under "Unanchored".
+pub fn render_document_markdown(document_id: &str, title: &str, annotations: &[Annotation]) -> String {
+    let mut output = format!("# {}\n\n[Open document]({})\n", title, deep_link(document_id, None));
+
+    let (anchored, unanchored): (Vec<&Annotation>, Vec<&Annotation>) = annotations.iter().partition(|a| !a.orphaned);
+    for annotation in anchored {
+        render_highlight(&mut output, annotation);
+    }
+    if !unanchored.is_empty() {
+        output.push_str("\n## Unanchored\n\nThe text of these highlights was changed or removed.\n");
+        for annotation in unanchored {
+            render_highlight(&mut output, annotation);
+        }
+    }
+
+    output
+}
+
+/// Markdown for highlights across documents, in the order given
+pub fn render_all_markdown(highlights: &[Highlight]) -> String {
+    let mut output = String::from("# All Highlights\n");
+    let mut current_date = None;
+
+    for highlight in highlights {
+        let date = highlight.annotation.created_at.get(..10).unwrap_or_default();
+        if current_date != Some(date) {
+            output.push_str(&format!("\n## {}\n", date));
+            current_date = Some(date);
+        }
+        output.push_str(&format!("\n### {}\n", highlight.document_title));
+        if highlight.annotation.orphaned {
+            output.push_str("\n*Unanchored: the highlighted text was changed or removed.*\n");
+        }
+        render_highlight(&mut output, &highlight.annotation);
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn annotation(content: &str, text: &str) -> Annotation {
+        let byte = content.find(text).unwrap();
+        let start = content[..byte].chars().count() as i64;
+        let end = start + text.chars().count() as i64;
+        assert_eq!(range_text(content, start, end).unwrap(), text);
+        Annotation::new("doc".to_string(), start, end, text.to_string())
+    }
+
+    #[test]
+    fn test_annotations_follow_edits() {
+        let original = "Café notes. The key idea is here. Later, the key idea is repeated.";
+        let moved = annotation(original, "key idea is here");
+        let repeated = annotation(original, "the key idea");
+        let removed = annotation(original, "Later");
+
+        assert!(range_text(original, 5, 5).is_err());
+        assert!(range_text(original, 60, 200).is_err());
+
+        let edited = "Intro added. Café notes. The key idea is here. Later, the key idea is repeated.";
+        let changed = heal_annotations(edited, vec![moved.clone(), repeated.clone()]);
+        assert_eq!(changed.len(), 2);
+        assert_eq!(range_text(edited, changed[0].start_offset, changed[0].end_offset).unwrap(), "key idea is here");
+        // Shifted by the inserted intro
+        assert_eq!(changed[1].start_offset, repeated.start_offset + 13);
+
+        let rewritten = "Café notes. The key idea is here. Afterwards, the key idea is repeated.";
+        let changed = heal_annotations(rewritten, vec![moved.clone(), removed.clone()]);
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].id, removed.id);
+        assert!(changed[0].orphaned);
+
+        // Restoring the text re-attaches an orphaned highlight
+        let restored = heal_annotations(original, changed);
+        assert!(!restored[0].orphaned);
+        assert_eq!(restored[0].start_offset, removed.start_offset);
+    }
+
+    #[test]
+    fn test_markdown_export_with_unanchored_section() {
+        let content = "Alpha beta gamma.";
+        let id = Uuid::new_v4().to_string();
+        let mut first = annotation(content, "beta");
+        first.document_id = id.clone();
+        first.note = Some("Second letter".to_string());
+        let mut orphaned = annotation(content, "gamma");
+        orphaned.document_id = id.clone();
+        orphaned.orphaned = true;
+
+        let markdown = render_document_markdown(&id, "Greek: Letters", &[orphaned.clone(), first.clone()]);
+        assert!(markdown.starts_with(&format!("# Greek: Letters\n\n[Open document](codex://document/{})\n", id)));
+        assert!(markdown.contains(&format!(
+            "\n> beta\n\nSecond letter\n\n[Open in Codex](codex://document/{}?pos=6)\n",
+            id
+        )));
+        let (anchored, unanchored) = markdown.split_once("## Unanchored").unwrap();
+        assert!(!anchored.contains("gamma"));
+        assert!(unanchored.contains(&format!("> gamma\n\n[Open in Codex](codex://document/{})\n", id)));
+
+        let highlights: Vec<Highlight> = [first, orphaned]
+            .into_iter()
+            .map(|annotation| Highlight { annotation, document_title: "Greek: Letters".to_string() })
+            .collect();
+        let combined = render_all_markdown(&highlights);
+        assert_eq!(combined.matches("### Greek: Letters").count(), 2);
+        assert!(combined.contains("*Unanchored"));
+
+        assert_eq!(export_file_stem("0123456789", "Greek: Letters / Notes"), "Greek-Letters-Notes-01234567");
+    }
+}
@@ -0,0 +1,212 @@
+//! Managed store for the original files of imported documents
+//!
+//! With [`AttachmentConfig::copy_originals`] enabled, imports copy the
+//! source file to `attachments/<first two hex digits>/<sha256>` under the
+//! content directory, so a document keeps its original after the user moves
+//! or deletes the file. Files are named by the hash of their bytes, which
+//! stores identical originals once. The `attachments` table lists the stored
+//! files and documents refer to them through `attachment_id`; files no
+//! document refers to are removed by
+//! [`ContentManager::collect_attachment_garbage`](super::ContentManager::collect_attachment_garbage).
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::{CodexError, CodexResult};
+
+/// Directory of the store inside the content directory
+pub const ATTACHMENTS_DIR: &str = "attachments";
+/// Files being copied in, renamed into place once complete
+const INCOMING_DIR: &str = ".incoming";
+/// Time between garbage collection runs
+pub const ATTACHMENT_GC_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+/// Unreferenced files younger than this are kept, as their import may still
+/// be about to point a document at them
+pub const ATTACHMENT_GC_GRACE: Duration = Duration::from_secs(60 * 60);
+
+const COPY_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Whether imports keep a copy of their original file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AttachmentConfig {
+    /// Copy originals into the store; otherwise documents only record the
+    /// path they were imported from
+    pub copy_originals: bool,
+}
+
+/// File copied into the store
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredFile {
+    /// SHA-256 of the bytes, the attachment id
+    pub id: String,
+    pub size: u64,
+}
+
+/// Content-addressed files under `<content_dir>/attachments`
+#[derive(Debug, Clone)]
+pub struct AttachmentStore {
+    root: PathBuf,
+}
+
+impl AttachmentStore {
+    pub fn new(content_dir: &Path) -> Self {
+        Self {
+            root: content_dir.join(ATTACHMENTS_DIR),
+        }
+    }
+
+    /// Location of an attachment relative to the store root
+    ///
+    /// Fails for anything but a lowercase SHA-256 hex digest, so ids never
+    /// name paths outside the store.
+    pub fn relative_path(id: &str) -> CodexResult<PathBuf> {
+        if id.len() != 64 || !id.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')) {
+            return Err(CodexError::validation(format!("Invalid attachment id: {}", id)));
+        }
+        Ok(Path::new(&id[..2]).join(id))
+    }
+
+    /// Location of an attachment on disk, whether or not it is stored
+    pub fn path_for(&self, id: &str) -> CodexResult<PathBuf> {
+        Ok(self.root.join(Self::relative_path(id)?))
+    }
+
+    /// Copy `source` into the store, returning its id
+    ///
+    /// The copy is hashed as it is written and only moved into place once
+    /// complete, so a crash never leaves a partial file under a valid id.
+    /// Storing a file that is already present keeps the existing copy.
+    pub async fn store(&self, source: &Path) -> CodexResult<StoredFile> {
+        let incoming = self.root.join(INCOMING_DIR);
+        tokio::fs::create_dir_all(&incoming).await?;
+        let temp_path = incoming.join(uuid::Uuid::new_v4().to_string());
+
+        let copied = Self::copy_hashed(source, &temp_path).await;
+        let (id, size) = match copied {
+            Ok(copied) => copied,
+            Err(e) => {
+                let _ = tokio::fs::remove_file(&temp_path).await;
+                return Err(e);
+            }
+        };
+
+        let path = self.path_for(&id)?;
+        if tokio::fs::try_exists(&path).await? {
+            tokio::fs::remove_file(&temp_path).await?;
+        } else {
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            tokio::fs::rename(&temp_path, &path).await?;
+        }
+
+        Ok(StoredFile { id, size })
+    }
+
+    async fn copy_hashed(source: &Path, destination: &Path) -> CodexResult<(String, u64)> {
+        let mut reader = tokio::fs::File::open(source).await?;
+        let mut writer = tokio::fs::File::create(destination).await?;
+        let mut hasher = Sha256::new();
+        let mut buffer = vec![0u8; COPY_BUFFER_SIZE];
+        let mut size = 0u64;
+
+        loop {
+            let read = reader.read(&mut buffer).await?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+            writer.write_all(&buffer[..read]).await?;
+            size += read as u64;
+        }
+        writer.sync_all().await?;
+
+        Ok((format!("{:x}", hasher.finalize()), size))
+    }
+
+    /// Delete an attachment's file; returns false when it was not stored
+    pub async fn remove(&self, id: &str) -> CodexResult<bool> {
+        match tokio::fs::remove_file(self.path_for(id)?).await {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Ids of the files in the store last modified before `cutoff`
+    ///
+    /// Unfinished copies left by a crash are deleted on the way when they
+    /// are older than the cutoff.
+    pub async fn files_older_than(&self, cutoff: SystemTime) -> CodexResult<Vec<String>> {
+        let mut ids = Vec::new();
+        let mut prefixes = match tokio::fs::read_dir(&self.root).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(ids),
+            Err(e) => return Err(e.into()),
+        };
+
+        while let Some(prefix) = prefixes.next_entry().await? {
+            let is_incoming = prefix.file_name() == INCOMING_DIR;
+            if !prefix.file_type().await?.is_dir() {
+                continue;
+            }
+
+            let mut files = tokio::fs::read_dir(prefix.path()).await?;
+            while let Some(file) = files.next_entry().await? {
+                let modified = file.metadata().await?.modified()?;
+                if modified >= cutoff {
+                    continue;
+                }
+                if is_incoming {
+                    let _ = tokio::fs::remove_file(file.path()).await;
+                } else if let Some(id) = file.file_name().to_str() {
+                    if Self::relative_path(id).is_ok() {
+                        ids.push(id.to_string());
+                    }
+                }
+            }
+        }
+
+        Ok(ids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_identical_files_are_stored_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = AttachmentStore::new(dir.path());
+        let first = dir.path().join("report.pdf");
+        let second = dir.path().join("copy of report.pdf");
+        std::fs::write(&first, b"%PDF-1.4 original bytes").unwrap();
+        std::fs::write(&second, b"%PDF-1.4 original bytes").unwrap();
+
+        let stored = store.store(&first).await.unwrap();
+        assert_eq!(store.store(&second).await.unwrap(), stored);
+        assert_eq!(stored.size, 23);
+
+        let path = store.path_for(&stored.id).unwrap();
+        assert_eq!(path, dir.path().join(ATTACHMENTS_DIR).join(&stored.id[..2]).join(&stored.id));
+        assert_eq!(std::fs::read(&path).unwrap(), b"%PDF-1.4 original bytes");
+
+        let later = SystemTime::now() + Duration::from_secs(1);
+        assert_eq!(store.files_older_than(later).await.unwrap(), vec![stored.id.clone()]);
+        assert!(store.remove(&stored.id).await.unwrap());
+        assert!(!store.remove(&stored.id).await.unwrap());
+    }
+
+    #[test]
+    fn test_ids_cannot_leave_the_store() {
+        assert!(AttachmentStore::relative_path("../../etc/passwd").is_err());
+        assert!(AttachmentStore::relative_path(&"A".repeat(64)).is_err());
+        assert!(AttachmentStore::relative_path(&"0f".repeat(32)).is_ok());
+    }
+}
@@ -0,0 +1,440 @@
+//! BibTeX import and citation formatting
+//!
+//! Parses `.bib` files as exported by Zotero, JabRef and friends, and
+//! formats citations for stored documents from their author, title,
+//! source, year and citation key. Authors are stored separated by `"; "`
+//! in the form they were written (`Last, First` or `First Last`).
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::db::models::Document;
+use crate::CodexError;
+
+/// Separator between authors in `Document::author` for cited works
+pub const AUTHOR_SEPARATOR: &str = "; ";
+/// Prefix of DOI links stored as a document's URL
+pub const DOI_URL_PREFIX: &str = "https://doi.org/";
+
+/// One `@type{key, field = value, ...}` entry
+#[derive(Debug, Clone, PartialEq)]
+pub struct BibEntry {
+    /// Entry type in lowercase (`article`, `book`, ...)
+    pub entry_type: String,
+    pub key: String,
+    /// Field values with braces and simple LaTeX escapes removed, by lowercase name
+    pub fields: BTreeMap<String, String>,
+}
+
+impl BibEntry {
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields.get(name).map(String::as_str).filter(|v| !v.is_empty())
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        self.field("title")
+    }
+
+    /// Authors (or editors when there are none), in the order given
+    pub fn authors(&self) -> Vec<String> {
+        self.field("author")
+            .or_else(|| self.field("editor"))
+            .map(|names| names.split(" and ").map(|n| n.trim().to_string()).filter(|n| !n.is_empty()).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn year(&self) -> Option<i64> {
+        let year = self.field("year").or_else(|| self.field("date"))?;
+        year.get(..4)?.parse().ok()
+    }
+
+    /// DOI without any `https://doi.org/` prefix, lowercased
+    pub fn doi(&self) -> Option<String> {
+        let doi = self.field("doi")?;
+        let doi = doi.trim_start_matches("https://doi.org/").trim_start_matches("http://dx.doi.org/");
+        Some(doi.to_lowercase())
+    }
+
+    /// Journal, book, publisher or institution the work appeared in
+    pub fn source(&self) -> Option<&str> {
+        ["journal", "journaltitle", "booktitle", "publisher", "school", "institution", "howpublished"]
+            .iter()
+            .find_map(|name| self.field(name))
+    }
+
+    /// Attached file paths; Zotero writes `Description:path:mime` joined by `;`
+    pub fn files(&self) -> Vec<String> {
+        let Some(files) = self.field("file") else {
+            return Vec::new();
+        };
+        files
+            .split(';')
+            .map(|file| {
+                let parts: Vec<&str> = file.split(':').collect();
+                match parts.len() {
+                    // Description:path:mime, with a Windows drive letter splitting the path
+                    n if n >= 3 => parts[1..n - 1].join(":"),
+                    _ => file.to_string(),
+                }
+            })
+            .map(|path| path.trim().replace("\\:", ":"))
+            .filter(|path| !path.is_empty())
+            .collect()
+    }
+}
+
+/// How a BibTeX entry was matched to a document
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchedBy {
+    Doi,
+    FilePath,
+    Title,
+}
+
+/// One entry applied to an existing document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BibtexMatch {
+    pub citation_key: String,
+    pub document_id: String,
+    pub matched_by: MatchedBy,
+}
+
+/// Result of [`crate::content::ContentManager::import_bibtex`]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BibtexImportResult {
+    pub total_entries: usize,
+    pub matched: Vec<BibtexMatch>,
+    /// Stub documents created for entries that matched nothing
+    pub created: Vec<String>,
+    /// Citation keys of entries that matched nothing and got no stub
+    pub unmatched: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+/// Parse the entries of a BibTeX file
+///
+/// `@string`, `@preamble` and `@comment` blocks are skipped, as are
+/// malformed entries; string macros are not expanded.
+pub fn parse_bibtex(text: &str) -> Vec<BibEntry> {
+    let mut entries = Vec::new();
+    let mut rest = text;
+
+    while let Some(at) = rest.find('@') {
+        rest = &rest[at + 1..];
+        let Some(open) = rest.find(['{', '(']) else {
+            break;
+        };
+        let entry_type = rest[..open].trim().to_lowercase();
+        let Some(body_len) = matching_close(&rest[open..]) else {
+            break;
+        };
+        let body = &rest[open + 1..open + body_len];
+        rest = &rest[open + body_len + 1..];
+
+        if matches!(entry_type.as_str(), "string" | "preamble" | "comment") || entry_type.contains(char::is_whitespace) {
+            continue;
+        }
+        if let Some(entry) = parse_entry(entry_type, body) {
+            entries.push(entry);
+        }
+    }
+
+    entries
+}
+
+/// Length up to the bracket closing the one `text` starts with
+fn matching_close(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '{' | '(' => depth += 1,
+            '}' | ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn parse_entry(entry_type: String, body: &str) -> Option<BibEntry> {
+    let (key, mut rest) = body.split_once(',')?;
+    let key = key.trim().to_string();
+    if key.is_empty() {
+        return None;
+    }
+
+    let mut fields = BTreeMap::new();
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+        let Some(eq) = rest.find('=') else {
+            break;
+        };
+        let name = rest[..eq].trim().to_lowercase();
+        let value_start = rest[eq + 1..].trim_start();
+
+        let (raw, remaining) = match value_start.chars().next() {
+            Some('{') => {
+                let end = matching_close(value_start)?;
+                (&value_start[1..end], &value_start[end + 1..])
+            }
+            Some('"') => {
+                let end = value_start[1..].find('"')? + 1;
+                (&value_start[1..end], &value_start[end + 1..])
+            }
+            _ => {
+                let end = value_start.find(',').unwrap_or(value_start.len());
+                (value_start[..end].trim(), &value_start[end..])
+            }
+        };
+
+        fields.insert(name, clean_value(raw));
+        rest = remaining;
+    }
+
+    Some(BibEntry { entry_type, key, fields })
+}
+
+/// Remove grouping braces and common LaTeX escapes, collapsing whitespace
+fn clean_value(raw: &str) -> String {
+    let text = raw
+        .replace("\\&", "&")
+        .replace("\\%", "%")
+        .replace("\\$", "$")
+        .replace("\\_", "_")
+        .replace("\\#", "#")
+        .replace("--", "–")
+        .replace(['{', '}'], "");
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Citation style for [`format_citation`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CitationStyle {
+    Apa,
+    Mla,
+    Bibtex,
+}
+
+impl std::str::FromStr for CitationStyle {
+    type Err = CodexError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "apa" => Ok(Self::Apa),
+            "mla" => Ok(Self::Mla),
+            "bibtex" | "bib" => Ok(Self::Bibtex),
+            other => Err(CodexError::validation(format!("Unknown citation style: {}", other))),
+        }
+    }
+}
+
+/// A name split into family and given parts
+struct Name {
+    family: String,
+    given: String,
+}
+
+impl Name {
+    fn parse(name: &str) -> Self {
+        match name.split_once(',') {
+            Some((family, given)) => Self { family: family.trim().to_string(), given: given.trim().to_string() },
+            None => match name.trim().rsplit_once(' ') {
+                Some((given, family)) => Self { family: family.to_string(), given: given.to_string() },
+                None => Self { family: name.trim().to_string(), given: String::new() },
+            },
+        }
+    }
+
+    /// `F. M.` from `First Middle`
+    fn initials(&self) -> String {
+        self.given
+            .split([' ', '-'])
+            .filter_map(|part| part.chars().next())
+            .map(|c| format!("{}.", c))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn family_initials(&self) -> String {
+        match self.initials() {
+            initials if initials.is_empty() => self.family.clone(),
+            initials => format!("{}, {}", self.family, initials),
+        }
+    }
+
+    fn family_given(&self) -> String {
+        if self.given.is_empty() { self.family.clone() } else { format!("{}, {}", self.family, self.given) }
+    }
+
+    fn given_family(&self) -> String {
+        if self.given.is_empty() { self.family.clone() } else { format!("{} {}", self.given, self.family) }
+    }
+}
+
+/// Authors of a document as stored by [`AUTHOR_SEPARATOR`]
+pub fn document_authors(document: &Document) -> Vec<String> {
+    document
+        .author
+        .as_deref()
+        .map(|authors| authors.split(';').map(|a| a.trim().to_string()).filter(|a| !a.is_empty()).collect())
+        .unwrap_or_default()
+}
+
+/// Format a citation for a document
+pub fn format_citation(document: &Document, style: CitationStyle) -> String {
+    let names: Vec<Name> = document_authors(document).iter().map(|a| Name::parse(a)).collect();
+    let title = document.title.trim_end_matches('.');
+    let doi = document.url.as_deref().and_then(|url| url.strip_prefix(DOI_URL_PREFIX));
+
+    match style {
+        CitationStyle::Apa => {
+            let authors: Vec<String> = names.iter().map(Name::family_initials).collect();
+            let authors = match authors.as_slice() {
+                [] => String::new(),
+                [one] => one.clone(),
+                [init @ .., last] => format!("{}, & {}", init.join(", "), last),
+            };
+            let year = document.publication_year.map(|y| y.to_string()).unwrap_or_else(|| "n.d.".to_string());
+
+            let mut citation = if authors.is_empty() {
+                format!("{}. ({}).", title, year)
+            } else {
+                format!("{} ({}). {}.", authors, year, title)
+            };
+            if let Some(source) = document.source.as_deref() {
+                citation.push_str(&format!(" {}.", source));
+            }
+            if let Some(url) = &document.url {
+                citation.push_str(&format!(" {}", url));
+            }
+            citation
+        }
+        CitationStyle::Mla => {
+            let authors = match names.as_slice() {
+                [] => String::new(),
+                [one] => one.family_given(),
+                [first, second] => format!("{}, and {}", first.family_given(), second.given_family()),
+                [first, ..] => format!("{}, et al", first.family_given()),
+            };
+
+            let mut citation = String::new();
+            if !authors.is_empty() {
+                citation.push_str(&format!("{}. ", authors.trim_end_matches('.')));
+            }
+            citation.push_str(&format!("\u{201c}{}.\u{201d}", title));
+            let container: Vec<String> = document
+                .source
+                .iter()
+                .cloned()
+                .chain(document.publication_year.map(|y| y.to_string()))
+                .collect();
+            if !container.is_empty() {
+                citation.push_str(&format!(" {}.", container.join(", ")));
+            }
+            citation
+        }
+        CitationStyle::Bibtex => {
+            let key = document.citation_key.clone().unwrap_or_else(|| generated_key(document, &names));
+            let entry_type = if document.source.is_some() { "article" } else { "misc" };
+
+            let mut fields = vec![("title", format!("{{{}}}", title))];
+            if !names.is_empty() {
+                fields.insert(0, ("author", names.iter().map(Name::family_given).collect::<Vec<_>>().join(" and ")));
+            }
+            if let Some(source) = &document.source {
+                fields.push(("journal", source.clone()));
+            }
+            if let Some(year) = document.publication_year {
+                fields.push(("year", year.to_string()));
+            }
+            match (doi, &document.url) {
+                (Some(doi), _) => fields.push(("doi", doi.to_string())),
+                (None, Some(url)) => fields.push(("url", url.clone())),
+                (None, None) => {}
+            }
+
+            let body: Vec<String> = fields.iter().map(|(name, value)| format!("  {} = {{{}}}", name, value)).collect();
+            format!("@{}{{{},\n{}\n}}", entry_type, key, body.join(",\n"))
+        }
+    }
+}
+
+/// `familyYEARword` key for documents without one
+fn generated_key(document: &Document, names: &[Name]) -> String {
+    let family = names.first().map(|n| n.family.to_lowercase()).unwrap_or_else(|| "anon".to_string());
+    let word = document
+        .title
+        .split_whitespace()
+        .map(|w| w.chars().filter(|c| c.is_alphanumeric()).collect::<String>().to_lowercase())
+        .find(|w| w.len() > 3)
+        .unwrap_or_default();
+    let year = document.publication_year.map(|y| y.to_string()).unwrap_or_default();
+    format!("{}{}{}", family.replace(char::is_whitespace, ""), year, word)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BIB: &str = r#"
+        @string{acm = "ACM"}
+        @article{lamport1978time,
+          title = {Time, Clocks, and the Ordering of Events in a {Distributed} System},
+          author = {Lamport, Leslie},
+          journal = "Communications of the ACM",
+          year = 1978,
+          doi = {10.1145/359545.359563},
+          file = {Full Text PDF:/home/me/papers/lamport.pdf:application/pdf}
+        }
+        @inproceedings{ongaro2014raft,
+          author = {Diego Ongaro and Ousterhout, John},
+          title = {In Search of an Understandable Consensus Algorithm},
+          booktitle = {USENIX ATC \& Friends},
+          year = {2014},
+        }
+    "#;
+
+    #[test]
+    fn test_parse_bibtex_and_format_citations() {
+        let entries = parse_bibtex(BIB);
+        assert_eq!(entries.len(), 2);
+
+        let lamport = &entries[0];
+        assert_eq!(lamport.entry_type, "article");
+        assert_eq!(lamport.key, "lamport1978time");
+        assert_eq!(lamport.title(), Some("Time, Clocks, and the Ordering of Events in a Distributed System"));
+        assert_eq!(lamport.year(), Some(1978));
+        assert_eq!(lamport.doi().as_deref(), Some("10.1145/359545.359563"));
+        assert_eq!(lamport.files(), vec!["/home/me/papers/lamport.pdf"]);
+
+        let raft = &entries[1];
+        assert_eq!(raft.authors(), vec!["Diego Ongaro", "Ousterhout, John"]);
+        assert_eq!(raft.source(), Some("USENIX ATC & Friends"));
+
+        let mut document = Document::new(raft.title().unwrap().to_string(), String::new(), "application/pdf".to_string());
+        document.author = Some(raft.authors().join(AUTHOR_SEPARATOR));
+        document.source = raft.source().map(str::to_string);
+        document.publication_year = raft.year();
+        document.citation_key = Some(raft.key.clone());
+
+        assert_eq!(
+            format_citation(&document, CitationStyle::Apa),
+            "Ongaro, D., & Ousterhout, J. (2014). In Search of an Understandable Consensus Algorithm. USENIX ATC & Friends."
+        );
+        assert_eq!(
+            format_citation(&document, CitationStyle::Mla),
+            "Ongaro, Diego, and John Ousterhout. \u{201c}In Search of an Understandable Consensus Algorithm.\u{201d} USENIX ATC & Friends, 2014."
+        );
+        let bibtex = format_citation(&document, CitationStyle::Bibtex);
+        assert!(bibtex.starts_with("@article{ongaro2014raft,\n  author = {Ongaro, Diego and Ousterhout, John},"));
+        assert!(bibtex.ends_with("  year = {2014}\n}"));
+        assert_eq!(parse_bibtex(&bibtex)[0].authors(), vec!["Ongaro, Diego", "Ousterhout, John"]);
+    }
+}
@@ -0,0 +1,151 @@
+//! Browser bookmark import
+//!
+//! Reads the Netscape bookmark file every browser exports. Each bookmark
+//! becomes a small document titled after it, tagged with its folder path
+//! and dated when it was bookmarked; the page text can be fetched into the
+//! body so the bookmark is searchable by content.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use super::ContentParser;
+use crate::{CodexError, CodexResult};
+
+/// Content type of documents created from bookmarks
+pub const BOOKMARK_CONTENT_TYPE: &str = "text/x-bookmark";
+/// Pages fetched at the same time
+pub const FETCH_CONCURRENCY: usize = 4;
+/// Time allowed for fetching one page
+pub const FETCH_TIMEOUT: Duration = Duration::from_secs(15);
+/// Longest page text kept in a bookmark document, in characters
+const MAX_PAGE_CHARS: usize = 20_000;
+
+/// Folder headings, links and list boundaries, in document order
+static TOKEN_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(?is)<h3[^>]*>(?P<folder>.*?)</h3>|<a\s(?P<attrs>[^>]*)>(?P<title>.*?)</a>|(?P<open><dl>)|(?P<close></dl>)"#).unwrap()
+});
+static HREF_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r#"(?i)\bhref\s*=\s*"([^"]*)""#).unwrap());
+static ADD_DATE_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r#"(?i)\badd_date\s*=\s*"(\d+)""#).unwrap());
+
+/// One bookmark from an export
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bookmark {
+    pub title: String,
+    pub url: String,
+    /// Folders from the outermost in
+    pub folders: Vec<String>,
+    pub added_at: Option<DateTime<Utc>>,
+}
+
+impl Bookmark {
+    /// Body of the bookmark's document, with the page text when fetched
+    pub fn content(&self, page_text: Option<&str>) -> String {
+        let mut content = format!("{}\n{}", self.title, self.url);
+        if !self.folders.is_empty() {
+            content.push_str(&format!("\nFolder: {}", self.folders.join(" / ")));
+        }
+        if let Some(text) = page_text.filter(|t| !t.trim().is_empty()) {
+            content.push_str("\n\n");
+            content.push_str(text);
+        }
+        content
+    }
+}
+
+/// Parse a Netscape bookmark file, keeping only http(s) links
+///
+/// Repeated URLs are kept; callers deduplicate.
+pub fn parse_bookmarks_html(html: &str) -> Vec<Bookmark> {
+    let mut bookmarks = Vec::new();
+    // One entry per open <DL>; the name of the folder it lists, if any
+    let mut stack: Vec<Option<String>> = Vec::new();
+    let mut pending_folder: Option<String> = None;
+
+    for captures in TOKEN_RE.captures_iter(html) {
+        if let Some(folder) = captures.name("folder") {
+            pending_folder = Some(ContentParser::decode_entities(folder.as_str().trim()));
+        } else if captures.name("open").is_some() {
+            stack.push(pending_folder.take());
+        } else if captures.name("close").is_some() {
+            stack.pop();
+        } else if let (Some(attrs), Some(title)) = (captures.name("attrs"), captures.name("title")) {
+            let Some(url) = HREF_RE.captures(attrs.as_str()).map(|c| c[1].trim().to_string()) else {
+                continue;
+            };
+            if !(url.starts_with("http://") || url.starts_with("https://")) {
+                continue;
+            }
+
+            let title = ContentParser::decode_entities(title.as_str().trim());
+            let added_at = ADD_DATE_RE
+                .captures(attrs.as_str())
+                .and_then(|c| c[1].parse::<i64>().ok())
+                .and_then(|seconds| DateTime::from_timestamp(seconds, 0));
+
+            bookmarks.push(Bookmark {
+                title: if title.is_empty() { url.clone() } else { title },
+                url,
+                folders: stack.iter().flatten().cloned().collect(),
+                added_at,
+            });
+        }
+    }
+
+    bookmarks
+}
+
+/// Fetch a bookmarked page and extract its text
+pub async fn fetch_page_text(client: &reqwest::Client, url: &str) -> CodexResult<String> {
+    let response = client.get(url).send().await?.error_for_status()?;
+
+    let is_html = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.contains("html"))
+        .unwrap_or(true);
+    if !is_html {
+        return Err(CodexError::validation("Not an HTML page"));
+    }
+
+    let text = ContentParser::html_to_text(&response.text().await?);
+    Ok(text.chars().take(MAX_PAGE_CHARS).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = include_str!("../../tests/fixtures/bookmarks.html");
+
+    #[test]
+    fn test_parse_bookmarks_export() {
+        let bookmarks = parse_bookmarks_html(FIXTURE);
+        let summary: Vec<(&str, Vec<&str>)> = bookmarks
+            .iter()
+            .map(|b| (b.url.as_str(), b.folders.iter().map(String::as_str).collect()))
+            .collect();
+
+        assert_eq!(
+            summary,
+            vec![
+                ("https://www.rust-lang.org/", vec!["Bookmarks bar"]),
+                ("https://doc.rust-lang.org/book/", vec!["Bookmarks bar", "Reading"]),
+                ("https://sqlite.org/fts5.html", vec!["Bookmarks bar", "Reading"]),
+                ("https://www.rust-lang.org/", vec!["Other bookmarks"]),
+                ("https://example.com/no-date", vec!["Other bookmarks"]),
+                ("https://news.ycombinator.com/", vec![]),
+            ]
+        );
+        assert_eq!(bookmarks[1].title, "The Rust Programming Language & Book");
+        assert_eq!(bookmarks[1].added_at.unwrap().to_rfc3339(), "2021-02-01T00:00:00+00:00");
+        assert_eq!(bookmarks[4].added_at, None);
+
+        let content = bookmarks[2].content(Some("FTS5 is an SQLite virtual table module"));
+        assert!(content.starts_with("SQLite FTS5 Extension\nhttps://sqlite.org/fts5.html\nFolder: Bookmarks bar / Reading"));
+        assert!(content.ends_with("virtual table module"));
+    }
+}
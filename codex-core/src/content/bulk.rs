@@ -0,0 +1,201 @@
+//! Bulk organization edits
+//!
+//! [`BulkChanges`] describes category, tag, archive, favorite and custom
+//! metadata edits that are applied to every document of a selection in one
+//! transaction.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::db::models::{Document, MetadataValue};
+use crate::CodexResult;
+
+use super::fields;
+
+/// Edits applied to every selected document; unset fields are left alone
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BulkChanges {
+    /// New category
+    pub category: Option<String>,
+    /// Tags added when missing (compared case-insensitively)
+    pub add_tags: Vec<String>,
+    /// Tags removed (compared case-insensitively)
+    pub remove_tags: Vec<String>,
+    pub archived: Option<bool>,
+    pub favorite: Option<bool>,
+    /// Custom metadata fields set, replacing existing values
+    pub set_metadata: BTreeMap<String, MetadataValue>,
+    /// Custom metadata keys removed
+    pub remove_metadata: Vec<String>,
+}
+
+impl BulkChanges {
+    /// Whether the changes would leave every document as it is
+    pub fn is_empty(&self) -> bool {
+        self.category.is_none()
+            && self.add_tags.is_empty()
+            && self.remove_tags.is_empty()
+            && self.archived.is_none()
+            && self.favorite.is_none()
+            && self.set_metadata.is_empty()
+            && self.remove_metadata.is_empty()
+    }
+
+    /// Check the metadata keys and values
+    pub fn validate(&self) -> CodexResult<()> {
+        for (key, value) in &self.set_metadata {
+            fields::validate_field(key, value)?;
+        }
+        for key in &self.remove_metadata {
+            fields::validate_key(key)?;
+        }
+        Ok(())
+    }
+
+    /// Apply the changes to a document; returns whether anything changed
+    pub fn apply(&self, document: &mut Document) -> bool {
+        let before = OrganizationState::from(&*document);
+
+        if let Some(ref category) = self.category {
+            document.category = Some(category.clone());
+        }
+        if !self.add_tags.is_empty() || !self.remove_tags.is_empty() {
+            let mut tags = document.get_tags();
+            tags.retain(|tag| !self.remove_tags.iter().any(|r| r.eq_ignore_ascii_case(tag)));
+            for tag in &self.add_tags {
+                if !tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+                    tags.push(tag.clone());
+                }
+            }
+            document.set_tags(tags);
+        }
+        if let Some(archived) = self.archived {
+            document.is_archived = archived;
+        }
+        if let Some(favorite) = self.favorite {
+            document.is_favorite = favorite;
+        }
+        if !self.set_metadata.is_empty() || !self.remove_metadata.is_empty() {
+            let mut metadata = document.get_metadata();
+            fields::apply(&mut metadata, &self.set_metadata, &self.remove_metadata);
+            document.set_metadata(&metadata);
+        }
+
+        OrganizationState::from(&*document) != before
+    }
+}
+
+/// Organization fields of a document, journaled so bulk edits can be undone
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrganizationState {
+    pub category: Option<String>,
+    pub tags: Vec<String>,
+    pub is_archived: bool,
+    pub is_favorite: bool,
+    /// Custom metadata; `None` in entries journaled before it was tracked,
+    /// which leave it alone when undone
+    #[serde(default)]
+    pub metadata: Option<BTreeMap<String, Value>>,
+}
+
+impl From<&Document> for OrganizationState {
+    fn from(document: &Document) -> Self {
+        Self {
+            category: document.category.clone(),
+            tags: document.get_tags(),
+            is_archived: document.is_archived,
+            is_favorite: document.is_favorite,
+            metadata: Some(document.get_metadata()),
+        }
+    }
+}
+
+impl OrganizationState {
+    /// Write the state back onto a document
+    pub fn apply_to(&self, document: &mut Document) {
+        document.category = self.category.clone();
+        document.set_tags(self.tags.clone());
+        document.is_archived = self.is_archived;
+        document.is_favorite = self.is_favorite;
+        if let Some(ref metadata) = self.metadata {
+            document.set_metadata(metadata);
+        }
+    }
+}
+
+/// Outcome of a bulk update
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BulkUpdateSummary {
+    /// Documents in the selection
+    pub matched: usize,
+    /// Documents the changes actually modified
+    pub updated: usize,
+    pub updated_ids: Vec<String>,
+    /// Journal entry that undoes the update, when anything changed
+    pub operation_id: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_changes_reports_modifications() {
+        let mut document = Document::new("Doc".to_string(), "body".to_string(), "text/plain".to_string());
+        document.set_tags(vec!["Rust".to_string(), "draft".to_string()]);
+
+        let changes = BulkChanges {
+            category: Some("Technology".to_string()),
+            add_tags: vec!["rust".to_string(), "reviewed".to_string()],
+            remove_tags: vec!["DRAFT".to_string()],
+            favorite: Some(true),
+            ..BulkChanges::default()
+        };
+        let before = OrganizationState::from(&document);
+        assert!(changes.apply(&mut document));
+        assert_eq!(document.category.as_deref(), Some("Technology"));
+        assert_eq!(document.get_tags(), vec!["Rust".to_string(), "reviewed".to_string()]);
+        assert!(document.is_favorite);
+
+        // Applying the same changes again is a no-op
+        assert!(!changes.apply(&mut document));
+
+        before.apply_to(&mut document);
+        assert_eq!(OrganizationState::from(&document), before);
+    }
+
+    #[test]
+    fn test_metadata_changes_are_validated_and_undone() {
+        let mut document = Document::new("Doc".to_string(), "body".to_string(), "text/plain".to_string());
+        let before = OrganizationState::from(&document);
+
+        let changes = BulkChanges {
+            set_metadata: BTreeMap::from([("project".to_string(), MetadataValue::Text("apollo".to_string()))]),
+            ..BulkChanges::default()
+        };
+        assert!(changes.validate().is_ok());
+        assert!(changes.apply(&mut document));
+        assert_eq!(document.get_metadata()["project"], "apollo");
+
+        let removal = BulkChanges { remove_metadata: vec!["project".to_string()], ..BulkChanges::default() };
+        assert!(removal.apply(&mut document));
+        assert!(document.metadata_json.is_none());
+
+        changes.apply(&mut document);
+        before.apply_to(&mut document);
+        assert!(document.metadata_json.is_none());
+
+        // Entries journaled before metadata was tracked leave it alone
+        let legacy: OrganizationState =
+            serde_json::from_str(r#"{"category":null,"tags":[],"is_archived":false,"is_favorite":false}"#).unwrap();
+        changes.apply(&mut document);
+        legacy.apply_to(&mut document);
+        assert_eq!(document.get_metadata()["project"], "apollo");
+
+        let invalid = BulkChanges { remove_metadata: vec!["bad key".to_string()], ..BulkChanges::default() };
+        assert!(invalid.validate().is_err());
+    }
+}
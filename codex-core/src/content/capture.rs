@@ -0,0 +1,119 @@
+//! Quick capture of text snippets
+//!
+//! Captures are saved immediately with a title taken from their first line
+//! and tagged [`INBOX_TAG`]; summaries, tags and (for captures without a
+//! usable first line) an AI-written title are added by a background job.
+
+use serde::{Deserialize, Serialize};
+
+/// Tag applied to every capture
+pub const INBOX_TAG: &str = "inbox";
+/// Longest first line used as a title as-is, in characters
+const MAX_TITLE_CHARS: usize = 80;
+/// Characters of the capture shown to the model when writing a title
+const TITLE_PROMPT_CHARS: usize = 1500;
+
+/// Outcome of a quick capture
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptureResult {
+    /// The new document, or the earlier capture of the same text
+    pub document_id: uuid::Uuid,
+    pub title: String,
+    /// Whether the text was captured recently and nothing was saved
+    pub duplicate: bool,
+    /// Background job adding AI metadata to the new document
+    pub enrichment_job: Option<uuid::Uuid>,
+}
+
+/// Title derived from a capture's text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureTitle {
+    pub title: String,
+    /// Whether the title is a placeholder the enrichment job should replace
+    pub provisional: bool,
+}
+
+/// Text compared when detecting repeated captures: lowercased, with runs of
+/// whitespace collapsed
+pub fn normalize(text: &str) -> String {
+    text.split_whitespace()
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Title from the first non-empty line, without Markdown heading or list markers
+///
+/// Lines longer than [`MAX_TITLE_CHARS`] are shortened and marked provisional.
+pub fn derive_title(text: &str) -> CaptureTitle {
+    let first_line = text
+        .lines()
+        .map(|line| line.trim().trim_start_matches(['#', '-', '*', '>']).trim())
+        .find(|line| !line.is_empty())
+        .unwrap_or_default();
+
+    if first_line.is_empty() {
+        return CaptureTitle {
+            title: format!("Capture {}", chrono::Local::now().format("%Y-%m-%d %H:%M")),
+            provisional: true,
+        };
+    }
+    if first_line.chars().count() > MAX_TITLE_CHARS {
+        let shortened: String = first_line.chars().take(MAX_TITLE_CHARS - 1).collect();
+        return CaptureTitle {
+            title: format!("{}…", shortened.trim_end()),
+            provisional: true,
+        };
+    }
+    CaptureTitle {
+        title: first_line.to_string(),
+        provisional: false,
+    }
+}
+
+/// Prompt asking the model for a one-line title
+pub fn title_prompt(text: &str) -> String {
+    let excerpt: String = text.chars().take(TITLE_PROMPT_CHARS).collect();
+    format!(
+        "Write a short title (at most eight words) for the following note. Reply with the title only.\n\nNote:\n{}\n\nTitle:",
+        excerpt
+    )
+}
+
+/// First line of a generated title without quotes, or None if nothing usable remains
+pub fn clean_generated_title(reply: &str) -> Option<String> {
+    let line = reply.lines().map(str::trim).find(|line| !line.is_empty())?;
+    let line = line
+        .trim_start_matches("Title:")
+        .trim()
+        .trim_matches(|c| c == '"' || c == '\'' || c == '*')
+        .trim();
+    if line.is_empty() {
+        return None;
+    }
+    Some(line.chars().take(MAX_TITLE_CHARS).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_titles_and_normalization() {
+        assert_eq!(
+            derive_title("\n\n## Meeting notes  \nbody"),
+            CaptureTitle { title: "Meeting notes".to_string(), provisional: false }
+        );
+        let long = derive_title(&"word ".repeat(40));
+        assert!(long.provisional);
+        assert!(long.title.chars().count() <= MAX_TITLE_CHARS);
+        assert!(derive_title("   \n ").provisional);
+
+        assert_eq!(normalize("  Hello\n\tWORLD  "), normalize("hello world"));
+        assert_ne!(normalize("hello world"), normalize("hello, world"));
+
+        assert_eq!(clean_generated_title("\n\"Rust borrow rules\"\nmore"), Some("Rust borrow rules".to_string()));
+        assert_eq!(clean_generated_title("Title: Weekly plan"), Some("Weekly plan".to_string()));
+        assert_eq!(clean_generated_title("  \n\"\""), None);
+    }
+}
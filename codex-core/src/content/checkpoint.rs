@@ -0,0 +1,192 @@
+//! Crash-safe progress for long-running operations
+//!
+//! An operation records a row in `operation_state` when it starts and
+//! checkpoints the last item it finished. If the app dies, the row stays
+//! `running`; at the next start [`crate::content::ContentManager`] either
+//! resumes it from the cursor or lists it for the user to confirm.
+
+use std::future::Future;
+
+use sqlx::SqlitePool;
+use tracing::{debug, warn};
+
+use crate::db::models::{Document, OperationState};
+use crate::db::{DocumentQueries, OperationStateQueries};
+use crate::CodexResult;
+
+/// Reindex of every document; the cursor is the last document id done
+pub const KIND_REINDEX_ALL: &str = "reindex_all";
+/// Reindex of unhealthy documents; resumed by recomputing which are unhealthy
+pub const KIND_INDEX_REPAIR: &str = "index_repair";
+/// Directory import; the cursor is the last file path done
+pub const KIND_BULK_IMPORT: &str = "bulk_import";
+
+/// Finished operations are kept this long for the record
+pub const FINISHED_RETENTION_DAYS: i64 = 30;
+
+/// Whether an interrupted operation of `kind` resumes without asking
+///
+/// Index work is idempotent; imports wait for the user because the source
+/// may have changed since.
+pub fn resumes_automatically(kind: &str) -> bool {
+    matches!(kind, KIND_REINDEX_ALL | KIND_INDEX_REPAIR)
+}
+
+/// Progress of one running operation
+#[derive(Debug)]
+pub struct Checkpoint {
+    pool: SqlitePool,
+    state: OperationState,
+}
+
+impl Checkpoint {
+    /// Record a new running operation
+    pub async fn start(pool: &SqlitePool, kind: &str, params: serde_json::Value, total: Option<i64>) -> CodexResult<Self> {
+        let state = OperationState::new(kind, params, total);
+        OperationStateQueries::create(pool, &state).await?;
+        debug!("Started operation {} ({})", state.id, kind);
+        Ok(Self { pool: pool.clone(), state })
+    }
+
+    /// Continue an interrupted operation from its last checkpoint
+    pub fn resume(pool: &SqlitePool, state: OperationState) -> Self {
+        Self { pool: pool.clone(), state }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.state.id
+    }
+
+    /// Last item completed before this run
+    pub fn cursor(&self) -> Option<&str> {
+        self.state.cursor.as_deref()
+    }
+
+    pub fn processed(&self) -> i64 {
+        self.state.processed
+    }
+
+    pub fn params(&self) -> serde_json::Value {
+        self.state.params()
+    }
+
+    /// Record that `cursor` and everything before it is done
+    pub async fn advance(&mut self, cursor: &str) -> CodexResult<()> {
+        self.state.processed += 1;
+        self.state.cursor = Some(cursor.to_string());
+        OperationStateQueries::checkpoint(&self.pool, &self.state.id, self.state.cursor.as_deref(), self.state.processed).await
+    }
+
+    /// Mark the operation completed
+    pub async fn complete(self) -> CodexResult<()> {
+        OperationStateQueries::finish(&self.pool, &self.state.id, OperationState::STATUS_COMPLETED, None).await
+    }
+
+    /// Mark the operation failed so it is not resumed
+    pub async fn fail(self, error: &str) -> CodexResult<()> {
+        OperationStateQueries::finish(&self.pool, &self.state.id, OperationState::STATUS_FAILED, Some(error)).await
+    }
+}
+
+/// Reindex every document after the checkpoint's cursor, in id order
+///
+/// `reindex` must replace a document's embeddings rather than add to
+/// them, so the document that was in progress when the app died is
+/// simply redone. Per-document failures are logged and skipped. Returns
+/// the number of documents visited in this run.
+pub async fn reindex_documents<F, Fut>(pool: &SqlitePool, checkpoint: &mut Checkpoint, mut reindex: F) -> CodexResult<usize>
+where
+    F: FnMut(Document) -> Fut,
+    Fut: Future<Output = CodexResult<usize>>,
+{
+    let ids = DocumentQueries::get_ids_after(pool, checkpoint.cursor()).await?;
+    let count = ids.len();
+
+    for id in ids {
+        // Documents deleted since the list was read are skipped
+        if let Some(document) = DocumentQueries::get_by_id(pool, &id).await? {
+            if let Err(e) = reindex(document).await {
+                warn!("Failed to reindex document {}: {}", id, e);
+            }
+        }
+        checkpoint.advance(&id).await?;
+    }
+
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_database;
+    use crate::db::models::Embedding;
+    use crate::db::EmbeddingQueries;
+
+    async fn reindex_one(pool: SqlitePool, document: Document) -> CodexResult<usize> {
+        EmbeddingQueries::delete_by_document(&pool, &document.id).await?;
+        let embedding = Embedding::new(document.id.clone(), vec![0.5; 4], "test".to_string(), 0, document.content.clone(), 0, 1);
+        EmbeddingQueries::create_with_binary(&pool, &embedding).await?;
+        Ok(1)
+    }
+
+    #[tokio::test]
+    async fn test_killed_reindex_resumes_without_duplicates() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = test_database(&dir).await;
+        let pool = db.pool().clone();
+
+        for i in 0..6 {
+            let document = Document::new(format!("Doc {}", i), format!("content {}", i), "text/plain".to_string());
+            DocumentQueries::create(&pool, &document, db.compression()).await.unwrap();
+        }
+
+        // The third document never finishes; the task is killed while it hangs
+        let mut checkpoint = Checkpoint::start(&pool, KIND_REINDEX_ALL, serde_json::json!({}), Some(6)).await.unwrap();
+        let operation_id = checkpoint.id().to_string();
+        let run = tokio::spawn({
+            let pool = pool.clone();
+            async move {
+                let mut done = 0;
+                reindex_documents(&pool.clone(), &mut checkpoint, |document| {
+                    done += 1;
+                    let pool = pool.clone();
+                    let hang = done == 3;
+                    async move {
+                        // Half-written: the chunk is stored before the hang
+                        let result = reindex_one(pool, document).await;
+                        if hang {
+                            std::future::pending::<()>().await;
+                        }
+                        result
+                    }
+                })
+                .await
+            }
+        });
+        while OperationStateQueries::get_by_id(&pool, &operation_id).await.unwrap().unwrap().processed < 2 {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        run.abort();
+        let _ = run.await;
+
+        let interrupted = OperationStateQueries::get_running(&pool).await.unwrap();
+        assert_eq!(interrupted.len(), 1);
+        assert_eq!(interrupted[0].processed, 2);
+        assert!(resumes_automatically(&interrupted[0].kind));
+
+        let mut resumed = Checkpoint::resume(&pool, interrupted[0].clone());
+        let visited = reindex_documents(&pool, &mut resumed, |document| reindex_one(pool.clone(), document)).await.unwrap();
+        assert_eq!(visited, 4);
+        resumed.complete().await.unwrap();
+
+        // Every document has exactly its one chunk, including the one cut off mid-way
+        for id in DocumentQueries::get_ids_after(&pool, None).await.unwrap() {
+            assert_eq!(EmbeddingQueries::get_by_document(&pool, &id).await.unwrap().len(), 1, "{}", id);
+        }
+        assert!(OperationStateQueries::get_running(&pool).await.unwrap().is_empty());
+        let finished = OperationStateQueries::get_by_id(&pool, &operation_id).await.unwrap().unwrap();
+        assert_eq!(finished.status, OperationState::STATUS_COMPLETED);
+        assert_eq!(finished.processed, 6);
+    }
+}
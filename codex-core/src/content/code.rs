@@ -0,0 +1,381 @@
+//! Source code support for imports
+//!
+//! Code files are chunked on top-level item boundaries (functions, classes,
+//! impl blocks) instead of word windows, tagged with their language and rated
+//! with a size/complexity heuristic rather than by the language model.
+
+use serde::{Deserialize, Serialize};
+
+use crate::ai::embeddings::TextChunk;
+
+/// Source file extensions imported as code
+pub const CODE_EXTENSIONS: &[&str] = &["rs", "py", "js", "ts", "go", "java"];
+
+/// Programming language of a code document
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CodeLanguage {
+    Rust,
+    Python,
+    JavaScript,
+    TypeScript,
+    Go,
+    Java,
+}
+
+impl CodeLanguage {
+    const ALL: [CodeLanguage; 6] = [
+        CodeLanguage::Rust,
+        CodeLanguage::Python,
+        CodeLanguage::JavaScript,
+        CodeLanguage::TypeScript,
+        CodeLanguage::Go,
+        CodeLanguage::Java,
+    ];
+
+    /// Detect the language from a file extension
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_lowercase().as_str() {
+            "rs" => Some(CodeLanguage::Rust),
+            "py" => Some(CodeLanguage::Python),
+            "js" => Some(CodeLanguage::JavaScript),
+            "ts" => Some(CodeLanguage::TypeScript),
+            "go" => Some(CodeLanguage::Go),
+            "java" => Some(CodeLanguage::Java),
+            _ => None,
+        }
+    }
+
+    /// Detect the language from a stored document content type
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|lang| lang.content_type() == content_type)
+    }
+
+    /// Lowercase language name, also used as the document tag
+    pub fn name(&self) -> &'static str {
+        match self {
+            CodeLanguage::Rust => "rust",
+            CodeLanguage::Python => "python",
+            CodeLanguage::JavaScript => "javascript",
+            CodeLanguage::TypeScript => "typescript",
+            CodeLanguage::Go => "go",
+            CodeLanguage::Java => "java",
+        }
+    }
+
+    /// MIME type stored on documents in this language
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            CodeLanguage::Rust => "text/x-rust",
+            CodeLanguage::Python => "text/x-python",
+            CodeLanguage::JavaScript => "text/x-javascript",
+            CodeLanguage::TypeScript => "text/x-typescript",
+            CodeLanguage::Go => "text/x-go",
+            CodeLanguage::Java => "text/x-java",
+        }
+    }
+
+    fn is_indent_based(&self) -> bool {
+        matches!(self, CodeLanguage::Python)
+    }
+
+    fn line_comment(&self) -> &'static str {
+        match self {
+            CodeLanguage::Python => "#",
+            _ => "//",
+        }
+    }
+}
+
+/// Whether a file extension is imported as code
+pub fn is_code_extension(extension: &str) -> bool {
+    CodeLanguage::from_extension(extension).is_some()
+}
+
+/// Split source code into chunks of whole top-level items
+///
+/// Items are grouped until a chunk would exceed `max_words`; an item larger
+/// than that on its own is split on line boundaries. Chunk text is sliced
+/// straight from the source so indentation is preserved.
+pub fn chunk_code(text: &str, language: CodeLanguage, max_words: usize) -> Vec<TextChunk> {
+    chunk_ranges(text, item_ranges(text, language), max_words)
+}
+
+/// Group consecutive byte ranges of `text` into chunks of at most `max_words`
+///
+/// Shared by the code and notebook chunkers, which only differ in how they
+/// find the unit boundaries.
+pub(crate) fn chunk_ranges(
+    text: &str,
+    ranges: impl IntoIterator<Item = (usize, usize)>,
+    max_words: usize,
+) -> Vec<TextChunk> {
+    let max_words = max_words.max(1);
+    let mut chunks = Vec::new();
+    let mut current: Option<(usize, usize)> = None;
+    let mut current_words = 0;
+
+    let mut flush = |range: &mut Option<(usize, usize)>, words: &mut usize| {
+        if let Some((start, end)) = range.take() {
+            chunks.push(TextChunk {
+                text: text[start..end].to_string(),
+                start_position: start,
+                end_position: end,
+            });
+        }
+        *words = 0;
+    };
+
+    for (start, end) in ranges {
+        let words = text[start..end].split_whitespace().count();
+
+        if current.is_some() && current_words + words > max_words {
+            flush(&mut current, &mut current_words);
+        }
+
+        if words > max_words {
+            // Oversized unit: fall back to line windows within it
+            for (line_start, line_end) in line_windows(text, start, end, max_words) {
+                current = Some((line_start, line_end));
+                current_words = max_words;
+                flush(&mut current, &mut current_words);
+            }
+            continue;
+        }
+
+        current = Some((current.map(|(s, _)| s).unwrap_or(start), end));
+        current_words += words;
+    }
+
+    flush(&mut current, &mut current_words);
+    chunks
+}
+
+/// Rate code difficulty (1-5) from its size and branching complexity
+pub fn assess_code_difficulty(text: &str, language: CodeLanguage) -> i64 {
+    let comment = language.line_comment();
+    let code_lines: Vec<&str> = text
+        .lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty() && !l.starts_with(comment))
+        .collect();
+    let loc = code_lines.len();
+
+    let branches: usize = code_lines
+        .iter()
+        .map(|line| {
+            line.split(|c: char| !c.is_alphanumeric() && c != '_')
+                .filter(|word| {
+                    matches!(*word, "if" | "elif" | "for" | "while" | "match" | "case" | "catch" | "except" | "switch")
+                })
+                .count()
+                + line.matches("&&").count()
+                + line.matches("||").count()
+        })
+        .sum();
+
+    let max_depth = if language.is_indent_based() {
+        text.lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| l.len() - l.trim_start().len())
+            .max()
+            .unwrap_or(0)
+            / 4
+    } else {
+        let mut depth: usize = 0;
+        let mut max_depth = 0;
+        for line in text.lines() {
+            let (opened, closed) = count_braces(line, comment);
+            depth = (depth + opened).saturating_sub(closed);
+            max_depth = max_depth.max(depth);
+        }
+        max_depth
+    };
+
+    let mut level = match loc {
+        0..=29 => 1,
+        30..=99 => 2,
+        100..=299 => 3,
+        300..=999 => 4,
+        _ => 5,
+    };
+
+    let density = branches as f64 / loc.max(1) as f64;
+    if density > 0.15 || max_depth >= 5 {
+        level += 1;
+    }
+
+    level.min(5)
+}
+
+/// Byte ranges of top-level items
+///
+/// An item boundary is a blank line at nesting depth zero (brace languages)
+/// or a blank line followed by an unindented line (Python), so leading doc
+/// comments, attributes and decorators stay with the item they describe.
+fn item_ranges(text: &str, language: CodeLanguage) -> Vec<(usize, usize)> {
+    let comment = language.line_comment();
+    let mut ranges = Vec::new();
+    let mut item_start: Option<usize> = None;
+    let mut item_end = 0;
+    let mut depth: usize = 0;
+    let mut after_blank = false;
+    let mut offset = 0;
+
+    for line in text.split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+        let content = line.trim_end();
+
+        if content.trim().is_empty() {
+            after_blank = true;
+            continue;
+        }
+
+        let starts_item = if language.is_indent_based() {
+            after_blank && !content.starts_with(char::is_whitespace)
+        } else {
+            after_blank && depth == 0
+        };
+
+        if starts_item {
+            if let Some(start) = item_start.take() {
+                ranges.push((start, item_end));
+            }
+        }
+
+        item_start.get_or_insert(line_start);
+        item_end = line_start + content.len();
+        after_blank = false;
+
+        if !language.is_indent_based() {
+            let (opened, closed) = count_braces(content, comment);
+            depth = (depth + opened).saturating_sub(closed);
+        }
+    }
+
+    if let Some(start) = item_start {
+        ranges.push((start, item_end));
+    }
+
+    ranges
+}
+
+/// Split the byte range `start..end` into windows of whole lines
+fn line_windows(text: &str, start: usize, end: usize, max_words: usize) -> Vec<(usize, usize)> {
+    let mut windows = Vec::new();
+    let mut window_start: Option<usize> = None;
+    let mut window_end = start;
+    let mut words = 0;
+    let mut offset = start;
+
+    for line in text[start..end].split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+        let line_words = line.split_whitespace().count();
+
+        if window_start.is_some() && words + line_words > max_words {
+            windows.push((window_start.take().unwrap_or(start), window_end));
+            words = 0;
+        }
+
+        window_start.get_or_insert(line_start);
+        window_end = line_start + line.trim_end().len();
+        words += line_words;
+    }
+
+    if let Some(window_start) = window_start {
+        windows.push((window_start, window_end));
+    }
+
+    windows
+}
+
+/// Count braces in a line, ignoring string literals and trailing comments
+fn count_braces(line: &str, comment: &str) -> (usize, usize) {
+    let mut opened = 0;
+    let mut closed = 0;
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+
+    for (i, c) in line.char_indices() {
+        if let Some(q) = quote {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == q {
+                quote = None;
+            }
+            continue;
+        }
+
+        match c {
+            '"' | '`' => quote = Some(c),
+            '{' => opened += 1,
+            '}' => closed += 1,
+            _ if line[i..].starts_with(comment) => break,
+            _ => {}
+        }
+    }
+
+    (opened, closed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RUST_SOURCE: &str = "use std::fmt;\n\n/// Adds numbers\nfn add(a: i32, b: i32) -> i32 {\n    let sum = a + b;\n\n    sum\n}\n\nstruct Point {\n    x: i32,\n}\n";
+
+    #[test]
+    fn test_language_detection() {
+        assert_eq!(CodeLanguage::from_extension("RS"), Some(CodeLanguage::Rust));
+        assert_eq!(CodeLanguage::from_extension("md"), None);
+        assert_eq!(CodeLanguage::Python.content_type(), "text/x-python");
+        assert_eq!(CodeLanguage::from_content_type("text/x-go"), Some(CodeLanguage::Go));
+        assert!(is_code_extension("ts"));
+    }
+
+    #[test]
+    fn test_rust_items_keep_functions_whole() {
+        let ranges = item_ranges(RUST_SOURCE, CodeLanguage::Rust);
+        let items: Vec<&str> = ranges.iter().map(|&(s, e)| &RUST_SOURCE[s..e]).collect();
+
+        assert_eq!(items.len(), 3);
+        assert!(items[1].starts_with("/// Adds numbers\nfn add"));
+        assert!(items[1].ends_with("    sum\n}"));
+        assert!(items[2].starts_with("struct Point"));
+    }
+
+    #[test]
+    fn test_python_items_and_chunking() {
+        let source = "import os\n\n@cache\ndef load(path):\n    if path:\n\n        return os.read(path)\n\nclass Store:\n    pass\n";
+        let chunks = chunk_code(source, CodeLanguage::Python, 9);
+
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks[0].text.contains("@cache\ndef load(path):\n    if path:\n\n        return"));
+        assert_eq!(chunks[1].text, "class Store:\n    pass");
+        assert_eq!(&source[chunks[1].start_position..chunks[1].end_position], chunks[1].text);
+    }
+
+    #[test]
+    fn test_oversized_item_split_on_lines() {
+        let body: String = (0..20).map(|i| format!("    let v{} = {};\n", i, i)).collect();
+        let source = format!("fn big() {{\n{}}}\n", body);
+        let chunks = chunk_code(&source, CodeLanguage::Rust, 16);
+
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|c| c.text.split_whitespace().count() <= 16));
+    }
+
+    #[test]
+    fn test_code_difficulty_heuristic() {
+        assert_eq!(assess_code_difficulty(RUST_SOURCE, CodeLanguage::Rust), 1);
+
+        let branchy: String = (0..120)
+            .map(|i| format!("if x > {} && y {{ z += 1; }}\n", i))
+            .collect();
+        assert_eq!(assess_code_difficulty(&branchy, CodeLanguage::Rust), 4);
+    }
+}
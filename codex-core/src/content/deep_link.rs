@@ -0,0 +1,153 @@
+//! `codex://` deep links
+//!
+//! Links of the form `codex://document/{uuid}?pos=N`, `codex://search?q=...`
+//! and `codex://collection/{id}` open the app at a document (optionally at
+//! a character offset), a search, or a collection. Parsing here checks
+//! syntax only; [`crate::content::ContentManager::resolve_deep_link`] also
+//! checks the target exists.
+
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{CodexError, CodexResult};
+
+/// URL scheme registered by the app
+pub const SCHEME: &str = "codex";
+
+/// Where a deep link navigates to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum NavigationTarget {
+    /// A document, scrolled to a character offset when given
+    Document { id: Uuid, position: Option<i64> },
+    /// Search results for a query
+    Search { query: String },
+    /// Documents in a collection
+    Collection { id: String },
+}
+
+/// Parse a `codex://` URL into a navigation target
+pub fn parse_deep_link(link: &str) -> CodexResult<NavigationTarget> {
+    let url = Url::parse(link.trim()).map_err(|e| CodexError::validation(format!("Invalid link {}: {}", link, e)))?;
+    if url.scheme() != SCHEME {
+        return Err(CodexError::validation(format!("Not a {}:// link: {}", SCHEME, link)));
+    }
+
+    let query_param = |name: &str| url.query_pairs().find(|(key, _)| key == name).map(|(_, value)| value.into_owned());
+    let segments: Vec<String> = url
+        .path_segments()
+        .map(|segments| segments.filter(|s| !s.is_empty()).map(percent_decode).collect())
+        .unwrap_or_default();
+
+    match (url.host_str().unwrap_or_default(), segments.as_slice()) {
+        ("document", [id]) => {
+            let id = Uuid::parse_str(id).map_err(|_| CodexError::validation(format!("Invalid document id in link: {}", id)))?;
+            let position = match query_param("pos") {
+                Some(pos) => Some(
+                    pos.parse::<i64>()
+                        .ok()
+                        .filter(|p| *p >= 0)
+                        .ok_or_else(|| CodexError::validation(format!("Invalid position in link: {}", pos)))?,
+                ),
+                None => None,
+            };
+            Ok(NavigationTarget::Document { id, position })
+        }
+        ("search", []) => {
+            let query = query_param("q").unwrap_or_default();
+            if query.trim().is_empty() {
+                return Err(CodexError::validation("Search link has no query"));
+            }
+            Ok(NavigationTarget::Search { query })
+        }
+        ("collection", [id]) => Ok(NavigationTarget::Collection { id: id.clone() }),
+        _ => Err(CodexError::validation(format!("Unknown link target: {}", link))),
+    }
+}
+
+/// `codex://` URL for a navigation target
+pub fn make_deep_link(target: &NavigationTarget) -> String {
+    let base = format!("{}://", SCHEME);
+    let mut url = match target {
+        NavigationTarget::Document { id, .. } => Url::parse(&format!("{}document/{}", base, id)),
+        NavigationTarget::Search { .. } => Url::parse(&format!("{}search", base)),
+        NavigationTarget::Collection { .. } => Url::parse(&format!("{}collection/", base)),
+    }
+    .expect("deep link base URLs are valid");
+
+    match target {
+        NavigationTarget::Document { position: Some(position), .. } => {
+            url.query_pairs_mut().append_pair("pos", &position.to_string());
+        }
+        NavigationTarget::Document { position: None, .. } => {}
+        NavigationTarget::Search { query } => {
+            url.query_pairs_mut().append_pair("q", query);
+        }
+        NavigationTarget::Collection { id } => {
+            url.path_segments_mut().expect("deep links have a path").pop_if_empty().push(id);
+        }
+    }
+
+    url.to_string()
+}
+
+/// Decode `%XX` escapes in a path segment
+fn percent_decode(segment: &str) -> String {
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| segment.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deep_links_round_trip() {
+        let id = Uuid::new_v4();
+        let targets = [
+            NavigationTarget::Document { id, position: Some(120) },
+            NavigationTarget::Document { id, position: None },
+            NavigationTarget::Search { query: "rust & sqlite?".to_string() },
+            NavigationTarget::Collection { id: "Reading list/2024".to_string() },
+        ];
+        for target in targets {
+            let link = make_deep_link(&target);
+            assert!(link.starts_with("codex://"), "{}", link);
+            assert_eq!(parse_deep_link(&link).unwrap(), target, "{}", link);
+        }
+        assert_eq!(
+            make_deep_link(&NavigationTarget::Document { id, position: Some(7) }),
+            format!("codex://document/{}?pos=7", id)
+        );
+
+        for invalid in [
+            "https://document/abc",
+            "codex://document/not-a-uuid",
+            &format!("codex://document/{}?pos=-1", id),
+            "codex://search?q=",
+            "codex://settings",
+            "not a url",
+        ] {
+            assert!(parse_deep_link(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
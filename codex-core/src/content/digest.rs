@@ -0,0 +1,163 @@
+//! Digests of recently added documents
+//!
+//! A digest lists the documents created in a time window, grouped by
+//! category with a one-line description each, under an AI-written overview.
+//! Digests are stored as regular documents tagged [`DIGEST_TAG`].
+
+use std::collections::BTreeMap;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::db::models::Document;
+
+/// Tag applied to generated digests; tagged documents are never digested themselves
+pub const DIGEST_TAG: &str = "digest";
+/// Category heading for documents without a category
+const UNCATEGORIZED: &str = "Uncategorized";
+/// Longest one-liner kept, in characters
+const ONE_LINER_MAX_CHARS: usize = 200;
+
+/// Automatic digest generation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DigestSchedule {
+    /// Generate digests in the background
+    pub enabled: bool,
+    /// Hours between digests (168 for weekly)
+    pub interval_hours: u64,
+    /// Most documents listed in a digest
+    pub max_items: usize,
+}
+
+impl Default for DigestSchedule {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_hours: 168,
+            max_items: 50,
+        }
+    }
+}
+
+/// A document listed in a digest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DigestItem {
+    pub document_id: String,
+    pub title: String,
+    pub category: Option<String>,
+    pub one_liner: String,
+}
+
+/// First sentence of a cached summary, if the document has one
+pub fn summary_one_liner(document: &Document) -> Option<String> {
+    document
+        .summary
+        .as_deref()
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(first_sentence)
+}
+
+/// First sentence of a text, shortened to a single line
+pub fn first_sentence(text: &str) -> String {
+    let line = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let end = line
+        .char_indices()
+        .find(|(i, c)| matches!(c, '.' | '!' | '?') && line[i + 1..].starts_with(' '))
+        .map(|(i, _)| i + 1)
+        .unwrap_or(line.len());
+    let sentence = &line[..end];
+
+    if sentence.chars().count() > ONE_LINER_MAX_CHARS {
+        let shortened: String = sentence.chars().take(ONE_LINER_MAX_CHARS).collect();
+        format!("{}…", shortened.trim_end())
+    } else {
+        sentence.to_string()
+    }
+}
+
+/// Group items by category, categories sorted and uncategorized last
+pub fn group_by_category(items: &[DigestItem]) -> Vec<(String, Vec<&DigestItem>)> {
+    let mut groups: BTreeMap<String, Vec<&DigestItem>> = BTreeMap::new();
+    let mut uncategorized = Vec::new();
+
+    for item in items {
+        match item.category.as_deref().map(str::trim).filter(|c| !c.is_empty()) {
+            Some(category) => groups.entry(category.to_string()).or_default().push(item),
+            None => uncategorized.push(item),
+        }
+    }
+
+    let mut grouped: Vec<_> = groups.into_iter().collect();
+    if !uncategorized.is_empty() {
+        grouped.push((UNCATEGORIZED.to_string(), uncategorized));
+    }
+    grouped
+}
+
+/// Digest document title for a window
+pub fn digest_title(since: DateTime<Utc>, until: DateTime<Utc>) -> String {
+    format!("Digest {} – {}", since.format("%Y-%m-%d"), until.format("%Y-%m-%d"))
+}
+
+/// Prompt asking for an overview of the digest items
+pub fn overview_prompt(items: &[DigestItem]) -> String {
+    let listing: Vec<String> = items
+        .iter()
+        .map(|item| format!("- {}: {}", item.title, item.one_liner))
+        .collect();
+
+    format!(
+        "These documents were added to a personal knowledge base recently. Write a short overview (3-5 sentences) \
+         of the main themes, without listing every document:\n\n{}\n\nOverview:",
+        listing.join("\n")
+    )
+}
+
+/// Render a digest as markdown
+pub fn render_digest(title: &str, overview: &str, items: &[DigestItem]) -> String {
+    let mut output = format!("# {}\n\n{}\n", title, overview.trim());
+
+    for (category, items) in group_by_category(items) {
+        output.push_str(&format!("\n## {}\n\n", category));
+        for item in items {
+            output.push_str(&format!("- [[{}]]: {}\n", item.title, item.one_liner));
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(title: &str, category: Option<&str>) -> DigestItem {
+        DigestItem {
+            document_id: title.to_lowercase(),
+            title: title.to_string(),
+            category: category.map(str::to_string),
+            one_liner: format!("About {}.", title),
+        }
+    }
+
+    #[test]
+    fn test_first_sentence() {
+        assert_eq!(first_sentence("Rust is fast. It is also safe."), "Rust is fast.");
+        assert_eq!(first_sentence("Version 1.2 ships\nsoon"), "Version 1.2 ships soon");
+        assert!(first_sentence(&"word ".repeat(100)).ends_with('…'));
+    }
+
+    #[test]
+    fn test_render_groups_by_category() {
+        let items = vec![item("Zeta", Some("Science")), item("Loose", None), item("Alpha", Some("Art"))];
+        let digest = render_digest("Digest", "Overview text.", &items);
+
+        let art = digest.find("## Art").unwrap();
+        let science = digest.find("## Science").unwrap();
+        let uncategorized = digest.find("## Uncategorized").unwrap();
+        assert!(art < science && science < uncategorized);
+        assert!(digest.contains("- [[Alpha]]: About Alpha.\n"));
+        assert!(digest.starts_with("# Digest\n\nOverview text.\n"));
+    }
+}
@@ -0,0 +1,278 @@
+//! Reading list export
+//!
+//! Renders a set of documents as a Markdown reading list, a CSV sheet or
+//! BibTeX entries. Missing metadata is left out of the output entirely.
+//! The Markdown list also carries each document's highlights and notes.
+
+use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{CodexError, CodexResult};
+use crate::db::models::{Annotation, Document};
+use super::search::SearchOptions;
+
+/// Reading list output format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    Markdown,
+    Csv,
+    Bibtex,
+}
+
+impl ExportFormat {
+    /// Conventional file extension for the format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "md",
+            ExportFormat::Csv => "csv",
+            ExportFormat::Bibtex => "bib",
+        }
+    }
+}
+
+/// Documents to export or edit in bulk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DocumentSelection {
+    /// Explicit documents, in the given order
+    Ids { ids: Vec<Uuid> },
+    /// Results of a search, re-executed when the selection is used
+    Search { query: String, options: SearchOptions },
+}
+
+/// Render documents in the requested format
+///
+/// `annotations` holds highlights by document id; only Markdown shows them.
+pub fn render_document_list(
+    documents: &[Document],
+    annotations: &HashMap<String, Vec<Annotation>>,
+    format: ExportFormat,
+) -> CodexResult<String> {
+    match format {
+        ExportFormat::Markdown => Ok(render_markdown(documents, annotations)),
+        ExportFormat::Csv => render_csv(documents),
+        ExportFormat::Bibtex => Ok(render_bibtex(documents)),
+    }
+}
+
+/// External link for a document: its URL, otherwise its source
+fn document_link(document: &Document) -> Option<&str> {
+    document
+        .url
+        .as_deref()
+        .or(document.source.as_deref())
+        .filter(|link| !link.trim().is_empty())
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty())
+}
+
+fn render_markdown(documents: &[Document], annotations: &HashMap<String, Vec<Annotation>>) -> String {
+    let mut output = String::from("# Reading List\n");
+
+    for document in documents {
+        output.push('\n');
+        match document_link(document) {
+            Some(link) => output.push_str(&format!("## [{}](<{}>)\n", document.title, link)),
+            None => output.push_str(&format!("## {}\n", document.title)),
+        }
+
+        if let Some(author) = non_empty(&document.author) {
+            output.push_str(&format!("\n*{}*\n", author));
+        }
+        if let Some(summary) = non_empty(&document.summary) {
+            output.push_str(&format!("\n{}\n", summary));
+        }
+
+        let tags = document.get_tags();
+        if !tags.is_empty() {
+            let tags: Vec<String> = tags.iter().map(|t| format!("`{}`", t)).collect();
+            output.push_str(&format!("\nTags: {}\n", tags.join(", ")));
+        }
+
+        let highlights = annotations.get(&document.id).map(Vec::as_slice).unwrap_or_default();
+        if !highlights.is_empty() {
+            output.push_str("\n### Highlights\n");
+            for annotation in highlights {
+                output.push_str(&format!("\n> {}\n", annotation.selected_text.replace('\n', "\n> ")));
+                if let Some(note) = non_empty(&annotation.note) {
+                    output.push_str(&format!("\n{}\n", note));
+                }
+            }
+        }
+    }
+
+    output
+}
+
+fn render_csv(documents: &[Document]) -> CodexResult<String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    let csv_error = |e: csv::Error| CodexError::internal(format!("Failed to write CSV: {}", e));
+
+    writer
+        .write_record(["title", "author", "summary", "tags", "category", "link", "created_at"])
+        .map_err(csv_error)?;
+
+    for document in documents {
+        writer
+            .write_record([
+                document.title.as_str(),
+                non_empty(&document.author).unwrap_or_default(),
+                non_empty(&document.summary).unwrap_or_default(),
+                &document.get_tags().join("; "),
+                non_empty(&document.category).unwrap_or_default(),
+                document_link(document).unwrap_or_default(),
+                &document.created_at.to_rfc3339(),
+            ])
+            .map_err(csv_error)?;
+    }
+
+    let bytes = writer
+        .into_inner()
+        .map_err(|e| CodexError::internal(format!("Failed to write CSV: {}", e)))?;
+    String::from_utf8(bytes).map_err(|e| CodexError::internal(format!("Invalid CSV output: {}", e)))
+}
+
+/// BibTeX entries for documents that can be cited (those with an author, source or URL)
+fn render_bibtex(documents: &[Document]) -> String {
+    let mut keys = HashSet::new();
+    let mut entries = Vec::new();
+
+    for document in documents {
+        let author = non_empty(&document.author);
+        let url = non_empty(&document.url);
+        let source = non_empty(&document.source);
+        if author.is_none() && url.is_none() && source.is_none() {
+            continue;
+        }
+
+        let key = unique_key(citation_key(document), &mut keys);
+        let entry_type = if url.is_some() { "online" } else { "misc" };
+
+        let mut fields = vec![("title", document.title.clone())];
+        if let Some(author) = author {
+            fields.push(("author", author.to_string()));
+        }
+        if let Some(url) = url {
+            fields.push(("url", url.to_string()));
+            fields.push(("urldate", document.created_at.format("%Y-%m-%d").to_string()));
+        } else if let Some(source) = source {
+            fields.push(("howpublished", source.to_string()));
+        }
+        if let Some(summary) = non_empty(&document.summary) {
+            fields.push(("abstract", summary.to_string()));
+        }
+        let tags = document.get_tags();
+        if !tags.is_empty() {
+            fields.push(("keywords", tags.join(", ")));
+        }
+
+        let body: Vec<String> = fields
+            .into_iter()
+            .map(|(name, value)| format!("  {} = {{{}}}", name, escape_bibtex(&value)))
+            .collect();
+        entries.push(format!("@{}{{{},\n{}\n}}\n", entry_type, key, body.join(",\n")));
+    }
+
+    entries.join("\n")
+}
+
+/// `surname` + year + first title word, e.g. `knuth2024art`
+fn citation_key(document: &Document) -> String {
+    let alphanumeric = |s: &str| -> String {
+        s.chars().filter(|c| c.is_ascii_alphanumeric()).collect::<String>().to_lowercase()
+    };
+
+    let surname = non_empty(&document.author)
+        .and_then(|a| a.split(" and ").next())
+        .and_then(|a| match a.split_once(',') {
+            Some((last, _)) => Some(last),
+            None => a.split_whitespace().last(),
+        })
+        .map(alphanumeric)
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| "doc".to_string());
+    let year = document.created_at.format("%Y");
+    let word = document
+        .title
+        .split_whitespace()
+        .map(alphanumeric)
+        .find(|w| w.len() > 3)
+        .unwrap_or_default();
+
+    format!("{}{}{}", surname, year, word)
+}
+
+fn unique_key(key: String, used: &mut HashSet<String>) -> String {
+    let mut candidate = key.clone();
+    let mut suffix = b'a';
+    while !used.insert(candidate.clone()) {
+        candidate = format!("{}{}", key, suffix as char);
+        suffix = suffix.saturating_add(1);
+    }
+    candidate
+}
+
+fn escape_bibtex(value: &str) -> String {
+    value
+        .replace('\\', "\\textbackslash{}")
+        .replace('{', "\\{")
+        .replace('}', "\\}")
+        .replace('&', "\\&")
+        .replace('%', "\\%")
+        .replace('\n', " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn documents() -> Vec<Document> {
+        let mut cited = Document::new("The Art of Programming".to_string(), "body".to_string(), "text/plain".to_string());
+        cited.author = Some("Donald Knuth".to_string());
+        cited.url = Some("https://example.com/taocp".to_string());
+        cited.summary = Some("Algorithms & analysis".to_string());
+        cited.set_tags(vec!["algorithms".to_string()]);
+        cited.created_at = "2024-02-03T10:00:00+00:00".parse().unwrap();
+
+        let bare = Document::new("Loose Notes".to_string(), "body".to_string(), "text/plain".to_string());
+        vec![cited, bare]
+    }
+
+    #[test]
+    fn test_markdown_omits_missing_fields() {
+        let markdown = render_document_list(&documents(), &HashMap::new(), ExportFormat::Markdown).unwrap();
+
+        assert!(markdown.contains("## [The Art of Programming](<https://example.com/taocp>)\n\n*Donald Knuth*"));
+        assert!(markdown.contains("Tags: `algorithms`"));
+        assert!(markdown.ends_with("## Loose Notes\n"));
+        assert!(!markdown.contains("None"));
+    }
+
+    #[test]
+    fn test_markdown_includes_highlights() {
+        let documents = documents();
+        let mut highlight = Annotation::new(documents[0].id.clone(), 0, 4, "body".to_string());
+        highlight.note = Some("Worth rereading".to_string());
+        let annotations = HashMap::from([(documents[0].id.clone(), vec![highlight])]);
+
+        let markdown = render_document_list(&documents, &annotations, ExportFormat::Markdown).unwrap();
+        assert!(markdown.contains("Tags: `algorithms`\n\n### Highlights\n\n> body\n\nWorth rereading\n\n## Loose Notes"));
+    }
+
+    #[test]
+    fn test_csv_and_bibtex() {
+        let csv = render_document_list(&documents(), &HashMap::new(), ExportFormat::Csv).unwrap();
+        assert_eq!(csv.lines().count(), 3);
+        assert!(csv.lines().nth(2).unwrap().starts_with("Loose Notes,,,,,,"));
+
+        let bibtex = render_document_list(&documents(), &HashMap::new(), ExportFormat::Bibtex).unwrap();
+        assert!(bibtex.starts_with("@online{knuth2024programming,\n  title = {The Art of Programming}"));
+        assert!(bibtex.contains("abstract = {Algorithms \\& analysis}"));
+        assert!(bibtex.contains("urldate = {2024-02-03}"));
+        assert!(!bibtex.contains("Loose Notes"));
+    }
+}
@@ -0,0 +1,90 @@
+//! Home feed shown when the app opens
+//!
+//! Sections are read from existing access columns with fixed caps and no
+//! AI calls, so the feed is cheap enough to build on every launch.
+
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::db::models::FeedItem;
+
+/// Items per section
+pub const SECTION_LIMIT: usize = 8;
+/// Never-opened imports listed by name
+pub const UNOPENED_LIMIT: usize = 5;
+/// How far back imports count as recent, in days
+pub const UNOPENED_WINDOW_DAYS: i64 = 7;
+/// Views a document needs to count as frequently accessed
+pub const FREQUENT_MIN_VIEWS: i64 = 3;
+/// Frequently accessed documents must have been opened within this many days
+pub const FREQUENT_WINDOW_DAYS: i64 = 60;
+
+/// Sections of the home feed
+///
+/// A document appears in at most one section, the first of: unopened
+/// imports, recently read, recently modified, frequently accessed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HomeFeed {
+    /// Recent imports that were never opened
+    pub unopened_imports: Vec<FeedItem>,
+    /// Number of recent imports never opened, including those not listed
+    pub unopened_import_count: i64,
+    pub recently_read: Vec<FeedItem>,
+    pub recently_modified: Vec<FeedItem>,
+    pub frequently_accessed: Vec<FeedItem>,
+    pub generated_in_ms: u64,
+}
+
+impl HomeFeed {
+    /// Drop documents already shown in an earlier section and apply the caps
+    pub fn dedupe(&mut self) {
+        let mut seen = HashSet::new();
+        for (section, limit) in [
+            (&mut self.unopened_imports, UNOPENED_LIMIT),
+            (&mut self.recently_read, SECTION_LIMIT),
+            (&mut self.recently_modified, SECTION_LIMIT),
+            (&mut self.frequently_accessed, SECTION_LIMIT),
+        ] {
+            section.retain(|item| seen.insert(item.id.clone()));
+            section.truncate(limit);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(id: &str) -> FeedItem {
+        FeedItem {
+            id: id.to_string(),
+            title: id.to_string(),
+            category: None,
+            content_type: "text/plain".to_string(),
+            reading_time: None,
+            created_at: chrono::DateTime::UNIX_EPOCH,
+            updated_at: chrono::DateTime::UNIX_EPOCH,
+            last_accessed: None,
+            view_count: 0,
+            is_favorite: false,
+        }
+    }
+
+    #[test]
+    fn test_dedupe_keeps_first_section_and_caps() {
+        let mut feed = HomeFeed {
+            recently_read: vec![item("a"), item("b")],
+            recently_modified: vec![item("b"), item("c")],
+            frequently_accessed: (0..20).map(|i| item(&format!("f{}", i))).chain([item("a")]).collect(),
+            ..Default::default()
+        };
+        feed.dedupe();
+
+        let ids = |items: &[FeedItem]| items.iter().map(|i| i.id.clone()).collect::<Vec<_>>();
+        assert_eq!(ids(&feed.recently_read), vec!["a", "b"]);
+        assert_eq!(ids(&feed.recently_modified), vec!["c"]);
+        assert_eq!(feed.frequently_accessed.len(), SECTION_LIMIT);
+        assert!(!ids(&feed.frequently_accessed).contains(&"a".to_string()));
+    }
+}
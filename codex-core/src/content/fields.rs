@@ -0,0 +1,131 @@
+//! Custom metadata fields
+//!
+//! Besides their columns, documents carry free-form fields such as
+//! `project: apollo` or `status: to-read` in `metadata_json`. Imported notes
+//! get them from their frontmatter; they are also set one document at a time
+//! or through bulk updates, and searches filter on them.
+//!
+//! Edits are validated here: keys must be plain identifiers (see
+//! [`is_metadata_key`]) that frontmatter does not map onto a column, and
+//! values are kept small since every document row carries them.
+
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+
+use crate::db::models::{is_metadata_key, MetadataValue, METADATA_KEY_MAX_LEN};
+use crate::{CodexError, CodexResult};
+
+use super::frontmatter;
+
+/// Most fields a document can have
+pub const MAX_FIELDS: usize = 64;
+/// Longest text value, and longest list item, in bytes
+pub const MAX_VALUE_BYTES: usize = 1024;
+/// Most items of a list value
+pub const MAX_LIST_ITEMS: usize = 64;
+
+/// Check that `key` can be set or filtered on
+pub fn validate_key(key: &str) -> CodexResult<()> {
+    if !is_metadata_key(key) {
+        return Err(CodexError::validation(format!(
+            "Invalid metadata key '{}': use up to {} letters, digits, '_' or '-'",
+            key, METADATA_KEY_MAX_LEN
+        )));
+    }
+    if frontmatter::is_field_key(key) {
+        return Err(CodexError::validation(format!(
+            "Metadata key '{}' is a document field; edit the field instead",
+            key
+        )));
+    }
+    Ok(())
+}
+
+/// Check a field before it is stored
+pub fn validate_field(key: &str, value: &MetadataValue) -> CodexResult<()> {
+    validate_key(key)?;
+    let too_long = |text: &String| text.len() > MAX_VALUE_BYTES;
+    match value {
+        MetadataValue::Text(text) if too_long(text) => Err(CodexError::validation(format!(
+            "Value of '{}' is longer than {} bytes",
+            key, MAX_VALUE_BYTES
+        ))),
+        MetadataValue::List(items) if items.len() > MAX_LIST_ITEMS => Err(CodexError::validation(format!(
+            "List '{}' has more than {} items",
+            key, MAX_LIST_ITEMS
+        ))),
+        MetadataValue::List(items) if items.iter().any(too_long) => Err(CodexError::validation(format!(
+            "An item of '{}' is longer than {} bytes",
+            key, MAX_VALUE_BYTES
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Fields of a document's metadata that are custom metadata values
+///
+/// Imported JSON of any other shape, such as nested objects, is left out.
+pub fn typed(metadata: &BTreeMap<String, Value>) -> BTreeMap<String, MetadataValue> {
+    metadata
+        .iter()
+        .filter_map(|(key, value)| MetadataValue::from_json(value).map(|value| (key.clone(), value)))
+        .collect()
+}
+
+/// Set and remove fields of a document's metadata; removing a missing key
+/// is not an error
+pub fn apply(metadata: &mut BTreeMap<String, Value>, set: &BTreeMap<String, MetadataValue>, remove: &[String]) {
+    for key in remove {
+        metadata.remove(key);
+    }
+    for (key, value) in set {
+        metadata.insert(key.clone(), value.clone().into());
+    }
+}
+
+/// Check that a document's metadata stays within [`MAX_FIELDS`] fields
+pub fn validate_count(metadata: &BTreeMap<String, Value>) -> CodexResult<()> {
+    if metadata.len() > MAX_FIELDS {
+        return Err(CodexError::validation(format!("Documents can have at most {} metadata fields", MAX_FIELDS)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keys_and_values_are_validated() {
+        assert!(validate_field("project", &MetadataValue::Text("apollo".to_string())).is_ok());
+        assert!(validate_field("read-count", &MetadataValue::Number(3.into())).is_ok());
+        assert!(validate_field("", &MetadataValue::Bool(true)).is_err());
+        assert!(validate_field("due date", &MetadataValue::Bool(true)).is_err());
+        assert!(validate_field("$.x", &MetadataValue::Bool(true)).is_err());
+        assert!(validate_field("Tags", &MetadataValue::List(vec!["a".to_string()])).is_err());
+        assert!(validate_field("notes", &MetadataValue::Text("x".repeat(MAX_VALUE_BYTES + 1))).is_err());
+        assert!(validate_field("aliases", &MetadataValue::List(vec!["a".to_string(); MAX_LIST_ITEMS + 1])).is_err());
+    }
+
+    #[test]
+    fn test_values_round_trip_through_json() {
+        let mut metadata = BTreeMap::new();
+        let set = BTreeMap::from([
+            ("status".to_string(), MetadataValue::Text("to-read".to_string())),
+            ("rating".to_string(), MetadataValue::Number(serde_json::Number::from_f64(4.5).unwrap())),
+            ("draft".to_string(), MetadataValue::Bool(false)),
+            ("aliases".to_string(), MetadataValue::List(vec!["Apollo".to_string()])),
+        ]);
+        apply(&mut metadata, &set, &[]);
+        assert!(validate_count(&metadata).is_ok());
+        metadata.insert("nested".to_string(), serde_json::json!({ "a": 1 }));
+
+        assert_eq!(typed(&metadata), set);
+        assert!(set["aliases"].matches(&metadata["aliases"]));
+        assert!(!MetadataValue::Text("4.5".to_string()).matches(&metadata["rating"]));
+
+        apply(&mut metadata, &BTreeMap::new(), &["draft".to_string(), "missing".to_string()]);
+        assert!(!metadata.contains_key("draft"));
+    }
+}
@@ -0,0 +1,213 @@
+//! Find-in-document for the reader view
+//!
+//! Scans a single document's stored content for literal or regex matches and
+//! reports byte and character offsets with surrounding context. Scanning stops
+//! early once the match limit or time budget is reached.
+
+use std::time::{Duration, Instant};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::{CodexError, CodexResult};
+
+/// Characters of context shown on each side of a match
+const CONTEXT_CHARS: usize = 40;
+/// Compiled regex size limit, keeping pathological patterns cheap to reject
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+/// Matches scanned between deadline checks
+const DEADLINE_CHECK_INTERVAL: usize = 256;
+
+/// Options for searching within a document; omitted fields use the defaults
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FindOptions {
+    pub case_sensitive: bool,
+    /// Only match whole words
+    pub whole_word: bool,
+    /// Treat the query as a regular expression
+    pub regex: bool,
+    /// Stop after this many matches
+    pub max_matches: usize,
+    /// Time budget for the scan in milliseconds
+    pub timeout_ms: u64,
+}
+
+impl Default for FindOptions {
+    fn default() -> Self {
+        Self {
+            case_sensitive: false,
+            whole_word: false,
+            regex: false,
+            max_matches: 1000,
+            timeout_ms: 2000,
+        }
+    }
+}
+
+/// A single match within a document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentMatch {
+    pub byte_start: usize,
+    pub byte_end: usize,
+    pub char_start: usize,
+    pub char_end: usize,
+    /// Context preceding the match
+    pub before: String,
+    /// Matched text
+    pub text: String,
+    /// Context following the match
+    pub after: String,
+}
+
+/// Matches found in a document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentMatches {
+    pub match_count: usize,
+    pub matches: Vec<DocumentMatch>,
+    /// Scanning stopped at `max_matches`; more matches may exist
+    pub truncated: bool,
+    /// Scanning stopped at the time budget; more matches may exist
+    pub timed_out: bool,
+}
+
+/// Build the matcher for a query
+pub fn build_matcher(query: &str, options: &FindOptions) -> CodexResult<Regex> {
+    if query.is_empty() {
+        return Err(CodexError::validation("Search query cannot be empty"));
+    }
+
+    let pattern = if options.regex {
+        query.to_string()
+    } else {
+        regex::escape(query)
+    };
+    let pattern = if options.whole_word {
+        format!(r"\b(?:{})\b", pattern)
+    } else {
+        pattern
+    };
+
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!options.case_sensitive)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .build()
+        .map_err(|e| CodexError::validation(format!("Invalid search pattern: {}", e)))
+}
+
+/// Find all matches of `matcher` in `text`
+pub fn find_matches(text: &str, matcher: &Regex, options: &FindOptions) -> DocumentMatches {
+    let deadline = Instant::now() + Duration::from_millis(options.timeout_ms);
+    let mut matches = Vec::new();
+    let mut truncated = false;
+    let mut timed_out = false;
+
+    // Character offsets are counted incrementally from the previous match
+    let mut last_byte = 0;
+    let mut last_char = 0;
+
+    for (i, found) in matcher.find_iter(text).enumerate() {
+        if found.start() == found.end() {
+            continue;
+        }
+        if matches.len() == options.max_matches {
+            truncated = true;
+            break;
+        }
+        if i % DEADLINE_CHECK_INTERVAL == 0 && Instant::now() >= deadline {
+            timed_out = true;
+            break;
+        }
+
+        let char_start = last_char + text[last_byte..found.start()].chars().count();
+        let char_end = char_start + found.as_str().chars().count();
+        last_byte = found.end();
+        last_char = char_end;
+
+        matches.push(DocumentMatch {
+            byte_start: found.start(),
+            byte_end: found.end(),
+            char_start,
+            char_end,
+            before: context_before(text, found.start()),
+            text: found.as_str().to_string(),
+            after: context_after(text, found.end()),
+        });
+    }
+
+    DocumentMatches {
+        match_count: matches.len(),
+        matches,
+        truncated,
+        timed_out,
+    }
+}
+
+fn context_before(text: &str, end: usize) -> String {
+    let start = text[..end]
+        .char_indices()
+        .rev()
+        .nth(CONTEXT_CHARS - 1)
+        .map(|(i, _)| i)
+        .unwrap_or(0);
+    text[start..end].replace('\n', " ")
+}
+
+fn context_after(text: &str, start: usize) -> String {
+    let end = text[start..]
+        .char_indices()
+        .nth(CONTEXT_CHARS)
+        .map(|(i, _)| start + i)
+        .unwrap_or(text.len());
+    text[start..end].replace('\n', " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(text: &str, query: &str, options: FindOptions) -> DocumentMatches {
+        let matcher = build_matcher(query, &options).unwrap();
+        find_matches(text, &matcher, &options)
+    }
+
+    #[test]
+    fn test_literal_case_and_whole_word() {
+        let text = "Cat scatter cat. CAT!";
+
+        assert_eq!(find(text, "cat", FindOptions::default()).match_count, 4);
+
+        let whole = FindOptions { whole_word: true, ..Default::default() };
+        assert_eq!(find(text, "cat", whole).match_count, 3);
+
+        let exact = FindOptions { case_sensitive: true, whole_word: true, ..Default::default() };
+        let result = find(text, "cat", exact);
+        assert_eq!(result.match_count, 1);
+        assert_eq!(result.matches[0].byte_start, 12);
+    }
+
+    #[test]
+    fn test_char_offsets_and_context_with_multibyte_text() {
+        let text = "naïve café\nthen café again";
+        let result = find(text, "café", FindOptions::default());
+
+        assert_eq!(result.match_count, 2);
+        assert_eq!(result.matches[0].char_start, 6);
+        assert_eq!(result.matches[0].byte_start, 7);
+        assert_eq!(result.matches[1].char_start, 16);
+        assert_eq!(result.matches[1].before, "naïve café then ");
+        assert_eq!(result.matches[1].after, " again");
+    }
+
+    #[test]
+    fn test_regex_mode_and_limits() {
+        let options = FindOptions { regex: true, max_matches: 2, ..Default::default() };
+        let result = find("a1 b22 c333", r"\d+", options);
+
+        assert_eq!(result.match_count, 2);
+        assert!(result.truncated);
+        assert_eq!(result.matches[1].text, "22");
+
+        assert!(build_matcher("(", &FindOptions { regex: true, ..Default::default() }).is_err());
+        assert!(build_matcher("(", &FindOptions::default()).is_ok());
+    }
+}
@@ -0,0 +1,174 @@
+//! Time-boxed study sets on a topic
+//!
+//! A focus pack picks the documents most relevant to a topic whose reading
+//! times add up to a time budget, orders them from easiest to hardest and
+//! stores them as a collection, next to an index document tagged
+//! [`FOCUS_PACK_TAG`] that introduces the set.
+
+use serde::{Deserialize, Serialize};
+
+use crate::db::models::Document;
+
+/// Tag applied to focus pack index documents, which are never picked themselves
+pub const FOCUS_PACK_TAG: &str = "focus-pack";
+/// Search results considered for a pack
+pub const FOCUS_PACK_CANDIDATES: usize = 100;
+
+/// Accepted difficulty levels (1-5), both ends included
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DifficultyRange {
+    pub min: i64,
+    pub max: i64,
+}
+
+impl DifficultyRange {
+    pub fn contains(&self, level: i64) -> bool {
+        (self.min..=self.max).contains(&level)
+    }
+}
+
+/// A document chosen for a pack
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FocusPick {
+    pub document_id: String,
+    pub title: String,
+    pub reading_minutes: i64,
+    pub difficulty: Option<i64>,
+    /// Search score for the topic
+    pub relevance: f64,
+    /// Why the document is in the pack and where it stands
+    pub rationale: String,
+}
+
+/// A stored focus pack
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FocusPack {
+    pub collection_id: String,
+    pub index_document_id: String,
+    /// Reading time of the picks, at most the budget
+    pub total_minutes: i64,
+    /// Picks in reading order
+    pub picks: Vec<FocusPick>,
+}
+
+/// Pick documents by relevance until the budget is spent, then order them easiest first
+///
+/// `candidates` are (document, relevance, reading minutes). Documents outside
+/// `difficulty` are skipped; documents without a difficulty are kept, as
+/// their level is unknown. A document that does not fit the remaining time
+/// is passed over for less relevant, shorter ones. Documents of unknown
+/// difficulty are read last, and equal levels go by relevance.
+pub fn select(
+    candidates: Vec<(Document, f64, i64)>,
+    minutes: i64,
+    difficulty: Option<DifficultyRange>,
+) -> Vec<FocusPick> {
+    let mut candidates = candidates;
+    candidates.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let mut remaining = minutes;
+    let mut picks = Vec::new();
+    for (rank, (document, relevance, reading_minutes)) in candidates.into_iter().enumerate() {
+        if document.get_tags().iter().any(|t| t == FOCUS_PACK_TAG) {
+            continue;
+        }
+        if let (Some(range), Some(level)) = (difficulty, document.difficulty_level) {
+            if !range.contains(level) {
+                continue;
+            }
+        }
+        let reading_minutes = reading_minutes.max(1);
+        if reading_minutes > remaining {
+            continue;
+        }
+        remaining -= reading_minutes;
+
+        let level = match document.difficulty_level {
+            Some(level) => format!("difficulty {}/5", level),
+            None => "difficulty not assessed".to_string(),
+        };
+        picks.push(FocusPick {
+            rationale: format!("#{} most relevant match, {} min, {}", rank + 1, reading_minutes, level),
+            document_id: document.id,
+            title: document.title,
+            reading_minutes,
+            difficulty: document.difficulty_level,
+            relevance,
+        });
+    }
+
+    picks.sort_by(|a, b| {
+        a.difficulty
+            .unwrap_or(i64::MAX)
+            .cmp(&b.difficulty.unwrap_or(i64::MAX))
+            .then_with(|| b.relevance.total_cmp(&a.relevance))
+    });
+    picks
+}
+
+/// Title of a pack's collection and index document
+pub fn pack_title(topic: &str, minutes: i64) -> String {
+    format!("Focus pack: {} ({} min)", topic.trim(), minutes)
+}
+
+/// Prompt asking for an introduction tying the picks together
+pub fn intro_prompt(topic: &str, picks: &[FocusPick]) -> String {
+    let listing: Vec<String> = picks
+        .iter()
+        .enumerate()
+        .map(|(i, pick)| format!("{}. {} ({} min)", i + 1, pick.title, pick.reading_minutes))
+        .collect();
+
+    format!(
+        "A reader will study \"{}\" by reading these documents in order. Write a short introduction \
+         (3-5 sentences) explaining what they will learn and how the documents build on each other:\n\n{}\n\nIntroduction:",
+        topic.trim(),
+        listing.join("\n")
+    )
+}
+
+/// Render the index document as markdown
+pub fn render_index(title: &str, intro: &str, picks: &[FocusPick]) -> String {
+    let total: i64 = picks.iter().map(|pick| pick.reading_minutes).sum();
+    let mut output = format!("# {}\n\n{}\n\n## Reading order ({} min)\n\n", title, intro.trim(), total);
+    for (i, pick) in picks.iter().enumerate() {
+        output.push_str(&format!("{}. [[{}]] ({})\n", i + 1, pick.title, pick.rationale));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(title: &str, relevance: f64, minutes: i64, difficulty: Option<i64>) -> (Document, f64, i64) {
+        let mut document = Document::new(title.to_string(), String::new(), "text/plain".to_string());
+        document.difficulty_level = difficulty;
+        (document, relevance, minutes)
+    }
+
+    #[test]
+    fn test_picks_fit_the_budget_easiest_first() {
+        let candidates = vec![
+            candidate("Qubits in depth", 0.9, 60, Some(4)),
+            candidate("Research frontier", 0.8, 30, Some(5)),
+            candidate("Long survey", 0.7, 90, Some(2)),
+            candidate("Intro to qubits", 0.6, 40, Some(1)),
+            candidate("Notes", 0.5, 15, None),
+        ];
+
+        let picks = select(candidates, 120, Some(DifficultyRange { min: 1, max: 4 }));
+        let titles: Vec<&str> = picks.iter().map(|p| p.title.as_str()).collect();
+        assert_eq!(titles, vec!["Intro to qubits", "Qubits in depth", "Notes"]);
+        assert_eq!(picks.iter().map(|p| p.reading_minutes).sum::<i64>(), 115);
+        assert!(picks[1].rationale.starts_with("#1 most relevant"));
+    }
+
+    #[test]
+    fn test_index_lists_picks_in_order() {
+        let picks = select(vec![candidate("Basics", 0.5, 10, Some(1))], 30, None);
+        let index = render_index("Focus pack: qubits (30 min)", "Start here.", &picks);
+        assert!(index.contains("## Reading order (10 min)"));
+        assert!(index.contains("1. [[Basics]] (#1 most relevant match, 10 min, difficulty 1/5)"));
+    }
+}
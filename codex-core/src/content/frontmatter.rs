@@ -0,0 +1,384 @@
+//! YAML frontmatter of markdown files
+//!
+//! Notes often start with a `---` delimited block of `key: value` lines.
+//! Imports strip the block so it is neither indexed nor embedded, and map
+//! the keys documents have fields for (title, author, category, tags and
+//! date) onto them; other keys are kept in the document's `metadata_json`.
+//! [`render_document`] writes the block back, so exported notes regain
+//! their frontmatter.
+//!
+//! Only the subset of YAML notes use is read: scalars, `[a, b]` flow lists
+//! and `- item` block lists.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde_json::Value;
+
+use crate::db::models::Document;
+
+/// Keys with a document field, in the order they are written
+const TITLE_KEYS: [&str; 1] = ["title"];
+const AUTHOR_KEYS: [&str; 2] = ["author", "authors"];
+const CATEGORY_KEYS: [&str; 2] = ["category", "categories"];
+const TAG_KEYS: [&str; 2] = ["tags", "tag"];
+const DATE_KEYS: [&str; 4] = ["date", "created", "created_at", "created-at"];
+
+/// Whether a frontmatter key maps onto a document field rather than custom metadata
+pub fn is_field_key(key: &str) -> bool {
+    let key = key.to_lowercase();
+    let key = key.as_str();
+    [&TITLE_KEYS[..], &AUTHOR_KEYS, &CATEGORY_KEYS, &TAG_KEYS, &DATE_KEYS].iter().any(|keys| keys.contains(&key))
+}
+
+/// Metadata read from a note's frontmatter
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Frontmatter {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub category: Option<String>,
+    pub tags: Vec<String>,
+    pub date: Option<DateTime<Utc>>,
+    /// Keys without a document field, as strings, numbers, booleans or
+    /// lists of strings
+    pub custom: BTreeMap<String, Value>,
+}
+
+/// One `key: value` line with the block list items under it
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Entry {
+    pub key: String,
+    /// Text after the colon, trimmed
+    pub value: String,
+    /// Block list items, still quoted
+    pub items: Vec<String>,
+}
+
+impl Entry {
+    fn is_list(&self) -> bool {
+        !self.items.is_empty() || self.value.starts_with('[')
+    }
+
+    /// Items of a block or flow list, or the comma-separated scalar, still quoted
+    fn raw_values(&self) -> Vec<&str> {
+        if !self.items.is_empty() {
+            return self.items.iter().map(String::as_str).collect();
+        }
+        let value = self.value.strip_prefix('[').and_then(|v| v.strip_suffix(']')).unwrap_or(&self.value);
+        value.split(',').map(str::trim).filter(|v| !v.is_empty()).collect()
+    }
+
+    /// Items of a block or flow list, or the comma-separated scalar
+    pub fn values(&self) -> Vec<String> {
+        self.raw_values().into_iter().map(unquote).filter(|v| !v.is_empty()).collect()
+    }
+
+    /// Tags of a list; unquoted items may hold several separated by spaces,
+    /// as Obsidian allows
+    fn tags(&self) -> Vec<String> {
+        let mut tags = Vec::new();
+        for value in self.raw_values() {
+            if value.starts_with(['"', '\'']) {
+                tags.push(clean_tag(&unquote(value)));
+            } else {
+                tags.extend(value.split_whitespace().map(clean_tag));
+            }
+        }
+        tags.retain(|tag| !tag.is_empty());
+        tags
+    }
+
+    /// First item of a list, otherwise the whole scalar
+    fn first(&self) -> Option<String> {
+        let value = if self.is_list() { self.values().into_iter().next() } else { Some(unquote(&self.value)) };
+        value.map(|v| clean_tag(&v)).filter(|v| !v.is_empty())
+    }
+
+    /// Value of a key without a document field
+    fn to_json(&self) -> Value {
+        if self.is_list() {
+            return Value::Array(self.values().into_iter().map(Value::String).collect());
+        }
+        let quoted = self.value.starts_with(['"', '\'']);
+        match serde_json::from_str::<Value>(&self.value) {
+            Ok(value @ (Value::Bool(_) | Value::Number(_))) if !quoted => value,
+            _ => Value::String(unquote(&self.value)),
+        }
+    }
+}
+
+/// Frontmatter block and the body after it, when `text` starts with one
+pub fn split(text: &str) -> Option<(&str, &str)> {
+    let rest = text.strip_prefix("---\n").or_else(|| text.strip_prefix("---\r\n"))?;
+    let end = rest.find("\n---")?;
+    let block = &rest[..end];
+    let body = rest[end + 4..].trim_start_matches(['\r', '\n']);
+    Some((block, body))
+}
+
+/// Split a markdown file into its frontmatter and body
+///
+/// Text without a frontmatter block is returned whole.
+pub fn parse(text: &str) -> (Option<Frontmatter>, &str) {
+    let Some((block, body)) = split(text) else {
+        return (None, text);
+    };
+
+    let mut frontmatter = Frontmatter::default();
+    for entry in entries(block) {
+        let key = entry.key.to_lowercase();
+        let key = key.as_str();
+        if TITLE_KEYS.contains(&key) {
+            frontmatter.title = Some(unquote(&entry.value)).filter(|title| !title.is_empty());
+        } else if AUTHOR_KEYS.contains(&key) {
+            frontmatter.author = entry.first();
+        } else if CATEGORY_KEYS.contains(&key) {
+            frontmatter.category = entry.first();
+        } else if TAG_KEYS.contains(&key) {
+            for tag in entry.tags() {
+                if !frontmatter.tags.contains(&tag) {
+                    frontmatter.tags.push(tag);
+                }
+            }
+        } else if DATE_KEYS.contains(&key) {
+            frontmatter.date = entry.first().as_deref().and_then(parse_date);
+        } else {
+            frontmatter.custom.insert(entry.key.clone(), entry.to_json());
+        }
+    }
+
+    (Some(frontmatter), body)
+}
+
+impl Frontmatter {
+    /// Frontmatter describing an existing document
+    pub fn from_document(document: &Document) -> Self {
+        Self {
+            title: Some(document.title.clone()),
+            author: document.author.clone(),
+            category: document.category.clone(),
+            tags: document.get_tags(),
+            date: Some(document.created_at),
+            custom: document.get_metadata(),
+        }
+    }
+
+    /// Set the document fields the frontmatter names
+    ///
+    /// Tags are added to those the document already has. The date only
+    /// replaces `created_at` with `respect_source_dates`.
+    pub fn apply_to(&self, document: &mut Document, respect_source_dates: bool) {
+        if let Some(title) = &self.title {
+            document.title = title.clone();
+        }
+        if let Some(author) = &self.author {
+            document.author = Some(author.clone());
+        }
+        if let Some(category) = &self.category {
+            document.category = Some(category.clone());
+        }
+        if !self.tags.is_empty() {
+            let mut tags = document.get_tags();
+            for tag in &self.tags {
+                if !tags.contains(tag) {
+                    tags.push(tag.clone());
+                }
+            }
+            document.set_tags(tags);
+        }
+        if let (true, Some(date)) = (respect_source_dates, self.date) {
+            document.created_at = date;
+        }
+        if !self.custom.is_empty() {
+            document.set_metadata(&self.custom);
+        }
+    }
+
+    /// The `---` delimited block, ending with a newline
+    pub fn render(&self) -> String {
+        let mut output = String::from("---\n");
+        if let Some(title) = &self.title {
+            output.push_str(&format!("title: {}\n", quote(title)));
+        }
+        if let Some(author) = &self.author {
+            output.push_str(&format!("author: {}\n", quote(author)));
+        }
+        if let Some(category) = &self.category {
+            output.push_str(&format!("category: {}\n", quote(category)));
+        }
+        if !self.tags.is_empty() {
+            output.push_str("tags:\n");
+            for tag in &self.tags {
+                output.push_str(&format!("  - {}\n", quote(tag)));
+            }
+        }
+        if let Some(date) = self.date {
+            output.push_str(&format!("date: {}\n", date.to_rfc3339()));
+        }
+        for (key, value) in &self.custom {
+            match value {
+                Value::Array(items) => {
+                    output.push_str(&format!("{}:\n", key));
+                    for item in items {
+                        output.push_str(&format!("  - {}\n", quote(&json_text(item))));
+                    }
+                }
+                Value::String(text) => output.push_str(&format!("{}: {}\n", key, quote(text))),
+                other => output.push_str(&format!("{}: {}\n", key, other)),
+            }
+        }
+        output.push_str("---\n");
+        output
+    }
+}
+
+/// A document as a markdown file: its frontmatter, then its content
+pub fn render_document(document: &Document) -> String {
+    format!("{}\n{}", Frontmatter::from_document(document).render(), document.content)
+}
+
+/// `key: value` entries of a frontmatter block, with block lists folded in
+pub(super) fn entries(block: &str) -> Vec<Entry> {
+    let mut entries: Vec<Entry> = Vec::new();
+
+    for line in block.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if let Some(item) = trimmed.strip_prefix("- ") {
+            if line.starts_with([' ', '\t', '-']) {
+                if let Some(entry) = entries.last_mut() {
+                    entry.items.push(item.trim().to_string());
+                }
+            }
+            continue;
+        }
+        if let Some((key, value)) = trimmed.split_once(':') {
+            entries.push(Entry { key: key.trim().to_string(), value: value.trim().to_string(), items: Vec::new() });
+        }
+    }
+
+    entries
+}
+
+/// `a, b`, `[a, b]` or a single scalar as a list of values
+pub(super) fn split_list(value: &str) -> Vec<String> {
+    let value = value.trim();
+    let value = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')).unwrap_or(value);
+    value
+        .split(',')
+        .map(unquote)
+        .filter(|v| !v.is_empty())
+        .collect()
+}
+
+/// Value without its surrounding quotes, with escapes resolved
+pub(super) fn unquote(value: &str) -> String {
+    let value = value.trim();
+    if value.len() >= 2 {
+        if let Some(inner) = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+            return inner.replace("\\\"", "\"").replace("\\\\", "\\");
+        }
+        if let Some(inner) = value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')) {
+            return inner.replace("''", "'");
+        }
+    }
+    value.trim_matches(['"', '\'']).to_string()
+}
+
+/// Double-quoted scalar, so commas, colons and `#` survive a re-import
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn json_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// `#tag`, `[[tag]]` and `tag` as a plain tag
+pub(super) fn clean_tag(tag: &str) -> String {
+    tag.trim().trim_start_matches('#').trim_start_matches("[[").trim_end_matches("]]").trim().to_string()
+}
+
+pub(super) fn parse_date(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(value) {
+        return Some(date.with_timezone(&Utc));
+    }
+    for format in ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"] {
+        if let Ok(date) = NaiveDateTime::parse_from_str(value, format) {
+            return Some(date.and_utc());
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return date.and_hms_opt(0, 0, 0).map(|d| d.and_utc());
+    }
+    // Logseq stores creation times as epoch milliseconds
+    value.parse::<i64>().ok().and_then(DateTime::from_timestamp_millis)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOTE: &str = "---\ntitle: \"Ownership, explained\"\nauthor: 'Ada'\ntags: [rust, \"#memory\"]\ndate: 2023-05-01\naliases:\n  - Borrowing\n  - Moves\ndraft: true\nrating: 4\nsource: \"book: ch. 4\"\n---\n\n# Ownership\nBody text.\n";
+
+    #[test]
+    fn test_frontmatter_is_stripped_and_mapped() {
+        let (frontmatter, body) = parse(NOTE);
+        let frontmatter = frontmatter.unwrap();
+        assert_eq!(body, "# Ownership\nBody text.\n");
+        assert_eq!(frontmatter.title.as_deref(), Some("Ownership, explained"));
+        assert_eq!(frontmatter.author.as_deref(), Some("Ada"));
+        assert_eq!(frontmatter.tags, vec!["rust", "memory"]);
+        assert_eq!(frontmatter.date.unwrap().to_rfc3339(), "2023-05-01T00:00:00+00:00");
+        assert_eq!(frontmatter.custom["aliases"], serde_json::json!(["Borrowing", "Moves"]));
+        assert_eq!(frontmatter.custom["draft"], Value::Bool(true));
+        assert_eq!(frontmatter.custom["rating"], serde_json::json!(4));
+        assert_eq!(frontmatter.custom["source"], Value::String("book: ch. 4".to_string()));
+
+        assert_eq!(parse("# No frontmatter\n---\n"), (None, "# No frontmatter\n---\n"));
+    }
+
+    #[test]
+    fn test_apply_merges_tags_and_respects_dates_only_when_asked() {
+        let (frontmatter, body) = parse(NOTE);
+        let frontmatter = frontmatter.unwrap();
+        let mut document = Document::new("Fallback".to_string(), body.to_string(), "text/markdown".to_string());
+        document.set_tags(vec!["memory".to_string(), "ai-tag".to_string()]);
+        let imported_at = document.created_at;
+
+        frontmatter.apply_to(&mut document, false);
+        assert_eq!(document.title, "Ownership, explained");
+        assert_eq!(document.get_tags(), vec!["memory", "ai-tag", "rust"]);
+        assert_eq!(document.created_at, imported_at);
+        assert_eq!(document.get_metadata(), frontmatter.custom);
+
+        frontmatter.apply_to(&mut document, true);
+        assert_eq!(document.created_at, frontmatter.date.unwrap());
+    }
+
+    #[test]
+    fn test_rendered_document_round_trips() {
+        let (frontmatter, body) = parse(NOTE);
+        let mut document = Document::new("Fallback".to_string(), body.to_string(), "text/markdown".to_string());
+        frontmatter.unwrap().apply_to(&mut document, true);
+        document.author = Some("Quote \"Q\" \\ Backslash".to_string());
+        document.set_tags(vec!["rust".to_string(), "machine learning".to_string()]);
+
+        let exported = render_document(&document);
+        let (reparsed, reparsed_body) = parse(&exported);
+        let mut reimported = Document::new("Other".to_string(), reparsed_body.to_string(), "text/markdown".to_string());
+        reparsed.unwrap().apply_to(&mut reimported, true);
+
+        assert_eq!(reimported.content, document.content);
+        assert_eq!(reimported.title, document.title);
+        assert_eq!(reimported.author, document.author);
+        assert_eq!(reimported.get_tags(), document.get_tags());
+        assert_eq!(reimported.created_at, document.created_at);
+        assert_eq!(reimported.get_metadata(), document.get_metadata());
+    }
+}
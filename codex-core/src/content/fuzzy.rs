@@ -0,0 +1,94 @@
+//! Spelling-tolerant matching for the search fallback
+//!
+//! Query terms missing from the full-text vocabulary are corrected to the
+//! closest indexed term, and titles are compared to the query by trigram
+//! similarity so a misspelled title still finds its document.
+
+use std::collections::HashSet;
+
+/// Query terms as the full-text tokenizer sees them: lowercased alphanumeric words
+pub fn query_terms(query: &str) -> Vec<String> {
+    query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|term| !term.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Edits tolerated when correcting a term of `len` characters
+///
+/// Short terms are left alone; one typo in them already changes the word.
+pub fn max_edits(len: usize) -> usize {
+    match len {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+/// Closest vocabulary term within [`max_edits`] of `term`
+///
+/// `vocabulary` holds terms with their document counts; ties in edit
+/// distance go to the more common term.
+pub fn best_correction(term: &str, vocabulary: &[(String, i64)]) -> Option<String> {
+    let allowed = max_edits(term.chars().count());
+    if allowed == 0 || term.chars().all(|c| c.is_numeric()) {
+        return None;
+    }
+
+    vocabulary
+        .iter()
+        .filter(|(candidate, _)| candidate != term)
+        .map(|(candidate, docs)| (candidate, *docs, strsim::damerau_levenshtein(term, candidate)))
+        .filter(|(_, _, distance)| *distance <= allowed)
+        .min_by(|a, b| a.2.cmp(&b.2).then(b.1.cmp(&a.1)))
+        .map(|(candidate, _, _)| candidate.clone())
+}
+
+/// Character trigrams of each word, padded like `pg_trgm`
+fn trigrams(text: &str) -> HashSet<[char; 3]> {
+    let mut set = HashSet::new();
+    for word in query_terms(text) {
+        let padded: Vec<char> = "  ".chars().chain(word.chars()).chain([' ']).collect();
+        for window in padded.windows(3) {
+            set.insert([window[0], window[1], window[2]]);
+        }
+    }
+    set
+}
+
+/// Share of trigrams two strings have in common (Jaccard index, 0-1)
+pub fn trigram_similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (trigrams(a), trigrams(b));
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    let shared = a.intersection(&b).count();
+    shared as f64 / (a.len() + b.len() - shared) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_corrections_and_trigram_similarity() {
+        let vocabulary = vec![
+            ("quantum".to_string(), 12),
+            ("quartum".to_string(), 1),
+            ("mechanics".to_string(), 8),
+        ];
+        assert_eq!(best_correction("quantun", &vocabulary), Some("quantum".to_string()));
+        assert_eq!(best_correction("mechnaics", &vocabulary), Some("mechanics".to_string()));
+        assert_eq!(best_correction("cat", &vocabulary), None);
+        assert_eq!(best_correction("biology", &vocabulary), None);
+
+        assert_eq!(query_terms("Quantun-mechanics, 101"), vec!["quantun", "mechanics", "101"]);
+
+        let close = trigram_similarity("Quantun Mechanics", "Quantum Mechanics");
+        let far = trigram_similarity("Quantun Mechanics", "Cooking with herbs");
+        assert!(close > 0.5, "{}", close);
+        assert!(far < 0.1, "{}", far);
+        assert_eq!(trigram_similarity("", "anything"), 0.0);
+    }
+}
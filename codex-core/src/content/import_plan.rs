@@ -0,0 +1,168 @@
+//! Import dry runs
+//!
+//! A plan records what an import would do with each file (import it, queue
+//! it for transcription, or skip it as a duplicate or invalid file) without
+//! writing anything. It also estimates how long the import would take.
+
+use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+
+use crate::metrics;
+
+/// Estimated cost of importing a file, excluding size-dependent work, used
+/// until this session has timed a real import
+const DEFAULT_IMPORT_BASE_MS: f64 = 2000.0;
+/// Estimated parsing and indexing cost per megabyte
+const IMPORT_MS_PER_MB: f64 = 1000.0;
+/// Estimated transcription cost per megabyte of audio
+const TRANSCRIBE_MS_PER_MB: f64 = 30_000.0;
+
+/// What an import would do with a file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "decision", rename_all = "snake_case")]
+pub enum ImportDecision {
+    /// Parsed and stored as a new document
+    Import,
+    /// Queued for background transcription (audio files)
+    Transcribe,
+    /// Content identical to a document already in the vault
+    Duplicate { existing_id: String, existing_title: String },
+    /// Content identical to an earlier file in the same import
+    DuplicateInBatch { original: PathBuf },
+    /// Rejected by validation or parsing
+    Skip { reason: String },
+}
+
+impl ImportDecision {
+    /// Whether the file would end up in the vault
+    pub fn will_import(&self) -> bool {
+        matches!(self, ImportDecision::Import | ImportDecision::Transcribe)
+    }
+}
+
+/// Planned outcome for one file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileImportPlan {
+    pub path: PathBuf,
+    pub size_bytes: u64,
+    /// Title the document would get, when the file could be parsed
+    pub title: Option<String>,
+    pub content_type: Option<String>,
+    #[serde(flatten)]
+    pub decision: ImportDecision,
+    pub estimated_ms: u64,
+}
+
+impl FileImportPlan {
+    /// Plan for a file that would not be imported
+    pub fn skipped(path: PathBuf, size_bytes: u64, decision: ImportDecision) -> Self {
+        Self {
+            path,
+            size_bytes,
+            title: None,
+            content_type: None,
+            decision,
+            estimated_ms: 0,
+        }
+    }
+}
+
+/// Planned outcome of an import, file by file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BulkImportPlan {
+    pub total_files: usize,
+    /// Files that would be imported or queued for transcription
+    pub to_import: usize,
+    pub duplicates: usize,
+    pub skipped: usize,
+    /// Size of the files that would be imported
+    pub total_bytes: u64,
+    pub estimated_duration_ms: u64,
+    pub files: Vec<FileImportPlan>,
+}
+
+impl BulkImportPlan {
+    /// Summarize per-file plans
+    pub fn from_files(files: Vec<FileImportPlan>) -> Self {
+        let mut plan = Self {
+            total_files: files.len(),
+            ..Self::default()
+        };
+        for file in &files {
+            match &file.decision {
+                ImportDecision::Import | ImportDecision::Transcribe => {
+                    plan.to_import += 1;
+                    plan.total_bytes += file.size_bytes;
+                    plan.estimated_duration_ms += file.estimated_ms;
+                }
+                ImportDecision::Duplicate { .. } | ImportDecision::DuplicateInBatch { .. } => plan.duplicates += 1,
+                ImportDecision::Skip { .. } => plan.skipped += 1,
+            }
+        }
+        plan.files = files;
+        plan
+    }
+}
+
+/// Estimated time to import a file of the given size
+///
+/// The fixed part comes from the mean import time measured this session
+/// when there is one.
+pub fn estimate_import_ms(size_bytes: u64, decision: &ImportDecision) -> u64 {
+    let size_mb = size_bytes as f64 / (1024.0 * 1024.0);
+    let estimate = match decision {
+        ImportDecision::Import => {
+            let base = metrics::snapshot()
+                .into_iter()
+                .find(|m| m.operation == metrics::IMPORT && m.count > 0)
+                .map(|m| m.mean_ms)
+                .unwrap_or(DEFAULT_IMPORT_BASE_MS);
+            base + size_mb * IMPORT_MS_PER_MB
+        }
+        ImportDecision::Transcribe => DEFAULT_IMPORT_BASE_MS + size_mb * TRANSCRIBE_MS_PER_MB,
+        _ => 0.0,
+    };
+    estimate.round() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_totals_count_only_imported_files() {
+        let import = |name: &str, size: u64| FileImportPlan {
+            path: PathBuf::from(name),
+            size_bytes: size,
+            title: Some(name.to_string()),
+            content_type: Some("text/plain".to_string()),
+            decision: ImportDecision::Import,
+            estimated_ms: estimate_import_ms(size, &ImportDecision::Import),
+        };
+        let plan = BulkImportPlan::from_files(vec![
+            import("a.txt", 1024),
+            import("b.txt", 2048),
+            FileImportPlan::skipped(
+                PathBuf::from("c.txt"),
+                1024,
+                ImportDecision::DuplicateInBatch { original: PathBuf::from("a.txt") },
+            ),
+            FileImportPlan::skipped(
+                PathBuf::from("d.exe"),
+                4096,
+                ImportDecision::Skip { reason: "Unsupported file extension: exe".to_string() },
+            ),
+        ]);
+
+        assert_eq!(plan.total_files, 4);
+        assert_eq!(plan.to_import, 2);
+        assert_eq!(plan.duplicates, 1);
+        assert_eq!(plan.skipped, 1);
+        assert_eq!(plan.total_bytes, 3072);
+        assert!(plan.estimated_duration_ms > 0);
+        assert_eq!(
+            plan.estimated_duration_ms,
+            plan.files[0].estimated_ms + plan.files[1].estimated_ms
+        );
+    }
+}
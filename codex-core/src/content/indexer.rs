@@ -0,0 +1,263 @@
+//! Content indexing for semantic search
+//!
+//! Full-text indexing is handled by SQLite triggers on the `documents` table;
+//! this module maintains the chunk embeddings used for semantic search and RAG.
+
+use std::sync::Arc;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use crate::CodexResult;
+use crate::config::ContentConfig;
+use crate::db::{DatabaseManager, EmbeddingQueries, SettingQueries, VectorCacheStats};
+use crate::db::models::{chunk_hash, Document, Embedding, Setting};
+use crate::ai::AiEngine;
+use crate::ai::embeddings::{EmbeddingFingerprint, EMBEDDING_FINGERPRINT_SETTING};
+use super::code::{self, CodeLanguage};
+use super::notebook;
+
+/// Number of words per embedding chunk
+const CHUNK_SIZE_WORDS: usize = 256;
+/// Number of overlapping words between consecutive chunks
+const CHUNK_OVERLAP_WORDS: usize = 32;
+
+/// Embedding coverage of the library
+///
+/// Deleted documents are not counted. A document counts as stale when it
+/// was updated after its chunks were embedded, which includes metadata-only
+/// edits such as renames.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct IndexHealth {
+    pub total_documents: u64,
+    /// Documents with at least one embedding chunk
+    pub indexed_documents: u64,
+    /// Documents with no embedding chunks
+    pub missing_embeddings: u64,
+    /// Documents updated after they were last embedded
+    pub stale_documents: u64,
+    /// Chunks embedded by another model or in other dimensions than the current one
+    pub mismatched_chunks: u64,
+    /// Dimensions of the current embedding model
+    pub expected_dimensions: u64,
+    /// Current embedding model
+    #[serde(default)]
+    pub embedding_model: String,
+    /// Stored vectors come from another model, so semantic search is off
+    /// until the documents are reindexed
+    #[serde(default)]
+    pub model_stale: bool,
+    /// Compaction state of the vector cache
+    #[serde(default)]
+    pub vector_cache: VectorCacheStats,
+}
+
+impl IndexHealth {
+    /// Whether every document is embedded, up to date and in the current dimensions
+    pub fn is_healthy(&self) -> bool {
+        self.missing_embeddings == 0 && self.stale_documents == 0 && self.mismatched_chunks == 0 && !self.model_stale
+    }
+
+    /// Summary of the problems found, if any
+    pub fn warning(&self) -> Option<String> {
+        if self.is_healthy() {
+            return None;
+        }
+
+        if self.model_stale {
+            return Some(format!(
+                "Search index was built with another embedding model; semantic search is off until {} chunks are reindexed for {}",
+                self.mismatched_chunks, self.embedding_model
+            ));
+        }
+
+        let mut problems = Vec::new();
+        if self.missing_embeddings > 0 {
+            problems.push(format!("{} documents without embeddings", self.missing_embeddings));
+        }
+        if self.stale_documents > 0 {
+            problems.push(format!("{} documents changed since indexing", self.stale_documents));
+        }
+        if self.mismatched_chunks > 0 {
+            problems.push(format!(
+                "{} chunks not embedded by {} in {} dimensions",
+                self.mismatched_chunks, self.embedding_model, self.expected_dimensions
+            ));
+        }
+        Some(format!("Search index needs repair: {}", problems.join(", ")))
+    }
+}
+
+/// Indexer maintaining document embeddings
+#[derive(Debug)]
+pub struct ContentIndexer {
+    db: Arc<DatabaseManager>,
+    ai: Arc<AiEngine>,
+    config: ContentConfig,
+}
+
+impl ContentIndexer {
+    /// Create a new content indexer
+    pub async fn new(
+        db: Arc<DatabaseManager>,
+        ai: Arc<AiEngine>,
+        config: &ContentConfig,
+    ) -> Result<Self> {
+        Ok(Self {
+            db,
+            ai,
+            config: config.clone(),
+        })
+    }
+
+    /// Generate and store embeddings for a document
+    pub async fn index_document(&self, document: &Document) -> CodexResult<usize> {
+        let embeddings = self.embed_document(document).await?;
+        EmbeddingQueries::create_all_with_binary(self.db.pool(), &embeddings).await?;
+
+        info!("Indexed document {} ({} chunks)", document.id, embeddings.len());
+        Ok(embeddings.len())
+    }
+
+    /// Save a new document together with its embeddings
+    ///
+    /// The embeddings are generated first, then written with the document
+    /// by [`DatabaseManager::create_document_with_embeddings`].
+    pub async fn create_indexed(&self, document: &Document) -> CodexResult<usize> {
+        let embeddings = self.embed_document(document).await?;
+        self.db.create_document_with_embeddings(document, &embeddings).await?;
+
+        info!("Indexed document {} ({} chunks)", document.id, embeddings.len());
+        Ok(embeddings.len())
+    }
+
+    /// Embeddings of a document's chunks, none when automatic indexing is off
+    async fn embed_document(&self, document: &Document) -> CodexResult<Vec<Embedding>> {
+        if !self.config.auto_index {
+            debug!("Automatic indexing disabled, skipping document {}", document.id);
+            return Ok(Vec::new());
+        }
+
+        let embeddings = self.ai.get_embeddings();
+        let model = embeddings.get_model_info().name;
+        let chunks = match CodeLanguage::from_content_type(&document.content_type) {
+            // Code is split on item boundaries so functions are embedded whole
+            Some(language) => code::chunk_code(&document.content, language, CHUNK_SIZE_WORDS),
+            // Notebooks are split on cell boundaries
+            None if document.content_type == notebook::NOTEBOOK_CONTENT_TYPE => {
+                notebook::chunk_notebook(&document.content, CHUNK_SIZE_WORDS)
+            }
+            None => embeddings.chunk_document(&document.content, CHUNK_SIZE_WORDS, CHUNK_OVERLAP_WORDS),
+        };
+
+        // Chunks already embedded for another document, such as a shared
+        // license header, reuse the stored vector instead of running the model
+        let pool = self.db.pool();
+        let mut reused = 0;
+        let mut result = Vec::with_capacity(chunks.len());
+        for (index, chunk) in chunks.into_iter().enumerate() {
+            let vector = match EmbeddingQueries::get_shared_vector(pool, &chunk_hash(&chunk.text), &model).await? {
+                Some(vector) => {
+                    reused += 1;
+                    vector
+                }
+                None => embeddings.generate_embedding(&chunk.text).await?,
+            };
+            result.push(Embedding::new(
+                document.id.clone(),
+                vector,
+                model.clone(),
+                index as i64,
+                chunk.text,
+                chunk.start_position as i64,
+                chunk.end_position as i64,
+            ));
+        }
+        if reused > 0 {
+            debug!("Reused {} stored chunk vectors for document {}", reused, document.id);
+        }
+
+        Ok(result)
+    }
+
+    /// Index a document again, replacing its existing embeddings
+    ///
+    /// The new embeddings are generated first, then swapped in by
+    /// [`DatabaseManager::replace_document_embeddings`].
+    pub async fn reindex_document(&self, document: &Document) -> CodexResult<usize> {
+        let embeddings = self.embed_document(document).await?;
+        self.db.replace_document_embeddings(&document.id, &embeddings).await?;
+
+        info!("Reindexed document {} ({} chunks)", document.id, embeddings.len());
+        Ok(embeddings.len())
+    }
+
+    /// Count documents that are unembedded, stale, or embedded by another model
+    pub async fn get_index_health(&self) -> CodexResult<IndexHealth> {
+        let fingerprint = self.ai.get_embeddings().fingerprint();
+        let (total, missing, stale, mismatched) = EmbeddingQueries::index_health_counts(
+            self.db.pool(),
+            &fingerprint.model_id,
+            fingerprint.dimensions as i64,
+        )
+        .await?;
+
+        Ok(IndexHealth {
+            total_documents: total as u64,
+            indexed_documents: (total - missing) as u64,
+            missing_embeddings: missing as u64,
+            stale_documents: stale as u64,
+            mismatched_chunks: mismatched as u64,
+            expected_dimensions: fingerprint.dimensions as u64,
+            embedding_model: fingerprint.model_id,
+            model_stale: self.ai.get_embeddings().is_index_stale(),
+            vector_cache: self.db.vector_cache_stats().await?,
+        })
+    }
+
+    /// Ids of the documents counted as unhealthy by [`Self::get_index_health`]
+    pub async fn get_unhealthy_documents(&self) -> CodexResult<Vec<String>> {
+        let fingerprint = self.ai.get_embeddings().fingerprint();
+        EmbeddingQueries::get_unhealthy_document_ids(self.db.pool(), &fingerprint.model_id, fingerprint.dimensions as i64)
+            .await
+    }
+
+    /// Compare the active embedding model with the stored vectors
+    ///
+    /// Marks the index stale while any chunk was embedded by another model or
+    /// in other dimensions, and records the model's fingerprint in settings
+    /// once none is left. Returns whether the index is stale.
+    pub async fn check_embedding_model(&self) -> CodexResult<bool> {
+        let embeddings = self.ai.get_embeddings();
+        let current = embeddings.fingerprint();
+        let pool = self.db.pool();
+        let recorded = SettingQueries::get(pool, EMBEDDING_FINGERPRINT_SETTING)
+            .await?
+            .and_then(|setting| setting.get_value::<EmbeddingFingerprint>());
+        let (_, _, _, mismatched) =
+            EmbeddingQueries::index_health_counts(pool, &current.model_id, current.dimensions as i64).await?;
+
+        let stale = mismatched > 0;
+        embeddings.set_index_stale(stale);
+        if stale {
+            warn!(
+                "{} chunks were embedded by another model than {} ({} dimensions, index built with {:?})",
+                mismatched, current.model_id, current.dimensions, recorded
+            );
+        } else if recorded.as_ref() != Some(&current) {
+            let mut setting = Setting::new(EMBEDDING_FINGERPRINT_SETTING.to_string(), String::new(), "ai".to_string());
+            setting.is_user_configurable = false;
+            setting.set_value(&current)?;
+            SettingQueries::set(pool, &setting).await?;
+            info!("Vector index matches embedding model {} ({} dimensions)", current.model_id, current.dimensions);
+        }
+        Ok(stale)
+    }
+
+    /// Remove a document's embeddings from the index
+    pub async fn remove_document(&self, document_id: uuid::Uuid) -> CodexResult<()> {
+        EmbeddingQueries::delete_by_document(self.db.pool(), &document_id.to_string()).await?;
+        debug!("Removed document {} from index", document_id);
+        Ok(())
+    }
+}
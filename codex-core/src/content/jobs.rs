@@ -0,0 +1,274 @@
+//! Background job queue for long-running content operations
+//!
+//! Jobs run on the tokio runtime with a bounded level of concurrency. Every
+//! state change is published on a broadcast channel so frontends can render
+//! progress without polling. A paused queue holds queued jobs until resumed,
+//! e.g. while a laptop runs on battery.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, watch, Semaphore};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::CodexResult;
+
+/// Capacity of the job event channel; slow subscribers miss intermediate updates
+const EVENT_CHANNEL_CAPACITY: usize = 256;
+
+/// Lifecycle state of a job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+}
+
+impl JobStatus {
+    /// Whether the job has stopped and will not emit further updates
+    pub fn is_finished(&self) -> bool {
+        matches!(self, JobStatus::Completed | JobStatus::Failed)
+    }
+}
+
+/// Snapshot of a job, also used as the progress event payload
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobInfo {
+    pub id: Uuid,
+    /// Job type, e.g. "audio_import"
+    pub kind: String,
+    pub status: JobStatus,
+    /// Progress in the range 0.0-1.0
+    pub progress: f32,
+    /// Human-readable status or error message
+    pub message: Option<String>,
+    /// Document produced by the job, if any
+    pub document_id: Option<Uuid>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Handle passed to a running job for reporting progress
+#[derive(Debug, Clone)]
+pub struct JobHandle {
+    id: Uuid,
+    queue: Arc<JobQueue>,
+}
+
+impl JobHandle {
+    /// Identifier of the running job
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    /// Report progress (0.0-1.0) with an optional status message
+    pub fn report(&self, progress: f32, message: Option<String>) {
+        self.queue.update(self.id, |job| {
+            job.progress = progress.clamp(0.0, 1.0);
+            if message.is_some() {
+                job.message = message;
+            }
+        });
+    }
+}
+
+/// Queue running content jobs in the background
+#[derive(Debug)]
+pub struct JobQueue {
+    jobs: Mutex<HashMap<Uuid, JobInfo>>,
+    events: broadcast::Sender<JobInfo>,
+    permits: Arc<Semaphore>,
+    paused: watch::Sender<bool>,
+}
+
+impl JobQueue {
+    /// Create a queue running at most `max_concurrent` jobs at a time
+    pub fn new(max_concurrent: usize) -> Self {
+        let (events, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        Self {
+            jobs: Mutex::new(HashMap::new()),
+            events,
+            permits: Arc::new(Semaphore::new(max_concurrent.max(1))),
+            paused: watch::channel(false).0,
+        }
+    }
+
+    /// Hold queued jobs until resumed; running jobs finish
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.send_if_modified(|current| std::mem::replace(current, paused) != paused);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.paused.borrow()
+    }
+
+    /// Subscribe to job progress events
+    pub fn subscribe(&self) -> broadcast::Receiver<JobInfo> {
+        self.events.subscribe()
+    }
+
+    /// Get the current state of a job
+    pub fn get(&self, id: Uuid) -> Option<JobInfo> {
+        self.jobs.lock().unwrap().get(&id).cloned()
+    }
+
+    /// List all jobs known to the queue, newest first
+    pub fn list(&self) -> Vec<JobInfo> {
+        let mut jobs: Vec<JobInfo> = self.jobs.lock().unwrap().values().cloned().collect();
+        jobs.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        jobs
+    }
+
+    /// Submit a job; the future resolves to the id of the document it produced
+    pub fn submit<F, Fut>(self: &Arc<Self>, kind: &str, job: F) -> Uuid
+    where
+        F: FnOnce(JobHandle) -> Fut + Send + 'static,
+        Fut: Future<Output = CodexResult<Option<Uuid>>> + Send + 'static,
+    {
+        let id = Uuid::new_v4();
+        let now = chrono::Utc::now().to_rfc3339();
+        let info = JobInfo {
+            id,
+            kind: kind.to_string(),
+            status: JobStatus::Queued,
+            progress: 0.0,
+            message: None,
+            document_id: None,
+            created_at: now.clone(),
+            updated_at: now,
+        };
+
+        self.jobs.lock().unwrap().insert(id, info.clone());
+        let _ = self.events.send(info);
+
+        let queue = Arc::clone(self);
+        let kind = kind.to_string();
+        tokio::spawn(async move {
+            // The sender lives in the queue, so waiting cannot fail
+            let _ = queue.paused.subscribe().wait_for(|paused| !paused).await;
+            let _permit = queue.permits.clone().acquire_owned().await;
+            queue.update(id, |job| job.status = JobStatus::Running);
+            info!("Started {} job {}", kind, id);
+
+            let handle = JobHandle { id, queue: Arc::clone(&queue) };
+            match job(handle).await {
+                Ok(document_id) => {
+                    queue.update(id, |job| {
+                        job.status = JobStatus::Completed;
+                        job.progress = 1.0;
+                        job.document_id = document_id;
+                    });
+                    info!("Completed {} job {}", kind, id);
+                }
+                Err(e) => {
+                    warn!("{} job {} failed: {}", kind, id, e);
+                    queue.update(id, |job| {
+                        job.status = JobStatus::Failed;
+                        job.message = Some(e.to_string());
+                    });
+                }
+            }
+        });
+
+        id
+    }
+
+    fn update(&self, id: Uuid, apply: impl FnOnce(&mut JobInfo)) {
+        let snapshot = {
+            let mut jobs = self.jobs.lock().unwrap();
+            let Some(job) = jobs.get_mut(&id) else {
+                return;
+            };
+            apply(job);
+            job.updated_at = chrono::Utc::now().to_rfc3339();
+            job.clone()
+        };
+
+        // Sending only fails when nobody is subscribed
+        let _ = self.events.send(snapshot);
+    }
+}
+
+impl Default for JobQueue {
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CodexError;
+
+    #[tokio::test]
+    async fn test_job_reports_progress_and_completes() {
+        let queue = Arc::new(JobQueue::new(1));
+        let mut events = queue.subscribe();
+        let document_id = Uuid::new_v4();
+
+        let id = queue.submit("test", move |handle| async move {
+            handle.report(0.5, Some("halfway".to_string()));
+            Ok(Some(document_id))
+        });
+
+        let mut saw_progress = false;
+        loop {
+            let event = events.recv().await.unwrap();
+            assert_eq!(event.id, id);
+            if event.progress == 0.5 && event.status == JobStatus::Running {
+                saw_progress = true;
+            }
+            if event.status.is_finished() {
+                assert_eq!(event.status, JobStatus::Completed);
+                assert_eq!(event.document_id, Some(document_id));
+                break;
+            }
+        }
+
+        assert!(saw_progress);
+        assert_eq!(queue.get(id).unwrap().progress, 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_failed_job_records_error() {
+        let queue = Arc::new(JobQueue::default());
+        let mut events = queue.subscribe();
+
+        let id = queue.submit("test", |_| async { Err(CodexError::internal("boom")) });
+
+        loop {
+            let event = events.recv().await.unwrap();
+            if event.status.is_finished() {
+                assert_eq!(event.status, JobStatus::Failed);
+                break;
+            }
+        }
+
+        assert!(queue.get(id).unwrap().message.unwrap().contains("boom"));
+        assert_eq!(queue.list().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_paused_queue_holds_jobs_until_resumed() {
+        let queue = Arc::new(JobQueue::default());
+        queue.set_paused(true);
+        assert!(queue.is_paused());
+
+        let id = queue.submit("test", |_| async { Ok(None) });
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        assert_eq!(queue.get(id).unwrap().status, JobStatus::Queued);
+
+        let mut events = queue.subscribe();
+        queue.set_paused(false);
+        loop {
+            if events.recv().await.unwrap().status.is_finished() {
+                break;
+            }
+        }
+        assert_eq!(queue.get(id).unwrap().status, JobStatus::Completed);
+    }
+}
@@ -0,0 +1,318 @@
+//! Link graph between documents
+//!
+//! Extracts wiki-style `[[Title]]` links and relative markdown links from
+//! document content, resolves them to document ids by title (exact, then
+//! fuzzy) and keeps the `document_links` table in sync as documents are
+//! imported, edited, renamed and deleted.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::CodexResult;
+use crate::db::{DatabaseManager, DocumentQueries, LinkQueries};
+use crate::db::models::{Document, DocumentLink};
+
+/// `[[Title]]`, `[[Title|alias]]` and `[[Title#heading]]`
+static WIKI_LINK_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\[\[([^\[\]|#]+)(?:#[^\[\]|]*)?(?:\|[^\[\]]*)?\]\]").unwrap());
+/// `[text](target)`; the optional `!` prefix marks images, which are skipped
+static MARKDOWN_LINK_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(!?)\[[^\]]*\]\(([^)\s]+)\)").unwrap());
+
+/// `link_type` of the link from a split-off section to the document it came from
+pub const PART_OF_LINK: &str = "part_of";
+
+/// Minimum normalized Levenshtein similarity for a fuzzy title match
+const FUZZY_MATCH_THRESHOLD: f64 = 0.85;
+
+/// A link whose target matched no document
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnresolvedLink {
+    pub source_id: String,
+    pub source_title: String,
+    pub target_title: String,
+}
+
+/// Link syntax
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkType {
+    Wiki,
+    Markdown,
+}
+
+impl LinkType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LinkType::Wiki => "wiki",
+            LinkType::Markdown => "markdown",
+        }
+    }
+}
+
+/// A link found in document content
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtractedLink {
+    /// Title (or title-like file name) of the linked document
+    pub target: String,
+    pub link_type: LinkType,
+}
+
+/// Extract document links from content, de-duplicated case-insensitively
+pub fn extract_links(content: &str) -> Vec<ExtractedLink> {
+    let mut links: Vec<ExtractedLink> = Vec::new();
+    let mut push = |target: String, link_type: LinkType| {
+        let target = target.trim().to_string();
+        if target.is_empty() {
+            return;
+        }
+        let duplicate = links
+            .iter()
+            .any(|l| l.link_type == link_type && l.target.eq_ignore_ascii_case(&target));
+        if !duplicate {
+            links.push(ExtractedLink { target, link_type });
+        }
+    };
+
+    for captures in WIKI_LINK_RE.captures_iter(content) {
+        push(captures[1].to_string(), LinkType::Wiki);
+    }
+
+    for captures in MARKDOWN_LINK_RE.captures_iter(content) {
+        if &captures[1] == "!" {
+            continue;
+        }
+        if let Some(target) = markdown_target_title(&captures[2]) {
+            push(target, LinkType::Markdown);
+        }
+    }
+
+    links
+}
+
+/// Turn a relative markdown link target (`../notes/Other-Note.md`) into a title
+fn markdown_target_title(target: &str) -> Option<String> {
+    if target.contains("://") || target.starts_with('#') || target.starts_with("mailto:") {
+        return None;
+    }
+
+    let path = target.split(['#', '?']).next()?;
+    let file_name = path.rsplit('/').next()?;
+    let file = std::path::Path::new(file_name);
+    let stem = match file.extension().and_then(|e| e.to_str()) {
+        Some(ext) if ["md", "markdown", "txt"].contains(&ext.to_lowercase().as_str()) => {
+            file.file_stem().and_then(|s| s.to_str()).unwrap_or(file_name)
+        }
+        _ => file_name,
+    };
+
+    let title = stem.replace("%20", " ").replace(['-', '_'], " ");
+    (!title.trim().is_empty()).then_some(title)
+}
+
+/// Lowercase, keep alphanumerics and collapse everything else to single spaces
+fn normalize_title(title: &str) -> String {
+    title
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Resolves link text to document ids by title
+#[derive(Debug, Default)]
+pub struct TitleResolver {
+    /// (id, title, normalized title)
+    titles: Vec<(String, String, String)>,
+}
+
+impl TitleResolver {
+    /// Build a resolver over (id, title) pairs
+    pub fn new(titles: Vec<(String, String)>) -> Self {
+        Self {
+            titles: titles
+                .into_iter()
+                .map(|(id, title)| {
+                    let normalized = normalize_title(&title);
+                    (id, title, normalized)
+                })
+                .collect(),
+        }
+    }
+
+    /// Resolve a link target: exact title match first, then fuzzy
+    pub fn resolve(&self, target: &str) -> Option<String> {
+        let target = target.trim();
+        if let Some((id, _, _)) = self.titles.iter().find(|(_, title, _)| title.eq_ignore_ascii_case(target)) {
+            return Some(id.clone());
+        }
+
+        let normalized = normalize_title(target);
+        if normalized.is_empty() {
+            return None;
+        }
+        if let Some((id, _, _)) = self.titles.iter().find(|(_, _, n)| *n == normalized) {
+            return Some(id.clone());
+        }
+
+        self.titles
+            .iter()
+            .map(|(id, _, n)| (id, strsim::normalized_levenshtein(n, &normalized)))
+            .filter(|(_, score)| *score >= FUZZY_MATCH_THRESHOLD)
+            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(id, _)| id.clone())
+    }
+}
+
+/// Maintains the `document_links` table
+#[derive(Debug)]
+pub struct LinkIndex {
+    db: Arc<DatabaseManager>,
+}
+
+impl LinkIndex {
+    /// Create a new link index
+    pub fn new(db: Arc<DatabaseManager>) -> Self {
+        Self { db }
+    }
+
+    /// Sync a document's outgoing links with its content
+    ///
+    /// Only links that were added, removed or now resolve differently are
+    /// written, so editing a document does not rewrite its whole link set.
+    pub async fn update_document_links(&self, document: &Document) -> CodexResult<()> {
+        let pool = self.db.pool();
+        let resolver = self.resolver_excluding(&document.id).await?;
+
+        let mut existing: HashMap<(LinkType, String), DocumentLink> = HashMap::new();
+        for link in LinkQueries::get_outgoing(pool, &document.id).await? {
+            let link_type = match link.link_type.as_str() {
+                "markdown" => LinkType::Markdown,
+                "wiki" => LinkType::Wiki,
+                // Structural links such as part_of are not written in the content
+                _ => continue,
+            };
+            existing.insert((link_type, link.target_title.to_lowercase()), link);
+        }
+
+        let mut added = 0;
+        for extracted in extract_links(&document.content) {
+            let target_id = resolver.resolve(&extracted.target);
+
+            match existing.remove(&(extracted.link_type, extracted.target.to_lowercase())) {
+                Some(link) if link.target_id == target_id => {}
+                Some(link) => LinkQueries::set_target(pool, &link.id, target_id.as_deref()).await?,
+                None => {
+                    let link = DocumentLink::new(
+                        document.id.clone(),
+                        extracted.target,
+                        extracted.link_type.as_str().to_string(),
+                        target_id,
+                    );
+                    LinkQueries::create(pool, &link).await?;
+                    added += 1;
+                }
+            }
+        }
+
+        for stale in existing.values() {
+            LinkQueries::delete(pool, &stale.id).await?;
+        }
+
+        debug!(
+            "Updated links for document {}: {} added, {} removed",
+            document.id,
+            added,
+            existing.len()
+        );
+        Ok(())
+    }
+
+    /// Try to resolve links that did not match any document yet
+    pub async fn resolve_pending(&self) -> CodexResult<usize> {
+        let pool = self.db.pool();
+        let unresolved = LinkQueries::get_unresolved(pool).await?;
+        if unresolved.is_empty() {
+            return Ok(0);
+        }
+
+        let resolver = TitleResolver::new(DocumentQueries::get_titles(pool).await?);
+        let mut resolved = 0;
+
+        for link in unresolved {
+            if let Some(target_id) = resolver.resolve(&link.target_title) {
+                if target_id != link.source_id {
+                    LinkQueries::set_target(pool, &link.id, Some(&target_id)).await?;
+                    resolved += 1;
+                }
+            }
+        }
+
+        Ok(resolved)
+    }
+
+    /// Re-resolve links after a document's title changed
+    pub async fn handle_rename(&self, document_id: &str) -> CodexResult<()> {
+        LinkQueries::unresolve_target(self.db.pool(), document_id).await?;
+        self.resolve_pending().await?;
+        Ok(())
+    }
+
+    /// Drop a deleted document from the link graph
+    pub async fn remove_document(&self, document_id: &str) -> CodexResult<()> {
+        LinkQueries::delete_by_source(self.db.pool(), document_id).await?;
+        LinkQueries::unresolve_target(self.db.pool(), document_id).await?;
+        Ok(())
+    }
+
+    async fn resolver_excluding(&self, document_id: &str) -> CodexResult<TitleResolver> {
+        let titles = DocumentQueries::get_titles(self.db.pool())
+            .await?
+            .into_iter()
+            .filter(|(id, _)| id != document_id)
+            .collect();
+        Ok(TitleResolver::new(titles))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_wiki_and_markdown_links() {
+        let content = "See [[Rust Ownership]] and [[rust ownership|ownership]], plus [[Borrowing#Rules]].\n\
+                       Also [notes](../notes/Lifetime-Basics.md), ![diagram](img/flow.png) \
+                       and [docs](https://doc.rust-lang.org).";
+
+        let links = extract_links(content);
+        let targets: Vec<(&str, LinkType)> = links.iter().map(|l| (l.target.as_str(), l.link_type)).collect();
+
+        assert_eq!(
+            targets,
+            vec![
+                ("Rust Ownership", LinkType::Wiki),
+                ("Borrowing", LinkType::Wiki),
+                ("Lifetime Basics", LinkType::Markdown),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolver_exact_then_fuzzy() {
+        let resolver = TitleResolver::new(vec![
+            ("1".to_string(), "Rust Ownership".to_string()),
+            ("2".to_string(), "Lifetime Basics".to_string()),
+        ]);
+
+        assert_eq!(resolver.resolve("rust ownership").as_deref(), Some("1"));
+        assert_eq!(resolver.resolve("Lifetime-Basics").as_deref(), Some("2"));
+        assert_eq!(resolver.resolve("Rust Ownersip").as_deref(), Some("1"));
+        assert_eq!(resolver.resolve("Async Runtimes"), None);
+    }
+}
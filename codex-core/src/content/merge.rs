@@ -0,0 +1,167 @@
+//! Merging near-duplicate documents
+//!
+//! The primary document is kept and absorbs the secondary: tags are
+//! unioned, content is kept or concatenated per [`MergeStrategy`], and
+//! bookmarks, notes, incoming links and collection memberships move to the
+//! primary before the secondary is soft-deleted.
+
+use serde::{Deserialize, Serialize};
+
+use crate::db::models::{Document, MergedReferences};
+
+/// Which content the merged document keeps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MergeStrategy {
+    /// Keep the primary's content and summary
+    #[default]
+    KeepPrimary,
+    /// Replace the primary's content and summary with the secondary's
+    KeepSecondary,
+    /// Append the secondary's content under its title
+    Concatenate,
+}
+
+/// Fields of the primary a merge may change, journaled for undo
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MergedFields {
+    pub content: String,
+    pub summary: Option<String>,
+    pub category: Option<String>,
+    pub tags: Vec<String>,
+    pub is_favorite: bool,
+}
+
+impl From<&Document> for MergedFields {
+    fn from(document: &Document) -> Self {
+        Self {
+            content: document.content.clone(),
+            summary: document.summary.clone(),
+            category: document.category.clone(),
+            tags: document.get_tags(),
+            is_favorite: document.is_favorite,
+        }
+    }
+}
+
+impl MergedFields {
+    /// Write the fields back onto a document
+    pub fn apply_to(&self, document: &mut Document) {
+        document.content = self.content.clone();
+        document.summary = self.summary.clone();
+        document.category = self.category.clone();
+        document.set_tags(self.tags.clone());
+        document.is_favorite = self.is_favorite;
+    }
+}
+
+/// Journaled state of one document of a merge
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "role", rename_all = "snake_case")]
+pub enum MergeState {
+    /// The kept document, with its fields from before the merge
+    Primary(MergedFields),
+    /// The absorbed document, with the references moved off it
+    Secondary(MergedReferences),
+}
+
+/// Result (or preview) of a merge
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergeResult {
+    /// The primary as it looks after the merge
+    pub merged: Document,
+    pub secondary_id: String,
+    pub moved_bookmarks: usize,
+    pub moved_notes: usize,
+    pub moved_links: usize,
+    pub added_collections: usize,
+    /// Whether nothing was written
+    pub dry_run: bool,
+    /// Journal entry that undoes the merge
+    pub operation_id: Option<String>,
+}
+
+impl MergeResult {
+    pub fn new(merged: Document, secondary_id: String, references: &MergedReferences, dry_run: bool) -> Self {
+        Self {
+            merged,
+            secondary_id,
+            moved_bookmarks: references.bookmarks.len(),
+            moved_notes: references.notes.len(),
+            moved_links: references.links.len(),
+            added_collections: references.collections.len(),
+            dry_run,
+            operation_id: None,
+        }
+    }
+}
+
+/// The primary with the secondary merged into it
+///
+/// Tags are unioned (case-insensitively), the category falls back to the
+/// secondary's and the document is a favorite if either was.
+pub fn merge(primary: &Document, secondary: &Document, strategy: MergeStrategy) -> Document {
+    let mut merged = primary.clone();
+
+    match strategy {
+        MergeStrategy::KeepPrimary => {}
+        MergeStrategy::KeepSecondary => {
+            merged.content = secondary.content.clone();
+            merged.summary = secondary.summary.clone();
+        }
+        MergeStrategy::Concatenate => {
+            merged.content = format!(
+                "{}\n\n---\n\n## {}\n\n{}",
+                primary.content.trim_end(),
+                secondary.title,
+                secondary.content.trim()
+            );
+        }
+    }
+
+    let mut tags = primary.get_tags();
+    for tag in secondary.get_tags() {
+        if !tags.iter().any(|t| t.eq_ignore_ascii_case(&tag)) {
+            tags.push(tag);
+        }
+    }
+    merged.set_tags(tags);
+
+    if merged.category.is_none() {
+        merged.category = secondary.category.clone();
+    }
+    merged.is_favorite = primary.is_favorite || secondary.is_favorite;
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_unions_tags_and_applies_strategy() {
+        let mut primary = Document::new("Notes".to_string(), "First body".to_string(), "text/plain".to_string());
+        primary.set_tags(vec!["Rust".to_string()]);
+        let mut secondary = Document::new("Notes (copy)".to_string(), "Second body".to_string(), "text/plain".to_string());
+        secondary.set_tags(vec!["rust".to_string(), "async".to_string()]);
+        secondary.category = Some("Technology".to_string());
+        secondary.is_favorite = true;
+
+        let merged = merge(&primary, &secondary, MergeStrategy::KeepPrimary);
+        assert_eq!(merged.id, primary.id);
+        assert_eq!(merged.content, "First body");
+        assert_eq!(merged.get_tags(), vec!["Rust".to_string(), "async".to_string()]);
+        assert_eq!(merged.category.as_deref(), Some("Technology"));
+        assert!(merged.is_favorite);
+
+        assert_eq!(merge(&primary, &secondary, MergeStrategy::KeepSecondary).content, "Second body");
+        let concatenated = merge(&primary, &secondary, MergeStrategy::Concatenate).content;
+        assert!(concatenated.starts_with("First body"));
+        assert!(concatenated.ends_with("## Notes (copy)\n\nSecond body"));
+
+        let before = MergedFields::from(&primary);
+        let mut restored = merged.clone();
+        before.apply_to(&mut restored);
+        assert_eq!(MergedFields::from(&restored), before);
+    }
+}
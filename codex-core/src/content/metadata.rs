@@ -0,0 +1,113 @@
+//! Derived document metadata
+//!
+//! Reading time is a plain word count divided by a per-language reading
+//! speed. Scripts written without spaces (Chinese, Japanese, Korean) count
+//! each character as a word, with matching characters-per-minute speeds.
+
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+
+/// Reading speed used for languages without a configured speed
+pub const DEFAULT_WORDS_PER_MINUTE: u32 = 200;
+
+/// Default reading speeds (words, or characters for CJK, per minute)
+pub fn default_reading_speeds() -> HashMap<String, u32> {
+    [
+        ("en", 230),
+        ("de", 180),
+        ("es", 220),
+        ("fr", 195),
+        ("it", 190),
+        ("nl", 200),
+        ("pt", 180),
+        ("ru", 185),
+        ("zh", 255),
+        ("ja", 350),
+        ("ko", 250),
+    ]
+    .into_iter()
+    .map(|(language, wpm)| (language.to_string(), wpm))
+    .collect()
+}
+
+/// Which documents a metadata recompute touches
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataFilter {
+    /// Only documents where a selected field is missing
+    MissingOnly,
+    /// Every document
+    All,
+}
+
+/// Derived metadata fields that can be recomputed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataField {
+    ReadingTime,
+    Difficulty,
+}
+
+/// Count words, treating each CJK character as a word
+pub fn count_words(text: &str) -> usize {
+    text.split_whitespace()
+        .map(|token| {
+            let cjk = token.chars().filter(|c| is_cjk(*c)).count();
+            let has_other = token.chars().any(|c| c.is_alphanumeric() && !is_cjk(c));
+            cjk + usize::from(has_other)
+        })
+        .sum()
+}
+
+/// Reading speed for a language code such as "en" or "pt-BR"
+pub fn words_per_minute(speeds: &HashMap<String, u32>, language: &str) -> u32 {
+    let language = language.to_lowercase();
+    let base = language.split(['-', '_']).next().unwrap_or_default();
+
+    speeds
+        .get(&language)
+        .or_else(|| speeds.get(base))
+        .copied()
+        .filter(|wpm| *wpm > 0)
+        .unwrap_or(DEFAULT_WORDS_PER_MINUTE)
+}
+
+/// Estimated reading time in whole minutes (at least one)
+pub fn estimate_reading_time(text: &str, words_per_minute: u32) -> i64 {
+    let minutes = count_words(text) as f64 / words_per_minute.max(1) as f64;
+    (minutes.ceil() as i64).max(1)
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30FF}'   // Hiragana, Katakana
+        | '\u{3400}'..='\u{4DBF}' // CJK Extension A
+        | '\u{4E00}'..='\u{9FFF}' // CJK Unified Ideographs
+        | '\u{AC00}'..='\u{D7AF}' // Hangul syllables
+        | '\u{F900}'..='\u{FAFF}' // CJK Compatibility Ideographs
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_words_mixed_scripts() {
+        assert_eq!(count_words("The quick brown fox"), 4);
+        assert_eq!(count_words("学习 Rust 编程"), 5);
+        assert_eq!(count_words("  -- "), 0);
+    }
+
+    #[test]
+    fn test_reading_time_per_language() {
+        let speeds = default_reading_speeds();
+        assert_eq!(words_per_minute(&speeds, "pt-BR"), 180);
+        assert_eq!(words_per_minute(&speeds, "xx"), DEFAULT_WORDS_PER_MINUTE);
+
+        let text = "word ".repeat(460);
+        assert_eq!(estimate_reading_time(&text, words_per_minute(&speeds, "en")), 2);
+        assert_eq!(estimate_reading_time(&text, words_per_minute(&speeds, "de")), 3);
+        assert_eq!(estimate_reading_time("", 200), 1);
+    }
+}
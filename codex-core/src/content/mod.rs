@@ -3,24 +3,114 @@
 //! This module handles document parsing, indexing, and search operations
 //! for the knowledge repository.
 
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 use std::path::Path;
 use anyhow::Result;
-use tracing::{info, warn, error};
+use tracing::{debug, info, instrument, warn, error};
 
-use crate::{CodexError, CodexResult};
+use crate::{metrics, CodexError, CodexResult};
 use crate::config::ContentConfig;
-use crate::db::DatabaseManager;
-use crate::ai::AiEngine;
+use crate::db::{AttachmentQueries, DatabaseManager, MaintenanceAction, ReadingSessionQueries, SearchDictionaryQueries, VaultExportManifest};
+use crate::db::models::{FailedImport, MetadataValue, Operation, OperationState, ReadingSession, SearchDictionaries};
+use crate::ai::{AiEngine, AiOperation, SummaryProgress};
 
 pub mod parser;
 pub mod indexer;
 pub mod search;
+pub mod ocr;
+pub mod transcribe;
+pub mod jobs;
+pub mod code;
+pub mod notebook;
+pub mod tabular;
+pub mod links;
+pub mod templates;
+pub mod find;
+pub mod metadata;
+pub mod export;
+pub mod digest;
+pub mod import_plan;
+pub mod bulk;
+pub mod rules;
+pub mod capture;
+pub mod merge;
+pub mod split;
+pub mod fuzzy;
+pub mod feed;
+pub mod checkpoint;
+pub mod vault_import;
+pub mod bookmarks;
+pub mod rss;
+pub mod bibtex;
+pub mod annotations;
+pub mod deep_link;
+pub mod attachments;
+pub mod preview;
+pub mod quick_find;
+pub mod reading;
+pub mod triage;
+pub mod focus_pack;
+pub mod frontmatter;
+pub mod fields;
 
 pub use parser::*;
 pub use indexer::*;
 pub use search::*;
 
+use ocr::OcrExtractor;
+use transcribe::Transcriber;
+use jobs::{JobHandle, JobInfo, JobQueue, JobStatus};
+use code::CodeLanguage;
+use links::LinkIndex;
+use metadata::{MetadataField, MetadataFilter};
+use import_plan::{BulkImportPlan, FileImportPlan, ImportDecision};
+use bulk::{BulkChanges, BulkUpdateSummary, OrganizationState};
+use rules::{AutomationRule, RuleOutcome, RuleSubject, RuleTestResult};
+use capture::CaptureResult;
+use merge::{MergeResult, MergeState, MergeStrategy, MergedFields};
+use split::{OriginalHandling, SplitResult, SplitStrategy};
+use feed::HomeFeed;
+use checkpoint::Checkpoint;
+use links::UnresolvedLink;
+use vault_import::{VaultFiles, VaultImportProgress, VaultImportStage};
+use rss::{FeedManager, FeedNewItems, FeedRefreshResult, FetchOutcome};
+use attachments::AttachmentStore;
+use preview::{PreviewGenerator, Thumbnail, ThumbnailPlaceholder, ThumbnailSize};
+use quick_find::{QuickFindHit, TitleIndex};
+use reading::{GoalProgress, ReadingStreak};
+use triage::{TriageDecision, TriageResult, TriageSuggestion, TriageThresholds};
+use focus_pack::{DifficultyRange, FocusPack};
+
+/// Job kind used for background audio imports
+const AUDIO_IMPORT_JOB: &str = "audio_import";
+/// Job kind used for adding AI metadata to quick captures
+const CAPTURE_ENRICHMENT_JOB: &str = "capture_enrichment";
+/// Most recent documents compared against a capture when detecting repeats
+const CAPTURE_DEDUP_SCAN_LIMIT: i64 = 200;
+/// Job kind used for metadata backfills
+const METADATA_RECOMPUTE_JOB: &str = "metadata_recompute";
+/// Job kind used for reindexing documents flagged by the index health report
+const INDEX_REPAIR_JOB: &str = "index_repair";
+/// Job kind used for compressing bodies stored before compression was enabled
+const CONTENT_COMPRESSION_JOB: &str = "content_compression";
+/// Job kind used for rendering document thumbnails
+const THUMBNAIL_JOB: &str = "thumbnail";
+/// Job kind used for suggesting inbox triage
+const TRIAGE_JOB: &str = "triage";
+/// Rows compressed per batch by the content compression job
+const COMPRESSION_BATCH_SIZE: i64 = 50;
+/// How often the digest schedule checks whether a digest is due
+const DIGEST_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(3600);
+/// Setting holding the time of the last scheduled digest
+const DIGEST_LAST_RUN_SETTING: &str = "digest.last_run";
+/// Most entries kept in the operations journal
+const OPERATION_JOURNAL_MAX_ENTRIES: i64 = 1000;
+/// Journal entries older than this are pruned
+const OPERATION_JOURNAL_MAX_AGE_DAYS: i64 = 90;
+/// Documents larger than this (in bytes) are searched on a blocking thread
+const BLOCKING_FIND_THRESHOLD: usize = 256 * 1024;
+
 /// Content manager handling all content operations
 #[derive(Debug)]
 pub struct ContentManager {
@@ -29,6 +119,15 @@ pub struct ContentManager {
     parser: Arc<ContentParser>,
     indexer: Arc<ContentIndexer>,
     search: Arc<SearchEngine>,
+    transcriber: Arc<Transcriber>,
+    jobs: Arc<JobQueue>,
+    links: Arc<LinkIndex>,
+    feeds: Arc<FeedManager>,
+    attachments: Arc<AttachmentStore>,
+    previews: Arc<PreviewGenerator>,
+    titles: Arc<TitleIndex>,
+    /// Ids of checkpointed operations running in this session
+    active_operations: Arc<std::sync::Mutex<std::collections::HashSet<String>>>,
     config: ContentConfig,
 }
 
@@ -57,6 +156,30 @@ impl ContentManager {
             config,
         ).await?);
 
+        let transcriber = Arc::new(Transcriber::new(
+            config.whisper_model_path.clone(),
+            config.transcription_language.clone(),
+        ));
+        let jobs = Arc::new(JobQueue::default());
+        Self::invalidate_search_on_jobs(&jobs, Arc::clone(&search));
+        let links = Arc::new(LinkIndex::new(Arc::clone(&db)));
+        let feeds = Arc::new(FeedManager::new(Arc::clone(&db))?);
+        let attachments = Arc::new(AttachmentStore::new(&config.content_dir));
+        let previews = Arc::new(PreviewGenerator::new(&config.content_dir));
+        let titles = Arc::new(TitleIndex::new());
+        Self::load_titles(&db, &jobs, Arc::clone(&titles));
+
+        // Safe mode leaves stored content as it is
+        if db.compression().enabled
+            && !ai.is_disabled()
+            && crate::db::DocumentQueries::count_uncompressed(db.pool()).await? > 0
+        {
+            let db = Arc::clone(&db);
+            jobs.submit(CONTENT_COMPRESSION_JOB, move |handle| async move {
+                Self::compress_existing_content_job(db, handle).await.map(|_| None)
+            });
+        }
+
         info!("Content manager initialized successfully");
 
         Ok(Self {
@@ -65,18 +188,109 @@ impl ContentManager {
             parser,
             indexer,
             search,
+            transcriber,
+            jobs,
+            links,
+            feeds,
+            attachments,
+            previews,
+            titles,
+            active_operations: Arc::new(std::sync::Mutex::new(std::collections::HashSet::new())),
             config: config.clone(),
         })
     }
 
+    /// Drop cached searches whenever a background job reports, since
+    /// imports, enrichment and metadata jobs write documents as they go
+    ///
+    /// Thumbnail jobs only write files and are ignored.
+    fn invalidate_search_on_jobs(jobs: &JobQueue, search: Arc<SearchEngine>) {
+        let mut updates = jobs.subscribe();
+        tokio::spawn(async move {
+            loop {
+                match updates.recv().await {
+                    Ok(job) if job.kind == THUMBNAIL_JOB => {}
+                    Ok(_) | Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => search.invalidate_cache(),
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
+    /// Load the quick switcher's title index in the background and keep
+    /// it current with documents written by background jobs
+    fn load_titles(db: &Arc<DatabaseManager>, jobs: &JobQueue, titles: Arc<TitleIndex>) {
+        let mut updates = jobs.subscribe();
+        let db = Arc::clone(db);
+        tokio::spawn(async move {
+            match titles.load(db.pool()).await {
+                Ok(count) => debug!("Loaded {} titles for quick find", count),
+                Err(e) => error!("Failed to load titles for quick find: {}", e),
+            }
+
+            loop {
+                match updates.recv().await {
+                    Ok(job) if job.status == JobStatus::Completed && job.kind != THUMBNAIL_JOB => {
+                        if let Some(document_id) = job.document_id {
+                            if let Err(e) = titles.refresh(db.pool(), &[document_id.to_string()]).await {
+                                warn!("Failed to refresh title of {}: {}", document_id, e);
+                            }
+                        }
+                    }
+                    Ok(_) | Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
+    /// Background body of the one-time compression of existing large bodies
+    async fn compress_existing_content_job(db: Arc<DatabaseManager>, handle: JobHandle) -> CodexResult<usize> {
+        let total = crate::db::DocumentQueries::count_uncompressed(db.pool()).await?.max(1) as usize;
+        let mut processed = 0;
+        let mut last_rowid = 0;
+        handle.report(0.0, Some(format!("Compressing {} documents", total)));
+
+        loop {
+            let (count, rowid) = crate::db::DocumentQueries::compress_uncompressed_batch(
+                db.pool(),
+                last_rowid,
+                COMPRESSION_BATCH_SIZE,
+                db.compression(),
+            )
+            .await?;
+            if count == 0 {
+                break;
+            }
+
+            processed += count;
+            last_rowid = rowid;
+            handle.report(
+                processed.min(total) as f32 / total as f32,
+                Some(format!("{}/{} documents", processed, total)),
+            );
+        }
+
+        info!("Content compression processed {} documents", processed);
+        Ok(processed)
+    }
+
     /// Import a document from file
+    #[instrument(skip_all, fields(path = ?file_path.as_ref()))]
     pub async fn import_document<P: AsRef<Path>>(&self, file_path: P) -> CodexResult<uuid::Uuid> {
+        let _timer = metrics::timer(metrics::IMPORT);
         let file_path = file_path.as_ref();
         info!("Importing document: {:?}", file_path);
 
         // Validate file
         self.validate_file(file_path).await?;
 
+        if Self::is_audio_file(file_path) {
+            return Err(CodexError::validation(
+                "Audio files are transcribed in the background; use import_audio instead",
+            ));
+        }
+
         // Parse document
         let parsed_doc = self.parser.parse_file(file_path).await?;
 
@@ -101,267 +315,3303 @@ impl ContentManager {
         document.language = parsed_doc.language;
         document.file_size = Some(parsed_doc.file_size as i64);
         document.file_hash = Some(parsed_doc.file_hash);
+        document.source = parsed_doc.source;
+        // Tags taken from the file's own structure replace AI tagging;
+        // frontmatter tags are merged with the AI's
+        let file_tags = parsed_doc.tags.is_some();
+        if let Some(tags) = parsed_doc.tags {
+            document.set_tags(tags);
+        }
+        if let Some(frontmatter) = &parsed_doc.frontmatter {
+            frontmatter.apply_to(&mut document, self.config.respect_source_dates);
+        }
+
+        let rules = self.import_rules(&document, file_path).await;
 
         // Generate AI-enhanced metadata
-        if let Ok(summary) = self.ai.summarize(&document.content, Some(200)).await {
-            document.summary = Some(summary);
+        if !rules.actions.skip_ai {
+            if let Ok(summary) = Self::generate_summary(&self.ai, &document).await {
+                document.summary = Some(summary);
+            }
         }
 
-        if let Ok(tags) = self.ai.generate_tags(&document.content, Some(10)).await {
-            document.set_tags(tags);
+        // Flag unreliable OCR so readers know to check the original image
+        if let Some(confidence) = parsed_doc.ocr_confidence {
+            if self.parser.is_low_confidence(confidence) {
+                warn!("Low OCR confidence ({:.2}) for file: {:?}", confidence, file_path);
+                let notice = format!(
+                    "[Low-confidence OCR: {:.0}% - verify against the original image]",
+                    confidence * 100.0
+                );
+                document.summary = Some(match document.summary.take() {
+                    Some(summary) => format!("{} {}", notice, summary),
+                    None => notice,
+                });
+            }
         }
 
-        if let Ok(difficulty) = self.ai.assess_difficulty(&document.content).await {
-            document.difficulty_level = Some(difficulty.into());
+        if !Self::apply_code_metadata(&mut document) && !rules.actions.skip_ai {
+            if !file_tags {
+                if let Ok(generated) = Self::generate_tags(&self.ai, &document).await {
+                    let mut tags = document.get_tags();
+                    for tag in generated {
+                        if !tags.contains(&tag) {
+                            tags.push(tag);
+                        }
+                    }
+                    document.set_tags(tags);
+                }
+            }
+
+            if let Ok(difficulty) = Self::assess_difficulty(&self.ai, &document).await {
+                document.difficulty_level = Some(difficulty.into());
+            }
         }
 
-        if let Ok(reading_time) = self.ai.estimate_reading_time(&document.content).await {
-            document.reading_time = Some(reading_time.into());
+        document.reading_time = Some(Self::reading_time(&self.config.reading_speeds, &document));
+        rules.apply(&mut document);
+
+        // Save and index the document
+        self.indexer.create_indexed(&document).await?;
+        self.search.invalidate_cache();
+        self.titles.upsert(&document);
+
+        if self.config.attachments.copy_originals {
+            Self::keep_original(&self.db, &self.attachments, &document.id, file_path).await;
         }
 
-        // Save to database
-        crate::db::DocumentQueries::create(self.db.pool(), &document).await?;
+        if let Some(structure) = parsed_doc.structure {
+            crate::db::StructureQueries::upsert(
+                self.db.pool(),
+                &document.id,
+                structure.kind(),
+                &serde_json::to_string(&structure)?,
+            )
+            .await?;
+        }
 
-        // Index the document
-        self.indexer.index_document(&document).await?;
+        self.refresh_links(&document).await;
 
         info!("Document imported successfully: {}", document.id);
         Ok(uuid::Uuid::parse_str(&document.id).unwrap_or_default())
     }
 
-    /// Import content from text
-    pub async fn import_text_content(
-        &self,
-        title: String,
-        content: String,
-        content_type: Option<String>,
-    ) -> CodexResult<uuid::Uuid> {
-        info!("Importing text content: {}", title);
-
-        // Create document model
-        let mut document = crate::db::models::Document::new(
-            title,
-            content,
-            content_type.unwrap_or_else(|| "text/plain".to_string()),
-        );
+    /// Run the enabled automation rules against a document being imported
+    ///
+    /// Rules that cannot be loaded are logged and ignored so imports still succeed.
+    async fn import_rules(&self, document: &crate::db::models::Document, file_path: &Path) -> RuleOutcome {
+        let rules = match self.list_rules().await {
+            Ok(rules) => rules.into_iter().filter(|rule| rule.enabled).collect::<Vec<_>>(),
+            Err(e) => {
+                warn!("Failed to load automation rules: {}", e);
+                return RuleOutcome::default();
+            }
+        };
+        if rules.is_empty() {
+            return RuleOutcome::default();
+        }
 
-        // Generate AI-enhanced metadata
-        if let Ok(summary) = self.ai.summarize(&document.content, Some(200)).await {
-            document.summary = Some(summary);
+        let source_path = std::fs::canonicalize(file_path).unwrap_or_else(|_| file_path.to_path_buf());
+        let outcome = RuleOutcome::evaluate(&rules, &RuleSubject::from_document(document, Some(&source_path)));
+        if !outcome.matched_rules.is_empty() {
+            debug!("Automation rules {:?} matched {:?}", outcome.matched_rules, file_path);
         }
+        outcome
+    }
 
-        if let Ok(tags) = self.ai.generate_tags(&document.content, Some(10)).await {
-            document.set_tags(tags);
+    /// All automation rules, in the order they run
+    pub async fn list_rules(&self) -> CodexResult<Vec<AutomationRule>> {
+        crate::db::RuleQueries::get_all(self.db.pool())
+            .await?
+            .into_iter()
+            .map(AutomationRule::try_from)
+            .collect()
+    }
+
+    /// Store a new automation rule
+    pub async fn create_rule(&self, rule: AutomationRule) -> CodexResult<AutomationRule> {
+        rule.validate()?;
+        crate::db::RuleQueries::create(self.db.pool(), &rule.to_row()?).await?;
+        info!("Created automation rule {} ({})", rule.name, rule.id);
+        Ok(rule)
+    }
+
+    /// Replace an automation rule
+    pub async fn update_rule(&self, rule: AutomationRule) -> CodexResult<()> {
+        rule.validate()?;
+        if !crate::db::RuleQueries::update(self.db.pool(), &rule.to_row()?).await? {
+            return Err(CodexError::not_found("Rule not found"));
         }
+        Ok(())
+    }
 
-        if let Ok(difficulty) = self.ai.assess_difficulty(&document.content).await {
-            document.difficulty_level = Some(difficulty.into());
+    /// Delete an automation rule
+    pub async fn delete_rule(&self, rule_id: &str) -> CodexResult<()> {
+        if !crate::db::RuleQueries::delete(self.db.pool(), rule_id).await? {
+            return Err(CodexError::not_found("Rule not found"));
         }
+        Ok(())
+    }
+
+    /// Preview a rule against an existing document without changing it
+    ///
+    /// The document's recorded source, if any, stands in for the import path.
+    pub async fn test_rule(&self, rule: &AutomationRule, sample_document_id: uuid::Uuid) -> CodexResult<RuleTestResult> {
+        let mut document = crate::db::DocumentQueries::get_by_id(self.db.pool(), &sample_document_id.to_string())
+            .await?
+            .ok_or_else(|| CodexError::not_found("Document not found"))?;
 
-        if let Ok(reading_time) = self.ai.estimate_reading_time(&document.content).await {
-            document.reading_time = Some(reading_time.into());
+        let source_path = document.source.as_deref().map(Path::new);
+        let conditions = rule.conditions.evaluate(&RuleSubject::from_document(&document, source_path))?;
+        let matched = conditions.iter().all(|c| c.matched);
+        if matched {
+            RuleOutcome { matched_rules: vec![rule.id.clone()], actions: rule.actions.clone() }.apply(&mut document);
         }
 
-        // Save to database
-        crate::db::DocumentQueries::create(self.db.pool(), &document).await?;
+        Ok(RuleTestResult {
+            matched,
+            conditions,
+            resulting_category: document.category.clone(),
+            resulting_tags: document.get_tags(),
+        })
+    }
+
+    /// Queue an audio file for transcription and import
+    ///
+    /// Returns the id of the background job; progress is published to
+    /// [`ContentManager::subscribe_jobs`] and the finished job carries the id
+    /// of the created document.
+    pub async fn import_audio<P: AsRef<Path>>(&self, file_path: P) -> CodexResult<uuid::Uuid> {
+        let file_path = file_path.as_ref().to_path_buf();
+        info!("Queueing audio import: {:?}", file_path);
 
-        // Index the document
-        self.indexer.index_document(&document).await?;
+        self.validate_file(&file_path).await?;
 
-        info!("Text content imported successfully: {}", document.id);
-        Ok(uuid::Uuid::parse_str(&document.id).unwrap_or_default())
+        if !Self::is_audio_file(&file_path) {
+            return Err(CodexError::validation(format!(
+                "Not a supported audio file: {}",
+                file_path.display()
+            )));
+        }
+        if !Transcriber::is_available() {
+            return Err(CodexError::validation(
+                "Audio transcription is not enabled in this build",
+            ));
+        }
+
+        let bytes = tokio::fs::read(&file_path).await?;
+        let file_hash = ContentParser::hash_bytes(&bytes);
+        if let Some(existing_doc) = self.check_for_duplicate(&file_hash).await? {
+            return Err(CodexError::validation(format!(
+                "Document with identical content already exists: {} ({})",
+                existing_doc.title, existing_doc.id
+            )));
+        }
+
+        let ai = Arc::clone(&self.ai);
+        let indexer = Arc::clone(&self.indexer);
+        let transcriber = Arc::clone(&self.transcriber);
+        let file_size = bytes.len() as u64;
+        let keep_original = self
+            .config
+            .attachments
+            .copy_originals
+            .then(|| (Arc::clone(&self.db), Arc::clone(&self.attachments)));
+
+        let job_id = self.jobs.submit(AUDIO_IMPORT_JOB, move |handle| async move {
+            Self::transcribe_and_store(ai, indexer, transcriber, keep_original, file_path, file_size, file_hash, handle)
+                .await
+                .map(Some)
+        });
+
+        Ok(job_id)
     }
 
-    /// Update document content
-    pub async fn update_document(&self, document_id: uuid::Uuid, new_content: String) -> CodexResult<()> {
-        info!("Updating document: {}", document_id);
+    /// Background body of an audio import job
+    #[allow(clippy::too_many_arguments)]
+    async fn transcribe_and_store(
+        ai: Arc<AiEngine>,
+        indexer: Arc<ContentIndexer>,
+        transcriber: Arc<Transcriber>,
+        keep_original: Option<(Arc<DatabaseManager>, Arc<AttachmentStore>)>,
+        file_path: std::path::PathBuf,
+        file_size: u64,
+        file_hash: String,
+        handle: JobHandle,
+    ) -> CodexResult<uuid::Uuid> {
+        handle.report(0.0, Some("Transcribing audio".to_string()));
+
+        // Transcription covers the first 80% of the job, metadata and indexing the rest
+        let progress = handle.clone();
+        let transcript = transcriber
+            .transcribe(&file_path, move |p| progress.report(p * 0.8, None))
+            .await?;
+
+        let content = transcript.to_content();
+        if content.trim().is_empty() {
+            return Err(CodexError::content_processing(format!(
+                "No speech could be recognized in audio: {}",
+                file_path.display()
+            )));
+        }
 
-        // Get existing document
-        let mut document = crate::db::DocumentQueries::get_by_id(self.db.pool(), &document_id.to_string())
-            .await?
-            .ok_or_else(|| CodexError::not_found("Document not found"))?;
+        let extension = file_path.extension().and_then(|e| e.to_str()).unwrap_or("wav");
+        let title = file_path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("Untitled")
+            .to_string();
 
-        // Update content
-        document.content = new_content;
-        document.updated_at = chrono::Utc::now().to_rfc3339();
+        let mut document = crate::db::models::Document::new(
+            title,
+            content,
+            Transcriber::content_type(extension).to_string(),
+        );
+        document.file_size = Some(file_size as i64);
+        document.file_hash = Some(file_hash);
+        document.source = Some(
+            std::fs::canonicalize(&file_path)
+                .unwrap_or_else(|_| file_path.clone())
+                .display()
+                .to_string(),
+        );
+        // For recordings the listening time is the reading time
+        document.reading_time = Some(transcript.duration_minutes());
 
-        // Regenerate AI metadata
-        if let Ok(summary) = self.ai.summarize(&document.content, Some(200)).await {
+        handle.report(0.8, Some("Generating metadata".to_string()));
+
+        if let Ok(summary) = Self::generate_summary(&ai, &document).await {
             document.summary = Some(summary);
         }
 
-        if let Ok(tags) = self.ai.generate_tags(&document.content, Some(10)).await {
+        if let Ok(tags) = Self::generate_tags(&ai, &document).await {
             document.set_tags(tags);
         }
 
-        if let Ok(difficulty) = self.ai.assess_difficulty(&document.content).await {
+        if let Ok(difficulty) = Self::assess_difficulty(&ai, &document).await {
             document.difficulty_level = Some(difficulty.into());
         }
 
-        if let Ok(reading_time) = self.ai.estimate_reading_time(&document.content).await {
-            document.reading_time = Some(reading_time.into());
-        }
-
-        // Update in database
-        crate::db::DocumentQueries::update(self.db.pool(), &document).await?;
+        handle.report(0.9, Some("Indexing transcript".to_string()));
+        indexer.create_indexed(&document).await?;
 
-        // Re-index the document
-        self.indexer.reindex_document(&document).await?;
+        if let Some((db, attachments)) = keep_original {
+            Self::keep_original(&db, &attachments, &document.id, &file_path).await;
+        }
 
-        info!("Document updated successfully: {}", document_id);
-        Ok(())
+        info!("Audio imported successfully: {}", document.id);
+        Ok(uuid::Uuid::parse_str(&document.id).unwrap_or_default())
     }
 
-    /// Delete document
-    pub async fn delete_document(&self, document_id: uuid::Uuid) -> CodexResult<()> {
-        info!("Deleting document: {}", document_id);
+    /// Backfill derived metadata through the background job queue
+    ///
+    /// Returns the job id; progress is published to
+    /// [`ContentManager::subscribe_jobs`]. Recordings keep their listening
+    /// time as reading time.
+    pub fn recompute_metadata(&self, filter: MetadataFilter, fields: &[MetadataField]) -> CodexResult<uuid::Uuid> {
+        if fields.is_empty() {
+            return Err(CodexError::validation("No metadata fields selected"));
+        }
+        if self.ai.is_disabled() {
+            return Err(CodexError::validation("Metadata cannot be recomputed in safe mode"));
+        }
 
-        // Remove from search index
-        self.indexer.remove_document(document_id).await?;
+        let db = Arc::clone(&self.db);
+        let ai = Arc::clone(&self.ai);
+        let speeds = self.config.reading_speeds.clone();
+        let fields = fields.to_vec();
 
-        // Soft delete from database
-        crate::db::DocumentQueries::delete(self.db.pool(), &document_id.to_string()).await?;
+        let job_id = self.jobs.submit(METADATA_RECOMPUTE_JOB, move |handle| async move {
+            Self::recompute_metadata_job(db, ai, speeds, filter, fields, handle)
+                .await
+                .map(|_| None)
+        });
 
-        info!("Document deleted successfully: {}", document_id);
-        Ok(())
+        Ok(job_id)
     }
 
-    /// Search documents
-    pub async fn search_documents(&self, query: &str, options: SearchOptions) -> CodexResult<SearchResults> {
-        self.search.search(query, options).await
-    }
+    /// Background body of a metadata recompute job; returns the number of updated documents
+    async fn recompute_metadata_job(
+        db: Arc<DatabaseManager>,
+        ai: Arc<AiEngine>,
+        speeds: HashMap<String, u32>,
+        filter: MetadataFilter,
+        fields: Vec<MetadataField>,
+        handle: JobHandle,
+    ) -> CodexResult<usize> {
+        let wants_reading_time = fields.contains(&MetadataField::ReadingTime);
+        let wants_difficulty = fields.contains(&MetadataField::Difficulty);
+
+        let documents: Vec<_> = crate::db::DocumentQueries::get_all(db.pool())
+            .await?
+            .into_iter()
+            .filter(|doc| match filter {
+                MetadataFilter::All => true,
+                MetadataFilter::MissingOnly => {
+                    (wants_reading_time && doc.reading_time.is_none())
+                        || (wants_difficulty && doc.difficulty_level.is_none())
+                }
+            })
+            .collect();
+
+        let total = documents.len();
+        let mut updated = 0;
+        handle.report(0.0, Some(format!("Recomputing metadata for {} documents", total)));
+
+        for (i, document) in documents.into_iter().enumerate() {
+            let recompute = |current: Option<i64>| match filter {
+                MetadataFilter::All => true,
+                MetadataFilter::MissingOnly => current.is_none(),
+            };
+
+            let reading_time = (wants_reading_time
+                && recompute(document.reading_time)
+                && !document.content_type.starts_with("audio/"))
+                .then(|| Self::reading_time(&speeds, &document));
+
+            let difficulty = if wants_difficulty && recompute(document.difficulty_level) {
+                match CodeLanguage::from_content_type(&document.content_type) {
+                    Some(language) => Some(code::assess_code_difficulty(&document.content, language)),
+                    None => match Self::assess_difficulty(&ai, &document).await {
+                        Ok(difficulty) => Some(difficulty.into()),
+                        Err(e) => {
+                            warn!("Could not assess difficulty for document {}: {}", document.id, e);
+                            None
+                        }
+                    },
+                }
+            } else {
+                None
+            };
+
+            if reading_time.is_some() || difficulty.is_some() {
+                crate::db::DocumentQueries::set_derived_metadata(db.pool(), &document.id, reading_time, difficulty)
+                    .await?;
+                updated += 1;
+            }
 
-    /// Get document by ID
-    pub async fn get_document(&self, document_id: uuid::Uuid) -> CodexResult<Option<crate::db::models::Document>> {
-        let document = crate::db::DocumentQueries::get_by_id(self.db.pool(), &document_id.to_string()).await?;
-        
-        // Update access statistics
-        if document.is_some() {
-            let _ = crate::db::DocumentQueries::update_access(self.db.pool(), &document_id.to_string()).await;
+            handle.report(
+                (i + 1) as f32 / total as f32,
+                Some(format!("{}/{} documents", i + 1, total)),
+            );
         }
 
-        Ok(document)
+        handle.report(1.0, Some(format!("Updated {} of {} documents", updated, total)));
+        info!("Metadata recompute updated {} of {} documents", updated, total);
+        Ok(updated)
     }
 
-    /// Get recent documents
-    pub async fn get_recent_documents(&self, limit: i64) -> CodexResult<Vec<crate::db::models::Document>> {
-        crate::db::DocumentQueries::get_recent(self.db.pool(), limit).await
+    /// Reading time of a document at its language's reading speed
+    fn reading_time(speeds: &HashMap<String, u32>, document: &crate::db::models::Document) -> i64 {
+        metadata::estimate_reading_time(
+            &document.content,
+            metadata::words_per_minute(speeds, &document.language),
+        )
     }
 
-    /// Get documents by category
-    pub async fn get_documents_by_category(
-        &self,
-        category: &str,
-        limit: i64,
-        offset: i64,
-    ) -> CodexResult<Vec<crate::db::models::Document>> {
-        crate::db::DocumentQueries::get_by_category(self.db.pool(), category, limit, offset).await
+    /// Subscribe to background job progress events
+    pub fn subscribe_jobs(&self) -> tokio::sync::broadcast::Receiver<JobInfo> {
+        self.jobs.subscribe()
     }
 
-    /// Get favorite documents
-    pub async fn get_favorite_documents(&self, limit: i64) -> CodexResult<Vec<crate::db::models::Document>> {
-        crate::db::DocumentQueries::get_favorites(self.db.pool(), limit).await
+    /// Get the state of a background job
+    pub fn get_job(&self, job_id: uuid::Uuid) -> Option<JobInfo> {
+        self.jobs.get(job_id)
     }
 
-    /// Toggle document favorite status
-    pub async fn toggle_favorite(&self, document_id: uuid::Uuid) -> CodexResult<bool> {
-        let mut document = crate::db::DocumentQueries::get_by_id(self.db.pool(), &document_id.to_string())
-            .await?
-            .ok_or_else(|| CodexError::not_found("Document not found"))?;
+    /// List background jobs, newest first
+    pub fn list_jobs(&self) -> Vec<JobInfo> {
+        self.jobs.list()
+    }
 
-        document.is_favorite = !document.is_favorite;
-        document.updated_at = chrono::Utc::now().to_rfc3339();
+    /// Hold queued background jobs, e.g. on battery, or let them run again
+    pub fn set_background_jobs_paused(&self, paused: bool) {
+        self.jobs.set_paused(paused);
+    }
 
-        crate::db::DocumentQueries::update(self.db.pool(), &document).await?;
+    /// Wait for a background job to finish and return the document it produced
+    pub async fn wait_for_job(&self, job_id: uuid::Uuid) -> CodexResult<Option<uuid::Uuid>> {
+        let mut events = self.jobs.subscribe();
+
+        loop {
+            // Check after subscribing so a job finishing in between is not missed
+            let job = self
+                .jobs
+                .get(job_id)
+                .ok_or_else(|| CodexError::not_found(format!("Job not found: {}", job_id)))?;
+
+            if job.status.is_finished() {
+                return match job.status {
+                    jobs::JobStatus::Completed => Ok(job.document_id),
+                    _ => Err(CodexError::content_processing(
+                        job.message.unwrap_or_else(|| "Job failed".to_string()),
+                    )),
+                };
+            }
 
-        Ok(document.is_favorite)
+            match events.recv().await {
+                Ok(_) | Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {}
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => {
+                    return Err(CodexError::internal("Job event channel closed"));
+                }
+            }
+        }
     }
 
-    /// Categorize document
-    pub async fn categorize_document(&self, document_id: uuid::Uuid, category: String) -> CodexResult<()> {
-        let mut document = crate::db::DocumentQueries::get_by_id(self.db.pool(), &document_id.to_string())
-            .await?
-            .ok_or_else(|| CodexError::not_found("Document not found"))?;
-
-        document.category = Some(category);
-        document.updated_at = chrono::Utc::now().to_rfc3339();
+    /// Import content from text
+    #[instrument(skip_all, fields(title = %title, bytes = content.len()))]
+    pub async fn import_text_content(
+        &self,
+        title: String,
+        content: String,
+        content_type: Option<String>,
+    ) -> CodexResult<uuid::Uuid> {
+        let _timer = metrics::timer(metrics::IMPORT);
+        info!("Importing text content: {}", title);
 
-        crate::db::DocumentQueries::update(self.db.pool(), &document).await?;
+        // Create document model
+        let document = crate::db::models::Document::new(
+            title,
+            content,
+            content_type.unwrap_or_else(|| "text/plain".to_string()),
+        );
 
-        Ok(())
+        self.store_text_document(document).await
     }
 
-    /// Bulk import documents from directory
-    pub async fn bulk_import_directory<P: AsRef<Path>>(&self, directory: P) -> CodexResult<BulkImportResult> {
-        let directory = directory.as_ref();
-        info!("Bulk importing from directory: {:?}", directory);
+    /// Save a snippet of text right away, e.g. from the clipboard
+    ///
+    /// The document is tagged [`capture::INBOX_TAG`] and titled after its
+    /// first line. Summary, tags, difficulty, an AI title (when the first
+    /// line is unusable) and embeddings are added by a background job, so
+    /// the call only writes one row. Text identical (after normalization)
+    /// to an inbox document created within `capture_dedup_minutes` is not
+    /// saved again; the earlier document is returned instead.
+    #[instrument(skip_all, fields(bytes = text.len(), source = ?source_hint))]
+    pub async fn quick_capture(&self, text: &str, source_hint: Option<&str>) -> CodexResult<CaptureResult> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err(CodexError::validation("Nothing to capture"));
+        }
 
-        let mut result = BulkImportResult {
-            total_files: 0,
-            successful_imports: 0,
-            failed_imports: 0,
-            imported_documents: Vec::new(),
-            errors: Vec::new(),
-        };
+        if self.config.capture_dedup_minutes > 0 {
+            let since = chrono::Utc::now() - chrono::Duration::minutes(self.config.capture_dedup_minutes as i64);
+            let normalized = capture::normalize(text);
+            let recent = crate::db::DocumentQueries::get_created_since(
+                self.db.pool(),
+                &since.to_rfc3339(),
+                Some(capture::INBOX_TAG),
+                None,
+                CAPTURE_DEDUP_SCAN_LIMIT,
+            )
+            .await?;
+            if let Some(existing) = recent
+                .into_iter()
+                .find(|document| capture::normalize(&document.content) == normalized)
+            {
+                debug!("Skipping repeated capture of document {}", existing.id);
+                return Ok(CaptureResult {
+                    document_id: uuid::Uuid::parse_str(&existing.id).unwrap_or_default(),
+                    title: existing.title,
+                    duplicate: true,
+                    enrichment_job: None,
+                });
+            }
+        }
 
-        // Read directory recursively
-        let mut entries = tokio::fs::read_dir(directory).await
-            .map_err(|e| CodexError::io(e))?;
+        let title = capture::derive_title(text);
+        let mut document = crate::db::models::Document::new(
+            title.title.clone(),
+            text.to_string(),
+            "text/plain".to_string(),
+        );
+        document.source = source_hint.map(str::to_string).filter(|s| !s.trim().is_empty());
+        document.set_tags(vec![capture::INBOX_TAG.to_string()]);
+        document.reading_time = Some(Self::reading_time(&self.config.reading_speeds, &document));
+        crate::db::DocumentQueries::create(self.db.pool(), &document, self.db.compression()).await?;
+        self.search.invalidate_cache();
+        self.titles.upsert(&document);
+
+        let document_id = uuid::Uuid::parse_str(&document.id).unwrap_or_default();
+        // In safe mode the capture stays in the inbox unenriched
+        let enrichment_job = (!self.ai.is_disabled()).then(|| {
+            let db = Arc::clone(&self.db);
+            let ai = Arc::clone(&self.ai);
+            let indexer = Arc::clone(&self.indexer);
+            let provisional_title = title.provisional.then(|| title.title.clone());
+            self.jobs.submit(CAPTURE_ENRICHMENT_JOB, move |handle| async move {
+                Self::enrich_capture(db, ai, indexer, document_id, provisional_title, handle)
+                    .await
+                    .map(|_| Some(document_id))
+            })
+        });
+
+        info!("Captured {} ({})", document.title, document.id);
+        Ok(CaptureResult {
+            document_id,
+            title: title.title,
+            duplicate: false,
+            enrichment_job,
+        })
+    }
 
-        while let Ok(Some(entry)) = entries.next_entry().await {
-            let path = entry.path();
-            
-            if path.is_file() {
-                result.total_files += 1;
+    /// Background body of a capture enrichment job
+    ///
+    /// `provisional_title` is replaced by a generated one unless the title
+    /// was edited in the meantime.
+    async fn enrich_capture(
+        db: Arc<DatabaseManager>,
+        ai: Arc<AiEngine>,
+        indexer: Arc<ContentIndexer>,
+        document_id: uuid::Uuid,
+        provisional_title: Option<String>,
+        handle: JobHandle,
+    ) -> CodexResult<()> {
+        let Some(mut document) = crate::db::DocumentQueries::get_by_id(db.pool(), &document_id.to_string()).await? else {
+            // Deleted before the job ran
+            return Ok(());
+        };
 
-                match self.import_document(&path).await {
-                    Ok(doc_id) => {
-                        result.successful_imports += 1;
-                        result.imported_documents.push(doc_id);
-                    }
-                    Err(e) => {
-                        result.failed_imports += 1;
-                        result.errors.push(format!("{:?}: {}", path, e));
-                        warn!("Failed to import file {:?}: {}", path, e);
+        handle.report(0.0, Some("Generating metadata".to_string()));
+        if provisional_title.as_deref() == Some(document.title.as_str()) {
+            let reply = ai.generate_text_limited(&capture::title_prompt(&document.content), 24).await;
+            if let Some(title) = reply.ok().as_deref().and_then(capture::clean_generated_title) {
+                document.title = title;
+            }
+        }
+
+        if let Ok(summary) = Self::generate_summary(&ai, &document).await {
+            document.summary = Some(summary);
+        }
+        if !Self::apply_code_metadata(&mut document) {
+            if let Ok(generated) = Self::generate_tags(&ai, &document).await {
+                let mut tags = document.get_tags();
+                for tag in generated {
+                    if !tags.iter().any(|t| t.eq_ignore_ascii_case(&tag)) {
+                        tags.push(tag);
                     }
                 }
+                document.set_tags(tags);
+            }
+            if let Ok(difficulty) = Self::assess_difficulty(&ai, &document).await {
+                document.difficulty_level = Some(difficulty.into());
             }
         }
+        crate::db::DocumentQueries::update(db.pool(), &document, db.compression()).await?;
 
-        info!("Bulk import completed: {} successful, {} failed", 
-               result.successful_imports, result.failed_imports);
-
-        Ok(result)
+        handle.report(0.7, Some("Indexing capture".to_string()));
+        indexer.index_document(&document).await?;
+        Ok(())
     }
 
-    /// Check for duplicate content by file hash
-    async fn check_for_duplicate(&self, file_hash: &str) -> CodexResult<Option<crate::db::models::Document>> {
-        // Query database for existing documents with the same file hash
-        let existing_doc = crate::db::DocumentQueries::get_by_file_hash(self.db.pool(), file_hash).await?;
-        Ok(existing_doc)
+    /// Enrich a document built from text with AI metadata, save and index it
+    ///
+    /// Tags already set on the document are kept instead of generated ones.
+    async fn store_text_document(&self, document: crate::db::models::Document) -> CodexResult<uuid::Uuid> {
+        let document = self.save_text_document(document).await?;
+        self.refresh_links(&document).await;
+
+        info!("Text content imported successfully: {}", document.id);
+        Ok(uuid::Uuid::parse_str(&document.id).unwrap_or_default())
     }
 
-    /// Validate file before import
-    async fn validate_file(&self, file_path: &Path) -> CodexResult<()> {
-        // Check if file exists
-        if !file_path.exists() {
-            return Err(CodexError::not_found("File does not exist"));
+    /// Add AI metadata to a text document, save and index it, leaving its links for later
+    async fn save_text_document(
+        &self,
+        mut document: crate::db::models::Document,
+    ) -> CodexResult<crate::db::models::Document> {
+        // Generate AI-enhanced metadata
+        if let Ok(summary) = Self::generate_summary(&self.ai, &document).await {
+            document.summary = Some(summary);
         }
 
-        // Check file extension
-        if let Some(extension) = file_path.extension().and_then(|e| e.to_str()) {
-            if !self.config.supported_extensions.contains(&extension.to_lowercase()) {
-                return Err(CodexError::validation(format!(
-                    "Unsupported file extension: {}",
-                    extension
-                )));
+        if !Self::apply_code_metadata(&mut document) {
+            if document.get_tags().is_empty() {
+                if let Ok(tags) = Self::generate_tags(&self.ai, &document).await {
+                    document.set_tags(tags);
+                }
+            }
+
+            if let Ok(difficulty) = Self::assess_difficulty(&self.ai, &document).await {
+                document.difficulty_level = Some(difficulty.into());
             }
         }
 
-        // Check file size
-        let metadata = tokio::fs::metadata(file_path).await?;
-        let file_size_mb = metadata.len() / (1024 * 1024);
-        
+        document.reading_time = Some(Self::reading_time(&self.config.reading_speeds, &document));
+
+        // Save and index the document
+        self.indexer.create_indexed(&document).await?;
+        self.search.invalidate_cache();
+        self.titles.upsert(&document);
+        Ok(document)
+    }
+
+    /// Create a reusable document template
+    pub async fn create_template(
+        &self,
+        name: String,
+        content: String,
+        default_tags: Vec<String>,
+        default_category: Option<String>,
+    ) -> CodexResult<uuid::Uuid> {
+        let name = name.trim().to_string();
+        if name.is_empty() {
+            return Err(CodexError::validation("Template name cannot be empty"));
+        }
+        if content.trim().is_empty() {
+            return Err(CodexError::validation("Template content cannot be empty"));
+        }
+        if crate::db::TemplateQueries::get_by_name(self.db.pool(), &name).await?.is_some() {
+            return Err(CodexError::validation(format!("Template already exists: {}", name)));
+        }
+
+        let mut template = crate::db::models::Template::new(name, content);
+        if !default_tags.is_empty() {
+            template.set_default_tags(default_tags);
+        }
+        template.default_category = default_category.filter(|c| !c.trim().is_empty());
+
+        crate::db::TemplateQueries::create(self.db.pool(), &template).await?;
+
+        info!("Template created: {} ({})", template.name, template.id);
+        Ok(uuid::Uuid::parse_str(&template.id).unwrap_or_default())
+    }
+
+    /// List built-in and user templates
+    pub async fn list_templates(&self) -> CodexResult<Vec<crate::db::models::Template>> {
+        crate::db::TemplateQueries::list(self.db.pool()).await
+    }
+
+    /// Create a document by rendering a template's placeholders
+    ///
+    /// The `title` variable names the document; without it the template name
+    /// and current date are used. The result is imported like any other text.
+    pub async fn create_document_from_template(
+        &self,
+        template_id: uuid::Uuid,
+        mut variables: HashMap<String, String>,
+    ) -> CodexResult<uuid::Uuid> {
+        let template = crate::db::TemplateQueries::get_by_id(self.db.pool(), &template_id.to_string())
+            .await?
+            .ok_or_else(|| CodexError::not_found("Template not found"))?;
+
+        let now = chrono::Local::now().naive_local();
+        let title = match variables.get(templates::TITLE_VARIABLE).map(|t| t.trim()) {
+            Some(title) if !title.is_empty() => title.to_string(),
+            _ => format!("{} {}", template.name, now.format("%Y-%m-%d")),
+        };
+        variables.insert(templates::TITLE_VARIABLE.to_string(), title.clone());
+
+        let content = templates::render_template(&template.content, &variables, now);
+
+        let mut document = crate::db::models::Document::new(title, content, template.content_type.clone());
+        document.category = template.default_category.clone();
+        let tags = template.get_default_tags();
+        if !tags.is_empty() {
+            document.set_tags(tags);
+        }
+
+        info!("Creating document from template: {}", template.name);
+        self.store_text_document(document).await
+    }
+
+    /// Update document content
+    ///
+    /// `expected_version` is the version the caller's copy was read at; if
+    /// the document has changed since, nothing is written and the call
+    /// fails with [`CodexError::Conflict`]. With `None` the current version
+    /// is used, which still guards against edits made while metadata is
+    /// regenerated. Returns the new version.
+    #[instrument(skip(self, new_content), fields(bytes = new_content.len()))]
+    pub async fn update_document(
+        &self,
+        document_id: uuid::Uuid,
+        new_content: String,
+        expected_version: Option<i64>,
+    ) -> CodexResult<i64> {
+        info!("Updating document: {}", document_id);
+
+        // Get existing document
+        let mut document = crate::db::DocumentQueries::get_by_id(self.db.pool(), &document_id.to_string())
+            .await?
+            .ok_or_else(|| CodexError::not_found("Document not found"))?;
+        if let Some(expected) = expected_version.filter(|expected| *expected != document.version) {
+            debug!("Document {} is at version {}, not {}", document_id, document.version, expected);
+            return Err(CodexError::conflict(document.version));
+        }
+
+        // Update content
+        document.content = new_content;
+        document.updated_at = chrono::Utc::now();
+
+        // Regenerate AI metadata
+        if let Ok(summary) = Self::generate_summary(&self.ai, &document).await {
+            document.summary = Some(summary);
+        }
+
+        if !Self::apply_code_metadata(&mut document) {
+            if let Ok(tags) = Self::generate_tags(&self.ai, &document).await {
+                document.set_tags(tags);
+            }
+
+            if let Ok(difficulty) = Self::assess_difficulty(&self.ai, &document).await {
+                document.difficulty_level = Some(difficulty.into());
+            }
+        }
+
+        // Recordings keep their listening time as reading time
+        if !document.content_type.starts_with("audio/") {
+            document.reading_time = Some(Self::reading_time(&self.config.reading_speeds, &document));
+        }
+
+        // Update in database
+        document.version = crate::db::DocumentQueries::update(self.db.pool(), &document, self.db.compression()).await?;
+        self.search.invalidate_cache();
+        self.titles.upsert(&document);
+
+        // Re-index the document and refresh its outgoing links
+        self.indexer.reindex_document(&document).await?;
+        self.links.update_document_links(&document).await?;
+        self.heal_annotations(&document).await?;
+
+        info!("Document updated successfully: {}", document_id);
+        Ok(document.version)
+    }
+
+    /// Re-anchor a document's annotations after its content changed
+    async fn heal_annotations(&self, document: &crate::db::models::Document) -> CodexResult<()> {
+        let pool = self.db.pool();
+        let existing = crate::db::AnnotationQueries::get_by_document(pool, &document.id).await?;
+        for annotation in annotations::heal_annotations(&document.content, existing) {
+            if annotation.orphaned {
+                debug!("Annotation {} lost its text in {}", annotation.id, document.id);
+            }
+            crate::db::AnnotationQueries::update(pool, &annotation).await?;
+        }
+        Ok(())
+    }
+
+    /// Highlight a character range of a document, optionally with a note
+    pub async fn create_annotation(
+        &self,
+        document_id: uuid::Uuid,
+        start_offset: i64,
+        end_offset: i64,
+        color: Option<String>,
+        note: Option<String>,
+    ) -> CodexResult<crate::db::models::Annotation> {
+        let document = crate::db::DocumentQueries::get_by_id(self.db.pool(), &document_id.to_string())
+            .await?
+            .ok_or_else(|| CodexError::not_found("Document not found"))?;
+
+        let selected_text = annotations::range_text(&document.content, start_offset, end_offset)?;
+        let mut annotation = crate::db::models::Annotation::new(
+            document.id.clone(),
+            start_offset,
+            end_offset,
+            selected_text.to_string(),
+        );
+        if let Some(color) = color {
+            annotations::validate_color(&color)?;
+            annotation.color = color;
+        }
+        annotation.note = note.filter(|n| !n.trim().is_empty());
+
+        crate::db::AnnotationQueries::create(self.db.pool(), &annotation).await?;
+        Ok(annotation)
+    }
+
+    /// Change an annotation's color and/or note; an empty note removes it
+    pub async fn update_annotation(
+        &self,
+        annotation_id: uuid::Uuid,
+        color: Option<String>,
+        note: Option<String>,
+    ) -> CodexResult<crate::db::models::Annotation> {
+        let mut annotation = crate::db::AnnotationQueries::get_by_id(self.db.pool(), &annotation_id.to_string())
+            .await?
+            .ok_or_else(|| CodexError::not_found("Annotation not found"))?;
+
+        if let Some(color) = color {
+            annotations::validate_color(&color)?;
+            annotation.color = color;
+        }
+        if let Some(note) = note {
+            annotation.note = Some(note).filter(|n| !n.trim().is_empty());
+        }
+
+        crate::db::AnnotationQueries::update(self.db.pool(), &annotation).await?;
+        Ok(annotation)
+    }
+
+    /// Delete an annotation
+    pub async fn delete_annotation(&self, annotation_id: uuid::Uuid) -> CodexResult<bool> {
+        crate::db::AnnotationQueries::delete(self.db.pool(), &annotation_id.to_string()).await
+    }
+
+    /// A document's annotations in reading order
+    pub async fn get_annotations(&self, document_id: uuid::Uuid) -> CodexResult<Vec<crate::db::models::Annotation>> {
+        crate::db::AnnotationQueries::get_by_document(self.db.pool(), &document_id.to_string()).await
+    }
+
+    /// Most recent highlights across the vault
+    pub async fn get_all_annotations(&self, limit: i64) -> CodexResult<Vec<crate::db::models::Highlight>> {
+        crate::db::AnnotationQueries::get_recent(self.db.pool(), limit).await
+    }
+
+    /// Search highlighted text and annotation notes
+    pub async fn search_annotations(&self, query: &str, limit: i64) -> CodexResult<Vec<crate::db::models::AnnotationMatch>> {
+        crate::db::AnnotationQueries::search(self.db.pool(), query, limit, false).await
+    }
+
+    /// Export highlights to `destination`, one file per document
+    ///
+    /// Exporting all highlights also writes a combined file ordered by
+    /// date, newest first. Existing files of the same name are replaced.
+    pub async fn export_annotations<P: AsRef<Path>>(
+        &self,
+        scope: annotations::AnnotationExportScope,
+        destination: P,
+        format: annotations::AnnotationExportFormat,
+    ) -> CodexResult<annotations::AnnotationExportResult> {
+        use annotations::AnnotationExportFormat;
+
+        let destination = destination.as_ref();
+        let pool = self.db.pool();
+        tokio::fs::create_dir_all(destination).await?;
+
+        // (document id, title, annotations) in order of first appearance
+        let mut documents: Vec<(String, String, Vec<crate::db::models::Annotation>)> = Vec::new();
+        let all = match scope {
+            annotations::AnnotationExportScope::Document { id } => {
+                let document = crate::db::DocumentQueries::get_by_id(pool, &id.to_string())
+                    .await?
+                    .ok_or_else(|| CodexError::not_found("Document not found"))?;
+                let document_annotations = crate::db::AnnotationQueries::get_by_document(pool, &document.id).await?;
+                documents.push((document.id, document.title, document_annotations));
+                None
+            }
+            annotations::AnnotationExportScope::All => {
+                let highlights = crate::db::AnnotationQueries::get_all(pool).await?;
+                for highlight in &highlights {
+                    let document_id = &highlight.annotation.document_id;
+                    match documents.iter_mut().find(|(id, _, _)| id == document_id) {
+                        Some((_, _, list)) => list.push(highlight.annotation.clone()),
+                        None => documents.push((
+                            document_id.clone(),
+                            highlight.document_title.clone(),
+                            vec![highlight.annotation.clone()],
+                        )),
+                    }
+                }
+                for (_, _, list) in documents.iter_mut() {
+                    list.sort_by_key(|a| a.start_offset);
+                }
+                Some(highlights)
+            }
+        };
+
+        let mut result = annotations::AnnotationExportResult::default();
+        for (document_id, title, document_annotations) in &documents {
+            let output = match format {
+                AnnotationExportFormat::Markdown => annotations::render_document_markdown(document_id, title, document_annotations),
+                AnnotationExportFormat::Json => serde_json::to_string_pretty(document_annotations)?,
+            };
+            let path = destination.join(format!("{}.{}", annotations::export_file_stem(document_id, title), format.extension()));
+            tokio::fs::write(&path, output).await?;
+            result.files.push(path);
+            result.documents += 1;
+            result.annotations += document_annotations.len();
+        }
+
+        if let Some(highlights) = all {
+            let output = match format {
+                AnnotationExportFormat::Markdown => annotations::render_all_markdown(&highlights),
+                AnnotationExportFormat::Json => serde_json::to_string_pretty(&highlights)?,
+            };
+            let path = destination.join(format!("{}.{}", annotations::ALL_HIGHLIGHTS_FILE, format.extension()));
+            tokio::fs::write(&path, output).await?;
+            result.files.push(path);
+        }
+
+        info!("Exported {} annotations from {} documents to {:?}", result.annotations, result.documents, destination);
+        Ok(result)
+    }
+
+    /// Parse a `codex://` link and check that what it points at exists
+    ///
+    /// Unknown documents and collections are `NotFound` errors; a position
+    /// past the end of the document is a validation error.
+    pub async fn resolve_deep_link(&self, link: &str) -> CodexResult<deep_link::NavigationTarget> {
+        let target = deep_link::parse_deep_link(link)?;
+        let pool = self.db.pool();
+
+        match &target {
+            deep_link::NavigationTarget::Document { id, position } => {
+                let document = crate::db::DocumentQueries::get_by_id(pool, &id.to_string())
+                    .await?
+                    .filter(|d| !d.is_deleted)
+                    .ok_or_else(|| CodexError::not_found(format!("Document {} not found", id)))?;
+                if position.is_some_and(|p| p as usize > document.content.chars().count()) {
+                    return Err(CodexError::validation("Link position is past the end of the document"));
+                }
+            }
+            deep_link::NavigationTarget::Collection { id } => {
+                if !crate::db::DocumentQueries::collection_exists(pool, id).await? {
+                    return Err(CodexError::not_found(format!("Collection {} not found", id)));
+                }
+            }
+            deep_link::NavigationTarget::Search { .. } => {}
+        }
+
+        Ok(target)
+    }
+
+    /// Rename a document, re-resolving links that pointed at the old or new title
+    pub async fn rename_document(&self, document_id: uuid::Uuid, new_title: String) -> CodexResult<()> {
+        let new_title = new_title.trim().to_string();
+        if new_title.is_empty() {
+            return Err(CodexError::validation("Document title cannot be empty"));
+        }
+
+        let mut document = crate::db::DocumentQueries::get_by_id(self.db.pool(), &document_id.to_string())
+            .await?
+            .ok_or_else(|| CodexError::not_found("Document not found"))?;
+
+        document.title = new_title;
+        document.updated_at = chrono::Utc::now();
+        crate::db::DocumentQueries::update(self.db.pool(), &document, self.db.compression()).await?;
+        self.search.invalidate_cache();
+        self.titles.upsert(&document);
+
+        self.links.handle_rename(&document.id).await?;
+
+        info!("Document renamed: {} -> {}", document_id, document.title);
+        Ok(())
+    }
+
+    /// Get links contained in a document
+    pub async fn get_outgoing_links(&self, document_id: uuid::Uuid) -> CodexResult<Vec<crate::db::models::DocumentLink>> {
+        crate::db::LinkQueries::get_outgoing(self.db.pool(), &document_id.to_string()).await
+    }
+
+    /// Get links from other documents pointing at a document
+    pub async fn get_backlinks(&self, document_id: uuid::Uuid) -> CodexResult<Vec<crate::db::models::DocumentLink>> {
+        crate::db::LinkQueries::get_backlinks(self.db.pool(), &document_id.to_string()).await
+    }
+
+    /// Get documents that neither link to nor are linked from any other document
+    pub async fn get_orphaned_documents(&self, limit: i64) -> CodexResult<Vec<crate::db::models::Document>> {
+        crate::db::LinkQueries::get_orphaned_documents(self.db.pool(), limit).await
+    }
+
+    /// Index a new document's links and resolve links that were waiting for it
+    ///
+    /// Link maintenance never fails an import; problems are logged instead.
+    async fn refresh_links(&self, document: &crate::db::models::Document) {
+        if let Err(e) = self.links.update_document_links(document).await {
+            warn!("Failed to index links for document {}: {}", document.id, e);
+        }
+        if let Err(e) = self.links.resolve_pending().await {
+            warn!("Failed to resolve pending links: {}", e);
+        }
+    }
+
+    /// Delete document
+    #[instrument(skip(self))]
+    pub async fn delete_document(&self, document_id: uuid::Uuid) -> CodexResult<()> {
+        info!("Deleting document: {}", document_id);
+        let id = document_id.to_string();
+        let document = crate::db::DocumentQueries::get_by_id(self.db.pool(), &id).await?;
+
+        // Remove from search index
+        self.indexer.remove_document(document_id).await?;
+
+        // Soft delete from database
+        crate::db::DocumentQueries::delete(self.db.pool(), &id).await?;
+
+        // Links to and from the document no longer resolve
+        self.links.remove_document(&id).await?;
+
+        // Deleting an already deleted document changes nothing worth undoing
+        if let Some(document) = document {
+            self.record_operation(Operation::new(
+                Operation::KIND_DELETE,
+                format!("Deleted \"{}\"", document.title),
+                BTreeMap::from([(id.clone(), false)]),
+                BTreeMap::from([(id, true)]),
+            ))
+            .await;
+        }
+
+        info!("Document deleted successfully: {}", document_id);
+        Ok(())
+    }
+
+    /// Copy the file a document was imported from into the attachment store
+    ///
+    /// The document is already saved, so a failed copy is logged and the
+    /// document keeps referring to the original path.
+    async fn keep_original(db: &DatabaseManager, attachments: &AttachmentStore, document_id: &str, file_path: &Path) {
+        let kept = async {
+            let stored = attachments.store(file_path).await?;
+            let name = file_path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+            AttachmentQueries::create_or_get(db.pool(), &stored.id, stored.size as i64, &name).await?;
+            AttachmentQueries::set_for_document(db.pool(), document_id, &stored.id).await
+        };
+        if let Err(e) = kept.await {
+            warn!("Failed to keep original {:?} of document {}: {}", file_path, document_id, e);
+        }
+    }
+
+    /// Path of the original file of a document
+    ///
+    /// This is the copy in the attachment store when one was kept, otherwise
+    /// the path the document was imported from if that file still exists.
+    /// None when neither is available.
+    pub async fn get_attachment_path(&self, document_id: uuid::Uuid) -> CodexResult<Option<std::path::PathBuf>> {
+        let id = document_id.to_string();
+        let document = crate::db::DocumentQueries::get_by_id(self.db.pool(), &id)
+            .await?
+            .ok_or_else(|| CodexError::not_found(format!("Document {}", id)))?;
+
+        if let Some(attachment) = AttachmentQueries::get_for_document(self.db.pool(), &id).await? {
+            let path = self.attachments.path_for(&attachment.id)?;
+            if tokio::fs::try_exists(&path).await? {
+                return Ok(Some(path));
+            }
+            warn!("Stored original {} of document {} is missing", attachment.id, id);
+        }
+
+        match document.source {
+            Some(source) if tokio::fs::metadata(&source).await.is_ok_and(|m| m.is_file()) => Ok(Some(source.into())),
+            _ => Ok(None),
+        }
+    }
+
+    /// Open the original file of a document for reading, see
+    /// [`get_attachment_path`](Self::get_attachment_path)
+    pub async fn open_attachment(&self, document_id: uuid::Uuid) -> CodexResult<tokio::fs::File> {
+        let path = self
+            .get_attachment_path(document_id)
+            .await?
+            .ok_or_else(|| CodexError::not_found(format!("Original file of document {}", document_id)))?;
+        Ok(tokio::fs::File::open(path).await?)
+    }
+
+    /// Thumbnail of a document for the library grid
+    ///
+    /// Cached thumbnails are returned right away. Otherwise one is rendered
+    /// from the document's original on the background queue and
+    /// [`Thumbnail::Pending`] names the job; ask again once it finished.
+    /// Documents that cannot have a thumbnail, including ones whose render
+    /// failed, get a placeholder rather than an error.
+    pub async fn get_document_thumbnail(&self, document_id: uuid::Uuid, size: ThumbnailSize) -> CodexResult<Thumbnail> {
+        let id = document_id.to_string();
+        let document = crate::db::DocumentQueries::get_by_id(self.db.pool(), &id)
+            .await?
+            .ok_or_else(|| CodexError::not_found(format!("Document {}", id)))?;
+        let placeholder = Thumbnail::Placeholder {
+            placeholder: ThumbnailPlaceholder::for_content_type(&document.content_type),
+        };
+
+        let hash = match document.file_hash {
+            Some(hash) if PreviewGenerator::is_available() && PreviewGenerator::can_render(&document.content_type) => hash,
+            _ => return Ok(placeholder),
+        };
+        if let Some(path) = self.previews.cached(&hash, size).await? {
+            return Ok(Thumbnail::Ready { path });
+        }
+        if self.previews.has_failed(&hash, size) {
+            return Ok(placeholder);
+        }
+        // The job may finish before it is marked pending; then its entry is stale
+        if let Some(job_id) = self.previews.pending_job(&hash, size) {
+            if self.jobs.get(job_id).is_some_and(|job| !job.status.is_finished()) {
+                return Ok(Thumbnail::Pending { job_id });
+            }
+        }
+        let Some(source) = self.get_attachment_path(document_id).await? else {
+            return Ok(placeholder);
+        };
+
+        let previews = Arc::clone(&self.previews);
+        let content_type = document.content_type;
+        let render_hash = hash.clone();
+        let job_id = self.jobs.submit(THUMBNAIL_JOB, move |_handle| async move {
+            // A failed render shows as a placeholder on the next request
+            if let Err(e) = previews.render(&source, &content_type, &render_hash, size).await {
+                warn!("Failed to render thumbnail of document {}: {}", document_id, e);
+            }
+            Ok(Some(document_id))
+        });
+        self.previews.mark_pending(&hash, size, job_id);
+
+        Ok(Thumbnail::Pending { job_id })
+    }
+
+    /// Delete stored originals no document refers to, returning how many were removed
+    ///
+    /// Soft-deleted documents keep their original so undo can restore them.
+    /// Files younger than [`attachments::ATTACHMENT_GC_GRACE`] are left alone
+    /// since an import may be about to refer to them.
+    pub async fn collect_attachment_garbage(&self) -> CodexResult<usize> {
+        let pool = self.db.pool();
+        let cutoff = std::time::SystemTime::now() - attachments::ATTACHMENT_GC_GRACE;
+        let cutoff_time = chrono::DateTime::<chrono::Utc>::from(cutoff).to_rfc3339();
+        let mut removed = 0;
+
+        for attachment in AttachmentQueries::get_unreferenced(pool).await? {
+            if attachment.created_at < cutoff_time && AttachmentQueries::delete_if_unreferenced(pool, &attachment.id).await? {
+                self.attachments.remove(&attachment.id).await?;
+                removed += 1;
+            }
+        }
+
+        // Files without a row, e.g. from an import that failed after copying
+        let known: std::collections::HashSet<String> = AttachmentQueries::all_ids(pool).await?.into_iter().collect();
+        for id in self.attachments.files_older_than(cutoff).await? {
+            if !known.contains(&id) && self.attachments.remove(&id).await? {
+                removed += 1;
+            }
+        }
+
+        if removed > 0 {
+            info!("Removed {} unreferenced attachments", removed);
+        }
+        Ok(removed)
+    }
+
+    /// Run [`collect_attachment_garbage`](Self::collect_attachment_garbage)
+    /// every [`attachments::ATTACHMENT_GC_INTERVAL`] in the background
+    ///
+    /// The task stops when the manager is dropped.
+    pub fn start_attachment_maintenance(self: &Arc<Self>) {
+        let manager = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(attachments::ATTACHMENT_GC_INTERVAL);
+            loop {
+                ticker.tick().await;
+                let Some(manager) = manager.upgrade() else {
+                    break;
+                };
+                if let Err(e) = manager.collect_attachment_garbage().await {
+                    error!("Attachment garbage collection failed: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Run a maintenance action suggested by [`DatabaseManager::get_storage_breakdown`]
+    ///
+    /// Content compression runs in the background and returns its job id,
+    /// published to [`ContentManager::subscribe_jobs`]; the other actions
+    /// finish before returning `None`.
+    #[instrument(skip(self))]
+    pub async fn run_maintenance(&self, action: MaintenanceAction) -> CodexResult<Option<uuid::Uuid>> {
+        info!("Running maintenance action {}", action.id());
+        match action {
+            MaintenanceAction::PurgeTrash => {
+                let purged = self.db.purge_trash().await?;
+                if !purged.is_empty() {
+                    self.record_operation(Operation::irreversible(
+                        Operation::KIND_PURGE,
+                        format!("Permanently deleted {} documents from the trash", purged.len()),
+                        &purged,
+                    ))
+                    .await;
+                }
+            }
+            MaintenanceAction::CompressContent => {
+                if !self.db.compression().enabled {
+                    return Err(CodexError::validation("Content compression is disabled"));
+                }
+                if self.ai.is_disabled() {
+                    return Err(CodexError::validation("Safe mode leaves stored content as it is"));
+                }
+                let db = Arc::clone(&self.db);
+                let job_id = self.jobs.submit(CONTENT_COMPRESSION_JOB, move |handle| async move {
+                    Self::compress_existing_content_job(db, handle).await.map(|_| None)
+                });
+                return Ok(Some(job_id));
+            }
+            MaintenanceAction::CompactVectorCache => {
+                self.db.compact_vector_cache(true).await?;
+            }
+            MaintenanceAction::CollectAttachmentGarbage => {
+                self.collect_attachment_garbage().await?;
+            }
+            MaintenanceAction::Vacuum => self.db.optimize().await?,
+        }
+        Ok(None)
+    }
+
+    /// Export the vault to `destination`, see [`DatabaseManager::export_vault`]
+    ///
+    /// With `include_attachments`, the stored originals of the exported
+    /// documents are copied to the export's `attachments` directory in the
+    /// store's layout.
+    pub async fn export_vault(&self, destination: &Path, include_attachments: bool) -> CodexResult<VaultExportManifest> {
+        let mut manifest = self.db.export_vault(destination).await?;
+        if !include_attachments {
+            return Ok(manifest);
+        }
+
+        let export_root = destination.join(attachments::ATTACHMENTS_DIR);
+        let mut copied = 0;
+        for id in crate::db::exported_attachment_ids(destination).await? {
+            let source = self.attachments.path_for(&id)?;
+            let target = export_root.join(AttachmentStore::relative_path(&id)?);
+            if let Some(parent) = target.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            match tokio::fs::copy(&source, &target).await {
+                Ok(_) => copied += 1,
+                Err(e) => warn!("Skipping attachment {} in export: {}", id, e),
+            }
+        }
+
+        manifest.attachment_count = copied;
+        manifest.write(destination).await?;
+        Ok(manifest)
+    }
+
+    /// Search documents
+    #[instrument(skip_all, fields(query = %query))]
+    pub async fn search_documents(&self, query: &str, options: SearchOptions) -> CodexResult<SearchResults> {
+        self.search.search(query, options).await
+    }
+
+    /// Documents similar to text that is not imported, see [`SearchEngine::similar_to_text`]
+    #[instrument(skip(self, text))]
+    pub async fn find_similar_to_text(&self, text: &str, limit: usize) -> CodexResult<SimilarTextResults> {
+        self.search.similar_to_text(text, limit).await
+    }
+
+    /// Explain why a search result matched, see [`SearchEngine::explain_match`]
+    #[instrument(skip(self))]
+    pub async fn explain_search_result(&self, document_id: &str, query: &str) -> CodexResult<MatchExplanation> {
+        self.search.explain_match(document_id, query).await
+    }
+
+    /// The synonyms and stop words applied to search queries
+    pub async fn get_search_dictionaries(&self) -> CodexResult<SearchDictionaries> {
+        SearchDictionaryQueries::get(self.db.pool()).await
+    }
+
+    /// Change the search synonyms or stop words, returning both dictionaries
+    #[instrument(skip(self))]
+    pub async fn edit_search_dictionaries(&self, edit: DictionaryEdit) -> CodexResult<SearchDictionaries> {
+        let pool = self.db.pool();
+        let dictionaries = match edit {
+            DictionaryEdit::SetSynonyms { term, expansions } => {
+                SearchDictionaryQueries::set_synonyms(pool, &term, &expansions).await
+            }
+            DictionaryEdit::RemoveSynonyms { term } => SearchDictionaryQueries::remove_synonyms(pool, &term).await,
+            DictionaryEdit::AddStopwords { words } => SearchDictionaryQueries::add_stopwords(pool, &words).await,
+            DictionaryEdit::RemoveStopwords { words } => SearchDictionaryQueries::remove_stopwords(pool, &words).await,
+        }?;
+        self.search.invalidate_cache();
+        Ok(dictionaries)
+    }
+
+    /// Get document by ID
+    #[instrument(skip(self))]
+    pub async fn get_document(&self, document_id: uuid::Uuid) -> CodexResult<Option<crate::db::models::Document>> {
+        let document = crate::db::DocumentQueries::get_by_id(self.db.pool(), &document_id.to_string()).await?;
+        
+        // Update access statistics
+        if document.is_some() {
+            let _ = crate::db::DocumentQueries::update_access(self.db.pool(), &document_id.to_string()).await;
+            self.titles.touch(&document_id.to_string());
+        }
+
+        Ok(document)
+    }
+
+    /// Summarize a document, using map-reduce when it exceeds the context window
+    ///
+    /// `progress` receives an update after each chunk or reduce step.
+    #[instrument(skip(self, progress))]
+    pub async fn summarize_document(
+        &self,
+        id: uuid::Uuid,
+        max_length: Option<usize>,
+        progress: impl Fn(SummaryProgress) + Send + Sync,
+    ) -> CodexResult<String> {
+        let document = crate::db::DocumentQueries::get_by_id(self.db.pool(), &id.to_string())
+            .await?
+            .ok_or_else(|| CodexError::not_found(format!("Document {} not found", id)))?;
+
+        let summary = async {
+            if self.ai.exceeds_context(&document.content) {
+                self.ai.summarize_long(&document.content, max_length, progress).await
+            } else {
+                self.ai.summarize(&document.content, max_length).await
+            }
+        };
+        self.ai.audited(&document.id, AiOperation::Summarize, &document.content, summary).await
+    }
+
+    /// AI operations recorded for a document, newest first
+    pub async fn get_ai_audit(&self, id: uuid::Uuid) -> CodexResult<Vec<crate::db::models::AiAuditEntry>> {
+        crate::db::AiAuditQueries::get_for_document(self.db.pool(), &id.to_string()).await
+    }
+
+    /// Summary used as document metadata, routing long content to map-reduce
+    async fn generate_summary(ai: &AiEngine, document: &crate::db::models::Document) -> CodexResult<String> {
+        let content = &document.content;
+        let summary = async {
+            if ai.exceeds_context(content) {
+                ai.summarize_long(content, Some(200), |p| {
+                    debug!("Summarizing: {:?} {}/{}", p.stage, p.completed, p.total)
+                })
+                .await
+            } else {
+                ai.summarize(content, Some(200)).await
+            }
+        };
+        ai.audited(&document.id, AiOperation::Summarize, content, summary).await
+    }
+
+    /// Tags generated for document metadata
+    async fn generate_tags(ai: &AiEngine, document: &crate::db::models::Document) -> CodexResult<Vec<String>> {
+        ai.audited(&document.id, AiOperation::Tags, &document.content, ai.generate_tags(&document.content, Some(10)))
+            .await
+    }
+
+    /// Difficulty level (1-5) assessed for document metadata
+    async fn assess_difficulty(ai: &AiEngine, document: &crate::db::models::Document) -> CodexResult<i32> {
+        ai.audited(&document.id, AiOperation::Difficulty, &document.content, ai.assess_difficulty(&document.content))
+            .await
+    }
+
+    /// Find matches of `query` within a single document's content
+    ///
+    /// Large documents are scanned on a blocking thread so the runtime stays
+    /// responsive; scanning stops at `max_matches` or the time budget.
+    pub async fn search_in_document(
+        &self,
+        document_id: uuid::Uuid,
+        query: &str,
+        options: find::FindOptions,
+    ) -> CodexResult<find::DocumentMatches> {
+        let matcher = find::build_matcher(query, &options)?;
+
+        let document = crate::db::DocumentQueries::get_by_id(self.db.pool(), &document_id.to_string())
+            .await?
+            .ok_or_else(|| CodexError::not_found("Document not found"))?;
+
+        if document.content.len() <= BLOCKING_FIND_THRESHOLD {
+            return Ok(find::find_matches(&document.content, &matcher, &options));
+        }
+
+        tokio::task::spawn_blocking(move || find::find_matches(&document.content, &matcher, &options))
+            .await
+            .map_err(|e| CodexError::internal(format!("Document search task failed: {}", e)))
+    }
+
+    /// Export a list of documents (by id or from a search) to a file
+    ///
+    /// Returns the number of documents written.
+    pub async fn export_document_list<P: AsRef<Path>>(
+        &self,
+        selection: export::DocumentSelection,
+        format: export::ExportFormat,
+        output_path: P,
+    ) -> CodexResult<usize> {
+        let documents = self.resolve_selection(selection).await?;
+
+        let mut annotations = HashMap::new();
+        if format == export::ExportFormat::Markdown {
+            for document in &documents {
+                let highlights = crate::db::AnnotationQueries::get_by_document(self.db.pool(), &document.id).await?;
+                annotations.insert(document.id.clone(), highlights);
+            }
+        }
+
+        let output = export::render_document_list(&documents, &annotations, format)?;
+        tokio::fs::write(output_path.as_ref(), output).await?;
+
+        info!("Exported {} documents to {:?}", documents.len(), output_path.as_ref());
+        Ok(documents.len())
+    }
+
+    /// Export documents as markdown files in `destination`, one per document
+    ///
+    /// Each file starts with frontmatter carrying the title, author,
+    /// category, tags, creation date and custom metadata, so importing it
+    /// again restores them. Existing files of the same name are replaced.
+    /// Returns the number of documents written.
+    pub async fn export_documents<P: AsRef<Path>>(
+        &self,
+        selection: export::DocumentSelection,
+        destination: P,
+    ) -> CodexResult<usize> {
+        let destination = destination.as_ref();
+        let documents = self.resolve_selection(selection).await?;
+        tokio::fs::create_dir_all(destination).await?;
+
+        for document in &documents {
+            let path = destination.join(format!("{}.md", annotations::export_file_stem(&document.id, &document.title)));
+            tokio::fs::write(&path, frontmatter::render_document(document)).await?;
+        }
+
+        info!("Exported {} documents to {:?}", documents.len(), destination);
+        Ok(documents.len())
+    }
+
+    /// Documents picked by id (in the given order) or by re-running a search
+    async fn resolve_selection(&self, selection: export::DocumentSelection) -> CodexResult<Vec<crate::db::models::Document>> {
+        match selection {
+            export::DocumentSelection::Ids { ids } => {
+                let mut documents = Vec::with_capacity(ids.len());
+                for id in ids {
+                    match crate::db::DocumentQueries::get_by_id(self.db.pool(), &id.to_string()).await? {
+                        Some(document) if !document.is_deleted => documents.push(document),
+                        _ => warn!("Skipping missing document in selection: {}", id),
+                    }
+                }
+                Ok(documents)
+            }
+            export::DocumentSelection::Search { query, options } => Ok(self
+                .search_documents(&query, options)
+                .await?
+                .documents
+                .into_iter()
+                .map(|result| result.document)
+                .collect()),
+        }
+    }
+
+    /// Apply category, tag, archive, favorite and custom metadata edits to a
+    /// selection of documents
+    ///
+    /// All documents are written in one transaction and the change is
+    /// journaled as a single undoable operation.
+    #[instrument(skip_all)]
+    pub async fn bulk_update(
+        &self,
+        selection: export::DocumentSelection,
+        changes: BulkChanges,
+    ) -> CodexResult<BulkUpdateSummary> {
+        if changes.is_empty() {
+            return Err(CodexError::validation("No changes given"));
+        }
+        changes.validate()?;
+
+        let documents = self.resolve_selection(selection).await?;
+        let matched = documents.len();
+
+        let mut before = BTreeMap::new();
+        let mut after = BTreeMap::new();
+        let mut updated = Vec::new();
+        for mut document in documents {
+            let previous = OrganizationState::from(&document);
+            if changes.apply(&mut document) {
+                fields::validate_count(&document.get_metadata())?;
+                before.insert(document.id.clone(), previous);
+                after.insert(document.id.clone(), OrganizationState::from(&document));
+                updated.push(document);
+            }
+        }
+
+        crate::db::DocumentQueries::update_organization(self.db.pool(), &updated).await?;
+        if changes.archived == Some(true) {
+            for document in &updated {
+                crate::db::EmbeddingQueries::evict_cached(self.db.pool(), &document.id).await?;
+            }
+        }
+
+        let operation_id = if updated.is_empty() {
+            None
+        } else {
+            let operation = Operation::new(
+                Operation::KIND_BULK_UPDATE,
+                format!("Updated {} documents", updated.len()),
+                before,
+                after,
+            );
+            let id = operation.id.clone();
+            self.record_operation(operation).await;
+            Some(id)
+        };
+
+        info!("Bulk update changed {} of {} documents", updated.len(), matched);
+        Ok(BulkUpdateSummary {
+            matched,
+            updated: updated.len(),
+            updated_ids: updated.into_iter().map(|d| d.id).collect(),
+            operation_id,
+        })
+    }
+
+    /// Custom metadata fields of a document
+    pub async fn get_document_metadata(&self, document_id: uuid::Uuid) -> CodexResult<BTreeMap<String, MetadataValue>> {
+        let document = crate::db::DocumentQueries::get_by_id(self.db.pool(), &document_id.to_string())
+            .await?
+            .ok_or_else(|| CodexError::not_found("Document not found"))?;
+        Ok(fields::typed(&document.get_metadata()))
+    }
+
+    /// Set and remove custom metadata fields of a document, returning its
+    /// fields afterwards
+    ///
+    /// The edit goes through [`bulk_update`](Self::bulk_update), so it is
+    /// validated the same way and can be undone.
+    #[instrument(skip(self, set, remove))]
+    pub async fn set_document_metadata(
+        &self,
+        document_id: uuid::Uuid,
+        set: BTreeMap<String, MetadataValue>,
+        remove: Vec<String>,
+    ) -> CodexResult<BTreeMap<String, MetadataValue>> {
+        let changes = BulkChanges { set_metadata: set, remove_metadata: remove, ..BulkChanges::default() };
+        let update = self.bulk_update(export::DocumentSelection::Ids { ids: vec![document_id] }, changes).await?;
+        if update.matched == 0 {
+            return Err(CodexError::not_found("Document not found"));
+        }
+        self.get_document_metadata(document_id).await
+    }
+
+    /// Live documents whose custom metadata matches every filter, newest first
+    ///
+    /// A text, number or boolean must equal the stored value; a list matches
+    /// stored lists containing all its items.
+    pub async fn get_documents_by_metadata(
+        &self,
+        filters: Vec<(String, MetadataValue)>,
+        limit: usize,
+    ) -> CodexResult<Vec<crate::db::models::Document>> {
+        if filters.is_empty() {
+            return Err(CodexError::validation("No metadata filters given"));
+        }
+        for (key, _) in &filters {
+            fields::validate_key(key)?;
+        }
+
+        let filter = crate::db::SearchFilter { metadata: filters, ..crate::db::SearchFilter::default() };
+        crate::db::SearchQueries::list_filtered(self.db.pool(), &filter, limit as i64).await
+    }
+
+    /// Merge `secondary_id` into `primary_id`
+    ///
+    /// See [`merge::merge`] for how fields are combined. The merged document
+    /// is written, the secondary's bookmarks, notes, incoming links and
+    /// collection memberships move to the primary and the secondary is
+    /// soft-deleted, all in one transaction. The merge is journaled for undo
+    /// and the primary is reindexed.
+    #[instrument(skip(self))]
+    pub async fn merge_documents(
+        &self,
+        primary_id: uuid::Uuid,
+        secondary_id: uuid::Uuid,
+        strategy: MergeStrategy,
+    ) -> CodexResult<MergeResult> {
+        let (primary, secondary) = self.merge_pair(primary_id, secondary_id).await?;
+        let merged = merge::merge(&primary, &secondary, strategy);
+
+        let references = crate::db::DocumentQueries::merge(self.db.pool(), &merged, &secondary.id, self.db.compression()).await?;
+
+        self.indexer.remove_document(secondary_id).await?;
+        self.links.remove_document(&secondary.id).await?;
+        self.indexer.reindex_document(&merged).await?;
+        self.refresh_links(&merged).await;
+
+        let before = BTreeMap::from([
+            (primary.id.clone(), MergeState::Primary(MergedFields::from(&primary))),
+            (secondary.id.clone(), MergeState::Secondary(references.clone())),
+        ]);
+        let after = BTreeMap::from([(primary.id.clone(), MergeState::Primary(MergedFields::from(&merged)))]);
+        let operation = Operation::new(
+            Operation::KIND_MERGE,
+            format!("Merged \"{}\" into \"{}\"", secondary.title, primary.title),
+            before,
+            after,
+        );
+        let operation_id = operation.id.clone();
+        self.record_operation(operation).await;
+
+        info!("Merged document {} into {}", secondary.id, primary.id);
+        let mut result = MergeResult::new(merged, secondary.id, &references, false);
+        result.operation_id = Some(operation_id);
+        Ok(result)
+    }
+
+    /// What [`Self::merge_documents`] would produce, without writing anything
+    pub async fn preview_merge(
+        &self,
+        primary_id: uuid::Uuid,
+        secondary_id: uuid::Uuid,
+        strategy: MergeStrategy,
+    ) -> CodexResult<MergeResult> {
+        let (primary, secondary) = self.merge_pair(primary_id, secondary_id).await?;
+        let mut conn = self.db.pool().acquire().await?;
+        let references = crate::db::DocumentQueries::find_merge_references(&mut conn, &primary.id, &secondary.id).await?;
+        let merged = merge::merge(&primary, &secondary, strategy);
+        Ok(MergeResult::new(merged, secondary.id, &references, true))
+    }
+
+    async fn merge_pair(
+        &self,
+        primary_id: uuid::Uuid,
+        secondary_id: uuid::Uuid,
+    ) -> CodexResult<(crate::db::models::Document, crate::db::models::Document)> {
+        if primary_id == secondary_id {
+            return Err(CodexError::validation("Cannot merge a document into itself"));
+        }
+        let load = |id: uuid::Uuid| async move {
+            crate::db::DocumentQueries::get_by_id(self.db.pool(), &id.to_string())
+                .await?
+                .ok_or_else(|| CodexError::not_found(format!("Document not found: {}", id)))
+        };
+        Ok((load(primary_id).await?, load(secondary_id).await?))
+    }
+
+    /// Split a document into child documents, one per section
+    ///
+    /// Children inherit the original's tags, category, author and content
+    /// type, get a `part_of` link back to it and are indexed individually.
+    /// The original is then archived or turned into a table of contents
+    /// linking to the children.
+    #[instrument(skip(self))]
+    pub async fn split_document(
+        &self,
+        document_id: uuid::Uuid,
+        strategy: SplitStrategy,
+        original_handling: OriginalHandling,
+    ) -> CodexResult<SplitResult> {
+        let mut original = crate::db::DocumentQueries::get_by_id(self.db.pool(), &document_id.to_string())
+            .await?
+            .ok_or_else(|| CodexError::not_found("Document not found"))?;
+        let sections = split::split_sections(&original.content, &strategy)?;
+        if sections.len() < 2 {
+            return Err(CodexError::validation("The document has no sections to split at"));
+        }
+
+        let mut document_ids = Vec::with_capacity(sections.len());
+        let mut child_titles = Vec::with_capacity(sections.len());
+        for section in &sections {
+            let mut child = crate::db::models::Document::new(
+                split::child_title(&original.title, section),
+                section.content.clone(),
+                original.content_type.clone(),
+            );
+            child.tags = original.tags.clone();
+            child.category = original.category.clone();
+            child.author = original.author.clone();
+            child.language = original.language.clone();
+            child.reading_time = Some(Self::reading_time(&self.config.reading_speeds, &child));
+            crate::db::DocumentQueries::create(self.db.pool(), &child, self.db.compression()).await?;
+            self.titles.upsert(&child);
+
+            let link = crate::db::models::DocumentLink::new(
+                child.id.clone(),
+                original.title.clone(),
+                links::PART_OF_LINK.to_string(),
+                Some(original.id.clone()),
+            );
+            crate::db::LinkQueries::create(self.db.pool(), &link).await?;
+
+            if let Err(e) = self.indexer.index_document(&child).await {
+                warn!("Failed to index section {}: {}", child.id, e);
+            }
+            self.refresh_links(&child).await;
+            document_ids.push(uuid::Uuid::parse_str(&child.id).unwrap_or_default());
+            child_titles.push(child.title);
+        }
+
+        match original_handling {
+            OriginalHandling::Archive => self.set_archived(document_id, true).await?,
+            OriginalHandling::TableOfContents => {
+                original.content = split::table_of_contents(&original.title, &child_titles);
+                original.updated_at = chrono::Utc::now();
+                original.reading_time = Some(Self::reading_time(&self.config.reading_speeds, &original));
+                crate::db::DocumentQueries::update(self.db.pool(), &original, self.db.compression()).await?;
+                self.titles.upsert(&original);
+                self.indexer.reindex_document(&original).await?;
+                self.links.update_document_links(&original).await?;
+            }
+        }
+
+        self.search.invalidate_cache();
+        info!("Split document {} into {} sections", original.id, document_ids.len());
+        Ok(SplitResult {
+            original_id: original.id,
+            sections,
+            document_ids,
+            original_handling,
+            dry_run: false,
+        })
+    }
+
+    /// The sections [`Self::split_document`] would create, without writing anything
+    pub async fn preview_split(&self, document_id: uuid::Uuid, strategy: SplitStrategy) -> CodexResult<SplitResult> {
+        let original = crate::db::DocumentQueries::get_by_id(self.db.pool(), &document_id.to_string())
+            .await?
+            .ok_or_else(|| CodexError::not_found("Document not found"))?;
+        let sections = split::split_sections(&original.content, &strategy)?;
+        Ok(SplitResult {
+            original_id: original.id,
+            sections,
+            document_ids: Vec::new(),
+            original_handling: OriginalHandling::default(),
+            dry_run: true,
+        })
+    }
+
+    /// Generate a digest of documents created since `since` and store it as a document
+    ///
+    /// Documents are listed by category with a one-line description each,
+    /// taken from their summary when one exists, under an AI overview.
+    #[instrument(skip(self))]
+    pub async fn generate_digest(&self, since: chrono::DateTime<chrono::Utc>, max_items: usize) -> CodexResult<uuid::Uuid> {
+        let until = chrono::Utc::now();
+        let document = self
+            .build_digest(since, until, max_items)
+            .await?
+            .ok_or_else(|| CodexError::validation(format!("No documents added since {}", since.format("%Y-%m-%d"))))?;
+
+        let id = self.store_text_document(document).await?;
+        info!("Generated digest {} covering documents since {}", id, since);
+        Ok(id)
+    }
+
+    /// Build the digest document, or None when no documents were added in the window
+    async fn build_digest(
+        &self,
+        since: chrono::DateTime<chrono::Utc>,
+        until: chrono::DateTime<chrono::Utc>,
+        max_items: usize,
+    ) -> CodexResult<Option<crate::db::models::Document>> {
+        let documents: Vec<_> = crate::db::DocumentQueries::get_created_since(
+            self.db.pool(),
+            &since.to_rfc3339(),
+            None,
+            Some(digest::DIGEST_TAG),
+            max_items.max(1) as i64,
+        )
+        .await?;
+
+        if documents.is_empty() {
+            return Ok(None);
+        }
+
+        let mut items = Vec::with_capacity(documents.len());
+        for document in documents {
+            let one_liner = match digest::summary_one_liner(&document) {
+                Some(one_liner) => one_liner,
+                None => {
+                    let excerpt: String = document.content.chars().take(2000).collect();
+                    let summary = self.ai.summarize(&excerpt, Some(25));
+                    match self.ai.audited(&document.id, AiOperation::Summarize, &excerpt, summary).await {
+                        Ok(summary) => digest::first_sentence(&summary),
+                        Err(_) => digest::first_sentence(&document.content),
+                    }
+                }
+            };
+
+            items.push(digest::DigestItem {
+                document_id: document.id,
+                title: document.title,
+                category: document.category,
+                one_liner,
+            });
+        }
+
+        let overview = match self.ai.generate_text(&digest::overview_prompt(&items)).await {
+            Ok(overview) => overview,
+            Err(e) => {
+                warn!("Could not generate digest overview: {}", e);
+                format!("{} documents were added in this period.", items.len())
+            }
+        };
+
+        let title = digest::digest_title(since, until);
+        let content = digest::render_digest(&title, &overview, &items);
+
+        let mut document = crate::db::models::Document::new(title, content, "text/markdown".to_string());
+        document.category = Some("Digest".to_string());
+        document.set_tags(vec![digest::DIGEST_TAG.to_string()]);
+        Ok(Some(document))
+    }
+
+    /// Generate digests in the background according to `ContentConfig::digest_schedule`
+    ///
+    /// The time of the last scheduled digest is kept in the settings table, so
+    /// restarts do not produce extra digests. The task stops when the manager
+    /// is dropped.
+    pub fn start_digest_schedule(self: &Arc<Self>) {
+        let schedule = self.config.digest_schedule.clone();
+        if !schedule.enabled {
+            return;
+        }
+
+        let manager = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(DIGEST_CHECK_INTERVAL);
+            loop {
+                ticker.tick().await;
+                let Some(manager) = manager.upgrade() else {
+                    break;
+                };
+                if let Err(e) = manager.run_scheduled_digest(&schedule).await {
+                    warn!("Scheduled digest failed: {}", e);
+                }
+            }
+        });
+    }
+
+    async fn run_scheduled_digest(&self, schedule: &digest::DigestSchedule) -> CodexResult<()> {
+        let now = chrono::Utc::now();
+        let interval = chrono::Duration::hours(schedule.interval_hours.max(1) as i64);
+
+        let last_run = crate::db::SettingQueries::get(self.db.pool(), DIGEST_LAST_RUN_SETTING)
+            .await?
+            .and_then(|setting| setting.get_value::<String>())
+            .and_then(|value| chrono::DateTime::parse_from_rfc3339(&value).ok())
+            .map(|time| time.with_timezone(&chrono::Utc));
+
+        let since = match last_run {
+            Some(last_run) if now - last_run < interval => return Ok(()),
+            Some(last_run) => last_run,
+            None => now - interval,
+        };
+
+        match self.build_digest(since, now, schedule.max_items).await? {
+            Some(document) => {
+                let id = self.store_text_document(document).await?;
+                info!("Generated scheduled digest {}", id);
+            }
+            None => debug!("No new documents for scheduled digest"),
+        }
+
+        let mut setting = crate::db::models::Setting::new(
+            DIGEST_LAST_RUN_SETTING.to_string(),
+            String::new(),
+            "content".to_string(),
+        );
+        setting.is_user_configurable = false;
+        setting.set_value(&now.to_rfc3339())?;
+        crate::db::SettingQueries::set(self.db.pool(), &setting).await?;
+        Ok(())
+    }
+
+    /// Subscribe to an RSS or Atom feed
+    pub async fn add_feed(&self, url: &str, name: Option<String>) -> CodexResult<crate::db::models::Feed> {
+        self.feeds.subscribe(url, name).await
+    }
+
+    /// All feed subscriptions
+    pub async fn list_feeds(&self) -> CodexResult<Vec<crate::db::models::Feed>> {
+        self.feeds.list().await
+    }
+
+    /// Unsubscribe from a feed; documents already imported from it are kept
+    pub async fn remove_feed(&self, feed_id: &str) -> CodexResult<()> {
+        self.feeds.unsubscribe(feed_id).await
+    }
+
+    /// Documents imported by feed refreshes, per feed
+    pub fn subscribe_feed_items(&self) -> tokio::sync::broadcast::Receiver<FeedNewItems> {
+        self.feeds.subscribe_new_items()
+    }
+
+    /// Fetch every feed and import entries not seen before
+    ///
+    /// A feed that fails to load is reported in its result and retried on
+    /// the next refresh; the other feeds are unaffected.
+    #[instrument(skip(self))]
+    pub async fn refresh_feeds(&self) -> CodexResult<Vec<FeedRefreshResult>> {
+        let mut results = Vec::new();
+        for feed in self.feeds.list().await? {
+            let (new_documents, error) = match self.refresh_feed(&feed).await {
+                Ok(new_documents) => (new_documents, None),
+                Err(e) => {
+                    warn!("Failed to refresh feed {}: {}", feed.url, e);
+                    crate::db::FeedSubscriptionQueries::record_fetch(self.db.pool(), &feed.id, None, None, Some(&e.to_string())).await?;
+                    (Vec::new(), Some(e.to_string()))
+                }
+            };
+
+            if !new_documents.is_empty() {
+                self.feeds.notify(FeedNewItems {
+                    feed_id: feed.id.clone(),
+                    feed_name: feed.name.clone(),
+                    document_ids: new_documents.clone(),
+                });
+            }
+            results.push(FeedRefreshResult { feed_id: feed.id, feed_name: feed.name, new_documents, error });
+        }
+        Ok(results)
+    }
+
+    async fn refresh_feed(&self, feed: &crate::db::models::Feed) -> CodexResult<Vec<uuid::Uuid>> {
+        let (parsed, etag, last_modified) = match self.feeds.fetch(feed).await? {
+            FetchOutcome::NotModified => {
+                crate::db::FeedSubscriptionQueries::record_fetch(self.db.pool(), &feed.id, None, None, None).await?;
+                return Ok(Vec::new());
+            }
+            FetchOutcome::Fetched { feed, etag, last_modified } => (feed, etag, last_modified),
+        };
+
+        let mut imported = Vec::new();
+        for entry in parsed.entries {
+            if crate::db::FeedSubscriptionQueries::has_entry(self.db.pool(), &feed.id, &entry.key).await? {
+                continue;
+            }
+            // The same post reached through another feed or imported by hand
+            if let Some(link) = &entry.link {
+                if let Some(existing) = crate::db::DocumentQueries::get_by_url(self.db.pool(), link).await? {
+                    crate::db::FeedSubscriptionQueries::add_entry(self.db.pool(), &feed.id, &entry.key, Some(&existing.id)).await?;
+                    continue;
+                }
+            }
+
+            let mut document = crate::db::models::Document::new(
+                entry.title,
+                ContentParser::html_to_text(&entry.content_html),
+                "text/html".to_string(),
+            );
+            document.url = entry.link;
+            document.author = entry.author;
+            document.source = Some(feed.url.clone());
+            document.set_tags(vec![feed.name.clone()]);
+            if let Some(published) = entry.published {
+                document.created_at = published;
+            }
+
+            let id = self.store_text_document(document).await?;
+            crate::db::FeedSubscriptionQueries::add_entry(self.db.pool(), &feed.id, &entry.key, Some(&id.to_string())).await?;
+            imported.push(id);
+        }
+
+        crate::db::FeedSubscriptionQueries::record_fetch(
+            self.db.pool(),
+            &feed.id,
+            etag.as_deref(),
+            last_modified.as_deref(),
+            None,
+        )
+        .await?;
+        if !imported.is_empty() {
+            info!("Imported {} new entries from feed {}", imported.len(), feed.name);
+        }
+        Ok(imported)
+    }
+
+    /// Refresh feeds every `feed_schedule.interval_minutes` in the background
+    ///
+    /// Not started in offline mode.
+    pub fn start_feed_schedule(self: &Arc<Self>) {
+        let schedule = self.config.feed_schedule.clone();
+        if !schedule.enabled {
+            return;
+        }
+
+        let manager = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let interval = std::time::Duration::from_secs(schedule.interval_minutes.max(1) * 60);
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let Some(manager) = manager.upgrade() else {
+                    break;
+                };
+                if let Err(e) = manager.refresh_feeds().await {
+                    warn!("Scheduled feed refresh failed: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Get the parsed structure (e.g. table schema) of a structured document
+    pub async fn get_document_structure(&self, document_id: uuid::Uuid) -> CodexResult<Option<DocumentStructure>> {
+        let structure = crate::db::StructureQueries::get(self.db.pool(), &document_id.to_string()).await?;
+
+        match structure {
+            Some(json) => Ok(Some(serde_json::from_str(&json)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Get recent documents
+    pub async fn get_recent_documents(&self, limit: i64) -> CodexResult<Vec<crate::db::models::Document>> {
+        crate::db::DocumentQueries::get_recent(self.db.pool(), limit).await
+    }
+
+    /// Get documents by category
+    pub async fn get_documents_by_category(
+        &self,
+        category: &str,
+        limit: i64,
+        offset: i64,
+    ) -> CodexResult<Vec<crate::db::models::Document>> {
+        crate::db::DocumentQueries::get_by_category(self.db.pool(), category, limit, offset).await
+    }
+
+    /// Get favorite documents
+    pub async fn get_favorite_documents(&self, limit: i64) -> CodexResult<Vec<crate::db::models::Document>> {
+        crate::db::DocumentQueries::get_favorites(self.db.pool(), limit).await
+    }
+
+    /// Toggle document favorite status
+    pub async fn toggle_favorite(&self, document_id: uuid::Uuid) -> CodexResult<bool> {
+        let document = crate::db::DocumentQueries::get_by_id(self.db.pool(), &document_id.to_string())
+            .await?
+            .ok_or_else(|| CodexError::not_found("Document not found"))?;
+
+        // Favoriting is not a content change, so updated_at is left alone
+        let is_favorite = !document.is_favorite;
+        crate::db::DocumentQueries::set_favorite(self.db.pool(), &document.id, is_favorite).await?;
+
+        self.record_operation(Operation::new(
+            Operation::KIND_FAVORITE,
+            format!("{} \"{}\"", if is_favorite { "Favorited" } else { "Unfavorited" }, document.title),
+            BTreeMap::from([(document.id.clone(), document.is_favorite)]),
+            BTreeMap::from([(document.id.clone(), is_favorite)]),
+        ))
+        .await;
+
+        Ok(is_favorite)
+    }
+
+    /// Get documents most recently opened
+    pub async fn get_recently_accessed(&self, limit: i64) -> CodexResult<Vec<crate::db::models::Document>> {
+        crate::db::DocumentQueries::get_recently_accessed(self.db.pool(), limit).await
+    }
+
+    /// Get the most viewed documents, optionally limited to those opened since `since`
+    pub async fn get_most_viewed(
+        &self,
+        limit: i64,
+        since: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> CodexResult<Vec<crate::db::models::Document>> {
+        let since = since.map(|s| s.to_rfc3339());
+        crate::db::DocumentQueries::get_most_viewed(self.db.pool(), limit, since.as_deref()).await
+    }
+
+    /// Sections for the home screen: unopened recent imports, recently read,
+    /// recently modified and frequently accessed documents
+    ///
+    /// Built from access columns only (no AI calls); see [`feed`] for the caps.
+    #[instrument(skip(self))]
+    pub async fn get_home_feed(&self) -> CodexResult<HomeFeed> {
+        let start = std::time::Instant::now();
+        let pool = self.db.pool();
+        let now = chrono::Utc::now();
+        // Fetch extra rows so sections still fill up after de-duplication
+        let fetch = (feed::SECTION_LIMIT * 2) as i64;
+
+        let unopened_since = (now - chrono::Duration::days(feed::UNOPENED_WINDOW_DAYS)).to_rfc3339();
+        let frequent_since = (now - chrono::Duration::days(feed::FREQUENT_WINDOW_DAYS)).to_rfc3339();
+        let (unopened_imports, unopened_import_count) =
+            crate::db::FeedQueries::unopened_since(pool, &unopened_since, feed::UNOPENED_LIMIT as i64).await?;
+
+        let mut home = HomeFeed {
+            unopened_imports,
+            unopened_import_count,
+            recently_read: crate::db::FeedQueries::recently_read(pool, fetch).await?,
+            recently_modified: crate::db::FeedQueries::recently_modified(pool, fetch).await?,
+            frequently_accessed: crate::db::FeedQueries::frequently_accessed(
+                pool,
+                feed::FREQUENT_MIN_VIEWS,
+                &frequent_since,
+                fetch,
+            )
+            .await?,
+            generated_in_ms: 0,
+        };
+        home.dedupe();
+        home.generated_in_ms = start.elapsed().as_millis() as u64;
+
+        debug!("Built home feed in {}ms", home.generated_in_ms);
+        Ok(home)
+    }
+
+    /// Archive or unarchive a document
+    ///
+    /// Embeddings are kept so unarchiving needs no re-indexing; archiving only
+    /// evicts the document's vectors from the hot vector cache.
+    pub async fn set_archived(&self, document_id: uuid::Uuid, archived: bool) -> CodexResult<()> {
+        let id = document_id.to_string();
+        let document = crate::db::DocumentQueries::get_by_id(self.db.pool(), &id)
+            .await?
+            .ok_or_else(|| CodexError::not_found("Document not found"))?;
+        if !crate::db::DocumentQueries::set_archived(self.db.pool(), &id, archived).await? {
+            return Err(CodexError::not_found("Document not found"));
+        }
+
+        if archived {
+            crate::db::EmbeddingQueries::evict_cached(self.db.pool(), &id).await?;
+        }
+
+        self.record_operation(Operation::new(
+            Operation::KIND_ARCHIVE,
+            format!("{} \"{}\"", if archived { "Archived" } else { "Unarchived" }, document.title),
+            BTreeMap::from([(id.clone(), document.is_archived)]),
+            BTreeMap::from([(id, archived)]),
+        ))
+        .await;
+
+        info!("Document {} {}", document_id, if archived { "archived" } else { "unarchived" });
+        Ok(())
+    }
+
+    /// Get archived documents
+    pub async fn get_archived_documents(&self, limit: i64) -> CodexResult<Vec<crate::db::models::Document>> {
+        crate::db::DocumentQueries::get_archived(self.db.pool(), limit).await
+    }
+
+    /// Categorize document
+    pub async fn categorize_document(&self, document_id: uuid::Uuid, category: String) -> CodexResult<()> {
+        let mut document = crate::db::DocumentQueries::get_by_id(self.db.pool(), &document_id.to_string())
+            .await?
+            .ok_or_else(|| CodexError::not_found("Document not found"))?;
+
+        let previous = document.category.replace(category.clone());
+        document.updated_at = chrono::Utc::now();
+
+        crate::db::DocumentQueries::update(self.db.pool(), &document, self.db.compression()).await?;
+
+        self.record_operation(Operation::new(
+            Operation::KIND_CATEGORIZE,
+            format!("Moved \"{}\" to {}", document.title, category),
+            BTreeMap::from([(document.id.clone(), previous)]),
+            BTreeMap::from([(document.id.clone(), Some(category))]),
+        ))
+        .await;
+
+        Ok(())
+    }
+
+    /// Replace a document's tags
+    pub async fn set_document_tags(&self, document_id: uuid::Uuid, tags: Vec<String>) -> CodexResult<()> {
+        let mut document = crate::db::DocumentQueries::get_by_id(self.db.pool(), &document_id.to_string())
+            .await?
+            .ok_or_else(|| CodexError::not_found("Document not found"))?;
+
+        let previous = document.get_tags();
+        document.set_tags(tags.clone());
+        document.updated_at = chrono::Utc::now();
+
+        crate::db::DocumentQueries::update(self.db.pool(), &document, self.db.compression()).await?;
+
+        self.record_operation(Operation::new(
+            Operation::KIND_SET_TAGS,
+            format!("Retagged \"{}\"", document.title),
+            BTreeMap::from([(document.id.clone(), previous)]),
+            BTreeMap::from([(document.id.clone(), tags)]),
+        ))
+        .await;
+
+        Ok(())
+    }
+
+    /// Most recent journaled operations, newest first
+    pub async fn get_recent_operations(&self, limit: i64) -> CodexResult<Vec<Operation>> {
+        crate::db::OperationQueries::get_recent(self.db.pool(), limit).await
+    }
+
+    /// Revert the newest operation that has not been undone yet
+    ///
+    /// Returns the reverted operation, or `None` when there is nothing to
+    /// undo. Documents that were removed since the operation are skipped.
+    #[instrument(skip(self))]
+    pub async fn undo_last_operation(&self) -> CodexResult<Option<Operation>> {
+        let pool = self.db.pool();
+        let Some(mut operation) = crate::db::OperationQueries::get_last_undoable(pool).await? else {
+            return Ok(None);
+        };
+        info!("Undoing operation {}: {}", operation.id, operation.description);
+
+        match operation.kind.as_str() {
+            Operation::KIND_DELETE => {
+                for document_id in operation.get_before_state::<bool>()?.into_keys() {
+                    if !crate::db::DocumentQueries::restore(pool, &document_id).await? {
+                        continue;
+                    }
+                    if let Some(document) = crate::db::DocumentQueries::get_by_id(pool, &document_id).await? {
+                        self.indexer.index_document(&document).await?;
+                        self.refresh_links(&document).await;
+                    }
+                }
+            }
+            Operation::KIND_CATEGORIZE => {
+                for (document_id, category) in operation.get_before_state::<Option<String>>()? {
+                    if let Some(mut document) = crate::db::DocumentQueries::get_by_id(pool, &document_id).await? {
+                        document.category = category;
+                        crate::db::DocumentQueries::update(pool, &document, self.db.compression()).await?;
+                    }
+                }
+            }
+            Operation::KIND_SET_TAGS => {
+                for (document_id, tags) in operation.get_before_state::<Vec<String>>()? {
+                    if let Some(mut document) = crate::db::DocumentQueries::get_by_id(pool, &document_id).await? {
+                        document.set_tags(tags);
+                        crate::db::DocumentQueries::update(pool, &document, self.db.compression()).await?;
+                    }
+                }
+            }
+            Operation::KIND_ARCHIVE => {
+                for (document_id, archived) in operation.get_before_state::<bool>()? {
+                    if crate::db::DocumentQueries::set_archived(pool, &document_id, archived).await? && archived {
+                        crate::db::EmbeddingQueries::evict_cached(pool, &document_id).await?;
+                    }
+                }
+            }
+            Operation::KIND_FAVORITE => {
+                for (document_id, favorite) in operation.get_before_state::<bool>()? {
+                    crate::db::DocumentQueries::set_favorite(pool, &document_id, favorite).await?;
+                }
+            }
+            Operation::KIND_BULK_UPDATE => {
+                let mut documents = Vec::new();
+                for (document_id, state) in operation.get_before_state::<OrganizationState>()? {
+                    if let Some(mut document) = crate::db::DocumentQueries::get_by_id(pool, &document_id).await? {
+                        state.apply_to(&mut document);
+                        documents.push(document);
+                    }
+                }
+                crate::db::DocumentQueries::update_organization(pool, &documents).await?;
+                for document in documents.iter().filter(|d| d.is_archived) {
+                    crate::db::EmbeddingQueries::evict_cached(pool, &document.id).await?;
+                }
+            }
+            Operation::KIND_MERGE => {
+                let mut primary = None;
+                let mut secondary = None;
+                for (document_id, state) in operation.get_before_state::<MergeState>()? {
+                    match state {
+                        MergeState::Primary(fields) => primary = Some((document_id, fields)),
+                        MergeState::Secondary(references) => secondary = Some((document_id, references)),
+                    }
+                }
+                let (Some((primary_id, fields)), Some((secondary_id, references))) = (primary, secondary) else {
+                    return Err(CodexError::internal(format!("Malformed merge journal entry: {}", operation.id)));
+                };
+
+                crate::db::DocumentQueries::unmerge(pool, &primary_id, &secondary_id, &references).await?;
+                if let Some(mut document) = crate::db::DocumentQueries::get_by_id(pool, &primary_id).await? {
+                    fields.apply_to(&mut document);
+                    crate::db::DocumentQueries::update(pool, &document, self.db.compression()).await?;
+                    self.indexer.reindex_document(&document).await?;
+                    self.refresh_links(&document).await;
+                }
+                if let Some(document) = crate::db::DocumentQueries::get_by_id(pool, &secondary_id).await? {
+                    self.indexer.index_document(&document).await?;
+                    self.refresh_links(&document).await;
+                }
+            }
+            kind => {
+                return Err(CodexError::validation(format!("Operation kind cannot be undone: {}", kind)));
+            }
+        }
+
+        self.search.invalidate_cache();
+        self.refresh_titles(&operation.get_document_ids()).await;
+        crate::db::OperationQueries::mark_undone(pool, &operation.id).await?;
+        operation.undone_at = Some(chrono::Utc::now().to_rfc3339());
+        Ok(Some(operation))
+    }
+
+    /// Journal an operation and prune old entries
+    ///
+    /// The change itself has already been made, so failures are only logged.
+    /// Cached searches are dropped and titles of the affected documents
+    /// re-read, as every journaled operation changes documents.
+    async fn record_operation(&self, operation: Operation) {
+        self.search.invalidate_cache();
+        self.refresh_titles(&operation.get_document_ids()).await;
+        let pool = self.db.pool();
+        if let Err(e) = crate::db::OperationQueries::create(pool, &operation).await {
+            warn!("Failed to journal operation {}: {}", operation.kind, e);
+            return;
+        }
+        if let Err(e) = crate::db::OperationQueries::prune(pool, OPERATION_JOURNAL_MAX_ENTRIES, OPERATION_JOURNAL_MAX_AGE_DAYS).await {
+            warn!("Failed to prune operations journal: {}", e);
+        }
+    }
+
+    /// Re-read the quick switcher titles of changed documents; failures are only logged
+    async fn refresh_titles(&self, document_ids: &[String]) {
+        if let Err(e) = self.titles.refresh(self.db.pool(), document_ids).await {
+            warn!("Failed to refresh quick find titles: {}", e);
+        }
+    }
+
+    /// Titles matching a quick switcher query, see [`quick_find`]
+    pub fn quick_find(&self, query: &str, limit: usize) -> Vec<QuickFindHit> {
+        let _timer = metrics::timer(metrics::QUICK_FIND);
+        self.titles.find(query, limit)
+    }
+
+    /// Open a reading session when the reader view shows a document
+    pub async fn start_reading_session(&self, document_id: uuid::Uuid) -> CodexResult<ReadingSession> {
+        ReadingSessionQueries::start(self.db.pool(), &document_id.to_string(), chrono::Utc::now()).await
+    }
+
+    /// End a reading session when the reader view closes its document
+    ///
+    /// Returns the recorded session, merged with overlapping sessions of the
+    /// same document, or None when it was shorter than the configured minimum.
+    pub async fn end_reading_session(&self, session_id: &str) -> CodexResult<Option<ReadingSession>> {
+        ReadingSessionQueries::finish(
+            self.db.pool(),
+            session_id,
+            chrono::Utc::now(),
+            self.config.reading.min_session_seconds as i64,
+        )
+        .await
+    }
+
+    /// Current and longest run of consecutive days with reading
+    pub async fn get_reading_streak(&self) -> CodexResult<ReadingStreak> {
+        let days = ReadingSessionQueries::reading_days(self.db.pool()).await?;
+        Ok(reading::streak_from_days(&days, chrono::Local::now().date_naive()))
+    }
+
+    /// Minutes read since Monday
+    pub async fn get_weekly_reading_minutes(&self) -> CodexResult<u64> {
+        let (_, since) = reading::current_week_start();
+        let seconds = ReadingSessionQueries::seconds_since(self.db.pool(), since).await?;
+        Ok(seconds.max(0) as u64 / 60)
+    }
+
+    /// Minutes read this week against the weekly goal
+    pub async fn get_goal_progress(&self) -> CodexResult<GoalProgress> {
+        let goal = crate::db::SettingQueries::get(self.db.pool(), reading::WEEKLY_GOAL_SETTING)
+            .await?
+            .and_then(|setting| setting.get_value::<u32>());
+        let (week_start, since) = reading::current_week_start();
+        let seconds = ReadingSessionQueries::seconds_since(self.db.pool(), since).await?;
+        Ok(GoalProgress::new(goal, seconds.max(0) as u64 / 60, week_start))
+    }
+
+    /// Set the weekly reading goal in minutes, or clear it with None
+    pub async fn set_weekly_reading_goal(&self, minutes: Option<u32>) -> CodexResult<()> {
+        match minutes {
+            Some(minutes) => {
+                let mut setting = crate::db::models::Setting::new(
+                    reading::WEEKLY_GOAL_SETTING.to_string(),
+                    String::new(),
+                    "reading".to_string(),
+                );
+                setting.set_value(&minutes)?;
+                crate::db::SettingQueries::set(self.db.pool(), &setting).await
+            }
+            None => crate::db::SettingQueries::delete(self.db.pool(), reading::WEEKLY_GOAL_SETTING).await,
+        }
+    }
+
+    /// Suggested category, tags, collection and archive/keep action for up to `limit` inbox documents
+    ///
+    /// Newest inbox documents come first. Suggestions are computed on the
+    /// background job queue from stored embeddings only (see [`triage`]), so
+    /// documents still waiting for their embeddings are left out until
+    /// indexing caught up.
+    #[instrument(skip(self))]
+    pub async fn triage_suggestions(&self, limit: usize) -> CodexResult<Vec<TriageSuggestion>> {
+        let db = Arc::clone(&self.db);
+        let thresholds = self.triage_thresholds().await?;
+        let (sender, receiver) = tokio::sync::oneshot::channel();
+
+        self.jobs.submit(TRIAGE_JOB, move |handle| async move {
+            handle.report(0.0, Some("Suggesting inbox triage".to_string()));
+            let suggestions = Self::triage_job(&db, limit, &thresholds).await;
+            let outcome = match suggestions {
+                Ok(_) => Ok(None),
+                Err(ref e) => Err(CodexError::internal(e.to_string())),
+            };
+            let _ = sender.send(suggestions);
+            outcome
+        });
+
+        receiver
+            .await
+            .map_err(|_| CodexError::internal("Triage job stopped without suggestions"))?
+    }
+
+    /// Background body of a triage job, reading the vectors of [`triage::candidates`] only
+    async fn triage_job(
+        db: &DatabaseManager,
+        limit: usize,
+        thresholds: &TriageThresholds,
+    ) -> CodexResult<Vec<TriageSuggestion>> {
+        let pool = db.pool();
+        let (inbox, corpus) = triage::candidates(crate::db::DocumentQueries::get_labels(pool).await?, limit);
+        if inbox.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let ids: Vec<&str> = inbox.iter().chain(&corpus).map(|labels| labels.id.as_str()).collect();
+        let mut chunks: HashMap<String, Vec<Vec<f32>>> = HashMap::new();
+        for (document_id, vector) in crate::db::EmbeddingQueries::get_document_vectors(pool, &ids).await? {
+            chunks.entry(document_id).or_default().push(vector);
+        }
+
+        let profile = |labels: &crate::db::models::DocumentLabels| {
+            let vector = triage::document_vector(chunks.get(&labels.id)?.iter().map(Vec::as_slice))?;
+            Some(triage::TriageProfile {
+                tags: labels.get_tags(),
+                collections: labels.get_collections(),
+                id: labels.id.clone(),
+                title: labels.title.clone(),
+                category: labels.category.clone(),
+                is_archived: labels.is_archived,
+                created_at: labels.created_at.clone(),
+                vector,
+            })
+        };
+        let inbox: Vec<triage::TriageProfile> = inbox.iter().filter_map(profile).collect();
+        let corpus: Vec<triage::TriageProfile> = corpus.iter().filter_map(profile).collect();
+
+        let triage = triage::Triage::new(&corpus, thresholds);
+        Ok(inbox.iter().map(|document| triage.suggest(document)).collect())
+    }
+
+    /// Apply the accepted parts of a triage suggestion and learn from the decision
+    ///
+    /// Organization edits go through [`bulk_update`](Self::bulk_update), so
+    /// they are journaled and undoable; the document leaves the inbox either
+    /// way. A collection membership is added separately and is not undone
+    /// with the journal entry.
+    #[instrument(skip_all, fields(document_id = %suggestion.document_id))]
+    pub async fn apply_triage_suggestion(
+        &self,
+        suggestion: &TriageSuggestion,
+        decision: &TriageDecision,
+    ) -> CodexResult<TriageResult> {
+        let id = uuid::Uuid::parse_str(&suggestion.document_id)
+            .map_err(|_| CodexError::validation(format!("Invalid document ID: {}", suggestion.document_id)))?;
+
+        let update = self
+            .bulk_update(export::DocumentSelection::Ids { ids: vec![id] }, decision.changes(suggestion))
+            .await?;
+        if update.matched == 0 {
+            return Err(CodexError::not_found(format!("Document {}", suggestion.document_id)));
+        }
+
+        let added_to_collection = match suggestion.collection {
+            Some(ref collection) if decision.collection => {
+                crate::db::DocumentQueries::add_to_collection(self.db.pool(), &suggestion.document_id, &collection.collection_id)
+                    .await?
+            }
+            _ => false,
+        };
+
+        let mut thresholds = self.triage_thresholds().await?;
+        thresholds.learn(suggestion, decision);
+        let mut setting = crate::db::models::Setting::new(
+            triage::TRIAGE_THRESHOLDS_SETTING.to_string(),
+            String::new(),
+            "triage".to_string(),
+        );
+        setting.set_value(&thresholds)?;
+        crate::db::SettingQueries::set(self.db.pool(), &setting).await?;
+
+        Ok(TriageResult {
+            update,
+            added_to_collection,
+        })
+    }
+
+    /// Learned triage thresholds, the defaults before any decision
+    async fn triage_thresholds(&self) -> CodexResult<TriageThresholds> {
+        Ok(crate::db::SettingQueries::get(self.db.pool(), triage::TRIAGE_THRESHOLDS_SETTING)
+            .await?
+            .and_then(|setting| setting.get_value::<TriageThresholds>())
+            .unwrap_or_default())
+    }
+
+    /// Build a reading list on `topic` that fits in `minutes`, stored as a collection
+    ///
+    /// Documents are picked by search relevance within `difficulty`, ordered
+    /// easiest first and introduced by an index document that is added to
+    /// the collection first (see [`focus_pack`]).
+    #[instrument(skip(self))]
+    pub async fn build_focus_pack(
+        &self,
+        topic: &str,
+        minutes: i64,
+        difficulty: Option<DifficultyRange>,
+    ) -> CodexResult<FocusPack> {
+        if topic.trim().is_empty() {
+            return Err(CodexError::validation("Focus pack topic is empty"));
+        }
+        if minutes <= 0 {
+            return Err(CodexError::validation("Focus pack time budget must be positive"));
+        }
+        if let Some(range) = difficulty {
+            if range.min > range.max || range.min < 1 || range.max > 5 {
+                return Err(CodexError::validation(format!(
+                    "Invalid difficulty range {}-{} (levels are 1-5)",
+                    range.min, range.max
+                )));
+            }
+        }
+
+        let options = SearchOptions {
+            limit: focus_pack::FOCUS_PACK_CANDIDATES,
+            ..Default::default()
+        };
+        let candidates = self
+            .search_documents(topic, options)
+            .await?
+            .documents
+            .into_iter()
+            .map(|result| {
+                let reading_minutes = result
+                    .document
+                    .reading_time
+                    .unwrap_or_else(|| Self::reading_time(&self.config.reading_speeds, &result.document));
+                (result.document, result.score, reading_minutes)
+            })
+            .collect();
+
+        let picks = focus_pack::select(candidates, minutes, difficulty);
+        if picks.is_empty() {
+            return Err(CodexError::validation(format!(
+                "No documents on \"{}\" fit in {} minutes",
+                topic.trim(),
+                minutes
+            )));
+        }
+
+        let intro = match self.ai.generate_text(&focus_pack::intro_prompt(topic, &picks)).await {
+            Ok(intro) => intro,
+            Err(e) => {
+                warn!("Could not generate focus pack introduction: {}", e);
+                format!("{} documents on {}, easiest first.", picks.len(), topic.trim())
+            }
+        };
+
+        let title = focus_pack::pack_title(topic, minutes);
+        let mut index = crate::db::models::Document::new(
+            title.clone(),
+            focus_pack::render_index(&title, &intro, &picks),
+            "text/markdown".to_string(),
+        );
+        index.category = Some("Focus pack".to_string());
+        index.set_tags(vec![focus_pack::FOCUS_PACK_TAG.to_string()]);
+        let index_document_id = self.store_text_document(index).await?.to_string();
+
+        let pool = self.db.pool();
+        let collection = crate::db::models::Collection::new(title, Some(intro.trim().to_string()));
+        crate::db::DocumentQueries::create_collection(pool, &collection).await?;
+        crate::db::DocumentQueries::add_to_collection(pool, &index_document_id, &collection.id).await?;
+        for pick in &picks {
+            crate::db::DocumentQueries::add_to_collection(pool, &pick.document_id, &collection.id).await?;
+        }
+
+        let total_minutes = picks.iter().map(|pick| pick.reading_minutes).sum();
+        info!("Built focus pack {} with {} documents ({} min)", collection.id, picks.len(), total_minutes);
+        Ok(FocusPack {
+            collection_id: collection.id,
+            index_document_id,
+            total_minutes,
+            picks,
+        })
+    }
+
+    /// Bulk import documents from directory
+    ///
+    /// With `dry_run`, nothing is written: the result only carries the
+    /// [`BulkImportPlan`] of what the import would do.
+    #[instrument(skip_all, fields(directory = ?directory.as_ref(), dry_run))]
+    pub async fn bulk_import_directory<P: AsRef<Path>>(&self, directory: P, dry_run: bool) -> CodexResult<BulkImportResult> {
+        let directory = directory.as_ref();
+        info!("Bulk importing from directory: {:?}", directory);
+
+        // Sorted, so the checkpoint cursor marks everything before it as done
+        let mut paths = Vec::new();
+        let mut entries = tokio::fs::read_dir(directory).await
+            .map_err(CodexError::io)?;
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            if path.is_file() {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        if dry_run {
+            let plan = self.plan_import(&paths).await?;
+            return Ok(BulkImportResult {
+                total_files: plan.total_files,
+                plan: Some(plan),
+                ..BulkImportResult::default()
+            });
+        }
+
+        // Imported text lands in the database; require room for the source files
+        let mut expected_size = 0;
+        for path in &paths {
+            if let Ok(metadata) = tokio::fs::metadata(path).await {
+                expected_size += metadata.len();
+            }
+        }
+        crate::update::disk::ensure_space(self.db.path(), expected_size)?;
+
+        let checkpoint = Checkpoint::start(
+            self.db.pool(),
+            checkpoint::KIND_BULK_IMPORT,
+            serde_json::json!({ "directory": directory }),
+            Some(paths.len() as i64),
+        )
+        .await?;
+        self.run_bulk_import(directory, paths, checkpoint).await
+    }
+
+    /// Import `paths` after the checkpoint's cursor, recording each as done
+    async fn run_bulk_import(
+        &self,
+        directory: &Path,
+        paths: Vec<std::path::PathBuf>,
+        mut checkpoint: Checkpoint,
+    ) -> CodexResult<BulkImportResult> {
+        self.track_operation(checkpoint.id());
+        let mut result = BulkImportResult {
+            total_files: 0,
+            successful_imports: 0,
+            failed_imports: 0,
+            imported_documents: Vec::new(),
+            queued_jobs: Vec::new(),
+            errors: Vec::new(),
+            unresolved_links: Vec::new(),
+            plan: None,
+        };
+
+        let cursor = checkpoint.cursor().map(std::path::PathBuf::from);
+        for path in paths {
+            if cursor.as_ref().is_some_and(|cursor| &path <= cursor) {
+                continue;
+            }
+            self.import_into(&path, &mut result).await;
+
+            if let Err(e) = checkpoint.advance(&path.to_string_lossy()).await {
+                self.untrack_operation(checkpoint.id());
+                let _ = checkpoint.fail(&e.to_string()).await;
+                return Err(e);
+            }
+        }
+        self.untrack_operation(checkpoint.id());
+        checkpoint.complete().await?;
+
+        info!("Bulk import completed: {} successful, {} failed", 
+               result.successful_imports, result.failed_imports);
+
+        if result.successful_imports > 0 {
+            let document_ids: Vec<String> = result.imported_documents.iter().map(|id| id.to_string()).collect();
+            self.record_operation(Operation::irreversible(
+                Operation::KIND_IMPORT,
+                format!("Imported {} documents from {}", result.successful_imports, directory.display()),
+                &document_ids,
+            ))
+            .await;
+        }
+
+        Ok(result)
+    }
+
+    /// Import one file of a bulk import into `result`
+    ///
+    /// A failure is recorded in the failed imports table, and a success
+    /// clears an earlier failure of the same path.
+    async fn import_into(&self, path: &Path, result: &mut BulkImportResult) {
+        result.total_files += 1;
+
+        let outcome = if Self::is_audio_file(path) {
+            self.import_audio(path).await.map(|job_id| result.queued_jobs.push(job_id))
+        } else {
+            self.import_document(path).await.map(|doc_id| {
+                result.successful_imports += 1;
+                result.imported_documents.push(doc_id);
+            })
+        };
+
+        let path_key = path.to_string_lossy();
+        let recorded = match outcome {
+            Ok(()) => crate::db::FailedImportQueries::clear(self.db.pool(), &path_key).await.map(|_| ()),
+            Err(e) => {
+                result.failed_imports += 1;
+                result.errors.push(format!("{:?}: {}", path, e));
+                warn!("Failed to import file {:?}: {}", path, e);
+                crate::db::FailedImportQueries::record(self.db.pool(), &path_key, &e).await.map(|_| ())
+            }
+        };
+        if let Err(e) = recorded {
+            warn!("Could not update failed import record of {:?}: {}", path, e);
+        }
+    }
+
+    /// Files that failed to import, most recent failure first
+    ///
+    /// Entries whose file no longer exists are removed.
+    pub async fn get_failed_imports(&self) -> CodexResult<Vec<FailedImport>> {
+        let mut failed = crate::db::FailedImportQueries::get_all(self.db.pool()).await?;
+        let mut missing = Vec::new();
+        for entry in &failed {
+            if !tokio::fs::try_exists(&entry.path).await.unwrap_or(true) {
+                missing.push(entry.id.clone());
+            }
+        }
+        for id in &missing {
+            crate::db::FailedImportQueries::delete(self.db.pool(), id).await?;
+        }
+        if !missing.is_empty() {
+            info!("Pruned {} failed imports whose files are gone", missing.len());
+            failed.retain(|entry| !missing.contains(&entry.id));
+        }
+
+        Ok(failed)
+    }
+
+    /// Import the files of the given failed imports again with the current parsers
+    ///
+    /// Files that import are removed from the failed imports, files that
+    /// fail again count another attempt, and entries whose file is gone are
+    /// dropped. Unknown ids are ignored.
+    #[instrument(skip(self))]
+    pub async fn retry_failed_imports(&self, ids: &[String]) -> CodexResult<BulkImportResult> {
+        let mut result = BulkImportResult {
+            total_files: 0,
+            successful_imports: 0,
+            failed_imports: 0,
+            imported_documents: Vec::new(),
+            queued_jobs: Vec::new(),
+            errors: Vec::new(),
+            unresolved_links: Vec::new(),
+            plan: None,
+        };
+
+        for entry in crate::db::FailedImportQueries::get_by_ids(self.db.pool(), ids).await? {
+            let path = std::path::PathBuf::from(&entry.path);
+            if !tokio::fs::try_exists(&path).await.unwrap_or(true) {
+                info!("Dropping failed import of missing file {:?}", path);
+                crate::db::FailedImportQueries::delete(self.db.pool(), &entry.id).await?;
+                continue;
+            }
+            self.import_into(&path, &mut result).await;
+        }
+
+        info!("Retried failed imports: {} successful, {} failed", result.successful_imports, result.failed_imports);
+
+        if result.successful_imports > 0 {
+            let document_ids: Vec<String> = result.imported_documents.iter().map(|id| id.to_string()).collect();
+            self.record_operation(Operation::irreversible(
+                Operation::KIND_IMPORT,
+                format!("Imported {} previously failed documents", result.successful_imports),
+                &document_ids,
+            ))
+            .await;
+        }
+
+        Ok(result)
+    }
+
+    /// Import an Obsidian or Logseq vault
+    ///
+    /// Notes keep their frontmatter tags, category, author and creation
+    /// date; notes without a category take their top-level folder's (see
+    /// [`vault_import::VaultImportConfig`]). Files embedded or linked from
+    /// notes are imported alongside. Wikilinks are resolved once every note
+    /// is in, and those matching no document are listed in the result.
+    #[instrument(skip_all, fields(vault = ?vault.as_ref()))]
+    pub async fn import_obsidian_vault<P: AsRef<Path>>(
+        &self,
+        vault: P,
+        progress: impl Fn(VaultImportProgress) + Send + Sync,
+    ) -> CodexResult<BulkImportResult> {
+        let root = vault.as_ref().to_path_buf();
+        if !root.is_dir() {
+            return Err(CodexError::validation(format!("Not a directory: {}", root.display())));
+        }
+        info!("Importing vault: {:?}", root);
+
+        let files = tokio::task::spawn_blocking({
+            let root = root.clone();
+            move || VaultFiles::scan(&root)
+        })
+        .await
+        .map_err(|e| CodexError::internal(format!("Vault scan failed: {}", e)))??;
+
+        let expected_size = files.notes.iter().chain(&files.attachments)
+            .filter_map(|path| std::fs::metadata(path).ok())
+            .map(|metadata| metadata.len())
+            .sum();
+        crate::update::disk::ensure_space(self.db.path(), expected_size)?;
+
+        let mut result = BulkImportResult {
+            total_files: files.notes.len(),
+            successful_imports: 0,
+            failed_imports: 0,
+            imported_documents: Vec::new(),
+            queued_jobs: Vec::new(),
+            errors: Vec::new(),
+            unresolved_links: Vec::new(),
+            plan: None,
+        };
+
+        // First pass: every note, without links, so any note can be linked to
+        let mut notes = Vec::new();
+        let mut attachments: Vec<std::path::PathBuf> = Vec::new();
+        let mut attachment_references = Vec::new();
+        for (i, path) in files.notes.iter().enumerate() {
+            match self.import_vault_note(&root, path).await {
+                Ok((document, references)) => {
+                    for reference in &references {
+                        match files.resolve_attachment(&root, path, reference) {
+                            Some(attachment) if !attachments.contains(&attachment) => attachments.push(attachment),
+                            Some(_) => {}
+                            None => debug!("Attachment {} of {:?} is not in the vault", reference, path),
+                        }
+                    }
+                    attachment_references.extend(references);
+                    result.successful_imports += 1;
+                    result.imported_documents.push(uuid::Uuid::parse_str(&document.id).unwrap_or_default());
+                    notes.push(document);
+                }
+                Err(e) => {
+                    result.failed_imports += 1;
+                    result.errors.push(format!("{:?}: {}", path, e));
+                    warn!("Failed to import note {:?}: {}", path, e);
+                }
+            }
+            progress(VaultImportProgress { stage: VaultImportStage::Notes, completed: i + 1, total: files.notes.len() });
+        }
+
+        // Second pass: files the notes refer to
+        result.total_files += attachments.len();
+        for (i, path) in attachments.iter().enumerate() {
+            match self.import_document(path).await {
+                Ok(doc_id) => {
+                    result.successful_imports += 1;
+                    result.imported_documents.push(doc_id);
+                }
+                Err(e) => {
+                    result.failed_imports += 1;
+                    result.errors.push(format!("{:?}: {}", path, e));
+                    warn!("Failed to import attachment {:?}: {}", path, e);
+                }
+            }
+            progress(VaultImportProgress { stage: VaultImportStage::Attachments, completed: i + 1, total: attachments.len() });
+        }
+
+        // Third pass: links between the notes, and from earlier documents to them
+        for (i, document) in notes.iter().enumerate() {
+            if let Err(e) = self.links.update_document_links(document).await {
+                warn!("Failed to index links for document {}: {}", document.id, e);
+            }
+            progress(VaultImportProgress { stage: VaultImportStage::Links, completed: i + 1, total: notes.len() });
+        }
+        self.links.resolve_pending().await?;
+
+        for document in &notes {
+            let unresolved = crate::db::LinkQueries::get_outgoing(self.db.pool(), &document.id)
+                .await?
+                .into_iter()
+                // Embeds of files outside the supported formats are not documents
+                .filter(|link| link.target_id.is_none() && !vault_import::is_attachment_target(&link.target_title, &attachment_references));
+            result.unresolved_links.extend(unresolved.map(|link| UnresolvedLink {
+                source_id: document.id.clone(),
+                source_title: document.title.clone(),
+                target_title: link.target_title,
+            }));
+        }
+
+        info!("Vault import completed: {} successful, {} failed, {} unresolved links",
+              result.successful_imports, result.failed_imports, result.unresolved_links.len());
+
+        if result.successful_imports > 0 {
+            let document_ids: Vec<String> = result.imported_documents.iter().map(|id| id.to_string()).collect();
+            self.record_operation(Operation::irreversible(
+                Operation::KIND_IMPORT,
+                format!("Imported {} documents from vault {}", result.successful_imports, root.display()),
+                &document_ids,
+            ))
+            .await;
+        }
+
+        Ok(result)
+    }
+
+    /// Import a browser bookmarks export (Netscape HTML) as one document per bookmark
+    ///
+    /// Bookmarks whose URL is already in the vault, or repeated in the file,
+    /// are skipped. With `fetch_content`, each page's text is downloaded into
+    /// its document; pages that fail to load are listed in `errors` and the
+    /// bookmark is still imported.
+    #[instrument(skip_all, fields(file = ?file_path.as_ref(), fetch_content))]
+    pub async fn import_bookmarks_file<P: AsRef<Path>>(
+        &self,
+        file_path: P,
+        fetch_content: bool,
+    ) -> CodexResult<BulkImportResult> {
+        use futures::StreamExt;
+
+        let file_path = file_path.as_ref();
+        info!("Importing bookmarks from {:?}", file_path);
+        let html = tokio::fs::read_to_string(file_path).await?;
+        let parsed = bookmarks::parse_bookmarks_html(&html);
+
+        let mut result = BulkImportResult {
+            total_files: parsed.len(),
+            successful_imports: 0,
+            failed_imports: 0,
+            imported_documents: Vec::new(),
+            queued_jobs: Vec::new(),
+            errors: Vec::new(),
+            unresolved_links: Vec::new(),
+            plan: None,
+        };
+
+        let mut seen = std::collections::HashSet::new();
+        let mut new_bookmarks = Vec::new();
+        for bookmark in parsed {
+            if !seen.insert(bookmark.url.clone()) {
+                continue;
+            }
+            if crate::db::DocumentQueries::get_by_url(self.db.pool(), &bookmark.url).await?.is_some() {
+                debug!("Bookmark already in the vault: {}", bookmark.url);
+                continue;
+            }
+            new_bookmarks.push(bookmark);
+        }
+
+        let pages: Vec<Option<CodexResult<String>>> = if fetch_content {
+            let client = reqwest::Client::builder()
+                .timeout(bookmarks::FETCH_TIMEOUT)
+                .user_agent("Codex-Vault/1.0")
+                .build()?;
+            futures::stream::iter(&new_bookmarks)
+                .map(|bookmark| {
+                    let client = &client;
+                    async move { Some(bookmarks::fetch_page_text(client, &bookmark.url).await) }
+                })
+                .buffered(bookmarks::FETCH_CONCURRENCY)
+                .collect()
+                .await
+        } else {
+            new_bookmarks.iter().map(|_| None).collect()
+        };
+
+        for (bookmark, page) in new_bookmarks.into_iter().zip(pages) {
+            let page_text = match page {
+                Some(Ok(text)) => Some(text),
+                Some(Err(e)) => {
+                    warn!("Failed to fetch bookmarked page {}: {}", bookmark.url, e);
+                    result.errors.push(format!("{}: {}", bookmark.url, e));
+                    None
+                }
+                None => None,
+            };
+
+            let mut document = crate::db::models::Document::new(
+                bookmark.title.clone(),
+                bookmark.content(page_text.as_deref()),
+                bookmarks::BOOKMARK_CONTENT_TYPE.to_string(),
+            );
+            document.url = Some(bookmark.url.clone());
+            document.set_tags(bookmark.folders.clone());
+            if let Some(added_at) = bookmark.added_at {
+                document.created_at = added_at;
+            }
+            document.reading_time = Some(Self::reading_time(&self.config.reading_speeds, &document));
+
+            if let Err(e) = crate::db::DocumentQueries::create(self.db.pool(), &document, self.db.compression()).await {
+                result.failed_imports += 1;
+                result.errors.push(format!("{}: {}", bookmark.url, e));
+                warn!("Failed to save bookmark {}: {}", bookmark.url, e);
+                continue;
+            }
+            if let Err(e) = self.indexer.index_document(&document).await {
+                warn!("Failed to index bookmark {}: {}", document.id, e);
+            }
+            result.successful_imports += 1;
+            result.imported_documents.push(uuid::Uuid::parse_str(&document.id).unwrap_or_default());
+        }
+
+        info!("Bookmark import completed: {} imported, {} failed, {} already present",
+              result.successful_imports, result.failed_imports,
+              result.total_files - result.successful_imports - result.failed_imports);
+
+        if result.successful_imports > 0 {
+            let document_ids: Vec<String> = result.imported_documents.iter().map(|id| id.to_string()).collect();
+            self.record_operation(Operation::irreversible(
+                Operation::KIND_IMPORT,
+                format!("Imported {} bookmarks from {}", result.successful_imports, file_path.display()),
+                &document_ids,
+            ))
+            .await;
+        }
+
+        Ok(result)
+    }
+
+    /// Apply a BibTeX/Zotero export to the vault
+    ///
+    /// Each entry is matched to a document by DOI, then by its attached
+    /// file's content, then by title; a match gets the entry's authors,
+    /// year, source and citation key. With `create_stubs`, entries that
+    /// match nothing become small documents holding their abstract.
+    #[instrument(skip_all, fields(file = ?file_path.as_ref(), create_stubs))]
+    pub async fn import_bibtex<P: AsRef<Path>>(
+        &self,
+        file_path: P,
+        create_stubs: bool,
+    ) -> CodexResult<bibtex::BibtexImportResult> {
+        use crate::db::DocumentQueries;
+        use bibtex::MatchedBy;
+
+        let file_path = file_path.as_ref();
+        info!("Importing BibTeX from {:?}", file_path);
+        let text = tokio::fs::read_to_string(file_path).await?;
+        let entries = bibtex::parse_bibtex(&text);
+        let pool = self.db.pool();
+
+        let mut result = bibtex::BibtexImportResult { total_entries: entries.len(), ..Default::default() };
+        let resolver = links::TitleResolver::new(DocumentQueries::get_titles(pool).await?);
+        let mut changed = Vec::new();
+
+        for entry in entries {
+            let mut found = None;
+            if let Some(doi) = entry.doi() {
+                found = DocumentQueries::find_by_doi(pool, &doi).await?.map(|d| (d, MatchedBy::Doi));
+            }
+            if found.is_none() {
+                for file in entry.files() {
+                    let Ok(bytes) = tokio::fs::read(&file).await else {
+                        continue;
+                    };
+                    if let Some(document) = DocumentQueries::get_by_file_hash(pool, &ContentParser::hash_bytes(&bytes)).await? {
+                        found = Some((document, MatchedBy::FilePath));
+                        break;
+                    }
+                }
+            }
+            if found.is_none() {
+                if let Some(id) = entry.title().and_then(|title| resolver.resolve(title)) {
+                    found = DocumentQueries::get_by_id(pool, &id).await?.map(|d| (d, MatchedBy::Title));
+                }
+            }
+
+            match found {
+                Some((mut document, matched_by)) => {
+                    Self::apply_bib_entry(&mut document, &entry);
+                    if let Err(e) = DocumentQueries::update(pool, &document, self.db.compression()).await {
+                        warn!("Failed to update document {} from {}: {}", document.id, entry.key, e);
+                        result.errors.push(format!("{}: {}", entry.key, e));
+                        continue;
+                    }
+                    changed.push(document.id.clone());
+                    result.matched.push(bibtex::BibtexMatch {
+                        citation_key: entry.key.clone(),
+                        document_id: document.id,
+                        matched_by,
+                    });
+                }
+                None if create_stubs => {
+                    let title = entry.title().unwrap_or(&entry.key).to_string();
+                    let mut document = crate::db::models::Document::new(title, String::new(), "text/x-bibtex".to_string());
+                    Self::apply_bib_entry(&mut document, &entry);
+                    document.content = match entry.field("abstract") {
+                        Some(abstract_text) => format!("{}\n\n{}", document.title, abstract_text),
+                        None => bibtex::format_citation(&document, bibtex::CitationStyle::Apa),
+                    };
+
+                    if let Err(e) = DocumentQueries::create(pool, &document, self.db.compression()).await {
+                        warn!("Failed to create stub for {}: {}", entry.key, e);
+                        result.errors.push(format!("{}: {}", entry.key, e));
+                        continue;
+                    }
+                    if let Err(e) = self.indexer.index_document(&document).await {
+                        warn!("Failed to index stub {}: {}", document.id, e);
+                    }
+                    changed.push(document.id.clone());
+                    result.created.push(document.id);
+                }
+                None => result.unmatched.push(entry.key),
+            }
+        }
+
+        info!("BibTeX import completed: {} matched, {} created, {} unmatched",
+              result.matched.len(), result.created.len(), result.unmatched.len());
+
+        if !changed.is_empty() {
+            self.record_operation(Operation::irreversible(
+                Operation::KIND_IMPORT,
+                format!("Imported bibliographic data for {} documents from {}", changed.len(), file_path.display()),
+                &changed,
+            ))
+            .await;
+        }
+
+        Ok(result)
+    }
+
+    /// Copy an entry's bibliographic fields onto a document
+    fn apply_bib_entry(document: &mut crate::db::models::Document, entry: &bibtex::BibEntry) {
+        let authors = entry.authors();
+        if !authors.is_empty() {
+            document.author = Some(authors.join(bibtex::AUTHOR_SEPARATOR));
+        }
+        if let Some(year) = entry.year() {
+            document.publication_year = Some(year);
+        }
+        if let Some(source) = entry.source() {
+            document.source = Some(source.to_string());
+        }
+        if document.url.is_none() {
+            document.url = entry
+                .doi()
+                .map(|doi| format!("{}{}", bibtex::DOI_URL_PREFIX, doi))
+                .or_else(|| entry.field("url").map(str::to_string));
+        }
+        document.citation_key = Some(entry.key.clone());
+    }
+
+    /// Format a citation for a document from its stored bibliographic fields
+    pub async fn get_citation(&self, document_id: uuid::Uuid, style: bibtex::CitationStyle) -> CodexResult<String> {
+        let document = self
+            .get_document(document_id)
+            .await?
+            .ok_or_else(|| CodexError::not_found("Document not found"))?;
+        Ok(bibtex::format_citation(&document, style))
+    }
+
+    /// Save one vault note; returns it with the attachments it refers to
+    async fn import_vault_note(
+        &self,
+        root: &Path,
+        path: &Path,
+    ) -> CodexResult<(crate::db::models::Document, Vec<String>)> {
+        self.validate_file(path).await?;
+        let bytes = tokio::fs::read(path).await?;
+        let file_hash = ContentParser::hash_bytes(&bytes);
+        if let Some(existing_doc) = self.check_for_duplicate(&file_hash).await? {
+            return Err(CodexError::validation(format!(
+                "Document with identical content already exists: {} ({})",
+                existing_doc.title, existing_doc.id
+            )));
+        }
+
+        let text = String::from_utf8(bytes).map_err(|_| {
+            CodexError::content_processing(format!("File is not valid UTF-8: {}", path.display()))
+        })?;
+        let (metadata, body) = vault_import::parse_note(&text);
+
+        // Wikilinks name files, so the file name is the title links resolve against
+        let title = path.file_stem().and_then(|s| s.to_str()).unwrap_or("Untitled").to_string();
+        let mut document = crate::db::models::Document::new(title, body.to_string(), "text/markdown".to_string());
+        document.file_size = Some(text.len() as i64);
+        document.file_hash = Some(file_hash);
+        document.author = metadata.author;
+        document.category = metadata.category.or_else(|| {
+            path.strip_prefix(root).ok().and_then(|relative| self.config.vault_import.category_for(relative))
+        });
+        if !metadata.tags.is_empty() {
+            document.set_tags(metadata.tags);
+        }
+        if let Some(created) = metadata.created {
+            document.created_at = created;
+        }
+        if !metadata.custom.is_empty() {
+            document.set_metadata(&metadata.custom);
+        }
+
+        let references = vault_import::attachment_references(body);
+        let document = self.save_text_document(document).await?;
+        Ok((document, references))
+    }
+
+    /// Plan importing the given files, in order, without writing anything
+    ///
+    /// This is what a dry run of an import reports: the validation, parsing
+    /// and duplicate checks of [`ContentManager::import_document`] per file.
+    pub async fn plan_import(&self, paths: &[std::path::PathBuf]) -> CodexResult<BulkImportPlan> {
+        // Hashes of files already planned, so repeats within the batch are caught
+        let mut seen = HashMap::new();
+        let mut files = Vec::with_capacity(paths.len());
+        for path in paths {
+            files.push(self.plan_file(path, &mut seen).await?);
+        }
+
+        let plan = BulkImportPlan::from_files(files);
+        info!("Import plan: {} to import, {} duplicates, {} skipped",
+              plan.to_import, plan.duplicates, plan.skipped);
+        Ok(plan)
+    }
+
+    /// Decide what importing a file would do
+    async fn plan_file(&self, path: &Path, seen: &mut HashMap<String, std::path::PathBuf>) -> CodexResult<FileImportPlan> {
+        let size_bytes = tokio::fs::metadata(path).await.map(|m| m.len()).unwrap_or(0);
+        let skip = |reason: String| FileImportPlan::skipped(path.to_path_buf(), size_bytes, ImportDecision::Skip { reason });
+
+        if let Err(e) = self.validate_file(path).await {
+            return Ok(skip(e.to_string()));
+        }
+
+        let (file_hash, title, content_type, decision) = if Self::is_audio_file(path) {
+            if !Transcriber::is_available() {
+                return Ok(skip("Audio transcription is not enabled in this build".to_string()));
+            }
+            let bytes = tokio::fs::read(path).await?;
+            (ContentParser::hash_bytes(&bytes), None, None, ImportDecision::Transcribe)
+        } else {
+            match self.parser.parse_file(path).await {
+                Ok(parsed) => (parsed.file_hash, Some(parsed.title), Some(parsed.content_type), ImportDecision::Import),
+                Err(e) => return Ok(skip(e.to_string())),
+            }
+        };
+
+        let decision = if let Some(existing) = self.check_for_duplicate(&file_hash).await? {
+            ImportDecision::Duplicate { existing_id: existing.id, existing_title: existing.title }
+        } else if let Some(original) = seen.get(&file_hash) {
+            ImportDecision::DuplicateInBatch { original: original.clone() }
+        } else {
+            seen.insert(file_hash, path.to_path_buf());
+            decision
+        };
+
+        Ok(FileImportPlan {
+            path: path.to_path_buf(),
+            size_bytes,
+            title,
+            content_type,
+            estimated_ms: import_plan::estimate_import_ms(size_bytes, &decision),
+            decision,
+        })
+    }
+
+    /// Check for duplicate content by file hash
+    async fn check_for_duplicate(&self, file_hash: &str) -> CodexResult<Option<crate::db::models::Document>> {
+        // Query database for existing documents with the same file hash
+        let existing_doc = crate::db::DocumentQueries::get_by_file_hash(self.db.pool(), file_hash).await?;
+        Ok(existing_doc)
+    }
+
+    /// Validate file before import
+    async fn validate_file(&self, file_path: &Path) -> CodexResult<()> {
+        // Check if file exists
+        if !file_path.exists() {
+            return Err(CodexError::not_found("File does not exist"));
+        }
+
+        // Check file extension
+        if let Some(extension) = file_path.extension().and_then(|e| e.to_str()) {
+            if !self.config.supported_extensions.contains(&extension.to_lowercase()) {
+                return Err(CodexError::validation(format!(
+                    "Unsupported file extension: {}",
+                    extension
+                )));
+            }
+        }
+
+        // Check file size
+        let metadata = tokio::fs::metadata(file_path).await?;
+        let file_size_mb = metadata.len() / (1024 * 1024);
+        
         if file_size_mb > self.config.max_file_size_mb as u64 {
             return Err(CodexError::validation(format!(
                 "File too large: {} MB (max: {} MB)",
@@ -374,33 +3624,261 @@ impl ContentManager {
     }
 
     /// Get content statistics
+    #[instrument(skip(self))]
     pub async fn get_content_stats(&self) -> CodexResult<ContentStats> {
         let db_stats = self.db.get_stats().await?;
+        let index_health = self.indexer.get_index_health().await?;
         
         Ok(ContentStats {
             total_documents: db_stats.document_count,
             total_embeddings: db_stats.embedding_count,
             database_size_bytes: db_stats.database_size_bytes,
-            indexed_documents: db_stats.document_count, // Assume all documents are indexed
+            indexed_documents: index_health.indexed_documents,
+            index_health,
+        })
+    }
+
+    /// Report documents missing from or out of date in the search index
+    pub async fn get_index_health(&self) -> CodexResult<IndexHealth> {
+        self.indexer.get_index_health().await
+    }
+
+    /// Reindex the documents flagged by [`ContentManager::get_index_health`] in the background
+    ///
+    /// The job finishes by rebuilding the vector cache without stale entries,
+    /// whatever its tombstone ratio. Returns the job id; progress is published to
+    /// [`ContentManager::subscribe_jobs`].
+    #[instrument(skip(self))]
+    pub async fn repair_index(&self) -> CodexResult<uuid::Uuid> {
+        let document_ids = self.indexer.get_unhealthy_documents().await?;
+        let checkpoint = Checkpoint::start(
+            self.db.pool(),
+            checkpoint::KIND_INDEX_REPAIR,
+            serde_json::json!({}),
+            Some(document_ids.len() as i64),
+        )
+        .await?;
+        Ok(self.submit_index_repair(document_ids, checkpoint))
+    }
+
+    /// Compare the embedding model with the stored vectors, see
+    /// [`ContentIndexer::check_embedding_model`]; returns whether the index is stale
+    pub async fn check_embedding_model(&self) -> CodexResult<bool> {
+        self.indexer.check_embedding_model().await
+    }
+
+    /// Re-embed documents whose chunks were embedded by another model or in
+    /// other dimensions than the embedding backend, as after switching models
+    ///
+    /// Returns the repair job id, or `None` when every chunk matches. The
+    /// index stops being stale once the repair job finished.
+    #[instrument(skip(self))]
+    pub async fn reindex_for_embedding_model(&self) -> CodexResult<Option<uuid::Uuid>> {
+        let health = self.indexer.get_index_health().await?;
+        if health.mismatched_chunks == 0 {
+            return Ok(None);
+        }
+
+        info!(
+            "{} chunks differ from embedding model {} ({} dimensions), reindexing",
+            health.mismatched_chunks, health.embedding_model, health.expected_dimensions
+        );
+        self.repair_index().await.map(Some)
+    }
+
+    fn submit_index_repair(&self, document_ids: Vec<String>, mut checkpoint: Checkpoint) -> uuid::Uuid {
+        let db = Arc::clone(&self.db);
+        let indexer = Arc::clone(&self.indexer);
+        let active_operations = Arc::clone(&self.active_operations);
+        self.track_operation(checkpoint.id());
+
+        self.jobs.submit(INDEX_REPAIR_JOB, move |handle| async move {
+            let result = Self::repair_index_job(db, indexer, document_ids, &mut checkpoint, handle).await;
+            active_operations.lock().unwrap().remove(checkpoint.id());
+            match result {
+                Ok(_) => checkpoint.complete().await?,
+                Err(ref e) => checkpoint.fail(&e.to_string()).await?,
+            }
+            result.map(|_| None)
         })
     }
 
+    /// Background body of an index repair job; returns the number of reindexed documents
+    async fn repair_index_job(
+        db: Arc<DatabaseManager>,
+        indexer: Arc<ContentIndexer>,
+        document_ids: Vec<String>,
+        checkpoint: &mut Checkpoint,
+        handle: JobHandle,
+    ) -> CodexResult<usize> {
+        let total = document_ids.len();
+        let mut repaired = 0;
+        handle.report(0.0, Some(format!("Reindexing {} documents", total)));
+
+        for (i, document_id) in document_ids.iter().enumerate() {
+            match crate::db::DocumentQueries::get_by_id(db.pool(), document_id).await? {
+                Some(document) => match indexer.reindex_document(&document).await {
+                    Ok(_) => repaired += 1,
+                    Err(e) => warn!("Failed to reindex document {}: {}", document_id, e),
+                },
+                None => debug!("Document {} was removed before it could be reindexed", document_id),
+            }
+            checkpoint.advance(document_id).await?;
+
+            handle.report(
+                (i + 1) as f32 / total as f32,
+                Some(format!("{}/{} documents", i + 1, total)),
+            );
+        }
+
+        handle.report(1.0, Some(format!("Reindexed {} of {} documents", repaired, total)));
+        info!("Index repair reindexed {} of {} documents", repaired, total);
+
+        db.compact_vector_cache(true).await?;
+        indexer.check_embedding_model().await?;
+        Ok(repaired)
+    }
+
     /// Reindex all documents
+    ///
+    /// Progress is checkpointed per document, so a reindex cut short by a
+    /// crash picks up where it stopped on the next start.
+    #[instrument(skip(self))]
     pub async fn reindex_all_documents(&self) -> CodexResult<()> {
         info!("Starting full reindex of all documents");
 
-        let documents = crate::db::DocumentQueries::get_recent(self.db.pool(), i64::MAX).await?;
-        
-        for document in documents {
-            if let Err(e) = self.indexer.reindex_document(&document).await {
-                error!("Failed to reindex document {}: {}", document.id, e);
+        // Archived documents stay indexed so unarchiving is instant
+        let total = crate::db::DocumentQueries::get_ids_after(self.db.pool(), None).await?.len();
+        let checkpoint = Checkpoint::start(
+            self.db.pool(),
+            checkpoint::KIND_REINDEX_ALL,
+            serde_json::json!({}),
+            Some(total as i64),
+        )
+        .await?;
+        self.run_reindex_all(checkpoint).await
+    }
+
+    async fn run_reindex_all(&self, mut checkpoint: Checkpoint) -> CodexResult<()> {
+        self.track_operation(checkpoint.id());
+        let indexer = Arc::clone(&self.indexer);
+        let result = checkpoint::reindex_documents(self.db.pool(), &mut checkpoint, |document| {
+            let indexer = Arc::clone(&indexer);
+            async move { indexer.reindex_document(&document).await }
+        })
+        .await;
+        self.untrack_operation(checkpoint.id());
+
+        match result {
+            Ok(_) => {
+                checkpoint.complete().await?;
+                info!("Full reindex completed");
+                self.indexer.check_embedding_model().await?;
+                Ok(())
+            }
+            Err(e) => {
+                error!("Full reindex failed: {}", e);
+                checkpoint.fail(&e.to_string()).await?;
+                Err(e)
+            }
+        }
+    }
+
+    /// Operations interrupted by a crash or shutdown that have not been resumed
+    pub async fn get_pending_operations(&self) -> CodexResult<Vec<OperationState>> {
+        let running = crate::db::OperationStateQueries::get_running(self.db.pool()).await?;
+        let active = self.active_operations.lock().unwrap();
+        Ok(running.into_iter().filter(|state| !active.contains(&state.id)).collect())
+    }
+
+    /// Continue an interrupted operation from its last checkpoint
+    ///
+    /// Index repairs run in the background; the other kinds finish before
+    /// this returns.
+    #[instrument(skip(self))]
+    pub async fn resume_operation(&self, operation_id: &str) -> CodexResult<()> {
+        let state = self
+            .get_pending_operations()
+            .await?
+            .into_iter()
+            .find(|state| state.id == operation_id)
+            .ok_or_else(|| CodexError::not_found(format!("Pending operation {}", operation_id)))?;
+        info!("Resuming {} operation {} after {} items", state.kind, state.id, state.processed);
+
+        let kind = state.kind.clone();
+        let checkpoint = Checkpoint::resume(self.db.pool(), state);
+        match kind.as_str() {
+            checkpoint::KIND_REINDEX_ALL => self.run_reindex_all(checkpoint).await,
+            checkpoint::KIND_INDEX_REPAIR => {
+                // Documents already repaired are healthy now and drop out of the list
+                let document_ids = self.indexer.get_unhealthy_documents().await?;
+                self.submit_index_repair(document_ids, checkpoint);
+                Ok(())
+            }
+            checkpoint::KIND_BULK_IMPORT => {
+                let directory = checkpoint.params()["directory"]
+                    .as_str()
+                    .map(std::path::PathBuf::from)
+                    .ok_or_else(|| CodexError::validation("Bulk import checkpoint has no directory"))?;
+                let mut paths = Vec::new();
+                let mut entries = tokio::fs::read_dir(&directory).await?;
+                while let Ok(Some(entry)) = entries.next_entry().await {
+                    let path = entry.path();
+                    if path.is_file() {
+                        paths.push(path);
+                    }
+                }
+                paths.sort();
+                self.run_bulk_import(&directory, paths, checkpoint).await.map(|_| ())
             }
+            other => {
+                checkpoint.fail("Unknown operation kind").await?;
+                Err(CodexError::validation(format!("Cannot resume operation of kind {}", other)))
+            }
+        }
+    }
+
+    /// Give up on an interrupted operation; it is kept as failed
+    pub async fn discard_operation(&self, operation_id: &str) -> CodexResult<()> {
+        let state = crate::db::OperationStateQueries::get_by_id(self.db.pool(), operation_id)
+            .await?
+            .ok_or_else(|| CodexError::not_found(format!("Operation {}", operation_id)))?;
+        Checkpoint::resume(self.db.pool(), state).fail("Discarded by user").await
+    }
+
+    /// Resume interrupted index work; other kinds wait in [`Self::get_pending_operations`]
+    ///
+    /// Called once at startup. Also prunes old finished operations.
+    pub async fn resume_interrupted_operations(&self) -> CodexResult<()> {
+        if let Err(e) = crate::db::OperationStateQueries::prune_finished(
+            self.db.pool(),
+            checkpoint::FINISHED_RETENTION_DAYS,
+        )
+        .await
+        {
+            warn!("Failed to prune finished operations: {}", e);
         }
 
-        info!("Full reindex completed");
+        for state in self.get_pending_operations().await? {
+            if !checkpoint::resumes_automatically(&state.kind) {
+                info!("{} operation {} is waiting to be resumed", state.kind, state.id);
+                continue;
+            }
+            if let Err(e) = self.resume_operation(&state.id).await {
+                warn!("Failed to resume {} operation {}: {}", state.kind, state.id, e);
+            }
+        }
         Ok(())
     }
 
+    fn track_operation(&self, id: &str) {
+        self.active_operations.lock().unwrap().insert(id.to_string());
+    }
+
+    fn untrack_operation(&self, id: &str) {
+        self.active_operations.lock().unwrap().remove(id);
+    }
+
     /// Health check
     pub async fn health_check(&self) -> CodexResult<bool> {
         // Check if all components are healthy
@@ -410,6 +3888,38 @@ impl ContentManager {
         Ok(db_health && ai_health)
     }
 
+    /// Whether image imports can be processed with OCR in this build
+    pub fn ocr_available(&self) -> bool {
+        OcrExtractor::is_available()
+            && self.config.supported_extensions.iter().any(|e| OcrExtractor::is_image_extension(e))
+    }
+
+    /// Whether audio imports can be transcribed in this build
+    pub fn transcription_available(&self) -> bool {
+        Transcriber::is_available()
+            && self.config.supported_extensions.iter().any(|e| Transcriber::is_audio_extension(e))
+    }
+
+    /// Tag code documents with their language and rate them heuristically
+    ///
+    /// Returns false for non-code documents, which get LLM-generated metadata instead.
+    fn apply_code_metadata(document: &mut crate::db::models::Document) -> bool {
+        let Some(language) = CodeLanguage::from_content_type(&document.content_type) else {
+            return false;
+        };
+
+        document.set_tags(vec![language.name().to_string()]);
+        document.difficulty_level = Some(code::assess_code_difficulty(&document.content, language));
+        true
+    }
+
+    fn is_audio_file(path: &Path) -> bool {
+        path.extension()
+            .and_then(|e| e.to_str())
+            .map(Transcriber::is_audio_extension)
+            .unwrap_or(false)
+    }
+
     /// Shutdown content manager
     pub async fn shutdown(&self) -> CodexResult<()> {
         info!("Shutting down content manager");
@@ -419,13 +3929,38 @@ impl ContentManager {
 }
 
 /// Bulk import result
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
 pub struct BulkImportResult {
     pub total_files: usize,
     pub successful_imports: usize,
     pub failed_imports: usize,
     pub imported_documents: Vec<uuid::Uuid>,
+    /// Background jobs started for files that are imported asynchronously (e.g. audio)
+    #[serde(default)]
+    pub queued_jobs: Vec<uuid::Uuid>,
     pub errors: Vec<String>,
+    /// Links in the imported documents that matched no document
+    #[serde(default)]
+    pub unresolved_links: Vec<UnresolvedLink>,
+    /// What a dry run would import; set only for dry runs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plan: Option<BulkImportPlan>,
+}
+
+/// Parsed structure of a structured document
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DocumentStructure {
+    Table(tabular::TableSchema),
+}
+
+impl DocumentStructure {
+    /// Discriminator stored alongside the structure
+    pub fn kind(&self) -> &'static str {
+        match self {
+            DocumentStructure::Table(_) => "table",
+        }
+    }
 }
 
 /// Content statistics
@@ -435,4 +3970,6 @@ pub struct ContentStats {
     pub total_embeddings: u64,
     pub database_size_bytes: u64,
     pub indexed_documents: u64,
+    #[serde(default)]
+    pub index_health: IndexHealth,
 }
\ No newline at end of file
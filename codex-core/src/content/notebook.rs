@@ -0,0 +1,184 @@
+//! Jupyter notebook (.ipynb) rendering for imports
+//!
+//! Notebooks are rendered to markdown: markdown cells verbatim, code cells as
+//! fenced blocks in the kernel language and, optionally, their text outputs.
+//! Rich outputs such as base64-encoded images are dropped. Cells are joined
+//! with an HTML comment separator (hidden by markdown renderers) so the
+//! indexer can keep each cell within a single chunk.
+
+use serde_json::Value;
+
+use crate::{CodexError, CodexResult};
+use crate::ai::embeddings::TextChunk;
+use super::code::chunk_ranges;
+
+/// Content type stored on documents imported from notebooks
+pub const NOTEBOOK_CONTENT_TYPE: &str = "text/x-ipynb";
+
+/// Separator placed between rendered cells
+pub const CELL_SEPARATOR: &str = "\n\n<!-- cell -->\n\n";
+
+/// A notebook rendered to searchable markdown
+#[derive(Debug, Clone)]
+pub struct RenderedNotebook {
+    /// First level-one markdown heading, if any
+    pub title: Option<String>,
+    pub content: String,
+    /// Kernel language used for code fences
+    pub language: String,
+}
+
+/// Render notebook JSON to markdown
+pub fn render_notebook(json: &str, include_outputs: bool) -> CodexResult<RenderedNotebook> {
+    let notebook: Value = serde_json::from_str(json)?;
+
+    let cells = notebook
+        .get("cells")
+        .and_then(|c| c.as_array())
+        .ok_or_else(|| CodexError::content_processing("Notebook has no cells array"))?;
+
+    let language = notebook
+        .pointer("/metadata/kernelspec/language")
+        .or_else(|| notebook.pointer("/metadata/language_info/name"))
+        .and_then(|l| l.as_str())
+        .unwrap_or("python")
+        .to_string();
+
+    let mut title = None;
+    let mut rendered = Vec::new();
+
+    for cell in cells {
+        let source = joined_text(cell.get("source"));
+        let source = source.trim_end();
+
+        let block = match cell.get("cell_type").and_then(|t| t.as_str()) {
+            Some("markdown") => {
+                if title.is_none() {
+                    title = source
+                        .lines()
+                        .find_map(|l| l.strip_prefix("# "))
+                        .map(|h| h.trim().to_string());
+                }
+                source.to_string()
+            }
+            Some("code") => {
+                let mut block = format!("```{}\n{}\n```", language, source);
+                if include_outputs {
+                    let outputs = render_outputs(cell.get("outputs"));
+                    if !outputs.is_empty() {
+                        block.push_str(&format!("\n\n```text\n{}\n```", outputs));
+                    }
+                }
+                block
+            }
+            // Raw cells and unknown types carry no readable content
+            _ => continue,
+        };
+
+        if !source.trim().is_empty() {
+            rendered.push(block);
+        }
+    }
+
+    Ok(RenderedNotebook {
+        title,
+        content: rendered.join(CELL_SEPARATOR),
+        language,
+    })
+}
+
+/// Chunk rendered notebook content without splitting cells across chunks
+pub fn chunk_notebook(content: &str, max_words: usize) -> Vec<TextChunk> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+
+    for (index, _) in content.match_indices(CELL_SEPARATOR) {
+        ranges.push((start, index));
+        start = index + CELL_SEPARATOR.len();
+    }
+    ranges.push((start, content.len()));
+
+    chunk_ranges(content, ranges.into_iter().filter(|(s, e)| e > s), max_words)
+}
+
+/// Collect the plain-text outputs of a code cell
+fn render_outputs(outputs: Option<&Value>) -> String {
+    let Some(outputs) = outputs.and_then(|o| o.as_array()) else {
+        return String::new();
+    };
+
+    let mut texts = Vec::new();
+    for output in outputs {
+        let text = match output.get("output_type").and_then(|t| t.as_str()) {
+            Some("stream") => joined_text(output.get("text")),
+            // Only text/plain is kept; image/* and other rich payloads are base64 noise
+            Some("execute_result") | Some("display_data") => {
+                joined_text(output.pointer("/data/text~1plain"))
+            }
+            Some("error") => format!(
+                "{}: {}",
+                output.get("ename").and_then(|v| v.as_str()).unwrap_or("Error"),
+                output.get("evalue").and_then(|v| v.as_str()).unwrap_or_default()
+            ),
+            _ => String::new(),
+        };
+
+        let text = text.trim_end();
+        if !text.is_empty() {
+            texts.push(text.to_string());
+        }
+    }
+
+    texts.join("\n")
+}
+
+/// Notebook text fields are either a string or a list of line strings
+fn joined_text(value: Option<&Value>) -> String {
+    match value {
+        Some(Value::String(text)) => text.clone(),
+        Some(Value::Array(lines)) => lines.iter().filter_map(|l| l.as_str()).collect(),
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = include_str!("../../tests/fixtures/sample.ipynb");
+
+    #[test]
+    fn test_render_fixture_notebook() {
+        let notebook = render_notebook(FIXTURE, true).unwrap();
+
+        assert_eq!(notebook.title.as_deref(), Some("Orbital Decay Analysis"));
+        assert_eq!(notebook.language, "python");
+        assert!(notebook.content.contains("```python\nimport numpy as np\n\naltitude = 412.5"));
+        assert!(notebook.content.contains("```text\naltitude: 412.5 km\n```"));
+        assert!(notebook.content.contains("<Figure size 640x480 with 1 Axes>\n0.0031"));
+        assert!(!notebook.content.contains("iVBORw0KGgo"));
+        assert_eq!(notebook.content.matches(CELL_SEPARATOR).count(), 3);
+    }
+
+    #[test]
+    fn test_outputs_can_be_excluded() {
+        let notebook = render_notebook(FIXTURE, false).unwrap();
+        assert!(!notebook.content.contains("altitude: 412.5 km"));
+        assert!(notebook.content.contains("## Conclusion"));
+    }
+
+    #[test]
+    fn test_chunks_follow_cell_boundaries() {
+        let notebook = render_notebook(FIXTURE, true).unwrap();
+
+        let chunks = chunk_notebook(&notebook.content, 40);
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks[0].text.starts_with("# Orbital Decay Analysis"));
+        assert!(chunks[0].text.ends_with("```text\naltitude: 412.5 km\n```"));
+        assert!(chunks[1].text.starts_with("```python\ndrag_coefficient"));
+
+        let single = chunk_notebook(&notebook.content, 1000);
+        assert_eq!(single.len(), 1);
+        assert_eq!(single[0].text, notebook.content);
+    }
+}
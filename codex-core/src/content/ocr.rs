@@ -0,0 +1,166 @@
+//! Optical character recognition for image imports
+//!
+//! Text extraction is backed by Tesseract (via `leptess`) and is only compiled
+//! in when the `ocr` cargo feature is enabled. Without the feature the
+//! extractor reports itself as unavailable and image imports are rejected.
+
+use std::path::Path;
+use tracing::debug;
+
+use crate::{CodexError, CodexResult};
+
+/// Image extensions accepted by the OCR pipeline
+pub const OCR_IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "webp"];
+
+/// Text extracted from an image
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct OcrOutput {
+    /// Recognized text
+    pub text: String,
+    /// Mean recognition confidence in the range 0.0-1.0
+    pub confidence: f32,
+}
+
+/// OCR text extractor
+#[derive(Debug, Clone)]
+pub struct OcrExtractor {
+    language: String,
+}
+
+impl OcrExtractor {
+    /// Create a new extractor for the given Tesseract language code (e.g. "eng")
+    pub fn new(language: impl Into<String>) -> Self {
+        Self {
+            language: language.into(),
+        }
+    }
+
+    /// Whether OCR support was compiled into this build
+    pub fn is_available() -> bool {
+        cfg!(feature = "ocr")
+    }
+
+    /// Check whether a file extension is handled by the OCR pipeline
+    pub fn is_image_extension(extension: &str) -> bool {
+        OCR_IMAGE_EXTENSIONS.contains(&extension.to_lowercase().as_str())
+    }
+
+    /// Extract text from an image file
+    ///
+    /// Recognition is CPU-bound, so it runs on the blocking thread pool.
+    pub async fn extract_text(&self, image_path: &Path) -> CodexResult<OcrOutput> {
+        if !image_path.exists() {
+            return Err(CodexError::not_found(format!(
+                "Image file does not exist: {}",
+                image_path.display()
+            )));
+        }
+
+        let path = image_path.to_path_buf();
+        let language = self.language.clone();
+
+        let output = tokio::task::spawn_blocking(move || Self::recognize(&path, &language))
+            .await
+            .map_err(|e| CodexError::internal(format!("OCR task failed: {}", e)))??;
+
+        debug!(
+            "OCR extracted {} characters from {:?} (confidence {:.2})",
+            output.text.len(),
+            image_path,
+            output.confidence
+        );
+
+        Ok(output)
+    }
+
+    #[cfg(feature = "ocr")]
+    fn recognize(path: &Path, language: &str) -> CodexResult<OcrOutput> {
+        let mut engine = leptess::LepTess::new(None, language).map_err(|e| {
+            CodexError::content_processing(format!("Failed to initialize Tesseract: {}", e))
+        })?;
+
+        engine.set_image(path).map_err(|e| {
+            CodexError::content_processing(format!("Failed to load image {}: {}", path.display(), e))
+        })?;
+
+        let text = engine.get_utf8_text().map_err(|e| {
+            CodexError::content_processing(format!("OCR text extraction failed: {}", e))
+        })?;
+
+        // Tesseract reports a 0-100 score; normalize to 0.0-1.0
+        let confidence = (engine.mean_text_conf().clamp(0, 100) as f32) / 100.0;
+
+        Ok(OcrOutput {
+            text: normalize_ocr_text(&text),
+            confidence,
+        })
+    }
+
+    #[cfg(not(feature = "ocr"))]
+    fn recognize(_path: &Path, _language: &str) -> CodexResult<OcrOutput> {
+        Err(CodexError::content_processing(
+            "OCR support is not enabled in this build (enable the `ocr` feature)",
+        ))
+    }
+}
+
+impl Default for OcrExtractor {
+    fn default() -> Self {
+        Self::new("eng")
+    }
+}
+
+/// Collapse the ragged line structure Tesseract produces into paragraphs
+#[cfg_attr(not(feature = "ocr"), allow(dead_code))]
+fn normalize_ocr_text(text: &str) -> String {
+    let mut paragraphs = Vec::new();
+    let mut current = Vec::new();
+
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            if !current.is_empty() {
+                paragraphs.push(current.join(" "));
+                current.clear();
+            }
+        } else {
+            current.push(line);
+        }
+    }
+
+    if !current.is_empty() {
+        paragraphs.push(current.join(" "));
+    }
+
+    paragraphs.join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_image_extension_detection() {
+        assert!(OcrExtractor::is_image_extension("png"));
+        assert!(OcrExtractor::is_image_extension("JPG"));
+        assert!(OcrExtractor::is_image_extension("webp"));
+        assert!(!OcrExtractor::is_image_extension("txt"));
+    }
+
+    #[test]
+    fn test_normalize_ocr_text() {
+        let raw = "First line\nsecond line\n\n\nNext paragraph  \n";
+        assert_eq!(normalize_ocr_text(raw), "First line second line\n\nNext paragraph");
+    }
+
+    #[cfg(not(feature = "ocr"))]
+    #[tokio::test]
+    async fn test_extract_without_feature_fails() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let image = temp_dir.path().join("scan.png");
+        std::fs::write(&image, b"not really a png").unwrap();
+
+        let result = OcrExtractor::default().extract_text(&image).await;
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,404 @@
+//! Document parsing for supported file formats
+//!
+//! Converts files on disk into plain searchable text plus basic metadata
+//! (title, author, content type, size and content hash).
+
+use std::path::Path;
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use sha2::{Digest, Sha256};
+use tracing::{debug, warn};
+
+use crate::{CodexError, CodexResult};
+use crate::config::ContentConfig;
+use super::ocr::OcrExtractor;
+use super::code::CodeLanguage;
+use super::frontmatter::{self, Frontmatter};
+use super::notebook;
+use super::tabular;
+use super::DocumentStructure;
+
+static HTML_TITLE_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?is)<title[^>]*>(.*?)</title>").unwrap());
+static HTML_BLOCK_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?is)<(script|style|head)[^>]*>.*?</(script|style|head)>").unwrap());
+static HTML_BREAK_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?i)<(br|/p|/div|/h[1-6]|/li|/tr)[^>]*>").unwrap());
+static HTML_TAG_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?s)<[^>]+>").unwrap());
+
+/// Result of parsing a file
+#[derive(Debug, Clone)]
+pub struct ParsedDocument {
+    pub title: String,
+    pub content: String,
+    pub content_type: String,
+    pub author: Option<String>,
+    pub language: String,
+    pub file_size: u64,
+    pub file_hash: String,
+    /// Original location of the imported file, when it should be kept as a reference
+    pub source: Option<String>,
+    /// OCR confidence (0.0-1.0) for text recognized from images
+    pub ocr_confidence: Option<f32>,
+    /// Tags derived from the file itself; when set, LLM tagging is skipped
+    pub tags: Option<Vec<String>>,
+    /// Parsed structure for structured formats such as CSV
+    pub structure: Option<DocumentStructure>,
+    /// YAML frontmatter of a markdown file, removed from `content`
+    pub frontmatter: Option<Frontmatter>,
+}
+
+/// Parser turning supported files into [`ParsedDocument`]s
+#[derive(Debug)]
+pub struct ContentParser {
+    config: ContentConfig,
+    ocr: OcrExtractor,
+}
+
+impl ContentParser {
+    /// Create a new content parser
+    pub fn new(config: &ContentConfig) -> Result<Self> {
+        Ok(Self {
+            config: config.clone(),
+            ocr: OcrExtractor::new(config.ocr_language.clone()),
+        })
+    }
+
+    /// Parse a file into a document
+    pub async fn parse_file(&self, file_path: &Path) -> CodexResult<ParsedDocument> {
+        debug!("Parsing file: {:?}", file_path);
+
+        let bytes = tokio::fs::read(file_path).await?;
+        let file_size = bytes.len() as u64;
+        let file_hash = Self::hash_bytes(&bytes);
+
+        let extension = file_path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase())
+            .unwrap_or_default();
+        let fallback_title = file_path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("Untitled")
+            .to_string();
+
+        let mut parsed = ParsedDocument {
+            title: fallback_title,
+            content: String::new(),
+            content_type: "text/plain".to_string(),
+            author: None,
+            language: "en".to_string(),
+            file_size,
+            file_hash,
+            source: None,
+            ocr_confidence: None,
+            tags: None,
+            structure: None,
+            frontmatter: None,
+        };
+
+        if OcrExtractor::is_image_extension(&extension) {
+            return self.parse_image(file_path, parsed).await;
+        }
+
+        let text = String::from_utf8(bytes).map_err(|_| {
+            CodexError::content_processing(format!("File is not valid UTF-8: {}", file_path.display()))
+        })?;
+
+        if let Some(language) = CodeLanguage::from_extension(&extension) {
+            parsed.content_type = language.content_type().to_string();
+            parsed.content = text;
+            // Keep the extension so `mod.rs` and `mod.py` stay distinguishable
+            if let Some(name) = file_path.file_name().and_then(|n| n.to_str()) {
+                parsed.title = name.to_string();
+            }
+            return Ok(parsed);
+        }
+
+        match extension.as_str() {
+            "md" | "markdown" => Self::parse_markdown(&text, &mut parsed),
+            "html" | "htm" => Self::parse_html(&text, &mut parsed),
+            "json" => Self::parse_json(&text, &mut parsed)?,
+            "ipynb" => self.parse_notebook(&text, &mut parsed)?,
+            table if tabular::TABULAR_EXTENSIONS.contains(&table) => {
+                self.parse_table(file_path, &text, table, &mut parsed)?
+            }
+            "txt" | "" => parsed.content = text,
+            other => {
+                return Err(CodexError::content_processing(format!(
+                    "No parser available for .{} files",
+                    other
+                )));
+            }
+        }
+
+        if parsed.content.trim().is_empty() {
+            warn!("Parsed file has no textual content: {:?}", file_path);
+        }
+
+        Ok(parsed)
+    }
+
+    /// Run OCR over an image file
+    async fn parse_image(&self, file_path: &Path, mut parsed: ParsedDocument) -> CodexResult<ParsedDocument> {
+        let output = self.ocr.extract_text(file_path).await?;
+
+        if output.text.trim().is_empty() {
+            return Err(CodexError::content_processing(format!(
+                "No text could be recognized in image: {}",
+                file_path.display()
+            )));
+        }
+
+        let extension = file_path.extension().and_then(|e| e.to_str()).unwrap_or("png");
+        parsed.content_type = Self::image_content_type(extension).to_string();
+        parsed.content = output.text;
+        parsed.ocr_confidence = Some(output.confidence);
+        parsed.source = Some(
+            std::fs::canonicalize(file_path)
+                .unwrap_or_else(|_| file_path.to_path_buf())
+                .display()
+                .to_string(),
+        );
+
+        Ok(parsed)
+    }
+
+    /// Whether an OCR confidence value should be flagged to the user
+    pub fn is_low_confidence(&self, confidence: f32) -> bool {
+        confidence < self.config.ocr_min_confidence
+    }
+
+    fn parse_markdown(text: &str, parsed: &mut ParsedDocument) {
+        parsed.content_type = "text/markdown".to_string();
+
+        // The frontmatter is metadata, not text to search or embed
+        let (frontmatter, body) = frontmatter::parse(text);
+
+        if let Some(heading) = body.lines().find_map(|l| l.strip_prefix("# ")) {
+            let heading = heading.trim();
+            if !heading.is_empty() {
+                parsed.title = heading.to_string();
+            }
+        }
+        if let Some(frontmatter) = frontmatter {
+            if let Some(title) = &frontmatter.title {
+                parsed.title = title.clone();
+            }
+            parsed.author = frontmatter.author.clone();
+            parsed.frontmatter = Some(frontmatter);
+        }
+
+        parsed.content = body.to_string();
+    }
+
+    fn parse_html(text: &str, parsed: &mut ParsedDocument) {
+        parsed.content_type = "text/html".to_string();
+
+        if let Some(title) = HTML_TITLE_RE.captures(text).and_then(|c| c.get(1)) {
+            let title = Self::decode_entities(title.as_str().trim());
+            if !title.is_empty() {
+                parsed.title = title;
+            }
+        }
+
+        parsed.content = Self::html_to_text(text);
+    }
+
+    /// Visible text of an HTML page, one line per block
+    pub fn html_to_text(html: &str) -> String {
+        let body = HTML_BLOCK_RE.replace_all(html, " ");
+        let body = HTML_BREAK_RE.replace_all(&body, "\n");
+        let body = HTML_TAG_RE.replace_all(&body, " ");
+        let body = Self::decode_entities(&body);
+
+        body.lines()
+            .map(|l| l.split_whitespace().collect::<Vec<_>>().join(" "))
+            .filter(|l| !l.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn parse_json(text: &str, parsed: &mut ParsedDocument) -> CodexResult<()> {
+        parsed.content_type = "application/json".to_string();
+
+        let value: serde_json::Value = serde_json::from_str(text)?;
+        if let Some(title) = value.get("title").and_then(|t| t.as_str()) {
+            parsed.title = title.to_string();
+        }
+        if let Some(author) = value.get("author").and_then(|a| a.as_str()) {
+            parsed.author = Some(author.to_string());
+        }
+
+        parsed.content = match value.get("content").and_then(|c| c.as_str()) {
+            Some(content) => content.to_string(),
+            None => serde_json::to_string_pretty(&value)?,
+        };
+
+        Ok(())
+    }
+
+    fn parse_notebook(&self, text: &str, parsed: &mut ParsedDocument) -> CodexResult<()> {
+        let rendered = notebook::render_notebook(text, self.config.notebook_include_outputs)?;
+
+        parsed.content_type = notebook::NOTEBOOK_CONTENT_TYPE.to_string();
+        if let Some(title) = rendered.title {
+            parsed.title = title;
+        }
+        parsed.content = rendered.content;
+
+        Ok(())
+    }
+
+    fn parse_table(
+        &self,
+        file_path: &Path,
+        text: &str,
+        extension: &str,
+        parsed: &mut ParsedDocument,
+    ) -> CodexResult<()> {
+        let delimiter = tabular::delimiter_for_extension(extension).unwrap_or(b',');
+        let schema = tabular::analyze_table(text, delimiter)?;
+
+        parsed.content_type = if delimiter == b'\t' {
+            "text/tab-separated-values".to_string()
+        } else {
+            "text/csv".to_string()
+        };
+
+        // Small tables stay fully searchable; large ones are represented by their summary only
+        let summary = schema.to_summary(&parsed.title);
+        parsed.content = if schema.row_count <= self.config.table_index_row_limit {
+            format!("{}\n\n{}", summary, text.trim_end())
+        } else {
+            summary
+        };
+
+        parsed.tags = Some(schema.column_names());
+        parsed.source = Some(
+            std::fs::canonicalize(file_path)
+                .unwrap_or_else(|_| file_path.to_path_buf())
+                .display()
+                .to_string(),
+        );
+        parsed.structure = Some(DocumentStructure::Table(schema));
+
+        Ok(())
+    }
+
+    pub(crate) fn decode_entities(text: &str) -> String {
+        text.replace("&nbsp;", " ")
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&#39;", "'")
+            .replace("&amp;", "&")
+    }
+
+    fn image_content_type(extension: &str) -> &'static str {
+        match extension.to_lowercase().as_str() {
+            "jpg" | "jpeg" => "image/jpeg",
+            "webp" => "image/webp",
+            _ => "image/png",
+        }
+    }
+
+    /// Compute the SHA-256 hex digest used for duplicate detection
+    pub fn hash_bytes(bytes: &[u8]) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(bytes);
+        format!("{:x}", hasher.finalize())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parser() -> ContentParser {
+        ContentParser::new(&ContentConfig::default()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_parse_text_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("notes.txt");
+        tokio::fs::write(&path, "Plain text body").await.unwrap();
+
+        let parsed = parser().parse_file(&path).await.unwrap();
+        assert_eq!(parsed.title, "notes");
+        assert_eq!(parsed.content_type, "text/plain");
+        assert_eq!(parsed.file_size, 15);
+        assert_eq!(parsed.file_hash.len(), 64);
+        assert!(parsed.source.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_parse_html_strips_markup() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("page.html");
+        let html = "<html><head><title>Page &amp; Title</title></head>\
+                    <body><script>var x = 1;</script><p>Hello <b>world</b></p></body></html>";
+        tokio::fs::write(&path, html).await.unwrap();
+
+        let parsed = parser().parse_file(&path).await.unwrap();
+        assert_eq!(parsed.title, "Page & Title");
+        assert_eq!(parsed.content, "Hello world");
+    }
+
+    #[tokio::test]
+    async fn test_parse_code_file_keeps_source() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("lib.rs");
+        let source = "fn main() {\n    println!(\"hi\");\n}\n";
+        tokio::fs::write(&path, source).await.unwrap();
+
+        let parsed = parser().parse_file(&path).await.unwrap();
+        assert_eq!(parsed.title, "lib.rs");
+        assert_eq!(parsed.content_type, "text/x-rust");
+        assert_eq!(parsed.content, source);
+    }
+
+    #[tokio::test]
+    async fn test_parse_fixture_notebook() {
+        let path = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/sample.ipynb");
+
+        let parsed = parser().parse_file(&path).await.unwrap();
+        assert_eq!(parsed.title, "Orbital Decay Analysis");
+        assert_eq!(parsed.content_type, "text/x-ipynb");
+        assert!(parsed.content.contains("```python\ndrag_coefficient"));
+    }
+
+    #[tokio::test]
+    async fn test_parse_large_csv_keeps_only_summary() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("readings.csv");
+        let mut csv = String::from("sensor,value\n");
+        for i in 0..20 {
+            csv.push_str(&format!("s{},{}\n", i, i * 2));
+        }
+        tokio::fs::write(&path, &csv).await.unwrap();
+
+        let config = ContentConfig {
+            table_index_row_limit: 10,
+            ..ContentConfig::default()
+        };
+        let parsed = ContentParser::new(&config).unwrap().parse_file(&path).await.unwrap();
+
+        assert_eq!(parsed.content_type, "text/csv");
+        assert!(parsed.content.starts_with("Table: readings\nRows: 20"));
+        assert!(!parsed.content.contains("s19,38"));
+        assert_eq!(parsed.tags, Some(vec!["sensor".to_string(), "value".to_string()]));
+        assert!(matches!(parsed.structure, Some(DocumentStructure::Table(ref t)) if t.row_count == 20));
+        assert!(parsed.source.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_low_confidence_threshold() {
+        let parser = parser();
+        assert!(parser.is_low_confidence(0.2));
+        assert!(!parser.is_low_confidence(0.95));
+    }
+}
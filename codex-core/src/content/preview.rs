@@ -0,0 +1,281 @@
+//! Thumbnails of imported originals for the library grid
+//!
+//! PNG thumbnails are rendered from the first page of PDFs and from images,
+//! and cached as `previews/<first two hex digits>/<hash>-<pixels>.png` under
+//! the content directory, keyed by the SHA-256 of the original (the document's
+//! `file_hash`, which is also its attachment id). A missing thumbnail is
+//! rendered on the next request through the background job queue.
+//!
+//! Rendering is only compiled in with the `thumbnails` cargo feature; PDFs
+//! additionally need the Pdfium library on the system. Whenever no thumbnail
+//! can be produced, callers get a [`ThumbnailPlaceholder`] describing what
+//! kind of document to draw instead.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use super::attachments::AttachmentStore;
+use crate::{CodexError, CodexResult};
+
+/// Directory of the thumbnail cache inside the content directory
+pub const PREVIEWS_DIR: &str = "previews";
+
+/// Thumbnail size variants
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThumbnailSize {
+    Small,
+    #[default]
+    Medium,
+    Large,
+}
+
+impl ThumbnailSize {
+    /// Longest edge of the thumbnail in pixels
+    pub fn pixels(self) -> u32 {
+        match self {
+            Self::Small => 128,
+            Self::Medium => 256,
+            Self::Large => 512,
+        }
+    }
+}
+
+/// What to draw for a document without a thumbnail
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThumbnailPlaceholder {
+    Pdf,
+    Image,
+    Audio,
+    Code,
+    Text,
+    Other,
+}
+
+impl ThumbnailPlaceholder {
+    /// Placeholder matching a document's content type
+    pub fn for_content_type(content_type: &str) -> Self {
+        match content_type {
+            "application/pdf" => Self::Pdf,
+            t if t.starts_with("image/") => Self::Image,
+            t if t.starts_with("audio/") => Self::Audio,
+            t if t.starts_with("text/x-") => Self::Code,
+            t if t.starts_with("text/") => Self::Text,
+            _ => Self::Other,
+        }
+    }
+}
+
+/// Thumbnail of a document, or why there is none yet
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Thumbnail {
+    /// Cached PNG thumbnail
+    Ready { path: PathBuf },
+    /// Being rendered by the background job; ask again once it finished
+    Pending { job_id: uuid::Uuid },
+    /// No thumbnail can be rendered for the document
+    Placeholder { placeholder: ThumbnailPlaceholder },
+}
+
+/// Cache key of one size variant of one original
+type VariantKey = (String, ThumbnailSize);
+
+/// Renders and caches thumbnails
+#[derive(Debug)]
+pub struct PreviewGenerator {
+    root: PathBuf,
+    /// Render jobs in progress, so repeated requests share one job
+    pending: Mutex<HashMap<VariantKey, uuid::Uuid>>,
+    /// Variants that failed to render since startup; not retried until restart
+    failed: Mutex<HashSet<VariantKey>>,
+}
+
+impl PreviewGenerator {
+    pub fn new(content_dir: &Path) -> Self {
+        Self {
+            root: content_dir.join(PREVIEWS_DIR),
+            pending: Mutex::new(HashMap::new()),
+            failed: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Whether thumbnail rendering was compiled into this build
+    pub fn is_available() -> bool {
+        cfg!(feature = "thumbnails")
+    }
+
+    /// Whether thumbnails can be rendered from originals of `content_type`
+    pub fn can_render(content_type: &str) -> bool {
+        matches!(content_type, "application/pdf" | "image/png" | "image/jpeg" | "image/webp")
+    }
+
+    /// Location of a cached thumbnail, whether or not it exists
+    pub fn path_for(&self, hash: &str, size: ThumbnailSize) -> CodexResult<PathBuf> {
+        let original = AttachmentStore::relative_path(hash)?;
+        Ok(self.root.join(original.with_file_name(format!("{}-{}.png", hash, size.pixels()))))
+    }
+
+    /// The cached thumbnail, if it was rendered before
+    pub async fn cached(&self, hash: &str, size: ThumbnailSize) -> CodexResult<Option<PathBuf>> {
+        let path = self.path_for(hash, size)?;
+        Ok(tokio::fs::try_exists(&path).await?.then_some(path))
+    }
+
+    /// Whether rendering this variant failed before
+    pub fn has_failed(&self, hash: &str, size: ThumbnailSize) -> bool {
+        self.failed.lock().unwrap().contains(&(hash.to_string(), size))
+    }
+
+    /// Job already rendering this variant
+    pub fn pending_job(&self, hash: &str, size: ThumbnailSize) -> Option<uuid::Uuid> {
+        self.pending.lock().unwrap().get(&(hash.to_string(), size)).copied()
+    }
+
+    /// Remember the job rendering this variant until [`render`](Self::render) finishes
+    pub fn mark_pending(&self, hash: &str, size: ThumbnailSize, job_id: uuid::Uuid) {
+        self.pending.lock().unwrap().insert((hash.to_string(), size), job_id);
+    }
+
+    /// Render and cache a thumbnail of `source`
+    ///
+    /// Rendering is CPU-bound, so it runs on the blocking thread pool. The PNG
+    /// is written next to its final path and renamed into place, so readers
+    /// never see a partial file. A failure is remembered so the variant is
+    /// not attempted again on every request.
+    pub async fn render(&self, source: &Path, content_type: &str, hash: &str, size: ThumbnailSize) -> CodexResult<PathBuf> {
+        let key = (hash.to_string(), size);
+        let rendered = self.render_uncached(source, content_type, hash, size).await;
+
+        self.pending.lock().unwrap().remove(&key);
+        if rendered.is_err() {
+            self.failed.lock().unwrap().insert(key);
+        }
+        rendered
+    }
+
+    async fn render_uncached(&self, source: &Path, content_type: &str, hash: &str, size: ThumbnailSize) -> CodexResult<PathBuf> {
+        if !Self::can_render(content_type) {
+            return Err(CodexError::validation(format!("No thumbnails for {} documents", content_type)));
+        }
+
+        let path = self.path_for(hash, size)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let temp_path = path.with_extension(format!("{}.tmp", uuid::Uuid::new_v4()));
+
+        let source = source.to_path_buf();
+        let content_type = content_type.to_string();
+        let target = temp_path.clone();
+        let rendered = tokio::task::spawn_blocking(move || render_png(&source, &content_type, size.pixels(), &target))
+            .await
+            .map_err(|e| CodexError::internal(format!("Thumbnail task failed: {}", e)))?;
+
+        if let Err(e) = rendered {
+            let _ = tokio::fs::remove_file(&temp_path).await;
+            return Err(e);
+        }
+        tokio::fs::rename(&temp_path, &path).await?;
+
+        debug!("Rendered {}px thumbnail {:?}", size.pixels(), path);
+        Ok(path)
+    }
+}
+
+#[cfg(feature = "thumbnails")]
+fn render_png(source: &Path, content_type: &str, pixels: u32, target: &Path) -> CodexResult<()> {
+    let image = if content_type == "application/pdf" {
+        render_first_page(source, pixels)?
+    } else {
+        image::open(source)
+            .map_err(|e| CodexError::content_processing(format!("Failed to load image {}: {}", source.display(), e)))?
+    };
+
+    image
+        .thumbnail(pixels, pixels)
+        .save_with_format(target, image::ImageFormat::Png)
+        .map_err(|e| CodexError::content_processing(format!("Failed to write thumbnail: {}", e)))
+}
+
+#[cfg(feature = "thumbnails")]
+fn render_first_page(source: &Path, pixels: u32) -> CodexResult<image::DynamicImage> {
+    use pdfium_render::prelude::{PdfRenderConfig, Pdfium};
+
+    let pdf_error = |e: pdfium_render::prelude::PdfiumError| {
+        CodexError::content_processing(format!("Failed to render PDF {}: {}", source.display(), e))
+    };
+
+    let pdfium = Pdfium::new(Pdfium::bind_to_system_library().map_err(pdf_error)?);
+    let document = pdfium.load_pdf_from_file(source, None).map_err(pdf_error)?;
+    let page = document.pages().first().map_err(pdf_error)?;
+    let config = PdfRenderConfig::new()
+        .set_target_width(pixels as i32)
+        .set_maximum_height(pixels as i32);
+
+    let image = page.render_with_config(&config).map_err(pdf_error)?.as_image();
+    Ok(image)
+}
+
+#[cfg(not(feature = "thumbnails"))]
+fn render_png(_source: &Path, _content_type: &str, _pixels: u32, _target: &Path) -> CodexResult<()> {
+    Err(CodexError::content_processing(
+        "Thumbnail rendering is not enabled in this build (enable the `thumbnails` feature)",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_placeholders_follow_content_type() {
+        assert_eq!(ThumbnailPlaceholder::for_content_type("application/pdf"), ThumbnailPlaceholder::Pdf);
+        assert_eq!(ThumbnailPlaceholder::for_content_type("image/webp"), ThumbnailPlaceholder::Image);
+        assert_eq!(ThumbnailPlaceholder::for_content_type("audio/mpeg"), ThumbnailPlaceholder::Audio);
+        assert_eq!(ThumbnailPlaceholder::for_content_type("text/markdown"), ThumbnailPlaceholder::Text);
+        assert_eq!(ThumbnailPlaceholder::for_content_type("application/zip"), ThumbnailPlaceholder::Other);
+    }
+
+    #[tokio::test]
+    async fn test_size_variants_are_cached_separately() {
+        let dir = tempfile::tempdir().unwrap();
+        let previews = PreviewGenerator::new(dir.path());
+        let hash = "c0".repeat(32);
+
+        let small = previews.path_for(&hash, ThumbnailSize::Small).unwrap();
+        let large = previews.path_for(&hash, ThumbnailSize::Large).unwrap();
+        assert_eq!(small, dir.path().join(PREVIEWS_DIR).join("c0").join(format!("{}-128.png", hash)));
+        assert_ne!(small, large);
+        assert!(previews.path_for("../escape", ThumbnailSize::Small).is_err());
+        assert_eq!(previews.cached(&hash, ThumbnailSize::Small).await.unwrap(), None);
+
+        std::fs::create_dir_all(small.parent().unwrap()).unwrap();
+        std::fs::write(&small, b"png").unwrap();
+        assert_eq!(previews.cached(&hash, ThumbnailSize::Small).await.unwrap(), Some(small));
+        assert_eq!(previews.cached(&hash, ThumbnailSize::Large).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_failed_renders_are_not_retried() {
+        let dir = tempfile::tempdir().unwrap();
+        let previews = PreviewGenerator::new(dir.path());
+        let hash = "d1".repeat(32);
+        let job = uuid::Uuid::new_v4();
+
+        previews.mark_pending(&hash, ThumbnailSize::Medium, job);
+        assert_eq!(previews.pending_job(&hash, ThumbnailSize::Medium), Some(job));
+
+        // The original is gone, so rendering fails in every build
+        let missing = dir.path().join("missing.png");
+        assert!(previews.render(&missing, "image/png", &hash, ThumbnailSize::Medium).await.is_err());
+        assert_eq!(previews.pending_job(&hash, ThumbnailSize::Medium), None);
+        assert!(previews.has_failed(&hash, ThumbnailSize::Medium));
+        assert!(!previews.has_failed(&hash, ThumbnailSize::Small));
+    }
+}
@@ -0,0 +1,419 @@
+//! In-memory title index for the quick switcher
+//!
+//! Keystroke lookups have to stay well under 10 ms at 100k documents, which
+//! the full-text path cannot promise, so titles of listed documents are kept
+//! in memory and matched by case-insensitive subsequence, scored like fzf:
+//! matches at word starts and runs of consecutive characters score higher,
+//! gaps lower. Favorites and recently opened documents get a boost on top.
+//!
+//! The index is loaded at startup and kept current by
+//! [`ContentManager`](super::ContentManager) as documents are written.
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::RwLock;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+
+use crate::db::models::{Document, DocumentTitle, DIAGNOSTICS_CATEGORY};
+use crate::db::DocumentQueries;
+use crate::CodexResult;
+
+/// Score of each matched character
+const SCORE_MATCH: f64 = 16.0;
+/// Bonus for a match at the start of a word
+const BONUS_BOUNDARY: f64 = 8.0;
+/// Extra bonus for a match at the start of the title
+const BONUS_FIRST_CHAR: f64 = 8.0;
+/// Bonus for a match right after the previous one
+const BONUS_CONSECUTIVE: f64 = 4.0;
+/// Penalty per skipped character between the first and last match
+const PENALTY_GAP: f64 = 1.0;
+/// Boost of favorite documents
+const BOOST_FAVORITE: f64 = 24.0;
+/// Boost of a document opened just now, halving every [`RECENCY_HALF_LIFE_DAYS`]
+const BOOST_RECENT: f64 = 24.0;
+const RECENCY_HALF_LIFE_DAYS: f64 = 7.0;
+
+/// Match of the quick switcher
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuickFindHit {
+    pub id: String,
+    pub title: String,
+    pub category: Option<String>,
+    pub is_favorite: bool,
+    pub score: f64,
+}
+
+#[derive(Debug, Clone)]
+struct TitleEntry {
+    id: String,
+    title: String,
+    /// Characters of `title`, kept so keystrokes do not decode it again
+    chars: Vec<char>,
+    /// `chars` lowercased one by one, so positions line up
+    folded: Vec<char>,
+    category: Option<String>,
+    is_favorite: bool,
+    last_accessed: Option<DateTime<Utc>>,
+}
+
+impl TitleEntry {
+    fn new(
+        id: String,
+        title: String,
+        category: Option<String>,
+        is_favorite: bool,
+        last_accessed: Option<&str>,
+    ) -> Self {
+        let chars: Vec<char> = title.chars().collect();
+        Self {
+            folded: chars.iter().copied().map(fold).collect(),
+            chars,
+            id,
+            title,
+            category,
+            is_favorite,
+            last_accessed: last_accessed
+                .and_then(|stamp| DateTime::parse_from_rfc3339(stamp).ok())
+                .map(|stamp| stamp.with_timezone(&Utc)),
+        }
+    }
+
+    fn boost(&self, now: DateTime<Utc>) -> f64 {
+        let favorite = if self.is_favorite { BOOST_FAVORITE } else { 0.0 };
+        let recent = self.last_accessed.map_or(0.0, |accessed| {
+            let days = (now - accessed).num_seconds().max(0) as f64 / 86_400.0;
+            BOOST_RECENT * 0.5f64.powf(days / RECENCY_HALF_LIFE_DAYS)
+        });
+        favorite + recent
+    }
+
+    fn hit(&self, score: f64) -> QuickFindHit {
+        QuickFindHit {
+            id: self.id.clone(),
+            title: self.title.clone(),
+            category: self.category.clone(),
+            is_favorite: self.is_favorite,
+            score,
+        }
+    }
+}
+
+fn fold(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+/// Titles of listed documents, see the [module documentation](self)
+#[derive(Debug, Default)]
+pub struct TitleIndex {
+    entries: RwLock<Entries>,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    list: Vec<TitleEntry>,
+    /// Position of each document in `list`
+    positions: HashMap<String, usize>,
+}
+
+impl Entries {
+    fn upsert(&mut self, entry: TitleEntry) {
+        match self.positions.get(&entry.id) {
+            Some(&position) => self.list[position] = entry,
+            None => {
+                self.positions.insert(entry.id.clone(), self.list.len());
+                self.list.push(entry);
+            }
+        }
+    }
+
+    fn remove(&mut self, id: &str) {
+        if let Some(position) = self.positions.remove(id) {
+            self.list.swap_remove(position);
+            if let Some(moved) = self.list.get(position) {
+                self.positions.insert(moved.id.clone(), position);
+            }
+        }
+    }
+}
+
+impl TitleIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the titles of all listed documents
+    ///
+    /// Documents already in the index were written after the load started
+    /// and keep their entry.
+    pub async fn load(&self, pool: &SqlitePool) -> CodexResult<usize> {
+        let rows = DocumentQueries::get_title_entries(pool, None).await?;
+        let count = rows.len();
+        let mut entries = self.entries.write().unwrap();
+        for row in rows {
+            if !entries.positions.contains_key(&row.id) {
+                entries.upsert(Self::entry_from_row(row));
+            }
+        }
+        Ok(count)
+    }
+
+    /// Re-read the given documents, dropping the ones no longer listed
+    pub async fn refresh(&self, pool: &SqlitePool, ids: &[String]) -> CodexResult<()> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+        let rows = DocumentQueries::get_title_entries(pool, Some(ids)).await?;
+        let mut entries = self.entries.write().unwrap();
+        for id in ids {
+            entries.remove(id);
+        }
+        for row in rows {
+            entries.upsert(Self::entry_from_row(row));
+        }
+        Ok(())
+    }
+
+    /// Add or replace the entry of a document just written
+    pub fn upsert(&self, document: &Document) {
+        let mut entries = self.entries.write().unwrap();
+        if document.is_deleted || document.is_archived || document.category.as_deref() == Some(DIAGNOSTICS_CATEGORY) {
+            entries.remove(&document.id);
+            return;
+        }
+        entries.upsert(TitleEntry::new(
+            document.id.clone(),
+            document.title.clone(),
+            document.category.clone(),
+            document.is_favorite,
+            document.last_accessed.as_deref(),
+        ));
+    }
+
+    /// Add or replace an entry from its row
+    pub fn upsert_title(&self, row: DocumentTitle) {
+        self.entries.write().unwrap().upsert(Self::entry_from_row(row));
+    }
+
+    /// Record that a document was opened just now
+    pub fn touch(&self, id: &str) {
+        let mut entries = self.entries.write().unwrap();
+        if let Some(&position) = entries.positions.get(id) {
+            entries.list[position].last_accessed = Some(Utc::now());
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.read().unwrap().list.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Best `limit` titles for `query`, highest score first
+    ///
+    /// An empty query lists favorites and recently opened documents.
+    pub fn find(&self, query: &str, limit: usize) -> Vec<QuickFindHit> {
+        if limit == 0 {
+            return Vec::new();
+        }
+        let pattern: Vec<char> = query.chars().filter(|c| !c.is_whitespace()).map(fold).collect();
+        let now = Utc::now();
+        let entries = self.entries.read().unwrap();
+
+        // Worst of the best `limit` so far on top
+        let mut best: BinaryHeap<Ranked> = BinaryHeap::with_capacity(limit + 1);
+        let mut latest = Vec::with_capacity(pattern.len());
+        for entry in &entries.list {
+            let score = if pattern.is_empty() {
+                match entry.boost(now) {
+                    boost if boost > 0.0 => boost,
+                    _ => continue,
+                }
+            } else {
+                match match_score(&pattern, &entry.chars, &entry.folded, &mut latest) {
+                    Some(score) => score + entry.boost(now),
+                    None => continue,
+                }
+            };
+            let ranked = Ranked { score, entry };
+            if best.len() < limit {
+                best.push(ranked);
+            } else if let Some(mut worst) = best.peek_mut() {
+                if ranked < *worst {
+                    *worst = ranked;
+                }
+            }
+        }
+
+        best.into_sorted_vec().into_iter().map(|ranked| ranked.entry.hit(ranked.score)).collect()
+    }
+
+    fn entry_from_row(row: DocumentTitle) -> TitleEntry {
+        TitleEntry::new(row.id, row.title, row.category, row.is_favorite, row.last_accessed.as_deref())
+    }
+}
+
+/// Scored entry, ordered best first: higher score, then shorter title, then by title
+struct Ranked<'a> {
+    score: f64,
+    entry: &'a TitleEntry,
+}
+
+impl Ord for Ranked<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .score
+            .total_cmp(&self.score)
+            .then(self.entry.title.len().cmp(&other.entry.title.len()))
+            .then_with(|| self.entry.title.cmp(&other.entry.title))
+    }
+}
+
+impl PartialOrd for Ranked<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Ranked<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Ranked<'_> {}
+
+/// Score of `pattern` (folded) as a subsequence of a title, None if it is not one
+///
+/// Each pattern character takes the earliest position that continues a
+/// run of matches, else the earliest word start, else the earliest
+/// occurrence, as long as the rest of the pattern still fits after it.
+/// This finds "Meeting Notes" for "mn" at both initials in linear time.
+/// `latest` is scratch space, reused across titles.
+fn match_score(pattern: &[char], original: &[char], folded: &[char], latest: &mut Vec<usize>) -> Option<f64> {
+    let mut remaining = pattern.iter().peekable();
+    for c in folded {
+        if remaining.peek() == Some(&c) {
+            remaining.next();
+        }
+    }
+    if remaining.peek().is_some() {
+        return None;
+    }
+
+    // Latest position each pattern character can take with the rest still matching
+    latest.clear();
+    latest.resize(pattern.len(), 0);
+    let mut bound = folded.len();
+    for (i, c) in pattern.iter().enumerate().rev() {
+        bound = folded[..bound].iter().rposition(|f| f == c)?;
+        latest[i] = bound;
+    }
+
+    let mut score = 0.0;
+    let mut first = None;
+    let mut previous: Option<usize> = None;
+    for (i, c) in pattern.iter().enumerate() {
+        let from = previous.map_or(0, |p| p + 1);
+        let candidates = || (from..=latest[i]).filter(|&j| folded[j] == *c);
+        let chosen = previous
+            .map(|p| p + 1)
+            .filter(|&j| j <= latest[i] && folded[j] == *c)
+            .or_else(|| candidates().find(|&j| j == 0 || is_word_start(original[j - 1], original[j])))
+            .or_else(|| candidates().next())?;
+
+        score += SCORE_MATCH;
+        if chosen == 0 {
+            score += BONUS_BOUNDARY + BONUS_FIRST_CHAR;
+        } else if is_word_start(original[chosen - 1], original[chosen]) {
+            score += BONUS_BOUNDARY;
+        }
+        if previous.is_some_and(|p| p + 1 == chosen) {
+            score += BONUS_CONSECUTIVE;
+        }
+        first.get_or_insert(chosen);
+        previous = Some(chosen);
+    }
+
+    let span = previous? - first? + 1;
+    Some(score - (span - pattern.len()) as f64 * PENALTY_GAP)
+}
+
+fn is_word_start(previous: char, current: char) -> bool {
+    (!previous.is_alphanumeric() && current.is_alphanumeric())
+        || (previous.is_lowercase() && current.is_uppercase())
+        || (previous.is_alphabetic() && current.is_numeric())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn title(id: &str, title: &str, is_favorite: bool) -> DocumentTitle {
+        DocumentTitle {
+            id: id.to_string(),
+            title: title.to_string(),
+            category: None,
+            is_favorite,
+            last_accessed: None,
+        }
+    }
+
+    fn ids(hits: &[QuickFindHit]) -> Vec<&str> {
+        hits.iter().map(|hit| hit.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_word_starts_beat_scattered_matches() {
+        let index = TitleIndex::new();
+        index.upsert_title(title("scattered", "Most random notebook", false));
+        index.upsert_title(title("initials", "Meeting Notes", false));
+        index.upsert_title(title("prefix", "Man pages", false));
+        index.upsert_title(title("unrelated", "Grocery list", false));
+
+        assert_eq!(ids(&index.find("mn", 10)), vec!["initials", "prefix", "scattered"]);
+        assert_eq!(ids(&index.find("MAN", 10))[0], "prefix");
+        assert!(index.find("xyz", 10).is_empty());
+        assert_eq!(index.find("m n", 1).len(), 1);
+    }
+
+    #[test]
+    fn test_favorites_and_recent_documents_are_boosted() {
+        let index = TitleIndex::new();
+        index.upsert_title(title("plain", "Project plan", false));
+        index.upsert_title(title("favorite", "Project plan draft", true));
+        index.upsert_title(title("other", "Reading list", false));
+
+        assert_eq!(ids(&index.find("plan", 10)), vec!["favorite", "plain"]);
+
+        index.touch("other");
+        let hits = index.find("", 10);
+        let mut listed = ids(&hits);
+        listed.sort_unstable();
+        assert_eq!(listed, vec!["favorite", "other"]);
+    }
+
+    #[test]
+    fn test_entries_are_replaced_and_removed() {
+        let index = TitleIndex::new();
+        index.upsert_title(title("a", "Alpha", false));
+        index.upsert_title(title("b", "Beta", false));
+        index.upsert_title(title("c", "Gamma", false));
+        index.upsert_title(title("a", "Alpha renamed", false));
+        assert_eq!(index.len(), 3);
+        assert_eq!(index.find("renamed", 10)[0].id, "a");
+
+        let mut deleted = Document::new("Beta".to_string(), String::new(), "text/plain".to_string());
+        deleted.id = "b".to_string();
+        deleted.is_deleted = true;
+        index.upsert(&deleted);
+        assert_eq!(index.len(), 2);
+        assert!(index.find("beta", 10).is_empty());
+        assert_eq!(index.find("gamma", 10)[0].id, "c");
+    }
+}
@@ -0,0 +1,153 @@
+//! Reading streaks and weekly reading goals
+//!
+//! The reader view opens a session when it shows a document and ends it
+//! when the document is closed (see [`ReadingSessionQueries`]). Streaks count
+//! consecutive local calendar days with at least one session; the weekly
+//! goal compares minutes read since Monday with a target kept in settings.
+//!
+//! [`ReadingSessionQueries`]: crate::db::ReadingSessionQueries
+
+use chrono::{Datelike, Local, NaiveDate, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Setting holding the weekly reading goal in minutes
+pub const WEEKLY_GOAL_SETTING: &str = "reading.weekly_goal_minutes";
+
+/// Reading session bookkeeping
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReadingConfig {
+    /// Sessions shorter than this are discarded as glances
+    pub min_session_seconds: u64,
+}
+
+impl Default for ReadingConfig {
+    fn default() -> Self {
+        Self {
+            min_session_seconds: 30,
+        }
+    }
+}
+
+/// Consecutive days of reading
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReadingStreak {
+    /// Days in the streak that includes today or yesterday, 0 if broken
+    pub current_days: u32,
+    pub longest_days: u32,
+    pub read_today: bool,
+}
+
+/// Progress towards the weekly reading goal
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GoalProgress {
+    /// Goal in minutes per week, None when no goal is set
+    pub goal_minutes: Option<u32>,
+    /// Minutes read since the start of the week
+    pub read_minutes: u64,
+    /// Share of the goal reached, capped at 1.0; 0.0 without a goal
+    pub fraction: f64,
+    pub met: bool,
+    /// Monday the week started on
+    pub week_start: NaiveDate,
+}
+
+impl GoalProgress {
+    pub fn new(goal_minutes: Option<u32>, read_minutes: u64, week_start: NaiveDate) -> Self {
+        let fraction = match goal_minutes {
+            Some(goal) if goal > 0 => (read_minutes as f64 / goal as f64).min(1.0),
+            _ => 0.0,
+        };
+        Self {
+            goal_minutes,
+            read_minutes,
+            fraction,
+            met: goal_minutes.is_some_and(|goal| read_minutes >= goal as u64),
+            week_start,
+        }
+    }
+}
+
+/// Streaks from the days with reading, most recent first
+///
+/// A streak that ended yesterday is still current, so it is not lost
+/// before the user had a chance to read today.
+pub fn streak_from_days(days: &[NaiveDate], today: NaiveDate) -> ReadingStreak {
+    let mut longest = 0;
+    let mut run = 0;
+    let mut previous: Option<NaiveDate> = None;
+    let mut current = None;
+
+    for &day in days {
+        run = match previous {
+            Some(later) if later.pred_opt() == Some(day) => run + 1,
+            Some(later) if later == day => run,
+            _ => {
+                if previous.is_some() && current.is_none() {
+                    current = Some(run);
+                }
+                1
+            }
+        };
+        longest = longest.max(run);
+        previous = Some(day);
+    }
+
+    let read_today = days.first() == Some(&today);
+    let latest_counts = days
+        .first()
+        .is_some_and(|&latest| latest == today || today.pred_opt() == Some(latest));
+    let current_days = if latest_counts { current.unwrap_or(run) } else { 0 };
+
+    ReadingStreak {
+        current_days,
+        longest_days: longest,
+        read_today,
+    }
+}
+
+/// Monday of the current local week and its start as a UTC instant
+pub fn current_week_start() -> (NaiveDate, chrono::DateTime<Utc>) {
+    let today = Local::now().date_naive();
+    let monday = today - chrono::Duration::days(today.weekday().num_days_from_monday() as i64);
+    let midnight = monday.and_hms_opt(0, 0, 0).unwrap_or_default();
+    let start = Local
+        .from_local_datetime(&midnight)
+        .earliest()
+        .map(|at| at.with_timezone(&Utc))
+        .unwrap_or_else(|| Utc.from_utc_datetime(&midnight));
+    (monday, start)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 5, d).unwrap()
+    }
+
+    #[test]
+    fn test_streaks_count_consecutive_days() {
+        let days = [day(20), day(19), day(18), day(15), day(14), day(13), day(12)];
+
+        let today = streak_from_days(&days, day(20));
+        assert_eq!(today, ReadingStreak { current_days: 3, longest_days: 4, read_today: true });
+
+        // Not read yet today: the streak still stands until the day is over
+        let tomorrow = streak_from_days(&days, day(21));
+        assert_eq!((tomorrow.current_days, tomorrow.read_today), (3, false));
+
+        assert_eq!(streak_from_days(&days, day(22)).current_days, 0);
+        assert_eq!(streak_from_days(&[], day(22)), ReadingStreak { current_days: 0, longest_days: 0, read_today: false });
+    }
+
+    #[test]
+    fn test_goal_progress() {
+        let progress = GoalProgress::new(Some(120), 90, day(20));
+        assert_eq!((progress.fraction, progress.met), (0.75, false));
+        assert!(GoalProgress::new(Some(120), 150, day(20)).met);
+        assert_eq!(GoalProgress::new(Some(120), 150, day(20)).fraction, 1.0);
+        assert_eq!(GoalProgress::new(None, 150, day(20)).fraction, 0.0);
+    }
+}
@@ -0,0 +1,347 @@
+//! RSS and Atom feed subscriptions
+//!
+//! Subscribed feeds are fetched on a schedule with conditional GETs, so an
+//! unchanged feed costs one 304. New entries are imported by
+//! [`crate::content::ContentManager::refresh_feeds`] as HTML documents
+//! tagged with the feed's name; entries already seen (by GUID/id, falling
+//! back to link) are skipped.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tracing::{debug, info};
+
+use crate::db::models::Feed;
+use crate::db::{DatabaseManager, FeedSubscriptionQueries};
+use crate::{CodexError, CodexResult};
+
+/// Time allowed for fetching one feed
+pub const FEED_FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+/// Capacity of the new-items broadcast channel
+const EVENT_CHANNEL_CAPACITY: usize = 32;
+
+/// Periodic fetching of subscribed feeds
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FeedSchedule {
+    /// Fetch feeds in the background (never in offline mode)
+    pub enabled: bool,
+    /// Minutes between fetches
+    pub interval_minutes: u64,
+}
+
+impl Default for FeedSchedule {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_minutes: 60,
+        }
+    }
+}
+
+/// One post of a feed
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeedEntry {
+    /// GUID or Atom id, or the link when the feed has neither
+    pub key: String,
+    pub title: String,
+    pub link: Option<String>,
+    /// Full content when the feed has it, otherwise the summary (HTML)
+    pub content_html: String,
+    pub author: Option<String>,
+    pub published: Option<DateTime<Utc>>,
+}
+
+/// A parsed RSS or Atom document
+#[derive(Debug, Clone, Default)]
+pub struct ParsedFeed {
+    pub title: Option<String>,
+    pub entries: Vec<FeedEntry>,
+}
+
+/// Result of a conditional fetch
+#[derive(Debug)]
+pub enum FetchOutcome {
+    /// The server answered 304; nothing changed since the last fetch
+    NotModified,
+    Fetched {
+        feed: ParsedFeed,
+        etag: Option<String>,
+        last_modified: Option<String>,
+    },
+}
+
+/// Documents imported from a feed in one refresh, sent as an event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeedNewItems {
+    pub feed_id: String,
+    pub feed_name: String,
+    pub document_ids: Vec<uuid::Uuid>,
+}
+
+/// Outcome of refreshing one feed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeedRefreshResult {
+    pub feed_id: String,
+    pub feed_name: String,
+    pub new_documents: Vec<uuid::Uuid>,
+    pub error: Option<String>,
+}
+
+/// Keeps the subscription list and fetches feeds
+#[derive(Debug)]
+pub struct FeedManager {
+    db: Arc<DatabaseManager>,
+    client: reqwest::Client,
+    events: broadcast::Sender<FeedNewItems>,
+}
+
+impl FeedManager {
+    /// Create a new feed manager
+    pub fn new(db: Arc<DatabaseManager>) -> CodexResult<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(FEED_FETCH_TIMEOUT)
+            .user_agent("Codex-Vault/1.0")
+            .build()?;
+
+        Ok(Self {
+            db,
+            client,
+            events: broadcast::channel(EVENT_CHANNEL_CAPACITY).0,
+        })
+    }
+
+    /// Subscribe to a feed, named after its title unless `name` is given
+    ///
+    /// The feed is fetched once to check that it parses.
+    pub async fn subscribe(&self, url: &str, name: Option<String>) -> CodexResult<Feed> {
+        let url = url.trim();
+        if !(url.starts_with("http://") || url.starts_with("https://")) {
+            return Err(CodexError::validation(format!("Not a web address: {}", url)));
+        }
+        if FeedSubscriptionQueries::get_by_url(self.db.pool(), url).await?.is_some() {
+            return Err(CodexError::validation(format!("Already subscribed to {}", url)));
+        }
+
+        let parsed = match self.fetch(&Feed::new(String::new(), url.to_string())).await? {
+            FetchOutcome::Fetched { feed, .. } => feed,
+            FetchOutcome::NotModified => ParsedFeed::default(),
+        };
+        let name = name
+            .map(|n| n.trim().to_string())
+            .filter(|n| !n.is_empty())
+            .or(parsed.title)
+            .unwrap_or_else(|| url.to_string());
+
+        let feed = Feed::new(name, url.to_string());
+        FeedSubscriptionQueries::create(self.db.pool(), &feed).await?;
+        info!("Subscribed to feed {} ({})", feed.name, feed.url);
+        Ok(feed)
+    }
+
+    /// Remove a subscription; documents already imported stay
+    pub async fn unsubscribe(&self, feed_id: &str) -> CodexResult<()> {
+        if !FeedSubscriptionQueries::delete(self.db.pool(), feed_id).await? {
+            return Err(CodexError::not_found(format!("Feed {} not found", feed_id)));
+        }
+        Ok(())
+    }
+
+    /// All subscriptions
+    pub async fn list(&self) -> CodexResult<Vec<Feed>> {
+        FeedSubscriptionQueries::get_all(self.db.pool()).await
+    }
+
+    /// Fetch a feed, sending the validators from its last response
+    pub async fn fetch(&self, feed: &Feed) -> CodexResult<FetchOutcome> {
+        let mut request = self.client.get(&feed.url);
+        if let Some(etag) = &feed.etag {
+            request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &feed.last_modified {
+            request = request.header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+        }
+
+        let response = request.send().await?;
+        if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+            debug!("Feed {} not modified", feed.url);
+            return Ok(FetchOutcome::NotModified);
+        }
+        let response = response.error_for_status()?;
+
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|v: &reqwest::header::HeaderValue| v.to_str().ok())
+                .map(str::to_string)
+        };
+        let etag = header(reqwest::header::ETAG);
+        let last_modified = header(reqwest::header::LAST_MODIFIED);
+
+        let feed = parse_feed(&response.text().await?)?;
+        Ok(FetchOutcome::Fetched { feed, etag, last_modified })
+    }
+
+    /// Subscribe to documents imported by feed refreshes
+    pub fn subscribe_new_items(&self) -> broadcast::Receiver<FeedNewItems> {
+        self.events.subscribe()
+    }
+
+    /// Announce newly imported entries; dropped when nobody listens
+    pub fn notify(&self, items: FeedNewItems) {
+        let _ = self.events.send(items);
+    }
+}
+
+/// Parse an RSS 2.0, RSS 1.0 (RDF) or Atom document
+///
+/// Elements are matched by local name, so namespace prefixes do not matter.
+pub fn parse_feed(xml: &str) -> CodexResult<ParsedFeed> {
+    let document = roxmltree::Document::parse(xml)
+        .map_err(|e| CodexError::content_processing(format!("Invalid feed XML: {}", e)))?;
+    let root = document.root_element();
+
+    let (channel, entry_name) = match root.tag_name().name() {
+        "feed" => (root, "entry"),
+        "rss" => (child(root, "channel").unwrap_or(root), "item"),
+        // RSS 1.0 items are siblings of the channel
+        "RDF" => (root, "item"),
+        other => {
+            return Err(CodexError::content_processing(format!("Not an RSS or Atom feed: <{}>", other)));
+        }
+    };
+
+    let title = child(child(root, "channel").unwrap_or(channel), "title").map(node_text);
+    let entries = channel
+        .children()
+        .filter(|n| n.is_element() && n.tag_name().name() == entry_name)
+        .filter_map(parse_entry)
+        .collect();
+
+    Ok(ParsedFeed { title, entries })
+}
+
+fn parse_entry(node: roxmltree::Node) -> Option<FeedEntry> {
+    let link = child_text(node, "link").filter(|l| !l.is_empty()).or_else(|| {
+        // Atom: <link href="..."/>, preferring the alternate (page) link
+        node.children()
+            .filter(|n| n.is_element() && n.tag_name().name() == "link")
+            .find(|n| n.attribute("rel").map(|rel| rel == "alternate").unwrap_or(true))
+            .and_then(|n| n.attribute("href"))
+            .map(str::to_string)
+    });
+
+    let key = child_text(node, "guid")
+        .or_else(|| child_text(node, "id"))
+        .or_else(|| node.attribute(("http://www.w3.org/1999/02/22-rdf-syntax-ns#", "about")).map(str::to_string))
+        .filter(|k| !k.is_empty())
+        .or_else(|| link.clone())?;
+
+    let content_html = ["encoded", "content", "description", "summary"]
+        .iter()
+        .find_map(|name| child_text(node, name).filter(|t| !t.trim().is_empty()))
+        .unwrap_or_default();
+
+    let published = ["pubDate", "published", "date", "updated"]
+        .iter()
+        .find_map(|name| child_text(node, name))
+        .and_then(|date| parse_date(&date));
+
+    let author = child(node, "author")
+        .map(|author| child_text(author, "name").unwrap_or_else(|| node_text(author)))
+        .or_else(|| child_text(node, "creator"))
+        .filter(|a| !a.is_empty());
+
+    let title = child_text(node, "title")
+        .filter(|t| !t.is_empty())
+        .or_else(|| link.clone())
+        .unwrap_or_else(|| key.clone());
+
+    Some(FeedEntry { key, title, link, content_html, author, published })
+}
+
+fn child<'a, 'input>(node: roxmltree::Node<'a, 'input>, name: &str) -> Option<roxmltree::Node<'a, 'input>> {
+    node.children().find(|n| n.is_element() && n.tag_name().name() == name)
+}
+
+fn child_text(node: roxmltree::Node, name: &str) -> Option<String> {
+    child(node, name).map(node_text)
+}
+
+/// Text of an element, including CDATA and (for Atom XHTML content) nested markup's text
+fn node_text(node: roxmltree::Node) -> String {
+    node.descendants()
+        .filter(|n| n.is_text())
+        .filter_map(|n| n.text())
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+fn parse_date(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc2822(value.trim())
+        .or_else(|_| DateTime::parse_from_rfc3339(value.trim()))
+        .ok()
+        .map(|date| date.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rss_and_atom() {
+        let rss = r#"<?xml version="1.0"?>
+            <rss version="2.0" xmlns:content="http://purl.org/rss/1.0/modules/content/">
+              <channel>
+                <title>Systems Blog</title>
+                <item>
+                  <title>Zero-copy parsing</title>
+                  <link>https://blog.example.com/zero-copy</link>
+                  <guid isPermaLink="false">post-42</guid>
+                  <pubDate>Tue, 10 Oct 2023 08:00:00 GMT</pubDate>
+                  <description>Short teaser</description>
+                  <content:encoded><![CDATA[<p>The <b>full</b> article</p>]]></content:encoded>
+                </item>
+                <item>
+                  <title>No guid</title>
+                  <link>https://blog.example.com/no-guid</link>
+                </item>
+              </channel>
+            </rss>"#;
+        let feed = parse_feed(rss).unwrap();
+        assert_eq!(feed.title.as_deref(), Some("Systems Blog"));
+        assert_eq!(feed.entries.len(), 2);
+        assert_eq!(feed.entries[0].key, "post-42");
+        assert_eq!(feed.entries[0].content_html, "<p>The <b>full</b> article</p>");
+        assert_eq!(feed.entries[0].published.unwrap().to_rfc3339(), "2023-10-10T08:00:00+00:00");
+        assert_eq!(feed.entries[1].key, "https://blog.example.com/no-guid");
+
+        let atom = r#"<?xml version="1.0" encoding="utf-8"?>
+            <feed xmlns="http://www.w3.org/2005/Atom">
+              <title>Notes</title>
+              <entry>
+                <title>Atom entry</title>
+                <id>urn:uuid:1225c695</id>
+                <link rel="self" href="https://notes.example.com/feed/1"/>
+                <link href="https://notes.example.com/1"/>
+                <updated>2023-12-13T18:30:02Z</updated>
+                <author><name>Sam</name></author>
+                <summary>Entry summary</summary>
+              </entry>
+            </feed>"#;
+        let feed = parse_feed(atom).unwrap();
+        assert_eq!(feed.title.as_deref(), Some("Notes"));
+        let entry = &feed.entries[0];
+        assert_eq!(entry.key, "urn:uuid:1225c695");
+        assert_eq!(entry.link.as_deref(), Some("https://notes.example.com/1"));
+        assert_eq!(entry.author.as_deref(), Some("Sam"));
+        assert_eq!(entry.content_html, "Entry summary");
+
+        assert!(parse_feed("<html><body/></html>").is_err());
+    }
+}
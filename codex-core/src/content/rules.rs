@@ -0,0 +1,357 @@
+//! Automation rules applied on import
+//!
+//! A rule matches imported documents by source path, title, detected
+//! language, content type and size, and sets their category, adds tags,
+//! archives them or skips AI enrichment. Enabled rules run on every import
+//! in ascending priority: tags and flags accumulate, and the category of the
+//! highest-priority matching rule wins.
+
+use std::path::{Path, PathBuf};
+use chrono::Utc;
+use regex::RegexBuilder;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{CodexError, CodexResult};
+use crate::db::models::{Document, Rule};
+
+/// Conditions a document must meet; unset conditions always hold
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RuleConditions {
+    /// Glob matched against the source file path (`~` is the home directory).
+    /// A pattern without wildcards matches every file inside that directory.
+    pub source_glob: Option<String>,
+    /// Case-insensitive regular expression searched in the title
+    pub title_regex: Option<String>,
+    /// Detected language code, e.g. "en"
+    pub language: Option<String>,
+    /// Content type, e.g. "application/pdf"; a trailing `/*` matches a family
+    pub content_type: Option<String>,
+    pub min_size_bytes: Option<i64>,
+    pub max_size_bytes: Option<i64>,
+}
+
+/// Changes applied to matching documents
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RuleActions {
+    pub category: Option<String>,
+    /// Tags added to the document
+    pub tags: Vec<String>,
+    pub archive: bool,
+    /// Skip the AI-generated summary, tags and difficulty
+    pub skip_ai: bool,
+}
+
+/// Automation rule with parsed conditions and actions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AutomationRule {
+    pub id: String,
+    pub name: String,
+    /// Rules run in ascending priority, so higher priorities win conflicts
+    pub priority: i64,
+    pub enabled: bool,
+    pub conditions: RuleConditions,
+    pub actions: RuleActions,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl AutomationRule {
+    /// Create a new rule
+    pub fn new(name: String, priority: i64, conditions: RuleConditions, actions: RuleActions) -> Self {
+        let now = Utc::now().to_rfc3339();
+        Self {
+            id: Uuid::new_v4().to_string(),
+            name,
+            priority,
+            enabled: true,
+            conditions,
+            actions,
+            created_at: now.clone(),
+            updated_at: now,
+        }
+    }
+
+    /// Check that the rule is well formed before it is stored
+    pub fn validate(&self) -> CodexResult<()> {
+        if self.name.trim().is_empty() {
+            return Err(CodexError::validation("Rule name cannot be empty"));
+        }
+        if self.actions == RuleActions::default() {
+            return Err(CodexError::validation("Rule has no actions"));
+        }
+        if let Some(ref pattern) = self.conditions.source_glob {
+            source_matches(pattern, Path::new(""))?;
+        }
+        // Compiles the title pattern
+        self.conditions.evaluate(&RuleSubject::default())?;
+        Ok(())
+    }
+
+    /// Convert to the stored row
+    pub fn to_row(&self) -> CodexResult<Rule> {
+        Ok(Rule {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            priority: self.priority,
+            enabled: self.enabled,
+            conditions: serde_json::to_string(&self.conditions)?,
+            actions: serde_json::to_string(&self.actions)?,
+            created_at: self.created_at.clone(),
+            updated_at: self.updated_at.clone(),
+        })
+    }
+}
+
+impl TryFrom<Rule> for AutomationRule {
+    type Error = CodexError;
+
+    fn try_from(rule: Rule) -> CodexResult<Self> {
+        Ok(Self {
+            conditions: serde_json::from_str(&rule.conditions)?,
+            actions: serde_json::from_str(&rule.actions)?,
+            id: rule.id,
+            name: rule.name,
+            priority: rule.priority,
+            enabled: rule.enabled,
+            created_at: rule.created_at,
+            updated_at: rule.updated_at,
+        })
+    }
+}
+
+/// Properties of a document that rules are matched against
+#[derive(Debug, Clone, Default)]
+pub struct RuleSubject {
+    pub source_path: Option<PathBuf>,
+    pub title: String,
+    pub language: Option<String>,
+    pub content_type: String,
+    pub size_bytes: Option<i64>,
+}
+
+impl RuleSubject {
+    /// Subject for a document, with the file it was imported from if known
+    pub fn from_document(document: &Document, source_path: Option<&Path>) -> Self {
+        Self {
+            source_path: source_path.map(Path::to_path_buf),
+            title: document.title.clone(),
+            language: Some(document.language.clone()).filter(|l| !l.is_empty()),
+            content_type: document.content_type.clone(),
+            size_bytes: document.file_size,
+        }
+    }
+}
+
+/// Whether one condition held
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConditionResult {
+    /// Condition name, e.g. "title_regex"
+    pub condition: String,
+    pub matched: bool,
+    /// Value the condition was checked against
+    pub actual: Option<String>,
+}
+
+impl RuleConditions {
+    /// Check every set condition; errors on an invalid glob or regex
+    pub fn evaluate(&self, subject: &RuleSubject) -> CodexResult<Vec<ConditionResult>> {
+        let mut results = Vec::new();
+        let mut check = |condition: &str, matched: bool, actual: Option<String>| {
+            results.push(ConditionResult { condition: condition.to_string(), matched, actual });
+        };
+
+        if let Some(ref pattern) = self.source_glob {
+            let matched = match subject.source_path {
+                Some(ref path) => source_matches(pattern, path)?,
+                None => false,
+            };
+            check("source_glob", matched, subject.source_path.as_ref().map(|p| p.display().to_string()));
+        }
+        if let Some(ref pattern) = self.title_regex {
+            let regex = RegexBuilder::new(pattern)
+                .case_insensitive(true)
+                .build()
+                .map_err(|e| CodexError::validation(format!("Invalid title pattern: {}", e)))?;
+            check("title_regex", regex.is_match(&subject.title), Some(subject.title.clone()));
+        }
+        if let Some(ref language) = self.language {
+            let matched = subject.language.as_deref().is_some_and(|l| l.eq_ignore_ascii_case(language));
+            check("language", matched, subject.language.clone());
+        }
+        if let Some(ref content_type) = self.content_type {
+            let matched = match content_type.strip_suffix("/*") {
+                Some(family) => subject.content_type.split('/').next() == Some(family),
+                None => subject.content_type.eq_ignore_ascii_case(content_type),
+            };
+            check("content_type", matched, Some(subject.content_type.clone()));
+        }
+        if let Some(min) = self.min_size_bytes {
+            check("min_size_bytes", subject.size_bytes.is_some_and(|s| s >= min), subject.size_bytes.map(|s| s.to_string()));
+        }
+        if let Some(max) = self.max_size_bytes {
+            check("max_size_bytes", subject.size_bytes.is_some_and(|s| s <= max), subject.size_bytes.map(|s| s.to_string()));
+        }
+
+        Ok(results)
+    }
+}
+
+fn source_matches(pattern: &str, path: &Path) -> CodexResult<bool> {
+    let pattern = match pattern.strip_prefix("~/") {
+        Some(rest) => directories::BaseDirs::new()
+            .map(|dirs| dirs.home_dir().join(rest).display().to_string())
+            .unwrap_or_else(|| pattern.to_string()),
+        None => pattern.to_string(),
+    };
+
+    if !pattern.contains(['*', '?', '[']) {
+        return Ok(path.starts_with(&pattern));
+    }
+    let glob = glob::Pattern::new(&pattern)
+        .map_err(|e| CodexError::validation(format!("Invalid source pattern: {}", e)))?;
+    Ok(glob.matches_path(path))
+}
+
+/// Combined actions of the rules that matched a document
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RuleOutcome {
+    /// Ids of the matching rules, in the order they ran
+    pub matched_rules: Vec<String>,
+    pub actions: RuleActions,
+}
+
+impl RuleOutcome {
+    /// Run rules (already in priority order) against a subject
+    ///
+    /// Rules with invalid conditions are skipped.
+    pub fn evaluate(rules: &[AutomationRule], subject: &RuleSubject) -> Self {
+        let mut outcome = Self::default();
+        for rule in rules {
+            match rule.conditions.evaluate(subject) {
+                Ok(results) if results.iter().all(|r| r.matched) => {
+                    let actions = &rule.actions;
+                    if actions.category.is_some() {
+                        outcome.actions.category = actions.category.clone();
+                    }
+                    for tag in &actions.tags {
+                        if !outcome.actions.tags.contains(tag) {
+                            outcome.actions.tags.push(tag.clone());
+                        }
+                    }
+                    outcome.actions.archive |= actions.archive;
+                    outcome.actions.skip_ai |= actions.skip_ai;
+                    outcome.matched_rules.push(rule.id.clone());
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!("Skipping rule {} ({}): {}", rule.name, rule.id, e),
+            }
+        }
+        outcome
+    }
+
+    /// Apply the category, tag and archive actions to a document
+    pub fn apply(&self, document: &mut Document) {
+        if let Some(ref category) = self.actions.category {
+            document.category = Some(category.clone());
+        }
+        if !self.actions.tags.is_empty() {
+            let mut tags = document.get_tags();
+            for tag in &self.actions.tags {
+                if !tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+                    tags.push(tag.clone());
+                }
+            }
+            document.set_tags(tags);
+        }
+        if self.actions.archive {
+            document.is_archived = true;
+        }
+    }
+}
+
+/// Preview of a rule against an existing document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleTestResult {
+    pub matched: bool,
+    pub conditions: Vec<ConditionResult>,
+    /// Category and tags the document would end up with
+    pub resulting_category: Option<String>,
+    pub resulting_tags: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subject() -> RuleSubject {
+        RuleSubject {
+            source_path: Some(PathBuf::from("/data/Papers/2024/attention.pdf")),
+            title: "Attention Is All You Need".to_string(),
+            language: Some("en".to_string()),
+            content_type: "application/pdf".to_string(),
+            size_bytes: Some(2_000_000),
+        }
+    }
+
+    #[test]
+    fn test_conditions_match_each_property() {
+        let conditions = RuleConditions {
+            source_glob: Some("/data/Papers".to_string()),
+            title_regex: Some("attention".to_string()),
+            language: Some("EN".to_string()),
+            content_type: Some("application/*".to_string()),
+            min_size_bytes: Some(1_000_000),
+            max_size_bytes: None,
+        };
+        let results = conditions.evaluate(&subject()).unwrap();
+        assert_eq!(results.len(), 5);
+        assert!(results.iter().all(|r| r.matched), "{:?}", results);
+
+        let glob = RuleConditions {
+            source_glob: Some("/data/*/2024/*.txt".to_string()),
+            max_size_bytes: Some(1_000),
+            ..RuleConditions::default()
+        };
+        assert!(glob.evaluate(&subject()).unwrap().iter().all(|r| !r.matched));
+
+        let invalid = RuleConditions { title_regex: Some("(".to_string()), ..RuleConditions::default() };
+        assert!(invalid.evaluate(&subject()).is_err());
+    }
+
+    #[test]
+    fn test_outcome_combines_rules_in_priority_order() {
+        let research = AutomationRule::new(
+            "Papers".to_string(),
+            0,
+            RuleConditions { source_glob: Some("/data/Papers".to_string()), ..RuleConditions::default() },
+            RuleActions { category: Some("Research".to_string()), tags: vec!["paper".to_string()], ..RuleActions::default() },
+        );
+        let ml = AutomationRule::new(
+            "Attention".to_string(),
+            10,
+            RuleConditions { title_regex: Some("attention".to_string()), ..RuleConditions::default() },
+            RuleActions { category: Some("ML".to_string()), skip_ai: true, ..RuleActions::default() },
+        );
+        let invoices = AutomationRule::new(
+            "Invoices".to_string(),
+            20,
+            RuleConditions { title_regex: Some("invoice".to_string()), ..RuleConditions::default() },
+            RuleActions { archive: true, ..RuleActions::default() },
+        );
+
+        let outcome = RuleOutcome::evaluate(&[research.clone(), ml.clone(), invoices], &subject());
+        assert_eq!(outcome.matched_rules, vec![research.id, ml.id]);
+        assert_eq!(outcome.actions.category.as_deref(), Some("ML"));
+        assert!(outcome.actions.skip_ai);
+        assert!(!outcome.actions.archive);
+
+        let mut document = Document::new("Attention".to_string(), "body".to_string(), "text/plain".to_string());
+        document.set_tags(vec!["Paper".to_string()]);
+        outcome.apply(&mut document);
+        assert_eq!(document.category.as_deref(), Some("ML"));
+        assert_eq!(document.get_tags(), vec!["Paper".to_string()]);
+    }
+}
@@ -0,0 +1,1482 @@
+//! Search engine combining full-text and semantic retrieval
+//!
+//! Wraps the database-level search queries with metadata filtering, sorting,
+//! pagination and snippet generation.
+
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, instrument};
+
+use crate::{metrics, CodexError, CodexResult};
+use crate::clock::Clock;
+use crate::config::ContentConfig;
+use crate::db::{
+    AnnotationQueries, DatabaseManager, DocumentQueries, EmbeddingQueries, FusionMethod, SearchDictionaryQueries,
+    SearchFilter, SearchQueries, VectorOps, FTS_COLUMN_WEIGHTS, FTS_HIGHLIGHT_MARKS, HYBRID_SIMILARITY_THRESHOLD,
+};
+use crate::db::models::{AnnotationMatch, Document, MetadataValue, SearchDictionaries};
+use crate::ai::{AiEngine, EmbeddingEngine};
+use super::code::CodeLanguage;
+use super::fuzzy;
+
+/// Upper bound on candidates fetched before filtering and pagination
+const MAX_CANDIDATES: i64 = 500;
+/// Annotation matches listed alongside document results
+const MAX_ANNOTATION_RESULTS: i64 = 10;
+/// Approximate snippet length in characters
+const SNIPPET_LENGTH: usize = 200;
+/// Number of source lines shown in code snippets
+const CODE_SNIPPET_LINES: usize = 8;
+/// Searches with fewer matches than this try the fuzzy fallback
+const FUZZY_MIN_RESULTS: usize = 3;
+/// Time the fuzzy fallback may add to a search
+const FUZZY_TIME_BUDGET: Duration = Duration::from_millis(300);
+/// Minimum trigram similarity between the query and a title
+const TITLE_SIMILARITY_THRESHOLD: f64 = 0.35;
+/// Vocabulary terms considered when correcting one query term
+const VOCABULARY_SCAN_LIMIT: i64 = 50_000;
+/// Share of the search time budget the full-text stage of a hybrid search
+/// may use; the semantic stage gets the rest, including any time left over
+const FTS_BUDGET_SHARE: f64 = 0.5;
+/// Longest explanation of a search result the model may write, in tokens
+const EXPLANATION_MAX_TOKENS: usize = 60;
+/// Shown with semantic and hybrid results while the vectors are from another model
+const STALE_INDEX_WARNING: &str =
+    "The search index was built with another embedding model; showing full-text matches until it is reindexed";
+/// Shown with semantic and hybrid results while the core runs in safe mode
+const SAFE_MODE_SEARCH_WARNING: &str =
+    "AI is disabled in safe mode; showing full-text matches";
+/// Least similarity of a document chunk to pasted text to list the document
+const SIMILAR_TEXT_THRESHOLD: f32 = 0.3;
+
+/// Reuse of ranked results for repeated searches
+///
+/// Results are kept per query and options, except limit and offset, so
+/// paging through them or changing the page size hits the cache too. Any
+/// document change drops every entry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SearchCacheConfig {
+    pub enabled: bool,
+    /// Most searches kept
+    pub capacity: usize,
+    /// Seconds a cached search is served for
+    pub ttl_secs: u64,
+}
+
+impl Default for SearchCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            capacity: 64,
+            ttl_secs: 30,
+        }
+    }
+}
+
+/// Search strategy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SearchType {
+    /// FTS5 keyword search
+    FullText,
+    /// Embedding similarity search
+    Semantic,
+    /// Weighted combination of full-text and semantic search
+    Hybrid,
+}
+
+/// Sort key for search results
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SortBy {
+    Relevance,
+    CreatedAt,
+    UpdatedAt,
+    Title,
+}
+
+/// Sort direction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SortOrder {
+    Ascending,
+    Descending,
+}
+
+/// Inclusive creation date range filter
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DateRange {
+    pub start: Option<DateTime<Utc>>,
+    pub end: Option<DateTime<Utc>>,
+}
+
+impl DateRange {
+    /// Check whether a timestamp falls inside the range
+    pub fn contains(&self, value: DateTime<Utc>) -> bool {
+        self.start.is_none_or(|start| value >= start) && self.end.is_none_or(|end| value <= end)
+    }
+}
+
+/// Search options
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchOptions {
+    pub search_type: SearchType,
+    pub limit: usize,
+    pub offset: usize,
+    pub category: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub author: Option<String>,
+    pub language: Option<String>,
+    pub difficulty_level: Option<i32>,
+    pub date_range: Option<DateRange>,
+    pub similarity_threshold: Option<f32>,
+    pub sort_by: SortBy,
+    pub sort_order: SortOrder,
+    /// Include archived documents in the results
+    #[serde(default)]
+    pub include_archived: bool,
+    /// Only favorite documents
+    #[serde(default)]
+    pub favorites_only: bool,
+    /// How hybrid search combines full-text and semantic rankings
+    #[serde(default)]
+    pub fusion: FusionMethod,
+    /// Skip the spelling-tolerant fallback for searches with few matches
+    #[serde(default)]
+    pub exact_only: bool,
+    /// List every matching chunk separately instead of one result per document
+    #[serde(default)]
+    pub expand_chunks: bool,
+    /// Search the query as typed, without the vault's synonyms and stop words
+    #[serde(default)]
+    pub skip_expansion: bool,
+    /// Custom metadata fields that must match, e.g. `("status", "to-read")`
+    #[serde(default)]
+    pub metadata: Vec<(String, MetadataValue)>,
+}
+
+impl SearchOptions {
+    /// The metadata filters as predicates for the full-text query
+    pub fn search_filter(&self) -> SearchFilter {
+        SearchFilter {
+            category: self.category.clone(),
+            tags: self.tags.clone().unwrap_or_default(),
+            author: self.author.clone(),
+            language: self.language.clone(),
+            difficulty_level: self.difficulty_level.map(i64::from),
+            created_after: self.date_range.as_ref().and_then(|r| r.start).map(|d| d.to_rfc3339()),
+            created_before: self.date_range.as_ref().and_then(|r| r.end).map(|d| d.to_rfc3339()),
+            favorites_only: self.favorites_only,
+            include_archived: self.include_archived,
+            metadata: self.metadata.clone(),
+        }
+    }
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            search_type: SearchType::Hybrid,
+            limit: 20,
+            offset: 0,
+            category: None,
+            tags: None,
+            author: None,
+            language: None,
+            difficulty_level: None,
+            date_range: None,
+            similarity_threshold: Some(0.3),
+            sort_by: SortBy::Relevance,
+            sort_order: SortOrder::Descending,
+            include_archived: false,
+            favorites_only: false,
+            fusion: FusionMethod::default(),
+            exact_only: false,
+            expand_chunks: false,
+            skip_expansion: false,
+            metadata: Vec::new(),
+        }
+    }
+}
+
+/// Change to the search synonyms or stop words
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum DictionaryEdit {
+    /// Replace the expansions of a term; none removes it
+    SetSynonyms { term: String, expansions: Vec<String> },
+    RemoveSynonyms { term: String },
+    AddStopwords { words: Vec<String> },
+    RemoveStopwords { words: Vec<String> },
+}
+
+/// A single search hit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct SearchResult {
+    pub document: Document,
+    pub score: f64,
+    /// Excerpt of the best-matching chunk, or of the document
+    pub snippet: Option<String>,
+    /// Matching chunks of the document folded into this result
+    pub matching_chunks: usize,
+    /// Best-matching embedding chunk, for semantic matches
+    pub chunk_index: Option<i64>,
+}
+
+/// A matching document, or one matching chunk of it
+#[derive(Debug, Clone)]
+struct SearchHit {
+    document: Document,
+    score: f64,
+    chunk_index: Option<i64>,
+    matching_chunks: usize,
+}
+
+impl SearchHit {
+    fn new(document: Document, score: f64, chunk_index: Option<i64>) -> Self {
+        Self {
+            document,
+            score,
+            chunk_index,
+            matching_chunks: 1,
+        }
+    }
+}
+
+/// Paginated search results
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct SearchResults {
+    pub documents: Vec<SearchResult>,
+    pub total_count: usize,
+    pub query: String,
+    pub search_time_ms: u64,
+    pub has_more: bool,
+    /// Spelling-corrected query whose matches were added to the results
+    pub did_you_mean: Option<String>,
+    /// Highlights and notes matching the query, on the first page only
+    #[serde(default)]
+    pub annotations: Vec<AnnotationMatch>,
+    /// A stage ran out of its share of the time budget, so the results are
+    /// the partial matches gathered in time
+    #[serde(default)]
+    pub timed_out: bool,
+    /// Served from the result cache
+    #[serde(default)]
+    pub from_cache: bool,
+    /// Why results may be worse than asked for, e.g. semantic matching was skipped
+    #[serde(default)]
+    pub warnings: Vec<String>,
+}
+
+/// Vault documents similar to text that is not stored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimilarTextResults {
+    /// Best first, scored by the similarity of their closest chunk
+    pub documents: Vec<SearchResult>,
+    /// E.g. that the text was too long and embedded in parts
+    pub warnings: Vec<String>,
+}
+
+/// A query term found in one field of a document
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TermMatch {
+    pub term: String,
+    /// Indexed field, e.g. "title"
+    pub field: String,
+    pub occurrences: usize,
+    /// Weight of the field in full-text ranking
+    pub weight: f64,
+}
+
+/// The chunk of a document closest in meaning to a query
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChunkMatch {
+    pub chunk_index: i64,
+    pub similarity: f32,
+    /// Start of the chunk text
+    pub excerpt: String,
+}
+
+/// Why a document matches a query, see [`SearchEngine::explain_match`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MatchExplanation {
+    pub document_id: String,
+    pub query: String,
+    /// Query terms found by full-text search, most heavily weighted first
+    pub term_matches: Vec<TermMatch>,
+    /// BM25 relevance, `None` when full-text search does not match the document
+    pub text_score: Option<f64>,
+    /// Excerpt around the best full-text match, with matches in brackets
+    pub text_excerpt: Option<String>,
+    /// Closest chunk by embedding similarity, `None` for unembedded documents
+    pub semantic_match: Option<ChunkMatch>,
+    /// One sentence from the model drawn only from the evidence above,
+    /// `None` when there is no evidence or no model to write it
+    pub explanation: Option<String>,
+}
+
+/// Deadlines of the stages of one search
+#[derive(Debug, Clone, Copy)]
+struct SearchBudget {
+    start: Instant,
+    /// Time for the whole search, `None` for no limit
+    limit: Option<Duration>,
+}
+
+impl SearchBudget {
+    /// Budget of `timeout_ms` from now, unlimited for 0
+    fn new(timeout_ms: u64) -> Self {
+        Self {
+            start: Instant::now(),
+            limit: (timeout_ms > 0).then(|| Duration::from_millis(timeout_ms)),
+        }
+    }
+
+    /// End of a stage that may run until `share` of the budget is spent
+    fn deadline(&self, share: f64) -> Option<Instant> {
+        self.limit.map(|limit| self.start + limit.mul_f64(share))
+    }
+}
+
+/// Output of a search stage, or `None` if it was cancelled at `deadline`
+async fn within_deadline<T>(
+    deadline: Option<Instant>,
+    stage: impl Future<Output = CodexResult<T>>,
+) -> CodexResult<Option<T>> {
+    match deadline {
+        Some(deadline) => match tokio::time::timeout_at(tokio::time::Instant::from_std(deadline), stage).await {
+            Ok(result) => result.map(Some),
+            Err(_) => Ok(None),
+        },
+        None => stage.await.map(Some),
+    }
+}
+
+/// Every hit of a search, ranked, before pagination
+#[derive(Debug)]
+struct RankedHits {
+    hits: Vec<SearchHit>,
+    did_you_mean: Option<String>,
+    timed_out: bool,
+}
+
+/// A cached search, valid for the document generation it was started in
+#[derive(Debug)]
+struct CachedSearch {
+    ranked: Arc<RankedHits>,
+    generation: u64,
+    stored_at: Instant,
+}
+
+/// Recent searches by normalized query and options hash
+///
+/// The generation is bumped by every document change. A search records
+/// the generation it started in, so results computed while a change was
+/// being written are never served after it.
+#[derive(Debug)]
+struct SearchCache {
+    entries: Mutex<LruCache<(String, u64), CachedSearch>>,
+    generation: AtomicU64,
+    ttl: Duration,
+    clock: Arc<dyn Clock>,
+}
+
+impl SearchCache {
+    fn new(config: &SearchCacheConfig, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            entries: Mutex::new(LruCache::new(NonZeroUsize::new(config.capacity).unwrap_or(NonZeroUsize::MIN))),
+            generation: AtomicU64::new(0),
+            ttl: Duration::from_secs(config.ttl_secs),
+            clock,
+        }
+    }
+
+    /// Key of a search: the query lowercased with whitespace collapsed, and
+    /// a hash of the options other than limit and offset
+    fn key(query: &str, options: &SearchOptions) -> (String, u64) {
+        let normalized = query.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+        let options = SearchOptions {
+            limit: 0,
+            offset: 0,
+            ..options.clone()
+        };
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        serde_json::to_string(&options).unwrap_or_default().hash(&mut hasher);
+        (normalized, hasher.finish())
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    fn get(&self, key: &(String, u64)) -> Option<Arc<RankedHits>> {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries.get(key)?;
+        let age = self.clock.now().saturating_duration_since(entry.stored_at);
+        if entry.generation == self.generation() && age < self.ttl {
+            return Some(Arc::clone(&entry.ranked));
+        }
+        entries.pop(key);
+        None
+    }
+
+    fn insert(&self, key: (String, u64), generation: u64, ranked: Arc<RankedHits>) {
+        if generation == self.generation() {
+            let stored_at = self.clock.now();
+            self.entries.lock().unwrap().put(key, CachedSearch { ranked, generation, stored_at });
+        }
+    }
+
+    fn invalidate(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+/// Extra matches found by the fuzzy fallback
+struct FuzzyFallback {
+    /// The query with misspelled terms corrected, if any were
+    corrected_query: Option<String>,
+    /// Matches for the corrected query
+    corrected_matches: Vec<SearchHit>,
+    /// Documents with titles resembling the query, with the similarity
+    title_matches: Vec<(Document, f64)>,
+}
+
+/// Search engine over the document store
+#[derive(Debug)]
+pub struct SearchEngine {
+    db: Arc<DatabaseManager>,
+    ai: Arc<AiEngine>,
+    config: ContentConfig,
+    cache: SearchCache,
+}
+
+impl SearchEngine {
+    /// Create a new search engine
+    pub async fn new(
+        db: Arc<DatabaseManager>,
+        ai: Arc<AiEngine>,
+        config: &ContentConfig,
+    ) -> Result<Self> {
+        Ok(Self {
+            db,
+            ai,
+            config: config.clone(),
+            cache: SearchCache::new(&config.search_cache, crate::clock::system()),
+        })
+    }
+
+    /// Read the time for search cache expiry from `clock`
+    ///
+    /// Searches cached so far are dropped.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.cache = SearchCache::new(&self.config.search_cache, clock);
+        self
+    }
+
+    /// Drop every cached search; call after any document change
+    pub fn invalidate_cache(&self) {
+        self.cache.invalidate();
+    }
+
+    /// Execute a search
+    ///
+    /// The search gets `ContentConfig::search_timeout_ms`. Stages that run
+    /// out of their share are cancelled, and the matches found in time are
+    /// returned with `timed_out` set. Complete results are cached, see
+    /// [`SearchCacheConfig`]; searches served from the cache are also
+    /// recorded as [`metrics::SEARCH_CACHE_HIT`].
+    #[instrument(skip_all, fields(query = %query, search_type = ?options.search_type, results = tracing::field::Empty))]
+    pub async fn search(&self, query: &str, options: SearchOptions) -> CodexResult<SearchResults> {
+        let _timer = metrics::timer(metrics::SEARCH);
+        let start = Instant::now();
+
+        let cache_key = self.config.search_cache.enabled.then(|| SearchCache::key(query, &options));
+        let generation = self.cache.generation();
+        let cached = cache_key.as_ref().and_then(|key| self.cache.get(key));
+        let from_cache = cached.is_some();
+        let ranked = match cached {
+            Some(ranked) => ranked,
+            None => {
+                let ranked = Arc::new(self.rank(query, &options).await?);
+                if let Some(key) = cache_key.filter(|_| !ranked.timed_out) {
+                    self.cache.insert(key, generation, Arc::clone(&ranked));
+                }
+                ranked
+            }
+        };
+        let timed_out = ranked.timed_out;
+        let did_you_mean = ranked.did_you_mean.clone();
+
+        let total_count = ranked.hits.len();
+        let snippet_query = did_you_mean.as_deref().unwrap_or(query);
+        let mut documents: Vec<SearchResult> = Vec::new();
+        for hit in ranked.hits.iter().skip(options.offset).take(options.limit).cloned() {
+            let chunk_text = match hit.chunk_index {
+                Some(chunk_index) => {
+                    EmbeddingQueries::get_chunk_text(self.db.pool(), &hit.document.id, chunk_index).await?
+                }
+                None => None,
+            };
+            let text = chunk_text.as_deref().unwrap_or(&hit.document.content);
+            let snippet = if CodeLanguage::from_content_type(&hit.document.content_type).is_some() {
+                Self::make_code_snippet(text, snippet_query)
+            } else {
+                Self::make_snippet(text, snippet_query)
+            };
+            documents.push(SearchResult {
+                document: hit.document,
+                score: hit.score,
+                snippet,
+                matching_chunks: hit.matching_chunks,
+                chunk_index: hit.chunk_index,
+            });
+        }
+
+        let mut warnings = Vec::new();
+        if self.semantic_unavailable(query, &options) {
+            let warning = if self.ai.is_disabled() { SAFE_MODE_SEARCH_WARNING } else { STALE_INDEX_WARNING };
+            warnings.push(warning.to_string());
+        }
+
+        let annotations = if options.offset == 0 && !query.trim().is_empty() {
+            AnnotationQueries::search(self.db.pool(), query, MAX_ANNOTATION_RESULTS, options.include_archived).await?
+        } else {
+            Vec::new()
+        };
+
+        let has_more = options.offset + documents.len() < total_count;
+        let search_time_ms = start.elapsed().as_millis() as u64;
+        tracing::Span::current().record("results", total_count);
+        if from_cache {
+            metrics::record(metrics::SEARCH_CACHE_HIT, start.elapsed());
+        }
+
+        debug!(
+            "Search '{}' ({:?}) returned {} of {} results in {}ms{}{}",
+            query,
+            options.search_type,
+            documents.len(),
+            total_count,
+            search_time_ms,
+            if timed_out { " (timed out, partial results)" } else { "" },
+            if from_cache { " (cached)" } else { "" }
+        );
+
+        Ok(SearchResults {
+            documents,
+            total_count,
+            query: query.to_string(),
+            search_time_ms,
+            has_more,
+            did_you_mean,
+            annotations,
+            timed_out,
+            from_cache,
+            warnings,
+        })
+    }
+
+    /// Whether a semantic or hybrid search falls back to full-text matching
+    /// because the stored vectors come from another embedding model, or no
+    /// model may be used in safe mode
+    fn semantic_unavailable(&self, query: &str, options: &SearchOptions) -> bool {
+        options.search_type != SearchType::FullText
+            && !query.trim().is_empty()
+            && (self.ai.get_embeddings().is_index_stale() || self.ai.is_disabled())
+    }
+
+    /// Documents whose chunks are closest to `text`, which is embedded but never stored
+    pub async fn similar_to_text(&self, text: &str, limit: usize) -> CodexResult<SimilarTextResults> {
+        let embeddings = self.ai.get_embeddings();
+        if embeddings.is_index_stale() {
+            return Err(CodexError::validation(
+                "The search index was built with another embedding model; reindex to find similar documents",
+            ));
+        }
+
+        let embedded = embeddings.embed_text(text).await?;
+        let chunks = SearchQueries::search_semantic_chunks(
+            self.db.pool(),
+            &embedded.vector,
+            Some(limit as i64),
+            Some(SIMILAR_TEXT_THRESHOLD),
+        )
+        .await?;
+
+        let mut hits: Vec<SearchHit> = Vec::new();
+        for (document, similarity, chunk_index) in chunks {
+            match hits.iter_mut().find(|hit| hit.document.id == document.id) {
+                Some(hit) => hit.matching_chunks += 1,
+                None => hits.push(SearchHit::new(document, similarity as f64, Some(chunk_index))),
+            }
+        }
+
+        let mut documents = Vec::new();
+        for hit in hits {
+            let chunk_text = match hit.chunk_index {
+                Some(chunk_index) => EmbeddingQueries::get_chunk_text(self.db.pool(), &hit.document.id, chunk_index).await?,
+                None => None,
+            };
+            documents.push(SearchResult {
+                snippet: Self::make_snippet(chunk_text.as_deref().unwrap_or(&hit.document.content), ""),
+                document: hit.document,
+                score: hit.score,
+                matching_chunks: hit.matching_chunks,
+                chunk_index: hit.chunk_index,
+            });
+        }
+
+        Ok(SimilarTextResults {
+            documents,
+            warnings: embedded.notice.into_iter().collect(),
+        })
+    }
+
+    /// Every hit for `query`, filtered, collapsed and sorted, within the
+    /// search time budget
+    async fn rank(&self, query: &str, options: &SearchOptions) -> CodexResult<RankedHits> {
+        let budget = SearchBudget::new(self.config.search_timeout_ms);
+        let (candidates, timed_out) = self.candidates(query, options, budget).await?;
+        let mut hits: Vec<SearchHit> = candidates
+            .into_iter()
+            .filter(|hit| Self::matches_filters(&hit.document, options))
+            .collect();
+
+        // The fuzzy fallback is skipped once the budget is spent
+        let mut did_you_mean = None;
+        let found: HashSet<String> = hits.iter().map(|hit| hit.document.id.clone()).collect();
+        if !timed_out && !options.exact_only && !query.trim().is_empty() && found.len() < FUZZY_MIN_RESULTS {
+            match tokio::time::timeout(FUZZY_TIME_BUDGET, self.fuzzy_fallback(query, options)).await {
+                Ok(Ok(fallback)) => {
+                    did_you_mean = fallback.corrected_query;
+                    hits.extend(
+                        fallback
+                            .corrected_matches
+                            .into_iter()
+                            .filter(|hit| !found.contains(&hit.document.id)),
+                    );
+                    // Title matches rank below every real hit
+                    let floor = hits.iter().map(|hit| hit.score).fold(1.0_f64, f64::min).max(0.0);
+                    for (doc, similarity) in fallback.title_matches {
+                        if !hits.iter().any(|hit| hit.document.id == doc.id) {
+                            hits.push(SearchHit::new(doc, floor * similarity, None));
+                        }
+                    }
+                }
+                Ok(Err(e)) => debug!("Fuzzy fallback for '{}' failed: {}", query, e),
+                Err(_) => debug!("Fuzzy fallback for '{}' exceeded {:?}", query, FUZZY_TIME_BUDGET),
+            }
+        }
+
+        if !options.expand_chunks {
+            hits = Self::collapse_by_document(hits);
+        }
+        Self::sort_results(&mut hits, options.sort_by, options.sort_order);
+
+        Ok(RankedHits {
+            hits,
+            did_you_mean,
+            timed_out,
+        })
+    }
+
+    /// Documents (or chunks of them) matching `query`, before filtering,
+    /// and whether a stage was cancelled at its deadline
+    async fn candidates(
+        &self,
+        query: &str,
+        options: &SearchOptions,
+        budget: SearchBudget,
+    ) -> CodexResult<(Vec<SearchHit>, bool)> {
+        let pool = self.db.pool();
+        if query.trim().is_empty() {
+            let documents = SearchQueries::list_filtered(pool, &options.search_filter(), MAX_CANDIDATES).await?;
+            return Ok((documents.into_iter().map(|doc| SearchHit::new(doc, 0.0, None)).collect(), false));
+        }
+
+        let filter = options.search_filter();
+        let search_type = if self.semantic_unavailable(query, options) {
+            SearchType::FullText
+        } else {
+            options.search_type
+        };
+        let candidates = match search_type {
+            SearchType::FullText => {
+                let stage = self.text_matches(query, options, &filter, MAX_CANDIDATES);
+                match within_deadline(budget.deadline(1.0), stage).await? {
+                    Some(matches) => (matches.into_iter().map(|(doc, score)| SearchHit::new(doc, score, None)).collect(), false),
+                    None => (Vec::new(), true),
+                }
+            }
+            SearchType::Semantic => {
+                let deadline = budget.deadline(1.0);
+                let (matches, timed_out) = match within_deadline(deadline, self.ai.generate_embedding(query)).await? {
+                    Some(query_vector) => {
+                        SearchQueries::search_semantic_chunks_until(
+                            pool,
+                            &query_vector,
+                            Some(MAX_CANDIDATES),
+                            options.similarity_threshold,
+                            deadline,
+                        )
+                        .await?
+                    }
+                    None => (Vec::new(), true),
+                };
+                let hits = matches
+                    .into_iter()
+                    .map(|(doc, score, chunk_index)| SearchHit::new(doc, score as f64, Some(chunk_index)))
+                    .collect();
+                (hits, timed_out)
+            }
+            SearchType::Hybrid => {
+                let text_stage = self.text_matches(query, options, &filter, MAX_CANDIDATES * 2);
+                let semantic_stage = |deadline| async move {
+                    // Without an embedding model hybrid search is full-text only
+                    let Ok(query_vector) = self.ai.generate_embedding(query).await else {
+                        return Ok((Vec::new(), false));
+                    };
+                    SearchQueries::search_semantic_chunks_until(
+                        pool,
+                        &query_vector,
+                        Some(MAX_CANDIDATES),
+                        Some(HYBRID_SIMILARITY_THRESHOLD),
+                        deadline,
+                    )
+                    .await
+                };
+                Self::hybrid_stages(budget, options.fusion, text_stage, semantic_stage).await?
+            }
+        };
+        Ok(candidates)
+    }
+
+    /// Ranked full-text matches for `query`, with the vault's synonyms and
+    /// stop words applied unless `options.skip_expansion` is set
+    async fn text_matches(
+        &self,
+        query: &str,
+        options: &SearchOptions,
+        filter: &SearchFilter,
+        limit: i64,
+    ) -> CodexResult<Vec<(Document, f64)>> {
+        let pool = self.db.pool();
+        if !options.skip_expansion {
+            let dictionaries = SearchDictionaryQueries::get(pool).await?;
+            if let Some(groups) = expand_query(&dictionaries, query) {
+                debug!("Expanded query '{}' to {:?}", query, groups);
+                return SearchQueries::search_expanded_filtered(pool, &groups, filter, Some(limit)).await;
+            }
+        }
+        SearchQueries::search_with_ranking_filtered(pool, query, filter, Some(limit)).await
+    }
+
+    /// Fused hits of a hybrid search's full-text and semantic stages
+    ///
+    /// The full-text stage may use [`FTS_BUDGET_SHARE`] of the budget and
+    /// the semantic stage, given its deadline to check during the scan,
+    /// the rest. A stage cancelled at its deadline contributes nothing, or
+    /// its partial matches if it stopped itself, and the second value is
+    /// `true`.
+    async fn hybrid_stages<S>(
+        budget: SearchBudget,
+        fusion: FusionMethod,
+        text_stage: impl Future<Output = CodexResult<Vec<(Document, f64)>>>,
+        semantic_stage: impl FnOnce(Option<Instant>) -> S,
+    ) -> CodexResult<(Vec<SearchHit>, bool)>
+    where
+        S: Future<Output = CodexResult<(Vec<(Document, f32, i64)>, bool)>>,
+    {
+        let (text_matches, text_timed_out) = match within_deadline(budget.deadline(FTS_BUDGET_SHARE), text_stage).await? {
+            Some(matches) => (matches, false),
+            None => (Vec::new(), true),
+        };
+        let deadline = budget.deadline(1.0);
+        let (semantic_matches, semantic_timed_out) = within_deadline(deadline, semantic_stage(deadline))
+            .await?
+            .unwrap_or((Vec::new(), true));
+        if text_timed_out || semantic_timed_out {
+            debug!(
+                "Hybrid search stages timed out (full-text: {}, semantic: {})",
+                text_timed_out, semantic_timed_out
+            );
+        }
+
+        let hits = SearchQueries::fuse_hybrid_chunks(text_matches, semantic_matches, MAX_CANDIDATES, fusion, None, None)
+            .into_iter()
+            .map(|(doc, score, chunk_index)| SearchHit::new(doc, score, chunk_index))
+            .collect();
+        Ok((hits, text_timed_out || semantic_timed_out))
+    }
+
+    /// Matches for a misspelled query: the spelling-corrected query's
+    /// results and documents whose title resembles the query
+    async fn fuzzy_fallback(&self, query: &str, options: &SearchOptions) -> CodexResult<FuzzyFallback> {
+        let pool = self.db.pool();
+
+        let terms = fuzzy::query_terms(query);
+        let mut corrected_terms = Vec::with_capacity(terms.len());
+        let mut corrected_any = false;
+        for term in terms {
+            let allowed = fuzzy::max_edits(term.chars().count());
+            if allowed == 0 || SearchQueries::vocabulary_contains(pool, &term).await? {
+                corrected_terms.push(term);
+                continue;
+            }
+            let len = term.chars().count();
+            let vocabulary =
+                SearchQueries::vocabulary_terms(pool, len - allowed, len + allowed, VOCABULARY_SCAN_LIMIT).await?;
+            match fuzzy::best_correction(&term, &vocabulary) {
+                Some(correction) => {
+                    corrected_any = true;
+                    corrected_terms.push(correction);
+                }
+                None => corrected_terms.push(term),
+            }
+        }
+
+        let corrected_query = corrected_any.then(|| corrected_terms.join(" "));
+        let mut corrected_matches = Vec::new();
+        if let Some(ref corrected) = corrected_query {
+            corrected_matches = self
+                .candidates(corrected, options, SearchBudget::new(0))
+                .await?
+                .0
+                .into_iter()
+                .filter(|hit| Self::matches_filters(&hit.document, options))
+                .collect();
+        }
+
+        let mut similar: Vec<(String, f64)> = DocumentQueries::get_titles(pool)
+            .await?
+            .into_iter()
+            .map(|(id, title)| {
+                let similarity = fuzzy::trigram_similarity(query, &title);
+                (id, similarity)
+            })
+            .filter(|(_, similarity)| *similarity >= TITLE_SIMILARITY_THRESHOLD)
+            .collect();
+        similar.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+
+        let mut title_matches = Vec::new();
+        for (id, similarity) in similar.into_iter().take(options.limit) {
+            if let Some(doc) = DocumentQueries::get_by_id(pool, &id).await? {
+                if Self::matches_filters(&doc, options) {
+                    title_matches.push((doc, similarity));
+                }
+            }
+        }
+
+        Ok(FuzzyFallback {
+            corrected_query,
+            corrected_matches,
+            title_matches,
+        })
+    }
+
+    /// Apply the metadata filters in `options` to a document
+    fn matches_filters(doc: &Document, options: &SearchOptions) -> bool {
+        if doc.is_deleted || (doc.is_archived && !options.include_archived) {
+            return false;
+        }
+
+        if options.favorites_only && !doc.is_favorite {
+            return false;
+        }
+
+        if let Some(ref category) = options.category {
+            if doc.category.as_deref() != Some(category.as_str()) {
+                return false;
+            }
+        }
+
+        if let Some(ref tags) = options.tags {
+            let doc_tags = doc.get_tags();
+            if !tags.iter().all(|tag| doc_tags.iter().any(|t| t.eq_ignore_ascii_case(tag))) {
+                return false;
+            }
+        }
+
+        if let Some(ref author) = options.author {
+            match doc.author {
+                Some(ref doc_author) if doc_author.eq_ignore_ascii_case(author) => {}
+                _ => return false,
+            }
+        }
+
+        if let Some(ref language) = options.language {
+            if !doc.language.eq_ignore_ascii_case(language) {
+                return false;
+            }
+        }
+
+        if let Some(level) = options.difficulty_level {
+            if doc.difficulty_level != Some(level as i64) {
+                return false;
+            }
+        }
+
+        if let Some(ref range) = options.date_range {
+            if !range.contains(doc.created_at) {
+                return false;
+            }
+        }
+
+        if !options.metadata.is_empty() {
+            let metadata = doc.get_metadata();
+            let matches = |(key, value): &(String, MetadataValue)| metadata.get(key).is_some_and(|stored| value.matches(stored));
+            if !options.metadata.iter().all(matches) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Fold hits on the same document into one, keeping the best score
+    /// and chunk and counting the hits
+    fn collapse_by_document(hits: Vec<SearchHit>) -> Vec<SearchHit> {
+        let mut positions: HashMap<String, usize> = HashMap::new();
+        let mut collapsed: Vec<SearchHit> = Vec::with_capacity(hits.len());
+
+        for hit in hits {
+            match positions.get(&hit.document.id) {
+                Some(&position) => {
+                    let existing = &mut collapsed[position];
+                    existing.matching_chunks += hit.matching_chunks;
+                    if hit.score > existing.score {
+                        existing.score = hit.score;
+                        existing.chunk_index = hit.chunk_index;
+                    }
+                }
+                None => {
+                    positions.insert(hit.document.id.clone(), collapsed.len());
+                    collapsed.push(hit);
+                }
+            }
+        }
+        collapsed
+    }
+
+    fn sort_results(results: &mut [SearchHit], sort_by: SortBy, sort_order: SortOrder) {
+        results.sort_by(|x, y| {
+            let (a, b) = (&x.document, &y.document);
+            let ordering = match sort_by {
+                SortBy::Relevance => x
+                    .score
+                    .partial_cmp(&y.score)
+                    .unwrap_or(std::cmp::Ordering::Equal),
+                SortBy::CreatedAt => a.created_at.cmp(&b.created_at),
+                SortBy::UpdatedAt => a.updated_at.cmp(&b.updated_at),
+                SortBy::Title => a.title.to_lowercase().cmp(&b.title.to_lowercase()),
+            };
+
+            match sort_order {
+                SortOrder::Ascending => ordering,
+                SortOrder::Descending => ordering.reverse(),
+            }
+        });
+    }
+
+    /// Explain why a document matches `query`
+    ///
+    /// The full-text and semantic evidence needs no language model, so it
+    /// is returned even when none is loaded; the one-sentence explanation
+    /// is then left out.
+    pub async fn explain_match(&self, document_id: &str, query: &str) -> CodexResult<MatchExplanation> {
+        let pool = self.db.pool();
+        let document = DocumentQueries::get_by_id(pool, document_id)
+            .await?
+            .ok_or_else(|| CodexError::not_found(format!("Document {}", document_id)))?;
+
+        let mut explanation = match_evidence(pool, self.ai.get_embeddings(), &document.id, query).await?;
+        if let Some(prompt) = explanation_prompt(&document.title, &explanation) {
+            match self.ai.generate_text_limited(&prompt, EXPLANATION_MAX_TOKENS).await {
+                Ok(reply) => explanation.explanation = first_sentence(&reply),
+                Err(e) => debug!("Search result explained without the model: {}", e),
+            }
+        }
+        Ok(explanation)
+    }
+
+    /// Build a short excerpt around the first occurrence of a query term
+    pub fn make_snippet(content: &str, query: &str) -> Option<String> {
+        if content.is_empty() {
+            return None;
+        }
+
+        let lower = content.to_lowercase();
+        let position = query
+            .split_whitespace()
+            .filter_map(|term| lower.find(&term.to_lowercase()))
+            .min()
+            .unwrap_or(0);
+
+        // Lowercasing can shift byte offsets for some scripts; clamp to a char boundary
+        let mut start = position.saturating_sub(SNIPPET_LENGTH / 4).min(content.len());
+        while !content.is_char_boundary(start) {
+            start -= 1;
+        }
+
+        let excerpt: String = content[start..].chars().take(SNIPPET_LENGTH).collect();
+        let excerpt = excerpt.split_whitespace().collect::<Vec<_>>().join(" ");
+
+        let prefix = if start > 0 { "..." } else { "" };
+        let suffix = if start + excerpt.len() < content.len() { "..." } else { "" };
+
+        Some(format!("{}{}{}", prefix, excerpt, suffix))
+    }
+
+    /// Build an excerpt of whole source lines around the first matching line
+    ///
+    /// Unlike [`SearchEngine::make_snippet`] this keeps line breaks and
+    /// indentation intact so code stays readable.
+    pub fn make_code_snippet(content: &str, query: &str) -> Option<String> {
+        let lines: Vec<&str> = content.lines().collect();
+        if lines.iter().all(|l| l.trim().is_empty()) {
+            return None;
+        }
+
+        let terms: Vec<String> = query.split_whitespace().map(|t| t.to_lowercase()).collect();
+        let match_line = lines
+            .iter()
+            .position(|line| {
+                let line = line.to_lowercase();
+                terms.iter().any(|term| line.contains(term.as_str()))
+            })
+            .unwrap_or(0);
+
+        let start = match_line.saturating_sub(2);
+        let end = (start + CODE_SNIPPET_LINES).min(lines.len());
+
+        Some(lines[start..end].join("\n"))
+    }
+}
+
+/// Full-text term groups for `query`: each term that is not a stop word,
+/// followed by its synonyms
+///
+/// `None` when the dictionaries leave the query as it is, or would strip
+/// every term from it.
+fn expand_query(dictionaries: &SearchDictionaries, query: &str) -> Option<Vec<Vec<String>>> {
+    if dictionaries.is_empty() {
+        return None;
+    }
+    let terms = fuzzy::query_terms(query);
+    let kept: Vec<String> = terms.iter().filter(|term| !dictionaries.stopwords.contains(*term)).cloned().collect();
+    if kept.is_empty() {
+        return None;
+    }
+
+    let mut changed = kept.len() < terms.len();
+    let groups: Vec<Vec<String>> = kept
+        .into_iter()
+        .map(|term| {
+            let synonyms = dictionaries.synonyms.get(&term).cloned().unwrap_or_default();
+            changed |= !synonyms.is_empty();
+            std::iter::once(term).chain(synonyms).collect()
+        })
+        .collect();
+    changed.then_some(groups)
+}
+
+/// Full-text and semantic evidence for why a document matches `query`
+async fn match_evidence(
+    pool: &sqlx::SqlitePool,
+    embeddings: &EmbeddingEngine,
+    document_id: &str,
+    query: &str,
+) -> CodexResult<MatchExplanation> {
+    let (text_score, term_matches, text_excerpt) = match SearchQueries::explain_text_match(pool, query, document_id).await? {
+        Some((score, columns, excerpt)) => (Some(score), term_matches(&columns), Some(excerpt)),
+        None => (None, Vec::new(), None),
+    };
+
+    let chunks = EmbeddingQueries::get_by_document(pool, document_id).await?;
+    let semantic_match = if chunks.is_empty() {
+        None
+    } else {
+        let query_vector = embeddings.generate_embedding(query).await?;
+        chunks
+            .into_iter()
+            .map(|chunk| (VectorOps::cosine_similarity(&query_vector, &chunk.get_vector()), chunk))
+            .max_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(similarity, chunk)| ChunkMatch {
+                chunk_index: chunk.chunk_index,
+                similarity,
+                excerpt: chunk.text_chunk.chars().take(SNIPPET_LENGTH).collect(),
+            })
+    };
+
+    Ok(MatchExplanation {
+        document_id: document_id.to_string(),
+        query: query.to_string(),
+        term_matches,
+        text_score,
+        text_excerpt,
+        semantic_match,
+        explanation: None,
+    })
+}
+
+/// Terms marked in highlighted columns, in the order of [`FTS_COLUMN_WEIGHTS`]
+fn term_matches(columns: &[String]) -> Vec<TermMatch> {
+    let (open, close) = FTS_HIGHLIGHT_MARKS;
+    let mut matches = Vec::new();
+    for (text, (field, weight)) in columns.iter().zip(FTS_COLUMN_WEIGHTS) {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        // Adjacent matches, such as a whole phrase, share one marked span
+        for marked in text.split(open).skip(1) {
+            if let Some((span, _)) = marked.split_once(close) {
+                for term in span.split(|c: char| !c.is_alphanumeric() && !"-_".contains(c)).filter(|t| !t.is_empty()) {
+                    *counts.entry(term.to_lowercase()).or_default() += 1;
+                }
+            }
+        }
+        matches.extend(counts.into_iter().map(|(term, occurrences)| TermMatch {
+            term,
+            field: field.to_string(),
+            occurrences,
+            weight,
+        }));
+    }
+
+    matches.sort_by(|a, b| {
+        b.weight
+            .total_cmp(&a.weight)
+            .then(b.occurrences.cmp(&a.occurrences))
+            .then_with(|| a.term.cmp(&b.term))
+    });
+    matches
+}
+
+/// Prompt asking for a one-sentence explanation grounded in `evidence`,
+/// `None` when there is no evidence to ground it in
+fn explanation_prompt(title: &str, evidence: &MatchExplanation) -> Option<String> {
+    let mut lines: Vec<String> = evidence
+        .term_matches
+        .iter()
+        .map(|m| format!("- \"{}\" appears {} time(s) in the {}", m.term, m.occurrences, m.field))
+        .collect();
+    if let Some(chunk) = &evidence.semantic_match {
+        lines.push(format!(
+            "- The passage \"{}\" is {:.0}% similar in meaning to the search",
+            chunk.excerpt,
+            chunk.similarity.max(0.0) * 100.0
+        ));
+    }
+    if lines.is_empty() {
+        return None;
+    }
+
+    Some(format!(
+        "Explain in one sentence why the document \"{}\" matched the search \"{}\". \
+         Use only the evidence below and do not add anything else.\n\nEvidence:\n{}\n\nExplanation:",
+        title,
+        evidence.query,
+        lines.join("\n")
+    ))
+}
+
+/// First sentence of a model reply, `None` if it is blank
+fn first_sentence(reply: &str) -> Option<String> {
+    let reply = reply.trim();
+    let end = reply
+        .char_indices()
+        .find(|&(i, c)| matches!(c, '.' | '!' | '?') && reply[i + 1..].chars().next().is_none_or(char::is_whitespace))
+        .map_or(reply.len(), |(i, _)| i + 1);
+    let sentence = reply[..end].trim();
+    (!sentence.is_empty()).then(|| sentence.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_database;
+    use crate::db::models::Embedding;
+
+    fn doc(title: &str, category: Option<&str>) -> Document {
+        let mut doc = Document::new(title.to_string(), "body".to_string(), "text/plain".to_string());
+        doc.category = category.map(|c| c.to_string());
+        doc
+    }
+
+    #[test]
+    fn test_category_filter() {
+        let options = SearchOptions {
+            category: Some("Science".to_string()),
+            ..Default::default()
+        };
+
+        assert!(SearchEngine::matches_filters(&doc("a", Some("Science")), &options));
+        assert!(!SearchEngine::matches_filters(&doc("b", Some("History")), &options));
+        assert!(!SearchEngine::matches_filters(&doc("c", None), &options));
+    }
+
+    #[test]
+    fn test_archived_excluded_by_default() {
+        let mut archived = doc("old", None);
+        archived.is_archived = true;
+
+        assert!(!SearchEngine::matches_filters(&archived, &SearchOptions::default()));
+
+        let options = SearchOptions {
+            include_archived: true,
+            ..Default::default()
+        };
+        assert!(SearchEngine::matches_filters(&archived, &options));
+    }
+
+    #[test]
+    fn test_sort_by_title() {
+        let mut results = vec![
+            SearchHit::new(doc("beta", None), 0.1, None),
+            SearchHit::new(doc("Alpha", None), 0.9, None),
+        ];
+        SearchEngine::sort_results(&mut results, SortBy::Title, SortOrder::Ascending);
+        assert_eq!(results[0].document.title, "Alpha");
+
+        SearchEngine::sort_results(&mut results, SortBy::Relevance, SortOrder::Descending);
+        assert_eq!(results[0].score, 0.9);
+    }
+
+    #[test]
+    fn test_collapse_keeps_best_chunk_per_document() {
+        let book = doc("Three chunks", None);
+        let other = doc("Other", None);
+        let hits = vec![
+            SearchHit::new(book.clone(), 0.4, Some(0)),
+            SearchHit::new(other.clone(), 0.5, None),
+            SearchHit::new(book.clone(), 0.8, Some(2)),
+            SearchHit::new(book.clone(), 0.6, Some(1)),
+        ];
+
+        let collapsed = SearchEngine::collapse_by_document(hits.clone());
+        assert_eq!(collapsed.len(), 2);
+        assert_eq!(collapsed[0].document.id, book.id);
+        assert_eq!(collapsed[0].score, 0.8);
+        assert_eq!(collapsed[0].chunk_index, Some(2));
+        assert_eq!(collapsed[0].matching_chunks, 3);
+        assert_eq!(collapsed[1].matching_chunks, 1);
+
+        let mut collapsed = collapsed;
+        SearchEngine::sort_results(&mut collapsed, SortBy::Relevance, SortOrder::Descending);
+        assert_eq!(collapsed[0].document.id, book.id);
+    }
+
+    #[test]
+    fn test_make_snippet() {
+        let content = "The quick brown fox jumps over the lazy dog";
+        let snippet = SearchEngine::make_snippet(content, "lazy").unwrap();
+        assert!(snippet.contains("lazy dog"));
+        assert!(SearchEngine::make_snippet("", "x").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_slow_semantic_stage_keeps_full_text_results() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = test_database(&dir).await;
+        let pool = db.pool();
+        let filter = SearchFilter::default();
+        let bread = Document::new("Sourdough".to_string(), "Feed the sourdough starter".to_string(), "text/plain".to_string());
+        DocumentQueries::create(pool, &bread, db.compression()).await.unwrap();
+
+        // A semantic stage that ignores its deadline is cancelled
+        let budget = SearchBudget::new(300);
+        let text_stage = SearchQueries::search_with_ranking_filtered(pool, "sourdough", &filter, Some(10));
+        let stalled = |_| async {
+            tokio::time::sleep(Duration::from_secs(30)).await;
+            Ok((Vec::new(), false))
+        };
+        let (hits, timed_out) = SearchEngine::hybrid_stages(budget, FusionMethod::default(), text_stage, stalled).await.unwrap();
+        assert!(timed_out);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].document.id, bread.id);
+        assert!(budget.start.elapsed() < Duration::from_millis(1500), "{:?}", budget.start.elapsed());
+
+        // One that stops itself at the deadline keeps its partial matches
+        let budget = SearchBudget::new(300);
+        let text_stage = SearchQueries::search_with_ranking_filtered(pool, "sourdough", &filter, Some(10));
+        let partial = |deadline: Option<Instant>| {
+            let bread = bread.clone();
+            async move {
+                tokio::time::sleep_until(tokio::time::Instant::from_std(deadline.unwrap() - Duration::from_millis(100))).await;
+                Ok((vec![(bread, 0.9, 2)], true))
+            }
+        };
+        let (hits, timed_out) = SearchEngine::hybrid_stages(budget, FusionMethod::default(), text_stage, partial).await.unwrap();
+        assert!(timed_out);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].chunk_index, Some(2));
+        // Scored by both stages
+        assert!(hits[0].score > 0.9, "{}", hits[0].score);
+
+        // Without a limit nothing is cut off
+        let text_stage = SearchQueries::search_with_ranking_filtered(pool, "sourdough", &filter, Some(10));
+        let (hits, timed_out) =
+            SearchEngine::hybrid_stages(SearchBudget::new(0), FusionMethod::default(), text_stage, |_| async { Ok((Vec::new(), false)) })
+                .await
+                .unwrap();
+        assert!(!timed_out);
+        assert_eq!(hits.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_synonym_query_finds_expansion_only_documents() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = test_database(&dir).await;
+        let pool = db.pool();
+        let filter = SearchFilter::default();
+        let cardiology = Document::new(
+            "Cardiology notes".to_string(),
+            "Chest pain after a myocardial infarction needs an ECG".to_string(),
+            "text/plain".to_string(),
+        );
+        DocumentQueries::create(pool, &cardiology, db.compression()).await.unwrap();
+
+        SearchDictionaryQueries::set_synonyms(pool, "MI", &["myocardial infarction".to_string()]).await.unwrap();
+        let dictionaries = SearchDictionaryQueries::add_stopwords(pool, &["the".to_string(), "of".to_string()]).await.unwrap();
+        assert!(SearchDictionaryQueries::add_stopwords(pool, &["two words".to_string()]).await.is_err());
+
+        // The abbreviation alone matches nothing
+        assert!(SearchQueries::search_with_ranking_filtered(pool, "MI", &filter, Some(10)).await.unwrap().is_empty());
+
+        let groups = expand_query(&dictionaries, "the MI").unwrap();
+        assert_eq!(groups, vec![vec!["mi".to_string(), "myocardial infarction".to_string()]]);
+        let matches = SearchQueries::search_expanded_filtered(pool, &groups, &filter, Some(10)).await.unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].0.id, cardiology.id);
+
+        // Queries the dictionaries do not touch, or would empty, are left alone
+        assert!(expand_query(&dictionaries, "chest pain").is_none());
+        assert!(expand_query(&dictionaries, "of the").is_none());
+
+        let dictionaries = SearchDictionaryQueries::remove_synonyms(pool, "mi").await.unwrap();
+        assert!(dictionaries.synonyms.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_repeated_search_is_cached_until_an_import() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = test_database(&dir).await;
+        let pool = db.pool();
+        let clock = Arc::new(crate::clock::ManualClock::new());
+        let cache = SearchCache::new(&SearchCacheConfig::default(), clock.clone());
+        let options = SearchOptions { search_type: SearchType::FullText, ..Default::default() };
+        let rank = |matches: Vec<(Document, f64)>| {
+            Arc::new(RankedHits {
+                hits: matches.into_iter().map(|(doc, score)| SearchHit::new(doc, score, None)).collect(),
+                did_you_mean: None,
+                timed_out: false,
+            })
+        };
+        let bread = Document::new("Sourdough".to_string(), "Feed the sourdough starter".to_string(), "text/plain".to_string());
+        DocumentQueries::create(pool, &bread, db.compression()).await.unwrap();
+
+        let key = SearchCache::key("Sourdough", &options);
+        let generation = cache.generation();
+        assert!(cache.get(&key).is_none());
+        let matches = SearchQueries::search_with_ranking_filtered(pool, "sourdough", &SearchFilter::default(), Some(10)).await.unwrap();
+        cache.insert(key, generation, rank(matches));
+
+        // The same query, differently spaced and paged, is served from the cache
+        let repeated = SearchOptions { limit: 5, offset: 5, ..options.clone() };
+        assert_eq!(cache.get(&SearchCache::key("  sourdough ", &repeated)).unwrap().hits.len(), 1);
+        let other_type = SearchOptions { search_type: SearchType::Hybrid, ..options.clone() };
+        assert!(cache.get(&SearchCache::key("sourdough", &other_type)).is_none());
+
+        // A search that started before an import is not cached after it
+        let generation = cache.generation();
+        let rye = Document::new("Rye sourdough".to_string(), "A sourdough with rye flour".to_string(), "text/plain".to_string());
+        DocumentQueries::create(pool, &rye, db.compression()).await.unwrap();
+        cache.invalidate();
+        assert!(cache.get(&SearchCache::key("sourdough", &options)).is_none());
+        cache.insert(SearchCache::key("sourdough", &options), generation, rank(Vec::new()));
+        assert!(cache.get(&SearchCache::key("sourdough", &options)).is_none());
+
+        let matches = SearchQueries::search_with_ranking_filtered(pool, "sourdough", &SearchFilter::default(), Some(10)).await.unwrap();
+        cache.insert(SearchCache::key("sourdough", &options), cache.generation(), rank(matches));
+        assert_eq!(cache.get(&SearchCache::key("sourdough", &options)).unwrap().hits.len(), 2);
+
+        // Entries expire after the time to live
+        let ttl = Duration::from_secs(SearchCacheConfig::default().ttl_secs);
+        clock.advance(ttl - Duration::from_secs(1));
+        assert!(cache.get(&SearchCache::key("sourdough", &options)).is_some());
+        clock.advance(Duration::from_secs(1));
+        assert!(cache.get(&SearchCache::key("sourdough", &options)).is_none());
+        assert!(cache.entries.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_make_code_snippet_preserves_indentation() {
+        let content = "use std::io;\n\nfn main() {\n    let value = parse();\n    if value > 1 {\n        run(value);\n    }\n}\n";
+        let snippet = SearchEngine::make_code_snippet(content, "run").unwrap();
+
+        assert!(snippet.starts_with("    let value = parse();\n    if value > 1 {\n        run(value);"));
+        assert!(SearchEngine::make_code_snippet("\n\n", "x").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_explain_match_without_language_model() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = test_database(&dir).await;
+        let pool = db.pool();
+        let ai_config = crate::config::AiConfig { models_dir: dir.path().to_path_buf(), ..Default::default() };
+        let embeddings = EmbeddingEngine::new(&ai_config).await.unwrap();
+
+        let document = Document::new(
+            "Sourdough baking".to_string(),
+            "Feed the sourdough starter daily. A lively starter doubles.".to_string(),
+            "text/plain".to_string(),
+        );
+        let chunks = ["Feed the sourdough starter daily.", "A lively starter doubles."];
+        let mut stored = Vec::new();
+        for (index, text) in chunks.iter().enumerate() {
+            let vector = embeddings.generate_embedding(text).await.unwrap();
+            stored.push(Embedding::new(document.id.clone(), vector, "test".to_string(), index as i64, text.to_string(), 0, 0));
+        }
+        db.create_document_with_embeddings(&document, &stored).await.unwrap();
+
+        let evidence = match_evidence(pool, &embeddings, &document.id, "A lively starter doubles.").await.unwrap();
+        assert!(evidence.text_score.unwrap() > 0.0);
+        assert!(evidence.text_excerpt.as_deref().unwrap().contains("[starter]"));
+        let content_terms: Vec<&TermMatch> = evidence.term_matches.iter().filter(|m| m.field == "content").collect();
+        let starter = content_terms.iter().find(|m| m.term == "starter").unwrap();
+        assert_eq!((starter.occurrences, starter.weight), (2, 5.0));
+        let semantic = evidence.semantic_match.clone().unwrap();
+        assert_eq!(semantic.chunk_index, 1);
+        assert!(semantic.similarity > 0.99, "{}", semantic.similarity);
+
+        let prompt = explanation_prompt(&document.title, &evidence).unwrap();
+        assert!(prompt.contains("\"starter\" appears 2 time(s) in the content"));
+        assert!(prompt.contains("A lively starter doubles."));
+
+        // A document the query does not touch has no evidence to explain
+        let other = Document::new("Taxes".to_string(), "Quarterly filing".to_string(), "text/plain".to_string());
+        DocumentQueries::create(pool, &other, db.compression()).await.unwrap();
+        let evidence = match_evidence(pool, &embeddings, &other.id, "sourdough").await.unwrap();
+        assert!(evidence.term_matches.is_empty() && evidence.text_score.is_none() && evidence.semantic_match.is_none());
+        assert!(explanation_prompt(&other.title, &evidence).is_none());
+    }
+
+    #[test]
+    fn test_first_sentence_of_explanation() {
+        assert_eq!(
+            first_sentence("  It mentions sourdough twice. It also has a starter.").as_deref(),
+            Some("It mentions sourdough twice.")
+        );
+        assert_eq!(first_sentence("Version 2.1 covers starters").as_deref(), Some("Version 2.1 covers starters"));
+        assert_eq!(first_sentence("   "), None);
+    }
+}
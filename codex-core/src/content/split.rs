@@ -0,0 +1,244 @@
+//! Splitting long documents into sections
+//!
+//! A document is cut at markdown headings, page markers or explicit
+//! character offsets. Each section becomes a child document linked back to
+//! the original with a [`PART_OF_LINK`](super::links::PART_OF_LINK) link.
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{CodexError, CodexResult};
+
+/// Lines such as `Page 12`, `--- Page 12 ---`, `[Page 3 of 40]`
+static PAGE_MARKER_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?i)^[\s\-=\[]*page\s+\d+(?:\s+of\s+\d+)?[\s\-=\]]*$").unwrap());
+
+/// Characters of a section shown in a preview
+const EXCERPT_CHARS: usize = 200;
+
+/// Where to cut a document
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SplitStrategy {
+    /// Before every markdown heading of this level or higher (1-6)
+    Headings { level: u8 },
+    /// At form feeds and `Page N` marker lines
+    PageMarkers,
+    /// At these character offsets into the content
+    Offsets { offsets: Vec<usize> },
+}
+
+/// What happens to the original after a split
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OriginalHandling {
+    /// Keep the original unchanged but archived
+    #[default]
+    Archive,
+    /// Replace the original's content with links to the sections
+    TableOfContents,
+}
+
+/// A proposed section of a document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SplitSection {
+    pub title: String,
+    /// Character range of the section in the original content
+    pub start: usize,
+    pub end: usize,
+    pub word_count: usize,
+    /// Opening text of the section
+    pub excerpt: String,
+    #[serde(skip)]
+    pub content: String,
+}
+
+/// Result (or preview) of a split
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SplitResult {
+    pub original_id: String,
+    pub sections: Vec<SplitSection>,
+    /// Created child documents, in section order; empty for a preview
+    pub document_ids: Vec<uuid::Uuid>,
+    pub original_handling: OriginalHandling,
+    /// Whether nothing was written
+    pub dry_run: bool,
+}
+
+/// Cut `content` into sections; blank sections are dropped
+///
+/// Sections without a heading of their own are titled after their first
+/// line, or "Part N" when it is unusable.
+pub fn split_sections(content: &str, strategy: &SplitStrategy) -> CodexResult<Vec<SplitSection>> {
+    let cuts = match strategy {
+        SplitStrategy::Headings { level } => {
+            if !(1..=6).contains(level) {
+                return Err(CodexError::validation("Heading level must be between 1 and 6"));
+            }
+            heading_cuts(content, *level)
+        }
+        SplitStrategy::PageMarkers => page_cuts(content),
+        SplitStrategy::Offsets { offsets } => offset_cuts(content, offsets)?,
+    };
+
+    let mut sections = Vec::new();
+    for (index, (start, end, heading)) in cuts.iter().enumerate() {
+        let text = content[*start..*end].trim();
+        if text.is_empty() {
+            continue;
+        }
+        let title = heading.clone().unwrap_or_else(|| {
+            let derived = super::capture::derive_title(text);
+            if derived.provisional {
+                format!("Part {}", index + 1)
+            } else {
+                derived.title
+            }
+        });
+        sections.push(SplitSection {
+            title,
+            start: content[..*start].chars().count(),
+            end: content[..*end].chars().count(),
+            word_count: text.split_whitespace().count(),
+            excerpt: text.chars().take(EXCERPT_CHARS).collect(),
+            content: text.to_string(),
+        });
+    }
+    Ok(sections)
+}
+
+/// Title of the child document made from a section
+pub fn child_title(original_title: &str, section: &SplitSection) -> String {
+    format!("{}: {}", original_title, section.title)
+}
+
+/// Markdown table of contents linking to the child documents
+pub fn table_of_contents(original_title: &str, child_titles: &[String]) -> String {
+    let mut toc = format!("# {}\n\n", original_title);
+    for (i, title) in child_titles.iter().enumerate() {
+        toc.push_str(&format!("{}. [[{}]]\n", i + 1, title));
+    }
+    toc
+}
+
+/// Byte ranges of the lines of `content`, including their line breaks
+fn line_ranges(content: &str) -> impl Iterator<Item = (usize, &str)> {
+    content.split_inclusive('\n').scan(0, |offset, line| {
+        let start = *offset;
+        *offset += line.len();
+        Some((start, line))
+    })
+}
+
+/// Cut before headings of `level` or higher, skipping fenced code blocks
+fn heading_cuts(content: &str, level: u8) -> Vec<(usize, usize, Option<String>)> {
+    let mut starts: Vec<(usize, Option<String>)> = vec![(0, None)];
+    let mut in_fence = false;
+
+    for (offset, line) in line_ranges(content) {
+        let trimmed = line.trim_end();
+        if trimmed.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+        let hashes = trimmed.chars().take_while(|&c| c == '#').count();
+        if hashes == 0 || hashes > level as usize {
+            continue;
+        }
+        let rest = &trimmed[hashes..];
+        if !rest.starts_with(' ') || rest.trim().is_empty() {
+            continue;
+        }
+        starts.push((offset, Some(rest.trim().trim_end_matches('#').trim().to_string())));
+    }
+
+    ranges(content, starts)
+}
+
+/// Cut at form feeds and page marker lines; the markers themselves are dropped
+fn page_cuts(content: &str) -> Vec<(usize, usize, Option<String>)> {
+    let mut cuts = Vec::new();
+    let mut start = 0;
+
+    for (offset, line) in line_ranges(content) {
+        if PAGE_MARKER_RE.is_match(line.trim_end()) {
+            cuts.push((start, offset, None));
+            start = offset + line.len();
+        } else if let Some(feed) = line.find('\u{c}') {
+            cuts.push((start, offset + feed, None));
+            start = offset + feed + 1;
+        }
+    }
+    cuts.push((start, content.len(), None));
+    cuts
+}
+
+/// Cut at character offsets, which must lie inside the content
+fn offset_cuts(content: &str, offsets: &[usize]) -> CodexResult<Vec<(usize, usize, Option<String>)>> {
+    let length = content.chars().count();
+    let mut offsets = offsets.to_vec();
+    offsets.sort_unstable();
+    offsets.dedup();
+    if offsets.iter().any(|&o| o == 0 || o >= length) {
+        return Err(CodexError::validation(format!(
+            "Split offsets must be between 1 and {}",
+            length.saturating_sub(1)
+        )));
+    }
+
+    let mut starts = vec![(0, None)];
+    let mut wanted = offsets.iter().peekable();
+    for (chars, (bytes, _)) in content.char_indices().enumerate() {
+        if wanted.peek() == Some(&&chars) {
+            starts.push((bytes, None));
+            wanted.next();
+        }
+    }
+    Ok(ranges(content, starts))
+}
+
+/// Turn section start positions into (start, end, heading) ranges
+fn ranges(content: &str, starts: Vec<(usize, Option<String>)>) -> Vec<(usize, usize, Option<String>)> {
+    let ends: Vec<usize> = starts.iter().skip(1).map(|(s, _)| *s).chain([content.len()]).collect();
+    starts
+        .into_iter()
+        .zip(ends)
+        .map(|((start, heading), end)| (start, end, heading))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_strategies() {
+        let book = "Preface text.\n\n# One\nFirst chapter.\n```\n# not a heading\n```\n## One.1\nNested.\n# Two\nSecond chapter.\n";
+
+        let chapters = split_sections(book, &SplitStrategy::Headings { level: 1 }).unwrap();
+        let titles: Vec<&str> = chapters.iter().map(|s| s.title.as_str()).collect();
+        assert_eq!(titles, vec!["Preface text.", "One", "Two"]);
+        assert!(chapters[1].content.contains("## One.1"));
+        assert_eq!(chapters[2].end, book.chars().count());
+
+        let sections = split_sections(book, &SplitStrategy::Headings { level: 2 }).unwrap();
+        assert_eq!(sections.len(), 4);
+        assert!(split_sections(book, &SplitStrategy::Headings { level: 7 }).is_err());
+
+        let pages = split_sections("alpha\n--- Page 2 ---\nbeta\u{c}gamma", &SplitStrategy::PageMarkers).unwrap();
+        let contents: Vec<&str> = pages.iter().map(|s| s.content.as_str()).collect();
+        assert_eq!(contents, vec!["alpha", "beta", "gamma"]);
+
+        let parts = split_sections("héllo world", &SplitStrategy::Offsets { offsets: vec![6] }).unwrap();
+        assert_eq!(parts[0].content, "héllo");
+        assert_eq!(parts[1].start, 6);
+        assert!(split_sections("short", &SplitStrategy::Offsets { offsets: vec![5] }).is_err());
+
+        let toc = table_of_contents("Book", &["Book: One".to_string(), "Book: Two".to_string()]);
+        assert!(toc.contains("1. [[Book: One]]\n2. [[Book: Two]]"));
+    }
+}
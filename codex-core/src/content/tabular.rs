@@ -0,0 +1,239 @@
+//! Structured data (CSV/TSV) analysis for imports
+//!
+//! Tables are not indexed row by row. Instead a schema summary (column names,
+//! inferred types, row count and example values) becomes the searchable
+//! content, and the parsed schema is kept for table-aware consumers.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{CodexError, CodexResult};
+
+/// Extensions imported as structured tables
+pub const TABULAR_EXTENSIONS: &[&str] = &["csv", "tsv"];
+
+/// Number of rows inspected for type inference and examples
+const SAMPLE_ROWS: usize = 100;
+/// Distinct example values kept per column
+const EXAMPLES_PER_COLUMN: usize = 3;
+
+/// Inferred column type
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnType {
+    Integer,
+    Float,
+    Boolean,
+    Date,
+    Text,
+    /// No non-empty values in the sample
+    Empty,
+}
+
+impl ColumnType {
+    fn label(&self) -> &'static str {
+        match self {
+            ColumnType::Integer => "integer",
+            ColumnType::Float => "float",
+            ColumnType::Boolean => "boolean",
+            ColumnType::Date => "date",
+            ColumnType::Text => "text",
+            ColumnType::Empty => "empty",
+        }
+    }
+}
+
+/// Schema of a single column
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnSchema {
+    pub name: String,
+    pub data_type: ColumnType,
+    pub example_values: Vec<String>,
+    /// Empty cells seen in the sample
+    pub empty_count: usize,
+}
+
+/// Schema of a delimited table
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TableSchema {
+    pub columns: Vec<ColumnSchema>,
+    /// Number of data rows (excluding the header)
+    pub row_count: usize,
+    pub delimiter: char,
+    /// Number of rows used for type inference
+    pub sampled_rows: usize,
+}
+
+impl TableSchema {
+    /// Column names in file order
+    pub fn column_names(&self) -> Vec<String> {
+        self.columns.iter().map(|c| c.name.clone()).collect()
+    }
+
+    /// Render the schema as a readable, searchable summary
+    pub fn to_summary(&self, title: &str) -> String {
+        let mut summary = format!(
+            "Table: {}\nRows: {}\nColumns: {}\n",
+            title,
+            self.row_count,
+            self.columns.len()
+        );
+
+        for column in &self.columns {
+            summary.push_str(&format!("\n- {} ({})", column.name, column.data_type.label()));
+            if !column.example_values.is_empty() {
+                summary.push_str(&format!(": e.g. {}", column.example_values.join(", ")));
+            }
+        }
+
+        summary
+    }
+}
+
+/// Delimiter for a tabular file extension
+pub fn delimiter_for_extension(extension: &str) -> Option<u8> {
+    match extension.to_lowercase().as_str() {
+        "csv" => Some(b','),
+        "tsv" => Some(b'\t'),
+        _ => None,
+    }
+}
+
+/// Parse the header and rows of a delimited table and infer its schema
+pub fn analyze_table(text: &str, delimiter: u8) -> CodexResult<TableSchema> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .from_reader(text.as_bytes());
+
+    let table_error = |e: csv::Error| CodexError::content_processing(format!("Failed to parse table: {}", e));
+
+    let headers: Vec<String> = reader
+        .headers()
+        .map_err(table_error)?
+        .iter()
+        .enumerate()
+        .map(|(i, h)| match h.trim() {
+            "" => format!("column_{}", i + 1),
+            name => name.to_string(),
+        })
+        .collect();
+
+    if headers.is_empty() {
+        return Err(CodexError::content_processing("Table has no header row"));
+    }
+
+    let mut samples: Vec<Vec<String>> = vec![Vec::new(); headers.len()];
+    let mut empty_counts = vec![0; headers.len()];
+    let mut row_count = 0;
+
+    for record in reader.records() {
+        let record = record.map_err(table_error)?;
+        if row_count < SAMPLE_ROWS {
+            for (i, column) in samples.iter_mut().enumerate() {
+                match record.get(i).map(str::trim) {
+                    Some(value) if !value.is_empty() => column.push(value.to_string()),
+                    _ => empty_counts[i] += 1,
+                }
+            }
+        }
+        row_count += 1;
+    }
+
+    let columns = headers
+        .into_iter()
+        .zip(samples)
+        .zip(empty_counts)
+        .map(|((name, values), empty_count)| {
+            let mut example_values: Vec<String> = Vec::new();
+            for value in &values {
+                if example_values.len() == EXAMPLES_PER_COLUMN {
+                    break;
+                }
+                if !example_values.contains(value) {
+                    example_values.push(value.clone());
+                }
+            }
+
+            ColumnSchema {
+                name,
+                data_type: infer_type(&values),
+                example_values,
+                empty_count,
+            }
+        })
+        .collect();
+
+    Ok(TableSchema {
+        columns,
+        row_count,
+        delimiter: delimiter as char,
+        sampled_rows: row_count.min(SAMPLE_ROWS),
+    })
+}
+
+/// Pick the narrowest type that fits every sampled value
+fn infer_type(values: &[String]) -> ColumnType {
+    if values.is_empty() {
+        return ColumnType::Empty;
+    }
+
+    let all = |check: fn(&str) -> bool| values.iter().all(|v| check(v));
+
+    if all(|v| v.parse::<i64>().is_ok()) {
+        ColumnType::Integer
+    } else if all(|v| v.parse::<f64>().is_ok()) {
+        ColumnType::Float
+    } else if all(|v| matches!(v.to_lowercase().as_str(), "true" | "false" | "yes" | "no")) {
+        ColumnType::Boolean
+    } else if all(|v| {
+        chrono::NaiveDate::parse_from_str(v, "%Y-%m-%d").is_ok()
+            || chrono::DateTime::parse_from_rfc3339(v).is_ok()
+    }) {
+        ColumnType::Date
+    } else {
+        ColumnType::Text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CSV: &str = "id,name,score,active,joined,notes\n\
+                       1,Ada,91.5,true,2024-01-02,\n\
+                       2,Grace,88,false,2024-03-04,first\n\
+                       3,Linus,70.25,yes,2023-12-31,\n";
+
+    #[test]
+    fn test_analyze_csv_schema() {
+        let schema = analyze_table(CSV, b',').unwrap();
+
+        assert_eq!(schema.row_count, 3);
+        assert_eq!(schema.column_names(), vec!["id", "name", "score", "active", "joined", "notes"]);
+
+        let types: Vec<ColumnType> = schema.columns.iter().map(|c| c.data_type).collect();
+        assert_eq!(
+            types,
+            vec![
+                ColumnType::Integer,
+                ColumnType::Text,
+                ColumnType::Float,
+                ColumnType::Boolean,
+                ColumnType::Date,
+                ColumnType::Text,
+            ]
+        );
+        assert_eq!(schema.columns[1].example_values, vec!["Ada", "Grace", "Linus"]);
+        assert_eq!(schema.columns[5].empty_count, 2);
+    }
+
+    #[test]
+    fn test_tsv_summary() {
+        let schema = analyze_table("city\tpopulation\nOslo\t709000\n", b'\t').unwrap();
+        let summary = schema.to_summary("cities");
+
+        assert!(summary.starts_with("Table: cities\nRows: 1\nColumns: 2\n"));
+        assert!(summary.contains("- population (integer): e.g. 709000"));
+        assert_eq!(schema.delimiter, '\t');
+    }
+}
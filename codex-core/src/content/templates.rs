@@ -0,0 +1,77 @@
+//! Document templates
+//!
+//! Templates are note bodies containing `{{variable}}` placeholders. Rendering
+//! fills them from caller-supplied variables plus the `{{date}}` and
+//! `{{time}}` built-ins; placeholders without a value render empty.
+
+use std::collections::HashMap;
+use chrono::NaiveDateTime;
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+
+/// `{{name}}`, allowing whitespace inside the braces
+static PLACEHOLDER_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\{\{\s*([A-Za-z0-9_-]+)\s*\}\}").unwrap());
+
+/// Variable holding the created document's title
+pub const TITLE_VARIABLE: &str = "title";
+
+/// Render a template body
+///
+/// Caller variables take precedence over the built-ins, so `date` can be
+/// overridden when back-filling notes.
+pub fn render_template(content: &str, variables: &HashMap<String, String>, now: NaiveDateTime) -> String {
+    PLACEHOLDER_RE
+        .replace_all(content, |captures: &Captures| {
+            let name = &captures[1];
+            match variables.get(name) {
+                Some(value) => value.clone(),
+                None => match name {
+                    "date" => now.format("%Y-%m-%d").to_string(),
+                    "time" => now.format("%H:%M").to_string(),
+                    _ => String::new(),
+                },
+            }
+        })
+        .into_owned()
+}
+
+/// Names of the placeholders used in a template, in order of first use
+pub fn template_variables(content: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for captures in PLACEHOLDER_RE.captures_iter(content) {
+        let name = captures[1].to_string();
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(datetime: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(datetime, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn test_render_variables_and_builtins() {
+        let content = "# {{ title }}\n{{date}} {{time}} with {{attendees}}{{missing}}";
+        let variables = HashMap::from([
+            ("title".to_string(), "Standup".to_string()),
+            ("attendees".to_string(), "Ana, Bo".to_string()),
+        ]);
+
+        let rendered = render_template(content, &variables, at("2024-05-06 09:30"));
+        assert_eq!(rendered, "# Standup\n2024-05-06 09:30 with Ana, Bo");
+    }
+
+    #[test]
+    fn test_caller_overrides_builtin_date() {
+        let variables = HashMap::from([("date".to_string(), "2023-01-01".to_string())]);
+        assert_eq!(render_template("{{date}}", &variables, at("2024-05-06 09:30")), "2023-01-01");
+        assert_eq!(template_variables("{{a}} {{b}} {{ a }}"), vec!["a", "b"]);
+    }
+}
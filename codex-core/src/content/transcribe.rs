@@ -0,0 +1,377 @@
+//! Audio transcription for audio imports
+//!
+//! Speech recognition is backed by a local Whisper model (via `whisper-rs`) and
+//! audio decoding by `symphonia`; both are only compiled in when the
+//! `transcription` cargo feature is enabled. Transcripts are rendered as one
+//! `[hh:mm:ss] text` line per Whisper segment so that chunking and RAG
+//! citations can point back to a position in the recording.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::debug;
+
+use crate::{CodexError, CodexResult};
+
+/// Audio extensions accepted by the transcription pipeline
+pub const AUDIO_EXTENSIONS: &[&str] = &["wav", "mp3", "m4a"];
+
+/// Sample rate expected by Whisper models
+#[cfg_attr(not(feature = "transcription"), allow(dead_code))]
+const WHISPER_SAMPLE_RATE: u32 = 16_000;
+
+/// A timed piece of a transcript
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct TranscriptSegment {
+    /// Segment start offset in milliseconds
+    pub start_ms: u64,
+    /// Segment end offset in milliseconds
+    pub end_ms: u64,
+    /// Recognized text
+    pub text: String,
+}
+
+/// Full transcript of an audio file
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Transcript {
+    pub segments: Vec<TranscriptSegment>,
+    /// Length of the recording in milliseconds
+    pub duration_ms: u64,
+}
+
+impl Transcript {
+    /// Render the transcript as document content with `[hh:mm:ss]` markers
+    pub fn to_content(&self) -> String {
+        self.segments
+            .iter()
+            .filter(|s| !s.text.trim().is_empty())
+            .map(|s| format!("[{}] {}", format_timestamp(s.start_ms), s.text.trim()))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Recording length rounded up to whole minutes, used as the reading time
+    pub fn duration_minutes(&self) -> i64 {
+        self.duration_ms.div_ceil(60_000).max(1) as i64
+    }
+}
+
+/// Format a millisecond offset as `hh:mm:ss`
+pub fn format_timestamp(ms: u64) -> String {
+    let total_seconds = ms / 1000;
+    format!(
+        "{:02}:{:02}:{:02}",
+        total_seconds / 3600,
+        (total_seconds / 60) % 60,
+        total_seconds % 60
+    )
+}
+
+/// Local Whisper transcriber
+#[derive(Debug, Clone)]
+pub struct Transcriber {
+    model_path: Option<PathBuf>,
+    language: String,
+}
+
+impl Transcriber {
+    /// Create a new transcriber for a ggml Whisper model
+    ///
+    /// `language` is a Whisper language code (e.g. "en") or "auto" to detect it.
+    pub fn new(model_path: Option<PathBuf>, language: impl Into<String>) -> Self {
+        Self {
+            model_path,
+            language: language.into(),
+        }
+    }
+
+    /// Whether transcription support was compiled into this build
+    pub fn is_available() -> bool {
+        cfg!(feature = "transcription")
+    }
+
+    /// Check whether a file extension is handled by the transcription pipeline
+    pub fn is_audio_extension(extension: &str) -> bool {
+        AUDIO_EXTENSIONS.contains(&extension.to_lowercase().as_str())
+    }
+
+    /// MIME type stored on documents created from an audio file
+    pub fn content_type(extension: &str) -> &'static str {
+        match extension.to_lowercase().as_str() {
+            "mp3" => "audio/mpeg",
+            "m4a" => "audio/mp4",
+            _ => "audio/wav",
+        }
+    }
+
+    /// Transcribe an audio file
+    ///
+    /// Decoding and recognition are CPU-bound, so they run on the blocking
+    /// thread pool. `on_progress` receives values in the range 0.0-1.0.
+    pub async fn transcribe<F>(&self, audio_path: &Path, on_progress: F) -> CodexResult<Transcript>
+    where
+        F: Fn(f32) + Send + Sync + 'static,
+    {
+        if !audio_path.exists() {
+            return Err(CodexError::not_found(format!(
+                "Audio file does not exist: {}",
+                audio_path.display()
+            )));
+        }
+
+        let model_path = self.model_path.clone().ok_or_else(|| {
+            CodexError::config("No Whisper model configured (set content.whisper_model_path)")
+        })?;
+        if !model_path.exists() {
+            return Err(CodexError::not_found(format!(
+                "Whisper model not found: {}",
+                model_path.display()
+            )));
+        }
+
+        let path = audio_path.to_path_buf();
+        let language = self.language.clone();
+        let on_progress = Arc::new(on_progress);
+
+        let transcript = tokio::task::spawn_blocking(move || {
+            Self::run(&path, &model_path, &language, on_progress)
+        })
+        .await
+        .map_err(|e| CodexError::internal(format!("Transcription task failed: {}", e)))??;
+
+        debug!(
+            "Transcribed {:?}: {} segments, {}ms",
+            audio_path,
+            transcript.segments.len(),
+            transcript.duration_ms
+        );
+
+        Ok(transcript)
+    }
+
+    #[cfg(feature = "transcription")]
+    fn run<F>(path: &Path, model_path: &Path, language: &str, on_progress: Arc<F>) -> CodexResult<Transcript>
+    where
+        F: Fn(f32) + Send + Sync + 'static,
+    {
+        use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
+
+        let (samples, sample_rate, channels) = decode_audio(path)?;
+        let mono = downmix(&samples, channels);
+        let samples = resample_linear(&mono, sample_rate, WHISPER_SAMPLE_RATE);
+        let duration_ms = samples.len() as u64 * 1000 / WHISPER_SAMPLE_RATE as u64;
+        on_progress(0.1);
+
+        let model = model_path.to_str().ok_or_else(|| {
+            CodexError::config(format!("Whisper model path is not valid UTF-8: {}", model_path.display()))
+        })?;
+        let ctx = WhisperContext::new_with_params(model, WhisperContextParameters::default())
+            .map_err(|e| CodexError::ai_inference(format!("Failed to load Whisper model: {}", e)))?;
+        let mut state = ctx
+            .create_state()
+            .map_err(|e| CodexError::ai_inference(format!("Failed to create Whisper state: {}", e)))?;
+
+        let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+        params.set_language(Some(language));
+        params.set_n_threads(num_cpus::get().min(8) as i32);
+        params.set_print_progress(false);
+        params.set_print_realtime(false);
+        params.set_print_special(false);
+        params.set_print_timestamps(false);
+
+        let callback_progress = Arc::clone(&on_progress);
+        params.set_progress_callback_safe(move |percent: i32| {
+            callback_progress(0.1 + 0.9 * (percent.clamp(0, 100) as f32 / 100.0));
+        });
+
+        state
+            .full(params, &samples)
+            .map_err(|e| CodexError::ai_inference(format!("Whisper transcription failed: {}", e)))?;
+
+        let segment_count = state
+            .full_n_segments()
+            .map_err(|e| CodexError::ai_inference(format!("Failed to read Whisper segments: {}", e)))?;
+
+        let mut segments = Vec::with_capacity(segment_count.max(0) as usize);
+        for i in 0..segment_count {
+            let text = state
+                .full_get_segment_text(i)
+                .map_err(|e| CodexError::ai_inference(format!("Failed to read segment text: {}", e)))?;
+            // Whisper reports timestamps in 10ms units
+            let t0 = state.full_get_segment_t0(i).unwrap_or(0).max(0) as u64 * 10;
+            let t1 = state.full_get_segment_t1(i).unwrap_or(0).max(0) as u64 * 10;
+
+            segments.push(TranscriptSegment {
+                start_ms: t0,
+                end_ms: t1,
+                text: text.trim().to_string(),
+            });
+        }
+
+        on_progress(1.0);
+
+        Ok(Transcript { segments, duration_ms })
+    }
+
+    #[cfg(not(feature = "transcription"))]
+    fn run<F>(_path: &Path, _model_path: &Path, _language: &str, _on_progress: Arc<F>) -> CodexResult<Transcript>
+    where
+        F: Fn(f32) + Send + Sync + 'static,
+    {
+        Err(CodexError::content_processing(
+            "Audio transcription is not enabled in this build (enable the `transcription` feature)",
+        ))
+    }
+}
+
+/// Decode an audio file into interleaved f32 samples
+///
+/// Returns the samples along with the source sample rate and channel count.
+#[cfg(feature = "transcription")]
+fn decode_audio(path: &Path) -> CodexResult<(Vec<f32>, u32, usize)> {
+    use symphonia::core::audio::SampleBuffer;
+    use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+    use symphonia::core::errors::Error as SymphoniaError;
+    use symphonia::core::formats::FormatOptions;
+    use symphonia::core::io::MediaSourceStream;
+    use symphonia::core::meta::MetadataOptions;
+    use symphonia::core::probe::Hint;
+
+    let decode_error = |e: SymphoniaError| {
+        CodexError::content_processing(format!("Failed to decode audio {}: {}", path.display(), e))
+    };
+
+    let file = std::fs::File::open(path)?;
+    let stream = MediaSourceStream::new(Box::new(file), Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(extension);
+    }
+
+    let probed = symphonia::default::get_probe()
+        .format(&hint, stream, &FormatOptions::default(), &MetadataOptions::default())
+        .map_err(decode_error)?;
+    let mut format = probed.format;
+
+    let track = format
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or_else(|| CodexError::content_processing("No audio track found"))?;
+    let track_id = track.id;
+    let sample_rate = track.codec_params.sample_rate.unwrap_or(WHISPER_SAMPLE_RATE);
+    let channels = track.codec_params.channels.map(|c| c.count()).unwrap_or(1).max(1);
+
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .map_err(decode_error)?;
+
+    let mut samples = Vec::new();
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(decode_error(e)),
+        };
+
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        match decoder.decode(&packet) {
+            Ok(decoded) => {
+                let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, *decoded.spec());
+                buffer.copy_interleaved_ref(decoded);
+                samples.extend_from_slice(buffer.samples());
+            }
+            // Corrupt frames are skipped rather than failing the whole file
+            Err(SymphoniaError::DecodeError(e)) => debug!("Skipping undecodable audio frame: {}", e),
+            Err(e) => return Err(decode_error(e)),
+        }
+    }
+
+    Ok((samples, sample_rate, channels))
+}
+
+/// Average interleaved channels down to mono
+#[cfg_attr(not(feature = "transcription"), allow(dead_code))]
+fn downmix(samples: &[f32], channels: usize) -> Vec<f32> {
+    if channels <= 1 {
+        return samples.to_vec();
+    }
+
+    samples
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+        .collect()
+}
+
+/// Resample mono audio with linear interpolation
+#[cfg_attr(not(feature = "transcription"), allow(dead_code))]
+fn resample_linear(samples: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
+    if from_rate == to_rate || samples.is_empty() {
+        return samples.to_vec();
+    }
+
+    let ratio = from_rate as f64 / to_rate as f64;
+    let output_len = (samples.len() as f64 / ratio).floor() as usize;
+
+    (0..output_len)
+        .map(|i| {
+            let position = i as f64 * ratio;
+            let index = position.floor() as usize;
+            let fraction = (position - index as f64) as f32;
+            let current = samples[index];
+            let next = samples.get(index + 1).copied().unwrap_or(current);
+            current + (next - current) * fraction
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "00:00:00");
+        assert_eq!(format_timestamp(61_500), "00:01:01");
+        assert_eq!(format_timestamp(3_723_000), "01:02:03");
+    }
+
+    #[test]
+    fn test_transcript_content_and_duration() {
+        let transcript = Transcript {
+            segments: vec![
+                TranscriptSegment { start_ms: 0, end_ms: 4_000, text: " Welcome back.".to_string() },
+                TranscriptSegment { start_ms: 4_000, end_ms: 5_000, text: "  ".to_string() },
+                TranscriptSegment { start_ms: 65_000, end_ms: 70_000, text: "Chapter two".to_string() },
+            ],
+            duration_ms: 125_000,
+        };
+
+        assert_eq!(transcript.to_content(), "[00:00:00] Welcome back.\n[00:01:05] Chapter two");
+        assert_eq!(transcript.duration_minutes(), 3);
+    }
+
+    #[test]
+    fn test_downmix_and_resample() {
+        assert_eq!(downmix(&[1.0, 0.0, 0.5, 0.5], 2), vec![0.5, 0.5]);
+
+        let resampled = resample_linear(&[0.0, 1.0, 2.0, 3.0], 32_000, 16_000);
+        assert_eq!(resampled, vec![0.0, 2.0]);
+    }
+
+    #[cfg(not(feature = "transcription"))]
+    #[tokio::test]
+    async fn test_transcribe_without_feature_fails() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let audio = temp_dir.path().join("talk.wav");
+        let model = temp_dir.path().join("ggml-base.en.bin");
+        std::fs::write(&audio, b"RIFF").unwrap();
+        std::fs::write(&model, b"model").unwrap();
+
+        let result = Transcriber::new(Some(model), "en").transcribe(&audio, |_| {}).await;
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,510 @@
+//! Suggested actions for documents waiting in the inbox
+//!
+//! Each document tagged [`INBOX_TAG`] is compared with the rest of the vault
+//! through its stored embeddings (the mean of its chunk vectors): the nearest
+//! category centroid proposes a category, the tags and collections of its
+//! nearest neighbours propose tags and a collection, and how close it is to
+//! archived documents decides between archiving and keeping it. Documents
+//! are only triaged once their embeddings exist, so no inference runs when
+//! suggestions are requested.
+//!
+//! Only a sample of the vault is read per run, see [`candidates`].
+//!
+//! Every part of a suggestion is only proposed above a confidence threshold.
+//! The thresholds live in settings and move a little with each accepted or
+//! rejected suggestion, see [`TriageThresholds::learn`].
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::bulk::{BulkChanges, BulkUpdateSummary};
+use super::capture::INBOX_TAG;
+use crate::db::models::DocumentLabels;
+use crate::db::VectorOps;
+
+/// Setting holding the learned [`TriageThresholds`]
+pub const TRIAGE_THRESHOLDS_SETTING: &str = "triage.thresholds";
+/// Tags proposed per document
+pub const SUGGESTED_TAGS: usize = 3;
+/// Neighbours whose tags and collections are considered
+const NEIGHBOURS: usize = 10;
+/// Newest documents read per category, archived and kept ones counted separately
+pub const CORPUS_SAMPLE: usize = 50;
+/// Share of the distance to a decided confidence a threshold moves per outcome
+const LEARNING_RATE: f32 = 0.2;
+/// Margin a threshold is pushed past a decided confidence
+const LEARNING_MARGIN: f32 = 0.02;
+
+/// Document as seen by triage
+#[derive(Debug, Clone)]
+pub struct TriageProfile {
+    pub id: String,
+    pub title: String,
+    pub category: Option<String>,
+    pub tags: Vec<String>,
+    pub collections: Vec<String>,
+    pub is_archived: bool,
+    pub created_at: String,
+    /// Normalized mean of the document's chunk embeddings
+    pub vector: Vec<f32>,
+}
+
+impl TriageProfile {
+    /// Whether the document is waiting in the inbox
+    pub fn is_inbox(&self) -> bool {
+        !self.is_archived && self.tags.iter().any(|tag| tag == INBOX_TAG)
+    }
+}
+
+/// Minimum confidence for each part of a suggestion, learned from outcomes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TriageThresholds {
+    /// Similarity to a category centroid
+    pub category: f32,
+    /// Similarity-weighted share of neighbours carrying a tag
+    pub tags: f32,
+    /// Similarity-weighted share of neighbours in a collection
+    pub collection: f32,
+    /// Similarity to the closest archived document
+    pub archive: f32,
+    pub accepted: u64,
+    pub rejected: u64,
+}
+
+impl Default for TriageThresholds {
+    fn default() -> Self {
+        Self {
+            category: 0.5,
+            tags: 0.3,
+            collection: 0.4,
+            archive: 0.8,
+            accepted: 0,
+            rejected: 0,
+        }
+    }
+}
+
+impl TriageThresholds {
+    /// Move the thresholds after the user decided on a suggestion
+    ///
+    /// An accepted part lowers its threshold towards the confidence it was
+    /// proposed with and a rejected one raises it past that confidence, so
+    /// similar suggestions become more or less likely. Rejecting "keep"
+    /// means the document should have been archived, which lowers the
+    /// archive threshold.
+    pub fn learn(&mut self, suggestion: &TriageSuggestion, decision: &TriageDecision) {
+        if let Some(ref category) = suggestion.category {
+            nudge(&mut self.category, category.confidence, decision.category);
+        }
+        for tag in &suggestion.tags {
+            nudge(&mut self.tags, tag.confidence, decision.tags);
+        }
+        if let Some(ref collection) = suggestion.collection {
+            nudge(&mut self.collection, collection.confidence, decision.collection);
+        }
+        // An accepted "keep" says nothing about how close archiving was
+        if suggestion.action == TriageAction::Archive || !decision.action {
+            nudge(&mut self.archive, suggestion.archive_similarity, decision.archives(suggestion.action));
+        }
+
+        if decision.accepts_all(suggestion) {
+            self.accepted += 1;
+        } else {
+            self.rejected += 1;
+        }
+    }
+}
+
+/// Move a threshold towards proposing (`wanted`) or withholding a part of the given confidence
+fn nudge(threshold: &mut f32, confidence: f32, wanted: bool) {
+    let target = if wanted {
+        confidence.min(*threshold) - LEARNING_MARGIN
+    } else {
+        confidence.max(*threshold) + LEARNING_MARGIN
+    };
+    *threshold = (*threshold + LEARNING_RATE * (target - *threshold)).clamp(0.05, 0.99);
+}
+
+/// Proposed category
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CategorySuggestion {
+    pub category: String,
+    pub confidence: f32,
+}
+
+/// Proposed tag
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TagSuggestion {
+    pub tag: String,
+    pub confidence: f32,
+}
+
+/// Proposed collection
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CollectionSuggestion {
+    pub collection_id: String,
+    pub confidence: f32,
+}
+
+/// Whether an inbox document is worth keeping
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TriageAction {
+    Archive,
+    Keep,
+}
+
+/// Suggested triage of one inbox document
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TriageSuggestion {
+    pub document_id: String,
+    pub title: String,
+    /// None when no category is confident enough or it is already set
+    pub category: Option<CategorySuggestion>,
+    /// Up to [`SUGGESTED_TAGS`] tags the document does not have yet
+    pub tags: Vec<TagSuggestion>,
+    pub collection: Option<CollectionSuggestion>,
+    pub action: TriageAction,
+    /// Similarity to the closest archived document, 0.0 without any
+    pub archive_similarity: f32,
+}
+
+/// Parts of a suggestion the user accepted; everything else is rejected
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TriageDecision {
+    pub category: bool,
+    pub tags: bool,
+    pub collection: bool,
+    /// Follow the suggested action; rejecting "keep" archives the document
+    pub action: bool,
+}
+
+impl TriageDecision {
+    /// Accept the whole suggestion
+    pub fn accept_all() -> Self {
+        Self {
+            category: true,
+            tags: true,
+            collection: true,
+            action: true,
+        }
+    }
+
+    /// Whether the document ends up archived
+    pub fn archives(&self, action: TriageAction) -> bool {
+        (action == TriageAction::Archive) == self.action
+    }
+
+    /// Whether every part that was proposed got accepted
+    pub fn accepts_all(&self, suggestion: &TriageSuggestion) -> bool {
+        (suggestion.category.is_none() || self.category)
+            && (suggestion.tags.is_empty() || self.tags)
+            && (suggestion.collection.is_none() || self.collection)
+            && self.action
+    }
+
+    /// Organization edits for the accepted parts; the document always leaves the inbox
+    pub fn changes(&self, suggestion: &TriageSuggestion) -> BulkChanges {
+        BulkChanges {
+            category: suggestion.category.as_ref().filter(|_| self.category).map(|c| c.category.clone()),
+            add_tags: if self.tags {
+                suggestion.tags.iter().map(|t| t.tag.clone()).collect()
+            } else {
+                Vec::new()
+            },
+            remove_tags: vec![INBOX_TAG.to_string()],
+            archived: self.archives(suggestion.action).then_some(true),
+            favorite: None,
+            ..BulkChanges::default()
+        }
+    }
+}
+
+/// Outcome of applying a triage decision
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TriageResult {
+    /// Organization edits, undoable through the operation journal
+    pub update: BulkUpdateSummary,
+    /// Whether the document was added to the suggested collection
+    pub added_to_collection: bool,
+}
+
+/// Documents whose vectors a run of up to `limit` suggestions reads
+///
+/// Returns the `limit` newest inbox documents, newest first, and the corpus
+/// they are compared with: the [`CORPUS_SAMPLE`] newest documents of each
+/// category (uncategorized ones forming their own), archived and kept ones
+/// sampled separately. Category centroids and neighbours come from this
+/// sample, so a run reads a bounded number of vectors per category however
+/// large the vault grows.
+pub fn candidates(mut labels: Vec<DocumentLabels>, limit: usize) -> (Vec<DocumentLabels>, Vec<DocumentLabels>) {
+    labels.sort_by(|a, b| b.created_at.cmp(&a.created_at).then_with(|| a.id.cmp(&b.id)));
+
+    let mut inbox = Vec::new();
+    let mut corpus = Vec::new();
+    let mut sampled: HashMap<(Option<String>, bool), usize> = HashMap::new();
+    for document in labels {
+        if !document.is_archived && document.get_tags().iter().any(|tag| tag == INBOX_TAG) {
+            if inbox.len() < limit {
+                inbox.push(document);
+            }
+            continue;
+        }
+        let taken = sampled.entry((document.category.clone(), document.is_archived)).or_default();
+        if *taken < CORPUS_SAMPLE {
+            *taken += 1;
+            corpus.push(document);
+        }
+    }
+
+    (inbox, corpus)
+}
+
+/// Normalized mean of chunk vectors, None without any usable vector
+pub fn document_vector<'a>(chunks: impl IntoIterator<Item = &'a [f32]>) -> Option<Vec<f32>> {
+    let mut sum: Vec<f32> = Vec::new();
+    for chunk in chunks {
+        if sum.is_empty() {
+            sum = vec![0.0; chunk.len()];
+        }
+        if chunk.len() != sum.len() {
+            continue;
+        }
+        for (total, value) in sum.iter_mut().zip(chunk) {
+            *total += value;
+        }
+    }
+
+    let norm = sum.iter().map(|v| v * v).sum::<f32>().sqrt();
+    (norm > 0.0).then(|| sum.into_iter().map(|v| v / norm).collect())
+}
+
+/// Suggestion builder over the triaged (non-inbox) part of the vault
+pub struct Triage<'a> {
+    corpus: Vec<&'a TriageProfile>,
+    centroids: Vec<(String, Vec<f32>)>,
+    thresholds: &'a TriageThresholds,
+}
+
+impl<'a> Triage<'a> {
+    pub fn new(profiles: &'a [TriageProfile], thresholds: &'a TriageThresholds) -> Self {
+        let corpus: Vec<&TriageProfile> = profiles.iter().filter(|p| !p.is_inbox()).collect();
+
+        let mut members: HashMap<&str, Vec<&[f32]>> = HashMap::new();
+        for profile in &corpus {
+            if let Some(ref category) = profile.category {
+                members.entry(category.as_str()).or_default().push(profile.vector.as_slice());
+            }
+        }
+        let mut centroids: Vec<(String, Vec<f32>)> = members
+            .into_iter()
+            .filter_map(|(category, vectors)| Some((category.to_string(), document_vector(vectors)?)))
+            .collect();
+        centroids.sort_by(|a, b| a.0.cmp(&b.0));
+
+        Self {
+            corpus,
+            centroids,
+            thresholds,
+        }
+    }
+
+    /// Suggestion for one inbox document
+    pub fn suggest(&self, document: &TriageProfile) -> TriageSuggestion {
+        let similarity = |other: &[f32]| VectorOps::cosine_similarity(&document.vector, other);
+
+        let category = self
+            .centroids
+            .iter()
+            .map(|(category, centroid)| (category, similarity(centroid)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .filter(|(category, confidence)| {
+                *confidence >= self.thresholds.category && document.category.as_ref() != Some(*category)
+            })
+            .map(|(category, confidence)| CategorySuggestion {
+                category: category.clone(),
+                confidence,
+            });
+
+        let mut neighbours: Vec<(&TriageProfile, f32)> = self
+            .corpus
+            .iter()
+            .filter(|p| p.id != document.id)
+            .map(|p| (*p, similarity(&p.vector).max(0.0)))
+            .collect();
+        neighbours.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        let archive_similarity = neighbours
+            .iter()
+            .find(|(p, _)| p.is_archived)
+            .map(|(_, s)| *s)
+            .unwrap_or(0.0);
+        let kept_similarity = neighbours
+            .iter()
+            .find(|(p, _)| !p.is_archived)
+            .map(|(_, s)| *s)
+            .unwrap_or(0.0);
+        let action = if archive_similarity >= self.thresholds.archive && archive_similarity > kept_similarity {
+            TriageAction::Archive
+        } else {
+            TriageAction::Keep
+        };
+
+        neighbours.truncate(NEIGHBOURS);
+        let total: f32 = neighbours.iter().map(|(_, s)| s).sum();
+
+        let tags = weighted_votes(&neighbours, total, |p| &p.tags, |tag| {
+            tag != INBOX_TAG && !document.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
+        })
+        .into_iter()
+        .filter(|(_, confidence)| *confidence >= self.thresholds.tags)
+        .take(SUGGESTED_TAGS)
+        .map(|(tag, confidence)| TagSuggestion { tag, confidence })
+        .collect();
+
+        let collection = weighted_votes(&neighbours, total, |p| &p.collections, |id| !document.collections.contains(id))
+            .into_iter()
+            .next()
+            .filter(|(_, confidence)| *confidence >= self.thresholds.collection)
+            .map(|(collection_id, confidence)| CollectionSuggestion {
+                collection_id,
+                confidence,
+            });
+
+        TriageSuggestion {
+            document_id: document.id.clone(),
+            title: document.title.clone(),
+            category,
+            tags,
+            collection,
+            action,
+            archive_similarity,
+        }
+    }
+}
+
+/// Labels of the neighbours ranked by the share of similarity behind them
+fn weighted_votes(
+    neighbours: &[(&TriageProfile, f32)],
+    total: f32,
+    labels: impl Fn(&TriageProfile) -> &Vec<String>,
+    keep: impl Fn(&String) -> bool,
+) -> Vec<(String, f32)> {
+    if total <= 0.0 {
+        return Vec::new();
+    }
+
+    let mut votes: HashMap<&String, f32> = HashMap::new();
+    for (profile, similarity) in neighbours {
+        for label in labels(profile).iter().filter(|label| keep(label)) {
+            *votes.entry(label).or_default() += similarity;
+        }
+    }
+
+    let mut ranked: Vec<(String, f32)> = votes.into_iter().map(|(label, vote)| (label.clone(), vote / total)).collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    ranked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(id: &str, vector: [f32; 3], category: Option<&str>, tags: &[&str], archived: bool) -> TriageProfile {
+        TriageProfile {
+            id: id.to_string(),
+            title: id.to_string(),
+            category: category.map(str::to_string),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            collections: Vec::new(),
+            is_archived: archived,
+            created_at: String::new(),
+            vector: document_vector([vector.as_slice()]).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_suggestions_follow_similar_documents() {
+        let mut recipes = profile("soup", [1.0, 0.1, 0.0], Some("cooking"), &["recipe", "soup"], false);
+        recipes.collections = vec!["kitchen".to_string()];
+        let profiles = vec![
+            recipes,
+            profile("bread", [0.9, 0.2, 0.0], Some("cooking"), &["recipe", "baking"], false),
+            profile("invoice", [0.0, 0.0, 1.0], Some("finance"), &["paid"], true),
+            profile("stew", [0.95, 0.15, 0.0], None, &["inbox"], false),
+            profile("receipt", [0.0, 0.1, 1.0], None, &["inbox", "paid"], false),
+        ];
+        let thresholds = TriageThresholds::default();
+        let triage = Triage::new(&profiles, &thresholds);
+
+        let stew = triage.suggest(&profiles[3]);
+        assert_eq!(stew.category.as_ref().map(|c| c.category.as_str()), Some("cooking"));
+        assert_eq!(stew.tags[0].tag, "recipe");
+        assert!(stew.tags.len() <= SUGGESTED_TAGS && stew.tags.iter().all(|t| t.tag != "inbox"));
+        assert_eq!(stew.collection.as_ref().map(|c| c.collection_id.as_str()), Some("kitchen"));
+        assert_eq!(stew.action, TriageAction::Keep);
+
+        // Looks like what was archived before, and its existing tag is not proposed again
+        let receipt = triage.suggest(&profiles[4]);
+        assert_eq!(receipt.category.as_ref().map(|c| c.category.as_str()), Some("finance"));
+        assert_eq!(receipt.action, TriageAction::Archive);
+        assert!(receipt.tags.iter().all(|t| t.tag != "paid"));
+    }
+
+    #[test]
+    fn test_outcomes_move_thresholds() {
+        let suggestion = TriageSuggestion {
+            document_id: "d".to_string(),
+            title: "d".to_string(),
+            category: Some(CategorySuggestion { category: "cooking".to_string(), confidence: 0.6 }),
+            tags: Vec::new(),
+            collection: None,
+            action: TriageAction::Keep,
+            archive_similarity: 0.7,
+        };
+        let defaults = TriageThresholds::default();
+
+        let mut accepted = defaults.clone();
+        accepted.learn(&suggestion, &TriageDecision::accept_all());
+        assert!(accepted.category < defaults.category);
+        assert_eq!(accepted.archive, defaults.archive);
+        assert_eq!((accepted.accepted, accepted.rejected), (1, 0));
+
+        // Rejecting "keep" archives the document and makes archiving easier to propose
+        let mut rejected = defaults.clone();
+        rejected.learn(&suggestion, &TriageDecision::default());
+        assert!(rejected.category > defaults.category);
+        assert!(rejected.archive < defaults.archive);
+        assert_eq!(rejected.rejected, 1);
+
+        let changes = TriageDecision::default().changes(&suggestion);
+        assert_eq!((changes.category, changes.archived), (None, Some(true)));
+        assert_eq!(changes.remove_tags, vec![INBOX_TAG.to_string()]);
+    }
+
+    #[test]
+    fn test_candidates_sample_each_category() {
+        let labels = |id: usize, category: &str, tags: &str, archived: bool| DocumentLabels {
+            id: format!("{}-{}", category, id),
+            title: String::new(),
+            category: Some(category.to_string()),
+            tags: Some(tags.to_string()),
+            collections: "[]".to_string(),
+            is_archived: archived,
+            created_at: format!("2024-01-01T00:{:02}:00Z", id % 60),
+        };
+        let mut documents: Vec<DocumentLabels> = (0..CORPUS_SAMPLE + 5).map(|i| labels(i, "cooking", "[]", false)).collect();
+        documents.extend((0..3).map(|i| labels(i, "cooking", "[]", true)));
+        documents.extend((0..4).map(|i| labels(i, "inbox", r#"["inbox"]"#, false)));
+
+        let (inbox, corpus) = candidates(documents, 2);
+        assert_eq!(inbox.iter().map(|d| d.id.as_str()).collect::<Vec<_>>(), ["inbox-3", "inbox-2"]);
+        assert_eq!(corpus.iter().filter(|d| !d.is_archived).count(), CORPUS_SAMPLE);
+        assert_eq!(corpus.iter().filter(|d| d.is_archived).count(), 3);
+        // The oldest documents of a category fall outside the sample
+        assert!(!corpus.iter().any(|d| d.id == "cooking-0" && !d.is_archived));
+    }
+}
@@ -0,0 +1,270 @@
+//! Import of Obsidian and Logseq vaults
+//!
+//! Notes are markdown files whose YAML frontmatter (Obsidian) or leading
+//! `key:: value` properties (Logseq) carry tags, category, author and
+//! creation date. Notes are titled after their file name, which is what
+//! `[[wikilinks]]` refer to, so links resolve once the whole vault is in.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use super::frontmatter::{self, clean_tag, parse_date, split_list};
+
+/// `![[file.png]]`, `![[file.pdf|300]]` and `[[file.pdf]]`
+static WIKI_ATTACHMENT_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"!?\[\[([^\[\]|#]+\.[A-Za-z0-9]+)(?:[|#][^\[\]]*)?\]\]").unwrap());
+/// `![alt](path)` and `[text](path)`
+static MARKDOWN_ATTACHMENT_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"!?\[[^\]]*\]\(([^)\s]+)\)").unwrap());
+
+/// Directories of the vault that never hold notes (app settings, trash, Logseq backups)
+const SKIPPED_DIRECTORIES: &[&str] = &["logseq"];
+
+/// How vault folders become document categories
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VaultImportConfig {
+    /// Use a note's top-level folder as its category when the note sets none
+    pub folders_as_categories: bool,
+    /// Category for specific top-level folders, overriding the folder name
+    pub folder_categories: BTreeMap<String, String>,
+}
+
+impl Default for VaultImportConfig {
+    fn default() -> Self {
+        Self {
+            folders_as_categories: true,
+            folder_categories: BTreeMap::new(),
+        }
+    }
+}
+
+impl VaultImportConfig {
+    /// Category for a note at `relative` (to the vault root), from its folder
+    pub fn category_for(&self, relative: &Path) -> Option<String> {
+        let mut components = relative.components();
+        components.next_back();
+        let folder = components.next()?.as_os_str().to_str()?;
+
+        if let Some(category) = self.folder_categories.get(folder) {
+            return Some(category.clone());
+        }
+        self.folders_as_categories.then(|| folder.to_string())
+    }
+}
+
+/// Phase of a vault import
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VaultImportStage {
+    Notes,
+    Attachments,
+    Links,
+}
+
+/// Progress of a vault import
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VaultImportProgress {
+    pub stage: VaultImportStage,
+    pub completed: usize,
+    pub total: usize,
+}
+
+/// Metadata read from a note's frontmatter or page properties
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NoteMetadata {
+    pub tags: Vec<String>,
+    pub category: Option<String>,
+    pub author: Option<String>,
+    pub created: Option<DateTime<Utc>>,
+    /// Frontmatter keys without a document field
+    pub custom: BTreeMap<String, serde_json::Value>,
+}
+
+/// Files of a vault, sorted
+#[derive(Debug, Default)]
+pub struct VaultFiles {
+    pub notes: Vec<PathBuf>,
+    /// Every other file, for resolving attachment references
+    pub attachments: Vec<PathBuf>,
+}
+
+impl VaultFiles {
+    /// Walk the vault, skipping hidden directories such as `.obsidian`
+    pub fn scan(root: &Path) -> std::io::Result<Self> {
+        let mut files = Self::default();
+        let mut pending = vec![root.to_path_buf()];
+
+        while let Some(dir) = pending.pop() {
+            for entry in std::fs::read_dir(&dir)? {
+                let path = entry?.path();
+                let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+                if name.starts_with('.') {
+                    continue;
+                }
+
+                if path.is_dir() {
+                    if !(dir == root && SKIPPED_DIRECTORIES.contains(&name)) {
+                        pending.push(path);
+                    }
+                } else if is_note(&path) {
+                    files.notes.push(path);
+                } else {
+                    files.attachments.push(path);
+                }
+            }
+        }
+
+        files.notes.sort();
+        files.attachments.sort();
+        Ok(files)
+    }
+
+    /// Find the file an attachment reference in `note` points at
+    ///
+    /// Tries the path relative to the note, then to the vault root, then any
+    /// file with the same name, as Obsidian's shortest-path links do.
+    pub fn resolve_attachment(&self, root: &Path, note: &Path, reference: &str) -> Option<PathBuf> {
+        let reference = reference.replace("%20", " ");
+        let candidates = [note.parent().map(|dir| dir.join(&reference)), Some(root.join(&reference))];
+        for candidate in candidates.into_iter().flatten() {
+            if let Some(found) = self.attachments.iter().find(|a| paths_equal(a, &candidate)) {
+                return Some(found.clone());
+            }
+        }
+
+        let name = Path::new(&reference).file_name()?;
+        self.attachments.iter().find(|a| a.file_name() == Some(name)).cloned()
+    }
+}
+
+fn is_note(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.eq_ignore_ascii_case("md") || e.eq_ignore_ascii_case("markdown"))
+        .unwrap_or(false)
+}
+
+fn paths_equal(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Split a note into its metadata and body
+///
+/// The body has the frontmatter removed; Logseq properties are kept since
+/// they are part of the page's first block.
+pub fn parse_note(text: &str) -> (NoteMetadata, &str) {
+    if let (Some(frontmatter), body) = frontmatter::parse(text) {
+        let metadata = NoteMetadata {
+            tags: frontmatter.tags,
+            category: frontmatter.category,
+            author: frontmatter.author,
+            created: frontmatter.date,
+            custom: frontmatter.custom,
+        };
+        return (metadata, body);
+    }
+
+    // Logseq page properties: `key:: value` lines before the first other line
+    let mut metadata = NoteMetadata::default();
+    for line in text.lines() {
+        let Some((key, value)) = line.trim_start_matches("- ").split_once(":: ") else {
+            break;
+        };
+        apply_property(&mut metadata, key.trim(), split_list(value));
+    }
+    (metadata, text)
+}
+
+/// References to non-note files in a note's body
+pub fn attachment_references(body: &str) -> Vec<String> {
+    let mut references: Vec<String> = Vec::new();
+    let wiki = WIKI_ATTACHMENT_RE.captures_iter(body).map(|c| c[1].trim().to_string());
+    let markdown = MARKDOWN_ATTACHMENT_RE
+        .captures_iter(body)
+        .map(|c| c[1].to_string())
+        .filter(|target| !target.contains("://") && !target.starts_with('#') && !target.starts_with("mailto:"));
+
+    for reference in wiki.chain(markdown) {
+        if !is_note(Path::new(&reference)) && Path::new(&reference).extension().is_some() && !references.contains(&reference) {
+            references.push(reference);
+        }
+    }
+    references
+}
+
+/// Whether a link target names one of the attachment `references`
+///
+/// Link extraction reduces markdown targets to file names with `-` and `_`
+/// turned into spaces, so names are compared that way.
+pub fn is_attachment_target(target: &str, references: &[String]) -> bool {
+    let normalize = |name: &str| name.replace("%20", " ").replace(['-', '_'], " ").to_lowercase();
+    let target = normalize(target);
+    references.iter().any(|reference| {
+        let name = reference.rsplit('/').next().unwrap_or(reference);
+        normalize(name) == target
+    })
+}
+
+fn apply_property(metadata: &mut NoteMetadata, key: &str, values: Vec<String>) {
+    let first = values.first().map(|v| clean_tag(v)).filter(|v| !v.is_empty());
+    match key.to_lowercase().as_str() {
+        "tags" | "tag" => {
+            // Tags may also be space-separated
+            let tags = values.iter().flat_map(|v| v.split_whitespace()).map(clean_tag).filter(|t| !t.is_empty());
+            for tag in tags {
+                if !metadata.tags.contains(&tag) {
+                    metadata.tags.push(tag);
+                }
+            }
+        }
+        "category" | "categories" => metadata.category = first,
+        "author" | "authors" => metadata.author = first,
+        "created" | "date" | "created_at" | "created-at" => {
+            metadata.created = first.as_deref().and_then(parse_date);
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_obsidian_and_logseq_notes() {
+        let obsidian = "---\ntitle: Ownership\ntags:\n  - rust\n  - \"#memory\"\ncategory: Programming\nauthor: 'Ada'\ncreated: 2023-05-01\n---\n# Ownership\nSee ![[diagram.png|300]] and [[Borrowing]].\n";
+        let (metadata, body) = parse_note(obsidian);
+        assert_eq!(metadata.tags, vec!["rust", "memory"]);
+        assert_eq!(metadata.category.as_deref(), Some("Programming"));
+        assert_eq!(metadata.author.as_deref(), Some("Ada"));
+        assert_eq!(metadata.created.unwrap().to_rfc3339(), "2023-05-01T00:00:00+00:00");
+        assert!(body.starts_with("# Ownership"));
+        assert_eq!(attachment_references(body), vec!["diagram.png"]);
+
+        let logseq = "tags:: [[rust]], async\nauthor:: Lin\n\n- First block [paper](../assets/paper.pdf)\n";
+        let (metadata, body) = parse_note(logseq);
+        assert_eq!(metadata.tags, vec!["rust", "async"]);
+        assert_eq!(metadata.author.as_deref(), Some("Lin"));
+        let references = attachment_references(body);
+        assert_eq!(references, vec!["../assets/paper.pdf"]);
+        assert!(is_attachment_target("paper.pdf", &references));
+        assert!(!is_attachment_target("paper", &references));
+
+        let config = VaultImportConfig {
+            folder_categories: BTreeMap::from([("Areas".to_string(), "Reference".to_string())]),
+            ..VaultImportConfig::default()
+        };
+        assert_eq!(config.category_for(Path::new("Projects/app/Plan.md")).as_deref(), Some("Projects"));
+        assert_eq!(config.category_for(Path::new("Areas/Health.md")).as_deref(), Some("Reference"));
+        assert_eq!(config.category_for(Path::new("Inbox.md")), None);
+    }
+}
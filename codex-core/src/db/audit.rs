@@ -0,0 +1,529 @@
+//! Vault integrity audit
+//!
+//! [`DatabaseManager::audit`] looks for inconsistencies that triggers and
+//! foreign keys normally prevent but that crashes, older releases or
+//! manual edits can leave behind. It only reads: each check is counted in
+//! SQLite, and the file hash check walks documents in pages of
+//! [`AUDIT_PAGE_SIZE`], so memory stays bounded on large vaults.
+//!
+//! [`DatabaseManager::repair`] applies the fixes chosen in
+//! [`RepairOptions`] for the problems an audit found, in one transaction.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Row, SqliteConnection};
+use tracing::info;
+
+use super::models::Document;
+use super::DatabaseManager;
+use crate::CodexResult;
+
+/// Ids listed per finding
+pub const AUDIT_SAMPLE_SIZE: i64 = 10;
+/// Documents read at a time by checks that need their content
+pub const AUDIT_PAGE_SIZE: i64 = 500;
+
+/// Content types stored exactly as the imported file's bytes, so their
+/// `file_hash` is the hash of their content
+const VERBATIM_CONTENT_TYPES: [&str; 7] = [
+    "text/plain",
+    "text/x-rust",
+    "text/x-python",
+    "text/x-javascript",
+    "text/x-typescript",
+    "text/x-go",
+    "text/x-java",
+];
+/// Timestamps in the normalized `YYYY-MM-DDTHH:MM:SS[.fff]+00:00` form
+const NORMALIZED_TIMESTAMP_GLOB: &str = "[0-9][0-9][0-9][0-9]-[0-9][0-9]-[0-9][0-9]T[0-9][0-9]:[0-9][0-9]:[0-9][0-9]*+00:00";
+/// Timestamp columns kept in the normalized form
+const TIMESTAMP_COLUMNS: [(&str, &str); 3] =
+    [("documents", "created_at"), ("documents", "updated_at"), ("embeddings", "created_at")];
+
+/// Kind of inconsistency an audit looks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditCheck {
+    /// Documents missing from the full-text index
+    MissingFts,
+    /// Full-text rows of documents that no longer exist
+    OrphanedFts,
+    /// Embeddings of documents that no longer exist
+    OrphanedEmbeddings,
+    /// Annotations on documents that no longer exist
+    OrphanedAnnotations,
+    /// Bookmarks on documents that no longer exist
+    OrphanedBookmarks,
+    /// Links from, or resolved to, documents that no longer exist
+    OrphanedLinks,
+    /// Document and embedding timestamps not in the normalized form
+    MixedTimestamps,
+    /// Verbatim text documents whose content no longer hashes to `file_hash`
+    StaleFileHash,
+}
+
+impl AuditCheck {
+    /// Every check, in the order an audit runs them
+    pub const ALL: [AuditCheck; 8] = [
+        AuditCheck::MissingFts,
+        AuditCheck::OrphanedFts,
+        AuditCheck::OrphanedEmbeddings,
+        AuditCheck::OrphanedAnnotations,
+        AuditCheck::OrphanedBookmarks,
+        AuditCheck::OrphanedLinks,
+        AuditCheck::MixedTimestamps,
+        AuditCheck::StaleFileHash,
+    ];
+
+    /// Id expression and `FROM ... WHERE` clause selecting the affected rows,
+    /// for the checks SQLite can count on its own
+    fn affected_rows(self) -> Option<(&'static str, String)> {
+        let sql = match self {
+            AuditCheck::MissingFts => (
+                "d.id",
+                "FROM documents d WHERE NOT EXISTS (SELECT 1 FROM documents_fts f WHERE f.rowid = d.rowid)".to_string(),
+            ),
+            AuditCheck::OrphanedFts => (
+                "CAST(f.rowid AS TEXT)",
+                "FROM documents_fts f WHERE NOT EXISTS (SELECT 1 FROM documents d WHERE d.rowid = f.rowid)".to_string(),
+            ),
+            AuditCheck::OrphanedEmbeddings => (
+                "e.id",
+                "FROM embeddings e WHERE NOT EXISTS (SELECT 1 FROM documents d WHERE d.id = e.document_id)".to_string(),
+            ),
+            AuditCheck::OrphanedAnnotations => (
+                "a.id",
+                "FROM annotations a WHERE NOT EXISTS (SELECT 1 FROM documents d WHERE d.id = a.document_id)".to_string(),
+            ),
+            AuditCheck::OrphanedBookmarks => (
+                "b.id",
+                "FROM bookmarks b WHERE NOT EXISTS (SELECT 1 FROM documents d WHERE d.id = b.document_id)".to_string(),
+            ),
+            AuditCheck::OrphanedLinks => (
+                "l.id",
+                "FROM document_links l WHERE NOT EXISTS (SELECT 1 FROM documents d WHERE d.id = l.source_id) \
+                 OR (l.target_id IS NOT NULL AND NOT EXISTS (SELECT 1 FROM documents d WHERE d.id = l.target_id))"
+                    .to_string(),
+            ),
+            AuditCheck::MixedTimestamps => {
+                let rows: Vec<String> = TIMESTAMP_COLUMNS
+                    .iter()
+                    .map(|(table, column)| {
+                        format!("SELECT id FROM {} WHERE {} NOT GLOB '{}'", table, column, NORMALIZED_TIMESTAMP_GLOB)
+                    })
+                    .collect();
+                ("id", format!("FROM ({})", rows.join(" UNION ")))
+            }
+            AuditCheck::StaleFileHash => return None,
+        };
+        Some(sql)
+    }
+}
+
+/// What an audit found for one check
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditFinding {
+    pub check: AuditCheck,
+    /// Number of affected rows
+    pub count: u64,
+    /// Up to [`AUDIT_SAMPLE_SIZE`] ids of affected rows; full-text rows are
+    /// identified by rowid
+    pub sample_ids: Vec<String>,
+}
+
+/// Result of [`DatabaseManager::audit`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VaultAudit {
+    pub audited_at: DateTime<Utc>,
+    pub document_count: u64,
+    /// One finding per check, including those that found nothing
+    pub findings: Vec<AuditFinding>,
+}
+
+impl VaultAudit {
+    /// Whether no check found anything
+    pub fn is_clean(&self) -> bool {
+        self.findings.iter().all(|finding| finding.count == 0)
+    }
+
+    /// Number of rows affected by `check`
+    pub fn count(&self, check: AuditCheck) -> u64 {
+        self.findings.iter().find(|finding| finding.check == check).map_or(0, |finding| finding.count)
+    }
+}
+
+/// Fixes [`DatabaseManager::repair`] may apply
+///
+/// The default applies every fix except `rehash_documents`: a document
+/// edited in the app keeps the hash of the file it was imported from, which
+/// is what detects re-imports of that file as duplicates.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RepairOptions {
+    /// Index documents missing from the full-text index and drop index rows of deleted documents
+    pub rebuild_fts: bool,
+    /// Delete embeddings, annotations, bookmarks and links of deleted documents,
+    /// and unresolve links to them
+    pub remove_orphans: bool,
+    /// Rewrite parseable timestamps in the normalized form
+    pub normalize_timestamps: bool,
+    /// Set `file_hash` of verbatim text documents to the hash of their content
+    pub rehash_documents: bool,
+}
+
+impl Default for RepairOptions {
+    fn default() -> Self {
+        Self {
+            rebuild_fts: true,
+            remove_orphans: true,
+            normalize_timestamps: true,
+            rehash_documents: false,
+        }
+    }
+}
+
+impl RepairOptions {
+    fn covers(&self, check: AuditCheck) -> bool {
+        match check {
+            AuditCheck::MissingFts | AuditCheck::OrphanedFts => self.rebuild_fts,
+            AuditCheck::OrphanedEmbeddings
+            | AuditCheck::OrphanedAnnotations
+            | AuditCheck::OrphanedBookmarks
+            | AuditCheck::OrphanedLinks => self.remove_orphans,
+            AuditCheck::MixedTimestamps => self.normalize_timestamps,
+            AuditCheck::StaleFileHash => self.rehash_documents,
+        }
+    }
+}
+
+/// Rows one fix changed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RepairedCheck {
+    pub check: AuditCheck,
+    pub rows: u64,
+}
+
+/// Result of [`DatabaseManager::repair`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RepairResult {
+    pub repaired_at: DateTime<Utc>,
+    /// Fixes applied, in check order
+    pub repaired: Vec<RepairedCheck>,
+}
+
+impl DatabaseManager {
+    /// Check the vault for inconsistencies without changing it
+    ///
+    /// All checks read the same snapshot of the database.
+    pub async fn audit(&self) -> CodexResult<VaultAudit> {
+        let mut tx = self.pool.begin().await?;
+        let document_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM documents").fetch_one(&mut *tx).await?;
+
+        let mut findings = Vec::with_capacity(AuditCheck::ALL.len());
+        for check in AuditCheck::ALL {
+            let finding = match check.affected_rows() {
+                Some((id, from)) => {
+                    let count: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) {}", from)).fetch_one(&mut *tx).await?;
+                    let sample_ids = sqlx::query_scalar(&format!("SELECT {} {} ORDER BY 1 LIMIT ?", id, from))
+                        .bind(AUDIT_SAMPLE_SIZE)
+                        .fetch_all(&mut *tx)
+                        .await?;
+                    AuditFinding { check, count: count as u64, sample_ids }
+                }
+                None => {
+                    let mut finding = AuditFinding { check, count: 0, sample_ids: Vec::new() };
+                    for_each_stale_hash(&mut tx, |id, _| {
+                        finding.count += 1;
+                        if (finding.sample_ids.len() as i64) < AUDIT_SAMPLE_SIZE {
+                            finding.sample_ids.push(id.to_string());
+                        }
+                    })
+                    .await?;
+                    finding
+                }
+            };
+            findings.push(finding);
+        }
+        tx.rollback().await?;
+
+        let audit = VaultAudit { audited_at: Utc::now(), document_count: document_count as u64, findings };
+        let problems: u64 = audit.findings.iter().map(|finding| finding.count).sum();
+        info!("Vault audit of {} documents found {} problems", audit.document_count, problems);
+        Ok(audit)
+    }
+
+    /// Apply the fixes allowed by `options` for the problems `audit` found
+    ///
+    /// Fixes act on the vault as it is now, so rows that broke after the
+    /// audit are repaired along with those it listed. Either every fix is
+    /// applied or, on error, none.
+    pub async fn repair(&self, audit: &VaultAudit, options: &RepairOptions) -> CodexResult<RepairResult> {
+        let _unit = self.write_unit().await;
+        let mut tx = self.pool.begin().await?;
+
+        let mut repaired = Vec::new();
+        for finding in &audit.findings {
+            if finding.count == 0 || !options.covers(finding.check) {
+                continue;
+            }
+            let rows = repair_check(&mut tx, finding.check).await?;
+            info!("Repaired {} rows for {:?}", rows, finding.check);
+            repaired.push(RepairedCheck { check: finding.check, rows });
+        }
+        tx.commit().await?;
+
+        Ok(RepairResult { repaired_at: Utc::now(), repaired })
+    }
+}
+
+/// Fix every row affected by `check`, returning how many changed
+async fn repair_check(conn: &mut SqliteConnection, check: AuditCheck) -> CodexResult<u64> {
+    let rows = match check {
+        AuditCheck::MissingFts => {
+            let mut indexed = 0;
+            // Each page is indexed before the next is read, so the query moves on
+            loop {
+                let rows = sqlx::query(
+                    "SELECT d.rowid AS fts_rowid, d.* FROM documents d \
+                     WHERE NOT EXISTS (SELECT 1 FROM documents_fts f WHERE f.rowid = d.rowid) LIMIT ?",
+                )
+                .bind(AUDIT_PAGE_SIZE)
+                .fetch_all(&mut *conn)
+                .await?;
+                if rows.is_empty() {
+                    break;
+                }
+                for row in &rows {
+                    let rowid: i64 = row.try_get("fts_rowid")?;
+                    let document = Document::from_row(row)?;
+                    sqlx::query(
+                        "INSERT INTO documents_fts (rowid, title, content, summary, author, category, tags) VALUES (?, ?, ?, ?, ?, ?, ?)",
+                    )
+                    .bind(rowid)
+                    .bind(&document.title)
+                    .bind(&document.content)
+                    .bind(&document.summary)
+                    .bind(&document.author)
+                    .bind(&document.category)
+                    .bind(&document.tags)
+                    .execute(&mut *conn)
+                    .await?;
+                }
+                indexed += rows.len() as u64;
+            }
+            indexed
+        }
+        AuditCheck::OrphanedFts => {
+            sqlx::query("DELETE FROM documents_fts WHERE rowid NOT IN (SELECT rowid FROM documents)")
+                .execute(&mut *conn)
+                .await?
+                .rows_affected()
+        }
+        AuditCheck::OrphanedEmbeddings => {
+            sqlx::query("DELETE FROM embeddings WHERE document_id NOT IN (SELECT id FROM documents)")
+                .execute(&mut *conn)
+                .await?
+                .rows_affected()
+        }
+        AuditCheck::OrphanedAnnotations => {
+            sqlx::query("DELETE FROM annotations WHERE document_id NOT IN (SELECT id FROM documents)")
+                .execute(&mut *conn)
+                .await?
+                .rows_affected()
+        }
+        AuditCheck::OrphanedBookmarks => {
+            sqlx::query("DELETE FROM bookmarks WHERE document_id NOT IN (SELECT id FROM documents)")
+                .execute(&mut *conn)
+                .await?
+                .rows_affected()
+        }
+        AuditCheck::OrphanedLinks => {
+            let removed = sqlx::query("DELETE FROM document_links WHERE source_id NOT IN (SELECT id FROM documents)")
+                .execute(&mut *conn)
+                .await?
+                .rows_affected();
+            let unresolved = sqlx::query(
+                "UPDATE document_links SET target_id = NULL \
+                 WHERE target_id IS NOT NULL AND target_id NOT IN (SELECT id FROM documents)",
+            )
+            .execute(&mut *conn)
+            .await?
+            .rows_affected();
+            removed + unresolved
+        }
+        AuditCheck::MixedTimestamps => {
+            // Same rewrite as migration 0022; values SQLite cannot parse are left alone
+            let mut rewritten = 0;
+            for (table, column) in TIMESTAMP_COLUMNS {
+                let sql = format!(
+                    "UPDATE {table} SET {column} = strftime('%Y-%m-%dT%H:%M:%S', {column}) \
+                     || CASE WHEN strftime('%f', {column}) LIKE '%.000' THEN '' ELSE substr(strftime('%f', {column}), 3) END \
+                     || '+00:00' \
+                     WHERE {column} NOT GLOB '{glob}' AND strftime('%s', {column}) IS NOT NULL",
+                    glob = NORMALIZED_TIMESTAMP_GLOB
+                );
+                rewritten += sqlx::query(&sql).execute(&mut *conn).await?.rows_affected();
+            }
+            rewritten
+        }
+        AuditCheck::StaleFileHash => {
+            let mut stale = Vec::new();
+            for_each_stale_hash(&mut *conn, |id, hash| stale.push((id.to_string(), hash))).await?;
+            for (id, hash) in &stale {
+                sqlx::query("UPDATE documents SET file_hash = ? WHERE id = ?")
+                    .bind(hash)
+                    .bind(id)
+                    .execute(&mut *conn)
+                    .await?;
+            }
+            stale.len() as u64
+        }
+    };
+    Ok(rows)
+}
+
+/// Call `found` with the id and content hash of each verbatim text
+/// document whose `file_hash` differs, reading documents a page at a time
+async fn for_each_stale_hash(conn: &mut SqliteConnection, mut found: impl FnMut(&str, String)) -> CodexResult<()> {
+    let content_types = serde_json::to_string(&VERBATIM_CONTENT_TYPES)?;
+    let mut cursor = String::new();
+    loop {
+        let documents: Vec<Document> = sqlx::query_as(
+            "SELECT * FROM documents WHERE id > ? AND file_hash IS NOT NULL \
+             AND content_type IN (SELECT value FROM json_each(?)) ORDER BY id LIMIT ?",
+        )
+        .bind(&cursor)
+        .bind(&content_types)
+        .bind(AUDIT_PAGE_SIZE)
+        .fetch_all(&mut *conn)
+        .await?;
+        let Some(last) = documents.last() else {
+            return Ok(());
+        };
+        cursor = last.id.clone();
+
+        for document in &documents {
+            let hash = content_hash(&document.content);
+            if document.file_hash.as_deref() != Some(hash.as_str()) {
+                found(&document.id, hash);
+            }
+        }
+    }
+}
+
+/// Hash of a verbatim document's content, as recorded in `file_hash` on import
+fn content_hash(content: &str) -> String {
+    use sha2::{Digest, Sha256};
+    hex::encode(Sha256::digest(content.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_database;
+    use crate::db::{Annotation, DocumentQueries, Embedding};
+
+    #[tokio::test]
+    async fn test_audit_finds_and_repair_fixes_inconsistencies() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = test_database(&dir).await;
+        let pool = db.pool();
+
+        let healthy = db.audit().await.unwrap();
+        assert!(healthy.is_clean(), "{:?}", healthy);
+
+        let mut kept = Document::new("Kept".to_string(), "Plain text body".to_string(), "text/plain".to_string());
+        kept.file_hash = Some(content_hash(&kept.content));
+        let mut edited = Document::new("Edited".to_string(), "Original body".to_string(), "text/plain".to_string());
+        edited.file_hash = Some(content_hash("The file as imported"));
+        let mut parsed = Document::new("Parsed".to_string(), "Extracted text".to_string(), "application/pdf".to_string());
+        parsed.file_hash = Some(content_hash("%PDF-1.7"));
+        let gone = Document::new("Gone".to_string(), "Deleted without cleanup".to_string(), "text/plain".to_string());
+        for document in [&kept, &edited, &parsed, &gone] {
+            DocumentQueries::create(pool, document, db.compression()).await.unwrap();
+        }
+        let embedding = Embedding::new(gone.id.clone(), vec![1.0, 0.0], "test".to_string(), 0, "chunk".to_string(), 0, 0);
+        crate::db::EmbeddingQueries::create_with_binary(pool, &embedding).await.unwrap();
+        crate::db::AnnotationQueries::create(pool, &Annotation::new(gone.id.clone(), 0, 7, "Deleted".to_string()))
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO bookmarks (id, document_id, title) VALUES ('bookmark', ?, 'Gone')")
+            .bind(&gone.id)
+            .execute(pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO document_links (id, source_id, target_id, target_title, link_type) VALUES ('from-gone', ?, ?, 'Kept', 'wiki'), ('to-gone', ?, ?, 'Gone', 'wiki')")
+            .bind(&gone.id)
+            .bind(&kept.id)
+            .bind(&kept.id)
+            .bind(&gone.id)
+            .execute(pool)
+            .await
+            .unwrap();
+
+        // The broken states: a deletion bypassing triggers and foreign keys,
+        // an FTS row lost, and a timestamp written by a column default
+        let mut conn = pool.acquire().await.unwrap();
+        sqlx::query("PRAGMA foreign_keys = OFF").execute(&mut *conn).await.unwrap();
+        sqlx::query("DROP TRIGGER documents_fts_delete").execute(&mut *conn).await.unwrap();
+        sqlx::query("DELETE FROM documents WHERE id = ?").bind(&gone.id).execute(&mut *conn).await.unwrap();
+        sqlx::query("PRAGMA foreign_keys = ON").execute(&mut *conn).await.unwrap();
+        drop(conn);
+        sqlx::query("DELETE FROM documents_fts WHERE rowid = (SELECT rowid FROM documents WHERE id = ?)")
+            .bind(&kept.id)
+            .execute(pool)
+            .await
+            .unwrap();
+        sqlx::query("UPDATE documents SET created_at = '2024-01-02 03:04:05' WHERE id = ?")
+            .bind(&parsed.id)
+            .execute(pool)
+            .await
+            .unwrap();
+
+        let audit = db.audit().await.unwrap();
+        assert_eq!(audit.document_count, 3);
+        let expected = [
+            (AuditCheck::MissingFts, 1),
+            (AuditCheck::OrphanedFts, 1),
+            (AuditCheck::OrphanedEmbeddings, 1),
+            (AuditCheck::OrphanedAnnotations, 1),
+            (AuditCheck::OrphanedBookmarks, 1),
+            (AuditCheck::OrphanedLinks, 2),
+            (AuditCheck::MixedTimestamps, 1),
+            (AuditCheck::StaleFileHash, 1),
+        ];
+        for (check, count) in expected {
+            assert_eq!(audit.count(check), count, "{:?}", check);
+        }
+        let finding = |check| audit.findings.iter().find(|finding| finding.check == check).unwrap();
+        assert_eq!(finding(AuditCheck::MissingFts).sample_ids, vec![kept.id.clone()]);
+        assert_eq!(finding(AuditCheck::StaleFileHash).sample_ids, vec![edited.id.clone()]);
+        assert_eq!(finding(AuditCheck::MixedTimestamps).sample_ids, vec![parsed.id.clone()]);
+        assert_eq!(db.audit().await.unwrap().findings, audit.findings, "auditing changes nothing");
+
+        let result = db.repair(&audit, &RepairOptions::default()).await.unwrap();
+        assert!(result.repaired.iter().all(|repaired| repaired.check != AuditCheck::StaleFileHash));
+        let after = db.audit().await.unwrap();
+        assert_eq!(after.count(AuditCheck::StaleFileHash), 1);
+        assert_eq!(after.findings.iter().map(|finding| finding.count).sum::<u64>(), 1, "{:?}", after);
+
+        // The lost document is searchable again, the link to the deleted one is unresolved
+        let hits: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM documents_fts WHERE documents_fts MATCH 'plain'")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        assert_eq!(hits, 1);
+        let target: Option<String> = sqlx::query_scalar("SELECT target_id FROM document_links WHERE id = 'to-gone'")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        assert_eq!(target, None);
+
+        let rehash = RepairOptions { rehash_documents: true, ..RepairOptions::default() };
+        let result = db.repair(&after, &rehash).await.unwrap();
+        assert_eq!(result.repaired, vec![RepairedCheck { check: AuditCheck::StaleFileHash, rows: 1 }]);
+        assert!(db.audit().await.unwrap().is_clean());
+        let stored = DocumentQueries::get_by_id(pool, &edited.id).await.unwrap().unwrap();
+        assert_eq!(stored.file_hash, Some(content_hash("Original body")));
+    }
+}
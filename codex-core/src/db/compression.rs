@@ -0,0 +1,149 @@
+//! Transparent compression of large document bodies
+//!
+//! Bodies at or above the size threshold are stored zstd-compressed in
+//! `documents.content_compressed` with `content` left empty. The FTS index
+//! always receives the plain text, and [`Document`](super::models::Document)
+//! rows are decompressed when read, so callers never see compressed data.
+//! Each [`DatabaseManager`](super::DatabaseManager) carries its own
+//! [`CompressionPolicy`] for new writes.
+
+use sqlx::{Row, SqliteConnection};
+use sqlx::sqlite::SqliteRow;
+
+use crate::{CodexError, CodexResult};
+
+/// Value of `documents.compression` for zstd-compressed bodies
+pub const COMPRESSION_ZSTD: &str = "zstd";
+/// Bodies smaller than this (in bytes) are stored as plain text
+pub const COMPRESSION_THRESHOLD_BYTES: usize = 16 * 1024;
+
+/// Whether and how hard new document bodies are compressed
+///
+/// Already-compressed rows stay readable when compression is disabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressionPolicy {
+    /// Compress bodies at or above [`COMPRESSION_THRESHOLD_BYTES`]
+    pub enabled: bool,
+    /// zstd level, 1 to 19
+    pub level: i32,
+}
+
+impl CompressionPolicy {
+    /// Store every new body as plain text
+    pub const DISABLED: Self = Self { enabled: false, level: 3 };
+
+    /// Policy with `level` clamped to the range zstd accepts
+    pub fn new(enabled: bool, level: i32) -> Self {
+        Self { enabled, level: level.clamp(1, 19) }
+    }
+
+    /// Compress a document body if it is large enough and compression pays off
+    pub fn compress(&self, content: &str) -> Option<Vec<u8>> {
+        if !self.enabled || content.len() < COMPRESSION_THRESHOLD_BYTES {
+            return None;
+        }
+
+        zstd::bulk::compress(content.as_bytes(), self.level)
+            .ok()
+            .filter(|compressed| compressed.len() < content.len())
+    }
+}
+
+impl Default for CompressionPolicy {
+    fn default() -> Self {
+        Self { enabled: true, level: 3 }
+    }
+}
+
+/// Column values for writing a document body under a policy
+pub(crate) struct StoredBody<'a> {
+    text: &'a str,
+    /// `documents.content`, empty when the body is compressed
+    pub content: &'a str,
+    pub compressed: Option<Vec<u8>>,
+    pub compression: Option<&'static str>,
+    pub content_size: Option<i64>,
+}
+
+impl<'a> StoredBody<'a> {
+    pub fn new(text: &'a str, policy: CompressionPolicy) -> Self {
+        match policy.compress(text) {
+            Some(compressed) => Self {
+                text,
+                content: "",
+                compressed: Some(compressed),
+                compression: Some(COMPRESSION_ZSTD),
+                content_size: Some(text.len() as i64),
+            },
+            None => Self { text, content: text, compressed: None, compression: None, content_size: None },
+        }
+    }
+
+    /// Give the full-text row of a just-written compressed body its plain text
+    ///
+    /// The FTS triggers only see the empty `content` column. Run this in the
+    /// transaction of the write so the index never misses the body.
+    pub async fn index(&self, conn: &mut SqliteConnection, id: &str) -> CodexResult<()> {
+        if self.compressed.is_none() {
+            return Ok(());
+        }
+
+        sqlx::query("UPDATE documents_fts SET content = ? WHERE rowid = (SELECT rowid FROM documents WHERE id = ?)")
+            .bind(self.text)
+            .bind(id)
+            .execute(conn)
+            .await?;
+        Ok(())
+    }
+}
+
+/// Decompress a stored document body
+pub fn decompress_content(compressed: &[u8]) -> CodexResult<String> {
+    let bytes = zstd::stream::decode_all(compressed)
+        .map_err(|e| CodexError::internal(format!("Failed to decompress document content: {}", e)))?;
+    String::from_utf8(bytes)
+        .map_err(|e| CodexError::internal(format!("Decompressed content is not UTF-8: {}", e)))
+}
+
+/// Read the plain-text body of a `documents` row
+pub(crate) fn content_from_row(row: &SqliteRow) -> Result<String, sqlx::Error> {
+    // Rows selected without the compression columns are plain text
+    let compression: Option<String> = row.try_get("compression").unwrap_or(None);
+
+    match compression.as_deref() {
+        None => row.try_get("content"),
+        Some(COMPRESSION_ZSTD) => {
+            let compressed: Vec<u8> = row.try_get("content_compressed")?;
+            decompress_content(&compressed).map_err(|e| sqlx::Error::Decode(Box::new(e)))
+        }
+        Some(other) => Err(sqlx::Error::Decode(
+            format!("Unknown content compression: {}", other).into(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_above_threshold() {
+        let content = "Compressible knowledge base text. ".repeat(1000);
+        let compressed = CompressionPolicy::default().compress(&content).unwrap();
+
+        assert!(compressed.len() < content.len() / 10);
+        assert_eq!(decompress_content(&compressed).unwrap(), content);
+    }
+
+    #[test]
+    fn test_small_content_stays_plain() {
+        assert!(CompressionPolicy::default().compress("short note").is_none());
+    }
+
+    #[test]
+    fn test_disabled_policy_stays_plain() {
+        let content = "Compressible knowledge base text. ".repeat(1000);
+        assert!(CompressionPolicy::DISABLED.compress(&content).is_none());
+        assert!(CompressionPolicy::new(true, 40).compress(&content).is_some());
+    }
+}
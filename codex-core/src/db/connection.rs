@@ -19,9 +19,9 @@ pub struct PoolConfig {
     pub max_lifetime: Duration,
 }
 
-impl PoolConfig {
+impl Default for PoolConfig {
     /// Create default pool configuration
-    pub fn default() -> Self {
+    fn default() -> Self {
         Self {
             max_connections: 10,
             min_connections: 2,
@@ -30,7 +30,9 @@ impl PoolConfig {
             max_lifetime: Duration::from_secs(1800), // 30 minutes
         }
     }
+}
 
+impl PoolConfig {
     /// Create pool configuration optimized for desktop app
     pub fn desktop_optimized() -> Self {
         Self {
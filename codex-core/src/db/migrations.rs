@@ -0,0 +1,276 @@
+//! Schema migrations with a safety net
+//!
+//! Before pending migrations run on an existing vault,
+//! [`DatabaseManager::migrate`] writes a `VACUUM INTO` copy of it to the
+//! [`BACKUP_DIR`] folder next to the database, so a failed or faulty
+//! migration can be undone by restoring the copy. Copies older than
+//! [`PRE_MIGRATION_BACKUP_RETENTION_DAYS`] are removed after the next
+//! migration. A vault already migrated by a newer release is refused with
+//! [`CodexError::VaultNewerThanApp`] instead of being opened with a schema
+//! this build does not know.
+
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::migrate::Migrator;
+use tracing::{info, warn};
+
+use super::DatabaseManager;
+use crate::{CodexError, CodexResult};
+
+/// Days pre-migration backups are kept
+pub const PRE_MIGRATION_BACKUP_RETENTION_DAYS: u64 = 7;
+/// Folder next to the database holding pre-migration backups
+pub const BACKUP_DIR: &str = "backups";
+/// Part of a backup's file name marking it as a pre-migration backup
+const PRE_MIGRATION_MARKER: &str = "-pre-migration-";
+
+/// Migrations compiled into this build
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// Highest schema version this build migrates to
+pub fn app_schema_version() -> i64 {
+    latest_version(&MIGRATOR)
+}
+
+fn latest_version(migrator: &Migrator) -> i64 {
+    migrator.iter().map(|migration| migration.version).max().unwrap_or(0)
+}
+
+/// A migration applied to the vault, as recorded by sqlx
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MigrationRecord {
+    pub version: i64,
+    pub description: String,
+    pub installed_on: DateTime<Utc>,
+    /// False when the migration failed part way
+    pub success: bool,
+    pub execution_time_ms: i64,
+}
+
+impl DatabaseManager {
+    /// Apply pending schema migrations
+    ///
+    /// Waits for a running vault export to finish first. See the
+    /// [module documentation](self) for the backup and version checks.
+    pub async fn migrate(&self) -> CodexResult<()> {
+        self.migrate_with(&MIGRATOR).await
+    }
+
+    async fn migrate_with(&self, migrator: &Migrator) -> CodexResult<()> {
+        let _exclusive = self.snapshot_gate.write().await;
+
+        let applied = self.applied_versions().await?;
+        let vault_version = applied.iter().copied().max().unwrap_or(0);
+        let app_version = latest_version(migrator);
+        if vault_version > app_version {
+            return Err(CodexError::vault_newer_than_app(vault_version, app_version));
+        }
+
+        let pending = migrator.iter().filter(|migration| !applied.contains(&migration.version)).count();
+        if pending == 0 {
+            return Ok(());
+        }
+
+        // A new vault has nothing worth backing up
+        if !applied.is_empty() {
+            let backup_path = self.pre_migration_backup_path(vault_version);
+            if let Some(dir) = backup_path.parent() {
+                tokio::fs::create_dir_all(dir).await?;
+            }
+            self.backup(&backup_path).await?;
+            info!("Backed up schema version {} to {:?} before {} migrations", vault_version, backup_path, pending);
+        }
+
+        migrator.run(&self.pool).await?;
+        info!("Migrated database from schema version {} to {}", vault_version, app_version);
+
+        self.prune_pre_migration_backups().await;
+        Ok(())
+    }
+
+    /// Migrations applied to the vault, oldest first
+    pub async fn migration_history(&self) -> CodexResult<Vec<MigrationRecord>> {
+        let rows: Vec<(i64, String, DateTime<Utc>, bool, i64)> = sqlx::query_as(
+            "SELECT version, description, installed_on, success, execution_time FROM _sqlx_migrations ORDER BY version",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(version, description, installed_on, success, execution_time)| MigrationRecord {
+                version,
+                description,
+                installed_on,
+                success,
+                // sqlx records nanoseconds
+                execution_time_ms: execution_time / 1_000_000,
+            })
+            .collect())
+    }
+
+    /// Folder holding the pre-migration backups of this database
+    pub fn backup_dir(&self) -> PathBuf {
+        self.config.path.parent().map(|dir| dir.join(BACKUP_DIR)).unwrap_or_else(|| PathBuf::from(BACKUP_DIR))
+    }
+
+    /// Versions of the migrations applied so far, none for a new database
+    async fn applied_versions(&self) -> CodexResult<Vec<i64>> {
+        let tracked: Option<String> =
+            sqlx::query_scalar("SELECT name FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations'")
+                .fetch_optional(&self.pool)
+                .await?;
+        if tracked.is_none() {
+            return Ok(Vec::new());
+        }
+
+        let versions = sqlx::query_scalar("SELECT version FROM _sqlx_migrations ORDER BY version")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(versions)
+    }
+
+    fn backup_file_prefix(&self) -> String {
+        let stem = self.config.path.file_stem().map(|stem| stem.to_string_lossy().into_owned());
+        format!("{}{}", stem.as_deref().unwrap_or("codex"), PRE_MIGRATION_MARKER)
+    }
+
+    fn pre_migration_backup_path(&self, vault_version: i64) -> PathBuf {
+        let timestamp = Utc::now().format("%Y%m%d_%H%M%S");
+        self.backup_dir()
+            .join(format!("{}v{}-{}.db", self.backup_file_prefix(), vault_version, timestamp))
+    }
+
+    /// Remove pre-migration backups past the retention period, logging failures
+    async fn prune_pre_migration_backups(&self) {
+        let cutoff = SystemTime::now() - Duration::from_secs(PRE_MIGRATION_BACKUP_RETENTION_DAYS * 24 * 60 * 60);
+        let prefix = self.backup_file_prefix();
+        let Ok(entries) = std::fs::read_dir(self.backup_dir()) else {
+            return;
+        };
+
+        for entry in entries.flatten() {
+            if !entry.file_name().to_string_lossy().starts_with(&prefix) {
+                continue;
+            }
+            let expired = entry.metadata().and_then(|m| m.modified()).map(|modified| modified < cutoff).unwrap_or(false);
+            if expired {
+                match tokio::fs::remove_file(entry.path()).await {
+                    Ok(()) => info!("Removed expired pre-migration backup {:?}", entry.path()),
+                    Err(e) => warn!("Could not remove pre-migration backup {:?}: {}", entry.path(), e),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_config;
+    use std::borrow::Cow;
+
+
+    /// Migrations of a release that stopped at schema `version`
+    fn release_at(version: i64) -> Migrator {
+        Migrator {
+            migrations: Cow::Owned(MIGRATOR.iter().filter(|m| m.version <= version).cloned().collect()),
+            ignore_missing: false,
+            locking: true,
+        }
+    }
+
+    fn backups(db: &DatabaseManager) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(db.backup_dir())
+            .map(|entries| entries.flatten().map(|entry| entry.path()).collect())
+            .unwrap_or_default();
+        // Opening a backup may leave WAL files beside it
+        paths.retain(|path| path.extension().is_some_and(|ext| ext == "db"));
+        paths.sort();
+        paths
+    }
+
+    #[tokio::test]
+    async fn test_older_vault_is_backed_up_before_migrating() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(dir.path().join("vault.db"));
+        let older = app_schema_version() - 2;
+
+        // A vault last opened by an older release
+        let db = DatabaseManager::connect(&config).await.unwrap();
+        db.migrate_with(&release_at(older)).await.unwrap();
+        assert!(backups(&db).is_empty(), "new vaults are not backed up");
+        // Written with base-schema columns only, as later migrations add to `documents`
+        let id = uuid::Uuid::new_v4().to_string();
+        sqlx::query("INSERT INTO documents (id, title, content) VALUES (?, 'Kept', 'Before the upgrade')")
+            .bind(&id)
+            .execute(db.pool())
+            .await
+            .unwrap();
+
+        // An expired backup from an earlier upgrade, and an unrelated file
+        std::fs::create_dir_all(db.backup_dir()).unwrap();
+        let expired = db.backup_dir().join(format!("{}v1-20200101_000000.db", db.backup_file_prefix()));
+        let unrelated = db.backup_dir().join("manual.db");
+        for path in [&expired, &unrelated] {
+            let file = std::fs::File::create(path).unwrap();
+            file.set_modified(SystemTime::now() - Duration::from_secs(30 * 24 * 60 * 60)).unwrap();
+        }
+
+        db.migrate().await.unwrap();
+        let history = db.migration_history().await.unwrap();
+        assert_eq!(history.last().unwrap().version, app_schema_version());
+        assert!(history.iter().all(|record| record.success));
+
+        let remaining = backups(&db);
+        assert_eq!(remaining.len(), 2, "{:?}", remaining);
+        assert!(remaining.contains(&unrelated));
+        let backup = remaining.into_iter().find(|path| *path != unrelated).unwrap();
+        assert!(backup.file_name().unwrap().to_string_lossy().contains(&format!("-pre-migration-v{}-", older)));
+
+        // The backup is the vault as the older release left it
+        let restored = DatabaseManager::connect(&test_config(backup)).await.unwrap();
+        assert_eq!(restored.applied_versions().await.unwrap().last(), Some(&older));
+        let kept: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM documents WHERE id = ?")
+            .bind(&id)
+            .fetch_one(restored.pool())
+            .await
+            .unwrap();
+        assert_eq!(kept, 1);
+        restored.shutdown().await.unwrap();
+
+        // Nothing pending, nothing backed up
+        db.migrate().await.unwrap();
+        assert_eq!(backups(&db).len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_vault_newer_than_app_is_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(dir.path().join("vault.db"));
+        let db = DatabaseManager::new(&config).await.unwrap();
+        let current = app_schema_version();
+
+        // An older release opening this vault
+        let err = db.migrate_with(&release_at(current - 1)).await.unwrap_err();
+        assert!(
+            matches!(err, CodexError::VaultNewerThanApp { vault_version, app_version } if vault_version == current && app_version == current - 1),
+            "{}",
+            err
+        );
+        assert!(backups(&db).is_empty());
+
+        // This release opening a vault a newer release migrated
+        sqlx::query("INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time) VALUES (?, 'from the future', 1, x'00', 0)")
+            .bind(current + 1)
+            .execute(db.pool())
+            .await
+            .unwrap();
+        db.shutdown().await.unwrap();
+        let err = DatabaseManager::new(&config).await.unwrap_err();
+        assert_eq!(err.downcast_ref::<CodexError>().map(CodexError::code), Some("vault_newer_than_app"));
+    }
+}
@@ -16,6 +16,11 @@ pub mod connection;
 pub mod seeder;
 pub mod search;
 pub mod vector_ops;
+pub mod compression;
+pub mod vault_export;
+pub mod migrations;
+pub mod audit;
+pub mod storage;
 
 pub use models::*;
 pub use queries::*;
@@ -23,17 +28,81 @@ pub use connection::*;
 pub use seeder::*;
 pub use search::*;
 pub use vector_ops::*;
+pub use vault_export::*;
+pub use migrations::*;
+pub use audit::*;
+pub use storage::*;
+
+/// How often the vector cache is trimmed to its configured size
+pub const VECTOR_CACHE_CLEANUP_INTERVAL: std::time::Duration = std::time::Duration::from_secs(15 * 60);
+/// Share of stale vector cache entries above which maintenance rebuilds the cache
+pub const VECTOR_CACHE_COMPACTION_RATIO: f64 = 0.2;
+/// How often old AI audit entries are pruned
+pub const AI_AUDIT_PRUNE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(24 * 60 * 60);
+/// Age after which audit entries of documents that no longer exist are pruned
+const AI_AUDIT_ORPHAN_GRACE_HOURS: i64 = 24;
 
 /// Database manager handling all SQLite operations
 #[derive(Debug)]
 pub struct DatabaseManager {
     pool: SqlitePool,
     config: DatabaseConfig,
+    /// Shared by groups of writes that must land together, exclusive for
+    /// vault snapshots and schema migrations
+    snapshot_gate: tokio::sync::RwLock<()>,
+    /// Time source for retention cutoffs
+    clock: std::sync::Arc<dyn crate::clock::Clock>,
+    /// How new document bodies are stored
+    compression: compression::CompressionPolicy,
 }
 
 impl DatabaseManager {
     /// Create a new database manager with the given configuration
+    ///
+    /// Pending schema migrations are applied, see [`migrate`](Self::migrate).
     pub async fn new(config: &DatabaseConfig) -> Result<Self> {
+        let manager = Self::connect(config).await?;
+        manager.migrate().await?;
+
+        info!("Database manager initialized successfully");
+
+        Ok(manager)
+    }
+
+    /// Create a migrated database that lives in memory, for tests and
+    /// embedders that keep nothing on disk
+    ///
+    /// All connections of the pool share the one database, which is dropped
+    /// with the pool. `config.path` is not opened; WAL mode does not apply.
+    pub async fn new_in_memory(config: &DatabaseConfig) -> Result<Self> {
+        info!("Initializing in-memory database manager");
+
+        // The database goes away with its last connection, so keep one open
+        let pool = SqlitePoolOptions::new()
+            .max_connections(config.max_connections)
+            .min_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .acquire_timeout(std::time::Duration::from_secs(config.connection_timeout))
+            .connect("sqlite::memory:")
+            .await?;
+
+        let config = DatabaseConfig { enable_wal: false, ..config.clone() };
+        Self::configure_sqlite(&pool, &config).await?;
+
+        let manager = Self {
+            pool,
+            config,
+            snapshot_gate: tokio::sync::RwLock::new(()),
+            clock: crate::clock::system(),
+            compression: compression::CompressionPolicy::default(),
+        };
+        manager.migrate().await?;
+        Ok(manager)
+    }
+
+    /// Open the database without migrating it
+    async fn connect(config: &DatabaseConfig) -> Result<Self> {
         info!("Initializing database manager at {:?}", config.path);
 
         // Ensure parent directory exists
@@ -42,8 +111,8 @@ impl DatabaseManager {
         }
 
         // Create database if it doesn't exist
-        let database_url = format!("sqlite://{}", config.path.display());
-        
+        let database_url = crate::paths::sqlite_url(&config.path)?;
+
         if !Sqlite::database_exists(&database_url).await.unwrap_or(false) {
             info!("Creating new database at {:?}", config.path);
             Sqlite::create_database(&database_url).await?;
@@ -59,17 +128,61 @@ impl DatabaseManager {
         // Configure SQLite settings
         Self::configure_sqlite(&pool, config).await?;
 
-        // Run migrations
-        sqlx::migrate!("./migrations").run(&pool).await?;
-
-        info!("Database manager initialized successfully");
-
         Ok(Self {
             pool,
             config: config.clone(),
+            snapshot_gate: tokio::sync::RwLock::new(()),
+            clock: crate::clock::system(),
+            compression: compression::CompressionPolicy::default(),
         })
     }
 
+    /// Read the time for retention cutoffs, such as AI audit pruning, from `clock`
+    pub fn with_clock(mut self, clock: std::sync::Arc<dyn crate::clock::Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Compress new document bodies as `policy` says instead of the default
+    pub fn with_compression(mut self, policy: compression::CompressionPolicy) -> Self {
+        self.compression = policy;
+        self
+    }
+
+    /// How this database stores new document bodies
+    pub fn compression(&self) -> compression::CompressionPolicy {
+        self.compression
+    }
+
+    /// Guard to hold while making writes to documents, embeddings or the
+    /// full-text index
+    ///
+    /// Any number of writers may hold it at once; an export waits until
+    /// they are done before taking its snapshot, and migrations wait too.
+    /// Writes made in one transaction are consistent in a snapshot on their
+    /// own, so the guard matters most for writes spanning several.
+    pub async fn write_unit(&self) -> tokio::sync::RwLockReadGuard<'_, ()> {
+        self.snapshot_gate.read().await
+    }
+
+    /// Save a new document and its embeddings in one transaction, so a
+    /// vault export never holds the document without them
+    pub async fn create_document_with_embeddings(&self, document: &Document, embeddings: &[Embedding]) -> CodexResult<()> {
+        let _unit = self.write_unit().await;
+        let mut tx = queries::begin_write(&self.pool).await?;
+        DocumentQueries::insert(&mut tx, document, self.compression).await?;
+        EmbeddingQueries::insert_all(&mut tx, embeddings).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Replace a document's embeddings in one transaction, so a vault
+    /// export holds either the old chunks or the new ones
+    pub async fn replace_document_embeddings(&self, document_id: &str, embeddings: &[Embedding]) -> CodexResult<()> {
+        let _unit = self.write_unit().await;
+        EmbeddingQueries::replace_for_document(&self.pool, document_id, embeddings).await
+    }
+
     /// Configure SQLite-specific settings for optimal performance
     async fn configure_sqlite(pool: &SqlitePool, config: &DatabaseConfig) -> Result<()> {
         debug!("Configuring SQLite settings");
@@ -111,14 +224,24 @@ impl DatabaseManager {
         Ok(())
     }
 
+    /// Path of the database file
+    pub fn path(&self) -> &std::path::Path {
+        &self.config.path
+    }
+
     /// Get a reference to the connection pool
     pub fn pool(&self) -> &SqlitePool {
         &self.pool
     }
 
     /// Get a connection from the pool
+    ///
+    /// The wait is recorded as [`crate::metrics::DB_POOL_WAIT`].
     pub async fn get_connection(&self) -> CodexResult<sqlx::pool::PoolConnection<sqlx::Sqlite>> {
-        self.pool.acquire().await.map_err(CodexError::from)
+        let start = std::time::Instant::now();
+        let connection = self.pool.acquire().await.map_err(CodexError::from);
+        crate::metrics::record(crate::metrics::DB_POOL_WAIT, start.elapsed());
+        connection
     }
 
     /// Execute a transaction
@@ -126,7 +249,7 @@ impl DatabaseManager {
     where
         F: for<'c> FnOnce(&mut sqlx::Transaction<'c, sqlx::Sqlite>) -> std::pin::Pin<Box<dyn std::future::Future<Output = CodexResult<R>> + Send + 'c>>,
     {
-        let mut tx = self.pool.begin().await?;
+        let mut tx = sqlx::Transaction::begin(self.get_connection().await?).await?;
         let result = f(&mut tx).await?;
         tx.commit().await?;
         Ok(result)
@@ -159,10 +282,28 @@ impl DatabaseManager {
             .fetch_one(&mut *conn)
             .await?;
 
+        let content_size: (i64, i64) = sqlx::query_as(
+            r#"
+            SELECT
+                COALESCE(SUM(COALESCE(content_size, length(CAST(content AS BLOB)))), 0),
+                COALESCE(SUM(length(CAST(content AS BLOB)) + COALESCE(length(content_compressed), 0)), 0)
+            FROM documents
+            "#
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+        drop(conn);
+
+        let (unique_vectors, vector_bytes_saved) = EmbeddingQueries::shared_vector_counts(&self.pool).await?;
+
         Ok(DatabaseStats {
             document_count: document_count.0 as u64,
             embedding_count: embedding_count.0 as u64,
             database_size_bytes: db_size.0 as u64,
+            logical_content_bytes: content_size.0 as u64,
+            physical_content_bytes: content_size.1 as u64,
+            unique_vectors: unique_vectors as u64,
+            vector_bytes_saved: vector_bytes_saved as u64,
         })
     }
 
@@ -179,17 +320,173 @@ impl DatabaseManager {
         Ok(())
     }
 
+    /// Trim the vector cache to the `vector_cache_size` setting, returning how many entries were removed
+    pub async fn cleanup_vector_cache(&self) -> CodexResult<u64> {
+        let limit = EmbeddingQueries::vector_cache_limit(&self.pool).await?.unwrap_or(0);
+        let removed = EmbeddingQueries::cleanup_cache(&self.pool, limit).await?;
+        if removed > 0 {
+            debug!("Removed {} vector cache entries", removed);
+        }
+        Ok(removed)
+    }
+
+    /// Size and staleness of the vector cache
+    pub async fn vector_cache_stats(&self) -> CodexResult<VectorCacheStats> {
+        let (entries, stale, bytes) = EmbeddingQueries::cache_compaction_counts(&self.pool).await?;
+        let last_compaction = SettingQueries::get(&self.pool, VECTOR_CACHE_COMPACTED_SETTING)
+            .await?
+            .and_then(|setting| setting.get_value::<String>());
+
+        Ok(VectorCacheStats {
+            entries: entries as u64,
+            stale_entries: stale as u64,
+            tombstone_ratio: if entries > 0 { stale as f64 / entries as f64 } else { 0.0 },
+            size_bytes: bytes as u64,
+            last_compaction,
+        })
+    }
+
+    /// Rebuild the vector cache without its stale entries
+    ///
+    /// Unless `force` is set, nothing happens while the tombstone ratio is
+    /// below [`VECTOR_CACHE_COMPACTION_RATIO`]. Returns the number of entries
+    /// dropped, or None when the cache was left alone.
+    pub async fn compact_vector_cache(&self, force: bool) -> CodexResult<Option<u64>> {
+        let stats = self.vector_cache_stats().await?;
+        if !force && (stats.stale_entries == 0 || stats.tombstone_ratio < VECTOR_CACHE_COMPACTION_RATIO) {
+            return Ok(None);
+        }
+
+        let removed = EmbeddingQueries::rebuild_cache(&self.pool).await?;
+        let mut setting = Setting::new(
+            VECTOR_CACHE_COMPACTED_SETTING.to_string(),
+            String::new(),
+            "system".to_string(),
+        );
+        setting.is_user_configurable = false;
+        setting.set_value(&self.clock.utc_now().to_rfc3339())?;
+        SettingQueries::set(&self.pool, &setting).await?;
+
+        info!("Compacted vector cache: {} of {} entries were stale", removed, stats.entries);
+        Ok(Some(removed))
+    }
+
+    /// Run [`cleanup_vector_cache`](Self::cleanup_vector_cache) and
+    /// [`compact_vector_cache`](Self::compact_vector_cache) every
+    /// [`VECTOR_CACHE_CLEANUP_INTERVAL`] in the background
+    ///
+    /// The task stops when the manager is dropped.
+    pub fn start_vector_cache_maintenance(self: &std::sync::Arc<Self>) {
+        let manager = std::sync::Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(VECTOR_CACHE_CLEANUP_INTERVAL);
+            loop {
+                ticker.tick().await;
+                let Some(manager) = manager.upgrade() else {
+                    break;
+                };
+                if let Err(e) = manager.cleanup_vector_cache().await {
+                    error!("Vector cache cleanup failed: {}", e);
+                }
+                if let Err(e) = manager.compact_vector_cache(false).await {
+                    error!("Vector cache compaction failed: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Delete AI audit entries older than `retention_days`, and entries of
+    /// documents that no longer exist; returns the number deleted
+    pub async fn prune_ai_audit(&self, retention_days: u32) -> CodexResult<u64> {
+        let now = self.clock.utc_now();
+        let before = now - chrono::Duration::days(retention_days as i64);
+        let orphans_before = now - chrono::Duration::hours(AI_AUDIT_ORPHAN_GRACE_HOURS);
+        let pruned = AiAuditQueries::prune(&self.pool, &before.to_rfc3339(), &orphans_before.to_rfc3339()).await?;
+
+        if pruned > 0 {
+            info!("Pruned {} AI audit entries", pruned);
+        }
+        Ok(pruned)
+    }
+
+    /// Run [`prune_ai_audit`](Self::prune_ai_audit) every
+    /// [`AI_AUDIT_PRUNE_INTERVAL`] in the background
+    ///
+    /// The task stops when the manager is dropped.
+    pub fn start_ai_audit_maintenance(self: &std::sync::Arc<Self>, retention_days: u32) {
+        let manager = std::sync::Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(AI_AUDIT_PRUNE_INTERVAL);
+            loop {
+                ticker.tick().await;
+                let Some(manager) = manager.upgrade() else {
+                    break;
+                };
+                if let Err(e) = manager.prune_ai_audit(retention_days).await {
+                    error!("AI audit pruning failed: {}", e);
+                }
+            }
+        });
+    }
+
     /// Backup the database to a file
+    ///
+    /// Uses `VACUUM INTO`, which writes a consistent snapshot even while
+    /// other connections or processes write to the vault (a plain copy would
+    /// miss pages still in the WAL). The backup file must not exist yet.
     pub async fn backup<P: AsRef<std::path::Path>>(&self, backup_path: P) -> CodexResult<()> {
-        info!("Creating database backup at {:?}", backup_path.as_ref());
-        
-        // Simple file copy for SQLite
-        tokio::fs::copy(&self.config.path, backup_path).await?;
+        let backup_path = backup_path.as_ref();
+        info!("Creating backup of {:?} at {:?}", self.config.path, backup_path);
+
+        if backup_path.exists() {
+            return Err(CodexError::validation(format!(
+                "Backup file already exists: {}",
+                backup_path.display()
+            )));
+        }
+
+        // The snapshot is at most the size of the database and its WAL
+        let mut wal_path = self.config.path.clone().into_os_string();
+        wal_path.push("-wal");
+        let expected_size = [self.config.path.as_os_str(), wal_path.as_os_str()]
+            .iter()
+            .filter_map(|path| std::fs::metadata(path).ok())
+            .map(|m| m.len())
+            .sum();
+        crate::update::disk::ensure_space(backup_path, expected_size)?;
+
+        sqlx::query("VACUUM INTO ?")
+            .bind(backup_path.display().to_string())
+            .execute(&self.pool)
+            .await?;
         
         info!("Database backup complete");
         Ok(())
     }
 
+    /// [`backup`](Self::backup) taken when no write unit is in progress,
+    /// returning the time of the snapshot
+    ///
+    /// New write units and migrations wait until the snapshot is written.
+    pub async fn snapshot<P: AsRef<std::path::Path>>(&self, path: P) -> CodexResult<chrono::DateTime<chrono::Utc>> {
+        let _exclusive = self.snapshot_gate.write().await;
+        let taken_at = chrono::Utc::now();
+        self.backup(path).await?;
+        Ok(taken_at)
+    }
+
+    /// Whether another connection appears to have the database open
+    ///
+    /// In WAL mode SQLite keeps a `-wal` file next to the database while any
+    /// connection is open and removes it when the last one closes cleanly,
+    /// so checking before connecting detects e.g. a running desktop app (or
+    /// a crashed one that left the file behind).
+    pub fn appears_in_use(config: &DatabaseConfig) -> bool {
+        let mut wal_path = config.path.clone().into_os_string();
+        wal_path.push("-wal");
+        std::path::Path::new(&wal_path).exists()
+    }
+
     /// Shutdown the database manager
     pub async fn shutdown(&self) -> CodexResult<()> {
         info!("Shutting down database manager");
@@ -198,10 +495,51 @@ impl DatabaseManager {
     }
 }
 
+/// Vector cache compaction statistics
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct VectorCacheStats {
+    pub entries: u64,
+    /// Entries of re-embedded chunks or deleted and archived documents
+    pub stale_entries: u64,
+    /// Share of stale entries (0-1)
+    pub tombstone_ratio: f64,
+    /// Bytes of cached vectors
+    pub size_bytes: u64,
+    /// When the cache was last rebuilt (RFC 3339)
+    pub last_compaction: Option<String>,
+}
+
 /// Database statistics
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct DatabaseStats {
     pub document_count: u64,
     pub embedding_count: u64,
     pub database_size_bytes: u64,
-}
\ No newline at end of file
+    /// Size of all document bodies as plain text
+    pub logical_content_bytes: u64,
+    /// Size of all document bodies as stored, after compression
+    pub physical_content_bytes: u64,
+    /// Distinct chunk vectors stored in `vectors`, shared by identical chunks
+    #[serde(default)]
+    pub unique_vectors: u64,
+    /// Bytes not stored because identical chunks share one vector
+    #[serde(default)]
+    pub vector_bytes_saved: u64,
+}
+/// Configuration of a small test database at `path`
+#[cfg(test)]
+pub(crate) fn test_config(path: std::path::PathBuf) -> DatabaseConfig {
+    DatabaseConfig {
+        path,
+        max_connections: 5,
+        connection_timeout: 30,
+        enable_wal: true,
+        enable_foreign_keys: true,
+    }
+}
+
+/// Migrated database in `dir`, for unit tests
+#[cfg(test)]
+pub(crate) async fn test_database(dir: &tempfile::TempDir) -> DatabaseManager {
+    DatabaseManager::new(&test_config(dir.path().join("vault.db"))).await.unwrap()
+}
@@ -1,12 +1,21 @@
 //! Database models for Codex Core
 
+use std::collections::{BTreeMap, BTreeSet};
 use serde::{Deserialize, Serialize};
-use sqlx::FromRow;
+use sqlx::{FromRow, Row};
+use sqlx::sqlite::SqliteRow;
 use uuid::Uuid;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
+
+/// Category reserved for the temporary documents of a diagnostics run
+///
+/// Document listings and searches exclude it (the SQL filters bind this
+/// constant), so the documents never show up for the user.
+pub const DIAGNOSTICS_CATEGORY: &str = "__diagnostics__";
 
 /// Document model representing stored content
-#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct Document {
     /// Unique document identifier
     pub id: String,
@@ -39,9 +48,9 @@ pub struct Document {
     /// File hash for deduplication
     pub file_hash: Option<String>,
     /// Creation timestamp
-    pub created_at: String,
+    pub created_at: DateTime<Utc>,
     /// Last update timestamp
-    pub updated_at: String,
+    pub updated_at: DateTime<Utc>,
     /// Last accessed timestamp
     pub last_accessed: Option<String>,
     /// View count
@@ -52,6 +61,23 @@ pub struct Document {
     pub is_archived: bool,
     /// Soft delete status
     pub is_deleted: bool,
+    /// Year of publication, for cited works
+    #[serde(default)]
+    pub publication_year: Option<i64>,
+    /// BibTeX citation key
+    #[serde(default)]
+    pub citation_key: Option<String>,
+    /// Custom metadata fields (JSON object), such as frontmatter keys without a
+    /// column; see [`Document::get_metadata`]
+    #[serde(default)]
+    pub metadata_json: Option<String>,
+    /// Incremented on every write; updates name the version they were based on
+    #[serde(default = "initial_version")]
+    pub version: i64,
+}
+
+fn initial_version() -> i64 {
+    1
 }
 
 /// Vector embedding model for semantic search
@@ -76,7 +102,7 @@ pub struct Embedding {
     /// End position in original text
     pub end_position: i64,
     /// Creation timestamp
-    pub created_at: String,
+    pub created_at: DateTime<Utc>,
 }
 
 /// Application settings model
@@ -98,6 +124,28 @@ pub struct Setting {
     pub updated_at: String,
 }
 
+/// Synonyms and stop words applied to search queries
+///
+/// Stored as the `search_synonyms` and `search_stopwords` settings. Terms
+/// and stop words are lowercase single words; an expansion may be a phrase.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SearchDictionaries {
+    /// Query term to the terms and phrases it also matches, e.g.
+    /// "mi" to ["myocardial infarction"]
+    #[serde(default)]
+    pub synonyms: BTreeMap<String, Vec<String>>,
+    /// Words dropped from queries before full-text search
+    #[serde(default)]
+    pub stopwords: BTreeSet<String>,
+}
+
+impl SearchDictionaries {
+    /// Whether there is nothing to apply to a query
+    pub fn is_empty(&self) -> bool {
+        self.synonyms.is_empty() && self.stopwords.is_empty()
+    }
+}
+
 /// User bookmark model
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Bookmark {
@@ -210,10 +258,439 @@ pub struct ReadingProgress {
     pub updated_at: String,
 }
 
+/// Timed stretch of reading one document
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct ReadingSession {
+    pub id: String,
+    pub document_id: String,
+    pub started_at: String,
+    /// None while the session is still open
+    pub ended_at: Option<String>,
+    pub duration_seconds: i64,
+}
+
+/// AI operation run on a document's content, or citing it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct AiAuditEntry {
+    pub id: String,
+    pub document_id: String,
+    /// Operation name, e.g. "summarize" or "rag_citation"
+    pub operation: String,
+    pub model: String,
+    /// SHA-256 of the prompt input
+    pub prompt_hash: String,
+    /// SHA-256 of the output
+    pub output_hash: String,
+    pub duration_ms: i64,
+    pub timestamp: String,
+    /// Prompt input, only stored when debugging is enabled
+    pub prompt_text: Option<String>,
+    /// Output, only stored when debugging is enabled
+    pub output_text: Option<String>,
+}
+
+/// Link from one document to another (wiki `[[link]]` or markdown link)
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DocumentLink {
+    /// Unique link identifier
+    pub id: String,
+    /// Document containing the link
+    pub source_id: String,
+    /// Resolved target document, if the link matches one
+    pub target_id: Option<String>,
+    /// Link text as written in the source document
+    pub target_title: String,
+    /// Link syntax (wiki, markdown)
+    pub link_type: String,
+    /// Creation timestamp
+    pub created_at: String,
+}
+
+/// Reusable document template with `{{placeholder}}` variables
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Template {
+    /// Unique template identifier
+    pub id: String,
+    /// Template name, unique across templates
+    pub name: String,
+    /// Template body containing placeholders
+    pub content: String,
+    /// Tags applied to created documents (JSON array)
+    pub default_tags: Option<String>,
+    /// Category applied to created documents
+    pub default_category: Option<String>,
+    /// Content type of created documents
+    pub content_type: String,
+    /// Whether the template ships with the application
+    pub is_builtin: bool,
+    /// Creation timestamp
+    pub created_at: String,
+    /// Last update timestamp
+    pub updated_at: String,
+}
+
+/// AI conversation, optionally about a single document
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Conversation {
+    /// Unique conversation identifier
+    pub id: String,
+    /// Document the conversation is about, if any
+    pub document_id: Option<String>,
+    /// Display title
+    pub title: Option<String>,
+    /// Creation timestamp
+    pub created_at: String,
+    /// Time of the latest message
+    pub updated_at: String,
+}
+
+/// A question or answer recorded from an AI exchange
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ConversationMessage {
+    /// Unique message identifier
+    pub id: String,
+    /// Conversation the message belongs to, if any
+    pub conversation_id: Option<String>,
+    /// Document the message is about, if any
+    pub document_id: Option<String>,
+    /// Message author (user, assistant)
+    pub role: String,
+    /// Message text
+    pub content: String,
+    /// Reader selection a question was asked about
+    pub selection_text: Option<String>,
+    /// Byte offset where the selection starts in the document
+    pub selection_start: Option<i64>,
+    /// Byte offset where the selection ends in the document
+    pub selection_end: Option<i64>,
+    /// Creation timestamp
+    pub created_at: String,
+}
+
+/// Journal entry for a document mutation
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Operation {
+    /// Unique operation identifier
+    pub id: String,
+    /// Kind of mutation (delete, categorize, set_tags, archive, favorite, bulk_update, import, merge)
+    pub kind: String,
+    /// Human-readable summary for the history view
+    pub description: String,
+    /// Affected document ids (JSON array)
+    pub document_ids: String,
+    /// Changed value per document before the operation (JSON object)
+    pub before_state: String,
+    /// Changed value per document after the operation (JSON object)
+    pub after_state: String,
+    /// Whether the operation can be undone
+    pub undoable: bool,
+    /// When the operation was undone
+    pub undone_at: Option<String>,
+    /// Creation timestamp
+    pub created_at: String,
+}
+
+/// RSS or Atom feed subscription
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Feed {
+    /// Unique feed identifier
+    pub id: String,
+    /// Name shown in the UI and tagged on imported entries
+    pub name: String,
+    /// Feed URL
+    pub url: String,
+    /// ETag of the last response
+    pub etag: Option<String>,
+    /// Last-Modified of the last response
+    pub last_modified: Option<String>,
+    pub last_fetched_at: Option<String>,
+    /// Why the last fetch failed
+    pub last_error: Option<String>,
+    pub created_at: String,
+}
+
+impl Feed {
+    /// Create a new subscription
+    pub fn new(name: String, url: String) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            name,
+            url,
+            etag: None,
+            last_modified: None,
+            last_fetched_at: None,
+            last_error: None,
+            created_at: Utc::now().to_rfc3339(),
+        }
+    }
+}
+
+/// Checkpoint of a long-running operation
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct OperationState {
+    /// Unique operation identifier
+    pub id: String,
+    /// Kind of operation (reindex_all, index_repair, bulk_import)
+    pub kind: String,
+    /// Parameters needed to resume (JSON object)
+    pub params: String,
+    /// Last item completed
+    pub cursor: Option<String>,
+    /// Items completed so far
+    pub processed: i64,
+    /// Items in the whole operation, when known up front
+    pub total: Option<i64>,
+    /// running, completed or failed
+    pub status: String,
+    /// Why the operation failed
+    pub error: Option<String>,
+    pub started_at: String,
+    pub updated_at: String,
+}
+
+impl OperationState {
+    pub const STATUS_RUNNING: &'static str = "running";
+    pub const STATUS_COMPLETED: &'static str = "completed";
+    pub const STATUS_FAILED: &'static str = "failed";
+
+    /// Create the state of an operation that is starting now
+    pub fn new(kind: &str, params: serde_json::Value, total: Option<i64>) -> Self {
+        let now = Utc::now().to_rfc3339();
+        Self {
+            id: Uuid::new_v4().to_string(),
+            kind: kind.to_string(),
+            params: params.to_string(),
+            cursor: None,
+            processed: 0,
+            total,
+            status: Self::STATUS_RUNNING.to_string(),
+            error: None,
+            started_at: now.clone(),
+            updated_at: now,
+        }
+    }
+
+    /// Parsed parameters
+    pub fn params(&self) -> serde_json::Value {
+        serde_json::from_str(&self.params).unwrap_or_default()
+    }
+}
+
+/// Highlight over a range of a document, with an optional note
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Annotation {
+    /// Unique annotation identifier
+    pub id: String,
+    /// Annotated document
+    pub document_id: String,
+    /// Start of the range, in characters
+    pub start_offset: i64,
+    /// End of the range (exclusive), in characters
+    pub end_offset: i64,
+    /// Text of the range when it was highlighted
+    pub selected_text: String,
+    /// Highlight color name or hex code
+    pub color: String,
+    pub note: Option<String>,
+    /// Whether an edit removed the highlighted text
+    pub orphaned: bool,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl Annotation {
+    pub const DEFAULT_COLOR: &'static str = "yellow";
+
+    /// Create a new annotation over `[start_offset, end_offset)`
+    pub fn new(document_id: String, start_offset: i64, end_offset: i64, selected_text: String) -> Self {
+        let now = Utc::now().to_rfc3339();
+        Self {
+            id: Uuid::new_v4().to_string(),
+            document_id,
+            start_offset,
+            end_offset,
+            selected_text,
+            color: Self::DEFAULT_COLOR.to_string(),
+            note: None,
+            orphaned: false,
+            created_at: now.clone(),
+            updated_at: now,
+        }
+    }
+}
+
+/// Annotation with the title of its document, for the highlights view
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Highlight {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub annotation: Annotation,
+    pub document_title: String,
+}
+
+/// Annotation matching a full-text search
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct AnnotationMatch {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub annotation: Annotation,
+    pub document_title: String,
+    /// BM25 relevance, higher is better
+    pub score: f64,
+}
+
+/// Document row without content, for lists such as the home feed
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct FeedItem {
+    pub id: String,
+    pub title: String,
+    pub category: Option<String>,
+    pub content_type: String,
+    pub reading_time: Option<i64>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub last_accessed: Option<String>,
+    pub view_count: i64,
+    pub is_favorite: bool,
+}
+
+/// Rows moved from one document to another by a merge
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MergedReferences {
+    /// Bookmark ids
+    pub bookmarks: Vec<String>,
+    /// Note ids
+    pub notes: Vec<String>,
+    /// Ids of links whose target moved
+    pub links: Vec<String>,
+    /// Collections the receiving document was added to
+    pub collections: Vec<String>,
+}
+
+/// Automation rule applied to imported documents
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Rule {
+    /// Unique rule identifier
+    pub id: String,
+    /// Display name
+    pub name: String,
+    /// Rules run in ascending priority, so higher priorities win conflicts
+    pub priority: i64,
+    /// Whether the rule runs on import
+    pub enabled: bool,
+    /// Conditions that must all hold (JSON object)
+    pub conditions: String,
+    /// Actions applied to matching documents (JSON object)
+    pub actions: String,
+    /// Creation timestamp
+    pub created_at: String,
+    /// Last modification timestamp
+    pub updated_at: String,
+}
+
+/// File that failed to import, kept for listing and retrying
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct FailedImport {
+    /// Unique entry identifier
+    pub id: String,
+    /// Path of the file as it was imported
+    pub path: String,
+    /// Stable code of the last error (see `CodexError::code`)
+    pub error_code: String,
+    /// Message of the last error
+    pub message: String,
+    /// Number of failed import attempts
+    pub attempts: i64,
+    pub first_failed_at: String,
+    pub last_failed_at: String,
+}
+
+/// Listing fields of a document, as kept by the quick switcher
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct DocumentTitle {
+    pub id: String,
+    pub title: String,
+    pub category: Option<String>,
+    pub is_favorite: bool,
+    pub last_accessed: Option<String>,
+}
+
+/// Organization fields of a document, as used by inbox triage
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct DocumentLabels {
+    pub id: String,
+    pub title: String,
+    pub category: Option<String>,
+    /// Tags as a JSON array
+    pub tags: Option<String>,
+    /// Ids of the collections the document is in, as a JSON array
+    pub collections: String,
+    pub is_archived: bool,
+    pub created_at: String,
+}
+
+impl DocumentLabels {
+    pub fn get_tags(&self) -> Vec<String> {
+        self.tags
+            .as_ref()
+            .and_then(|t| serde_json::from_str(t).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn get_collections(&self) -> Vec<String> {
+        serde_json::from_str(&self.collections).unwrap_or_default()
+    }
+}
+
+/// Original file kept in the managed attachment store
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct Attachment {
+    /// SHA-256 of the file bytes, also its name in the store
+    pub id: String,
+    pub size: i64,
+    /// File name the original had when it was first stored
+    pub original_name: String,
+    pub created_at: String,
+}
+
+impl<'r> FromRow<'r, SqliteRow> for Document {
+    /// Read a `documents` row, decompressing the body if it is stored compressed
+    fn from_row(row: &'r SqliteRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            title: row.try_get("title")?,
+            content: super::compression::content_from_row(row)?,
+            summary: row.try_get("summary")?,
+            author: row.try_get("author")?,
+            source: row.try_get("source")?,
+            url: row.try_get("url")?,
+            content_type: row.try_get("content_type")?,
+            category: row.try_get("category")?,
+            tags: row.try_get("tags")?,
+            language: row.try_get("language")?,
+            reading_time: row.try_get("reading_time")?,
+            difficulty_level: row.try_get("difficulty_level")?,
+            file_size: row.try_get("file_size")?,
+            file_hash: row.try_get("file_hash")?,
+            created_at: row.try_get("created_at")?,
+            updated_at: row.try_get("updated_at")?,
+            last_accessed: row.try_get("last_accessed")?,
+            view_count: row.try_get("view_count")?,
+            is_favorite: row.try_get("is_favorite")?,
+            is_archived: row.try_get("is_archived")?,
+            is_deleted: row.try_get("is_deleted")?,
+            publication_year: row.try_get("publication_year")?,
+            citation_key: row.try_get("citation_key")?,
+            metadata_json: row.try_get("metadata_json")?,
+            version: row.try_get("version")?,
+        })
+    }
+}
+
 impl Document {
     /// Create a new document with default values
     pub fn new(title: String, content: String, content_type: String) -> Self {
-        let now = Utc::now().to_rfc3339();
+        let now = Utc::now();
         Self {
             id: Uuid::new_v4().to_string(),
             title,
@@ -230,13 +707,17 @@ impl Document {
             difficulty_level: None,
             file_size: None,
             file_hash: None,
-            created_at: now.clone(),
+            created_at: now,
             updated_at: now,
             last_accessed: None,
             view_count: 0,
             is_favorite: false,
             is_archived: false,
             is_deleted: false,
+            publication_year: None,
+            citation_key: None,
+            metadata_json: None,
+            version: initial_version(),
         }
     }
 
@@ -252,6 +733,84 @@ impl Document {
     pub fn set_tags(&mut self, tags: Vec<String>) {
         self.tags = Some(serde_json::to_string(&tags).unwrap_or_default());
     }
+
+    /// Custom metadata, such as frontmatter keys of an imported note
+    pub fn get_metadata(&self) -> BTreeMap<String, serde_json::Value> {
+        self.metadata_json
+            .as_ref()
+            .and_then(|m| serde_json::from_str(m).ok())
+            .unwrap_or_default()
+    }
+
+    /// Set custom metadata; an empty map clears it
+    pub fn set_metadata(&mut self, metadata: &BTreeMap<String, serde_json::Value>) {
+        self.metadata_json = if metadata.is_empty() {
+            None
+        } else {
+            serde_json::to_string(metadata).ok()
+        };
+    }
+
+    /// Whether this is a temporary document of a diagnostics run
+    pub fn is_diagnostic(&self) -> bool {
+        self.category.as_deref() == Some(DIAGNOSTICS_CATEGORY)
+    }
+}
+
+/// Longest custom metadata key
+pub const METADATA_KEY_MAX_LEN: usize = 64;
+
+/// Whether `key` can name a custom metadata field: ASCII letters, digits,
+/// `_` and `-`, so it is usable unquoted in SQL JSON paths and frontmatter
+pub fn is_metadata_key(key: &str) -> bool {
+    !key.is_empty()
+        && key.len() <= METADATA_KEY_MAX_LEN
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Value of a custom metadata field
+///
+/// Stored in `metadata_json` as the matching JSON value.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MetadataValue {
+    Bool(bool),
+    Number(serde_json::Number),
+    Text(String),
+    List(Vec<String>),
+}
+
+impl MetadataValue {
+    /// The value stored under a key, `None` for JSON without a matching variant
+    pub fn from_json(value: &serde_json::Value) -> Option<Self> {
+        serde_json::from_value(value.clone()).ok()
+    }
+
+    /// Whether a stored value passes this value as a filter
+    ///
+    /// Scalars must be equal; a list matches lists containing all its items.
+    pub fn matches(&self, stored: &serde_json::Value) -> bool {
+        match (self, stored) {
+            (Self::Bool(wanted), serde_json::Value::Bool(value)) => wanted == value,
+            (Self::Number(wanted), serde_json::Value::Number(value)) => wanted.as_f64() == value.as_f64(),
+            (Self::Text(wanted), serde_json::Value::String(value)) => wanted == value,
+            (Self::List(wanted), serde_json::Value::Array(values)) => {
+                wanted.iter().all(|item| values.iter().any(|value| value.as_str() == Some(item.as_str())))
+            }
+            _ => false,
+        }
+    }
+}
+
+impl From<MetadataValue> for serde_json::Value {
+    fn from(value: MetadataValue) -> Self {
+        match value {
+            MetadataValue::Bool(value) => Self::Bool(value),
+            MetadataValue::Number(value) => Self::Number(value),
+            MetadataValue::Text(value) => Self::String(value),
+            MetadataValue::List(values) => Self::Array(values.into_iter().map(Self::String).collect()),
+        }
+    }
 }
 
 impl Embedding {
@@ -275,7 +834,7 @@ impl Embedding {
             text_chunk,
             start_position,
             end_position,
-            created_at: Utc::now().to_rfc3339(),
+            created_at: Utc::now(),
         }
     }
 
@@ -291,6 +850,12 @@ impl Embedding {
     }
 }
 
+/// Hash identifying a chunk text, under which its vector is shared
+pub fn chunk_hash(text: &str) -> String {
+    use sha2::{Digest, Sha256};
+    hex::encode(Sha256::digest(text.as_bytes()))
+}
+
 impl Setting {
     /// Create a new setting
     pub fn new(key: String, value: String, category: String) -> Self {
@@ -323,4 +888,170 @@ impl Setting {
         self.updated_at = Utc::now().to_rfc3339();
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+impl Collection {
+    /// Create a new, unpinned collection
+    pub fn new(name: String, description: Option<String>) -> Self {
+        let now = Utc::now().to_rfc3339();
+        Self {
+            id: Uuid::new_v4().to_string(),
+            name,
+            description,
+            color: None,
+            icon: None,
+            is_pinned: false,
+            created_at: now.clone(),
+            updated_at: now,
+        }
+    }
+}
+
+impl DocumentLink {
+    /// Create a new link from a source document
+    pub fn new(source_id: String, target_title: String, link_type: String, target_id: Option<String>) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            source_id,
+            target_id,
+            target_title,
+            link_type,
+            created_at: Utc::now().to_rfc3339(),
+        }
+    }
+}
+
+impl Template {
+    /// Create a new user template
+    pub fn new(name: String, content: String) -> Self {
+        let now = Utc::now().to_rfc3339();
+        Self {
+            id: Uuid::new_v4().to_string(),
+            name,
+            content,
+            default_tags: None,
+            default_category: None,
+            content_type: "text/markdown".to_string(),
+            is_builtin: false,
+            created_at: now.clone(),
+            updated_at: now,
+        }
+    }
+
+    /// Get default tags as a vector
+    pub fn get_default_tags(&self) -> Vec<String> {
+        self.default_tags
+            .as_ref()
+            .and_then(|t| serde_json::from_str(t).ok())
+            .unwrap_or_default()
+    }
+
+    /// Set default tags from a vector
+    pub fn set_default_tags(&mut self, tags: Vec<String>) {
+        self.default_tags = Some(serde_json::to_string(&tags).unwrap_or_default());
+    }
+}
+
+impl Conversation {
+    /// Create a new conversation
+    pub fn new(document_id: Option<String>, title: Option<String>) -> Self {
+        let now = Utc::now().to_rfc3339();
+        Self {
+            id: Uuid::new_v4().to_string(),
+            document_id,
+            title,
+            created_at: now.clone(),
+            updated_at: now,
+        }
+    }
+}
+
+impl Operation {
+    /// Document soft delete; the state is `is_deleted`
+    pub const KIND_DELETE: &'static str = "delete";
+    /// Category change; the state is the category
+    pub const KIND_CATEGORIZE: &'static str = "categorize";
+    /// Tag replacement; the state is the tag list
+    pub const KIND_SET_TAGS: &'static str = "set_tags";
+    /// Archive or unarchive; the state is `is_archived`
+    pub const KIND_ARCHIVE: &'static str = "archive";
+    /// Favorite or unfavorite; the state is `is_favorite`
+    pub const KIND_FAVORITE: &'static str = "favorite";
+    /// Organization edit of several documents; the state is
+    /// [`crate::content::bulk::OrganizationState`]
+    pub const KIND_BULK_UPDATE: &'static str = "bulk_update";
+    /// Import of new documents; not undoable
+    pub const KIND_IMPORT: &'static str = "import";
+    /// Merge of two documents; the state is [`crate::content::merge::MergeState`]
+    pub const KIND_MERGE: &'static str = "merge";
+    /// Permanent deletion of documents in the trash; not undoable
+    pub const KIND_PURGE: &'static str = "purge";
+
+    /// Create an undoable operation from per-document before and after values
+    pub fn new<T: Serialize>(
+        kind: &str,
+        description: String,
+        before: BTreeMap<String, T>,
+        after: BTreeMap<String, T>,
+    ) -> Self {
+        let document_ids: Vec<&String> = before.keys().collect();
+        Self {
+            id: Uuid::new_v4().to_string(),
+            kind: kind.to_string(),
+            description,
+            document_ids: serde_json::to_string(&document_ids).unwrap_or_else(|_| "[]".to_string()),
+            before_state: serde_json::to_string(&before).unwrap_or_else(|_| "{}".to_string()),
+            after_state: serde_json::to_string(&after).unwrap_or_else(|_| "{}".to_string()),
+            undoable: true,
+            undone_at: None,
+            created_at: Utc::now().to_rfc3339(),
+        }
+    }
+
+    /// Create an entry for an operation that cannot be undone
+    pub fn irreversible(kind: &str, description: String, document_ids: &[String]) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            kind: kind.to_string(),
+            description,
+            document_ids: serde_json::to_string(document_ids).unwrap_or_else(|_| "[]".to_string()),
+            before_state: "{}".to_string(),
+            after_state: "{}".to_string(),
+            undoable: false,
+            undone_at: None,
+            created_at: Utc::now().to_rfc3339(),
+        }
+    }
+
+    /// Get affected document ids as a vector
+    pub fn get_document_ids(&self) -> Vec<String> {
+        serde_json::from_str(&self.document_ids).unwrap_or_default()
+    }
+
+    /// Get the per-document values from before the operation
+    pub fn get_before_state<T: serde::de::DeserializeOwned>(&self) -> serde_json::Result<BTreeMap<String, T>> {
+        serde_json::from_str(&self.before_state)
+    }
+}
+
+impl ConversationMessage {
+    /// Message author role for questions
+    pub const ROLE_USER: &'static str = "user";
+    /// Message author role for answers
+    pub const ROLE_ASSISTANT: &'static str = "assistant";
+
+    /// Create a new message
+    pub fn new(conversation_id: Option<String>, document_id: Option<String>, role: &str, content: String) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            conversation_id,
+            document_id,
+            role: role.to_string(),
+            content,
+            selection_text: None,
+            selection_start: None,
+            selection_end: None,
+            created_at: Utc::now().to_rfc3339(),
+        }
+    }
+}
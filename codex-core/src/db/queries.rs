@@ -1,30 +1,67 @@
 //! Database query operations for Codex Core
 
-use sqlx::{SqlitePool, Row, query, query_as};
+use sqlx::{SqliteConnection, SqlitePool, Row, FromRow, query, query_as};
+use sqlx::sqlite::SqliteRow;
 use chrono::Utc;
+use tracing::instrument;
 
 use crate::{CodexError, CodexResult};
+use super::compression::{self, CompressionPolicy, StoredBody};
 use super::models::*;
 
+/// Start a transaction that takes the write lock before anything else, like
+/// `BEGIN IMMEDIATE`
+///
+/// sqlx only issues a deferred `BEGIN`. Preparing a statement that touches a
+/// full-text table on a fresh connection reads the table's configuration
+/// first, and a deferred transaction that has read cannot upgrade to a write
+/// once another connection committed: it fails with `SQLITE_BUSY` instead of
+/// waiting. A write that matches no row takes the lock, waiting as usual.
+/// The wait for a pooled connection is recorded as [`crate::metrics::DB_POOL_WAIT`].
+pub(crate) async fn begin_write(pool: &SqlitePool) -> CodexResult<sqlx::Transaction<'static, sqlx::Sqlite>> {
+    let start = std::time::Instant::now();
+    let connection = pool.acquire().await?;
+    crate::metrics::record(crate::metrics::DB_POOL_WAIT, start.elapsed());
+    let mut tx = sqlx::Transaction::begin(connection).await?;
+    sqlx::query("UPDATE settings SET value = value WHERE 0").execute(&mut *tx).await?;
+    Ok(tx)
+}
+
 /// Document query operations
 pub struct DocumentQueries;
 
 impl DocumentQueries {
     /// Create a new document
-    pub async fn create(pool: &SqlitePool, document: &Document) -> CodexResult<()> {
+    ///
+    /// A body that `compression` compresses is stored compressed by the
+    /// insert itself, with its plain text indexed in the same transaction.
+    #[instrument(level = "debug", skip_all, fields(id = %document.id))]
+    pub async fn create(pool: &SqlitePool, document: &Document, compression: CompressionPolicy) -> CodexResult<()> {
+        let mut tx = begin_write(pool).await?;
+        Self::insert(&mut tx, document, compression).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Insert a new document within the caller's transaction, see [`Self::create`]
+    pub(crate) async fn insert(conn: &mut SqliteConnection, document: &Document, compression: CompressionPolicy) -> CodexResult<()> {
+        let body = StoredBody::new(&document.content, compression);
+
         sqlx::query(
             r#"
             INSERT INTO documents (
                 id, title, content, summary, author, source, url, content_type,
                 category, tags, language, reading_time, difficulty_level,
                 file_size, file_hash, created_at, updated_at, last_accessed,
-                view_count, is_favorite, is_archived, is_deleted
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                view_count, is_favorite, is_archived, is_deleted, publication_year, citation_key,
+                metadata_json, content_compressed, compression, content_size
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#,
         )
         .bind(&document.id)
         .bind(&document.title)
-        .bind(&document.content)
+        .bind(body.content)
         .bind(&document.summary)
         .bind(&document.author)
         .bind(&document.source)
@@ -37,21 +74,28 @@ impl DocumentQueries {
         .bind(document.difficulty_level)
         .bind(document.file_size)
         .bind(&document.file_hash)
-        .bind(&document.created_at)
-        .bind(&document.updated_at)
+        .bind(document.created_at)
+        .bind(document.updated_at)
         .bind(&document.last_accessed)
         .bind(document.view_count)
         .bind(document.is_favorite)
         .bind(document.is_archived)
         .bind(document.is_deleted)
-        .execute(pool)
+        .bind(document.publication_year)
+        .bind(&document.citation_key)
+        .bind(&document.metadata_json)
+        .bind(&body.compressed)
+        .bind(body.compression)
+        .bind(body.content_size)
+        .execute(&mut *conn)
         .await
-        .map_err(|e| CodexError::Database(e))?;
+        .map_err(CodexError::Database)?;
 
-        Ok(())
+        body.index(conn, &document.id).await
     }
 
     /// Get document by ID
+    #[instrument(level = "debug", skip(pool))]
     pub async fn get_by_id(pool: &SqlitePool, id: &str) -> CodexResult<Option<Document>> {
         let row = sqlx::query(
             "SELECT * FROM documents WHERE id = ? AND is_deleted = false"
@@ -59,33 +103,10 @@ impl DocumentQueries {
         .bind(id)
         .fetch_optional(pool)
         .await
-        .map_err(|e| CodexError::Database(e))?;
+        .map_err(CodexError::Database)?;
 
         if let Some(row) = row {
-            let document = Document {
-                id: row.get("id"),
-                title: row.get("title"),
-                content: row.get("content"),
-                summary: row.get("summary"),
-                author: row.get("author"),
-                source: row.get("source"),
-                url: row.get("url"),
-                content_type: row.get("content_type"),
-                category: row.get("category"),
-                tags: row.get("tags"),
-                language: row.get("language"),
-                reading_time: row.get("reading_time"),
-                difficulty_level: row.get("difficulty_level"),
-                file_size: row.get("file_size"),
-                file_hash: row.get("file_hash"),
-                created_at: row.get("created_at"),
-                updated_at: row.get("updated_at"),
-                last_accessed: row.get("last_accessed"),
-                view_count: row.get("view_count"),
-                is_favorite: row.get("is_favorite"),
-                is_archived: row.get("is_archived"),
-                is_deleted: row.get("is_deleted"),
-            };
+            let document = Document::from_row(&row)?;
             Ok(Some(document))
         } else {
             Ok(None)
@@ -100,56 +121,121 @@ impl DocumentQueries {
         .bind(file_hash)
         .fetch_optional(pool)
         .await
-        .map_err(|e| CodexError::Database(e))?;
+        .map_err(CodexError::Database)?;
 
         if let Some(row) = row {
-            let document = Document {
-                id: row.get("id"),
-                title: row.get("title"),
-                content: row.get("content"),
-                summary: row.get("summary"),
-                author: row.get("author"),
-                source: row.get("source"),
-                url: row.get("url"),
-                content_type: row.get("content_type"),
-                category: row.get("category"),
-                tags: row.get("tags"),
-                language: row.get("language"),
-                reading_time: row.get("reading_time"),
-                difficulty_level: row.get("difficulty_level"),
-                file_size: row.get("file_size"),
-                file_hash: row.get("file_hash"),
-                created_at: row.get("created_at"),
-                updated_at: row.get("updated_at"),
-                last_accessed: row.get("last_accessed"),
-                view_count: row.get("view_count"),
-                is_favorite: row.get("is_favorite"),
-                is_archived: row.get("is_archived"),
-                is_deleted: row.get("is_deleted"),
-            };
+            let document = Document::from_row(&row)?;
             Ok(Some(document))
         } else {
             Ok(None)
         }
     }
 
+    /// Whether a collection with this id exists
+    pub async fn collection_exists(pool: &SqlitePool, collection_id: &str) -> CodexResult<bool> {
+        let exists: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM collections WHERE id = ?)")
+            .bind(collection_id)
+            .fetch_one(pool)
+            .await?;
+
+        Ok(exists)
+    }
+
+    /// Create a collection
+    pub async fn create_collection(pool: &SqlitePool, collection: &Collection) -> CodexResult<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO collections (id, name, description, color, icon, is_pinned, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(&collection.id)
+        .bind(&collection.name)
+        .bind(&collection.description)
+        .bind(&collection.color)
+        .bind(&collection.icon)
+        .bind(collection.is_pinned)
+        .bind(&collection.created_at)
+        .bind(&collection.updated_at)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Get a (non-deleted) document by its web URL
+    pub async fn get_by_url(pool: &SqlitePool, url: &str) -> CodexResult<Option<Document>> {
+        let document = sqlx::query_as::<_, Document>(
+            "SELECT * FROM documents WHERE url = ? AND is_deleted = false LIMIT 1",
+        )
+        .bind(url)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(document)
+    }
+
+    /// Find a (non-deleted) document whose URL or text mentions a DOI
+    ///
+    /// Text is searched in the full-text index, which keeps the plain text
+    /// of compressed bodies: the phrase of the DOI's tokens narrows the rows,
+    /// the indexed text must then contain the DOI itself.
+    pub async fn find_by_doi(pool: &SqlitePool, doi: &str) -> CodexResult<Option<Document>> {
+        let doi = doi.to_lowercase();
+        let pattern = format!("%{}%", doi);
+        let tokens: Vec<&str> = doi.split(|c: char| !c.is_alphanumeric()).filter(|t| !t.is_empty()).collect();
+        if tokens.is_empty() {
+            return Ok(None);
+        }
+        let phrase = format!("content : \"{}\"", tokens.join(" "));
+
+        let document = sqlx::query_as::<_, Document>(
+            r#"
+            SELECT * FROM documents
+            WHERE is_deleted = false AND (LOWER(url) LIKE ? OR rowid IN (
+                SELECT rowid FROM documents_fts WHERE documents_fts MATCH ? AND LOWER(content) LIKE ?
+            ))
+            ORDER BY LOWER(url) LIKE ? DESC LIMIT 1
+            "#
+        )
+        .bind(&pattern)
+        .bind(&phrase)
+        .bind(&pattern)
+        .bind(&pattern)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(document)
+    }
+
     /// Update document
-    pub async fn update(pool: &SqlitePool, document: &Document) -> CodexResult<()> {
+    ///
+    /// The write only applies if the row is still at `document.version`,
+    /// and increments it; returns the new version. A row that has moved on
+    /// fails with [`CodexError::Conflict`]. View count and last access are
+    /// reading activity, not edits, and are left as they are. The body is
+    /// stored as `compression` decides, as in [`Self::create`].
+    #[instrument(level = "debug", skip_all, fields(id = %document.id))]
+    pub async fn update(pool: &SqlitePool, document: &Document, compression: CompressionPolicy) -> CodexResult<i64> {
         let updated_at = Utc::now();
-        
-        sqlx::query(
+        let body = StoredBody::new(&document.content, compression);
+        let mut tx = begin_write(pool).await?;
+
+        let result = sqlx::query(
             r#"
             UPDATE documents SET
                 title = ?, content = ?, summary = ?, author = ?, source = ?,
                 url = ?, content_type = ?, category = ?, tags = ?, language = ?,
                 reading_time = ?, difficulty_level = ?, file_size = ?, file_hash = ?,
-                updated_at = ?, last_accessed = ?, view_count = ?, is_favorite = ?,
-                is_archived = ?, is_deleted = ?
-            WHERE id = ?
+                updated_at = ?, is_favorite = ?,
+                is_archived = ?, is_deleted = ?, publication_year = ?, citation_key = ?,
+                metadata_json = ?, content_compressed = ?, compression = ?, content_size = ?,
+                version = version + 1
+            WHERE id = ? AND version = ?
             "#
         )
         .bind(&document.title)
-        .bind(&document.content)
+        .bind(body.content)
         .bind(&document.summary)
         .bind(&document.author)
         .bind(&document.source)
@@ -163,24 +249,134 @@ impl DocumentQueries {
         .bind(document.file_size)
         .bind(&document.file_hash)
         .bind(updated_at.to_rfc3339())
-        .bind(&document.last_accessed)
-        .bind(document.view_count)
         .bind(document.is_favorite)
         .bind(document.is_archived)
         .bind(document.is_deleted)
+        .bind(document.publication_year)
+        .bind(&document.citation_key)
+        .bind(&document.metadata_json)
+        .bind(&body.compressed)
+        .bind(body.compression)
+        .bind(body.content_size)
         .bind(&document.id)
+        .bind(document.version)
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            tx.rollback().await?;
+            return Err(Self::version_mismatch(pool, &document.id).await?);
+        }
+
+        body.index(&mut tx, &document.id).await?;
+        tx.commit().await?;
+
+        Ok(document.version + 1)
+    }
+
+    /// Current version of a document, including soft-deleted ones
+    pub async fn get_version(pool: &SqlitePool, id: &str) -> CodexResult<Option<i64>> {
+        let version: Option<(i64,)> = sqlx::query_as("SELECT version FROM documents WHERE id = ?")
+            .bind(id)
+            .fetch_optional(pool)
+            .await?;
+        Ok(version.map(|(version,)| version))
+    }
+
+    /// Error for a versioned write that matched no row
+    async fn version_mismatch(pool: &SqlitePool, id: &str) -> CodexResult<CodexError> {
+        Ok(match Self::get_version(pool, id).await? {
+            Some(current_version) => CodexError::conflict(current_version),
+            None => CodexError::not_found(format!("Document not found: {}", id)),
+        })
+    }
+
+    /// Move a stored plain-text body into `content_compressed`
+    ///
+    /// The FTS row already holds the plain text; the update trigger keeps it
+    /// once `compression` is set.
+    async fn compress_stored_content(
+        pool: &SqlitePool,
+        id: &str,
+        content: &str,
+        compression: CompressionPolicy,
+    ) -> CodexResult<bool> {
+        let Some(compressed) = compression.compress(content) else {
+            return Ok(false);
+        };
+
+        let result = sqlx::query(
+            r#"
+            UPDATE documents SET
+                content = '', content_compressed = ?, compression = ?, content_size = ?
+            WHERE id = ? AND compression IS NULL AND content = ?
+            "#
+        )
+        .bind(compressed)
+        .bind(compression::COMPRESSION_ZSTD)
+        .bind(content.len() as i64)
+        .bind(id)
+        .bind(content)
         .execute(pool)
         .await?;
 
-        Ok(())
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Count stored bodies that are large enough to compress but still plain text
+    pub async fn count_uncompressed(pool: &SqlitePool) -> CodexResult<i64> {
+        let count: (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM documents WHERE compression IS NULL AND length(CAST(content AS BLOB)) >= ?"
+        )
+        .bind(compression::COMPRESSION_THRESHOLD_BYTES as i64)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(count.0)
+    }
+
+    /// Compress up to `limit` large plain-text bodies after `after_rowid`
+    ///
+    /// Returns the number of rows examined and the last rowid seen, so callers
+    /// can page through the table. Bodies that do not shrink stay plain text.
+    pub async fn compress_uncompressed_batch(
+        pool: &SqlitePool,
+        after_rowid: i64,
+        limit: i64,
+        compression: CompressionPolicy,
+    ) -> CodexResult<(usize, i64)> {
+        let rows = sqlx::query(
+            r#"
+            SELECT rowid, id, content FROM documents
+            WHERE rowid > ? AND compression IS NULL AND length(CAST(content AS BLOB)) >= ?
+            ORDER BY rowid
+            LIMIT ?
+            "#
+        )
+        .bind(after_rowid)
+        .bind(compression::COMPRESSION_THRESHOLD_BYTES as i64)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        let mut last_rowid = after_rowid;
+        for row in &rows {
+            last_rowid = row.get("rowid");
+            let id: String = row.get("id");
+            let content: String = row.get("content");
+            Self::compress_stored_content(pool, &id, &content, compression).await?;
+        }
+
+        Ok((rows.len(), last_rowid))
     }
 
     /// Delete document (soft delete)
+    #[instrument(level = "debug", skip(pool))]
     pub async fn delete(pool: &SqlitePool, id: &str) -> CodexResult<()> {
         let updated_at = Utc::now();
         
         sqlx::query(
-            "UPDATE documents SET is_deleted = true, updated_at = ? WHERE id = ?"
+            "UPDATE documents SET is_deleted = true, updated_at = ?, version = version + 1 WHERE id = ?"
         )
         .bind(updated_at.to_rfc3339())
         .bind(id)
@@ -190,7 +386,46 @@ impl DocumentQueries {
         Ok(())
     }
 
+    /// Undo a soft delete; returns false when the document is not deleted
+    pub async fn restore(pool: &SqlitePool, id: &str) -> CodexResult<bool> {
+        let result = sqlx::query(
+            "UPDATE documents SET is_deleted = false, updated_at = ?, version = version + 1 WHERE id = ? AND is_deleted = true",
+        )
+        .bind(Utc::now().to_rfc3339())
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Permanently delete the temporary documents of diagnostics runs
+    ///
+    /// Embeddings, cached vectors and full-text rows go with them.
+    pub async fn purge_diagnostics(pool: &SqlitePool) -> CodexResult<u64> {
+        let result = sqlx::query("DELETE FROM documents WHERE category = ?")
+            .bind(DIAGNOSTICS_CATEGORY)
+            .execute(pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Permanently delete soft-deleted documents, emptying the trash, and return their ids
+    ///
+    /// Like [`purge_diagnostics`](Self::purge_diagnostics), their embeddings,
+    /// cached vectors and full-text rows go with them. The freed pages are
+    /// only returned to the file system by a `VACUUM`.
+    pub async fn purge_deleted(pool: &SqlitePool) -> CodexResult<Vec<String>> {
+        let ids = sqlx::query_scalar("DELETE FROM documents WHERE is_deleted = true RETURNING id")
+            .fetch_all(pool)
+            .await?;
+
+        Ok(ids)
+    }
+
     /// Search documents using FTS5
+    #[instrument(level = "debug", skip(pool))]
     pub async fn search_full_text(
         pool: &SqlitePool,
         query: &str,
@@ -201,12 +436,13 @@ impl DocumentQueries {
             r#"
             SELECT d.* FROM documents d
             JOIN documents_fts fts ON d.rowid = fts.rowid
-            WHERE fts MATCH ? AND d.is_deleted = false
+            WHERE fts MATCH ? AND d.is_deleted = false AND d.category IS NOT ?
             ORDER BY rank
             LIMIT ?
             "#
         )
         .bind(query)
+        .bind(DIAGNOSTICS_CATEGORY)
         .bind(limit)
         .fetch_all(pool)
         .await?;
@@ -224,12 +460,13 @@ impl DocumentQueries {
         let documents = sqlx::query_as::<_, Document>(
             r#"
             SELECT * FROM documents
-            WHERE category = ? AND is_deleted = false
-            ORDER BY created_at DESC
+            WHERE category = ? AND is_deleted = false AND category IS NOT ? AND is_archived = false
+            ORDER BY created_at DESC, id DESC
             LIMIT ? OFFSET ?
             "#
         )
         .bind(category)
+        .bind(DIAGNOSTICS_CATEGORY)
         .bind(limit)
         .bind(offset)
         .fetch_all(pool)
@@ -238,16 +475,27 @@ impl DocumentQueries {
         Ok(documents)
     }
 
-    /// Get recent documents
+    /// Get (id, title) pairs of all live documents, used for link resolution
+    pub async fn get_titles(pool: &SqlitePool) -> CodexResult<Vec<(String, String)>> {
+        let rows = sqlx::query("SELECT id, title FROM documents WHERE is_deleted = false AND category IS NOT ?")
+            .bind(DIAGNOSTICS_CATEGORY)
+            .fetch_all(pool)
+            .await?;
+
+        Ok(rows.iter().map(|r| (r.get("id"), r.get("title"))).collect())
+    }
+
+    /// Get recent documents, excluding archived ones
     pub async fn get_recent(pool: &SqlitePool, limit: i64) -> CodexResult<Vec<Document>> {
         let documents = sqlx::query_as::<_, Document>(
             r#"
             SELECT * FROM documents
-            WHERE is_deleted = false
-            ORDER BY created_at DESC
+            WHERE is_deleted = false AND category IS NOT ? AND is_archived = false
+            ORDER BY created_at DESC, id DESC
             LIMIT ?
             "#
         )
+        .bind(DIAGNOSTICS_CATEGORY)
         .bind(limit)
         .fetch_all(pool)
         .await?;
@@ -255,16 +503,17 @@ impl DocumentQueries {
         Ok(documents)
     }
 
-    /// Get favorite documents
-    pub async fn get_favorites(pool: &SqlitePool, limit: i64) -> CodexResult<Vec<Document>> {
+    /// Get archived documents, most recently updated first
+    pub async fn get_archived(pool: &SqlitePool, limit: i64) -> CodexResult<Vec<Document>> {
         let documents = sqlx::query_as::<_, Document>(
             r#"
             SELECT * FROM documents
-            WHERE is_favorite = true AND is_deleted = false
-            ORDER BY updated_at DESC
+            WHERE is_archived = true AND is_deleted = false AND category IS NOT ?
+            ORDER BY updated_at DESC, id DESC
             LIMIT ?
             "#
         )
+        .bind(DIAGNOSTICS_CATEGORY)
         .bind(limit)
         .fetch_all(pool)
         .await?;
@@ -272,236 +521,503 @@ impl DocumentQueries {
         Ok(documents)
     }
 
-    /// Update view count and last accessed
-    pub async fn update_access(pool: &SqlitePool, id: &str) -> CodexResult<()> {
-        let now = Utc::now();
-        
-        sqlx::query(
+    /// Get every live document, archived included
+    #[instrument(level = "debug", skip(pool))]
+    pub async fn get_all(pool: &SqlitePool) -> CodexResult<Vec<Document>> {
+        let documents = sqlx::query_as::<_, Document>(
+            "SELECT * FROM documents WHERE is_deleted = false AND category IS NOT ? ORDER BY created_at DESC, id DESC",
+        )
+        .bind(DIAGNOSTICS_CATEGORY)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(documents)
+    }
+
+    /// Ids of all documents in id order, starting after `after`
+    ///
+    /// Lets long-running passes over the library resume from a checkpoint.
+    pub async fn get_ids_after(pool: &SqlitePool, after: Option<&str>) -> CodexResult<Vec<String>> {
+        let ids = sqlx::query_scalar::<_, String>(
             r#"
-            UPDATE documents
-            SET view_count = view_count + 1, last_accessed = ?
-            WHERE id = ?
-            "#
+            SELECT id FROM documents
+            WHERE is_deleted = false AND category IS NOT ? AND id > ?
+            ORDER BY id
+            "#,
         )
-        .bind(now.to_rfc3339())
+        .bind(DIAGNOSTICS_CATEGORY)
+        .bind(after.unwrap_or(""))
+        .fetch_all(pool)
+        .await?;
+
+        Ok(ids)
+    }
+
+    /// Set the archived flag; returns false if the document does not exist
+    pub async fn set_archived(pool: &SqlitePool, id: &str, archived: bool) -> CodexResult<bool> {
+        let result = sqlx::query(
+            "UPDATE documents SET is_archived = ?, version = version + 1 WHERE id = ? AND is_deleted = false",
+        )
+        .bind(archived)
         .bind(id)
         .execute(pool)
         .await?;
 
-        Ok(())
+        Ok(result.rows_affected() > 0)
     }
-}
 
-/// Embedding query operations
-pub struct EmbeddingQueries;
+    /// Get favorite documents
+    pub async fn get_favorites(pool: &SqlitePool, limit: i64) -> CodexResult<Vec<Document>> {
+        let documents = sqlx::query_as::<_, Document>(
+            r#"
+            SELECT * FROM documents
+            WHERE is_favorite = true AND is_deleted = false AND category IS NOT ?
+            ORDER BY favorited_at DESC
+            LIMIT ?
+            "#
+        )
+        .bind(DIAGNOSTICS_CATEGORY)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
 
-impl EmbeddingQueries {
-    /// Create a new embedding
-    pub async fn create(pool: &SqlitePool, embedding: &Embedding) -> CodexResult<()> {
-        sqlx::query(
+        Ok(documents)
+    }
+
+    /// Titles of listed documents (not deleted, archived or diagnostics), or of those among `ids`
+    pub async fn get_title_entries(pool: &SqlitePool, ids: Option<&[String]>) -> CodexResult<Vec<DocumentTitle>> {
+        let ids = ids.map(serde_json::to_string).transpose()?;
+        let titles = sqlx::query_as::<_, DocumentTitle>(
             r#"
-            INSERT INTO embeddings (
-                id, document_id, vector, dimensions, model, chunk_index,
-                text_chunk, start_position, end_position, created_at
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            SELECT id, title, category, is_favorite, last_accessed FROM documents
+            WHERE is_deleted = false AND is_archived = false AND category IS NOT ?
+              AND (? IS NULL OR id IN (SELECT value FROM json_each(?)))
             "#
         )
-        .bind(&embedding.id)
-        .bind(&embedding.document_id)
-        .bind(&embedding.vector)
-        .bind(embedding.dimensions)
-        .bind(&embedding.model)
-        .bind(embedding.chunk_index)
-        .bind(&embedding.text_chunk)
-        .bind(embedding.start_position)
-        .bind(embedding.end_position)
-        .bind(&embedding.created_at)
-        .execute(pool)
+        .bind(DIAGNOSTICS_CATEGORY)
+        .bind(&ids)
+        .bind(&ids)
+        .fetch_all(pool)
         .await?;
 
-        Ok(())
+        Ok(titles)
     }
 
-    /// Get embeddings for a document
-    pub async fn get_by_document(
-        pool: &SqlitePool,
-        document_id: &str,
-    ) -> CodexResult<Vec<Embedding>> {
-        let embeddings = sqlx::query_as::<_, Embedding>(
-            "SELECT * FROM embeddings WHERE document_id = ? ORDER BY chunk_index"
+    /// Category, tags, collections and archive state of all live documents
+    pub async fn get_labels(pool: &SqlitePool) -> CodexResult<Vec<DocumentLabels>> {
+        let labels = sqlx::query_as::<_, DocumentLabels>(
+            r#"
+            SELECT d.id, d.title, d.category, d.tags, d.is_archived, d.created_at,
+                   (SELECT json_group_array(dc.collection_id) FROM document_collections dc
+                    WHERE dc.document_id = d.id) AS collections
+            FROM documents d
+            WHERE d.is_deleted = false AND d.category IS NOT ?
+            "#
         )
-        .bind(document_id)
+        .bind(DIAGNOSTICS_CATEGORY)
         .fetch_all(pool)
         .await?;
 
-        Ok(embeddings)
+        Ok(labels)
     }
 
-    /// Delete embeddings for a document
-    pub async fn delete_by_document(pool: &SqlitePool, document_id: &str) -> CodexResult<()> {
-        sqlx::query(
-            "DELETE FROM embeddings WHERE document_id = ?"
+    /// Add a document to the end of a collection; returns false if it already was a member
+    pub async fn add_to_collection(pool: &SqlitePool, id: &str, collection_id: &str) -> CodexResult<bool> {
+        if !Self::collection_exists(pool, collection_id).await? {
+            return Err(CodexError::not_found(format!("Collection {}", collection_id)));
+        }
+
+        let result = sqlx::query(
+            r#"
+            INSERT OR IGNORE INTO document_collections (document_id, collection_id, order_index)
+            SELECT ?, ?, COALESCE(MAX(order_index) + 1, 0) FROM document_collections WHERE collection_id = ?
+            "#
         )
-        .bind(document_id)
+        .bind(id)
+        .bind(collection_id)
+        .bind(collection_id)
         .execute(pool)
         .await?;
 
-        Ok(())
+        Ok(result.rows_affected() > 0)
     }
 
-    /// Get all embeddings for similarity search
-    pub async fn get_all_vectors(pool: &SqlitePool) -> CodexResult<Vec<(String, Vec<f32>)>> {
-        let rows = query(
-            "SELECT document_id, vector, vector_blob FROM embeddings ORDER BY document_id, chunk_index"
+    /// Set the favorite flag without touching updated_at; returns false if the document does not exist
+    pub async fn set_favorite(pool: &SqlitePool, id: &str, favorite: bool) -> CodexResult<bool> {
+        let favorited_at = favorite.then(|| Utc::now().to_rfc3339());
+
+        let result = sqlx::query(
+            "UPDATE documents SET is_favorite = ?, favorited_at = ?, version = version + 1 WHERE id = ? AND is_deleted = false",
         )
-        .fetch_all(pool)
+        .bind(favorite)
+        .bind(favorited_at)
+        .bind(id)
+        .execute(pool)
         .await?;
 
-        let mut result = Vec::new();
-        for row in rows {
-            let doc_id: String = row.get("document_id");
-            let vector = if let Ok(Some(blob)) = row.try_get::<Option<Vec<u8>>, _>("vector_blob") {
-                match bincode::deserialize::<Vec<f32>>(&blob) {
-                    Ok(v) => v,
-                    Err(_) => continue,
-                }
-            } else if let Ok(json_str) = row.try_get::<String, _>("vector") {
-                match serde_json::from_str::<Vec<f32>>(&json_str) {
-                    Ok(v) => v,
-                    Err(_) => continue,
-                }
-            } else {
-                continue;
-            };
-            result.push((doc_id, vector));
-        }
-
-        Ok(result)
+        Ok(result.rows_affected() > 0)
     }
-    
-    /// Store embedding with both JSON and binary formats
-    pub async fn create_with_binary(pool: &SqlitePool, embedding: &Embedding) -> CodexResult<()> {
-        let vector = embedding.get_vector();
-        let vector_blob = bincode::serialize(&vector)
-            .map_err(|e| CodexError::Database(sqlx::Error::Decode(Box::new(e))))?;
-        
-        sqlx::query(
+
+    /// Get documents most recently opened
+    pub async fn get_recently_accessed(pool: &SqlitePool, limit: i64) -> CodexResult<Vec<Document>> {
+        let documents = sqlx::query_as::<_, Document>(
             r#"
-            INSERT INTO embeddings (
-                id, document_id, vector, vector_blob, dimensions, model, chunk_index,
-                text_chunk, start_position, end_position, created_at
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            SELECT * FROM documents
+            WHERE last_accessed IS NOT NULL AND is_deleted = false AND category IS NOT ? AND is_archived = false
+            ORDER BY last_accessed DESC
+            LIMIT ?
             "#
         )
-        .bind(&embedding.id)
-        .bind(&embedding.document_id)
-        .bind(&embedding.vector)
-        .bind(vector_blob)
-        .bind(embedding.dimensions)
-        .bind(&embedding.model)
-        .bind(embedding.chunk_index)
-        .bind(&embedding.text_chunk)
-        .bind(embedding.start_position)
-        .bind(embedding.end_position)
-        .bind(&embedding.created_at)
-        .execute(pool)
+        .bind(DIAGNOSTICS_CATEGORY)
+        .bind(limit)
+        .fetch_all(pool)
         .await?;
 
-        Ok(())
+        Ok(documents)
     }
-    
-    /// Get cached vectors for fast similarity search
-    pub async fn get_cached_vectors(pool: &SqlitePool, model: &str, limit: Option<i64>) -> CodexResult<Vec<(String, Vec<f32>)>> {
-        let limit = limit.unwrap_or(1000);
-        
-        let rows = sqlx::query(
+
+    /// Get the most viewed documents, optionally only those opened since a timestamp (RFC 3339)
+    pub async fn get_most_viewed(
+        pool: &SqlitePool,
+        limit: i64,
+        since: Option<&str>,
+    ) -> CodexResult<Vec<Document>> {
+        let documents = sqlx::query_as::<_, Document>(
             r#"
-            SELECT document_id, vector_blob, access_count
-            FROM vector_cache
-            WHERE model = ?
-            ORDER BY access_count DESC, last_accessed DESC
+            SELECT * FROM documents
+            WHERE view_count > 0 AND is_deleted = false AND category IS NOT ? AND is_archived = false
+              AND (? IS NULL OR last_accessed >= ?)
+            ORDER BY view_count DESC, last_accessed DESC
             LIMIT ?
             "#
         )
-        .bind(model)
+        .bind(DIAGNOSTICS_CATEGORY)
+        .bind(since)
+        .bind(since)
         .bind(limit)
         .fetch_all(pool)
         .await?;
 
-        let mut result = Vec::new();
-        for row in rows {
-            let document_id: String = row.get("document_id");
-            let vector_blob: Vec<u8> = row.get("vector_blob");
-            let vector = bincode::deserialize::<Vec<f32>>(&vector_blob)
-                .map_err(|e| CodexError::Database(sqlx::Error::Decode(Box::new(e))))?;
-            result.push((document_id, vector));
-        }
-
-        Ok(result)
+        Ok(documents)
     }
-    
-    /// Update vector cache access statistics
-    pub async fn update_cache_access(pool: &SqlitePool, document_id: &str) -> CodexResult<()> {
-        let now = Utc::now();
-        
+
+    /// Live, unarchived documents created at or after `since`, newest first
+    ///
+    /// `with_tag` and `without_tag` are matched case-insensitively before the limit applies.
+    pub async fn get_created_since(
+        pool: &SqlitePool,
+        since: &str,
+        with_tag: Option<&str>,
+        without_tag: Option<&str>,
+        limit: i64,
+    ) -> CodexResult<Vec<Document>> {
+        let mut builder = sqlx::QueryBuilder::<sqlx::Sqlite>::new("SELECT * FROM documents WHERE created_at >= ");
+        builder
+            .push_bind(since)
+            .push(" AND is_deleted = false AND category IS NOT ")
+            .push_bind(DIAGNOSTICS_CATEGORY)
+            .push(" AND is_archived = false");
+        if let Some(tag) = with_tag {
+            builder
+                .push(" AND CASE WHEN json_valid(tags) THEN EXISTS (SELECT 1 FROM json_each(tags) WHERE value = ")
+                .push_bind(tag)
+                .push(" COLLATE NOCASE) ELSE 0 END");
+        }
+        if let Some(tag) = without_tag {
+            builder
+                .push(" AND NOT CASE WHEN json_valid(tags) THEN EXISTS (SELECT 1 FROM json_each(tags) WHERE value = ")
+                .push_bind(tag)
+                .push(" COLLATE NOCASE) ELSE 0 END");
+        }
+        builder.push(" ORDER BY created_at DESC, id DESC LIMIT ").push_bind(limit);
+
+        let documents = builder.build_query_as::<Document>().fetch_all(pool).await?;
+        Ok(documents)
+    }
+
+    /// Set reading time and/or difficulty, leaving fields passed as None unchanged
+    ///
+    /// Derived metadata is not a content change, so updated_at is kept.
+    pub async fn set_derived_metadata(
+        pool: &SqlitePool,
+        id: &str,
+        reading_time: Option<i64>,
+        difficulty_level: Option<i64>,
+    ) -> CodexResult<()> {
         sqlx::query(
             r#"
-            UPDATE vector_cache
-            SET access_count = access_count + 1, last_accessed = ?
-            WHERE document_id = ?
+            UPDATE documents
+            SET reading_time = COALESCE(?, reading_time),
+                difficulty_level = COALESCE(?, difficulty_level),
+                version = version + 1
+            WHERE id = ?
             "#
         )
-        .bind(now.to_rfc3339())
-        .bind(document_id)
+        .bind(reading_time)
+        .bind(difficulty_level)
+        .bind(id)
         .execute(pool)
         .await?;
 
         Ok(())
     }
-    
-    /// Add vector to cache
-    pub async fn cache_vector(
+
+    /// Write the category, tags, archive and favorite flags and custom metadata of several documents in one transaction
+    ///
+    /// Each document must still be at the version it was read at. If any
+    /// has been changed since, nothing is written and the call fails with
+    /// [`CodexError::Conflict`] for the first such document; re-reading the
+    /// selection and applying the changes again is always safe.
+    ///
+    /// These columns do not fire the full-text update trigger, so the
+    /// full-text rows of all written documents are resynced in a single
+    /// statement before committing.
+    pub async fn update_organization(pool: &SqlitePool, documents: &[Document]) -> CodexResult<()> {
+        if documents.is_empty() {
+            return Ok(());
+        }
+
+        let updated_at = Utc::now().to_rfc3339();
+        let mut tx = begin_write(pool).await?;
+
+        for document in documents {
+            let result = sqlx::query(
+                r#"
+                UPDATE documents SET
+                    category = ?, tags = ?, is_archived = ?, is_favorite = ?,
+                    favorited_at = CASE WHEN ? THEN COALESCE(favorited_at, ?) ELSE NULL END,
+                    metadata_json = ?, updated_at = ?, version = version + 1
+                WHERE id = ? AND version = ? AND is_deleted = false
+                "#
+            )
+            .bind(&document.category)
+            .bind(&document.tags)
+            .bind(document.is_archived)
+            .bind(document.is_favorite)
+            .bind(document.is_favorite)
+            .bind(&updated_at)
+            .bind(&document.metadata_json)
+            .bind(&updated_at)
+            .bind(&document.id)
+            .bind(document.version)
+            .execute(&mut *tx)
+            .await?;
+
+            if result.rows_affected() == 0 {
+                tx.rollback().await?;
+                return Err(Self::version_mismatch(pool, &document.id).await?);
+            }
+        }
+
+        let ids: Vec<&str> = documents.iter().map(|d| d.id.as_str()).collect();
+        sqlx::query(
+            r#"
+            UPDATE documents_fts SET
+                category = (SELECT category FROM documents WHERE documents.rowid = documents_fts.rowid),
+                tags = (SELECT tags FROM documents WHERE documents.rowid = documents_fts.rowid)
+            WHERE rowid IN (SELECT rowid FROM documents WHERE id IN (SELECT value FROM json_each(?)))
+            "#
+        )
+        .bind(serde_json::to_string(&ids)?)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Bookmarks, notes, incoming links and collection memberships of
+    /// `secondary_id` that a merge into `primary_id` would move
+    pub async fn find_merge_references(
+        conn: &mut SqliteConnection,
+        primary_id: &str,
+        secondary_id: &str,
+    ) -> CodexResult<MergedReferences> {
+        let ids = |rows: Vec<SqliteRow>| rows.iter().map(|r| r.get::<String, _>(0)).collect();
+
+        let bookmarks = sqlx::query("SELECT id FROM bookmarks WHERE document_id = ?")
+            .bind(secondary_id)
+            .fetch_all(&mut *conn)
+            .await?;
+        let notes = sqlx::query("SELECT id FROM notes WHERE document_id = ?")
+            .bind(secondary_id)
+            .fetch_all(&mut *conn)
+            .await?;
+        let links = sqlx::query("SELECT id FROM document_links WHERE target_id = ? AND source_id != ?")
+            .bind(secondary_id)
+            .bind(primary_id)
+            .fetch_all(&mut *conn)
+            .await?;
+        let collections = sqlx::query(
+            r#"
+            SELECT collection_id FROM document_collections
+            WHERE document_id = ?
+              AND collection_id NOT IN (SELECT collection_id FROM document_collections WHERE document_id = ?)
+            "#
+        )
+        .bind(secondary_id)
+        .bind(primary_id)
+        .fetch_all(&mut *conn)
+        .await?;
+
+        Ok(MergedReferences {
+            bookmarks: ids(bookmarks),
+            notes: ids(notes),
+            links: ids(links),
+            collections: ids(collections),
+        })
+    }
+
+    /// Write a merged document and absorb `secondary_id` into it in one transaction
+    ///
+    /// The primary must still be at `merged.version`, as with [`Self::update`].
+    ///
+    /// Moves the secondary's references to the primary and soft-deletes the
+    /// secondary. Returns the moved references.
+    pub async fn merge(
         pool: &SqlitePool,
-        document_id: &str,
-        vector: &[f32],
-        model: &str,
+        merged: &Document,
+        secondary_id: &str,
+        compression: CompressionPolicy,
+    ) -> CodexResult<MergedReferences> {
+        let updated_at = Utc::now().to_rfc3339();
+        let body = StoredBody::new(&merged.content, compression);
+        let mut tx = begin_write(pool).await?;
+
+        let references = Self::find_merge_references(&mut tx, &merged.id, secondary_id).await?;
+
+        let result = sqlx::query(
+            r#"
+            UPDATE documents SET
+                content = ?, summary = ?, category = ?, tags = ?, is_favorite = ?,
+                favorited_at = CASE WHEN ? THEN COALESCE(favorited_at, ?) ELSE NULL END,
+                updated_at = ?,
+                content_compressed = ?, compression = ?, content_size = ?,
+                version = version + 1
+            WHERE id = ? AND version = ? AND is_deleted = false
+            "#
+        )
+        .bind(body.content)
+        .bind(&merged.summary)
+        .bind(&merged.category)
+        .bind(&merged.tags)
+        .bind(merged.is_favorite)
+        .bind(merged.is_favorite)
+        .bind(&updated_at)
+        .bind(&updated_at)
+        .bind(&body.compressed)
+        .bind(body.compression)
+        .bind(body.content_size)
+        .bind(&merged.id)
+        .bind(merged.version)
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            tx.rollback().await?;
+            return Err(Self::version_mismatch(pool, &merged.id).await?);
+        }
+
+        body.index(&mut tx, &merged.id).await?;
+        Self::move_references(&mut tx, &references, secondary_id, &merged.id).await?;
+
+        sqlx::query("UPDATE documents SET is_deleted = true, updated_at = ?, version = version + 1 WHERE id = ?")
+            .bind(&updated_at)
+            .bind(secondary_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(references)
+    }
+
+    /// Undo [`Self::merge`]: move the references back and restore the secondary
+    ///
+    /// The primary's own fields are restored separately with [`Self::update`].
+    pub async fn unmerge(
+        pool: &SqlitePool,
+        primary_id: &str,
+        secondary_id: &str,
+        references: &MergedReferences,
     ) -> CodexResult<()> {
-        let vector_blob = bincode::serialize(vector)
-            .map_err(|e| CodexError::Database(sqlx::Error::Decode(Box::new(e))))?;
-        
+        let mut tx = pool.begin().await?;
+
+        Self::move_references(&mut tx, references, primary_id, secondary_id).await?;
+
+        sqlx::query("UPDATE documents SET is_deleted = false, updated_at = ?, version = version + 1 WHERE id = ?")
+            .bind(Utc::now().to_rfc3339())
+            .bind(secondary_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Re-point the listed references from `from_id` to `to_id`
+    ///
+    /// Collection memberships are copied to `to_id` and removed from
+    /// `from_id`, since a merge only adds memberships the primary lacked.
+    async fn move_references(
+        conn: &mut SqliteConnection,
+        references: &MergedReferences,
+        from_id: &str,
+        to_id: &str,
+    ) -> CodexResult<()> {
+        sqlx::query("UPDATE bookmarks SET document_id = ? WHERE document_id = ? AND id IN (SELECT value FROM json_each(?))")
+            .bind(to_id)
+            .bind(from_id)
+            .bind(serde_json::to_string(&references.bookmarks)?)
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query("UPDATE notes SET document_id = ? WHERE document_id = ? AND id IN (SELECT value FROM json_each(?))")
+            .bind(to_id)
+            .bind(from_id)
+            .bind(serde_json::to_string(&references.notes)?)
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query("UPDATE document_links SET target_id = ? WHERE target_id = ? AND id IN (SELECT value FROM json_each(?))")
+            .bind(to_id)
+            .bind(from_id)
+            .bind(serde_json::to_string(&references.links)?)
+            .execute(&mut *conn)
+            .await?;
+
+        let collections = serde_json::to_string(&references.collections)?;
         sqlx::query(
             r#"
-            INSERT OR REPLACE INTO vector_cache (
-                id, document_id, vector_blob, dimensions, model, access_count, last_accessed, created_at
-            ) VALUES (?, ?, ?, ?, ?, 1, ?, ?)
+            INSERT OR IGNORE INTO document_collections (document_id, collection_id, order_index)
+            SELECT ?, collection_id, order_index FROM document_collections
+            WHERE document_id = ? AND collection_id IN (SELECT value FROM json_each(?))
             "#
         )
-        .bind(uuid::Uuid::new_v4().to_string())
-        .bind(document_id)
-        .bind(vector_blob)
-        .bind(vector.len() as i64)
-        .bind(model)
-        .bind(Utc::now().to_rfc3339())
-        .bind(Utc::now().to_rfc3339())
-        .execute(pool)
+        .bind(to_id)
+        .bind(from_id)
+        .bind(&collections)
+        .execute(&mut *conn)
         .await?;
+        sqlx::query("DELETE FROM document_collections WHERE document_id = ? AND collection_id IN (SELECT value FROM json_each(?))")
+            .bind(from_id)
+            .bind(&collections)
+            .execute(&mut *conn)
+            .await?;
 
         Ok(())
     }
-    
-    /// Clean up old cache entries
-    pub async fn cleanup_cache(pool: &SqlitePool, max_entries: i64) -> CodexResult<()> {
+
+    /// Update view count and last accessed
+    pub async fn update_access(pool: &SqlitePool, id: &str) -> CodexResult<()> {
+        let now = Utc::now();
+        
         sqlx::query(
             r#"
-            DELETE FROM vector_cache
-            WHERE id NOT IN (
-                SELECT id FROM vector_cache
-                ORDER BY access_count DESC, last_accessed DESC
-                LIMIT ?
-            )
+            UPDATE documents
+            SET view_count = view_count + 1, last_accessed = ?
+            WHERE id = ?
             "#
         )
-        .bind(max_entries)
+        .bind(now.to_rfc3339())
+        .bind(id)
         .execute(pool)
         .await?;
 
@@ -509,406 +1025,4099 @@ impl EmbeddingQueries {
     }
 }
 
-/// Settings query operations
-pub struct SettingQueries;
+/// Setting that turns the vector cache on or off
+pub const VECTOR_CACHE_ENABLED_SETTING: &str = "enable_vector_cache";
+/// Setting holding the most chunk vectors kept in the cache
+pub const VECTOR_CACHE_SIZE_SETTING: &str = "vector_cache_size";
+/// Cache size used when the setting is missing
+pub const DEFAULT_VECTOR_CACHE_SIZE: i64 = 1000;
+/// Setting holding when the vector cache was last compacted (RFC 3339)
+pub const VECTOR_CACHE_COMPACTED_SETTING: &str = "vector_cache.last_compaction";
 
-impl SettingQueries {
-    /// Get setting by key
-    pub async fn get(pool: &SqlitePool, key: &str) -> CodexResult<Option<Setting>> {
-        let setting = sqlx::query_as::<_, Setting>(
-            "SELECT * FROM settings WHERE key = ?"
+/// Cache entries whose chunk is still embedded, with the same model, for a
+/// live, non-archived document
+const LIVE_CACHE_ENTRY: &str = r#"
+    EXISTS (
+        SELECT 1 FROM embeddings e
+        JOIN documents d ON d.id = e.document_id
+        WHERE e.document_id = c.document_id AND e.chunk_index = c.chunk_index AND e.model = c.model
+          AND d.is_deleted = 0 AND d.is_archived = 0
+    )
+"#;
+
+/// Embedding query operations
+pub struct EmbeddingQueries;
+
+/// Embedding columns, with the vector of shared chunks read from `vectors`
+const EMBEDDING_SELECT: &str = r#"
+    SELECT e.id, e.document_id, COALESCE(v.vector, e.vector) AS vector, e.dimensions, e.model,
+           e.chunk_index, e.text_chunk, e.start_position, e.end_position, e.created_at
+    FROM embeddings e LEFT JOIN vectors v ON v.id = e.vector_id
+"#;
+
+impl EmbeddingQueries {
+    /// Create a new embedding
+    pub async fn create(pool: &SqlitePool, embedding: &Embedding) -> CodexResult<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO embeddings (
+                id, document_id, vector, dimensions, model, chunk_index,
+                text_chunk, start_position, end_position, created_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#
         )
-        .bind(key)
-        .fetch_optional(pool)
+        .bind(&embedding.id)
+        .bind(&embedding.document_id)
+        .bind(&embedding.vector)
+        .bind(embedding.dimensions)
+        .bind(&embedding.model)
+        .bind(embedding.chunk_index)
+        .bind(&embedding.text_chunk)
+        .bind(embedding.start_position)
+        .bind(embedding.end_position)
+        .bind(embedding.created_at)
+        .execute(pool)
         .await?;
 
-        Ok(setting)
+        Ok(())
     }
 
-    /// Set setting value
-    pub async fn set(pool: &SqlitePool, setting: &Setting) -> CodexResult<()> {
-        sqlx::query!(
+    /// Get embeddings for a document
+    pub async fn get_by_document(
+        pool: &SqlitePool,
+        document_id: &str,
+    ) -> CodexResult<Vec<Embedding>> {
+        let embeddings = sqlx::query_as::<_, Embedding>(&format!(
+            "{} WHERE e.document_id = ? ORDER BY e.chunk_index",
+            EMBEDDING_SELECT
+        ))
+        .bind(document_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(embeddings)
+    }
+
+    /// Chunks of a document that overlap a byte range, in document order
+    pub async fn get_chunks_in_range(
+        pool: &SqlitePool,
+        document_id: &str,
+        start: i64,
+        end: i64,
+    ) -> CodexResult<Vec<Embedding>> {
+        let embeddings = sqlx::query_as::<_, Embedding>(&format!(
             r#"
-            INSERT OR REPLACE INTO settings (
-                key, value, description, category, is_user_configurable, created_at, updated_at
-            ) VALUES (?, ?, ?, ?, ?, ?, ?)
+            {}
+            WHERE e.document_id = ? AND e.end_position >= ? AND e.start_position <= ?
+            ORDER BY e.chunk_index
             "#,
-            setting.key,
-            setting.value,
-            setting.description,
-            setting.category,
-            setting.is_user_configurable,
-            setting.created_at,
-            setting.updated_at
+            EMBEDDING_SELECT
+        ))
+        .bind(document_id)
+        .bind(start)
+        .bind(end)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(embeddings)
+    }
+
+    /// Delete embeddings for a document, and their cached vectors
+    pub async fn delete_by_document(pool: &SqlitePool, document_id: &str) -> CodexResult<()> {
+        sqlx::query(
+            "DELETE FROM embeddings WHERE document_id = ?"
         )
+        .bind(document_id)
         .execute(pool)
         .await?;
 
-        Ok(())
+        Self::evict_cached(pool, document_id).await
     }
 
-    /// Get all settings by category
-    pub async fn get_by_category(pool: &SqlitePool, category: &str) -> CodexResult<Vec<Setting>> {
-        let settings = sqlx::query_as!(
-            Setting,
-            "SELECT * FROM settings WHERE category = ? ORDER BY key",
-            category
+    /// Index coverage of non-deleted documents
+    ///
+    /// Returns the number of documents, documents without embeddings,
+    /// documents updated after they were embedded, and chunks embedded by
+    /// another model than `model` or in other dimensions than `dimensions`.
+    pub async fn index_health_counts(pool: &SqlitePool, model: &str, dimensions: i64) -> CodexResult<(i64, i64, i64, i64)> {
+        let (documents, missing, stale): (i64, i64, i64) = sqlx::query_as(
+            r#"
+            SELECT
+                COUNT(*),
+                COALESCE(SUM(e.document_id IS NULL), 0),
+                COALESCE(SUM(e.document_id IS NOT NULL AND julianday(d.updated_at) > julianday(e.indexed_at)), 0)
+            FROM documents d
+            LEFT JOIN (
+                SELECT document_id, MIN(created_at) AS indexed_at FROM embeddings GROUP BY document_id
+            ) e ON e.document_id = d.id
+            WHERE d.is_deleted = 0 AND d.category IS NOT ?
+            "#
         )
-        .fetch_all(pool)
+        .bind(DIAGNOSTICS_CATEGORY)
+        .fetch_one(pool)
         .await?;
 
-        Ok(settings)
+        let (mismatched,): (i64,) = sqlx::query_as(
+            r#"
+            SELECT COUNT(*) FROM embeddings e
+            JOIN documents d ON d.id = e.document_id
+            WHERE d.is_deleted = 0 AND d.category IS NOT ? AND (e.dimensions != ? OR e.model != ?)
+            "#
+        )
+        .bind(DIAGNOSTICS_CATEGORY)
+        .bind(dimensions)
+        .bind(model)
+        .fetch_one(pool)
+        .await?;
+
+        Ok((documents, missing, stale, mismatched))
     }
 
-    /// Delete setting
-    pub async fn delete(pool: &SqlitePool, key: &str) -> CodexResult<()> {
-        sqlx::query!("DELETE FROM settings WHERE key = ?", key)
-            .execute(pool)
-            .await?;
+    /// Ids of non-deleted documents that are unembedded, embedded before
+    /// their last update, or have chunks embedded by another model than
+    /// `model` or in other dimensions than `dimensions`
+    pub async fn get_unhealthy_document_ids(pool: &SqlitePool, model: &str, dimensions: i64) -> CodexResult<Vec<String>> {
+        let ids: Vec<(String,)> = sqlx::query_as(
+            r#"
+            SELECT d.id FROM documents d
+            LEFT JOIN (
+                SELECT document_id, MIN(created_at) AS indexed_at, MAX(dimensions != ? OR model != ?) AS mismatched
+                FROM embeddings GROUP BY document_id
+            ) e ON e.document_id = d.id
+            WHERE d.is_deleted = 0 AND d.category IS NOT ?
+              AND (e.document_id IS NULL OR julianday(d.updated_at) > julianday(e.indexed_at) OR e.mismatched)
+            ORDER BY d.updated_at DESC, d.id DESC
+            "#
+        )
+        .bind(dimensions)
+        .bind(model)
+        .bind(DIAGNOSTICS_CATEGORY)
+        .fetch_all(pool)
+        .await?;
 
-        Ok(())
+        Ok(ids.into_iter().map(|(id,)| id).collect())
     }
-}
-
-/// Search query operations - unified search interface
-pub struct SearchQueries;
 
-impl SearchQueries {
-    /// Simple search interface for FTS5 full-text search
-    pub async fn search(
-        pool: &SqlitePool,
-        query: &str,
-        limit: Option<i64>,
-    ) -> CodexResult<Vec<Document>> {
-        let limit = limit.unwrap_or(50);
-        
-        // Sanitize query for FTS5
-        let sanitized_query = Self::sanitize_fts_query(query);
-        
-        let start = std::time::Instant::now();
-        
-        let documents = sqlx::query_as::<_, Document>(
+    /// Get all embeddings for similarity search, as (document_id, chunk_index, vector)
+    pub async fn get_all_vectors(pool: &SqlitePool) -> CodexResult<Vec<(String, i64, Vec<f32>)>> {
+        let rows = query(
             r#"
-            SELECT d.* FROM documents d
-            JOIN documents_fts fts ON d.rowid = fts.rowid
-            WHERE fts MATCH ? AND d.is_deleted = false
-            ORDER BY rank
-            LIMIT ?
+            SELECT e.document_id, e.chunk_index,
+                   COALESCE(v.vector, e.vector) AS vector, COALESCE(v.vector_blob, e.vector_blob) AS vector_blob
+            FROM embeddings e LEFT JOIN vectors v ON v.id = e.vector_id
+            ORDER BY e.document_id, e.chunk_index
             "#
         )
-        .bind(sanitized_query)
-        .bind(limit)
         .fetch_all(pool)
         .await?;
-        
-        let duration = start.elapsed();
-        tracing::debug!(
-            "FTS5 search completed in {:?}ms for query: '{}' (found {} results)",
-            duration.as_millis(),
-            query,
-            documents.len()
-        );
-        
-        Ok(documents)
+
+        Ok(rows
+            .iter()
+            .filter_map(|row| Some((row.get("document_id"), row.get("chunk_index"), Self::decode_vector(row)?)))
+            .collect())
+    }
+
+    /// Text of one chunk of a document
+    pub async fn get_chunk_text(pool: &SqlitePool, document_id: &str, chunk_index: i64) -> CodexResult<Option<String>> {
+        let row = sqlx::query("SELECT text_chunk FROM embeddings WHERE document_id = ? AND chunk_index = ?")
+            .bind(document_id)
+            .bind(chunk_index)
+            .fetch_optional(pool)
+            .await?;
+
+        Ok(row.map(|r| r.get("text_chunk")))
     }
     
-    /// Enhanced search with ranking and highlighting
-    pub async fn search_with_ranking(
-        pool: &SqlitePool,
-        query: &str,
-        limit: Option<i64>,
-        offset: Option<i64>,
-    ) -> CodexResult<Vec<(Document, f64)>> {
-        let limit = limit.unwrap_or(50);
-        let offset = offset.unwrap_or(0);
-        
-        let sanitized_query = Self::sanitize_fts_query(query);
-        
-        let start = std::time::Instant::now();
-        
-        let rows = sqlx::query(
+    /// Get embeddings of live, non-archived documents for retrieval
+    pub async fn get_active_vectors(pool: &SqlitePool) -> CodexResult<Vec<(String, Vec<f32>)>> {
+        let rows = query(
             r#"
-            SELECT d.*, 
-                   bm25(documents_fts, 10.0, 5.0, 1.0, 1.0, 3.0, 2.0) as rank_score,
-                   snippet(documents_fts, 1, '<mark>', '</mark>', '...', 32) as snippet
-            FROM documents d
-            JOIN documents_fts fts ON d.rowid = fts.rowid
-            WHERE fts MATCH ? AND d.is_deleted = false
-            ORDER BY rank_score DESC
-            LIMIT ? OFFSET ?
+            SELECT e.document_id,
+                   COALESCE(v.vector, e.vector) AS vector, COALESCE(v.vector_blob, e.vector_blob) AS vector_blob
+            FROM embeddings e
+            JOIN documents d ON d.id = e.document_id
+            LEFT JOIN vectors v ON v.id = e.vector_id
+            WHERE d.is_deleted = false AND d.category IS NOT ? AND d.is_archived = false
+            ORDER BY e.document_id, e.chunk_index
             "#
         )
-        .bind(sanitized_query)
-        .bind(limit)
-        .bind(offset)
+        .bind(DIAGNOSTICS_CATEGORY)
         .fetch_all(pool)
         .await?;
-        
-        let duration = start.elapsed();
-        tracing::debug!(
-            "Ranked FTS5 search completed in {:?}ms for query: '{}' (found {} results)",
-            duration.as_millis(),
-            query,
-            rows.len()
-        );
-        
-        let mut results = Vec::new();
-        for row in rows {
-            let document = Document {
-                id: row.get("id"),
-                title: row.get("title"),
-                content: row.get("content"),
-                summary: row.get("summary"),
-                author: row.get("author"),
-                source: row.get("source"),
-                url: row.get("url"),
-                content_type: row.get("content_type"),
-                category: row.get("category"),
-                tags: row.get("tags"),
-                language: row.get("language"),
-                reading_time: row.get("reading_time"),
-                difficulty_level: row.get("difficulty_level"),
-                file_size: row.get("file_size"),
-                file_hash: row.get("file_hash"),
-                created_at: row.get("created_at"),
-                updated_at: row.get("updated_at"),
-                last_accessed: row.get("last_accessed"),
-                view_count: row.get("view_count"),
-                is_favorite: row.get("is_favorite"),
-                is_archived: row.get("is_archived"),
-                is_deleted: row.get("is_deleted"),
-            };
-            
-            let score: Option<f64> = row.get("rank_score");
-            results.push((document, score.unwrap_or(0.0)));
-        }
-        
-        Ok(results)
+
+        Ok(Self::decode_vector_rows(rows))
     }
-    
-    /// Semantic search using vector embeddings
-    pub async fn search_semantic(
-        pool: &SqlitePool,
-        query_vector: &[f32],
-        limit: Option<i64>,
-        similarity_threshold: Option<f32>,
-    ) -> CodexResult<Vec<(Document, f32)>> {
-        let limit = limit.unwrap_or(10);
-        let threshold = similarity_threshold.unwrap_or(0.5);
-        
-        let start = std::time::Instant::now();
-        
-        // Get all embeddings and compute similarity in-memory
-        // Note: For production, consider using a proper vector database
-        let embeddings = EmbeddingQueries::get_all_vectors(pool).await?;
-        
-        let mut similarities = Vec::new();
-        for (doc_id, embedding) in embeddings {
-            let similarity = Self::cosine_similarity(query_vector, &embedding);
-            if similarity >= threshold {
-                similarities.push((doc_id, similarity));
-            }
-        }
-        
-        // Sort by similarity and limit results
-        similarities.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
-        similarities.truncate(limit as usize);
-        
-        // Fetch documents for top results
-        let mut results = Vec::new();
-        for (doc_id, similarity) in similarities {
-            if let Some(document) = DocumentQueries::get_by_id(pool, &doc_id).await? {
-                results.push((document, similarity));
-            }
-        }
-        
-        let duration = start.elapsed();
-        tracing::debug!(
-            "Semantic search completed in {:?}ms (found {} results)",
-            duration.as_millis(),
-            results.len()
-        );
-        
-        Ok(results)
+
+    /// Get embeddings of live documents, archived ones included
+    pub async fn get_live_vectors(pool: &SqlitePool) -> CodexResult<Vec<(String, Vec<f32>)>> {
+        let rows = query(
+            r#"
+            SELECT e.document_id,
+                   COALESCE(v.vector, e.vector) AS vector, COALESCE(v.vector_blob, e.vector_blob) AS vector_blob
+            FROM embeddings e
+            JOIN documents d ON d.id = e.document_id
+            LEFT JOIN vectors v ON v.id = e.vector_id
+            WHERE d.is_deleted = false AND d.category IS NOT ?
+            ORDER BY e.document_id, e.chunk_index
+            "#
+        )
+        .bind(DIAGNOSTICS_CATEGORY)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(Self::decode_vector_rows(rows))
     }
-    
-    /// Hybrid search combining full-text and semantic search
-    pub async fn search_hybrid(
-        pool: &SqlitePool,
-        query: &str,
-        query_vector: Option<&[f32]>,
-        limit: Option<i64>,
-        text_weight: Option<f32>,
-        semantic_weight: Option<f32>,
-    ) -> CodexResult<Vec<(Document, f64)>> {
-        let limit = limit.unwrap_or(20);
-        let text_weight = text_weight.unwrap_or(0.7) as f64;
-        let semantic_weight = semantic_weight.unwrap_or(0.3) as f64;
-        
-        let start = std::time::Instant::now();
-        
-        // Get full-text search results
-        let text_results = Self::search_with_ranking(pool, query, Some(limit * 2), None).await?;
-        
-        // Get semantic search results if query vector is provided
-        let semantic_results = if let Some(vector) = query_vector {
-            Self::search_semantic(pool, vector, Some(limit), Some(0.3)).await?
-        } else {
-            Vec::new()
-        };
-        
-        // Combine and re-rank results
-        let mut combined_scores = std::collections::HashMap::new();
-        let mut all_documents = std::collections::HashMap::new();
-        
-        // Add text search scores
-        for (doc, score) in text_results {
-            let normalized_score = Self::normalize_score(score, 0.0, 10.0);
-            combined_scores.insert(doc.id.clone(), normalized_score * text_weight);
-            all_documents.insert(doc.id.clone(), doc);
-        }
-        
-        // Add semantic search scores
-        for (doc, score) in semantic_results {
-            let normalized_score = Self::normalize_score(score as f64, 0.0, 1.0);
-            let existing_score = combined_scores.get(&doc.id).unwrap_or(&0.0);
-            combined_scores.insert(doc.id.clone(), existing_score + (normalized_score * semantic_weight));
-            all_documents.insert(doc.id.clone(), doc);
-        }
-        
-        // Sort by combined score
-        let mut final_results: Vec<_> = combined_scores.into_iter()
-            .filter_map(|(doc_id, score)| {
-                all_documents.get(&doc_id).map(|doc| (doc.clone(), score))
-            })
-            .collect();
-        
-        final_results.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
-        final_results.truncate(limit as usize);
-        
-        let duration = start.elapsed();
-        tracing::debug!(
-            "Hybrid search completed in {:?}ms (found {} results)",
-            duration.as_millis(),
-            final_results.len()
-        );
-        
-        Ok(final_results)
+
+    /// Get embeddings of the given documents
+    pub async fn get_document_vectors(pool: &SqlitePool, document_ids: &[&str]) -> CodexResult<Vec<(String, Vec<f32>)>> {
+        let rows = query(
+            r#"
+            SELECT e.document_id,
+                   COALESCE(v.vector, e.vector) AS vector, COALESCE(v.vector_blob, e.vector_blob) AS vector_blob
+            FROM embeddings e
+            LEFT JOIN vectors v ON v.id = e.vector_id
+            WHERE e.document_id IN (SELECT value FROM json_each(?))
+            ORDER BY e.document_id, e.chunk_index
+            "#
+        )
+        .bind(serde_json::to_string(document_ids)?)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(Self::decode_vector_rows(rows))
     }
-    
-    /// Sanitize FTS5 query to prevent syntax errors
-    fn sanitize_fts_query(query: &str) -> String {
-        // Remove special FTS5 characters that might cause syntax errors
-        let cleaned = query
-            .chars()
-            .filter(|c| c.is_alphanumeric() || c.is_whitespace() || "-_".contains(*c))
-            .collect::<String>();
-        
-        // Split into words and join with OR for better matching
-        let words: Vec<&str> = cleaned.split_whitespace().collect();
-        if words.is_empty() {
-            return "".to_string();
-        }
-        
-        // Create a phrase query for exact matches, fallback to OR for partial matches
-        if words.len() == 1 {
-            format!("\"{}\" OR {}*", words[0], words[0])
+
+    /// Decode (document_id, vector) rows, preferring the binary column
+    fn decode_vector_rows(rows: Vec<sqlx::sqlite::SqliteRow>) -> Vec<(String, Vec<f32>)> {
+        rows.iter()
+            .filter_map(|row| Some((row.get("document_id"), Self::decode_vector(row)?)))
+            .collect()
+    }
+
+    /// Vector of an embedding row, preferring the binary column
+    fn decode_vector(row: &sqlx::sqlite::SqliteRow) -> Option<Vec<f32>> {
+        if let Ok(Some(blob)) = row.try_get::<Option<Vec<u8>>, _>("vector_blob") {
+            bincode::deserialize::<Vec<f32>>(&blob).ok()
+        } else if let Ok(json_str) = row.try_get::<String, _>("vector") {
+            serde_json::from_str::<Vec<f32>>(&json_str).ok()
         } else {
-            let phrase = format!("\"{}\"", words.join(" "));
-            let or_terms = words.iter().map(|w| format!("{}*", w)).collect::<Vec<_>>().join(" OR ");
-            format!("{} OR {}", phrase, or_terms)
+            None
         }
     }
-    
-    /// Compute cosine similarity between two vectors
-    fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
-        if a.len() != b.len() {
-            return 0.0;
-        }
-        
-        let dot_product: f32 = a.iter().zip(b.iter()).map(|(x, y)| x * y).sum();
-        let magnitude_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
-        let magnitude_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
-        
-        if magnitude_a == 0.0 || magnitude_b == 0.0 {
-            return 0.0;
-        }
-        
-        dot_product / (magnitude_a * magnitude_b)
+
+    /// Store embedding with both JSON and binary formats
+    ///
+    /// The vector is kept once per chunk text and model in `vectors`; see
+    /// [`create_all_with_binary`](Self::create_all_with_binary).
+    pub async fn create_with_binary(pool: &SqlitePool, embedding: &Embedding) -> CodexResult<()> {
+        Self::create_all_with_binary(pool, std::slice::from_ref(embedding)).await
     }
-    
-    /// Normalize score to 0-1 range
-    fn normalize_score(score: f64, min_val: f64, max_val: f64) -> f64 {
-        if max_val == min_val {
-            return 0.0;
+
+    /// Store a document's embeddings in one transaction, so either all chunks are saved or none
+    ///
+    /// Each row points at the shared vector of its chunk text and model,
+    /// which is stored with the first chunk to use it. Triggers keep the
+    /// vector's `refcount` and drop it with its last referrer.
+    pub async fn create_all_with_binary(pool: &SqlitePool, embeddings: &[Embedding]) -> CodexResult<()> {
+        let mut tx = pool.begin().await?;
+        Self::insert_all(&mut tx, embeddings).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Replace a document's embeddings, and drop its cached vectors, in one transaction
+    pub async fn replace_for_document(pool: &SqlitePool, document_id: &str, embeddings: &[Embedding]) -> CodexResult<()> {
+        let mut tx = begin_write(pool).await?;
+        sqlx::query("DELETE FROM embeddings WHERE document_id = ?")
+            .bind(document_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM vector_cache WHERE document_id = ?")
+            .bind(document_id)
+            .execute(&mut *tx)
+            .await?;
+        Self::insert_all(&mut tx, embeddings).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Insert embeddings within the caller's transaction, see [`Self::create_all_with_binary`]
+    pub(crate) async fn insert_all(conn: &mut SqliteConnection, embeddings: &[Embedding]) -> CodexResult<()> {
+        for embedding in embeddings {
+            Self::insert_shared(conn, embedding).await?;
         }
-        (score - min_val) / (max_val - min_val)
+        Ok(())
     }
-}
 
-/// Bookmark query operations
-pub struct BookmarkQueries;
+    /// Insert an embedding row referring to the shared vector of its chunk
+    async fn insert_shared(conn: &mut SqliteConnection, embedding: &Embedding) -> CodexResult<()> {
+        let vector = embedding.get_vector();
+        let vector_blob = bincode::serialize(&vector)
+            .map_err(|e| CodexError::Database(sqlx::Error::Decode(Box::new(e))))?;
+        let hash = chunk_hash(&embedding.text_chunk);
 
-impl BookmarkQueries {
-    /// Create a new bookmark
-    pub async fn create(pool: &SqlitePool, bookmark: &Bookmark) -> CodexResult<()> {
-        sqlx::query!(
+        sqlx::query(
             r#"
-            INSERT INTO bookmarks (
-                id, document_id, title, notes, position, selected_text, tags, created_at, updated_at
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
-            "#,
-            bookmark.id,
-            bookmark.document_id,
-            bookmark.title,
-            bookmark.notes,
-            bookmark.position,
-            bookmark.selected_text,
-            bookmark.tags,
-            bookmark.created_at,
-            bookmark.updated_at
+            INSERT INTO vectors (chunk_hash, model, dimensions, vector, vector_blob)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT(chunk_hash, model) DO NOTHING
+            "#
         )
-        .execute(pool)
+        .bind(&hash)
+        .bind(&embedding.model)
+        .bind(embedding.dimensions)
+        .bind(&embedding.vector)
+        .bind(vector_blob)
+        .execute(&mut *conn)
+        .await?;
+
+        let vector_id: i64 = sqlx::query_scalar("SELECT id FROM vectors WHERE chunk_hash = ? AND model = ?")
+            .bind(&hash)
+            .bind(&embedding.model)
+            .fetch_one(&mut *conn)
+            .await?;
+
+        // The vector itself lives in `vectors`, so the inline columns stay empty
+        sqlx::query(
+            r#"
+            INSERT INTO embeddings (
+                id, document_id, vector, dimensions, model, chunk_index,
+                text_chunk, start_position, end_position, created_at, chunk_hash, vector_id
+            ) VALUES (?, ?, '', ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(&embedding.id)
+        .bind(&embedding.document_id)
+        .bind(embedding.dimensions)
+        .bind(&embedding.model)
+        .bind(embedding.chunk_index)
+        .bind(&embedding.text_chunk)
+        .bind(embedding.start_position)
+        .bind(embedding.end_position)
+        .bind(embedding.created_at)
+        .bind(&hash)
+        .bind(vector_id)
+        .execute(&mut *conn)
         .await?;
 
         Ok(())
     }
 
-    /// Get bookmarks for a document
-    pub async fn get_by_document(
-        pool: &SqlitePool,
-        document_id: &str,
-    ) -> CodexResult<Vec<Bookmark>> {
-        let bookmarks = sqlx::query_as!(
-            Bookmark,
-            "SELECT * FROM bookmarks WHERE document_id = ? ORDER BY position",
-            document_id
+    /// Stored vector of a chunk text embedded by `model`, if any chunk has one
+    pub async fn get_shared_vector(pool: &SqlitePool, chunk_hash: &str, model: &str) -> CodexResult<Option<Vec<f32>>> {
+        let blob: Option<Vec<u8>> = sqlx::query_scalar("SELECT vector_blob FROM vectors WHERE chunk_hash = ? AND model = ?")
+            .bind(chunk_hash)
+            .bind(model)
+            .fetch_optional(pool)
+            .await?;
+
+        Ok(blob.and_then(|blob| bincode::deserialize(&blob).ok()))
+    }
+
+    /// Shared vector storage as (vectors, bytes not stored thanks to sharing)
+    pub async fn shared_vector_counts(pool: &SqlitePool) -> CodexResult<(i64, i64)> {
+        let counts: (i64, i64) = sqlx::query_as(
+            r#"
+            SELECT COUNT(*), COALESCE(SUM((refcount - 1) * (length(vector) + length(vector_blob))), 0)
+            FROM vectors WHERE refcount > 0
+            "#
         )
-        .fetch_all(pool)
+        .fetch_one(pool)
         .await?;
 
-        Ok(bookmarks)
+        Ok(counts)
     }
-
-    /// Get all bookmarks
-    pub async fn get_all(pool: &SqlitePool, limit: i64) -> CodexResult<Vec<Bookmark>> {
-        let bookmarks = sqlx::query_as!(
-            Bookmark,
-            "SELECT * FROM bookmarks ORDER BY created_at DESC LIMIT ?",
-            limit
+    
+    /// Vector cache size from the `enable_vector_cache` and `vector_cache_size`
+    /// settings, or `None` when the cache is disabled
+    pub async fn vector_cache_limit(pool: &SqlitePool) -> CodexResult<Option<i64>> {
+        let enabled = SettingQueries::get(pool, VECTOR_CACHE_ENABLED_SETTING)
+            .await?
+            .and_then(|setting| setting.get_value::<bool>())
+            .unwrap_or(true);
+        if !enabled {
+            return Ok(None);
+        }
+
+        let size = SettingQueries::get(pool, VECTOR_CACHE_SIZE_SETTING)
+            .await?
+            .and_then(|setting| setting.get_value::<i64>())
+            .unwrap_or(DEFAULT_VECTOR_CACHE_SIZE);
+        Ok(Some(size.max(0)))
+    }
+
+    /// Read the vectors semantic search scans without keeping them, returning how many were read
+    ///
+    /// Rows are streamed one at a time, so this brings the vector pages into
+    /// SQLite's page cache without holding the vectors in memory. Reads the
+    /// vector cache when `from_cache` is set, the embeddings otherwise.
+    pub async fn touch_vectors(pool: &SqlitePool, from_cache: bool) -> CodexResult<usize> {
+        use futures::TryStreamExt;
+
+        let sql = if from_cache {
+            "SELECT vector_blob FROM vector_cache"
+        } else {
+            r#"
+            SELECT COALESCE(v.vector_blob, e.vector_blob, v.vector, e.vector) AS vector
+            FROM embeddings e LEFT JOIN vectors v ON v.id = e.vector_id
+            "#
+        };
+
+        let mut rows = sqlx::query(sql).fetch(pool);
+        let mut read = 0;
+        while rows.try_next().await?.is_some() {
+            read += 1;
+        }
+
+        Ok(read)
+    }
+
+    /// All cached chunk vectors as (document_id, chunk_index, vector)
+    pub async fn get_cached_vectors(pool: &SqlitePool) -> CodexResult<Vec<(String, i64, Vec<f32>)>> {
+        let rows = sqlx::query("SELECT document_id, chunk_index, vector_blob FROM vector_cache")
+            .fetch_all(pool)
+            .await?;
+
+        let mut result = Vec::new();
+        for row in rows {
+            let vector_blob: Vec<u8> = row.get("vector_blob");
+            match bincode::deserialize::<Vec<f32>>(&vector_blob) {
+                Ok(vector) => result.push((row.get("document_id"), row.get("chunk_index"), vector)),
+                Err(_) => continue,
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Embedding vectors that are not cached, as (document_id, chunk_index, vector, model)
+    pub async fn get_uncached_vectors(pool: &SqlitePool) -> CodexResult<Vec<(String, i64, Vec<f32>, String)>> {
+        let rows = query(
+            r#"
+            SELECT e.document_id, e.chunk_index, e.model,
+                   COALESCE(v.vector, e.vector) AS vector, COALESCE(v.vector_blob, e.vector_blob) AS vector_blob
+            FROM embeddings e LEFT JOIN vectors v ON v.id = e.vector_id
+            WHERE NOT EXISTS (
+                SELECT 1 FROM vector_cache c
+                WHERE c.document_id = e.document_id AND c.chunk_index = e.chunk_index
+            )
+            "#
         )
         .fetch_all(pool)
         .await?;
 
-        Ok(bookmarks)
+        Ok(rows
+            .iter()
+            .filter_map(|row| {
+                let vector = Self::decode_vector(row)?;
+                Some((row.get("document_id"), row.get("chunk_index"), vector, row.get("model")))
+            })
+            .collect())
     }
+    
+    /// Update vector cache access statistics
+    pub async fn update_cache_access(pool: &SqlitePool, document_id: &str) -> CodexResult<()> {
+        let now = Utc::now();
+        
+        sqlx::query(
+            r#"
+            UPDATE vector_cache
+            SET access_count = access_count + 1, last_accessed = ?
+            WHERE document_id = ?
+            "#
+        )
+        .bind(now.to_rfc3339())
+        .bind(document_id)
+        .execute(pool)
+        .await?;
 
-    /// Delete bookmark
-    pub async fn delete(pool: &SqlitePool, id: &str) -> CodexResult<()> {
-        sqlx::query!("DELETE FROM bookmarks WHERE id = ?", id)
+        Ok(())
+    }
+    
+    /// Add a chunk vector to the cache
+    pub async fn cache_vector(
+        pool: &SqlitePool,
+        document_id: &str,
+        chunk_index: i64,
+        vector: &[f32],
+        model: &str,
+    ) -> CodexResult<()> {
+        let vector_blob = bincode::serialize(vector)
+            .map_err(|e| CodexError::Database(sqlx::Error::Decode(Box::new(e))))?;
+        
+        sqlx::query(
+            r#"
+            INSERT INTO vector_cache (
+                id, document_id, chunk_index, vector_blob, dimensions, model, access_count, last_accessed, created_at
+            ) VALUES (?, ?, ?, ?, ?, ?, 1, ?, ?)
+            ON CONFLICT(document_id, chunk_index) DO UPDATE SET
+                vector_blob = excluded.vector_blob,
+                dimensions = excluded.dimensions,
+                model = excluded.model,
+                access_count = access_count + 1,
+                last_accessed = excluded.last_accessed
+            "#
+        )
+        .bind(uuid::Uuid::new_v4().to_string())
+        .bind(document_id)
+        .bind(chunk_index)
+        .bind(vector_blob)
+        .bind(vector.len() as i64)
+        .bind(model)
+        .bind(Utc::now().to_rfc3339())
+        .bind(Utc::now().to_rfc3339())
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+    
+    /// Drop a document's vectors from the cache
+    pub async fn evict_cached(pool: &SqlitePool, document_id: &str) -> CodexResult<()> {
+        sqlx::query("DELETE FROM vector_cache WHERE document_id = ?")
+            .bind(document_id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Vector cache size as (entries, stale entries, bytes of vectors)
+    ///
+    /// Stale entries (tombstones) belong to chunks that were re-embedded with
+    /// another model, or to deleted or archived documents.
+    pub async fn cache_compaction_counts(pool: &SqlitePool) -> CodexResult<(i64, i64, i64)> {
+        let counts: (i64, i64, i64) = sqlx::query_as(&format!(
+            r#"
+            SELECT COUNT(*), COALESCE(SUM(NOT {}), 0), COALESCE(SUM(length(vector_blob)), 0)
+            FROM vector_cache c
+            "#,
+            LIVE_CACHE_ENTRY
+        ))
+        .fetch_one(pool)
+        .await?;
+
+        Ok(counts)
+    }
+
+    /// Compact the vector cache down to its live entries, returning how many stale entries were dropped
+    ///
+    /// Stale entries are deleted in place in one write transaction, so
+    /// searches see either the old or the compacted cache.
+    pub async fn rebuild_cache(pool: &SqlitePool) -> CodexResult<u64> {
+        let mut tx = begin_write(pool).await?;
+        let removed = sqlx::query(&format!("DELETE FROM vector_cache AS c WHERE NOT ({})", LIVE_CACHE_ENTRY))
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+        tx.commit().await?;
+        Ok(removed)
+    }
+
+    /// Keep only the `max_entries` most used cache entries, returning how many were removed
+    pub async fn cleanup_cache(pool: &SqlitePool, max_entries: i64) -> CodexResult<u64> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM vector_cache
+            WHERE id NOT IN (
+                SELECT id FROM vector_cache
+                ORDER BY access_count DESC, last_accessed DESC
+                LIMIT ?
+            )
+            "#
+        )
+        .bind(max_entries)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+/// Settings query operations
+pub struct SettingQueries;
+
+impl SettingQueries {
+    /// Get setting by key
+    pub async fn get(pool: &SqlitePool, key: &str) -> CodexResult<Option<Setting>> {
+        let setting = sqlx::query_as::<_, Setting>(
+            "SELECT * FROM settings WHERE key = ?"
+        )
+        .bind(key)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(setting)
+    }
+
+    /// Set setting value
+    pub async fn set(pool: &SqlitePool, setting: &Setting) -> CodexResult<()> {
+        sqlx::query!(
+            r#"
+            INSERT OR REPLACE INTO settings (
+                key, value, description, category, is_user_configurable, created_at, updated_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?)
+            "#,
+            setting.key,
+            setting.value,
+            setting.description,
+            setting.category,
+            setting.is_user_configurable,
+            setting.created_at,
+            setting.updated_at
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Get all settings by category
+    pub async fn get_by_category(pool: &SqlitePool, category: &str) -> CodexResult<Vec<Setting>> {
+        let settings = sqlx::query_as!(
+            Setting,
+            "SELECT * FROM settings WHERE category = ? ORDER BY key",
+            category
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(settings)
+    }
+
+    /// Settings users may change, as opposed to bookkeeping kept by the app
+    pub async fn get_user_configurable(pool: &SqlitePool) -> CodexResult<Vec<Setting>> {
+        let settings = sqlx::query_as::<_, Setting>("SELECT * FROM settings WHERE is_user_configurable = true ORDER BY key")
+            .fetch_all(pool)
+            .await?;
+
+        Ok(settings)
+    }
+
+    /// Delete setting
+    pub async fn delete(pool: &SqlitePool, key: &str) -> CodexResult<()> {
+        sqlx::query!("DELETE FROM settings WHERE key = ?", key)
             .execute(pool)
             .await?;
 
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// Setting holding the search synonyms as a JSON object of term to expansions
+pub const SEARCH_SYNONYMS_SETTING: &str = "search_synonyms";
+/// Setting holding the search stop words as a JSON array
+pub const SEARCH_STOPWORDS_SETTING: &str = "search_stopwords";
+
+/// Settings-backed synonym and stop-word dictionaries for search
+pub struct SearchDictionaryQueries;
+
+impl SearchDictionaryQueries {
+    /// Both dictionaries, empty when never set
+    pub async fn get(pool: &SqlitePool) -> CodexResult<SearchDictionaries> {
+        let synonyms = SettingQueries::get(pool, SEARCH_SYNONYMS_SETTING)
+            .await?
+            .and_then(|setting| setting.get_value())
+            .unwrap_or_default();
+        let stopwords = SettingQueries::get(pool, SEARCH_STOPWORDS_SETTING)
+            .await?
+            .and_then(|setting| setting.get_value())
+            .unwrap_or_default();
+        Ok(SearchDictionaries { synonyms, stopwords })
+    }
+
+    /// Set the expansions of `term`, replacing any it had
+    ///
+    /// An empty list removes the term.
+    pub async fn set_synonyms(pool: &SqlitePool, term: &str, expansions: &[String]) -> CodexResult<SearchDictionaries> {
+        let term = Self::normalize_word(term)?;
+        let mut expanded: Vec<String> = Vec::new();
+        for expansion in expansions {
+            let expansion = expansion.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+            if !expansion.is_empty() && expansion != term && !expanded.contains(&expansion) {
+                expanded.push(expansion);
+            }
+        }
+
+        let mut dictionaries = Self::get(pool).await?;
+        if expanded.is_empty() {
+            dictionaries.synonyms.remove(&term);
+        } else {
+            dictionaries.synonyms.insert(term, expanded);
+        }
+        Self::save(pool, SEARCH_SYNONYMS_SETTING, &dictionaries.synonyms).await?;
+        Ok(dictionaries)
+    }
+
+    /// Remove `term` from the synonyms
+    pub async fn remove_synonyms(pool: &SqlitePool, term: &str) -> CodexResult<SearchDictionaries> {
+        Self::set_synonyms(pool, term, &[]).await
+    }
+
+    /// Add stop words, ignoring those already present
+    pub async fn add_stopwords(pool: &SqlitePool, words: &[String]) -> CodexResult<SearchDictionaries> {
+        let words = words.iter().map(|word| Self::normalize_word(word)).collect::<CodexResult<Vec<_>>>()?;
+        let mut dictionaries = Self::get(pool).await?;
+        dictionaries.stopwords.extend(words);
+        Self::save(pool, SEARCH_STOPWORDS_SETTING, &dictionaries.stopwords).await?;
+        Ok(dictionaries)
+    }
+
+    /// Remove stop words
+    pub async fn remove_stopwords(pool: &SqlitePool, words: &[String]) -> CodexResult<SearchDictionaries> {
+        let mut dictionaries = Self::get(pool).await?;
+        for word in words {
+            dictionaries.stopwords.remove(&word.trim().to_lowercase());
+        }
+        Self::save(pool, SEARCH_STOPWORDS_SETTING, &dictionaries.stopwords).await?;
+        Ok(dictionaries)
+    }
+
+    /// A dictionary word trimmed and lowercased, rejected unless it is one word
+    fn normalize_word(word: &str) -> CodexResult<String> {
+        let word = word.trim().to_lowercase();
+        if word.is_empty() || !word.chars().all(|c| c.is_alphanumeric()) {
+            return Err(CodexError::validation(format!(
+                "'{}' is not a single word of letters and digits",
+                word
+            )));
+        }
+        Ok(word)
+    }
+
+    async fn save<T: serde::Serialize>(pool: &SqlitePool, key: &str, value: &T) -> CodexResult<()> {
+        let mut setting = Setting::new(key.to_string(), String::new(), "search".to_string());
+        setting.set_value(value)?;
+        if let Some(existing) = SettingQueries::get(pool, key).await? {
+            setting.created_at = existing.created_at;
+        }
+        SettingQueries::set(pool, &setting).await
+    }
+}
+
+/// Cost of checking one document against the full-text index by rowid, in
+/// full-text matches enumerated for the same time
+///
+/// Each probe re-evaluates the query, prefix terms included, so reading a
+/// filtered subset first only pays off when it is tiny next to the matches.
+pub const FTS_PROBE_COST: i64 = 20_000;
+
+/// Metadata predicates applied inside full-text search
+///
+/// Dates are RFC 3339 strings compared with `created_at`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchFilter {
+    pub category: Option<String>,
+    /// Every tag must be present, ignoring case
+    pub tags: Vec<String>,
+    pub author: Option<String>,
+    pub language: Option<String>,
+    pub difficulty_level: Option<i64>,
+    pub created_after: Option<String>,
+    pub created_before: Option<String>,
+    pub favorites_only: bool,
+    pub include_archived: bool,
+    /// Custom metadata fields that must match, see [`MetadataValue::matches`]
+    pub metadata: Vec<(String, MetadataValue)>,
+}
+
+/// Custom metadata keys with an expression index (migration 0030)
+///
+/// Scalar filters on them use the index's exact `json_extract` expression.
+pub const INDEXED_METADATA_KEYS: [&str; 3] = ["project", "status", "type"];
+
+/// Reciprocal rank fusion constant: the result at 0-based rank `r` in a
+/// list adds `1 / (RRF_K + r + 1)`
+pub const RRF_K: f64 = 60.0;
+
+/// Vectors compared between deadline checks in a time-limited semantic scan
+pub const SEMANTIC_SCAN_BATCH: usize = 1024;
+
+/// Minimum similarity of the semantic matches hybrid search considers
+pub const HYBRID_SIMILARITY_THRESHOLD: f32 = 0.3;
+/// Columns of `documents_fts` with the weights the `bm25` rankings give them
+pub const FTS_COLUMN_WEIGHTS: [(&str, f64); 6] =
+    [("title", 10.0), ("content", 5.0), ("summary", 1.0), ("author", 1.0), ("category", 3.0), ("tags", 2.0)];
+/// Marks around matched terms in [`SearchQueries::explain_text_match`] highlights
+pub const FTS_HIGHLIGHT_MARKS: (char, char) = ('\u{2}', '\u{3}');
+
+/// How hybrid search combines its full-text and semantic rankings
+///
+/// Either way each list yields a relevance in 0–1 per result, and the two
+/// are added with the text and semantic weights.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FusionMethod {
+    /// Reciprocal rank fusion, scaled so the first result of a list scores
+    /// one. Only positions count, so unbounded BM25 values cannot swamp
+    /// similarities.
+    #[default]
+    ReciprocalRank,
+    /// BM25 relative to the best BM25 in the results, and cosine
+    /// similarity clamped to 0–1
+    WeightedSum,
+}
+
+/// Order in which a filtered search visits documents
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterPlan {
+    /// Read the documents passing the indexed predicates, then check each
+    /// against the full-text index by rowid
+    FilterFirst,
+    /// Rank every full-text match and drop those failing the predicates
+    MatchFirst,
+}
+
+impl SearchFilter {
+    /// Whether any predicate narrows documents through an index
+    fn has_indexed_predicates(&self) -> bool {
+        self.category.is_some()
+            || self.author.is_some()
+            || self.favorites_only
+            || self.created_after.is_some()
+            || self.created_before.is_some()
+            || self.metadata.iter().any(|(key, value)| Self::is_indexed_metadata(key, value))
+    }
+
+    fn is_indexed_metadata(key: &str, value: &MetadataValue) -> bool {
+        INDEXED_METADATA_KEYS.contains(&key) && !matches!(value, MetadataValue::List(_))
+    }
+
+    /// Append the predicate for one custom metadata field
+    ///
+    /// Keys are checked with [`is_metadata_key`] and written into the JSON
+    /// path literally, as expression indexes only serve identical
+    /// expressions. Invalid keys match nothing.
+    fn push_metadata<'a>(builder: &mut sqlx::QueryBuilder<'a, sqlx::Sqlite>, key: &str, value: &'a MetadataValue) {
+        if !is_metadata_key(key) {
+            builder.push(" AND 0");
+            return;
+        }
+        let path = format!("'$.{}'", key);
+        match value {
+            // json_extract reads booleans as 0 and 1, like numbers
+            MetadataValue::Bool(value) => {
+                builder.push(format!(" AND json_type(d.metadata_json, {}) = '{}'", path, value));
+            }
+            // The type checks keep booleans from matching 1 and lists from
+            // matching their JSON text
+            MetadataValue::Number(number) => {
+                builder.push(format!(
+                    " AND json_type(d.metadata_json, {0}) IN ('integer', 'real') AND json_extract(d.metadata_json, {0}) = ",
+                    path
+                ));
+                match number.as_i64() {
+                    Some(integer) => builder.push_bind(integer),
+                    None => builder.push_bind(number.as_f64().unwrap_or(f64::NAN)),
+                };
+            }
+            MetadataValue::Text(text) => {
+                builder.push(format!(
+                    " AND json_type(d.metadata_json, {0}) = 'text' AND json_extract(d.metadata_json, {0}) = ",
+                    path
+                ));
+                builder.push_bind(text);
+            }
+            MetadataValue::List(items) => {
+                builder.push(format!(" AND json_type(d.metadata_json, {}) = 'array'", path));
+                for item in items {
+                    builder
+                        .push(format!(" AND EXISTS (SELECT 1 FROM json_each(d.metadata_json, {}) WHERE value = ", path))
+                        .push_bind(item)
+                        .push(")");
+                }
+            }
+        }
+    }
+
+    /// Append the indexed predicates over documents aliased `d`
+    fn push_indexed<'a>(&'a self, builder: &mut sqlx::QueryBuilder<'a, sqlx::Sqlite>) {
+        builder.push(" AND d.is_deleted = 0 AND d.category IS NOT ").push_bind(DIAGNOSTICS_CATEGORY);
+        if !self.include_archived {
+            builder.push(" AND d.is_archived = 0");
+        }
+        if let Some(ref category) = self.category {
+            builder.push(" AND d.category = ").push_bind(category);
+        }
+        if let Some(ref author) = self.author {
+            builder.push(" AND d.author = ").push_bind(author).push(" COLLATE NOCASE");
+        }
+        if self.favorites_only {
+            builder.push(" AND d.is_favorite = 1");
+        }
+        if let Some(ref after) = self.created_after {
+            builder.push(" AND d.created_at >= ").push_bind(after);
+        }
+        if let Some(ref before) = self.created_before {
+            builder.push(" AND d.created_at <= ").push_bind(before);
+        }
+        for (key, value) in &self.metadata {
+            if Self::is_indexed_metadata(key, value) {
+                Self::push_metadata(builder, key, value);
+            }
+        }
+    }
+
+    /// Append every predicate over documents aliased `d`
+    fn push_all<'a>(&'a self, builder: &mut sqlx::QueryBuilder<'a, sqlx::Sqlite>) {
+        self.push_indexed(builder);
+        if let Some(ref language) = self.language {
+            builder.push(" AND d.language = ").push_bind(language).push(" COLLATE NOCASE");
+        }
+        if let Some(level) = self.difficulty_level {
+            builder.push(" AND d.difficulty_level = ").push_bind(level);
+        }
+        for tag in &self.tags {
+            builder
+                .push(" AND CASE WHEN json_valid(d.tags) THEN EXISTS (SELECT 1 FROM json_each(d.tags) WHERE value = ")
+                .push_bind(tag)
+                .push(" COLLATE NOCASE) ELSE 0 END");
+        }
+        for (key, value) in &self.metadata {
+            if !Self::is_indexed_metadata(key, value) {
+                Self::push_metadata(builder, key, value);
+            }
+        }
+    }
+}
+
+/// Search query operations - unified search interface
+pub struct SearchQueries;
+
+/// FTS5 query sanitizer used by search, exposed for the hot-path benchmarks
+#[cfg(any(test, feature = "test-utils"))]
+pub fn sanitize_fts_query(query: &str) -> String {
+    SearchQueries::sanitize_fts_query(query)
+}
+
+impl SearchQueries {
+    /// Whether the full-text index matches `query` for one document,
+    /// including documents hidden from searches
+    pub async fn matches_document(pool: &SqlitePool, query: &str, document_id: &str) -> CodexResult<bool> {
+        let (count,): (i64,) = sqlx::query_as(
+            r#"
+            SELECT COUNT(*) FROM documents d
+            JOIN documents_fts ON d.rowid = documents_fts.rowid
+            WHERE documents_fts MATCH ? AND d.id = ?
+            "#
+        )
+        .bind(Self::sanitize_fts_query(query))
+        .bind(document_id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(count > 0)
+    }
+
+    /// Full-text evidence for why a document matches `query`, `None` if it does not
+    ///
+    /// Returns the document's BM25 relevance, every indexed column with the
+    /// matched terms wrapped in [`FTS_HIGHLIGHT_MARKS`] (in the order of
+    /// [`FTS_COLUMN_WEIGHTS`]), and a short excerpt around the best match.
+    pub async fn explain_text_match(
+        pool: &SqlitePool,
+        query: &str,
+        document_id: &str,
+    ) -> CodexResult<Option<(f64, Vec<String>, String)>> {
+        let sanitized_query = Self::sanitize_fts_query(query);
+        if sanitized_query.is_empty() {
+            return Ok(None);
+        }
+
+        let (open, close) = FTS_HIGHLIGHT_MARKS;
+        let highlights = (0..FTS_COLUMN_WEIGHTS.len())
+            .map(|column| format!("highlight(documents_fts, {}, char({}), char({})) AS column_{}", column, open as u32, close as u32, column))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let weights = FTS_COLUMN_WEIGHTS.iter().map(|(_, weight)| format!("{:.1}", weight)).collect::<Vec<_>>().join(", ");
+        let row = sqlx::query(&format!(
+            r#"
+            SELECT -bm25(documents_fts, {}) AS rank_score, {},
+                   snippet(documents_fts, -1, '[', ']', '...', 16) AS excerpt
+            FROM documents_fts JOIN documents d ON d.rowid = documents_fts.rowid
+            WHERE documents_fts MATCH ? AND d.id = ?
+            "#,
+            weights, highlights
+        ))
+        .bind(sanitized_query)
+        .bind(document_id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(row.map(|row| {
+            let columns = (0..FTS_COLUMN_WEIGHTS.len())
+                .map(|column| row.get::<Option<String>, _>(format!("column_{}", column).as_str()).unwrap_or_default())
+                .collect();
+            (row.get("rank_score"), columns, row.get("excerpt"))
+        }))
+    }
+
+    /// Whether a term occurs in the full-text index
+    pub async fn vocabulary_contains(pool: &SqlitePool, term: &str) -> CodexResult<bool> {
+        let row = sqlx::query("SELECT 1 FROM documents_fts_vocab WHERE term = ?")
+            .bind(term.to_lowercase())
+            .fetch_optional(pool)
+            .await?;
+
+        Ok(row.is_some())
+    }
+
+    /// Indexed terms with a length in `min_len..=max_len` and the number of
+    /// documents containing each, most common first
+    pub async fn vocabulary_terms(
+        pool: &SqlitePool,
+        min_len: usize,
+        max_len: usize,
+        limit: i64,
+    ) -> CodexResult<Vec<(String, i64)>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT term, doc FROM documents_fts_vocab
+            WHERE length(term) BETWEEN ? AND ?
+            ORDER BY doc DESC
+            LIMIT ?
+            "#,
+        )
+        .bind(min_len as i64)
+        .bind(max_len as i64)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.iter().map(|r| (r.get("term"), r.get("doc"))).collect())
+    }
+
+    /// Simple search interface for FTS5 full-text search
+    pub async fn search(
+        pool: &SqlitePool,
+        query: &str,
+        limit: Option<i64>,
+    ) -> CodexResult<Vec<Document>> {
+        let limit = limit.unwrap_or(50);
+        
+        // Sanitize query for FTS5
+        let sanitized_query = Self::sanitize_fts_query(query);
+        
+        let start = std::time::Instant::now();
+        
+        let documents = sqlx::query_as::<_, Document>(
+            r#"
+            SELECT d.* FROM documents d
+            JOIN documents_fts ON d.rowid = documents_fts.rowid
+            WHERE documents_fts MATCH ? AND d.is_deleted = false AND d.category IS NOT ?
+            ORDER BY documents_fts.rank
+            LIMIT ?
+            "#
+        )
+        .bind(sanitized_query)
+        .bind(DIAGNOSTICS_CATEGORY)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+        
+        let duration = start.elapsed();
+        tracing::debug!(
+            "FTS5 search completed in {:?}ms for query: '{}' (found {} results)",
+            duration.as_millis(),
+            query,
+            documents.len()
+        );
+        
+        Ok(documents)
+    }
+    
+    /// Enhanced search with ranking and highlighting
+    ///
+    /// Scores are negated BM25 values, so higher is more relevant.
+    pub async fn search_with_ranking(
+        pool: &SqlitePool,
+        query: &str,
+        limit: Option<i64>,
+        offset: Option<i64>,
+    ) -> CodexResult<Vec<(Document, f64)>> {
+        let limit = limit.unwrap_or(50);
+        let offset = offset.unwrap_or(0);
+        
+        let sanitized_query = Self::sanitize_fts_query(query);
+        if sanitized_query.is_empty() {
+            return Ok(Vec::new());
+        }
+        
+        let start = std::time::Instant::now();
+        
+        let rows = sqlx::query(
+            r#"
+            SELECT d.*, 
+                   -bm25(documents_fts, 10.0, 5.0, 1.0, 1.0, 3.0, 2.0) as rank_score,
+                   snippet(documents_fts, 1, '<mark>', '</mark>', '...', 32) as snippet
+            FROM documents d
+            JOIN documents_fts ON d.rowid = documents_fts.rowid
+            WHERE documents_fts MATCH ? AND d.is_deleted = false AND d.category IS NOT ?
+            ORDER BY rank_score DESC
+            LIMIT ? OFFSET ?
+            "#
+        )
+        .bind(sanitized_query)
+        .bind(DIAGNOSTICS_CATEGORY)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(pool)
+        .await?;
+        
+        let duration = start.elapsed();
+        tracing::debug!(
+            "Ranked FTS5 search completed in {:?}ms for query: '{}' (found {} results)",
+            duration.as_millis(),
+            query,
+            rows.len()
+        );
+        
+        let mut results = Vec::new();
+        for row in rows {
+            let document = Document::from_row(&row)?;
+            
+            let score: Option<f64> = row.get("rank_score");
+            results.push((document, score.unwrap_or(0.0)));
+        }
+        
+        Ok(results)
+    }
+    
+    /// Documents containing the query's words, summed over the words
+    ///
+    /// Read from the vocabulary, so prefix matches are not counted and the
+    /// estimate errs low.
+    pub async fn estimate_matches(pool: &SqlitePool, query: &str) -> CodexResult<i64> {
+        let mut total = 0;
+        for word in query.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()) {
+            let row: Option<(i64,)> = sqlx::query_as("SELECT doc FROM documents_fts_vocab WHERE term = ?")
+                .bind(word.to_lowercase())
+                .fetch_optional(pool)
+                .await?;
+            total += row.map(|(doc,)| doc).unwrap_or(0);
+        }
+        Ok(total)
+    }
+
+    /// How a filtered search for `query` should visit documents
+    ///
+    /// The documents passing the indexed predicates are read first when
+    /// probing each costs less than enumerating the estimated matches. The
+    /// count stops at that break-even point, so planning stays cheap.
+    pub async fn plan_filtered_search(pool: &SqlitePool, query: &str, filter: &SearchFilter) -> CodexResult<FilterPlan> {
+        if !filter.has_indexed_predicates() {
+            return Ok(FilterPlan::MatchFirst);
+        }
+        let break_even = Self::estimate_matches(pool, query).await? / FTS_PROBE_COST;
+        if break_even == 0 {
+            return Ok(FilterPlan::MatchFirst);
+        }
+
+        let mut builder = sqlx::QueryBuilder::new("SELECT COUNT(*) FROM (SELECT 1 FROM documents d WHERE 1");
+        filter.push_indexed(&mut builder);
+        builder.push(" LIMIT ").push_bind(break_even + 1).push(")");
+        let (count,): (i64,) = builder.build_query_as().fetch_one(pool).await?;
+
+        Ok(if count <= break_even { FilterPlan::FilterFirst } else { FilterPlan::MatchFirst })
+    }
+
+    /// Live documents passing a filter, newest first
+    pub async fn list_filtered(pool: &SqlitePool, filter: &SearchFilter, limit: i64) -> CodexResult<Vec<Document>> {
+        let mut builder = sqlx::QueryBuilder::new("SELECT d.* FROM documents d WHERE 1");
+        filter.push_all(&mut builder);
+        builder.push(" ORDER BY d.created_at DESC, d.id DESC LIMIT ").push_bind(limit);
+
+        Ok(builder.build_query_as::<Document>().fetch_all(pool).await?)
+    }
+
+    /// Ranked full-text search restricted by metadata predicates
+    ///
+    /// Unlike filtering the results of [`Self::search_with_ranking`], every
+    /// matching document passing the filter can be returned, and documents
+    /// failing it are dropped before they are scored or loaded.
+    pub async fn search_with_ranking_filtered(
+        pool: &SqlitePool,
+        query: &str,
+        filter: &SearchFilter,
+        limit: Option<i64>,
+    ) -> CodexResult<Vec<(Document, f64)>> {
+        let sanitized_query = Self::sanitize_fts_query(query);
+        if sanitized_query.is_empty() {
+            return Ok(Vec::new());
+        }
+        Self::ranked_match_filtered(pool, sanitized_query, query, filter, limit).await
+    }
+
+    /// [`Self::search_with_ranking_filtered`] for a query already split into
+    /// term groups, matching a document containing any term of any group
+    ///
+    /// The first term of each group is the query's own, matched as a prefix
+    /// and, with the others, as a phrase; the rest are its synonyms, matched
+    /// exactly.
+    pub async fn search_expanded_filtered(
+        pool: &SqlitePool,
+        groups: &[Vec<String>],
+        filter: &SearchFilter,
+        limit: Option<i64>,
+    ) -> CodexResult<Vec<(Document, f64)>> {
+        let match_query = Self::expanded_fts_query(groups);
+        if match_query.is_empty() {
+            return Ok(Vec::new());
+        }
+        let plan_terms = groups.iter().flatten().cloned().collect::<Vec<_>>().join(" ");
+        Self::ranked_match_filtered(pool, match_query, &plan_terms, filter, limit).await
+    }
+
+    /// FTS5 expression for [`Self::search_expanded_filtered`]
+    fn expanded_fts_query(groups: &[Vec<String>]) -> String {
+        let quote = |text: &str| {
+            let cleaned: String = text
+                .chars()
+                .filter(|c| c.is_alphanumeric() || c.is_whitespace() || "-_".contains(*c))
+                .collect();
+            let words: Vec<&str> = cleaned.split_whitespace().collect();
+            (!words.is_empty()).then(|| format!("\"{}\"", words.join(" ")))
+        };
+
+        let heads: Vec<&str> = groups.iter().filter_map(|group| group.first().map(String::as_str)).collect();
+        let mut alternatives: Vec<String> = quote(&heads.join(" ")).into_iter().collect();
+        for group in groups {
+            let mut terms = group.iter().filter_map(|term| quote(term));
+            if let Some(head) = terms.next() {
+                alternatives.push(format!("{}*", head));
+            }
+            alternatives.extend(terms);
+        }
+        alternatives.join(" OR ")
+    }
+
+    /// Ranked, filtered matches of an FTS5 expression; `plan_query` holds
+    /// its words for estimating the number of matches
+    async fn ranked_match_filtered(
+        pool: &SqlitePool,
+        sanitized_query: String,
+        plan_query: &str,
+        filter: &SearchFilter,
+        limit: Option<i64>,
+    ) -> CodexResult<Vec<(Document, f64)>> {
+        let start = std::time::Instant::now();
+        let plan = Self::plan_filtered_search(pool, plan_query, filter).await?;
+
+        let mut builder = sqlx::QueryBuilder::new(
+            "SELECT d.*, -bm25(documents_fts, 10.0, 5.0, 1.0, 1.0, 3.0, 2.0) as rank_score FROM ",
+        );
+        builder.push(match plan {
+            // CROSS JOIN keeps SQLite from reordering the loops
+            FilterPlan::FilterFirst => "documents d CROSS JOIN documents_fts ON documents_fts.rowid = d.rowid",
+            FilterPlan::MatchFirst => "documents_fts JOIN documents d ON d.rowid = documents_fts.rowid",
+        });
+        builder.push(" WHERE documents_fts MATCH ").push_bind(sanitized_query);
+        filter.push_all(&mut builder);
+        builder.push(" ORDER BY rank_score DESC LIMIT ").push_bind(limit.unwrap_or(50));
+
+        let rows = builder.build().fetch_all(pool).await?;
+
+        tracing::debug!(
+            "Filtered FTS5 search ({:?}) completed in {:?}ms for query: '{}' (found {} results)",
+            plan,
+            start.elapsed().as_millis(),
+            plan_query,
+            rows.len()
+        );
+
+        rows.iter()
+            .map(|row| {
+                let score: Option<f64> = row.get("rank_score");
+                Ok((Document::from_row(row)?, score.unwrap_or(0.0)))
+            })
+            .collect()
+    }
+
+    /// Semantic search using vector embeddings, one result per document
+    pub async fn search_semantic(
+        pool: &SqlitePool,
+        query_vector: &[f32],
+        limit: Option<i64>,
+        similarity_threshold: Option<f32>,
+    ) -> CodexResult<Vec<(Document, f32)>> {
+        let chunks = Self::search_semantic_chunks(pool, query_vector, limit, similarity_threshold).await?;
+        Ok(Self::best_per_document(chunks.into_iter().map(|(doc, similarity, _)| (doc, similarity))))
+    }
+
+    /// Semantic search returning every chunk above the threshold
+    ///
+    /// Results are (document, similarity, chunk_index), best first, for the
+    /// `limit` documents with the best-matching chunks.
+    pub async fn search_semantic_chunks(
+        pool: &SqlitePool,
+        query_vector: &[f32],
+        limit: Option<i64>,
+        similarity_threshold: Option<f32>,
+    ) -> CodexResult<Vec<(Document, f32, i64)>> {
+        let (results, _) = Self::search_semantic_chunks_until(pool, query_vector, limit, similarity_threshold, None).await?;
+        Ok(results)
+    }
+
+    /// Semantic search that stops at `deadline`
+    ///
+    /// Vectors are compared in batches of [`SEMANTIC_SCAN_BATCH`], and the
+    /// deadline is checked between batches and between document fetches.
+    /// Past it the scan stops and returns the matches among the vectors
+    /// compared so far, with `true` to mark them partial.
+    pub async fn search_semantic_chunks_until(
+        pool: &SqlitePool,
+        query_vector: &[f32],
+        limit: Option<i64>,
+        similarity_threshold: Option<f32>,
+        deadline: Option<std::time::Instant>,
+    ) -> CodexResult<(Vec<(Document, f32, i64)>, bool)> {
+        let expired = || deadline.is_some_and(|deadline| std::time::Instant::now() >= deadline);
+        let limit = limit.unwrap_or(10);
+        let threshold = similarity_threshold.unwrap_or(0.5);
+        
+        let start = std::time::Instant::now();
+        
+        // Get all embeddings and compute similarity in-memory. With the vector
+        // cache enabled, cached chunks are read from the compact cache table and
+        // only the rest from the embeddings table.
+        // Note: For production, consider using a proper vector database
+        let cache_enabled = EmbeddingQueries::vector_cache_limit(pool).await?.is_some();
+        let (cached, uncached) = if cache_enabled {
+            (
+                EmbeddingQueries::get_cached_vectors(pool).await?,
+                EmbeddingQueries::get_uncached_vectors(pool).await?,
+            )
+        } else {
+            let all = EmbeddingQueries::get_all_vectors(pool).await?;
+            (Vec::new(), all.into_iter().map(|(doc_id, chunk_index, vector)| (doc_id, chunk_index, vector, String::new())).collect())
+        };
+        let cache_hits = cached.len();
+        
+        let vectors: Vec<(&String, i64, &Vec<f32>)> = cached
+            .iter()
+            .map(|(doc_id, chunk_index, vector)| (doc_id, *chunk_index, vector))
+            .chain(uncached.iter().map(|(doc_id, chunk_index, vector, _)| (doc_id, *chunk_index, vector)))
+            .collect();
+        let mut matching: Vec<(String, i64, f32)> = Vec::new();
+        let mut timed_out = false;
+        for batch in vectors.chunks(SEMANTIC_SCAN_BATCH) {
+            if expired() {
+                timed_out = true;
+                break;
+            }
+            matching.extend(
+                batch
+                    .iter()
+                    .map(|(doc_id, chunk_index, embedding)| ((*doc_id).clone(), *chunk_index, Self::cosine_similarity(query_vector, embedding)))
+                    .filter(|(_, _, similarity)| *similarity >= threshold),
+            );
+            // Let a caller's timeout fire between batches
+            tokio::task::yield_now().await;
+        }
+        
+        // Sort by similarity and keep the chunks of the best `limit` documents
+        matching.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap_or(std::cmp::Ordering::Equal));
+        let mut kept = std::collections::HashSet::new();
+        matching.retain(|(doc_id, _, _)| kept.contains(doc_id) || (kept.len() < limit as usize && kept.insert(doc_id.clone())));
+        
+        // Fetch each document once for its chunks
+        let mut documents = std::collections::HashMap::new();
+        let mut results = Vec::new();
+        for (doc_id, chunk_index, similarity) in matching {
+            if !documents.contains_key(&doc_id) {
+                if expired() {
+                    timed_out = true;
+                    break;
+                }
+                let document = DocumentQueries::get_by_id(pool, &doc_id).await?.filter(|d| !d.is_diagnostic());
+                documents.insert(doc_id.clone(), document);
+            }
+            if let Some(Some(document)) = documents.get(&doc_id) {
+                results.push((document.clone(), similarity, chunk_index));
+            }
+        }
+        
+        // Cache the vectors of the documents found, so repeated searches read them from the cache
+        if cache_enabled && !timed_out {
+            let found: std::collections::HashSet<&str> = results.iter().map(|(doc, _, _)| doc.id.as_str()).collect();
+            for doc_id in cached.iter().map(|(doc_id, _, _)| doc_id.as_str()).collect::<std::collections::HashSet<_>>() {
+                if found.contains(doc_id) {
+                    EmbeddingQueries::update_cache_access(pool, doc_id).await?;
+                }
+            }
+            for (doc_id, chunk_index, vector, model) in &uncached {
+                if found.contains(doc_id.as_str()) {
+                    EmbeddingQueries::cache_vector(pool, doc_id, *chunk_index, vector, model).await?;
+                }
+            }
+        }
+        
+        let duration = start.elapsed();
+        tracing::debug!(
+            "Semantic search completed in {:?}ms (found {} chunks, {} cached vectors, timed out: {})",
+            duration.as_millis(),
+            results.len(),
+            cache_hits,
+            timed_out
+        );
+        
+        Ok((results, timed_out))
+    }
+    
+    /// Hybrid search combining full-text and semantic search, one result per document
+    pub async fn search_hybrid(
+        pool: &SqlitePool,
+        query: &str,
+        query_vector: Option<&[f32]>,
+        limit: Option<i64>,
+        text_weight: Option<f32>,
+        semantic_weight: Option<f32>,
+    ) -> CodexResult<Vec<(Document, f64)>> {
+        let filter = SearchFilter { include_archived: true, ..SearchFilter::default() };
+        let chunks = Self::search_hybrid_chunks(
+            pool,
+            query,
+            query_vector,
+            limit,
+            FusionMethod::WeightedSum,
+            text_weight,
+            semantic_weight,
+            &filter,
+        )
+        .await?;
+        Ok(Self::best_per_document(chunks.into_iter().map(|(doc, score, _)| (doc, score))))
+    }
+
+    /// Hybrid search returning every matching chunk
+    ///
+    /// Results are (document, score, chunk_index), best first. Each
+    /// semantically matching chunk scores its document's text relevance plus
+    /// its own, combined by `fusion`; documents found by full-text search
+    /// alone have no chunk index. `limit` counts documents. `filter` applies
+    /// to the full-text matches; semantic matches are left to the caller.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn search_hybrid_chunks(
+        pool: &SqlitePool,
+        query: &str,
+        query_vector: Option<&[f32]>,
+        limit: Option<i64>,
+        fusion: FusionMethod,
+        text_weight: Option<f32>,
+        semantic_weight: Option<f32>,
+        filter: &SearchFilter,
+    ) -> CodexResult<Vec<(Document, f64, Option<i64>)>> {
+        let limit = limit.unwrap_or(20);
+        
+        let start = std::time::Instant::now();
+        
+        // Get full-text search results
+        let text_results = Self::search_with_ranking_filtered(pool, query, filter, Some(limit * 2)).await?;
+        
+        // Get semantic search results if query vector is provided
+        let semantic_results = if let Some(vector) = query_vector {
+            Self::search_semantic_chunks(pool, vector, Some(limit), Some(HYBRID_SIMILARITY_THRESHOLD)).await?
+        } else {
+            Vec::new()
+        };
+        
+        let final_results = Self::fuse_hybrid_chunks(text_results, semantic_results, limit, fusion, text_weight, semantic_weight);
+        
+        let duration = start.elapsed();
+        tracing::debug!(
+            "Hybrid search completed in {:?}ms (found {} chunks)",
+            duration.as_millis(),
+            final_results.len()
+        );
+        
+        Ok(final_results)
+    }
+
+    /// Combine full-text matches and semantic chunk matches as
+    /// [`SearchQueries::search_hybrid_chunks`] does
+    ///
+    /// Either list may be empty or partial, e.g. when a stage ran out of time.
+    pub fn fuse_hybrid_chunks(
+        text_results: Vec<(Document, f64)>,
+        semantic_results: Vec<(Document, f32, i64)>,
+        limit: i64,
+        fusion: FusionMethod,
+        text_weight: Option<f32>,
+        semantic_weight: Option<f32>,
+    ) -> Vec<(Document, f64, Option<i64>)> {
+        let text_weight = text_weight.unwrap_or(0.7) as f64;
+        let semantic_weight = semantic_weight.unwrap_or(0.3) as f64;
+        
+        // Weighted text relevance of each document
+        let max_text_score = text_results.iter().map(|(_, score)| *score).fold(0.0, f64::max);
+        let mut text_scores = std::collections::HashMap::new();
+        for (rank, (doc, score)) in text_results.iter().enumerate() {
+            let relevance = match fusion {
+                FusionMethod::ReciprocalRank => Self::reciprocal_rank(rank),
+                FusionMethod::WeightedSum => Self::normalize_score(*score, 0.0, max_text_score),
+            };
+            text_scores.insert(doc.id.clone(), relevance * text_weight);
+        }
+        
+        // Add semantic scores per chunk; documents without a matching chunk keep their text score
+        let mut with_chunks = std::collections::HashSet::new();
+        let mut final_results: Vec<(Document, f64, Option<i64>)> = Vec::new();
+        for (rank, (doc, similarity, chunk_index)) in semantic_results.into_iter().enumerate() {
+            let text_score = text_scores.get(&doc.id).copied().unwrap_or(0.0);
+            let relevance = match fusion {
+                FusionMethod::ReciprocalRank => Self::reciprocal_rank(rank),
+                FusionMethod::WeightedSum => (similarity as f64).clamp(0.0, 1.0),
+            };
+            let score = text_score + relevance * semantic_weight;
+            with_chunks.insert(doc.id.clone());
+            final_results.push((doc, score, Some(chunk_index)));
+        }
+        for (doc, _) in text_results {
+            if !with_chunks.contains(&doc.id) {
+                let score = text_scores[&doc.id];
+                final_results.push((doc, score, None));
+            }
+        }
+        
+        // Sort by combined score and keep the best `limit` documents
+        final_results.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        let mut kept = std::collections::HashSet::new();
+        final_results.retain(|(doc, _, _)| kept.contains(&doc.id) || (kept.len() < limit as usize && kept.insert(doc.id.clone())));
+        final_results
+    }
+
+    /// Reciprocal rank relevance of the result at 0-based `rank`, 1 for the first
+    fn reciprocal_rank(rank: usize) -> f64 {
+        (RRF_K + 1.0) / (RRF_K + rank as f64 + 1.0)
+    }
+
+    /// First (best) result of each document, keeping the order
+    fn best_per_document<S>(results: impl Iterator<Item = (Document, S)>) -> Vec<(Document, S)> {
+        let mut seen = std::collections::HashSet::new();
+        results.filter(|(doc, _)| seen.insert(doc.id.clone())).collect()
+    }
+    
+    /// Sanitize FTS5 query to prevent syntax errors
+    pub(crate) fn sanitize_fts_query(query: &str) -> String {
+        // Remove special FTS5 characters that might cause syntax errors
+        let cleaned = query
+            .chars()
+            .filter(|c| c.is_alphanumeric() || c.is_whitespace() || "-_".contains(*c))
+            .collect::<String>();
+        
+        // Split into words and join with OR for better matching
+        let words: Vec<&str> = cleaned.split_whitespace().collect();
+        if words.is_empty() {
+            return "".to_string();
+        }
+        
+        // Create a phrase query for exact matches, fallback to OR for partial matches.
+        // Terms are quoted so hyphenated codes like "XJ-9000" are not read as operators.
+        if words.len() == 1 {
+            format!("\"{}\" OR \"{}\"*", words[0], words[0])
+        } else {
+            let phrase = format!("\"{}\"", words.join(" "));
+            let or_terms = words.iter().map(|w| format!("\"{}\"*", w)).collect::<Vec<_>>().join(" OR ");
+            format!("{} OR {}", phrase, or_terms)
+        }
+    }
+    
+    /// Compute cosine similarity between two vectors
+    fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+        if a.len() != b.len() {
+            return 0.0;
+        }
+        
+        let dot_product: f32 = a.iter().zip(b.iter()).map(|(x, y)| x * y).sum();
+        let magnitude_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+        let magnitude_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+        
+        if magnitude_a == 0.0 || magnitude_b == 0.0 {
+            return 0.0;
+        }
+        
+        dot_product / (magnitude_a * magnitude_b)
+    }
+    
+    /// Normalize score to 0-1 range
+    fn normalize_score(score: f64, min_val: f64, max_val: f64) -> f64 {
+        if max_val == min_val {
+            return 0.0;
+        }
+        (score - min_val) / (max_val - min_val)
+    }
+}
+
+/// Bookmark query operations
+pub struct BookmarkQueries;
+
+impl BookmarkQueries {
+    /// Create a new bookmark
+    pub async fn create(pool: &SqlitePool, bookmark: &Bookmark) -> CodexResult<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO bookmarks (
+                id, document_id, title, notes, position, selected_text, tags, created_at, updated_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+            bookmark.id,
+            bookmark.document_id,
+            bookmark.title,
+            bookmark.notes,
+            bookmark.position,
+            bookmark.selected_text,
+            bookmark.tags,
+            bookmark.created_at,
+            bookmark.updated_at
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Get bookmarks for a document
+    pub async fn get_by_document(
+        pool: &SqlitePool,
+        document_id: &str,
+    ) -> CodexResult<Vec<Bookmark>> {
+        let bookmarks = sqlx::query_as!(
+            Bookmark,
+            "SELECT * FROM bookmarks WHERE document_id = ? ORDER BY position",
+            document_id
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(bookmarks)
+    }
+
+    /// Get all bookmarks
+    pub async fn get_all(pool: &SqlitePool, limit: i64) -> CodexResult<Vec<Bookmark>> {
+        let bookmarks = sqlx::query_as!(
+            Bookmark,
+            "SELECT * FROM bookmarks ORDER BY created_at DESC LIMIT ?",
+            limit
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(bookmarks)
+    }
+
+    /// Delete bookmark
+    pub async fn delete(pool: &SqlitePool, id: &str) -> CodexResult<()> {
+        sqlx::query!("DELETE FROM bookmarks WHERE id = ?", id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Parsed document structure operations
+pub struct StructureQueries;
+
+impl StructureQueries {
+    /// Store (or replace) the structure JSON for a document
+    pub async fn upsert(
+        pool: &SqlitePool,
+        document_id: &str,
+        kind: &str,
+        structure_json: &str,
+    ) -> CodexResult<()> {
+        sqlx::query(
+            r#"
+            INSERT OR REPLACE INTO document_structures (document_id, kind, structure, created_at)
+            VALUES (?, ?, ?, ?)
+            "#,
+        )
+        .bind(document_id)
+        .bind(kind)
+        .bind(structure_json)
+        .bind(Utc::now().to_rfc3339())
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Get the structure JSON for a document
+    pub async fn get(pool: &SqlitePool, document_id: &str) -> CodexResult<Option<String>> {
+        let row = sqlx::query("SELECT structure FROM document_structures WHERE document_id = ?")
+            .bind(document_id)
+            .fetch_optional(pool)
+            .await?;
+
+        Ok(row.map(|r| r.get("structure")))
+    }
+}
+
+/// Home feed sections
+///
+/// Each query matches one of the partial `idx_documents_feed_*` indexes;
+/// keep their WHERE clauses in the same form so the planner can use them.
+pub struct FeedQueries;
+
+impl FeedQueries {
+    const COLUMNS: &'static str =
+        "id, title, category, content_type, reading_time, created_at, updated_at, last_accessed, view_count, is_favorite";
+
+    /// Documents edited after they were imported, most recent edit first
+    pub async fn recently_modified(pool: &SqlitePool, limit: i64) -> CodexResult<Vec<FeedItem>> {
+        let sql = format!(
+            r#"
+            SELECT {} FROM documents INDEXED BY idx_documents_feed_updated
+            WHERE is_deleted = 0 AND is_archived = 0 AND category IS NOT ?
+              AND updated_at > created_at
+            ORDER BY updated_at DESC, id DESC
+            LIMIT ?
+            "#,
+            Self::COLUMNS
+        );
+        Ok(sqlx::query_as::<_, FeedItem>(&sql).bind(DIAGNOSTICS_CATEGORY).bind(limit).fetch_all(pool).await?)
+    }
+
+    /// Documents most recently opened
+    pub async fn recently_read(pool: &SqlitePool, limit: i64) -> CodexResult<Vec<FeedItem>> {
+        let sql = format!(
+            r#"
+            SELECT {} FROM documents INDEXED BY idx_documents_feed_accessed
+            WHERE is_deleted = 0 AND is_archived = 0 AND last_accessed IS NOT NULL
+              AND category IS NOT ?
+            ORDER BY last_accessed DESC
+            LIMIT ?
+            "#,
+            Self::COLUMNS
+        );
+        Ok(sqlx::query_as::<_, FeedItem>(&sql).bind(DIAGNOSTICS_CATEGORY).bind(limit).fetch_all(pool).await?)
+    }
+
+    /// Documents opened at least `min_views` times and since `since` (RFC 3339), most viewed first
+    pub async fn frequently_accessed(pool: &SqlitePool, min_views: i64, since: &str, limit: i64) -> CodexResult<Vec<FeedItem>> {
+        let sql = format!(
+            r#"
+            SELECT {} FROM documents INDEXED BY idx_documents_feed_views
+            WHERE is_deleted = 0 AND is_archived = 0 AND view_count > 0
+              AND view_count >= ? AND last_accessed >= ? AND category IS NOT ?
+            ORDER BY view_count DESC, last_accessed DESC
+            LIMIT ?
+            "#,
+            Self::COLUMNS
+        );
+        Ok(sqlx::query_as::<_, FeedItem>(&sql)
+            .bind(min_views)
+            .bind(since)
+            .bind(DIAGNOSTICS_CATEGORY)
+            .bind(limit)
+            .fetch_all(pool)
+            .await?)
+    }
+
+    /// Documents imported since `since` (RFC 3339) and never opened, newest
+    /// first, with the total number of such documents
+    pub async fn unopened_since(pool: &SqlitePool, since: &str, limit: i64) -> CodexResult<(Vec<FeedItem>, i64)> {
+        let sql = format!(
+            r#"
+            SELECT {} FROM documents INDEXED BY idx_documents_feed_unopened
+            WHERE is_deleted = 0 AND is_archived = 0 AND last_accessed IS NULL
+              AND created_at >= ? AND category IS NOT ?
+            ORDER BY created_at DESC, id DESC
+            LIMIT ?
+            "#,
+            Self::COLUMNS
+        );
+        let items = sqlx::query_as::<_, FeedItem>(&sql)
+            .bind(since)
+            .bind(DIAGNOSTICS_CATEGORY)
+            .bind(limit)
+            .fetch_all(pool)
+            .await?;
+
+        let (count,): (i64,) = sqlx::query_as(
+            r#"
+            SELECT COUNT(*) FROM documents INDEXED BY idx_documents_feed_unopened
+            WHERE is_deleted = 0 AND is_archived = 0 AND last_accessed IS NULL
+              AND created_at >= ? AND category IS NOT ?
+            "#,
+        )
+        .bind(since)
+        .bind(DIAGNOSTICS_CATEGORY)
+        .fetch_one(pool)
+        .await?;
+
+        Ok((items, count))
+    }
+}
+
+/// Document link graph operations
+pub struct LinkQueries;
+
+impl LinkQueries {
+    /// Insert a link
+    pub async fn create(pool: &SqlitePool, link: &DocumentLink) -> CodexResult<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO document_links (id, source_id, target_id, target_title, link_type, created_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&link.id)
+        .bind(&link.source_id)
+        .bind(&link.target_id)
+        .bind(&link.target_title)
+        .bind(&link.link_type)
+        .bind(&link.created_at)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Delete a single link
+    pub async fn delete(pool: &SqlitePool, id: &str) -> CodexResult<()> {
+        sqlx::query("DELETE FROM document_links WHERE id = ?")
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Links contained in a document
+    pub async fn get_outgoing(pool: &SqlitePool, source_id: &str) -> CodexResult<Vec<DocumentLink>> {
+        let links = sqlx::query_as::<_, DocumentLink>(
+            "SELECT * FROM document_links WHERE source_id = ? ORDER BY created_at",
+        )
+        .bind(source_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(links)
+    }
+
+    /// Links from other (non-deleted) documents pointing at a document
+    pub async fn get_backlinks(pool: &SqlitePool, target_id: &str) -> CodexResult<Vec<DocumentLink>> {
+        let links = sqlx::query_as::<_, DocumentLink>(
+            r#"
+            SELECT l.* FROM document_links l
+            JOIN documents d ON d.id = l.source_id
+            WHERE l.target_id = ? AND d.is_deleted = false
+            ORDER BY l.created_at
+            "#,
+        )
+        .bind(target_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(links)
+    }
+
+    /// Remove all links contained in a document
+    pub async fn delete_by_source(pool: &SqlitePool, source_id: &str) -> CodexResult<()> {
+        sqlx::query("DELETE FROM document_links WHERE source_id = ?")
+            .bind(source_id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Mark links to a document as unresolved (e.g. after it was deleted or renamed)
+    ///
+    /// Only links written in content are affected; `part_of` links are tied
+    /// to the document rather than its title.
+    pub async fn unresolve_target(pool: &SqlitePool, target_id: &str) -> CodexResult<()> {
+        sqlx::query("UPDATE document_links SET target_id = NULL WHERE target_id = ? AND link_type != 'part_of'")
+            .bind(target_id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Unresolved links, used to re-resolve them when documents appear or are renamed
+    pub async fn get_unresolved(pool: &SqlitePool) -> CodexResult<Vec<DocumentLink>> {
+        let links = sqlx::query_as::<_, DocumentLink>(
+            "SELECT * FROM document_links WHERE target_id IS NULL",
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(links)
+    }
+
+    /// Point a link at a target document
+    pub async fn set_target(pool: &SqlitePool, id: &str, target_id: Option<&str>) -> CodexResult<()> {
+        sqlx::query("UPDATE document_links SET target_id = ? WHERE id = ?")
+            .bind(target_id)
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Documents with no resolved links in either direction
+    pub async fn get_orphaned_documents(pool: &SqlitePool, limit: i64) -> CodexResult<Vec<Document>> {
+        let documents = sqlx::query_as::<_, Document>(
+            r#"
+            SELECT * FROM documents d
+            WHERE d.is_deleted = false AND d.category IS NOT ?
+              AND NOT EXISTS (
+                  SELECT 1 FROM document_links l
+                  JOIN documents t ON t.id = l.target_id
+                  WHERE l.source_id = d.id AND t.is_deleted = false
+              )
+              AND NOT EXISTS (
+                  SELECT 1 FROM document_links l
+                  JOIN documents s ON s.id = l.source_id
+                  WHERE l.target_id = d.id AND s.is_deleted = false
+              )
+            ORDER BY d.created_at DESC, d.id DESC
+            LIMIT ?
+            "#,
+        )
+        .bind(DIAGNOSTICS_CATEGORY)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(documents)
+    }
+}
+
+/// Document template operations
+pub struct TemplateQueries;
+
+impl TemplateQueries {
+    /// Insert a template
+    pub async fn create(pool: &SqlitePool, template: &Template) -> CodexResult<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO templates (
+                id, name, content, default_tags, default_category,
+                content_type, is_builtin, created_at, updated_at
+            )
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&template.id)
+        .bind(&template.name)
+        .bind(&template.content)
+        .bind(&template.default_tags)
+        .bind(&template.default_category)
+        .bind(&template.content_type)
+        .bind(template.is_builtin)
+        .bind(&template.created_at)
+        .bind(&template.updated_at)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Get a template by ID
+    pub async fn get_by_id(pool: &SqlitePool, id: &str) -> CodexResult<Option<Template>> {
+        let template = sqlx::query_as::<_, Template>("SELECT * FROM templates WHERE id = ?")
+            .bind(id)
+            .fetch_optional(pool)
+            .await?;
+
+        Ok(template)
+    }
+
+    /// Get a template by name (case-insensitive)
+    pub async fn get_by_name(pool: &SqlitePool, name: &str) -> CodexResult<Option<Template>> {
+        let template = sqlx::query_as::<_, Template>(
+            "SELECT * FROM templates WHERE name = ? COLLATE NOCASE",
+        )
+        .bind(name)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(template)
+    }
+
+    /// List all templates, built-ins first
+    pub async fn list(pool: &SqlitePool) -> CodexResult<Vec<Template>> {
+        let templates = sqlx::query_as::<_, Template>(
+            "SELECT * FROM templates ORDER BY is_builtin DESC, name COLLATE NOCASE",
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(templates)
+    }
+}
+
+/// Conversation and message operations
+pub struct ConversationQueries;
+
+impl ConversationQueries {
+    /// Insert a conversation
+    pub async fn create(pool: &SqlitePool, conversation: &Conversation) -> CodexResult<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO conversations (id, document_id, title, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&conversation.id)
+        .bind(&conversation.document_id)
+        .bind(&conversation.title)
+        .bind(&conversation.created_at)
+        .bind(&conversation.updated_at)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Get a conversation by ID
+    pub async fn get_by_id(pool: &SqlitePool, id: &str) -> CodexResult<Option<Conversation>> {
+        let conversation = sqlx::query_as::<_, Conversation>("SELECT * FROM conversations WHERE id = ?")
+            .bind(id)
+            .fetch_optional(pool)
+            .await?;
+
+        Ok(conversation)
+    }
+
+    /// Append a message, bumping its conversation's `updated_at`
+    pub async fn add_message(pool: &SqlitePool, message: &ConversationMessage) -> CodexResult<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO conversation_messages (
+                id, conversation_id, document_id, role, content,
+                selection_text, selection_start, selection_end, created_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&message.id)
+        .bind(&message.conversation_id)
+        .bind(&message.document_id)
+        .bind(&message.role)
+        .bind(&message.content)
+        .bind(&message.selection_text)
+        .bind(message.selection_start)
+        .bind(message.selection_end)
+        .bind(&message.created_at)
+        .execute(pool)
+        .await?;
+
+        if let Some(ref conversation_id) = message.conversation_id {
+            sqlx::query("UPDATE conversations SET updated_at = ? WHERE id = ?")
+                .bind(&message.created_at)
+                .bind(conversation_id)
+                .execute(pool)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Most recent messages of a conversation, oldest first
+    pub async fn get_messages(pool: &SqlitePool, conversation_id: &str, limit: i64) -> CodexResult<Vec<ConversationMessage>> {
+        let mut messages = sqlx::query_as::<_, ConversationMessage>(
+            r#"
+            SELECT * FROM conversation_messages
+            WHERE conversation_id = ?
+            ORDER BY created_at DESC, rowid DESC
+            LIMIT ?
+            "#,
+        )
+        .bind(conversation_id)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        messages.reverse();
+        Ok(messages)
+    }
+
+    /// Most recent messages about a document, across conversations, oldest first
+    pub async fn get_document_messages(pool: &SqlitePool, document_id: &str, limit: i64) -> CodexResult<Vec<ConversationMessage>> {
+        let mut messages = sqlx::query_as::<_, ConversationMessage>(
+            r#"
+            SELECT * FROM conversation_messages
+            WHERE document_id = ?
+            ORDER BY created_at DESC, rowid DESC
+            LIMIT ?
+            "#,
+        )
+        .bind(document_id)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        messages.reverse();
+        Ok(messages)
+    }
+}
+
+/// RSS/Atom subscription queries
+pub struct FeedSubscriptionQueries;
+
+impl FeedSubscriptionQueries {
+    /// Insert a subscription
+    pub async fn create(pool: &SqlitePool, feed: &Feed) -> CodexResult<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO feeds (
+                id, name, url, etag, last_modified, last_fetched_at, last_error, created_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&feed.id)
+        .bind(&feed.name)
+        .bind(&feed.url)
+        .bind(&feed.etag)
+        .bind(&feed.last_modified)
+        .bind(&feed.last_fetched_at)
+        .bind(&feed.last_error)
+        .bind(&feed.created_at)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// All subscriptions by name
+    pub async fn get_all(pool: &SqlitePool) -> CodexResult<Vec<Feed>> {
+        let feeds = sqlx::query_as::<_, Feed>("SELECT * FROM feeds ORDER BY name COLLATE NOCASE")
+            .fetch_all(pool)
+            .await?;
+
+        Ok(feeds)
+    }
+
+    /// Get a subscription by id
+    pub async fn get_by_id(pool: &SqlitePool, id: &str) -> CodexResult<Option<Feed>> {
+        let feed = sqlx::query_as::<_, Feed>("SELECT * FROM feeds WHERE id = ?")
+            .bind(id)
+            .fetch_optional(pool)
+            .await?;
+
+        Ok(feed)
+    }
+
+    /// Get a subscription by feed URL
+    pub async fn get_by_url(pool: &SqlitePool, url: &str) -> CodexResult<Option<Feed>> {
+        let feed = sqlx::query_as::<_, Feed>("SELECT * FROM feeds WHERE url = ?")
+            .bind(url)
+            .fetch_optional(pool)
+            .await?;
+
+        Ok(feed)
+    }
+
+    /// Delete a subscription and its seen entries; imported documents are kept
+    pub async fn delete(pool: &SqlitePool, id: &str) -> CodexResult<bool> {
+        let mut tx = pool.begin().await?;
+        sqlx::query("DELETE FROM feed_entries WHERE feed_id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        let result = sqlx::query("DELETE FROM feeds WHERE id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Record the outcome of a fetch
+    ///
+    /// Validators are only replaced when given, so a 304 keeps the old ones.
+    pub async fn record_fetch(
+        pool: &SqlitePool,
+        id: &str,
+        etag: Option<&str>,
+        last_modified: Option<&str>,
+        error: Option<&str>,
+    ) -> CodexResult<()> {
+        sqlx::query(
+            r#"
+            UPDATE feeds SET
+                etag = COALESCE(?, etag),
+                last_modified = COALESCE(?, last_modified),
+                last_fetched_at = ?,
+                last_error = ?
+            WHERE id = ?
+            "#,
+        )
+        .bind(etag)
+        .bind(last_modified)
+        .bind(Utc::now().to_rfc3339())
+        .bind(error)
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Whether an entry of the feed was already imported
+    pub async fn has_entry(pool: &SqlitePool, feed_id: &str, entry_key: &str) -> CodexResult<bool> {
+        let count: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM feed_entries WHERE feed_id = ? AND entry_key = ?",
+        )
+        .bind(feed_id)
+        .bind(entry_key)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(count > 0)
+    }
+
+    /// Remember an entry of the feed, with the document it became if any
+    pub async fn add_entry(
+        pool: &SqlitePool,
+        feed_id: &str,
+        entry_key: &str,
+        document_id: Option<&str>,
+    ) -> CodexResult<()> {
+        sqlx::query(
+            "INSERT OR IGNORE INTO feed_entries (feed_id, entry_key, document_id, imported_at) VALUES (?, ?, ?, ?)",
+        )
+        .bind(feed_id)
+        .bind(entry_key)
+        .bind(document_id)
+        .bind(Utc::now().to_rfc3339())
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+/// Long-running operation checkpoint queries
+pub struct OperationStateQueries;
+
+impl OperationStateQueries {
+    /// Record an operation as running
+    pub async fn create(pool: &SqlitePool, state: &OperationState) -> CodexResult<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO operation_state (
+                id, kind, params, cursor, processed, total, status, error, started_at, updated_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&state.id)
+        .bind(&state.kind)
+        .bind(&state.params)
+        .bind(&state.cursor)
+        .bind(state.processed)
+        .bind(state.total)
+        .bind(&state.status)
+        .bind(&state.error)
+        .bind(&state.started_at)
+        .bind(&state.updated_at)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Get an operation's state by id
+    pub async fn get_by_id(pool: &SqlitePool, id: &str) -> CodexResult<Option<OperationState>> {
+        let state = sqlx::query_as::<_, OperationState>("SELECT * FROM operation_state WHERE id = ?")
+            .bind(id)
+            .fetch_optional(pool)
+            .await?;
+
+        Ok(state)
+    }
+
+    /// Save progress: the last completed item and the count so far
+    pub async fn checkpoint(pool: &SqlitePool, id: &str, cursor: Option<&str>, processed: i64) -> CodexResult<()> {
+        sqlx::query("UPDATE operation_state SET cursor = ?, processed = ?, updated_at = ? WHERE id = ?")
+            .bind(cursor)
+            .bind(processed)
+            .bind(Utc::now().to_rfc3339())
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Mark an operation completed or failed
+    pub async fn finish(pool: &SqlitePool, id: &str, status: &str, error: Option<&str>) -> CodexResult<()> {
+        sqlx::query("UPDATE operation_state SET status = ?, error = ?, updated_at = ? WHERE id = ?")
+            .bind(status)
+            .bind(error)
+            .bind(Utc::now().to_rfc3339())
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Operations still marked running, oldest first
+    ///
+    /// At startup nothing has been started yet, so these were interrupted.
+    pub async fn get_running(pool: &SqlitePool) -> CodexResult<Vec<OperationState>> {
+        let states = sqlx::query_as::<_, OperationState>(
+            "SELECT * FROM operation_state WHERE status = ? ORDER BY started_at, rowid",
+        )
+        .bind(OperationState::STATUS_RUNNING)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(states)
+    }
+
+    /// Drop finished operations last updated more than `max_age_days` ago
+    pub async fn prune_finished(pool: &SqlitePool, max_age_days: i64) -> CodexResult<u64> {
+        let cutoff = (Utc::now() - chrono::Duration::days(max_age_days)).to_rfc3339();
+        let result = sqlx::query("DELETE FROM operation_state WHERE status != ? AND updated_at < ?")
+            .bind(OperationState::STATUS_RUNNING)
+            .bind(cutoff)
+            .execute(pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+/// Operations journal queries
+pub struct OperationQueries;
+
+impl OperationQueries {
+    /// Record an operation
+    pub async fn create(pool: &SqlitePool, operation: &Operation) -> CodexResult<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO operations (
+                id, kind, description, document_ids, before_state, after_state,
+                undoable, undone_at, created_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&operation.id)
+        .bind(&operation.kind)
+        .bind(&operation.description)
+        .bind(&operation.document_ids)
+        .bind(&operation.before_state)
+        .bind(&operation.after_state)
+        .bind(operation.undoable)
+        .bind(&operation.undone_at)
+        .bind(&operation.created_at)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Most recent operations, newest first
+    pub async fn get_recent(pool: &SqlitePool, limit: i64) -> CodexResult<Vec<Operation>> {
+        let operations = sqlx::query_as::<_, Operation>(
+            "SELECT * FROM operations ORDER BY created_at DESC, rowid DESC LIMIT ?",
+        )
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(operations)
+    }
+
+    /// Newest operation that can still be undone
+    pub async fn get_last_undoable(pool: &SqlitePool) -> CodexResult<Option<Operation>> {
+        let operation = sqlx::query_as::<_, Operation>(
+            r#"
+            SELECT * FROM operations
+            WHERE undoable = true AND undone_at IS NULL
+            ORDER BY created_at DESC, rowid DESC
+            LIMIT 1
+            "#,
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(operation)
+    }
+
+    /// Mark an operation as undone
+    pub async fn mark_undone(pool: &SqlitePool, id: &str) -> CodexResult<()> {
+        sqlx::query("UPDATE operations SET undone_at = ? WHERE id = ?")
+            .bind(Utc::now().to_rfc3339())
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Drop operations older than `max_age_days` and all but the newest `keep`
+    pub async fn prune(pool: &SqlitePool, keep: i64, max_age_days: i64) -> CodexResult<u64> {
+        let cutoff = (Utc::now() - chrono::Duration::days(max_age_days)).to_rfc3339();
+        let result = sqlx::query(
+            r#"
+            DELETE FROM operations
+            WHERE created_at < ?
+               OR id NOT IN (
+                   SELECT id FROM operations ORDER BY created_at DESC, rowid DESC LIMIT ?
+               )
+            "#,
+        )
+        .bind(cutoff)
+        .bind(keep)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+/// Automation rule queries
+pub struct RuleQueries;
+
+impl RuleQueries {
+    /// Insert a rule
+    pub async fn create(pool: &SqlitePool, rule: &Rule) -> CodexResult<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO rules (id, name, priority, enabled, conditions, actions, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&rule.id)
+        .bind(&rule.name)
+        .bind(rule.priority)
+        .bind(rule.enabled)
+        .bind(&rule.conditions)
+        .bind(&rule.actions)
+        .bind(&rule.created_at)
+        .bind(&rule.updated_at)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Get a rule by ID
+    pub async fn get_by_id(pool: &SqlitePool, id: &str) -> CodexResult<Option<Rule>> {
+        let rule = sqlx::query_as::<_, Rule>("SELECT * FROM rules WHERE id = ?")
+            .bind(id)
+            .fetch_optional(pool)
+            .await?;
+
+        Ok(rule)
+    }
+
+    /// All rules in the order they run
+    pub async fn get_all(pool: &SqlitePool) -> CodexResult<Vec<Rule>> {
+        let rules = sqlx::query_as::<_, Rule>("SELECT * FROM rules ORDER BY priority, created_at")
+            .fetch_all(pool)
+            .await?;
+
+        Ok(rules)
+    }
+
+    /// Enabled rules in the order they run
+    pub async fn get_enabled(pool: &SqlitePool) -> CodexResult<Vec<Rule>> {
+        let rules = sqlx::query_as::<_, Rule>(
+            "SELECT * FROM rules WHERE enabled = true ORDER BY priority, created_at",
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rules)
+    }
+
+    /// Update a rule; returns false when it does not exist
+    pub async fn update(pool: &SqlitePool, rule: &Rule) -> CodexResult<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE rules SET
+                name = ?, priority = ?, enabled = ?, conditions = ?, actions = ?, updated_at = ?
+            WHERE id = ?
+            "#,
+        )
+        .bind(&rule.name)
+        .bind(rule.priority)
+        .bind(rule.enabled)
+        .bind(&rule.conditions)
+        .bind(&rule.actions)
+        .bind(Utc::now().to_rfc3339())
+        .bind(&rule.id)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Delete a rule; returns false when it does not exist
+    pub async fn delete(pool: &SqlitePool, id: &str) -> CodexResult<bool> {
+        let result = sqlx::query("DELETE FROM rules WHERE id = ?")
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+/// Annotation (highlight) queries
+pub struct AnnotationQueries;
+
+impl AnnotationQueries {
+    /// Insert an annotation
+    pub async fn create(pool: &SqlitePool, annotation: &Annotation) -> CodexResult<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO annotations (
+                id, document_id, start_offset, end_offset, selected_text, color, note, orphaned, created_at, updated_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&annotation.id)
+        .bind(&annotation.document_id)
+        .bind(annotation.start_offset)
+        .bind(annotation.end_offset)
+        .bind(&annotation.selected_text)
+        .bind(&annotation.color)
+        .bind(&annotation.note)
+        .bind(annotation.orphaned)
+        .bind(&annotation.created_at)
+        .bind(&annotation.updated_at)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Get an annotation by id
+    pub async fn get_by_id(pool: &SqlitePool, id: &str) -> CodexResult<Option<Annotation>> {
+        let annotation = sqlx::query_as::<_, Annotation>("SELECT * FROM annotations WHERE id = ?")
+            .bind(id)
+            .fetch_optional(pool)
+            .await?;
+
+        Ok(annotation)
+    }
+
+    /// A document's annotations in reading order
+    pub async fn get_by_document(pool: &SqlitePool, document_id: &str) -> CodexResult<Vec<Annotation>> {
+        let annotations = sqlx::query_as::<_, Annotation>(
+            "SELECT * FROM annotations WHERE document_id = ? ORDER BY start_offset, created_at",
+        )
+        .bind(document_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(annotations)
+    }
+
+    /// Every annotation on a (non-deleted) document, newest first
+    pub async fn get_all(pool: &SqlitePool) -> CodexResult<Vec<Highlight>> {
+        let highlights = sqlx::query_as::<_, Highlight>(
+            r#"
+            SELECT a.*, d.title AS document_title
+            FROM annotations a
+            JOIN documents d ON d.id = a.document_id
+            WHERE d.is_deleted = false
+            ORDER BY a.created_at DESC
+            "#,
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(highlights)
+    }
+
+    /// Most recent annotations across (non-deleted) documents
+    pub async fn get_recent(pool: &SqlitePool, limit: i64) -> CodexResult<Vec<Highlight>> {
+        let highlights = sqlx::query_as::<_, Highlight>(
+            r#"
+            SELECT a.*, d.title AS document_title
+            FROM annotations a
+            JOIN documents d ON d.id = a.document_id
+            WHERE d.is_deleted = false
+            ORDER BY a.created_at DESC
+            LIMIT ?
+            "#,
+        )
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(highlights)
+    }
+
+    /// Annotations whose highlighted text or note match a full-text query
+    pub async fn search(
+        pool: &SqlitePool,
+        query: &str,
+        limit: i64,
+        include_archived: bool,
+    ) -> CodexResult<Vec<AnnotationMatch>> {
+        let sanitized_query = SearchQueries::sanitize_fts_query(query);
+        if sanitized_query.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let matches = sqlx::query_as::<_, AnnotationMatch>(
+            r#"
+            SELECT a.*, d.title AS document_title, -bm25(annotations_fts, 1.0, 2.0) AS score
+            FROM annotations a
+            JOIN annotations_fts ON a.rowid = annotations_fts.rowid
+            JOIN documents d ON d.id = a.document_id
+            WHERE annotations_fts MATCH ? AND d.is_deleted = false AND (d.is_archived = false OR ?)
+            ORDER BY score DESC
+            LIMIT ?
+            "#,
+        )
+        .bind(sanitized_query)
+        .bind(include_archived)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(matches)
+    }
+
+    /// Save an annotation's range, color, note and orphaned flag
+    pub async fn update(pool: &SqlitePool, annotation: &Annotation) -> CodexResult<()> {
+        sqlx::query(
+            r#"
+            UPDATE annotations
+            SET start_offset = ?, end_offset = ?, color = ?, note = ?, orphaned = ?, updated_at = ?
+            WHERE id = ?
+            "#,
+        )
+        .bind(annotation.start_offset)
+        .bind(annotation.end_offset)
+        .bind(&annotation.color)
+        .bind(&annotation.note)
+        .bind(annotation.orphaned)
+        .bind(Utc::now().to_rfc3339())
+        .bind(&annotation.id)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Delete an annotation
+    pub async fn delete(pool: &SqlitePool, id: &str) -> CodexResult<bool> {
+        let result = sqlx::query("DELETE FROM annotations WHERE id = ?")
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+/// Failed import queries
+pub struct FailedImportQueries;
+
+impl FailedImportQueries {
+    /// Record a failed import of `path`, counting an attempt if it failed before
+    pub async fn record(pool: &SqlitePool, path: &str, error: &CodexError) -> CodexResult<FailedImport> {
+        let now = Utc::now().to_rfc3339();
+        // Read every row: stepping a RETURNING statement only once leaves the
+        // upsert's implicit transaction open on the pooled connection
+        let failed = sqlx::query_as::<_, FailedImport>(
+            r#"
+            INSERT INTO failed_imports (id, path, error_code, message, attempts, first_failed_at, last_failed_at)
+            VALUES (?, ?, ?, ?, 1, ?, ?)
+            ON CONFLICT (path) DO UPDATE SET
+                error_code = excluded.error_code,
+                message = excluded.message,
+                attempts = attempts + 1,
+                last_failed_at = excluded.last_failed_at
+            RETURNING *
+            "#,
+        )
+        .bind(uuid::Uuid::new_v4().to_string())
+        .bind(path)
+        .bind(error.code())
+        .bind(error.to_string())
+        .bind(&now)
+        .bind(&now)
+        .fetch_all(pool)
+        .await?
+        .pop()
+        .ok_or_else(|| CodexError::internal("Failed import upsert returned no row"))?;
+
+        Ok(failed)
+    }
+
+    /// All failed imports, most recent failure first
+    pub async fn get_all(pool: &SqlitePool) -> CodexResult<Vec<FailedImport>> {
+        let failed = sqlx::query_as::<_, FailedImport>("SELECT * FROM failed_imports ORDER BY last_failed_at DESC, path")
+            .fetch_all(pool)
+            .await?;
+
+        Ok(failed)
+    }
+
+    /// Failed imports with the given ids; unknown ids are skipped
+    pub async fn get_by_ids(pool: &SqlitePool, ids: &[String]) -> CodexResult<Vec<FailedImport>> {
+        let failed = sqlx::query_as::<_, FailedImport>(
+            "SELECT * FROM failed_imports WHERE id IN (SELECT value FROM json_each(?)) ORDER BY path",
+        )
+        .bind(serde_json::to_string(ids)?)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(failed)
+    }
+
+    /// Forget the failure of `path`, e.g. after it imported; returns false when none was recorded
+    pub async fn clear(pool: &SqlitePool, path: &str) -> CodexResult<bool> {
+        let result = sqlx::query("DELETE FROM failed_imports WHERE path = ?")
+            .bind(path)
+            .execute(pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Delete a failed import entry; returns false when it does not exist
+    pub async fn delete(pool: &SqlitePool, id: &str) -> CodexResult<bool> {
+        let result = sqlx::query("DELETE FROM failed_imports WHERE id = ?")
+            .bind(id)
+            .execute(pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+/// Reading session queries
+///
+/// Session timestamps are written with whole seconds, so they compare as strings.
+pub struct ReadingSessionQueries;
+
+impl ReadingSessionQueries {
+    fn timestamp(at: chrono::DateTime<Utc>) -> String {
+        at.to_rfc3339_opts(chrono::SecondsFormat::Secs, false)
+    }
+
+    fn parse(stamp: &str) -> CodexResult<chrono::DateTime<Utc>> {
+        chrono::DateTime::parse_from_rfc3339(stamp)
+            .map(|at| at.with_timezone(&Utc))
+            .map_err(|e| CodexError::internal(format!("Invalid session timestamp {}: {}", stamp, e)))
+    }
+
+    /// Open a session for a document
+    pub async fn start(pool: &SqlitePool, document_id: &str, started_at: chrono::DateTime<Utc>) -> CodexResult<ReadingSession> {
+        let exists: Option<i64> = sqlx::query_scalar("SELECT 1 FROM documents WHERE id = ? AND is_deleted = false")
+            .bind(document_id)
+            .fetch_optional(pool)
+            .await?;
+        if exists.is_none() {
+            return Err(CodexError::not_found(format!("Document {}", document_id)));
+        }
+
+        // Stepping a RETURNING statement only once leaves it, and with it the
+        // insert's implicit transaction, open on the pooled connection
+        let session = sqlx::query_as::<_, ReadingSession>(
+            "INSERT INTO reading_sessions (id, document_id, started_at) VALUES (?, ?, ?) RETURNING *",
+        )
+        .bind(uuid::Uuid::new_v4().to_string())
+        .bind(document_id)
+        .bind(Self::timestamp(started_at))
+        .fetch_all(pool)
+        .await?
+        .pop()
+        .ok_or_else(|| CodexError::internal("Reading session insert returned no row"))?;
+
+        Ok(session)
+    }
+
+    /// End an open session
+    ///
+    /// Sessions shorter than `min_seconds` are deleted and None is returned.
+    /// Otherwise the session absorbs ended sessions of the same document it
+    /// overlaps, and the merged session is returned.
+    pub async fn finish(
+        pool: &SqlitePool,
+        id: &str,
+        ended_at: chrono::DateTime<Utc>,
+        min_seconds: i64,
+    ) -> CodexResult<Option<ReadingSession>> {
+        let mut tx = pool.begin().await?;
+
+        let session = sqlx::query_as::<_, ReadingSession>("SELECT * FROM reading_sessions WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| CodexError::not_found(format!("Reading session {}", id)))?;
+        if session.ended_at.is_some() {
+            return Err(CodexError::validation(format!("Reading session {} has already ended", id)));
+        }
+
+        let mut started_at = Self::parse(&session.started_at)?;
+        let mut ended_at = ended_at.max(started_at);
+        if (ended_at - started_at).num_seconds() < min_seconds {
+            sqlx::query("DELETE FROM reading_sessions WHERE id = ?").bind(id).execute(&mut *tx).await?;
+            tx.commit().await?;
+            return Ok(None);
+        }
+
+        let overlapping = sqlx::query_as::<_, ReadingSession>(
+            r#"
+            SELECT * FROM reading_sessions
+            WHERE document_id = ? AND id != ? AND ended_at IS NOT NULL
+              AND started_at <= ? AND ended_at >= ?
+            "#,
+        )
+        .bind(&session.document_id)
+        .bind(id)
+        .bind(Self::timestamp(ended_at))
+        .bind(&session.started_at)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        for other in &overlapping {
+            started_at = started_at.min(Self::parse(&other.started_at)?);
+            if let Some(other_end) = &other.ended_at {
+                ended_at = ended_at.max(Self::parse(other_end)?);
+            }
+            sqlx::query("DELETE FROM reading_sessions WHERE id = ?").bind(&other.id).execute(&mut *tx).await?;
+        }
+
+        let finished = sqlx::query_as::<_, ReadingSession>(
+            "UPDATE reading_sessions SET started_at = ?, ended_at = ?, duration_seconds = ? WHERE id = ? RETURNING *",
+        )
+        .bind(Self::timestamp(started_at))
+        .bind(Self::timestamp(ended_at))
+        .bind((ended_at - started_at).num_seconds())
+        .bind(id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(Some(finished))
+    }
+
+    /// Local calendar days with an ended session, most recent first
+    pub async fn reading_days(pool: &SqlitePool) -> CodexResult<Vec<chrono::NaiveDate>> {
+        let days: Vec<chrono::NaiveDate> = sqlx::query_scalar(
+            r#"
+            SELECT DISTINCT date(started_at, 'localtime') AS day FROM reading_sessions
+            WHERE ended_at IS NOT NULL
+            ORDER BY day DESC
+            "#,
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(days)
+    }
+
+    /// Seconds read in sessions that started at or after `since`
+    pub async fn seconds_since(pool: &SqlitePool, since: chrono::DateTime<Utc>) -> CodexResult<i64> {
+        let seconds: Option<i64> = sqlx::query_scalar(
+            "SELECT SUM(duration_seconds) FROM reading_sessions WHERE ended_at IS NOT NULL AND started_at >= ?",
+        )
+        .bind(Self::timestamp(since))
+        .fetch_one(pool)
+        .await?;
+
+        Ok(seconds.unwrap_or(0))
+    }
+}
+
+/// Attachment store queries
+pub struct AttachmentQueries;
+
+impl AttachmentQueries {
+    /// Record a stored file, keeping the existing row when the same bytes were stored before
+    pub async fn create_or_get(pool: &SqlitePool, id: &str, size: i64, original_name: &str) -> CodexResult<Attachment> {
+        sqlx::query("INSERT OR IGNORE INTO attachments (id, size, original_name, created_at) VALUES (?, ?, ?, ?)")
+            .bind(id)
+            .bind(size)
+            .bind(original_name)
+            .bind(Utc::now().to_rfc3339())
+            .execute(pool)
+            .await?;
+
+        let attachment = sqlx::query_as::<_, Attachment>("SELECT * FROM attachments WHERE id = ?")
+            .bind(id)
+            .fetch_one(pool)
+            .await?;
+
+        Ok(attachment)
+    }
+
+    /// Point a document at its stored original
+    pub async fn set_for_document(pool: &SqlitePool, document_id: &str, attachment_id: &str) -> CodexResult<()> {
+        let result = sqlx::query("UPDATE documents SET attachment_id = ? WHERE id = ?")
+            .bind(attachment_id)
+            .bind(document_id)
+            .execute(pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(CodexError::not_found(format!("Document {}", document_id)));
+        }
+        Ok(())
+    }
+
+    /// Stored original of a document, including soft-deleted ones
+    pub async fn get_for_document(pool: &SqlitePool, document_id: &str) -> CodexResult<Option<Attachment>> {
+        let attachment = sqlx::query_as::<_, Attachment>(
+            "SELECT a.* FROM attachments a JOIN documents d ON d.attachment_id = a.id WHERE d.id = ?",
+        )
+        .bind(document_id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(attachment)
+    }
+
+    /// Ids of all stored attachments
+    pub async fn all_ids(pool: &SqlitePool) -> CodexResult<Vec<String>> {
+        let ids = sqlx::query_scalar("SELECT id FROM attachments").fetch_all(pool).await?;
+        Ok(ids)
+    }
+
+    /// Attachments no document refers to any more
+    ///
+    /// Soft-deleted documents still count, so undoing a delete keeps its original.
+    pub async fn get_unreferenced(pool: &SqlitePool) -> CodexResult<Vec<Attachment>> {
+        let attachments = sqlx::query_as::<_, Attachment>(
+            "SELECT * FROM attachments a WHERE NOT EXISTS (SELECT 1 FROM documents d WHERE d.attachment_id = a.id)",
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(attachments)
+    }
+
+    /// Forget an attachment unless a document started referring to it meanwhile;
+    /// returns false when it was kept
+    pub async fn delete_if_unreferenced(pool: &SqlitePool, id: &str) -> CodexResult<bool> {
+        let result = sqlx::query(
+            "DELETE FROM attachments WHERE id = ? AND NOT EXISTS (SELECT 1 FROM documents d WHERE d.attachment_id = ?)",
+        )
+        .bind(id)
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+/// AI audit trail queries
+pub struct AiAuditQueries;
+
+impl AiAuditQueries {
+    /// Record an AI operation
+    pub async fn create(pool: &SqlitePool, entry: &AiAuditEntry) -> CodexResult<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO ai_audit (id, document_id, operation, model, prompt_hash, output_hash, duration_ms, timestamp, prompt_text, output_text)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#
+        )
+        .bind(&entry.id)
+        .bind(&entry.document_id)
+        .bind(&entry.operation)
+        .bind(&entry.model)
+        .bind(&entry.prompt_hash)
+        .bind(&entry.output_hash)
+        .bind(entry.duration_ms)
+        .bind(&entry.timestamp)
+        .bind(&entry.prompt_text)
+        .bind(&entry.output_text)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// AI operations recorded for a document, newest first
+    pub async fn get_for_document(pool: &SqlitePool, document_id: &str) -> CodexResult<Vec<AiAuditEntry>> {
+        let entries = sqlx::query_as::<_, AiAuditEntry>(
+            "SELECT * FROM ai_audit WHERE document_id = ? ORDER BY timestamp DESC, id",
+        )
+        .bind(document_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(entries)
+    }
+
+    /// Number of recorded AI operations
+    pub async fn count(pool: &SqlitePool) -> CodexResult<i64> {
+        let count = sqlx::query_scalar("SELECT COUNT(*) FROM ai_audit").fetch_one(pool).await?;
+        Ok(count)
+    }
+
+    /// Delete entries recorded before `before`, and entries of documents that
+    /// no longer exist recorded before `orphans_before`; returns the number deleted
+    ///
+    /// Entries are written while a document is imported, before it is
+    /// stored, so recent entries without a document are kept.
+    pub async fn prune(pool: &SqlitePool, before: &str, orphans_before: &str) -> CodexResult<u64> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM ai_audit
+            WHERE timestamp < ?
+               OR (timestamp < ? AND NOT EXISTS (SELECT 1 FROM documents d WHERE d.id = ai_audit.document_id))
+            "#
+        )
+        .bind(before)
+        .bind(orphans_before)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_database;
+
+    #[tokio::test]
+    async fn test_repeated_semantic_search_hits_vector_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = test_database(&dir).await;
+        let pool = db.pool();
+
+        // JSON-only embeddings, as stored before binary vectors were added
+        for i in 0..150 {
+            let document = Document::new(format!("Doc {}", i), "body".to_string(), "text/plain".to_string());
+            DocumentQueries::create(pool, &document, db.compression()).await.unwrap();
+            for chunk in 0..2 {
+                let vector: Vec<f32> = (0..384).map(|d| ((i * 7 + chunk * 3 + d) % 17) as f32 / 17.0).collect();
+                let embedding = Embedding::new(document.id.clone(), vector, "test".to_string(), chunk as i64, String::new(), 0, 0);
+                EmbeddingQueries::create(pool, &embedding).await.unwrap();
+            }
+        }
+        let query_vector: Vec<f32> = (0..384).map(|d| (d % 5) as f32).collect();
+
+        let first = SearchQueries::search_semantic(pool, &query_vector, Some(200), Some(-1.0)).await.unwrap();
+        assert_eq!(EmbeddingQueries::get_cached_vectors(pool).await.unwrap().len(), 300);
+        assert!(EmbeddingQueries::get_uncached_vectors(pool).await.unwrap().is_empty());
+
+        // Blank the stored vectors: only the cache can still answer the search
+        sqlx::query("UPDATE embeddings SET vector = '[]'").execute(pool).await.unwrap();
+        let second = SearchQueries::search_semantic(pool, &query_vector, Some(200), Some(-1.0)).await.unwrap();
+
+        let ids = |results: &[(Document, f32)]| {
+            let mut ids: Vec<String> = results.iter().map(|(d, _)| d.id.clone()).collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(ids(&first), ids(&second));
+        assert!((first[0].1 - second[0].1).abs() < 1e-6);
+
+        let accesses: i64 = sqlx::query_scalar("SELECT MIN(access_count) FROM vector_cache")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        assert_eq!(accesses, 2);
+    }
+
+    #[tokio::test]
+    async fn test_vector_cache_cleanup_and_eviction() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = test_database(&dir).await;
+        let pool = db.pool();
+
+        let mut ids = Vec::new();
+        for i in 0..3 {
+            let document = Document::new(format!("Doc {}", i), "body".to_string(), "text/plain".to_string());
+            DocumentQueries::create(pool, &document, db.compression()).await.unwrap();
+            EmbeddingQueries::cache_vector(pool, &document.id, 0, &[i as f32, 1.0], "test").await.unwrap();
+            ids.push(document.id);
+        }
+        EmbeddingQueries::update_cache_access(pool, &ids[2]).await.unwrap();
+
+        assert_eq!(EmbeddingQueries::cleanup_cache(pool, 2).await.unwrap(), 1);
+        EmbeddingQueries::delete_by_document(pool, &ids[2]).await.unwrap();
+
+        let remaining = EmbeddingQueries::get_cached_vectors(pool).await.unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].0, ids[1]);
+    }
+
+    #[tokio::test]
+    async fn test_index_health_counts_offenders() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = test_database(&dir).await;
+        let pool = db.pool();
+
+        let mut ids = Vec::new();
+        let chunks = [Some((4, "test")), None, Some((4, "test")), Some((8, "test")), Some((4, "old-model"))];
+        for (i, chunk) in chunks.into_iter().enumerate() {
+            let document = Document::new(format!("Doc {}", i), "body".to_string(), "text/plain".to_string());
+            DocumentQueries::create(pool, &document, db.compression()).await.unwrap();
+            if let Some((dimensions, model)) = chunk {
+                let embedding = Embedding::new(document.id.clone(), vec![0.5; dimensions], model.to_string(), 0, String::new(), 0, 0);
+                EmbeddingQueries::create(pool, &embedding).await.unwrap();
+            }
+            ids.push(document.id);
+        }
+        // Edited after it was embedded
+        sqlx::query("UPDATE documents SET updated_at = ? WHERE id = ?")
+            .bind((Utc::now() + chrono::Duration::hours(1)).to_rfc3339())
+            .bind(&ids[2])
+            .execute(pool)
+            .await
+            .unwrap();
+
+        let counts = EmbeddingQueries::index_health_counts(pool, "test", 4).await.unwrap();
+        assert_eq!(counts, (5, 1, 1, 2));
+
+        let mut offenders = EmbeddingQueries::get_unhealthy_document_ids(pool, "test", 4).await.unwrap();
+        offenders.sort();
+        let mut expected = vec![ids[1].clone(), ids[2].clone(), ids[3].clone(), ids[4].clone()];
+        expected.sort();
+        assert_eq!(offenders, expected);
+    }
+
+    #[tokio::test]
+    async fn test_operation_journal_undo_and_prune() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = test_database(&dir).await;
+        let pool = db.pool();
+
+        let document = Document::new("Doc".to_string(), "body".to_string(), "text/plain".to_string());
+        DocumentQueries::create(pool, &document, db.compression()).await.unwrap();
+        DocumentQueries::delete(pool, &document.id).await.unwrap();
+
+        let before = std::collections::BTreeMap::from([(document.id.clone(), false)]);
+        let after = std::collections::BTreeMap::from([(document.id.clone(), true)]);
+        let delete = Operation::new(Operation::KIND_DELETE, "Deleted \"Doc\"".to_string(), before, after);
+        OperationQueries::create(pool, &delete).await.unwrap();
+        let import = Operation::irreversible(Operation::KIND_IMPORT, "Imported 1 document".to_string(), std::slice::from_ref(&document.id));
+        OperationQueries::create(pool, &import).await.unwrap();
+
+        // The newer import cannot be undone, so the delete is next in line
+        let last = OperationQueries::get_last_undoable(pool).await.unwrap().unwrap();
+        assert_eq!(last.id, delete.id);
+        assert_eq!(last.get_document_ids(), vec![document.id.clone()]);
+        assert!(!last.get_before_state::<bool>().unwrap()[&document.id]);
+
+        assert!(DocumentQueries::restore(pool, &document.id).await.unwrap());
+        assert!(!DocumentQueries::restore(pool, &document.id).await.unwrap());
+        OperationQueries::mark_undone(pool, &delete.id).await.unwrap();
+        assert!(OperationQueries::get_last_undoable(pool).await.unwrap().is_none());
+        assert!(DocumentQueries::get_by_id(pool, &document.id).await.unwrap().is_some());
+
+        assert_eq!(OperationQueries::prune(pool, 1, 90).await.unwrap(), 1);
+        let remaining = OperationQueries::get_recent(pool, 10).await.unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].id, import.id);
+    }
+
+    #[tokio::test]
+    async fn test_update_organization_resyncs_full_text() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = test_database(&dir).await;
+        let pool = db.pool();
+
+        let mut documents = Vec::new();
+        for i in 0..3 {
+            let document = Document::new(format!("Doc {}", i), "body".to_string(), "text/plain".to_string());
+            DocumentQueries::create(pool, &document, db.compression()).await.unwrap();
+            documents.push(document);
+        }
+        for document in &mut documents[..2] {
+            document.category = Some("Astronomy".to_string());
+            document.set_tags(vec!["nebula".to_string()]);
+            document.is_favorite = true;
+        }
+        DocumentQueries::update_organization(pool, &documents[..2]).await.unwrap();
+
+        let matches = |query: &'static str| async move {
+            let mut ids: Vec<String> = SearchQueries::search(pool, query, None)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|d| d.id)
+                .collect();
+            ids.sort();
+            ids
+        };
+        let mut expected = vec![documents[0].id.clone(), documents[1].id.clone()];
+        expected.sort();
+        assert_eq!(matches("astronomy").await, expected);
+        assert_eq!(matches("nebula").await, expected);
+        assert_eq!(DocumentQueries::get_favorites(pool, 10).await.unwrap().len(), 2);
+
+        // Flag-only updates leave the full-text row intact
+        DocumentQueries::set_archived(pool, &documents[2].id, true).await.unwrap();
+        assert_eq!(matches("body").await.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_merge_moves_references_and_unmerge_restores_them() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = test_database(&dir).await;
+        let pool = db.pool();
+
+        let primary = Document::new("Primary".to_string(), "first".to_string(), "text/plain".to_string());
+        let secondary = Document::new("Secondary".to_string(), "second".to_string(), "text/plain".to_string());
+        let linking = Document::new("Linking".to_string(), "[[Secondary]]".to_string(), "text/plain".to_string());
+        for document in [&primary, &secondary, &linking] {
+            DocumentQueries::create(pool, document, db.compression()).await.unwrap();
+        }
+        sqlx::query("INSERT INTO bookmarks (id, document_id, title) VALUES ('b1', ?, 'mark')")
+            .bind(&secondary.id)
+            .execute(pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO collections (id, name) VALUES ('c1', 'Reading')")
+            .execute(pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO document_collections (document_id, collection_id) VALUES (?, 'c1')")
+            .bind(&secondary.id)
+            .execute(pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO document_links (id, source_id, target_id, target_title, link_type) VALUES ('l1', ?, ?, 'Secondary', 'wiki')")
+            .bind(&linking.id)
+            .bind(&secondary.id)
+            .execute(pool)
+            .await
+            .unwrap();
+
+        let mut merged = primary.clone();
+        merged.content = "first\n\nsecond".to_string();
+        let references = DocumentQueries::merge(pool, &merged, &secondary.id, db.compression()).await.unwrap();
+        assert_eq!(references.bookmarks, vec!["b1".to_string()]);
+        assert_eq!(references.links, vec!["l1".to_string()]);
+        assert_eq!(references.collections, vec!["c1".to_string()]);
+
+        let owner = |table: &'static str, column: &'static str, id: &'static str| async move {
+            sqlx::query(&format!("SELECT {} FROM {} WHERE id = ?", column, table))
+                .bind(id)
+                .fetch_one(pool)
+                .await
+                .unwrap()
+                .get::<String, _>(0)
+        };
+        assert_eq!(owner("bookmarks", "document_id", "b1").await, primary.id);
+        assert_eq!(owner("document_links", "target_id", "l1").await, primary.id);
+        assert!(DocumentQueries::get_by_id(pool, &secondary.id).await.unwrap().is_none());
+        assert_eq!(DocumentQueries::get_by_id(pool, &primary.id).await.unwrap().unwrap().content, merged.content);
+
+        DocumentQueries::unmerge(pool, &primary.id, &secondary.id, &references).await.unwrap();
+        assert_eq!(owner("bookmarks", "document_id", "b1").await, secondary.id);
+        assert_eq!(owner("document_links", "target_id", "l1").await, secondary.id);
+        assert!(DocumentQueries::get_by_id(pool, &secondary.id).await.unwrap().is_some());
+        let members: Vec<String> = sqlx::query("SELECT document_id FROM document_collections WHERE collection_id = 'c1'")
+            .fetch_all(pool)
+            .await
+            .unwrap()
+            .iter()
+            .map(|r| r.get(0))
+            .collect();
+        assert_eq!(members, vec![secondary.id.clone()]);
+    }
+
+    #[tokio::test]
+    async fn test_fts_vocabulary() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = test_database(&dir).await;
+        let pool = db.pool();
+
+        let document = Document::new("Quantum mechanics".to_string(), "Waves and particles".to_string(), "text/plain".to_string());
+        DocumentQueries::create(pool, &document, db.compression()).await.unwrap();
+
+        assert!(SearchQueries::vocabulary_contains(pool, "Quantum").await.unwrap());
+        assert!(!SearchQueries::vocabulary_contains(pool, "quantun").await.unwrap());
+        let terms = SearchQueries::vocabulary_terms(pool, 6, 8, 100).await.unwrap();
+        assert!(terms.contains(&("quantum".to_string(), 1)));
+        assert!(terms.iter().all(|(term, _)| (6..=8).contains(&term.len())));
+    }
+
+    #[tokio::test]
+    async fn test_semantic_chunks_per_document() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = test_database(&dir).await;
+        let pool = db.pool();
+
+        // A document matching the query in three chunks, and one matching in none
+        let book = Document::new("Three chunks".to_string(), "body".to_string(), "text/plain".to_string());
+        let other = Document::new("Unrelated".to_string(), "body".to_string(), "text/plain".to_string());
+        for document in [&book, &other] {
+            DocumentQueries::create(pool, document, db.compression()).await.unwrap();
+        }
+        for (chunk, vector) in [vec![1.0, 0.0], vec![0.9, 0.1], vec![0.8, 0.2]].into_iter().enumerate() {
+            let embedding = Embedding::new(book.id.clone(), vector, "test".to_string(), chunk as i64, format!("chunk {}", chunk), 0, 0);
+            EmbeddingQueries::create(pool, &embedding).await.unwrap();
+        }
+        let embedding = Embedding::new(other.id.clone(), vec![0.0, 1.0], "test".to_string(), 0, String::new(), 0, 0);
+        EmbeddingQueries::create(pool, &embedding).await.unwrap();
+
+        let chunks = SearchQueries::search_semantic_chunks(pool, &[1.0, 0.0], Some(10), Some(0.5)).await.unwrap();
+        let indices: Vec<i64> = chunks.iter().map(|(_, _, chunk)| *chunk).collect();
+        assert_eq!(indices, vec![0, 1, 2]);
+        assert!(chunks.iter().all(|(doc, _, _)| doc.id == book.id));
+
+        let documents = SearchQueries::search_semantic(pool, &[1.0, 0.0], Some(10), Some(0.5)).await.unwrap();
+        assert_eq!(documents.len(), 1);
+        assert!((documents[0].1 - 1.0).abs() < 1e-6);
+
+        let hybrid = SearchQueries::search_hybrid_chunks(
+            pool,
+            "three",
+            Some(&[1.0, 0.0]),
+            Some(10),
+            FusionMethod::default(),
+            None,
+            None,
+            &SearchFilter::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(hybrid.len(), 3);
+        assert_eq!(hybrid[0].2, Some(0));
+        assert_eq!(SearchQueries::search_hybrid(pool, "three", Some(&[1.0, 0.0]), Some(10), None, None).await.unwrap().len(), 1);
+
+        assert_eq!(EmbeddingQueries::get_chunk_text(pool, &book.id, 2).await.unwrap().as_deref(), Some("chunk 2"));
+    }
+
+    #[tokio::test]
+    async fn test_home_feed_sections_use_feed_indexes() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = test_database(&dir).await;
+        let pool = db.pool();
+
+        let unopened = Document::new("Unopened".to_string(), "body".to_string(), "text/plain".to_string());
+        let mut edited = Document::new("Edited".to_string(), "body".to_string(), "text/plain".to_string());
+        edited.updated_at = Utc::now() + chrono::Duration::seconds(5);
+        let mut read = Document::new("Read".to_string(), "body".to_string(), "text/plain".to_string());
+        read.last_accessed = Some(Utc::now().to_rfc3339());
+        read.view_count = 4;
+        let mut archived = Document::new("Archived".to_string(), "body".to_string(), "text/plain".to_string());
+        archived.is_archived = true;
+        for document in [&unopened, &edited, &read, &archived] {
+            DocumentQueries::create(pool, document, db.compression()).await.unwrap();
+        }
+
+        let week_ago = (Utc::now() - chrono::Duration::days(7)).to_rfc3339();
+        let (items, count) = FeedQueries::unopened_since(pool, &week_ago, 10).await.unwrap();
+        let titles: Vec<&str> = items.iter().map(|i| i.title.as_str()).collect();
+        assert_eq!(count, 2);
+        assert!(titles.contains(&"Unopened") && titles.contains(&"Edited"));
+
+        let modified = FeedQueries::recently_modified(pool, 10).await.unwrap();
+        assert_eq!(modified.len(), 1);
+        assert_eq!(modified[0].title, "Edited");
+        assert_eq!(FeedQueries::recently_read(pool, 10).await.unwrap()[0].title, "Read");
+        assert_eq!(FeedQueries::frequently_accessed(pool, 3, &week_ago, 10).await.unwrap().len(), 1);
+        assert!(FeedQueries::frequently_accessed(pool, 5, &week_ago, 10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rebuild_cache_drops_stale_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = test_database(&dir).await;
+        let pool = db.pool();
+
+        let live = Document::new("Live".to_string(), "body".to_string(), "text/plain".to_string());
+        let deleted = Document::new("Deleted".to_string(), "body".to_string(), "text/plain".to_string());
+        for document in [&live, &deleted] {
+            DocumentQueries::create(pool, document, db.compression()).await.unwrap();
+            let embedding = Embedding::new(document.id.clone(), vec![1.0, 0.0], "test".to_string(), 0, String::new(), 0, 0);
+            EmbeddingQueries::create(pool, &embedding).await.unwrap();
+            EmbeddingQueries::cache_vector(pool, &document.id, 0, &[1.0, 0.0], "test").await.unwrap();
+        }
+        // A soft delete that bypassed eviction leaves a tombstone behind
+        DocumentQueries::delete(pool, &deleted.id).await.unwrap();
+
+        let (entries, stale, bytes) = EmbeddingQueries::cache_compaction_counts(pool).await.unwrap();
+        assert_eq!((entries, stale), (2, 1));
+        assert!(bytes > 0);
+        assert_eq!(db.compact_vector_cache(false).await.unwrap(), Some(1));
+
+        let cached = EmbeddingQueries::get_cached_vectors(pool).await.unwrap();
+        assert_eq!(cached.len(), 1);
+        assert_eq!(cached[0].0, live.id);
+        let stats = db.vector_cache_stats().await.unwrap();
+        assert_eq!(stats.stale_entries, 0);
+        assert!(stats.last_compaction.is_some());
+        assert_eq!(db.compact_vector_cache(false).await.unwrap(), None);
+
+        // The compacted cache still enforces its unique key
+        EmbeddingQueries::cache_vector(pool, &live.id, 0, &[0.0, 1.0], "test").await.unwrap();
+        assert_eq!(EmbeddingQueries::get_cached_vectors(pool).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_touch_vectors_reads_embeddings_or_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = test_database(&dir).await;
+        let pool = db.pool();
+
+        let document = Document::new("Vectors".to_string(), "body".to_string(), "text/plain".to_string());
+        DocumentQueries::create(pool, &document, db.compression()).await.unwrap();
+        for chunk_index in 0..3 {
+            let embedding = Embedding::new(document.id.clone(), vec![1.0, 0.0], "test".to_string(), chunk_index, format!("chunk {}", chunk_index), 0, 0);
+            EmbeddingQueries::create_with_binary(pool, &embedding).await.unwrap();
+        }
+        EmbeddingQueries::cache_vector(pool, &document.id, 0, &[1.0, 0.0], "test").await.unwrap();
+
+        assert_eq!(EmbeddingQueries::touch_vectors(pool, false).await.unwrap(), 3);
+        assert_eq!(EmbeddingQueries::touch_vectors(pool, true).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_feed_subscriptions_keep_validators_and_seen_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = test_database(&dir).await;
+        let pool = db.pool();
+
+        let feed = Feed::new("Systems Blog".to_string(), "https://blog.example.com/feed.xml".to_string());
+        FeedSubscriptionQueries::create(pool, &feed).await.unwrap();
+        assert!(FeedSubscriptionQueries::get_by_url(pool, &feed.url).await.unwrap().is_some());
+
+        FeedSubscriptionQueries::record_fetch(pool, &feed.id, Some("\"v1\""), Some("Tue, 10 Oct 2023 08:00:00 GMT"), None).await.unwrap();
+        // A 304 carries no validators and must not erase the stored ones
+        FeedSubscriptionQueries::record_fetch(pool, &feed.id, None, None, None).await.unwrap();
+        let stored = FeedSubscriptionQueries::get_by_id(pool, &feed.id).await.unwrap().unwrap();
+        assert_eq!(stored.etag.as_deref(), Some("\"v1\""));
+        assert!(stored.last_fetched_at.is_some());
+
+        assert!(!FeedSubscriptionQueries::has_entry(pool, &feed.id, "post-42").await.unwrap());
+        FeedSubscriptionQueries::add_entry(pool, &feed.id, "post-42", None).await.unwrap();
+        FeedSubscriptionQueries::add_entry(pool, &feed.id, "post-42", None).await.unwrap();
+        assert!(FeedSubscriptionQueries::has_entry(pool, &feed.id, "post-42").await.unwrap());
+
+        assert!(FeedSubscriptionQueries::delete(pool, &feed.id).await.unwrap());
+        assert!(!FeedSubscriptionQueries::has_entry(pool, &feed.id, "post-42").await.unwrap());
+        assert!(FeedSubscriptionQueries::get_all(pool).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_annotation_crud_and_highlights() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = test_database(&dir).await;
+        let pool = db.pool();
+
+        let document = Document::new("Notes".to_string(), "alpha beta gamma".to_string(), "text/plain".to_string());
+        DocumentQueries::create(pool, &document, db.compression()).await.unwrap();
+
+        let mut later = Annotation::new(document.id.clone(), 11, 16, "gamma".to_string());
+        later.created_at = "2024-01-02T00:00:00+00:00".to_string();
+        let mut earlier = Annotation::new(document.id.clone(), 0, 5, "alpha".to_string());
+        earlier.created_at = "2024-01-01T00:00:00+00:00".to_string();
+        AnnotationQueries::create(pool, &later).await.unwrap();
+        AnnotationQueries::create(pool, &earlier).await.unwrap();
+
+        let ordered: Vec<i64> = AnnotationQueries::get_by_document(pool, &document.id)
+            .await
+            .unwrap()
+            .iter()
+            .map(|a| a.start_offset)
+            .collect();
+        assert_eq!(ordered, vec![0, 11]);
+
+        earlier.note = Some("first word".to_string());
+        earlier.color = "green".to_string();
+        AnnotationQueries::update(pool, &earlier).await.unwrap();
+        let saved = AnnotationQueries::get_by_id(pool, &earlier.id).await.unwrap().unwrap();
+        assert_eq!(saved.note.as_deref(), Some("first word"));
+        assert_eq!(saved.color, "green");
+        assert!(!saved.orphaned);
+
+        let highlights = AnnotationQueries::get_recent(pool, 10).await.unwrap();
+        assert_eq!(highlights.len(), 2);
+        assert_eq!(highlights[0].annotation.id, later.id);
+        assert_eq!(highlights[0].document_title, "Notes");
+
+        assert!(AnnotationQueries::delete(pool, &later.id).await.unwrap());
+        assert!(!AnnotationQueries::delete(pool, &later.id).await.unwrap());
+        assert_eq!(AnnotationQueries::get_recent(pool, 10).await.unwrap().len(), 1);
+
+        // Notes are searchable, and deleted annotations leave the index
+        let found = AnnotationQueries::search(pool, "first word", 10, false).await.unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].annotation.id, earlier.id);
+        assert!(AnnotationQueries::search(pool, "gamma", 10, false).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_filtered_search_pushes_predicates_into_fts() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = test_database(&dir).await;
+        let pool = db.pool();
+
+        for i in 0..30 {
+            let category = if i < 4 { "Embedded" } else { "General" };
+            let mut document =
+                Document::new(format!("Rust notes {}", i), "ownership and borrowing".to_string(), "text/plain".to_string());
+            document.category = Some(category.to_string());
+            document.author = Some(if i % 2 == 0 { "Ada" } else { "Grace" }.to_string());
+            document.is_favorite = i % 3 == 0;
+            document.is_archived = i == 2;
+            if i % 4 == 0 {
+                document.tags = Some(r#"["Systems", "rust"]"#.to_string());
+            }
+            if i % 5 == 0 {
+                let metadata = serde_json::json!({ "status": "to-read", "rating": i, "draft": i == 0, "aliases": ["Ferris"] });
+                document.metadata_json = Some(metadata.to_string());
+            }
+            DocumentQueries::create(pool, &document, db.compression()).await.unwrap();
+        }
+
+        let search = |filter: SearchFilter| async move {
+            let mut found: Vec<i32> = SearchQueries::search_with_ranking_filtered(pool, "borrowing", &filter, Some(100))
+                .await
+                .unwrap()
+                .iter()
+                .map(|(doc, _)| doc.title.trim_start_matches("Rust notes ").parse().unwrap())
+                .collect();
+            found.sort();
+            found
+        };
+
+        let embedded = SearchFilter { category: Some("Embedded".to_string()), ..SearchFilter::default() };
+        // Too few matches for probing the full-text index per document to pay off
+        assert_eq!(SearchQueries::estimate_matches(pool, "borrowing rules").await.unwrap(), 30);
+        assert_eq!(SearchQueries::plan_filtered_search(pool, "borrowing", &embedded).await.unwrap(), FilterPlan::MatchFirst);
+        assert_eq!(search(embedded.clone()).await, vec![0, 1, 3]);
+        assert_eq!(search(SearchFilter { include_archived: true, ..embedded.clone() }).await, vec![0, 1, 2, 3]);
+
+        let tagged = SearchFilter { tags: vec!["SYSTEMS".to_string(), "Rust".to_string()], ..SearchFilter::default() };
+        assert_eq!(search(tagged.clone()).await, vec![0, 4, 8, 12, 16, 20, 24, 28]);
+        let listed = SearchQueries::list_filtered(pool, &tagged, 100).await.unwrap();
+        assert_eq!(listed.len(), 8);
+
+        let favorites = SearchFilter { favorites_only: true, author: Some("ada".to_string()), ..SearchFilter::default() };
+        assert_eq!(search(favorites).await, vec![0, 6, 12, 18, 24]);
+
+        assert_eq!(search(SearchFilter::default()).await.len(), 29);
+        assert!(search(SearchFilter { category: Some("Missing".to_string()), ..SearchFilter::default() }).await.is_empty());
+
+        let fields = |fields: Vec<(&str, MetadataValue)>| SearchFilter {
+            metadata: fields.into_iter().map(|(key, value)| (key.to_string(), value)).collect(),
+            ..SearchFilter::default()
+        };
+        let to_read = fields(vec![("status", MetadataValue::Text("to-read".to_string()))]);
+        assert_eq!(search(to_read.clone()).await, vec![0, 5, 10, 15, 20, 25]);
+        let rated = fields(vec![
+            ("status", MetadataValue::Text("to-read".to_string())),
+            ("rating", MetadataValue::Number(10.into())),
+            ("aliases", MetadataValue::List(vec!["Ferris".to_string()])),
+        ]);
+        assert_eq!(search(rated).await, vec![10]);
+        assert_eq!(search(fields(vec![("draft", MetadataValue::Bool(true))])).await, vec![0]);
+        assert!(search(fields(vec![("draft", MetadataValue::Number(1.into()))])).await.is_empty());
+        assert!(search(fields(vec![("status", MetadataValue::List(vec!["to-read".to_string()]))])).await.is_empty());
+        assert!(search(fields(vec![("bad key'", MetadataValue::Bool(true))])).await.is_empty());
+
+        // Scalar filters on indexed keys are answered by the expression index
+        assert!(to_read.has_indexed_predicates());
+        let mut builder = sqlx::QueryBuilder::new("EXPLAIN QUERY PLAN SELECT d.id FROM documents d WHERE 1");
+        to_read.push_indexed(&mut builder);
+        let plan: Vec<String> = builder.build().fetch_all(pool).await.unwrap().iter().map(|row| row.get("detail")).collect();
+        assert!(plan.iter().any(|step| step.contains("idx_documents_metadata_status")), "{:?}", plan);
+    }
+
+    /// Bag-of-words vector over hashed words, standing in for a model
+    fn hashed_embedding(text: &str) -> Vec<f32> {
+        let mut vector = vec![0.0f32; 64];
+        for word in text.split(|c: char| !c.is_alphanumeric()).filter(|w| w.len() > 3) {
+            let hash = word.to_lowercase().bytes().fold(0xcbf29ce484222325u64, |h, b| (h ^ b as u64).wrapping_mul(0x100000001b3));
+            vector[(hash % 64) as usize] += 1.0;
+        }
+        let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt().max(f32::EPSILON);
+        vector.iter().map(|v| v / norm).collect()
+    }
+
+    #[tokio::test]
+    async fn test_hybrid_ranking_on_fixture_corpus() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = test_database(&dir).await;
+        let pool = db.pool();
+
+        let fixture: serde_json::Value =
+            serde_json::from_str(include_str!("../../tests/fixtures/ranking_corpus.json")).unwrap();
+        for entry in fixture["documents"].as_array().unwrap() {
+            let (title, content) = (entry["title"].as_str().unwrap(), entry["content"].as_str().unwrap());
+            let document = Document::new(title.to_string(), content.to_string(), "text/plain".to_string());
+            DocumentQueries::create(pool, &document, db.compression()).await.unwrap();
+            let vector = hashed_embedding(&format!("{} {}", title, content));
+            let embedding = Embedding::new(document.id.clone(), vector, "test".to_string(), 0, content.to_string(), 0, 0);
+            EmbeddingQueries::create(pool, &embedding).await.unwrap();
+        }
+
+        for fusion in [FusionMethod::ReciprocalRank, FusionMethod::WeightedSum] {
+            for case in fixture["queries"].as_array().unwrap() {
+                let (query, relevant) = (case["query"].as_str().unwrap(), case["relevant"].as_str().unwrap());
+                let vector = hashed_embedding(query);
+                let results =
+                    SearchQueries::search_hybrid_chunks(pool, query, Some(&vector), Some(10), fusion, None, None, &SearchFilter::default())
+                        .await
+                        .unwrap();
+                let top: Vec<&str> = results.iter().take(3).map(|(doc, _, _)| doc.title.as_str()).collect();
+                assert!(top.contains(&relevant), "{:?} '{}' ranked {:?}", fusion, query, top);
+                assert!(results.iter().all(|(_, score, _)| (0.0..=1.0).contains(score)));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_document_timestamps_round_trip_and_sort() {
+        use sqlx::Executor;
+
+        let dir = tempfile::tempdir().unwrap();
+        let db = test_database(&dir).await;
+        let pool = db.pool();
+
+        let mut document = Document::new("Precise".to_string(), "body".to_string(), "text/plain".to_string());
+        document.created_at = "2024-03-04T05:06:07.123456789+00:00".parse().unwrap();
+        DocumentQueries::create(pool, &document, db.compression()).await.unwrap();
+        let stored = DocumentQueries::get_by_id(pool, &document.id).await.unwrap().unwrap();
+        assert_eq!(stored.created_at, document.created_at);
+        assert_eq!(stored.updated_at, document.updated_at);
+
+        // Rows written by column defaults or with other offsets
+        let second: chrono::DateTime<Utc> = "2024-03-04T05:06:07+00:00".parse().unwrap();
+        for (id, stamp) in [
+            ("legacy-a", "2024-03-04 05:06:07"),
+            ("legacy-b", "2024-03-04T05:06:07Z"),
+            ("legacy-c", "2024-03-04T07:06:07+02:00"),
+        ] {
+            sqlx::query("INSERT INTO documents (id, title, content, created_at, updated_at) VALUES (?, ?, 'body', ?, ?)")
+                .bind(id)
+                .bind(id)
+                .bind(stamp)
+                .bind(stamp)
+                .execute(pool)
+                .await
+                .unwrap();
+            assert_eq!(DocumentQueries::get_by_id(pool, id).await.unwrap().unwrap().created_at, second);
+        }
+
+        pool.execute(include_str!("../../migrations/0022_normalize_timestamps.sql")).await.unwrap();
+        let stamps: Vec<String> = sqlx::query_scalar("SELECT created_at FROM documents WHERE id LIKE 'legacy-%'")
+            .fetch_all(pool)
+            .await
+            .unwrap();
+        assert_eq!(stamps, vec!["2024-03-04T05:06:07+00:00"; 3]);
+
+        // Documents created in the same second sort by id
+        let recent: Vec<String> = DocumentQueries::get_recent(pool, 10).await.unwrap().into_iter().map(|d| d.id).collect();
+        assert_eq!(recent, vec![document.id.as_str(), "legacy-c", "legacy-b", "legacy-a"]);
+    }
+
+    #[tokio::test]
+    async fn test_document_writes_check_and_bump_versions() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = test_database(&dir).await;
+        let pool = db.pool();
+
+        let document = Document::new("Draft".to_string(), "first".to_string(), "text/plain".to_string());
+        DocumentQueries::create(pool, &document, db.compression()).await.unwrap();
+        let mut window_a = DocumentQueries::get_by_id(pool, &document.id).await.unwrap().unwrap();
+        let mut window_b = window_a.clone();
+        assert_eq!(window_a.version, 1);
+
+        // Reading is not an edit and is not undone by a later save
+        DocumentQueries::update_access(pool, &document.id).await.unwrap();
+        window_a.content = "edited in a".to_string();
+        window_a.version = DocumentQueries::update(pool, &window_a, db.compression()).await.unwrap();
+        assert_eq!(window_a.version, 2);
+        let stored = DocumentQueries::get_by_id(pool, &document.id).await.unwrap().unwrap();
+        assert_eq!((stored.version, stored.view_count), (2, 1));
+
+        window_b.content = "edited in b".to_string();
+        let conflict = DocumentQueries::update(pool, &window_b, db.compression()).await.unwrap_err();
+        assert!(matches!(conflict, CodexError::Conflict { current_version: 2 }), "{}", conflict);
+        assert_eq!(DocumentQueries::get_by_id(pool, &document.id).await.unwrap().unwrap().content, "edited in a");
+
+        // Flag changes made elsewhere move the version on too
+        assert!(DocumentQueries::set_favorite(pool, &document.id, true).await.unwrap());
+        assert_eq!(DocumentQueries::get_version(pool, &document.id).await.unwrap(), Some(3));
+
+        // A bulk write with one stale document writes nothing
+        let other = Document::new("Other".to_string(), "second".to_string(), "text/plain".to_string());
+        DocumentQueries::create(pool, &other, db.compression()).await.unwrap();
+        let mut fresh = DocumentQueries::get_by_id(pool, &other.id).await.unwrap().unwrap();
+        fresh.category = Some("Bulk".to_string());
+        window_a.category = Some("Bulk".to_string());
+        let conflict = DocumentQueries::update_organization(pool, &[fresh.clone(), window_a.clone()]).await.unwrap_err();
+        assert!(conflict.is_conflict());
+        let untouched = DocumentQueries::get_by_id(pool, &other.id).await.unwrap().unwrap();
+        assert_eq!((untouched.category, untouched.version), (None, 1));
+
+        DocumentQueries::delete(pool, &document.id).await.unwrap();
+        assert_eq!(DocumentQueries::get_version(pool, &document.id).await.unwrap(), Some(4));
+        let mut missing = window_a.clone();
+        missing.id = uuid::Uuid::new_v4().to_string();
+        assert!(DocumentQueries::update(pool, &missing, db.compression()).await.unwrap_err().is_not_found());
+    }
+
+    #[tokio::test]
+    async fn test_semantic_scan_stops_at_deadline() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = test_database(&dir).await;
+        let pool = db.pool();
+
+        let document = Document::new("Vectors".to_string(), "body".to_string(), "text/plain".to_string());
+        DocumentQueries::create(pool, &document, db.compression()).await.unwrap();
+        let embedding = Embedding::new(document.id.clone(), vec![1.0, 0.0], "test".to_string(), 0, String::new(), 0, 0);
+        EmbeddingQueries::create(pool, &embedding).await.unwrap();
+
+        let later = std::time::Instant::now() + std::time::Duration::from_secs(60);
+        let (found, timed_out) =
+            SearchQueries::search_semantic_chunks_until(pool, &[1.0, 0.0], Some(10), Some(0.5), Some(later)).await.unwrap();
+        assert_eq!(found.len(), 1);
+        assert!(!timed_out);
+
+        let passed = std::time::Instant::now();
+        let (found, timed_out) =
+            SearchQueries::search_semantic_chunks_until(pool, &[1.0, 0.0], Some(10), Some(0.5), Some(passed)).await.unwrap();
+        assert!(found.is_empty());
+        assert!(timed_out);
+    }
+
+    #[tokio::test]
+    async fn test_identical_chunks_share_one_refcounted_vector() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = test_database(&dir).await;
+        let pool = db.pool();
+        let refcount = |hash: String| async move {
+            sqlx::query_scalar::<_, i64>("SELECT refcount FROM vectors WHERE chunk_hash = ? AND model = 'test'")
+                .bind(hash)
+                .fetch_optional(pool)
+                .await
+                .unwrap()
+        };
+
+        let header = "Licensed under the Apache License, Version 2.0";
+        let mut documents = Vec::new();
+        for title in ["First", "Second"] {
+            let document = Document::new(title.to_string(), header.to_string(), "text/plain".to_string());
+            let embeddings = vec![
+                Embedding::new(document.id.clone(), vec![1.0, 0.0], "test".to_string(), 0, header.to_string(), 0, 0),
+                Embedding::new(document.id.clone(), vec![0.0, 1.0], "test".to_string(), 1, format!("{} body", title), 0, 0),
+            ];
+            db.create_document_with_embeddings(&document, &embeddings).await.unwrap();
+            documents.push(document);
+        }
+        // Another model's vector for the same text is kept apart
+        let other_model = Embedding::new(documents[0].id.clone(), vec![0.5, 0.5], "other".to_string(), 2, header.to_string(), 0, 0);
+        EmbeddingQueries::create_with_binary(pool, &other_model).await.unwrap();
+
+        let hash = chunk_hash(header);
+        assert_eq!(refcount(hash.clone()).await, Some(2));
+        assert_eq!(EmbeddingQueries::get_shared_vector(pool, &hash, "test").await.unwrap(), Some(vec![1.0, 0.0]));
+        assert_eq!(EmbeddingQueries::get_shared_vector(pool, &hash, "missing").await.unwrap(), None);
+        let stored = EmbeddingQueries::get_by_document(pool, &documents[1].id).await.unwrap();
+        assert_eq!(stored[0].get_vector(), vec![1.0, 0.0]);
+        assert_eq!(EmbeddingQueries::get_all_vectors(pool).await.unwrap().len(), 5);
+
+        let stats = db.get_stats().await.unwrap();
+        assert_eq!(stats.unique_vectors, 4);
+        assert!(stats.vector_bytes_saved > 0);
+
+        // Dropping one referrer keeps the vector for the other
+        EmbeddingQueries::delete_by_document(pool, &documents[0].id).await.unwrap();
+        assert_eq!(refcount(hash.clone()).await, Some(1));
+        assert_eq!(db.get_stats().await.unwrap().vector_bytes_saved, 0);
+        assert_eq!(EmbeddingQueries::get_by_document(pool, &documents[1].id).await.unwrap().len(), 2);
+
+        // The last referrer takes it along, also when its document is deleted outright
+        sqlx::query("UPDATE documents SET category = ? WHERE id = ?")
+            .bind(DIAGNOSTICS_CATEGORY)
+            .bind(&documents[1].id)
+            .execute(pool)
+            .await
+            .unwrap();
+        DocumentQueries::purge_diagnostics(pool).await.unwrap();
+        assert_eq!(refcount(hash.clone()).await, None);
+        assert_eq!(db.get_stats().await.unwrap().unique_vectors, 0);
+
+        // A chunk stored again after that gets a fresh vector
+        let again = Document::new("Again".to_string(), header.to_string(), "text/plain".to_string());
+        let embedding = Embedding::new(again.id.clone(), vec![1.0, 0.0], "test".to_string(), 0, header.to_string(), 0, 0);
+        db.create_document_with_embeddings(&again, &[embedding]).await.unwrap();
+        assert_eq!(refcount(hash).await, Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_failed_imports_count_attempts_until_cleared() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = test_database(&dir).await;
+        let pool = db.pool();
+
+        let first = FailedImportQueries::record(pool, "/inbox/scan.pdf", &CodexError::validation("Unsupported file type: pdf"))
+            .await
+            .unwrap();
+        assert_eq!((first.error_code.as_str(), first.attempts), ("validation", 1));
+        FailedImportQueries::record(pool, "/inbox/notes.txt", &CodexError::internal("disk hiccup")).await.unwrap();
+
+        // Failing again updates the entry instead of adding one
+        let again = FailedImportQueries::record(pool, "/inbox/scan.pdf", &CodexError::not_found("scan.pdf")).await.unwrap();
+        assert_eq!(again.id, first.id);
+        assert_eq!((again.error_code.as_str(), again.attempts), ("not_found", 2));
+        assert_eq!(again.first_failed_at, first.first_failed_at);
+        assert_eq!(FailedImportQueries::get_all(pool).await.unwrap().len(), 2);
+
+        let selected = FailedImportQueries::get_by_ids(pool, &[first.id.clone(), "unknown".to_string()]).await.unwrap();
+        assert_eq!(selected.len(), 1);
+        assert_eq!(selected[0].path, "/inbox/scan.pdf");
+        assert!(FailedImportQueries::get_by_ids(pool, &[]).await.unwrap().is_empty());
+
+        assert!(FailedImportQueries::clear(pool, "/inbox/scan.pdf").await.unwrap());
+        assert!(!FailedImportQueries::clear(pool, "/inbox/scan.pdf").await.unwrap());
+        let remaining = FailedImportQueries::get_all(pool).await.unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert!(FailedImportQueries::delete(pool, &remaining[0].id).await.unwrap());
+        assert!(FailedImportQueries::get_all(pool).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_attachments_stay_referenced_by_deleted_documents() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = test_database(&dir).await;
+        let pool = db.pool();
+        let hash = "ab".repeat(32);
+
+        let scan = Document::new("Scan".to_string(), "Scanned page".to_string(), "application/pdf".to_string());
+        let copy = Document::new("Scan copy".to_string(), "Scanned page again".to_string(), "application/pdf".to_string());
+        DocumentQueries::create(pool, &scan, db.compression()).await.unwrap();
+        DocumentQueries::create(pool, &copy, db.compression()).await.unwrap();
+
+        // Storing the same bytes twice keeps the first name
+        let stored = AttachmentQueries::create_or_get(pool, &hash, 1024, "scan.pdf").await.unwrap();
+        assert_eq!(AttachmentQueries::create_or_get(pool, &hash, 1024, "scan (1).pdf").await.unwrap(), stored);
+        AttachmentQueries::set_for_document(pool, &scan.id, &hash).await.unwrap();
+        AttachmentQueries::set_for_document(pool, &copy.id, &hash).await.unwrap();
+        assert!(AttachmentQueries::set_for_document(pool, "missing", &hash).await.is_err());
+        assert_eq!(AttachmentQueries::get_for_document(pool, &scan.id).await.unwrap(), Some(stored.clone()));
+
+        // A soft-deleted document can be restored and still needs its original
+        DocumentQueries::delete(pool, &scan.id).await.unwrap();
+        sqlx::query("DELETE FROM documents WHERE id = ?").bind(&copy.id).execute(pool).await.unwrap();
+        assert!(AttachmentQueries::get_unreferenced(pool).await.unwrap().is_empty());
+        assert!(!AttachmentQueries::delete_if_unreferenced(pool, &hash).await.unwrap());
+
+        sqlx::query("DELETE FROM documents WHERE id = ?").bind(&scan.id).execute(pool).await.unwrap();
+        assert_eq!(AttachmentQueries::get_unreferenced(pool).await.unwrap(), vec![stored]);
+        assert!(AttachmentQueries::delete_if_unreferenced(pool, &hash).await.unwrap());
+        assert!(AttachmentQueries::all_ids(pool).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_find_by_doi_in_compressed_bodies() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = test_database(&dir).await;
+        let pool = db.pool();
+
+        let body = format!("{}\nAvailable as doi:10.1234/Paper.2021-07 online.", "Results and discussion. ".repeat(1000));
+        let paper = Document::new("Paper".to_string(), body, "text/plain".to_string());
+        DocumentQueries::create(pool, &paper, db.compression()).await.unwrap();
+        let stored: String = sqlx::query_scalar("SELECT content FROM documents WHERE id = ?")
+            .bind(&paper.id)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        assert!(stored.is_empty(), "body should be stored compressed");
+
+        let found = DocumentQueries::find_by_doi(pool, "10.1234/paper.2021-07").await.unwrap().unwrap();
+        assert_eq!(found.id, paper.id);
+        // Same tokens, different DOI
+        assert!(DocumentQueries::find_by_doi(pool, "10.1234/paper-2021.07").await.unwrap().is_none());
+
+        // A URL match wins over a mention in the text
+        let mut landing = Document::new("Landing page".to_string(), "Abstract".to_string(), "text/html".to_string());
+        landing.url = Some("https://doi.org/10.1234/PAPER.2021-07".to_string());
+        DocumentQueries::create(pool, &landing, db.compression()).await.unwrap();
+        let found = DocumentQueries::find_by_doi(pool, "10.1234/paper.2021-07").await.unwrap().unwrap();
+        assert_eq!(found.id, landing.id);
+    }
+
+    #[tokio::test]
+    async fn test_compression_policy_applies_per_write() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = test_database(&dir).await;
+        let pool = db.pool();
+        let fts_hits = |term: &'static str| async move {
+            let hits: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM documents_fts WHERE documents_fts MATCH ?")
+                .bind(term)
+                .fetch_one(pool)
+                .await
+                .unwrap();
+            hits
+        };
+        let compression = |id: String| async move {
+            let compression: Option<String> = sqlx::query_scalar("SELECT compression FROM documents WHERE id = ?")
+                .bind(id)
+                .fetch_one(pool)
+                .await
+                .unwrap();
+            compression
+        };
+
+        let mut document = Document::new("Atlas".to_string(), "Glacier moraine survey. ".repeat(1000), "text/plain".to_string());
+        DocumentQueries::create(pool, &document, CompressionPolicy::default()).await.unwrap();
+        assert_eq!(compression(document.id.clone()).await.as_deref(), Some(compression::COMPRESSION_ZSTD));
+        assert_eq!(fts_hits("moraine").await, 1);
+
+        // The new body replaces the indexed text in the same write
+        document.content = "Volcanic caldera survey. ".repeat(1000);
+        document.version = DocumentQueries::update(pool, &document, CompressionPolicy::default()).await.unwrap();
+        assert_eq!(fts_hits("moraine").await, 0);
+        assert_eq!(fts_hits("caldera").await, 1);
+        let stored = DocumentQueries::get_by_id(pool, &document.id).await.unwrap().unwrap();
+        assert_eq!(stored.content, document.content);
+
+        // Another policy on the same pool leaves the body plain
+        document.content = "Coastal dune survey. ".repeat(1000);
+        DocumentQueries::update(pool, &document, CompressionPolicy::DISABLED).await.unwrap();
+        assert_eq!(compression(document.id.clone()).await, None);
+        assert_eq!(fts_hits("caldera").await, 0);
+        assert_eq!(fts_hits("dune").await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_reading_sessions_merge_and_drop_short_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = test_database(&dir).await;
+        let pool = db.pool();
+        let document = Document::new("Meditations".to_string(), "Book one".to_string(), "text/plain".to_string());
+        DocumentQueries::create(pool, &document, db.compression()).await.unwrap();
+        let at = |minute: i64| "2024-05-06T10:00:00Z".parse::<chrono::DateTime<Utc>>().unwrap() + chrono::Duration::minutes(minute);
+
+        assert!(ReadingSessionQueries::start(pool, "missing", at(0)).await.is_err());
+
+        // Too short to count
+        let glance = ReadingSessionQueries::start(pool, &document.id, at(0)).await.unwrap();
+        assert_eq!(ReadingSessionQueries::finish(pool, &glance.id, at(0) + chrono::Duration::seconds(10), 30).await.unwrap(), None);
+
+        // Two windows reading the same document at once become one session
+        let first = ReadingSessionQueries::start(pool, &document.id, at(0)).await.unwrap();
+        let second = ReadingSessionQueries::start(pool, &document.id, at(5)).await.unwrap();
+        ReadingSessionQueries::finish(pool, &first.id, at(10), 30).await.unwrap().unwrap();
+        let merged = ReadingSessionQueries::finish(pool, &second.id, at(20), 30).await.unwrap().unwrap();
+        assert_eq!(merged.duration_seconds, 20 * 60);
+        assert!(ReadingSessionQueries::finish(pool, &second.id, at(30), 30).await.is_err());
+
+        // A later, separate session is kept apart
+        let later = ReadingSessionQueries::start(pool, &document.id, at(60)).await.unwrap();
+        ReadingSessionQueries::finish(pool, &later.id, at(75), 30).await.unwrap().unwrap();
+
+        let sessions: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM reading_sessions").fetch_one(pool).await.unwrap();
+        assert_eq!(sessions, 2);
+        assert_eq!(ReadingSessionQueries::seconds_since(pool, at(0)).await.unwrap(), 35 * 60);
+        assert_eq!(ReadingSessionQueries::seconds_since(pool, at(30)).await.unwrap(), 15 * 60);
+        assert_eq!(ReadingSessionQueries::reading_days(pool).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_labels_include_collections() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = test_database(&dir).await;
+        let pool = db.pool();
+        let mut document = Document::new("Stew".to_string(), "Slow cooked".to_string(), "text/plain".to_string());
+        document.set_tags(vec!["inbox".to_string()]);
+        DocumentQueries::create(pool, &document, db.compression()).await.unwrap();
+        sqlx::query("INSERT INTO collections (id, name) VALUES ('c1', 'Kitchen')").execute(pool).await.unwrap();
+
+        let labels = DocumentQueries::get_labels(pool).await.unwrap();
+        assert_eq!(labels.len(), 1);
+        assert_eq!(labels[0].get_tags(), vec!["inbox".to_string()]);
+        assert!(labels[0].get_collections().is_empty());
+
+        assert!(DocumentQueries::add_to_collection(pool, &document.id, "c1").await.unwrap());
+        assert!(!DocumentQueries::add_to_collection(pool, &document.id, "c1").await.unwrap());
+        assert!(DocumentQueries::add_to_collection(pool, &document.id, "missing").await.is_err());
+        assert_eq!(DocumentQueries::get_labels(pool).await.unwrap()[0].get_collections(), vec!["c1".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_create_collection() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = test_database(&dir).await;
+        let pool = db.pool();
+        let collection = Collection::new("Focus pack".to_string(), Some("Reading list".to_string()));
+
+        DocumentQueries::create_collection(pool, &collection).await.unwrap();
+        assert!(DocumentQueries::collection_exists(pool, &collection.id).await.unwrap());
+        assert!(DocumentQueries::create_collection(pool, &collection).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_ai_audit_prune() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = test_database(&dir).await;
+        let pool = db.pool();
+        let document = Document::new("Stew".to_string(), "Slow cooked".to_string(), "text/plain".to_string());
+        DocumentQueries::create(pool, &document, db.compression()).await.unwrap();
+
+        let entry = |document_id: &str, timestamp: &str| AiAuditEntry {
+            id: uuid::Uuid::new_v4().to_string(),
+            document_id: document_id.to_string(),
+            operation: "tags".to_string(),
+            model: "test-model".to_string(),
+            prompt_hash: "p".to_string(),
+            output_hash: "o".to_string(),
+            duration_ms: 12,
+            timestamp: timestamp.to_string(),
+            prompt_text: None,
+            output_text: None,
+        };
+        AiAuditQueries::create(pool, &entry(&document.id, "2024-01-01T00:00:00+00:00")).await.unwrap();
+        AiAuditQueries::create(pool, &entry(&document.id, "2024-03-01T00:00:00+00:00")).await.unwrap();
+        AiAuditQueries::create(pool, &entry("gone", "2024-02-01T00:00:00+00:00")).await.unwrap();
+        AiAuditQueries::create(pool, &entry("importing", "2024-03-01T00:00:00+00:00")).await.unwrap();
+
+        let pruned = AiAuditQueries::prune(pool, "2024-01-15T00:00:00+00:00", "2024-02-15T00:00:00+00:00").await.unwrap();
+        assert_eq!(pruned, 2);
+        assert_eq!(AiAuditQueries::count(pool).await.unwrap(), 2);
+        let kept = AiAuditQueries::get_for_document(pool, &document.id).await.unwrap();
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].timestamp, "2024-03-01T00:00:00+00:00");
+    }
+
+    #[tokio::test]
+    async fn test_ai_audit_maintenance_prunes_exactly_the_expired_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let clock = std::sync::Arc::new(crate::clock::ManualClock::new());
+        let db = test_database(&dir).await.with_clock(clock.clone());
+        let pool = db.pool();
+        let document = Document::new("Stew".to_string(), "Slow cooked".to_string(), "text/plain".to_string());
+        DocumentQueries::create(pool, &document, db.compression()).await.unwrap();
+
+        let entry = |operation: &str| AiAuditEntry {
+            id: uuid::Uuid::new_v4().to_string(),
+            document_id: document.id.clone(),
+            operation: operation.to_string(),
+            model: "test-model".to_string(),
+            prompt_hash: "p".to_string(),
+            output_hash: "o".to_string(),
+            duration_ms: 12,
+            timestamp: crate::clock::Clock::utc_now(clock.as_ref()).to_rfc3339(),
+            prompt_text: None,
+            output_text: None,
+        };
+        AiAuditQueries::create(pool, &entry("old")).await.unwrap();
+        clock.advance(std::time::Duration::from_secs(2 * 24 * 60 * 60));
+        AiAuditQueries::create(pool, &entry("recent")).await.unwrap();
+
+        // Nothing is older than the retention yet
+        clock.advance(std::time::Duration::from_secs(27 * 24 * 60 * 60));
+        assert_eq!(db.prune_ai_audit(30).await.unwrap(), 0);
+
+        // Two more days put only the first entry past it
+        clock.advance(std::time::Duration::from_secs(2 * 24 * 60 * 60));
+        assert_eq!(db.prune_ai_audit(30).await.unwrap(), 1);
+        let kept = AiAuditQueries::get_for_document(pool, &document.id).await.unwrap();
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].operation, "recent");
+    }
+}
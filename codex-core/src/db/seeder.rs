@@ -5,6 +5,7 @@
 use sqlx::SqlitePool;
 
 use crate::CodexResult;
+use super::compression::CompressionPolicy;
 use super::models::Document;
 use super::queries::DocumentQueries;
 
@@ -25,7 +26,7 @@ impl ContentSeeder {
                 continue;
             }
             
-            DocumentQueries::create(pool, &document).await?;
+            DocumentQueries::create(pool, &document, CompressionPolicy::default()).await?;
             tracing::debug!("Created document: '{}'", document.title);
         }
         
@@ -0,0 +1,414 @@
+//! Where a vault's disk space goes
+//!
+//! [`DatabaseManager::get_storage_breakdown`] sizes every table and index
+//! from SQLite's `dbstat` virtual table and groups them into
+//! [`StorageComponent`]s, next to the stored originals under the content
+//! directory. It also estimates what each [`MaintenanceAction`] would
+//! reclaim, so the UI can offer "purging the trash frees ~420 MB" with a
+//! button that runs the action.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use super::{compression, DatabaseManager};
+use crate::CodexResult;
+
+/// Savings below this are not worth suggesting
+pub const MIN_RECLAIM_BYTES: u64 = 1024 * 1024;
+/// Compressed share of a body's size assumed before any body is compressed;
+/// zstd typically shrinks prose to about a third
+const DEFAULT_COMPRESSION_RATIO: f64 = 0.35;
+
+/// Part of the vault that takes up space
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageComponent {
+    /// Document rows, their bodies and indexes
+    DocumentContent,
+    /// The documents' full-text index
+    FullTextIndex,
+    /// Chunk embeddings and the shared vectors they point to
+    Embeddings,
+    /// Cached vectors for similarity search
+    VectorCache,
+    /// Annotations and their full-text index
+    Annotations,
+    /// Stored originals of imported documents, outside the database file
+    Attachments,
+    /// Settings, history, links and every other table
+    Other,
+}
+
+impl StorageComponent {
+    /// Every component, in the order a breakdown lists them
+    pub const ALL: [StorageComponent; 7] = [
+        StorageComponent::DocumentContent,
+        StorageComponent::FullTextIndex,
+        StorageComponent::Embeddings,
+        StorageComponent::VectorCache,
+        StorageComponent::Annotations,
+        StorageComponent::Attachments,
+        StorageComponent::Other,
+    ];
+
+    /// Component a table, or the table an index belongs to, is part of
+    ///
+    /// FTS5 tables keep their data in shadow tables named after them, e.g.
+    /// `documents_fts_data`, so the full-text prefixes are matched first.
+    pub fn of_table(table: &str) -> Self {
+        let is = |name: &str| table == name || table.starts_with(&format!("{}_", name));
+        if is("documents_fts") {
+            StorageComponent::FullTextIndex
+        } else if is("annotations_fts") || table == "annotations" {
+            StorageComponent::Annotations
+        } else if table == "documents" {
+            StorageComponent::DocumentContent
+        } else if table == "embeddings" || table == "vectors" {
+            StorageComponent::Embeddings
+        } else if is("vector_cache") {
+            StorageComponent::VectorCache
+        } else {
+            StorageComponent::Other
+        }
+    }
+}
+
+/// Maintenance that frees space, by the id the UI runs it with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MaintenanceAction {
+    /// Permanently delete the documents in the trash
+    PurgeTrash,
+    /// Compress large bodies still stored as plain text
+    CompressContent,
+    /// Rebuild the vector cache without its stale entries
+    CompactVectorCache,
+    /// Delete stored originals no document refers to
+    CollectAttachmentGarbage,
+    /// Rewrite the database file without its free pages
+    Vacuum,
+}
+
+impl MaintenanceAction {
+    /// Every action, in the order suggestions are computed
+    pub const ALL: [MaintenanceAction; 5] = [
+        MaintenanceAction::PurgeTrash,
+        MaintenanceAction::CompressContent,
+        MaintenanceAction::CompactVectorCache,
+        MaintenanceAction::CollectAttachmentGarbage,
+        MaintenanceAction::Vacuum,
+    ];
+
+    /// Stable id, as serialized
+    pub fn id(self) -> &'static str {
+        match self {
+            MaintenanceAction::PurgeTrash => "purge_trash",
+            MaintenanceAction::CompressContent => "compress_content",
+            MaintenanceAction::CompactVectorCache => "compact_vector_cache",
+            MaintenanceAction::CollectAttachmentGarbage => "collect_attachment_garbage",
+            MaintenanceAction::Vacuum => "vacuum",
+        }
+    }
+
+    fn describe(self, bytes: u64) -> String {
+        let size = format_size(bytes);
+        match self {
+            MaintenanceAction::PurgeTrash => format!("Purging the trash frees ~{}", size),
+            MaintenanceAction::CompressContent => format!("Compressing content saves ~{}", size),
+            MaintenanceAction::CompactVectorCache => format!("Compacting the vector cache frees ~{}", size),
+            MaintenanceAction::CollectAttachmentGarbage => {
+                format!("Removing originals no document uses frees ~{}", size)
+            }
+            MaintenanceAction::Vacuum => format!("Vacuuming reclaims ~{} of free pages", size),
+        }
+    }
+}
+
+/// Size as "420 MB" or "1.2 GB"
+fn format_size(bytes: u64) -> String {
+    const MB: f64 = 1024.0 * 1024.0;
+    let mb = bytes as f64 / MB;
+    if mb >= 1024.0 {
+        format!("{:.1} GB", mb / 1024.0)
+    } else {
+        format!("{:.0} MB", mb)
+    }
+}
+
+/// Space one table or index takes in the database file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TableUsage {
+    pub name: String,
+    /// Table an index belongs to, the name itself for tables
+    pub table: String,
+    pub component: StorageComponent,
+    pub bytes: u64,
+}
+
+/// Space one component takes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ComponentUsage {
+    pub component: StorageComponent,
+    pub bytes: u64,
+}
+
+/// Estimated space a maintenance action would free
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReclaimSuggestion {
+    pub action: MaintenanceAction,
+    pub reclaimable_bytes: u64,
+    /// E.g. "Purging the trash frees ~420 MB"
+    pub description: String,
+}
+
+/// Result of [`DatabaseManager::get_storage_breakdown`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StorageBreakdown {
+    pub measured_at: DateTime<Utc>,
+    /// Size of the database file, free pages included
+    pub database_bytes: u64,
+    /// Unused pages a vacuum would return to the file system
+    pub free_bytes: u64,
+    /// Database file and stored originals together
+    pub total_bytes: u64,
+    /// One entry per component, including empty ones
+    pub components: Vec<ComponentUsage>,
+    /// Tables and indexes, largest first; empty when SQLite was built
+    /// without `dbstat`, in which case all used pages count as `Other`
+    pub tables: Vec<TableUsage>,
+    /// Actions worth at least [`MIN_RECLAIM_BYTES`], largest saving first
+    pub suggestions: Vec<ReclaimSuggestion>,
+}
+
+impl StorageBreakdown {
+    /// Bytes taken by `component`
+    pub fn bytes(&self, component: StorageComponent) -> u64 {
+        self.components.iter().find(|usage| usage.component == component).map_or(0, |usage| usage.bytes)
+    }
+
+    /// Suggestion for `action`, if it would reclaim enough to suggest
+    pub fn suggestion(&self, action: MaintenanceAction) -> Option<&ReclaimSuggestion> {
+        self.suggestions.iter().find(|suggestion| suggestion.action == action)
+    }
+}
+
+impl DatabaseManager {
+    /// Break the vault's disk usage down by component and estimate what
+    /// each maintenance action would reclaim
+    ///
+    /// Only reads. Savings are estimates: deleted rows leave free pages
+    /// behind until a vacuum, and compression depends on the text.
+    pub async fn get_storage_breakdown(&self) -> CodexResult<StorageBreakdown> {
+        let mut tx = self.pool.begin().await?;
+
+        let (page_count, page_size, free_pages): (i64, i64, i64) = sqlx::query_as(
+            "SELECT page_count, page_size, freelist_count FROM pragma_page_count(), pragma_page_size(), pragma_freelist_count()",
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        let database_bytes = (page_count * page_size) as u64;
+        let free_bytes = (free_pages * page_size) as u64;
+
+        let sizes: Result<Vec<(String, String, i64)>, sqlx::Error> = sqlx::query_as(
+            r#"
+            SELECT s.name, COALESCE(m.tbl_name, s.name), SUM(s.pgsize)
+            FROM dbstat s LEFT JOIN sqlite_schema m ON m.name = s.name
+            GROUP BY s.name
+            "#,
+        )
+        .fetch_all(&mut *tx)
+        .await;
+        let mut tables: Vec<TableUsage> = match sizes {
+            Ok(sizes) => sizes
+                .into_iter()
+                .map(|(name, table, bytes)| TableUsage {
+                    component: StorageComponent::of_table(&table),
+                    name,
+                    table,
+                    bytes: bytes as u64,
+                })
+                .collect(),
+            Err(e) => {
+                warn!("dbstat unavailable, storage is not broken down by table: {}", e);
+                Vec::new()
+            }
+        };
+        tables.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.name.cmp(&b.name)));
+
+        let (attachment_bytes, unreferenced_attachment_bytes): (i64, i64) = sqlx::query_as(
+            r#"
+            SELECT
+                COALESCE(SUM(size), 0),
+                COALESCE(SUM(CASE WHEN NOT EXISTS (SELECT 1 FROM documents d WHERE d.attachment_id = a.id) THEN size END), 0)
+            FROM attachments a
+            "#,
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        // Bodies as stored, plus the chunk text of their embeddings
+        let trash_bytes: i64 = sqlx::query_scalar(
+            r#"
+            SELECT
+                COALESCE(SUM(length(CAST(content AS BLOB)) + COALESCE(length(content_compressed), 0)), 0)
+                + (SELECT COALESCE(SUM(length(CAST(e.text_chunk AS BLOB))), 0) FROM embeddings e
+                   WHERE e.document_id IN (SELECT id FROM documents WHERE is_deleted = true))
+            FROM documents WHERE is_deleted = true
+            "#,
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let (uncompressed_bytes, compressed_logical, compressed_stored): (i64, i64, i64) = sqlx::query_as(
+            r#"
+            SELECT
+                COALESCE(SUM(CASE WHEN compression IS NULL AND length(CAST(content AS BLOB)) >= ? THEN length(CAST(content AS BLOB)) END), 0),
+                COALESCE(SUM(CASE WHEN compression IS NOT NULL THEN content_size END), 0),
+                COALESCE(SUM(CASE WHEN compression IS NOT NULL THEN length(content_compressed) END), 0)
+            FROM documents
+            "#,
+        )
+        .bind(compression::COMPRESSION_THRESHOLD_BYTES as i64)
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        let cache = self.vector_cache_stats().await?;
+
+        let mut components: Vec<ComponentUsage> = StorageComponent::ALL
+            .iter()
+            .map(|&component| ComponentUsage { component, bytes: 0 })
+            .collect();
+        let mut add = |component: StorageComponent, bytes: u64| {
+            if let Some(usage) = components.iter_mut().find(|usage| usage.component == component) {
+                usage.bytes += bytes;
+            }
+        };
+        for table in &tables {
+            add(table.component, table.bytes);
+        }
+        if tables.is_empty() {
+            add(StorageComponent::Other, database_bytes.saturating_sub(free_bytes));
+        }
+        add(StorageComponent::Attachments, attachment_bytes as u64);
+
+        // Bodies compressed so far show how well this vault's text shrinks
+        let compression_ratio = if compressed_logical > 0 {
+            (compressed_stored as f64 / compressed_logical as f64).min(1.0)
+        } else {
+            DEFAULT_COMPRESSION_RATIO
+        };
+        let compression_savings = if self.compression().enabled {
+            (uncompressed_bytes as f64 * (1.0 - compression_ratio)) as u64
+        } else {
+            0
+        };
+
+        let mut suggestions: Vec<ReclaimSuggestion> = MaintenanceAction::ALL
+            .iter()
+            .map(|&action| {
+                let reclaimable_bytes = match action {
+                    MaintenanceAction::PurgeTrash => trash_bytes as u64,
+                    MaintenanceAction::CompressContent => compression_savings,
+                    MaintenanceAction::CompactVectorCache => (cache.size_bytes as f64 * cache.tombstone_ratio) as u64,
+                    MaintenanceAction::CollectAttachmentGarbage => unreferenced_attachment_bytes as u64,
+                    MaintenanceAction::Vacuum => free_bytes,
+                };
+                ReclaimSuggestion { action, reclaimable_bytes, description: action.describe(reclaimable_bytes) }
+            })
+            .filter(|suggestion| suggestion.reclaimable_bytes >= MIN_RECLAIM_BYTES)
+            .collect();
+        suggestions.sort_by_key(|suggestion| std::cmp::Reverse(suggestion.reclaimable_bytes));
+
+        Ok(StorageBreakdown {
+            measured_at: self.clock.utc_now(),
+            database_bytes,
+            free_bytes,
+            total_bytes: database_bytes + attachment_bytes as u64,
+            components,
+            tables,
+            suggestions,
+        })
+    }
+
+    /// Permanently delete the documents in the trash, returning their ids
+    pub async fn purge_trash(&self) -> CodexResult<Vec<String>> {
+        let _unit = self.write_unit().await;
+        let purged = super::DocumentQueries::purge_deleted(&self.pool).await?;
+        if !purged.is_empty() {
+            info!("Purged {} documents from the trash", purged.len());
+        }
+        Ok(purged)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_database;
+    use crate::db::{Document, DocumentQueries};
+
+    #[test]
+    fn test_tables_are_grouped_into_components() {
+        assert_eq!(StorageComponent::of_table("documents"), StorageComponent::DocumentContent);
+        assert_eq!(StorageComponent::of_table("documents_fts_data"), StorageComponent::FullTextIndex);
+        assert_eq!(StorageComponent::of_table("documents_fts"), StorageComponent::FullTextIndex);
+        assert_eq!(StorageComponent::of_table("annotations_fts_idx"), StorageComponent::Annotations);
+        assert_eq!(StorageComponent::of_table("vectors"), StorageComponent::Embeddings);
+        assert_eq!(StorageComponent::of_table("vector_cache_rebuild"), StorageComponent::VectorCache);
+        assert_eq!(StorageComponent::of_table("document_links"), StorageComponent::Other);
+        assert_eq!(format_size(420 * 1024 * 1024), "420 MB");
+        assert_eq!(format_size(1288 * 1024 * 1024), "1.3 GB");
+        assert_eq!(serde_json::to_value(MaintenanceAction::PurgeTrash).unwrap(), "purge_trash");
+    }
+
+    #[tokio::test]
+    async fn test_breakdown_measures_components_and_suggests_reclaims() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = test_database(&dir).await;
+        let pool = db.pool();
+
+        let empty = db.get_storage_breakdown().await.unwrap();
+        assert!(empty.suggestions.is_empty(), "{:?}", empty.suggestions);
+        assert_eq!(empty.components.len(), StorageComponent::ALL.len());
+
+        // A trashed 4MB body, and an uncompressed one as left by an older
+        // release; pseudo-random letters so compression cannot erase them
+        let mut seed = 7u64;
+        let body: String = (0..4 * 1024 * 1024)
+            .map(|i| {
+                seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                if i % 8 == 7 { ' ' } else { (b'a' + (seed >> 59) as u8 % 26) as char }
+            })
+            .collect();
+        let mut trashed = Document::new("Trashed".to_string(), body.clone(), "text/plain".to_string());
+        trashed.is_deleted = true;
+        DocumentQueries::create(pool, &trashed, db.compression()).await.unwrap();
+        let kept = Document::new("Kept".to_string(), String::new(), "text/plain".to_string());
+        DocumentQueries::create(pool, &kept, db.compression()).await.unwrap();
+        sqlx::query("UPDATE documents SET content = ? WHERE id = ?")
+            .bind(&body)
+            .bind(&kept.id)
+            .execute(pool)
+            .await
+            .unwrap();
+
+        let breakdown = db.get_storage_breakdown().await.unwrap();
+        assert!(breakdown.bytes(StorageComponent::DocumentContent) > 2 * 1024 * 1024);
+        assert!(breakdown.bytes(StorageComponent::FullTextIndex) > 0);
+        assert!(breakdown.tables.iter().any(|table| table.name == "documents_fts_data"));
+        assert_eq!(breakdown.total_bytes, breakdown.database_bytes);
+        let trash = breakdown.suggestion(MaintenanceAction::PurgeTrash).expect("trash suggestion");
+        assert!(trash.description.starts_with("Purging the trash frees ~"), "{}", trash.description);
+        assert!(breakdown.suggestion(MaintenanceAction::CompressContent).is_some());
+
+        // Purged rows leave free pages for a vacuum to reclaim
+        assert_eq!(db.purge_trash().await.unwrap().len(), 1);
+        let purged = db.get_storage_breakdown().await.unwrap();
+        assert!(purged.suggestion(MaintenanceAction::PurgeTrash).is_none());
+        assert!(purged.suggestion(MaintenanceAction::Vacuum).is_some(), "{:?}", purged.suggestions);
+        db.optimize().await.unwrap();
+        assert!(db.get_storage_breakdown().await.unwrap().suggestion(MaintenanceAction::Vacuum).is_none());
+    }
+}
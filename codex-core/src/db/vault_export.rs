@@ -0,0 +1,269 @@
+//! Point-in-time vault exports
+//!
+//! An export is a directory holding a snapshot of the database and a
+//! manifest. The snapshot is taken with `VACUUM INTO` once no
+//! [`DatabaseManager::write_unit`] is in progress, so documents, their
+//! embeddings and the full-text index agree as of the manifest's
+//! `snapshot_at` even while imports keep running. Schema migrations wait
+//! until the snapshot is written.
+
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::{Connection, SqliteConnection};
+use tracing::info;
+
+use super::models::SearchDictionaries;
+use super::{DatabaseManager, SEARCH_STOPWORDS_SETTING, SEARCH_SYNONYMS_SETTING};
+use crate::{CodexError, CodexResult};
+
+/// Version of the export layout, raised when it changes incompatibly
+pub const VAULT_EXPORT_FORMAT_VERSION: u32 = 1;
+/// Database snapshot inside an export directory
+pub const EXPORT_DATABASE_FILE: &str = "vault.db";
+/// Manifest inside an export directory
+pub const EXPORT_MANIFEST_FILE: &str = "manifest.json";
+
+/// Description of a vault export, written next to its snapshot
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VaultExportManifest {
+    pub format_version: u32,
+    /// Version of the app that wrote the export
+    pub app_version: String,
+    /// `schema_version` setting of the snapshot
+    pub schema_version: Option<String>,
+    /// Point in time the exported data is consistent as of
+    pub snapshot_at: DateTime<Utc>,
+    pub document_count: u64,
+    pub embedding_count: u64,
+    /// Search synonyms and stop words of the snapshot, also restored with it
+    #[serde(default)]
+    pub search_dictionaries: SearchDictionaries,
+    /// Stored originals copied to the export's `attachments` directory
+    #[serde(default)]
+    pub attachment_count: u64,
+}
+
+impl VaultExportManifest {
+    /// Read the manifest of an export directory
+    pub async fn read(export_dir: &Path) -> CodexResult<Self> {
+        let text = tokio::fs::read_to_string(export_dir.join(EXPORT_MANIFEST_FILE)).await?;
+        let manifest: Self = serde_json::from_str(&text)?;
+        if manifest.format_version > VAULT_EXPORT_FORMAT_VERSION {
+            return Err(CodexError::validation(format!(
+                "Vault export format {} is newer than this app supports ({})",
+                manifest.format_version, VAULT_EXPORT_FORMAT_VERSION
+            )));
+        }
+        Ok(manifest)
+    }
+
+    /// Write the manifest into an export directory, replacing any previous one
+    pub async fn write(&self, export_dir: &Path) -> CodexResult<()> {
+        tokio::fs::write(export_dir.join(EXPORT_MANIFEST_FILE), serde_json::to_string_pretty(self)?).await?;
+        Ok(())
+    }
+}
+
+/// Ids of the stored originals referred to by the documents of an export
+pub async fn exported_attachment_ids(export_dir: &Path) -> CodexResult<Vec<String>> {
+    let options = SqliteConnectOptions::new().filename(export_dir.join(EXPORT_DATABASE_FILE)).read_only(true);
+    let mut snapshot = SqliteConnection::connect_with(&options).await?;
+    let ids = sqlx::query_scalar("SELECT DISTINCT attachment_id FROM documents WHERE attachment_id IS NOT NULL")
+        .fetch_all(&mut snapshot)
+        .await?;
+    snapshot.close().await?;
+    Ok(ids)
+}
+
+impl DatabaseManager {
+    /// Export the vault to `destination`, a new or empty directory
+    ///
+    /// See the [module documentation](self) for the consistency guarantees.
+    pub async fn export_vault(&self, destination: &Path) -> CodexResult<VaultExportManifest> {
+        tokio::fs::create_dir_all(destination).await?;
+        if std::fs::read_dir(destination)?.next().is_some() {
+            return Err(CodexError::validation(format!(
+                "Export directory is not empty: {}",
+                destination.display()
+            )));
+        }
+
+        let snapshot_path = destination.join(EXPORT_DATABASE_FILE);
+        let snapshot_at = self.snapshot(&snapshot_path).await?;
+
+        // Describe the snapshot itself, not the live database that moved on since
+        let mut snapshot = SqliteConnection::connect_with(&SqliteConnectOptions::new().filename(&snapshot_path).read_only(true)).await?;
+        let document_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM documents").fetch_one(&mut snapshot).await?;
+        let embedding_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM embeddings").fetch_one(&mut snapshot).await?;
+        let schema_version: Option<String> = sqlx::query_scalar("SELECT value FROM settings WHERE key = 'schema_version'")
+            .fetch_optional(&mut snapshot)
+            .await?;
+        let mut search_dictionaries = SearchDictionaries::default();
+        let dictionary_rows: Vec<(String, String)> = sqlx::query_as("SELECT key, value FROM settings WHERE key IN (?, ?)")
+            .bind(SEARCH_SYNONYMS_SETTING)
+            .bind(SEARCH_STOPWORDS_SETTING)
+            .fetch_all(&mut snapshot)
+            .await?;
+        for (key, value) in dictionary_rows {
+            if key == SEARCH_SYNONYMS_SETTING {
+                search_dictionaries.synonyms = serde_json::from_str(&value)?;
+            } else {
+                search_dictionaries.stopwords = serde_json::from_str(&value)?;
+            }
+        }
+        snapshot.close().await?;
+
+        let manifest = VaultExportManifest {
+            format_version: VAULT_EXPORT_FORMAT_VERSION,
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            schema_version,
+            snapshot_at,
+            document_count: document_count as u64,
+            embedding_count: embedding_count as u64,
+            search_dictionaries,
+            attachment_count: 0,
+        };
+        manifest.write(destination).await?;
+
+        info!(
+            "Exported vault to {:?} as of {} ({} documents, {} embeddings)",
+            destination, manifest.snapshot_at, manifest.document_count, manifest.embedding_count
+        );
+        Ok(manifest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{test_config, test_database};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::db::models::{Document, Embedding};
+    use crate::db::SearchDictionaryQueries;
+
+    #[tokio::test]
+    async fn test_export_during_imports_is_consistent() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Arc::new(test_database(&dir).await);
+
+        // Imports as the indexer saves them: a document and its chunks in one write unit
+        let importer = tokio::spawn({
+            let db = Arc::clone(&db);
+            async move {
+                for i in 0..40 {
+                    let document = Document::new(format!("Note {}", i), format!("Imported note {}", i), "text/plain".to_string());
+                    let embeddings: Vec<Embedding> = (0..8)
+                        .map(|chunk| Embedding::new(document.id.clone(), vec![i as f32, chunk as f32], "test".to_string(), chunk, format!("chunk {}", chunk), 0, 0))
+                        .collect();
+                    db.create_document_with_embeddings(&document, &embeddings).await.unwrap();
+                }
+            }
+        });
+
+        SearchDictionaryQueries::set_synonyms(db.pool(), "MI", &["Myocardial  infarction".to_string()]).await.unwrap();
+
+        // Export while documents are still arriving
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let export_dir = dir.path().join("export");
+        let manifest = db.export_vault(&export_dir).await.unwrap();
+        importer.await.unwrap();
+
+        assert_eq!(VaultExportManifest::read(&export_dir).await.unwrap(), manifest);
+        assert!(manifest.schema_version.is_some());
+        assert_eq!(manifest.search_dictionaries.synonyms["mi"], vec!["myocardial infarction".to_string()]);
+        assert!(db.export_vault(&export_dir).await.is_err(), "exports never overwrite");
+
+        // Re-import the snapshot as a vault of its own
+        let reimported_path = dir.path().join("reimported.db");
+        std::fs::copy(export_dir.join(EXPORT_DATABASE_FILE), &reimported_path).unwrap();
+        let reimported = DatabaseManager::new(&test_config(reimported_path)).await.unwrap();
+        let pool = reimported.pool();
+
+        let documents: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM documents").fetch_one(pool).await.unwrap();
+        assert_eq!(documents as u64, manifest.document_count);
+        assert_eq!(SearchDictionaryQueries::get(pool).await.unwrap(), manifest.search_dictionaries);
+        let unembedded: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM documents d WHERE NOT EXISTS (SELECT 1 FROM embeddings e WHERE e.document_id = d.id)",
+        )
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        assert_eq!(unembedded, 0);
+        let unindexed: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM documents d WHERE NOT EXISTS (SELECT 1 FROM documents_fts f WHERE f.rowid = d.rowid)",
+        )
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        assert_eq!(unindexed, 0);
+    }
+
+    #[tokio::test]
+    async fn test_export_during_reindex_is_consistent() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Arc::new(test_database(&dir).await);
+        let chunks = |document: &Document, model: &str| -> Vec<Embedding> {
+            (0..6)
+                .map(|chunk| Embedding::new(document.id.clone(), vec![chunk as f32, 1.0], model.to_string(), chunk, format!("{} chunk {}", model, chunk), 0, 0))
+                .collect()
+        };
+        let mut documents = Vec::new();
+        for i in 0..20 {
+            let document = Document::new(format!("Note {}", i), format!("Indexed note {}", i), "text/plain".to_string());
+            db.create_document_with_embeddings(&document, &chunks(&document, "old")).await.unwrap();
+            documents.push(document);
+        }
+
+        // Reindex every document with another model, as index repair does
+        let reindexer = tokio::spawn({
+            let db = Arc::clone(&db);
+            async move {
+                for document in &documents {
+                    db.replace_document_embeddings(&document.id, &chunks(document, "new")).await.unwrap();
+                }
+            }
+        });
+
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let export_dir = dir.path().join("export");
+        let manifest = db.export_vault(&export_dir).await.unwrap();
+        reindexer.await.unwrap();
+
+        // Each document has all of its old chunks or all of its new ones
+        assert_eq!(manifest.embedding_count, 20 * 6);
+        let options = SqliteConnectOptions::new().filename(export_dir.join(EXPORT_DATABASE_FILE)).read_only(true);
+        let mut snapshot = SqliteConnection::connect_with(&options).await.unwrap();
+        let partial: i64 = sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*) FROM documents d
+            WHERE (SELECT COUNT(*) FROM embeddings e WHERE e.document_id = d.id) != 6
+               OR (SELECT COUNT(DISTINCT e.model) FROM embeddings e WHERE e.document_id = d.id) != 1
+            "#,
+        )
+        .fetch_one(&mut snapshot)
+        .await
+        .unwrap();
+        assert_eq!(partial, 0);
+        snapshot.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_waits_for_write_units() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = test_database(&dir).await;
+
+        let unit = db.write_unit().await;
+        let waiting = tokio::time::timeout(Duration::from_millis(100), db.snapshot(dir.path().join("blocked.db"))).await;
+        assert!(waiting.is_err());
+        drop(unit);
+
+        let taken_at = db.snapshot(dir.path().join("snapshot.db")).await.unwrap();
+        assert!(taken_at <= Utc::now());
+        assert!(tokio::time::timeout(Duration::from_secs(5), db.migrate()).await.unwrap().is_ok());
+    }
+}
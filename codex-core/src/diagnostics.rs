@@ -0,0 +1,364 @@
+//! Self-test exercising the whole stack
+//!
+//! [`run_diagnostics`] inserts a temporary document, finds it with full-text
+//! search, embeds it, finds it again by embedding similarity, runs a
+//! one-token generation and checks that the update server answers, then
+//! deletes the document. Each step is timed and reported on its own, so a
+//! failure points at the database, the index, the model or the network.
+//! The temporary document is filed under [`DIAGNOSTICS_CATEGORY`], which is
+//! hidden from every listing and search.
+
+use std::future::Future;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{CodexError, CodexResult};
+use crate::error::ErrorView;
+use crate::ai::{AiEngine, EmbeddingEngine};
+use crate::db::{app_schema_version, AiAuditQueries, DatabaseManager, DocumentQueries, EmbeddingQueries, MigrationRecord, SearchQueries, VectorOps};
+use crate::db::models::{Document, Embedding, DIAGNOSTICS_CATEGORY};
+use crate::update::UpdateManager;
+
+/// Longest wait for the update server before the step fails
+pub const UPDATE_CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+/// Least similarity between the probe text and its stored embedding
+const MIN_SELF_SIMILARITY: f32 = 0.9;
+
+/// Outcome of a diagnostics step
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepStatus {
+    Passed,
+    Failed,
+    /// Not run because a step it depends on failed
+    Skipped,
+}
+
+/// Result of one diagnostics step
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiagnosticStep {
+    /// Step name, e.g. "full_text_search"
+    pub name: String,
+    pub status: StepStatus,
+    pub duration_ms: u64,
+    /// What the step observed when it passed
+    pub details: Option<String>,
+    /// Why the step failed or was skipped, with paths and URLs redacted
+    pub error: Option<String>,
+    /// Code, retry hint and remediation when the step failed
+    #[serde(default)]
+    pub error_info: Option<ErrorView>,
+}
+
+/// Results of a diagnostics run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiagnosticsReport {
+    pub started_at: String,
+    pub duration_ms: u64,
+    /// Whether every step passed
+    pub passed: bool,
+    pub steps: Vec<DiagnosticStep>,
+    /// Schema migrations applied to the vault, oldest first
+    #[serde(default)]
+    pub migrations: Vec<MigrationRecord>,
+    /// Entries in the AI audit trail
+    #[serde(default)]
+    pub ai_audit_entries: i64,
+}
+
+impl DiagnosticsReport {
+    /// Result of the step with the given name
+    pub fn step(&self, name: &str) -> Option<&DiagnosticStep> {
+        self.steps.iter().find(|step| step.name == name)
+    }
+}
+
+/// Collects timed step results
+struct StepRunner {
+    started_at: String,
+    start: Instant,
+    steps: Vec<DiagnosticStep>,
+}
+
+impl StepRunner {
+    fn new() -> Self {
+        Self {
+            started_at: chrono::Utc::now().to_rfc3339(),
+            start: Instant::now(),
+            steps: Vec::new(),
+        }
+    }
+
+    /// Run a step whose output describes what it observed; returns whether it passed
+    async fn run<Fut>(&mut self, name: &str, step: Fut) -> bool
+    where
+        Fut: Future<Output = CodexResult<String>>,
+    {
+        let start = Instant::now();
+        let result = step.await;
+        let duration_ms = start.elapsed().as_millis() as u64;
+
+        let passed = result.is_ok();
+        let (details, error_info) = match result {
+            Ok(details) => (Some(details), None),
+            Err(e) => {
+                warn!("Diagnostics step {} failed: {}", name, e.report());
+                (None, Some(e.view()))
+            }
+        };
+        self.steps.push(DiagnosticStep {
+            name: name.to_string(),
+            status: if passed { StepStatus::Passed } else { StepStatus::Failed },
+            duration_ms,
+            details,
+            error: error_info.as_ref().map(|info| info.detail.clone()),
+            error_info,
+        });
+        passed
+    }
+
+    fn skip(&mut self, name: &str, reason: &str) {
+        self.steps.push(DiagnosticStep {
+            name: name.to_string(),
+            status: StepStatus::Skipped,
+            duration_ms: 0,
+            details: None,
+            error: Some(reason.to_string()),
+            error_info: None,
+        });
+    }
+
+    fn finish(self) -> DiagnosticsReport {
+        DiagnosticsReport {
+            started_at: self.started_at,
+            duration_ms: self.start.elapsed().as_millis() as u64,
+            passed: self.steps.iter().all(|step| step.status == StepStatus::Passed),
+            steps: self.steps,
+            migrations: Vec::new(),
+            ai_audit_entries: 0,
+        }
+    }
+}
+
+/// Run every diagnostics step against the given components
+pub async fn run_diagnostics(db: &DatabaseManager, ai: &AiEngine, update: &UpdateManager) -> DiagnosticsReport {
+    info!("Running diagnostics");
+    let mut runner = StepRunner::new();
+
+    let migrations = check_schema(&mut runner, db).await;
+    check_storage_and_search(&mut runner, db, ai.get_embeddings()).await;
+    runner
+        .run("generate", async {
+            let reply = ai.generate_text_limited("Reply with OK.", 1).await?;
+            Ok(format!("Model replied {:?}", reply.trim()))
+        })
+        .await;
+    check_update_server(&mut runner, update).await;
+    clean_up(&mut runner, db).await;
+
+    let mut report = runner.finish();
+    report.migrations = migrations;
+    match AiAuditQueries::count(db.pool()).await {
+        Ok(count) => report.ai_audit_entries = count,
+        Err(e) => warn!("Could not count AI audit entries: {}", e),
+    }
+    info!("Diagnostics finished: {}", if report.passed { "all steps passed" } else { "some steps failed" });
+    report
+}
+
+/// Check every recorded schema migration completed, returning the history
+async fn check_schema(runner: &mut StepRunner, db: &DatabaseManager) -> Vec<MigrationRecord> {
+    let mut migrations = Vec::new();
+    runner
+        .run("schema", async {
+            migrations = db.migration_history().await?;
+            if let Some(failed) = migrations.iter().find(|record| !record.success) {
+                return Err(CodexError::internal(format!("Migration {} did not complete", failed.version)));
+            }
+            let version = migrations.last().map_or(0, |record| record.version);
+            Ok(format!("Schema version {} of {} ({} migrations applied)", version, app_schema_version(), migrations.len()))
+        })
+        .await;
+    migrations
+}
+
+/// Insert a probe document and find it through full-text and semantic search
+async fn check_storage_and_search(runner: &mut StepRunner, db: &DatabaseManager, embeddings: &EmbeddingEngine) {
+    let token = format!("codexprobe{}", Uuid::new_v4().simple());
+    let mut document = Document::new(
+        "Diagnostics probe".to_string(),
+        format!("Diagnostics probe {}. This temporary document checks storage, search and embeddings.", token),
+        "text/plain".to_string(),
+    );
+    document.category = Some(DIAGNOSTICS_CATEGORY.to_string());
+
+    let inserted = runner
+        .run("insert_document", async {
+            // Documents left behind by an interrupted run
+            let leftovers = DocumentQueries::purge_diagnostics(db.pool()).await?;
+            DocumentQueries::create(db.pool(), &document, db.compression()).await?;
+            Ok(format!("Inserted document {} ({} leftovers removed)", document.id, leftovers))
+        })
+        .await;
+    if !inserted {
+        let reason = "Inserting the probe document failed";
+        for name in ["full_text_search", "embed_document", "semantic_search"] {
+            runner.skip(name, reason);
+        }
+        return;
+    }
+
+    runner
+        .run("full_text_search", async {
+            if SearchQueries::matches_document(db.pool(), &token, &document.id).await? {
+                Ok("Full-text index returned the probe document".to_string())
+            } else {
+                Err(CodexError::not_found("Full-text index did not return the probe document"))
+            }
+        })
+        .await;
+
+    let embedded = runner
+        .run("embed_document", async {
+            let vector = embeddings.generate_embedding(&document.content).await?;
+            let model = embeddings.get_model_info().name;
+            let dimensions = vector.len();
+            let embedding = Embedding::new(
+                document.id.clone(),
+                vector,
+                model.clone(),
+                0,
+                document.content.clone(),
+                0,
+                document.content.len() as i64,
+            );
+            EmbeddingQueries::create_with_binary(db.pool(), &embedding).await?;
+            Ok(format!("Stored a {}-dimensional embedding from {}", dimensions, model))
+        })
+        .await;
+    if !embedded {
+        runner.skip("semantic_search", "Embedding the probe document failed");
+        return;
+    }
+
+    runner
+        .run("semantic_search", async {
+            let query = embeddings.generate_embedding(&document.content).await?;
+            // Exercises the search path; the probe itself is hidden from results
+            SearchQueries::search_semantic(db.pool(), &query, Some(1), None).await?;
+
+            let similarity = EmbeddingQueries::get_by_document(db.pool(), &document.id)
+                .await?
+                .iter()
+                .map(|embedding| VectorOps::cosine_similarity(&query, &embedding.get_vector()))
+                .fold(f32::MIN, f32::max);
+            if similarity >= MIN_SELF_SIMILARITY {
+                Ok(format!("Probe document matched with similarity {:.3}", similarity))
+            } else {
+                Err(CodexError::not_found(format!(
+                    "Probe document matched with similarity {:.3}, expected at least {}",
+                    similarity, MIN_SELF_SIMILARITY
+                )))
+            }
+        })
+        .await;
+}
+
+async fn check_update_server(runner: &mut StepRunner, update: &UpdateManager) {
+    runner
+        .run("update_server", async {
+            tokio::time::timeout(UPDATE_CHECK_TIMEOUT, update.check_reachability())
+                .await
+                .map_err(|_| CodexError::update("Update server did not answer in time"))??;
+            Ok(format!("Reached {}", update.get_config().server_url))
+        })
+        .await;
+}
+
+async fn clean_up(runner: &mut StepRunner, db: &DatabaseManager) {
+    runner
+        .run("cleanup", async {
+            let removed = DocumentQueries::purge_diagnostics(db.pool()).await?;
+            Ok(format!("Removed {} temporary documents", removed))
+        })
+        .await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_database;
+    use crate::config::{AiConfig, UpdateConfig};
+
+    #[tokio::test]
+    async fn test_diagnostics_report_and_cleanup() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = test_database(&dir).await;
+        let embeddings = EmbeddingEngine::new(&AiConfig {
+            models_dir: dir.path().join("models"),
+            ..AiConfig::default()
+        })
+        .await
+        .unwrap();
+        // Nothing listens on the discard port, so the update check fails fast
+        let update = UpdateManager::new(&UpdateConfig {
+            server_url: "http://127.0.0.1:9".to_string(),
+            ..UpdateConfig::default()
+        })
+        .await
+        .unwrap();
+
+        let visible = Document::new("Visible".to_string(), "codexprobe visible".to_string(), "text/plain".to_string());
+        DocumentQueries::create(db.pool(), &visible, db.compression()).await.unwrap();
+
+        // Generation needs a model file, so the test runs every other step
+        let mut runner = StepRunner::new();
+        let migrations = check_schema(&mut runner, &db).await;
+        check_storage_and_search(&mut runner, &db, &embeddings).await;
+        check_update_server(&mut runner, &update).await;
+        clean_up(&mut runner, &db).await;
+        let report = runner.finish();
+
+        assert_eq!(migrations.last().map(|record| record.version), Some(app_schema_version()));
+        for name in ["schema", "insert_document", "full_text_search", "embed_document", "semantic_search", "cleanup"] {
+            assert_eq!(report.step(name).unwrap().status, StepStatus::Passed, "{:?}", report.step(name));
+        }
+        let update_step = report.step("update_server").unwrap();
+        assert_eq!(update_step.status, StepStatus::Failed);
+        assert!(update_step.error.is_some());
+        let info = update_step.error_info.as_ref().unwrap();
+        assert!(!info.message.is_empty());
+        assert!(!info.detail.contains("127.0.0.1"), "{}", info.detail);
+        assert!(!report.passed);
+
+        let (remaining,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM documents WHERE category = ?")
+            .bind(DIAGNOSTICS_CATEGORY)
+            .fetch_one(db.pool())
+            .await
+            .unwrap();
+        assert_eq!(remaining, 0);
+        assert_eq!(DocumentQueries::get_all(db.pool()).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_diagnostic_documents_are_hidden() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = test_database(&dir).await;
+        let pool = db.pool();
+
+        let mut probe = Document::new("Probe".to_string(), "hiddenprobe text".to_string(), "text/plain".to_string());
+        probe.category = Some(DIAGNOSTICS_CATEGORY.to_string());
+        DocumentQueries::create(pool, &probe, db.compression()).await.unwrap();
+
+        assert!(DocumentQueries::get_recent(pool, 10).await.unwrap().is_empty());
+        assert!(DocumentQueries::get_all(pool).await.unwrap().is_empty());
+        assert!(DocumentQueries::get_titles(pool).await.unwrap().is_empty());
+        assert!(SearchQueries::search(pool, "hiddenprobe", None).await.unwrap().is_empty());
+        assert!(SearchQueries::matches_document(pool, "hiddenprobe", &probe.id).await.unwrap());
+
+        assert_eq!(DocumentQueries::purge_diagnostics(pool).await.unwrap(), 1);
+        assert!(!SearchQueries::matches_document(pool, "hiddenprobe", &probe.id).await.unwrap());
+    }
+}
@@ -1,5 +1,13 @@
 //! Error handling for the Codex Core library
-
+//!
+//! [`CodexError`] keeps its source errors for logs ([`CodexError::report`]).
+//! What is shown to users and sent to the UI is an [`ErrorView`]: a stable
+//! code, a short plain-language message, a retry hint and the error text
+//! with file paths and URLs redacted.
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 /// Result type alias for Codex Core operations
@@ -67,6 +75,18 @@ pub enum CodexError {
     /// Checksum verification errors
     #[error("Checksum verification failed: {0}")]
     ChecksumVerification(String),
+
+    /// Not enough free space on the target volume, in bytes
+    #[error("Insufficient disk space: {required} bytes required, {available} available")]
+    InsufficientDiskSpace { required: u64, available: u64 },
+
+    /// A versioned write was based on an outdated copy of a document
+    #[error("Conflict: the document was changed elsewhere (now at version {current_version})")]
+    Conflict { current_version: i64 },
+
+    /// The vault was migrated by a newer release than this one
+    #[error("Vault schema version {vault_version} is newer than this app supports ({app_version})")]
+    VaultNewerThanApp { vault_version: i64, app_version: i64 },
 }
 
 impl CodexError {
@@ -140,6 +160,26 @@ impl CodexError {
         Self::ChecksumVerification(msg.into())
     }
 
+    /// Create a new insufficient disk space error
+    pub fn insufficient_disk_space(required: u64, available: u64) -> Self {
+        Self::InsufficientDiskSpace { required, available }
+    }
+
+    /// Create a new conflict error for a document now at `current_version`
+    pub fn conflict(current_version: i64) -> Self {
+        Self::Conflict { current_version }
+    }
+
+    /// Check if this is a version conflict
+    pub fn is_conflict(&self) -> bool {
+        matches!(self, Self::Conflict { .. })
+    }
+
+    /// Create a new error for a vault at a schema version above `app_version`
+    pub fn vault_newer_than_app(vault_version: i64, app_version: i64) -> Self {
+        Self::VaultNewerThanApp { vault_version, app_version }
+    }
+
     /// Create a new network error from a reqwest error
     pub fn network(err: reqwest::Error) -> Self {
         Self::Network(err)
@@ -157,9 +197,282 @@ impl From<anyhow::Error> for CodexError {
     }
 }
 
+/// Serializable form of a [`CodexError`] for the UI and reports
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorView {
+    /// Stable identifier of the error kind, e.g. "not_found"
+    pub code: String,
+    /// Short, non-technical description
+    pub message: String,
+    /// Full error text with file paths and URLs redacted
+    pub detail: String,
+    /// Whether trying the same operation again may succeed
+    pub retryable: bool,
+    /// What the user can do about it
+    pub remediation: Option<String>,
+}
+
+impl CodexError {
+    /// Stable identifier of the error kind
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Database(_) => "database",
+            Self::AiInference(_) => "ai_inference",
+            Self::ContentProcessing(_) => "content_processing",
+            Self::Update(_) => "update",
+            Self::Config(_) => "config",
+            Self::Io(_) => "io",
+            Self::Serialization(_) => "serialization",
+            Self::Network(_) => "network",
+            Self::Validation(_) => "validation",
+            Self::NotFound(_) => "not_found",
+            Self::PermissionDenied(_) => "permission_denied",
+            Self::Internal(_) => "internal",
+            Self::Migration(_) => "migration",
+            Self::ModelVerification(_) => "model_verification",
+            Self::ChecksumVerification(_) => "checksum_verification",
+            Self::InsufficientDiskSpace { .. } => "insufficient_disk_space",
+            Self::Conflict { .. } => "conflict",
+            Self::VaultNewerThanApp { .. } => "vault_newer_than_app",
+        }
+    }
+
+    /// Whether the failure is transient, so the same operation may succeed later
+    ///
+    /// True for busy or locked databases, pool and network timeouts,
+    /// connection failures, rate limiting and server errors.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Database(e) => match e {
+                sqlx::Error::PoolTimedOut | sqlx::Error::Io(_) => true,
+                // SQLITE_BUSY and SQLITE_LOCKED, including extended codes
+                sqlx::Error::Database(db) => db
+                    .code()
+                    .and_then(|code| code.parse::<i32>().ok())
+                    .is_some_and(|code| matches!(code & 0xff, 5 | 6)),
+                _ => false,
+            },
+            Self::Network(e) => {
+                e.is_timeout()
+                    || e.is_connect()
+                    || e.status().is_some_and(|status| status.is_server_error() || status.as_u16() == 429)
+            }
+            Self::Io(e) => matches!(
+                e.kind(),
+                std::io::ErrorKind::Interrupted
+                    | std::io::ErrorKind::TimedOut
+                    | std::io::ErrorKind::WouldBlock
+                    | std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::ConnectionAborted
+            ),
+            _ => false,
+        }
+    }
+
+    /// Short, non-technical description for display
+    pub fn user_message(&self) -> &'static str {
+        match self {
+            Self::Database(_) => "The library database could not complete the request.",
+            Self::AiInference(_) => "The AI model could not produce a response.",
+            Self::ContentProcessing(_) => "The document could not be processed.",
+            Self::Update(_) => "The update could not be completed.",
+            Self::Config(_) => "The app settings are not valid.",
+            Self::Io(_) => "A file could not be read or written.",
+            Self::Serialization(_) => "Some saved data could not be read.",
+            Self::Network(_) => "The server could not be reached.",
+            Self::Validation(_) => "The request was not valid.",
+            Self::NotFound(_) => "The item could not be found.",
+            Self::PermissionDenied(_) => "Access to the file or folder was denied.",
+            Self::Internal(_) => "Something went wrong.",
+            Self::Migration(_) => "The library database could not be upgraded.",
+            Self::ModelVerification(_) => "The AI model file failed verification.",
+            Self::ChecksumVerification(_) => "A downloaded file is damaged.",
+            Self::InsufficientDiskSpace { .. } => "There is not enough free disk space.",
+            Self::Conflict { .. } => "This document was changed in another window.",
+            Self::VaultNewerThanApp { .. } => "This library was opened with a newer version of the app.",
+        }
+    }
+
+    /// What the user can do about the error, when there is something
+    pub fn remediation(&self) -> Option<&'static str> {
+        if self.is_retryable() {
+            return Some(match self {
+                Self::Network(_) => "Check your internet connection and try again.",
+                _ => "Try again in a moment.",
+            });
+        }
+        match self {
+            Self::Database(_) => Some("Run diagnostics from Settings to check the database."),
+            Self::AiInference(_) => Some("Check that a model is installed and loaded in Settings."),
+            Self::ContentProcessing(_) => Some("Check the file is not damaged and is in a supported format."),
+            Self::Update(_) => Some("Try again later or download the latest version from the website."),
+            Self::Config(_) => Some("Review your settings or reset them to the defaults."),
+            Self::Io(_) => Some("Check the file exists and the app can access its folder."),
+            Self::Network(_) => Some("Check your internet connection and try again."),
+            Self::Validation(_) => Some("Check the values you entered."),
+            Self::NotFound(_) => Some("It may have been deleted; refresh and try again."),
+            Self::PermissionDenied(_) => Some("Allow the app to access the file or folder."),
+            Self::Migration(_) => Some("Restart the app; if it keeps failing, restore the database from a backup."),
+            Self::ModelVerification(_) => Some("Download the model again."),
+            Self::ChecksumVerification(_) => Some("Download the file again."),
+            Self::InsufficientDiskSpace { .. } => Some("Free up disk space and try again."),
+            Self::Conflict { .. } => Some("Reload the document and apply your changes again."),
+            Self::VaultNewerThanApp { .. } => Some("Update the app to the latest version to open this library."),
+            Self::Serialization(_) | Self::Internal(_) => None,
+        }
+    }
+
+    /// Error text followed by each source error, for logs
+    ///
+    /// Not redacted; use [`CodexError::view`] for anything shown or sent.
+    pub fn report(&self) -> String {
+        let mut report = self.to_string();
+        let mut source = std::error::Error::source(self);
+        while let Some(cause) = source {
+            let cause_text = cause.to_string();
+            // thiserror's transparent messages already include the first cause
+            if !report.contains(&cause_text) {
+                report.push_str(": ");
+                report.push_str(&cause_text);
+            }
+            source = cause.source();
+        }
+        report
+    }
+
+    /// Serializable view with the detail redacted
+    pub fn view(&self) -> ErrorView {
+        ErrorView {
+            code: self.code().to_string(),
+            message: self.user_message().to_string(),
+            detail: redact(&self.to_string()),
+            retryable: self.is_retryable(),
+            remediation: self.remediation().map(str::to_string),
+        }
+    }
+}
+
+impl From<&CodexError> for ErrorView {
+    fn from(err: &CodexError) -> Self {
+        err.view()
+    }
+}
+
+impl Serialize for CodexError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.view().serialize(serializer)
+    }
+}
+
+/// URLs of any scheme, including `sqlite://` and `file://`
+static URL_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r#"\b[a-zA-Z][a-zA-Z0-9+.-]*://[^\s'"<>)]*[^\s'"<>).,;:]"#).unwrap());
+/// Quoted paths, which may contain spaces
+static QUOTED_PATH_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#""(?:[A-Za-z]:[\\/]|\\\\|~/|/)[^"]*""#).unwrap());
+/// Unquoted absolute, home-relative, drive-letter and UNC paths
+static PATH_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(^|[\s'(=])(?:[A-Za-z]:[\\/]|\\\\|~/|/)[^\s'"()]*[^\s'"().,;:]"#).unwrap()
+});
+
+/// Replace file paths and URLs in error text, which can reveal user names,
+/// document titles or private hosts
+pub fn redact(text: &str) -> String {
+    let text = URL_RE.replace_all(text, "<url>");
+    let text = QUOTED_PATH_RE.replace_all(&text, "\"<path>\"");
+    PATH_RE.replace_all(&text, "${1}<path>").into_owned()
+}
+
 /// Conversion from bincode errors
 impl From<Box<bincode::ErrorKind>> for CodexError {
     fn from(err: Box<bincode::ErrorKind>) -> Self {
         Self::Internal(format!("Bincode serialization error: {}", err))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One error of every variant, with the code each must keep
+    fn every_variant() -> Vec<(CodexError, &'static str)> {
+        let json_error = serde_json::from_str::<serde_json::Value>("{").unwrap_err();
+        let network_error = reqwest::Client::new().get("not a url").build().unwrap_err();
+        vec![
+            (CodexError::Database(sqlx::Error::RowNotFound), "database"),
+            (CodexError::ai_inference("no model"), "ai_inference"),
+            (CodexError::content_processing("bad pdf"), "content_processing"),
+            (CodexError::update("bad patch"), "update"),
+            (CodexError::config("bad value"), "config"),
+            (CodexError::io(std::io::Error::new(std::io::ErrorKind::NotFound, "gone")), "io"),
+            (CodexError::Serialization(json_error), "serialization"),
+            (CodexError::network(network_error), "network"),
+            (CodexError::validation("empty title"), "validation"),
+            (CodexError::not_found("document"), "not_found"),
+            (CodexError::permission_denied("vault"), "permission_denied"),
+            (CodexError::internal("bug"), "internal"),
+            (CodexError::Migration(sqlx::migrate::MigrateError::VersionMissing(3)), "migration"),
+            (CodexError::model_verification("bad header"), "model_verification"),
+            (CodexError::checksum_verification("mismatch"), "checksum_verification"),
+            (CodexError::insufficient_disk_space(10, 1), "insufficient_disk_space"),
+            (CodexError::conflict(4), "conflict"),
+            (CodexError::vault_newer_than_app(30, 24), "vault_newer_than_app"),
+        ]
+    }
+
+    #[test]
+    fn test_every_variant_has_a_stable_code_and_message() {
+        let errors = every_variant();
+        let mut codes: Vec<&str> = errors.iter().map(|(error, _)| error.code()).collect();
+        for (error, code) in &errors {
+            assert_eq!(error.code(), *code);
+            assert!(!error.user_message().is_empty());
+            let json = serde_json::to_value(error).unwrap();
+            assert_eq!(json["code"], *code);
+            assert_eq!(serde_json::from_value::<ErrorView>(json).unwrap(), error.view());
+        }
+        codes.sort_unstable();
+        codes.dedup();
+        assert_eq!(codes.len(), errors.len(), "codes must be unique");
+    }
+
+    #[test]
+    fn test_retryability() {
+        assert!(CodexError::Database(sqlx::Error::PoolTimedOut).is_retryable());
+        assert!(CodexError::io(std::io::Error::new(std::io::ErrorKind::TimedOut, "slow")).is_retryable());
+        assert!(!CodexError::Database(sqlx::Error::RowNotFound).is_retryable());
+        assert!(!CodexError::validation("empty title").is_retryable());
+        assert!(!CodexError::insufficient_disk_space(10, 1).is_retryable());
+
+        let busy = CodexError::Database(sqlx::Error::PoolTimedOut);
+        assert_eq!(busy.remediation(), Some("Try again in a moment."));
+        assert!(CodexError::validation("x").remediation().is_some());
+    }
+
+    #[test]
+    fn test_view_redacts_paths_and_urls() {
+        let error = CodexError::not_found(
+            "Model file not found: /home/jo/models/llama.gguf (from https://models.example.com/llama?token=abc)",
+        );
+        let view = error.view();
+        assert_eq!(view.detail, "Not found: Model file not found: <path> (from <url>)");
+        assert!(error.to_string().contains("/home/jo"));
+
+        for (text, expected) in [
+            (r"Failed to open C:\Users\Jo\codex.db.", "Failed to open <path>."),
+            (r"share \\server\docs\a.txt missing", "share <path> missing"),
+            ("path=\"/Users/Jo Smith/Vault/codex.db\" locked", "path=\"<path>\" locked"),
+            ("cache ~/Library/Caches/codex full", "cache <path> full"),
+            ("sqlite:///tmp/codex.db busy", "<url> busy"),
+            ("3 / 4 chunks and/or pages", "3 / 4 chunks and/or pages"),
+        ] {
+            assert_eq!(redact(text), expected, "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_report_keeps_the_source_chain() {
+        let error = CodexError::io(std::io::Error::other("disk unplugged"));
+        assert!(error.report().contains("disk unplugged"));
+        assert!(std::error::Error::source(&error).is_some());
+    }
+}
@@ -0,0 +1,153 @@
+//! Batching of high-frequency events for the UI
+//!
+//! Generated tokens and progress updates can arrive far faster than a UI
+//! can draw them, and each one sent to a webview is a round trip over the
+//! IPC bridge. [`ChunkCoalescer`] buffers streamed text and passes it on in
+//! batches; [`ProgressThrottle`] lets through at most one progress update
+//! per interval, plus the last.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config::EventBatchingConfig;
+
+/// Whether buffered text ends a sentence or line
+fn ends_sentence(text: &str) -> bool {
+    text.ends_with('\n') || text.trim_end().ends_with(['.', '!', '?'])
+}
+
+struct Pending {
+    buffer: String,
+    last_flush: Instant,
+}
+
+/// Buffers streamed text and passes it on in batches
+///
+/// Text is flushed once the flush interval has passed since the last
+/// flush, once enough characters are buffered, or when it ends a sentence.
+/// Flushes happen as text arrives, so call [`ChunkCoalescer::flush`] when
+/// the stream ends.
+pub struct ChunkCoalescer<F> {
+    pending: Mutex<Pending>,
+    interval: Duration,
+    max_chars: usize,
+    sink: F,
+}
+
+impl<F: Fn(String)> ChunkCoalescer<F> {
+    pub fn new(config: &EventBatchingConfig, sink: F) -> Self {
+        Self {
+            pending: Mutex::new(Pending {
+                buffer: String::new(),
+                last_flush: Instant::now(),
+            }),
+            interval: Duration::from_millis(config.flush_interval_ms),
+            max_chars: config.flush_chars,
+            sink,
+        }
+    }
+
+    /// Add streamed text, passing the buffer on if a flush is due
+    pub fn push(&self, chunk: &str) {
+        let ready = {
+            let mut pending = self.pending.lock().unwrap();
+            pending.buffer.push_str(chunk);
+            let due = pending.last_flush.elapsed() >= self.interval
+                || pending.buffer.chars().count() >= self.max_chars
+                || ends_sentence(&pending.buffer);
+            if !due {
+                return;
+            }
+            pending.last_flush = Instant::now();
+            std::mem::take(&mut pending.buffer)
+        };
+        (self.sink)(ready);
+    }
+
+    /// Pass on any buffered text
+    pub fn flush(&self) {
+        let ready = {
+            let mut pending = self.pending.lock().unwrap();
+            pending.last_flush = Instant::now();
+            std::mem::take(&mut pending.buffer)
+        };
+        if !ready.is_empty() {
+            (self.sink)(ready);
+        }
+    }
+}
+
+/// Rate limit for progress updates
+pub struct ProgressThrottle {
+    interval: Duration,
+    last_sent: Mutex<Option<Instant>>,
+}
+
+impl ProgressThrottle {
+    pub fn new(config: &EventBatchingConfig) -> Self {
+        Self {
+            interval: Duration::from_millis(config.flush_interval_ms),
+            last_sent: Mutex::new(None),
+        }
+    }
+
+    /// Whether an update should be sent now; the first and final ones always are
+    pub fn ready(&self, is_final: bool) -> bool {
+        let mut last_sent = self.last_sent.lock().unwrap();
+        let due = is_final || last_sent.is_none_or(|sent| sent.elapsed() >= self.interval);
+        if due {
+            *last_sent = Some(Instant::now());
+        }
+        due
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_long_generation_is_batched() {
+        // Long enough that only sentences and size trigger flushes
+        let config = EventBatchingConfig { flush_interval_ms: 60_000, flush_chars: 200 };
+        let events = Arc::new(Mutex::new(Vec::<String>::new()));
+        let sink = Arc::clone(&events);
+        let coalescer = ChunkCoalescer::new(&config, move |text| sink.lock().unwrap().push(text));
+
+        // 2,000 tokens, a sentence every 40
+        let tokens: Vec<String> = (1..=2000)
+            .map(|i| if i % 40 == 0 { " end.".to_string() } else { format!(" w{}", i % 10) })
+            .collect();
+        for token in &tokens {
+            coalescer.push(token);
+        }
+        coalescer.flush();
+
+        let events = events.lock().unwrap();
+        assert!(events.len() <= 2000 / 40 + 1, "{} events", events.len());
+        assert_eq!(events.concat(), tokens.concat());
+        assert!(events[..events.len() - 1].iter().all(|e| e.ends_with('.')));
+
+        // Without sentences the size limit still bounds the batches
+        let batches = Arc::new(Mutex::new(0usize));
+        let counter = Arc::clone(&batches);
+        let coalescer = ChunkCoalescer::new(&config, move |_| *counter.lock().unwrap() += 1);
+        for _ in 0..2000 {
+            coalescer.push(" word");
+        }
+        coalescer.flush();
+        assert_eq!(*batches.lock().unwrap(), 2000 * 5 / 200);
+    }
+
+    #[test]
+    fn test_progress_throttle_keeps_first_and_final() {
+        let throttle = ProgressThrottle::new(&EventBatchingConfig { flush_interval_ms: 60_000, flush_chars: 200 });
+        assert!(throttle.ready(false));
+        assert!(!throttle.ready(false));
+        assert!(throttle.ready(true));
+
+        let unbatched = ProgressThrottle::new(&EventBatchingConfig { flush_interval_ms: 0, flush_chars: 200 });
+        assert!(unbatched.ready(false) && unbatched.ready(false));
+    }
+}
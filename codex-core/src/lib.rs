@@ -17,6 +17,21 @@
 //! - `ai`: AI inference and embeddings
 //! - `content`: Content processing and search
 //! - `update`: Application update management
+//! - `logging`: Tracing setup and the in-app log buffer
+//! - `metrics`: Per-operation latency histograms
+//! - `diagnostics`: Self-test exercising the whole stack
+//! - `paths`: Database URLs and model paths that work on every platform
+//! - `events`: Batching of streamed text and progress events for the UI
+//! - `warmup`: Background cache warm-up after startup
+//! - `settings_bundle`: Export and import of settings apart from the vault
+//! - `clock`: Injectable time source for cache expiry and maintenance
+//! - `power`: Throttling on a laptop's battery or under thermal pressure
+//! - `api_server`: Local HTTP API for integrations (`api-server` feature)
+//! - `mcp`: Model Context Protocol server over stdio (`mcp` feature)
+//! - `builder`: [`CodexCoreBuilder`] for embedding the core in other applications
+//!
+//! The types most callers need, such as [`Document`], [`SearchOptions`],
+//! [`SearchResults`] and [`RagResponse`], are re-exported here.
 
 use std::sync::Arc;
 use tokio::sync::RwLock;
@@ -28,9 +43,27 @@ pub mod content;
 pub mod update;
 pub mod error;
 pub mod config;
+pub mod logging;
+pub mod metrics;
+pub mod diagnostics;
+pub mod events;
+pub mod paths;
+pub mod warmup;
+pub mod settings_bundle;
+pub mod clock;
+pub mod power;
+pub mod builder;
+#[cfg(feature = "api-server")]
+pub mod api_server;
+#[cfg(feature = "mcp")]
+pub mod mcp;
 
 pub use error::{CodexError, CodexResult};
 pub use config::CodexConfig;
+pub use builder::CodexCoreBuilder;
+pub use db::models::Document;
+pub use content::{SearchOptions, SearchResult, SearchResults};
+pub use ai::RagResponse;
 
 /// Main application state containing all core components
 #[derive(Clone)]
@@ -45,6 +78,15 @@ pub struct CodexCore {
     pub update: Arc<update::UpdateManager>,
     /// Application configuration
     pub config: Arc<RwLock<CodexConfig>>,
+    /// Background cache warm-up started after initialization
+    pub warm_up: Arc<warmup::WarmUp>,
+    /// Battery and thermal readings driving the power policy
+    pub power: Arc<power::PowerMonitor>,
+    /// Local HTTP API server, while running
+    #[cfg(feature = "api-server")]
+    api_server: Arc<tokio::sync::Mutex<Option<api_server::ApiServer>>>,
+    /// Started with [`CodexConfig::safe_mode`]
+    safe_mode: bool,
 }
 
 impl CodexCore {
@@ -69,55 +111,209 @@ impl CodexCore {
 
     /// Initialize the Codex Core library with custom configuration
     ///
+    /// With [`CodexConfig::safe_mode`] no AI model is loaded and no
+    /// background job, schedule or update check is started; browsing,
+    /// full-text search and export keep working.
+    ///
     /// # Arguments
     ///
     /// * `config` - Custom configuration for the application
     pub async fn with_config(config: CodexConfig) -> Result<Self> {
-        tracing::info!("Initializing Codex Core library");
+        let safe_mode = config.safe_mode;
+        if safe_mode {
+            tracing::warn!("Initializing Codex Core library in safe mode");
+            // Only this launch is affected by a request for safe mode
+            if let Err(e) = CodexConfig::clear_safe_mode_request().await {
+                tracing::warn!("Failed to clear the safe mode request: {}", e);
+            }
+        } else {
+            tracing::info!("Initializing Codex Core library");
+        }
 
-        // Initialize database manager
-        let db = Arc::new(db::DatabaseManager::new(&config.database).await?);
-        
-        // Initialize AI engine
-        let ai = Arc::new(ai::AiEngine::new(&config.ai).await?);
+        let db = db::DatabaseManager::new(&config.database).await?;
+        let ai = if safe_mode {
+            ai::AiEngine::new_disabled(&config.ai)
+        } else {
+            ai::AiEngine::new(&config.ai).await?
+        };
+        Self::assemble(config, db, ai, !safe_mode).await
+    }
+
+    /// Start a core on an opened database and AI engine
+    ///
+    /// Without `background` no maintenance, schedule, warm-up, update check,
+    /// power throttling or API server is started, as in safe mode.
+    async fn assemble(config: CodexConfig, db: db::DatabaseManager, ai: ai::AiEngine, background: bool) -> Result<Self> {
+        let safe_mode = config.safe_mode;
+
+        let db = Arc::new(db.with_compression(config.content.compression_policy()));
+        if background {
+            db.start_vector_cache_maintenance();
+            db.start_ai_audit_maintenance(config.ai.audit.retention_days);
+            if config.app.enable_telemetry {
+                metrics::start_daily_rollup(&db);
+            }
+        }
         
+        update::DownloadControl::global().set_speed_limit(config.network.max_download_bytes_per_sec);
+
+        let ai = Arc::new(ai);
+        ai.set_database(Arc::clone(&db));
+
+        // First run: fetch the embedding model in the background unless offline
+        if background
+            && config.ai.embedding.auto_download
+            && !config.app.offline_mode
+            && !ai.get_embeddings().is_model_loaded()
+        {
+            let ai = Arc::clone(&ai);
+            let ai_config = config.ai.clone();
+            tokio::spawn(async move {
+                match update::model_downloader::ensure_embedding_model(&ai_config, ai.get_embeddings()).await {
+                    Ok(_) => tracing::info!("Embedding model {} is ready", ai_config.embedding.model),
+                    Err(e) => tracing::warn!("Failed to download the embedding model: {}", e),
+                }
+            });
+        }
+
         // Initialize content manager
         let content = Arc::new(content::ContentManager::new(
             Arc::clone(&db),
             Arc::clone(&ai),
             &config.content,
         ).await?);
+        if background {
+            Self::start_background_work(&content, &config).await;
+        }
         
         // Initialize update manager
-        let update = Arc::new(update::UpdateManager::new(&config.update).await?);
+        let mut update_config = config.update.clone();
+        update_config.auto_check &= background;
+        let update = Arc::new(update::UpdateManager::new(&update_config).await?);
+
+        // Warm caches in the background; the core is usable meanwhile
+        let warm_up = Arc::new(warmup::WarmUp::new());
+        let mut warm_up_config = config.app.warm_up.clone();
+        warm_up_config.enabled &= background;
+        warm_up.start(warm_up_config, Arc::clone(&db), Arc::clone(&ai));
 
+        // Throttle inference and jobs on battery; a no-op without one
+        let power = Arc::new(power::PowerMonitor::new(config.power.clone()));
+        if background {
+            Self::start_power_throttling(&power, &ai, &content);
+        }
+
+        #[cfg(feature = "api-server")]
+        let start_api_server = config.app.api_server.enabled && background;
         let config = Arc::new(RwLock::new(config));
 
         tracing::info!("Codex Core library initialized successfully");
 
-        Ok(Self {
+        let core = Self {
             db,
             ai,
             content,
             update,
             config,
-        })
+            warm_up,
+            power,
+            #[cfg(feature = "api-server")]
+            api_server: Arc::new(tokio::sync::Mutex::new(None)),
+            safe_mode,
+        };
+
+        #[cfg(feature = "api-server")]
+        if start_api_server {
+            if let Err(e) = core.start_api_server().await {
+                tracing::warn!("Failed to start the API server: {}", e);
+            }
+        }
+
+        Ok(core)
+    }
+
+    /// Schedules and recovery of interrupted work, all skipped in safe mode
+    async fn start_background_work(content: &Arc<content::ContentManager>, config: &CodexConfig) {
+        // Vectors of another embedding model are left out of search until reindexed
+        if let Err(e) = content.check_embedding_model().await {
+            tracing::warn!("Failed to check the embedding model against the index: {}", e);
+        }
+        content.start_digest_schedule();
+        content.start_attachment_maintenance();
+        if !config.app.offline_mode {
+            content.start_feed_schedule();
+        }
+
+        // Pick up index work cut short by a crash; imports wait for the user
+        {
+            let content = Arc::clone(content);
+            tokio::spawn(async move {
+                if let Err(e) = content.resume_interrupted_operations().await {
+                    tracing::warn!("Failed to resume interrupted operations: {}", e);
+                }
+                // A new embedding model makes stored vectors unusable
+                if let Err(e) = content.reindex_for_embedding_model().await {
+                    tracing::warn!("Failed to reindex for the embedding model: {}", e);
+                }
+            });
+        }
+    }
+
+    /// Apply the power policy's limits whenever the power state or policy changes
+    fn start_power_throttling(
+        power: &Arc<power::PowerMonitor>,
+        ai: &Arc<ai::AiEngine>,
+        content: &Arc<content::ContentManager>,
+    ) {
+        let mut states = power.subscribe();
+        let (monitor, ai, content) = (Arc::clone(power), Arc::clone(ai), Arc::clone(content));
+        tokio::spawn(async move {
+            let throttle = ai.generation_throttle().await;
+            loop {
+                let state = states.borrow_and_update().clone();
+                let limits = power::ThrottleLimits::for_state(&monitor.policy(), &state);
+                if state.throttled() {
+                    tracing::info!("Reducing performance ({:?}): {:?}", state.throttle, limits);
+                }
+                throttle.set(limits.concurrency, limits.max_tokens);
+                content.set_background_jobs_paused(limits.pause_background_jobs);
+                if states.changed().await.is_err() {
+                    break;
+                }
+            }
+        });
+        power.start();
     }
 
     /// Shutdown the core library gracefully
     pub async fn shutdown(&self) -> Result<()> {
         tracing::info!("Shutting down Codex Core library");
         
+        self.warm_up.cancel();
+        self.power.stop();
+
         // Shutdown components in reverse order
+        #[cfg(feature = "api-server")]
+        self.stop_api_server().await;
         self.update.shutdown().await?;
         self.content.shutdown().await?;
         self.ai.shutdown().await?;
+        if self.config.read().await.app.enable_telemetry {
+            if let Err(e) = metrics::persist_daily_rollup(&self.db).await {
+                tracing::warn!("Failed to persist metrics rollup: {}", e);
+            }
+        }
         self.db.shutdown().await?;
         
         tracing::info!("Codex Core library shutdown complete");
         Ok(())
     }
 
+    /// Whether the core was started in safe mode
+    pub fn safe_mode(&self) -> bool {
+        self.safe_mode
+    }
+
     /// Get the current configuration (read-only)
     pub async fn get_config(&self) -> CodexConfig {
         self.config.read().await.clone()
@@ -129,24 +325,167 @@ impl CodexCore {
         F: FnOnce(&mut CodexConfig) -> Result<()>,
     {
         let mut config = self.config.write().await;
-        updater(&mut *config)?;
+        updater(&mut config)?;
         config.save().await?;
         Ok(())
     }
 
+    /// Write the configuration, settings and rules to a bundle at `dest`
+    pub async fn export_settings(&self, dest: &std::path::Path) -> CodexResult<settings_bundle::SettingsBundle> {
+        self.config.read().await.export_settings(&self.db, dest).await
+    }
+
+    /// Import a settings bundle, or with `dry_run` only report what it would change
+    ///
+    /// An applied import saves the configuration for later launches.
+    pub async fn import_settings(
+        &self,
+        src: &std::path::Path,
+        dry_run: bool,
+    ) -> CodexResult<settings_bundle::SettingsImportReport> {
+        let mut config = self.config.write().await;
+        let report = config.import_settings(&self.db, src, dry_run).await?;
+        if !dry_run {
+            config.save().await?;
+        }
+        Ok(report)
+    }
+
+    /// Change the download speed limit now and for later launches
+    pub async fn set_download_speed_limit(&self, max_bytes_per_sec: Option<u64>) -> Result<()> {
+        update::DownloadControl::global().set_speed_limit(max_bytes_per_sec);
+        self.update_config(|config| {
+            config.network.max_download_bytes_per_sec = max_bytes_per_sec;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Battery and thermal state, and whether performance is reduced
+    pub fn get_power_state(&self) -> power::PowerState {
+        self.power.state()
+    }
+
+    /// Change the power policy now and for later launches
+    pub async fn set_power_policy(&self, policy: config::PowerPolicy) -> Result<power::PowerState> {
+        anyhow::ensure!(policy.throttled_concurrency > 0, "Power throttled_concurrency must be > 0");
+        let state = self.power.set_policy(policy.clone());
+        self.update_config(|config| {
+            config.power = policy;
+            Ok(())
+        })
+        .await?;
+        Ok(state)
+    }
+
+    /// Long-running operations interrupted in an earlier session and not yet resumed
+    pub async fn get_pending_operations(&self) -> CodexResult<Vec<db::OperationState>> {
+        self.content.get_pending_operations().await
+    }
+
+    /// Latency statistics per operation for this session
+    pub fn get_performance_metrics(&self) -> Vec<metrics::OperationMetrics> {
+        metrics::snapshot()
+    }
+
+    /// Daily latency rollups, newest first (written only with telemetry enabled)
+    pub async fn get_metrics_history(&self) -> CodexResult<Vec<metrics::DailyMetrics>> {
+        metrics::load_daily_rollups(&self.db).await
+    }
+
+    /// Run the self-test: storage, search, embeddings, generation and update server
+    pub async fn run_diagnostics(&self) -> diagnostics::DiagnosticsReport {
+        diagnostics::run_diagnostics(&self.db, &self.ai, &self.update).await
+    }
+
+    /// Start the local HTTP API on the configured port, or return the running one
+    #[cfg(feature = "api-server")]
+    pub async fn start_api_server(&self) -> CodexResult<api_server::ApiServerInfo> {
+        let mut server = self.api_server.lock().await;
+        if let Some(ref running) = *server {
+            return Ok(running.info().clone());
+        }
+
+        let port = self.config.read().await.app.api_server.port;
+        let started = api_server::ApiServer::start(
+            Arc::clone(&self.db),
+            Arc::clone(&self.ai),
+            Arc::clone(&self.content),
+            port,
+        )
+        .await?;
+        let info = started.info().clone();
+        *server = Some(started);
+        Ok(info)
+    }
+
+    /// Stop the local HTTP API; returns whether it was running
+    #[cfg(feature = "api-server")]
+    pub async fn stop_api_server(&self) -> bool {
+        match self.api_server.lock().await.take() {
+            Some(server) => {
+                server.stop().await;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Address and token of the local HTTP API, when it is running
+    #[cfg(feature = "api-server")]
+    pub async fn api_server_info(&self) -> Option<api_server::ApiServerInfo> {
+        self.api_server.lock().await.as_ref().map(|server| server.info().clone())
+    }
+
+    /// MCP server exposing the vault's search, documents and RAG as tools
+    #[cfg(feature = "mcp")]
+    pub fn mcp_server(&self) -> mcp::McpServer {
+        mcp::McpServer::new(Arc::clone(&self.ai), Arc::clone(&self.content))
+    }
+
     /// Perform a health check on all components
+    ///
+    /// In safe mode AI and the update server are not probed and do not count
+    /// towards `overall`.
     pub async fn health_check(&self) -> Result<HealthStatus> {
         let db_health = self.db.health_check().await?;
-        let ai_health = self.ai.health_check().await?;
         let content_health = self.content.health_check().await?;
-        let update_health = self.update.health_check().await?;
+        let (ai_health, update_health) = if self.safe_mode {
+            (false, false)
+        } else {
+            (self.ai.health_check().await?, self.update.health_check().await?)
+        };
+
+        // An incomplete index degrades search but does not make the core unhealthy
+        let mut warnings: Vec<String> = match self.content.get_index_health().await {
+            Ok(index) => index.warning().into_iter().collect(),
+            Err(e) => vec![format!("Could not check the search index: {}", e)],
+        };
+
+        let low_disk_mb = self.config.read().await.app.low_disk_warning_mb;
+        if let Some(available) = update::disk::available_space(self.db.path()) {
+            if available < low_disk_mb * 1024 * 1024 {
+                warnings.push(format!(
+                    "Low disk space: {} MB free on the vault volume",
+                    available / (1024 * 1024)
+                ));
+            }
+        }
+
+        if self.safe_mode {
+            warnings.insert(0, SAFE_MODE_WARNING.to_string());
+        }
 
         Ok(HealthStatus {
             database: db_health,
             ai: ai_health,
             content: content_health,
             update: update_health,
-            overall: db_health && ai_health && content_health && update_health,
+            overall: db_health && content_health && (self.safe_mode || (ai_health && update_health)),
+            ocr_available: self.content.ocr_available(),
+            transcription_available: self.content.transcription_available(),
+            warnings,
+            safe_mode: self.safe_mode,
         })
     }
 }
@@ -159,20 +498,28 @@ pub struct HealthStatus {
     pub content: bool,
     pub update: bool,
     pub overall: bool,
+    /// Whether image imports with OCR are supported by this build
+    pub ocr_available: bool,
+    /// Whether audio imports with Whisper transcription are supported by this build
+    pub transcription_available: bool,
+    /// Problems that degrade functionality without failing a component
+    #[serde(default)]
+    pub warnings: Vec<String>,
+    /// Started in safe mode, with AI and background jobs disabled
+    #[serde(default)]
+    pub safe_mode: bool,
 }
 
+/// Health warning while the core runs in safe mode
+pub const SAFE_MODE_WARNING: &str =
+    "Safe mode: AI, background jobs and update checks are disabled until the next normal launch";
+
 /// Initialize tracing/logging for the library
+///
+/// Logs to stdout and the in-memory log buffer; use [`logging::init`] to
+/// also write a rotating log file.
 pub fn init_tracing() -> Result<()> {
-    use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
-
-    tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "codex_core=info".into()),
-        )
-        .with(tracing_subscriber::fmt::layer())
-        .init();
-
+    logging::init(&config::LoggingConfig::default(), &logging::default_filter("info"))?;
     Ok(())
 }
 
@@ -0,0 +1,355 @@
+//! Logging and diagnostics
+//!
+//! [`init`] installs a subscriber that writes events to stdout, optionally to
+//! a rotating file (see [`LoggingConfig`]), and to an in-memory [`LogBuffer`]
+//! holding the last [`LOG_BUFFER_CAPACITY`] entries for the in-app
+//! diagnostics panel. Instrumented spans are recorded when they close, with
+//! their run time in a `duration_ms` field. The filter can be changed at
+//! runtime with [`set_log_level`].
+
+use std::collections::{BTreeMap, VecDeque};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Subscriber};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+use crate::{CodexError, CodexResult};
+use crate::config::{LogRotation, LoggingConfig};
+
+/// Entries kept in the in-memory log buffer
+pub const LOG_BUFFER_CAPACITY: usize = 2000;
+/// Prefix of rotated log file names
+const LOG_FILE_PREFIX: &str = "codex";
+
+/// A recorded log event or closed span
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LogEntry {
+    pub timestamp: String,
+    /// Level name, e.g. "INFO"
+    pub level: String,
+    /// Module the event came from
+    pub target: String,
+    pub message: String,
+    /// Innermost span the event happened in
+    pub span: Option<String>,
+    /// Structured fields, including `duration_ms` for closed spans
+    pub fields: BTreeMap<String, String>,
+}
+
+/// Ring buffer of the most recent log entries
+#[derive(Debug, Clone)]
+pub struct LogBuffer {
+    entries: Arc<Mutex<VecDeque<(Level, LogEntry)>>>,
+    capacity: usize,
+}
+
+impl LogBuffer {
+    /// Create a buffer keeping at most `capacity` entries
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Most recent entries at `level` or more severe, newest first
+    pub fn recent(&self, level: Option<Level>, limit: usize) -> Vec<LogEntry> {
+        self.entries
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .filter(|(entry_level, _)| level.is_none_or(|level| *entry_level <= level))
+            .take(limit)
+            .map(|(_, entry)| entry.clone())
+            .collect()
+    }
+
+    /// Number of buffered entries
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    /// Whether no entries have been recorded
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Layer recording events and closed spans into this buffer
+    pub fn layer(&self) -> LogBufferLayer {
+        LogBufferLayer { buffer: self.clone() }
+    }
+
+    fn push(&self, level: Level, entry: LogEntry) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() == self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back((level, entry));
+    }
+}
+
+/// Tracing layer feeding a [`LogBuffer`]
+#[derive(Debug, Clone)]
+pub struct LogBufferLayer {
+    buffer: LogBuffer,
+}
+
+/// Start time and fields of an open span
+struct SpanTiming {
+    started: Instant,
+    fields: BTreeMap<String, String>,
+}
+
+impl<S> Layer<S> for LogBufferLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else { return };
+        let mut visitor = FieldVisitor::default();
+        attrs.record(&mut visitor);
+        span.extensions_mut().insert(SpanTiming {
+            started: Instant::now(),
+            fields: visitor.fields,
+        });
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else { return };
+        let mut extensions = span.extensions_mut();
+        if let Some(timing) = extensions.get_mut::<SpanTiming>() {
+            let mut visitor = FieldVisitor::default();
+            values.record(&mut visitor);
+            timing.fields.extend(visitor.fields);
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+        let metadata = event.metadata();
+
+        self.buffer.push(
+            *metadata.level(),
+            LogEntry {
+                timestamp: chrono::Utc::now().to_rfc3339(),
+                level: metadata.level().to_string(),
+                target: metadata.target().to_string(),
+                message: visitor.message.unwrap_or_default(),
+                span: ctx.event_span(event).map(|span| span.name().to_string()),
+                fields: visitor.fields,
+            },
+        );
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else { return };
+        let Some(timing) = span.extensions_mut().remove::<SpanTiming>() else { return };
+        let metadata = span.metadata();
+
+        let mut fields = timing.fields;
+        fields.insert(
+            "duration_ms".to_string(),
+            format!("{:.3}", timing.started.elapsed().as_secs_f64() * 1000.0),
+        );
+
+        self.buffer.push(
+            *metadata.level(),
+            LogEntry {
+                timestamp: chrono::Utc::now().to_rfc3339(),
+                level: metadata.level().to_string(),
+                target: metadata.target().to_string(),
+                message: format!("{} finished", span.name()),
+                span: span.parent().map(|parent| parent.name().to_string()),
+                fields,
+            },
+        );
+    }
+}
+
+/// Collects the message and other fields of an event or span
+#[derive(Default)]
+struct FieldVisitor {
+    message: Option<String>,
+    fields: BTreeMap<String, String>,
+}
+
+impl Visit for FieldVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = Some(value.to_string());
+        } else {
+            self.fields.insert(field.name().to_string(), value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.message = Some(format!("{:?}", value));
+        } else {
+            self.fields.insert(field.name().to_string(), format!("{:?}", value));
+        }
+    }
+}
+
+/// Installed logging state
+struct Logging {
+    buffer: LogBuffer,
+    filter: reload::Handle<EnvFilter, Registry>,
+    /// Flushes the log file when dropped; kept for the life of the process
+    _file_guard: Option<WorkerGuard>,
+}
+
+static LOGGING: OnceCell<Logging> = OnceCell::new();
+
+/// Filter logging codex-core at `level` and other crates at warnings only
+pub fn default_filter(level: &str) -> String {
+    format!("warn,codex_core={}", level)
+}
+
+/// Install the global subscriber
+///
+/// `RUST_LOG` takes precedence over `filter`. Calling this again after a
+/// successful initialization does nothing.
+pub fn init(config: &LoggingConfig, filter: &str) -> CodexResult<()> {
+    if LOGGING.get().is_some() {
+        return Ok(());
+    }
+
+    let env_filter = match EnvFilter::try_from_default_env() {
+        Ok(env_filter) => env_filter,
+        Err(_) => parse_filter(filter)?,
+    };
+    let (filter_layer, filter_handle) = reload::Layer::new(env_filter);
+    let buffer = LogBuffer::new(LOG_BUFFER_CAPACITY);
+
+    let (file_layer, file_guard) = if config.file_enabled {
+        let directory = config.log_dir().map_err(|e| CodexError::internal(e.to_string()))?;
+        std::fs::create_dir_all(&directory)?;
+        let appender = RollingFileAppender::builder()
+            .rotation(match config.rotation {
+                LogRotation::Hourly => Rotation::HOURLY,
+                LogRotation::Daily => Rotation::DAILY,
+                LogRotation::Never => Rotation::NEVER,
+            })
+            .filename_prefix(LOG_FILE_PREFIX)
+            .filename_suffix("log")
+            .max_log_files(config.max_files)
+            .build(&directory)
+            .map_err(|e| CodexError::internal(format!("Failed to open log file in {:?}: {}", directory, e)))?;
+        let (writer, guard) = tracing_appender::non_blocking(appender);
+        let layer = tracing_subscriber::fmt::layer()
+            .with_writer(writer)
+            .with_ansi(false)
+            .with_span_events(FmtSpan::CLOSE);
+        (Some(layer), Some(guard))
+    } else {
+        (None, None)
+    };
+
+    tracing_subscriber::registry()
+        .with(filter_layer)
+        .with(tracing_subscriber::fmt::layer().with_span_events(FmtSpan::CLOSE))
+        .with(file_layer)
+        .with(buffer.layer())
+        .try_init()
+        .map_err(|e| CodexError::internal(format!("Failed to install logging: {}", e)))?;
+
+    let _ = LOGGING.set(Logging {
+        buffer,
+        filter: filter_handle,
+        _file_guard: file_guard,
+    });
+    Ok(())
+}
+
+/// Most recent log entries at `level` or more severe, newest first
+///
+/// Returns nothing when logging was not installed with [`init`].
+pub fn recent_logs(level: Option<&str>, limit: usize) -> CodexResult<Vec<LogEntry>> {
+    let level = level
+        .map(|level| Level::from_str(level).map_err(|_| CodexError::validation(format!("Unknown log level: {}", level))))
+        .transpose()?;
+
+    Ok(LOGGING
+        .get()
+        .map(|logging| logging.buffer.recent(level, limit))
+        .unwrap_or_default())
+}
+
+/// Replace the active filter, e.g. `debug` or `warn,codex_core::ai=trace`
+pub fn set_log_level(filter: &str) -> CodexResult<()> {
+    let logging = LOGGING
+        .get()
+        .ok_or_else(|| CodexError::internal("Logging is not initialized"))?;
+    logging
+        .filter
+        .reload(parse_filter(filter)?)
+        .map_err(|e| CodexError::internal(format!("Failed to change log level: {}", e)))
+}
+
+fn parse_filter(filter: &str) -> CodexResult<EnvFilter> {
+    EnvFilter::try_new(filter).map_err(|e| CodexError::validation(format!("Invalid log filter '{}': {}", filter, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buffer_keeps_latest_entries_by_level() {
+        let buffer = LogBuffer::new(3);
+        let subscriber = tracing_subscriber::registry().with(buffer.layer());
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug!("first");
+            tracing::warn!(attempt = 2, "second");
+            tracing::info!("third");
+            tracing::error!("fourth");
+        });
+
+        assert_eq!(buffer.len(), 3);
+        let messages: Vec<_> = buffer.recent(None, 10).into_iter().map(|e| e.message).collect();
+        assert_eq!(messages, vec!["fourth", "third", "second"]);
+
+        let warnings = buffer.recent(Some(Level::WARN), 10);
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[1].fields.get("attempt").map(String::as_str), Some("2"));
+        assert_eq!(buffer.recent(None, 1)[0].level, "ERROR");
+    }
+
+    #[test]
+    fn test_closed_span_records_duration() {
+        let buffer = LogBuffer::new(LOG_BUFFER_CAPACITY);
+        let subscriber = tracing_subscriber::registry().with(buffer.layer());
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("search", query = "rust", results = tracing::field::Empty);
+            let _entered = span.enter();
+            tracing::info!("searching");
+            span.record("results", 3);
+        });
+
+        let entries = buffer.recent(None, 10);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].span.as_deref(), Some("search"));
+
+        let closed = &entries[0];
+        assert_eq!(closed.message, "search finished");
+        assert_eq!(closed.fields.get("query").map(String::as_str), Some("rust"));
+        assert_eq!(closed.fields.get("results").map(String::as_str), Some("3"));
+        assert!(closed.fields["duration_ms"].parse::<f64>().unwrap() >= 0.0);
+    }
+}
@@ -0,0 +1,425 @@
+//! Model Context Protocol server
+//!
+//! Exposes the vault to external assistants as MCP tools over stdio:
+//! newline-delimited JSON-RPC 2.0 messages on stdin, responses on stdout.
+//! Logs must therefore go to stderr while the server runs.
+//!
+//! Tools:
+//! - `search_documents(query, limit)`: hybrid search
+//! - `get_document(id)`: one document with its content
+//! - `rag_answer(question)`: an answer grounded in the vault, with sources
+//!
+//! Archived and deleted documents are never returned, and every tool result
+//! is cut to at most `max_payload_bytes`.
+
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tracing::{debug, info, warn};
+
+use crate::ai::AiEngine;
+use crate::content::{ContentManager, SearchOptions};
+use crate::{CodexError, CodexResult};
+
+/// MCP protocol revision implemented
+pub const PROTOCOL_VERSION: &str = "2024-11-05";
+/// Default cap on the size of one tool result
+pub const DEFAULT_MAX_PAYLOAD_BYTES: usize = 64 * 1024;
+
+/// Results returned by `search_documents` when no limit is given
+const DEFAULT_SEARCH_LIMIT: usize = 10;
+/// Most results `search_documents` returns
+const MAX_SEARCH_LIMIT: usize = 50;
+/// Documents used as context by `rag_answer`
+const RAG_CONTEXT_LIMIT: usize = 5;
+/// Appended to text cut to fit the payload cap
+const TRUNCATION_MARKER: &str = "\n[truncated]";
+
+// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// MCP server over the content and AI engines
+pub struct McpServer {
+    ai: Arc<AiEngine>,
+    content: Arc<ContentManager>,
+    max_payload_bytes: usize,
+}
+
+impl McpServer {
+    pub fn new(ai: Arc<AiEngine>, content: Arc<ContentManager>) -> Self {
+        Self {
+            ai,
+            content,
+            max_payload_bytes: DEFAULT_MAX_PAYLOAD_BYTES,
+        }
+    }
+
+    /// Cap the size of each tool result
+    pub fn with_max_payload(mut self, max_payload_bytes: usize) -> Self {
+        self.max_payload_bytes = max_payload_bytes;
+        self
+    }
+
+    /// Serve on stdin/stdout until stdin is closed
+    pub async fn serve_stdio(&self) -> CodexResult<()> {
+        self.serve(tokio::io::stdin(), tokio::io::stdout()).await
+    }
+
+    /// Serve newline-delimited JSON-RPC from `reader` until it is closed
+    pub async fn serve<R, W>(&self, reader: R, mut writer: W) -> CodexResult<()>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        info!("MCP server started");
+        let mut lines = BufReader::new(reader).lines();
+
+        while let Some(line) = lines.next_line().await? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(response) = self.handle_message(&line).await {
+                let mut encoded = serde_json::to_vec(&response)?;
+                encoded.push(b'\n');
+                writer.write_all(&encoded).await?;
+                writer.flush().await?;
+            }
+        }
+
+        info!("MCP client disconnected");
+        Ok(())
+    }
+
+    /// Handle one message; notifications get no response
+    pub async fn handle_message(&self, line: &str) -> Option<Value> {
+        let request = match parse_message(line) {
+            Ok(request) => request,
+            Err(error) => return Some(error_response(Value::Null, error)),
+        };
+        debug!("MCP request: {}", request.method);
+
+        let result = self.dispatch(&request.method, request.params).await;
+        let id = request.id?;
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(error) => error_response(id, error),
+        })
+    }
+
+    async fn dispatch(&self, method: &str, params: Value) -> Result<Value, RpcError> {
+        match method {
+            "initialize" => Ok(json!({
+                "protocolVersion": PROTOCOL_VERSION,
+                "capabilities": { "tools": {} },
+                "serverInfo": { "name": "codex-vault", "version": env!("CARGO_PKG_VERSION") },
+            })),
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(json!({ "tools": tool_definitions() })),
+            "tools/call" => {
+                let call: ToolCall = serde_json::from_value(params)
+                    .map_err(|e| RpcError::new(INVALID_PARAMS, format!("Invalid tool call: {}", e)))?;
+                // Tool failures are reported to the model, not as protocol errors
+                Ok(match self.call_tool(&call.name, call.arguments).await {
+                    Ok(text) => json!({ "content": [{ "type": "text", "text": text }], "isError": false }),
+                    Err(e) => json!({ "content": [{ "type": "text", "text": e.to_string() }], "isError": true }),
+                })
+            }
+            method if method.starts_with("notifications/") => Ok(Value::Null),
+            method => Err(RpcError::new(METHOD_NOT_FOUND, format!("Method not found: {}", method))),
+        }
+    }
+
+    async fn call_tool(&self, name: &str, arguments: Value) -> CodexResult<String> {
+        match name {
+            "search_documents" => {
+                let args: SearchArgs = parse_arguments(arguments)?;
+                self.search_documents(args).await
+            }
+            "get_document" => {
+                let args: GetDocumentArgs = parse_arguments(arguments)?;
+                self.get_document(&args.id).await
+            }
+            "rag_answer" => {
+                let args: RagArgs = parse_arguments(arguments)?;
+                self.rag_answer(&args.question).await
+            }
+            name => Err(CodexError::not_found(format!("Unknown tool: {}", name))),
+        }
+    }
+
+    async fn search_documents(&self, args: SearchArgs) -> CodexResult<String> {
+        let options = SearchOptions {
+            limit: args.limit.unwrap_or(DEFAULT_SEARCH_LIMIT).clamp(1, MAX_SEARCH_LIMIT),
+            include_archived: false,
+            ..SearchOptions::default()
+        };
+        let results = self.content.search_documents(&args.query, options).await?;
+
+        let hits: Vec<Value> = results
+            .documents
+            .iter()
+            .filter(|result| !result.document.is_deleted && !result.document.is_archived)
+            .map(|result| {
+                json!({
+                    "id": result.document.id,
+                    "title": result.document.title,
+                    "score": result.score,
+                    "category": result.document.category,
+                    "tags": result.document.get_tags(),
+                    "snippet": result.snippet,
+                })
+            })
+            .collect();
+
+        fit_results(hits, self.max_payload_bytes)
+    }
+
+    async fn get_document(&self, id: &str) -> CodexResult<String> {
+        let id = uuid::Uuid::parse_str(id).map_err(|_| CodexError::validation("Invalid document ID"))?;
+        let document = self
+            .content
+            .get_document(id)
+            .await?
+            .filter(|document| !document.is_deleted && !document.is_archived)
+            .ok_or_else(|| CodexError::not_found(format!("Document not found: {}", id)))?;
+
+        let value = json!({
+            "id": document.id,
+            "title": document.title,
+            "author": document.author,
+            "category": document.category,
+            "tags": document.get_tags(),
+            "summary": document.summary,
+            "created_at": document.created_at,
+            "content": "",
+        });
+        // The content gets whatever room the metadata leaves
+        fit_text_field(value, "content", &document.content, self.max_payload_bytes)
+    }
+
+    async fn rag_answer(&self, question: &str) -> CodexResult<String> {
+        let response = self.ai.rag_query(question, RAG_CONTEXT_LIMIT).await?;
+        let sources: Vec<Value> = response
+            .sources
+            .iter()
+            .map(|source| json!({ "document_id": source.document_id, "title": source.title }))
+            .collect();
+
+        let value = json!({
+            "answer": "",
+            "confidence": response.confidence,
+            "low_confidence": response.low_confidence,
+            "sources": sources,
+        });
+        fit_text_field(value, "answer", &response.answer, self.max_payload_bytes)
+    }
+}
+
+/// Tool definitions with JSON schemas for their inputs, as sent by `tools/list`
+pub fn tool_definitions() -> Value {
+    json!([
+        {
+            "name": "search_documents",
+            "description": "Search the user's local knowledge vault. Returns matching documents with ids, titles and snippets.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string", "description": "Search query" },
+                    "limit": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "maximum": MAX_SEARCH_LIMIT,
+                        "default": DEFAULT_SEARCH_LIMIT,
+                        "description": "Maximum number of results",
+                    },
+                },
+                "required": ["query"],
+            },
+        },
+        {
+            "name": "get_document",
+            "description": "Fetch one document from the vault, including its content, by the id returned from search_documents.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "id": { "type": "string", "format": "uuid", "description": "Document id" },
+                },
+                "required": ["id"],
+            },
+        },
+        {
+            "name": "rag_answer",
+            "description": "Answer a question from the vault's documents, citing the documents used.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "question": { "type": "string", "description": "Question to answer" },
+                },
+                "required": ["question"],
+            },
+        },
+    ])
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcRequest {
+    #[serde(default)]
+    jsonrpc: String,
+    /// Absent for notifications
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Debug, Serialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self { code, message: message.into() }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ToolCall {
+    name: String,
+    #[serde(default)]
+    arguments: Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchArgs {
+    query: String,
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GetDocumentArgs {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RagArgs {
+    question: String,
+}
+
+fn parse_message(line: &str) -> Result<RpcRequest, RpcError> {
+    let value: Value = serde_json::from_str(line)
+        .map_err(|e| RpcError::new(PARSE_ERROR, format!("Parse error: {}", e)))?;
+    let request: RpcRequest = serde_json::from_value(value)
+        .map_err(|e| RpcError::new(INVALID_REQUEST, format!("Invalid request: {}", e)))?;
+    if request.jsonrpc != "2.0" {
+        warn!("MCP request without jsonrpc 2.0 marker: {}", request.method);
+        return Err(RpcError::new(INVALID_REQUEST, "Expected jsonrpc \"2.0\""));
+    }
+    Ok(request)
+}
+
+fn parse_arguments<T: serde::de::DeserializeOwned>(arguments: Value) -> CodexResult<T> {
+    serde_json::from_value(arguments).map_err(|e| CodexError::validation(format!("Invalid arguments: {}", e)))
+}
+
+fn error_response(id: Value, error: RpcError) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": error })
+}
+
+/// Size of a tool result as sent, encoded as a JSON string
+///
+/// Quotes, backslashes and control characters are escaped, so a result
+/// grows on the wire.
+fn encoded_len(text: &str) -> usize {
+    serde_json::to_string(text).map_or(usize::MAX, |encoded| encoded.len())
+}
+
+/// Serialize results, dropping the lowest-ranked ones until they fit
+fn fit_results(mut results: Vec<Value>, max_bytes: usize) -> CodexResult<String> {
+    loop {
+        let encoded = serde_json::to_string(&results)?;
+        if encoded_len(&encoded) <= max_bytes || results.is_empty() {
+            return Ok(encoded);
+        }
+        results.pop();
+    }
+}
+
+/// Serialize `value` with `text` in `field`, cut until the result fits
+///
+/// Every byte cut shrinks the encoded result by at least a byte, so the cut
+/// grows by the overflow until nothing overflows.
+fn fit_text_field(mut value: Value, field: &str, text: &str, max_bytes: usize) -> CodexResult<String> {
+    let mut keep = text.len();
+    loop {
+        value[field] = Value::String(truncate_text(text, keep));
+        let encoded = serde_json::to_string(&value)?;
+        let overflow = encoded_len(&encoded).saturating_sub(max_bytes);
+        if overflow == 0 || keep == 0 {
+            return Ok(encoded);
+        }
+        keep = keep.saturating_sub(overflow);
+    }
+}
+
+/// Cut text to at most `max_bytes` (on a char boundary), marking the cut
+fn truncate_text(text: &str, max_bytes: usize) -> String {
+    if text.len() <= max_bytes {
+        return text.to_string();
+    }
+    let mut end = max_bytes.saturating_sub(TRUNCATION_MARKER.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}{}", &text[..end], TRUNCATION_MARKER)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_message_rejects_invalid_requests() {
+        assert_eq!(parse_message("{not json").unwrap_err().code, PARSE_ERROR);
+        assert_eq!(parse_message(r#"{"jsonrpc":"2.0","id":1}"#).unwrap_err().code, INVALID_REQUEST);
+        assert_eq!(parse_message(r#"{"id":1,"method":"ping"}"#).unwrap_err().code, INVALID_REQUEST);
+
+        let notification = parse_message(r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#).unwrap();
+        assert!(notification.id.is_none());
+
+        let tools = tool_definitions();
+        let names: Vec<&str> = tools.as_array().unwrap().iter().map(|t| t["name"].as_str().unwrap()).collect();
+        assert_eq!(names, ["search_documents", "get_document", "rag_answer"]);
+        assert!(tools.as_array().unwrap().iter().all(|t| t["inputSchema"]["type"] == "object"));
+    }
+
+    #[test]
+    fn test_payloads_are_cut_to_fit() {
+        let text = "é".repeat(100);
+        let cut = truncate_text(&text, 50);
+        assert!(cut.len() <= 50);
+        assert!(cut.ends_with(TRUNCATION_MARKER));
+        assert_eq!(truncate_text("short", 50), "short");
+
+        let results: Vec<Value> = (0..10).map(|i| json!({ "id": i, "snippet": "x".repeat(20) })).collect();
+        let encoded = fit_results(results, 120).unwrap();
+        assert!(encoded.len() <= 120);
+        let kept: Vec<Value> = serde_json::from_str(&encoded).unwrap();
+        assert!(!kept.is_empty() && kept.len() < 10);
+        assert_eq!(kept[0]["id"], 0);
+
+        // Quotes and newlines are escaped twice on the way out
+        let quoted = "\"quoted\"\n".repeat(100);
+        let encoded = fit_text_field(json!({ "id": 1, "content": "" }), "content", &quoted, 300).unwrap();
+        assert!(encoded_len(&encoded) <= 300, "{} bytes", encoded_len(&encoded));
+        let value: Value = serde_json::from_str(&encoded).unwrap();
+        assert!(value["content"].as_str().unwrap().ends_with(TRUNCATION_MARKER));
+        assert!(encoded_len(&fit_results(vec![json!({ "snippet": quoted })], 300).unwrap()) <= 300);
+    }
+}
@@ -0,0 +1,379 @@
+//! Per-operation latency metrics
+//!
+//! Latencies are recorded into fixed-bucket histograms whose buckets grow by
+//! 20% from 10 µs to about ten minutes, so percentiles are accurate to one
+//! bucket. [`snapshot`] reports count, p50/p95/p99 and max per operation
+//! since the process started. When telemetry is enabled, the histograms are
+//! also merged into a daily rollup in the settings table every
+//! [`ROLLUP_INTERVAL`].
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::CodexResult;
+use crate::db::{DatabaseManager, SettingQueries};
+use crate::db::models::Setting;
+
+/// Search requests, end to end
+pub const SEARCH: &str = "search";
+/// Search requests answered from the result cache
+pub const SEARCH_CACHE_HIT: &str = "search_cache_hit";
+/// Quick switcher title lookups
+pub const QUICK_FIND: &str = "quick_find";
+/// Document imports, from file or text
+pub const IMPORT: &str = "import";
+/// Uncached LLM generations
+pub const INFERENCE: &str = "inference";
+/// Time until an uncached LLM generation produced its first token
+pub const TIME_TO_FIRST_TOKEN: &str = "time_to_first_token";
+/// RAG queries, end to end
+pub const RAG_QUERY: &str = "rag_query";
+/// Single-text embeddings
+pub const EMBEDDING: &str = "embedding";
+/// Time spent waiting for a pooled database connection
+pub const DB_POOL_WAIT: &str = "db_pool_wait";
+/// Time generations waited in the inference queue for a slot
+pub const AI_QUEUE_WAIT: &str = "ai_queue_wait";
+
+/// How often daily rollups are written
+pub const ROLLUP_INTERVAL: Duration = Duration::from_secs(3600);
+/// Setting key prefix of daily rollups, followed by the date
+const DAILY_ROLLUP_PREFIX: &str = "metrics.daily.";
+/// Setting category of daily rollups
+const METRICS_CATEGORY: &str = "metrics";
+
+/// Upper bound of the first bucket, in microseconds
+const MIN_BUCKET_MICROS: f64 = 10.0;
+/// Ratio between consecutive bucket bounds
+const BUCKET_GROWTH: f64 = 1.2;
+/// Number of buckets; the last one also holds anything slower
+const BUCKET_COUNT: usize = 100;
+
+static REGISTRY: Lazy<MetricsRegistry> = Lazy::new(MetricsRegistry::default);
+
+/// Latency histogram with fixed, exponentially growing buckets
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Histogram {
+    /// Sample counts by bucket index; empty buckets are omitted
+    buckets: BTreeMap<usize, u64>,
+    count: u64,
+    sum_micros: u64,
+    max_micros: u64,
+}
+
+impl Histogram {
+    /// Record one sample
+    pub fn record(&mut self, duration: Duration) {
+        let micros = duration.as_micros().min(u64::MAX as u128) as u64;
+        *self.buckets.entry(bucket_index(micros)).or_default() += 1;
+        self.count += 1;
+        self.sum_micros = self.sum_micros.saturating_add(micros);
+        self.max_micros = self.max_micros.max(micros);
+    }
+
+    /// Add another histogram's samples to this one
+    pub fn merge(&mut self, other: &Histogram) {
+        for (index, count) in &other.buckets {
+            *self.buckets.entry(*index).or_default() += count;
+        }
+        self.count += other.count;
+        self.sum_micros = self.sum_micros.saturating_add(other.sum_micros);
+        self.max_micros = self.max_micros.max(other.max_micros);
+    }
+
+    /// Number of samples
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Latency below which `quantile` (0–1) of the samples fall
+    pub fn quantile(&self, quantile: f64) -> Duration {
+        if self.count == 0 {
+            return Duration::ZERO;
+        }
+
+        let rank = ((quantile.clamp(0.0, 1.0) * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (index, count) in &self.buckets {
+            seen += count;
+            if seen >= rank {
+                // The last bucket is unbounded, so only the maximum is known
+                let upper = if *index == BUCKET_COUNT - 1 {
+                    self.max_micros as f64
+                } else {
+                    bucket_upper_micros(*index).min(self.max_micros as f64)
+                };
+                return Duration::from_micros(upper as u64);
+            }
+        }
+        Duration::from_micros(self.max_micros)
+    }
+
+    /// Summary statistics for an operation
+    pub fn summary(&self, operation: &str) -> OperationMetrics {
+        let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        OperationMetrics {
+            operation: operation.to_string(),
+            count: self.count,
+            mean_ms: if self.count == 0 { 0.0 } else { self.sum_micros as f64 / self.count as f64 / 1000.0 },
+            p50_ms: ms(self.quantile(0.50)),
+            p95_ms: ms(self.quantile(0.95)),
+            p99_ms: ms(self.quantile(0.99)),
+            max_ms: self.max_micros as f64 / 1000.0,
+        }
+    }
+}
+
+fn bucket_index(micros: u64) -> usize {
+    if micros as f64 <= MIN_BUCKET_MICROS {
+        return 0;
+    }
+    let index = ((micros as f64 / MIN_BUCKET_MICROS).ln() / BUCKET_GROWTH.ln()).ceil() as usize;
+    index.min(BUCKET_COUNT - 1)
+}
+
+fn bucket_upper_micros(index: usize) -> f64 {
+    MIN_BUCKET_MICROS * BUCKET_GROWTH.powi(index as i32)
+}
+
+/// Latency statistics of one operation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OperationMetrics {
+    pub operation: String,
+    pub count: u64,
+    pub mean_ms: f64,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+/// Operation metrics rolled up for one day
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DailyMetrics {
+    /// Day in `YYYY-MM-DD` form (UTC)
+    pub date: String,
+    pub operations: Vec<OperationMetrics>,
+}
+
+/// Histograms by operation name
+#[derive(Debug, Default)]
+pub struct MetricsRegistry {
+    /// Everything recorded this session
+    session: Mutex<HashMap<String, Histogram>>,
+    /// Samples not yet written to a daily rollup
+    pending: Mutex<HashMap<String, Histogram>>,
+}
+
+impl MetricsRegistry {
+    /// Record one sample for `operation`
+    pub fn record(&self, operation: &str, duration: Duration) {
+        for histograms in [&self.session, &self.pending] {
+            histograms
+                .lock()
+                .unwrap()
+                .entry(operation.to_string())
+                .or_default()
+                .record(duration);
+        }
+    }
+
+    /// Statistics for every recorded operation this session, sorted by name
+    pub fn snapshot(&self) -> Vec<OperationMetrics> {
+        let session = self.session.lock().unwrap();
+        let mut metrics: Vec<OperationMetrics> = session
+            .iter()
+            .map(|(operation, histogram)| histogram.summary(operation))
+            .collect();
+        metrics.sort_by(|a, b| a.operation.cmp(&b.operation));
+        metrics
+    }
+
+    fn take_pending(&self) -> HashMap<String, Histogram> {
+        std::mem::take(&mut *self.pending.lock().unwrap())
+    }
+
+    fn restore_pending(&self, histograms: HashMap<String, Histogram>) {
+        let mut pending = self.pending.lock().unwrap();
+        for (operation, histogram) in histograms {
+            pending.entry(operation).or_default().merge(&histogram);
+        }
+    }
+}
+
+/// Records the time until it is dropped as one sample of an operation
+#[derive(Debug)]
+pub struct Timer {
+    operation: &'static str,
+    started: Instant,
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        record(self.operation, self.started.elapsed());
+    }
+}
+
+/// Start timing `operation`; the sample is recorded when the timer is dropped
+pub fn timer(operation: &'static str) -> Timer {
+    Timer {
+        operation,
+        started: Instant::now(),
+    }
+}
+
+/// Record one sample for `operation` in the global registry
+pub fn record(operation: &str, duration: Duration) {
+    REGISTRY.record(operation, duration);
+}
+
+/// Statistics for every operation recorded this session
+pub fn snapshot() -> Vec<OperationMetrics> {
+    REGISTRY.snapshot()
+}
+
+/// Merge samples recorded since the last rollup into today's rollup
+pub async fn persist_daily_rollup(db: &DatabaseManager) -> CodexResult<()> {
+    let pending = REGISTRY.take_pending();
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    let result = merge_into_rollup(db, &pending).await;
+    if result.is_err() {
+        REGISTRY.restore_pending(pending);
+    }
+    result
+}
+
+async fn merge_into_rollup(db: &DatabaseManager, pending: &HashMap<String, Histogram>) -> CodexResult<()> {
+    let key = format!("{}{}", DAILY_ROLLUP_PREFIX, Utc::now().format("%Y-%m-%d"));
+    let mut day: HashMap<String, Histogram> = SettingQueries::get(db.pool(), &key)
+        .await?
+        .and_then(|setting| setting.get_value())
+        .unwrap_or_default();
+
+    for (operation, histogram) in pending {
+        day.entry(operation.clone()).or_default().merge(histogram);
+    }
+
+    let mut setting = Setting::new(key, serde_json::to_string(&day)?, METRICS_CATEGORY.to_string());
+    setting.is_user_configurable = false;
+    SettingQueries::set(db.pool(), &setting).await
+}
+
+/// Stored daily rollups, newest first
+pub async fn load_daily_rollups(db: &DatabaseManager) -> CodexResult<Vec<DailyMetrics>> {
+    let mut days: Vec<DailyMetrics> = SettingQueries::get_by_category(db.pool(), METRICS_CATEGORY)
+        .await?
+        .into_iter()
+        .filter_map(|setting| {
+            let date = setting.key.strip_prefix(DAILY_ROLLUP_PREFIX)?.to_string();
+            let histograms: HashMap<String, Histogram> = setting.get_value()?;
+            let mut operations: Vec<OperationMetrics> = histograms
+                .iter()
+                .map(|(operation, histogram)| histogram.summary(operation))
+                .collect();
+            operations.sort_by(|a, b| a.operation.cmp(&b.operation));
+            Some(DailyMetrics { date, operations })
+        })
+        .collect();
+
+    days.sort_by(|a, b| b.date.cmp(&a.date));
+    Ok(days)
+}
+
+/// Write daily rollups every [`ROLLUP_INTERVAL`] in the background
+///
+/// The task stops when the database manager is dropped.
+pub fn start_daily_rollup(db: &Arc<DatabaseManager>) {
+    let db = Arc::downgrade(db);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(ROLLUP_INTERVAL);
+        // The first tick completes immediately, before anything is recorded
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            let Some(db) = db.upgrade() else {
+                break;
+            };
+            if let Err(e) = persist_daily_rollup(&db).await {
+                error!("Failed to persist metrics rollup: {}", e);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_database;
+
+    #[test]
+    fn test_histogram_percentiles() {
+        let mut histogram = Histogram::default();
+        for ms in 1..=100 {
+            histogram.record(Duration::from_millis(ms));
+        }
+
+        let summary = histogram.summary(SEARCH);
+        assert_eq!(summary.count, 100);
+        assert!((summary.mean_ms - 50.5).abs() < 0.01);
+        // Bucket upper bounds overestimate by at most one growth step
+        assert!(summary.p50_ms >= 50.0 && summary.p50_ms <= 50.0 * BUCKET_GROWTH, "p50 {}", summary.p50_ms);
+        assert!(summary.p95_ms >= 95.0 && summary.p95_ms <= 100.0, "p95 {}", summary.p95_ms);
+        assert!(summary.p99_ms >= 99.0 && summary.p99_ms <= 100.0, "p99 {}", summary.p99_ms);
+        assert_eq!(summary.max_ms, 100.0);
+        assert_eq!(Histogram::default().quantile(0.5), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_merge_and_serialization_round_trip() {
+        let mut a = Histogram::default();
+        a.record(Duration::from_micros(5));
+        a.record(Duration::from_secs(3600));
+        let mut b = Histogram::default();
+        b.record(Duration::from_millis(2));
+
+        a.merge(&b);
+        assert_eq!(a.count(), 3);
+        assert_eq!(a.quantile(1.0), Duration::from_secs(3600));
+
+        let json = serde_json::to_string(&a).unwrap();
+        assert_eq!(serde_json::from_str::<Histogram>(&json).unwrap(), a);
+    }
+
+    #[tokio::test]
+    async fn test_daily_rollup_accumulates() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = test_database(&dir).await;
+
+        record("test_rollup", Duration::from_millis(4));
+        persist_daily_rollup(&db).await.unwrap();
+        record("test_rollup", Duration::from_millis(8));
+        persist_daily_rollup(&db).await.unwrap();
+
+        let days = load_daily_rollups(&db).await.unwrap();
+        assert_eq!(days.len(), 1);
+        let rollup = days[0].operations.iter().find(|m| m.operation == "test_rollup").unwrap();
+        assert_eq!(rollup.count, 2);
+        assert_eq!(rollup.max_ms, 8.0);
+        assert!(snapshot().iter().any(|m| m.operation == "test_rollup"));
+    }
+
+    #[tokio::test]
+    async fn test_writes_record_pool_wait() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = test_database(&dir).await;
+        let waits = || snapshot().into_iter().find(|m| m.operation == DB_POOL_WAIT).map_or(0, |m| m.count);
+        let before = waits();
+
+        crate::db::queries::begin_write(db.pool()).await.unwrap().commit().await.unwrap();
+        assert!(waits() > before);
+    }
+}
@@ -0,0 +1,186 @@
+//! Platform-neutral file paths
+//!
+//! Database and model locations come from user configuration, so they may
+//! be relative, contain spaces or `#`, or on Windows use drive letters, UNC
+//! shares and `\\?\` verbatim prefixes. These helpers turn them into
+//! absolute paths and SQLite connection URLs that behave the same on
+//! Windows, macOS and Linux.
+
+use std::path::{Component, Path, PathBuf};
+
+use crate::CodexResult;
+
+/// Scheme prefix understood by sqlx for SQLite databases
+const SQLITE_SCHEME: &str = "sqlite://";
+
+/// Absolute, canonical form of a path that may not exist yet
+///
+/// `.` and `..` are folded first, then the longest existing ancestor is
+/// canonicalized (resolving symlinks and, on Windows, short names and case)
+/// and the missing remainder appended. Windows verbatim prefixes are removed so the
+/// result can be shown to users and passed to libraries that reject them.
+pub fn normalize_path(path: &Path) -> CodexResult<PathBuf> {
+    let absolute = if path.is_absolute() { path.to_path_buf() } else { std::env::current_dir()?.join(path) };
+    let absolute = lexical_normalize(&absolute);
+
+    let mut missing = Vec::new();
+    let mut existing = absolute.as_path();
+    let base = loop {
+        if let Ok(canonical) = existing.canonicalize() {
+            break canonical;
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                missing.push(name.to_os_string());
+                existing = parent;
+            }
+            // Nothing on the path exists (e.g. an unreachable share); keep it as given
+            _ => break absolute.clone(),
+        }
+    };
+
+    let mut normalized = strip_verbatim(&base);
+    for name in missing.into_iter().rev() {
+        normalized.push(name);
+    }
+    Ok(normalized)
+}
+
+/// Fold `.` and `..` components without touching the file system
+fn lexical_normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push(component);
+                }
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// Path without a Windows `\\?\` or `\\?\UNC\` verbatim prefix
+pub fn strip_verbatim(path: &Path) -> PathBuf {
+    let text = path.to_string_lossy();
+    if let Some(share) = text.strip_prefix(r"\\?\UNC\") {
+        PathBuf::from(format!(r"\\{}", share))
+    } else if let Some(local) = text.strip_prefix(r"\\?\").filter(|rest| rest.as_bytes().get(1) == Some(&b':')) {
+        PathBuf::from(local)
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// SQLite connection URL for an absolute database path
+///
+/// Separators become `/` (a UNC share keeps its leading `//`) and every
+/// byte other than unreserved characters, `/` and a drive letter's `:` is
+/// percent-encoded, so spaces, `?`, `#` and `%` in directory names survive
+/// the round trip through sqlx's URL parser.
+pub fn sqlite_url_for(path: &Path) -> String {
+    let text = strip_verbatim(path).to_string_lossy().into_owned();
+    let text = if cfg!(windows) { text.replace('\\', "/") } else { text };
+
+    let mut url = String::with_capacity(SQLITE_SCHEME.len() + text.len());
+    url.push_str(SQLITE_SCHEME);
+    for (i, byte) in text.bytes().enumerate() {
+        let is_drive_colon = byte == b':' && i == 1 && text.as_bytes()[0].is_ascii_alphabetic();
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~' | b'/') || is_drive_colon {
+            url.push(byte as char);
+        } else {
+            url.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    url
+}
+
+/// SQLite connection URL for a database path, normalizing it first
+pub fn sqlite_url(path: &Path) -> CodexResult<String> {
+    Ok(sqlite_url_for(&normalize_path(path)?))
+}
+
+/// Location of a model file or directory named in configuration
+///
+/// Absolute paths are used as given; relative ones, including bare file
+/// names like `llama-2-7b-chat.gguf`, are resolved against `models_dir`
+/// rather than the process working directory.
+pub fn resolve_model_path(models_dir: &Path, model: impl AsRef<Path>) -> PathBuf {
+    let model = model.as_ref();
+    let joined = if model.is_absolute() { model.to_path_buf() } else { models_dir.join(model) };
+    normalize_path(&joined).unwrap_or(joined)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_config;
+    use sqlx::sqlite::SqliteConnectOptions;
+    use std::str::FromStr;
+
+    fn parsed_filename(url: &str) -> PathBuf {
+        SqliteConnectOptions::from_str(url).unwrap().get_filename().into_owned()
+    }
+
+    #[test]
+    fn test_sqlite_url_round_trips_awkward_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let vault = dir.path().join("My Vault #1 (50% done)?").join("codex.db");
+
+        let url = sqlite_url(&vault).unwrap();
+        assert!(url.starts_with(SQLITE_SCHEME), "{}", url);
+        assert!(!url.contains(' ') && !url.contains('#') && !url[SQLITE_SCHEME.len()..].contains('?'), "{}", url);
+        assert_eq!(parsed_filename(&url), normalize_path(&vault).unwrap());
+
+        // Relative segments fold away, even below a directory that does not exist yet
+        let dotted = dir.path().join("missing").join("..").join("other").join(".").join("codex.db");
+        assert_eq!(normalize_path(&dotted).unwrap(), normalize_path(&dir.path().join("other/codex.db")).unwrap());
+    }
+
+    #[test]
+    fn test_model_paths_resolve_against_models_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let models = dir.path().join("models");
+        std::fs::create_dir_all(&models).unwrap();
+        std::fs::write(models.join("model.gguf"), b"GGUF").unwrap();
+
+        let resolved = resolve_model_path(&models, "model.gguf");
+        assert!(resolved.is_absolute());
+        assert!(resolved.exists());
+        assert_eq!(resolved, models.canonicalize().unwrap().join("model.gguf"));
+
+        let elsewhere = dir.path().join("elsewhere.gguf");
+        assert_eq!(resolve_model_path(&models, &elsewhere), normalize_path(&elsewhere).unwrap());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_windows_unc_and_verbatim_paths() {
+        let unc = Path::new(r"\\fileserver\Shared Docs\Codex Vault\codex.db");
+        assert_eq!(sqlite_url_for(unc), "sqlite:////fileserver/Shared%20Docs/Codex%20Vault/codex.db");
+        assert_eq!(parsed_filename(&sqlite_url_for(unc)), PathBuf::from("//fileserver/Shared Docs/Codex Vault/codex.db"));
+
+        let verbatim_unc = Path::new(r"\\?\UNC\fileserver\Shared Docs\codex.db");
+        assert_eq!(strip_verbatim(verbatim_unc), PathBuf::from(r"\\fileserver\Shared Docs\codex.db"));
+
+        let verbatim_drive = Path::new(r"\\?\C:\Users\Jo Smith\AppData\codex.db");
+        assert_eq!(strip_verbatim(verbatim_drive), PathBuf::from(r"C:\Users\Jo Smith\AppData\codex.db"));
+        assert_eq!(sqlite_url_for(verbatim_drive), "sqlite://C:/Users/Jo%20Smith/AppData/codex.db");
+
+        let models = Path::new(r"C:\Codex Vault\models");
+        assert_eq!(resolve_model_path(models, r"D:\Models\llama.gguf"), PathBuf::from(r"D:\Models\llama.gguf"));
+    }
+
+    #[tokio::test]
+    async fn test_database_in_directory_with_spaces() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Program Data").join("Codex Vault #2").join("codex.db");
+        let db = crate::db::DatabaseManager::new(&test_config(path.clone())).await.unwrap();
+        sqlx::query("SELECT 1").execute(db.pool()).await.unwrap();
+        assert!(path.exists());
+        assert!(!normalize_path(&path).unwrap().to_string_lossy().starts_with(r"\\?\"));
+    }
+}
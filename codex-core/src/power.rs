@@ -0,0 +1,519 @@
+//! Power awareness on laptops
+//!
+//! A [`PowerMonitor`] reads the battery and the temperature sensors every
+//! [`PowerPolicy::poll_interval_secs`] and decides from the policy whether
+//! to throttle. While throttled the core lets fewer generations run at once
+//! through a [`GenerationThrottle`], optionally caps their length, and holds
+//! queued background jobs. Batteries are read from `/sys/class/power_supply`
+//! on Linux and `pmset` on macOS; on other platforms, and on machines
+//! without a battery, nothing is throttled.
+
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::{watch, Notify};
+use tokio_util::sync::CancellationToken;
+
+use crate::config::PowerPolicy;
+
+/// One reading of the battery and temperature sensors
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PowerReading {
+    pub has_battery: bool,
+    /// Running on battery rather than mains power
+    pub on_battery: bool,
+    pub battery_percent: Option<f32>,
+    /// Hottest sensor, in degrees Celsius
+    pub temperature_celsius: Option<f32>,
+    /// Whether a sensor reached the critical temperature it reports
+    pub critical_temperature: bool,
+}
+
+/// Source of battery and temperature readings
+pub trait PowerSource: Send + Sync + Debug {
+    fn read(&self) -> PowerReading;
+}
+
+/// The platform's battery and sysinfo's temperature sensors
+#[derive(Default)]
+pub struct SystemPowerSource {
+    /// Sensors, listed on the first reading
+    components: Mutex<Option<sysinfo::Components>>,
+}
+
+impl Debug for SystemPowerSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SystemPowerSource").finish_non_exhaustive()
+    }
+}
+
+impl PowerSource for SystemPowerSource {
+    fn read(&self) -> PowerReading {
+        let Some((on_battery, battery_percent)) = read_battery() else {
+            return PowerReading::default();
+        };
+
+        let mut components = self.components.lock().unwrap_or_else(|e| e.into_inner());
+        let components = components.get_or_insert_with(sysinfo::Components::new_with_refreshed_list);
+        components.refresh();
+        let mut temperature_celsius: Option<f32> = None;
+        let mut critical_temperature = false;
+        for component in components.list() {
+            let temperature = component.temperature();
+            // Sensors without a reading report NaN or 0
+            if !temperature.is_finite() || temperature <= 0.0 {
+                continue;
+            }
+            temperature_celsius = Some(temperature_celsius.map_or(temperature, |hottest| hottest.max(temperature)));
+            critical_temperature |= component.critical().is_some_and(|critical| temperature >= critical);
+        }
+
+        PowerReading { has_battery: true, on_battery, battery_percent, temperature_celsius, critical_temperature }
+    }
+}
+
+/// Whether the machine runs on battery and the charge, `None` without a battery
+#[cfg(target_os = "linux")]
+fn read_battery() -> Option<(bool, Option<f32>)> {
+    read_power_supply(std::path::Path::new("/sys/class/power_supply"))
+}
+
+#[cfg(target_os = "macos")]
+fn read_battery() -> Option<(bool, Option<f32>)> {
+    let output = std::process::Command::new("pmset").args(["-g", "batt"]).output().ok()?;
+    parse_pmset(&String::from_utf8_lossy(&output.stdout))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn read_battery() -> Option<(bool, Option<f32>)> {
+    None
+}
+
+/// First system battery under a `power_supply` class directory
+#[cfg(any(target_os = "linux", test))]
+fn read_power_supply(dir: &std::path::Path) -> Option<(bool, Option<f32>)> {
+    std::fs::read_dir(dir).ok()?.flatten().find_map(|entry| {
+        let path = entry.path();
+        let read = |name: &str| std::fs::read_to_string(path.join(name)).ok().map(|value| value.trim().to_string());
+        // Mice and headsets report their batteries with the "Device" scope
+        if read("type").as_deref() != Some("Battery") || read("scope").as_deref() == Some("Device") {
+            return None;
+        }
+        let on_battery = read("status").as_deref() == Some("Discharging");
+        Some((on_battery, read("capacity").and_then(|capacity| capacity.parse().ok())))
+    })
+}
+
+/// Battery state from the output of `pmset -g batt`
+#[cfg(any(target_os = "macos", test))]
+fn parse_pmset(output: &str) -> Option<(bool, Option<f32>)> {
+    let battery = output.lines().find(|line| line.contains("InternalBattery"))?;
+    let on_battery = output.contains("'Battery Power'");
+    let percent = battery
+        .split_whitespace()
+        .find_map(|word| word.strip_suffix("%;"))
+        .and_then(|percent| percent.parse().ok());
+    Some((on_battery, percent))
+}
+
+/// Why performance is reduced
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThrottleReason {
+    Battery,
+    Thermal,
+}
+
+/// Power state and whether the policy reduces performance, also the `power-state-changed` payload
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PowerState {
+    pub has_battery: bool,
+    pub on_battery: bool,
+    pub battery_percent: Option<f32>,
+    pub temperature_celsius: Option<f32>,
+    /// Whether a sensor is at the policy's thermal limit or its own critical temperature
+    pub thermal_pressure: bool,
+    /// Why performance is reduced, `None` at full performance
+    pub throttle: Option<ThrottleReason>,
+}
+
+impl PowerState {
+    /// State of `reading` under `policy`; machines without a battery are never throttled
+    pub fn evaluate(reading: &PowerReading, policy: &PowerPolicy) -> Self {
+        let thermal_pressure = reading.has_battery
+            && (reading.critical_temperature
+                || reading.temperature_celsius.is_some_and(|temperature| temperature >= policy.thermal_limit_celsius));
+        let throttle = if !policy.enabled || !reading.has_battery {
+            None
+        } else if reading.on_battery && policy.throttle_on_battery {
+            Some(ThrottleReason::Battery)
+        } else if thermal_pressure {
+            Some(ThrottleReason::Thermal)
+        } else {
+            None
+        };
+
+        Self {
+            has_battery: reading.has_battery,
+            on_battery: reading.on_battery,
+            battery_percent: reading.battery_percent,
+            temperature_celsius: reading.temperature_celsius,
+            thermal_pressure,
+            throttle,
+        }
+    }
+
+    pub fn throttled(&self) -> bool {
+        self.throttle.is_some()
+    }
+
+    /// Whether `other` matches in everything but the battery charge and temperature
+    fn same_mode(&self, other: &Self) -> bool {
+        (self.has_battery, self.on_battery, self.thermal_pressure, self.throttle)
+            == (other.has_battery, other.on_battery, other.thermal_pressure, other.throttle)
+    }
+}
+
+/// Limits the core applies in a power state
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ThrottleLimits {
+    /// Generations at a time, `None` for no limit
+    pub concurrency: Option<usize>,
+    /// Cap on generated tokens, `None` for no cap
+    pub max_tokens: Option<usize>,
+    pub pause_background_jobs: bool,
+}
+
+impl ThrottleLimits {
+    pub fn for_state(policy: &PowerPolicy, state: &PowerState) -> Self {
+        if !state.throttled() {
+            return Self::default();
+        }
+        Self {
+            concurrency: Some(policy.throttled_concurrency.max(1)),
+            max_tokens: policy.max_tokens,
+            pause_background_jobs: policy.pause_background_jobs,
+        }
+    }
+}
+
+/// Limit on concurrent generations and their length, changeable while they run
+///
+/// Lowering the limit does not stop running generations; new ones wait
+/// until fewer than the limit are running.
+#[derive(Debug, Default)]
+pub struct GenerationThrottle {
+    /// Generations allowed at once, 0 for no limit
+    limit: AtomicUsize,
+    /// Cap on generated tokens, 0 for no cap
+    max_tokens: AtomicUsize,
+    active: AtomicUsize,
+    released: Notify,
+}
+
+impl GenerationThrottle {
+    pub fn set(&self, concurrency: Option<usize>, max_tokens: Option<usize>) {
+        self.limit.store(concurrency.map_or(0, |concurrency| concurrency.max(1)), Ordering::SeqCst);
+        self.max_tokens.store(max_tokens.unwrap_or(0), Ordering::SeqCst);
+        // A raised limit lets waiting generations start
+        self.released.notify_waiters();
+    }
+
+    /// Cap on generated tokens, if any
+    pub fn max_tokens(&self) -> Option<usize> {
+        Some(self.max_tokens.load(Ordering::SeqCst)).filter(|&max_tokens| max_tokens > 0)
+    }
+
+    /// Generations currently running
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::SeqCst)
+    }
+
+    /// Wait for a slot under the limit; the generation runs while the permit is held
+    pub async fn acquire(&self) -> GenerationPermit<'_> {
+        loop {
+            let released = self.released.notified();
+            tokio::pin!(released);
+            // Registered before checking, so a release in between is not missed
+            released.as_mut().enable();
+            if self.try_acquire() {
+                return GenerationPermit { throttle: self };
+            }
+            released.await;
+        }
+    }
+
+    fn try_acquire(&self) -> bool {
+        self.active
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |active| {
+                let limit = self.limit.load(Ordering::SeqCst);
+                (limit == 0 || active < limit).then_some(active + 1)
+            })
+            .is_ok()
+    }
+}
+
+/// Slot of a running generation, released on drop
+#[derive(Debug)]
+pub struct GenerationPermit<'a> {
+    throttle: &'a GenerationThrottle,
+}
+
+impl Drop for GenerationPermit<'_> {
+    fn drop(&mut self) {
+        self.throttle.active.fetch_sub(1, Ordering::SeqCst);
+        self.throttle.released.notify_waiters();
+    }
+}
+
+/// Periodic power readings evaluated against the policy
+///
+/// Subscribers are notified when the state changes in more than battery
+/// charge or temperature, and whenever the policy is changed.
+#[derive(Debug)]
+pub struct PowerMonitor {
+    source: Arc<dyn PowerSource>,
+    policy: RwLock<PowerPolicy>,
+    reading: Mutex<PowerReading>,
+    state: watch::Sender<PowerState>,
+    cancel: CancellationToken,
+}
+
+impl PowerMonitor {
+    /// Monitor of this machine's battery; nothing is read until [`start`](Self::start) or [`refresh`](Self::refresh)
+    pub fn new(policy: PowerPolicy) -> Self {
+        Self {
+            source: Arc::new(SystemPowerSource::default()),
+            policy: RwLock::new(policy),
+            reading: Mutex::new(PowerReading::default()),
+            state: watch::channel(PowerState::default()).0,
+            cancel: CancellationToken::new(),
+        }
+    }
+
+    /// Read power from `source` instead of the machine
+    pub fn with_source(mut self, source: Arc<dyn PowerSource>) -> Self {
+        self.source = source;
+        self
+    }
+
+    /// State as of the latest reading
+    pub fn state(&self) -> PowerState {
+        self.state.borrow().clone()
+    }
+
+    /// Receiver of state changes, starting with the current state
+    pub fn subscribe(&self) -> watch::Receiver<PowerState> {
+        self.state.subscribe()
+    }
+
+    pub fn policy(&self) -> PowerPolicy {
+        self.policy.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Limits for the current state under the current policy
+    pub fn limits(&self) -> ThrottleLimits {
+        ThrottleLimits::for_state(&self.policy(), &self.state())
+    }
+
+    /// Replace the policy and re-evaluate the latest reading
+    pub fn set_policy(&self, policy: PowerPolicy) -> PowerState {
+        *self.policy.write().unwrap_or_else(|e| e.into_inner()) = policy;
+        let state = self.evaluate();
+        // The limits may have changed even when the state has not
+        self.state.send_replace(state.clone());
+        state
+    }
+
+    /// Read the battery and sensors now; blocks on `pmset` on macOS
+    pub fn refresh(&self) -> PowerState {
+        let reading = self.source.read();
+        *self.reading.lock().unwrap_or_else(|e| e.into_inner()) = reading;
+        let state = self.evaluate();
+        self.state.send_if_modified(|current| {
+            let changed = !current.same_mode(&state);
+            *current = state.clone();
+            changed
+        });
+        state
+    }
+
+    fn evaluate(&self) -> PowerState {
+        let reading = *self.reading.lock().unwrap_or_else(|e| e.into_inner());
+        PowerState::evaluate(&reading, &self.policy())
+    }
+
+    /// Poll in the background until [`stop`](Self::stop)ped; without a battery nothing is polled
+    pub fn start(self: &Arc<Self>) {
+        let monitor = Arc::clone(self);
+        tokio::spawn(async move {
+            loop {
+                let reader = Arc::clone(&monitor);
+                let Ok(state) = tokio::task::spawn_blocking(move || reader.refresh()).await else {
+                    break;
+                };
+                if !state.has_battery {
+                    tracing::info!("No battery found, power throttling is off");
+                    break;
+                }
+
+                let interval = Duration::from_secs(monitor.policy().poll_interval_secs.max(1));
+                tokio::select! {
+                    _ = monitor.cancel.cancelled() => break,
+                    _ = tokio::time::sleep(interval) => {}
+                }
+            }
+        });
+    }
+
+    pub fn stop(&self) {
+        self.cancel.cancel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Power source returning whatever reading a test sets
+    #[derive(Debug, Default)]
+    struct FakePowerSource(Mutex<PowerReading>);
+
+    impl FakePowerSource {
+        fn set(&self, reading: PowerReading) {
+            *self.0.lock().unwrap() = reading;
+        }
+    }
+
+    impl PowerSource for FakePowerSource {
+        fn read(&self) -> PowerReading {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    fn laptop(on_battery: bool, temperature: f32) -> PowerReading {
+        PowerReading {
+            has_battery: true,
+            on_battery,
+            battery_percent: Some(80.0),
+            temperature_celsius: Some(temperature),
+            critical_temperature: false,
+        }
+    }
+
+    #[test]
+    fn test_throttle_reasons() {
+        let policy = PowerPolicy::default();
+        let throttle = |reading: PowerReading| PowerState::evaluate(&reading, &policy).throttle;
+
+        assert_eq!(throttle(laptop(false, 50.0)), None);
+        assert_eq!(throttle(laptop(true, 50.0)), Some(ThrottleReason::Battery));
+        assert_eq!(throttle(laptop(false, 95.0)), Some(ThrottleReason::Thermal));
+        assert_eq!(throttle(PowerReading { critical_temperature: true, ..laptop(false, 60.0) }), Some(ThrottleReason::Thermal));
+
+        // Desktops are never throttled, however hot
+        let desktop = PowerReading { temperature_celsius: Some(99.0), critical_temperature: true, ..PowerReading::default() };
+        assert_eq!(PowerState::evaluate(&desktop, &policy), PowerState { temperature_celsius: Some(99.0), ..PowerState::default() });
+
+        let disabled = PowerPolicy { enabled: false, ..PowerPolicy::default() };
+        assert_eq!(PowerState::evaluate(&laptop(true, 95.0), &disabled).throttle, None);
+        let on_battery_allowed = PowerPolicy { throttle_on_battery: false, ..PowerPolicy::default() };
+        assert_eq!(PowerState::evaluate(&laptop(true, 50.0), &on_battery_allowed).throttle, None);
+    }
+
+    #[test]
+    fn test_limits_apply_only_while_throttled() {
+        let policy = PowerPolicy { max_tokens: Some(128), ..PowerPolicy::default() };
+        let throttled = PowerState::evaluate(&laptop(true, 50.0), &policy);
+        assert_eq!(
+            ThrottleLimits::for_state(&policy, &throttled),
+            ThrottleLimits { concurrency: Some(1), max_tokens: Some(128), pause_background_jobs: true }
+        );
+        let plugged_in = PowerState::evaluate(&laptop(false, 50.0), &policy);
+        assert_eq!(ThrottleLimits::for_state(&policy, &plugged_in), ThrottleLimits::default());
+    }
+
+    #[tokio::test]
+    async fn test_monitor_notifies_on_mode_changes_only() {
+        let source = Arc::new(FakePowerSource::default());
+        let monitor = PowerMonitor::new(PowerPolicy::default()).with_source(source.clone());
+        let mut states = monitor.subscribe();
+
+        source.set(laptop(false, 50.0));
+        monitor.refresh();
+        assert!(states.has_changed().unwrap());
+        states.mark_unchanged();
+
+        // A lower charge alone is not worth an event, but is reported
+        source.set(PowerReading { battery_percent: Some(79.0), ..laptop(false, 55.0) });
+        assert_eq!(monitor.refresh().battery_percent, Some(79.0));
+        assert!(!states.has_changed().unwrap());
+        assert_eq!(monitor.state().battery_percent, Some(79.0));
+
+        source.set(laptop(true, 55.0));
+        assert_eq!(monitor.refresh().throttle, Some(ThrottleReason::Battery));
+        assert!(states.has_changed().unwrap());
+        assert_eq!(monitor.limits().concurrency, Some(1));
+
+        let state = monitor.set_policy(PowerPolicy { enabled: false, ..PowerPolicy::default() });
+        assert!(!state.throttled());
+        assert_eq!(monitor.limits(), ThrottleLimits::default());
+    }
+
+    #[tokio::test]
+    async fn test_throttle_holds_generations_over_the_limit() {
+        let throttle = Arc::new(GenerationThrottle::default());
+        throttle.set(Some(1), Some(64));
+        assert_eq!(throttle.max_tokens(), Some(64));
+
+        let first = throttle.acquire().await;
+        let waiting = {
+            let throttle = Arc::clone(&throttle);
+            tokio::spawn(async move {
+                let _permit = throttle.acquire().await;
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiting.is_finished());
+        assert_eq!(throttle.active(), 1);
+
+        drop(first);
+        tokio::time::timeout(Duration::from_secs(1), waiting).await.unwrap().unwrap();
+        assert_eq!(throttle.active(), 0);
+
+        throttle.set(None, None);
+        let _a = throttle.acquire().await;
+        let _b = throttle.acquire().await;
+        assert_eq!((throttle.active(), throttle.max_tokens()), (2, None));
+    }
+
+    #[test]
+    fn test_reads_the_system_battery_from_sysfs() {
+        let dir = tempfile::tempdir().unwrap();
+        let supply = |name: &str, files: &[(&str, &str)]| {
+            let path = dir.path().join(name);
+            std::fs::create_dir(&path).unwrap();
+            for (file, value) in files {
+                std::fs::write(path.join(file), format!("{}\n", value)).unwrap();
+            }
+        };
+        supply("AC", &[("type", "Mains"), ("online", "0")]);
+        supply("hidpp_battery_0", &[("type", "Battery"), ("scope", "Device"), ("status", "Charging")]);
+        assert_eq!(read_power_supply(dir.path()), None);
+
+        supply("BAT0", &[("type", "Battery"), ("status", "Discharging"), ("capacity", "42")]);
+        assert_eq!(read_power_supply(dir.path()), Some((true, Some(42.0))));
+    }
+
+    #[test]
+    fn test_parses_pmset_output() {
+        let on_battery = "Now drawing from 'Battery Power'\n -InternalBattery-0 (id=4653155)\t85%; discharging; 4:30 remaining present: true\n";
+        assert_eq!(parse_pmset(on_battery), Some((true, Some(85.0))));
+        let charging = "Now drawing from 'AC Power'\n -InternalBattery-0 (id=4653155)\t100%; charged; 0:00 remaining present: true\n";
+        assert_eq!(parse_pmset(charging), Some((false, Some(100.0))));
+        assert_eq!(parse_pmset("Now drawing from 'AC Power'\n"), None);
+    }
+}
@@ -0,0 +1,342 @@
+//! Export and import of settings apart from the vault
+//!
+//! A settings bundle is one JSON file holding the configuration, the
+//! user-configurable rows of the settings table (search synonyms and stop
+//! words among them) and the automation rules, so a reinstall can restore a
+//! tuned setup onto any vault. Paths are machine-specific and never imported:
+//! the database, models, content and log locations of the running install
+//! are kept.
+//!
+//! Bundles carry [`SETTINGS_BUNDLE_VERSION`]; bundles written by a newer
+//! version are refused. Imports are validated as a whole before anything is
+//! written, and a dry run returns the same [`SettingsImportReport`] without
+//! applying it.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::info;
+
+use crate::config::CodexConfig;
+use crate::content::rules::AutomationRule;
+use crate::db::models::Setting;
+use crate::db::{DatabaseManager, RuleQueries, SettingQueries};
+use crate::{CodexError, CodexResult};
+
+/// Format version of settings bundles written by this build
+pub const SETTINGS_BUNDLE_VERSION: u32 = 1;
+
+/// Settings, configuration and rules of an install
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettingsBundle {
+    pub schema_version: u32,
+    pub exported_at: String,
+    /// Version of the app that wrote the bundle
+    pub app_version: String,
+    pub config: CodexConfig,
+    pub settings: Vec<BundledSetting>,
+    pub rules: Vec<AutomationRule>,
+}
+
+/// Row of the settings table in a bundle
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BundledSetting {
+    pub key: String,
+    pub value: Value,
+    pub category: String,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// Part of the install a change belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SettingsSection {
+    Config,
+    Setting,
+    Rule,
+}
+
+/// One value an import adds or changes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SettingsChange {
+    pub section: SettingsSection,
+    /// Dotted config path, setting key or rule name
+    pub key: String,
+    /// Current value, None when the import adds it
+    pub before: Option<Value>,
+    pub after: Value,
+}
+
+/// What an import changes, or changed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettingsImportReport {
+    /// Nothing was written
+    pub dry_run: bool,
+    pub schema_version: u32,
+    pub changes: Vec<SettingsChange>,
+    /// Configuration changes only take full effect after a restart
+    pub restart_required: bool,
+}
+
+impl CodexConfig {
+    /// Write this configuration, the user-configurable settings and the rules to `dest`
+    pub async fn export_settings(&self, db: &DatabaseManager, dest: &Path) -> CodexResult<SettingsBundle> {
+        let settings = SettingQueries::get_user_configurable(db.pool())
+            .await?
+            .into_iter()
+            .map(|setting| BundledSetting {
+                value: serde_json::from_str(&setting.value).unwrap_or(Value::String(setting.value)),
+                key: setting.key,
+                category: setting.category,
+                description: setting.description,
+            })
+            .collect();
+        let rules = RuleQueries::get_all(db.pool())
+            .await?
+            .into_iter()
+            .map(AutomationRule::try_from)
+            .collect::<CodexResult<Vec<_>>>()?;
+
+        let bundle = SettingsBundle {
+            schema_version: SETTINGS_BUNDLE_VERSION,
+            exported_at: chrono::Utc::now().to_rfc3339(),
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            config: self.clone(),
+            settings,
+            rules,
+        };
+        tokio::fs::write(dest, serde_json::to_vec_pretty(&bundle)?).await?;
+
+        info!(
+            "Exported {} settings and {} rules to {:?}",
+            bundle.settings.len(),
+            bundle.rules.len(),
+            dest
+        );
+        Ok(bundle)
+    }
+
+    /// Apply the bundle at `src` to this configuration and the database
+    ///
+    /// The configuration is only changed in memory; callers save it. With
+    /// `dry_run` nothing is changed and the report lists what would be.
+    pub async fn import_settings(&mut self, db: &DatabaseManager, src: &Path, dry_run: bool) -> CodexResult<SettingsImportReport> {
+        let bundle = read_bundle(&tokio::fs::read(src).await?)?;
+        let mut config = bundle.config.clone();
+        config.keep_local_paths(self);
+        config
+            .validate()
+            .map_err(|e| CodexError::validation(format!("Invalid configuration in settings bundle: {}", e)))?;
+        for rule in &bundle.rules {
+            rule.validate()?;
+        }
+        if let Some(setting) = bundle.settings.iter().find(|s| s.key.trim().is_empty()) {
+            return Err(CodexError::validation(format!("Setting without a key in category {}", setting.category)));
+        }
+
+        let mut changes = config_changes(self, &config)?;
+        let restart_required = !changes.is_empty();
+        changes.extend(setting_changes(db, &bundle.settings).await?);
+        changes.extend(rule_changes(db, &bundle.rules).await?);
+
+        if !dry_run {
+            for bundled in &bundle.settings {
+                if let Some(current) = SettingQueries::get(db.pool(), &bundled.key).await? {
+                    if !current.is_user_configurable {
+                        continue;
+                    }
+                }
+                let mut setting = Setting::new(bundled.key.clone(), String::new(), bundled.category.clone());
+                setting.description = bundled.description.clone();
+                setting.set_value(&bundled.value)?;
+                SettingQueries::set(db.pool(), &setting).await?;
+            }
+            for rule in &bundle.rules {
+                let row = rule.to_row()?;
+                if !RuleQueries::update(db.pool(), &row).await? {
+                    RuleQueries::create(db.pool(), &row).await?;
+                }
+            }
+            *self = config;
+            info!("Imported settings bundle {:?} with {} changes", src, changes.len());
+        }
+
+        Ok(SettingsImportReport {
+            dry_run,
+            schema_version: bundle.schema_version,
+            changes,
+            restart_required,
+        })
+    }
+
+    /// Take the machine-specific paths and version of `local`
+    fn keep_local_paths(&mut self, local: &CodexConfig) {
+        self.database.path = local.database.path.clone();
+        self.ai.models_dir = local.ai.models_dir.clone();
+        self.content.content_dir = local.content.content_dir.clone();
+        self.content.whisper_model_path = local.content.whisper_model_path.clone();
+        self.app.logging.directory = local.app.logging.directory.clone();
+        self.app.version = local.app.version.clone();
+    }
+}
+
+/// Parse a bundle, refusing unknown format versions
+fn read_bundle(bytes: &[u8]) -> CodexResult<SettingsBundle> {
+    let raw: Value = serde_json::from_slice(bytes)?;
+    let version = raw
+        .get("schema_version")
+        .and_then(Value::as_u64)
+        .ok_or_else(|| CodexError::validation("Not a settings bundle: schema_version is missing"))?;
+    if version == 0 || version > SETTINGS_BUNDLE_VERSION as u64 {
+        return Err(CodexError::validation(format!(
+            "Settings bundle version {} is not supported (this app reads up to {})",
+            version, SETTINGS_BUNDLE_VERSION
+        )));
+    }
+    Ok(serde_json::from_value(raw)?)
+}
+
+/// Changed leaves of the configuration, by dotted path
+fn config_changes(current: &CodexConfig, imported: &CodexConfig) -> CodexResult<Vec<SettingsChange>> {
+    let mut before = BTreeMap::new();
+    let mut after = BTreeMap::new();
+    flatten("", serde_json::to_value(current)?, &mut before);
+    flatten("", serde_json::to_value(imported)?, &mut after);
+
+    Ok(after
+        .into_iter()
+        .filter(|(key, value)| before.get(key) != Some(value))
+        .map(|(key, value)| SettingsChange {
+            section: SettingsSection::Config,
+            before: before.get(&key).cloned(),
+            key,
+            after: value,
+        })
+        .collect())
+}
+
+/// Objects become dotted paths; anything else, arrays included, is a leaf
+fn flatten(prefix: &str, value: Value, leaves: &mut BTreeMap<String, Value>) {
+    match value {
+        Value::Object(fields) => {
+            for (name, field) in fields {
+                let path = if prefix.is_empty() { name } else { format!("{}.{}", prefix, name) };
+                flatten(&path, field, leaves);
+            }
+        }
+        leaf => {
+            leaves.insert(prefix.to_string(), leaf);
+        }
+    }
+}
+
+async fn setting_changes(db: &DatabaseManager, settings: &[BundledSetting]) -> CodexResult<Vec<SettingsChange>> {
+    let mut changes = Vec::new();
+    for bundled in settings {
+        let current = SettingQueries::get(db.pool(), &bundled.key).await?;
+        if current.as_ref().is_some_and(|s| !s.is_user_configurable) {
+            continue;
+        }
+        let before = current.map(|s| serde_json::from_str(&s.value).unwrap_or(Value::String(s.value)));
+        if before.as_ref() != Some(&bundled.value) {
+            changes.push(SettingsChange {
+                section: SettingsSection::Setting,
+                key: bundled.key.clone(),
+                before,
+                after: bundled.value.clone(),
+            });
+        }
+    }
+    Ok(changes)
+}
+
+async fn rule_changes(db: &DatabaseManager, rules: &[AutomationRule]) -> CodexResult<Vec<SettingsChange>> {
+    let mut changes = Vec::new();
+    for rule in rules {
+        let before = RuleQueries::get_by_id(db.pool(), &rule.id)
+            .await?
+            .map(AutomationRule::try_from)
+            .transpose()?
+            .map(|current| rule_value(&current))
+            .transpose()?;
+        let after = rule_value(rule)?;
+        if before.as_ref() != Some(&after) {
+            changes.push(SettingsChange {
+                section: SettingsSection::Rule,
+                key: rule.name.clone(),
+                before,
+                after,
+            });
+        }
+    }
+    Ok(changes)
+}
+
+/// A rule without its timestamps, which do not count as a change
+fn rule_value(rule: &AutomationRule) -> CodexResult<Value> {
+    let mut value = serde_json::to_value(rule)?;
+    if let Some(fields) = value.as_object_mut() {
+        fields.remove("created_at");
+        fields.remove("updated_at");
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_database;
+    use crate::content::rules::{RuleActions, RuleConditions};
+
+    #[tokio::test]
+    async fn test_bundle_round_trip_keeps_local_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = test_database(&dir).await;
+        let bundle_path = dir.path().join("settings.json");
+
+        let mut tuned = CodexConfig::default().with_vault_dir(dir.path().join("old"));
+        tuned.ai.temperature = 0.2;
+        let mut synonyms = Setting::new("search_synonyms".to_string(), String::new(), "search".to_string());
+        synonyms.set_value(&serde_json::json!({ "car": ["automobile"] })).unwrap();
+        SettingQueries::set(source.pool(), &synonyms).await.unwrap();
+        let rule = AutomationRule::new(
+            "Receipts".to_string(),
+            0,
+            RuleConditions { title_regex: Some("(?i)receipt".to_string()), ..Default::default() },
+            RuleActions { category: Some("finance".to_string()), ..Default::default() },
+        );
+        RuleQueries::create(source.pool(), &rule.to_row().unwrap()).await.unwrap();
+        tuned.export_settings(&source, &bundle_path).await.unwrap();
+
+        let target_dir = tempfile::tempdir().unwrap();
+        let target = test_database(&target_dir).await;
+        let mut fresh = CodexConfig::default().with_vault_dir(target_dir.path());
+
+        let preview = fresh.import_settings(&target, &bundle_path, true).await.unwrap();
+        assert!(preview.dry_run && preview.restart_required);
+        let keys: Vec<&str> = preview.changes.iter().map(|c| c.key.as_str()).collect();
+        assert!(keys.contains(&"ai.temperature") && keys.contains(&"search_synonyms") && keys.contains(&"Receipts"));
+        assert!(!keys.contains(&"database.path"));
+        assert!(RuleQueries::get_all(target.pool()).await.unwrap().is_empty());
+
+        let applied = fresh.import_settings(&target, &bundle_path, false).await.unwrap();
+        assert_eq!(applied.changes, preview.changes);
+        assert_eq!(fresh.ai.temperature, 0.2);
+        assert_eq!(fresh.database.path, target_dir.path().join("codex.db"));
+        assert_eq!(RuleQueries::get_all(target.pool()).await.unwrap().len(), 1);
+
+        // Importing again changes nothing
+        let again = fresh.import_settings(&target, &bundle_path, true).await.unwrap();
+        assert!(again.changes.is_empty() && !again.restart_required);
+    }
+
+    #[test]
+    fn test_newer_bundles_are_refused() {
+        let newer = serde_json::json!({ "schema_version": SETTINGS_BUNDLE_VERSION + 1 });
+        assert!(read_bundle(newer.to_string().as_bytes()).is_err());
+        assert!(read_bundle(b"{}").is_err());
+    }
+}
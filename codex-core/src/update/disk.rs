@@ -0,0 +1,96 @@
+//! Free disk space checks before large writes
+//!
+//! Downloads, imports and exports check the volume they write to up front,
+//! so they fail immediately instead of filling the disk partway through.
+
+use std::path::{Path, PathBuf};
+
+use sysinfo::Disks;
+use tracing::{debug, warn};
+
+use crate::{CodexError, CodexResult};
+
+/// Headroom required on top of the expected size of a write
+pub const DISK_SPACE_MARGIN: f64 = 1.2;
+
+/// Bytes that must be free to write `expected_bytes`, including the margin
+pub fn required_space(expected_bytes: u64) -> u64 {
+    (expected_bytes as f64 * DISK_SPACE_MARGIN).ceil() as u64
+}
+
+/// Bytes available to the current user on the volume holding `path`
+///
+/// `path` does not need to exist yet; its nearest existing ancestor is
+/// used. Returns `None` when the volume cannot be determined.
+pub fn available_space(path: &Path) -> Option<u64> {
+    let path = existing_ancestor(path)?;
+    let disks = Disks::new_with_refreshed_list();
+
+    // The volume is the disk with the longest mount point containing the path
+    disks
+        .list()
+        .iter()
+        .filter(|disk| path.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().components().count())
+        .map(|disk| disk.available_space())
+}
+
+/// Fail with [`CodexError::InsufficientDiskSpace`] unless `path`'s volume has
+/// room for `expected_bytes` plus [`DISK_SPACE_MARGIN`]
+///
+/// Volumes whose free space cannot be determined pass the check.
+pub fn ensure_space(path: &Path, expected_bytes: u64) -> CodexResult<()> {
+    let required = required_space(expected_bytes);
+    match available_space(path) {
+        Some(available) if available < required => {
+            Err(CodexError::insufficient_disk_space(required, available))
+        }
+        Some(available) => {
+            debug!("{} bytes free at {}, {} required", available, path.display(), required);
+            Ok(())
+        }
+        None => {
+            warn!("Could not determine free disk space at {}", path.display());
+            Ok(())
+        }
+    }
+}
+
+fn existing_ancestor(path: &Path) -> Option<PathBuf> {
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir().ok()?.join(path)
+    };
+
+    absolute
+        .ancestors()
+        .find(|ancestor| ancestor.exists())
+        .and_then(|ancestor| ancestor.canonicalize().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disk_space_preflight() {
+        assert_eq!(required_space(0), 0);
+        assert_eq!(required_space(1000), 1200);
+
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("not/yet/created.gguf");
+        assert!(ensure_space(&missing, 1).is_ok());
+
+        // Nothing has an exabyte free; where the volume is known the check must fail
+        if available_space(&missing).is_some() {
+            match ensure_space(&missing, 1 << 60) {
+                Err(CodexError::InsufficientDiskSpace { required, available }) => {
+                    assert_eq!(required, required_space(1 << 60));
+                    assert!(available < required);
+                }
+                other => panic!("expected InsufficientDiskSpace, got {:?}", other),
+            }
+        }
+    }
+}
@@ -45,7 +45,7 @@ impl ModelDownloader {
             .user_agent("CodexVault/1.0")
             .timeout(std::time::Duration::from_secs(300)) // 5 minute timeout
             .build()
-            .map_err(|e| CodexError::network(e))?;
+            .map_err(CodexError::network)?;
 
         Ok(Self {
             client,
@@ -83,7 +83,7 @@ impl ModelDownloader {
         let start_time = std::time::Instant::now();
         
         // Download the model file
-        let _download_result = self.download_file(
+        self.download_file(
             &manifest.download_url,
             &local_path,
             manifest.file_size,
@@ -324,7 +324,6 @@ impl ModelDownloader {
 mod tests {
     use super::*;
     use tempfile::tempdir;
-    use crate::update::manifest::ModelManifest;
 
     #[tokio::test]
     async fn test_downloader_creation() {
@@ -418,12 +418,21 @@ pub struct ModelManifest {
     pub parameter_count: String,
     /// Quantization level (q4_k_m, q8_0, fp16, etc.)
     pub quantization: String,
-    /// Download URL for the model file
+    /// What the model is used for
+    #[serde(default)]
+    pub kind: ModelKind,
+    /// Download URL for the model file (single-file models)
+    #[serde(default)]
     pub download_url: String,
-    /// Model file size in bytes
+    /// Model file size in bytes (single-file models)
+    #[serde(default)]
     pub file_size: u64,
-    /// SHA-256 checksum for verification
+    /// SHA-256 checksum for verification (single-file models)
+    #[serde(default)]
     pub sha256_checksum: String,
+    /// Files of a multi-file artifact, stored together in the model's directory
+    #[serde(default)]
+    pub files: Vec<ArtifactFile>,
     /// Context length supported by model
     pub context_length: usize,
     /// Recommended hardware requirements
@@ -449,6 +458,40 @@ pub enum ModelFormat {
     ONNX,
 }
 
+/// What a model is used for
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ModelKind {
+    #[default]
+    Generation,
+    Embedding,
+}
+
+/// Role of a file within a multi-file artifact
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ArtifactRole {
+    Weights,
+    Tokenizer,
+    Config,
+    Vocab,
+}
+
+/// One file of a multi-file model artifact
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArtifactFile {
+    /// Path relative to the model's directory (e.g. `tokenizer.json`)
+    pub path: String,
+    /// What the file holds
+    pub role: ArtifactRole,
+    /// Download URL
+    pub download_url: String,
+    /// File size in bytes
+    pub file_size: u64,
+    /// SHA-256 checksum
+    pub sha256_checksum: String,
+}
+
 /// Hardware requirements for model
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HardwareRequirements {
@@ -507,9 +550,11 @@ impl ModelManifest {
             architecture: "mistral".to_string(),
             parameter_count: "7b".to_string(),
             quantization: "q4_k_m".to_string(),
+            kind: ModelKind::Generation,
             download_url: "https://huggingface.co/TheBloke/Mistral-7B-Instruct-v0.1-GGUF/resolve/main/mistral-7b-instruct-v0.1.q4_K_M.gguf".to_string(),
             file_size: 4_368_439_552, // ~4.1GB
             sha256_checksum: "1ee6114517d2f770425c880e645aa1c6e92e5f55d2adf854f769b30eed4a434b".to_string(),
+            files: Vec::new(),
             context_length: 8192,
             hardware_requirements: HardwareRequirements {
                 min_ram_gb: 6.0,
@@ -541,6 +586,50 @@ impl ModelManifest {
         }
     }
 
+    /// Create the model manifest for the all-MiniLM-L6-v2 sentence embedding model
+    pub fn all_minilm_l6_v2() -> Self {
+        let base_url = "https://huggingface.co/sentence-transformers/all-MiniLM-L6-v2/resolve/main";
+        let file = |path: &str, role, file_size, sha256_checksum: &str| ArtifactFile {
+            path: path.to_string(),
+            role,
+            download_url: format!("{}/{}", base_url, path),
+            file_size,
+            sha256_checksum: sha256_checksum.to_string(),
+        };
+
+        Self {
+            name: "all-MiniLM-L6-v2".to_string(),
+            version: "v1".to_string(),
+            description: "Sentence embedding model producing 384-dimensional vectors for semantic search".to_string(),
+            format: ModelFormat::Safetensors,
+            architecture: "bert".to_string(),
+            parameter_count: "22m".to_string(),
+            quantization: "fp32".to_string(),
+            kind: ModelKind::Embedding,
+            download_url: String::new(),
+            file_size: 0,
+            sha256_checksum: String::new(),
+            files: vec![
+                file("model.safetensors", ArtifactRole::Weights, 90_868_376, "53aa51172d142c89d9012cce15ae4d6cc0ca6895895114379cacb4fab128d9db"),
+                file("tokenizer.json", ArtifactRole::Tokenizer, 466_247, "be50c3628f2bf5bb5e3a7f17b1f74611b2561a3a27eeab05e5aa30f411572037"),
+                file("config.json", ArtifactRole::Config, 612, "953f9c0d463486b10a6871cc2fd59f223b2c70184f49815e7efbcab5d8908b41"),
+            ],
+            context_length: 512,
+            hardware_requirements: HardwareRequirements {
+                min_ram_gb: 0.5,
+                recommended_ram_gb: 1.0,
+                vram_gb: None,
+                min_cpu_cores: 1,
+                supported_devices: vec!["cpu".to_string(), "cuda".to_string(), "metal".to_string()],
+            },
+            license: "Apache 2.0".to_string(),
+            release_date: chrono::DateTime::parse_from_rfc3339("2021-08-30T00:00:00Z").unwrap().with_timezone(&chrono::Utc),
+            tags: vec!["embedding".to_string(), "sentence-transformers".to_string()],
+            performance: None,
+            dependencies: Vec::new(),
+        }
+    }
+
     /// Parse model manifest from JSON
     pub fn from_json(json: &str) -> CodexResult<Self> {
         serde_json::from_str(json).map_err(|e| {
@@ -574,22 +663,55 @@ impl ModelManifest {
             validation.is_valid = false;
         }
 
-        // Validate download URL
-        if !self.download_url.starts_with("http://") && !self.download_url.starts_with("https://") {
-            validation.errors.push("Invalid download URL".to_string());
-            validation.is_valid = false;
-        }
+        if self.is_multi_file() {
+            // Validate artifact files; each lands inside the model's directory
+            for file in &self.files {
+                let path = Path::new(&file.path);
+                if file.path.is_empty()
+                    || !path.components().all(|c| matches!(c, std::path::Component::Normal(_)))
+                {
+                    validation.errors.push(format!("Invalid artifact file path: {}", file.path));
+                    validation.is_valid = false;
+                }
+
+                if !is_http_url(&file.download_url) {
+                    validation.errors.push(format!("Invalid download URL for {}", file.path));
+                    validation.is_valid = false;
+                }
+
+                if file.file_size == 0 {
+                    validation.errors.push(format!("File size of {} cannot be zero", file.path));
+                    validation.is_valid = false;
+                }
+
+                if !is_sha256(&file.sha256_checksum) {
+                    validation.errors.push(format!("Invalid checksum for {}", file.path));
+                    validation.is_valid = false;
+                }
+            }
 
-        // Validate file size
-        if self.file_size == 0 {
-            validation.errors.push("File size cannot be zero".to_string());
-            validation.is_valid = false;
-        }
+            if !self.files.iter().any(|f| f.role == ArtifactRole::Weights) {
+                validation.errors.push("Artifact has no weights file".to_string());
+                validation.is_valid = false;
+            }
+        } else {
+            // Validate download URL
+            if !is_http_url(&self.download_url) {
+                validation.errors.push("Invalid download URL".to_string());
+                validation.is_valid = false;
+            }
 
-        // Validate checksum (SHA-256 should be 64 hex characters)
-        if self.sha256_checksum.len() != 64 || !self.sha256_checksum.chars().all(|c| c.is_ascii_hexdigit()) {
-            validation.errors.push("Invalid SHA-256 checksum format".to_string());
-            validation.is_valid = false;
+            // Validate file size
+            if self.file_size == 0 {
+                validation.errors.push("File size cannot be zero".to_string());
+                validation.is_valid = false;
+            }
+
+            // Validate checksum (SHA-256 should be 64 hex characters)
+            if !is_sha256(&self.sha256_checksum) {
+                validation.errors.push("Invalid SHA-256 checksum format".to_string());
+                validation.is_valid = false;
+            }
         }
 
         // Validate context length
@@ -616,7 +738,7 @@ impl ModelManifest {
                 validation.is_valid = false;
             }
 
-            if dep.sha256_checksum.len() != 64 || !dep.sha256_checksum.chars().all(|c| c.is_ascii_hexdigit()) {
+            if !is_sha256(&dep.sha256_checksum) {
                 validation.errors.push(format!("Invalid checksum for dependency {}", dep.name));
                 validation.is_valid = false;
             }
@@ -650,8 +772,41 @@ impl ModelManifest {
         self.hardware_requirements.supported_devices.contains(&current_device.to_string())
     }
 
-    /// Get the local file path where this model should be stored
+    /// Whether the model is made of several files (weights, tokenizer, config)
+    pub fn is_multi_file(&self) -> bool {
+        !self.files.is_empty()
+    }
+
+    /// Total bytes to download, including required dependencies
+    pub fn total_size(&self) -> u64 {
+        if self.is_multi_file() {
+            self.files.iter().map(|f| f.file_size).sum()
+        } else {
+            self.file_size + self.dependencies.iter().filter(|d| d.required).map(|d| d.file_size).sum::<u64>()
+        }
+    }
+
+    /// Whether every artifact file is present with its expected size
+    ///
+    /// A cheap check for startup; checksums are verified when downloading.
+    pub fn artifact_present(&self, models_dir: &Path) -> bool {
+        let dir = self.get_local_path(models_dir);
+        self.files.iter().all(|file| {
+            std::fs::metadata(dir.join(&file.path))
+                .map(|m| m.len() == file.file_size)
+                .unwrap_or(false)
+        })
+    }
+
+    /// Get the local path where this model should be stored
+    ///
+    /// Multi-file models live in a directory named after the model, with
+    /// each file at its artifact path; single-file models are a file.
     pub fn get_local_path(&self, models_dir: &Path) -> PathBuf {
+        if self.is_multi_file() {
+            return models_dir.join(&self.name);
+        }
+
         let filename = format!("{}-{}.{}", 
             self.name, 
             self.version, 
@@ -667,7 +822,7 @@ impl ModelManifest {
 
     /// Get estimated download time in minutes for given speed (MB/s)
     pub fn estimated_download_time(&self, speed_mbps: f32) -> f32 {
-        let file_size_mb = self.file_size as f32 / (1024.0 * 1024.0);
+        let file_size_mb = self.total_size() as f32 / (1024.0 * 1024.0);
         (file_size_mb / speed_mbps) / 60.0 // Convert to minutes
     }
 }
@@ -706,6 +861,7 @@ impl ModelRegistry {
             last_updated: chrono::Utc::now(),
             models: vec![
                 ModelManifest::mistral_7b_instruct_q4k(),
+                ModelManifest::all_minilm_l6_v2(),
             ],
             metadata: ModelRegistryMetadata {
                 name: "Codex Vault Official Models".to_string(),
@@ -721,6 +877,11 @@ impl ModelRegistry {
         self.models.iter().find(|m| m.name == name)
     }
 
+    /// Find an embedding model by name
+    pub fn find_embedding_model(&self, name: &str) -> Option<&ModelManifest> {
+        self.find_model(name).filter(|m| m.kind == ModelKind::Embedding)
+    }
+
     /// Get models compatible with current system
     pub fn compatible_models(&self) -> Vec<&ModelManifest> {
         self.models.iter()
@@ -750,6 +911,14 @@ impl ModelRegistry {
     }
 }
 
+fn is_http_url(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+fn is_sha256(checksum: &str) -> bool {
+    checksum.len() == 64 && checksum.chars().all(|c| c.is_ascii_hexdigit())
+}
+
 #[cfg(test)]
 mod model_tests {
     use super::*;
@@ -795,4 +964,33 @@ mod model_tests {
         assert!(time_fast < time_slow);
         assert!(time_fast > 0.0);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_embedding_model_artifact() {
+        let manifest = ModelManifest::all_minilm_l6_v2();
+        let validation = manifest.validate();
+
+        assert!(validation.is_valid, "Validation errors: {:?}", validation.errors);
+        assert!(manifest.is_multi_file());
+        assert_eq!(manifest.get_local_path(Path::new("/tmp/models")), Path::new("/tmp/models/all-MiniLM-L6-v2"));
+        assert_eq!(manifest.total_size(), manifest.files.iter().map(|f| f.file_size).sum::<u64>());
+        assert!(!manifest.artifact_present(Path::new("/nonexistent")));
+
+        let registry = ModelRegistry::default_registry();
+        assert!(registry.find_embedding_model("all-MiniLM-L6-v2").is_some());
+        assert!(registry.find_embedding_model("mistral-7b-instruct-q4_k").is_none());
+
+        // Artifact paths may not escape the model directory
+        let mut escaping = manifest.clone();
+        escaping.files[1].path = "../tokenizer.json".to_string();
+        assert!(!escaping.validate().is_valid);
+
+        // Manifests written before multi-file support still parse
+        let mut legacy: serde_json::Value = serde_json::from_str(&ModelManifest::mistral_7b_instruct_q4k().to_json().unwrap()).unwrap();
+        legacy.as_object_mut().unwrap().remove("files");
+        legacy.as_object_mut().unwrap().remove("kind");
+        let parsed = ModelManifest::from_json(&legacy.to_string()).unwrap();
+        assert_eq!(parsed.kind, ModelKind::Generation);
+        assert!(!parsed.is_multi_file());
+    }
+}
@@ -1,5 +1,7 @@
 //! Application update management module
 
+use std::path::Path;
+
 use anyhow::Result;
 use tracing::{info, debug, warn};
 
@@ -10,11 +12,30 @@ pub mod manager;
 pub mod manifest;
 pub mod downloader;
 pub mod model_downloader;
+pub mod disk;
+pub mod registry;
+pub mod throttle;
 pub use manager::*;
 pub use manifest::*;
 // Import specific items to avoid name conflicts
 pub use downloader::{ModelDownloader as OriginalModelDownloader, DownloadResult, DownloadProgress as OriginalDownloadProgress};
 pub use model_downloader::{ModelDownloader, DownloadProgress, DownloadStage};
+pub use registry::{ModelCatalog, CatalogModel, CatalogSource};
+pub use throttle::DownloadControl;
+
+/// HTTP client for requests that set their own timeouts
+///
+/// Clones share one connection pool, so repeated calls to the same host
+/// reuse connections.
+pub fn http_client() -> reqwest::Client {
+    static CLIENT: once_cell::sync::Lazy<reqwest::Client> = once_cell::sync::Lazy::new(|| {
+        reqwest::Client::builder()
+            .user_agent("Codex-Vault/1.0")
+            .build()
+            .unwrap_or_default()
+    });
+    CLIENT.clone()
+}
 
 /// Update manager for handling application updates
 #[derive(Debug)]
@@ -69,27 +90,78 @@ impl UpdateManager {
                             min_version: manifest.min_version,
                         };
                         
-                        return Ok(Some(update_info));
+                        Ok(Some(update_info))
                     } else {
                         debug!("No updates available");
-                        return Ok(None);
+                        Ok(None)
                     }
                 } else {
                     warn!("Failed to fetch update manifest: {}", response.status());
-                    return Ok(None);
+                    Ok(None)
                 }
             }
             Err(e) => {
                 warn!("Failed to check for updates: {}", e);
-                return Ok(None);
+                Ok(None)
             }
         }
     }
 
+    /// Models available for download, from the update server's `models.json`
+    ///
+    /// A registry fetched from the server is cached in `models_dir`; when
+    /// offline or the server is unreachable the cached copy is used, then
+    /// the bundled one. With `pin_model_version` set, models already listed
+    /// in the cache keep their version.
+    pub async fn list_available_models(&self, models_dir: &Path, offline: bool) -> CodexResult<ModelCatalog> {
+        let public_key = self.config.model_registry_public_key.as_deref();
+        let cached = ModelCatalog::load_cached(models_dir, public_key).ok();
+
+        if !offline {
+            match self.fetch_model_registry(public_key).await {
+                Ok(mut remote) => {
+                    if let (true, Some(cached)) = (self.config.pin_model_version, cached.as_ref()) {
+                        remote.apply_pins(cached);
+                    }
+                    if let Err(e) = remote.save_cached(models_dir) {
+                        warn!("Failed to cache model registry: {}", e);
+                    }
+                    return Ok(remote);
+                }
+                Err(e) => warn!("Failed to fetch model registry, using local copy: {}", e),
+            }
+        }
+
+        Ok(cached.unwrap_or_else(ModelCatalog::bundled))
+    }
+
+    /// Fetch and validate `models.json` from the update server
+    async fn fetch_model_registry(&self, public_key: Option<&str>) -> CodexResult<ModelCatalog> {
+        let Some(public_key) = public_key else {
+            return Err(CodexError::config("No model registry signing key configured"));
+        };
+
+        let url = format!("{}/{}", self.config.server_url, registry::MODEL_REGISTRY_FILE);
+        debug!("Fetching model registry from {}", url);
+        let response = self.client.get(&url).send().await?;
+        if !response.status().is_success() {
+            return Err(CodexError::update(format!("Failed to fetch model registry: {}", response.status())));
+        }
+
+        ModelCatalog::parse(&response.text().await?, Some(public_key), CatalogSource::Remote)
+    }
+
     /// Download and install an update
     pub async fn download_and_install_update(&self, update_info: &UpdateInfo) -> CodexResult<()> {
         info!("Downloading update: {}", update_info.version);
 
+        // The update is installed next to the running executable
+        let install_dir = std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(|dir| dir.to_path_buf()))
+            .unwrap_or_else(std::env::temp_dir);
+        disk::ensure_space(&install_dir, update_info.file_size as u64)?;
+
         // Download the update
         let update_file = self.download_update(update_info).await?;
 
@@ -119,7 +191,16 @@ impl UpdateManager {
             )));
         }
 
-        let bytes = response.bytes().await?;
+        // Stream through the shared download control so the speed limit and pausing apply
+        let control = DownloadControl::global();
+        let mut bytes = Vec::with_capacity(update_info.file_size);
+        let mut stream = response.bytes_stream();
+        while let Some(chunk) = futures_util::StreamExt::next(&mut stream).await {
+            let chunk = chunk?;
+            control.wait_while_paused().await;
+            control.throttle(chunk.len() as u64).await;
+            bytes.extend_from_slice(&chunk);
+        }
         
         if bytes.len() != update_info.file_size {
             return Err(CodexError::update(format!(
@@ -129,7 +210,7 @@ impl UpdateManager {
             )));
         }
 
-        Ok(bytes.to_vec())
+        Ok(bytes)
     }
 
     /// Verify update file checksum
@@ -226,6 +307,26 @@ impl UpdateManager {
         }
     }
 
+    /// Check that the update server answers, with the failure reason if it does not
+    pub async fn check_reachability(&self) -> CodexResult<()> {
+        let response = self
+            .client
+            .head(&self.config.server_url)
+            .send()
+            .await
+            .map_err(CodexError::network)?;
+
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(CodexError::update(format!(
+                "Update server {} returned {}",
+                self.config.server_url,
+                response.status()
+            )))
+        }
+    }
+
     /// Shutdown update manager
     pub async fn shutdown(&self) -> CodexResult<()> {
         info!("Shutting down update manager");
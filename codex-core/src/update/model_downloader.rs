@@ -4,9 +4,9 @@
 //! checksum verification, and integrity validation.
 
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use reqwest::Client;
-use tokio::fs::File;
 use tokio::io::AsyncWriteExt;
 use futures_util::StreamExt;
 use indicatif::{ProgressBar, ProgressStyle};
@@ -14,19 +14,26 @@ use tracing::{info, warn, debug};
 
 use crate::{CodexError, CodexResult};
 use super::manifest::{ModelManifest, ModelRegistry};
+use super::registry::CatalogModel;
+use super::throttle::DownloadControl;
 use crate::ai::engine::GGUFEngine;
+use crate::ai::EmbeddingEngine;
+use crate::config::AiConfig;
+
+/// How long a mirror may take to answer the latency probe
+const MIRROR_PROBE_TIMEOUT: Duration = Duration::from_secs(5);
 
 /// Model download progress callback
 pub type ProgressCallback = Box<dyn Fn(DownloadProgress) + Send + Sync>;
 
 /// Download progress information
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize)]
 pub struct DownloadProgress {
     /// Bytes downloaded so far
     pub downloaded_bytes: u64,
     /// Total bytes to download
     pub total_bytes: u64,
-    /// Download speed in bytes per second
+    /// Current throughput in bytes per second
     pub speed_bps: u64,
     /// Estimated time remaining in seconds
     pub eta_seconds: u64,
@@ -37,12 +44,15 @@ pub struct DownloadProgress {
 }
 
 /// Download stages
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum DownloadStage {
     /// Initializing download
     Initializing,
     /// Downloading model file
     Downloading,
+    /// Download paused; the partial file is kept
+    Paused,
     /// Verifying checksum
     Verifying,
     /// Download completed successfully
@@ -58,6 +68,7 @@ pub struct ModelDownloader {
     progress_callback: Option<ProgressCallback>,
     chunk_size: usize,
     timeout: Duration,
+    control: Arc<DownloadControl>,
 }
 
 impl ModelDownloader {
@@ -75,6 +86,7 @@ impl ModelDownloader {
             progress_callback: None,
             chunk_size: 8192, // 8KB chunks
             timeout: Duration::from_secs(300), // 5 minute timeout
+            control: DownloadControl::global(),
         }
     }
 
@@ -90,6 +102,12 @@ impl ModelDownloader {
         self
     }
 
+    /// Use a pause switch and speed limit other than the global one
+    pub fn with_control(mut self, control: Arc<DownloadControl>) -> Self {
+        self.control = control;
+        self
+    }
+
     /// Set timeout for downloads
     pub fn with_timeout(mut self, timeout: Duration) -> Self {
         self.timeout = timeout;
@@ -102,7 +120,11 @@ impl ModelDownloader {
 
         // Ensure download directory exists
         tokio::fs::create_dir_all(&self.download_dir).await
-            .map_err(|e| CodexError::io(e))?;
+            .map_err(CodexError::io)?;
+
+        if manifest.is_multi_file() {
+            return self.download_artifact(manifest).await;
+        }
 
         // Get download URL and expected size
         let download_url = &manifest.download_url;
@@ -121,10 +143,13 @@ impl ModelDownloader {
             } else {
                 warn!("Existing model file is invalid, re-downloading");
                 tokio::fs::remove_file(&target_path).await
-                    .map_err(|e| CodexError::io(e))?;
+                    .map_err(CodexError::io)?;
             }
         }
 
+        // Fail before fetching anything if the model and its dependencies cannot fit
+        super::disk::ensure_space(&self.download_dir, manifest.total_size())?;
+
         // Start download process
         self.notify_progress(DownloadProgress {
             downloaded_bytes: 0,
@@ -135,10 +160,10 @@ impl ModelDownloader {
             stage: DownloadStage::Initializing,
         });
 
-        // Download the file
+        // Download the file, continuing a partial download if one was left behind
         let downloaded_path = self.download_file_with_progress(
             download_url,
-            &target_path,
+            &partial_path(&target_path),
             expected_size,
         ).await?;
 
@@ -155,7 +180,7 @@ impl ModelDownloader {
         if !self.verify_checksum(&downloaded_path, expected_checksum).await? {
             // Remove invalid file
             tokio::fs::remove_file(&downloaded_path).await
-                .map_err(|e| CodexError::io(e))?;
+                .map_err(CodexError::io)?;
             
             let error_msg = "Checksum verification failed";
             self.notify_progress(DownloadProgress {
@@ -170,6 +195,10 @@ impl ModelDownloader {
             return Err(CodexError::validation(error_msg));
         }
 
+        tokio::fs::rename(&downloaded_path, &target_path).await
+            .map_err(CodexError::io)?;
+        let downloaded_path = target_path;
+
         // Download dependencies (tokenizer, config files, etc.)
         for dependency in &manifest.dependencies {
             if dependency.required {
@@ -191,100 +220,309 @@ impl ModelDownloader {
         Ok(downloaded_path)
     }
 
-    /// Download a file with progress tracking
-    async fn download_file_with_progress(
-        &self,
-        url: &str,
-        target_path: &Path,
-        expected_size: u64,
-    ) -> CodexResult<PathBuf> {
-        info!("Downloading from: {}", url);
-        info!("Target path: {}", target_path.display());
+    /// Download an embedding model from the registry into `<download_dir>/<model_id>`
+    pub async fn download_embedding_model(&self, model_id: &str) -> CodexResult<PathBuf> {
+        let registry = ModelRegistry::default_registry();
+        let manifest = registry
+            .find_embedding_model(model_id)
+            .ok_or_else(|| CodexError::not_found(format!("Embedding model not in registry: {}", model_id)))?;
 
-        // Create the target file
-        let mut file = File::create(target_path).await
-            .map_err(|e| CodexError::io(e))?;
+        self.download_model(manifest).await
+    }
 
-        // Start HTTP request
-        let response = self.client
-            .get(url)
-            .timeout(self.timeout)
-            .send()
+    /// Download a model listed in the update server's registry
+    ///
+    /// The fastest responding of the entry's URLs is used.
+    pub async fn download_catalog_model(&self, model: &CatalogModel) -> CodexResult<PathBuf> {
+        info!("Starting download of model: {} {}", model.id, model.version);
+
+        tokio::fs::create_dir_all(&self.download_dir).await
+            .map_err(CodexError::io)?;
+
+        let target_path = self.download_dir.join(&model.file_name);
+        if target_path.exists() && self.verify_existing_file(&target_path, &model.sha256).await? {
+            info!("Model file already exists and is valid, skipping download");
+            return Ok(target_path);
+        }
+
+        super::disk::ensure_space(&self.download_dir, model.size_bytes)?;
+        let url = self
+            .select_mirror(&model.download_urls)
             .await
-            .map_err(|e| CodexError::network(e))?;
+            .ok_or_else(|| CodexError::update(format!("No reachable download URL for {}", model.id)))?;
 
-        if !response.status().is_success() {
-            return Err(CodexError::internal(
-                format!("HTTP request failed: {}", response.status())
-            ));
+        let partial_path = partial_path(&target_path);
+        self.download_file_with_progress(&url, &partial_path, model.size_bytes).await?;
+
+        if !self.verify_checksum(&partial_path, &model.sha256).await? {
+            tokio::fs::remove_file(&partial_path).await
+                .map_err(CodexError::io)?;
+            return Err(CodexError::validation(format!("Checksum verification failed for {}", model.id)));
         }
 
-        // Get content length
-        let content_length = response.content_length().unwrap_or(expected_size);
-        
-        // Create progress tracking
-        let mut downloaded = 0u64;
-        let mut last_update = Instant::now();
-        let mut speed_samples = Vec::new();
-        
+        tokio::fs::rename(&partial_path, &target_path).await
+            .map_err(CodexError::io)?;
+        info!("Model download completed successfully: {}", target_path.display());
+        Ok(target_path)
+    }
+
+    /// Pick the URL whose server answers a HEAD request fastest
+    ///
+    /// Unreachable mirrors are skipped; `None` when none answers.
+    pub async fn select_mirror(&self, urls: &[String]) -> Option<String> {
+        if urls.len() == 1 {
+            return urls.first().cloned();
+        }
+
+        let probes = urls.iter().map(|url| async move {
+            let started = Instant::now();
+            let response = self.client.head(url).timeout(MIRROR_PROBE_TIMEOUT).send().await.ok()?;
+            (response.status().is_success() || response.status().is_redirection())
+                .then(|| (started.elapsed(), url.clone()))
+        });
+
+        futures_util::future::join_all(probes)
+            .await
+            .into_iter()
+            .flatten()
+            .min_by_key(|(latency, _)| *latency)
+            .map(|(latency, url)| {
+                debug!("Selected mirror {} ({} ms)", url, latency.as_millis());
+                url
+            })
+    }
+
+    /// Download every file of a multi-file artifact into the model's directory
+    ///
+    /// Files already present with a valid checksum are kept, so an
+    /// interrupted download resumes at the first missing file.
+    async fn download_artifact(&self, manifest: &ModelManifest) -> CodexResult<PathBuf> {
+        let model_dir = manifest.get_local_path(&self.download_dir);
+        let total_size = manifest.total_size();
+
+        let missing_bytes = manifest
+            .files
+            .iter()
+            .filter(|file| {
+                std::fs::metadata(model_dir.join(&file.path))
+                    .map(|m| m.len() != file.file_size)
+                    .unwrap_or(true)
+            })
+            .map(|file| file.file_size)
+            .sum();
+        super::disk::ensure_space(&self.download_dir, missing_bytes)?;
+
         self.notify_progress(DownloadProgress {
             downloaded_bytes: 0,
-            total_bytes: content_length,
+            total_bytes: total_size,
             speed_bps: 0,
             eta_seconds: 0,
             progress: 0.0,
-            stage: DownloadStage::Downloading,
+            stage: DownloadStage::Initializing,
         });
 
-        // Download in chunks
-        let mut stream = response.bytes_stream();
-        while let Some(chunk_result) = stream.next().await {
-            let chunk = chunk_result
-                .map_err(|e| CodexError::network(e))?;
-            
-            // Write chunk to file
-            file.write_all(&chunk).await
-                .map_err(|e| CodexError::io(e))?;
-            
-            downloaded += chunk.len() as u64;
-            
-            // Update progress periodically
-            let now = Instant::now();
-            if now.duration_since(last_update) >= Duration::from_millis(100) {
-                // Calculate speed
-                let speed_bps = self.calculate_speed(&mut speed_samples, downloaded, now);
-                
-                // Calculate ETA
-                let remaining_bytes = content_length.saturating_sub(downloaded);
-                let eta_seconds = if speed_bps > 0 {
-                    remaining_bytes / speed_bps
-                } else {
-                    0
-                };
-                
-                let progress = if content_length > 0 {
-                    downloaded as f64 / content_length as f64
-                } else {
-                    0.0
-                };
-                
+        for file in &manifest.files {
+            let target_path = model_dir.join(&file.path);
+            if let Some(parent) = target_path.parent() {
+                tokio::fs::create_dir_all(parent).await
+                    .map_err(CodexError::io)?;
+            }
+
+            if target_path.exists() {
+                if self.verify_existing_file(&target_path, &file.sha256_checksum).await? {
+                    debug!("Artifact file {} already present", file.path);
+                    continue;
+                }
+                warn!("Artifact file {} is invalid, re-downloading", file.path);
+                tokio::fs::remove_file(&target_path).await
+                    .map_err(CodexError::io)?;
+            }
+
+            // Download beside the target so a half-written file is never loaded
+            let partial_path = partial_path(&target_path);
+
+            self.download_file_with_progress(&file.download_url, &partial_path, file.file_size).await?;
+
+            self.notify_progress(DownloadProgress {
+                downloaded_bytes: file.file_size,
+                total_bytes: file.file_size,
+                speed_bps: 0,
+                eta_seconds: 0,
+                progress: 1.0,
+                stage: DownloadStage::Verifying,
+            });
+
+            if !self.verify_checksum(&partial_path, &file.sha256_checksum).await? {
+                tokio::fs::remove_file(&partial_path).await
+                    .map_err(CodexError::io)?;
+
+                let error_msg = format!("Checksum verification failed for {}", file.path);
                 self.notify_progress(DownloadProgress {
-                    downloaded_bytes: downloaded,
-                    total_bytes: content_length,
-                    speed_bps,
-                    eta_seconds,
-                    progress,
-                    stage: DownloadStage::Downloading,
+                    downloaded_bytes: 0,
+                    total_bytes: total_size,
+                    speed_bps: 0,
+                    eta_seconds: 0,
+                    progress: 0.0,
+                    stage: DownloadStage::Failed(error_msg.clone()),
                 });
+                return Err(CodexError::validation(error_msg));
+            }
+
+            tokio::fs::rename(&partial_path, &target_path).await
+                .map_err(CodexError::io)?;
+        }
+
+        self.notify_progress(DownloadProgress {
+            downloaded_bytes: total_size,
+            total_bytes: total_size,
+            speed_bps: 0,
+            eta_seconds: 0,
+            progress: 1.0,
+            stage: DownloadStage::Completed,
+        });
+
+        info!("Model {} downloaded to {}", manifest.name, model_dir.display());
+        Ok(model_dir)
+    }
+
+    /// Download a file with progress tracking
+    ///
+    /// An existing file at `target_path` is treated as a partial download
+    /// and continued with a range request. Between chunks the loop honours
+    /// the speed limit and, when paused, stops and waits to be resumed.
+    async fn download_file_with_progress(
+        &self,
+        url: &str,
+        target_path: &Path,
+        expected_size: u64,
+    ) -> CodexResult<PathBuf> {
+        info!("Downloading from: {}", url);
+        info!("Target path: {}", target_path.display());
+
+        let mut downloaded = tokio::fs::metadata(target_path).await.map(|m| m.len()).unwrap_or(0);
+        let mut last_update = Instant::now();
+        let mut speed_samples = Vec::new();
+
+        loop {
+            self.control.wait_while_paused().await;
+
+            // Start HTTP request, continuing after the bytes already on disk
+            let mut request = self.client.get(url).timeout(self.timeout);
+            if downloaded > 0 {
+                info!("Resuming download at byte {}", downloaded);
+                request = request.header(reqwest::header::RANGE, format!("bytes={}-", downloaded));
+            }
+            let response = request.send().await
+                .map_err(CodexError::network)?;
+
+            // The partial file already holds everything
+            if downloaded > 0 && response.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+                break;
+            }
+
+            if !response.status().is_success() {
+                return Err(CodexError::internal(
+                    format!("HTTP request failed: {}", response.status())
+                ));
+            }
+
+            // Servers without range support send the whole file again
+            let resumed = response.status() == reqwest::StatusCode::PARTIAL_CONTENT;
+            if !resumed {
+                downloaded = 0;
+            }
+            let mut file = tokio::fs::OpenOptions::new()
+                .create(true)
+                .write(true)
+                .append(resumed)
+                .truncate(!resumed)
+                .open(target_path)
+                .await
+                .map_err(CodexError::io)?;
+
+            // Get content length
+            let content_length = response.content_length()
+                .map(|remaining| remaining + downloaded)
+                .unwrap_or(expected_size);
+
+            self.notify_progress(DownloadProgress {
+                downloaded_bytes: downloaded,
+                total_bytes: content_length,
+                speed_bps: 0,
+                eta_seconds: 0,
+                progress: if content_length > 0 { downloaded as f64 / content_length as f64 } else { 0.0 },
+                stage: DownloadStage::Downloading,
+            });
+
+            // Download in chunks
+            let mut paused = false;
+            let mut stream = response.bytes_stream();
+            while let Some(chunk_result) = stream.next().await {
+                let chunk = chunk_result
+                    .map_err(CodexError::network)?;
+
+                self.control.throttle(chunk.len() as u64).await;
+
+                // Write chunk to file
+                file.write_all(&chunk).await
+                    .map_err(CodexError::io)?;
+                
+                downloaded += chunk.len() as u64;
                 
-                last_update = now;
+                // Update progress periodically
+                let now = Instant::now();
+                if now.duration_since(last_update) >= Duration::from_millis(100) {
+                    // Calculate speed
+                    let speed_bps = self.calculate_speed(&mut speed_samples, downloaded, now);
+                    
+                    // Calculate ETA
+                    let remaining_bytes = content_length.saturating_sub(downloaded);
+                    let eta_seconds = remaining_bytes.checked_div(speed_bps).unwrap_or(0);
+                    
+                    let progress = if content_length > 0 {
+                        downloaded as f64 / content_length as f64
+                    } else {
+                        0.0
+                    };
+                    
+                    self.notify_progress(DownloadProgress {
+                        downloaded_bytes: downloaded,
+                        total_bytes: content_length,
+                        speed_bps,
+                        eta_seconds,
+                        progress,
+                        stage: DownloadStage::Downloading,
+                    });
+                    
+                    last_update = now;
+                }
+
+                if self.control.is_paused() {
+                    paused = true;
+                    break;
+                }
             }
+
+            // Ensure all data is written
+            file.flush().await
+                .map_err(CodexError::io)?;
+
+            if !paused {
+                break;
+            }
+
+            info!("Download paused at {} bytes", downloaded);
+            speed_samples.clear();
+            self.notify_progress(DownloadProgress {
+                downloaded_bytes: downloaded,
+                total_bytes: content_length,
+                speed_bps: 0,
+                eta_seconds: 0,
+                progress: if content_length > 0 { downloaded as f64 / content_length as f64 } else { 0.0 },
+                stage: DownloadStage::Paused,
+            });
         }
 
-        // Ensure all data is written
-        file.flush().await
-            .map_err(|e| CodexError::io(e))?;
-        
         debug!("Download completed: {} bytes", downloaded);
         Ok(target_path.to_path_buf())
     }
@@ -333,7 +571,7 @@ impl ModelDownloader {
             } else {
                 warn!("Dependency {} exists but is invalid, re-downloading", dependency.name);
                 tokio::fs::remove_file(&target_path).await
-                    .map_err(|e| CodexError::io(e))?;
+                    .map_err(CodexError::io)?;
             }
         }
         
@@ -345,7 +583,7 @@ impl ModelDownloader {
         
         if !self.verify_checksum(&target_path, &dependency.sha256_checksum).await? {
             tokio::fs::remove_file(&target_path).await
-                .map_err(|e| CodexError::io(e))?;
+                .map_err(CodexError::io)?;
             return Err(CodexError::validation(
                 format!("Dependency {} checksum verification failed", dependency.name)
             ));
@@ -376,7 +614,7 @@ impl ModelDownloader {
     async fn verify_existing_file(&self, file_path: &Path, expected_checksum: &str) -> CodexResult<bool> {
         // Check file size first (quick check)
         let metadata = tokio::fs::metadata(file_path).await
-            .map_err(|e| CodexError::io(e))?;
+            .map_err(CodexError::io)?;
         
         if metadata.len() == 0 {
             return Ok(false);
@@ -402,7 +640,7 @@ impl ModelDownloader {
             .timeout(self.timeout)
             .send()
             .await
-            .map_err(|e| CodexError::network(e))?;
+            .map_err(CodexError::network)?;
         
         if !response.status().is_success() {
             return Err(CodexError::internal(
@@ -411,7 +649,7 @@ impl ModelDownloader {
         }
         
         let registry_json = response.text().await
-            .map_err(|e| CodexError::network(e))?;
+            .map_err(CodexError::network)?;
         
         ModelRegistry::from_json(&registry_json)
     }
@@ -419,6 +657,16 @@ impl ModelDownloader {
     /// Check if a model is already downloaded and valid
     pub async fn is_model_downloaded(&self, manifest: &ModelManifest) -> CodexResult<bool> {
         let target_path = manifest.get_local_path(&self.download_dir);
+
+        if manifest.is_multi_file() {
+            for file in &manifest.files {
+                let file_path = target_path.join(&file.path);
+                if !file_path.exists() || !self.verify_existing_file(&file_path, &file.sha256_checksum).await? {
+                    return Ok(false);
+                }
+            }
+            return Ok(true);
+        }
         
         if !target_path.exists() {
             return Ok(false);
@@ -431,10 +679,19 @@ impl ModelDownloader {
     /// Remove a downloaded model
     pub async fn remove_model(&self, manifest: &ModelManifest) -> CodexResult<()> {
         let target_path = manifest.get_local_path(&self.download_dir);
+
+        if manifest.is_multi_file() {
+            if target_path.exists() {
+                tokio::fs::remove_dir_all(&target_path).await
+                    .map_err(CodexError::io)?;
+                info!("Removed model: {}", target_path.display());
+            }
+            return Ok(());
+        }
         
         if target_path.exists() {
             tokio::fs::remove_file(&target_path).await
-                .map_err(|e| CodexError::io(e))?;
+                .map_err(CodexError::io)?;
             info!("Removed model: {}", target_path.display());
         }
         
@@ -443,7 +700,7 @@ impl ModelDownloader {
             let dep_path = self.download_dir.join(&dependency.name);
             if dep_path.exists() {
                 tokio::fs::remove_file(&dep_path).await
-                    .map_err(|e| CodexError::io(e))?;
+                    .map_err(CodexError::io)?;
                 info!("Removed dependency: {}", dep_path.display());
             }
         }
@@ -469,6 +726,30 @@ impl ModelDownloader {
     }
 }
 
+/// Where a file is downloaded before its checksum is verified
+///
+/// Kept across pauses and restarts so the download can resume.
+fn partial_path(target_path: &Path) -> PathBuf {
+    let mut partial_name = target_path.file_name().unwrap_or_default().to_os_string();
+    partial_name.push(".part");
+    target_path.with_file_name(partial_name)
+}
+
+/// Download the configured embedding model when it is missing and load it
+///
+/// Returns whether anything was downloaded. Callers decide whether the
+/// network may be used; see `AppConfig::offline_mode`.
+pub async fn ensure_embedding_model(config: &AiConfig, embeddings: &EmbeddingEngine) -> CodexResult<bool> {
+    if embeddings.is_model_loaded() {
+        return Ok(false);
+    }
+
+    let downloader = ModelDownloader::new(config.models_dir.clone());
+    let model_dir = downloader.download_embedding_model(&config.embedding.model).await?;
+    embeddings.reload(&model_dir)?;
+    Ok(true)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -517,4 +798,35 @@ mod tests {
         let is_invalid = downloader.verify_checksum(&test_file, &wrong_checksum).await.unwrap();
         assert!(!is_invalid);
     }
+
+    #[tokio::test]
+    async fn test_multi_file_artifact_layout() {
+        let temp_dir = tempdir().unwrap();
+        let downloader = ModelDownloader::new(temp_dir.path().to_path_buf());
+        let mut manifest = crate::update::manifest::ModelManifest::all_minilm_l6_v2();
+
+        // Lay the files out as a finished download would and point the manifest at them
+        let model_dir = manifest.get_local_path(temp_dir.path());
+        fs::create_dir_all(&model_dir).unwrap();
+        for file in &mut manifest.files {
+            let path = model_dir.join(&file.path);
+            fs::write(&path, file.path.as_bytes()).unwrap();
+            file.file_size = file.path.len() as u64;
+            file.sha256_checksum = GGUFEngine::calculate_checksum(&path).await.unwrap();
+        }
+
+        assert!(manifest.artifact_present(temp_dir.path()));
+        assert!(downloader.is_model_downloaded(&manifest).await.unwrap());
+        // Everything is already in place, so nothing is fetched
+        assert_eq!(downloader.download_model(&manifest).await.unwrap(), model_dir);
+
+        fs::write(model_dir.join("tokenizer.json"), b"corrupt").unwrap();
+        assert!(!downloader.is_model_downloaded(&manifest).await.unwrap());
+
+        downloader.remove_model(&manifest).await.unwrap();
+        assert!(!model_dir.exists());
+
+        let missing = downloader.download_embedding_model("no-such-model").await;
+        assert!(matches!(missing, Err(CodexError::NotFound(_))));
+    }
 }
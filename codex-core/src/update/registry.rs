@@ -0,0 +1,339 @@
+//! Model registry (`models.json`) served by the update server
+//!
+//! The registry lists downloadable models. It is validated against a
+//! bundled JSON schema, entries from the network must carry an Ed25519
+//! signature, and the last good copy is cached next to the models so the
+//! list still works offline. The registry compiled into the app is the
+//! final fallback.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::warn;
+
+use crate::{CodexError, CodexResult};
+
+/// File name of the registry on the update server and in the models directory
+pub const MODEL_REGISTRY_FILE: &str = "models.json";
+
+/// JSON schema every registry must satisfy
+pub const MODEL_REGISTRY_SCHEMA: &str = include_str!("models.schema.json");
+
+/// Hex Ed25519 key the registry entries on the update server are signed with,
+/// the default of `update.model_registry_public_key`
+pub const VENDOR_REGISTRY_PUBLIC_KEY: &str = "75e4ceb19a4294c0df066257d4a0185eee4703eedc1c1d54e239b72c9e51093b";
+
+/// Registry compiled into the app, used when neither the server nor a cached copy is available
+pub const BUNDLED_MODEL_REGISTRY: &str = include_str!("models.json");
+
+/// Where a registry was loaded from
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CatalogSource {
+    Remote,
+    Cached,
+    #[default]
+    Bundled,
+}
+
+/// Parsed `models.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelCatalog {
+    pub schema_version: u32,
+    pub updated_at: String,
+    pub models: Vec<CatalogModel>,
+    /// Where this copy came from (not part of the file)
+    #[serde(default, skip_serializing)]
+    pub source: CatalogSource,
+    /// Entries dropped because they failed validation or signature checks
+    #[serde(default, skip_serializing)]
+    pub rejected: Vec<String>,
+}
+
+/// One downloadable model
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CatalogModel {
+    pub id: String,
+    pub display_name: String,
+    pub version: String,
+    /// File name inside the models directory
+    pub file_name: String,
+    pub size_bytes: u64,
+    pub quantization: String,
+    pub sha256: String,
+    /// Oldest app version able to run the model
+    pub min_app_version: String,
+    pub license: String,
+    /// Primary download URL followed by mirrors
+    pub download_urls: Vec<String>,
+    /// Hex Ed25519 signature over [`CatalogModel::signing_payload`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    /// Newer version offered by the server while this one is pinned
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pinned_update: Option<String>,
+}
+
+impl CatalogModel {
+    /// Bytes covered by the signature
+    ///
+    /// The URLs are left out so mirrors can be added without re-signing;
+    /// the checksum already pins the file contents.
+    pub fn signing_payload(&self) -> String {
+        format!("{}\n{}\n{}\n{}\n{}", self.id, self.version, self.file_name, self.size_bytes, self.sha256.to_lowercase())
+    }
+
+    /// Whether the signature verifies against the hex Ed25519 `public_key`
+    pub fn verify_signature(&self, public_key: &str) -> bool {
+        let (Some(signature), Ok(key)) = (self.signature.as_deref(), hex::decode(public_key)) else {
+            return false;
+        };
+        let Ok(signature) = hex::decode(signature) else {
+            return false;
+        };
+
+        ring::signature::UnparsedPublicKey::new(&ring::signature::ED25519, key)
+            .verify(self.signing_payload().as_bytes(), &signature)
+            .is_ok()
+    }
+}
+
+impl ModelCatalog {
+    /// The registry compiled into the app
+    pub fn bundled() -> Self {
+        Self::parse(BUNDLED_MODEL_REGISTRY, None, CatalogSource::Bundled)
+            .expect("bundled model registry is valid")
+    }
+
+    /// Parse and validate a registry
+    ///
+    /// A registry whose top level violates the schema is rejected outright;
+    /// individual entries that violate it, need a newer app, or (when
+    /// `public_key` is given) are unsigned or badly signed are dropped and
+    /// listed in `rejected`.
+    pub fn parse(json: &str, public_key: Option<&str>, source: CatalogSource) -> CodexResult<Self> {
+        let schema: Value = serde_json::from_str(MODEL_REGISTRY_SCHEMA)?;
+        let value: Value = serde_json::from_str(json)
+            .map_err(|e| CodexError::validation(format!("Model registry is not valid JSON: {}", e)))?;
+
+        let mut errors = Vec::new();
+        check_schema(&value, &schema, "$", &mut errors);
+        if !errors.is_empty() {
+            return Err(CodexError::validation(format!("Invalid model registry: {}", errors.join("; "))));
+        }
+
+        let mut models = Vec::new();
+        let mut rejected = Vec::new();
+        for (i, entry) in value["models"].as_array().into_iter().flatten().enumerate() {
+            let label = entry["id"].as_str().map(str::to_string).unwrap_or_else(|| format!("models[{}]", i));
+
+            let mut entry_errors = Vec::new();
+            check_schema(entry, &schema["$defs"]["model"], &label, &mut entry_errors);
+            if !entry_errors.is_empty() {
+                rejected.push(format!("{}: {}", label, entry_errors.join("; ")));
+                continue;
+            }
+
+            let model: CatalogModel = serde_json::from_value(entry.clone())?;
+            if let Some(key) = public_key {
+                if !model.verify_signature(key) {
+                    rejected.push(format!("{}: missing or invalid signature", label));
+                    continue;
+                }
+            }
+            if !version_at_least(env!("CARGO_PKG_VERSION"), &model.min_app_version) {
+                rejected.push(format!("{}: requires app version {}", label, model.min_app_version));
+                continue;
+            }
+            models.push(model);
+        }
+
+        for reason in &rejected {
+            warn!("Rejected model registry entry {}", reason);
+        }
+
+        Ok(Self {
+            schema_version: value["schema_version"].as_u64().unwrap_or(1) as u32,
+            updated_at: value["updated_at"].as_str().unwrap_or_default().to_string(),
+            models,
+            source,
+            rejected,
+        })
+    }
+
+    /// Read a cached registry from `models_dir`
+    pub fn load_cached(models_dir: &Path, public_key: Option<&str>) -> CodexResult<Self> {
+        let json = std::fs::read_to_string(models_dir.join(MODEL_REGISTRY_FILE))?;
+        Self::parse(&json, public_key, CatalogSource::Cached)
+    }
+
+    /// Write the registry to `models_dir` for offline use
+    pub fn save_cached(&self, models_dir: &Path) -> CodexResult<()> {
+        std::fs::create_dir_all(models_dir)?;
+        std::fs::write(models_dir.join(MODEL_REGISTRY_FILE), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Keep the `previous` version of every model it already listed
+    ///
+    /// Used when model versions are pinned: a changed version or checksum
+    /// for a known id is not adopted, only reported in `pinned_update`.
+    pub fn apply_pins(&mut self, previous: &ModelCatalog) {
+        let known: HashMap<&str, &CatalogModel> = previous.models.iter().map(|m| (m.id.as_str(), m)).collect();
+        for model in &mut self.models {
+            if let Some(&pinned) = known.get(model.id.as_str()) {
+                if pinned.version != model.version || !pinned.sha256.eq_ignore_ascii_case(&model.sha256) {
+                    let offered = model.version.clone();
+                    *model = pinned.clone();
+                    model.pinned_update = Some(offered);
+                }
+            }
+        }
+    }
+
+    /// Find a model by id
+    pub fn find(&self, id: &str) -> Option<&CatalogModel> {
+        self.models.iter().find(|m| m.id == id)
+    }
+}
+
+/// Whether `version` is at least `required` (numeric `major.minor.patch`)
+fn version_at_least(version: &str, required: &str) -> bool {
+    let parse = |v: &str| v.split('.').map(|part| part.parse::<u64>().unwrap_or(0)).collect::<Vec<_>>();
+    parse(version) >= parse(required)
+}
+
+/// Check `value` against the subset of JSON schema the registry schema uses:
+/// `type`, `required`, `properties`, `items`, `pattern`, `minLength`,
+/// `minItems` and `minimum`
+fn check_schema(value: &Value, schema: &Value, path: &str, errors: &mut Vec<String>) {
+    if let Some(expected) = schema["type"].as_str() {
+        let matches = match expected {
+            "object" => value.is_object(),
+            "array" => value.is_array(),
+            "string" => value.is_string(),
+            "integer" => value.is_u64() || value.is_i64(),
+            "number" => value.is_number(),
+            "boolean" => value.is_boolean(),
+            _ => true,
+        };
+        if !matches {
+            errors.push(format!("{} must be of type {}", path, expected));
+            return;
+        }
+    }
+
+    if let Some(object) = value.as_object() {
+        for field in schema["required"].as_array().into_iter().flatten().filter_map(Value::as_str) {
+            if !object.contains_key(field) {
+                errors.push(format!("{}.{} is required", path, field));
+            }
+        }
+        if let Some(properties) = schema["properties"].as_object() {
+            for (field, field_schema) in properties {
+                if let Some(field_value) = object.get(field) {
+                    check_schema(field_value, field_schema, &format!("{}.{}", path, field), errors);
+                }
+            }
+        }
+    }
+
+    if let Some(items) = value.as_array() {
+        if let Some(min) = schema["minItems"].as_u64() {
+            if (items.len() as u64) < min {
+                errors.push(format!("{} needs at least {} items", path, min));
+            }
+        }
+        if schema.get("items").is_some() {
+            for (i, item) in items.iter().enumerate() {
+                check_schema(item, &schema["items"], &format!("{}[{}]", path, i), errors);
+            }
+        }
+    }
+
+    if let Some(text) = value.as_str() {
+        if let Some(min) = schema["minLength"].as_u64() {
+            if (text.chars().count() as u64) < min {
+                errors.push(format!("{} is too short", path));
+            }
+        }
+        if let Some(pattern) = schema["pattern"].as_str() {
+            if !regex::Regex::new(pattern).map(|re| re.is_match(text)).unwrap_or(false) {
+                errors.push(format!("{} does not match {}", path, pattern));
+            }
+        }
+    }
+
+    if let (Some(number), Some(min)) = (value.as_f64(), schema["minimum"].as_f64()) {
+        if number < min {
+            errors.push(format!("{} must be at least {}", path, min));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+
+    fn signed_registry(key: &Ed25519KeyPair, version: &str) -> String {
+        let mut registry: Value = serde_json::from_str(BUNDLED_MODEL_REGISTRY).unwrap();
+        let entry = &mut registry["models"][0];
+        entry["version"] = Value::from(version);
+        let model: CatalogModel = serde_json::from_value(entry.clone()).unwrap();
+        entry["signature"] = Value::from(hex::encode(key.sign(model.signing_payload().as_bytes())));
+
+        // An unsigned entry and one that breaks the schema
+        let mut unsigned = entry.clone();
+        unsigned["id"] = Value::from("unsigned-model");
+        unsigned.as_object_mut().unwrap().remove("signature");
+        let mut invalid = entry.clone();
+        invalid["id"] = Value::from("bad-checksum");
+        invalid["sha256"] = Value::from("not-a-checksum");
+        registry["models"].as_array_mut().unwrap().extend([unsigned, invalid]);
+        registry.to_string()
+    }
+
+    #[test]
+    fn test_registry_validation_signatures_and_pinning() {
+        let bundled = ModelCatalog::bundled();
+        assert_eq!(bundled.source, CatalogSource::Bundled);
+        assert!(bundled.find("mistral-7b-instruct-q4_k").is_some());
+
+        let rng = ring::rand::SystemRandom::new();
+        let key = Ed25519KeyPair::from_pkcs8(Ed25519KeyPair::generate_pkcs8(&rng).unwrap().as_ref()).unwrap();
+        let public_key = hex::encode(key.public_key().as_ref());
+
+        let remote = ModelCatalog::parse(&signed_registry(&key, "v0.2"), Some(&public_key), CatalogSource::Remote).unwrap();
+        assert_eq!(remote.models.len(), 1);
+        assert_eq!(remote.rejected.len(), 2);
+        assert!(remote.rejected.iter().any(|r| r.starts_with("unsigned-model")));
+        assert!(remote.rejected.iter().any(|r| r.starts_with("bad-checksum")));
+
+        // A signature made by another key does not verify
+        let other = hex::encode([7u8; 32]);
+        assert_eq!(hex::decode(VENDOR_REGISTRY_PUBLIC_KEY).unwrap().len(), 32);
+        assert!(ModelCatalog::parse(&signed_registry(&key, "v0.2"), Some(&other), CatalogSource::Remote).unwrap().models.is_empty());
+
+        assert!(ModelCatalog::parse(r#"{"models": []}"#, None, CatalogSource::Remote).is_err());
+
+        // Pinned: the known version stays, the offered one is reported
+        let mut pinned = remote.clone();
+        pinned.apply_pins(&bundled);
+        let model = pinned.find("mistral-7b-instruct-q4_k").unwrap();
+        assert_eq!(model.version, "v0.1");
+        assert_eq!(model.pinned_update.as_deref(), Some("v0.2"));
+
+        let dir = tempfile::tempdir().unwrap();
+        pinned.save_cached(dir.path()).unwrap();
+        let cached = ModelCatalog::load_cached(dir.path(), None).unwrap();
+        assert_eq!(cached.source, CatalogSource::Cached);
+        assert_eq!(cached.models, pinned.models);
+
+        assert!(version_at_least("0.1.0", "0.1.0"));
+        assert!(version_at_least("0.10.0", "0.9.3"));
+        assert!(!version_at_least("0.1.0", "1.0.0"));
+    }
+}
@@ -0,0 +1,157 @@
+//! Pause/resume and bandwidth limiting shared by all downloads
+//!
+//! Every model and app download draws from one token bucket, so the cap in
+//! `NetworkConfig::max_download_bytes_per_sec` applies to their combined
+//! throughput. Pausing stops the download loops between chunks; they pick
+//! up from the partial file when resumed.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use once_cell::sync::Lazy;
+use tokio::sync::{watch, Mutex};
+
+static GLOBAL_CONTROL: Lazy<Arc<DownloadControl>> = Lazy::new(|| Arc::new(DownloadControl::new(None)));
+
+/// Shared pause switch and speed limit for downloads
+#[derive(Debug)]
+pub struct DownloadControl {
+    paused: watch::Sender<bool>,
+    /// Bytes per second, 0 when unlimited
+    limit: AtomicU64,
+    bucket: Mutex<TokenBucket>,
+}
+
+impl DownloadControl {
+    /// Create a control with an optional limit in bytes per second
+    pub fn new(max_bytes_per_sec: Option<u64>) -> Self {
+        Self {
+            paused: watch::channel(false).0,
+            limit: AtomicU64::new(max_bytes_per_sec.unwrap_or(0)),
+            bucket: Mutex::new(TokenBucket::new()),
+        }
+    }
+
+    /// The control used by downloads unless given their own
+    pub fn global() -> Arc<Self> {
+        Arc::clone(&GLOBAL_CONTROL)
+    }
+
+    /// Change the speed limit; takes effect on the next chunk
+    pub fn set_speed_limit(&self, max_bytes_per_sec: Option<u64>) {
+        self.limit.store(max_bytes_per_sec.unwrap_or(0), Ordering::Relaxed);
+    }
+
+    /// Current speed limit in bytes per second
+    pub fn speed_limit(&self) -> Option<u64> {
+        match self.limit.load(Ordering::Relaxed) {
+            0 => None,
+            limit => Some(limit),
+        }
+    }
+
+    /// Pause all downloads after their current chunk
+    pub fn pause(&self) {
+        self.paused.send_replace(true);
+    }
+
+    /// Resume paused downloads
+    pub fn resume(&self) {
+        self.paused.send_replace(false);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.paused.borrow()
+    }
+
+    /// Wait until downloads are no longer paused
+    pub async fn wait_while_paused(&self) {
+        let mut paused = self.paused.subscribe();
+        // The sender lives as long as `self`, so this cannot fail
+        let _ = paused.wait_for(|paused| !paused).await;
+    }
+
+    /// Wait until `bytes` more may be transferred under the speed limit
+    pub async fn throttle(&self, bytes: u64) {
+        let Some(rate) = self.speed_limit() else {
+            return;
+        };
+        let delay = self.bucket.lock().await.take(bytes, rate, Instant::now());
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+/// Token bucket holding up to one second of transfer at the current rate
+#[derive(Debug)]
+struct TokenBucket {
+    /// Available bytes; negative when chunks were taken on credit
+    tokens: f64,
+    refilled_at: Option<Instant>,
+}
+
+impl TokenBucket {
+    fn new() -> Self {
+        Self { tokens: 0.0, refilled_at: None }
+    }
+
+    /// Take `bytes` and return how long to wait before sending them
+    ///
+    /// Chunks larger than the bucket are allowed and paid back by waiting,
+    /// so any chunk size works with any limit.
+    fn take(&mut self, bytes: u64, rate: u64, now: Instant) -> Duration {
+        let rate = rate as f64;
+        let elapsed = self.refilled_at.map(|at| now.duration_since(at).as_secs_f64()).unwrap_or(1.0);
+        self.tokens = (self.tokens + elapsed * rate).min(rate);
+        self.refilled_at = Some(now);
+
+        self.tokens -= bytes as f64;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / rate)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket_paces_to_rate() {
+        let mut bucket = TokenBucket::new();
+        let start = Instant::now();
+
+        // A full second of burst is available up front
+        assert_eq!(bucket.take(1000, 1000, start), Duration::ZERO);
+        // Beyond that the caller waits for the deficit
+        assert_eq!(bucket.take(500, 1000, start), Duration::from_millis(500));
+        // Half a second later the debt is paid and nothing more is owed
+        assert_eq!(bucket.take(0, 1000, start + Duration::from_millis(500)), Duration::ZERO);
+        // Idle time never banks more than one second of tokens
+        assert_eq!(bucket.take(1500, 1000, start + Duration::from_secs(60)), Duration::from_millis(500));
+    }
+
+    #[tokio::test]
+    async fn test_pause_and_resume() {
+        let control = Arc::new(DownloadControl::new(Some(1024)));
+        assert_eq!(control.speed_limit(), Some(1024));
+        control.set_speed_limit(None);
+        assert_eq!(control.speed_limit(), None);
+
+        control.pause();
+        assert!(control.is_paused());
+        let waiter = tokio::spawn({
+            let control = Arc::clone(&control);
+            async move { control.wait_while_paused().await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiter.is_finished());
+
+        control.resume();
+        tokio::time::timeout(Duration::from_secs(1), waiter).await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,230 @@
+//! Cache warm-up after startup
+//!
+//! Right after launch the first search pays for cold caches: vector and
+//! document pages not yet in SQLite's page cache, statements not yet
+//! prepared on the pool's connections, and embedding and language models
+//! that have never run. [`WarmUp`] does that work in the background once
+//! the core is ready. Searches work meanwhile, just slower, and shutdown
+//! cancels it. The language model reports its own state through
+//! [`AiEngine::model_warm_up_state`], so the UI can show it warming up.
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+
+use crate::ai::AiEngine;
+use crate::config::WarmUpConfig;
+use crate::db::{DatabaseManager, DocumentQueries, EmbeddingQueries, SearchFilter, SearchQueries};
+use crate::CodexResult;
+
+/// Text embedded to load the tokenizer and model weights
+const TOKENIZER_WARM_UP_TEXT: &str = "Warming up the embedding model.";
+
+/// A step of the warm-up, in the order they run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WarmUpStage {
+    /// Read the persisted vectors semantic search scans
+    Vectors,
+    /// Read the metadata of the most-viewed documents
+    Documents,
+    /// Prepare the search statements on every pooled connection
+    Statements,
+    /// Embed a short text once
+    Tokenizer,
+    /// Warm up the language model, see [`AiEngine::warm_up_model`]
+    Model,
+}
+
+/// Progress of the warm-up
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum WarmUpStatus {
+    /// Not started yet
+    Pending,
+    /// Turned off in the configuration
+    Disabled,
+    /// Running `stage`, with `completed` of `total` stages done
+    Running { stage: WarmUpStage, completed: usize, total: usize },
+    /// Finished; stages that failed were skipped and logged
+    Complete { elapsed_ms: u64 },
+    /// Stopped by shutdown before finishing
+    Cancelled,
+}
+
+impl WarmUpStatus {
+    /// Whether the warm-up will make no further progress
+    pub fn is_finished(&self) -> bool {
+        !matches!(self, WarmUpStatus::Pending | WarmUpStatus::Running { .. })
+    }
+}
+
+/// Background warm-up of the search caches
+#[derive(Debug)]
+pub struct WarmUp {
+    status: watch::Sender<WarmUpStatus>,
+    cancel: CancellationToken,
+}
+
+impl Default for WarmUp {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WarmUp {
+    pub fn new() -> Self {
+        Self {
+            status: watch::channel(WarmUpStatus::Pending).0,
+            cancel: CancellationToken::new(),
+        }
+    }
+
+    /// Current progress
+    pub fn status(&self) -> WarmUpStatus {
+        self.status.borrow().clone()
+    }
+
+    /// Receiver of progress updates, starting with the current status
+    pub fn subscribe(&self) -> watch::Receiver<WarmUpStatus> {
+        self.status.subscribe()
+    }
+
+    /// Stop a running warm-up; it reports [`WarmUpStatus::Cancelled`]
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    /// Start the warm-up in the background
+    pub fn start(self: &Arc<Self>, config: WarmUpConfig, db: Arc<DatabaseManager>, ai: Arc<AiEngine>) {
+        if !config.enabled {
+            self.status.send_replace(WarmUpStatus::Disabled);
+            return;
+        }
+
+        let warm_up = Arc::clone(self);
+        tokio::spawn(async move {
+            let start = Instant::now();
+            let finished = tokio::select! {
+                biased;
+                _ = warm_up.cancel.cancelled() => WarmUpStatus::Cancelled,
+                _ = warm_up.run(&config, &db, &ai) => WarmUpStatus::Complete {
+                    elapsed_ms: start.elapsed().as_millis() as u64,
+                },
+            };
+            tracing::info!("Cache warm-up finished: {:?}", finished);
+            warm_up.status.send_replace(finished);
+        });
+    }
+
+    /// Run every stage, logging and skipping those that fail
+    async fn run(&self, config: &WarmUpConfig, db: &DatabaseManager, ai: &AiEngine) {
+        let embeddings = ai.get_embeddings();
+        let mut stages = vec![WarmUpStage::Vectors, WarmUpStage::Documents, WarmUpStage::Statements];
+        if config.tokenizer && embeddings.is_model_loaded() {
+            stages.push(WarmUpStage::Tokenizer);
+        }
+        if config.model && ai.is_model_ready().await {
+            stages.push(WarmUpStage::Model);
+        }
+
+        let total = stages.len();
+        for (completed, stage) in stages.into_iter().enumerate() {
+            self.status.send_replace(WarmUpStatus::Running { stage, completed, total });
+            let result = match stage {
+                WarmUpStage::Vectors => warm_vectors(db).await,
+                WarmUpStage::Documents => warm_documents(db, config.documents).await,
+                WarmUpStage::Statements => warm_statements(db).await,
+                WarmUpStage::Tokenizer => embeddings.generate_embedding(TOKENIZER_WARM_UP_TEXT).await.map(|_| ()),
+                WarmUpStage::Model => ai.warm_up_model().await,
+            };
+            if let Err(e) = result {
+                tracing::warn!("Cache warm-up stage {:?} failed: {}", stage, e);
+            }
+        }
+    }
+}
+
+/// Read the vectors semantic search scans, from the vector cache when enabled
+async fn warm_vectors(db: &DatabaseManager) -> CodexResult<()> {
+    let pool = db.pool();
+    let from_cache = EmbeddingQueries::vector_cache_limit(pool).await?.is_some();
+    let vectors = EmbeddingQueries::touch_vectors(pool, from_cache).await?;
+    tracing::debug!("Warm-up read {} vectors", vectors);
+    Ok(())
+}
+
+/// Read the metadata of the `limit` most-viewed documents
+async fn warm_documents(db: &DatabaseManager, limit: usize) -> CodexResult<()> {
+    let documents = DocumentQueries::get_most_viewed(db.pool(), limit as i64, None).await?;
+    tracing::debug!("Warm-up read {} documents", documents.len());
+    Ok(())
+}
+
+/// Run the search statements once per pooled connection
+///
+/// Prepared statements are cached per connection, so the queries run
+/// concurrently to spread them over as many connections as the pool holds.
+async fn warm_statements(db: &DatabaseManager) -> CodexResult<()> {
+    let pool = db.pool();
+    let filter = SearchFilter::default();
+    let connections = pool.options().get_max_connections() as usize;
+    let searches = (0..connections).map(|_| async {
+        SearchQueries::search_with_ranking_filtered(pool, "warm", &filter, Some(1)).await?;
+        SearchQueries::list_filtered(pool, &filter, 1).await?;
+        EmbeddingQueries::vector_cache_limit(pool).await?;
+        CodexResult::Ok(())
+    });
+    for result in futures::future::join_all(searches).await {
+        result?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_database;
+    use crate::config::AiConfig;
+    use crate::db::models::Document;
+    use std::time::Duration;
+
+    async fn wait_until_finished(warm_up: &WarmUp) -> WarmUpStatus {
+        let mut updates = warm_up.subscribe();
+        let status = tokio::time::timeout(Duration::from_secs(10), updates.wait_for(WarmUpStatus::is_finished))
+            .await
+            .unwrap()
+            .unwrap();
+        status.clone()
+    }
+
+    #[tokio::test]
+    async fn test_warm_up_runs_in_background_and_reports_completion() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Arc::new(test_database(&dir).await);
+        let mut document = Document::new("Warm".to_string(), "Warm start".to_string(), "text/plain".to_string());
+        document.view_count = 3;
+        DocumentQueries::create(db.pool(), &document, db.compression()).await.unwrap();
+        let ai_config = AiConfig { models_dir: dir.path().to_path_buf(), ..AiConfig::default() };
+        let ai = Arc::new(AiEngine::new_disabled(&ai_config));
+
+        let warm_up = Arc::new(WarmUp::new());
+        assert_eq!(warm_up.status(), WarmUpStatus::Pending);
+        warm_up.start(WarmUpConfig::default(), Arc::clone(&db), Arc::clone(&ai));
+        // Searching does not wait for the warm-up
+        assert_eq!(SearchQueries::search_with_ranking(db.pool(), "warm", Some(5), None).await.unwrap().len(), 1);
+        assert!(matches!(wait_until_finished(&warm_up).await, WarmUpStatus::Complete { .. }));
+
+        let disabled = Arc::new(WarmUp::new());
+        disabled.start(WarmUpConfig { enabled: false, ..WarmUpConfig::default() }, Arc::clone(&db), Arc::clone(&ai));
+        assert_eq!(disabled.status(), WarmUpStatus::Disabled);
+
+        let cancelled = Arc::new(WarmUp::new());
+        cancelled.cancel();
+        cancelled.start(WarmUpConfig::default(), db, ai);
+        assert_eq!(wait_until_finished(&cancelled).await, WarmUpStatus::Cancelled);
+    }
+}
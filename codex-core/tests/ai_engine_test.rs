@@ -2,30 +2,38 @@
 
 mod common;
 
-use common::ai::{MockAiEngine, AiFixtures, TestAiConfig};
-use common::db::{TestDatabase, DocumentBuilder, SampleData};
+use common::ai::{offline_ai_engine, MockAiEngine, TestAiConfig};
+use common::db::{TestDatabase, SampleData};
+use common::fixtures::TestFixtures;
 use codex_core::{
-    ai::{AiEngine, AiStats},
-    config::AiConfig,
+    ai::AiEngine,
     CodexResult,
 };
 use rstest::*;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
+/// AI engine over the offline mocks, so no model files are needed
+fn test_engine() -> Arc<AiEngine> {
+    offline_ai_engine(&TestAiConfig::new().to_ai_config())
+}
+
 /// Test AI engine creation and configuration
 #[rstest]
 #[tokio::test]
 async fn test_ai_engine_creation() -> CodexResult<()> {
-    let config = TestAiConfig::new().to_ai_config();
-    let engine = AiEngine::new(config).await;
-    
-    // Engine creation should succeed with valid config
-    assert!(engine.is_ok());
-    
-    let engine = engine.unwrap();
+    let models_dir = tempfile::tempdir()?;
+    let mut config = TestAiConfig::new().to_ai_config();
+    config.models_dir = models_dir.path().to_path_buf();
+
+    // Engine creation should fail cleanly when the model is missing
+    let engine = AiEngine::new(&config).await;
+    assert!(engine.is_err());
+
+    let engine = offline_ai_engine(&config);
     let health = engine.health_check().await?;
     assert!(health);
-    
+
     Ok(())
 }
 
@@ -38,17 +46,13 @@ async fn test_ai_engine_creation() -> CodexResult<()> {
 async fn test_ai_config_validation(#[case] max_tokens: usize, #[case] should_succeed: bool) -> CodexResult<()> {
     let mut config = TestAiConfig::new().to_ai_config();
     config.max_tokens = max_tokens;
-    
-    let result = AiEngine::new(config).await;
-    
-    if should_succeed {
-        assert!(result.is_ok());
-    } else {
-        // Note: In a real implementation, this might validate and return an error
-        // For now, we just verify the engine handles the config
-        assert!(result.is_ok() || result.is_err());
-    }
-    
+
+    let engine = offline_ai_engine(&config);
+    let response = engine.generate_text("What is artificial intelligence?").await?;
+
+    // Without a token budget nothing can be generated
+    assert_eq!(!response.is_empty(), should_succeed);
+
     Ok(())
 }
 
@@ -56,22 +60,17 @@ async fn test_ai_config_validation(#[case] max_tokens: usize, #[case] should_suc
 #[rstest]
 #[tokio::test]
 async fn test_ai_stats_collection() -> CodexResult<()> {
-    let config = TestAiConfig::new().to_ai_config();
-    let engine = AiEngine::new(config).await?;
-    
+    let engine = test_engine();
+
     let stats = engine.get_stats().await?;
-    
+
     // Verify stats structure
     assert!(stats.memory_usage_mb >= 0.0);
-    assert!(stats.uptime_seconds >= 0);
-    assert!(stats.total_requests >= 0);
-    assert!(stats.successful_requests >= 0);
-    assert!(stats.failed_requests >= 0);
-    assert!(stats.average_response_time_ms >= 0.0);
-    
+    assert!(stats.average_inference_time_ms >= 0.0);
+
     // Logical constraints
-    assert!(stats.successful_requests + stats.failed_requests <= stats.total_requests);
-    
+    assert!((0.0..=1.0).contains(&stats.cache_hit_rate));
+
     Ok(())
 }
 
@@ -79,25 +78,15 @@ async fn test_ai_stats_collection() -> CodexResult<()> {
 #[rstest]
 #[tokio::test]
 async fn test_text_generation() -> CodexResult<()> {
-    let config = TestAiConfig::new().to_ai_config();
-    let engine = AiEngine::new(config).await?;
-    
+    let engine = test_engine();
+
     // Test basic text generation
     let prompt = "What is artificial intelligence?";
-    let result = engine.generate_text(prompt).await;
-    
-    // Generation should either succeed or fail gracefully
-    match result {
-        Ok(response) => {
-            assert!(!response.is_empty());
-            println!("Generated response: {}", response);
-        },
-        Err(_) => {
-            // Model might not be available in test environment
-            println!("Text generation failed (expected in test environment)");
-        }
-    }
-    
+    let response = engine.generate_text(prompt).await?;
+
+    assert!(!response.is_empty());
+    println!("Generated response: {}", response);
+
     Ok(())
 }
 
@@ -105,29 +94,22 @@ async fn test_text_generation() -> CodexResult<()> {
 #[rstest]
 #[tokio::test]
 async fn test_streaming_generation() -> CodexResult<()> {
-    let config = TestAiConfig::new().to_ai_config();
-    let engine = AiEngine::new(config).await?;
-    
-    let mut chunks = Vec::new();
+    let engine = test_engine();
+
+    let chunks = Arc::new(Mutex::new(Vec::new()));
     let prompt = "Explain machine learning in simple terms.";
-    
-    let result = engine.generate_text_stream(prompt, |chunk| {
-        chunks.push(chunk);
-    }).await;
-    
-    match result {
-        Ok(response) => {
-            assert!(!response.is_empty());
-            assert!(!chunks.is_empty());
-            // Last chunk should be the complete response
-            assert_eq!(chunks.last().unwrap(), &response);
-        },
-        Err(_) => {
-            // Model might not be available in test environment
-            println!("Streaming generation failed (expected in test environment)");
-        }
-    }
-    
+
+    let sink = Arc::clone(&chunks);
+    let response = engine.generate_text_stream(prompt, move |chunk| {
+        sink.lock().unwrap().push(chunk);
+    }).await?;
+
+    let chunks = chunks.lock().unwrap();
+    assert!(!response.is_empty());
+    assert!(!chunks.is_empty());
+    // Last chunk should be the complete response
+    assert_eq!(chunks.last().unwrap(), &response);
+
     Ok(())
 }
 
@@ -135,24 +117,15 @@ async fn test_streaming_generation() -> CodexResult<()> {
 #[rstest]
 #[tokio::test]
 async fn test_simple_inference() -> CodexResult<()> {
-    let config = TestAiConfig::new().to_ai_config();
-    let engine = AiEngine::new(config).await?;
-    
+    let engine = test_engine();
+
     let prompt = "Define quantum computing.";
-    let result = engine.infer(prompt).await;
-    
-    match result {
-        Ok(response) => {
-            assert!(!response.is_empty());
-            // Response should be reasonable length
-            assert!(response.len() > 10);
-        },
-        Err(_) => {
-            // Model might not be available in test environment
-            println!("Inference failed (expected in test environment)");
-        }
-    }
-    
+    let response = engine.infer(prompt).await?;
+
+    assert!(!response.is_empty());
+    // Response should be reasonable length
+    assert!(response.len() > 10);
+
     Ok(())
 }
 
@@ -160,25 +133,16 @@ async fn test_simple_inference() -> CodexResult<()> {
 #[rstest]
 #[tokio::test]
 async fn test_content_summarization() -> CodexResult<()> {
-    let config = TestAiConfig::new().to_ai_config();
-    let engine = AiEngine::new(config).await?;
-    
+    let engine = test_engine();
+
     let long_content = "Artificial intelligence (AI) is intelligence demonstrated by machines, in contrast to the natural intelligence displayed by humans and animals. Leading AI textbooks define the field as the study of \"intelligent agents\": any device that perceives its environment and takes actions that maximize its chance of successfully achieving its goals. Colloquially, the term \"artificial intelligence\" is often used to describe machines (or computers) that mimic \"cognitive\" functions that humans associate with the human mind, such as \"learning\" and \"problem solving\".";
-    
-    let result = engine.summarize_content(long_content).await;
-    
-    match result {
-        Ok(summary) => {
-            assert!(!summary.is_empty());
-            assert!(summary.len() < long_content.len());
-            println!("Generated summary: {}", summary);
-        },
-        Err(_) => {
-            // Model might not be available in test environment
-            println!("Summarization failed (expected in test environment)");
-        }
-    }
-    
+
+    let summary = engine.summarize(long_content, Some(50)).await?;
+
+    assert!(!summary.is_empty());
+    assert!(summary.len() < long_content.len());
+    println!("Generated summary: {}", summary);
+
     Ok(())
 }
 
@@ -186,32 +150,23 @@ async fn test_content_summarization() -> CodexResult<()> {
 #[rstest]
 #[tokio::test]
 async fn test_tag_generation() -> CodexResult<()> {
-    let config = TestAiConfig::new().to_ai_config();
-    let engine = AiEngine::new(config).await?;
-    
+    let engine = test_engine();
+
     let content = "Machine learning is a subset of artificial intelligence that focuses on developing algorithms that can learn and make predictions from data without being explicitly programmed.";
-    
-    let result = engine.generate_tags(content, 5).await;
-    
-    match result {
-        Ok(tags) => {
-            assert!(!tags.is_empty());
-            assert!(tags.len() <= 5);
-            
-            // Tags should be non-empty strings
-            for tag in &tags {
-                assert!(!tag.is_empty());
-                assert!(!tag.contains(' ') || tag.len() <= 20); // Single words or short phrases
-            }
-            
-            println!("Generated tags: {:?}", tags);
-        },
-        Err(_) => {
-            // Model might not be available in test environment
-            println!("Tag generation failed (expected in test environment)");
-        }
+
+    let tags = engine.generate_tags(content, Some(5)).await?;
+
+    assert!(!tags.is_empty());
+    assert!(tags.len() <= 5);
+
+    // Tags should be non-empty strings
+    for tag in &tags {
+        assert!(!tag.is_empty());
+        assert!(!tag.contains(' ') || tag.len() <= 20); // Single words or short phrases
     }
-    
+
+    println!("Generated tags: {:?}", tags);
+
     Ok(())
 }
 
@@ -223,9 +178,8 @@ async fn test_tag_generation() -> CodexResult<()> {
 #[case("Regular exercise and proper nutrition are essential for good health.", "Health")]
 #[tokio::test]
 async fn test_content_categorization(#[case] content: &str, #[case] expected_category: &str) -> CodexResult<()> {
-    let config = TestAiConfig::new().to_ai_config();
-    let engine = AiEngine::new(config).await?;
-    
+    let engine = test_engine();
+
     let categories = vec![
         "Philosophy".to_string(),
         "Science".to_string(),
@@ -234,21 +188,14 @@ async fn test_content_categorization(#[case] content: &str, #[case] expected_cat
         "History".to_string(),
         "Arts".to_string(),
     ];
-    
-    let result = engine.categorize_content(content, &categories).await;
-    
-    match result {
-        Ok(category) => {
-            assert!(categories.contains(&category));
-            // In a real test with actual AI, we might check if it matches expected
-            println!("Categorized '{}' as: {}", &content[..50], category);
-        },
-        Err(_) => {
-            // Model might not be available in test environment
-            println!("Categorization failed (expected in test environment)");
-        }
-    }
-    
+    assert!(categories.iter().any(|c| c == expected_category));
+
+    let category = engine.categorize_content(content, &categories).await?;
+
+    assert!(categories.contains(&category));
+    // The mock answers one category; with a real model it should match the expected one
+    println!("Categorized '{}' as: {}", &content[..50], category);
+
     Ok(())
 }
 
@@ -259,22 +206,13 @@ async fn test_content_categorization(#[case] content: &str, #[case] expected_cat
 #[case("Quantum entanglement demonstrates non-local correlations between particles.", 5)] // Complex
 #[tokio::test]
 async fn test_difficulty_assessment(#[case] content: &str, #[case] expected_level: i32) -> CodexResult<()> {
-    let config = TestAiConfig::new().to_ai_config();
-    let engine = AiEngine::new(config).await?;
-    
-    let result = engine.assess_difficulty(content).await;
-    
-    match result {
-        Ok(difficulty) => {
-            assert!(difficulty >= 1 && difficulty <= 5);
-            println!("Assessed difficulty of '{}' as: {}", &content[..30], difficulty);
-        },
-        Err(_) => {
-            // Model might not be available in test environment
-            println!("Difficulty assessment failed (expected in test environment)");
-        }
-    }
-    
+    let engine = test_engine();
+
+    let difficulty = engine.assess_difficulty(content).await?;
+
+    assert!((1..=5).contains(&difficulty));
+    println!("Assessed difficulty of '{}' as: {} (expected about {})", &content[..15], difficulty, expected_level);
+
     Ok(())
 }
 
@@ -283,40 +221,39 @@ async fn test_difficulty_assessment(#[case] content: &str, #[case] expected_leve
 #[tokio::test]
 async fn test_rag_query() -> CodexResult<()> {
     let db = TestDatabase::new().await?;
-    let config = TestAiConfig::new().to_ai_config();
-    let engine = AiEngine::new(config).await?;
-    
+    let engine = test_engine();
+    engine.set_database(Arc::clone(&db.manager));
+
     // Create some documents for RAG context
     let philosophy_docs = SampleData::philosophy_docs();
-    for doc_request in philosophy_docs {
-        db.manager.content().create_document(doc_request).await?;
+    for document in &philosophy_docs {
+        db.insert(document).await?;
     }
-    
+
     let query = "What is stoicism?";
     let result = engine.rag_query(query, 3).await;
-    
+
     match result {
         Ok(rag_response) => {
             assert!(!rag_response.answer.is_empty());
-            assert!(!rag_response.sources.is_empty());
             assert!(rag_response.sources.len() <= 3);
-            
+
             // Verify source documents are relevant
             for source in &rag_response.sources {
-                assert!(!source.id.is_empty());
+                assert!(!source.document_id.is_nil());
                 assert!(!source.title.is_empty());
                 assert!(source.relevance_score >= 0.0 && source.relevance_score <= 1.0);
             }
-            
+
             println!("RAG Response: {}", rag_response.answer);
             println!("Sources: {} documents", rag_response.sources.len());
         },
-        Err(_) => {
-            // RAG might fail without proper model or embeddings
-            println!("RAG query failed (expected in test environment)");
+        Err(e) => {
+            // The documents have no embeddings yet, so retrieval may find nothing
+            println!("RAG query failed without embeddings: {}", e);
         }
     }
-    
+
     Ok(())
 }
 
@@ -324,64 +261,56 @@ async fn test_rag_query() -> CodexResult<()> {
 #[rstest]
 #[tokio::test]
 async fn test_performance_metrics() -> CodexResult<()> {
-    let config = TestAiConfig::new().to_ai_config();
-    let engine = AiEngine::new(config).await?;
-    
+    let engine = test_engine();
+
     // Get initial stats
     let initial_stats = engine.get_stats().await?;
-    
+
     // Perform some operations
-    let prompts = vec![
+    let prompts = [
         "What is AI?",
         "Explain machine learning.",
         "Define neural networks.",
     ];
-    
+
     for prompt in prompts {
-        let _ = engine.infer(prompt).await; // Ignore errors in test environment
+        engine.infer(prompt).await?;
     }
-    
+
     // Get updated stats
     let updated_stats = engine.get_stats().await?;
-    
-    // Stats should show some activity (even if operations failed)
+
+    // Stats should show the activity
     assert!(updated_stats.uptime_seconds >= initial_stats.uptime_seconds);
-    // In real implementation, request counts would increase
-    
+    assert!(updated_stats.total_inferences >= initial_stats.total_inferences);
+
     Ok(())
 }
 
 /// Test AI engine with various configurations
 #[rstest]
-#[case(0.0, 0.9, 1.0)] // Conservative settings
-#[case(0.7, 0.9, 1.1)] // Balanced settings
-#[case(1.0, 0.8, 1.2)] // Creative settings
+#[case(0.0, 0.9)] // Conservative settings
+#[case(0.7, 0.9)] // Balanced settings
+#[case(1.0, 0.8)] // Creative settings
 #[tokio::test]
 async fn test_various_configurations(
     #[case] temperature: f32,
     #[case] top_p: f32,
-    #[case] repeat_penalty: f32,
 ) -> CodexResult<()> {
     let mut config = TestAiConfig::new().to_ai_config();
     config.temperature = temperature;
     config.top_p = top_p;
-    config.repeat_penalty = repeat_penalty;
-    
-    let engine = AiEngine::new(config).await?;
-    
+
+    let engine = offline_ai_engine(&config);
+
     // Engine should initialize with any reasonable configuration
     let health = engine.health_check().await?;
     assert!(health);
-    
+
     // Test inference with different settings
-    let result = engine.infer("Test prompt for configuration").await;
-    
-    // Should handle gracefully (succeed or fail cleanly)
-    match result {
-        Ok(response) => println!("Response with temp={}: {}", temperature, response),
-        Err(_) => println!("Generation failed with temp={} (expected)", temperature),
-    }
-    
+    let response = engine.infer("Test prompt for configuration").await?;
+    println!("Response with temp={}: {}", temperature, response);
+
     Ok(())
 }
 
@@ -389,20 +318,19 @@ async fn test_various_configurations(
 #[rstest]
 #[tokio::test]
 async fn test_error_recovery() -> CodexResult<()> {
-    let config = TestAiConfig::new().to_ai_config();
-    let engine = AiEngine::new(config).await?;
-    
+    let engine = test_engine();
+
     // Test with problematic inputs
-    let problematic_prompts = vec![
-        "", // Empty prompt
+    let problematic_prompts = [
+        String::new(), // Empty prompt
         "A".repeat(10000), // Very long prompt
-        "\0\0\0", // Null characters
-        "Prompt with\ninvalid\tcharacters\r\n",
+        "\0\0\0".to_string(), // Null characters
+        "Prompt with\ninvalid\tcharacters\r\n".to_string(),
     ];
-    
+
     for prompt in problematic_prompts {
         let result = engine.infer(&prompt).await;
-        
+
         // Should either succeed or fail gracefully (not panic)
         match result {
             Ok(response) => {
@@ -411,11 +339,11 @@ async fn test_error_recovery() -> CodexResult<()> {
             Err(e) => {
                 println!("Gracefully failed on problematic prompt: {}", e);
                 // Error should be meaningful, not a panic
-                assert!(e.to_string().len() > 0);
+                assert!(!e.to_string().is_empty());
             }
         }
     }
-    
+
     Ok(())
 }
 
@@ -423,37 +351,34 @@ async fn test_error_recovery() -> CodexResult<()> {
 #[rstest]
 #[tokio::test]
 async fn test_concurrent_operations() -> CodexResult<()> {
-    let config = TestAiConfig::new().to_ai_config();
-    let engine = AiEngine::new(config).await?;
-    
+    let engine = test_engine();
+
     // Start multiple concurrent operations
+    let infer = |prompt: &'static str| {
+        let engine = Arc::clone(&engine);
+        tokio::spawn(async move { engine.infer(prompt).await.map(|_| ()) })
+    };
     let handles = vec![
+        infer("Concurrent prompt 1"),
+        infer("Concurrent prompt 2"),
         tokio::spawn({
-            let engine = &engine;
-            async move { engine.infer("Concurrent prompt 1").await }
-        }),
-        tokio::spawn({
-            let engine = &engine;
-            async move { engine.infer("Concurrent prompt 2").await }
+            let engine = Arc::clone(&engine);
+            async move { engine.get_stats().await.map(|_| ()) }
         }),
         tokio::spawn({
-            let engine = &engine;
-            async move { engine.get_stats().await }
-        }),
-        tokio::spawn({
-            let engine = &engine;
-            async move { engine.health_check().await }
+            let engine = Arc::clone(&engine);
+            async move { engine.health_check().await.map(|_| ()) }
         }),
     ];
-    
+
     // Wait for all operations to complete
     let results = futures::future::join_all(handles).await;
-    
+
     // All operations should complete without panicking
     for result in results {
         assert!(result.is_ok());
     }
-    
+
     Ok(())
 }
 
@@ -465,21 +390,21 @@ async fn test_ai_engine_with_mock() -> CodexResult<()> {
         .add_response("What is the meaning of life?", "42")
         .add_response("Explain quantum physics", "It's very small and very weird")
         .with_delay(Duration::from_millis(100));
-    
+
     // Test basic functionality
     let response1 = mock_engine.generate_text("What is the meaning of life?").await?;
     assert_eq!(response1, "42");
-    
+
     let response2 = mock_engine.generate_text("Explain quantum physics").await?;
     assert_eq!(response2, "It's very small and very weird");
-    
+
     // Test call counting
     assert_eq!(mock_engine.call_count(), 2);
-    
+
     // Test system metrics
     let metrics = mock_engine.get_system_metrics().await?;
-    assert!(metrics.process_memory_usage_mb > 0.0);
-    
+    assert!(metrics.process_memory_mb > 0.0);
+
     Ok(())
 }
 
@@ -487,27 +412,26 @@ async fn test_ai_engine_with_mock() -> CodexResult<()> {
 #[rstest]
 #[tokio::test]
 async fn test_memory_management() -> CodexResult<()> {
-    let config = TestAiConfig::new().to_ai_config();
-    let engine = AiEngine::new(config).await?;
-    
+    let engine = test_engine();
+
     // Get initial memory usage
     let initial_stats = engine.get_stats().await?;
     let initial_memory = initial_stats.memory_usage_mb;
-    
+
     // Perform many operations to test memory management
     for i in 0..50 {
         let prompt = format!("Memory test prompt number {}", i);
-        let _ = engine.infer(&prompt).await; // Ignore results
+        engine.infer(&prompt).await?;
     }
-    
+
     // Check final memory usage
     let final_stats = engine.get_stats().await?;
     let final_memory = final_stats.memory_usage_mb;
-    
+
     // Memory shouldn't grow excessively
     let memory_growth = final_memory - initial_memory;
     assert!(memory_growth < 200.0, "Memory grew too much: {} MB", memory_growth);
-    
+
     Ok(())
 }
 
@@ -515,29 +439,26 @@ async fn test_memory_management() -> CodexResult<()> {
 #[rstest]
 #[tokio::test]
 async fn test_ai_engine_fixtures() -> CodexResult<()> {
-    let config = TestAiConfig::new().to_ai_config();
-    let engine = AiEngine::new(config).await?;
-    
-    let test_cases = AiFixtures::ai_test_prompts();
-    
+    let engine = test_engine();
+
+    let test_cases = TestFixtures::ai_test_prompts();
+
     for test_case in test_cases {
-        let result = engine.infer(&test_case.prompt).await;
-        
-        match result {
-            Ok(response) => {
-                // Verify response meets basic criteria
-                assert!(response.len() >= test_case.min_response_length);
-                
-                // Check for expected keywords (in real implementation)
-                println!("Prompt: {}", test_case.prompt);
-                println!("Response: {}", response);
-            },
-            Err(_) => {
-                // Expected in test environment without real model
-                println!("AI test case failed (expected): {}", test_case.prompt);
-            }
-        }
+        let start = std::time::Instant::now();
+        let response = engine.infer(&test_case.prompt).await?;
+        let elapsed = start.elapsed();
+
+        // Verify response meets basic criteria
+        assert!(!response.is_empty());
+        assert!(
+            elapsed.as_millis() < u128::from(test_case.max_response_time_ms),
+            "'{}' took {:?}", test_case.prompt, elapsed
+        );
+
+        // Length and keywords depend on a real model
+        println!("Prompt: {}", test_case.prompt);
+        println!("Response: {}", response);
     }
-    
+
     Ok(())
-}
\ No newline at end of file
+}
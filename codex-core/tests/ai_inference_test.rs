@@ -2,28 +2,34 @@
 
 mod common;
 
-use common::ai::{MockAiEngine, AiFixtures, AiPerformanceTest, TestAiConfig};
+use common::ai::{offline_mock_engine, MockAiEngine, AiFixtures, AiPerformanceTest, TestAiConfig};
 use codex_core::{
-    ai::{inference::InferenceEngine, AiStats, SystemMetricsSnapshot, TokenCacheStats},
-    config::AiConfig,
+    ai::inference::InferenceEngine,
     CodexResult,
 };
 use rstest::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 use tokio::time::timeout;
 
+/// Inference engine generating through the offline mock instead of model weights
+fn mock_inference_engine() -> InferenceEngine {
+    InferenceEngine::with_engine(&TestAiConfig::new().to_ai_config(), Arc::new(offline_mock_engine()))
+}
+
 /// Test basic inference engine creation and initialization
 #[rstest]
 #[tokio::test]
 async fn test_inference_engine_creation() -> CodexResult<()> {
     let config = TestAiConfig::new().to_ai_config();
-    let engine = InferenceEngine::new(&config).await;
     
-    // Engine creation should succeed even without a real model file
-    assert!(engine.is_ok());
+    // Loading fails without a real model file
+    assert!(InferenceEngine::new(&config).await.is_err());
     
-    let engine = engine.unwrap();
-    assert!(!engine.is_model_loaded());
+    // An unloaded engine is not ready, one with a backend is
+    assert!(!InferenceEngine::unloaded(&config).is_ready());
+    assert!(mock_inference_engine().is_ready());
     
     Ok(())
 }
@@ -33,12 +39,15 @@ async fn test_inference_engine_creation() -> CodexResult<()> {
 #[tokio::test]
 async fn test_inference_engine_health_check() -> CodexResult<()> {
     let config = TestAiConfig::new().to_ai_config();
-    let engine = InferenceEngine::new(&config).await?;
+    let engine = mock_inference_engine();
     
-    let health = engine.health_check().await?;
+    // Generation goes through the backend without a model loaded
+    let response = engine.generate("Are you healthy?", &config).await?;
+    assert!(!response.is_empty());
     
-    // Health check should work even without model loaded
-    assert!(health);
+    // An unloaded engine refuses to generate
+    let unloaded = InferenceEngine::unloaded(&config);
+    assert!(unloaded.generate("Are you healthy?", &config).await.is_err());
     
     Ok(())
 }
@@ -47,26 +56,20 @@ async fn test_inference_engine_health_check() -> CodexResult<()> {
 #[rstest]
 #[tokio::test]
 async fn test_system_metrics_collection() -> CodexResult<()> {
-    let config = TestAiConfig::new().to_ai_config();
-    let engine = InferenceEngine::new(&config).await?;
+    let engine = mock_inference_engine();
     
     let metrics = engine.get_system_metrics().await?;
     
     // Verify metrics structure
-    assert!(metrics.process_memory_usage_mb > 0.0);
-    assert!(metrics.total_memory_mb > 0.0);
-    assert!(metrics.system_memory_usage_mb > 0.0);
-    assert!(metrics.uptime_seconds > 0);
-    assert!(metrics.process_cpu_usage >= 0.0);
-    assert!(metrics.system_cpu_usage >= 0.0);
+    assert!(metrics.process_memory_mb > 0.0);
+    assert!(metrics.system_memory_total_mb > 0.0);
+    assert!(metrics.system_memory_used_mb > 0.0);
+    assert!(metrics.cpu_count > 0);
+    assert!(metrics.peak_cpu_percent >= 0.0);
     
     // Memory usage should be reasonable
-    assert!(metrics.process_memory_usage_mb < metrics.total_memory_mb);
-    assert!(metrics.system_memory_usage_mb <= metrics.total_memory_mb);
-    
-    // CPU usage should be between 0 and 100
-    assert!(metrics.process_cpu_usage <= 100.0);
-    assert!(metrics.system_cpu_usage <= 100.0);
+    assert!(metrics.process_memory_mb < metrics.system_memory_total_mb);
+    assert!(metrics.system_memory_used_mb <= metrics.system_memory_total_mb);
     
     Ok(())
 }
@@ -75,16 +78,17 @@ async fn test_system_metrics_collection() -> CodexResult<()> {
 #[rstest]
 #[tokio::test]
 async fn test_token_cache_stats() -> CodexResult<()> {
-    let config = TestAiConfig::new().to_ai_config();
-    let engine = InferenceEngine::new(&config).await?;
+    let engine = mock_inference_engine();
     
     let stats = engine.get_token_cache_stats().await?;
     
     // Verify initial cache stats
-    assert_eq!(stats.total_tokens, 0);
-    assert!(stats.cache_size > 0);
-    assert_eq!(stats.hit_rate, 0.0);
-    assert_eq!(stats.evictions, 0);
+    assert_eq!(stats.current_token_count, 0);
+    assert!(stats.max_token_count > 0);
+    assert_eq!(stats.prompt_cache_size, 0);
+    assert_eq!(stats.sequence_cache_size, 0);
+    assert_eq!(stats.text_cache_size, 0);
+    assert_eq!(stats.memory_usage_mb, 0.0);
     
     Ok(())
 }
@@ -119,10 +123,12 @@ async fn test_mock_streaming() -> CodexResult<()> {
         .add_response("Stream test", "This is a streaming response test")
         .with_delay(Duration::from_millis(50));
     
-    let mut chunks = Vec::new();
-    let response = mock_engine.generate_text_stream("Stream test", |chunk| {
-        chunks.push(chunk);
+    let chunks = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&chunks);
+    let response = mock_engine.generate_text_stream("Stream test", move |chunk| {
+        sink.lock().unwrap().push(chunk);
     }).await?;
+    let chunks = chunks.lock().unwrap();
     
     // Should receive progressive chunks
     assert!(chunks.len() > 1);
@@ -182,27 +188,18 @@ async fn test_concurrent_inference() -> CodexResult<()> {
         .with_delay(Duration::from_millis(50));
     
     // Start multiple concurrent requests
-    let handles = vec![
-        tokio::spawn({
-            let engine = &mock_engine;
-            async move { engine.generate_text("concurrent 1").await }
-        }),
-        tokio::spawn({
-            let engine = &mock_engine;
-            async move { engine.generate_text("concurrent 2").await }
-        }),
-        tokio::spawn({
-            let engine = &mock_engine;
-            async move { engine.generate_text("concurrent 3").await }
-        }),
+    let requests = vec![
+        mock_engine.generate_text("concurrent 1"),
+        mock_engine.generate_text("concurrent 2"),
+        mock_engine.generate_text("concurrent 3"),
     ];
     
     // Wait for all to complete
-    let results = futures::future::join_all(handles).await;
+    let results = futures::future::join_all(requests).await;
     
     // All should succeed
     for result in results {
-        let response = result.unwrap()?;
+        let response = result?;
         assert!(response.starts_with("Response"));
     }
     
@@ -249,8 +246,8 @@ async fn test_ai_stats_tracking() -> CodexResult<()> {
     let updated_metrics = mock_engine.get_system_metrics().await?;
     
     // Metrics should be consistent
-    assert_eq!(initial_metrics.total_memory_mb, updated_metrics.total_memory_mb);
-    assert!(updated_metrics.uptime_seconds >= initial_metrics.uptime_seconds);
+    assert_eq!(initial_metrics.system_memory_total_mb, updated_metrics.system_memory_total_mb);
+    assert!(updated_metrics.uptime >= initial_metrics.uptime);
     
     Ok(())
 }
@@ -263,14 +260,10 @@ async fn test_cache_behavior() -> CodexResult<()> {
         .add_response("cached prompt", "This response should be cached");
     
     // First call
-    let start1 = Instant::now();
     let response1 = mock_engine.generate_text("cached prompt").await?;
-    let duration1 = start1.elapsed();
     
     // Second call with same prompt
-    let start2 = Instant::now();
     let response2 = mock_engine.generate_text("cached prompt").await?;
-    let duration2 = start2.elapsed();
     
     // Responses should be identical
     assert_eq!(response1, response2);
@@ -326,7 +319,7 @@ async fn test_memory_usage_tracking() -> CodexResult<()> {
     
     // Get initial memory metrics
     let initial_metrics = mock_engine.get_system_metrics().await?;
-    let initial_memory = initial_metrics.process_memory_usage_mb;
+    let initial_memory = initial_metrics.process_memory_mb;
     
     // Perform multiple inferences
     for i in 0..10 {
@@ -336,7 +329,7 @@ async fn test_memory_usage_tracking() -> CodexResult<()> {
     
     // Get final memory metrics
     let final_metrics = mock_engine.get_system_metrics().await?;
-    let final_memory = final_metrics.process_memory_usage_mb;
+    let final_memory = final_metrics.process_memory_mb;
     
     // Memory should be reasonable (not growing excessively)
     let memory_growth = final_memory - initial_memory;
@@ -366,8 +359,8 @@ async fn test_token_cache_updates() -> CodexResult<()> {
     
     // Cache should show some activity (in a real implementation)
     // For mock, we just verify the structure is maintained
-    assert_eq!(initial_stats.cache_size, updated_stats.cache_size);
-    assert!(updated_stats.hit_rate >= 0.0 && updated_stats.hit_rate <= 1.0);
+    assert_eq!(initial_stats.max_token_count, updated_stats.max_token_count);
+    assert!(updated_stats.current_token_count <= updated_stats.max_token_count);
     
     Ok(())
 }
@@ -379,19 +372,23 @@ async fn test_streaming_callbacks() -> CodexResult<()> {
     let mock_engine = MockAiEngine::new()
         .add_response("callback test", "Testing streaming callbacks with multiple words");
     
-    let mut callback_count = 0;
-    let mut last_chunk = String::new();
+    let callback_count = Arc::new(AtomicUsize::new(0));
+    let last_chunk = Arc::new(Mutex::new(String::new()));
     
-    let response = mock_engine.generate_text_stream("callback test", |chunk| {
-        callback_count += 1;
-        last_chunk = chunk;
+    let response = mock_engine.generate_text_stream("callback test", {
+        let callback_count = Arc::clone(&callback_count);
+        let last_chunk = Arc::clone(&last_chunk);
+        move |chunk| {
+            callback_count.fetch_add(1, Ordering::SeqCst);
+            *last_chunk.lock().unwrap() = chunk;
+        }
     }).await?;
     
     // Should have received multiple callbacks
-    assert!(callback_count > 1);
+    assert!(callback_count.load(Ordering::SeqCst) > 1);
     
     // Last chunk should be the complete response
-    assert_eq!(last_chunk, response);
+    assert_eq!(*last_chunk.lock().unwrap(), response);
     assert_eq!(response, "Testing streaming callbacks with multiple words");
     
     Ok(())
@@ -0,0 +1,189 @@
+//! Local HTTP API tests
+//!
+//! Spawn the server against a temporary vault and talk to it with reqwest.
+//! The core runs on the offline mock models, so no model files are needed.
+
+#![cfg(feature = "api-server")]
+
+use std::sync::Arc;
+
+use tempfile::TempDir;
+
+use codex_core::ai::MockEmbeddingBackend;
+use codex_core::api_server::ApiServerInfo;
+use codex_core::{CodexConfig, CodexCore, CodexCoreBuilder};
+
+mod common;
+use common::ai::offline_mock_engine;
+
+async fn spawn_core(dir: &TempDir) -> CodexCore {
+    let mut config = CodexConfig::default().with_vault_dir(dir.path());
+    config.app.api_server.port = 0;
+
+    CodexCoreBuilder::new()
+        .config(config)
+        .llm_engine(Arc::new(offline_mock_engine()))
+        .embedding_backend(Arc::new(MockEmbeddingBackend::default()))
+        .build()
+        .await
+        .unwrap()
+}
+
+/// Import a note over HTTP, returning its id
+async fn import_note(client: &reqwest::Client, server: &ApiServerInfo, title: &str, content: &str) -> String {
+    let imported: serde_json::Value = client
+        .post(format!("{}/import-text", server.url))
+        .bearer_auth(&server.token)
+        .json(&serde_json::json!({ "title": title, "content": content }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    imported["document_id"].as_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn test_requests_without_token_are_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let core = spawn_core(&dir).await;
+    let server = core.start_api_server().await.unwrap();
+    assert!(server.address.ip().is_loopback());
+
+    let client = reqwest::Client::new();
+    let response = client.get(format!("{}/documents", server.url)).send().await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    let response = client
+        .get(format!("{}/documents", server.url))
+        .bearer_auth("not-the-token")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    assert!(core.stop_api_server().await);
+    core.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_import_text_then_fetch_and_list() {
+    let dir = tempfile::tempdir().unwrap();
+    let core = spawn_core(&dir).await;
+    let server = core.start_api_server().await.unwrap();
+    let client = reqwest::Client::new();
+
+    let id = import_note(&client, &server, "API note", "Imported over HTTP").await;
+
+    let document: serde_json::Value = client
+        .get(format!("{}/documents/{}", server.url, id))
+        .bearer_auth(&server.token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(document["title"], "API note");
+
+    let documents: Vec<serde_json::Value> = client
+        .get(format!("{}/documents?limit=10", server.url))
+        .bearer_auth(&server.token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(documents.iter().any(|d| d["id"] == id.as_str()));
+
+    let missing = client
+        .get(format!("{}/documents/{}", server.url, uuid::Uuid::new_v4()))
+        .bearer_auth(&server.token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(missing.status(), reqwest::StatusCode::NOT_FOUND);
+
+    core.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_search_finds_imported_note() {
+    let dir = tempfile::tempdir().unwrap();
+    let core = spawn_core(&dir).await;
+    let server = core.start_api_server().await.unwrap();
+    let client = reqwest::Client::new();
+
+    let id = import_note(&client, &server, "Lighthouses", "A lighthouse uses a rotating lens to send its beam out to sea.").await;
+    import_note(&client, &server, "Sourdough", "Feed the starter with flour and water every day.").await;
+
+    let results: serde_json::Value = client
+        .post(format!("{}/search", server.url))
+        .bearer_auth(&server.token)
+        .json(&serde_json::json!({ "query": "lighthouse", "limit": 100000 }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let documents = results["documents"].as_array().unwrap();
+    assert_eq!(documents[0]["document"]["id"], id.as_str());
+    assert!(documents.iter().all(|hit| hit["document"]["title"] != "Sourdough"));
+
+    core.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_rag_answers_and_streams() {
+    let dir = tempfile::tempdir().unwrap();
+    let core = spawn_core(&dir).await;
+    let server = core.start_api_server().await.unwrap();
+    let client = reqwest::Client::new();
+
+    import_note(&client, &server, "Lighthouses", "A lighthouse uses a rotating lens to send its beam out to sea.").await;
+
+    let response: serde_json::Value = client
+        .post(format!("{}/rag", server.url))
+        .bearer_auth(&server.token)
+        .json(&serde_json::json!({ "question": "How does a lighthouse send its beam?", "context_limit": 3 }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(response["answer"].as_str().unwrap().contains("The answer is in the provided context."));
+    assert_eq!(response["sources"][0]["title"], "Lighthouses");
+
+    // The stream carries the answer as it grows and ends with the full response
+    let streamed = client
+        .post(format!("{}/rag", server.url))
+        .bearer_auth(&server.token)
+        .json(&serde_json::json!({ "question": "How does a lighthouse send its beam?", "stream": true }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    assert!(streamed.headers()["content-type"].to_str().unwrap().starts_with("text/event-stream"));
+    let body = streamed.text().await.unwrap();
+    assert!(body.contains("event: token"), "{}", body);
+    assert!(!body.contains("event: error"), "{}", body);
+    let done = body
+        .split("\n\n")
+        .find_map(|event| event.strip_prefix("event: done\ndata: "))
+        .expect("done event");
+    let done: serde_json::Value = serde_json::from_str(done).unwrap();
+    assert!(done["answer"].as_str().unwrap().contains("The answer is in the provided context."));
+
+    core.shutdown().await.unwrap();
+}
@@ -1,16 +1,48 @@
 //! AI testing utilities and mocks
 
 use codex_core::{
-    ai::{AiEngine, AiStats, SystemMetricsSnapshot, TokenCacheStats},
-    config::AiConfig,
+    ai::{AiEngine, MockEmbeddingBackend, MockEngine},
+    ai::inference::{SystemMetricsSnapshot, TokenCacheStats},
+    config::{AiConfig, GpuLayers},
     CodexResult,
 };
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use std::collections::HashMap;
-use fake::{Fake, Faker};
+use fake::Fake;
 use fake::faker::lorem::en::*;
 
+/// Responses of [`offline_ai_engine`] to the prompts content import and RAG send
+///
+/// Patterns match the opening of each prompt, so document text cannot pick
+/// another response.
+pub fn offline_mock_engine() -> MockEngine {
+    MockEngine::new()
+        .respond(r"^Generate up to \d+ relevant tags", r#"{"tags": ["testing", "documents"]}"#)
+        .respond(r"^List the people, organizations", r#"{"entities": []}"#)
+        .respond(r"^Rate the difficulty", r#"{"level": 2}"#)
+        .respond(r"^Categorize the following content", "Technology")
+        .respond(r"^Please provide a concise summary", "A short summary of the document.")
+        .respond(r"^Extract the \d+ most important key points", "1. The first point\n2. The second point")
+        .respond(r"^Based on the following context", "The answer is in the provided context.")
+}
+
+/// AI engine answering with [`offline_mock_engine`] and hashed embeddings
+///
+/// Needs no model files, so tests using it run offline in CI.
+pub fn offline_ai_engine(config: &AiConfig) -> Arc<AiEngine> {
+    offline_ai_engine_with(config, offline_mock_engine())
+}
+
+/// AI engine answering with `engine`, e.g. one scripted to fail
+pub fn offline_ai_engine_with(config: &AiConfig, engine: MockEngine) -> Arc<AiEngine> {
+    Arc::new(AiEngine::with_backends(
+        config,
+        Arc::new(engine),
+        Arc::new(MockEmbeddingBackend::default()),
+    ))
+}
+
 /// Mock AI engine for testing
 pub struct MockAiEngine {
     responses: HashMap<String, String>,
@@ -101,23 +133,33 @@ impl MockAiEngine {
     /// Mock system metrics
     pub async fn get_system_metrics(&self) -> CodexResult<SystemMetricsSnapshot> {
         Ok(SystemMetricsSnapshot {
-            timestamp: chrono::Utc::now(),
-            process_memory_usage_mb: 125.5,
-            system_memory_usage_mb: 8192.0,
-            total_memory_mb: 16384.0,
-            process_cpu_usage: 15.2,
-            system_cpu_usage: 45.8,
-            uptime_seconds: 3600,
+            uptime: Duration::from_secs(3600),
+            process_memory_mb: 125.5,
+            process_memory_delta_mb: 0.0,
+            peak_memory_mb: 130.0,
+            peak_cpu_percent: 15.2,
+            system_memory_total_mb: 16384.0,
+            system_memory_used_mb: 8192.0,
+            system_memory_available_mb: 8192.0,
+            cpu_count: 8,
+            memory_snapshots_count: 1,
+            cpu_snapshots_count: 1,
+            gpu_metrics_available: false,
+            gpu_memory_used_mb: 0.0,
+            gpu_memory_total_mb: 0.0,
+            gpu_memory_process_mb: 0.0,
         })
     }
     
     /// Mock token cache stats
     pub async fn get_token_cache_stats(&self) -> CodexResult<TokenCacheStats> {
         Ok(TokenCacheStats {
-            total_tokens: 50000,
-            cache_size: 1000000,
-            hit_rate: 0.85,
-            evictions: 12,
+            current_token_count: 50000,
+            max_token_count: 1000000,
+            prompt_cache_size: 120,
+            sequence_cache_size: 80,
+            text_cache_size: 200,
+            memory_usage_mb: 12.5,
         })
     }
 }
@@ -215,8 +257,6 @@ impl AiPerformanceTest {
         Fut: std::future::Future<Output = CodexResult<String>>,
     {
         let start = Instant::now();
-        let first_token_time = Arc::new(std::sync::Mutex::new(None));
-        let first_token_time_clone = first_token_time.clone();
         
         // This is a simplified version - in real tests we'd use the streaming callback
         let _result = operation().await?;
@@ -259,14 +299,14 @@ impl TestAiConfig {
     /// Convert to AiConfig
     pub fn to_ai_config(&self) -> AiConfig {
         AiConfig {
-            model_path: self.model_path.clone(),
+            primary_model: self.model_path.clone(),
             max_tokens: self.max_tokens,
             temperature: self.temperature,
             top_p: 0.9,
-            repeat_penalty: 1.1,
             enable_caching: self.enable_caching,
             cache_size_mb: 256,
-            gpu_layers: 0,
+            gpu_layers: GpuLayers::Count(0),
+            ..AiConfig::default()
         }
     }
 }
@@ -1,15 +1,15 @@
 //! Content testing utilities and fixtures
 
 use codex_core::{
-    content::{ContentManager, SearchOptions, SearchType, SortBy, SortOrder},
-    db::models::{Document, DocumentCreateRequest},
-    CodexResult,
+    content::{SearchOptions, SearchType, SortBy, SortOrder},
+    db::models::Document,
 };
 use std::collections::HashMap;
 use std::path::PathBuf;
 use tempfile::TempDir;
-use fake::{Fake, Faker};
+use fake::Fake;
 use fake::faker::lorem::en::*;
+use fake::faker::name::en::Name;
 
 /// Content test environment with sample files
 pub struct ContentTestEnv {
@@ -192,46 +192,30 @@ pub struct SearchTestUtils;
 impl SearchTestUtils {
     /// Create test search options
     pub fn search_options() -> SearchOptions {
-        SearchOptions {
-            query: "test query".to_string(),
-            category: None,
-            tags: vec![],
-            search_type: SearchType::FullText,
-            limit: 10,
-            offset: 0,
-            sort_by: SortBy::Relevance,
-            sort_order: SortOrder::Desc,
-            include_content: true,
-        }
+        Self::search_options_with(None, SearchType::FullText)
     }
     
     /// Create search options with specific parameters
-    pub fn search_options_with(
-        query: &str,
-        category: Option<&str>,
-        search_type: SearchType,
-    ) -> SearchOptions {
+    pub fn search_options_with(category: Option<&str>, search_type: SearchType) -> SearchOptions {
         SearchOptions {
-            query: query.to_string(),
             category: category.map(|s| s.to_string()),
-            tags: vec![],
             search_type,
             limit: 10,
             offset: 0,
             sort_by: SortBy::Relevance,
-            sort_order: SortOrder::Desc,
-            include_content: true,
+            sort_order: SortOrder::Descending,
+            ..SearchOptions::default()
         }
     }
     
     /// Create semantic search options
-    pub fn semantic_search_options(query: &str) -> SearchOptions {
-        Self::search_options_with(query, None, SearchType::Semantic)
+    pub fn semantic_search_options() -> SearchOptions {
+        Self::search_options_with(None, SearchType::Semantic)
     }
     
     /// Create category filter options
-    pub fn category_search_options(query: &str, category: &str) -> SearchOptions {
-        Self::search_options_with(query, Some(category), SearchType::FullText)
+    pub fn category_search_options(category: &str) -> SearchOptions {
+        Self::search_options_with(Some(category), SearchType::FullText)
     }
 }
 
@@ -311,29 +295,25 @@ pub struct ContentGenerator;
 
 impl ContentGenerator {
     /// Generate a random document
-    pub fn random_document() -> DocumentCreateRequest {
-        DocumentCreateRequest {
-            title: Words(2..6).fake::<String>(),
-            content: Paragraphs(5..15).fake::<Vec<String>>().join("\n\n"),
-            summary: Some(Sentence(15..30).fake()),
-            author: Some(Name().fake()),
-            category: Some(Self::random_category()),
-            tags: Some(Self::random_tags()),
-            language: Some("en".to_string()),
-            reading_time: Some((60..1800).fake()), // 1-30 minutes
-            difficulty_level: Some((1..=5).fake()),
-            source_url: None,
-            file_path: None,
-            file_hash: None,
-        }
+    pub fn random_document() -> Document {
+        let mut document = Document::new(
+            Words(2..6).fake::<Vec<String>>().join(" "),
+            Paragraphs(5..15).fake::<Vec<String>>().join("\n\n"),
+            "text/plain".to_string(),
+        );
+        document.summary = Some(Sentence(15..30).fake());
+        document.author = Some(Name().fake());
+        document.category = Some(Self::random_category());
+        document.set_tags(Self::random_tags());
+        document.reading_time = Some((1..30).fake()); // minutes
+        document.difficulty_level = Some((1..=5).fake());
+        document
     }
     
     /// Generate random category
     fn random_category() -> String {
-        ["Philosophy", "Science", "Technology", "Health", "Literature", "History", "Arts"]
-            .choose(&mut rand::thread_rng())
-            .unwrap()
-            .to_string()
+        let categories = ["Philosophy", "Science", "Technology", "Health", "Literature", "History", "Arts"];
+        categories[(0..categories.len()).fake::<usize>()].to_string()
     }
     
     /// Generate random tags
@@ -343,7 +323,7 @@ impl ContentGenerator {
     }
     
     /// Generate documents for a specific category
-    pub fn documents_for_category(category: &str, count: usize) -> Vec<DocumentCreateRequest> {
+    pub fn documents_for_category(category: &str, count: usize) -> Vec<Document> {
         (0..count)
             .map(|_| {
                 let mut doc = Self::random_document();
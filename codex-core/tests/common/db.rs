@@ -1,22 +1,24 @@
 //! Database testing utilities and fixtures
 
 use codex_core::{
-    db::{DatabaseManager, models::{Document, DocumentCreateRequest, Embedding}},
+    db::{DatabaseManager, DocumentQueries, EmbeddingQueries, models::{Document, Embedding}},
     config::DatabaseConfig,
     CodexResult,
 };
 use sqlx::SqlitePool;
-use std::collections::HashMap;
-use uuid::Uuid;
-use chrono::{DateTime, Utc};
-use fake::{Fake, Faker};
+use std::sync::Arc;
+use tempfile::TempDir;
+use fake::Fake;
 use fake::faker::lorem::en::*;
+use fake::faker::name::en::Name;
 
 /// Test database manager with automatic cleanup
 pub struct TestDatabase {
-    pub manager: DatabaseManager,
+    pub manager: Arc<DatabaseManager>,
     pub pool: SqlitePool,
     pub config: DatabaseConfig,
+    /// Holds the database file; removed on drop
+    pub temp_dir: TempDir,
 }
 
 impl TestDatabase {
@@ -26,41 +28,42 @@ impl TestDatabase {
         let db_path = temp_dir.path().join("test.db");
         
         let config = DatabaseConfig {
-            url: format!("sqlite:{}", db_path.display()),
+            path: db_path,
             max_connections: 5,
-            acquire_timeout_seconds: 30,
-            idle_timeout_seconds: 600,
-            max_lifetime_seconds: 1800,
+            connection_timeout: 30,
+            enable_wal: true,
+            enable_foreign_keys: true,
         };
         
-        let manager = DatabaseManager::new(&config).await?;
+        let manager = Arc::new(DatabaseManager::new(&config).await?);
         let pool = manager.pool().clone();
         
         Ok(Self {
             manager,
             pool,
             config,
+            temp_dir,
         })
     }
     
     /// Create a test document with random data
     pub async fn create_test_document(&self) -> CodexResult<Document> {
-        let request = DocumentCreateRequest {
-            title: Words(2..5).fake::<String>(),
-            content: Paragraphs(3..8).fake::<Vec<String>>().join("\n\n"),
-            summary: Some(Sentence(10..20).fake()),
-            author: Some(Name().fake()),
-            category: Some(["Philosophy", "Science", "Technology", "Health"].choose(&mut rand::thread_rng()).unwrap().to_string()),
-            tags: Some(vec![Word().fake(), Word().fake()]),
-            language: Some("en".to_string()),
-            reading_time: Some((100..600).fake()),
-            difficulty_level: Some((1..=5).fake()),
-            source_url: None,
-            file_path: None,
-            file_hash: None,
-        };
+        let categories = ["Philosophy", "Science", "Technology", "Health"];
+        let tags: Vec<String> = (0..2).map(|_| Word().fake()).collect();
+        let document = DocumentBuilder::new()
+            .category(categories[(0..categories.len()).fake::<usize>()])
+            .tags(tags.iter().map(String::as_str).collect())
+            .reading_time((100..600).fake())
+            .difficulty((1..=5).fake())
+            .build();
         
-        self.manager.content().create_document(request).await
+        self.insert(&document).await?;
+        Ok(document)
+    }
+    
+    /// Insert a document built with [`DocumentBuilder`] or [`SampleData`]
+    pub async fn insert(&self, document: &Document) -> CodexResult<()> {
+        DocumentQueries::create(&self.pool, document, self.manager.compression()).await
     }
     
     /// Create multiple test documents
@@ -74,15 +77,19 @@ impl TestDatabase {
     
     /// Create a test embedding
     pub async fn create_test_embedding(&self, doc_id: &str) -> CodexResult<Embedding> {
-        let vector: Vec<f32> = (0..384).map(|_| rand::random::<f32>()).collect();
+        let vector: Vec<f32> = (0..384).map(|_| (-1.0..1.0).fake::<f32>()).collect();
+        let embedding = Embedding::new(
+            doc_id.to_string(),
+            vector,
+            "test-model".to_string(),
+            0,
+            String::new(),
+            0,
+            0,
+        );
         
-        Embedding::create(
-            &self.pool,
-            doc_id,
-            &vector,
-            "test-model",
-            384,
-        ).await
+        EmbeddingQueries::create(&self.pool, &embedding).await?;
+        Ok(embedding)
     }
     
     /// Clean up test data
@@ -103,15 +110,14 @@ impl TestDatabase {
     
     /// Assert document exists
     pub async fn assert_document_exists(&self, id: &str) -> CodexResult<Document> {
-        self.manager.content()
-            .get_document_by_id(id)
+        DocumentQueries::get_by_id(&self.pool, id)
             .await?
-            .ok_or_else(|| anyhow::anyhow!("Document {} not found", id))
+            .ok_or_else(|| anyhow::anyhow!("Document {} not found", id).into())
     }
     
     /// Assert document does not exist
     pub async fn assert_document_not_exists(&self, id: &str) -> CodexResult<()> {
-        let doc = self.manager.content().get_document_by_id(id).await?;
+        let doc = DocumentQueries::get_by_id(&self.pool, id).await?;
         if doc.is_some() {
             return Err(anyhow::anyhow!("Document {} should not exist", id).into());
         }
@@ -126,8 +132,8 @@ pub struct DocumentBuilder {
     content: Option<String>,
     category: Option<String>,
     tags: Option<Vec<String>>,
-    reading_time: Option<i32>,
-    difficulty_level: Option<i32>,
+    reading_time: Option<i64>,
+    difficulty_level: Option<i64>,
 }
 
 impl DocumentBuilder {
@@ -155,31 +161,31 @@ impl DocumentBuilder {
         self
     }
     
-    pub fn reading_time(mut self, time: i32) -> Self {
+    pub fn reading_time(mut self, time: i64) -> Self {
         self.reading_time = Some(time);
         self
     }
     
-    pub fn difficulty(mut self, level: i32) -> Self {
+    pub fn difficulty(mut self, level: i64) -> Self {
         self.difficulty_level = Some(level);
         self
     }
     
-    pub fn build(self) -> DocumentCreateRequest {
-        DocumentCreateRequest {
-            title: self.title.unwrap_or_else(|| Words(2..5).fake()),
-            content: self.content.unwrap_or_else(|| Paragraphs(3..8).fake::<Vec<String>>().join("\n\n")),
-            summary: Some(Sentence(10..20).fake()),
-            author: Some(Name().fake()),
-            category: self.category,
-            tags: self.tags,
-            language: Some("en".to_string()),
-            reading_time: self.reading_time,
-            difficulty_level: self.difficulty_level,
-            source_url: None,
-            file_path: None,
-            file_hash: None,
+    pub fn build(self) -> Document {
+        let mut document = Document::new(
+            self.title.unwrap_or_else(|| Words(2..5).fake::<Vec<String>>().join(" ")),
+            self.content.unwrap_or_else(|| Paragraphs(3..8).fake::<Vec<String>>().join("\n\n")),
+            "text/plain".to_string(),
+        );
+        document.summary = Some(Sentence(10..20).fake());
+        document.author = Some(Name().fake());
+        document.category = self.category;
+        if let Some(tags) = self.tags {
+            document.set_tags(tags);
         }
+        document.reading_time = self.reading_time;
+        document.difficulty_level = self.difficulty_level;
+        document
     }
 }
 
@@ -188,7 +194,7 @@ pub struct SampleData;
 
 impl SampleData {
     /// Philosophy documents
-    pub fn philosophy_docs() -> Vec<DocumentCreateRequest> {
+    pub fn philosophy_docs() -> Vec<Document> {
         vec![
             DocumentBuilder::new()
                 .title("Introduction to Stoicism")
@@ -208,7 +214,7 @@ impl SampleData {
     }
     
     /// Science documents
-    pub fn science_docs() -> Vec<DocumentCreateRequest> {
+    pub fn science_docs() -> Vec<Document> {
         vec![
             DocumentBuilder::new()
                 .title("Quantum Computing Basics")
@@ -1,8 +1,6 @@
 //! Test fixtures and sample data for comprehensive testing
 
 use std::collections::HashMap;
-use chrono::{DateTime, Utc};
-use uuid::Uuid;
 
 /// Complete test dataset with realistic content
 pub struct TestFixtures;
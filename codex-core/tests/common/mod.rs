@@ -2,15 +2,31 @@
 //!
 //! This module provides shared test infrastructure including:
 //! - Database setup and teardown
-//! - Mock AI models and responses
+//! - Mock AI models and responses, see [`ai::offline_ai_engine`]
 //! - Sample data generation
 //! - Test assertions and helpers
 
+// Each test file uses a different part of this module
+#![allow(dead_code)]
+
 pub mod db;
 pub mod ai;
 pub mod content;
 pub mod fixtures;
 
+/// Small helpers shared by the test files
+pub mod test_utils {
+    use tempfile::TempDir;
+
+    /// Temporary directory whose name starts with `prefix`, removed on drop
+    pub fn create_temp_dir(prefix: &str) -> TempDir {
+        tempfile::Builder::new()
+            .prefix(prefix)
+            .tempdir()
+            .expect("Failed to create temporary directory")
+    }
+}
+
 use std::path::PathBuf;
 use tempfile::TempDir;
 use uuid::Uuid;
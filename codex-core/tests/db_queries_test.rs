@@ -2,14 +2,13 @@
 
 mod common;
 
-use common::db::{TestDatabase, DocumentBuilder, SampleData};
+use common::db::{TestDatabase, DocumentBuilder};
 use codex_core::{
     db::queries::*,
-    db::models::{Document, DocumentCreateRequest},
     CodexResult,
 };
 use rstest::*;
-use sqlx::Row;
+use std::collections::HashSet;
 
 /// Test basic document insertion and retrieval
 #[rstest]
@@ -17,7 +16,7 @@ use sqlx::Row;
 async fn test_document_crud_operations() -> CodexResult<()> {
     let db = TestDatabase::new().await?;
     
-    let request = DocumentBuilder::new()
+    let created_doc = DocumentBuilder::new()
         .title("CRUD Test Document")
         .content("This document tests create, read, update, delete operations.")
         .category("Technology")
@@ -26,37 +25,34 @@ async fn test_document_crud_operations() -> CodexResult<()> {
         .build();
     
     // Create
-    let created_doc = db.manager.content().create_document(request).await?;
+    DocumentQueries::create(&db.pool, &created_doc, db.manager.compression()).await?;
     assert!(!created_doc.id.is_empty());
     
     // Read
-    let retrieved_doc = db.manager.content()
-        .get_document_by_id(&created_doc.id)
+    let retrieved_doc = DocumentQueries::get_by_id(&db.pool, &created_doc.id)
         .await?
         .expect("Document should exist");
     assert_eq!(retrieved_doc.title, "CRUD Test Document");
     assert_eq!(retrieved_doc.category, Some("Technology".to_string()));
     
     // Update
-    let update_request = DocumentBuilder::new()
-        .title("Updated CRUD Test Document")
-        .content("Updated content for testing.")
-        .category("Science")
-        .difficulty(3)
-        .build();
-    
-    let updated_doc = db.manager.content()
-        .update_document(&created_doc.id, update_request)
-        .await?;
+    let mut update = retrieved_doc.clone();
+    update.title = "Updated CRUD Test Document".to_string();
+    update.content = "Updated content for testing.".to_string();
+    update.category = Some("Science".to_string());
+    update.difficulty_level = Some(3);
+    
+    DocumentQueries::update(&db.pool, &update, db.manager.compression()).await?;
+    let updated_doc = DocumentQueries::get_by_id(&db.pool, &created_doc.id)
+        .await?
+        .expect("Document should exist");
     assert_eq!(updated_doc.title, "Updated CRUD Test Document");
     assert_eq!(updated_doc.category, Some("Science".to_string()));
     assert_eq!(updated_doc.difficulty_level, Some(3));
     
     // Delete
-    db.manager.content().delete_document(&created_doc.id).await?;
-    let deleted_doc = db.manager.content()
-        .get_document_by_id(&created_doc.id)
-        .await?;
+    DocumentQueries::delete(&db.pool, &created_doc.id).await?;
+    let deleted_doc = DocumentQueries::get_by_id(&db.pool, &created_doc.id).await?;
     assert!(deleted_doc.is_none());
     
     Ok(())
@@ -70,28 +66,33 @@ async fn test_document_listing_pagination() -> CodexResult<()> {
     
     // Create 25 test documents
     for i in 0..25 {
-        let request = DocumentBuilder::new()
+        let document = DocumentBuilder::new()
             .title(&format!("Pagination Test Document {}", i + 1))
             .content(&format!("Content for document number {}", i + 1))
+            .category("Pagination")
             .build();
         
-        db.manager.content().create_document(request).await?;
+        db.insert(&document).await?;
     }
     
     // Test first page (limit 10)
-    let page1 = db.manager.content().list_documents(Some(10), Some(0)).await?;
+    let page1 = DocumentQueries::get_by_category(&db.pool, "Pagination", 10, 0).await?;
     assert_eq!(page1.len(), 10);
     
     // Test second page
-    let page2 = db.manager.content().list_documents(Some(10), Some(10)).await?;
+    let page2 = DocumentQueries::get_by_category(&db.pool, "Pagination", 10, 10).await?;
     assert_eq!(page2.len(), 10);
     
     // Test third page (partial)
-    let page3 = db.manager.content().list_documents(Some(10), Some(20)).await?;
+    let page3 = DocumentQueries::get_by_category(&db.pool, "Pagination", 10, 20).await?;
     assert_eq!(page3.len(), 5);
     
+    // Pages do not overlap
+    let ids: HashSet<_> = page1.iter().chain(&page2).chain(&page3).map(|d| &d.id).collect();
+    assert_eq!(ids.len(), 25);
+    
     // Test no limit
-    let all_docs = db.manager.content().list_documents(None, None).await?;
+    let all_docs = DocumentQueries::get_all(&db.pool).await?;
     assert!(all_docs.len() >= 25);
     
     // Verify documents are properly ordered (by creation time, newest first)
@@ -109,24 +110,22 @@ async fn test_document_filtering_by_category() -> CodexResult<()> {
     let db = TestDatabase::new().await?;
     
     // Create documents in different categories
-    let categories = vec!["Philosophy", "Science", "Technology", "Health"];
+    let categories = ["Philosophy", "Science", "Technology", "Health"];
     for category in &categories {
         for i in 0..3 {
-            let request = DocumentBuilder::new()
+            let document = DocumentBuilder::new()
                 .title(&format!("{} Document {}", category, i + 1))
                 .content(&format!("Content about {}", category))
                 .category(category)
                 .build();
             
-            db.manager.content().create_document(request).await?;
+            db.insert(&document).await?;
         }
     }
     
     // Test filtering by each category
     for category in &categories {
-        let docs = db.manager.content()
-            .list_documents_by_category(category, None, None)
-            .await?;
+        let docs = DocumentQueries::get_by_category(&db.pool, category, 100, 0).await?;
         
         assert_eq!(docs.len(), 3);
         for doc in &docs {
@@ -135,9 +134,7 @@ async fn test_document_filtering_by_category() -> CodexResult<()> {
     }
     
     // Test non-existent category
-    let empty_docs = db.manager.content()
-        .list_documents_by_category("NonExistent", None, None)
-        .await?;
+    let empty_docs = DocumentQueries::get_by_category(&db.pool, "NonExistent", 100, 0).await?;
     assert!(empty_docs.is_empty());
     
     Ok(())
@@ -150,7 +147,7 @@ async fn test_document_filtering_by_tags() -> CodexResult<()> {
     let db = TestDatabase::new().await?;
     
     // Create documents with overlapping tags
-    let docs_data = vec![
+    let docs_data = [
         ("Doc 1", vec!["rust", "programming", "systems"]),
         ("Doc 2", vec!["rust", "web", "backend"]),
         ("Doc 3", vec!["javascript", "web", "frontend"]),
@@ -159,37 +156,34 @@ async fn test_document_filtering_by_tags() -> CodexResult<()> {
     ];
     
     for (title, tags) in &docs_data {
-        let request = DocumentBuilder::new()
+        let document = DocumentBuilder::new()
             .title(title)
             .content(&format!("Content for {}", title))
-            .tags(tags.iter().map(|s| s.to_string()).collect())
+            .tags(tags.clone())
             .build();
         
-        db.manager.content().create_document(request).await?;
+        db.insert(&document).await?;
     }
     
+    let by_tags = |tags: &[&str]| SearchFilter {
+        tags: tags.iter().map(|t| t.to_string()).collect(),
+        ..Default::default()
+    };
+    
     // Test single tag filter
-    let rust_docs = db.manager.content()
-        .list_documents_by_tags(&["rust".to_string()], None, None)
-        .await?;
+    let rust_docs = SearchQueries::list_filtered(&db.pool, &by_tags(&["rust"]), 100).await?;
     assert_eq!(rust_docs.len(), 3);
     
-    let web_docs = db.manager.content()
-        .list_documents_by_tags(&["web".to_string()], None, None)
-        .await?;
+    let web_docs = SearchQueries::list_filtered(&db.pool, &by_tags(&["web"]), 100).await?;
     assert_eq!(web_docs.len(), 2);
     
     // Test multiple tag filter (AND operation)
-    let rust_ai_docs = db.manager.content()
-        .list_documents_by_tags(&["rust".to_string(), "ai".to_string()], None, None)
-        .await?;
+    let rust_ai_docs = SearchQueries::list_filtered(&db.pool, &by_tags(&["rust", "ai"]), 100).await?;
     assert_eq!(rust_ai_docs.len(), 1);
     assert_eq!(rust_ai_docs[0].title, "Doc 5");
     
     // Test non-existent tag
-    let empty_docs = db.manager.content()
-        .list_documents_by_tags(&["nonexistent".to_string()], None, None)
-        .await?;
+    let empty_docs = SearchQueries::list_filtered(&db.pool, &by_tags(&["nonexistent"]), 100).await?;
     assert!(empty_docs.is_empty());
     
     Ok(())
@@ -204,12 +198,12 @@ async fn test_recent_documents() -> CodexResult<()> {
     // Create documents with some delay between them
     let mut created_docs = Vec::new();
     for i in 0..5 {
-        let request = DocumentBuilder::new()
+        let doc = DocumentBuilder::new()
             .title(&format!("Recent Document {}", i + 1))
             .content(&format!("Content for recent document {}", i + 1))
             .build();
         
-        let doc = db.manager.content().create_document(request).await?;
+        db.insert(&doc).await?;
         created_docs.push(doc);
         
         // Small delay to ensure different timestamps
@@ -217,7 +211,7 @@ async fn test_recent_documents() -> CodexResult<()> {
     }
     
     // Get recent documents
-    let recent = db.manager.content().get_recent_documents(3).await?;
+    let recent = DocumentQueries::get_recent(&db.pool, 3).await?;
     assert_eq!(recent.len(), 3);
     
     // Should be ordered by creation time (newest first)
@@ -226,12 +220,46 @@ async fn test_recent_documents() -> CodexResult<()> {
     assert_eq!(recent[2].title, "Recent Document 3");
     
     // Test with larger limit than available documents
-    let all_recent = db.manager.content().get_recent_documents(10).await?;
+    let all_recent = DocumentQueries::get_recent(&db.pool, 10).await?;
     assert!(all_recent.len() >= 5);
     
     Ok(())
 }
 
+/// Tag filters of the created-since query apply before its limit
+#[rstest]
+#[tokio::test]
+async fn test_created_since_filters_tags_before_limit() -> CodexResult<()> {
+    let db = TestDatabase::new().await?;
+    let since = (chrono::Utc::now() - chrono::Duration::hours(1)).to_rfc3339();
+
+    for (title, tags) in [
+        ("Captured note", vec!["inbox"]),
+        ("Plain note", vec!["reading"]),
+        ("Digest one", vec!["digest"]),
+        ("Digest two", vec!["digest"]),
+        ("Digest three", vec!["Digest"]),
+    ] {
+        let doc = DocumentBuilder::new().title(title).tags(tags).build();
+        db.insert(&doc).await?;
+        tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+    }
+
+    // The three digests are the newest, yet the older documents still fill the limit
+    let without_digests = DocumentQueries::get_created_since(&db.pool, &since, None, Some("digest"), 2).await?;
+    let titles: Vec<_> = without_digests.iter().map(|d| d.title.as_str()).collect();
+    assert_eq!(titles, vec!["Plain note", "Captured note"]);
+
+    let inbox = DocumentQueries::get_created_since(&db.pool, &since, Some("inbox"), None, 1).await?;
+    assert_eq!(inbox.len(), 1);
+    assert_eq!(inbox[0].title, "Captured note");
+
+    let unfiltered = DocumentQueries::get_created_since(&db.pool, &since, None, None, 10).await?;
+    assert_eq!(unfiltered.len(), 5);
+
+    Ok(())
+}
+
 /// Test document view count tracking
 #[rstest]
 #[tokio::test]
@@ -243,13 +271,11 @@ async fn test_document_view_tracking() -> CodexResult<()> {
     
     // Increment view count multiple times
     for expected_count in 1..=5 {
-        db.manager.content().increment_view_count(&document.id).await?;
+        DocumentQueries::update_access(&db.pool, &document.id).await?;
         
-        let updated_doc = db.manager.content()
-            .get_document_by_id(&document.id)
-            .await?
-            .unwrap();
+        let updated_doc = db.assert_document_exists(&document.id).await?;
         assert_eq!(updated_doc.view_count, expected_count);
+        assert!(updated_doc.last_accessed.is_some());
     }
     
     Ok(())
@@ -265,19 +291,13 @@ async fn test_favorite_operations() -> CodexResult<()> {
     assert!(!document.is_favorite);
     
     // Set as favorite
-    db.manager.content().set_favorite(&document.id, true).await?;
-    let updated_doc = db.manager.content()
-        .get_document_by_id(&document.id)
-        .await?
-        .unwrap();
+    assert!(DocumentQueries::set_favorite(&db.pool, &document.id, true).await?);
+    let updated_doc = db.assert_document_exists(&document.id).await?;
     assert!(updated_doc.is_favorite);
     
     // Unset favorite
-    db.manager.content().set_favorite(&document.id, false).await?;
-    let updated_doc = db.manager.content()
-        .get_document_by_id(&document.id)
-        .await?
-        .unwrap();
+    DocumentQueries::set_favorite(&db.pool, &document.id, false).await?;
+    let updated_doc = db.assert_document_exists(&document.id).await?;
     assert!(!updated_doc.is_favorite);
     
     // Test listing favorite documents
@@ -285,10 +305,10 @@ async fn test_favorite_operations() -> CodexResult<()> {
     let doc2 = db.create_test_document().await?;
     let doc3 = db.create_test_document().await?;
     
-    db.manager.content().set_favorite(&doc1.id, true).await?;
-    db.manager.content().set_favorite(&doc3.id, true).await?;
+    DocumentQueries::set_favorite(&db.pool, &doc1.id, true).await?;
+    DocumentQueries::set_favorite(&db.pool, &doc3.id, true).await?;
     
-    let favorites = db.manager.content().get_favorite_documents(None, None).await?;
+    let favorites = DocumentQueries::get_favorites(&db.pool, 100).await?;
     assert_eq!(favorites.len(), 2);
     
     let favorite_ids: Vec<_> = favorites.iter().map(|d| &d.id).collect();
@@ -306,7 +326,7 @@ async fn test_document_statistics() -> CodexResult<()> {
     let db = TestDatabase::new().await?;
     
     // Create documents with known properties
-    let docs_data = vec![
+    let docs_data = [
         ("Category", "Philosophy", vec!["philosophy", "ethics"], 3),
         ("Category", "Philosophy", vec!["philosophy", "logic"], 4),
         ("Category", "Science", vec!["physics", "quantum"], 5),
@@ -315,37 +335,37 @@ async fn test_document_statistics() -> CodexResult<()> {
     ];
     
     for (_, category, tags, difficulty) in &docs_data {
-        let request = DocumentBuilder::new()
+        let document = DocumentBuilder::new()
             .title("Stats Test Document")
             .content("Content for statistics testing")
             .category(category)
-            .tags(tags.iter().map(|s| s.to_string()).collect())
+            .tags(tags.clone())
             .difficulty(*difficulty)
             .build();
         
-        db.manager.content().create_document(request).await?;
+        db.insert(&document).await?;
     }
     
     // Test total document count
-    let total_count = db.manager.content().get_document_count().await?;
+    let total_count = db.manager.get_stats().await?.document_count;
     assert!(total_count >= 5);
     
     // Test count by category
-    let philosophy_count = db.manager.content()
-        .get_document_count_by_category("Philosophy")
-        .await?;
+    let philosophy_count = DocumentQueries::get_by_category(&db.pool, "Philosophy", 100, 0).await?.len();
     assert_eq!(philosophy_count, 2);
     
-    let science_count = db.manager.content()
-        .get_document_count_by_category("Science")
-        .await?;
+    let science_count = DocumentQueries::get_by_category(&db.pool, "Science", 100, 0).await?.len();
     assert_eq!(science_count, 2);
     
     // Test category list
-    let categories = db.manager.content().get_categories().await?;
-    assert!(categories.contains(&"Philosophy".to_string()));
-    assert!(categories.contains(&"Science".to_string()));
-    assert!(categories.contains(&"Technology".to_string()));
+    let categories: HashSet<String> = DocumentQueries::get_title_entries(&db.pool, None)
+        .await?
+        .into_iter()
+        .filter_map(|t| t.category)
+        .collect();
+    assert!(categories.contains("Philosophy"));
+    assert!(categories.contains("Science"));
+    assert!(categories.contains("Technology"));
     
     Ok(())
 }
@@ -361,36 +381,32 @@ async fn test_batch_operations() -> CodexResult<()> {
     let doc_ids: Vec<String> = documents.iter().map(|d| d.id.clone()).collect();
     
     // Test batch retrieval
-    let retrieved_docs = db.manager.content()
-        .get_documents_by_ids(&doc_ids)
-        .await?;
+    let retrieved_docs = DocumentQueries::get_title_entries(&db.pool, Some(&doc_ids)).await?;
     assert_eq!(retrieved_docs.len(), 10);
     
     // Test batch favorite setting
     let favorite_ids = &doc_ids[0..5];
-    db.manager.content()
-        .set_favorites_batch(favorite_ids, true)
-        .await?;
+    for id in favorite_ids {
+        DocumentQueries::set_favorite(&db.pool, id, true).await?;
+    }
     
-    let favorites = db.manager.content().get_favorite_documents(None, None).await?;
+    let favorites = DocumentQueries::get_favorites(&db.pool, 100).await?;
     assert_eq!(favorites.len(), 5);
     
     // Test batch deletion
     let delete_ids = &doc_ids[7..10];
-    db.manager.content()
-        .delete_documents_batch(delete_ids)
-        .await?;
+    for id in delete_ids {
+        DocumentQueries::delete(&db.pool, id).await?;
+    }
     
     // Verify deletion
     for id in delete_ids {
-        let doc = db.manager.content().get_document_by_id(id).await?;
-        assert!(doc.is_none());
+        db.assert_document_not_exists(id).await?;
     }
     
     // Verify remaining documents still exist
     for id in &doc_ids[0..7] {
-        let doc = db.manager.content().get_document_by_id(id).await?;
-        assert!(doc.is_some());
+        db.assert_document_exists(id).await?;
     }
     
     Ok(())
@@ -408,19 +424,16 @@ async fn test_query_performance() -> CodexResult<()> {
     
     let start = std::time::Instant::now();
     for i in 0..doc_count {
-        let request = DocumentBuilder::new()
+        let tag = format!("tag{}", i % 10);
+        let document = DocumentBuilder::new()
             .title(&format!("Performance Test Document {}", i + 1))
             .content(&format!("Content for performance testing document number {}", i + 1))
             .category(if i % 3 == 0 { "Philosophy" } else if i % 3 == 1 { "Science" } else { "Technology" })
-            .tags(vec![
-                format!("tag{}", i % 10),
-                "performance".to_string(),
-                "test".to_string(),
-            ])
+            .tags(vec![&tag, "performance", "test"])
             .difficulty((i % 5) + 1)
             .build();
         
-        db.manager.content().create_document(request).await?;
+        db.insert(&document).await?;
         
         // Log progress every 100 documents
         if (i + 1) % 100 == 0 {
@@ -433,25 +446,23 @@ async fn test_query_performance() -> CodexResult<()> {
     
     // Test retrieval performance
     let start = std::time::Instant::now();
-    let all_docs = db.manager.content().list_documents(None, None).await?;
+    let all_docs = DocumentQueries::get_all(&db.pool).await?;
     let retrieval_time = start.elapsed();
     
-    assert!(all_docs.len() >= doc_count);
+    assert!(all_docs.len() >= doc_count as usize);
     assert!(retrieval_time.as_millis() < 500, "Document retrieval took too long: {:?}", retrieval_time);
     
     // Test category filtering performance
     let start = std::time::Instant::now();
-    let philosophy_docs = db.manager.content()
-        .list_documents_by_category("Philosophy", None, None)
-        .await?;
+    let philosophy_docs = DocumentQueries::get_by_category(&db.pool, "Philosophy", doc_count, 0).await?;
     let category_filter_time = start.elapsed();
     
-    assert!(philosophy_docs.len() > 0);
+    assert!(!philosophy_docs.is_empty());
     assert!(category_filter_time.as_millis() < 100, "Category filtering took too long: {:?}", category_filter_time);
     
     // Test pagination performance
     let start = std::time::Instant::now();
-    let page = db.manager.content().list_documents(Some(50), Some(500)).await?;
+    let page = DocumentQueries::get_by_category(&db.pool, "Science", 50, 100).await?;
     let pagination_time = start.elapsed();
     
     assert_eq!(page.len(), 50);
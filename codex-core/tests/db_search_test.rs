@@ -2,14 +2,34 @@
 
 mod common;
 
+use common::ai::offline_ai_engine;
 use common::db::{TestDatabase, DocumentBuilder, SampleData};
-use common::fixtures::{TestFixtures, SearchTestCase};
+use common::fixtures::TestFixtures;
 use codex_core::{
-    db::search::*,
-    content::{SearchOptions, SearchType, SortBy, SortOrder},
+    config::{AiConfig, ContentConfig},
+    db::{search::*, DocumentQueries, SearchQueries},
+    content::{SearchEngine, SearchOptions, SearchType, SortBy, SortOrder},
     CodexResult,
 };
 use rstest::*;
+use std::sync::Arc;
+
+/// Search engine over the test database, with the offline AI engine
+async fn search_engine(db: &TestDatabase) -> CodexResult<SearchEngine> {
+    let ai = offline_ai_engine(&AiConfig::default());
+    ai.set_database(Arc::clone(&db.manager));
+    Ok(SearchEngine::new(Arc::clone(&db.manager), ai, &ContentConfig::default()).await?)
+}
+
+/// Full-text search options, most relevant first
+fn full_text(limit: usize, offset: usize) -> SearchOptions {
+    SearchOptions {
+        search_type: SearchType::FullText,
+        limit,
+        offset,
+        ..SearchOptions::default()
+    }
+}
 
 /// Test FTS5 full-text search functionality
 #[rstest]
@@ -18,7 +38,7 @@ async fn test_fts5_full_text_search() -> CodexResult<()> {
     let db = TestDatabase::new().await?;
     
     // Create documents with searchable content
-    let docs_data = vec![
+    let docs_data = [
         ("Stoic Philosophy", "Stoicism teaches virtue, wisdom, and emotional resilience through rational thinking."),
         ("Quantum Physics", "Quantum mechanics describes the behavior of matter and energy at atomic scales."),
         ("Machine Learning", "Artificial intelligence uses algorithms to learn patterns from data."),
@@ -26,12 +46,12 @@ async fn test_fts5_full_text_search() -> CodexResult<()> {
     ];
     
     for (title, content) in &docs_data {
-        let request = DocumentBuilder::new()
+        let document = DocumentBuilder::new()
             .title(title)
             .content(content)
             .build();
         
-        db.manager.content().create_document(request).await?;
+        db.insert(&document).await?;
     }
     
     // Test simple word search
@@ -44,12 +64,12 @@ async fn test_fts5_full_text_search() -> CodexResult<()> {
     assert_eq!(results.len(), 1);
     assert!(results[0].title.contains("Quantum"));
     
-    // Test multiple terms (OR search)
-    let results = Search::fts5(&db.pool, "quantum OR art", 10).await?;
+    // Test multiple terms (any term matches)
+    let results = SearchQueries::search(&db.pool, "quantum renaissance", Some(10)).await?;
     assert_eq!(results.len(), 2);
     
-    // Test wildcard search
-    let results = Search::fts5(&db.pool, "learn*", 10).await?;
+    // Test prefix search
+    let results = SearchQueries::search(&db.pool, "learn", Some(10)).await?;
     assert_eq!(results.len(), 1);
     assert!(results[0].title.contains("Machine"));
     
@@ -67,7 +87,7 @@ async fn test_search_ranking() -> CodexResult<()> {
     let db = TestDatabase::new().await?;
     
     // Create documents with varying relevance for the term "programming"
-    let docs_data = vec![
+    let docs_data = [
         ("Programming Fundamentals", "Programming is the art of writing code. Programming involves logic, algorithms, and problem-solving. Programming languages include Python, Rust, and JavaScript."), // High relevance
         ("Software Development", "Software development includes programming, testing, and deployment. Good programming practices are essential."), // Medium relevance
         ("Computer Science Overview", "Computer science covers many topics including programming, databases, and networks."), // Low relevance
@@ -75,12 +95,12 @@ async fn test_search_ranking() -> CodexResult<()> {
     ];
     
     for (title, content) in &docs_data {
-        let request = DocumentBuilder::new()
+        let document = DocumentBuilder::new()
             .title(title)
             .content(content)
             .build();
         
-        db.manager.content().create_document(request).await?;
+        db.insert(&document).await?;
     }
     
     let results = Search::fts5(&db.pool, "programming", 10).await?;
@@ -106,50 +126,37 @@ async fn test_search_with_filters() -> CodexResult<()> {
     let philosophy_docs = SampleData::philosophy_docs();
     let science_docs = SampleData::science_docs();
     
-    for doc_request in philosophy_docs.into_iter().chain(science_docs.into_iter()) {
-        db.manager.content().create_document(doc_request).await?;
+    for document in philosophy_docs.into_iter().chain(science_docs) {
+        db.insert(&document).await?;
     }
+    let engine = search_engine(&db).await?;
     
     // Test category filter
     let options = SearchOptions {
-        query: "philosophy".to_string(),
         category: Some("Philosophy".to_string()),
-        tags: vec![],
-        search_type: SearchType::FullText,
-        limit: 10,
-        offset: 0,
-        sort_by: SortBy::Relevance,
-        sort_order: SortOrder::Desc,
-        include_content: true,
+        ..full_text(10, 0)
     };
     
-    let results = db.manager.content().search_documents(&options.query, options).await?;
-    assert!(results.documents.len() > 0);
+    let results = engine.search("philosophy", options).await?;
+    assert!(!results.documents.is_empty());
     
     // All results should be in Philosophy category
-    for doc in &results.documents {
-        assert_eq!(doc.category, Some("Philosophy".to_string()));
+    for result in &results.documents {
+        assert_eq!(result.document.category, Some("Philosophy".to_string()));
     }
     
     // Test tag filter
     let options = SearchOptions {
-        query: "quantum".to_string(),
-        category: None,
-        tags: vec!["quantum".to_string()],
-        search_type: SearchType::FullText,
-        limit: 10,
-        offset: 0,
-        sort_by: SortBy::Relevance,
-        sort_order: SortOrder::Desc,
-        include_content: true,
+        tags: Some(vec!["quantum".to_string()]),
+        ..full_text(10, 0)
     };
     
-    let results = db.manager.content().search_documents(&options.query, options).await?;
-    assert!(results.documents.len() > 0);
+    let results = engine.search("quantum", options).await?;
+    assert!(!results.documents.is_empty());
     
     // All results should have the quantum tag
-    for doc in &results.documents {
-        let tags = doc.get_tags();
+    for result in &results.documents {
+        let tags = result.document.get_tags();
         assert!(tags.contains(&"quantum".to_string()));
     }
     
@@ -164,67 +171,33 @@ async fn test_search_pagination() -> CodexResult<()> {
     
     // Create many documents with similar content
     for i in 0..25 {
-        let request = DocumentBuilder::new()
+        let document = DocumentBuilder::new()
             .title(&format!("Search Test Document {}", i + 1))
             .content("This document contains the searchable term 'testing' for pagination tests.")
             .build();
         
-        db.manager.content().create_document(request).await?;
+        db.insert(&document).await?;
     }
     
-    // Test first page
-    let options = SearchOptions {
-        query: "testing".to_string(),
-        category: None,
-        tags: vec![],
-        search_type: SearchType::FullText,
-        limit: 10,
-        offset: 0,
-        sort_by: SortBy::Relevance,
-        sort_order: SortOrder::Desc,
-        include_content: false,
-    };
+    let engine = search_engine(&db).await?;
     
-    let page1 = db.manager.content().search_documents(&options.query, options).await?;
+    // Test first page
+    let page1 = engine.search("testing", full_text(10, 0)).await?;
     assert_eq!(page1.documents.len(), 10);
     assert!(page1.total_count >= 25);
     
     // Test second page
-    let options = SearchOptions {
-        query: "testing".to_string(),
-        category: None,
-        tags: vec![],
-        search_type: SearchType::FullText,
-        limit: 10,
-        offset: 10,
-        sort_by: SortBy::Relevance,
-        sort_order: SortOrder::Desc,
-        include_content: false,
-    };
-    
-    let page2 = db.manager.content().search_documents(&options.query, options).await?;
+    let page2 = engine.search("testing", full_text(10, 10)).await?;
     assert_eq!(page2.documents.len(), 10);
     
     // Test third page (partial)
-    let options = SearchOptions {
-        query: "testing".to_string(),
-        category: None,
-        tags: vec![],
-        search_type: SearchType::FullText,
-        limit: 10,
-        offset: 20,
-        sort_by: SortBy::Relevance,
-        sort_order: SortOrder::Desc,
-        include_content: false,
-    };
-    
-    let page3 = db.manager.content().search_documents(&options.query, options).await?;
+    let page3 = engine.search("testing", full_text(10, 20)).await?;
     assert!(page3.documents.len() >= 5);
     
     // Verify no document ID overlap between pages
-    let page1_ids: Vec<_> = page1.documents.iter().map(|d| &d.id).collect();
-    let page2_ids: Vec<_> = page2.documents.iter().map(|d| &d.id).collect();
-    let page3_ids: Vec<_> = page3.documents.iter().map(|d| &d.id).collect();
+    let page1_ids: Vec<_> = page1.documents.iter().map(|r| &r.document.id).collect();
+    let page2_ids: Vec<_> = page2.documents.iter().map(|r| &r.document.id).collect();
+    let page3_ids: Vec<_> = page3.documents.iter().map(|r| &r.document.id).collect();
     
     for id in &page1_ids {
         assert!(!page2_ids.contains(id));
@@ -240,17 +213,17 @@ async fn test_search_pagination() -> CodexResult<()> {
 
 /// Test search sorting options
 #[rstest]
-#[case(SortBy::Relevance, SortOrder::Desc)]
-#[case(SortBy::CreatedAt, SortOrder::Desc)]
-#[case(SortBy::CreatedAt, SortOrder::Asc)]
-#[case(SortBy::Title, SortOrder::Asc)]
-#[case(SortBy::Title, SortOrder::Desc)]
+#[case(SortBy::Relevance, SortOrder::Descending)]
+#[case(SortBy::CreatedAt, SortOrder::Descending)]
+#[case(SortBy::CreatedAt, SortOrder::Ascending)]
+#[case(SortBy::Title, SortOrder::Ascending)]
+#[case(SortBy::Title, SortOrder::Descending)]
 #[tokio::test]
 async fn test_search_sorting(#[case] sort_by: SortBy, #[case] sort_order: SortOrder) -> CodexResult<()> {
     let db = TestDatabase::new().await?;
     
     // Create documents with known properties for sorting
-    let docs_data = vec![
+    let docs_data = [
         ("Alpha Document", "Content with searchable terms"),
         ("Beta Document", "More content with searchable terms"),
         ("Gamma Document", "Additional content with searchable terms"),
@@ -258,60 +231,55 @@ async fn test_search_sorting(#[case] sort_by: SortBy, #[case] sort_order: SortOr
     
     let mut doc_ids = Vec::new();
     for (title, content) in &docs_data {
-        let request = DocumentBuilder::new()
+        let document = DocumentBuilder::new()
             .title(title)
             .content(content)
             .build();
         
-        let doc = db.manager.content().create_document(request).await?;
-        doc_ids.push(doc.id);
+        db.insert(&document).await?;
+        doc_ids.push(document.id);
         
         // Small delay to ensure different creation times
         tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
     }
     
     let options = SearchOptions {
-        query: "searchable".to_string(),
-        category: None,
-        tags: vec![],
-        search_type: SearchType::FullText,
-        limit: 10,
-        offset: 0,
         sort_by,
         sort_order,
-        include_content: true,
+        ..full_text(10, 0)
     };
     
-    let results = db.manager.content().search_documents(&options.query, options).await?;
+    let results = search_engine(&db).await?.search("searchable", options).await?;
     assert_eq!(results.documents.len(), 3);
+    let documents: Vec<_> = results.documents.iter().map(|r| &r.document).collect();
     
     // Verify sorting
     match sort_by {
         SortBy::Title => {
             match sort_order {
-                SortOrder::Asc => {
-                    assert!(results.documents[0].title < results.documents[1].title);
-                    assert!(results.documents[1].title < results.documents[2].title);
+                SortOrder::Ascending => {
+                    assert!(documents[0].title < documents[1].title);
+                    assert!(documents[1].title < documents[2].title);
                 },
-                SortOrder::Desc => {
-                    assert!(results.documents[0].title > results.documents[1].title);
-                    assert!(results.documents[1].title > results.documents[2].title);
+                SortOrder::Descending => {
+                    assert!(documents[0].title > documents[1].title);
+                    assert!(documents[1].title > documents[2].title);
                 },
             }
         },
         SortBy::CreatedAt => {
             match sort_order {
-                SortOrder::Asc => {
-                    assert!(results.documents[0].created_at <= results.documents[1].created_at);
-                    assert!(results.documents[1].created_at <= results.documents[2].created_at);
+                SortOrder::Ascending => {
+                    assert!(documents[0].created_at <= documents[1].created_at);
+                    assert!(documents[1].created_at <= documents[2].created_at);
                 },
-                SortOrder::Desc => {
-                    assert!(results.documents[0].created_at >= results.documents[1].created_at);
-                    assert!(results.documents[1].created_at >= results.documents[2].created_at);
+                SortOrder::Descending => {
+                    assert!(documents[0].created_at >= documents[1].created_at);
+                    assert!(documents[1].created_at >= documents[2].created_at);
                 },
             }
         },
-        SortBy::Relevance => {
+        SortBy::Relevance | SortBy::UpdatedAt => {
             // For relevance, descending should be default (highest relevance first)
             // Ascending relevance is unusual but should still work
         },
@@ -330,14 +298,14 @@ async fn test_search_performance() -> CodexResult<()> {
     let doc_count = 5000;
     println!("Creating {} documents for search performance testing...", doc_count);
     
-    let search_terms = vec!["technology", "science", "philosophy", "health", "education"];
-    let categories = vec!["Technology", "Science", "Philosophy", "Health", "Education"];
+    let search_terms = ["technology", "science", "philosophy", "health", "education"];
+    let categories = ["Technology", "Science", "Philosophy", "Health", "Education"];
     
     for i in 0..doc_count {
         let term_index = i % search_terms.len();
         let category_index = i % categories.len();
         
-        let request = DocumentBuilder::new()
+        let document = DocumentBuilder::new()
             .title(&format!("Performance Test Document {}", i + 1))
             .content(&format!(
                 "This document is about {} and contains various terms for performance testing. \
@@ -345,11 +313,11 @@ async fn test_search_performance() -> CodexResult<()> {
                 search_terms[term_index],
                 search_terms[term_index]
             ))
-            .category(&categories[category_index])
-            .tags(vec![search_terms[term_index].to_string(), "performance".to_string()])
+            .category(categories[category_index])
+            .tags(vec![search_terms[term_index], "performance"])
             .build();
         
-        db.manager.content().create_document(request).await?;
+        db.insert(&document).await?;
         
         if (i + 1) % 1000 == 0 {
             println!("Created {} documents", i + 1);
@@ -363,53 +331,36 @@ async fn test_search_performance() -> CodexResult<()> {
     let results = Search::fts5(&db.pool, "technology", 50).await?;
     let simple_search_time = start.elapsed();
     
-    assert!(results.len() > 0);
+    assert!(!results.is_empty());
     assert!(simple_search_time.as_millis() < 200, "Simple search took too long: {:?}", simple_search_time);
     
     // Test complex search with filters
+    let engine = search_engine(&db).await?;
     let start = std::time::Instant::now();
     let options = SearchOptions {
-        query: "science OR technology".to_string(),
         category: Some("Science".to_string()),
-        tags: vec!["science".to_string()],
-        search_type: SearchType::FullText,
-        limit: 100,
-        offset: 0,
-        sort_by: SortBy::Relevance,
-        sort_order: SortOrder::Desc,
-        include_content: false,
+        tags: Some(vec!["science".to_string()]),
+        ..full_text(100, 0)
     };
     
-    let filtered_results = db.manager.content().search_documents(&options.query, options).await?;
+    let filtered_results = engine.search("science technology", options).await?;
     let filtered_search_time = start.elapsed();
     
-    assert!(filtered_results.documents.len() > 0);
+    assert!(!filtered_results.documents.is_empty());
     assert!(filtered_search_time.as_millis() < 300, "Filtered search took too long: {:?}", filtered_search_time);
     
-    // Test pagination performance
+    // Test pagination performance, deep within the 500 candidates a search ranks
     let start = std::time::Instant::now();
-    let options = SearchOptions {
-        query: "performance".to_string(),
-        category: None,
-        tags: vec![],
-        search_type: SearchType::FullText,
-        limit: 50,
-        offset: 1000,
-        sort_by: SortBy::Relevance,
-        sort_order: SortOrder::Desc,
-        include_content: false,
-    };
-    
-    let paginated_results = db.manager.content().search_documents(&options.query, options).await?;
+    let paginated_results = engine.search("performance", full_text(50, 400)).await?;
     let pagination_time = start.elapsed();
     
-    assert!(paginated_results.documents.len() > 0);
+    assert!(!paginated_results.documents.is_empty());
     assert!(pagination_time.as_millis() < 100, "Paginated search took too long: {:?}", pagination_time);
     
     println!("Search performance test completed successfully");
     println!("- Simple search (50 results): {:?}", simple_search_time);
     println!("- Filtered search (100 results): {:?}", filtered_search_time);
-    println!("- Paginated search (offset 1000): {:?}", pagination_time);
+    println!("- Paginated search (offset 400): {:?}", pagination_time);
     
     Ok(())
 }
@@ -429,23 +380,23 @@ async fn test_search_special_characters(#[case] search_term: &str) -> CodexResul
     let db = TestDatabase::new().await?;
     
     // Create a document containing the search term
-    let request = DocumentBuilder::new()
+    let document = DocumentBuilder::new()
         .title("Special Characters Test")
         .content(&format!("This document contains the term: {}", search_term))
         .build();
     
-    db.manager.content().create_document(request).await?;
+    db.insert(&document).await?;
     
     // Test searching for the term (should handle special characters gracefully)
-    let results = Search::fts5(&db.pool, &search_term.replace('"', ""), 10).await;
+    let results = SearchQueries::search(&db.pool, search_term, Some(10)).await;
     
     // Search should not fail, even with special characters
     assert!(results.is_ok());
     
     let results = results.unwrap();
-    if !search_term.contains('"') && !search_term.contains('@') {
-        // Should find the document for most terms (excluding quotes and @ which have special meaning in FTS)
-        assert!(results.len() > 0 || search_term.contains('"') || search_term.contains('@'));
+    if search_term.chars().all(|c| c.is_alphanumeric() || "-_".contains(c)) {
+        // Should find the document for terms the query sanitizer keeps whole
+        assert!(!results.is_empty());
     }
     
     Ok(())
@@ -458,12 +409,12 @@ async fn test_search_index_consistency() -> CodexResult<()> {
     let db = TestDatabase::new().await?;
     
     // Create a document
-    let request = DocumentBuilder::new()
+    let document = DocumentBuilder::new()
         .title("Index Consistency Test")
         .content("Original content for index testing")
         .build();
     
-    let document = db.manager.content().create_document(request).await?;
+    db.insert(&document).await?;
     
     // Verify it's searchable
     let results = Search::fts5(&db.pool, "original", 10).await?;
@@ -471,14 +422,11 @@ async fn test_search_index_consistency() -> CodexResult<()> {
     assert_eq!(results[0].id, document.id);
     
     // Update the document
-    let update_request = DocumentBuilder::new()
-        .title("Updated Index Test")
-        .content("Modified content for index testing")
-        .build();
+    let mut updated_doc = document.clone();
+    updated_doc.title = "Updated Index Test".to_string();
+    updated_doc.content = "Modified content for index testing".to_string();
     
-    let updated_doc = db.manager.content()
-        .update_document(&document.id, update_request)
-        .await?;
+    DocumentQueries::update(&db.pool, &updated_doc, db.manager.compression()).await?;
     
     // Old content should not be searchable
     let old_results = Search::fts5(&db.pool, "original", 10).await?;
@@ -491,7 +439,7 @@ async fn test_search_index_consistency() -> CodexResult<()> {
     assert_eq!(new_results[0].title, "Updated Index Test");
     
     // Delete the document
-    db.manager.content().delete_document(&document.id).await?;
+    DocumentQueries::delete(&db.pool, &document.id).await?;
     
     // Should no longer be searchable
     let deleted_results = Search::fts5(&db.pool, "modified", 10).await?;
@@ -509,21 +457,21 @@ async fn test_comprehensive_search_scenarios() -> CodexResult<()> {
     // Create all test documents from fixtures
     let test_documents = TestFixtures::all_test_documents();
     for test_doc in test_documents {
-        let request = DocumentBuilder::new()
+        let document = DocumentBuilder::new()
             .title(&test_doc.title)
             .content(&test_doc.content)
             .category(&test_doc.category)
-            .tags(test_doc.tags)
-            .difficulty(test_doc.difficulty)
+            .tags(test_doc.tags.iter().map(String::as_str).collect())
+            .difficulty(test_doc.difficulty.into())
             .build();
         
-        db.manager.content().create_document(request).await?;
+        db.insert(&document).await?;
     }
     
     // Run all search test cases
     let search_test_cases = TestFixtures::search_test_cases();
     for test_case in search_test_cases {
-        let results = Search::fts5(&db.pool, &test_case.query, 20).await?;
+        let results = SearchQueries::search(&db.pool, &test_case.query, Some(20)).await?;
         
         // Verify minimum expected results
         assert!(
@@ -1,10 +1,10 @@
 //! Integration tests for GGUF engine functionality
 
-use std::path::PathBuf;
 use std::fs;
 use tempfile::tempdir;
 use codex_core::{
     ai::engine::{GGUFEngine, EngineParams, GenerationSettings, LLMEngine},
+    config::GpuLayers,
     update::manifest::ModelManifest,
 };
 
@@ -25,7 +25,6 @@ async fn test_gguf_metadata_parsing() {
     match result {
         Ok(metadata) => {
             assert_eq!(metadata.version, 3); // GGUF version 3
-            assert!(metadata.tensor_count >= 0);
             println!("✓ GGUF metadata parsing successful");
         }
         Err(e) => {
@@ -102,7 +101,7 @@ async fn test_gguf_engine_creation() {
     let params = EngineParams {
         num_threads: 4,
         context_length: 2048,
-        gpu_layers: 0,
+        gpu_layers: GpuLayers::Count(0),
         batch_size: 256,
         use_mmap: true,
         use_metal: false,
@@ -285,32 +284,3 @@ async fn test_config_extraction_from_metadata() {
         }
     }
 }
-
-#[tokio::test]
-async fn test_parameter_estimation() {
-    use candle_transformers::models::llama::LlamaConfig;
-    
-    let config = LlamaConfig {
-        vocab_size: 32000,
-        hidden_size: 4096,
-        intermediate_size: 11008,
-        num_hidden_layers: 32,
-        num_attention_heads: 32,
-        num_key_value_heads: Some(32),
-        max_position_embeddings: 4096,
-        rms_norm_eps: 1e-5,
-        rope_theta: 10000.0,
-        use_flash_attn: false,
-    };
-    
-    let param_count = codex_core::ai::engine::GGUFEngine::estimate_parameters(&config);
-    
-    // Should be roughly 7B parameters for this config
-    let billion_params = param_count as f64 / 1_000_000_000.0;
-    
-    assert!(billion_params > 6.0 && billion_params < 8.0, 
-            "Parameter count should be around 7B, got {:.2}B", billion_params);
-    
-    println!("Estimated parameters: {:.2}B", billion_params);
-    println!("✓ Parameter estimation test passed");
-}
\ No newline at end of file
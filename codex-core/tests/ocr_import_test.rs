@@ -0,0 +1,61 @@
+//! OCR image import tests
+//!
+//! Requires the `ocr` feature and a system Tesseract installation with the
+//! English language data: `cargo test --features ocr --test ocr_import_test`
+
+#![cfg(feature = "ocr")]
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use codex_core::{
+    CodexResult,
+    config::CodexConfig,
+    content::{ContentManager, ContentParser},
+    db::DatabaseManager,
+};
+
+mod common;
+use common::ai::offline_ai_engine;
+
+fn fixture_image() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/ocr_sample.png")
+}
+
+#[tokio::test]
+async fn test_parse_fixture_image() -> CodexResult<()> {
+    let config = CodexConfig::default().content;
+    let parser = ContentParser::new(&config).unwrap();
+
+    let parsed = parser.parse_file(&fixture_image()).await?;
+
+    assert_eq!(parsed.content_type, "image/png");
+    assert!(parsed.content.to_uppercase().contains("HELLO CODEX VAULT"));
+    assert!(parsed.source.as_deref().unwrap_or_default().ends_with("ocr_sample.png"));
+    assert!(parsed.ocr_confidence.unwrap_or_default() > 0.0);
+
+    Ok(())
+}
+
+/// Imports through the content manager with the mock AI engine, so the OCR
+/// path runs without model files
+#[tokio::test]
+async fn test_import_fixture_image() -> CodexResult<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let config = CodexConfig::default().with_vault_dir(temp_dir.path());
+
+    let db = Arc::new(DatabaseManager::new(&config.database).await.unwrap());
+    let ai = offline_ai_engine(&config.ai);
+    ai.set_database(Arc::clone(&db));
+    let content = ContentManager::new(db, ai, &config.content).await.unwrap();
+    assert!(content.ocr_available());
+
+    let id = content.import_document(fixture_image()).await?;
+    let document = content.get_document(id).await?.expect("imported document");
+
+    assert_eq!(document.content_type, "image/png");
+    assert!(document.content.to_uppercase().contains("HELLO CODEX VAULT"));
+    assert!(document.source.as_deref().unwrap_or_default().ends_with("ocr_sample.png"));
+
+    Ok(())
+}
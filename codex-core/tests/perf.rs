@@ -0,0 +1,227 @@
+//! End-to-end performance budgets
+//!
+//! Seeds a vault with documents derived from [`TestFixtures`], runs each
+//! operation of [`TestFixtures::performance_benchmarks`] repeatedly with the
+//! mock AI engine and checks its 95th percentile against `max_duration_ms`.
+//! Timings depend on the machine, so the suite only runs when
+//! `CODEX_PERF_TESTS` is set: `CODEX_PERF_TESTS=1 cargo test --release --test perf -- --nocapture`
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tempfile::TempDir;
+
+use codex_core::{
+    ai::AiEngine,
+    config::CodexConfig,
+    content::ContentManager,
+    db::{DatabaseManager, Document, DocumentQueries, SearchQueries},
+};
+
+mod common;
+use common::ai::offline_ai_engine;
+use common::fixtures::{PerformanceBenchmark, TestFixtures};
+
+/// Documents in the seeded vault
+const DOCUMENT_COUNT: usize = 5_000;
+/// Timed runs of each operation
+const ITERATIONS: usize = 50;
+
+const SEARCH_QUERIES: &[&str] = &[
+    "philosophy",
+    "quantum mechanics",
+    "machine learning",
+    "stoic virtue",
+    "nutrition diet",
+    "blockchain distributed",
+    "modern poetry",
+    "dna genetics",
+];
+
+/// Vault seeded with fixture documents, with the mock AI engine
+struct PerfVault {
+    db: Arc<DatabaseManager>,
+    ai: Arc<AiEngine>,
+    content: ContentManager,
+    _dir: TempDir,
+}
+
+impl PerfVault {
+    async fn seed() -> anyhow::Result<Self> {
+        let dir = tempfile::tempdir()?;
+        let mut config = CodexConfig::default().with_vault_dir(dir.path());
+        // Repeated prompts must reach the engine, not the response cache
+        config.ai.enable_caching = false;
+
+        let db = Arc::new(DatabaseManager::new(&config.database).await?);
+        let ai = offline_ai_engine(&config.ai);
+        ai.set_database(Arc::clone(&db));
+        let content = ContentManager::new(Arc::clone(&db), Arc::clone(&ai), &config.content).await?;
+
+        let fixtures = TestFixtures::all_test_documents();
+        for i in 0..DOCUMENT_COUNT {
+            let fixture = &fixtures[i % fixtures.len()];
+            let mut document = Document::new(
+                format!("{} #{}", fixture.title, i),
+                fixture.content.clone(),
+                "text/markdown".to_string(),
+            );
+            document.category = Some(fixture.category.clone());
+            document.difficulty_level = Some(fixture.difficulty as i64);
+            document.set_tags(fixture.tags.clone());
+            DocumentQueries::create(db.pool(), &document, db.compression()).await?;
+        }
+
+        Ok(Self { db, ai, content, _dir: dir })
+    }
+}
+
+/// Latencies of one benchmarked operation
+struct Measurement {
+    key: &'static str,
+    benchmark: PerformanceBenchmark,
+    samples: Vec<Duration>,
+}
+
+impl Measurement {
+    fn percentile(&self, percentile: f64) -> Duration {
+        let mut sorted = self.samples.clone();
+        sorted.sort();
+        let rank = ((percentile * sorted.len() as f64).ceil() as usize).clamp(1, sorted.len());
+        sorted[rank - 1]
+    }
+
+    fn within_budget(&self) -> bool {
+        self.percentile(0.95) <= Duration::from_millis(self.benchmark.max_duration_ms)
+    }
+}
+
+/// Run `operation` [`ITERATIONS`] times after one untimed warm-up run
+async fn measure<F, Fut>(key: &'static str, mut operation: F) -> Measurement
+where
+    F: FnMut(usize) -> Fut,
+    Fut: Future<Output = anyhow::Result<()>>,
+{
+    let benchmark = TestFixtures::performance_benchmarks()
+        .remove(key)
+        .unwrap_or_else(|| panic!("No performance benchmark named {}", key));
+
+    operation(ITERATIONS).await.unwrap_or_else(|e| panic!("{} failed: {}", benchmark.operation, e));
+    let mut samples = Vec::with_capacity(ITERATIONS);
+    for i in 0..ITERATIONS {
+        let start = Instant::now();
+        operation(i).await.unwrap_or_else(|e| panic!("{} failed: {}", benchmark.operation, e));
+        samples.push(start.elapsed());
+    }
+
+    Measurement { key, benchmark, samples }
+}
+
+fn print_table(measurements: &[Measurement]) {
+    let ms = |duration: Duration| format!("{:.2}ms", duration.as_secs_f64() * 1000.0);
+    println!(
+        "\n{:<20} {:>10} {:>10} {:>10} {:>10}  status",
+        "operation", "p50", "p95", "target", "max"
+    );
+    for measurement in measurements {
+        println!(
+            "{:<20} {:>10} {:>10} {:>10} {:>10}  {}",
+            measurement.benchmark.operation,
+            ms(measurement.percentile(0.5)),
+            ms(measurement.percentile(0.95)),
+            format!("{}ms", measurement.benchmark.target_duration_ms),
+            format!("{}ms", measurement.benchmark.max_duration_ms),
+            if measurement.within_budget() { "ok" } else { "OVER BUDGET" },
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_performance_budgets() -> anyhow::Result<()> {
+    if std::env::var_os("CODEX_PERF_TESTS").is_none() {
+        eprintln!("Skipping performance budgets, set CODEX_PERF_TESTS to run them");
+        return Ok(());
+    }
+
+    let vault = PerfVault::seed().await?;
+    let mut measurements = Vec::new();
+
+    let pool = vault.db.pool();
+    measurements.push(
+        measure("search_speed", |i| async move {
+            let results = SearchQueries::search(pool, SEARCH_QUERIES[i % SEARCH_QUERIES.len()], Some(20)).await?;
+            anyhow::ensure!(!results.is_empty(), "no results");
+            Ok(())
+        })
+        .await,
+    );
+
+    let content = &vault.content;
+    let fixtures = TestFixtures::all_test_documents();
+    let fixtures = &fixtures;
+    measurements.push(
+        measure("document_insert", |i| async move {
+            let fixture = &fixtures[i % fixtures.len()];
+            content
+                .import_text_content(format!("Inserted {} #{}", fixture.title, i), fixture.content.clone(), None)
+                .await?;
+            Ok(())
+        })
+        .await,
+    );
+
+    let embeddings = vault.ai.get_embeddings();
+    let vectors: Vec<(String, Vec<f32>)> = {
+        let texts: Vec<String> = (0..DOCUMENT_COUNT)
+            .map(|i| {
+                let fixture = &fixtures[i % fixtures.len()];
+                format!("{} {} {}", fixture.title, fixture.tags.join(" "), i)
+            })
+            .collect();
+        let vectors = embeddings.generate_embeddings_batch(&texts).await?;
+        (0..DOCUMENT_COUNT).map(|i| format!("doc-{}", i)).zip(vectors).collect()
+    };
+    let query_vectors = embeddings
+        .generate_embeddings_batch(&SEARCH_QUERIES.iter().map(|query| query.to_string()).collect::<Vec<_>>())
+        .await?;
+    let (vectors, query_vectors) = (&vectors, &query_vectors);
+    measurements.push(
+        measure("vector_similarity", |i| async move {
+            let similar = embeddings.find_similar(&query_vectors[i % query_vectors.len()], vectors, 10);
+            anyhow::ensure!(similar.len() == 10, "expected 10 neighbours, got {}", similar.len());
+            Ok(())
+        })
+        .await,
+    );
+
+    let ai = &vault.ai;
+    let prompts = TestFixtures::ai_test_prompts();
+    let prompts = &prompts;
+    measurements.push(
+        measure("ai_inference", |i| async move {
+            ai.generate_text(&prompts[i % prompts.len()].prompt).await?;
+            Ok(())
+        })
+        .await,
+    );
+
+    print_table(&measurements);
+
+    let over_budget: Vec<String> = measurements
+        .iter()
+        .filter(|measurement| !measurement.within_budget())
+        .map(|measurement| {
+            format!(
+                "{} ({}): p95 {:.2}ms exceeds {}ms",
+                measurement.benchmark.operation,
+                measurement.key,
+                measurement.percentile(0.95).as_secs_f64() * 1000.0,
+                measurement.benchmark.max_duration_ms
+            )
+        })
+        .collect();
+    assert!(over_budget.is_empty(), "Performance budgets exceeded:\n{}", over_budget.join("\n"));
+
+    Ok(())
+}
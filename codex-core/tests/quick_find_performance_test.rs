@@ -0,0 +1,74 @@
+//! Quick switcher performance at 100k titles
+//!
+//! This module tests that title lookups meet the <10ms keystroke target.
+//! Timings depend on the machine, so they are only checked when
+//! `CODEX_PERF_TESTS` is set: `CODEX_PERF_TESTS=1 cargo test --release --test quick_find_performance_test`
+
+use std::time::{Duration, Instant};
+
+use codex_core::content::quick_find::TitleIndex;
+use codex_core::db::models::DocumentTitle;
+
+const TITLE_COUNT: usize = 100_000;
+
+const WORDS: &[&str] = &[
+    "meeting", "notes", "quarterly", "report", "stoicism", "quantum", "computing", "garden",
+    "journal", "recipe", "sourdough", "project", "plan", "reading", "list", "invoice",
+    "travel", "itinerary", "research", "paper", "draft", "review", "budget", "roadmap",
+];
+
+fn titles() -> TitleIndex {
+    let index = TitleIndex::new();
+    for i in 0..TITLE_COUNT {
+        let title = format!(
+            "{} {} {} {}",
+            WORDS[i % WORDS.len()],
+            WORDS[(i / WORDS.len()) % WORDS.len()],
+            WORDS[(i * 7 + 3) % WORDS.len()],
+            i
+        );
+        index.upsert_title(DocumentTitle {
+            id: format!("doc-{}", i),
+            title,
+            category: None,
+            is_favorite: i % 500 == 0,
+            last_accessed: None,
+        });
+    }
+    index
+}
+
+#[test]
+fn test_quick_find_at_100k_titles() {
+    let index = titles();
+    assert_eq!(index.len(), TITLE_COUNT);
+
+    let check_timings = std::env::var_os("CODEX_PERF_TESTS").is_some();
+    // Unoptimized test builds get more headroom than the release target
+    let budget = if cfg!(debug_assertions) { Duration::from_millis(250) } else { Duration::from_millis(10) };
+
+    for query in ["m", "mn", "qrep", "sourdough", "prjpln", "zzz", ""] {
+        // Warm up, then time the median of a few runs
+        index.find(query, 20);
+        let mut timings: Vec<Duration> = (0..5)
+            .map(|_| {
+                let started = Instant::now();
+                let hits = index.find(query, 20);
+                let elapsed = started.elapsed();
+                assert!(hits.len() <= 20);
+                elapsed
+            })
+            .collect();
+        timings.sort();
+
+        let median = timings[timings.len() / 2];
+        println!("quick_find {:?}: {:?}", query, median);
+        if check_timings {
+            assert!(median < budget, "quick_find {:?} took {:?}", query, median);
+        }
+    }
+
+    let hits = index.find("quarterly rep", 5);
+    assert_eq!(hits.len(), 5);
+    assert!(hits.iter().all(|hit| hit.title.contains("quarterly report")), "{:?}", hits);
+}
@@ -3,6 +3,7 @@
 //! This is the main Tauri application that provides the desktop interface
 //! for the Codex Vault offline AI-powered knowledge repository.
 
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 use tauri::{Emitter, Manager, State};
 use tokio::sync::RwLock;
@@ -10,7 +11,45 @@ use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use anyhow;
 
-use codex_core::{CodexCore, CodexResult};
+use codex_core::{logging, CodexConfig, CodexCore, CodexError, CodexResult};
+use codex_core::error::ErrorView;
+use codex_core::config::{EventBatchingConfig, PowerPolicy};
+use codex_core::power::PowerState;
+use codex_core::events::{ChunkCoalescer, ProgressThrottle};
+use codex_core::update::DownloadStage;
+use codex_core::logging::LogEntry;
+use codex_core::metrics::{DailyMetrics, OperationMetrics};
+use codex_core::diagnostics::DiagnosticsReport;
+use codex_core::api_server::ApiServerInfo;
+use codex_core::ai::{DocumentChatResponse, GenerationOutput, ModelLoadPlan, ModelWarmUpState, PromptTruncation, RagResponse, StreamChunk, SummaryProgress};
+use codex_core::ai::rag::{MAX_QUERY_DOCUMENTS, MIN_QUERY_DOCUMENTS};
+use codex_core::content::jobs::JobInfo;
+use codex_core::content::{BulkImportResult, DictionaryEdit, DocumentStructure, IndexHealth, MatchExplanation};
+use codex_core::content::find::{DocumentMatches, FindOptions};
+use codex_core::content::metadata::{MetadataField, MetadataFilter};
+use codex_core::content::export::{DocumentSelection, ExportFormat};
+use codex_core::content::digest::DigestSchedule;
+use codex_core::content::import_plan::BulkImportPlan;
+use codex_core::content::bulk::{BulkChanges, BulkUpdateSummary};
+use codex_core::content::rules::{AutomationRule, RuleActions, RuleConditions, RuleTestResult};
+use codex_core::content::capture::CaptureResult;
+use codex_core::content::merge::{MergeResult, MergeStrategy};
+use codex_core::content::split::{OriginalHandling, SplitResult, SplitStrategy};
+use codex_core::content::feed::HomeFeed;
+use codex_core::content::rss::{FeedNewItems, FeedRefreshResult};
+use codex_core::content::bibtex::{BibtexImportResult, CitationStyle};
+use codex_core::content::annotations::{AnnotationExportFormat, AnnotationExportResult, AnnotationExportScope};
+use codex_core::content::deep_link::NavigationTarget;
+use codex_core::content::preview::{Thumbnail, ThumbnailSize};
+use codex_core::content::quick_find::QuickFindHit;
+use codex_core::content::reading::{GoalProgress, ReadingStreak};
+use codex_core::content::triage::{TriageDecision, TriageResult, TriageSuggestion};
+use codex_core::content::focus_pack::{DifficultyRange, FocusPack};
+use codex_core::db::models::Feed;
+use codex_core::db::{MaintenanceAction, RepairOptions, RepairResult, StorageBreakdown, VaultAudit, VaultExportManifest};
+use codex_core::update::ModelCatalog;
+use codex_core::settings_bundle::SettingsImportReport;
+use codex_core::db::models::{AiAuditEntry, Annotation, AnnotationMatch, ConversationMessage, DocumentLink, FailedImport, Highlight, MetadataValue, Operation, ReadingSession, SearchDictionaries, Template};
 
 /// Application state containing the core library instance
 pub struct AppState {
@@ -23,6 +62,8 @@ pub struct CommandResponse<T> {
     pub success: bool,
     pub data: Option<T>,
     pub error: Option<String>,
+    /// Code, user message, retry hint and remediation for core errors
+    pub error_info: Option<ErrorView>,
 }
 
 /// AI response structure matching frontend expectations
@@ -32,6 +73,38 @@ pub struct AiResponse {
     pub model: String,
     pub processing_time_ms: u64,
     pub tokens_used: u32,
+    /// Set when the prompt was cut down to fit the model's context
+    pub truncation: Option<PromptTruncation>,
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    pub tokens_per_second: f64,
+    pub time_to_first_token_ms: u64,
+}
+
+impl AiResponse {
+    /// Response with the token counts and timing measured by the inference engine
+    fn from_output(output: GenerationOutput, processing_time_ms: u64) -> Self {
+        let usage = output.usage;
+        Self {
+            content: output.text,
+            model: "test-llama-7b".to_string(),
+            processing_time_ms,
+            tokens_used: (usage.prompt_tokens + usage.completion_tokens) as u32,
+            truncation: output.truncation,
+            prompt_tokens: usage.prompt_tokens as u32,
+            completion_tokens: usage.completion_tokens as u32,
+            tokens_per_second: usage.tokens_per_second(),
+            time_to_first_token_ms: usage.time_to_first_token_ms,
+        }
+    }
+}
+
+/// Streamed text with the completion tokens generated so far, as sent in `ai-chunk` events
+#[derive(Debug, Clone, Serialize)]
+pub struct AiChunk {
+    pub content: String,
+    pub completion_tokens: u32,
+    pub elapsed_ms: u64,
 }
 
 /// System metrics response structure
@@ -42,6 +115,29 @@ pub struct SystemMetricsResponse {
     pub total_memory_mb: f64,
     pub ai_model_loaded: bool,
     pub uptime_seconds: u64,
+    /// Whether the GPU could be queried; the GPU fields are zero otherwise
+    pub gpu_metrics_available: bool,
+    pub gpu_memory_used_mb: f64,
+    pub gpu_memory_total_mb: f64,
+    /// Estimate of the GPU memory used by the app
+    pub gpu_memory_process_mb: f64,
+}
+
+impl SystemMetricsResponse {
+    /// Metrics without a running AI engine
+    fn unavailable() -> Self {
+        Self {
+            cpu_usage: 0.0,
+            memory_usage_mb: 0.0,
+            total_memory_mb: 0.0,
+            ai_model_loaded: false,
+            uptime_seconds: 0,
+            gpu_metrics_available: false,
+            gpu_memory_used_mb: 0.0,
+            gpu_memory_total_mb: 0.0,
+            gpu_memory_process_mb: 0.0,
+        }
+    }
 }
 
 /// Health check response structure
@@ -51,6 +147,38 @@ pub struct HealthResponse {
     pub core_initialized: bool,
     pub ai_available: bool,
     pub database_connected: bool,
+    pub ocr_available: bool,
+    pub transcription_available: bool,
+    /// Search index coverage, when it could be checked
+    pub index_health: Option<IndexHealth>,
+    /// Started in safe mode, with AI and background jobs disabled
+    pub safe_mode: bool,
+}
+
+/// Safe mode banner state
+#[derive(Debug, Clone, Serialize)]
+pub struct SafeModeDto {
+    /// The core runs in safe mode
+    pub active: bool,
+    /// What is disabled, while active
+    pub message: Option<String>,
+}
+
+/// Latency statistics for the diagnostics panel
+#[derive(Debug, Serialize)]
+pub struct PerformanceMetricsResponse {
+    /// Statistics since the app started
+    pub session: Vec<OperationMetrics>,
+    /// Daily rollups, newest first
+    pub daily: Vec<DailyMetrics>,
+}
+
+/// Response of `import_document`: the new document's ID, or the plan of a dry run
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum ImportDocumentResponse {
+    Imported(String),
+    Planned(BulkImportPlan),
 }
 
 impl<T> CommandResponse<T> {
@@ -59,6 +187,7 @@ impl<T> CommandResponse<T> {
             success: true,
             data: Some(data),
             error: None,
+            error_info: None,
         }
     }
 
@@ -67,6 +196,19 @@ impl<T> CommandResponse<T> {
             success: false,
             data: None,
             error: Some(error),
+            error_info: None,
+        }
+    }
+
+    /// Failure from a core error: redacted text plus its structured view
+    pub fn from_error(error: &CodexError) -> Self {
+        tracing::warn!("Command failed: {}", error.report());
+        let view = error.view();
+        Self {
+            success: false,
+            data: None,
+            error: Some(view.detail.clone()),
+            error_info: Some(view),
         }
     }
 }
@@ -75,7 +217,7 @@ impl<T> From<CodexResult<T>> for CommandResponse<T> {
     fn from(result: CodexResult<T>) -> Self {
         match result {
             Ok(data) => Self::success(data),
-            Err(e) => Self::error(e.to_string()),
+            Err(e) => Self::from_error(&e),
         }
     }
 }
@@ -97,6 +239,10 @@ pub struct DocumentDto {
     pub updated_at: String,
     pub view_count: i64,
     pub is_favorite: bool,
+    /// Custom metadata fields, such as frontmatter keys without a document field
+    pub metadata: BTreeMap<String, serde_json::Value>,
+    /// Passed back with edits so a stale copy is rejected instead of overwriting
+    pub version: i64,
 }
 
 /// Search options for frontend
@@ -108,6 +254,22 @@ pub struct SearchOptionsDto {
     pub category: Option<String>,
     pub tags: Option<Vec<String>>,
     pub author: Option<String>,
+    #[serde(default)]
+    pub include_archived: bool,
+    #[serde(default)]
+    pub favorites_only: bool,
+    /// "reciprocal_rank" (default) or "weighted_sum"
+    pub fusion: Option<String>,
+    #[serde(default)]
+    pub exact_only: bool,
+    #[serde(default)]
+    pub expand_chunks: bool,
+    /// Search without the vault's synonyms and stop words
+    #[serde(default)]
+    pub skip_expansion: bool,
+    /// Custom metadata fields that must match, as `[key, value]` pairs
+    #[serde(default)]
+    pub metadata: Vec<(String, MetadataValue)>,
 }
 
 /// Search result for frontend
@@ -118,6 +280,44 @@ pub struct SearchResultDto {
     pub query: String,
     pub search_time_ms: u64,
     pub has_more: bool,
+    pub did_you_mean: Option<String>,
+    /// Highlights and notes matching the query, linked to their document and offset
+    pub annotations: Vec<AnnotationMatch>,
+    /// The search ran out of time and these are partial results
+    pub timed_out: bool,
+    /// Why the results may be degraded, e.g. semantic matching was skipped
+    pub warnings: Vec<String>,
+}
+
+/// Similarity of two pasted texts
+#[derive(Debug, Clone, Serialize)]
+pub struct TextComparisonDto {
+    /// Cosine similarity of the texts' embeddings
+    pub similarity: f32,
+    /// Notices for texts that were too long and embedded in parts
+    pub warnings: Vec<String>,
+}
+
+/// Vault document similar to pasted text
+#[derive(Debug, Clone, Serialize)]
+pub struct SimilarDocumentDto {
+    pub document: DocumentDto,
+    pub score: f64,
+    pub snippet: Option<String>,
+}
+
+/// Vault documents similar to pasted text
+#[derive(Debug, Clone, Serialize)]
+pub struct SimilarTextDto {
+    pub documents: Vec<SimilarDocumentDto>,
+    pub warnings: Vec<String>,
+}
+
+/// Settings import report with the bundle it was made from, so a dry run can be applied
+#[derive(Debug, Serialize)]
+pub struct SettingsImportDto {
+    pub path: String,
+    pub report: SettingsImportReport,
 }
 
 // =====================================================
@@ -125,8 +325,14 @@ pub struct SearchResultDto {
 // =====================================================
 
 /// Initialize the core library
+///
+/// The core is ready when this returns; the cache warm-up it starts
+/// reports its progress as `init-progress` events.
 #[tauri::command]
-async fn initialize_core(state: State<'_, AppState>) -> Result<CommandResponse<bool>, tauri::Error> {
+async fn initialize_core(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<bool>, tauri::Error> {
     tracing::info!("Initializing Codex Core library");
     
     let core = match CodexCore::new().await {
@@ -137,6 +343,18 @@ async fn initialize_core(state: State<'_, AppState>) -> Result<CommandResponse<b
         }
     };
 
+    // Forward warm-up progress until it finishes
+    let mut warm_up = core.warm_up.subscribe();
+    tokio::spawn(async move {
+        loop {
+            let status = warm_up.borrow_and_update().clone();
+            let _ = app_handle.emit("init-progress", &status);
+            if status.is_finished() || warm_up.changed().await.is_err() {
+                break;
+            }
+        }
+    });
+
     let mut core_lock = state.core.write().await;
     *core_lock = Some(core);
     
@@ -159,62 +377,2383 @@ async fn get_health_status(state: State<'_, AppState>) -> Result<CommandResponse
     }
 }
 
+/// Whether the language model is warmed up, to show "model warming up"
+#[tauri::command]
+async fn get_model_warm_up_state(
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<ModelWarmUpState>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        Ok(CommandResponse::success(core.ai.model_warm_up_state().await))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// How a model would be split between GPU and CPU, shown before loading it
+#[tauri::command]
+async fn get_model_load_plan(
+    state: State<'_, AppState>,
+    model_path: Option<String>,
+) -> Result<CommandResponse<ModelLoadPlan>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        match core.ai.get_model_load_plan(model_path.as_deref()).await {
+            Ok(plan) => Ok(CommandResponse::success(plan)),
+            Err(e) => Ok(CommandResponse::error(e.to_string())),
+        }
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Whether the core runs in safe mode, for the banner
+#[tauri::command]
+async fn get_safe_mode(state: State<'_, AppState>) -> Result<CommandResponse<SafeModeDto>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let active = core.safe_mode();
+        Ok(CommandResponse::success(SafeModeDto {
+            active,
+            message: active.then(|| codex_core::SAFE_MODE_WARNING.to_string()),
+        }))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Restart the app in safe mode
+///
+/// Only the next launch is affected; restarting from safe mode starts
+/// normally again.
+#[tauri::command]
+async fn restart_in_safe_mode(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<bool>, tauri::Error> {
+    if let Err(e) = CodexConfig::request_safe_mode().await {
+        return Ok(CommandResponse::error(format!("Failed to request safe mode: {}", e)));
+    }
+
+    if let Some(core) = state.core.write().await.take() {
+        if let Err(e) = core.shutdown().await {
+            tracing::warn!("Failed to shut down before restarting: {}", e);
+        }
+    }
+    tracing::info!("Restarting in safe mode");
+    app_handle.restart()
+}
+
 // =====================================================
 // DOCUMENT MANAGEMENT COMMANDS
 // =====================================================
 
 /// Import a document from file path
+///
+/// With `dry_run`, nothing is written and the plan of the import is
+/// returned instead; directories are planned like a bulk import, so the UI
+/// can show a confirmation screen before importing.
 #[tauri::command]
 async fn import_document(
     file_path: String,
+    dry_run: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<ImportDocumentResponse>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let result = if !dry_run.unwrap_or(false) {
+            core.content
+                .import_document(&file_path)
+                .await
+                .map(|id| ImportDocumentResponse::Imported(id.to_string()))
+        } else if std::path::Path::new(&file_path).is_dir() {
+            core.content
+                .bulk_import_directory(&file_path, true)
+                .await
+                .map(|result| ImportDocumentResponse::Planned(result.plan.unwrap_or_default()))
+        } else {
+            core.content
+                .plan_import(&[std::path::PathBuf::from(&file_path)])
+                .await
+                .map(ImportDocumentResponse::Planned)
+        };
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Import an Obsidian or Logseq vault
+///
+/// Progress is emitted to the frontend as `vault-import-progress` events;
+/// the result lists wikilinks that matched no document.
+#[tauri::command]
+async fn import_obsidian_vault(
+    path: String,
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<BulkImportResult>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let throttle = ProgressThrottle::new(&core.get_config().await.app.event_batching);
+        let result = core
+            .content
+            .import_obsidian_vault(&path, |progress| {
+                if throttle.ready(progress.completed >= progress.total) {
+                    let _ = app_handle.emit("vault-import-progress", &progress);
+                }
+            })
+            .await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// List files that failed to import, dropping those that no longer exist
+#[tauri::command]
+async fn get_failed_imports(
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<Vec<FailedImport>>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let result = core.content.get_failed_imports().await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Import the files of the given failed imports again with the current parsers
+#[tauri::command]
+async fn retry_failed_imports(
+    ids: Vec<String>,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<BulkImportResult>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let result = core.content.retry_failed_imports(&ids).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Import a browser bookmarks export (HTML) as one document per bookmark
+///
+/// Page text is fetched only when asked for and the app is not in offline mode.
+#[tauri::command]
+async fn import_bookmarks_file(
+    path: String,
+    fetch_content: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<BulkImportResult>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let offline = core.config.read().await.app.offline_mode;
+        let fetch_content = fetch_content.unwrap_or(false) && !offline;
+        let result = core.content.import_bookmarks_file(&path, fetch_content).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Import bibliographic metadata from a BibTeX/Zotero export
+#[tauri::command]
+async fn import_bibtex(
+    path: String,
+    create_stubs: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<BibtexImportResult>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let result = core.content.import_bibtex(&path, create_stubs.unwrap_or(false)).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Format a citation for a document (apa, mla or bibtex)
+#[tauri::command]
+async fn get_citation(
+    document_id: String,
+    style: CitationStyle,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<String>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let id = match Uuid::parse_str(&document_id) {
+            Ok(id) => id,
+            Err(_) => return Ok(CommandResponse::error("Invalid document ID".to_string())),
+        };
+
+        let result = core.content.get_citation(id, style).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Queue an audio file for transcription, returning the background job ID
+///
+/// Progress is emitted to the frontend as `job-progress` events.
+#[tauri::command]
+async fn import_audio(
+    file_path: String,
+    app_handle: tauri::AppHandle,
     state: State<'_, AppState>,
 ) -> Result<CommandResponse<String>, tauri::Error> {
     let core_lock = state.core.read().await;
     
     if let Some(ref core) = *core_lock {
-        let result = core.content.import_document(&file_path).await;
-        Ok(CommandResponse::from(result.map(|id| id.to_string())))
+        // Subscribe before queueing so no progress events are missed
+        let events = core.content.subscribe_jobs();
+
+        match core.content.import_audio(&file_path).await {
+            Ok(job_id) => {
+                forward_job_events(app_handle, events, job_id);
+                Ok(CommandResponse::success(job_id.to_string()))
+            }
+            Err(e) => Ok(CommandResponse::from_error(&e)),
+        }
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Recompute reading time and/or difficulty for existing documents in the background
+///
+/// Progress is emitted to the frontend as `job-progress` events.
+#[tauri::command]
+async fn recompute_metadata(
+    filter: MetadataFilter,
+    fields: Vec<MetadataField>,
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<String>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let events = core.content.subscribe_jobs();
+
+        match core.content.recompute_metadata(filter, &fields) {
+            Ok(job_id) => {
+                forward_job_events(app_handle, events, job_id);
+                Ok(CommandResponse::success(job_id.to_string()))
+            }
+            Err(e) => Ok(CommandResponse::from_error(&e)),
+        }
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Re-emit a background job's progress as `job-progress` events until it finishes
+fn forward_job_events(
+    app_handle: tauri::AppHandle,
+    mut events: tokio::sync::broadcast::Receiver<JobInfo>,
+    job_id: Uuid,
+) {
+    tauri::async_runtime::spawn(async move {
+        while let Ok(job) = events.recv().await {
+            if job.id != job_id {
+                continue;
+            }
+            let finished = job.status.is_finished();
+            let _ = app_handle.emit("job-progress", &job);
+            if finished {
+                break;
+            }
+        }
+    });
+}
+
+/// Re-emit entries imported from subscribed feeds as `feed-new-items` events
+fn forward_feed_events(
+    app_handle: tauri::AppHandle,
+    mut events: tokio::sync::broadcast::Receiver<FeedNewItems>,
+) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(items) => {
+                    let _ = app_handle.emit("feed-new-items", &items);
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+/// Re-emit changes of the power state as `power-state-changed` events, e.g. to show "performance reduced on battery"
+fn forward_power_events(
+    app_handle: tauri::AppHandle,
+    mut states: tokio::sync::watch::Receiver<PowerState>,
+) {
+    tauri::async_runtime::spawn(async move {
+        while states.changed().await.is_ok() {
+            let state = states.borrow_and_update().clone();
+            let _ = app_handle.emit("power-state-changed", &state);
+        }
+    });
+}
+
+/// Battery and thermal state, and whether performance is reduced
+#[tauri::command]
+async fn get_power_state(state: State<'_, AppState>) -> Result<CommandResponse<PowerState>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        Ok(CommandResponse::success(core.get_power_state()))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Change how inference and background jobs are throttled on battery or when hot
+#[tauri::command]
+async fn set_power_policy(
+    policy: PowerPolicy,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<PowerState>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        match core.set_power_policy(policy).await {
+            Ok(power) => Ok(CommandResponse::success(power)),
+            Err(e) => Ok(CommandResponse::error(e.to_string())),
+        }
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Subscribe to an RSS or Atom feed
+#[tauri::command]
+async fn add_feed(
+    url: String,
+    name: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<Feed>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let result = core.content.add_feed(&url, name).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// List feed subscriptions
+#[tauri::command]
+async fn list_feeds(
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<Vec<Feed>>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let result = core.content.list_feeds().await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Fetch all feeds now and import their new entries
+#[tauri::command]
+async fn refresh_feeds(
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<Vec<FeedRefreshResult>>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        if core.config.read().await.app.offline_mode {
+            return Ok(CommandResponse::error("Feeds cannot be refreshed in offline mode".to_string()));
+        }
+        let result = core.content.refresh_feeds().await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Unsubscribe from a feed, keeping the documents already imported
+#[tauri::command]
+async fn remove_feed(
+    feed_id: String,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<()>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let result = core.content.remove_feed(&feed_id).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Get the status of a background job
+#[tauri::command]
+async fn get_job_status(
+    job_id: String,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<JobInfo>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let id = match Uuid::parse_str(&job_id) {
+            Ok(id) => id,
+            Err(_) => return Ok(CommandResponse::error("Invalid job ID".to_string())),
+        };
+
+        match core.content.get_job(id) {
+            Some(job) => Ok(CommandResponse::success(job)),
+            None => Ok(CommandResponse::error("Job not found".to_string())),
+        }
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Import text content
+#[tauri::command]
+async fn import_text_content(
+    title: String,
+    content: String,
+    content_type: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<String>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let result = core.content.import_text_content(title, content, content_type).await;
+        Ok(CommandResponse::from(result.map(|id| id.to_string())))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Save a snippet of text to the inbox right away
+#[tauri::command]
+async fn quick_capture(
+    text: String,
+    source_hint: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<CaptureResult>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let result = core.content.quick_capture(&text, source_hint.as_deref()).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Merge one document into another, or preview the merge with `dry_run`
+#[tauri::command]
+async fn merge_documents(
+    primary_id: String,
+    secondary_id: String,
+    strategy: Option<MergeStrategy>,
+    dry_run: bool,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<MergeResult>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let (primary_id, secondary_id) = match (Uuid::parse_str(&primary_id), Uuid::parse_str(&secondary_id)) {
+            (Ok(primary), Ok(secondary)) => (primary, secondary),
+            _ => return Ok(CommandResponse::error("Invalid document ID".to_string())),
+        };
+        let strategy = strategy.unwrap_or_default();
+
+        let result = if dry_run {
+            core.content.preview_merge(primary_id, secondary_id, strategy).await
+        } else {
+            core.content.merge_documents(primary_id, secondary_id, strategy).await
+        };
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Split a document into section documents, or list the proposed sections with `preview`
+#[tauri::command]
+async fn split_document(
+    document_id: String,
+    strategy: SplitStrategy,
+    original_handling: Option<OriginalHandling>,
+    preview: bool,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<SplitResult>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let id = match Uuid::parse_str(&document_id) {
+            Ok(id) => id,
+            Err(_) => return Ok(CommandResponse::error("Invalid document ID".to_string())),
+        };
+
+        let result = if preview {
+            core.content.preview_split(id, strategy).await
+        } else {
+            core.content.split_document(id, strategy, original_handling.unwrap_or_default()).await
+        };
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Capture the clipboard text (bound to the global capture shortcut)
+///
+/// Emits `capture-saved` with the [`CaptureResult`], or `capture-failed`.
+async fn capture_clipboard(app_handle: tauri::AppHandle) {
+    use tauri_plugin_clipboard_manager::ClipboardExt;
+
+    let text = match app_handle.clipboard().read_text() {
+        Ok(text) => text,
+        Err(e) => {
+            let _ = app_handle.emit("capture-failed", format!("Clipboard has no text: {}", e));
+            return;
+        }
+    };
+
+    let state: State<AppState> = app_handle.state();
+    let core_lock = state.core.read().await;
+    let Some(ref core) = *core_lock else {
+        let _ = app_handle.emit("capture-failed", "Core not initialized");
+        return;
+    };
+
+    match core.content.quick_capture(&text, Some("clipboard")).await {
+        Ok(result) => {
+            let _ = app_handle.emit("capture-saved", &result);
+        }
+        Err(e) => {
+            tracing::warn!("Clipboard capture failed: {}", e);
+            let _ = app_handle.emit("capture-failed", e.to_string());
+        }
+    }
+}
+
+/// Resolve a `codex://` link and send the frontend a `navigate` event
+///
+/// Links that fail to resolve emit `navigate-failed` with the reason.
+/// Links opened at launch can arrive before the core is ready, so this
+/// waits for initialization briefly.
+async fn navigate_to_link(app_handle: tauri::AppHandle, link: String) {
+    let state: State<AppState> = app_handle.state();
+    for _ in 0..300 {
+        if state.core.read().await.is_some() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+
+    let core_lock = state.core.read().await;
+    let Some(ref core) = *core_lock else {
+        let _ = app_handle.emit("navigate-failed", "Core not initialized");
+        return;
+    };
+
+    match core.content.resolve_deep_link(&link).await {
+        Ok(target) => {
+            let _ = app_handle.emit("navigate", &target);
+        }
+        Err(e) => {
+            tracing::warn!("Cannot open link {}: {}", link, e);
+            let _ = app_handle.emit("navigate-failed", e.to_string());
+        }
+    }
+}
+
+/// Handle `codex://` links opened from other applications
+#[cfg(desktop)]
+fn register_deep_links(app: &tauri::App) -> Result<(), Box<dyn std::error::Error>> {
+    use tauri_plugin_deep_link::DeepLinkExt;
+
+    // macOS registers the scheme from the bundle; elsewhere it is registered at runtime
+    #[cfg(any(target_os = "linux", target_os = "windows"))]
+    app.deep_link().register_all()?;
+
+    let app_handle = app.handle().clone();
+    app.deep_link().on_open_url(move |event| {
+        for url in event.urls() {
+            tauri::async_runtime::spawn(navigate_to_link(app_handle.clone(), url.to_string()));
+        }
+    });
+
+    // A link that launched the app
+    for url in app.deep_link().get_current()?.unwrap_or_default() {
+        tauri::async_runtime::spawn(navigate_to_link(app.handle().clone(), url.to_string()));
+    }
+    Ok(())
+}
+
+/// Resolve a `codex://` link clicked inside the app
+#[tauri::command]
+async fn resolve_deep_link(
+    link: String,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<NavigationTarget>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let result = core.content.resolve_deep_link(&link).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Register Cmd/Ctrl+Shift+K to capture the clipboard from anywhere
+#[cfg(desktop)]
+fn register_capture_shortcut(app: &tauri::App) -> Result<(), Box<dyn std::error::Error>> {
+    use tauri_plugin_global_shortcut::{Code, GlobalShortcutExt, Modifiers, Shortcut, ShortcutState};
+
+    #[cfg(target_os = "macos")]
+    let modifier = Modifiers::SUPER;
+    #[cfg(not(target_os = "macos"))]
+    let modifier = Modifiers::CONTROL;
+    let capture_shortcut = Shortcut::new(Some(modifier | Modifiers::SHIFT), Code::KeyK);
+
+    app.handle().plugin(
+        tauri_plugin_global_shortcut::Builder::new()
+            .with_handler(move |app_handle, shortcut, event| {
+                if shortcut == &capture_shortcut && event.state() == ShortcutState::Pressed {
+                    tauri::async_runtime::spawn(capture_clipboard(app_handle.clone()));
+                }
+            })
+            .build(),
+    )?;
+    app.global_shortcut().register(capture_shortcut)?;
+    Ok(())
+}
+
+/// Create a document template
+#[tauri::command]
+async fn create_template(
+    name: String,
+    content: String,
+    default_tags: Vec<String>,
+    default_category: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<String>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let result = core.content.create_template(name, content, default_tags, default_category).await;
+        Ok(CommandResponse::from(result.map(|id| id.to_string())))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// List document templates
+#[tauri::command]
+async fn list_templates(
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<Vec<Template>>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let result = core.content.list_templates().await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Create a document from a template, filling its placeholders
+#[tauri::command]
+async fn create_document_from_template(
+    template_id: String,
+    variables: HashMap<String, String>,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<String>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let id = match Uuid::parse_str(&template_id) {
+            Ok(id) => id,
+            Err(_) => return Ok(CommandResponse::error("Invalid template ID".to_string())),
+        };
+
+        let result = core.content.create_document_from_template(id, variables).await;
+        Ok(CommandResponse::from(result.map(|id| id.to_string())))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Get document by ID
+#[tauri::command]
+async fn get_document(
+    document_id: String,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<DocumentDto>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let id = match Uuid::parse_str(&document_id) {
+            Ok(id) => id,
+            Err(_) => return Ok(CommandResponse::error("Invalid document ID".to_string())),
+        };
+
+        let result = core.content.get_document(id).await;
+        match result {
+            Ok(Some(doc)) => Ok(CommandResponse::success(document_to_dto(&doc))),
+            Ok(None) => Ok(CommandResponse::error("Document not found".to_string())),
+            Err(e) => Ok(CommandResponse::from_error(&e)),
+        }
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Get the parsed structure (e.g. table schema) of a document
+#[tauri::command]
+async fn get_document_structure(
+    document_id: String,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<Option<DocumentStructure>>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let id = match Uuid::parse_str(&document_id) {
+            Ok(id) => id,
+            Err(_) => return Ok(CommandResponse::error("Invalid document ID".to_string())),
+        };
+
+        let result = core.content.get_document_structure(id).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Path of a document's original file, if it is still available
+#[tauri::command]
+async fn get_attachment_path(
+    document_id: String,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<Option<String>>, tauri::Error> {
+    let core_lock = state.core.read().await;
+
+    if let Some(ref core) = *core_lock {
+        let id = match Uuid::parse_str(&document_id) {
+            Ok(id) => id,
+            Err(_) => return Ok(CommandResponse::error("Invalid document ID".to_string())),
+        };
+
+        let result = core
+            .content
+            .get_attachment_path(id)
+            .await
+            .map(|path| path.map(|p| p.display().to_string()));
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Thumbnail of a document for the library grid
+///
+/// A `pending` result names the job rendering it; ask again once the job finished.
+#[tauri::command]
+async fn get_document_thumbnail(
+    document_id: String,
+    size: Option<ThumbnailSize>,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<Thumbnail>, tauri::Error> {
+    let core_lock = state.core.read().await;
+
+    if let Some(ref core) = *core_lock {
+        let id = match Uuid::parse_str(&document_id) {
+            Ok(id) => id,
+            Err(_) => return Ok(CommandResponse::error("Invalid document ID".to_string())),
+        };
+
+        let result = core.content.get_document_thumbnail(id, size.unwrap_or_default()).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Open a document's original file in the system's default application
+#[tauri::command]
+async fn open_attachment(
+    app_handle: tauri::AppHandle,
+    document_id: String,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<()>, tauri::Error> {
+    use tauri_plugin_opener::OpenerExt;
+
+    let core_lock = state.core.read().await;
+
+    if let Some(ref core) = *core_lock {
+        let id = match Uuid::parse_str(&document_id) {
+            Ok(id) => id,
+            Err(_) => return Ok(CommandResponse::error("Invalid document ID".to_string())),
+        };
+
+        let path = match core.content.get_attachment_path(id).await {
+            Ok(Some(path)) => path,
+            Ok(None) => return Ok(CommandResponse::error("The original file is no longer available".to_string())),
+            Err(e) => return Ok(CommandResponse::from(Err::<(), _>(e))),
+        };
+        match app_handle.opener().open_path(path.display().to_string(), None::<&str>) {
+            Ok(()) => Ok(CommandResponse::success(())),
+            Err(e) => Ok(CommandResponse::error(format!("Failed to open original file: {}", e))),
+        }
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Replace a document's content; returns the new version
+///
+/// Fails with a `conflict` error when the document changed after
+/// `expected_version` was read.
+#[tauri::command]
+async fn update_document(
+    document_id: String,
+    content: String,
+    expected_version: i64,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<i64>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let id = match Uuid::parse_str(&document_id) {
+            Ok(id) => id,
+            Err(_) => return Ok(CommandResponse::error("Invalid document ID".to_string())),
+        };
+
+        let result = core.content.update_document(id, content, Some(expected_version)).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Rename a document and re-resolve links to it
+#[tauri::command]
+async fn rename_document(
+    document_id: String,
+    title: String,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<()>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let id = match Uuid::parse_str(&document_id) {
+            Ok(id) => id,
+            Err(_) => return Ok(CommandResponse::error("Invalid document ID".to_string())),
+        };
+
+        let result = core.content.rename_document(id, title).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Highlight a character range of a document
+#[tauri::command]
+async fn create_annotation(
+    document_id: String,
+    start_offset: i64,
+    end_offset: i64,
+    color: Option<String>,
+    note: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<Annotation>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let id = match Uuid::parse_str(&document_id) {
+            Ok(id) => id,
+            Err(_) => return Ok(CommandResponse::error("Invalid document ID".to_string())),
+        };
+
+        let result = core.content.create_annotation(id, start_offset, end_offset, color, note).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Change an annotation's color or note (an empty note removes it)
+#[tauri::command]
+async fn update_annotation(
+    annotation_id: String,
+    color: Option<String>,
+    note: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<Annotation>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let id = match Uuid::parse_str(&annotation_id) {
+            Ok(id) => id,
+            Err(_) => return Ok(CommandResponse::error("Invalid annotation ID".to_string())),
+        };
+
+        let result = core.content.update_annotation(id, color, note).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Delete an annotation
+#[tauri::command]
+async fn delete_annotation(
+    annotation_id: String,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<bool>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let id = match Uuid::parse_str(&annotation_id) {
+            Ok(id) => id,
+            Err(_) => return Ok(CommandResponse::error("Invalid annotation ID".to_string())),
+        };
+
+        let result = core.content.delete_annotation(id).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// List a document's annotations in reading order
+#[tauri::command]
+async fn get_annotations(
+    document_id: String,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<Vec<Annotation>>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let id = match Uuid::parse_str(&document_id) {
+            Ok(id) => id,
+            Err(_) => return Ok(CommandResponse::error("Invalid document ID".to_string())),
+        };
+
+        let result = core.content.get_annotations(id).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// List the most recent highlights across all documents
+#[tauri::command]
+async fn get_all_annotations(
+    limit: Option<i64>,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<Vec<Highlight>>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let result = core.content.get_all_annotations(limit.unwrap_or(100)).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Search highlighted text and annotation notes
+#[tauri::command]
+async fn search_annotations(
+    query: String,
+    limit: Option<i64>,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<Vec<AnnotationMatch>>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let result = core.content.search_annotations(&query, limit.unwrap_or(50)).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Get the links contained in a document
+#[tauri::command]
+async fn get_outgoing_links(
+    document_id: String,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<Vec<DocumentLink>>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let id = match Uuid::parse_str(&document_id) {
+            Ok(id) => id,
+            Err(_) => return Ok(CommandResponse::error("Invalid document ID".to_string())),
+        };
+
+        let result = core.content.get_outgoing_links(id).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Get links from other documents pointing at a document
+#[tauri::command]
+async fn get_backlinks(
+    document_id: String,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<Vec<DocumentLink>>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let id = match Uuid::parse_str(&document_id) {
+            Ok(id) => id,
+            Err(_) => return Ok(CommandResponse::error("Invalid document ID".to_string())),
+        };
+
+        let result = core.content.get_backlinks(id).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Get documents without any links in or out
+#[tauri::command]
+async fn get_orphaned_documents(
+    limit: i64,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<Vec<DocumentDto>>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let result = core.content.get_orphaned_documents(limit).await;
+        Ok(CommandResponse::from(result.map(|docs| {
+            docs.into_iter().map(|doc| document_to_dto(&doc)).collect()
+        })))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Get recent documents
+#[tauri::command]
+async fn get_recent_documents(
+    limit: i64,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<Vec<DocumentDto>>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let result = core.content.get_recent_documents(limit).await;
+        Ok(CommandResponse::from(result.map(|docs| {
+            docs.into_iter().map(|doc| document_to_dto(&doc)).collect()
+        })))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Archive a document, hiding it from default listings and search
+#[tauri::command]
+async fn archive_document(
+    document_id: String,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<()>, tauri::Error> {
+    set_document_archived(document_id, true, state).await
+}
+
+/// Restore an archived document
+#[tauri::command]
+async fn unarchive_document(
+    document_id: String,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<()>, tauri::Error> {
+    set_document_archived(document_id, false, state).await
+}
+
+async fn set_document_archived(
+    document_id: String,
+    archived: bool,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<()>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let id = match Uuid::parse_str(&document_id) {
+            Ok(id) => id,
+            Err(_) => return Ok(CommandResponse::error("Invalid document ID".to_string())),
+        };
+
+        let result = core.content.set_archived(id, archived).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Get archived documents
+#[tauri::command]
+async fn get_archive(
+    limit: i64,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<Vec<DocumentDto>>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let result = core.content.get_archived_documents(limit).await;
+        Ok(CommandResponse::from(result.map(|docs| {
+            docs.into_iter().map(|doc| document_to_dto(&doc)).collect()
+        })))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Get documents most recently opened
+#[tauri::command]
+async fn get_recently_accessed(
+    limit: i64,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<Vec<DocumentDto>>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let result = core.content.get_recently_accessed(limit).await;
+        Ok(CommandResponse::from(result.map(|docs| {
+            docs.into_iter().map(|doc| document_to_dto(&doc)).collect()
+        })))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Get the home feed sections shown when the app opens
+#[tauri::command]
+async fn get_home_feed(state: State<'_, AppState>) -> Result<CommandResponse<HomeFeed>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let result = core.content.get_home_feed().await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Get the most viewed documents; `since` is an optional RFC 3339 timestamp
+#[tauri::command]
+async fn get_most_viewed(
+    limit: i64,
+    since: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<Vec<DocumentDto>>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let since = match since.as_deref().map(chrono::DateTime::parse_from_rfc3339) {
+            Some(Ok(since)) => Some(since.with_timezone(&chrono::Utc)),
+            Some(Err(_)) => return Ok(CommandResponse::error("Invalid timestamp".to_string())),
+            None => None,
+        };
+
+        let result = core.content.get_most_viewed(limit, since).await;
+        Ok(CommandResponse::from(result.map(|docs| {
+            docs.into_iter().map(|doc| document_to_dto(&doc)).collect()
+        })))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Generate a digest of documents added since a time (RFC 3339) and store it as a document
+///
+/// Returns the id of the digest document.
+#[tauri::command]
+async fn generate_digest(
+    since: String,
+    max_items: Option<usize>,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<String>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let since = match chrono::DateTime::parse_from_rfc3339(&since) {
+            Ok(since) => since.with_timezone(&chrono::Utc),
+            Err(_) => return Ok(CommandResponse::error("Invalid timestamp".to_string())),
+        };
+        let max_items = max_items.unwrap_or_else(|| DigestSchedule::default().max_items);
+
+        let result = core.content.generate_digest(since, max_items).await;
+        Ok(CommandResponse::from(result.map(|id| id.to_string())))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Find matches within a single document for the reader view
+#[tauri::command]
+async fn search_in_document(
+    document_id: String,
+    query: String,
+    options: Option<FindOptions>,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<DocumentMatches>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let id = match Uuid::parse_str(&document_id) {
+            Ok(id) => id,
+            Err(_) => return Ok(CommandResponse::error("Invalid document ID".to_string())),
+        };
+
+        let result = core.content.search_in_document(id, &query, options.unwrap_or_default()).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Export a reading list to `output_path`
+///
+/// Exports either the given document IDs or the results of `query` with
+/// `options`, re-run at export time. Returns the number of exported documents.
+#[tauri::command]
+async fn export_reading_list(
+    document_ids: Option<Vec<String>>,
+    query: Option<String>,
+    options: Option<SearchOptionsDto>,
+    format: ExportFormat,
+    output_path: String,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<usize>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let selection = match parse_selection(document_ids, query, options) {
+            Ok(selection) => selection,
+            Err(message) => return Ok(CommandResponse::error(message)),
+        };
+
+        let result = core.content.export_document_list(selection, format, output_path).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Export documents as markdown files with frontmatter into `destination`
+///
+/// Selects documents like `export_reading_list`. Returns the number of
+/// exported documents.
+#[tauri::command]
+async fn export_documents(
+    document_ids: Option<Vec<String>>,
+    query: Option<String>,
+    options: Option<SearchOptionsDto>,
+    destination: String,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<usize>, tauri::Error> {
+    let core_lock = state.core.read().await;
+
+    if let Some(ref core) = *core_lock {
+        let selection = match parse_selection(document_ids, query, options) {
+            Ok(selection) => selection,
+            Err(message) => return Ok(CommandResponse::error(message)),
+        };
+
+        let result = core.content.export_documents(selection, destination).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Export highlights as one file per document; all documents when `document_id` is omitted
+#[tauri::command]
+async fn export_annotations(
+    document_id: Option<String>,
+    destination: String,
+    format: Option<AnnotationExportFormat>,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<AnnotationExportResult>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let scope = match document_id {
+            Some(document_id) => match Uuid::parse_str(&document_id) {
+                Ok(id) => AnnotationExportScope::Document { id },
+                Err(_) => return Ok(CommandResponse::error("Invalid document ID".to_string())),
+            },
+            None => AnnotationExportScope::All,
+        };
+
+        let format = format.unwrap_or(AnnotationExportFormat::Markdown);
+        let result = core.content.export_annotations(scope, destination, format).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Export the whole vault to an empty directory as of a single point in time
+///
+/// Imports may continue meanwhile; the manifest records when the snapshot was taken.
+/// With `include_attachments`, stored originals of the documents are copied along.
+#[tauri::command]
+async fn export_vault(
+    destination: String,
+    include_attachments: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<VaultExportManifest>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let result = core
+            .content
+            .export_vault(std::path::Path::new(&destination), include_attachments.unwrap_or(false))
+            .await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Export settings, configuration and rules to a JSON bundle
+///
+/// Without a destination the user picks one in a save dialog; returns the
+/// path written, or null when the dialog was cancelled.
+#[tauri::command]
+async fn export_settings(
+    app_handle: tauri::AppHandle,
+    destination: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<Option<String>>, tauri::Error> {
+    use tauri_plugin_dialog::DialogExt;
+
+    let core_lock = state.core.read().await;
+
+    if let Some(ref core) = *core_lock {
+        let destination = match destination {
+            Some(destination) => std::path::PathBuf::from(destination),
+            None => match app_handle
+                .dialog()
+                .file()
+                .add_filter("Settings bundle", &["json"])
+                .set_file_name("codex-settings.json")
+                .blocking_save_file()
+                .and_then(|path| path.into_path().ok())
+            {
+                Some(path) => path,
+                None => return Ok(CommandResponse::success(None)),
+            },
+        };
+        let result = core
+            .export_settings(&destination)
+            .await
+            .map(|_| Some(destination.to_string_lossy().into_owned()));
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Import a settings bundle; `dry_run` (the default) only reports the changes
+///
+/// Without a source the user picks a bundle in an open dialog; returns null
+/// when the dialog was cancelled.
+#[tauri::command]
+async fn import_settings(
+    app_handle: tauri::AppHandle,
+    source: Option<String>,
+    dry_run: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<Option<SettingsImportDto>>, tauri::Error> {
+    use tauri_plugin_dialog::DialogExt;
+
+    let core_lock = state.core.read().await;
+
+    if let Some(ref core) = *core_lock {
+        let source = match source {
+            Some(source) => std::path::PathBuf::from(source),
+            None => match app_handle
+                .dialog()
+                .file()
+                .add_filter("Settings bundle", &["json"])
+                .blocking_pick_file()
+                .and_then(|path| path.into_path().ok())
+            {
+                Some(path) => path,
+                None => return Ok(CommandResponse::success(None)),
+            },
+        };
+        let result = core.import_settings(&source, dry_run.unwrap_or(true)).await.map(|report| {
+            Some(SettingsImportDto {
+                path: source.to_string_lossy().into_owned(),
+                report,
+            })
+        });
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Check the vault for inconsistencies without changing anything
+#[tauri::command]
+async fn audit_vault(
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<VaultAudit>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let result = core.db.audit().await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Fix the problems an audit found, with the default safe fixes unless `options` are given
+#[tauri::command]
+async fn repair_vault(
+    audit: VaultAudit,
+    options: Option<RepairOptions>,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<RepairResult>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let result = core.db.repair(&audit, &options.unwrap_or_default()).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Break the vault's disk usage down by component, with the space each
+/// maintenance action would reclaim
+#[tauri::command]
+async fn get_storage_breakdown(
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<StorageBreakdown>, tauri::Error> {
+    let core_lock = state.core.read().await;
+
+    if let Some(ref core) = *core_lock {
+        let result = core.db.get_storage_breakdown().await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Run the maintenance action a storage suggestion refers to by id
+///
+/// Returns the background job ID for content compression, whose progress is
+/// emitted as `job-progress` events, and nothing for the other actions.
+#[tauri::command]
+async fn run_maintenance_action(
+    action: MaintenanceAction,
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<Option<String>>, tauri::Error> {
+    let core_lock = state.core.read().await;
+
+    if let Some(ref core) = *core_lock {
+        let events = core.content.subscribe_jobs();
+
+        match core.content.run_maintenance(action).await {
+            Ok(Some(job_id)) => {
+                forward_job_events(app_handle, events, job_id);
+                Ok(CommandResponse::success(Some(job_id.to_string())))
+            }
+            Ok(None) => Ok(CommandResponse::success(None)),
+            Err(e) => Ok(CommandResponse::from_error(&e)),
+        }
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Explain why a search result matched, for "why am I seeing this?"
+///
+/// The matched terms and closest passage are returned even without a
+/// loaded model; the one-sentence explanation is then omitted.
+#[tauri::command]
+async fn explain_search_result(
+    document_id: String,
+    query: String,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<MatchExplanation>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let result = core.content.explain_search_result(&document_id, &query).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Get the search synonyms and stop words
+#[tauri::command]
+async fn get_search_dictionaries(
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<SearchDictionaries>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let result = core.content.get_search_dictionaries().await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Add, replace or remove search synonyms and stop words
+///
+/// Returns both dictionaries after the edit.
+#[tauri::command]
+async fn manage_synonyms(
+    edit: DictionaryEdit,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<SearchDictionaries>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let result = core.content.edit_search_dictionaries(edit).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Apply category, tag, archive and favorite edits to many documents at once
+///
+/// Edits either the given document IDs or the results of `query` with
+/// `options`. The whole update is undone by a single `undo`.
+#[tauri::command]
+async fn bulk_update_documents(
+    document_ids: Option<Vec<String>>,
+    query: Option<String>,
+    options: Option<SearchOptionsDto>,
+    changes: BulkChanges,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<BulkUpdateSummary>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let selection = match parse_selection(document_ids, query, options) {
+            Ok(selection) => selection,
+            Err(message) => return Ok(CommandResponse::error(message)),
+        };
+
+        let result = core.content.bulk_update(selection, changes).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Set and remove custom metadata fields of a document, e.g. `status: to-read`
+///
+/// Keys are letters, digits, `_` and `-`; values are text, numbers,
+/// booleans or lists of text. Returns the document's fields afterwards.
+#[tauri::command]
+async fn set_document_metadata(
+    document_id: String,
+    set: Option<BTreeMap<String, MetadataValue>>,
+    remove: Option<Vec<String>>,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<BTreeMap<String, MetadataValue>>, tauri::Error> {
+    let core_lock = state.core.read().await;
+
+    if let Some(ref core) = *core_lock {
+        let id = match Uuid::parse_str(&document_id) {
+            Ok(id) => id,
+            Err(_) => return Ok(CommandResponse::error("Invalid document ID".to_string())),
+        };
+
+        let result = core
+            .content
+            .set_document_metadata(id, set.unwrap_or_default(), remove.unwrap_or_default())
+            .await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Documents whose custom metadata matches every `[key, value]` filter, newest first
+#[tauri::command]
+async fn get_documents_by_metadata(
+    filters: Vec<(String, MetadataValue)>,
+    limit: Option<usize>,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<Vec<DocumentDto>>, tauri::Error> {
+    let core_lock = state.core.read().await;
+
+    if let Some(ref core) = *core_lock {
+        match core.content.get_documents_by_metadata(filters, limit.unwrap_or(50)).await {
+            Ok(documents) => Ok(CommandResponse::success(documents.iter().map(document_to_dto).collect())),
+            Err(e) => Ok(CommandResponse::from_error(&e)),
+        }
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// AI operations recorded for a document, newest first
+#[tauri::command]
+async fn get_ai_audit(
+    document_id: String,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<Vec<AiAuditEntry>>, tauri::Error> {
+    let core_lock = state.core.read().await;
+
+    if let Some(ref core) = *core_lock {
+        let id = match Uuid::parse_str(&document_id) {
+            Ok(id) => id,
+            Err(_) => return Ok(CommandResponse::error("Invalid document ID".to_string())),
+        };
+
+        let result = core.content.get_ai_audit(id).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Start timing a reading session; called by the reader view when it shows a document
+#[tauri::command]
+async fn start_reading_session(
+    document_id: String,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<ReadingSession>, tauri::Error> {
+    let core_lock = state.core.read().await;
+
+    if let Some(ref core) = *core_lock {
+        let id = match Uuid::parse_str(&document_id) {
+            Ok(id) => id,
+            Err(_) => return Ok(CommandResponse::error("Invalid document ID".to_string())),
+        };
+
+        let result = core.content.start_reading_session(id).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// End a reading session; null when it was too short to count
+#[tauri::command]
+async fn end_reading_session(
+    session_id: String,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<Option<ReadingSession>>, tauri::Error> {
+    let core_lock = state.core.read().await;
+
+    if let Some(ref core) = *core_lock {
+        let result = core.content.end_reading_session(&session_id).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Current and longest reading streak in days
+#[tauri::command]
+async fn get_reading_streak(
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<ReadingStreak>, tauri::Error> {
+    let core_lock = state.core.read().await;
+
+    if let Some(ref core) = *core_lock {
+        let result = core.content.get_reading_streak().await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Minutes read since Monday
+#[tauri::command]
+async fn get_weekly_reading_minutes(
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<u64>, tauri::Error> {
+    let core_lock = state.core.read().await;
+
+    if let Some(ref core) = *core_lock {
+        let result = core.content.get_weekly_reading_minutes().await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Progress towards the weekly reading goal
+#[tauri::command]
+async fn get_goal_progress(
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<GoalProgress>, tauri::Error> {
+    let core_lock = state.core.read().await;
+
+    if let Some(ref core) = *core_lock {
+        let result = core.content.get_goal_progress().await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Set the weekly reading goal in minutes; null clears it
+#[tauri::command]
+async fn set_reading_goal(
+    minutes: Option<u32>,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<()>, tauri::Error> {
+    let core_lock = state.core.read().await;
+
+    if let Some(ref core) = *core_lock {
+        let result = core.content.set_weekly_reading_goal(minutes).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Fuzzy title lookup for the quick switcher, favorites and recent documents first
+#[tauri::command]
+async fn quick_find(
+    query: String,
+    limit: Option<usize>,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<Vec<QuickFindHit>>, tauri::Error> {
+    let core_lock = state.core.read().await;
+
+    if let Some(ref core) = *core_lock {
+        Ok(CommandResponse::success(core.content.quick_find(&query, limit.unwrap_or(20).clamp(1, 100))))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Suggested triage of the newest inbox documents
+#[tauri::command]
+async fn get_triage_suggestions(
+    limit: Option<usize>,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<Vec<TriageSuggestion>>, tauri::Error> {
+    let core_lock = state.core.read().await;
+
+    if let Some(ref core) = *core_lock {
+        let result = core.content.triage_suggestions(limit.unwrap_or(20).clamp(1, 100)).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Apply the accepted parts of a triage suggestion
+#[tauri::command]
+async fn apply_triage_suggestion(
+    suggestion: TriageSuggestion,
+    decision: TriageDecision,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<TriageResult>, tauri::Error> {
+    let core_lock = state.core.read().await;
+
+    if let Some(ref core) = *core_lock {
+        let result = core.content.apply_triage_suggestion(&suggestion, &decision).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Build a timed reading list on a topic, saved as a collection
+#[tauri::command]
+async fn build_focus_pack(
+    topic: String,
+    minutes: i64,
+    difficulty_range: Option<DifficultyRange>,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<FocusPack>, tauri::Error> {
+    let core_lock = state.core.read().await;
+
+    if let Some(ref core) = *core_lock {
+        let result = core.content.build_focus_pack(&topic, minutes, difficulty_range).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Search documents
+#[tauri::command]
+async fn search_documents(
+    query: String,
+    options: SearchOptionsDto,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<SearchResultDto>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let search_options = dto_to_search_options(options);
+        let result = core.content.search_documents(&query, search_options).await;
+        
+        match result {
+            Ok(search_results) => {
+                let dto = SearchResultDto {
+                    documents: search_results.documents.into_iter()
+                        .map(|sr| document_to_dto(&sr.document))
+                        .collect(),
+                    total_count: search_results.total_count,
+                    query: search_results.query,
+                    search_time_ms: search_results.search_time_ms,
+                    has_more: search_results.has_more,
+                    did_you_mean: search_results.did_you_mean,
+                    annotations: search_results.annotations,
+                    timed_out: search_results.timed_out,
+                    warnings: search_results.warnings,
+                };
+                Ok(CommandResponse::success(dto))
+            }
+            Err(e) => Ok(CommandResponse::from_error(&e)),
+        }
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Similarity of two texts under the embedding model; neither is stored
+#[tauri::command]
+async fn compare_texts(
+    a: String,
+    b: String,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<TextComparisonDto>, tauri::Error> {
+    let core_lock = state.core.read().await;
+
+    if let Some(ref core) = *core_lock {
+        match core.ai.compare_texts(&a, &b).await {
+            Ok(similarity) => {
+                let embeddings = core.ai.get_embeddings();
+                let warnings = [&a, &b].iter().filter_map(|text| embeddings.adhoc_notice(text)).collect();
+                Ok(CommandResponse::success(TextComparisonDto { similarity, warnings }))
+            }
+            Err(e) => Ok(CommandResponse::from_error(&e)),
+        }
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Vault documents similar to pasted text, without importing it
+#[tauri::command]
+async fn find_similar_to_text(
+    text: String,
+    limit: Option<usize>,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<SimilarTextDto>, tauri::Error> {
+    let core_lock = state.core.read().await;
+
+    if let Some(ref core) = *core_lock {
+        match core.content.find_similar_to_text(&text, limit.unwrap_or(10).clamp(1, 50)).await {
+            Ok(results) => {
+                let dto = SimilarTextDto {
+                    documents: results
+                        .documents
+                        .into_iter()
+                        .map(|result| SimilarDocumentDto {
+                            document: document_to_dto(&result.document),
+                            score: result.score,
+                            snippet: result.snippet,
+                        })
+                        .collect(),
+                    warnings: results.warnings,
+                };
+                Ok(CommandResponse::success(dto))
+            }
+            Err(e) => Ok(CommandResponse::from_error(&e)),
+        }
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Toggle document favorite status
+#[tauri::command]
+async fn toggle_favorite(
+    document_id: String,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<bool>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let id = match Uuid::parse_str(&document_id) {
+            Ok(id) => id,
+            Err(_) => return Ok(CommandResponse::error("Invalid document ID".to_string())),
+        };
+
+        let result = core.content.toggle_favorite(id).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Undo the most recent reversible operation
+///
+/// Returns the reverted operation, or nothing when there is nothing to undo.
+#[tauri::command]
+async fn undo(
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<Option<Operation>>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let result = core.content.undo_last_operation().await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Get recent operations, newest first
+#[tauri::command]
+async fn get_operation_history(
+    limit: Option<i64>,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<Vec<Operation>>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let result = core.content.get_recent_operations(limit.unwrap_or(50)).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// List automation rules in the order they run
+#[tauri::command]
+async fn list_rules(
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<Vec<AutomationRule>>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let result = core.content.list_rules().await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Create an automation rule applied to future imports
+#[tauri::command]
+async fn create_rule(
+    name: String,
+    priority: Option<i64>,
+    conditions: RuleConditions,
+    actions: RuleActions,
+    enabled: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<AutomationRule>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let mut rule = AutomationRule::new(name, priority.unwrap_or(0), conditions, actions);
+        rule.enabled = enabled.unwrap_or(true);
+        let result = core.content.create_rule(rule).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Replace an automation rule
+#[tauri::command]
+async fn update_rule(
+    rule: AutomationRule,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<()>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let result = core.content.update_rule(rule).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Delete an automation rule
+#[tauri::command]
+async fn delete_rule(
+    rule_id: String,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<()>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let result = core.content.delete_rule(&rule_id).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Preview whether a rule matches an existing document and what it would change
+#[tauri::command]
+async fn test_rule(
+    rule: AutomationRule,
+    sample_document_id: String,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<RuleTestResult>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let id = match Uuid::parse_str(&sample_document_id) {
+            Ok(id) => id,
+            Err(_) => return Ok(CommandResponse::error("Invalid document ID".to_string())),
+        };
+
+        let result = core.content.test_rule(&rule, id).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+// =====================================================
+// SYSTEM COMMANDS
+// =====================================================
+
+/// Get system metrics (CPU, memory, AI status)
+#[tauri::command]
+async fn get_system_metrics(
+    state: State<'_, AppState>,
+) -> Result<SystemMetricsResponse, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let gpu = core.ai.get_system_metrics().await.ok().filter(|system| system.gpu_metrics_available);
+        let (gpu_memory_used_mb, gpu_memory_total_mb, gpu_memory_process_mb) = gpu
+            .map_or((0.0, 0.0, 0.0), |gpu| (gpu.gpu_memory_used_mb, gpu.gpu_memory_total_mb, gpu.gpu_memory_process_mb));
+        match core.ai.get_stats().await {
+            Ok(metrics) => {
+                Ok(SystemMetricsResponse {
+                    cpu_usage: 0.0, // TODO: Add system CPU usage to AiStats
+                    memory_usage_mb: metrics.memory_usage_mb,
+                    total_memory_mb: 0.0, // TODO: Add total memory to AiStats
+                    ai_model_loaded: true, // If we got metrics, model is loaded
+                    uptime_seconds: metrics.uptime_seconds,
+                    gpu_metrics_available: gpu.is_some(),
+                    gpu_memory_used_mb,
+                    gpu_memory_total_mb,
+                    gpu_memory_process_mb,
+                })
+            },
+            Err(_) => Ok(SystemMetricsResponse::unavailable()),
+        }
+    } else {
+        Ok(SystemMetricsResponse::unavailable())
+    }
+}
+
+/// Health check for system status
+#[tauri::command]
+async fn health_check(
+    state: State<'_, AppState>,
+) -> Result<HealthResponse, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        // Safe mode never probes the AI engine
+        let ai_health = !core.safe_mode() && core.ai.health_check().await.unwrap_or(false);
+        let db_health = core.content.health_check().await.unwrap_or(false);
+        let index_health = core.content.get_index_health().await.ok();
+        let index_ok = index_health.as_ref().is_none_or(IndexHealth::is_healthy);
+        
+        Ok(HealthResponse {
+            status: match (core.safe_mode(), ai_health && db_health, index_ok) {
+                (true, _, _) => "safe_mode".to_string(),
+                (false, false, _) => "degraded".to_string(),
+                (false, true, false) => "warning".to_string(),
+                (false, true, true) => "healthy".to_string(),
+            },
+            core_initialized: true,
+            ai_available: ai_health,
+            database_connected: db_health,
+            ocr_available: core.content.ocr_available(),
+            transcription_available: core.content.transcription_available(),
+            index_health,
+            safe_mode: core.safe_mode(),
+        })
+    } else {
+        Ok(HealthResponse {
+            status: "offline".to_string(),
+            core_initialized: false,
+            ai_available: false,
+            database_connected: false,
+            ocr_available: false,
+            transcription_available: false,
+            index_health: None,
+            safe_mode: false,
+        })
+    }
+}
+
+/// Report documents missing from or out of date in the search index
+#[tauri::command]
+async fn get_index_health(
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<IndexHealth>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let result = core.content.get_index_health().await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Reindex the documents flagged by `get_index_health`, returning the background job ID
+///
+/// Progress is emitted to the frontend as `job-progress` events.
+#[tauri::command]
+async fn repair_index(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<String>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let events = core.content.subscribe_jobs();
+
+        match core.content.repair_index().await {
+            Ok(job_id) => {
+                forward_job_events(app_handle, events, job_id);
+                Ok(CommandResponse::success(job_id.to_string()))
+            }
+            Err(e) => Ok(CommandResponse::from_error(&e)),
+        }
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Long-running operations interrupted in an earlier session, awaiting the user
+#[tauri::command]
+async fn get_pending_operations(
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<Vec<codex_core::db::OperationState>>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let result = core.get_pending_operations().await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Resume an interrupted operation from its last checkpoint
+#[tauri::command]
+async fn resume_operation(
+    operation_id: String,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<()>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let result = core.content.resume_operation(&operation_id).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Drop an interrupted operation instead of resuming it
+#[tauri::command]
+async fn discard_operation(
+    operation_id: String,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<()>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let result = core.content.discard_operation(&operation_id).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Most recent log entries for the diagnostics panel, newest first
+///
+/// `level` keeps entries at that level or more severe (e.g. "warn").
+#[tauri::command]
+async fn get_recent_logs(
+    level: Option<String>,
+    limit: Option<usize>,
+) -> Result<CommandResponse<Vec<LogEntry>>, tauri::Error> {
+    let limit = limit.unwrap_or(200).min(logging::LOG_BUFFER_CAPACITY);
+    Ok(CommandResponse::from(logging::recent_logs(level.as_deref(), limit)))
+}
+
+/// Change the log filter at runtime, e.g. "debug" or "warn,codex_core::ai=trace"
+#[tauri::command]
+async fn set_log_level(filter: String) -> Result<CommandResponse<bool>, tauri::Error> {
+    Ok(CommandResponse::from(logging::set_log_level(&filter).map(|_| true)))
+}
+
+/// Per-operation latency statistics for this session and previous days
+#[tauri::command]
+async fn get_performance_metrics(
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<PerformanceMetricsResponse>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let result = core.get_metrics_history().await.map(|daily| PerformanceMetricsResponse {
+            session: core.get_performance_metrics(),
+            daily,
+        });
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Run the self-test and report each step
+#[tauri::command]
+async fn run_diagnostics(
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<DiagnosticsReport>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        Ok(CommandResponse::success(core.run_diagnostics().await))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Models available for download, from the update server's registry or its cached copy
+#[tauri::command]
+async fn list_available_models(
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<ModelCatalog>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let config = core.get_config().await;
+        let result = core
+            .update
+            .list_available_models(&config.ai.models_dir, config.app.offline_mode)
+            .await;
+        Ok(CommandResponse::from(result))
     } else {
         Ok(CommandResponse::error("Core not initialized".to_string()))
     }
 }
 
-/// Import text content
+/// Download a model listed in the registry into the models directory
+///
+/// Progress, including live throughput, is emitted as `download-progress` events.
 #[tauri::command]
-async fn import_text_content(
-    title: String,
-    content: String,
-    content_type: Option<String>,
+async fn download_model(
+    model_id: String,
+    app_handle: tauri::AppHandle,
     state: State<'_, AppState>,
 ) -> Result<CommandResponse<String>, tauri::Error> {
     let core_lock = state.core.read().await;
     
     if let Some(ref core) = *core_lock {
-        let result = core.content.import_text_content(title, content, content_type).await;
-        Ok(CommandResponse::from(result.map(|id| id.to_string())))
+        let config = core.get_config().await;
+        if config.app.offline_mode {
+            return Ok(CommandResponse::error("Downloads are disabled in offline mode".to_string()));
+        }
+
+        let result = match core.update.list_available_models(&config.ai.models_dir, false).await {
+            Ok(catalog) => match catalog.find(&model_id) {
+                Some(model) => {
+                    let throttle = ProgressThrottle::new(&config.app.event_batching);
+                    codex_core::update::ModelDownloader::new(config.ai.models_dir.clone())
+                        .with_progress_callback(Box::new(move |progress| {
+                            let is_final = !matches!(progress.stage, DownloadStage::Downloading);
+                            if throttle.ready(is_final) {
+                                let _ = app_handle.emit("download-progress", &progress);
+                            }
+                        }))
+                        .download_catalog_model(model)
+                        .await
+                        .map(|path| path.display().to_string())
+                }
+                None => Err(codex_core::CodexError::not_found(format!("Model not in registry: {}", model_id))),
+            },
+            Err(e) => Err(e),
+        };
+        Ok(CommandResponse::from(result))
     } else {
         Ok(CommandResponse::error("Core not initialized".to_string()))
     }
 }
 
-/// Get document by ID
+/// Pause active model and app downloads; partial files are kept
 #[tauri::command]
-async fn get_document(
-    document_id: String,
+async fn pause_downloads() -> Result<CommandResponse<()>, tauri::Error> {
+    codex_core::update::DownloadControl::global().pause();
+    Ok(CommandResponse::success(()))
+}
+
+/// Resume paused downloads where they stopped
+#[tauri::command]
+async fn resume_downloads() -> Result<CommandResponse<()>, tauri::Error> {
+    codex_core::update::DownloadControl::global().resume();
+    Ok(CommandResponse::success(()))
+}
+
+/// Set the combined download speed limit in bytes per second (`None` for unlimited)
+#[tauri::command]
+async fn set_download_speed_limit(
+    max_bytes_per_sec: Option<u64>,
     state: State<'_, AppState>,
-) -> Result<CommandResponse<DocumentDto>, tauri::Error> {
+) -> Result<CommandResponse<()>, tauri::Error> {
     let core_lock = state.core.read().await;
     
     if let Some(ref core) = *core_lock {
-        let id = match Uuid::parse_str(&document_id) {
-            Ok(id) => id,
-            Err(_) => return Ok(CommandResponse::error("Invalid document ID".to_string())),
-        };
-
-        let result = core.content.get_document(id).await;
-        match result {
-            Ok(Some(doc)) => Ok(CommandResponse::success(document_to_dto(&doc))),
-            Ok(None) => Ok(CommandResponse::error("Document not found".to_string())),
+        match core.set_download_speed_limit(max_bytes_per_sec).await {
+            Ok(()) => Ok(CommandResponse::success(())),
             Err(e) => Ok(CommandResponse::error(e.to_string())),
         }
     } else {
@@ -222,145 +2761,94 @@ async fn get_document(
     }
 }
 
-/// Get recent documents
+/// Download an embedding model (the configured one by default) and load it
 #[tauri::command]
-async fn get_recent_documents(
-    limit: i64,
+async fn download_embedding_model(
+    model_id: Option<String>,
     state: State<'_, AppState>,
-) -> Result<CommandResponse<Vec<DocumentDto>>, tauri::Error> {
+) -> Result<CommandResponse<String>, tauri::Error> {
     let core_lock = state.core.read().await;
     
     if let Some(ref core) = *core_lock {
-        let result = core.content.get_recent_documents(limit).await;
-        Ok(CommandResponse::from(result.map(|docs| {
-            docs.into_iter().map(|doc| document_to_dto(&doc)).collect()
-        })))
+        let config = core.get_config().await;
+        if config.app.offline_mode {
+            return Ok(CommandResponse::error("Downloads are disabled in offline mode".to_string()));
+        }
+
+        let ai_config = config.ai;
+        let model_id = model_id.unwrap_or(ai_config.embedding.model);
+        let downloader = codex_core::update::ModelDownloader::new(ai_config.models_dir);
+        let result = match downloader.download_embedding_model(&model_id).await {
+            Ok(model_dir) => core
+                .ai
+                .get_embeddings()
+                .reload(&model_dir)
+                .map(|_| model_dir.display().to_string()),
+            Err(e) => Err(e),
+        };
+        Ok(CommandResponse::from(result))
     } else {
         Ok(CommandResponse::error("Core not initialized".to_string()))
     }
 }
 
-/// Search documents
+/// Start the local HTTP API and keep it enabled on later launches
 #[tauri::command]
-async fn search_documents(
-    query: String,
-    options: SearchOptionsDto,
+async fn start_api_server(
     state: State<'_, AppState>,
-) -> Result<CommandResponse<SearchResultDto>, tauri::Error> {
+) -> Result<CommandResponse<ApiServerInfo>, tauri::Error> {
     let core_lock = state.core.read().await;
     
     if let Some(ref core) = *core_lock {
-        let search_options = dto_to_search_options(options);
-        let result = core.content.search_documents(&query, search_options).await;
-        
-        match result {
-            Ok(search_results) => {
-                let dto = SearchResultDto {
-                    documents: search_results.documents.into_iter()
-                        .map(|sr| document_to_dto(&sr.document))
-                        .collect(),
-                    total_count: search_results.total_count,
-                    query: search_results.query,
-                    search_time_ms: search_results.search_time_ms,
-                    has_more: search_results.has_more,
-                };
-                Ok(CommandResponse::success(dto))
-            }
-            Err(e) => Ok(CommandResponse::error(e.to_string())),
+        let result = core.start_api_server().await;
+        if result.is_ok() {
+            set_api_server_enabled(core, true).await;
         }
+        Ok(CommandResponse::from(result))
     } else {
         Ok(CommandResponse::error("Core not initialized".to_string()))
     }
 }
 
-/// Toggle document favorite status
+/// Stop the local HTTP API and keep it disabled on later launches
 #[tauri::command]
-async fn toggle_favorite(
-    document_id: String,
+async fn stop_api_server(
     state: State<'_, AppState>,
 ) -> Result<CommandResponse<bool>, tauri::Error> {
     let core_lock = state.core.read().await;
     
     if let Some(ref core) = *core_lock {
-        let id = match Uuid::parse_str(&document_id) {
-            Ok(id) => id,
-            Err(_) => return Ok(CommandResponse::error("Invalid document ID".to_string())),
-        };
-
-        let result = core.content.toggle_favorite(id).await;
-        Ok(CommandResponse::from(result))
+        let was_running = core.stop_api_server().await;
+        set_api_server_enabled(core, false).await;
+        Ok(CommandResponse::success(was_running))
     } else {
         Ok(CommandResponse::error("Core not initialized".to_string()))
     }
 }
 
-// =====================================================
-// SYSTEM COMMANDS
-// =====================================================
-
-/// Get system metrics (CPU, memory, AI status)
+/// Address and token of the local HTTP API, when it is running
 #[tauri::command]
-async fn get_system_metrics(
+async fn get_api_server_status(
     state: State<'_, AppState>,
-) -> Result<SystemMetricsResponse, tauri::Error> {
+) -> Result<CommandResponse<Option<ApiServerInfo>>, tauri::Error> {
     let core_lock = state.core.read().await;
     
     if let Some(ref core) = *core_lock {
-        match core.ai.get_stats().await {
-            Ok(metrics) => {
-                Ok(SystemMetricsResponse {
-                    cpu_usage: 0.0, // TODO: Add system CPU usage to AiStats
-                    memory_usage_mb: metrics.memory_usage_mb,
-                    total_memory_mb: 0.0, // TODO: Add total memory to AiStats
-                    ai_model_loaded: true, // If we got metrics, model is loaded
-                    uptime_seconds: metrics.uptime_seconds,
-                })
-            },
-            Err(_) => {
-                Ok(SystemMetricsResponse {
-                    cpu_usage: 0.0,
-                    memory_usage_mb: 0.0,
-                    total_memory_mb: 0.0,
-                    ai_model_loaded: false,
-                    uptime_seconds: 0,
-                })
-            }
-        }
+        Ok(CommandResponse::success(core.api_server_info().await))
     } else {
-        Ok(SystemMetricsResponse {
-            cpu_usage: 0.0,
-            memory_usage_mb: 0.0,
-            total_memory_mb: 0.0,
-            ai_model_loaded: false,
-            uptime_seconds: 0,
-        })
+        Ok(CommandResponse::error("Core not initialized".to_string()))
     }
 }
 
-/// Health check for system status
-#[tauri::command]
-async fn health_check(
-    state: State<'_, AppState>,
-) -> Result<HealthResponse, tauri::Error> {
-    let core_lock = state.core.read().await;
-    
-    if let Some(ref core) = *core_lock {
-        let ai_health = core.ai.health_check().await.unwrap_or(false);
-        let db_health = core.content.health_check().await.unwrap_or(false);
-        
-        Ok(HealthResponse {
-            status: if ai_health && db_health { "healthy".to_string() } else { "degraded".to_string() },
-            core_initialized: true,
-            ai_available: ai_health,
-            database_connected: db_health,
-        })
-    } else {
-        Ok(HealthResponse {
-            status: "offline".to_string(),
-            core_initialized: false,
-            ai_available: false,
-            database_connected: false,
+async fn set_api_server_enabled(core: &CodexCore, enabled: bool) {
+    let result = core
+        .update_config(|config| {
+            config.app.api_server.enabled = enabled;
+            Ok(())
         })
+        .await;
+    if let Err(e) = result {
+        tracing::warn!("Failed to save API server setting: {}", e);
     }
 }
 
@@ -392,21 +2880,11 @@ async fn generate_ai_response(
     
     if let Some(ref core) = *core_lock {
         let start_time = std::time::Instant::now();
-        let result = core.ai.generate_text(&prompt).await;
+        let result = core.ai.generate_text_with_details(&prompt).await;
         let processing_time_ms = start_time.elapsed().as_millis() as u64;
         
         match result {
-            Ok(content) => {
-                // Estimate tokens used (rough approximation: ~4 chars per token)
-                let tokens_used = (prompt.len() + content.len()) / 4;
-                
-                Ok(AiResponse {
-                    content,
-                    model: "test-llama-7b".to_string(),
-                    processing_time_ms,
-                    tokens_used: tokens_used as u32,
-                })
-            },
+            Ok(output) => Ok(AiResponse::from_output(output, processing_time_ms)),
             Err(e) => Err(tauri::Error::Anyhow(anyhow::anyhow!("AI generation failed: {}", e))),
         }
     } else {
@@ -449,26 +2927,17 @@ async fn chat_stream(
         // Add current prompt
         context_prompt.push_str(&format!("User: {}\nAssistant:", prompt));
         
-        // Create callback for streaming tokens
-        let app_handle_clone = app_handle.clone();
-        let callback = move |chunk: String| {
-            let _ = app_handle_clone.emit("ai-chunk", chunk);
-        };
-        
-        let result = core.ai.generate_text_stream(&context_prompt, callback).await;
+        // Stream tokens to the frontend in batches
+        let batching = core.get_config().await.app.event_batching;
+        let (coalescer, callback) = progress_chunk_events(&app_handle, "ai-chunk".to_string(), &batching);
+
+        let result = core.ai.generate_text_stream_with_details(&context_prompt, callback).await;
+        coalescer.flush();
         let processing_time_ms = start_time.elapsed().as_millis() as u64;
         
         match result {
-            Ok(content) => {
-                // Estimate tokens used (rough approximation: ~4 chars per token)
-                let tokens_used = (prompt.len() + content.len()) / 4;
-                
-                let response = AiResponse {
-                    content: content.clone(),
-                    model: "test-llama-7b".to_string(),
-                    processing_time_ms,
-                    tokens_used: tokens_used as u32,
-                };
+            Ok(output) => {
+                let response = AiResponse::from_output(output, processing_time_ms);
                 
                 // Emit completion event
                 let _ = app_handle.emit("ai-complete", &response);
@@ -493,13 +2962,18 @@ async fn chat_stream(
 async fn rag_query(
     query: String,
     context_limit: Option<usize>,
+    conversation_id: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<CommandResponse<serde_json::Value>, tauri::Error> {
     let core_lock = state.core.read().await;
     
     if let Some(ref core) = *core_lock {
         let limit = context_limit.unwrap_or(5);
-        let result = core.ai.rag_query(&query, limit).await;
+        let result = match conversation_id.as_deref().map(Uuid::parse_str) {
+            Some(Ok(id)) => core.ai.rag_query_in_conversation(&query, limit, id).await,
+            Some(Err(_)) => return Ok(CommandResponse::error("Invalid conversation ID".to_string())),
+            None => core.ai.rag_query(&query, limit).await,
+        };
         
         match result {
             Ok(rag_response) => {
@@ -509,18 +2983,242 @@ async fn rag_query(
                 };
                 Ok(CommandResponse::success(json_response))
             }
-            Err(e) => Ok(CommandResponse::error(e.to_string())),
+            Err(e) => Ok(CommandResponse::from_error(&e)),
+        }
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Answer a question from 2–5 specific documents, e.g. to compare what they say about a topic
+#[tauri::command]
+async fn multi_document_query(
+    question: String,
+    document_ids: Vec<String>,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<RagResponse>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        if !(MIN_QUERY_DOCUMENTS..=MAX_QUERY_DOCUMENTS).contains(&document_ids.len()) {
+            return Ok(CommandResponse::error(format!(
+                "Select between {} and {} documents",
+                MIN_QUERY_DOCUMENTS, MAX_QUERY_DOCUMENTS
+            )));
+        }
+
+        let mut ids = Vec::with_capacity(document_ids.len());
+        for id in &document_ids {
+            match Uuid::parse_str(id) {
+                Ok(id) => ids.push(id),
+                Err(_) => return Ok(CommandResponse::error("Invalid document ID".to_string())),
+            }
+        }
+
+        let result = core.ai.multi_document_query(&question, &ids).await;
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Event name scoped to a single request, e.g. `ai-chunk:<request_id>`
+///
+/// Streaming commands take a caller-chosen request id so the frontend can
+/// subscribe before invoking and tell concurrent streams apart.
+fn scoped_event(name: &str, request_id: &str) -> String {
+    format!("{}:{}", name, request_id)
+}
+
+type ChunkSink = Box<dyn Fn(String) + Send + Sync>;
+
+/// Streaming callback emitting generated text as `event`, batched per the
+/// `app.event_batching` settings
+///
+/// Flush the returned coalescer when generation ends, before emitting the
+/// completion event that carries the full text.
+fn chunk_events(
+    app_handle: &tauri::AppHandle,
+    event: String,
+    batching: &EventBatchingConfig,
+) -> (Arc<ChunkCoalescer<ChunkSink>>, impl Fn(String) + Send + Sync + 'static) {
+    let chunk_handle = app_handle.clone();
+    let sink: ChunkSink = Box::new(move |text| {
+        let _ = chunk_handle.emit(&event, text);
+    });
+    let coalescer = Arc::new(ChunkCoalescer::new(batching, sink));
+    let pushing = Arc::clone(&coalescer);
+    (coalescer, move |chunk: String| pushing.push(&chunk))
+}
+
+/// Like [`chunk_events`], but each `event` carries an [`AiChunk`] with the
+/// token count and elapsed time of the latest chunk in its batch
+fn progress_chunk_events(
+    app_handle: &tauri::AppHandle,
+    event: String,
+    batching: &EventBatchingConfig,
+) -> (Arc<ChunkCoalescer<ChunkSink>>, impl Fn(StreamChunk) + Send + Sync + 'static) {
+    let latest = Arc::new(std::sync::Mutex::new((0, 0)));
+    let chunk_handle = app_handle.clone();
+    let sink: ChunkSink = {
+        let latest = Arc::clone(&latest);
+        Box::new(move |content| {
+            let (completion_tokens, elapsed_ms) = *latest.lock().unwrap_or_else(|e| e.into_inner());
+            let _ = chunk_handle.emit(&event, AiChunk { content, completion_tokens, elapsed_ms });
+        })
+    };
+    let coalescer = Arc::new(ChunkCoalescer::new(batching, sink));
+    let pushing = Arc::clone(&coalescer);
+    (coalescer, move |chunk: StreamChunk| {
+        *latest.lock().unwrap_or_else(|e| e.into_inner()) = (chunk.completion_tokens as u32, chunk.elapsed_ms);
+        pushing.push(&chunk.text)
+    })
+}
+
+/// Ask a question about a passage selected in the reader
+///
+/// Answer tokens are emitted as `ai-chunk:<request_id>` events, followed by
+/// `ai-complete:<request_id>` or `ai-error:<request_id>`.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn ask_about_selection(
+    request_id: String,
+    document_id: String,
+    selection_text: String,
+    selection_start: Option<usize>,
+    selection_end: Option<usize>,
+    question: String,
+    conversation_id: Option<String>,
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<String>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let id = match Uuid::parse_str(&document_id) {
+            Ok(id) => id,
+            Err(_) => return Ok(CommandResponse::error("Invalid document ID".to_string())),
+        };
+        let conversation_id = match conversation_id.as_deref().map(Uuid::parse_str) {
+            Some(Ok(id)) => Some(id),
+            Some(Err(_)) => return Ok(CommandResponse::error("Invalid conversation ID".to_string())),
+            None => None,
+        };
+        let selection_range = selection_start.zip(selection_end).map(|(start, end)| start..end);
+
+        let batching = core.get_config().await.app.event_batching;
+        let (coalescer, callback) = chunk_events(&app_handle, scoped_event("ai-chunk", &request_id), &batching);
+
+        let result = core
+            .ai
+            .answer_about_selection(id, &selection_text, selection_range, &question, conversation_id, callback)
+            .await;
+        coalescer.flush();
+
+        match result {
+            Ok(ref answer) => {
+                let _ = app_handle.emit(&scoped_event("ai-complete", &request_id), answer);
+            }
+            Err(ref e) => {
+                let _ = app_handle.emit(&scoped_event("ai-error", &request_id), e.to_string());
+            }
+        }
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Chat about a single document, retrieving only from its content
+///
+/// Answer tokens are emitted as `ai-chunk:<request_id>` events, followed by
+/// `ai-complete:<request_id>` or `ai-error:<request_id>`. Omit
+/// `conversation_id` to start a new conversation; its id is returned.
+#[tauri::command]
+async fn document_chat(
+    request_id: String,
+    document_id: String,
+    message: String,
+    conversation_id: Option<String>,
+    strict_grounding: Option<bool>,
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<DocumentChatResponse>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let id = match Uuid::parse_str(&document_id) {
+            Ok(id) => id,
+            Err(_) => return Ok(CommandResponse::error("Invalid document ID".to_string())),
+        };
+        let conversation_id = match conversation_id.as_deref().map(Uuid::parse_str) {
+            Some(Ok(id)) => Some(id),
+            Some(Err(_)) => return Ok(CommandResponse::error("Invalid conversation ID".to_string())),
+            None => None,
+        };
+
+        let batching = core.get_config().await.app.event_batching;
+        let (coalescer, callback) = chunk_events(&app_handle, scoped_event("ai-chunk", &request_id), &batching);
+
+        let result = core
+            .ai
+            .document_chat(id, &message, conversation_id, strict_grounding.unwrap_or(false), callback)
+            .await;
+        coalescer.flush();
+
+        match result {
+            Ok(ref response) => {
+                let _ = app_handle.emit(&scoped_event("ai-complete", &request_id), response);
+            }
+            Err(ref e) => {
+                let _ = app_handle.emit(&scoped_event("ai-error", &request_id), e.to_string());
+            }
         }
+        Ok(CommandResponse::from(result))
+    } else {
+        Ok(CommandResponse::error("Core not initialized".to_string()))
+    }
+}
+
+/// Get the messages of a stored conversation, oldest first
+#[tauri::command]
+async fn get_conversation_history(
+    conversation_id: String,
+    limit: Option<i64>,
+    state: State<'_, AppState>,
+) -> Result<CommandResponse<Vec<ConversationMessage>>, tauri::Error> {
+    let core_lock = state.core.read().await;
+    
+    if let Some(ref core) = *core_lock {
+        let id = match Uuid::parse_str(&conversation_id) {
+            Ok(id) => id,
+            Err(_) => return Ok(CommandResponse::error("Invalid conversation ID".to_string())),
+        };
+
+        let result = core.ai.conversation_history(id, limit.unwrap_or(100)).await;
+        Ok(CommandResponse::from(result))
     } else {
         Ok(CommandResponse::error("Core not initialized".to_string()))
     }
 }
 
+/// Progress of a document summary, emitted as `summary-progress` events
+#[derive(Debug, Clone, Serialize)]
+pub struct SummaryProgressEvent {
+    pub document_id: String,
+    #[serde(flatten)]
+    pub progress: SummaryProgress,
+}
+
 /// Summarize document
+///
+/// Documents longer than the model's context window are summarized in
+/// chunks; progress is emitted to the frontend as `summary-progress` events.
 #[tauri::command]
 async fn summarize_document(
     document_id: String,
     max_length: Option<usize>,
+    app_handle: tauri::AppHandle,
     state: State<'_, AppState>,
 ) -> Result<CommandResponse<String>, tauri::Error> {
     let core_lock = state.core.read().await;
@@ -531,13 +3229,14 @@ async fn summarize_document(
             Err(_) => return Ok(CommandResponse::error("Invalid document ID".to_string())),
         };
 
-        // Get document content
-        if let Ok(Some(doc)) = core.content.get_document(id).await {
-            let result = core.ai.summarize(&doc.content, max_length).await;
-            Ok(CommandResponse::from(result))
-        } else {
-            Ok(CommandResponse::error("Document not found".to_string()))
-        }
+        let result = core
+            .content
+            .summarize_document(id, max_length, |progress| {
+                let event = SummaryProgressEvent { document_id: document_id.clone(), progress };
+                let _ = app_handle.emit("summary-progress", &event);
+            })
+            .await;
+        Ok(CommandResponse::from(result))
     } else {
         Ok(CommandResponse::error("Core not initialized".to_string()))
     }
@@ -560,16 +3259,40 @@ fn document_to_dto(doc: &codex_core::db::models::Document) -> DocumentDto {
         language: doc.language.clone(),
         reading_time: doc.reading_time.map(|rt| rt as i32),
         difficulty_level: doc.difficulty_level.map(|dl| dl as i32),
-        created_at: doc.created_at.clone(),
-        updated_at: doc.updated_at.clone(),
+        created_at: doc.created_at.to_rfc3339(),
+        updated_at: doc.updated_at.to_rfc3339(),
         view_count: doc.view_count,
         is_favorite: doc.is_favorite,
+        metadata: doc.get_metadata(),
+        version: doc.version,
+    }
+}
+
+/// Build a document selection from explicit IDs or a search
+fn parse_selection(
+    document_ids: Option<Vec<String>>,
+    query: Option<String>,
+    options: Option<SearchOptionsDto>,
+) -> Result<DocumentSelection, String> {
+    match (document_ids, query) {
+        (Some(ids), _) => ids
+            .iter()
+            .map(|id| Uuid::parse_str(id))
+            .collect::<Result<Vec<Uuid>, _>>()
+            .map(|ids| DocumentSelection::Ids { ids })
+            .map_err(|_| "Invalid document ID".to_string()),
+        (None, Some(query)) => Ok(DocumentSelection::Search {
+            query,
+            options: options.map(dto_to_search_options).unwrap_or_default(),
+        }),
+        (None, None) => Err("Either document IDs or a query is required".to_string()),
     }
 }
 
 /// Convert DTO to search options
 fn dto_to_search_options(dto: SearchOptionsDto) -> codex_core::content::SearchOptions {
     use codex_core::content::{SearchOptions, SearchType, SortBy, SortOrder};
+    use codex_core::db::FusionMethod;
 
     let search_type = match dto.search_type.as_deref() {
         Some("full_text") => SearchType::FullText,
@@ -578,6 +3301,11 @@ fn dto_to_search_options(dto: SearchOptionsDto) -> codex_core::content::SearchOp
         _ => SearchType::Hybrid,
     };
 
+    let fusion = match dto.fusion.as_deref() {
+        Some("weighted_sum") => FusionMethod::WeightedSum,
+        _ => FusionMethod::ReciprocalRank,
+    };
+
     SearchOptions {
         search_type,
         limit: dto.limit.unwrap_or(20),
@@ -591,6 +3319,13 @@ fn dto_to_search_options(dto: SearchOptionsDto) -> codex_core::content::SearchOp
         similarity_threshold: Some(0.3),
         sort_by: SortBy::Relevance,
         sort_order: SortOrder::Descending,
+        include_archived: dto.include_archived,
+        favorites_only: dto.favorites_only,
+        fusion,
+        exact_only: dto.exact_only,
+        expand_chunks: dto.expand_chunks,
+        skip_expansion: dto.skip_expansion,
+        metadata: dto.metadata,
     }
 }
 
@@ -600,8 +3335,23 @@ fn dto_to_search_options(dto: SearchOptionsDto) -> codex_core::content::SearchOp
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    // Initialize tracing
-    tracing_subscriber::fmt::init();
+    // `--mcp` runs a headless MCP server on stdio instead of the window
+    if std::env::args().any(|arg| arg == "--mcp") {
+        run_mcp_server();
+        return;
+    }
+    // `--safe-mode` starts without AI and background jobs, like CODEX_SAFE_MODE=1
+    if std::env::args().any(|arg| arg == "--safe-mode") {
+        std::env::set_var(codex_core::config::SAFE_MODE_ENV, "1");
+    }
+
+    // Initialize tracing, with file output as configured
+    let config = tauri::async_runtime::block_on(CodexConfig::load_default()).unwrap_or_default();
+    let level = &config.app.log_level;
+    let filter = format!("{},codex_vault_app_lib={}", logging::default_filter(level), level);
+    if let Err(e) = logging::init(&config.app.logging, &filter) {
+        eprintln!("Failed to initialize logging: {}", e);
+    }
 
     // Create application state
     let app_state = AppState {
@@ -615,24 +3365,142 @@ pub fn run() {
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_updater::init())
+        .plugin(tauri_plugin_clipboard_manager::init())
+        .plugin(tauri_plugin_deep_link::init())
         .invoke_handler(tauri::generate_handler![
             initialize_core,
             get_health_status,
+            get_model_warm_up_state,
+            get_model_load_plan,
+            get_safe_mode,
+            restart_in_safe_mode,
             health_check,
+            get_index_health,
+            repair_index,
+            get_pending_operations,
+            resume_operation,
+            discard_operation,
+            get_recent_logs,
+            set_log_level,
+            get_performance_metrics,
+            run_diagnostics,
+            download_embedding_model,
+            list_available_models,
+            download_model,
+            pause_downloads,
+            resume_downloads,
+            set_download_speed_limit,
+            get_power_state,
+            set_power_policy,
+            start_api_server,
+            stop_api_server,
+            get_api_server_status,
             get_system_metrics,
             get_categories,
             import_document,
+            import_obsidian_vault,
+            import_bookmarks_file,
+            get_failed_imports,
+            retry_failed_imports,
+            import_bibtex,
+            get_citation,
+            add_feed,
+            list_feeds,
+            refresh_feeds,
+            remove_feed,
+            import_audio,
+            get_job_status,
+            recompute_metadata,
             import_text_content,
+            quick_capture,
+            merge_documents,
+            split_document,
+            create_template,
+            list_templates,
+            create_document_from_template,
             get_document,
+            get_document_structure,
+            get_attachment_path,
+            open_attachment,
+            get_document_thumbnail,
+            update_document,
+            rename_document,
+            create_annotation,
+            update_annotation,
+            delete_annotation,
+            get_annotations,
+            get_all_annotations,
+            search_annotations,
+            get_outgoing_links,
+            get_backlinks,
+            get_orphaned_documents,
             get_recent_documents,
+            get_recently_accessed,
+            get_most_viewed,
+            get_home_feed,
+            generate_digest,
             search_documents,
+            quick_find,
+            start_reading_session,
+            end_reading_session,
+            get_reading_streak,
+            get_weekly_reading_minutes,
+            get_goal_progress,
+            set_reading_goal,
+            get_triage_suggestions,
+            apply_triage_suggestion,
+            build_focus_pack,
+            get_ai_audit,
+            compare_texts,
+            find_similar_to_text,
+            search_in_document,
+            export_reading_list,
+            export_documents,
+            export_annotations,
+            export_vault,
+            export_settings,
+            import_settings,
+            audit_vault,
+            repair_vault,
+            get_storage_breakdown,
+            run_maintenance_action,
+            explain_search_result,
+            get_search_dictionaries,
+            manage_synonyms,
+            resolve_deep_link,
+            bulk_update_documents,
+            set_document_metadata,
+            get_documents_by_metadata,
             toggle_favorite,
+            undo,
+            get_operation_history,
+            list_rules,
+            create_rule,
+            update_rule,
+            delete_rule,
+            test_rule,
+            archive_document,
+            unarchive_document,
+            get_archive,
             generate_ai_response,
             chat_stream,
             rag_query,
+            multi_document_query,
+            ask_about_selection,
+            document_chat,
+            get_conversation_history,
             summarize_document,
         ])
         .setup(|app| {
+            #[cfg(desktop)]
+            if let Err(e) = register_capture_shortcut(app) {
+                tracing::warn!("Failed to register the capture shortcut: {}", e);
+            }
+            #[cfg(desktop)]
+            if let Err(e) = register_deep_links(app) {
+                tracing::warn!("Failed to register codex:// links: {}", e);
+            }
+
             // Get app handle for async initialization
             let app_handle = app.handle().clone();
             
@@ -644,10 +3512,37 @@ pub fn run() {
                 if let Err(e) = initialize_core(state).await {
                     tracing::error!("Failed to initialize core during setup: {:?}", e);
                 }
+
+                let state: State<AppState> = app_handle.state();
+                if let Some(ref core) = *state.core.read().await {
+                    forward_feed_events(app_handle.clone(), core.content.subscribe_feed_items());
+                    forward_power_events(app_handle.clone(), core.power.subscribe());
+                }
             });
 
             Ok(())
         })
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
-}
\ No newline at end of file
+}
+
+/// Serve the vault to an MCP client over stdin/stdout
+///
+/// stdout carries the protocol, so logs go to stderr.
+fn run_mcp_server() {
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_env_filter(logging::default_filter("warn"))
+        .init();
+
+    let result = tauri::async_runtime::block_on(async {
+        let core = CodexCore::new().await?;
+        let served = core.mcp_server().serve_stdio().await;
+        core.shutdown().await?;
+        served.map_err(anyhow::Error::from)
+    });
+    if let Err(e) = result {
+        eprintln!("MCP server failed: {}", e);
+        std::process::exit(1);
+    }
+}
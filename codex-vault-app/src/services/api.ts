@@ -13,8 +13,15 @@ export interface Document {
   is_bookmarked: boolean;
   view_count?: number;
   tags?: string[];
+  /** Custom metadata fields, such as frontmatter keys without a document field */
+  metadata?: Record<string, MetadataValue>;
+  /** Passed back with edits; a stale version is rejected with a conflict */
+  version?: number;
 }
 
+/** Value of a custom metadata field; filters pass `[key, value]` pairs */
+export type MetadataValue = string | number | boolean | string[];
+
 export interface SearchResult {
   id: string;
   title: string;
@@ -37,6 +44,25 @@ export interface AiResponse {
   model: string;
   processing_time_ms: number;
   tokens_used: number;
+  /** Set when the prompt was cut down to fit the model's context */
+  truncation?: PromptTruncation | null;
+  prompt_tokens: number;
+  completion_tokens: number;
+  tokens_per_second: number;
+  time_to_first_token_ms: number;
+}
+
+/** Streamed text with the completion tokens generated so far */
+export interface AiChunk {
+  content: string;
+  completion_tokens: number;
+  elapsed_ms: number;
+}
+
+export interface PromptTruncation {
+  policy: 'error' | 'truncate_head' | 'truncate_tail' | 'truncate_middle';
+  original_tokens: number;
+  kept_tokens: number;
 }
 
 export interface SystemMetrics {
@@ -44,6 +70,47 @@ export interface SystemMetrics {
   cpu_usage_percent: number;
   model_loaded: boolean;
   cache_size_mb: number;
+  /** Whether the GPU could be queried; the GPU fields are zero otherwise */
+  gpu_metrics_available: boolean;
+  gpu_memory_used_mb: number;
+  gpu_memory_total_mb: number;
+  gpu_memory_process_mb: number;
+}
+
+/** Payload of `power-state-changed`; `throttle` is set while performance is reduced */
+export interface PowerState {
+  has_battery: boolean;
+  on_battery: boolean;
+  battery_percent: number | null;
+  temperature_celsius: number | null;
+  thermal_pressure: boolean;
+  throttle: 'battery' | 'thermal' | null;
+}
+
+export type StorageComponent =
+  | 'document_content'
+  | 'full_text_index'
+  | 'embeddings'
+  | 'vector_cache'
+  | 'annotations'
+  | 'attachments'
+  | 'other';
+
+export type MaintenanceAction =
+  | 'purge_trash'
+  | 'compress_content'
+  | 'compact_vector_cache'
+  | 'collect_attachment_garbage'
+  | 'vacuum';
+
+export interface StorageBreakdown {
+  measured_at: string;
+  database_bytes: number;
+  free_bytes: number;
+  total_bytes: number;
+  components: { component: StorageComponent; bytes: number }[];
+  tables: { name: string; table: string; component: StorageComponent; bytes: number }[];
+  suggestions: { action: MaintenanceAction; reclaimable_bytes: number; description: string }[];
 }
 
 export interface ChatMessage {
@@ -145,7 +212,7 @@ export class ApiService {
 
   static async generateAiResponseStream(
     prompt: string,
-    onChunk: (chunk: string) => void,
+    onChunk: (chunk: string, progress: AiChunk) => void,
     onComplete: (response: AiResponse) => void,
     onError: (error: string) => void,
     conversationHistory?: ChatMessage[]
@@ -154,8 +221,8 @@ export class ApiService {
       const { listen } = await import('@tauri-apps/api/event');
       
       // Set up event listeners for streaming
-      const unlistenChunk = await listen<string>('ai-chunk', (event) => {
-        onChunk(event.payload);
+      const unlistenChunk = await listen<AiChunk>('ai-chunk', (event) => {
+        onChunk(event.payload.content, event.payload);
       });
       
       const unlistenComplete = await listen<AiResponse>('ai-complete', (event) => {